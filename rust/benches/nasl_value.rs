@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use scannerlib::nasl::syntax::NaslValue;
+use scannerlib::storage::types::Primitive;
+
+fn strings(amount: usize) -> NaslValue {
+    NaslValue::Array(
+        (0..amount)
+            .map(|i| NaslValue::String(format!("Host/banner/{i}/{}", "x".repeat(64)).into()))
+            .collect(),
+    )
+}
+
+pub fn clone_benchmark(c: &mut Criterion) {
+    let value = strings(1000);
+    c.bench_function("clone 1000 strings", |b| {
+        b.iter(|| black_box(value.clone()))
+    });
+}
+
+pub fn primitive_roundtrip_benchmark(c: &mut Criterion) {
+    let value = strings(1000);
+    c.bench_function("primitive roundtrip 1000 strings", |b| {
+        b.iter(|| {
+            let primitive: Primitive = black_box(value.clone()).as_primitive();
+            black_box(NaslValue::from(primitive))
+        })
+    });
+}
+
+criterion_group!(benches, clone_benchmark, primitive_roundtrip_benchmark);
+criterion_main!(benches);
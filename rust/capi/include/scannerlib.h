@@ -0,0 +1,88 @@
+/* SPDX-FileCopyrightText: 2024 Greenbone AG
+ *
+ * SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+ */
+
+/**
+ * @file scannerlib.h
+ * @brief C API of libscannerlib.
+ *
+ * Strings are UTF-8 and NUL terminated. Returned strings are owned by the
+ * caller and must be freed with scannerlib_string_free. On failure NULL or -1
+ * is returned and scannerlib_last_error describes the error.
+ *
+ * Keep in sync with src/lib.rs.
+ */
+
+#ifndef SCANNERLIB_H
+#define SCANNERLIB_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+  /**
+   * @brief Runs a script of a feed in description mode.
+   *
+   * @param feed Path of the feed.
+   * @param script Path of the script relative to the feed.
+   *
+   * @return The described VT as JSON or NULL on failure.
+   */
+  char *
+  scannerlib_describe (const char *feed, const char *script);
+
+  /**
+   * @brief Runs a notus scan of the installed packages of an operating system.
+   *
+   * @param products Path of the notus products.
+   * @param os Operating system, the name of the product without extension.
+   * @param packages Installed packages including their version.
+   * @param len Number of packages.
+   * @param signature_check Verifies the signature of the sums file when not 0.
+   *
+   * @return The vulnerable packages per advisory OID as JSON or NULL on
+   * failure.
+   */
+  char *
+  scannerlib_notus_scan (const char *products, const char *os,
+                         const char *const *packages, size_t len,
+                         int signature_check);
+
+  /**
+   * @brief Verifies the hash sums of each file within the sums file of a feed.
+   *
+   * @param feed Path of the feed.
+   * @param signature_check Verifies the signature of the sums file when not 0.
+   *
+   * @return The number of verified files or -1 on failure.
+   */
+  int
+  scannerlib_verify_feed (const char *feed, int signature_check);
+
+  /**
+   * @brief Returns the error of the last failed call within the current
+   * thread.
+   *
+   * @return The error or NULL. It is owned by the library and valid until the
+   * next call within the same thread.
+   */
+  const char *
+  scannerlib_last_error (void);
+
+  /**
+   * @brief Frees a string returned by the library.
+   *
+   * @param value The string or NULL.
+   */
+  void
+  scannerlib_string_free (char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCANNERLIB_H */
@@ -0,0 +1,299 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#![doc = include_str!("../README.md")]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, UnwindSafe},
+    ptr,
+};
+
+use scannerlib::{
+    feed::{self, HashSumNameLoader, Update},
+    nasl::FSPluginLoader,
+    notus::{HashsumProductLoader, Notus},
+    storage::{item::Nvt, ContextKey, DefaultDispatcher, Retrieve, Retriever},
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: String) {
+    // a NUL within the message would truncate it anyway
+    let error = CString::new(error.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|x| *x.borrow_mut() = Some(error));
+}
+
+/// Runs the call, stores its error or panic as last error and returns `on_error` instead.
+fn guarded<T, F>(on_error: T, f: F) -> T
+where
+    F: FnOnce() -> Result<T, String> + UnwindSafe,
+{
+    LAST_ERROR.with(|x| *x.borrow_mut() = None);
+    match panic::catch_unwind(f) {
+        Ok(Ok(x)) => x,
+        Ok(Err(e)) => {
+            set_last_error(e);
+            on_error
+        }
+        Err(_) => {
+            set_last_error("scannerlib panicked".to_string());
+            on_error
+        }
+    }
+}
+
+/// Returns the argument as str.
+///
+/// # Safety
+/// The pointer must be NULL or point to a NUL terminated string.
+unsafe fn argument<'a>(name: &str, value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{name} must not be NULL"));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|e| format!("{name} is not UTF-8: {e}"))
+}
+
+fn into_raw(value: String) -> Result<*mut c_char, String> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|e| e.to_string())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<*mut c_char, String> {
+    into_raw(serde_json::to_string(value).map_err(|e| e.to_string())?)
+}
+
+fn describe(feed: &str, script: &str) -> Result<Nvt, String> {
+    let loader = FSPluginLoader::new(feed);
+    let storage = DefaultDispatcher::new();
+    let verifier = std::iter::empty::<Result<feed::HashSumFileItem, feed::VerifyError>>();
+    let update = Update::init("scannerlib", 5, &loader, &storage, verifier);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    runtime
+        .block_on(update.describe(script))
+        .map_err(|e| e.to_string())?;
+    storage
+        .retrieve(
+            &ContextKey::FileName(script.to_string()),
+            Retrieve::NVT(None),
+        )
+        .map_err(|e| e.to_string())?
+        .find_map(|x| Nvt::try_from(x).ok())
+        .ok_or_else(|| format!("{script} did not describe a VT"))
+}
+
+fn verify_feed(feed: &str, signature_check: bool) -> Result<c_int, String> {
+    if signature_check {
+        feed::check_signature(feed).map_err(|e| e.to_string())?;
+    }
+    let loader = FSPluginLoader::new(feed);
+    let mut verified: c_int = 0;
+    for item in HashSumNameLoader::sha256(&loader).map_err(|e| e.to_string())? {
+        let item = item.map_err(|e| e.to_string())?;
+        item.verify().map_err(|e| e.to_string())?;
+        verified = verified.saturating_add(1);
+    }
+    Ok(verified)
+}
+
+/// Runs a script of a feed in description mode and returns the described VT as JSON.
+///
+/// Returns NULL on failure.
+///
+/// # Safety
+/// `feed` and `script` must be NULL or point to NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn scannerlib_describe(
+    feed: *const c_char,
+    script: *const c_char,
+) -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        let feed = argument("feed", feed)?;
+        let script = argument("script", script)?;
+        to_json(&describe(feed, script)?)
+    })
+}
+
+/// Runs a notus scan of the installed packages of an operating system and returns the vulnerable
+/// packages per advisory OID as JSON.
+///
+/// Returns NULL on failure.
+///
+/// # Safety
+/// `products` and `os` must be NULL or point to NUL terminated strings, `packages` must point to
+/// `len` of them.
+#[no_mangle]
+pub unsafe extern "C" fn scannerlib_notus_scan(
+    products: *const c_char,
+    os: *const c_char,
+    packages: *const *const c_char,
+    len: usize,
+    signature_check: c_int,
+) -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        let products = argument("products", products)?;
+        let os = argument("os", os)?;
+        if packages.is_null() && len > 0 {
+            return Err("packages must not be NULL".to_string());
+        }
+        let packages = (0..len)
+            .map(|i| argument("package", *packages.add(i)).map(|x| x.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let loader =
+            HashsumProductLoader::new(FSPluginLoader::new(products)).map_err(|e| e.to_string())?;
+        let results = Notus::new(loader, signature_check != 0)
+            .scan(os, &packages)
+            .map_err(|e| e.to_string())?;
+        to_json(&results)
+    })
+}
+
+/// Verifies the hash sums of each file within the sha256sums file of a feed and, when
+/// `signature_check` is not 0, the signature of the sha256sums file.
+///
+/// Returns the number of verified files or -1 on failure.
+///
+/// # Safety
+/// `feed` must be NULL or point to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn scannerlib_verify_feed(
+    feed: *const c_char,
+    signature_check: c_int,
+) -> c_int {
+    guarded(-1, || {
+        let feed = argument("feed", feed)?;
+        verify_feed(feed, signature_check != 0)
+    })
+}
+
+/// Returns the error of the last failed call within the current thread or NULL.
+///
+/// The string is owned by the library and valid until the next call within the same thread.
+#[no_mangle]
+pub extern "C" fn scannerlib_last_error() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+}
+
+/// Frees a string returned by the library.
+///
+/// # Safety
+/// `value` must be NULL or a string returned by the library that is not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scannerlib_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("../include/scannerlib.h");
+
+    fn feed(path: &str) -> CString {
+        CString::new(format!(
+            "{}/../examples/feed/{path}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    fn take(value: *mut c_char) -> String {
+        assert!(!value.is_null(), "{}", last_error());
+        let result = unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { scannerlib_string_free(value) };
+        result
+    }
+
+    fn last_error() -> String {
+        let error = scannerlib_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn header_declares_exports() {
+        for export in [
+            "scannerlib_describe",
+            "scannerlib_notus_scan",
+            "scannerlib_verify_feed",
+            "scannerlib_last_error",
+            "scannerlib_string_free",
+        ] {
+            assert!(
+                HEADER.contains(&format!("{export} (")),
+                "{export} is missing in include/scannerlib.h"
+            );
+        }
+    }
+
+    #[test]
+    fn describe() {
+        let script = CString::new("1.nasl").unwrap();
+        let vt = take(unsafe { scannerlib_describe(feed("nasl").as_ptr(), script.as_ptr()) });
+        let vt: serde_json::Value = serde_json::from_str(&vt).unwrap();
+        assert_eq!(vt["oid"], "0.0.0.0.0.0.0.0.0.1");
+
+        let script = CString::new("missing.nasl").unwrap();
+        let vt = unsafe { scannerlib_describe(feed("nasl").as_ptr(), script.as_ptr()) };
+        assert!(vt.is_null());
+        assert!(last_error().contains("missing.nasl"));
+        assert!(unsafe { scannerlib_describe(ptr::null(), script.as_ptr()) }.is_null());
+        assert_eq!(last_error(), "feed must not be NULL");
+    }
+
+    #[test]
+    fn notus_scan() {
+        let os = CString::new("test").unwrap();
+        let package = CString::new("man-db-2.8.5-1").unwrap();
+        let packages = [package.as_ptr()];
+        let results = take(unsafe {
+            scannerlib_notus_scan(
+                feed("notus/products").as_ptr(),
+                os.as_ptr(),
+                packages.as_ptr(),
+                1,
+                0,
+            )
+        });
+        assert!(results.contains("1.3.6.1.4.1.25623.1.1.1.2.2024.3731"));
+
+        let os = CString::new("unknown").unwrap();
+        let results = unsafe {
+            scannerlib_notus_scan(
+                feed("notus/products").as_ptr(),
+                os.as_ptr(),
+                ptr::null(),
+                0,
+                0,
+            )
+        };
+        assert!(results.is_null());
+        assert!(!last_error().is_empty());
+    }
+
+    #[test]
+    fn verify_feed() {
+        let verified = unsafe { scannerlib_verify_feed(feed("nasl").as_ptr(), 0) };
+        assert_eq!(verified, 7);
+        let missing = CString::new("/does/not/exist").unwrap();
+        assert_eq!(unsafe { scannerlib_verify_feed(missing.as_ptr(), 0) }, -1);
+        assert!(!last_error().is_empty());
+    }
+}
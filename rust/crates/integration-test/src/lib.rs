@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Harness to run the feed update and scans of scannerlib end to end.
+//!
+//! The bundled mini-feed within `feed/` is loaded into an in-memory storage or into an ephemeral
+//! Redis started by [redis::Redis]. Scans run against mock targets of the `mock-target` crate
+//! whose ports are seeded via an nmap report instead of a port scan.
+
+pub mod redis;
+
+use std::path::PathBuf;
+
+use futures::StreamExt;
+use scannerlib::feed::{LayeredNameLoader, Update};
+use scannerlib::models::{self, Scan, ScanPreference, Target, VT};
+use scannerlib::nasl::{nasl_std_functions, FSPluginLoader};
+use scannerlib::scanner::ScanRunner;
+use scannerlib::scheduling::{ExecutionPlaner, WaveExecutionPlan};
+use scannerlib::storage::{ContextKey, DefaultDispatcher, Dispatcher, Retriever};
+
+/// Scan preference containing the nmap report used instead of a port scan
+const NMAP_IMPORT: &str = "nmap_import";
+
+/// Host of the mock targets
+pub const HOST: &str = "127.0.0.1";
+
+/// OIDs of the VTs of the bundled feed
+pub mod oid {
+    /// Detects the software of an SSH server from its banner
+    pub const SSH_DETECT: &str = "1.3.6.1.4.1.25623.1.0.990001";
+    /// Reports OpenSSH servers older than version 8
+    pub const SSH_OUTDATED: &str = "1.3.6.1.4.1.25623.1.0.990002";
+    /// Detects the software of an HTTP server from its Server header
+    pub const HTTP_DETECT: &str = "1.3.6.1.4.1.25623.1.0.990003";
+}
+
+/// Returns the path of the bundled feed.
+pub fn feed_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("feed")
+}
+
+/// Returns a loader of the bundled feed.
+pub fn feed() -> FSPluginLoader {
+    FSPluginLoader::new(feed_path())
+}
+
+/// Loads the bundled feed into the given storage after verifying its sha256sums.
+pub async fn update<S>(storage: &S) -> Result<(), String>
+where
+    S: Dispatcher + Sync + Send,
+{
+    let loader = feed();
+    let layers = loader.layers();
+    let verifier = LayeredNameLoader::sha256(&layers).map_err(|e| e.to_string())?;
+    Update::init("1", 5, &loader, storage, verifier)
+        .perform_update()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns an nmap report of [HOST] with the given open TCP ports and their service.
+pub fn nmap_report(ports: &[(u16, &str)]) -> String {
+    let ports: String = ports
+        .iter()
+        .map(|(port, service)| {
+            format!(
+                r#"<port protocol="tcp" portid="{port}"><state state="open"/><service name="{service}"/></port>"#
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0"?><nmaprun><host><status state="up"/><address addr="{HOST}" addrtype="ipv4"/><ports>{ports}</ports></host></nmaprun>"#
+    )
+}
+
+/// Creates a scan of [HOST] running the given VTs, the open TCP ports and their service are
+/// imported instead of being scanned.
+pub fn scan(id: &str, ports: &[(u16, &str)], vts: &[&str]) -> Scan {
+    Scan {
+        scan_id: id.to_string(),
+        target: Target {
+            hosts: vec![HOST.to_string()],
+            ..Default::default()
+        },
+        scan_preferences: vec![ScanPreference {
+            id: NMAP_IMPORT.to_string(),
+            value: nmap_report(ports),
+        }],
+        vts: vts
+            .iter()
+            .map(|x| VT {
+                oid: x.to_string(),
+                parameters: vec![],
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Runs the scan with the bundled feed and returns its results ordered by OID.
+///
+/// The feed is loaded into a fresh in-memory storage before the scan is run. VTs that do not
+/// succeed, e.g. because of a missing key, are returned as error.
+pub async fn run(scan: &Scan) -> Result<Vec<models::Result>, String> {
+    let storage = DefaultDispatcher::new();
+    update(&storage).await?;
+    let loader = feed();
+    let schedule = storage
+        .execution_plan::<WaveExecutionPlan>(scan)
+        .map_err(|e| format!("{e:?}"))?;
+    let executor = nasl_std_functions();
+    let runner: ScanRunner<(_, _)> =
+        ScanRunner::new(&storage, &loader, &executor, schedule, scan).map_err(|e| e.to_string())?;
+    let mut stream = Box::pin(runner.stream());
+    while let Some(x) = stream.next().await {
+        let x = x.map_err(|e| e.to_string())?;
+        if !x.has_succeeded() {
+            return Err(format!("{} failed: {:?}", x.filename, x.kind));
+        }
+    }
+    let mut results: Vec<_> = storage
+        .results(&ContextKey::Scan(scan.scan_id.clone(), None))
+        .map_err(|e| e.to_string())?
+        .collect();
+    results.sort_by(|a, b| a.oid.cmp(&b.oid));
+    Ok(results)
+}
+
+/// Runs the scan like [run] and returns the results serialized as JSON.
+pub async fn run_json(scan: &Scan) -> Result<serde_json::Value, String> {
+    let results = run(scan).await?;
+    serde_json::to_value(results).map_err(|e| e.to_string())
+}
@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Starts an ephemeral Redis for a test.
+//!
+//! An existing Redis is used when `REDIS_URL` is set. Otherwise a `redis-server` is spawned on a
+//! free port without persistence, falling back to a `redis` docker container. The server or
+//! container is stopped when the [Redis] is dropped.
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time to wait for a started Redis to answer
+const STARTUP: Duration = Duration::from_secs(10);
+
+enum Instance {
+    External,
+    Process(Child),
+    Container(String),
+}
+
+/// A Redis available for the duration of a test
+pub struct Redis {
+    url: String,
+    instance: Instance,
+}
+
+impl Redis {
+    /// Returns an available Redis, none when neither `REDIS_URL` is set nor `redis-server` or
+    /// docker are usable.
+    pub fn start() -> Option<Self> {
+        if let Ok(url) = std::env::var("REDIS_URL") {
+            return Some(Self {
+                url,
+                instance: Instance::External,
+            });
+        }
+        Self::process().or_else(Self::container)
+    }
+
+    /// Returns the URL of the Redis.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn process() -> Option<Self> {
+        let port = free_port()?;
+        let child = Command::new("redis-server")
+            .args(["--port", &port.to_string()])
+            .args(["--bind", "127.0.0.1", "--save", "", "--appendonly", "no"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        Self::ready(port, Instance::Process(child))
+    }
+
+    fn container() -> Option<Self> {
+        let port = free_port()?;
+        let output = Command::new("docker")
+            .args(["run", "--detach", "--rm", "--publish"])
+            .arg(format!("127.0.0.1:{port}:6379"))
+            .args(["redis", "redis-server", "--save", "", "--appendonly", "no"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Self::ready(port, Instance::Container(id))
+    }
+
+    /// Waits until the Redis answers a PING, it is stopped when it does not within [STARTUP].
+    fn ready(port: u16, instance: Instance) -> Option<Self> {
+        let redis = Self {
+            url: format!("redis://127.0.0.1:{port}"),
+            instance,
+        };
+        let start = Instant::now();
+        while start.elapsed() < STARTUP {
+            if ping(port) {
+                return Some(redis);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        None
+    }
+}
+
+impl Drop for Redis {
+    fn drop(&mut self) {
+        match &mut self.instance {
+            Instance::External => {}
+            Instance::Process(child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Instance::Container(id) => {
+                let _ = Command::new("docker")
+                    .args(["stop", id.as_str()])
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+            }
+        }
+    }
+}
+
+fn free_port() -> Option<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0").ok()?;
+    Some(listener.local_addr().ok()?.port())
+}
+
+fn ping(port: u16) -> bool {
+    let Ok(mut stream) = TcpStream::connect(("127.0.0.1", port)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let mut buf = [0; 7];
+    stream.write_all(b"PING\r\n").is_ok()
+        && stream.read_exact(&mut buf).is_ok()
+        && &buf == b"+PONG\r\n"
+}
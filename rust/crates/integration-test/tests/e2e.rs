@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use integration_test::{oid, redis::Redis};
+use mock_target::{HttpResponse, MockServer, Service};
+use scannerlib::storage::redis::{
+    CacheDispatcher, RedisCtx, RedisGetNvt, RedisWrapper, FEEDUPDATE_SELECTOR,
+};
+use scannerlib::storage::{DefaultDispatcher, Retriever};
+
+#[tokio::test]
+async fn feed_update() {
+    let storage = DefaultDispatcher::new();
+    integration_test::update(&storage).await.unwrap();
+    let mut oids: Vec<_> = storage.vts().unwrap().map(|x| x.oid).collect();
+    oids.sort();
+    assert_eq!(
+        oids,
+        vec![oid::SSH_DETECT, oid::SSH_OUTDATED, oid::HTTP_DETECT]
+    );
+}
+
+#[tokio::test]
+#[ignore = "requires Redis, run with --include-ignored"]
+async fn feed_update_redis() {
+    let redis = Redis::start().expect("REDIS_URL, redis-server or docker is required");
+    let storage = CacheDispatcher::as_dispatcher(redis.url(), FEEDUPDATE_SELECTOR).unwrap();
+    integration_test::update(&storage).await.unwrap();
+    let mut ctx = RedisCtx::open(redis.url(), FEEDUPDATE_SELECTOR).unwrap();
+    let vt = ctx.redis_get_vt(oid::SSH_OUTDATED).unwrap().unwrap();
+    assert_eq!(vt.filename, "ssh_outdated.nasl");
+    assert_eq!(vt.dependencies, vec!["ssh_detect.nasl"]);
+    assert_eq!(
+        ctx.lindex("filename:ssh_outdated.nasl", 1).unwrap(),
+        oid::SSH_OUTDATED
+    );
+    assert_eq!(ctx.keys("nvt:*").unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn scan() {
+    let ssh = MockServer::start(Service::ssh("OpenSSH_7.4")).unwrap();
+    let http = MockServer::start(
+        Service::http("nginx/1.25.3").route("/", HttpResponse::new(200, "<html></html>")),
+    )
+    .unwrap();
+    let scan = integration_test::scan(
+        "e2e",
+        &[(ssh.port(), "ssh"), (http.port(), "http")],
+        &[oid::SSH_OUTDATED, oid::HTTP_DETECT],
+    );
+    let results = integration_test::run_json(&scan).await.unwrap();
+    let results: Vec<_> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| {
+            (
+                x["oid"].as_str().unwrap().to_string(),
+                x["type"].as_str().unwrap().to_string(),
+                x["port"].as_i64().unwrap() as u16,
+                x["message"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    let expected = [
+        (
+            oid::SSH_DETECT,
+            "log",
+            ssh.port(),
+            "Detected SSH server: OpenSSH_7.4",
+        ),
+        (
+            oid::SSH_OUTDATED,
+            "alarm",
+            ssh.port(),
+            "Installed version: OpenSSH_7.4",
+        ),
+        (
+            oid::HTTP_DETECT,
+            "log",
+            http.port(),
+            "Detected HTTP server: nginx/1.25.3",
+        ),
+    ]
+    // results are ordered by OID
+    .map(|(oid, kind, port, message)| {
+        (oid.to_string(), kind.to_string(), port, message.to_string())
+    });
+    assert_eq!(results, expected);
+}
+
+#[tokio::test]
+async fn scan_without_vulnerability() {
+    let ssh = MockServer::start(Service::ssh("OpenSSH_9.6")).unwrap();
+    let scan = integration_test::scan("current", &[(ssh.port(), "ssh")], &[oid::SSH_OUTDATED]);
+    let results = integration_test::run_json(&scan).await.unwrap();
+    let oids: Vec<_> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["oid"].as_str().unwrap())
+        .collect();
+    assert_eq!(oids, vec![oid::SSH_DETECT]);
+}
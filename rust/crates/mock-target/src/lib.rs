@@ -0,0 +1,574 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Mock target services for hermetic integration tests.
+//!
+//! A [MockServer] listens on a random port of the loopback interface and answers each connection
+//! according to a [Service]: a static banner, a line based protocol like FTP or SMTP or canned
+//! HTTP responses. Each service can be wrapped in TLS using the bundled self-signed
+//! [CERTIFICATE] for `localhost` and `127.0.0.1`.
+//!
+//! ```
+//! use std::io::Read;
+//! use mock_target::{MockServer, Service};
+//!
+//! let server = MockServer::start(Service::ssh("OpenSSH_9.2")).unwrap();
+//! let mut stream = std::net::TcpStream::connect(server.addr()).unwrap();
+//! let mut banner = [0; 21];
+//! stream.read_exact(&mut banner).unwrap();
+//! assert_eq!(&banner, b"SSH-2.0-OpenSSH_9.2\r\n");
+//! ```
+//!
+//! The server stops accepting connections when it is dropped.
+
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+/// Self-signed certificate in PEM format used by TLS services
+pub const CERTIFICATE: &str = include_str!("../certs/server.pem");
+/// Private key of [CERTIFICATE] in PEM format
+pub const PRIVATE_KEY: &str = include_str!("../certs/server.key");
+
+/// Time after which an idle connection is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A canned HTTP response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Default for HttpResponse {
+    fn default() -> Self {
+        Self::new(200, "")
+    }
+}
+
+impl HttpResponse {
+    /// Creates a response with the given status code and body.
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+
+    fn encode(&self, server: &str, head: bool) -> Vec<u8> {
+        let mut result = format!("HTTP/1.1 {} {}\r\n", self.status, self.reason());
+        if !server.is_empty() {
+            result.push_str(&format!("Server: {server}\r\n"));
+        }
+        for (name, value) in &self.headers {
+            result.push_str(&format!("{name}: {value}\r\n"));
+        }
+        result.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+        let mut result = result.into_bytes();
+        if !head {
+            result.extend_from_slice(&self.body);
+        }
+        result
+    }
+}
+
+/// Describes how a mock service answers a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Service {
+    /// Sends the banner and keeps the connection open until the client closes it.
+    Banner(Vec<u8>),
+    /// Sends the greeting and answers each line with the reply of its command.
+    ///
+    /// Commands are matched case-insensitively on the first word of a line, unknown commands get
+    /// the fallback. The connection is closed after answering `QUIT`.
+    Lines {
+        /// Sent after connecting
+        greeting: String,
+        /// Command and reply, multiple lines of a reply are separated by `\r\n`
+        replies: Vec<(String, String)>,
+        /// Reply to unknown commands
+        fallback: String,
+    },
+    /// Answers HTTP/1.x requests with the response registered for the path.
+    ///
+    /// The query of a request is ignored, unknown paths get a `404`.
+    Http {
+        /// Value of the `Server` header, omitted when empty
+        server: String,
+        /// Path and response
+        routes: Vec<(String, HttpResponse)>,
+    },
+}
+
+impl Service {
+    /// Creates a service sending the given banner.
+    pub fn banner(banner: impl Into<Vec<u8>>) -> Self {
+        Self::Banner(banner.into())
+    }
+
+    /// Creates an SSH service announcing the given software version.
+    pub fn ssh(software: &str) -> Self {
+        Self::banner(format!("SSH-2.0-{software}\r\n"))
+    }
+
+    /// Creates an FTP service with the given banner that rejects every login.
+    pub fn ftp(banner: &str) -> Self {
+        Self::Lines {
+            greeting: format!("220 {banner}"),
+            replies: vec![],
+            fallback: "500 Unknown command.".into(),
+        }
+        .reply("USER", "331 Please specify the password.")
+        .reply("PASS", "530 Login incorrect.")
+        .reply("SYST", "215 UNIX Type: L8")
+        .reply("QUIT", "221 Goodbye.")
+    }
+
+    /// Creates an SMTP service with the given host name and banner that does not offer STARTTLS.
+    pub fn smtp(hostname: &str, banner: &str) -> Self {
+        Self::Lines {
+            greeting: format!("220 {hostname} {banner}"),
+            replies: vec![],
+            fallback: "502 5.5.2 Error: command not recognized".into(),
+        }
+        .reply(
+            "EHLO",
+            &format!("250-{hostname}\r\n250-SIZE 10240000\r\n250 8BITMIME"),
+        )
+        .reply("HELO", &format!("250 {hostname}"))
+        .reply("STARTTLS", "454 4.7.0 TLS not available")
+        .reply("QUIT", "221 2.0.0 Bye")
+    }
+
+    /// Creates an HTTP service with the given `Server` header and without routes.
+    pub fn http(server: &str) -> Self {
+        Self::Http {
+            server: server.into(),
+            routes: vec![],
+        }
+    }
+
+    /// Sets the reply to a command of a line based service.
+    ///
+    /// Does nothing for other services.
+    pub fn reply(mut self, command: &str, reply: &str) -> Self {
+        if let Self::Lines { replies, .. } = &mut self {
+            replies.retain(|(x, _)| !x.eq_ignore_ascii_case(command));
+            replies.push((command.to_owned(), reply.to_owned()));
+        }
+        self
+    }
+
+    /// Sets the response to a path of an HTTP service.
+    ///
+    /// Does nothing for other services.
+    pub fn route(mut self, path: &str, response: HttpResponse) -> Self {
+        if let Self::Http { routes, .. } = &mut self {
+            routes.retain(|(x, _)| x != path);
+            routes.push((path.to_owned(), response));
+        }
+        self
+    }
+
+    fn serve<S: Read + Write>(&self, stream: &mut Recorder<S>) -> io::Result<()> {
+        match self {
+            Service::Banner(banner) => {
+                stream.write_all(banner)?;
+                let mut buffer = [0; 1024];
+                while stream.read(&mut buffer)? > 0 {}
+                Ok(())
+            }
+            Service::Lines {
+                greeting,
+                replies,
+                fallback,
+            } => {
+                stream.write_all(format!("{greeting}\r\n").as_bytes())?;
+                while let Some(line) = stream.read_until(b"\n")? {
+                    let line = String::from_utf8_lossy(&line);
+                    let command = line.split_whitespace().next().unwrap_or_default();
+                    let reply = replies
+                        .iter()
+                        .find(|(x, _)| x.eq_ignore_ascii_case(command))
+                        .map(|(_, x)| x)
+                        .unwrap_or(fallback);
+                    stream.write_all(format!("{reply}\r\n").as_bytes())?;
+                    if command.eq_ignore_ascii_case("QUIT") {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Service::Http { server, routes } => {
+                while let Some(head) = stream.read_until(b"\r\n\r\n")? {
+                    let head = String::from_utf8_lossy(&head);
+                    let mut lines = head.lines();
+                    let mut request = lines.next().unwrap_or_default().split_whitespace();
+                    let method = request.next().unwrap_or_default();
+                    let path = request.next().unwrap_or_default();
+                    let path = path.split('?').next().unwrap_or_default();
+                    let version = request.next().unwrap_or_default();
+                    let mut length = 0;
+                    let mut close = version == "HTTP/1.0";
+                    for (name, value) in lines.filter_map(|x| x.split_once(':')) {
+                        let value = value.trim();
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.parse().unwrap_or_default();
+                        } else if name.eq_ignore_ascii_case("connection") {
+                            close = value.eq_ignore_ascii_case("close");
+                        }
+                    }
+                    stream.skip(length)?;
+                    let not_found = HttpResponse::new(404, "");
+                    let response = routes
+                        .iter()
+                        .find(|(x, _)| x == path)
+                        .map(|(_, x)| x)
+                        .unwrap_or(&not_found);
+                    stream.write_all(&response.encode(server, method == "HEAD"))?;
+                    if close {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Stores the data read from a connection
+struct Recorder<S> {
+    stream: S,
+    pending: Vec<u8>,
+    received: Arc<Mutex<Vec<Vec<u8>>>>,
+    index: usize,
+}
+
+impl<S: Read + Write> Recorder<S> {
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        let read = match self.stream.read(&mut buffer) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => return Err(e),
+        };
+        self.pending.extend_from_slice(&buffer[..read]);
+        if let Ok(mut received) = self.received.lock() {
+            received[self.index].extend_from_slice(&buffer[..read]);
+        }
+        Ok(read > 0)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() && !self.fill()? {
+            return Ok(0);
+        }
+        let read = buffer.len().min(self.pending.len());
+        buffer[..read].copy_from_slice(&self.pending[..read]);
+        self.pending.drain(..read);
+        Ok(read)
+    }
+
+    /// Returns the data up to and including the delimiter or None when the connection got closed.
+    fn read_until(&mut self, delimiter: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(i) = self
+                .pending
+                .windows(delimiter.len())
+                .position(|x| x == delimiter)
+            {
+                return Ok(Some(self.pending.drain(..i + delimiter.len()).collect()));
+            }
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn skip(&mut self, mut amount: usize) -> io::Result<()> {
+        let mut buffer = [0; 1024];
+        while amount > 0 {
+            let len = buffer.len().min(amount);
+            match self.read(&mut buffer[..len])? {
+                0 => break,
+                read => amount -= read,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.stream.write_all(data)?;
+        self.stream.flush()
+    }
+}
+
+fn tls_config() -> io::Result<Arc<ServerConfig>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(CERTIFICATE.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(PRIVATE_KEY.as_bytes()))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing private key"))?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Arc::new(config))
+}
+
+/// A mock service listening on the loopback interface
+pub struct MockServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    received: Arc<Mutex<Vec<Vec<u8>>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts the service on a random port.
+    pub fn start(service: Service) -> io::Result<Self> {
+        Self::listen(service, None)
+    }
+
+    /// Starts the service wrapped in TLS using [CERTIFICATE] on a random port.
+    pub fn start_tls(service: Service) -> io::Result<Self> {
+        Self::listen(service, Some(tls_config()?))
+    }
+
+    fn listen(service: Service, tls: Option<Arc<ServerConfig>>) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let service = Arc::new(service);
+        let handle = {
+            let stop = stop.clone();
+            let received = received.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let index = match received.lock() {
+                        Ok(mut x) => {
+                            x.push(Vec::new());
+                            x.len() - 1
+                        }
+                        Err(_) => break,
+                    };
+                    let service = service.clone();
+                    let received = received.clone();
+                    let tls = tls.clone();
+                    thread::spawn(move || {
+                        let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+                        let _ = Self::handle(&service, stream, tls, received, index);
+                    });
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            stop,
+            received,
+            handle: Some(handle),
+        })
+    }
+
+    fn handle(
+        service: &Service,
+        stream: TcpStream,
+        tls: Option<Arc<ServerConfig>>,
+        received: Arc<Mutex<Vec<Vec<u8>>>>,
+        index: usize,
+    ) -> io::Result<()> {
+        match tls {
+            Some(config) => {
+                let connection = ServerConnection::new(config)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let stream = StreamOwned::new(connection, stream);
+                let mut recorder = Recorder {
+                    stream,
+                    pending: vec![],
+                    received,
+                    index,
+                };
+                let result = service.serve(&mut recorder);
+                recorder.stream.conn.send_close_notify();
+                let _ = recorder.stream.flush();
+                result
+            }
+            None => service.serve(&mut Recorder {
+                stream,
+                pending: vec![],
+                received,
+                index,
+            }),
+        }
+    }
+
+    /// Returns the address the service listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the port the service listens on.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Returns the data received so far, one entry per accepted connection.
+    ///
+    /// For TLS services the decrypted data is returned.
+    pub fn received(&self) -> Vec<Vec<u8>> {
+        self.received.lock().map(|x| x.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wakes up the blocking accept
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore};
+
+    use super::*;
+
+    fn exchange(stream: &mut (impl Read + Write), request: &[u8]) -> String {
+        if !request.is_empty() {
+            stream.write_all(request).unwrap();
+        }
+        let mut buffer = [0; 1024];
+        let read = stream.read(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer[..read]).into_owned()
+    }
+
+    #[test]
+    fn lines() {
+        let server = MockServer::start(
+            Service::ftp("(vsFTPd 3.0.3)").reply("PASS", "230 Login successful."),
+        )
+        .unwrap();
+        let mut stream = BufReader::new(TcpStream::connect(server.addr()).unwrap());
+        let mut read_line = |request: &[u8]| {
+            if !request.is_empty() {
+                stream.get_mut().write_all(request).unwrap();
+            }
+            let mut line = String::new();
+            stream.read_line(&mut line).unwrap();
+            line
+        };
+        assert_eq!(read_line(b""), "220 (vsFTPd 3.0.3)\r\n");
+        assert_eq!(
+            read_line(b"user anonymous\r\n"),
+            "331 Please specify the password.\r\n"
+        );
+        assert_eq!(read_line(b"PASS secret\r\n"), "230 Login successful.\r\n");
+        assert_eq!(read_line(b"NOOP\r\n"), "500 Unknown command.\r\n");
+        assert_eq!(read_line(b"QUIT\r\n"), "221 Goodbye.\r\n");
+        assert_eq!(read_line(b""), "");
+        assert_eq!(
+            server.received(),
+            vec![b"user anonymous\r\nPASS secret\r\nNOOP\r\nQUIT\r\n".to_vec()]
+        );
+    }
+
+    #[test]
+    fn http() {
+        let server = MockServer::start(Service::http("Apache/2.4.57").route(
+            "/index.html",
+            HttpResponse::new(200, "hello").header("Content-Type", "text/plain"),
+        ))
+        .unwrap();
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        assert_eq!(
+            exchange(&mut stream, b"GET /index.html?a=1 HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            "HTTP/1.1 200 OK\r\nServer: Apache/2.4.57\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello"
+        );
+        assert_eq!(
+            exchange(
+                &mut stream,
+                b"POST /missing HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc"
+            ),
+            "HTTP/1.1 404 Not Found\r\nServer: Apache/2.4.57\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert_eq!(exchange(&mut stream, b""), "");
+    }
+
+    #[test]
+    fn tls() {
+        let server = MockServer::start_tls(Service::smtp("mail.example.com", "ESMTP")).unwrap();
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(CERTIFICATE.as_bytes())) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connection =
+            ClientConnection::new(Arc::new(config), ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        let mut stream = StreamOwned::new(connection, TcpStream::connect(server.addr()).unwrap());
+        assert_eq!(exchange(&mut stream, b""), "220 mail.example.com ESMTP\r\n");
+        assert_eq!(
+            exchange(&mut stream, b"EHLO test\r\n"),
+            "250-mail.example.com\r\n250-SIZE 10240000\r\n250 8BITMIME\r\n"
+        );
+        assert_eq!(
+            exchange(&mut stream, b"STARTTLS\r\n"),
+            "454 4.7.0 TLS not available\r\n"
+        );
+        assert_eq!(
+            server.received(),
+            vec![b"EHLO test\r\nSTARTTLS\r\n".to_vec()]
+        );
+    }
+}
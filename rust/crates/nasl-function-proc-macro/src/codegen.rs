@@ -189,7 +189,10 @@ impl<'a> ArgsStruct<'a> {
         let mangled_name = format!("_internal_{}", ident);
         let mangled_ident = Ident::new(&mangled_name, ident.span());
         let inner_call = self.get_inner_call_expr(&mangled_ident, asyncness);
+        // lints allowed for the function also apply to its body within the inner function
+        let lint_attrs = attrs.iter().filter(|x| x.path().is_ident("allow"));
         quote! {
+            #(#lint_attrs)*
             #asyncness fn #mangled_ident #generics ( #fn_args ) -> #output_ty {
                 #(#stmts)*
             }
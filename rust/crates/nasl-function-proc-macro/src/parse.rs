@@ -172,13 +172,21 @@ fn parse_function_args<'a>(
     function: &'a ItemFn,
     attrs: &Attrs,
 ) -> Result<(Vec<Arg<'a>>, ReceiverType)> {
+    // The context and the register are no arguments of the NASL function,
+    // so they do not take up a position.
+    let mut position = 0;
     let args = function
         .sig
         .inputs
         .iter()
         .filter(|arg| !is_self_arg(arg))
-        .enumerate()
-        .map(|(position, arg)| Arg::new(arg, attrs, position))
+        .map(|arg| {
+            let arg = Arg::new(arg, attrs, position)?;
+            if !matches!(arg.kind, ArgKind::Context | ArgKind::Register) {
+                position += 1;
+            }
+            Ok(arg)
+        })
         .collect::<Result<Vec<_>>>()?;
     let receiver_type = ReceiverType::new(&function.sig.inputs)?;
     Ok((args, receiver_type))
@@ -0,0 +1,346 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Utilities to test builtin functions on interpreter level.
+//!
+//! [NaslTest] runs NASL snippets against an in-memory storage and keeps that storage between
+//! runs, so that the KB items and results a builtin dispatches can be inspected afterwards:
+//!
+//! ```
+//! use nasl_test::{assert_kb, assert_nasl, NaslTest};
+//!
+//! let test = NaslTest::default();
+//! assert_nasl!(test, r#"set_kb_item(name: "Ports/tcp/22", value: 1);"#, ());
+//! assert_nasl!(test, r#"get_kb_item("Ports/tcp/22");"#, 1);
+//! assert_kb!(test, "Ports/tcp/22", [1]);
+//! ```
+//!
+//! Snapshots compare the outcome of each statement and the dispatched results with a file in the
+//! `snapshots` directory of the calling crate, see [assert_snapshot!]. Run the tests with
+//! `NASL_TEST_UPDATE=1` to create or update them.
+
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use futures::StreamExt;
+use scannerlib::models;
+use scannerlib::nasl::interpreter::{CodeInterpreter, InterpretErrorKind};
+use scannerlib::nasl::nasl_std_functions;
+use scannerlib::nasl::prelude::*;
+use scannerlib::nasl::syntax::LoadError;
+use scannerlib::nasl::utils::{Executor, IntoFunctionSet};
+use scannerlib::storage::{
+    types::Primitive, ContextKey, DefaultDispatcher, Dispatcher, Field, Kb, Retrieve, Retriever,
+};
+
+/// Environment variable that enables writing snapshots instead of comparing them
+pub const UPDATE_SNAPSHOTS: &str = "NASL_TEST_UPDATE";
+
+/// Loads the files that were added via [NaslTest::with_file]
+#[derive(Debug, Default, Clone)]
+pub struct Files(HashMap<String, String>);
+
+impl Loader for Files {
+    fn load(&self, key: &str) -> Result<String, LoadError> {
+        self.0
+            .get(key)
+            .cloned()
+            .ok_or_else(|| LoadError::NotFound(key.to_owned()))
+    }
+
+    fn root_path(&self) -> Result<String, LoadError> {
+        Ok(String::default())
+    }
+}
+
+/// Runs NASL code against an in-memory context.
+///
+/// The storage is shared by all runs of the same NaslTest while the variables of each run start
+/// from the initial ones.
+pub struct NaslTest {
+    context: ContextFactory<Files, DefaultDispatcher>,
+    key: ContextKey,
+    variables: Vec<(String, NaslValue)>,
+}
+
+impl Default for NaslTest {
+    fn default() -> Self {
+        Self {
+            context: ContextFactory::new(Files::default(), DefaultDispatcher::default()),
+            key: ContextKey::Scan("nasl-test".to_owned(), Some("127.0.0.1".to_owned())),
+            variables: vec![],
+        }
+    }
+}
+
+impl NaslTest {
+    /// Replaces the builtin functions, by default all functions of the current build are
+    /// available.
+    pub fn with_executor(mut self, executor: Executor) -> Self {
+        self.context = self.context.functions(executor);
+        self
+    }
+
+    /// Replaces the builtin functions with the standard functions and the given set.
+    pub fn with_function_set<S>(self, set: S) -> Self
+    where
+        S: IntoFunctionSet + 'static,
+        S::State: Send + Sync,
+    {
+        let mut executor = nasl_std_functions();
+        executor.add_set(set);
+        self.with_executor(executor)
+    }
+
+    /// Sets the scan id and target the code runs against.
+    pub fn with_target(mut self, scan_id: &str, target: &str) -> Self {
+        self.key = ContextKey::Scan(scan_id.to_owned(), Some(target.to_owned()));
+        self
+    }
+
+    /// Adds a file that can be included by the code.
+    pub fn with_file(mut self, name: &str, code: &str) -> Self {
+        self.context
+            .loader
+            .0
+            .insert(name.to_owned(), code.to_owned());
+        self
+    }
+
+    /// Sets a variable before each run.
+    pub fn with_variable(mut self, name: &str, value: impl Into<NaslValue>) -> Self {
+        self.variables.push((name.to_owned(), value.into()));
+        self
+    }
+
+    /// Stores a KB item of the target before the code runs.
+    pub fn with_kb(self, key: &str, value: impl Into<Primitive>) -> Self {
+        let item = Kb {
+            key: key.to_owned(),
+            value: value.into(),
+            expire: None,
+        };
+        self.context
+            .storage
+            .dispatch(&self.key, Field::KB(item))
+            .expect("in-memory storage");
+        self
+    }
+
+    /// Returns the context the code runs in.
+    pub fn context(&self) -> Context<'_> {
+        self.context.build(self.key.clone())
+    }
+
+    /// Runs the code and returns the result of each statement.
+    ///
+    /// Panics when the interpreter fails with an error that is not caused by a function call,
+    /// e.g. on a syntax error.
+    pub fn run(&self, code: &str) -> Vec<NaslResult> {
+        let variables: Vec<_> = self
+            .variables
+            .iter()
+            .map(|(k, v)| (k.clone(), ContextType::Value(v.clone())))
+            .collect();
+        let register = Register::root_initial(&variables);
+        let context = self.context();
+        let interpreter = CodeInterpreter::new(code, register, &context);
+        futures::executor::block_on(async {
+            interpreter
+                .stream()
+                .map(|x| {
+                    x.map_err(|e| match e.kind {
+                        InterpretErrorKind::FunctionCallError(f) => f.kind,
+                        e => panic!("Unable to interpret {code:?}: {e}"),
+                    })
+                })
+                .collect()
+                .await
+        })
+    }
+
+    /// Runs the code and returns the result of the last statement.
+    pub fn eval(&self, code: &str) -> NaslResult {
+        self.run(code).pop().unwrap_or(Ok(NaslValue::Null))
+    }
+
+    /// Returns the values of a KB item of the target.
+    pub fn kb(&self, key: &str) -> Vec<Primitive> {
+        self.context
+            .storage
+            .retrieve(&self.key, Retrieve::KB(key.to_owned()))
+            .expect("in-memory storage")
+            .filter_map(|x| match x {
+                Field::KB(kb) => Some(kb.value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the results dispatched for the target, e.g. by log_message.
+    pub fn results(&self) -> Vec<models::Result> {
+        self.context
+            .storage
+            .results(&self.key)
+            .expect("in-memory storage")
+            .collect()
+    }
+
+    /// Runs the code and renders the outcome of each statement followed by the dispatched
+    /// results.
+    pub fn snapshot(&self, code: &str) -> String {
+        let mut out = String::new();
+        for (i, result) in self.run(code).iter().enumerate() {
+            let _ = match result {
+                Ok(x) => writeln!(out, "{i}: {x:?}"),
+                Err(e) => writeln!(out, "{i}: error: {e}"),
+            };
+        }
+        for result in self.results() {
+            let _ = writeln!(
+                out,
+                "result {:?}: {:?}",
+                result.r_type,
+                result.message.unwrap_or_default()
+            );
+        }
+        out
+    }
+}
+
+/// Compares a rendered snapshot with the `{name}.snap` file in `dir`.
+///
+/// Writes the file instead when it does not exist or [UPDATE_SNAPSHOTS] is set.
+#[track_caller]
+pub fn check_snapshot(dir: impl AsRef<Path>, name: &str, actual: &str) {
+    let path = dir.as_ref().join(format!("{name}.snap"));
+    let update = std::env::var_os(UPDATE_SNAPSHOTS).is_some();
+    match std::fs::read_to_string(&path) {
+        Ok(expected) if !update => {
+            if expected != actual {
+                panic!(
+                    "Snapshot {} differs, rerun with {UPDATE_SNAPSHOTS}=1 to update it.\nExpected:\n{expected}\nFound:\n{actual}",
+                    path.display()
+                );
+            }
+        }
+        _ => {
+            std::fs::create_dir_all(dir.as_ref()).expect("snapshot directory");
+            std::fs::write(&path, actual).expect("snapshot file");
+        }
+    }
+}
+
+/// Asserts that the last statement of the code returns the expected value.
+///
+/// Without a NaslTest the code runs in a new default one.
+#[macro_export]
+macro_rules! assert_nasl {
+    ($test: expr, $code: expr, $expected: expr $(,)?) => {
+        assert_eq!(
+            $test.eval($code),
+            $crate::__private::ToNaslResult::to_nasl_result($expected),
+            "In code {:?}",
+            $code
+        )
+    };
+    ($code: expr, $expected: expr $(,)?) => {
+        $crate::assert_nasl!($crate::NaslTest::default(), $code, $expected)
+    };
+}
+
+/// Asserts that the last statement of the code returns a value matching the pattern.
+#[macro_export]
+macro_rules! assert_nasl_matches {
+    ($test: expr, $code: expr, $pat: pat $(,)?) => {
+        match $test.eval($code) {
+            Ok($pat) => {}
+            x => panic!("In code {:?}: {x:?} does not match", $code),
+        }
+    };
+    ($code: expr, $pat: pat $(,)?) => {
+        $crate::assert_nasl_matches!($crate::NaslTest::default(), $code, $pat)
+    };
+}
+
+/// Asserts that the last statement of the code returns an error matching the pattern.
+#[macro_export]
+macro_rules! assert_nasl_err {
+    ($test: expr, $code: expr, $pat: pat $(,)?) => {
+        match $test.eval($code) {
+            Err($pat) => {}
+            x => panic!("In code {:?}: {x:?} is not the expected error", $code),
+        }
+    };
+    ($code: expr, $pat: pat $(,)?) => {
+        $crate::assert_nasl_err!($crate::NaslTest::default(), $code, $pat)
+    };
+}
+
+/// Asserts the values of a KB item of the target.
+#[macro_export]
+macro_rules! assert_kb {
+    ($test: expr, $key: expr, [$($value: expr),* $(,)?] $(,)?) => {
+        assert_eq!(
+            $test.kb($key),
+            vec![$($crate::__private::Primitive::from($value)),*],
+            "KB item {}",
+            $key
+        )
+    };
+}
+
+/// Asserts that the snapshot of the code equals `snapshots/{name}.snap` of the calling crate.
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($test: expr, $name: expr, $code: expr $(,)?) => {
+        $crate::check_snapshot(
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots"),
+            $name,
+            &$test.snapshot($code),
+        )
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use scannerlib::nasl::prelude::ToNaslResult;
+    pub use scannerlib::storage::types::Primitive;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_and_kb() {
+        let test = NaslTest::default()
+            .with_kb("Services/www", 80)
+            .with_variable("offset", 1)
+            .with_file("helper.inc", "function add(a) { return a + offset; }");
+        assert_nasl!(test, r#"get_kb_item("Services/www");"#, 80);
+        assert_nasl!(test, r#"include("helper.inc"); add(a: 2);"#, 3);
+        assert_nasl!(
+            test,
+            r#"set_kb_item(name: "Services/www", value: 443);"#,
+            ()
+        );
+        assert_kb!(test, "Services/www", [80, 443]);
+        assert_nasl_matches!("get_host_ip();", NaslValue::String(_));
+        assert_nasl_err!(
+            "set_kb_item(value: 1);",
+            FunctionErrorKind::MissingArguments(_)
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let test = NaslTest::default().with_target("sid", "192.0.2.1");
+        assert_snapshot!(
+            test,
+            "log_message",
+            r#"x = 1 + 2; log_message(data: "found " + x); get_host_ip();"#
+        );
+        assert_eq!(test.results().len(), 1);
+    }
+}
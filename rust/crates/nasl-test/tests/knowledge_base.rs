@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use nasl_test::{assert_kb, assert_nasl, NaslTest};
+
+#[test]
+fn set_kb_item_appends() {
+    let test = NaslTest::default().with_kb("test", 1);
+    assert_nasl!(test, r#"set_kb_item(name: "test", value: 2);"#, ());
+    assert_kb!(test, "test", [1, 2]);
+}
+
+#[test]
+fn replace_kb_item_replaces_all_values() {
+    let test = NaslTest::default().with_kb("test", 1).with_kb("test", 2);
+    assert_nasl!(test, r#"replace_kb_item(name: "test", value: 3);"#, ());
+    assert_kb!(test, "test", [3]);
+    assert_nasl!(test, r#"get_kb_item("test");"#, 3);
+}
@@ -0,0 +1,42 @@
+/* SPDX-FileCopyrightText: 2024 Greenbone AG
+ *
+ * SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+ */
+
+/* Defines `example_echo` returning its arguments.
+ *
+ * cc -shared -fPIC -o example.so example.c
+ */
+
+#include "nasl_plugin.h"
+
+#include <stdlib.h>
+#include <string.h>
+
+static int32_t
+echo (const char *arguments, char **result)
+{
+  *result = strdup (arguments);
+  return *result == NULL;
+}
+
+static void
+free_result (char *result)
+{
+  free (result);
+}
+
+static const nasl_plugin_function functions[] = {
+  {"example_echo", echo},
+};
+
+static const nasl_plugin plugin = {
+  NASL_PLUGIN_ABI_VERSION, "example", functions,
+  sizeof (functions) / sizeof (functions[0]), free_result,
+};
+
+const nasl_plugin *
+nasl_plugin_register (void)
+{
+  return &plugin;
+}
@@ -0,0 +1,45 @@
+/* SPDX-FileCopyrightText: 2024 Greenbone AG
+ *
+ * SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+ */
+
+/* Interface of shared objects defining additional builtin functions. */
+
+#ifndef NASL_PLUGIN_H
+#define NASL_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define NASL_PLUGIN_ABI_VERSION 1
+
+/* Called with a JSON object containing `target`, `positional` and `named`.
+ *
+ * Returns 0 on success and sets `result` to a JSON value or leaves it NULL.
+ * Any other value is an error and `result` may be set to an error message.
+ * Data is encoded as {"$data": "<hex>"} in both directions.
+ */
+typedef int32_t (*nasl_plugin_call) (const char *arguments, char **result);
+
+typedef struct
+{
+  const char *name;
+  nasl_plugin_call call;
+} nasl_plugin_function;
+
+typedef struct
+{
+  uint32_t abi_version;
+  const char *name;
+  const nasl_plugin_function *functions;
+  size_t functions_len;
+  /* frees the strings set as result */
+  void (*free_result) (char *result);
+} nasl_plugin;
+
+/* Must be exported by the shared object, the returned pointer must stay
+ * valid as long as the shared object is loaded. */
+const nasl_plugin *
+nasl_plugin_register (void);
+
+#endif
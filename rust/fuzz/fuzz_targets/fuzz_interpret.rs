@@ -0,0 +1,28 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scannerlib::nasl::{
+    interpreter::CodeInterpreter, nasl_offline_functions, ContextFactory, NoOpLoader, Register,
+};
+use scannerlib::storage::{ContextKey, DefaultDispatcher};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(code) = std::str::from_utf8(data) else {
+        return;
+    };
+    // loops are not bounded, an endless loop would be reported as timeout
+    if ["while", "for", "repeat"].iter().any(|x| code.contains(x)) {
+        return;
+    }
+    // neither network nor blocking functions are available and includes are not loaded
+    let factory = ContextFactory::new(NoOpLoader::default(), DefaultDispatcher::default())
+        .functions(nasl_offline_functions());
+    let context = factory.build(ContextKey::FileName("fuzz.nasl".to_owned()));
+    for result in CodeInterpreter::new(code, Register::default(), &context).iter_blocking() {
+        let _ = result;
+    }
+});
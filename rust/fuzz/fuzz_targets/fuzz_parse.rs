@@ -4,6 +4,6 @@ use libfuzzer_sys::fuzz_target;
 
 fuzz_target!(|data: &[u8]| {
     if let Ok(s) = std::str::from_utf8(data) {
-        let _stmt = scannerlib::nasl::syntax::parse(&s).collect::<Vec<Result<scannerlib::nasl::syntax::Statement, scannerlib::nasl::syntax::SyntaxError>>>();
+        let _stmt = scannerlib::nasl::syntax::parse(s).collect::<Vec<_>>();
     }
 });
@@ -0,0 +1,116 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#![doc = include_str!("../README.md")]
+
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use scannerlib::{
+    feed::{HashSumFileItem, Update, VerifyError},
+    nasl::{syntax::parse as parse_nasl, FSPluginLoader},
+    notus::{HashsumProductLoader, Notus},
+    storage::{item::Nvt, ContextKey, DefaultDispatcher, Retrieve, Retriever},
+};
+
+create_exception!(scannerlib, ScannerlibError, PyException);
+
+fn error<E: ToString>(e: E) -> PyErr {
+    ScannerlibError::new_err(e.to_string())
+}
+
+/// Converts a serializable value into python objects via json.
+fn to_python<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(error)?;
+    Ok(py.import_bound("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+/// A syntax error found while parsing
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Diagnostic {
+    /// Line of the error, starting with 1, None at the end of the file
+    line: Option<usize>,
+    /// Column of the error, starting with 1, None at the end of the file
+    column: Option<usize>,
+    message: String,
+}
+
+#[pymethods]
+impl Diagnostic {
+    fn __repr__(&self) -> String {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{line}:{column}: {}", self.message),
+            _ => self.message.clone(),
+        }
+    }
+}
+
+/// Parses NASL code and returns the syntax errors, an empty list when the code is valid.
+#[pyfunction]
+fn parse(code: &str) -> Vec<Diagnostic> {
+    parse_nasl(code)
+        .filter_map(|x| x.err())
+        .map(|e| {
+            let position = e.as_token().map(|x| x.line_column);
+            Diagnostic {
+                line: position.map(|x| x.0),
+                column: position.map(|x| x.1),
+                message: e.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Runs a script of a feed in description mode and returns the described VT.
+#[pyfunction]
+fn describe(py: Python<'_>, feed: &str, script: &str) -> PyResult<PyObject> {
+    let nvt = py.allow_threads(|| {
+        let loader = FSPluginLoader::new(feed);
+        let storage = DefaultDispatcher::new();
+        let verifier = std::iter::empty::<Result<HashSumFileItem, VerifyError>>();
+        let update = Update::init("scannerlib", 5, &loader, &storage, verifier);
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(error)?
+            .block_on(update.describe(script))
+            .map_err(error)?;
+        storage
+            .retrieve(&ContextKey::FileName(script.to_string()), Retrieve::NVT(None))
+            .map_err(error)?
+            .find_map(|x| Nvt::try_from(x).ok())
+            .ok_or_else(|| error(format!("{script} did not describe a VT")))
+    })?;
+    to_python(py, &nvt)
+}
+
+/// Runs a notus scan of the installed packages of an operating system and returns the vulnerable
+/// packages per advisory OID.
+#[pyfunction]
+#[pyo3(signature = (products, os, packages, signature_check = true))]
+fn notus_scan(
+    py: Python<'_>,
+    products: &str,
+    os: &str,
+    packages: Vec<String>,
+    signature_check: bool,
+) -> PyResult<PyObject> {
+    let results = py.allow_threads(|| {
+        let loader = HashsumProductLoader::new(FSPluginLoader::new(products)).map_err(error)?;
+        Notus::new(loader, signature_check)
+            .scan(os, &packages)
+            .map_err(error)
+    })?;
+    to_python(py, &results)
+}
+
+#[pymodule]
+#[pyo3(name = "scannerlib")]
+fn scannerlib_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ScannerlibError", m.py().get_type_bound::<ScannerlibError>())?;
+    m.add_class::<Diagnostic>()?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(describe, m)?)?;
+    m.add_function(wrap_pyfunction!(notus_scan, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+# SPDX-FileCopyrightText: 2024 Greenbone AG
+#
+# SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+from pathlib import Path
+
+import pytest
+
+import scannerlib
+
+FEED = Path(__file__).parents[2] / "examples" / "feed"
+
+
+def test_parse():
+    assert scannerlib.parse("a = 1;") == []
+    diagnostics = scannerlib.parse("a = 1;\nif (a {")
+    assert diagnostics
+    assert (diagnostics[0].line, diagnostics[0].column) == (2, 7)
+    assert diagnostics[0].message
+
+
+def test_describe():
+    vt = scannerlib.describe(str(FEED / "nasl"), "2.nasl")
+    assert vt["oid"] == "0.0.0.0.0.0.0.0.0.2"
+    assert vt["dependencies"] == ["1.nasl"]
+    with pytest.raises(scannerlib.ScannerlibError):
+        scannerlib.describe(str(FEED / "nasl"), "missing.nasl")
+
+
+def test_notus_scan():
+    results = scannerlib.notus_scan(
+        str(FEED / "notus" / "products"), "test", ["man-db-2.8.5-1"], signature_check=False
+    )
+    assert "1.3.6.1.4.1.25623.1.1.1.2.2024.3731" in results
+    with pytest.raises(scannerlib.ScannerlibError):
+        scannerlib.notus_scan(str(FEED / "notus" / "products"), "unknown", [], False)
@@ -0,0 +1,304 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Detects which hosts of a target are alive without running any VTs.
+//!
+//! The methods follow the alive test of the C scanner (Boreas) as close as possible without raw
+//! sockets:
+//! - `icmp` sends an ICMP echo request via an unprivileged ICMP socket
+//! - `tcp_ack` and `tcp_syn` connect to the alive test ports, a refused connection counts as
+//!   alive as the host responded with a reset
+//! - `consider_alive` reports every host as alive
+//!
+//! `arp` requires raw sockets and is skipped. The methods are tried in the given order until one
+//! of them detects the host.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol as SocketProtocol, Socket, Type};
+
+use crate::models::{AliveTestMethods, Host, Protocol, Target};
+
+/// Ports used by the TCP methods when a target does not define alive test ports.
+pub const DEFAULT_PORTS: &[u16] = &[80, 137, 587, 3128, 8081];
+
+/// Time to wait for an answer of a single probe.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Maximum number of hosts that are tested in parallel.
+const PARALLEL_HOSTS: usize = 64;
+
+/// The alive status of a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct AliveStatus {
+    /// The tested host
+    pub host: Host,
+    /// True when the host answered
+    pub alive: bool,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// The method that detected the host
+    pub method: Option<AliveTestMethods>,
+}
+
+/// Tests hosts with the configured methods
+#[derive(Debug, Clone)]
+pub struct AliveTest {
+    methods: Vec<AliveTestMethods>,
+    ports: Vec<u16>,
+    timeout: Duration,
+}
+
+impl Default for AliveTest {
+    fn default() -> Self {
+        Self {
+            methods: vec![AliveTestMethods::Icmp, AliveTestMethods::TcpAck],
+            ports: DEFAULT_PORTS.to_vec(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl From<&Target> for AliveTest {
+    fn from(target: &Target) -> Self {
+        let ports: Vec<u16> = target
+            .alive_test_ports
+            .iter()
+            .filter(|x| x.protocol != Some(Protocol::UDP))
+            .flat_map(|x| &x.range)
+            .flat_map(|x| x.start..=x.end.unwrap_or(x.start))
+            .filter_map(|x| u16::try_from(x).ok())
+            .collect();
+        Self::default()
+            .with_methods(target.alive_test_methods.clone())
+            .with_ports(ports)
+    }
+}
+
+impl AliveTest {
+    /// Uses the given methods, the default methods are kept when it is empty.
+    pub fn with_methods(mut self, methods: Vec<AliveTestMethods>) -> Self {
+        if !methods.is_empty() {
+            self.methods = methods;
+        }
+        self
+    }
+
+    /// Uses the given TCP ports, the default ports are kept when it is empty.
+    pub fn with_ports(mut self, ports: Vec<u16>) -> Self {
+        if !ports.is_empty() {
+            self.ports = ports;
+        }
+        self
+    }
+
+    /// Sets the time to wait for an answer of a single probe.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Tests a single host.
+    pub fn check(&self, host: &str) -> AliveStatus {
+        let addrs = resolve(host);
+        let mut tcp_tested = false;
+        for method in &self.methods {
+            let alive = match method {
+                AliveTestMethods::ConsiderAlive => true,
+                AliveTestMethods::Icmp => addrs.iter().any(|x| {
+                    icmp(*x, self.timeout).unwrap_or_else(|error| {
+                        tracing::debug!(%error, addr=%x, "unable to send ICMP echo request");
+                        false
+                    })
+                }),
+                AliveTestMethods::TcpAck | AliveTestMethods::TcpSyn if !tcp_tested => {
+                    tcp_tested = true;
+                    addrs
+                        .iter()
+                        .any(|x| self.ports.iter().any(|p| tcp(*x, *p, self.timeout)))
+                }
+                AliveTestMethods::TcpAck | AliveTestMethods::TcpSyn => false,
+                AliveTestMethods::Arp => {
+                    tracing::debug!(host, "ARP requires raw sockets, skipping");
+                    false
+                }
+            };
+            if alive {
+                return AliveStatus {
+                    host: host.to_owned(),
+                    alive,
+                    method: Some(method.clone()),
+                };
+            }
+        }
+        AliveStatus {
+            host: host.to_owned(),
+            alive: false,
+            method: None,
+        }
+    }
+
+    /// Tests the hosts in parallel and returns their status in the given order.
+    pub fn check_all(&self, hosts: &[Host]) -> Vec<AliveStatus> {
+        hosts
+            .chunks(PARALLEL_HOSTS)
+            .flat_map(|chunk| {
+                thread::scope(|s| {
+                    let handles: Vec<_> = chunk.iter().map(|x| s.spawn(|| self.check(x))).collect();
+                    handles
+                        .into_iter()
+                        .map(|x| x.join().expect("alive test thread panicked"))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect()
+    }
+
+    /// Tests all hosts of the target that are not excluded.
+    pub fn check_target(&self, target: &Target) -> Vec<AliveStatus> {
+        let hosts: Vec<Host> = target
+            .all_hosts()
+            .filter(|x| !target.excluded_hosts.contains(x))
+            .cloned()
+            .collect();
+        self.check_all(&hosts)
+    }
+}
+
+fn resolve(host: &str) -> Vec<IpAddr> {
+    if let Ok(addr) = host.parse() {
+        return vec![addr];
+    }
+    match (host, 0).to_socket_addrs() {
+        Ok(addrs) => addrs.map(|x| x.ip()).collect(),
+        Err(error) => {
+            tracing::debug!(%error, host, "unable to resolve");
+            vec![]
+        }
+    }
+}
+
+/// Returns true when a connection was established or refused.
+fn tcp(addr: IpAddr, port: u16, timeout: Duration) -> bool {
+    match TcpStream::connect_timeout(&SocketAddr::new(addr, port), timeout) {
+        Ok(_) => true,
+        Err(e) => e.kind() == io::ErrorKind::ConnectionRefused,
+    }
+}
+
+/// Calculates the internet checksum of RFC 1071.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], x.get(1).copied().unwrap_or_default()]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Sends an ICMP echo request and returns true when an echo reply is received.
+fn icmp(addr: IpAddr, timeout: Duration) -> io::Result<bool> {
+    let (domain, protocol, request, reply) = match addr {
+        IpAddr::V4(_) => (Domain::IPV4, SocketProtocol::ICMPV4, 8, 0),
+        IpAddr::V6(_) => (Domain::IPV6, SocketProtocol::ICMPV6, 128, 129),
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(protocol))?;
+    socket.connect(&SocketAddr::new(addr, 0).into())?;
+    // an unprivileged ICMP socket behaves like a connected UDP socket
+    let socket: UdpSocket = socket.into();
+    // type, code, checksum, identifier (set by the kernel), sequence number
+    let mut packet = [request, 0, 0, 0, 0, 0, 0, 1];
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    socket.send(&packet)?;
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv(&mut buffer) {
+            Ok(n) if n > 0 && buffer[0] == reply => return Ok(true),
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(false)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+
+    use crate::models::{Port, PortRange};
+
+    use super::*;
+
+    #[test]
+    fn internet_checksum() {
+        assert_eq!(checksum(&[8, 0, 0, 0, 0, 0, 0, 1]), 0xf7fe);
+        assert_eq!(checksum(&[0xff]), 0x00ff);
+    }
+
+    #[test]
+    fn tcp_methods() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let target = Target {
+            hosts: vec!["127.0.0.1".into(), "192.0.2.1".into()],
+            excluded_hosts: vec!["192.0.2.1".into()],
+            alive_test_methods: vec![AliveTestMethods::Arp, AliveTestMethods::TcpSyn],
+            alive_test_ports: vec![Port {
+                protocol: Some(Protocol::TCP),
+                range: vec![PortRange {
+                    start: port as usize,
+                    end: None,
+                }],
+            }],
+            ..Default::default()
+        };
+        let status = AliveTest::from(&target).check_target(&target);
+        assert_eq!(
+            status,
+            vec![AliveStatus {
+                host: "127.0.0.1".into(),
+                alive: true,
+                method: Some(AliveTestMethods::TcpSyn),
+            }]
+        );
+    }
+
+    #[test]
+    fn consider_alive() {
+        let test = AliveTest::default().with_methods(vec![AliveTestMethods::ConsiderAlive]);
+        let status = test.check_all(&["unresolvable.invalid".into()]);
+        assert!(status[0].alive);
+        let test = AliveTest::default()
+            .with_methods(vec![AliveTestMethods::TcpAck])
+            .with_timeout(Duration::from_millis(100));
+        assert!(!test.check("unresolvable.invalid").alive);
+    }
+}
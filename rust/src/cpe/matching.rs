@@ -0,0 +1,181 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::cmp::Ordering;
+
+use crate::notus::packages::PackageVersion;
+
+use super::{Attribute, Cpe};
+
+/// Describes the products affected by a vulnerability.
+///
+/// The attributes of the criteria are compared with the ones of a detected product: an attribute
+/// that matches any value matches all values, otherwise the values have to be equal. When a range
+/// of versions is given, the version of the criteria is ignored and the version of the detected
+/// product has to be within the range instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpeMatch {
+    /// The products described by the match
+    pub criteria: Cpe,
+    /// The first affected version
+    pub version_start_including: Option<String>,
+    /// The last version before the affected ones
+    pub version_start_excluding: Option<String>,
+    /// The last affected version
+    pub version_end_including: Option<String>,
+    /// The first fixed version
+    pub version_end_excluding: Option<String>,
+}
+
+fn matches_attribute(criteria: &Attribute, value: &Attribute) -> bool {
+    match criteria {
+        Attribute::Any => true,
+        x => x == value,
+    }
+}
+
+fn compare(version: &str, other: &str) -> Option<Ordering> {
+    PackageVersion(version.to_string()).partial_cmp(&PackageVersion(other.to_string()))
+}
+
+impl CpeMatch {
+    /// Creates a match of the products described by criteria
+    pub fn new(criteria: Cpe) -> Self {
+        Self {
+            criteria,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the first affected version
+    pub fn start_including(mut self, version: &str) -> Self {
+        self.version_start_including = Some(version.to_string());
+        self
+    }
+
+    /// Sets the last version before the affected ones
+    pub fn start_excluding(mut self, version: &str) -> Self {
+        self.version_start_excluding = Some(version.to_string());
+        self
+    }
+
+    /// Sets the last affected version
+    pub fn end_including(mut self, version: &str) -> Self {
+        self.version_end_including = Some(version.to_string());
+        self
+    }
+
+    /// Sets the first fixed version
+    pub fn end_excluding(mut self, version: &str) -> Self {
+        self.version_end_excluding = Some(version.to_string());
+        self
+    }
+
+    fn has_range(&self) -> bool {
+        self.version_start_including.is_some()
+            || self.version_start_excluding.is_some()
+            || self.version_end_including.is_some()
+            || self.version_end_excluding.is_some()
+    }
+
+    fn in_range(&self, version: &str) -> bool {
+        let check = |bound: &Option<String>, allowed: &[Ordering]| {
+            bound
+                .as_ref()
+                .is_none_or(|x| compare(version, x).is_some_and(|o| allowed.contains(&o)))
+        };
+        check(
+            &self.version_start_including,
+            &[Ordering::Greater, Ordering::Equal],
+        ) && check(&self.version_start_excluding, &[Ordering::Greater])
+            && check(
+                &self.version_end_including,
+                &[Ordering::Less, Ordering::Equal],
+            )
+            && check(&self.version_end_excluding, &[Ordering::Less])
+    }
+
+    /// Returns true when the detected product is affected
+    pub fn matches(&self, cpe: &Cpe) -> bool {
+        let c = &self.criteria;
+        let version = if self.has_range() {
+            cpe.version.value().is_some_and(|x| self.in_range(x))
+        } else {
+            matches_attribute(&c.version, &cpe.version)
+        };
+        c.part == cpe.part
+            && version
+            && matches_attribute(&c.vendor, &cpe.vendor)
+            && matches_attribute(&c.product, &cpe.product)
+            && matches_attribute(&c.update, &cpe.update)
+            && matches_attribute(&c.edition, &cpe.edition)
+            && matches_attribute(&c.language, &cpe.language)
+            && matches_attribute(&c.sw_edition, &cpe.sw_edition)
+            && matches_attribute(&c.target_sw, &cpe.target_sw)
+            && matches_attribute(&c.target_hw, &cpe.target_hw)
+            && matches_attribute(&c.other, &cpe.other)
+    }
+
+    /// Returns the detected products that are affected
+    pub fn affected<'a>(&self, detected: &'a [Cpe]) -> Vec<&'a Cpe> {
+        detected.iter().filter(|x| self.matches(x)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpe(name: &str) -> Cpe {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        let m = CpeMatch::new(cpe("cpe:2.3:a:apache:http_server:2.4.58:*:*:*:*:*:*:*"));
+        assert!(m.matches(&cpe("cpe:/a:apache:http_server:2.4.58")));
+        assert!(!m.matches(&cpe("cpe:/a:apache:http_server:2.4.59")));
+        assert!(!m.matches(&cpe("cpe:/o:apache:http_server:2.4.58")));
+        assert!(!m.matches(&cpe("cpe:/a:apache:tomcat:2.4.58")));
+    }
+
+    #[test]
+    fn ranges() {
+        let m = CpeMatch::new(cpe("cpe:/a:apache:http_server"))
+            .start_including("2.4.0")
+            .end_excluding("2.4.58");
+        assert!(m.matches(&cpe("cpe:/a:apache:http_server:2.4.0")));
+        assert!(m.matches(&cpe("cpe:/a:apache:http_server:2.4.9")));
+        assert!(m.matches(&cpe("cpe:/a:apache:http_server:2.4.57")));
+        assert!(!m.matches(&cpe("cpe:/a:apache:http_server:2.4.58")));
+        assert!(!m.matches(&cpe("cpe:/a:apache:http_server:2.2.34")));
+        // without a detected version it is unknown whether the product is affected
+        assert!(!m.matches(&cpe("cpe:/a:apache:http_server")));
+
+        let m = CpeMatch::new(cpe("cpe:/a:vendor:product"))
+            .start_excluding("1.0")
+            .end_including("1.10");
+        assert!(!m.matches(&cpe("cpe:/a:vendor:product:1.0")));
+        assert!(m.matches(&cpe("cpe:/a:vendor:product:1.9")));
+        assert!(m.matches(&cpe("cpe:/a:vendor:product:1.10")));
+        assert!(!m.matches(&cpe("cpe:/a:vendor:product:1.11")));
+    }
+
+    #[test]
+    fn update_and_edition() {
+        let m = CpeMatch::new(cpe("cpe:/o:microsoft:windows_7:-:sp1"));
+        assert!(m.matches(&cpe("cpe:/o:microsoft:windows_7:-:sp1")));
+        assert!(!m.matches(&cpe("cpe:/o:microsoft:windows_7:-:sp2")));
+        assert!(!m.matches(&cpe("cpe:/o:microsoft:windows_7:-")));
+
+        let m = CpeMatch::new(cpe("cpe:2.3:a:vendor:product:*:*:*:*:enterprise:*:*:*"))
+            .end_excluding("3.0");
+        let detected = [
+            cpe("cpe:/a:vendor:product:2.1::~~enterprise~~~"),
+            cpe("cpe:/a:vendor:product:2.1::~~community~~~"),
+            cpe("cpe:/a:vendor:product:3.1::~~enterprise~~~"),
+        ];
+        assert_eq!(m.affected(&detected), vec![&detected[0]]);
+    }
+}
@@ -0,0 +1,438 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Normalizes detected products into CPE names and matches them against applicability
+//! statements.
+//!
+//! Both the URI binding of CPE 2.2 (`cpe:/a:vendor:product:1.0`), which is used by the host
+//! details of the feed, and the formatted string binding of CPE 2.3
+//! (`cpe:2.3:a:vendor:product:1.0:*:*:*:*:*:*:*`) are parsed into a [Cpe]. Values are normalized
+//! on parsing, so that names of the same product compare equal regardless of their binding.
+//!
+//! Whether a detected product is affected is checked by a [CpeMatch].
+
+mod matching;
+
+use std::{fmt::Display, str::FromStr};
+
+pub use matching::CpeMatch;
+
+const URI_PREFIX: &str = "cpe:/";
+const FORMATTED_STRING_PREFIX: &str = "cpe:2.3:";
+
+/// Errors that occur when parsing a CPE
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CpeError {
+    /// The name is neither a CPE 2.2 URI nor a CPE 2.3 formatted string
+    #[error("{0} is not a CPE name")]
+    UnknownBinding(String),
+    /// The part is not one of `a`, `o` or `h`
+    #[error("invalid part {0}, expected a, o or h")]
+    InvalidPart(String),
+    /// The name contains more attributes than allowed by its binding
+    #[error("{0} contains too many attributes")]
+    TooManyAttributes(String),
+}
+
+/// The value of an attribute of a CPE
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    /// Any value, `*` or an empty attribute of an URI
+    #[default]
+    Any,
+    /// Not applicable, `-`
+    NotApplicable,
+    /// A normalized value
+    Value(String),
+}
+
+impl Attribute {
+    /// Normalizes a value: lower case with white space replaced by `_`
+    fn normalized(value: &str) -> Self {
+        let value = value.trim();
+        match value {
+            "" | "*" => Self::Any,
+            "-" => Self::NotApplicable,
+            _ => Self::Value(
+                value
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join("_")
+                    .to_lowercase(),
+            ),
+        }
+    }
+
+    /// Returns the value, if it is neither any nor not applicable
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Self::Value(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn formatted(&self) -> String {
+        match self {
+            Self::Any => "*".to_string(),
+            Self::NotApplicable => "-".to_string(),
+            Self::Value(x) => x
+                .chars()
+                .map(|c| match c {
+                    c if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') => c.to_string(),
+                    c => format!("\\{c}"),
+                })
+                .collect(),
+        }
+    }
+
+    fn uri(&self) -> String {
+        match self {
+            Self::Any => String::new(),
+            Self::NotApplicable => "-".to_string(),
+            Self::Value(x) => x
+                .bytes()
+                .map(|c| match c {
+                    c if c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'-') => {
+                        (c as char).to_string()
+                    }
+                    c => format!("%{c:02x}"),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for Attribute {
+    fn from(value: &str) -> Self {
+        Self::normalized(value)
+    }
+}
+
+/// The kind of a product
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Part {
+    /// Applications, `a`
+    #[default]
+    Application,
+    /// Operating systems, `o`
+    OperatingSystem,
+    /// Hardware, `h`
+    Hardware,
+}
+
+impl Part {
+    /// Returns the abbreviation used within a CPE name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Application => "a",
+            Self::OperatingSystem => "o",
+            Self::Hardware => "h",
+        }
+    }
+}
+
+impl FromStr for Part {
+    type Err = CpeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "a" => Ok(Self::Application),
+            "o" => Ok(Self::OperatingSystem),
+            "h" => Ok(Self::Hardware),
+            x => Err(CpeError::InvalidPart(x.to_string())),
+        }
+    }
+}
+
+/// A normalized CPE name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Cpe {
+    /// The kind of the product
+    pub part: Part,
+    /// The vendor, e.g. `apache`
+    pub vendor: Attribute,
+    /// The product, e.g. `http_server`
+    pub product: Attribute,
+    /// The version, e.g. `2.4.58`
+    pub version: Attribute,
+    /// The update or service pack, e.g. `sp1` or `rc2`
+    pub update: Attribute,
+    /// The edition, deprecated since CPE 2.3 in favour of the extended attributes
+    pub edition: Attribute,
+    /// The language, e.g. `en-us`
+    pub language: Attribute,
+    /// The software edition, e.g. `enterprise`
+    pub sw_edition: Attribute,
+    /// The software environment, e.g. `android`
+    pub target_sw: Attribute,
+    /// The hardware architecture, e.g. `x64`
+    pub target_hw: Attribute,
+    /// Any other information
+    pub other: Attribute,
+}
+
+/// Decodes the percent encoded characters of an URI attribute
+fn percent_decode(value: &str) -> String {
+    let mut result = Vec::with_capacity(value.len());
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match decoded {
+            Some(x) => {
+                result.push(x);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+/// Splits a formatted string at unescaped `:` and removes the escaping `\`
+fn split_formatted(value: &str) -> Vec<String> {
+    let mut result = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let (Some(x), Some(last)) = (chars.next(), result.last_mut()) {
+                    last.push(x);
+                }
+            }
+            ':' => result.push(String::new()),
+            c => {
+                if let Some(last) = result.last_mut() {
+                    last.push(c)
+                }
+            }
+        }
+    }
+    result
+}
+
+impl Cpe {
+    /// Creates a CPE of a product without version
+    pub fn new(part: Part, vendor: &str, product: &str) -> Self {
+        Self {
+            part,
+            vendor: vendor.into(),
+            product: product.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the version
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the update
+    pub fn with_update(mut self, update: &str) -> Self {
+        self.update = update.into();
+        self
+    }
+
+    /// Returns the CPE identifying the product regardless of its version
+    pub fn base(&self) -> Self {
+        Self {
+            part: self.part,
+            vendor: self.vendor.clone(),
+            product: self.product.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn from_uri(name: &str, value: &str) -> Result<Self, CpeError> {
+        let attributes: Vec<String> = value.split(':').map(percent_decode).collect();
+        if attributes.len() > 7 {
+            return Err(CpeError::TooManyAttributes(name.to_string()));
+        }
+        let get = |i: usize| attributes.get(i).map(|x| x.as_str()).unwrap_or_default();
+        let mut result = Self {
+            part: get(0).parse()?,
+            vendor: get(1).into(),
+            product: get(2).into(),
+            version: get(3).into(),
+            update: get(4).into(),
+            edition: get(5).into(),
+            language: get(6).into(),
+            ..Default::default()
+        };
+        // the extended attributes of CPE 2.3 are packed into the edition,
+        // e.g. `~~enterprise~android~x64~`
+        if let Some(packed) = get(5).strip_prefix('~') {
+            let packed: Vec<&str> = packed.split('~').collect();
+            let get = |i: usize| packed.get(i).copied().unwrap_or_default();
+            result.edition = get(0).into();
+            result.sw_edition = get(1).into();
+            result.target_sw = get(2).into();
+            result.target_hw = get(3).into();
+            result.other = get(4).into();
+        }
+        Ok(result)
+    }
+
+    fn from_formatted(name: &str, value: &str) -> Result<Self, CpeError> {
+        let attributes = split_formatted(value);
+        if attributes.len() > 11 {
+            return Err(CpeError::TooManyAttributes(name.to_string()));
+        }
+        let get = |i: usize| attributes.get(i).map(|x| x.as_str()).unwrap_or_default();
+        Ok(Self {
+            part: get(0).parse()?,
+            vendor: get(1).into(),
+            product: get(2).into(),
+            version: get(3).into(),
+            update: get(4).into(),
+            edition: get(5).into(),
+            language: get(6).into(),
+            sw_edition: get(7).into(),
+            target_sw: get(8).into(),
+            target_hw: get(9).into(),
+            other: get(10).into(),
+        })
+    }
+
+    /// Returns the CPE 2.2 URI binding, e.g. `cpe:/a:apache:http_server:2.4.58`
+    ///
+    /// Trailing attributes matching any value are omitted.
+    pub fn to_uri(&self) -> String {
+        let extended = [
+            &self.sw_edition,
+            &self.target_sw,
+            &self.target_hw,
+            &self.other,
+        ];
+        let edition = if extended.iter().all(|x| **x == Attribute::Any) {
+            self.edition.uri()
+        } else {
+            let mut packed = vec![self.edition.uri()];
+            packed.extend(extended.iter().map(|x| x.uri()));
+            format!("~{}", packed.join("~"))
+        };
+        let mut attributes = vec![
+            self.part.as_str().to_string(),
+            self.vendor.uri(),
+            self.product.uri(),
+            self.version.uri(),
+            self.update.uri(),
+            edition,
+            self.language.uri(),
+        ];
+        while attributes.last().is_some_and(|x| x.is_empty()) {
+            attributes.pop();
+        }
+        format!("{URI_PREFIX}{}", attributes.join(":"))
+    }
+}
+
+impl FromStr for Cpe {
+    type Err = CpeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        let lower = name.to_lowercase();
+        if lower.starts_with(FORMATTED_STRING_PREFIX) {
+            Self::from_formatted(s, &name[FORMATTED_STRING_PREFIX.len()..])
+        } else if lower.starts_with(URI_PREFIX) {
+            Self::from_uri(s, &name[URI_PREFIX.len()..])
+        } else {
+            Err(CpeError::UnknownBinding(s.to_string()))
+        }
+    }
+}
+
+impl Display for Cpe {
+    /// Formats the CPE as CPE 2.3 formatted string
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let attributes = [
+            &self.vendor,
+            &self.product,
+            &self.version,
+            &self.update,
+            &self.edition,
+            &self.language,
+            &self.sw_edition,
+            &self.target_sw,
+            &self.target_hw,
+            &self.other,
+        ];
+        write!(f, "{FORMATTED_STRING_PREFIX}{}", self.part.as_str())?;
+        for attribute in attributes {
+            write!(f, ":{}", attribute.formatted())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri() {
+        let cpe: Cpe = "cpe:/a:Apache:HTTP%20Server:2.4.58".parse().unwrap();
+        assert_eq!(
+            cpe,
+            Cpe::new(Part::Application, "apache", "http server").with_version("2.4.58")
+        );
+        assert_eq!(cpe.to_uri(), "cpe:/a:apache:http_server:2.4.58");
+        assert_eq!(
+            cpe.to_string(),
+            "cpe:2.3:a:apache:http_server:2.4.58:*:*:*:*:*:*:*"
+        );
+        assert_eq!(cpe.base().to_uri(), "cpe:/a:apache:http_server");
+
+        let packed: Cpe = "cpe:/a:vendor:product:1.0::~~enterprise~android~x64~"
+            .parse()
+            .unwrap();
+        assert_eq!(packed.sw_edition, Attribute::from("enterprise"));
+        assert_eq!(packed.target_hw, Attribute::from("x64"));
+        assert_eq!(packed.edition, Attribute::Any);
+        assert_eq!(
+            packed.to_uri(),
+            "cpe:/a:vendor:product:1.0::~~enterprise~android~x64~"
+        );
+    }
+
+    #[test]
+    fn formatted_string() {
+        let cpe: Cpe = r"cpe:2.3:o:microsoft:windows_10:1607:-:*:*:*:*:x64:*"
+            .parse()
+            .unwrap();
+        assert_eq!(cpe.part, Part::OperatingSystem);
+        assert_eq!(cpe.update, Attribute::NotApplicable);
+        assert_eq!(cpe.to_uri(), "cpe:/o:microsoft:windows_10:1607:-:~~~~x64~");
+
+        let escaped: Cpe = r"cpe:2.3:a:vendor:c\+\+_lib:1.0\:beta".parse().unwrap();
+        assert_eq!(escaped.product.value(), Some("c++_lib"));
+        assert_eq!(escaped.version.value(), Some("1.0:beta"));
+        assert_eq!(
+            escaped.to_string(),
+            r"cpe:2.3:a:vendor:c\+\+_lib:1.0\:beta:*:*:*:*:*:*:*"
+        );
+        assert_eq!(escaped.to_uri(), "cpe:/a:vendor:c%2b%2b_lib:1.0%3abeta");
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            "apache:http_server".parse::<Cpe>(),
+            Err(CpeError::UnknownBinding("apache:http_server".to_string()))
+        );
+        assert_eq!(
+            "cpe:/x:vendor:product".parse::<Cpe>(),
+            Err(CpeError::InvalidPart("x".to_string()))
+        );
+        assert!("cpe:/a:1:2:3:4:5:6:7".parse::<Cpe>().is_err());
+    }
+}
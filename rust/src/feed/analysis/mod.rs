@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Helpers shared by the static analyses of a feed, e.g. the linters and the KB graph.
+
+use std::collections::BTreeMap;
+
+use crate::nasl::syntax::{IdentifierType, Statement, StatementKind, TokenCategory};
+
+/// Files of a feed that cannot be loaded or parsed and are therefore skipped by an analysis,
+/// mapped to the reason.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Unparsable(BTreeMap<String, String>);
+
+impl Unparsable {
+    /// Adds a file with the reason it is skipped.
+    pub fn insert(&mut self, file: String, reason: String) {
+        self.0.insert(file, reason);
+    }
+
+    /// Returns true when the file is skipped.
+    pub fn contains_key(&self, file: &str) -> bool {
+        self.0.contains_key(file)
+    }
+
+    /// Returns the skipped files ordered by name.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+
+    /// Returns the skipped files together with the reason.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    /// Returns the number of skipped files.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true when no file is skipped.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Unparsable {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::collections::btree_map::Iter<'a, String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Returns the name of an identifier, e.g. of a called function or a named parameter.
+pub(crate) fn identifier(stmt: &Statement) -> Option<&str> {
+    match stmt.as_token().category() {
+        TokenCategory::Identifier(IdentifierType::Undefined(x)) => Some(x),
+        _ => None,
+    }
+}
+
+/// Returns the literal of a string or data primitive.
+pub(crate) fn string(stmt: &Statement) -> Option<String> {
+    match (stmt.kind(), stmt.as_token().category()) {
+        (StatementKind::Primitive, TokenCategory::String(x)) => Some(x.clone()),
+        (StatementKind::Primitive, TokenCategory::Data(x)) => {
+            Some(String::from_utf8_lossy(x).into())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the literal of a primitive or of `TRUE` and `FALSE` as it is written within a script.
+pub(crate) fn literal(stmt: &Statement) -> Option<String> {
+    match stmt.as_token().category() {
+        _ if !stmt.children().is_empty() => None,
+        TokenCategory::String(x) => Some(x.clone()),
+        TokenCategory::Data(x) => Some(String::from_utf8_lossy(x).into_owned()),
+        TokenCategory::Number(x) => Some(x.to_string()),
+        TokenCategory::Identifier(IdentifierType::True) => Some("TRUE".to_string()),
+        TokenCategory::Identifier(IdentifierType::False) => Some("FALSE".to_string()),
+        _ => None,
+    }
+}
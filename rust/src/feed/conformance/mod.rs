@@ -0,0 +1,618 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Compares the behavior of the rust interpreter with the C implementation based on a curated set
+//! of NASL scripts.
+//!
+//! Each `.nasl` file of a suite is a case. The case is executed by the rust interpreter while the
+//! KB items and the results it dispatches are recorded. The observation is compared with a
+//! reference, which is either produced by running the case with `openvas-nasl` or, when the
+//! binary is not available, read from the golden file `<case>.golden.json` next to it.
+//!
+//! To get the KB of `openvas-nasl` the case is executed with an epilogue that displays each KB
+//! item, the results are parsed from the `internal/results` item. A case must therefore not call
+//! `exit`, as the epilogue would not be executed.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use futures::StreamExt;
+
+use crate::models::{self, ResultType};
+use crate::nasl::interpreter::CodeInterpreter;
+use crate::nasl::utils::Context;
+use crate::nasl::{nasl_std_functions, Loader, RegisterBuilder};
+use crate::storage::{ContextKey, DefaultDispatcher, Dispatcher, Field, StorageError};
+
+use super::NaslFileFinder;
+
+/// Name of the C interpreter binary that is searched within PATH
+pub const OPENVAS_NASL: &str = "openvas-nasl";
+
+/// Prefix of the lines the epilogue displays for each KB item
+const KB_LINE: &str = "CONFORMANCE-KB";
+
+/// KB item the C implementation stores results in
+const RESULTS_KEY: &str = "internal/results";
+
+const EPILOGUE: &str = r#"
+foreach conformance_key (keys(get_kb_list("*"))) {
+  foreach conformance_value (get_kb_list(conformance_key)) {
+    display("CONFORMANCE-KB", '\t', conformance_key, '\t', conformance_value);
+  }
+}
+"#;
+
+/// Errors while running a conformance case
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The case or golden file cannot be read or written
+    #[error("{0}: {1}")]
+    IO(PathBuf, String),
+    /// The case cannot be loaded or interpreted
+    #[error("{0}: {1}")]
+    Interpret(String, String),
+    /// openvas-nasl cannot be executed or fails
+    #[error("openvas-nasl: {0}")]
+    Openvas(String),
+    /// The golden file is not valid
+    #[error("{0}: invalid golden file: {1}")]
+    Golden(PathBuf, String),
+}
+
+/// A result as reported by both implementations
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub struct ObservedResult {
+    /// Type of the result in the notation of the C implementation, e.g. `LOG` or `ALARM`
+    pub kind: String,
+    /// Port and protocol, e.g. `80/tcp` or `general/tcp`
+    pub port: String,
+    /// The message of the result
+    pub message: String,
+}
+
+impl From<&models::Result> for ObservedResult {
+    fn from(result: &models::Result) -> Self {
+        let kind = match &result.r_type {
+            ResultType::Alarm => "ALARM".to_owned(),
+            ResultType::Log => "LOG".to_owned(),
+            ResultType::Error => "ERRMSG".to_owned(),
+            x => format!("{x:?}").to_uppercase(),
+        };
+        let protocol = result
+            .protocol
+            .as_ref()
+            .map(|x| x.to_string())
+            .unwrap_or_else(|| "tcp".to_owned());
+        let port = match result.port {
+            Some(port) => format!("{port}/{protocol}"),
+            None => format!("general/{protocol}"),
+        };
+        Self {
+            kind,
+            port,
+            message: result.message.clone().unwrap_or_default().trim().to_owned(),
+        }
+    }
+}
+
+impl Display for ObservedResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", self.kind, self.port, self.message)
+    }
+}
+
+/// The KB items and results of a single run of a case
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Observation {
+    /// The values of each KB item
+    pub kb: BTreeMap<String, BTreeSet<String>>,
+    /// The dispatched results ordered by type, port and message
+    pub results: Vec<ObservedResult>,
+}
+
+impl Observation {
+    fn insert_kb(&mut self, key: &str, value: String) {
+        self.kb.entry(key.to_owned()).or_default().insert(value);
+    }
+
+    /// Parses the output of openvas-nasl running a case with the epilogue.
+    fn from_openvas_output(output: &str) -> Self {
+        let mut result = Self::default();
+        for line in output.lines() {
+            let mut parts = line.splitn(3, '\t');
+            if parts.next() != Some(KB_LINE) {
+                continue;
+            }
+            let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            if key == RESULTS_KEY {
+                // TYPE|||ip|||hostname|||port|||oid|||message|||uri
+                let fields: Vec<&str> = value.split("|||").collect();
+                if fields.len() >= 6 {
+                    result.results.push(ObservedResult {
+                        kind: fields[0].to_owned(),
+                        port: fields[3].to_owned(),
+                        message: fields[5].trim().to_owned(),
+                    });
+                }
+            }
+            result.insert_kb(key, value.to_owned());
+        }
+        result.results.sort();
+        result
+    }
+
+    /// Removes all KB items starting with one of the prefixes.
+    fn without(mut self, ignored: &[String]) -> Self {
+        self.kb
+            .retain(|key, _| !ignored.iter().any(|x| key.starts_with(x)));
+        self
+    }
+}
+
+/// Records the items a case dispatches while storing them in the shared storage
+struct Recorder<'a> {
+    storage: &'a DefaultDispatcher,
+    observation: Mutex<Observation>,
+}
+
+impl Dispatcher for Recorder<'_> {
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        {
+            let mut observation = self.observation.lock()?;
+            match &scope {
+                Field::KB(kb) => observation.insert_kb(&kb.key, kb.value.to_string()),
+                Field::Result(result) => observation.results.push(result.as_ref().into()),
+                _ => {}
+            }
+        }
+        self.storage.dispatch(key, scope)
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        if let Field::KB(kb) = &scope {
+            let mut observation = self.observation.lock()?;
+            observation.kb.remove(&kb.key);
+            observation.insert_kb(&kb.key, kb.value.to_string());
+        }
+        self.storage.dispatch_replace(key, scope)
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        self.storage.on_exit(key)
+    }
+}
+
+/// The source of the reference observation of a case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceSource {
+    /// The case was executed by openvas-nasl
+    Openvas,
+    /// The observation was read from the golden file
+    Golden,
+}
+
+/// A deviation of the rust interpreter from the reference
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difference {
+    /// The KB item is only set by the reference
+    MissingKb {
+        /// Key of the KB item
+        key: String,
+        /// Values of the reference
+        expected: BTreeSet<String>,
+    },
+    /// The KB item is only set by the rust interpreter
+    UnexpectedKb {
+        /// Key of the KB item
+        key: String,
+        /// Values of the rust interpreter
+        found: BTreeSet<String>,
+    },
+    /// The KB item has different values
+    KbValues {
+        /// Key of the KB item
+        key: String,
+        /// Values of the reference
+        expected: BTreeSet<String>,
+        /// Values of the rust interpreter
+        found: BTreeSet<String>,
+    },
+    /// The result is only reported by the reference
+    MissingResult(ObservedResult),
+    /// The result is only reported by the rust interpreter
+    UnexpectedResult(ObservedResult),
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::MissingKb { key, expected } => {
+                write!(f, "missing KB item {key}: {expected:?}")
+            }
+            Difference::UnexpectedKb { key, found } => {
+                write!(f, "unexpected KB item {key}: {found:?}")
+            }
+            Difference::KbValues {
+                key,
+                expected,
+                found,
+            } => write!(f, "KB item {key}: expected {expected:?}, found {found:?}"),
+            Difference::MissingResult(x) => write!(f, "missing result {x}"),
+            Difference::UnexpectedResult(x) => write!(f, "unexpected result {x}"),
+        }
+    }
+}
+
+/// Returns the deviations of the observation from the reference.
+pub fn compare(reference: &Observation, observation: &Observation) -> Vec<Difference> {
+    let mut result = vec![];
+    for (key, expected) in &reference.kb {
+        match observation.kb.get(key) {
+            None => result.push(Difference::MissingKb {
+                key: key.clone(),
+                expected: expected.clone(),
+            }),
+            Some(found) if found != expected => result.push(Difference::KbValues {
+                key: key.clone(),
+                expected: expected.clone(),
+                found: found.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, found) in &observation.kb {
+        if !reference.kb.contains_key(key) {
+            result.push(Difference::UnexpectedKb {
+                key: key.clone(),
+                found: found.clone(),
+            });
+        }
+    }
+    let mut unexpected = observation.results.clone();
+    for expected in &reference.results {
+        match unexpected.iter().position(|x| x == expected) {
+            Some(i) => {
+                unexpected.remove(i);
+            }
+            None => result.push(Difference::MissingResult(expected.clone())),
+        }
+    }
+    result.extend(unexpected.into_iter().map(Difference::UnexpectedResult));
+    result
+}
+
+/// The comparison of a single case
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CaseReport {
+    /// Name of the case relative to the suite
+    pub name: String,
+    /// Source of the reference, None when there is no reference for the case
+    pub reference: Option<ReferenceSource>,
+    /// The deviations from the reference
+    pub differences: Vec<Difference>,
+    /// The error that prevented the comparison
+    pub error: Option<String>,
+}
+
+impl CaseReport {
+    /// Returns true when the case was compared without any deviation.
+    pub fn matches(&self) -> bool {
+        self.reference.is_some() && self.error.is_none() && self.differences.is_empty()
+    }
+}
+
+/// The comparison of all cases of a suite
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConformanceReport {
+    /// The report of each case
+    pub cases: Vec<CaseReport>,
+}
+
+impl ConformanceReport {
+    /// Returns the amount of cases that were compared to a reference.
+    pub fn compared(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|x| x.reference.is_some() && x.error.is_none())
+            .count()
+    }
+
+    /// Returns the amount of cases without deviation.
+    pub fn matching(&self) -> usize {
+        self.cases.iter().filter(|x| x.matches()).count()
+    }
+
+    /// Returns the ratio of matching to compared cases.
+    pub fn parity(&self) -> f64 {
+        match self.compared() {
+            0 => 0.0,
+            compared => self.matching() as f64 / compared as f64,
+        }
+    }
+}
+
+/// Runs the cases of a suite on the rust interpreter and compares them with the reference
+pub struct Conformance {
+    root: PathBuf,
+    target: String,
+    openvas_nasl: Option<PathBuf>,
+    ignored: Vec<String>,
+}
+
+impl Conformance {
+    /// Creates a runner for the cases within root.
+    ///
+    /// KB items starting with `internal/` are ignored as they differ between the implementations
+    /// by design.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            target: "127.0.0.1".to_owned(),
+            openvas_nasl: None,
+            ignored: vec!["internal/".to_owned()],
+        }
+    }
+
+    /// Sets the target the cases run against.
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.target = target.to_owned();
+        self
+    }
+
+    /// Uses the given openvas-nasl binary as reference instead of the golden files.
+    pub fn with_openvas_nasl(mut self, binary: Option<PathBuf>) -> Self {
+        self.openvas_nasl = binary;
+        self
+    }
+
+    /// Ignores KB items starting with one of the given prefixes in addition to `internal/`.
+    pub fn with_ignored(mut self, prefixes: Vec<String>) -> Self {
+        self.ignored.extend(prefixes);
+        self
+    }
+
+    /// Searches openvas-nasl within PATH.
+    pub fn find_openvas_nasl() -> Option<PathBuf> {
+        std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|x| x.join(OPENVAS_NASL))
+                .find(|x| x.is_file())
+        })
+    }
+
+    fn root(&self) -> String {
+        self.root.to_string_lossy().to_string()
+    }
+
+    /// Returns the names of all cases of the suite.
+    pub fn cases(&self) -> Result<Vec<String>, Error> {
+        let mut cases = NaslFileFinder::new(self.root(), true)
+            .filter_map(|x| match x {
+                Ok(x) if x.ends_with(".nasl") => Some(Ok(x)),
+                Ok(_) => None,
+                Err(e) => Some(Err(Error::IO(self.root.clone(), e.to_string()))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        cases.sort();
+        Ok(cases)
+    }
+
+    /// Returns the path of the golden file of a case.
+    pub fn golden_path(&self, case: &str) -> PathBuf {
+        let case = case.strip_suffix(".nasl").unwrap_or(case);
+        self.root.join(format!("{case}.golden.json"))
+    }
+
+    /// Runs the case on the rust interpreter.
+    pub async fn observe(&self, case: &str) -> Result<Observation, Error> {
+        let loader = NaslFileFinder::new(self.root(), true);
+        let code = loader
+            .load(case)
+            .map_err(|e| Error::Interpret(case.to_owned(), e.to_string()))?;
+        let storage = DefaultDispatcher::new();
+        let recorder = Recorder {
+            storage: &storage,
+            observation: Mutex::new(Observation::default()),
+        };
+        let executor = nasl_std_functions();
+        let context = Context::new(
+            ContextKey::Scan("conformance".to_owned(), Some(self.target.clone())),
+            self.target.clone(),
+            &recorder,
+            &storage,
+            &loader,
+            &executor,
+        );
+        let results: Vec<_> = CodeInterpreter::new(&code, RegisterBuilder::build(), &context)
+            .stream()
+            .collect()
+            .await;
+        if let Some(Err(e)) = results.into_iter().find(|x| x.is_err()) {
+            return Err(Error::Interpret(case.to_owned(), e.to_string()));
+        }
+        let mut observation = recorder
+            .observation
+            .into_inner()
+            .map_err(|e| Error::Interpret(case.to_owned(), e.to_string()))?;
+        observation.results.sort();
+        Ok(observation.without(&self.ignored))
+    }
+
+    /// Runs the case with openvas-nasl.
+    pub fn observe_openvas(&self, binary: &Path, case: &str) -> Result<Observation, Error> {
+        let source = self.root.join(case);
+        let code = std::fs::read_to_string(&source)
+            .map_err(|e| Error::IO(source.clone(), e.to_string()))?;
+        let file = std::env::temp_dir().join(format!(
+            "conformance-{}-{}",
+            std::process::id(),
+            case.replace('/', "_")
+        ));
+        std::fs::write(&file, format!("{code}\n{EPILOGUE}"))
+            .map_err(|e| Error::IO(file.clone(), e.to_string()))?;
+        let output = Command::new(binary)
+            .arg("-X")
+            .arg("-i")
+            .arg(&self.root)
+            .arg("-t")
+            .arg(&self.target)
+            .arg(&file)
+            .output();
+        let _ = std::fs::remove_file(&file);
+        let output = output.map_err(|e| Error::Openvas(e.to_string()))?;
+        if !output.status.success() {
+            return Err(Error::Openvas(format!(
+                "{case} failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        Ok(Observation::from_openvas_output(&output).without(&self.ignored))
+    }
+
+    /// Returns the reference of a case, None when there is neither openvas-nasl nor a golden
+    /// file.
+    pub fn reference(&self, case: &str) -> Result<Option<(ReferenceSource, Observation)>, Error> {
+        if let Some(binary) = &self.openvas_nasl {
+            return self
+                .observe_openvas(binary, case)
+                .map(|x| Some((ReferenceSource::Openvas, x)));
+        }
+        let path = self.golden_path(case);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| Error::IO(path.clone(), e.to_string()))?;
+        let golden: Observation =
+            serde_json::from_str(&content).map_err(|e| Error::Golden(path, e.to_string()))?;
+        Ok(Some((
+            ReferenceSource::Golden,
+            golden.without(&self.ignored),
+        )))
+    }
+
+    /// Runs the case with openvas-nasl and stores the observation as golden file.
+    pub fn record(&self, case: &str) -> Result<Observation, Error> {
+        let Some(binary) = &self.openvas_nasl else {
+            return Err(Error::Openvas(format!("{OPENVAS_NASL} is not available")));
+        };
+        let observation = self.observe_openvas(binary, case)?;
+        let path = self.golden_path(case);
+        let content = serde_json::to_string_pretty(&observation)
+            .map_err(|e| Error::Golden(path.clone(), e.to_string()))?;
+        std::fs::write(&path, format!("{content}\n"))
+            .map_err(|e| Error::IO(path, e.to_string()))?;
+        Ok(observation)
+    }
+
+    /// Compares a single case with its reference.
+    pub async fn compare(&self, case: &str) -> CaseReport {
+        let mut report = CaseReport {
+            name: case.to_owned(),
+            reference: None,
+            differences: vec![],
+            error: None,
+        };
+        match self.reference(case) {
+            Ok(Some((source, reference))) => {
+                report.reference = Some(source);
+                match self.observe(case).await {
+                    Ok(observation) => report.differences = compare(&reference, &observation),
+                    Err(e) => report.error = Some(e.to_string()),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => report.error = Some(e.to_string()),
+        }
+        report
+    }
+
+    /// Compares all cases of the suite.
+    pub async fn run(&self) -> Result<ConformanceReport, Error> {
+        let mut cases = vec![];
+        for case in self.cases()? {
+            cases.push(self.compare(&case).await);
+        }
+        Ok(ConformanceReport { cases })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suite() -> Conformance {
+        Conformance::new(concat!(env!("CARGO_MANIFEST_DIR"), "/data/conformance"))
+    }
+
+    #[tokio::test]
+    async fn golden_suite() {
+        let report = suite().run().await.unwrap();
+        assert_eq!(report.compared(), 3);
+        assert_eq!(report.matching(), 2);
+        // the end index of substr is inclusive in the C implementation
+        let strings = report
+            .cases
+            .iter()
+            .find(|x| x.name == "strings.nasl")
+            .unwrap();
+        assert_eq!(
+            strings.differences,
+            vec![Difference::KbValues {
+                key: "Conformance/strings/substr".into(),
+                expected: ["conf".to_string()].into(),
+                found: ["con".to_string()].into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_openvas_output_and_compares() {
+        let output = [
+            "some display output",
+            "CONFORMANCE-KB\tinternal/results\tLOG|||127.0.0.1|||localhost|||general/tcp||||||found it|||",
+            "CONFORMANCE-KB\tinternal/ip\t127.0.0.1",
+            "CONFORMANCE-KB\tPorts/tcp/22\t1",
+            "CONFORMANCE-KB\tkey\ta",
+            "CONFORMANCE-KB\tkey\tb",
+        ]
+        .join("\n");
+        let reference = Observation::from_openvas_output(&output).without(&suite().ignored);
+        assert_eq!(
+            reference.results,
+            vec![ObservedResult {
+                kind: "LOG".into(),
+                port: "general/tcp".into(),
+                message: "found it".into(),
+            }]
+        );
+        assert_eq!(
+            reference.kb.keys().collect::<Vec<_>>(),
+            vec!["Ports/tcp/22", "key"]
+        );
+        let mut observation = reference.clone();
+        observation.results.clear();
+        observation.kb.remove("Ports/tcp/22");
+        observation.insert_kb("key", "c".into());
+        observation.insert_kb("other", "1".into());
+        let differences = compare(&reference, &observation);
+        assert_eq!(differences.len(), 4);
+        assert!(matches!(differences[0], Difference::MissingKb { .. }));
+        assert!(matches!(differences[1], Difference::KbValues { .. }));
+        assert!(matches!(differences[2], Difference::UnexpectedKb { .. }));
+        assert!(matches!(differences[3], Difference::MissingResult(_)));
+    }
+}
@@ -0,0 +1,243 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Is a module to build a graph of the includes within a feed.
+//!
+//! The graph is derived statically by parsing each script and collecting the `include` calls with
+//! a constant name. It is persisted after a feed update so that the scanner loads and parses the
+//! includes of the scripts of a scan once when it starts instead of resolving them lazily while
+//! interpreting each script.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+};
+
+use crate::nasl::syntax::{Loader, Statement, StatementKind, SyntaxError, TokenCategory};
+
+use crate::feed::{
+    update,
+    verify::{self, HashSumFileItem},
+};
+
+/// Contains the direct includes of each script and inc file within a feed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct IncludeGraph {
+    includes: BTreeMap<String, Vec<String>>,
+}
+
+fn include_name(stmt: &Statement) -> Option<String> {
+    match stmt.kind() {
+        StatementKind::Include(name) => match name.as_token().category() {
+            TokenCategory::String(x) => Some(x.clone()),
+            TokenCategory::Data(x) => Some(x.iter().map(|&b| b as char).collect()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl IncludeGraph {
+    /// Creates a new empty IncludeGraph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the graph of all `.nasl` files found by the verifier.
+    pub fn from_feed<'a, L, V>(loader: &L, verifier: V) -> Result<Self, update::Error>
+    where
+        L: Sync + Send + Loader,
+        V: Iterator<Item = Result<HashSumFileItem<'a>, verify::Error>>,
+    {
+        let mut graph = Self::new();
+        for item in verifier {
+            let item = item?;
+            let filename = item.get_filename();
+            if !filename.ends_with(".nasl") {
+                continue;
+            }
+            item.verify()?;
+            let filename = filename.trim_start_matches("./").to_string();
+            graph
+                .insert(loader, &filename)
+                .map_err(|kind| update::Error {
+                    kind,
+                    key: filename.clone(),
+                })?;
+        }
+        Ok(graph)
+    }
+
+    /// Parses the given code and returns the constant names of each include call.
+    ///
+    /// Includes with a non constant parameter are ignored as they cannot be resolved statically.
+    pub fn direct_includes(code: &str) -> Result<Vec<String>, SyntaxError> {
+        let mut results = vec![];
+        for stmt in crate::nasl::syntax::parse(code) {
+            let stmt = stmt?;
+            for inc in stmt.find(&|s| matches!(s.kind(), StatementKind::Include(_))) {
+                if let Some(name) = include_name(inc) {
+                    if !results.contains(&name) {
+                        results.push(name);
+                    }
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Loads the given key and adds it as well as all transitively included files to the graph.
+    ///
+    /// Keys that are already part of the graph are not loaded again.
+    pub fn insert<L>(&mut self, loader: &L, key: &str) -> Result<(), update::ErrorKind>
+    where
+        L: Loader + ?Sized,
+    {
+        let mut todo = vec![key.to_string()];
+        while let Some(key) = todo.pop() {
+            if self.includes.contains_key(&key) {
+                continue;
+            }
+            let code = loader.load(&key)?;
+            let includes = Self::direct_includes(&code)?;
+            todo.extend(
+                includes
+                    .iter()
+                    .filter(|x| !self.includes.contains_key(*x))
+                    .cloned(),
+            );
+            self.includes.insert(key, includes);
+        }
+        Ok(())
+    }
+
+    /// Returns the direct includes of a key.
+    pub fn includes(&self, key: &str) -> Option<&[String]> {
+        self.includes.get(key).map(|x| x.as_slice())
+    }
+
+    /// Returns all transitive includes of a key.
+    ///
+    /// The includes of an inc file are returned before the inc file itself so that the result can
+    /// be used as a loading order. Each file is returned once even on cyclic includes.
+    pub fn resolve(&self, key: &str) -> Vec<String> {
+        fn visit(
+            graph: &IncludeGraph,
+            key: &str,
+            seen: &mut HashSet<String>,
+            results: &mut Vec<String>,
+        ) {
+            for inc in graph.includes(key).unwrap_or_default() {
+                if seen.insert(inc.clone()) {
+                    visit(graph, inc, seen, results);
+                    results.push(inc.clone());
+                }
+            }
+        }
+        let mut seen = HashSet::from([key.to_string()]);
+        let mut results = vec![];
+        visit(self, key, &mut seen, &mut results);
+        results
+    }
+
+    /// Returns the transitive includes of all given keys, each file once.
+    pub fn resolve_all<'a, I>(&self, keys: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut seen = HashSet::new();
+        let mut results = vec![];
+        for key in keys {
+            for inc in self.resolve(key) {
+                if seen.insert(inc.clone()) {
+                    results.push(inc);
+                }
+            }
+        }
+        results
+    }
+
+    /// Returns the amount of known files.
+    pub fn len(&self) -> usize {
+        self.includes.len()
+    }
+
+    /// Returns true when the graph does not contain any file.
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty()
+    }
+
+    /// Writes the graph as json.
+    pub fn write<W>(&self, w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        serde_json::to_writer(w, self).map_err(io::Error::from)
+    }
+
+    /// Reads a graph previously written via `write`.
+    pub fn read<R>(r: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        serde_json::from_reader(r).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(key: &str) -> String {
+        match key {
+            "test.nasl" => r#"
+            if (description) { exit(0); }
+            include("a.inc");
+            if (1) include("b.inc");
+            include(variable);
+            "#
+            .to_string(),
+            "a.inc" => "include('c.inc'); include('b.inc');".to_string(),
+            "b.inc" => "include('a.inc');".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn direct_includes() {
+        assert_eq!(
+            IncludeGraph::direct_includes(&example("test.nasl")).unwrap(),
+            vec!["a.inc".to_string(), "b.inc".to_string()]
+        );
+    }
+
+    #[test]
+    fn resolve_transitive() {
+        let mut graph = IncludeGraph::new();
+        graph.insert(&example, "test.nasl").unwrap();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.includes("c.inc"), Some(&[][..]));
+        assert_eq!(
+            graph.resolve("test.nasl"),
+            vec![
+                "c.inc".to_string(),
+                "b.inc".to_string(),
+                "a.inc".to_string()
+            ]
+        );
+        assert_eq!(
+            graph.resolve_all(["test.nasl", "b.inc"]),
+            graph.resolve("test.nasl")
+        );
+    }
+
+    #[test]
+    fn persist() {
+        let mut graph = IncludeGraph::new();
+        graph.insert(&example, "test.nasl").unwrap();
+        let mut buf = vec![];
+        graph.write(&mut buf).unwrap();
+        assert_eq!(IncludeGraph::read(buf.as_slice()).unwrap(), graph);
+    }
+}
@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Checks that the KB items scripts share with each other are written and read.
+//!
+//! Each script and inc file is parsed to collect the KB keys it writes via `set_kb_item` and
+//! `replace_kb_item` and reads via `get_kb_item`, `get_kb_list` or requires via
+//! `script_require_keys`, `script_mandatory_keys` and `script_exclude_keys`. Parts of a key that
+//! are computed at runtime, e.g. `"www/" + port + "/banner"`, are replaced by the wildcard `*`;
+//! keys that are computed completely are skipped. A read key is written when any written key may
+//! match it and vice versa.
+//!
+//! Keys read but never written usually point to a broken detection chain, keys written but never
+//! read to a left over after a refactoring. Keys written by the scanner itself are reported as
+//! unwritten as well and have to be ignored by their prefix.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use crate::nasl::syntax::{Loader, Statement, StatementKind, SyntaxError, TokenCategory};
+
+use super::analysis::{identifier, Unparsable};
+
+/// Functions writing the key given as `name`
+pub(crate) const WRITES: &[&str] = &["set_kb_item", "replace_kb_item"];
+/// Functions reading the key given as first positional parameter
+pub(crate) const READS: &[&str] = &["get_kb_item", "get_kb_list"];
+/// Functions requiring each key given as positional parameter
+pub(crate) const REQUIRES: &[&str] = &[
+    "script_require_keys",
+    "script_mandatory_keys",
+    "script_exclude_keys",
+];
+
+/// Returns the key of an expression with runtime computed parts replaced by `*`.
+fn pattern(stmt: &Statement) -> String {
+    match (stmt.kind(), stmt.as_token().category()) {
+        (StatementKind::Primitive, TokenCategory::String(x)) => x.clone(),
+        (StatementKind::Primitive, TokenCategory::Data(x)) => String::from_utf8_lossy(x).into(),
+        (StatementKind::Primitive, TokenCategory::Number(x)) => x.to_string(),
+        (StatementKind::Operator(TokenCategory::Plus, operands), _) => {
+            operands.iter().map(pattern).collect()
+        }
+        _ => "*".to_string(),
+    }
+}
+
+/// Returns the pattern of an expression unless it is computed completely.
+pub(crate) fn key(stmt: &Statement) -> Option<String> {
+    let mut result = String::new();
+    for c in pattern(stmt).chars() {
+        if !(c == '*' && result.ends_with('*')) {
+            result.push(c);
+        }
+    }
+    (result != "*").then_some(result)
+}
+
+/// Returns true when a key may match both patterns.
+pub(crate) fn overlaps(a: &[u8], b: &[u8]) -> bool {
+    match (a.first(), b.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => overlaps(&a[1..], b) || (!b.is_empty() && overlaps(a, &b[1..])),
+        (_, Some(b'*')) => overlaps(a, &b[1..]) || (!a.is_empty() && overlaps(&a[1..], b)),
+        (Some(x), Some(y)) => x == y && overlaps(&a[1..], &b[1..]),
+        _ => false,
+    }
+}
+
+/// The KB keys a single file writes and reads
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KbUsage {
+    writes: BTreeSet<String>,
+    reads: BTreeSet<String>,
+}
+
+impl KbUsage {
+    /// Parses the given code and collects the written and read KB keys.
+    pub fn from_code(code: &str) -> Result<Self, SyntaxError> {
+        let result = RefCell::new(Self::default());
+        for stmt in crate::nasl::syntax::parse(code) {
+            let stmt = stmt?;
+            // find stops descending on a match, so nothing is matched and the keys are
+            // collected while visiting to include nested calls
+            stmt.find(&|s| {
+                if !matches!(s.kind(), StatementKind::Call(_)) {
+                    return false;
+                }
+                let mut result = result.borrow_mut();
+                let name = identifier(s).unwrap_or_default();
+                let positional = s
+                    .children()
+                    .iter()
+                    .filter(|x| !matches!(x.kind(), StatementKind::NamedParameter(_)));
+                if WRITES.contains(&name) {
+                    let keys = s.children().iter().filter_map(|x| match x.kind() {
+                        StatementKind::NamedParameter(value) if identifier(x) == Some("name") => {
+                            key(value)
+                        }
+                        _ => None,
+                    });
+                    result.writes.extend(keys);
+                } else if READS.contains(&name) {
+                    result.reads.extend(positional.take(1).filter_map(key));
+                } else if REQUIRES.contains(&name) {
+                    result.reads.extend(positional.filter_map(key));
+                }
+                false
+            });
+        }
+        Ok(result.into_inner())
+    }
+
+    /// Returns the written keys.
+    pub fn writes(&self) -> impl Iterator<Item = &str> {
+        self.writes.iter().map(|x| x.as_str())
+    }
+
+    /// Returns the read and required keys.
+    pub fn reads(&self) -> impl Iterator<Item = &str> {
+        self.reads.iter().map(|x| x.as_str())
+    }
+}
+
+/// Keys of one kind of access together with the files accessing them
+#[derive(Debug, Clone, Default)]
+struct Keys {
+    literals: BTreeMap<String, BTreeSet<String>>,
+    patterns: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Keys {
+    fn insert(&mut self, key: &str, file: &str) {
+        let keys = match key.contains('*') {
+            true => &mut self.patterns,
+            false => &mut self.literals,
+        };
+        keys.entry(key.to_string())
+            .or_default()
+            .insert(file.to_string());
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &BTreeSet<String>)> {
+        self.literals.iter().chain(self.patterns.iter())
+    }
+
+    /// Returns true when a key of this access may match the given key.
+    fn matches(&self, key: &str) -> bool {
+        let overlaps = |x: &String| overlaps(x.as_bytes(), key.as_bytes());
+        let literal = match key.split_once('*') {
+            None => self.literals.contains_key(key),
+            // only literals starting with the prefix of a pattern may match it
+            Some((prefix, _)) => self
+                .literals
+                .range(prefix.to_string()..)
+                .take_while(|(x, _)| x.starts_with(prefix))
+                .any(|(x, _)| overlaps(x)),
+        };
+        literal || self.patterns.keys().any(overlaps)
+    }
+}
+
+/// A KB key and the files accessing it
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct KbKey {
+    /// The key, runtime computed parts are replaced by `*`
+    pub key: String,
+    /// Files reading or writing the key
+    pub files: Vec<String>,
+}
+
+/// Result of the check of the KB items shared within a feed
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct KbGraphReport {
+    /// Number of analyzed files
+    pub files: usize,
+    /// Number of distinct written keys
+    pub written: usize,
+    /// Number of distinct read keys
+    pub read: usize,
+    /// Keys that are read but never written
+    pub unwritten: Vec<KbKey>,
+    /// Keys that are written but never read
+    pub unread: Vec<KbKey>,
+    /// Skipped files
+    pub unparsable: Unparsable,
+}
+
+/// The KB keys written and read by the files of a feed
+#[derive(Debug, Clone, Default)]
+pub struct KbGraph {
+    writes: Keys,
+    reads: Keys,
+    files: usize,
+    unparsable: Unparsable,
+}
+
+impl KbGraph {
+    /// Analyzes the given scripts and inc files.
+    ///
+    /// Files that cannot be loaded or parsed are skipped and listed as unparsable.
+    pub fn new<L, I>(loader: &L, files: I) -> Self
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut result = Self::default();
+        for key in files {
+            match loader
+                .load(&key)
+                .map_err(|e| e.to_string())
+                .and_then(|x| KbUsage::from_code(&x).map_err(|e| e.to_string()))
+            {
+                Ok(usage) => {
+                    usage.writes().for_each(|x| result.writes.insert(x, &key));
+                    usage.reads().for_each(|x| result.reads.insert(x, &key));
+                    result.files += 1;
+                }
+                Err(e) => {
+                    result.unparsable.insert(key, e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the files writing a key that may match the given key.
+    pub fn writers(&self, key: &str) -> BTreeSet<&str> {
+        self.writes
+            .iter()
+            .filter(|(x, _)| overlaps(x.as_bytes(), key.as_bytes()))
+            .flat_map(|(_, files)| files.iter().map(|x| x.as_str()))
+            .collect()
+    }
+
+    /// Reports the keys that are only read or only written.
+    ///
+    /// Keys starting with one of the ignored prefixes are not reported.
+    pub fn report(&self, ignored: &[String]) -> KbGraphReport {
+        let reported = |key: &str| !ignored.iter().any(|x| key.starts_with(x.as_str()));
+        let missing = |keys: &Keys, other: &Keys| {
+            keys.iter()
+                .filter(|(key, _)| reported(key) && !other.matches(key))
+                .map(|(key, files)| KbKey {
+                    key: key.clone(),
+                    files: files.iter().cloned().collect(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let sorted = |mut x: Vec<KbKey>| {
+            x.sort_by(|a, b| a.key.cmp(&b.key));
+            x
+        };
+        KbGraphReport {
+            files: self.files,
+            written: self.writes.literals.len() + self.writes.patterns.len(),
+            read: self.reads.literals.len() + self.reads.patterns.len(),
+            unwritten: sorted(missing(&self.reads, &self.writes)),
+            unread: sorted(missing(&self.writes, &self.reads)),
+            unparsable: self.unparsable.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(key: &str) -> String {
+        match key {
+            "detect.nasl" => r#"
+            if (description) {
+              script_require_keys("Services/www");
+              exit(0);
+            }
+            port = get_kb_item("Services/www");
+            set_kb_item(name: "www/" + port + "/server", value: "apache");
+            set_kb_item(name: "apache/detected", value: TRUE);
+            replace_kb_item(name: "apache/version", value: "2.4");
+            "#
+            .to_string(),
+            "vuln.nasl" => r#"
+            if (description) {
+              script_mandatory_keys("apache/detected", "apache/installed");
+              exit(0);
+            }
+            banner = get_kb_item("www/80/server");
+            report(value: get_kb_list("apache/*"));
+            set_kb_item(name: key, value: 1);
+            "#
+            .to_string(),
+            "find_service.inc" => {
+                "function register(port, proto) { set_kb_item(name: 'Services/' + proto, value: port); }"
+                    .to_string()
+            }
+            "broken.nasl" => "if (".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn collects_keys() {
+        let usage = KbUsage::from_code(&example("detect.nasl")).unwrap();
+        assert_eq!(
+            usage.writes().collect::<Vec<_>>(),
+            vec!["apache/detected", "apache/version", "www/*/server"]
+        );
+        assert_eq!(usage.reads().collect::<Vec<_>>(), vec!["Services/www"]);
+        let usage = KbUsage::from_code(&example("vuln.nasl")).unwrap();
+        assert_eq!(usage.writes().count(), 0);
+        assert_eq!(
+            usage.reads().collect::<Vec<_>>(),
+            vec![
+                "apache/*",
+                "apache/detected",
+                "apache/installed",
+                "www/80/server"
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_patterns() {
+        let overlaps = |a: &str, b: &str| overlaps(a.as_bytes(), b.as_bytes());
+        assert!(overlaps("www/*/server", "www/80/server"));
+        assert!(overlaps("apache/*", "*/version"));
+        assert!(overlaps("*", ""));
+        assert!(!overlaps("www/*/server", "www/80/banner"));
+        assert!(!overlaps("apache/version", "apache/versions"));
+    }
+
+    #[test]
+    fn reports_unmatched_keys() {
+        let files = [
+            "detect.nasl",
+            "vuln.nasl",
+            "find_service.inc",
+            "broken.nasl",
+        ]
+        .map(|x| x.to_string());
+        let graph = KbGraph::new(&example, files);
+        assert_eq!(
+            graph.writers("apache/*"),
+            ["detect.nasl"].into_iter().collect()
+        );
+        let report = graph.report(&[]);
+        assert_eq!(report.files, 3);
+        assert_eq!(report.written, 4);
+        assert_eq!(report.read, 5);
+        assert_eq!(
+            report.unparsable.keys().collect::<Vec<_>>(),
+            vec!["broken.nasl"]
+        );
+        assert_eq!(
+            report.unwritten,
+            vec![KbKey {
+                key: "apache/installed".to_string(),
+                files: vec!["vuln.nasl".to_string()],
+            }]
+        );
+        assert_eq!(report.unread, vec![]);
+        let report = graph.report(&["apache/".to_string()]);
+        assert_eq!(report.unwritten, vec![]);
+    }
+}
@@ -0,0 +1,517 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Checks the `script_tag` values of the scripts of a feed against the Greenbone conventions.
+//!
+//! The tags are read statically from the `script_tag` calls with literal name and value, the
+//! scripts are not run. Each [Rule] has a default [Severity] that can be overridden, a rule set to
+//! [Severity::Off] is not checked.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use time::{format_description, OffsetDateTime};
+
+use crate::nasl::syntax::{Loader, StatementKind, SyntaxError};
+use crate::storage::item::{QodType, SolutionType, TagKey};
+
+use super::analysis::{identifier, literal, Unparsable};
+
+/// The date of the creation_date, last_modification and severity_date tags
+const DATE: &str =
+    "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]";
+
+/// Tags that are not used anymore and their replacement
+const DEPRECATED_TAGS: [(&str, &str); 3] = [
+    (
+        "cvss_base",
+        "the severity is calculated from severity_vector",
+    ),
+    ("cvss_base_vector", "use severity_vector instead"),
+    ("solution_method", "use solution_type instead"),
+];
+
+/// How a finding is treated
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The rule is not checked
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "unknown severity {s}, expected off, info, warning or error"
+            )),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let x = match self {
+            Self::Off => "off",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        };
+        write!(f, "{x}")
+    }
+}
+
+macro_rules! rules {
+    ($($(#[doc = $doc:expr])* $name:ident => $id:literal, $severity:ident;)+) => {
+        /// The checks of the tag linter
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize,
+            serde::Deserialize,
+        )]
+        #[serde(rename_all = "kebab-case")]
+        pub enum Rule {
+            $($(#[doc = $doc])* $name,)+
+        }
+
+        impl Rule {
+            /// All rules
+            pub const ALL: &'static [Rule] = &[$(Rule::$name,)+];
+
+            /// Returns the id of the rule
+            pub fn id(&self) -> &'static str {
+                match self {
+                    $(Rule::$name => $id,)+
+                }
+            }
+
+            /// Returns the severity of the rule when it is not overridden
+            pub fn default_severity(&self) -> Severity {
+                match self {
+                    $(Rule::$name => Severity::$severity,)+
+                }
+            }
+        }
+
+        impl FromStr for Rule {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($id => Ok(Rule::$name),)+
+                    _ => Err(format!("unknown rule {s}")),
+                }
+            }
+        }
+    };
+}
+
+rules! {
+    /// The tag name is unknown
+    UnknownTag => "unknown-tag", Warning;
+    /// The solution_type is not within the vocabulary
+    SolutionType => "solution-type", Error;
+    /// The qod_type is not within the vocabulary
+    QodType => "qod-type", Error;
+    /// The qod is no number from 0 to 100
+    Qod => "qod", Error;
+    /// Both qod and qod_type are set
+    QodConflict => "qod-conflict", Warning;
+    /// A date is not formatted as `2024-01-31 10:00:00 +0100 (Wed, 31 Jan 2024)`
+    DateFormat => "date-format", Error;
+    /// The last_modification is before the creation_date
+    DateOrder => "date-order", Warning;
+    /// The value of the deprecated tag is no boolean
+    DeprecatedValue => "deprecated-value", Error;
+    /// The tag is not used anymore
+    DeprecatedTag => "deprecated-tag", Warning;
+    /// The tag is set more than once
+    DuplicateTag => "duplicate-tag", Warning;
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// A violated rule
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TagFinding {
+    pub file: String,
+    /// The line of the `script_tag` call
+    pub line: usize,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub tag: String,
+    pub message: String,
+}
+
+impl Display for TagFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} [{}] {}",
+            self.file, self.line, self.severity, self.rule, self.message
+        )
+    }
+}
+
+/// The findings of all scripts of a feed
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LintReport {
+    /// Number of linted scripts
+    pub scripts: usize,
+    /// The findings ordered by file and line
+    pub findings: Vec<TagFinding>,
+    /// Skipped scripts
+    pub unparsable: Unparsable,
+}
+
+impl LintReport {
+    /// Returns the number of findings of the severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|x| x.severity == severity)
+            .count()
+    }
+
+    /// Returns the number of scripts with findings of severity error
+    pub fn failed_scripts(&self) -> usize {
+        let mut files: Vec<_> = self
+            .findings
+            .iter()
+            .filter(|x| x.severity == Severity::Error)
+            .map(|x| &x.file)
+            .collect();
+        files.dedup();
+        files.len()
+    }
+}
+
+/// A tag set via `script_tag` with literal name and value
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tag {
+    name: String,
+    value: String,
+    line: usize,
+}
+
+/// Returns the tags set by the code.
+fn tags(code: &str) -> Result<Vec<Tag>, SyntaxError> {
+    let mut result = vec![];
+    for stmt in crate::nasl::syntax::parse(code) {
+        let stmt = stmt?;
+        let calls = stmt.find(&|s| {
+            matches!(s.kind(), StatementKind::Call(_)) && identifier(s) == Some("script_tag")
+        });
+        for call in calls {
+            let (mut name, mut value) = (None, None);
+            for parameter in call.children() {
+                if let StatementKind::NamedParameter(x) = parameter.kind() {
+                    match identifier(parameter) {
+                        Some("name") => name = literal(x),
+                        Some("value") => value = literal(x),
+                        _ => {}
+                    }
+                }
+            }
+            if let (Some(name), Some(value)) = (name, value) {
+                result.push(Tag {
+                    name,
+                    value,
+                    line: call.as_token().line_column.0,
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the timestamp of a date formatted as `2024-01-31 10:00:00 +0100 (Wed, 31 Jan 2024)`.
+fn parse_date(value: &str) -> Option<i64> {
+    let (date, suffix) = value.split_once(" (")?;
+    let format = format_description::parse(DATE).ok()?;
+    let date = OffsetDateTime::parse(date, &format).ok()?;
+    let expected = format!(
+        "{:.3}, {:02} {:.3} {})",
+        date.weekday().to_string(),
+        date.day(),
+        date.month().to_string(),
+        date.year()
+    );
+    (suffix == expected).then(|| date.unix_timestamp())
+}
+
+/// Checks the tags of scripts
+#[derive(Debug, Clone, Default)]
+pub struct TagLinter {
+    severities: HashMap<Rule, Severity>,
+}
+
+impl TagLinter {
+    /// Overrides the severity of a rule
+    pub fn with_severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+
+    /// Returns the severity of the rule
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+
+    /// Checks the tags of a single script.
+    pub fn lint_code(&self, file: &str, code: &str) -> Result<Vec<TagFinding>, SyntaxError> {
+        let tags = tags(code)?;
+        let mut findings = vec![];
+        let mut report = |tag: &Tag, rule: Rule, message: String| {
+            let severity = self.severity(rule);
+            if severity != Severity::Off {
+                findings.push(TagFinding {
+                    file: file.to_owned(),
+                    line: tag.line,
+                    rule,
+                    severity,
+                    tag: tag.name.clone(),
+                    message,
+                });
+            }
+        };
+        let mut seen: HashMap<&str, &Tag> = HashMap::new();
+        for tag in &tags {
+            if seen.insert(&tag.name, tag).is_some() {
+                report(
+                    tag,
+                    Rule::DuplicateTag,
+                    format!("{} is set more than once", tag.name),
+                );
+            }
+            if let Some((_, hint)) = DEPRECATED_TAGS.iter().find(|(x, _)| *x == tag.name) {
+                report(
+                    tag,
+                    Rule::DeprecatedTag,
+                    format!("{} is deprecated, {hint}", tag.name),
+                );
+            }
+            let Ok(key) = TagKey::from_str(&tag.name) else {
+                report(tag, Rule::UnknownTag, format!("unknown tag {}", tag.name));
+                continue;
+            };
+            match key {
+                TagKey::SolutionType if SolutionType::from_str(&tag.value).is_err() => report(
+                    tag,
+                    Rule::SolutionType,
+                    format!(
+                        "solution_type {} is none of VendorFix, Mitigation, Workaround, \
+                        WillNotFix and NoneAvailable",
+                        tag.value
+                    ),
+                ),
+                TagKey::QodType if QodType::from_str(&tag.value).is_err() => report(
+                    tag,
+                    Rule::QodType,
+                    format!("unknown qod_type {}", tag.value),
+                ),
+                TagKey::Qod if !tag.value.parse::<u8>().is_ok_and(|x| x <= 100) => report(
+                    tag,
+                    Rule::Qod,
+                    format!("qod {} is no number from 0 to 100", tag.value),
+                ),
+                TagKey::CreationDate | TagKey::LastModification | TagKey::SeverityDate
+                    if parse_date(&tag.value).is_none() =>
+                {
+                    report(
+                        tag,
+                        Rule::DateFormat,
+                        format!(
+                            "{} {} is not formatted as YYYY-MM-DD hh:mm:ss +hhmm (Www, DD Mmm \
+                            YYYY)",
+                            tag.name, tag.value
+                        ),
+                    )
+                }
+                TagKey::Deprecated
+                    if !matches!(
+                        tag.value.as_str(),
+                        "TRUE" | "FALSE" | "true" | "false" | "1" | "0"
+                    ) =>
+                {
+                    report(
+                        tag,
+                        Rule::DeprecatedValue,
+                        format!("deprecated {} is no boolean", tag.value),
+                    )
+                }
+                _ => {}
+            }
+        }
+        if let (Some(qod), Some(_)) = (seen.get("qod"), seen.get("qod_type")) {
+            report(
+                qod,
+                Rule::QodConflict,
+                "qod and qod_type are both set".to_string(),
+            );
+        }
+        let date = |name| seen.get(name).map(|x| (*x, parse_date(&x.value)));
+        if let (Some((_, Some(created))), Some((tag, Some(modified)))) =
+            (date("creation_date"), date("last_modification"))
+        {
+            if modified < created {
+                report(
+                    tag,
+                    Rule::DateOrder,
+                    "last_modification is before creation_date".to_string(),
+                );
+            }
+        }
+        findings.sort_by_key(|x| x.line);
+        Ok(findings)
+    }
+
+    /// Checks the tags of the given scripts.
+    ///
+    /// Scripts that cannot be loaded or parsed are skipped and listed as unparsable.
+    pub fn lint<L, I>(&self, loader: &L, scripts: I) -> LintReport
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut report = LintReport::default();
+        for key in scripts {
+            match loader
+                .load(&key)
+                .map_err(|e| e.to_string())
+                .and_then(|x| self.lint_code(&key, &x).map_err(|e| e.to_string()))
+            {
+                Ok(findings) => {
+                    report.scripts += 1;
+                    report.findings.extend(findings);
+                }
+                Err(e) => {
+                    report.unparsable.insert(key, e);
+                }
+            }
+        }
+        report
+            .findings
+            .sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+if (description) {
+  script_oid("1.3.6.1.4.1.25623.1.0.100001");
+  script_tag(name:"creation_date", value:"2024-01-31 10:00:00 +0100 (Wed, 31 Jan 2024)");
+  script_tag(name:"last_modification", value:"2023-12-01 10:00:00 +0100 (Fri, 01 Dec 2023)");
+  script_tag(name:"severity_date", value:"2024-01-31 10:00:00 +0100 (Thu, 31 Jan 2024)");
+  script_tag(name:"cvss_base", value:"5.0");
+  script_tag(name:"solution_type", value:"Upgrade");
+  script_tag(name:"qod_type", value:"remote_banner");
+  script_tag(name:"qod", value:"120");
+  script_tag(name:"deprecated", value:TRUE);
+  script_tag(name:"summary", value:"First.");
+  script_tag(name:"summary", value:"Second.");
+  script_tag(name:"vulnerability", value:"x");
+  script_tag(name:"insight", value:"computed " + "value");
+  exit(0);
+}
+"#;
+
+    fn rules(findings: &[TagFinding]) -> Vec<(usize, Rule)> {
+        findings.iter().map(|x| (x.line, x.rule)).collect()
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            parse_date("2018-09-07 11:08:31 +0200 (Fri, 07 Sep 2018)"),
+            Some(1536311311)
+        );
+        // the weekday does not match the date
+        assert_eq!(
+            parse_date("2018-09-07 11:08:31 +0200 (Sat, 07 Sep 2018)"),
+            None
+        );
+        assert_eq!(parse_date("2018-09-07 11:08:31 +0200"), None);
+        assert_eq!(parse_date("$Date: 2018-09-07 11:08:31 +0200$"), None);
+    }
+
+    #[test]
+    fn findings() {
+        let findings = TagLinter::default().lint_code("a.nasl", SCRIPT).unwrap();
+        assert_eq!(
+            rules(&findings),
+            vec![
+                (5, Rule::DateOrder),
+                (6, Rule::DateFormat),
+                (7, Rule::DeprecatedTag),
+                (8, Rule::SolutionType),
+                (10, Rule::Qod),
+                (10, Rule::QodConflict),
+                (13, Rule::DuplicateTag),
+                (14, Rule::UnknownTag),
+            ]
+        );
+        assert_eq!(
+            findings[0].to_string(),
+            "a.nasl:5: warning [date-order] last_modification is before creation_date"
+        );
+    }
+
+    #[test]
+    fn severities() {
+        let linter = TagLinter::default()
+            .with_severity(Rule::DeprecatedTag, Severity::Off)
+            .with_severity(Rule::UnknownTag, Severity::Error);
+        let findings = linter.lint_code("a.nasl", SCRIPT).unwrap();
+        assert!(!findings.iter().any(|x| x.rule == Rule::DeprecatedTag));
+        assert_eq!(
+            findings
+                .iter()
+                .find(|x| x.rule == Rule::UnknownTag)
+                .map(|x| x.severity),
+            Some(Severity::Error)
+        );
+        assert_eq!("date-format".parse(), Ok(Rule::DateFormat));
+        assert_eq!("warning".parse(), Ok(Severity::Warning));
+        assert!("fatal".parse::<Severity>().is_err());
+    }
+
+    #[test]
+    fn report() {
+        let example = |key: &str| match key {
+            "a.nasl" => SCRIPT.to_string(),
+            "b.nasl" => r#"script_tag(name:"qod_type", value:"guess");"#.to_string(),
+            "c.nasl" => "if (".to_string(),
+            _ => String::new(),
+        };
+        let scripts = ["b.nasl", "a.nasl", "c.nasl"].map(|x| x.to_string());
+        let report = TagLinter::default().lint(&example, scripts);
+        assert_eq!(report.scripts, 2);
+        assert_eq!(report.unparsable.keys().collect::<Vec<_>>(), vec!["c.nasl"]);
+        assert_eq!(report.findings.len(), 9);
+        assert_eq!(report.findings[8].rule, Rule::QodType);
+        assert_eq!(report.count(Severity::Error), 4);
+        assert_eq!(report.failed_scripts(), 2);
+    }
+}
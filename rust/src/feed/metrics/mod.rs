@@ -0,0 +1,344 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Collects complexity metrics of the scripts and inc files of a feed.
+//!
+//! Each file is parsed to count its statements, the decision points for the cyclomatic
+//! complexity, the deepest nesting of control structures and the calls of [NETWORK] functions.
+//! The includes of a file are its fan-out, the files including it its fan-in. The metrics are
+//! derived statically, a call within a loop is counted once.
+
+use std::collections::BTreeMap;
+
+use crate::nasl::syntax::{Loader, Statement, StatementKind, SyntaxError, TokenCategory};
+
+use super::analysis::{identifier, Unparsable};
+
+/// Functions opening connections or sending and receiving data over the network
+pub const NETWORK: &[&str] = &[
+    "open_sock_tcp",
+    "open_sock_udp",
+    "open_priv_sock_tcp",
+    "open_priv_sock_udp",
+    "send",
+    "recv",
+    "recv_line",
+    "send_packet",
+    "send_v6packet",
+    "send_capture",
+    "tcp_ping",
+    "http_send_recv",
+    "http_keepalive_send_recv",
+    "http_get_cache",
+    "get_http_banner",
+    "ftp_log_in",
+    "ftp_recv_line",
+    "ssh_connect",
+    "ssh_cmd",
+    "ssh_request_exec",
+    "smb_connect",
+    "snmp_get",
+];
+
+/// Metrics of a single file
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FileMetrics {
+    /// The file, relative to the feed
+    pub file: String,
+    /// Number of statements, blocks and empty statements are not counted
+    pub statements: usize,
+    /// Cyclomatic complexity of the whole file, functions included
+    pub complexity: usize,
+    /// Deepest nesting of `if`, `for`, `foreach`, `while` and `repeat`, an `else if` does not
+    /// nest
+    pub max_nesting: usize,
+    /// Number of calls of network functions
+    pub network_calls: usize,
+    /// Number of distinct files included by this file
+    pub fan_out: usize,
+    /// Number of files including this file
+    pub fan_in: usize,
+    #[serde(skip)]
+    includes: Vec<String>,
+}
+
+impl FileMetrics {
+    /// Parses the given code and collects its metrics.
+    pub fn from_code(file: &str, code: &str) -> Result<Self, SyntaxError> {
+        let mut result = Self {
+            file: file.to_string(),
+            complexity: 1,
+            ..Default::default()
+        };
+        for stmt in crate::nasl::syntax::parse(code) {
+            result.statement(&stmt?, 0);
+        }
+        result.fan_out = result.includes.len();
+        Ok(result)
+    }
+
+    /// Returns the constant names of the included files.
+    pub fn includes(&self) -> &[String] {
+        &self.includes
+    }
+
+    fn statement(&mut self, s: &Statement, depth: usize) {
+        if !matches!(
+            s.kind(),
+            StatementKind::Block(_) | StatementKind::NoOp | StatementKind::EoF
+        ) {
+            self.statements += 1;
+            self.max_nesting = self.max_nesting.max(depth);
+        }
+        self.walk(s, depth);
+    }
+
+    fn walk(&mut self, s: &Statement, depth: usize) {
+        match s.kind() {
+            StatementKind::Primitive
+            | StatementKind::AttackCategory
+            | StatementKind::Variable
+            | StatementKind::NoOp
+            | StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Array(None)
+            | StatementKind::EoF => {}
+            StatementKind::Include(x) => {
+                let name = match x.as_token().category() {
+                    TokenCategory::String(x) => Some(x.clone()),
+                    TokenCategory::Data(x) => Some(x.iter().map(|&b| b as char).collect()),
+                    _ => None,
+                };
+                if let Some(name) = name.filter(|x| !self.includes.contains(x)) {
+                    self.includes.push(name);
+                }
+            }
+            StatementKind::Call(x) => {
+                if identifier(s).is_some_and(|x| NETWORK.contains(&x)) {
+                    self.network_calls += 1;
+                }
+                self.walk(x, depth);
+            }
+            StatementKind::NamedParameter(x)
+            | StatementKind::Exit(x)
+            | StatementKind::Return(x)
+            | StatementKind::Array(Some(x)) => self.walk(x, depth),
+            StatementKind::Operator(operator, x) => {
+                if matches!(
+                    operator,
+                    TokenCategory::AmpersandAmpersand | TokenCategory::PipePipe
+                ) {
+                    self.complexity += 1;
+                }
+                x.iter().for_each(|x| self.walk(x, depth));
+            }
+            StatementKind::Parameter(x) | StatementKind::Declare(x) => {
+                x.iter().for_each(|x| self.walk(x, depth))
+            }
+            StatementKind::Block(x) => x.iter().for_each(|x| self.statement(x, depth)),
+            StatementKind::Assign(_, _, x, y) => {
+                self.walk(x, depth);
+                self.walk(y, depth);
+            }
+            StatementKind::If(condition, then, _, otherwise) => {
+                self.complexity += 1;
+                self.walk(condition, depth);
+                self.statement(then, depth + 1);
+                match otherwise.as_deref() {
+                    Some(x) if matches!(x.kind(), StatementKind::If(..)) => self.walk(x, depth),
+                    Some(x) => self.statement(x, depth + 1),
+                    None => {}
+                }
+            }
+            StatementKind::For(init, condition, update, body) => {
+                self.complexity += 1;
+                self.walk(init, depth);
+                self.walk(condition, depth);
+                self.walk(update, depth);
+                self.statement(body, depth + 1);
+            }
+            StatementKind::While(condition, body) => {
+                self.complexity += 1;
+                self.walk(condition, depth);
+                self.statement(body, depth + 1);
+            }
+            StatementKind::Repeat(body, condition) => {
+                self.complexity += 1;
+                self.statement(body, depth + 1);
+                self.walk(condition, depth);
+            }
+            StatementKind::ForEach(_, array, body) => {
+                self.complexity += 1;
+                self.walk(array, depth);
+                self.statement(body, depth + 1);
+            }
+            StatementKind::FunctionDeclaration(_, _, body) => self.statement(body, depth),
+        }
+    }
+}
+
+/// Aggregated metrics of all analyzed files
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MetricsSummary {
+    /// Number of analyzed files
+    pub files: usize,
+    /// Number of statements of all files
+    pub statements: usize,
+    /// Sum of the cyclomatic complexity of all files
+    pub complexity: usize,
+    /// Highest cyclomatic complexity of a file
+    pub max_complexity: usize,
+    /// Deepest nesting within a file
+    pub max_nesting: usize,
+    /// Number of network calls of all files
+    pub network_calls: usize,
+}
+
+/// Metrics of the files of a feed
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MetricsReport {
+    /// Metrics of each analyzed file ordered by name
+    pub files: Vec<FileMetrics>,
+    /// Aggregated metrics of the analyzed files
+    pub summary: MetricsSummary,
+    /// Skipped files
+    pub unparsable: Unparsable,
+}
+
+impl MetricsReport {
+    /// Analyzes the given scripts and inc files.
+    ///
+    /// Files that cannot be loaded or parsed are skipped and listed as unparsable. The fan-in of
+    /// a file only counts the analyzed files including it.
+    pub fn new<L, I>(loader: &L, files: I) -> Self
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut result = Self::default();
+        let mut metrics = BTreeMap::new();
+        for key in files {
+            match loader
+                .load(&key)
+                .map_err(|e| e.to_string())
+                .and_then(|x| FileMetrics::from_code(&key, &x).map_err(|e| e.to_string()))
+            {
+                Ok(x) => {
+                    metrics.insert(key, x);
+                }
+                Err(e) => {
+                    result.unparsable.insert(key, e);
+                }
+            }
+        }
+        let included: Vec<String> = metrics
+            .values()
+            .flat_map(|x| x.includes.iter().cloned())
+            .collect();
+        for name in included {
+            if let Some(x) = metrics.get_mut(&name) {
+                x.fan_in += 1;
+            }
+        }
+        let summary = &mut result.summary;
+        for x in metrics.values() {
+            summary.files += 1;
+            summary.statements += x.statements;
+            summary.complexity += x.complexity;
+            summary.max_complexity = summary.max_complexity.max(x.complexity);
+            summary.max_nesting = summary.max_nesting.max(x.max_nesting);
+            summary.network_calls += x.network_calls;
+        }
+        result.files = metrics.into_values().collect();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(key: &str) -> String {
+        match key {
+            "vuln.nasl" => r#"
+            include("http_func.inc");
+            include("misc.inc");
+            include("http_func.inc");
+            if (description) {
+              script_oid("1.3.6.1.4.1.25623.1.0.1");
+              exit(0);
+            }
+            port = get_kb_item("Services/www");
+            if (!port || port == 0) exit(0);
+            foreach url (make_list("/", "/admin")) {
+              res = http_keepalive_send_recv(port: port, data: url);
+              if (res) {
+                while (x < 3) { x++; }
+              } else if (isnull(res)) {
+                display("none");
+              } else {
+                ;
+              }
+            }
+            "#
+            .to_string(),
+            "http_func.inc" => r#"
+            include("misc.inc");
+            function http_keepalive_send_recv(port, data) {
+              soc = open_sock_tcp(port);
+              send(socket: soc, data: data);
+              return recv(socket: soc, length: 1024);
+            }
+            "#
+            .to_string(),
+            "misc.inc" => "function isnull(x) { return x == NULL; }".to_string(),
+            "broken.nasl" => "if (".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn file_metrics() {
+        let metrics = FileMetrics::from_code("vuln.nasl", &example("vuln.nasl")).unwrap();
+        assert_eq!(metrics.includes(), ["http_func.inc", "misc.inc"]);
+        assert_eq!(
+            metrics,
+            FileMetrics {
+                file: "vuln.nasl".to_string(),
+                statements: 15,
+                // if, if, ||, foreach, if, while, else if
+                complexity: 8,
+                max_nesting: 3,
+                network_calls: 1,
+                fan_out: 2,
+                fan_in: 0,
+                includes: metrics.includes.clone(),
+            }
+        );
+    }
+
+    #[test]
+    fn report() {
+        let files =
+            ["vuln.nasl", "http_func.inc", "misc.inc", "broken.nasl"].map(|x| x.to_string());
+        let report = MetricsReport::new(&example, files);
+        let fan_in: Vec<_> = report
+            .files
+            .iter()
+            .map(|x| (x.file.as_str(), x.fan_in))
+            .collect();
+        assert_eq!(
+            fan_in,
+            vec![("http_func.inc", 1), ("misc.inc", 2), ("vuln.nasl", 0)]
+        );
+        assert_eq!(
+            report.unparsable.keys().collect::<Vec<_>>(),
+            vec!["broken.nasl"]
+        );
+        assert_eq!(report.summary.files, 3);
+        assert_eq!(report.summary.network_calls, 4);
+        assert_eq!(report.summary.max_complexity, 8);
+        assert_eq!(report.summary.max_nesting, 3);
+    }
+}
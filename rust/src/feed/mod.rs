@@ -3,26 +3,67 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+mod analysis;
+mod conformance;
+mod include_graph;
+mod kb_graph;
+mod lint;
+mod metrics;
 mod oid;
+mod parity;
+mod taint;
 mod transpile;
+mod trial;
 mod update;
 mod verify;
 
 #[cfg(test)]
 mod update_tests;
 
+pub use analysis::Unparsable;
+pub use conformance::{
+    compare as compare_observations, CaseReport, Conformance, ConformanceReport, Difference,
+    Error as ConformanceError, Observation, ObservedResult, ReferenceSource,
+};
+pub use include_graph::IncludeGraph;
+pub use kb_graph::{KbGraph, KbGraphReport, KbKey, KbUsage};
+pub use lint::{LintReport, Rule as LintRule, Severity as LintSeverity, TagFinding, TagLinter};
+pub use metrics::{FileMetrics, MetricsReport, MetricsSummary};
 pub use oid::Oid;
+pub use parity::{FeedAnalysis, FunctionUsage, MissingFunction, ParityReport};
+pub use taint::{TaintFinding, TaintKind, TaintLinter, TaintReport};
+pub use trial::Trial;
 pub use update::feed_version as version;
 pub use update::Error as UpdateError;
 pub use update::ErrorKind as UpdateErrorKind;
+pub use update::FileReport;
+pub use update::Outcome as FileOutcome;
+pub use update::Report as UpdateReport;
 pub use update::Update;
+pub use verify::check_content_signature;
+pub use verify::check_file_signature;
 pub use verify::check_signature;
+pub use verify::check_signature_with;
+pub use verify::verify_detached;
+pub use verify::DetachedSignatureNameLoader;
 pub use verify::Error as VerifyError;
 pub use verify::FileNameLoader;
+pub use verify::HashSumFileItem;
 pub use verify::HashSumNameLoader;
 pub use verify::Hasher;
+pub use verify::Keyring;
+pub use verify::LayeredNameLoader;
 pub use verify::NaslFileFinder;
+pub use verify::ParallelVerifier;
 pub use verify::SignatureChecker;
+pub use verify::TrustStore;
 
+pub use transpile::deprecate;
+pub use transpile::BatchReplacer;
+pub use transpile::BatchSummary;
+pub use transpile::DynamicKbKey;
+pub use transpile::FeedDeprecator;
+pub use transpile::FeedKbRenamer;
 pub use transpile::FeedReplacer;
+pub use transpile::KbRenamer;
 pub use transpile::ReplaceCommand;
@@ -4,9 +4,7 @@
 
 //! Is a module to get oids within a feed
 
-use std::fs::File;
-
-use crate::nasl::syntax::{AsBufReader, Loader};
+use crate::nasl::syntax::Loader;
 use crate::nasl::syntax::{IdentifierType, Statement, StatementKind, TokenCategory};
 
 use crate::feed::{
@@ -22,7 +20,7 @@ pub struct Oid<L, V> {
 }
 impl<'a, L, V> Oid<L, V>
 where
-    L: Sync + Send + Loader + AsBufReader<File>,
+    L: Sync + Send + Loader,
     V: Iterator<Item = Result<HashSumFileItem<'a>, verify::Error>>,
 {
     /// Creates an oid finder. Returns a tuple of (filename, oid).
@@ -72,7 +70,7 @@ where
 
 impl<'a, L, V> Iterator for Oid<L, V>
 where
-    L: Sync + Send + Loader + AsBufReader<File>,
+    L: Sync + Send + Loader,
     V: Iterator<Item = Result<HashSumFileItem<'a>, verify::Error>>,
 {
     type Item = Result<(String, String), update::Error>;
@@ -0,0 +1,262 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Compares the functions called within a feed with the builtin functions of the current build.
+//!
+//! Each script and inc file is parsed to collect the names of the called and declared functions.
+//! A script depends on a function when it or one of its transitive includes calls it. Functions
+//! that are neither declared within the script or its includes nor known to the executor are
+//! reported as missing, ranked by the number of dependent scripts.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use crate::nasl::syntax::{IdentifierType, Loader, StatementKind, SyntaxError, TokenCategory};
+use crate::nasl::utils::Executor;
+
+use super::analysis::{identifier, Unparsable};
+use super::IncludeGraph;
+
+/// The functions a single file calls and declares
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionUsage {
+    calls: BTreeSet<String>,
+    declarations: BTreeSet<String>,
+}
+
+impl FunctionUsage {
+    /// Parses the given code and collects the names of all called and declared functions.
+    pub fn from_code(code: &str) -> Result<Self, SyntaxError> {
+        let result = RefCell::new(Self::default());
+        for stmt in crate::nasl::syntax::parse(code) {
+            let stmt = stmt?;
+            // find stops descending on a match, so nothing is matched and the calls are
+            // collected while visiting to include nested calls and declaration bodies
+            stmt.find(&|s| {
+                let mut result = result.borrow_mut();
+                match s.kind() {
+                    StatementKind::FunctionDeclaration(name, _, _) => {
+                        if let TokenCategory::Identifier(IdentifierType::Undefined(x)) =
+                            name.category()
+                        {
+                            result.declarations.insert(x.clone());
+                        }
+                    }
+                    StatementKind::Call(_) => result.calls.extend(identifier(s).map(String::from)),
+                    _ => {}
+                }
+                false
+            });
+        }
+        Ok(result.into_inner())
+    }
+
+    /// Returns the names of the called functions.
+    pub fn calls(&self) -> impl Iterator<Item = &str> {
+        self.calls.iter().map(|x| x.as_str())
+    }
+
+    /// Returns the names of the declared functions.
+    pub fn declarations(&self) -> impl Iterator<Item = &str> {
+        self.declarations.iter().map(|x| x.as_str())
+    }
+}
+
+/// A function that is used within the feed but neither builtin nor declared
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MissingFunction {
+    /// Name of the function
+    pub name: String,
+    /// Number of scripts calling the function directly or via an include
+    pub scripts: usize,
+    /// Number of scripts and inc files calling the function directly
+    pub files: usize,
+}
+
+/// Result of the comparison between a feed and the builtin functions
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ParityReport {
+    /// Number of analyzed scripts
+    pub scripts: usize,
+    /// Number of distinct functions called by the scripts that are not declared by them
+    pub called: usize,
+    /// Number of those functions that are builtin
+    pub implemented: usize,
+    /// The missing functions ordered by the number of dependent scripts
+    pub missing: Vec<MissingFunction>,
+    /// Skipped scripts and includes
+    pub unparsable: Unparsable,
+}
+
+/// The function usage of all files of a feed
+#[derive(Debug, Clone, Default)]
+pub struct FeedAnalysis {
+    usage: BTreeMap<String, FunctionUsage>,
+    graph: IncludeGraph,
+    scripts: Vec<String>,
+    unparsable: Unparsable,
+}
+
+impl FeedAnalysis {
+    /// Analyzes the given scripts and all of their transitive includes.
+    ///
+    /// Scripts as well as includes that cannot be loaded or parsed are skipped and listed as
+    /// unparsable.
+    pub fn new<L, I>(loader: &L, scripts: I) -> Self
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut result = Self::default();
+        for key in scripts {
+            if let Err(e) = result.graph.insert(loader, &key) {
+                result.unparsable.insert(key, e.to_string());
+                continue;
+            }
+            for key in std::iter::once(key.clone()).chain(result.graph.resolve(&key)) {
+                if result.usage.contains_key(&key) || result.unparsable.contains_key(&key) {
+                    continue;
+                }
+                match loader
+                    .load(&key)
+                    .map_err(|e| e.to_string())
+                    .and_then(|x| FunctionUsage::from_code(&x).map_err(|e| e.to_string()))
+                {
+                    Ok(usage) => {
+                        result.usage.insert(key, usage);
+                    }
+                    Err(e) => {
+                        result.unparsable.insert(key, e);
+                    }
+                }
+            }
+            result.scripts.push(key);
+        }
+        result
+    }
+
+    /// Returns the usage of a single file.
+    pub fn usage(&self, key: &str) -> Option<&FunctionUsage> {
+        self.usage.get(key)
+    }
+
+    /// Returns the functions a script depends on that are not declared by itself or its includes.
+    pub fn dependencies(&self, key: &str) -> BTreeSet<&str> {
+        let files: Vec<&FunctionUsage> = std::iter::once(key.to_string())
+            .chain(self.graph.resolve(key))
+            .filter_map(|x| self.usage.get(&x))
+            .collect();
+        let declared: BTreeSet<&str> = files.iter().flat_map(|x| x.declarations()).collect();
+        files
+            .iter()
+            .flat_map(|x| x.calls())
+            .filter(|x| !declared.contains(x))
+            .collect()
+    }
+
+    /// Compares the dependencies of all scripts with the functions known to the executor.
+    pub fn report(&self, executor: &Executor) -> ParityReport {
+        let mut scripts: BTreeMap<&str, usize> = BTreeMap::new();
+        for key in &self.scripts {
+            for name in self.dependencies(key) {
+                *scripts.entry(name).or_default() += 1;
+            }
+        }
+        let (implemented, missing): (Vec<_>, Vec<_>) = scripts
+            .iter()
+            .partition(|(name, _)| executor.contains(name));
+        let mut missing: Vec<MissingFunction> = missing
+            .into_iter()
+            .map(|(name, scripts)| MissingFunction {
+                name: name.to_string(),
+                scripts: *scripts,
+                files: self
+                    .usage
+                    .values()
+                    .filter(|x| x.calls.contains(*name))
+                    .count(),
+            })
+            .collect();
+        missing.sort_by(|a, b| {
+            b.scripts
+                .cmp(&a.scripts)
+                .then(b.files.cmp(&a.files))
+                .then(a.name.cmp(&b.name))
+        });
+        ParityReport {
+            scripts: self.scripts.len(),
+            called: scripts.len(),
+            implemented: implemented.len(),
+            missing,
+            unparsable: self.unparsable.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nasl::nasl_std_functions;
+
+    use super::*;
+
+    fn example(key: &str) -> String {
+        match key {
+            "a.nasl" => r#"
+            include("helper.inc");
+            if (description) { exit(0); }
+            port = get_kb_item("Services/www");
+            helper(port: port);
+            "#
+            .to_string(),
+            "b.nasl" => r#"
+            include("helper.inc");
+            function local() { return proprietary_check(); }
+            local();
+            "#
+            .to_string(),
+            "c.nasl" => "proprietary_check(); include('missing.inc');".to_string(),
+            "d.nasl" => "if (".to_string(),
+            "helper.inc" => "function helper(port) { return not_yet_ported(port); }".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn collects_calls_and_declarations() {
+        let usage = FunctionUsage::from_code(&example("b.nasl")).unwrap();
+        assert_eq!(
+            usage.calls().collect::<Vec<_>>(),
+            vec!["local", "proprietary_check"]
+        );
+        assert_eq!(usage.declarations().collect::<Vec<_>>(), vec!["local"]);
+    }
+
+    #[test]
+    fn ranks_missing_functions() {
+        let scripts = ["a.nasl", "b.nasl", "c.nasl", "d.nasl"].map(|x| x.to_string());
+        let analysis = FeedAnalysis::new(&example, scripts);
+        let report = analysis.report(&nasl_std_functions());
+        assert_eq!(report.scripts, 3);
+        assert_eq!(report.unparsable.keys().collect::<Vec<_>>(), vec!["d.nasl"]);
+        assert_eq!(report.called, 3);
+        assert_eq!(report.implemented, 1);
+        assert_eq!(
+            report.missing,
+            vec![
+                MissingFunction {
+                    name: "proprietary_check".to_string(),
+                    scripts: 2,
+                    files: 2,
+                },
+                MissingFunction {
+                    name: "not_yet_ported".to_string(),
+                    scripts: 2,
+                    files: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,507 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Finds data of the target or the user flowing unsanitized into commands, SQL statements or raw
+//! sends.
+//!
+//! Each script and inc file is parsed and the return values of the [SOURCES], e.g. `recv` or
+//! `script_get_preference`, are followed through assignments, string concatenations and function
+//! calls. A finding is reported when such a value reaches a parameter of a sink, e.g. `cmd` of
+//! `pread` or `data` of `send`, or is concatenated with a string literal looking like a SQL
+//! statement. The return values of [SANITIZERS] and additional sanitization helpers are never
+//! tainted.
+//!
+//! The analysis is flow insensitive and does not distinguish between scopes, a variable once
+//! assigned a tainted value is tainted within the whole file. It errs on the side of reporting,
+//! the findings are meant as hints for the review of a contributed script.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use crate::nasl::syntax::{
+    IdentifierType, Loader, Statement, StatementKind, SyntaxError, TokenCategory,
+};
+
+use super::analysis::{identifier, Unparsable};
+
+/// Functions returning data controlled by the target or the user
+pub const SOURCES: &[&str] = &[
+    "recv",
+    "recv_line",
+    "ws_recv",
+    "ftp_recv_line",
+    "http_recv",
+    "http_recv_body",
+    "http_send_recv",
+    "http_keepalive_send_recv",
+    "get_http_banner",
+    "ssh_request_exec",
+    "ssh_shell_read",
+    "get_kb_item",
+    "get_kb_list",
+    "script_get_preference",
+    "get_preference",
+];
+
+/// Functions whose return value is safe to use regardless of their arguments
+pub const SANITIZERS: &[&str] = &[
+    "int",
+    "strlen",
+    "isnull",
+    "ereg",
+    "hexstr",
+    "base64",
+    "base64_encode",
+    "urlencode",
+];
+
+/// Parameters of the sinks: function, parameter, kind
+///
+/// An empty parameter name checks the positional parameters.
+const SINKS: &[(&str, &str, TaintKind)] = &[
+    ("pread", "cmd", TaintKind::Command),
+    ("pread", "argv", TaintKind::Command),
+    ("ssh_cmd", "cmd", TaintKind::Command),
+    ("ssh_request_exec", "cmd", TaintKind::Command),
+    ("win_cmd_exec", "cmd", TaintKind::Command),
+    ("send", "data", TaintKind::Send),
+    ("send_capture", "data", TaintKind::Send),
+    ("send_packet", "", TaintKind::Send),
+    ("send_v6packet", "", TaintKind::Send),
+];
+
+/// Lower case keywords of which one group must be within a string to look like a SQL statement
+const SQL: &[&[&str]] = &[
+    &["select ", " from "],
+    &["insert into "],
+    &["update ", " set "],
+    &["delete from "],
+];
+
+/// The kind of injection a sink is prone to
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TaintKind {
+    /// A command executed on the scanner host or the target
+    Command,
+    /// A SQL statement built by concatenation
+    Sql,
+    /// Data sent via a socket or raw packet
+    Send,
+}
+
+impl Display for TaintKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let x = match self {
+            Self::Command => "command",
+            Self::Sql => "sql",
+            Self::Send => "send",
+        };
+        write!(f, "{x}")
+    }
+}
+
+/// Tainted data reaching a sink
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub struct TaintFinding {
+    pub file: String,
+    /// The line of the sink
+    pub line: usize,
+    pub kind: TaintKind,
+    /// The sink, e.g. `pread(cmd)`
+    pub sink: String,
+    /// The function the tainted data originates from
+    pub source: String,
+}
+
+impl Display for TaintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: [{}] {} receives data of {} without sanitization",
+            self.file, self.line, self.kind, self.sink, self.source
+        )
+    }
+}
+
+/// The findings of all files of a feed
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TaintReport {
+    /// Number of analyzed files
+    pub files: usize,
+    /// The findings ordered by file and line
+    pub findings: Vec<TaintFinding>,
+    /// Skipped files
+    pub unparsable: Unparsable,
+}
+
+impl TaintReport {
+    /// Returns the number of files with findings
+    pub fn failed_files(&self) -> usize {
+        self.findings
+            .iter()
+            .map(|x| &x.file)
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+}
+
+/// Returns the operands of a concatenation, nested concatenations are flattened.
+fn concatenated(stmt: &Statement) -> Vec<&Statement> {
+    match stmt.kind() {
+        StatementKind::Operator(TokenCategory::Plus, operands) => {
+            operands.iter().flat_map(concatenated).collect()
+        }
+        _ => vec![stmt],
+    }
+}
+
+/// Returns true when the string literals of a concatenation look like a SQL statement.
+fn is_sql(operands: &[&Statement]) -> bool {
+    let text = operands
+        .iter()
+        .map(|x| match (x.kind(), x.as_token().category()) {
+            (StatementKind::Primitive, TokenCategory::String(x)) => x.to_lowercase(),
+            (StatementKind::Primitive, TokenCategory::Data(x)) => {
+                String::from_utf8_lossy(x).to_lowercase()
+            }
+            _ => " * ".to_string(),
+        })
+        .collect::<String>();
+    SQL.iter()
+        .any(|keywords| keywords.iter().all(|x| text.contains(x)))
+}
+
+/// The tainted variables and functions of a file
+#[derive(Debug)]
+struct Taint<'a> {
+    sanitizers: &'a BTreeSet<String>,
+    /// Variables and the source of their tainted value
+    variables: HashMap<String, String>,
+    /// Functions declared within the file returning tainted data
+    functions: HashMap<String, String>,
+}
+
+impl Taint<'_> {
+    fn is_sanitizer(&self, name: &str) -> bool {
+        SANITIZERS.contains(&name) || self.sanitizers.contains(name)
+    }
+
+    /// Returns the source of the value of an expression when it is tainted.
+    fn source(&self, stmt: &Statement) -> Option<String> {
+        match stmt.kind() {
+            StatementKind::Variable | StatementKind::Array(_) => {
+                self.variables.get(identifier(stmt)?).cloned()
+            }
+            StatementKind::Call(_) => {
+                let name = identifier(stmt)?;
+                if self.is_sanitizer(name) {
+                    None
+                } else if SOURCES.contains(&name) {
+                    Some(name.to_string())
+                } else if let Some(x) = self.functions.get(name) {
+                    Some(x.clone())
+                } else {
+                    // unknown functions pass the taint of their arguments through
+                    stmt.children().iter().find_map(|x| self.source(x))
+                }
+            }
+            StatementKind::NamedParameter(x) | StatementKind::Assign(_, _, _, x) => self.source(x),
+            StatementKind::Operator(TokenCategory::Plus | TokenCategory::Minus, operands) => {
+                operands.iter().find_map(|x| self.source(x))
+            }
+            _ => None,
+        }
+    }
+
+    /// Taints the variable when the value is tainted, returns true when it was not tainted before.
+    fn assign(&mut self, name: Option<&str>, value: &Statement) -> bool {
+        match (name, self.source(value)) {
+            (Some(name), Some(source)) if !self.variables.contains_key(name) => {
+                self.variables.insert(name.to_string(), source);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Finds tainted data reaching sinks
+#[derive(Debug, Clone, Default)]
+pub struct TaintLinter {
+    sanitizers: BTreeSet<String>,
+}
+
+impl TaintLinter {
+    /// Adds a function whose return value is safe to use regardless of its arguments
+    pub fn with_sanitizer(mut self, name: &str) -> Self {
+        self.sanitizers.insert(name.to_string());
+        self
+    }
+
+    /// Analyzes the code and returns the findings without file.
+    pub fn lint_code(&self, code: &str) -> Result<Vec<TaintFinding>, SyntaxError> {
+        let statements = crate::nasl::syntax::parse(code).collect::<Result<Vec<_>, _>>()?;
+        let all = RefCell::new(vec![]);
+        for stmt in &statements {
+            stmt.find(&|s| {
+                all.borrow_mut().push(s);
+                false
+            });
+        }
+        let all = all.into_inner();
+        let declared = all
+            .iter()
+            .filter_map(|x| match x.kind() {
+                StatementKind::FunctionDeclaration(name, _, body) => match name.category() {
+                    TokenCategory::Identifier(IdentifierType::Undefined(name)) => {
+                        Some((name.as_str(), body.as_ref()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut taint = Taint {
+            sanitizers: &self.sanitizers,
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for stmt in &all {
+                match stmt.kind() {
+                    StatementKind::Assign(_, _, variable, value) => {
+                        changed |= taint.assign(identifier(variable), value);
+                    }
+                    StatementKind::ForEach(variable, array, _) => {
+                        let name = match variable.category() {
+                            TokenCategory::Identifier(IdentifierType::Undefined(x)) => Some(x),
+                            _ => None,
+                        };
+                        changed |= taint.assign(name.map(|x| x.as_str()), array);
+                    }
+                    // named parameters of a declared function are tainted by its calls
+                    StatementKind::Call(_)
+                        if declared.contains_key(identifier(stmt).unwrap_or_default()) =>
+                    {
+                        for parameter in stmt.children() {
+                            if matches!(parameter.kind(), StatementKind::NamedParameter(_)) {
+                                changed |= taint.assign(identifier(parameter), parameter);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            for (name, body) in &declared {
+                if taint.functions.contains_key(*name) {
+                    continue;
+                }
+                let source = body
+                    .find(&|s| matches!(s.kind(), StatementKind::Return(_)))
+                    .into_iter()
+                    .find_map(|x| match x.kind() {
+                        StatementKind::Return(x) => taint.source(x),
+                        _ => None,
+                    });
+                if let Some(source) = source {
+                    taint.functions.insert(name.to_string(), source);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut result = vec![];
+        for stmt in &all {
+            let line = stmt.as_token().line_column.0;
+            match stmt.kind() {
+                StatementKind::Call(_) => {
+                    let name = identifier(stmt).unwrap_or_default();
+                    for (_, parameter, kind) in SINKS.iter().filter(|(x, _, _)| *x == name) {
+                        let source = stmt
+                            .children()
+                            .iter()
+                            .filter(|x| match x.kind() {
+                                StatementKind::NamedParameter(_) => {
+                                    identifier(x) == Some(*parameter)
+                                }
+                                _ => parameter.is_empty(),
+                            })
+                            .find_map(|x| taint.source(x));
+                        if let Some(source) = source {
+                            let sink = match parameter.is_empty() {
+                                true => name.to_string(),
+                                false => format!("{name}({parameter})"),
+                            };
+                            result.push(TaintFinding {
+                                file: String::new(),
+                                line,
+                                kind: *kind,
+                                sink,
+                                source,
+                            });
+                        }
+                    }
+                }
+                StatementKind::Operator(TokenCategory::Plus, _) => {
+                    let operands = concatenated(stmt);
+                    let source = operands.iter().find_map(|x| taint.source(x));
+                    if let (true, Some(source)) = (is_sql(&operands), source) {
+                        result.push(TaintFinding {
+                            file: String::new(),
+                            line,
+                            kind: TaintKind::Sql,
+                            sink: "SQL statement".to_string(),
+                            source,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        // nested concatenations of a SQL statement are reported once
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+
+    /// Analyzes the given scripts and inc files.
+    ///
+    /// Files that cannot be loaded or parsed are skipped and listed as unparsable.
+    pub fn lint<L, I>(&self, loader: &L, files: I) -> TaintReport
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut report = TaintReport::default();
+        for key in files {
+            match loader
+                .load(&key)
+                .map_err(|e| e.to_string())
+                .and_then(|x| self.lint_code(&x).map_err(|e| e.to_string()))
+            {
+                Ok(findings) => {
+                    report
+                        .findings
+                        .extend(findings.into_iter().map(|x| TaintFinding {
+                            file: key.clone(),
+                            ..x
+                        }));
+                    report.files += 1;
+                }
+                Err(e) => {
+                    report.unparsable.insert(key, e);
+                }
+            }
+        }
+        report.findings.sort();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn findings(code: &str) -> Vec<(usize, TaintKind, String, String)> {
+        TaintLinter::default()
+            .with_sanitizer("escape_shell")
+            .lint_code(code)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.line, x.kind, x.sink, x.source))
+            .collect()
+    }
+
+    #[test]
+    fn command_injection() {
+        let code = r#"
+        dir = script_get_preference("Directory");
+        args = make_list("ls", "-l", dir);
+        pread(cmd: "ls", argv: args);
+        pread(cmd: "ls", argv: make_list("ls", escape_shell(dir)));
+        pread(cmd: "id", argv: make_list("id"));
+        "#;
+        assert_eq!(
+            findings(code),
+            vec![(
+                4,
+                TaintKind::Command,
+                "pread(argv)".to_string(),
+                "script_get_preference".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn sql_injection() {
+        let code = r#"
+        foreach user (get_kb_list("users/*")) {
+          query = "SELECT name FROM users WHERE id='" + user + "'";
+        }
+        query = "SELECT name FROM users WHERE id=" + int(get_kb_item("id"));
+        message = "Please update " + user;
+        "#;
+        assert_eq!(
+            findings(code),
+            vec![(
+                3,
+                TaintKind::Sql,
+                "SQL statement".to_string(),
+                "get_kb_list".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn taint_through_functions() {
+        let code = r#"
+        function banner(port) {
+          return recv(socket: open_sock_tcp(port), length: 1024);
+        }
+        function run(cmd) {
+          return ssh_request_exec(session, cmd: cmd);
+        }
+        soc = open_sock_tcp(80);
+        send(socket: soc, data: "GET / HTTP/1.0\r\n\r\n");
+        run(cmd: "uname -a; " + banner(port: 80));
+        send(socket: soc, data: strlen(banner(port: 80)));
+        "#;
+        assert_eq!(
+            findings(code),
+            vec![(
+                6,
+                TaintKind::Command,
+                "ssh_request_exec(cmd)".to_string(),
+                "recv".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_per_file() {
+        let loader = |key: &str| match key {
+            "a.nasl" => "send(socket: soc, data: recv(socket: soc, length: 10));".to_string(),
+            "b.nasl" => "if (".to_string(),
+            _ => String::new(),
+        };
+        let report =
+            TaintLinter::default().lint(&loader, ["a.nasl", "b.nasl"].map(|x| x.to_string()));
+        assert_eq!(report.files, 1);
+        assert_eq!(report.failed_files(), 1);
+        assert_eq!(
+            report.findings[0].to_string(),
+            "a.nasl:1: [send] send(data) receives data of recv without sanitization"
+        );
+        assert_eq!(report.unparsable.keys().collect::<Vec<_>>(), vec!["b.nasl"]);
+    }
+}
@@ -0,0 +1,235 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Applies replace commands to all files of a feed on multiple threads.
+
+use std::{
+    fs::{self, File, FileTimes},
+    io::Write,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::feed::NaslFileFinder;
+
+use super::{error::TranspileError, CodeReplacer, ReplaceCommand};
+
+/// Summary of a [BatchReplacer] run
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Amount of handled files
+    pub files: usize,
+    /// The changed files in the order of the feed
+    pub changed: Vec<String>,
+    /// Amount of replaced statements per command, in the order of the commands
+    pub replaced: Vec<usize>,
+    /// Files that could not be handled, they are left unchanged
+    pub failed: Vec<(String, TranspileError)>,
+}
+
+/// Outcome of a single file
+type Outcome = Result<Option<Vec<usize>>, TranspileError>;
+
+/// Applies replace commands to all nasl scripts and inc files of a feed.
+///
+/// Unlike [super::FeedReplacer] the files are handled on a pool of worker threads and the changes
+/// are written. Each changed file is written to a temporary file next to it, which then replaces
+/// the original, so that a file is never left half written. The permissions of a file are kept.
+pub struct BatchReplacer<'a> {
+    root: String,
+    replace: &'a [ReplaceCommand],
+    workers: usize,
+    preserve_times: bool,
+    dry_run: bool,
+}
+
+impl<'a> BatchReplacer<'a> {
+    /// Creates a new BatchReplacer using a worker per available CPU.
+    pub fn new<S>(root: S, replace: &'a [ReplaceCommand]) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            root: root.as_ref().to_string(),
+            replace,
+            workers: std::thread::available_parallelism()
+                .map(|x| x.get())
+                .unwrap_or(1),
+            preserve_times: false,
+            dry_run: false,
+        }
+    }
+
+    /// Sets the amount of worker threads.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Keeps the access and modification times of changed files.
+    pub fn with_preserved_times(mut self, preserve_times: bool) -> Self {
+        self.preserve_times = preserve_times;
+        self
+    }
+
+    /// Only summarizes the changes without writing them.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Writes the content to a temporary file and renames it to the given name.
+    fn write(&self, name: &str, content: &str) -> Result<(), TranspileError> {
+        let path = Path::new(name);
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id()
+        ));
+        let error = |e: std::io::Error| TranspileError::Write(name.to_string(), e.kind());
+        let result = (|| {
+            let metadata = fs::metadata(path)?;
+            let mut file = File::create(&tmp)?;
+            file.write_all(content.as_bytes())?;
+            file.set_permissions(metadata.permissions())?;
+            if self.preserve_times {
+                let times = FileTimes::new()
+                    .set_accessed(metadata.accessed()?)
+                    .set_modified(metadata.modified()?);
+                file.set_times(times)?;
+            }
+            file.sync_all()?;
+            fs::rename(&tmp, path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result.map_err(error)
+    }
+
+    /// Returns the amount of replaced statements per command when the file is changed.
+    fn single(&self, name: &str) -> Outcome {
+        let code = crate::nasl::syntax::load_non_utf8_path(name)?;
+        let (new_code, replaced) = CodeReplacer::replace_counted(&code, self.replace)?;
+        // otherwise  we will transform the whole feed to utf-8
+        if code == new_code {
+            return Ok(None);
+        }
+        if !self.dry_run {
+            self.write(name, &new_code)?;
+        }
+        Ok(Some(replaced))
+    }
+
+    /// Handles all files of the feed and returns the summary.
+    ///
+    /// Returns an error when the files of the feed cannot be listed. Errors of single files are
+    /// collected within the summary.
+    pub fn run(&self) -> Result<BatchSummary, TranspileError> {
+        let names = NaslFileFinder::new(&self.root, false).collect::<Result<Vec<_>, _>>()?;
+        let next = AtomicUsize::new(0);
+        let mut outcomes: Vec<(usize, Outcome)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..self.workers.min(names.len()))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut outcomes = vec![];
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(name) = names.get(index) else {
+                                return outcomes;
+                            };
+                            outcomes.push((index, self.single(name)));
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|x| x.join().expect("transpile worker panicked"))
+                .collect()
+        });
+        outcomes.sort_by_key(|(index, _)| *index);
+        let mut summary = BatchSummary {
+            files: names.len(),
+            replaced: vec![0; self.replace.len()],
+            ..Default::default()
+        };
+        for (index, outcome) in outcomes {
+            let name = &names[index];
+            match outcome {
+                Ok(Some(replaced)) => {
+                    summary.changed.push(name.clone());
+                    for (total, x) in summary.replaced.iter_mut().zip(replaced) {
+                        *total += x;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => summary.failed.push((name.clone(), e)),
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::feed::transpile::{Find, Replace};
+
+    use super::*;
+
+    #[test]
+    fn run() {
+        let dir = std::env::temp_dir().join(format!("feed-batch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let script = dir.join("a.nasl");
+        fs::write(&script, "old(a: 1);\nold(a: 2);\nother();\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o640)).unwrap();
+        fs::write(dir.join("sub/b.inc"), "other();\n").unwrap();
+        fs::write(dir.join("sub/c.inc"), "old();\n").unwrap();
+        let modified = fs::metadata(&script).unwrap().modified().unwrap();
+        let rules = [
+            ReplaceCommand {
+                find: Find::FunctionByName("old".into()),
+                with: Replace::Name("new".into()),
+            },
+            ReplaceCommand {
+                find: Find::FunctionByName("missing".into()),
+                with: Replace::Remove,
+            },
+        ];
+        let root = dir.to_string_lossy();
+        let replacer = BatchReplacer::new(&root, &rules).with_workers(2);
+
+        let summary = replacer.with_dry_run(true).run().unwrap();
+        assert_eq!(summary.changed.len(), 2);
+        assert_eq!(summary.replaced, vec![3, 0]);
+        assert!(fs::read_to_string(&script).unwrap().starts_with("old"));
+
+        let replacer = BatchReplacer::new(&root, &rules).with_preserved_times(true);
+        let summary = replacer.run().unwrap();
+        assert_eq!(summary.files, 3);
+        assert!(summary.failed.is_empty());
+        let mut changed = summary.changed.clone();
+        changed.sort();
+        assert_eq!(
+            changed,
+            vec![
+                script.to_string_lossy().to_string(),
+                dir.join("sub/c.inc").to_string_lossy().to_string()
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&script).unwrap(),
+            "new(a: 1);\nnew(a: 2);\nother();\n"
+        );
+        let metadata = fs::metadata(&script).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(metadata.modified().unwrap(), modified);
+        // no temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
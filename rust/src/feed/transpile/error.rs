@@ -17,12 +17,25 @@ pub enum TranspileError {
     /// Describes an error while verifying the file
     #[error("Replace error: {0}")]
     Replace(#[from] ReplaceError),
+    /// The changed file could not be written
+    #[error("Unable to write {0}: {1}")]
+    Write(String, std::io::ErrorKind),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 /// Error cases on a replace operation
 pub enum ReplaceError {
     /// The replace operation is invalid on statement
-    #[error("Operation {0} not allowed on {1}.")]
-    Unsupported(Replace, Statement),
+    #[error("Operation {} not allowed on {}.", .0.0, .0.1)]
+    Unsupported(Box<(Replace, Statement)>),
+    /// The script lacks the `if (description)` block
+    #[error("No description block found.")]
+    MissingDescription,
+}
+
+impl ReplaceError {
+    /// Creates an error for a replace operation that is invalid on the statement
+    pub fn unsupported(replace: &Replace, statement: &Statement) -> Self {
+        Self::Unsupported(Box::new((replace.clone(), statement.clone())))
+    }
 }
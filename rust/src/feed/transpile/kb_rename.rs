@@ -0,0 +1,251 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Renames KB keys across the feed based on a mapping of old to new keys.
+//!
+//! String literals used as keys by `set_kb_item`, `replace_kb_item`, `get_kb_item`,
+//! `get_kb_list`, `script_require_keys`, `script_mandatory_keys` and `script_exclude_keys` are
+//! rewritten when they equal an old key. Keys that are computed at runtime, e.g.
+//! `"www/" + port + "/banner"`, cannot be rewritten; they are reported when they may match an old
+//! key so that they can be migrated manually.
+
+use std::{cell::RefCell, collections::BTreeMap};
+
+use crate::feed::{
+    analysis::{identifier, string},
+    kb_graph::{key, overlaps, READS, REQUIRES, WRITES},
+    verify, NaslFileFinder,
+};
+use crate::nasl::syntax::{Statement, StatementKind};
+
+use super::error::TranspileError;
+
+/// A KB key computed at runtime that may match a renamed key
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DynamicKbKey {
+    /// The file using the key, empty when the code was renamed directly
+    pub file: String,
+    /// The line of the key, starting at 1
+    pub line: usize,
+    /// The function the key is given to
+    pub function: String,
+    /// The key, runtime computed parts are replaced by `*`
+    pub key: String,
+}
+
+/// Returns the statements used as KB keys by a call.
+fn keys(s: &Statement) -> Vec<&Statement> {
+    let name = identifier(s).unwrap_or_default();
+    let mut positional = s
+        .children()
+        .iter()
+        .filter(|x| !matches!(x.kind(), StatementKind::NamedParameter(_)));
+    if WRITES.contains(&name) {
+        s.children()
+            .iter()
+            .filter_map(|x| match x.kind() {
+                StatementKind::NamedParameter(value) if identifier(x) == Some("name") => {
+                    Some(&**value)
+                }
+                _ => None,
+            })
+            .collect()
+    } else if READS.contains(&name) {
+        positional.next().into_iter().collect()
+    } else if REQUIRES.contains(&name) {
+        positional.collect()
+    } else {
+        vec![]
+    }
+}
+
+/// Renames KB keys within nasl code
+#[derive(Debug, Clone, Default)]
+pub struct KbRenamer {
+    mapping: BTreeMap<String, String>,
+}
+
+impl KbRenamer {
+    /// Creates a new KbRenamer with the given mapping of old to new keys.
+    pub fn new(mapping: BTreeMap<String, String>) -> Self {
+        Self { mapping }
+    }
+
+    /// Returns true when a key computed at runtime may match an old key.
+    fn may_match(&self, pattern: Option<&str>) -> bool {
+        let Some(pattern) = pattern else {
+            return !self.mapping.is_empty();
+        };
+        self.mapping
+            .keys()
+            .any(|x| overlaps(x.as_bytes(), pattern.as_bytes()))
+    }
+
+    /// Renames the keys within the code and returns the result together with the keys that
+    /// cannot be renamed.
+    ///
+    /// Like [super::CodeReplacer::replace] statements that cannot be parsed are left unchanged.
+    pub fn rename(&self, code: &str) -> (String, Vec<DynamicKbKey>) {
+        let found = RefCell::new(vec![]);
+        let mut dynamic = vec![];
+        for stmt in crate::nasl::syntax::parse(code).filter_map(|x| x.ok()) {
+            // the calls are collected while visiting to include nested calls
+            stmt.find(&|s| {
+                if matches!(s.kind(), StatementKind::Call(_)) {
+                    let function = identifier(s).unwrap_or_default();
+                    for key in keys(s) {
+                        found.borrow_mut().push((function.to_string(), key.clone()));
+                    }
+                }
+                false
+            });
+        }
+        let mut replacements = vec![];
+        for (function, s) in found.into_inner() {
+            match string(&s) {
+                Some(old) => {
+                    if let Some(new) = self.mapping.get(&old) {
+                        replacements.push((s.as_token().position, new));
+                    }
+                }
+                None => {
+                    let pattern = key(&s);
+                    if self.may_match(pattern.as_deref()) {
+                        let at = s.start().position.0;
+                        dynamic.push(DynamicKbKey {
+                            file: String::new(),
+                            line: code[..at].matches('\n').count() + 1,
+                            function,
+                            key: pattern.unwrap_or_else(|| "*".to_string()),
+                        });
+                    }
+                }
+            }
+        }
+        replacements.sort_by_key(|((start, _), _)| *start);
+        let mut result = String::with_capacity(code.len());
+        let mut last = 0;
+        for ((start, end), new) in replacements {
+            // the quotes of the literal are kept
+            let quote = &code[start..start + 1];
+            result.push_str(&code[last..start]);
+            result.push_str(quote);
+            result.push_str(new);
+            result.push_str(quote);
+            last = end;
+        }
+        result.push_str(&code[last..]);
+        (result, dynamic)
+    }
+}
+
+/// Finds all nasl and inc files of a feed and renames the KB keys within them
+pub struct FeedKbRenamer {
+    finder: NaslFileFinder,
+    renamer: KbRenamer,
+    dynamic: Vec<DynamicKbKey>,
+}
+
+impl FeedKbRenamer {
+    /// Creates a new FeedKbRenamer
+    pub fn new<S>(root: S, mapping: BTreeMap<String, String>) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let finder = NaslFileFinder::new(&root, false);
+        Self {
+            finder,
+            renamer: KbRenamer::new(mapping),
+            dynamic: vec![],
+        }
+    }
+
+    /// Returns the keys computed at runtime that may match a renamed key found so far.
+    pub fn dynamic(&self) -> &[DynamicKbKey] {
+        &self.dynamic
+    }
+
+    fn rename(
+        &mut self,
+        path: Result<String, verify::Error>,
+    ) -> Result<Option<(String, String)>, TranspileError> {
+        let name = path?;
+        let code = crate::nasl::syntax::load_non_utf8_path(&name)?;
+        let (new_code, dynamic) = self.renamer.rename(&code);
+        self.dynamic
+            .extend(dynamic.into_iter().map(|x| DynamicKbKey {
+                file: name.clone(),
+                ..x
+            }));
+        if code != new_code {
+            Ok(Some((name, new_code)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Iterator for FeedKbRenamer {
+    type Item = Result<Option<(String, String)>, TranspileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.finder.next()?;
+        Some(self.rename(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamer() -> KbRenamer {
+        KbRenamer::new(BTreeMap::from([
+            ("www/banner".to_string(), "www/banner/raw".to_string()),
+            (
+                "Host/runs_windows".to_string(),
+                "Host/OS/windows".to_string(),
+            ),
+            ("smb/80/login".to_string(), "smb/login".to_string()),
+        ]))
+    }
+
+    #[test]
+    fn rename() {
+        let code = r#"
+script_mandatory_keys("Host/runs_windows", 'www/banner', "unrelated");
+set_kb_item(name: "www/banner", value: get_kb_item("Host/runs_windows"));
+replace_kb_item(name: 'Host/runs_windows', value: 1);
+display("www/banner");
+"#;
+        let expected = r#"
+script_mandatory_keys("Host/OS/windows", 'www/banner/raw', "unrelated");
+set_kb_item(name: "www/banner/raw", value: get_kb_item("Host/OS/windows"));
+replace_kb_item(name: 'Host/OS/windows', value: 1);
+display("www/banner");
+"#;
+        let (result, dynamic) = renamer().rename(code);
+        assert_eq!(result, expected);
+        assert!(dynamic.is_empty());
+    }
+
+    #[test]
+    fn dynamic() {
+        let code = r#"
+a = get_kb_item("www/" + port + "/banner");
+b = get_kb_list("smb/" + port + "/login");
+set_kb_item(name: key, value: 1);
+c = get_kb_item("ssh/" + port + "/login");
+"#;
+        let (result, dynamic) = renamer().rename(code);
+        assert_eq!(result, code);
+        let found: Vec<_> = dynamic
+            .iter()
+            .map(|x| (x.line, x.function.as_str(), x.key.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(3, "get_kb_list", "smb/*/login"), (4, "set_kb_item", "*"),]
+        );
+    }
+}
@@ -4,13 +4,17 @@
 
 //! Replaces the function calls within a feed.
 
+mod batch;
 mod error;
+mod kb_rename;
 
-use crate::nasl::syntax::{Statement, StatementKind};
+use crate::nasl::syntax::{AssignOrder, Statement, StatementKind, TokenCategory};
 
 use crate::feed::{verify, NaslFileFinder};
 
+pub use self::batch::{BatchReplacer, BatchSummary};
 use self::error::{ReplaceError, TranspileError};
+pub use self::kb_rename::{DynamicKbKey, FeedKbRenamer, KbRenamer};
 
 /// Is used to find parameter by either name or index within a ReplaceCommand
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -35,6 +39,22 @@ pub enum Find {
     FunctionByParameter(Vec<FindParameter>),
     /// Finds a function by name and parameter.
     FunctionByNameAndParameter(String, Vec<FindParameter>),
+    /// Finds assignments to a variable by name.
+    ///
+    /// Only assignments terminated by a semicolon, e.g. `port = 139;`, are found and not
+    /// assignments within an expression or increments like `port++;`.
+    AssignmentByName(String),
+    /// Finds assignments to a variable by name and the assigned value, e.g. `port` and `139`.
+    AssignmentByNameAndValue(String, String),
+    /// Finds binary operations by operator and optionally by their operands.
+    Operation {
+        /// The operator, e.g. `==`
+        operator: String,
+        /// The left operand, e.g. `port`
+        left: Option<String>,
+        /// The right operand, e.g. `139`
+        right: Option<String>,
+    },
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -118,6 +138,10 @@ pub enum Replace {
     Remove,
     /// Replace parameter
     Parameter(ParameterOperation),
+    /// Replaces the assigned value of an assignment or the right operand of an operation
+    Value(String),
+    /// Replaces the operator of an assignment or an operation
+    Operator(String),
 }
 
 impl std::fmt::Display for Replace {
@@ -128,6 +152,8 @@ impl std::fmt::Display for Replace {
                 write!(f, "Replace parameter: {}", p)
             }
             Replace::Remove => write!(f, "Remove found statement"),
+            Replace::Value(value) => write!(f, "Replace value: {value}"),
+            Replace::Operator(operator) => write!(f, "Replace operator: {operator}"),
         }
     }
 }
@@ -283,6 +309,66 @@ impl<'a> Matcher for FunctionNameMatcher<'a> {
     }
 }
 
+/// Returns the name of a variable or an array.
+fn variable_name(s: &Statement) -> Option<&str> {
+    match (s.kind(), s.start().category()) {
+        (
+            StatementKind::Variable | StatementKind::Array(_),
+            TokenCategory::Identifier(crate::nasl::syntax::IdentifierType::Undefined(x)),
+        ) => Some(x),
+        _ => None,
+    }
+}
+
+/// Returns the variable and the assigned value of an assignment that is a statement of its own.
+fn assignment(s: &Statement) -> Option<(&TokenCategory, &Statement, &Statement)> {
+    match s.kind() {
+        StatementKind::Assign(operator, AssignOrder::AssignReturn, variable, value)
+            if !matches!(value.kind(), StatementKind::NoOp)
+                && s.end().category() == &TokenCategory::Semicolon =>
+        {
+            Some((operator, variable, value))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct AssignmentMatcher<'a> {
+    name: &'a str,
+    value: Option<&'a str>,
+}
+
+impl Matcher for AssignmentMatcher<'_> {
+    fn matches(&self, s: &Statement) -> bool {
+        let Some((_, variable, value)) = assignment(s) else {
+            return false;
+        };
+        variable_name(variable) == Some(self.name)
+            && self.value.is_none_or(|x| value.to_string() == x)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct OperationMatcher<'a> {
+    operator: &'a str,
+    left: Option<&'a str>,
+    right: Option<&'a str>,
+}
+
+impl Matcher for OperationMatcher<'_> {
+    fn matches(&self, s: &Statement) -> bool {
+        match s.kind() {
+            StatementKind::Operator(operator, operands) if operands.len() == 2 => {
+                operator.to_string() == self.operator
+                    && self.left.is_none_or(|x| operands[0].to_string() == x)
+                    && self.right.is_none_or(|x| operands[1].to_string() == x)
+            }
+            _ => false,
+        }
+    }
+}
+
 impl Find {
     /// Checks if statement matches the wanted search operation
     pub fn matches(&self, s: &Statement) -> bool {
@@ -290,6 +376,28 @@ impl Find {
             Find::FunctionByName(name) => (Some(name as &str), None),
             Find::FunctionByParameter(x) => (None, Some(x as &[_])),
             Find::FunctionByNameAndParameter(x, y) => (Some(x as &str), Some(y as &[_])),
+            Find::AssignmentByName(name) => {
+                return AssignmentMatcher { name, value: None }.matches(s)
+            }
+            Find::AssignmentByNameAndValue(name, value) => {
+                return AssignmentMatcher {
+                    name,
+                    value: Some(value),
+                }
+                .matches(s)
+            }
+            Find::Operation {
+                operator,
+                left,
+                right,
+            } => {
+                return OperationMatcher {
+                    operator,
+                    left: left.as_deref(),
+                    right: right.as_deref(),
+                }
+                .matches(s)
+            }
         };
 
         FunctionNameMatcher { name, parameter }.matches(s)
@@ -309,6 +417,8 @@ pub struct ReplaceCommand {
 pub struct CodeReplacer {
     // since the first position we need to add offset
     offsets: Vec<(usize, i64)>,
+    // the code the statements were parsed from
+    source: String,
     code: String,
     changed: bool,
 }
@@ -350,6 +460,52 @@ impl CodeReplacer {
         None
     }
 
+    /// Returns the range of the last occurrence of the operator within the source range.
+    fn find_operator(&self, operator: &str, from: usize, to: usize) -> Option<(usize, usize)> {
+        let at = self.source.get(from..to)?.rfind(operator)? + from;
+        Some((at, at + operator.len()))
+    }
+
+    /// Returns the range of the operator of an assignment or a binary operation.
+    fn operator_range(&self, s: &Statement) -> Option<(usize, usize)> {
+        if let Some((operator, variable, value)) = assignment(s) {
+            return self.find_operator(
+                &operator.to_string(),
+                variable.start().position.1,
+                value.start().position.0,
+            );
+        }
+        match s.kind() {
+            StatementKind::Operator(operator, operands) if operands.len() == 2 => self
+                .find_operator(
+                    &operator.to_string(),
+                    operands[0].end().position.1,
+                    operands[1].start().position.0,
+                ),
+            _ => None,
+        }
+    }
+
+    /// Returns the range of the assigned value of an assignment or of the right operand of a
+    /// binary operation, when the operand is a single variable or primitive.
+    fn value_range(&self, s: &Statement) -> Option<(usize, usize)> {
+        let (_, operator_end) = self.operator_range(s)?;
+        if assignment(s).is_some() {
+            // the value ends before the semicolon of the assignment
+            let end = s.end().position.0;
+            let value = self.source.get(operator_end..end)?;
+            let start = end - value.trim_start().len();
+            let end = start + value.trim().len();
+            return Some((start, end));
+        }
+        match s.children().get(1).map(|x| x.kind()) {
+            Some(StatementKind::Primitive | StatementKind::Variable) => {
+                Some(s.children()[1].start().position)
+            }
+            _ => None,
+        }
+    }
+
     fn replace_range_with_offset(&mut self, new: &str, position: &(usize, usize)) {
         let new_pos = self.range_with_offset(position);
         self.replace_range(&new_pos, new, position)
@@ -391,15 +547,33 @@ impl CodeReplacer {
                     self.replace_range_with_offset(name, &s.start().position);
                     Ok(())
                 }
-                _ => Err(ReplaceError::Unsupported(r.clone(), s.clone())),
+                StatementKind::Assign(_, _, variable, _) if variable_name(variable).is_some() => {
+                    self.replace_range_with_offset(name, &variable.start().position);
+                    Ok(())
+                }
+                _ => Err(ReplaceError::unsupported(r, s)),
             },
+            Replace::Value(new) => {
+                let range = self
+                    .value_range(s)
+                    .ok_or_else(|| ReplaceError::unsupported(r, s))?;
+                self.replace_range_with_offset(new, &range);
+                Ok(())
+            }
+            Replace::Operator(new) => {
+                let range = self
+                    .operator_range(s)
+                    .ok_or_else(|| ReplaceError::unsupported(r, s))?;
+                self.replace_range_with_offset(new, &range);
+                Ok(())
+            }
             Replace::Parameter(params) => {
                 let parameter = match s.kind() {
                     StatementKind::FunctionDeclaration(_, stmt, ..)
                     | StatementKind::Call(stmt)
                     | StatementKind::Exit(stmt, ..)
                     | StatementKind::Include(stmt, ..) => stmt,
-                    _ => return Err(ReplaceError::Unsupported(r.clone(), s.clone())),
+                    _ => return Err(ReplaceError::unsupported(r, s)),
                 };
 
                 match params {
@@ -433,13 +607,24 @@ impl CodeReplacer {
     /// Spawns a Replacer that contains a copy of the source code and manipulates it iteratively
     /// based on the order of the given commands.
     pub fn replace(code: &str, replace: &[ReplaceCommand]) -> Result<String, ReplaceError> {
+        Self::replace_counted(code, replace).map(|(code, _)| code)
+    }
+
+    /// Replaces findings like [CodeReplacer::replace] and returns the amount of replaced
+    /// statements per command as well.
+    pub fn replace_counted(
+        code: &str,
+        replace: &[ReplaceCommand],
+    ) -> Result<(String, Vec<usize>), ReplaceError> {
         let mut code = code.to_string();
+        let mut counts = Vec::with_capacity(replace.len());
         let mut cached_stmts = Vec::new();
         // We need to be aware of parameter changes otherwise it can bug out
         // with the ordering of new parameter.
         for r in replace {
             let mut replacer = CodeReplacer {
                 offsets: Vec::with_capacity(replace.len()),
+                source: code.clone(),
                 code: code.clone(),
                 changed: false,
             };
@@ -449,19 +634,22 @@ impl CodeReplacer {
                     .collect();
             }
 
+            let mut count = 0;
             for s in cached_stmts.iter() {
                 let results = s.find(&|s| r.find.matches(s));
                 for s in results {
                     replacer.replace_as_string(s, &r.with)?;
+                    count += 1;
                 }
             }
+            counts.push(count);
             if replacer.changed {
                 cached_stmts.clear();
                 code = replacer.code;
             }
         }
 
-        Ok(code)
+        Ok((code, counts))
     }
 
     fn push_parameter(&mut self, s: &Statement, p: &Parameter) {
@@ -679,6 +867,147 @@ impl<'a> Iterator for FeedReplacer<'a> {
     }
 }
 
+/// The tag marking a script as deprecated
+const DEPRECATED_TAG: &str = "script_tag(name:\"deprecated\", value:TRUE);";
+
+fn is_description(s: &Statement) -> bool {
+    use crate::nasl::syntax::{IdentifierType::Undefined, TokenCategory::Identifier};
+    match s.kind() {
+        StatementKind::If(condition, ..) => {
+            matches!(condition.kind(), StatementKind::Variable)
+                && matches!(condition.start().category(), Identifier(Undefined(x)) if x == "description")
+        }
+        _ => false,
+    }
+}
+
+/// Returns the description block of the code.
+fn description(code: &str) -> Option<Statement> {
+    crate::nasl::syntax::parse(code)
+        .map_while(|x| x.ok())
+        .find(is_description)
+}
+
+/// Returns the oid set within the description block of the code.
+pub fn script_oid(code: &str) -> Option<String> {
+    let description = description(code)?;
+    let find = Find::FunctionByName("script_oid".to_string());
+    let calls = description.find(&|s| find.matches(s));
+    let oid = calls.first()?.children().first()?.to_string();
+    Some(oid.trim_matches('"').to_string())
+}
+
+/// Rewrites a script to the deprecated stub.
+///
+/// The description block is kept and gets the deprecated tag before its final `exit`. The code
+/// after the description block is replaced by `exit(66);`, so the script ends right after its
+/// description run.
+pub fn deprecate(code: &str) -> Result<String, ReplaceError> {
+    let description = description(code).ok_or(ReplaceError::MissingDescription)?;
+    let block = match description.kind() {
+        StatementKind::If(_, block, ..) => block,
+        _ => unreachable!("is_description only matches if statements"),
+    };
+    let tag = Find::FunctionByNameAndParameter(
+        "script_tag".to_string(),
+        vec![
+            FindParameter::NameValue("name".to_string(), "\"deprecated\"".to_string()),
+            FindParameter::Name("value".to_string()),
+        ],
+    );
+    let (_, end) = description.position();
+    let mut result = String::with_capacity(code.len());
+    if block.find(&|s| tag.matches(s)).is_empty() {
+        let exit = block
+            .children()
+            .iter()
+            .rev()
+            .find(|s| matches!(s.kind(), StatementKind::Exit(..)));
+        match exit {
+            Some(exit) => {
+                let at = exit.position().0;
+                let line = code[..at].rfind('\n').map_or(0, |x| x + 1);
+                let indent = code[line..at].trim_end_matches(|x: char| !x.is_whitespace());
+                result.push_str(&code[..at]);
+                result.push_str(DEPRECATED_TAG);
+                result.push_str("\n\n");
+                result.push_str(indent);
+                result.push_str(&code[at..end]);
+            }
+            None => {
+                let at = block.end().position.0;
+                result.push_str(&code[..at]);
+                result.push_str("  ");
+                result.push_str(DEPRECATED_TAG);
+                result.push('\n');
+                result.push_str(&code[at..end]);
+            }
+        }
+    } else {
+        result.push_str(&code[..end]);
+    }
+    result.push_str("\n\nexit(66);\n");
+    Ok(result)
+}
+
+/// Finds the nasl scripts of the given oids within a feed and rewrites them to the deprecated
+/// stub
+pub struct FeedDeprecator {
+    finder: NaslFileFinder,
+    oids: Vec<String>,
+}
+
+impl FeedDeprecator {
+    /// Creates a new FeedDeprecator
+    pub fn new<S>(root: S, oids: Vec<String>) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let finder = NaslFileFinder::new(&root, false);
+        Self { finder, oids }
+    }
+
+    /// Returns the oids for which no script was found so far.
+    pub fn missing(&self) -> &[String] {
+        &self.oids
+    }
+
+    fn deprecate(
+        &mut self,
+        path: Result<String, verify::Error>,
+    ) -> Result<Option<(String, String)>, TranspileError> {
+        let name = path?;
+        if !name.ends_with(".nasl") {
+            return Ok(None);
+        }
+        let code = crate::nasl::syntax::load_non_utf8_path(&name)?;
+        let Some(index) =
+            script_oid(&code).and_then(|oid| self.oids.iter().position(|x| x == &oid))
+        else {
+            return Ok(None);
+        };
+        self.oids.swap_remove(index);
+        let new_code = deprecate(&code)?;
+        if code != new_code {
+            Ok(Some((name, new_code)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Iterator for FeedDeprecator {
+    type Item = Result<Option<(String, String)>, TranspileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.oids.is_empty() {
+            return None;
+        }
+        let path = self.finder.next()?;
+        Some(self.deprecate(path))
+    }
+}
+
 #[cfg(test)]
 mod parsing {
     use crate::feed::transpile::{Find, FindParameter, Parameter, ParameterOperation, Replace};
@@ -739,6 +1068,18 @@ mod parsing {
                 find: Find::FunctionByName("script_xref".into()),
                 with: Replace::Remove,
             },
+            ReplaceCommand {
+                find: Find::AssignmentByNameAndValue("port".into(), "139".into()),
+                with: Replace::Value("445".into()),
+            },
+            ReplaceCommand {
+                find: Find::Operation {
+                    operator: "==".into(),
+                    left: Some("port".into()),
+                    right: None,
+                },
+                with: Replace::Operator("!=".into()),
+            },
         ]
     }
     #[test]
@@ -1176,3 +1517,172 @@ if (user_ports = get_kb_list("sophos/xg_firewall/http-user/port")) {
         );
     }
 }
+
+#[cfg(test)]
+mod deprecation {
+    use super::*;
+
+    #[test]
+    fn oid() {
+        let code = r#"
+if (description) {
+  script_oid("1.3.6.1.4.1.25623.1.0.100000");
+  exit(0);
+}
+"#;
+        assert_eq!(
+            script_oid(code),
+            Some("1.3.6.1.4.1.25623.1.0.100000".to_string())
+        );
+        assert_eq!(script_oid("script_oid(\"1\");"), None);
+    }
+
+    #[test]
+    fn stub() {
+        let code = r#"if (description) {
+  script_oid("1");
+  exit(0);
+}
+
+include("http_func.inc");
+port = get_http_port(default:80);
+security_message(port:port);
+"#;
+        let expected = r#"if (description) {
+  script_oid("1");
+  script_tag(name:"deprecated", value:TRUE);
+
+  exit(0);
+}
+
+exit(66);
+"#;
+        let result = deprecate(code).unwrap();
+        assert_eq!(result, expected);
+        assert_eq!(deprecate(&result).unwrap(), expected);
+    }
+
+    #[test]
+    fn stub_without_exit() {
+        let code = r#"if (description) {
+  script_oid("1");
+}
+display("hi");
+"#;
+        let expected = r#"if (description) {
+  script_oid("1");
+  script_tag(name:"deprecated", value:TRUE);
+}
+
+exit(66);
+"#;
+        assert_eq!(deprecate(code).unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_description() {
+        assert!(matches!(
+            deprecate("display(1);"),
+            Err(ReplaceError::MissingDescription)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod expressions {
+    use super::*;
+
+    fn check(find: Find, with: Replace, code: &str, expected: &str) {
+        let replaces = [ReplaceCommand { find, with }];
+        let result = CodeReplacer::replace(code, &replaces).unwrap();
+        assert_eq!(&result, expected);
+    }
+
+    #[test]
+    fn assignment_value() {
+        check(
+            Find::AssignmentByName("port".into()),
+            Replace::Value("445".into()),
+            "port = 139;\nif (port == 139) exit(0);",
+            "port = 445;\nif (port == 139) exit(0);",
+        );
+        check(
+            Find::AssignmentByName("port".into()),
+            Replace::Value("get_kb_item(\"Services/smb\")".into()),
+            "port = (139 + 1);",
+            "port = get_kb_item(\"Services/smb\");",
+        );
+        // only assignments of the value are changed
+        check(
+            Find::AssignmentByNameAndValue("port".into(), "139".into()),
+            Replace::Value("445".into()),
+            "port = 139;\nport = 80;\nports[0] = 139;",
+            "port = 445;\nport = 80;\nports[0] = 139;",
+        );
+        // increments and assignments within an expression are not changed
+        check(
+            Find::AssignmentByName("port".into()),
+            Replace::Value("445".into()),
+            "port++;\nif (port = 1) exit(0);\nfor (port = 1; port < 3; port++) {}",
+            "port++;\nif (port = 1) exit(0);\nfor (port = 445; port < 3; port++) {}",
+        );
+    }
+
+    #[test]
+    fn assignment_name_and_operator() {
+        check(
+            Find::AssignmentByName("vers".into()),
+            Replace::Name("version".into()),
+            "vers = \"1.0\";\nvers += \".1\";",
+            "version = \"1.0\";\nversion += \".1\";",
+        );
+        check(
+            Find::AssignmentByNameAndValue("vers".into(), "\".1\"".into()),
+            Replace::Operator("=".into()),
+            "vers = \"1.0\";\nvers += \".1\";",
+            "vers = \"1.0\";\nvers = \".1\";",
+        );
+        check(
+            Find::AssignmentByNameAndValue("port".into(), "139".into()),
+            Replace::Remove,
+            "port = 139;\nport = 80;",
+            "\nport = 80;",
+        );
+    }
+
+    #[test]
+    fn operation() {
+        let find = |left: Option<&str>, right: Option<&str>| Find::Operation {
+            operator: "==".into(),
+            left: left.map(|x| x.into()),
+            right: right.map(|x| x.into()),
+        };
+        check(
+            find(Some("port"), None),
+            Replace::Operator("!=".into()),
+            "if (port == 139 || port==445) exit(0);",
+            "if (port != 139 || port!=445) exit(0);",
+        );
+        check(
+            find(Some("port"), Some("139")),
+            Replace::Value("445".into()),
+            "if (port == 139 || port == 80) exit(0);",
+            "if (port == 445 || port == 80) exit(0);",
+        );
+        check(
+            find(None, Some("\"www\"")),
+            Replace::Value("\"http\"".into()),
+            "a = service == \"www\";",
+            "a = service == \"http\";",
+        );
+        // a composed operand cannot be replaced
+        let replaces = [ReplaceCommand {
+            find: find(None, None),
+            with: Replace::Value("1".into()),
+        }];
+        assert!(matches!(
+            CodeReplacer::replace("a = b == (c + 1);", &replaces),
+            Err(ReplaceError::Unsupported(..))
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Checks modified scripts stacked on top of a feed before they are merged.
+//!
+//! The modified files are given as the overlays of a [FSPluginLoader]. Only the changed files
+//! and the scripts including a changed file, directly or transitively, are checked. Each of them
+//! is parsed completely and the `.nasl` scripts are run in description mode. The files are not
+//! verified, as modified files are usually not part of a sums file yet.
+
+use std::{collections::BTreeSet, time::Instant};
+
+use crate::nasl::syntax::Loader;
+use crate::nasl::FSPluginLoader;
+use crate::storage::DefaultDispatcher;
+
+use crate::feed::{
+    update::{Error, ErrorKind, FileReport, Report, Update},
+    verify::{self, HashSumFileItem, NaslFileFinder},
+    IncludeGraph,
+};
+
+/// Checks the files of the overlays of a feed and the scripts depending on them
+pub struct Trial<'a> {
+    loader: &'a FSPluginLoader,
+}
+
+/// Returns the relative names of the `.nasl` and `.inc` files within the directory.
+fn files(dir: &std::path::Path) -> Result<Vec<String>, verify::Error> {
+    NaslFileFinder::new(dir.to_string_lossy(), true).collect()
+}
+
+impl<'a> Trial<'a> {
+    /// Creates a Trial of the overlays of the given loader.
+    pub fn new(loader: &'a FSPluginLoader) -> Self {
+        Self { loader }
+    }
+
+    /// Returns the files within the overlays.
+    pub fn changed(&self) -> Result<BTreeSet<String>, verify::Error> {
+        let mut results = BTreeSet::new();
+        for overlay in self.loader.overlays() {
+            results.extend(files(overlay)?);
+        }
+        Ok(results)
+    }
+
+    /// Returns the changed files and the scripts of the feed including a changed file.
+    ///
+    /// The include graph of the whole feed is only built when an inc file got changed. Scripts
+    /// whose includes cannot be resolved are skipped.
+    pub fn affected(&self, changed: &BTreeSet<String>) -> Result<BTreeSet<String>, verify::Error> {
+        let mut results = changed.clone();
+        if changed.iter().all(|x| x.ends_with(".nasl")) {
+            return Ok(results);
+        }
+        let mut scripts = files(self.loader.root())?;
+        scripts.extend(changed.iter().cloned());
+        scripts.retain(|x| x.ends_with(".nasl"));
+        let mut graph = IncludeGraph::new();
+        for script in &scripts {
+            if let Err(e) = graph.insert(self.loader, script) {
+                tracing::debug!(script, %e, "unable to resolve includes");
+            }
+        }
+        results.extend(
+            scripts
+                .into_iter()
+                .filter(|x| graph.resolve(x).iter().any(|x| changed.contains(x))),
+        );
+        Ok(results)
+    }
+
+    /// Parses each affected file and runs the affected scripts in description mode.
+    pub async fn run(&self) -> Result<Report, Error> {
+        let changed = self.changed()?;
+        let affected = self.affected(&changed)?;
+        tracing::debug!(
+            changed = changed.len(),
+            affected = affected.len(),
+            "checking files"
+        );
+        let storage = DefaultDispatcher::new();
+        let update = Update::init(
+            "1",
+            5,
+            self.loader,
+            &storage,
+            std::iter::empty::<Result<HashSumFileItem, verify::Error>>(),
+        );
+        let mut report = Report::default();
+        for key in affected {
+            let start = Instant::now();
+            let result = self
+                .check(&update, &key)
+                .await
+                .map(|_| key.clone())
+                .map_err(|kind| Error { key, kind });
+            report.push(FileReport::new(&result, start.elapsed()));
+        }
+        Ok(report)
+    }
+
+    async fn check<'b, V>(
+        &self,
+        update: &Update<'b, DefaultDispatcher, FSPluginLoader, V>,
+        key: &str,
+    ) -> Result<(), ErrorKind>
+    where
+        V: Iterator<Item = Result<HashSumFileItem<'b>, verify::Error>> + 'b,
+    {
+        let code = self.loader.load(key)?;
+        if let Some(e) = crate::nasl::syntax::parse(&code).find_map(|x| x.err()) {
+            return Err(e.into());
+        }
+        if key.ends_with(".nasl") {
+            update.describe(key).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::feed::FileOutcome;
+
+    #[tokio::test]
+    async fn affected_scripts() {
+        let dir = std::env::temp_dir().join(format!("feed-trial-{}", std::process::id()));
+        let feed = dir.join("feed");
+        let changes = dir.join("changes");
+        fs::create_dir_all(&feed).unwrap();
+        fs::create_dir_all(&changes).unwrap();
+        let script = |include: &str| {
+            format!("if (description) {{ script_oid(\"1\"); exit(0); }}\n{include}\nexit(0);\n")
+        };
+        fs::write(feed.join("a.inc"), "a = 1;").unwrap();
+        fs::write(feed.join("b.inc"), "include(\"a.inc\");").unwrap();
+        fs::write(feed.join("uses_a.nasl"), script("include(\"b.inc\");")).unwrap();
+        fs::write(feed.join("other.nasl"), script("")).unwrap();
+        fs::write(feed.join("new.nasl"), "exit(0);").unwrap();
+        fs::write(changes.join("a.inc"), "if (a {").unwrap();
+        fs::write(changes.join("new.nasl"), "if (description) { }").unwrap();
+
+        let loader = FSPluginLoader::new(&feed).with_overlays([&changes]);
+        let trial = Trial::new(&loader);
+        let changed = trial.changed().unwrap();
+        assert_eq!(
+            changed.iter().collect::<Vec<_>>(),
+            vec!["a.inc", "new.nasl"]
+        );
+        assert_eq!(
+            trial.affected(&changed).unwrap().iter().collect::<Vec<_>>(),
+            vec!["a.inc", "new.nasl", "uses_a.nasl"]
+        );
+        let report = trial.run().await.unwrap();
+        let outcomes: Vec<_> = report
+            .files
+            .iter()
+            .map(|x| (x.key.as_str(), x.outcome.is_ok()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![("a.inc", false), ("new.nasl", false), ("uses_a.nasl", true)]
+        );
+        assert!(matches!(report.files[1].outcome, FileOutcome::MissingExit));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
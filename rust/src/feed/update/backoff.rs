@@ -0,0 +1,63 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Delays between the attempts of a retried operation.
+
+use std::time::Duration;
+
+/// Exponential backoff between retries
+///
+/// The first retry waits `initial`, each further retry waits twice as long as the previous one
+/// up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Delay before the first retry
+    pub initial: Duration,
+    /// Upper bound of the delay
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns a backoff that does not wait between retries.
+    pub fn none() -> Self {
+        Self {
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Returns the delay before the given retry, starting with 0 for the first retry.
+    pub fn delay(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn delay() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+        };
+        let delays: Vec<_> = (0..6).map(|x| backoff.delay(x).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+        assert_eq!(Backoff::none().delay(3), Duration::ZERO);
+    }
+}
@@ -2,9 +2,9 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use crate::nasl::interpreter::InterpretError;
+use crate::nasl::interpreter::{InterpretError, InterpretErrorKind};
 use crate::nasl::syntax::{LoadError, SyntaxError};
-use crate::storage::StorageError;
+use crate::storage::{item::NvtError, StorageError};
 use thiserror::Error;
 
 use crate::feed::{verify, VerifyError};
@@ -30,6 +30,30 @@ pub enum ErrorKind {
     /// Describes an error while verifying the file
     #[error("Verify error: {0}")]
     VerifyError(#[from] verify::Error),
+    /// The description of the script is invalid
+    #[error("Invalid NVT: {0}")]
+    InvalidNvt(#[from] NvtError),
+}
+
+impl ErrorKind {
+    /// Returns true when the operation may succeed when it is retried.
+    ///
+    /// This is the case for retryable load and storage errors, also when they occur while
+    /// interpreting a script.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::LoadError(LoadError::Retry(_)) | Self::StorageError(StorageError::Retry(_)) => {
+                true
+            }
+            Self::InterpretError(e) => matches!(
+                e.kind,
+                InterpretErrorKind::LoadError(LoadError::Retry(_))
+                    | InterpretErrorKind::StorageError(StorageError::Retry(_))
+                    | InterpretErrorKind::IOError(std::io::ErrorKind::Interrupted)
+            ),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
@@ -55,6 +79,7 @@ impl From<verify::Error> for Error {
             } => key,
             VerifyError::BadSignature(e) => e,
             VerifyError::MissingKeyring => "",
+            VerifyError::MissingSignature(key) => key,
         };
         Self {
             key: key.to_string(),
@@ -2,19 +2,24 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+mod backoff;
 mod error;
+mod report;
+mod write_back;
 
+pub use backoff::Backoff;
 pub use error::Error;
 pub use error::ErrorKind;
+pub use report::{FileReport, Outcome, Report};
+pub use write_back::WriteBack;
 
-use futures::{stream, Stream, StreamExt};
-use std::fs::File;
+use futures::{stream, Future, Stream, StreamExt};
+use std::time::Instant;
 use tracing::trace;
 
 use crate::nasl::interpreter::{CodeInterpreter, Interpreter};
 use crate::nasl::nasl_std_functions;
 use crate::nasl::prelude::*;
-use crate::nasl::syntax::AsBufReader;
 use crate::nasl::ContextType;
 use crate::storage::{item::NVTField, ContextKey, Dispatcher, NoOpRetriever};
 
@@ -34,6 +39,8 @@ pub struct Update<'a, S, L, V> {
     initial: Vec<(String, ContextType)>,
     /// How often loader or storage should retry before giving up when a retryable error occurs.
     max_retry: usize,
+    /// Delay between the retries
+    backoff: Backoff,
     verifier: V,
     feed_version_set: bool,
 }
@@ -69,7 +76,7 @@ pub async fn feed_version(
 impl<'a, S, L, V> SignatureChecker for Update<'a, S, L, V>
 where
     S: Sync + Send + Dispatcher,
-    L: Sync + Send + Loader + AsBufReader<File>,
+    L: Sync + Send + Loader,
     V: Iterator<Item = Result<HashSumFileItem<'a>, verify::Error>>,
 {
 }
@@ -77,7 +84,7 @@ where
 impl<'a, S, L, V> Update<'a, S, L, V>
 where
     S: Sync + Send + Dispatcher,
-    L: Sync + Send + Loader + AsBufReader<File>,
+    L: Sync + Send + Loader,
     V: Iterator<Item = Result<HashSumFileItem<'a>, verify::Error>> + 'a,
 {
     /// Creates an updater. This updater is implemented as a iterator.
@@ -101,6 +108,7 @@ where
         Self {
             initial,
             max_retry,
+            backoff: Backoff::default(),
             loader,
             dispatcher: storage,
             verifier,
@@ -108,6 +116,32 @@ where
         }
     }
 
+    /// Sets the delay between retries of a file that failed with a retryable error.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Runs the operation again when it fails with a retryable error until max_retry is reached.
+    async fn retry<T, F, Fut>(&self, key: &str, mut operation: F) -> Result<T, ErrorKind>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ErrorKind>>,
+    {
+        let mut retry = 0;
+        loop {
+            match operation().await {
+                Err(e) if e.is_retryable() && retry < self.max_retry => {
+                    let delay = self.backoff.delay(retry);
+                    tracing::debug!(key, retry, ?delay, %e, "retrying");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Loads the plugin_feed_info and returns the feed version
     pub async fn feed_version(&self) -> Result<String, ErrorKind> {
         feed_version(self.loader, self.dispatcher).await
@@ -131,19 +165,23 @@ where
     /// Therefore we need to load the plugin_feed_info and extract the feed_version
     /// to put into the corresponding dispatcher.
     async fn dispatch_feed_info(&self) -> Result<String, ErrorKind> {
-        let feed_version = self.feed_version().await?;
-        self.dispatcher.retry_dispatch(
-            self.max_retry,
-            &Default::default(),
-            NVTField::Version(feed_version).into(),
-        )?;
         let feed_info_key = "plugin_feed_info.inc";
+        self.retry(feed_info_key, || async {
+            let feed_version = self.feed_version().await?;
+            self.dispatcher
+                .dispatch(&Default::default(), NVTField::Version(feed_version).into())?;
+            Ok(())
+        })
+        .await?;
         Ok(feed_info_key.into())
     }
 
     /// Runs a single plugin in description mode.
+    ///
+    /// The NVT is validated and stored at once when the plugin exits.
     async fn single(&self, key: &ContextKey) -> Result<i64, ErrorKind> {
         let code = self.loader.load(&key.value())?;
+        let dispatcher = WriteBack::new(self.dispatcher);
 
         let register = Register::root_initial(&self.initial);
         let fr = NoOpRetriever::default();
@@ -153,16 +191,21 @@ where
         let context = Context::new(
             key.clone(),
             target,
-            self.dispatcher,
+            &dispatcher,
             &fr,
             self.loader,
             &functions,
         );
-        let mut results = Box::pin(CodeInterpreter::new(&code, register, &context).stream());
+        let mut results = Box::pin(
+            CodeInterpreter::new(&code, register, &context)
+                .optimized()
+                .stream(),
+        );
         while let Some(stmt) = results.next().await {
             match stmt {
                 Ok(NaslValue::Exit(i)) => {
-                    self.dispatcher.on_exit(context.key())?;
+                    dispatcher.validate()?;
+                    dispatcher.on_exit(context.key())?;
                     return Ok(i);
                 }
                 Ok(_) => {}
@@ -172,9 +215,17 @@ where
         Err(ErrorKind::MissingExit(key.value()))
     }
 
+    /// Runs a single plugin in description mode without verifying it.
+    ///
+    /// Retryable errors are retried like within an update.
+    pub async fn describe(&self, key: &str) -> Result<i64, ErrorKind> {
+        let key = ContextKey::FileName(key.to_string());
+        self.retry(&key.value(), || self.single(&key)).await
+    }
+
     /// Perform a signature check of the sha256sums file
     pub fn verify_signature(&self) -> Result<(), verify::Error> {
-        let path = self.loader.root_path().unwrap();
+        let path = self.loader.root_path()?;
         check_signature(&path)
     }
 
@@ -193,6 +244,34 @@ where
         stream::unfold(self, |mut s| async move { s.next().await.map(|x| (x, s)) })
     }
 
+    /// Returns a stream of each result together with its report.
+    pub fn report_stream(self) -> impl Stream<Item = (Result<String, Error>, FileReport)> + 'a {
+        stream::unfold(self, |mut s| async move {
+            let start = Instant::now();
+            let result = s.next().await?;
+            let report = FileReport::new(&result, start.elapsed());
+            Some(((result, report), s))
+        })
+    }
+
+    /// Runs the feed update and returns the report of each file.
+    ///
+    /// Unlike `perform_update` a broken file does not abort the update. Only when the storage
+    /// fails the update is aborted, as the following files would fail as well.
+    pub async fn report(self) -> Result<Report, Error> {
+        let mut report = Report::default();
+        let mut results = Box::pin(self.report_stream());
+        while let Some((result, file)) = results.next().await {
+            match result {
+                Err(e) if matches!(e.kind, ErrorKind::StorageError(_)) => return Err(e),
+                Err(e) => tracing::warn!(%e, "unable to handle file"),
+                Ok(key) => trace!(key, "updated"),
+            }
+            report.push(file);
+        }
+        Ok(report)
+    }
+
     async fn next(&mut self) -> Option<Result<String, Error>> {
         match self.verifier.find(|x| {
             x.as_ref()
@@ -201,7 +280,10 @@ where
         }) {
             Some(Ok(k)) => {
                 if let Err(e) = k.verify() {
-                    return Some(Err(e.into()));
+                    return Some(Err(Error {
+                        key: k.get_filename(),
+                        kind: e.into(),
+                    }));
                 }
 
                 let mut filename = k.get_filename();
@@ -211,7 +293,7 @@ where
                     filename = filename[2..].to_string();
                 }
                 let k = ContextKey::FileName(filename.clone());
-                self.single(&k)
+                self.retry(&filename, || self.single(&k))
                     .await
                     .map(|_| k.value())
                     .map_err(|kind| Error {
@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Structured outcome of a feed update.
+//!
+//! Each file handled by [super::Update] results in a [FileReport] so that broken scripts can be
+//! triaged without scraping the logs.
+
+use std::{fmt::Display, time::Duration};
+
+use super::{Error, ErrorKind};
+
+/// Outcome of a single file within a feed update
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "message")]
+pub enum Outcome {
+    /// The file was handled successfully
+    Ok,
+    /// The file contains a syntax error
+    SyntaxError(String),
+    /// The interpreter failed while running the file in description mode
+    InterpretError(String),
+    /// The hash sum or signature verification failed
+    VerifyError(String),
+    /// The file could not be loaded
+    LoadError(String),
+    /// The storage was unable to store the description
+    StorageError(String),
+    /// The description block did not exit
+    MissingExit,
+    /// The description of the script is invalid and was not stored
+    InvalidNvt(String),
+}
+
+impl From<&ErrorKind> for Outcome {
+    fn from(value: &ErrorKind) -> Self {
+        match value {
+            ErrorKind::InterpretError(e) => Self::InterpretError(e.to_string()),
+            ErrorKind::SyntaxError(e) => Self::SyntaxError(e.to_string()),
+            ErrorKind::StorageError(e) => Self::StorageError(e.to_string()),
+            ErrorKind::LoadError(e) => Self::LoadError(e.to_string()),
+            ErrorKind::MissingExit(_) => Self::MissingExit,
+            ErrorKind::VerifyError(e) => Self::VerifyError(e.to_string()),
+            ErrorKind::InvalidNvt(e) => Self::InvalidNvt(e.to_string()),
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::SyntaxError(e) => write!(f, "syntax error: {e}"),
+            Self::InterpretError(e) => write!(f, "interpret error: {e}"),
+            Self::VerifyError(e) => write!(f, "verify error: {e}"),
+            Self::LoadError(e) => write!(f, "load error: {e}"),
+            Self::StorageError(e) => write!(f, "storage error: {e}"),
+            Self::MissingExit => write!(f, "description block without exit"),
+            Self::InvalidNvt(e) => write!(f, "invalid nvt: {e}"),
+        }
+    }
+}
+
+impl Outcome {
+    /// Returns true if the file was handled successfully.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+/// The outcome of a single file and how long it took to handle it
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FileReport {
+    /// The key of the file, usually the path relative to the feed root
+    pub key: String,
+    /// The outcome of the file
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    /// Duration in milliseconds
+    pub duration: u64,
+}
+
+impl FileReport {
+    /// Creates a report of a result returned by the update stream.
+    pub fn new(result: &Result<String, Error>, duration: Duration) -> Self {
+        let (key, outcome) = match result {
+            Ok(key) => (key.clone(), Outcome::Ok),
+            Err(e) => (e.key.clone(), Outcome::from(&e.kind)),
+        };
+        Self {
+            key,
+            outcome,
+            duration: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// The report of a whole feed update
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// The file reports in the order they got handled
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    /// Returns the amount of handled files.
+    pub fn total(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns the reports of the files that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|x| !x.outcome.is_ok())
+    }
+
+    /// Returns the amount of files that failed.
+    pub fn failed(&self) -> usize {
+        self.failures().count()
+    }
+
+    /// Adds a file report.
+    pub fn push(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    /// Writes the report as json into the given writer.
+    pub fn write<W: std::io::Write>(&self, writer: W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+impl FromIterator<FileReport> for Report {
+    fn from_iter<T: IntoIterator<Item = FileReport>>(iter: T) -> Self {
+        Self {
+            files: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::feed::update::{Error, ErrorKind};
+
+    use super::*;
+
+    #[test]
+    fn serialize() {
+        let report: Report = [
+            Ok("ok.nasl".to_string()),
+            Err(Error {
+                key: "missing_exit.nasl".to_string(),
+                kind: ErrorKind::MissingExit("missing_exit.nasl".to_string()),
+            }),
+        ]
+        .iter()
+        .map(|x| FileReport::new(x, Duration::from_millis(3)))
+        .collect();
+        assert_eq!(report.total(), 2);
+        assert_eq!(report.failed(), 1);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "files": [
+                    {"key": "ok.nasl", "status": "ok", "duration": 3},
+                    {"key": "missing_exit.nasl", "status": "missing_exit", "duration": 3},
+                ]
+            })
+        );
+        let back: Report = serde_json::from_value(json).unwrap();
+        assert_eq!(back, report);
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::sync::{Mutex, PoisonError};
+
+use crate::storage::{
+    item::{NVTField, Nvt, NvtError},
+    ContextKey, Dispatcher, Field, StorageError,
+};
+
+/// Collects the NVT fields of a description run and stores them as a single NVT on exit.
+///
+/// A script that fails before it calls exit does not leave a partially stored NVT behind and a
+/// retried script does not append its fields twice. All other fields are dispatched right away.
+pub struct WriteBack<'a, S> {
+    dispatcher: &'a S,
+    nvt: Mutex<Option<Nvt>>,
+}
+
+impl<'a, S> WriteBack<'a, S>
+where
+    S: Dispatcher,
+{
+    /// Creates a new WriteBack without any collected fields.
+    pub fn new(dispatcher: &'a S) -> Self {
+        Self {
+            dispatcher,
+            nvt: Mutex::new(None),
+        }
+    }
+
+    /// Validates the collected NVT, see [Nvt::validate].
+    pub fn validate(&self) -> Result<(), NvtError> {
+        match &*self.nvt.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(nvt) => nvt.validate(),
+            None => Ok(()),
+        }
+    }
+
+    fn collect(&self, key: &ContextKey, field: NVTField) -> Result<(), StorageError> {
+        if let NVTField::Version(_) = field {
+            return self.dispatcher.dispatch(key, field.into());
+        }
+        let mut nvt = self.nvt.lock()?;
+        // a version is the only field that cannot be set and is handled above
+        let _ = nvt
+            .get_or_insert_with(Default::default)
+            .set_from_field(field);
+        Ok(())
+    }
+}
+
+impl<S> Dispatcher for WriteBack<'_, S>
+where
+    S: Dispatcher,
+{
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        match scope {
+            Field::NVT(field) => self.collect(key, field),
+            scope => self.dispatcher.dispatch(key, scope),
+        }
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        match scope {
+            Field::NVT(field) => self.collect(key, field),
+            scope => self.dispatcher.dispatch_replace(key, scope),
+        }
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        let nvt = self.nvt.lock()?.take();
+        if let Some(nvt) = nvt {
+            self.dispatcher.dispatch(key, NVTField::Nvt(nvt).into())?;
+        }
+        self.dispatcher.on_exit(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{DefaultDispatcher, Retrieve, Retriever};
+
+    use super::*;
+
+    #[test]
+    fn flush_on_exit() {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::FileName("test.nasl".into());
+        let stored = || -> Vec<Nvt> {
+            storage
+                .retrieve(&key, Retrieve::NVT(None))
+                .unwrap()
+                .filter_map(|x| match x {
+                    Field::NVT(NVTField::Nvt(nvt)) => Some(nvt),
+                    _ => None,
+                })
+                .collect()
+        };
+        let write_back = WriteBack::new(&storage);
+        for field in [
+            NVTField::Oid("1".into()),
+            NVTField::FileName("test.nasl".into()),
+            NVTField::Dependencies(vec!["a.nasl".into()]),
+        ] {
+            write_back.dispatch(&key, field.into()).unwrap();
+        }
+        assert!(stored().is_empty());
+        write_back.on_exit(&key).unwrap();
+        let nvts = stored();
+        assert_eq!(nvts.len(), 1);
+        assert_eq!(nvts[0].oid, "1");
+        assert_eq!(nvts[0].dependencies, vec!["a.nasl"]);
+
+        // a run without exit does not change the stored NVT
+        let write_back = WriteBack::new(&storage);
+        write_back
+            .dispatch(&key, NVTField::Dependencies(vec!["b.nasl".into()]).into())
+            .unwrap();
+        assert_eq!(stored()[0].dependencies, vec!["a.nasl"]);
+    }
+
+    #[test]
+    fn validate() {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::FileName("test.nasl".into());
+        let write_back = WriteBack::new(&storage);
+        // scripts without a description are not validated
+        assert_eq!(write_back.validate(), Ok(()));
+        write_back
+            .dispatch(&key, NVTField::Oid("1.3.6.1.x".into()).into())
+            .unwrap();
+        assert_eq!(
+            write_back.validate(),
+            Err(NvtError::InvalidOid("1.3.6.1.x".into()))
+        );
+    }
+}
@@ -2,11 +2,15 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::storage::DefaultDispatcher;
+use crate::storage::{ContextKey, DefaultDispatcher, Dispatcher, Field, StorageError};
 use crate::{
-    feed::{HashSumNameLoader, Update},
+    feed::{update::Backoff, HashSumNameLoader, Update, UpdateError, UpdateErrorKind},
     nasl::syntax::FSPluginLoader,
 };
 use futures::StreamExt;
@@ -53,3 +57,86 @@ async fn verify_feed() {
         &["test.nasl".to_owned(), "plugin_feed_info.inc".to_owned()]
     );
 }
+
+#[tokio::test]
+async fn report() {
+    let loader = loader();
+    let storage: DefaultDispatcher = DefaultDispatcher::new();
+    let verifier = HashSumNameLoader::sha256(&loader).expect("sha256sums should be available");
+    let updater = Update::init("1", 1, &loader, &storage, verifier);
+    let report = updater.report().await.expect("storage should not fail");
+    assert_eq!(report.total(), 2);
+    assert_eq!(report.failed(), 0);
+    assert_eq!(
+        report
+            .files
+            .iter()
+            .map(|x| x.key.as_str())
+            .collect::<Vec<_>>(),
+        ["test.nasl", "plugin_feed_info.inc"]
+    );
+}
+
+/// Fails the first dispatches with a retryable error.
+#[derive(Default)]
+struct FlakyDispatcher {
+    failures: AtomicUsize,
+    underlying: DefaultDispatcher,
+}
+
+impl FlakyDispatcher {
+    fn fail(&self) -> Result<(), StorageError> {
+        match self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| x.checked_sub(1))
+        {
+            Ok(_) => Err(StorageError::Retry("connection lost".to_string())),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl Dispatcher for FlakyDispatcher {
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.fail()?;
+        self.underlying.dispatch(key, scope)
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        self.fail()?;
+        self.underlying.dispatch_replace(key, scope)
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        self.underlying.on_exit(key)
+    }
+}
+
+#[tokio::test]
+async fn retry_flaky_storage() {
+    let loader = loader();
+    let report = |max_retry, failures| {
+        let loader = &loader;
+        async move {
+            let storage = FlakyDispatcher {
+                failures: AtomicUsize::new(failures),
+                ..Default::default()
+            };
+            let verifier = HashSumNameLoader::sha256(loader).unwrap();
+            Update::init("1", max_retry, loader, &storage, verifier)
+                .with_backoff(Backoff::none())
+                .report()
+                .await
+                .map(|x| x.failed())
+        }
+    };
+    // the NVT is stored on exit, each failed store reruns the script
+    assert_eq!(report(2, 2).await, Ok(0));
+    assert!(matches!(
+        report(1, 2).await,
+        Err(UpdateError {
+            kind: UpdateErrorKind::StorageError(StorageError::Retry(_)),
+            ..
+        })
+    ));
+}
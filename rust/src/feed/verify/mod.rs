@@ -9,14 +9,19 @@
 //! This is required to prevent load modified nasl scripts.
 //! If you want to manipulate the feed you have to create a new hashsum file otherwise the modificated data will not
 //! be loaded
+//!
+//! Files that are not part of a signed sums file, e.g. custom VTs or advisories, can be verified
+//! by a detached signature next to them (`<file>.asc`). The certificates used for both checks are
+//! provided by a [TrustStore].
 
 use std::{
+    collections::VecDeque,
     fs::File,
     io::{self, BufRead, BufReader, Read},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use crate::nasl::syntax::{AsBufReader, LoadError};
+use crate::nasl::syntax::{ContentReader, LoadError};
 use crate::nasl::syntax::{FSPluginLoader, Loader};
 use hex::encode;
 use sha2::{Digest, Sha256};
@@ -58,30 +63,51 @@ pub enum Error {
     #[error("Signature check is enabled but there is no keyring. Set the GNUPGHOME environment variable")]
     /// Missing keyring
     MissingKeyring,
+    #[error("Missing detached signature for file with key '{0}'.")]
+    /// The detached signature of a file does not exist
+    MissingSignature(String),
 }
 
-struct VHelper {
-    keyring: String,
-    not_before: Option<std::time::SystemTime>,
-    not_after: std::time::SystemTime,
+/// Provides the certificates that are trusted to sign feed files.
+pub trait TrustStore: Send + Sync + std::fmt::Debug {
+    /// Returns the trusted certificates.
+    fn certs(&self) -> Result<Vec<Cert>, Error>;
 }
 
-impl VHelper {
-    fn new(keyring: String) -> Self {
-        Self {
-            keyring,
-            not_before: None,
-            not_after: std::time::SystemTime::now(),
-        }
+impl TrustStore for Vec<Cert> {
+    fn certs(&self) -> Result<Vec<Cert>, Error> {
+        Ok(self.clone())
     }
 }
 
-impl VerificationHelper for VHelper {
-    fn get_certs(&mut self, _ids: &[KeyHandle]) -> openpgp::Result<Vec<Cert>> {
-        let file = File::open(self.keyring.as_str())?;
-        let kbx = Keybox::from_reader(file)?;
+/// A gnupg keybox containing the trusted certificates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyring {
+    path: PathBuf,
+}
+
+impl Keyring {
+    /// Creates a keyring of the given keybox file.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self { path: path.into() }
+    }
+
+    /// Uses the `pubring.kbx` within the directory set by the GNUPGHOME environment variable.
+    pub fn from_env() -> Result<Self, Error> {
+        let gnupghome = std::env::var("GNUPGHOME").map_err(|_| Error::MissingKeyring)?;
+        Ok(Self::new(Path::new(&gnupghome).join("pubring.kbx")))
+    }
+}
 
-        let certs = kbx
+impl TrustStore for Keyring {
+    fn certs(&self) -> Result<Vec<Cert>, Error> {
+        let file = File::open(&self.path).map_err(|e| LoadError::from((self.path.as_path(), e)))?;
+        let kbx = Keybox::from_reader(file).map_err(|e| Error::BadSignature(e.to_string()))?;
+
+        kbx
             // Keep only records which were parsed successfully.
             .filter_map(|kbx_record| kbx_record.ok())
             // Map the OpenPGP records to the contained certs.
@@ -89,8 +115,36 @@ impl VerificationHelper for VHelper {
                 KeyboxRecord::OpenPGP(r) => Some(r.cert()),
                 _ => None,
             })
-            .collect::<openpgp::Result<Vec<Cert>>>()?;
-        Ok(certs)
+            .collect::<openpgp::Result<Vec<Cert>>>()
+            .map_err(|e| Error::BadSignature(e.to_string()))
+    }
+}
+
+struct VHelper<'a, T: ?Sized> {
+    store: &'a T,
+    not_before: Option<std::time::SystemTime>,
+    not_after: std::time::SystemTime,
+}
+
+impl<'a, T> VHelper<'a, T>
+where
+    T: TrustStore + ?Sized,
+{
+    fn new(store: &'a T) -> Self {
+        Self {
+            store,
+            not_before: None,
+            not_after: std::time::SystemTime::now(),
+        }
+    }
+}
+
+impl<T> VerificationHelper for VHelper<'_, T>
+where
+    T: TrustStore + ?Sized,
+{
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> openpgp::Result<Vec<Cert>> {
+        Ok(self.store.certs()?)
     }
 
     fn check(&mut self, structure: MessageStructure) -> openpgp::Result<()> {
@@ -153,27 +207,74 @@ pub fn check_signature<P>(path: &P) -> Result<(), Error>
 where
     P: AsRef<Path> + ?Sized,
 {
-    let mut gnupghome = match std::env::var("GNUPGHOME") {
-        Ok(v) => v,
-        Err(_) => {
-            return Err(Error::MissingKeyring);
-        }
-    };
-    gnupghome.push_str("/pubring.kbx");
+    check_signature_with(&Keyring::from_env()?, path)
+}
+
+/// Verifies the sha256sums file within path with the certificates of the given trust store.
+pub fn check_signature_with<T, P>(store: &T, path: &P) -> Result<(), Error>
+where
+    T: TrustStore + ?Sized,
+    P: AsRef<Path> + ?Sized,
+{
+    check_file_signature(store, &path.as_ref().join(Hasher::Sha256.sum_file()))
+}
 
-    let helper = VHelper::new(gnupghome);
+/// Verifies the file in path with its detached signature `<path>.asc`.
+pub fn check_file_signature<T, P>(store: &T, path: &P) -> Result<(), Error>
+where
+    T: TrustStore + ?Sized,
+    P: AsRef<Path> + ?Sized,
+{
+    let data_path = path.as_ref();
+    let mut sign_path = data_path.as_os_str().to_owned();
+    sign_path.push(".asc");
+    let sign_path = PathBuf::from(sign_path);
+
+    let key = data_path.to_string_lossy();
+    if !sign_path.is_file() {
+        return Err(Error::MissingSignature(key.to_string()));
+    }
+    let signature = read(&sign_path)?;
+    let data = read(data_path)?;
+    verify_detached(store, &data, &signature)
+}
 
-    let sign_path = path.as_ref().to_path_buf().join("sha256sums.asc");
-    let mut sig_file = File::open(sign_path).unwrap();
-    let mut signature = Vec::new();
-    let _ = sig_file.read_to_end(&mut signature);
+/// Verifies the sha256sums file of the given reader with its detached signature.
+///
+/// Unlike [check_signature_with] the files are read through the reader so that feeds that are not
+/// stored within a directory (e.g. an archive) can be verified.
+pub fn check_content_signature<T, R>(store: &T, reader: &R) -> Result<(), Error>
+where
+    T: TrustStore + ?Sized,
+    R: ContentReader + ?Sized,
+{
+    let key = Hasher::Sha256.sum_file();
+    let sign_key = format!("{key}.asc");
+    if !reader.contains(&sign_key) {
+        return Err(Error::MissingSignature(key.to_string()));
+    }
+    let read = |key: &str| -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        reader
+            .open(key)?
+            .read_to_end(&mut data)
+            .map_err(|e| LoadError::from((key, e)))?;
+        Ok(data)
+    };
+    verify_detached(store, &read(key)?, &read(&sign_key)?)
+}
 
-    let data_path = path.as_ref().to_path_buf().join("sha256sums");
-    let mut data_file = File::open(data_path).unwrap();
-    let mut data = Vec::new();
-    let _ = data_file.read_to_end(&mut data);
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|e| LoadError::from((path, e)).into())
+}
 
-    let v = match DetachedVerifierBuilder::from_bytes(&signature[..]) {
+/// Verifies data with the given detached signature and the certificates of the given trust store.
+pub fn verify_detached<T>(store: &T, data: &[u8], signature: &[u8]) -> Result<(), Error>
+where
+    T: TrustStore + ?Sized,
+{
+    let helper = VHelper::new(store);
+    let v = match DetachedVerifierBuilder::from_bytes(signature) {
         Ok(v) => v,
         Err(_) => {
             return Err(Error::BadSignature(
@@ -182,16 +283,13 @@ where
         }
     };
 
-    let p = &StandardPolicy::new();
-    if let Ok(mut verifier) = v.with_policy(p, None, helper) {
-        match verifier.verify_bytes(data) {
-            Ok(_) => return Ok(()),
-            Err(e) => return Err(Error::BadSignature(e.to_string())),
-        }
-    };
-    Err(Error::BadSignature(
-        "Signature verification failed".to_string(),
-    ))
+    let p = StandardPolicy::new();
+    let mut verifier = v
+        .with_policy(&p, None, helper)
+        .map_err(|e| Error::BadSignature(e.to_string()))?;
+    verifier
+        .verify_bytes(data)
+        .map_err(|e| Error::BadSignature(e.to_string()))
 }
 
 /// Trait for signature check
@@ -259,14 +357,18 @@ impl Hasher {
 /// Loads a given hashsums file and lazily verifies the loaded filename key of the sums file and verifies
 /// the hash within the sums file with an calculated hash of the found content.
 pub struct HashSumNameLoader<'a> {
-    reader: &'a FSPluginLoader,
+    reader: &'a dyn ContentReader,
     hasher: Hasher,
-    buf: io::Lines<BufReader<File>>,
+    buf: io::Lines<BufReader<Box<dyn Read + Send + Sync + 'a>>>,
 }
 
 /// Loads hashsum verified names of the feed based on a sum file.
 impl<'a> HashSumNameLoader<'a> {
-    fn new(buf: io::Lines<BufReader<File>>, reader: &'a FSPluginLoader, hasher: Hasher) -> Self {
+    fn new(
+        buf: io::Lines<BufReader<Box<dyn Read + Send + Sync + 'a>>>,
+        reader: &'a dyn ContentReader,
+        hasher: Hasher,
+    ) -> Self {
         Self {
             reader,
             hasher,
@@ -275,10 +377,13 @@ impl<'a> HashSumNameLoader<'a> {
     }
 
     /// Returns a sha256 implementation of HashSumNameLoader
-    pub fn sha256(reader: &'a FSPluginLoader) -> Result<HashSumNameLoader<'a>, Error> {
+    pub fn sha256<R>(reader: &'a R) -> Result<HashSumNameLoader<'a>, Error>
+    where
+        R: ContentReader,
+    {
         let buf = reader
-            .as_bufreader(Hasher::Sha256.sum_file())
-            .map(|x| x.lines())
+            .open(Hasher::Sha256.sum_file())
+            .map(|x| BufReader::new(x).lines())
             .map_err(Error::LoadError)?;
         Ok(Self::new(buf, reader, Hasher::Sha256))
     }
@@ -286,7 +391,7 @@ impl<'a> HashSumNameLoader<'a> {
     /// Returns the hashsum of the sums file
     pub fn sumfile_hash(&self) -> Result<String, Error> {
         self.hasher.hash(
-            &mut self.reader.as_bufreader(self.hasher.sum_file())?,
+            &mut BufReader::new(self.reader.open(self.hasher.sum_file())?),
             self.hasher.sum_file(),
         )
     }
@@ -311,9 +416,12 @@ impl<'a> Iterator for HashSumNameLoader<'a> {
 
                 Some(Ok(HashSumFileItem {
                     file_name: file_name.to_string(),
-                    hashsum: hashsum.to_string(),
-                    hasher: self.hasher.clone(),
+                    check: FileCheck::HashSum {
+                        hashsum: hashsum.to_string(),
+                        hasher: self.hasher.clone(),
+                    },
                     reader: self.reader,
+                    verified: None,
                 }))
             }
             Err(_) => Some(Err(Error::SumsFileCorrupt(self.hasher.clone()))),
@@ -321,29 +429,173 @@ impl<'a> Iterator for HashSumNameLoader<'a> {
     }
 }
 
-/// Contains all information  necessary to do a hash sum check
+/// Chains the sums files of each layer of a [FSPluginLoader] with overlays.
+///
+/// The layers are iterated from the lowest to the highest precedence so that a VT of an overlay
+/// replaces a VT with the same OID of a lower layer. A file that is overridden by a layer with a
+/// higher precedence is skipped, as it would never be loaded.
+pub struct LayeredNameLoader<'a, L = FSPluginLoader> {
+    layers: &'a [L],
+    loaders: VecDeque<HashSumNameLoader<'a>>,
+}
+
+impl<'a, L> LayeredNameLoader<'a, L>
+where
+    L: ContentReader,
+{
+    /// Returns a sha256 implementation of LayeredNameLoader based on the layers returned by
+    /// [FSPluginLoader::layers].
+    ///
+    /// Each layer requires its own sums file.
+    pub fn sha256(layers: &'a [L]) -> Result<Self, Error> {
+        let loaders = layers
+            .iter()
+            .map(HashSumNameLoader::sha256)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { layers, loaders })
+    }
+
+    /// Returns the hashsum of the sums file when there is a single layer, otherwise the hashsum
+    /// of the hashsums of each sums file.
+    pub fn sumfile_hash(&self) -> Result<String, Error> {
+        let hashes = self
+            .layers
+            .iter()
+            .map(|x| HashSumNameLoader::sha256(x)?.sumfile_hash())
+            .collect::<Result<Vec<_>, _>>()?;
+        match &hashes[..] {
+            [hash] => Ok(hash.clone()),
+            hashes => Hasher::Sha256.hash(&mut BufReader::new(hashes.concat().as_bytes()), ""),
+        }
+    }
+
+    fn is_shadowed(&self, layer: usize, file_name: &str) -> bool {
+        self.layers[layer + 1..]
+            .iter()
+            .any(|x| x.contains(file_name))
+    }
+}
+
+impl<'a, L> Iterator for LayeredNameLoader<'a, L>
+where
+    L: ContentReader,
+{
+    type Item = Result<HashSumFileItem<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let layer = self.layers.len() - self.loaders.len();
+            match self.loaders.front_mut()?.next() {
+                None => {
+                    self.loaders.pop_front();
+                }
+                Some(Ok(x)) if self.is_shadowed(layer, &x.file_name) => {}
+                Some(x) => return Some(x),
+            }
+        }
+    }
+}
+
+/// Loads the names of the .nasl and .inc files of a directory that are verified by their
+/// detached signature instead of a sums file.
+///
+/// This is meant for files that are not part of the signed feed, e.g. custom VTs. Each file
+/// requires a `<file>.asc` signature signed by a certificate of the given trust store.
+pub struct DetachedSignatureNameLoader<'a> {
+    reader: &'a FSPluginLoader,
+    store: &'a dyn TrustStore,
+    files: NaslFileFinder,
+}
+
+impl<'a> DetachedSignatureNameLoader<'a> {
+    /// Creates a DetachedSignatureNameLoader for the root path of the given reader.
+    pub fn new(reader: &'a FSPluginLoader, store: &'a dyn TrustStore) -> Result<Self, Error> {
+        let files = NaslFileFinder::new(reader.root_path()?, true);
+        Ok(Self {
+            reader,
+            store,
+            files,
+        })
+    }
+}
+
+impl<'a> Iterator for DetachedSignatureNameLoader<'a> {
+    type Item = Result<HashSumFileItem<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let file_name = match self.files.next()? {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(Ok(HashSumFileItem {
+            file_name,
+            check: FileCheck::Signature(self.store),
+            reader: self.reader,
+            verified: None,
+        }))
+    }
+}
+
+enum FileCheck<'a> {
+    HashSum { hashsum: String, hasher: Hasher },
+    Signature(&'a dyn TrustStore),
+}
+
+/// Contains all information  necessary to do a hash sum or signature check
 pub struct HashSumFileItem<'a> {
     file_name: String,
-    hashsum: String,
-    hasher: Hasher,
-    reader: &'a FSPluginLoader,
+    check: FileCheck<'a>,
+    reader: &'a dyn ContentReader,
+    verified: Option<Result<(), Error>>,
 }
 
 impl<'a> HashSumFileItem<'a> {
-    /// Verifies Hashsum
+    /// Verifies the hashsum or the detached signature
+    ///
+    /// Returns the stored outcome when the item got verified in advance.
     pub fn verify(&self) -> Result<(), Error> {
-        let hashsum = self.hasher.hash(
-            &mut self.reader.as_bufreader(&self.file_name)?,
-            &self.file_name,
-        )?;
-        if self.hashsum != hashsum {
-            return Err(Error::HashInvalid {
-                expected: self.hashsum.clone(),
-                actual: hashsum,
-                key: self.file_name.clone(),
-            });
+        match &self.verified {
+            Some(result) => result.clone(),
+            None => self.check(),
+        }
+    }
+
+    /// Verifies the item and stores the outcome for later calls of [Self::verify].
+    pub fn verify_in_advance(&mut self) {
+        if self.verified.is_none() {
+            self.verified = Some(self.check());
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        match &self.check {
+            FileCheck::HashSum { hashsum, hasher } => {
+                let actual = hasher.hash(
+                    &mut BufReader::new(self.reader.open(&self.file_name)?),
+                    &self.file_name,
+                )?;
+                if hashsum != &actual {
+                    return Err(Error::HashInvalid {
+                        expected: hashsum.clone(),
+                        actual,
+                        key: self.file_name.clone(),
+                    });
+                }
+                Ok(())
+            }
+            FileCheck::Signature(store) => {
+                let root = self.reader.root_path()?;
+                check_file_signature(*store, &Path::new(&root).join(&self.file_name)).map_err(|e| {
+                    match e {
+                        // the key is relative to the feed like for the other errors
+                        Error::MissingSignature(_) => {
+                            Error::MissingSignature(self.file_name.clone())
+                        }
+                        e => e,
+                    }
+                })
+            }
         }
-        Ok(())
     }
 
     /// returns file name
@@ -351,9 +603,76 @@ impl<'a> HashSumFileItem<'a> {
         self.file_name.clone()
     }
 
-    /// returns hash sum
+    /// returns hash sum, empty when the file is verified by a detached signature
     pub fn get_hashsum(&self) -> String {
-        self.hashsum.clone()
+        match &self.check {
+            FileCheck::HashSum { hashsum, .. } => hashsum.clone(),
+            FileCheck::Signature(_) => String::new(),
+        }
+    }
+}
+
+/// Verifies the items of a name loader in advance on multiple threads.
+///
+/// Reading and hashing each file one after another dominates the time of a feed update. This
+/// takes a batch of items from the inner iterator, verifies them on the given amount of worker
+/// threads and returns them in the original order. Calling [HashSumFileItem::verify] on a
+/// returned item does not read the file again.
+pub struct ParallelVerifier<'a, I> {
+    inner: I,
+    workers: usize,
+    buffer: VecDeque<Result<HashSumFileItem<'a>, Error>>,
+}
+
+impl<'a, I> ParallelVerifier<'a, I>
+where
+    I: Iterator<Item = Result<HashSumFileItem<'a>, Error>>,
+{
+    /// Amount of items verified by each worker per batch
+    const ITEMS_PER_WORKER: usize = 16;
+
+    /// Creates a ParallelVerifier using the given amount of worker threads.
+    pub fn new(inner: I, workers: usize) -> Self {
+        let workers = workers.max(1);
+        Self {
+            inner,
+            workers,
+            buffer: VecDeque::with_capacity(workers * Self::ITEMS_PER_WORKER),
+        }
+    }
+
+    fn fill(&mut self) {
+        let mut batch: Vec<_> = self
+            .inner
+            .by_ref()
+            .take(self.workers * Self::ITEMS_PER_WORKER)
+            .collect();
+        let chunk_size = batch.len().div_ceil(self.workers).max(1);
+        std::thread::scope(|s| {
+            for chunk in batch.chunks_mut(chunk_size) {
+                s.spawn(|| {
+                    chunk
+                        .iter_mut()
+                        .filter_map(|x| x.as_mut().ok())
+                        .for_each(HashSumFileItem::verify_in_advance)
+                });
+            }
+        });
+        self.buffer.extend(batch);
+    }
+}
+
+impl<'a, I> Iterator for ParallelVerifier<'a, I>
+where
+    I: Iterator<Item = Result<HashSumFileItem<'a>, Error>>,
+{
+    type Item = Result<HashSumFileItem<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() {
+            self.fill();
+        }
+        self.buffer.pop_front()
     }
 }
 
@@ -441,3 +760,198 @@ impl FileNameLoader for NaslFileFinder {
         self.next()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use openpgp::{
+        cert::CertBuilder,
+        serialize::stream::{Armorer, Message, Signer},
+    };
+
+    use super::*;
+
+    fn cert() -> Cert {
+        let (cert, _) = CertBuilder::general_purpose(None, Some("test@example.com"))
+            .generate()
+            .unwrap();
+        cert
+    }
+
+    fn sign(cert: &Cert, data: &[u8]) -> Vec<u8> {
+        let keypair = cert
+            .keys()
+            .secret()
+            .with_policy(&StandardPolicy::new(), None)
+            .for_signing()
+            .next()
+            .unwrap()
+            .key()
+            .clone()
+            .into_keypair()
+            .unwrap();
+        let mut signature = Vec::new();
+        let message = Armorer::new(Message::new(&mut signature)).build().unwrap();
+        let mut signer = Signer::new(message, keypair).detached().build().unwrap();
+        signer.write_all(data).unwrap();
+        signer.finalize().unwrap();
+        signature
+    }
+
+    #[test]
+    fn detached_signature() {
+        let signer = cert();
+        let signature = sign(&signer, b"exit(0);");
+        let store = vec![signer];
+        verify_detached(&store, b"exit(0);", &signature).unwrap();
+        assert!(matches!(
+            verify_detached(&store, b"exit(1);", &signature),
+            Err(Error::BadSignature(_))
+        ));
+        let untrusted = vec![cert()];
+        assert!(matches!(
+            verify_detached(&untrusted, b"exit(0);", &signature),
+            Err(Error::BadSignature(_))
+        ));
+    }
+
+    #[test]
+    fn content_signature() {
+        let root = std::env::temp_dir().join(format!("content-signature-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let loader = FSPluginLoader::new(&root);
+        let signer = cert();
+        let store = vec![signer.clone()];
+        std::fs::write(root.join("sha256sums"), b"sums").unwrap();
+        assert!(matches!(
+            check_content_signature(&store, &loader),
+            Err(Error::MissingSignature(_))
+        ));
+        std::fs::write(root.join("sha256sums.asc"), sign(&signer, b"sums")).unwrap();
+        check_content_signature(&store, &loader).unwrap();
+        std::fs::write(root.join("sha256sums"), b"changed").unwrap();
+        assert!(matches!(
+            check_content_signature(&store, &loader),
+            Err(Error::BadSignature(_))
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detached_signature_name_loader() {
+        let root = std::env::temp_dir().join(format!("detached-signature-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let cert = cert();
+        for (name, code) in [("signed.nasl", "exit(0);"), ("unsigned.nasl", "exit(1);")] {
+            std::fs::write(root.join(name), code).unwrap();
+        }
+        std::fs::write(root.join("signed.nasl.asc"), sign(&cert, b"exit(0);")).unwrap();
+        let store = vec![cert];
+        let loader = FSPluginLoader::new(&root);
+
+        let results = DetachedSignatureNameLoader::new(&loader, &store)
+            .unwrap()
+            .map(|x| {
+                let x = x.unwrap();
+                (x.get_filename(), x.verify())
+            })
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                ("signed.nasl".to_string(), Ok(())),
+                (
+                    "unsigned.nasl".to_string(),
+                    Err(Error::MissingSignature("unsigned.nasl".to_string()))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn layered_name_loader() {
+        let root = std::env::temp_dir().join(format!("layered-name-loader-{}", std::process::id()));
+        let layers = ["feed", "local"].map(|x| root.join(x));
+        let files: [&[_]; 2] = [
+            &[("a.nasl", "exit(0);"), ("b.nasl", "exit(1);")],
+            &[("b.nasl", "exit(2);"), ("c.nasl", "exit(3);")],
+        ];
+        for (layer, files) in layers.iter().zip(files) {
+            std::fs::create_dir_all(layer).unwrap();
+            let mut sums = String::new();
+            for (name, code) in files {
+                std::fs::write(layer.join(name), code).unwrap();
+                let hash = Hasher::Sha256
+                    .hash(&mut BufReader::new(code.as_bytes()), "")
+                    .unwrap();
+                sums.push_str(&format!("{hash}  {name}\n"));
+            }
+            std::fs::write(layer.join("sha256sums"), sums).unwrap();
+        }
+        let loader = FSPluginLoader::new(&layers[0]).with_overlays(&layers[1..]);
+        let layers = loader.layers();
+
+        let verifier = LayeredNameLoader::sha256(&layers).unwrap();
+        let feed_hash = HashSumNameLoader::sha256(&layers[0])
+            .unwrap()
+            .sumfile_hash()
+            .unwrap();
+        assert_ne!(verifier.sumfile_hash().unwrap(), feed_hash);
+        let results: Vec<_> = verifier
+            .map(|x| {
+                let x = x.unwrap();
+                let code = loader.load(&x.get_filename()).unwrap();
+                (x.get_filename(), x.verify().is_ok(), code)
+            })
+            .collect();
+        let single = LayeredNameLoader::sha256(&layers[..1]).unwrap();
+        assert_eq!(single.sumfile_hash().unwrap(), feed_hash);
+        std::fs::remove_dir_all(&root).unwrap();
+        let expected = [
+            ("a.nasl", "exit(0);"),
+            ("b.nasl", "exit(2);"),
+            ("c.nasl", "exit(3);"),
+        ]
+        .map(|(name, code)| (name.to_string(), true, code.to_string()));
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn parallel_verifier() {
+        let root = std::env::temp_dir().join(format!("parallel-verifier-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut sums = String::new();
+        for i in 0..50 {
+            let code = format!("exit({i});");
+            std::fs::write(root.join(format!("{i}.nasl")), &code).unwrap();
+            let hash = Hasher::Sha256
+                .hash(&mut BufReader::new(code.as_bytes()), "")
+                .unwrap();
+            // every 7th file got modified after the sums file was created
+            let hash = if i % 7 == 0 { "0".repeat(64) } else { hash };
+            sums.push_str(&format!("{hash}  {i}.nasl\n"));
+        }
+        std::fs::write(root.join("sha256sums"), sums).unwrap();
+        let loader = FSPluginLoader::new(&root);
+
+        let verify = |x: Result<HashSumFileItem, Error>| {
+            let x = x.unwrap();
+            (x.get_filename(), x.verify().is_ok())
+        };
+        let expected: Vec<_> = HashSumNameLoader::sha256(&loader)
+            .unwrap()
+            .map(verify)
+            .collect();
+        let items: Vec<_> = ParallelVerifier::new(HashSumNameLoader::sha256(&loader).unwrap(), 3)
+            .map(|x| x.unwrap())
+            .collect();
+        // the outcome is kept, even when the files are gone
+        std::fs::remove_dir_all(&root).unwrap();
+        let results: Vec<_> = items.into_iter().map(|x| verify(Ok(x))).collect();
+        assert_eq!(results.len(), 50);
+        assert_eq!(results.iter().filter(|(_, ok)| !ok).count(), 8);
+        assert_eq!(results, expected);
+    }
+}
@@ -1,9 +1,13 @@
+pub mod alive;
+pub mod cpe;
 pub mod feed;
 pub mod models;
 pub mod nasl;
 pub mod notus;
 pub mod openvas;
 pub mod osp;
+pub mod redact;
+pub mod report;
 pub mod scanner;
 pub mod scheduling;
 pub mod storage;
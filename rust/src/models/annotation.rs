@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::models::Labels;
+
+/// A note attached to a scan or to one of its results.
+///
+/// Annotations keep triage context, like the reason a result is accepted, together with the
+/// results of a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Annotation {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Incremental ID of the annotation within the scan, assigned when it is stored
+    pub id: usize,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// ID of the annotated result, when not set the scan itself is annotated
+    pub result_id: Option<usize>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Free-form text
+    pub note: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Labels::is_empty", default)
+    )]
+    /// Structured annotations, e.g. `"triage": "false_positive"`
+    pub labels: Labels,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Creator of the annotation
+    pub author: Option<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Creation time as unix timestamp, assigned when it is stored
+    pub created: u64,
+}
+
+impl Annotation {
+    /// Returns true when neither a note nor labels are set
+    pub fn is_empty(&self) -> bool {
+        self.note.as_ref().is_none_or(|x| x.is_empty()) && self.labels.is_empty()
+    }
+}
@@ -0,0 +1,325 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::collections::BTreeMap;
+
+use super::{normalize_host, Result, ResultType};
+
+/// OID of the VT consolidating the OS detections of a host
+pub const OS_DETECTION_OID: &str = "1.3.6.1.4.1.25623.1.0.105937";
+/// Description of the host details of the consolidated OS detection
+pub const OS_DETECTION_DESCRIPTION: &str = "OS Detection Consolidation and Reporting";
+/// Name of the host detail containing the CPE of the best OS guess
+pub const HOST_DETAIL_BEST_OS_CPE: &str = "best_os_cpe";
+/// Name of the host detail containing the name of the best OS guess
+pub const HOST_DETAIL_BEST_OS_TXT: &str = "best_os_txt";
+/// Name of the host detail containing the CPE of a detected product
+pub const HOST_DETAIL_APP: &str = "App";
+
+/// Weights of the detection methods, the first contained keyword of a description wins.
+///
+/// Authenticated detections are the most reliable ones, guesses based on the network stack the
+/// least reliable ones.
+const WEIGHTS: &[(&str, u8)] = &[
+    ("ssh", 100),
+    ("login", 100),
+    ("wmi", 95),
+    ("smb", 90),
+    ("snmp", 80),
+    ("nmap", 60),
+    ("banner", 50),
+    ("http", 50),
+    ("ftp", 50),
+    ("telnet", 50),
+    ("smtp", 50),
+    ("ttl", 30),
+    ("icmp", 30),
+];
+/// Weight of a detection method not contained in [WEIGHTS]
+const DEFAULT_WEIGHT: u8 = 40;
+
+/// An OS reported by a VT
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OsEvidence {
+    /// OID of the reporting VT
+    pub oid: String,
+    /// Description of the detection method
+    pub method: String,
+    /// CPE of the OS
+    pub cpe: Option<String>,
+    /// Name of the OS
+    pub name: Option<String>,
+}
+
+impl OsEvidence {
+    /// Returns the confidence weight of the detection method between 0 and 100.
+    pub fn weight(&self) -> u8 {
+        let method = self.method.to_lowercase();
+        WEIGHTS
+            .iter()
+            .find(|(keyword, _)| method.contains(keyword))
+            .map(|(_, weight)| *weight)
+            .unwrap_or(DEFAULT_WEIGHT)
+    }
+
+    /// Returns the identity of the OS, the lowercase CPE or the name when there is no CPE.
+    fn candidate(&self) -> Option<String> {
+        self.cpe
+            .as_deref()
+            .or(self.name.as_deref())
+            .map(|x| x.trim().to_lowercase())
+            .filter(|x| !x.is_empty())
+    }
+}
+
+/// The best guess of the OS of a host
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct OperatingSystem {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// CPE of the OS
+    pub cpe: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Name of the OS
+    pub name: Option<String>,
+    /// Confidence of the guess between 0 and 100
+    pub confidence: u8,
+}
+
+impl OperatingSystem {
+    /// Combines the OS evidence of a host into the best guess, none without evidence.
+    ///
+    /// Each candidate is scored by the sum of the weights of its evidence; ties are broken by the
+    /// highest single weight. The confidence is the highest weight of the chosen candidate scaled
+    /// by its share of the total score, so contradicting evidence lowers it.
+    pub fn determine(evidence: &[OsEvidence]) -> Option<Self> {
+        #[derive(Default)]
+        struct Score<'a> {
+            sum: u32,
+            max: u8,
+            best: Option<&'a OsEvidence>,
+        }
+        let mut scores: BTreeMap<String, Score> = BTreeMap::new();
+        let mut total = 0;
+        for x in evidence {
+            let Some(candidate) = x.candidate() else {
+                continue;
+            };
+            let weight = x.weight();
+            total += weight as u32;
+            let score = scores.entry(candidate).or_default();
+            score.sum += weight as u32;
+            if score.best.is_none() || weight > score.max {
+                score.max = weight;
+                score.best = Some(x);
+            }
+        }
+        // BTreeMap iterates in order, so equal scores are decided lexicographically
+        let (_, score) = scores.into_iter().reduce(|a, b| {
+            if (b.1.sum, b.1.max) > (a.1.sum, a.1.max) {
+                b
+            } else {
+                a
+            }
+        })?;
+        let best = score.best?;
+        let name = best.name.clone().or_else(|| {
+            // the evidence of the chosen CPE that names the OS
+            evidence
+                .iter()
+                .filter(|x| x.name.is_some() && x.candidate() == best.candidate())
+                .max_by_key(|x| x.weight())
+                .and_then(|x| x.name.clone())
+        });
+        Some(Self {
+            cpe: best.cpe.clone(),
+            name,
+            confidence: (score.max as u32 * score.sum / total.max(1)) as u8,
+        })
+    }
+}
+
+/// A scanned host with its consolidated host details
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Asset {
+    /// IP address of the host
+    pub ip: String,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Host name of the host
+    pub hostname: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// The best guess of the OS
+    pub os: Option<OperatingSystem>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// CPEs of the detected products
+    pub apps: Vec<String>,
+}
+
+impl Asset {
+    /// Returns the hosts of the results ordered by their IP address.
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a Result>) -> Vec<Self> {
+        let mut assets: BTreeMap<String, Asset> = BTreeMap::new();
+        for result in results {
+            let Some(ip) = normalize_host(result.ip_address.as_deref(), None) else {
+                continue;
+            };
+            let asset = assets.entry(ip.clone()).or_insert_with(|| Asset {
+                ip,
+                ..Default::default()
+            });
+            if asset.hostname.is_none() {
+                asset.hostname.clone_from(&result.hostname);
+            }
+            let Some(detail) = result
+                .detail
+                .as_ref()
+                .filter(|_| result.r_type == ResultType::HostDetail)
+            else {
+                continue;
+            };
+            match detail.name.as_str() {
+                name @ (HOST_DETAIL_BEST_OS_CPE | HOST_DETAIL_BEST_OS_TXT) => {
+                    let os = asset.os.get_or_insert_with(Default::default);
+                    let value = Some(detail.value.clone());
+                    if name == HOST_DETAIL_BEST_OS_CPE {
+                        os.cpe = value;
+                    } else {
+                        os.name = value;
+                    }
+                    os.confidence = os.confidence.max(result.qod.unwrap_or_default());
+                }
+                HOST_DETAIL_APP if !asset.apps.contains(&detail.value) => {
+                    asset.apps.push(detail.value.clone());
+                }
+                _ => {}
+            }
+        }
+        assets.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Detail, Source};
+
+    fn evidence(oid: &str, method: &str, cpe: Option<&str>, name: Option<&str>) -> OsEvidence {
+        OsEvidence {
+            oid: oid.into(),
+            method: method.into(),
+            cpe: cpe.map(|x| x.into()),
+            name: name.map(|x| x.into()),
+        }
+    }
+
+    #[test]
+    fn weight() {
+        assert_eq!(
+            evidence("1", "OS Detection (SSH Login)", None, None).weight(),
+            100
+        );
+        assert_eq!(evidence("1", "SMB NativeLanMan", None, None).weight(), 90);
+        assert_eq!(
+            evidence("1", "ICMP based OS Fingerprinting", None, None).weight(),
+            30
+        );
+        assert_eq!(evidence("1", "", None, None).weight(), DEFAULT_WEIGHT);
+    }
+
+    #[test]
+    fn determine() {
+        assert_eq!(OperatingSystem::determine(&[]), None);
+        let debian = "cpe:/o:debian:debian_linux:12";
+        let windows = "cpe:/o:microsoft:windows";
+        let found = vec![
+            evidence("1", "OS Detection (SSH Login)", Some(debian), None),
+            evidence("2", "HTTP Server Banner", Some(debian), Some("Debian")),
+            evidence("3", "ICMP based OS Fingerprinting", Some(windows), None),
+        ];
+        let os = OperatingSystem::determine(&found).unwrap();
+        assert_eq!(os.cpe.as_deref(), Some(debian));
+        // the name is taken from the weaker evidence of the same OS
+        assert_eq!(os.name.as_deref(), Some("Debian"));
+        // 100 * 150 / 180
+        assert_eq!(os.confidence, 83);
+
+        // the weaker but agreeing evidence outweighs a single stronger one
+        let os = OperatingSystem::determine(&[
+            evidence("1", "SNMP sysDescr", Some(windows), None),
+            evidence("2", "FTP Banner", Some(debian), None),
+            evidence("3", "HTTP Banner", Some(debian), None),
+        ])
+        .unwrap();
+        assert_eq!(os.cpe.as_deref(), Some(debian));
+    }
+
+    #[test]
+    fn from_results() {
+        let detail = |ip: &str, name: &str, value: &str, qod| Result {
+            r_type: ResultType::HostDetail,
+            ip_address: Some(ip.into()),
+            qod,
+            detail: Some(Detail {
+                name: name.into(),
+                value: value.into(),
+                source: Source::default(),
+            }),
+            ..Default::default()
+        };
+        let results = vec![
+            detail(
+                "192.168.0.2",
+                HOST_DETAIL_APP,
+                "cpe:/a:openbsd:openssh",
+                None,
+            ),
+            detail(
+                "192.168.0.1",
+                HOST_DETAIL_BEST_OS_CPE,
+                "cpe:/o:debian:debian_linux",
+                Some(80),
+            ),
+            detail("192.168.0.1", HOST_DETAIL_BEST_OS_TXT, "Debian", Some(80)),
+            Result {
+                r_type: ResultType::Alarm,
+                ip_address: Some("192.168.0.1".into()),
+                hostname: Some("example.com".into()),
+                ..Default::default()
+            },
+        ];
+        let assets = Asset::from_results(&results);
+        assert_eq!(assets.len(), 2);
+        assert_eq!(assets[0].ip, "192.168.0.1");
+        assert_eq!(assets[0].hostname.as_deref(), Some("example.com"));
+        assert_eq!(
+            assets[0].os,
+            Some(OperatingSystem {
+                cpe: Some("cpe:/o:debian:debian_linux".into()),
+                name: Some("Debian".into()),
+                confidence: 80,
+            })
+        );
+        assert_eq!(assets[1].os, None);
+        assert_eq!(assets[1].apps, vec!["cpe:/a:openbsd:openssh"]);
+    }
+}
@@ -31,6 +31,32 @@ impl Credential {
         })
     }
 
+    /// Returns the secret values of the credential, e.g. passwords and private keys.
+    pub fn secrets(&self) -> Vec<&str> {
+        fn privilege(x: &Option<PrivilegeInformation>) -> &str {
+            x.as_ref().map(|x| x.password.as_str()).unwrap_or_default()
+        }
+        match &self.credential_type {
+            CredentialType::UP {
+                password,
+                privilege: p,
+                ..
+            } => vec![password, privilege(p)],
+            CredentialType::USK {
+                password,
+                private_key,
+                privilege: p,
+                ..
+            } => vec![password, private_key, privilege(p)],
+            CredentialType::SNMP {
+                password,
+                community,
+                privacy_password,
+                ..
+            } => vec![password, community, privacy_password],
+        }
+    }
+
     /// Gets the password of the credential.
     pub fn password(&self) -> &str {
         match &self.credential_type {
@@ -88,6 +114,20 @@ pub enum Service {
     SNMP,
 }
 
+impl TryFrom<&str> for Service {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "ssh" => Ok(Service::SSH),
+            "smb" => Ok(Service::SMB),
+            "esxi" => Ok(Service::ESXi),
+            "snmp" => Ok(Service::SNMP),
+            _ => Err(format!("Invalid service: {}", value)),
+        }
+    }
+}
+
 impl AsRef<str> for Service {
     fn as_ref(&self) -> &str {
         match self {
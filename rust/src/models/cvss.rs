@@ -0,0 +1,286 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Base scores of CVSS vectors as used in the `severity_vector` of VTs.
+//!
+//! Supports CVSS v2 as well as v3.0 and v3.1, other versions are not scored.
+
+use std::collections::HashMap;
+
+/// Qualitative severity of a score as defined by CVSS v3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SeverityClass {
+    /// Score of 0.0
+    None,
+    /// Score from 0.1 to 3.9
+    Low,
+    /// Score from 4.0 to 6.9
+    Medium,
+    /// Score from 7.0 to 8.9
+    High,
+    /// Score from 9.0 to 10.0
+    Critical,
+}
+
+impl SeverityClass {
+    /// Classifies a base score.
+    pub fn from_score(score: f64) -> Self {
+        match score {
+            x if x >= 9.0 => Self::Critical,
+            x if x >= 7.0 => Self::High,
+            x if x >= 4.0 => Self::Medium,
+            x if x > 0.0 => Self::Low,
+            _ => Self::None,
+        }
+    }
+}
+
+/// A named level of a [SeverityProfile]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SeverityLevel {
+    /// Name of the level, e.g. `high`
+    pub name: String,
+    /// Lowest base score of the level
+    pub min: f64,
+}
+
+/// Maps base scores to named levels, e.g. the levels used within an organization
+///
+/// A score gets the level with the highest minimum that is not above the score. Scores below
+/// all levels have no level. The default profile uses the classes of [SeverityClass].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SeverityProfile {
+    pub levels: Vec<SeverityLevel>,
+}
+
+impl Default for SeverityProfile {
+    fn default() -> Self {
+        let level = |name: &str, min| SeverityLevel {
+            name: name.to_string(),
+            min,
+        };
+        Self {
+            levels: vec![
+                level("critical", 9.0),
+                level("high", 7.0),
+                level("medium", 4.0),
+                level("low", 0.1),
+                level("none", 0.0),
+            ],
+        }
+    }
+}
+
+impl SeverityProfile {
+    /// Returns the name of the level of a base score.
+    pub fn level(&self, score: f64) -> Option<&str> {
+        self.levels
+            .iter()
+            .filter(|x| x.min <= score)
+            .max_by(|a, b| a.min.total_cmp(&b.min))
+            .map(|x| x.name.as_str())
+    }
+
+    /// Returns the names of the levels ordered from the highest to the lowest minimum.
+    pub fn names(&self) -> Vec<&str> {
+        let mut levels: Vec<&SeverityLevel> = self.levels.iter().collect();
+        levels.sort_by(|a, b| b.min.total_cmp(&a.min));
+        levels.into_iter().map(|x| x.name.as_str()).collect()
+    }
+}
+
+fn metrics(vector: &str) -> HashMap<&str, &str> {
+    vector
+        .split('/')
+        .filter_map(|x| x.split_once(':'))
+        .collect()
+}
+
+/// Rounds up to one decimal as defined by CVSS v3.1.
+fn round_up(value: f64) -> f64 {
+    let int = (value * 100_000.0).round() as u64;
+    if int.is_multiple_of(10_000) {
+        int as f64 / 100_000.0
+    } else {
+        ((int / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+fn v3(metrics: &HashMap<&str, &str>) -> Option<f64> {
+    let changed = match *metrics.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (*metrics.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let cia = |key| match *metrics.get(key)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let iss = 1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?);
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02_f64).powi(15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let score = if changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    Some(round_up(score.min(10.0)))
+}
+
+fn v2(metrics: &HashMap<&str, &str>) -> Option<f64> {
+    let av = match *metrics.get("AV")? {
+        "L" => 0.395,
+        "A" => 0.646,
+        "N" => 1.0,
+        _ => return None,
+    };
+    let ac = match *metrics.get("AC")? {
+        "H" => 0.35,
+        "M" => 0.61,
+        "L" => 0.71,
+        _ => return None,
+    };
+    let au = match *metrics.get("Au")? {
+        "M" => 0.45,
+        "S" => 0.56,
+        "N" => 0.704,
+        _ => return None,
+    };
+    let cia = |key| match *metrics.get(key)? {
+        "N" => Some(0.0),
+        "P" => Some(0.275),
+        "C" => Some(0.66),
+        _ => None,
+    };
+    let impact = 10.41 * (1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?));
+    if impact == 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 20.0 * av * ac * au;
+    let score: f64 = (0.6 * impact + 0.4 * exploitability - 1.5) * 1.176;
+    Some((score * 10.0).round() / 10.0)
+}
+
+/// Calculates the base score of a CVSS vector.
+///
+/// Returns None when the vector is malformed or of an unsupported version.
+pub fn base_score(vector: &str) -> Option<f64> {
+    let vector = vector.trim();
+    match vector.split_once('/') {
+        Some(("CVSS:3.0" | "CVSS:3.1", rest)) => v3(&metrics(rest)),
+        Some((x, _)) if x.starts_with("CVSS:") => None,
+        _ => v2(&metrics(vector)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base_score, SeverityClass, SeverityLevel, SeverityProfile};
+
+    #[test]
+    fn v3() {
+        let score = |x| base_score(x).unwrap();
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"), 9.8);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"), 10.0);
+        assert_eq!(score("CVSS:3.0/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N"), 6.1);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:H/PR:N/UI:N/S:C/C:N/I:H/A:N"), 6.8);
+        assert_eq!(score("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:N/I:N/A:N"), 0.0);
+        assert_eq!(
+            base_score("CVSS:3.1/AV:X/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            None
+        );
+        assert_eq!(base_score("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N"), None);
+    }
+
+    #[test]
+    fn v2() {
+        let score = |x| base_score(x).unwrap();
+        assert_eq!(score("AV:N/AC:L/Au:N/C:P/I:P/A:P"), 7.5);
+        assert_eq!(score("AV:N/AC:M/Au:N/C:C/I:C/A:C"), 9.3);
+        assert_eq!(score("AV:N/AC:L/Au:N/C:N/I:N/A:P"), 5.0);
+        assert_eq!(score("AV:N/AC:L/Au:N/C:N/I:N/A:N"), 0.0);
+        assert_eq!(base_score("AV:N/AC:L"), None);
+    }
+
+    #[test]
+    fn classify() {
+        assert_eq!(SeverityClass::from_score(0.0), SeverityClass::None);
+        assert_eq!(SeverityClass::from_score(3.9), SeverityClass::Low);
+        assert_eq!(SeverityClass::from_score(4.0), SeverityClass::Medium);
+        assert_eq!(SeverityClass::from_score(8.9), SeverityClass::High);
+        assert_eq!(SeverityClass::from_score(9.0), SeverityClass::Critical);
+    }
+
+    #[test]
+    fn profile() {
+        let default = SeverityProfile::default();
+        for score in [0.0, 0.1, 3.9, 4.0, 6.9, 7.0, 8.9, 9.0, 10.0] {
+            let class = format!("{:?}", SeverityClass::from_score(score)).to_lowercase();
+            assert_eq!(default.level(score), Some(class.as_str()), "{score}");
+        }
+        let custom = SeverityProfile {
+            levels: vec![
+                SeverityLevel {
+                    name: "P3".to_string(),
+                    min: 5.0,
+                },
+                SeverityLevel {
+                    name: "P1".to_string(),
+                    min: 8.5,
+                },
+            ],
+        };
+        assert_eq!(custom.level(4.9), None);
+        assert_eq!(custom.level(5.0), Some("P3"));
+        assert_eq!(custom.level(9.8), Some("P1"));
+        assert_eq!(custom.names(), vec!["P1", "P3"]);
+    }
+}
@@ -0,0 +1,183 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::net::IpAddr;
+
+use super::{Protocol, Result, ResultType};
+
+/// Canonical identity of a finding.
+///
+/// Two results describe the same finding when the same VT reported it on the same target, port
+/// and virtual host. The parts are normalized so that differently spelled results, e.g.
+/// `2001:DB8::1` and `2001:db8:0::1` or `Example.com.` and `example.com`, share a key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct FindingKey {
+    /// IP address of the host, the host name when the result has no IP address
+    pub target: String,
+    /// ID of the VT, which generated the result
+    pub oid: String,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Port, none for findings of the host in general
+    pub port: Option<i16>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Protocol of the port, TCP when the result has a port without a protocol
+    pub protocol: Option<Protocol>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Virtual host, only set when the host name differs from the target
+    pub vhost: Option<String>,
+}
+
+/// Returns the canonical form of an IP address, none when it is not one.
+fn normalize_ip(ip: &str) -> Option<String> {
+    let ip = ip.trim().trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>().ok().map(|x| x.to_string())
+}
+
+/// Returns the lowercase host name without a trailing dot, none when it is empty.
+pub fn normalize_hostname(hostname: &str) -> Option<String> {
+    let hostname = hostname.trim().trim_end_matches('.').to_lowercase();
+    (!hostname.is_empty()).then_some(hostname)
+}
+
+/// Returns the normalized host of a result.
+///
+/// The IP address is preferred over the host name. An IP address that cannot be parsed is
+/// normalized like a host name.
+pub fn normalize_host(ip_address: Option<&str>, hostname: Option<&str>) -> Option<String> {
+    ip_address
+        .and_then(|x| normalize_ip(x).or_else(|| normalize_hostname(x)))
+        .or_else(|| hostname.and_then(normalize_hostname))
+}
+
+impl FindingKey {
+    /// Returns the key of a result, none when it has no OID or no host.
+    pub fn of(result: &Result) -> Option<Self> {
+        let oid = result
+            .oid
+            .as_deref()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())?;
+        let target = normalize_host(result.ip_address.as_deref(), result.hostname.as_deref())?;
+        let port = result.port.filter(|x| *x > 0);
+        let vhost = result
+            .hostname
+            .as_deref()
+            .and_then(normalize_hostname)
+            .filter(|x| x != &target);
+        Some(Self {
+            target,
+            oid: oid.to_string(),
+            port,
+            protocol: port.map(|_| result.protocol.unwrap_or(Protocol::TCP)),
+            vhost,
+        })
+    }
+}
+
+impl Result {
+    /// Returns the canonical identity of an alarm, none for any other result.
+    pub fn finding_key(&self) -> Option<FindingKey> {
+        if self.r_type != ResultType::Alarm {
+            return None;
+        }
+        FindingKey::of(self)
+    }
+}
+
+impl std::fmt::Display for FindingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.target)?;
+        if let Some(vhost) = &self.vhost {
+            write!(f, " ({vhost})")?;
+        }
+        match (self.port, self.protocol) {
+            (Some(port), Some(protocol)) => write!(f, " {port}/{protocol}")?,
+            (Some(port), None) => write!(f, " {port}")?,
+            _ => write!(f, " general")?,
+        }
+        write!(f, " {}", self.oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alarm(ip: Option<&str>, hostname: Option<&str>, port: Option<i16>) -> Result {
+        Result {
+            r_type: ResultType::Alarm,
+            ip_address: ip.map(|x| x.to_string()),
+            hostname: hostname.map(|x| x.to_string()),
+            oid: Some("1.3.6.1.4.1.25623.1.0.1".to_string()),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalized() {
+        let a = alarm(Some("2001:DB8::1"), Some("Example.com."), Some(443));
+        let b = Result {
+            protocol: Some(Protocol::TCP),
+            ..alarm(Some("[2001:db8:0::1]"), Some("example.com"), Some(443))
+        };
+        assert_eq!(a.finding_key(), b.finding_key());
+        assert_eq!(
+            a.finding_key().unwrap().to_string(),
+            "2001:db8::1 (example.com) 443/tcp 1.3.6.1.4.1.25623.1.0.1"
+        );
+    }
+
+    #[test]
+    fn distinct() {
+        let key = |r: Result| r.finding_key().unwrap();
+        let base = key(alarm(Some("192.0.2.1"), None, Some(80)));
+        assert_ne!(base, key(alarm(Some("192.0.2.2"), None, Some(80))));
+        assert_ne!(base, key(alarm(Some("192.0.2.1"), None, None)));
+        assert_ne!(
+            base,
+            key(alarm(Some("192.0.2.1"), Some("a.example"), Some(80)))
+        );
+        let udp = Result {
+            protocol: Some(Protocol::UDP),
+            ..alarm(Some("192.0.2.1"), None, Some(80))
+        };
+        assert_ne!(base, key(udp));
+    }
+
+    #[test]
+    fn host_name_only() {
+        let key = alarm(None, Some("Host.Example."), None)
+            .finding_key()
+            .unwrap();
+        assert_eq!(key.target, "host.example");
+        assert_eq!(key.vhost, None);
+        assert_eq!(key.port, None);
+        assert_eq!(key.protocol, None);
+    }
+
+    #[test]
+    fn no_key() {
+        assert_eq!(alarm(None, None, None).finding_key(), None);
+        let log = Result {
+            r_type: ResultType::Log,
+            ..alarm(Some("192.0.2.1"), None, None)
+        };
+        assert_eq!(log.finding_key(), None);
+        assert!(FindingKey::of(&log).is_some());
+    }
+}
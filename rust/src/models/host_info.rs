@@ -4,7 +4,7 @@
 
 use std::collections::HashMap;
 
-use crate::models::Host;
+use crate::models::{Host, Target};
 
 #[derive(Default)]
 pub struct HostInfoBuilder {
@@ -28,10 +28,34 @@ impl HostInfoBuilder {
             finished: self.finished,
             scanning: self.scanning,
             remaining_vts_per_host: HashMap::new(),
+            groups: HashMap::new(),
+            host_groups: HashMap::new(),
         }
     }
 }
 
+/// Information about the hosts of a target group of a running scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct GroupHostInfo {
+    all: u64,
+    queued: u64,
+    finished: u64,
+}
+
+impl GroupHostInfo {
+    pub fn queued(&self) -> u64 {
+        self.queued
+    }
+
+    pub fn finished(&self) -> u64 {
+        self.finished
+    }
+}
+
 /// Information about hosts of a running scan
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
@@ -55,6 +79,15 @@ pub struct HostInfo {
     // Hosts that are currently being scanned. The second entry is the number of
     // remaining VTs for this host.
     remaining_vts_per_host: HashMap<String, usize>,
+    // Progress of the hosts of each target group.
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
+    groups: HashMap<String, GroupHostInfo>,
+    // The group of each host that is part of a target group.
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    host_groups: HashMap<Host, String>,
 }
 
 impl HostInfo {
@@ -67,6 +100,21 @@ impl HostInfo {
         }
     }
 
+    /// Creates the host information of the hosts of a target and its groups.
+    pub fn from_target_and_num_vts(target: &Target, num_vts: usize) -> Self {
+        let hosts: Vec<Host> = target.all_hosts().cloned().collect();
+        let mut info = Self::from_hosts_and_num_vts(&hosts, num_vts);
+        for host in hosts {
+            if let Some(group) = target.group(&host) {
+                let entry = info.groups.entry(group.name.clone()).or_default();
+                entry.all += 1;
+                entry.queued += 1;
+                info.host_groups.insert(host, group.name.clone());
+            }
+        }
+        info
+    }
+
     pub fn register_finished_script(&mut self, target: &Host) {
         if let Some(num_vts) = self.remaining_vts_per_host.get_mut(target) {
             *num_vts -= 1;
@@ -74,10 +122,23 @@ impl HostInfo {
                 self.finished += 1;
                 self.queued -= 1;
                 self.remaining_vts_per_host.remove(target);
+                if let Some(group) = self
+                    .host_groups
+                    .get(target)
+                    .and_then(|x| self.groups.get_mut(x))
+                {
+                    group.finished += 1;
+                    group.queued -= 1;
+                }
             }
         }
     }
 
+    /// Returns the progress of the hosts of each target group.
+    pub fn groups(&self) -> &HashMap<String, GroupHostInfo> {
+        &self.groups
+    }
+
     pub fn finish(&mut self) {
         self.remaining_vts_per_host.clear();
         assert_eq!(self.queued, 0);
@@ -91,6 +152,27 @@ impl HostInfo {
         self.finished
     }
 
+    pub fn all(&self) -> u64 {
+        self.all
+    }
+
+    pub fn excluded(&self) -> u64 {
+        self.excluded
+    }
+
+    pub fn dead(&self) -> u64 {
+        self.dead
+    }
+
+    pub fn alive(&self) -> u64 {
+        self.alive
+    }
+
+    /// Returns the hosts that are currently scanned with their progress
+    pub fn scanning(&self) -> Option<&HashMap<String, i32>> {
+        self.scanning.as_ref()
+    }
+
     pub fn update_with(mut self, other: &HostInfo) -> Self {
         // total hosts value is sent once and only once must be updated
         if other.all != 0 {
@@ -122,3 +204,30 @@ impl HostInfo {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TargetGroup;
+
+    #[test]
+    fn groups_are_reported_separately() {
+        let target = Target {
+            hosts: vec!["10.0.0.1".into()],
+            groups: vec![TargetGroup {
+                name: "dmz".into(),
+                hosts: vec!["192.0.2.1".into(), "192.0.2.2".into()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut info = HostInfo::from_target_and_num_vts(&target, 1);
+        assert_eq!(info.queued(), 3);
+        assert_eq!(info.groups()["dmz"].queued(), 2);
+        info.register_finished_script(&"10.0.0.1".to_string());
+        info.register_finished_script(&"192.0.2.1".to_string());
+        assert_eq!(info.finished(), 2);
+        assert_eq!(info.groups()["dmz"].finished(), 1);
+        assert_eq!(info.groups()["dmz"].queued(), 1);
+    }
+}
@@ -3,15 +3,21 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 mod advisories;
+mod annotation;
+mod asset;
 mod credential;
+pub mod cvss;
+mod finding;
 mod host_info;
 mod parameter;
 mod port;
 mod product;
+mod reference;
 pub mod resources;
 mod result;
 mod scan;
 mod scan_action;
+mod scan_plan;
 pub mod scanner;
 mod scanner_preference;
 mod status;
@@ -19,14 +25,19 @@ mod target;
 mod vt;
 
 pub use advisories::*;
+pub use annotation::*;
+pub use asset::*;
 pub use credential::*;
+pub use finding::*;
 pub use host_info::*;
 pub use parameter::*;
 pub use port::*;
 pub use product::*;
+pub use reference::*;
 pub use result::*;
 pub use scan::*;
 pub use scan_action::*;
+pub use scan_plan::*;
 pub use scanner_preference::*;
 pub use status::*;
 pub use target::*;
@@ -52,7 +52,7 @@ impl Display for PortRange {
 }
 
 /// Enum representing the protocol used for scanning a port.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(
     feature = "serde_support",
     derive(serde::Serialize, serde::Deserialize)
@@ -142,10 +142,89 @@ pub fn ports_to_openvas_port_list(ports: Vec<Port>) -> Option<String> {
     Some(port_list)
 }
 
+/// Parses a port list as used by OSP, e.g. `22,T:80-90,U:53`.
+///
+/// Ports before the first `T:` or `U:` apply to both protocols.
+pub fn ports_from_openvas_port_list(list: &str) -> Result<Vec<Port>, String> {
+    let mut ports = vec![
+        Port {
+            protocol: None,
+            range: vec![],
+        },
+        Port {
+            protocol: Some(Protocol::TCP),
+            range: vec![],
+        },
+        Port {
+            protocol: Some(Protocol::UDP),
+            range: vec![],
+        },
+    ];
+    let mut current = 0;
+    let number = |x: &str| {
+        x.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid port: {x}"))
+    };
+    for entry in list.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let entry = if let Some(x) = entry.strip_prefix("T:") {
+            current = 1;
+            x
+        } else if let Some(x) = entry.strip_prefix("U:") {
+            current = 2;
+            x
+        } else {
+            entry
+        };
+        if entry.is_empty() {
+            continue;
+        }
+        let range = match entry.split_once('-') {
+            Some((start, end)) => PortRange {
+                start: number(start)?,
+                end: Some(number(end)?),
+            },
+            None => PortRange {
+                start: number(entry)?,
+                end: None,
+            },
+        };
+        ports[current].range.push(range);
+    }
+    ports.retain(|x| !x.range.is_empty());
+    Ok(ports)
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::models::{ports_to_openvas_port_list, Port, PortRange, Protocol};
+    use crate::models::{
+        ports_from_openvas_port_list, ports_to_openvas_port_list, Port, PortRange, Protocol,
+    };
+
+    #[test]
+    fn test_port_list_parsing() {
+        let range = |start, end| PortRange { start, end };
+        assert_eq!(
+            ports_from_openvas_port_list("22,T:42,33-35,U:53,").unwrap(),
+            vec![
+                Port {
+                    protocol: None,
+                    range: vec![range(22, None)],
+                },
+                Port {
+                    protocol: Some(Protocol::TCP),
+                    range: vec![range(42, None), range(33, Some(35))],
+                },
+                Port {
+                    protocol: Some(Protocol::UDP),
+                    range: vec![range(53, None)],
+                },
+            ]
+        );
+        assert_eq!(ports_from_openvas_port_list("").unwrap(), vec![]);
+        assert!(ports_from_openvas_port_list("T:http").is_err());
+    }
 
     #[test]
     fn test_port_conversion_to_string() {
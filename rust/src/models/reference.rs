@@ -0,0 +1,178 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::collections::HashSet;
+
+/// A normalized reference of a VT, e.g. a CVE or an advisory
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Reference {
+    #[cfg_attr(feature = "serde_support", serde(rename = "type"))]
+    /// Lowercase type of the reference, e.g. `cve`, `dfn-cert`, `cert-bund` or `url`
+    pub r_type: String,
+    /// Normalized ID of the reference, the URL itself for URLs
+    pub id: String,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// URL of the advisory, none when the type has no known location
+    pub url: Option<String>,
+}
+
+/// Returns true when the ID consists of the prefix, the year, the separator and a number.
+fn is_yearly_id(id: &str, prefix: &str, year_digits: usize, separator: char) -> bool {
+    let Some((year, number)) = id
+        .strip_prefix(prefix)
+        .and_then(|x| x.split_once(separator))
+    else {
+        return false;
+    };
+    year.len() == year_digits
+        && year.bytes().all(|x| x.is_ascii_digit())
+        && !number.is_empty()
+        && number.bytes().all(|x| x.is_ascii_digit())
+}
+
+fn is_number(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|x| x.is_ascii_digit())
+}
+
+impl Reference {
+    /// Normalizes and validates a single reference, none when it is invalid.
+    pub fn new(r_type: &str, id: &str) -> Option<Self> {
+        let r_type = r_type.trim().to_lowercase();
+        let id = id.trim();
+        if id.is_empty() {
+            return None;
+        }
+        let (id, url) = match r_type.as_str() {
+            "cve" => {
+                let id = id.to_uppercase();
+                if !is_yearly_id(&id, "CVE-", 4, '-') || id.len() < "CVE-2000-0000".len() {
+                    return None;
+                }
+                let url = format!("https://nvd.nist.gov/vuln/detail/{id}");
+                (id, Some(url))
+            }
+            "dfn-cert" => {
+                let id = id.to_uppercase();
+                if !is_yearly_id(&id, "DFN-CERT-", 4, '-') {
+                    return None;
+                }
+                let url = format!("https://portal.cert.dfn.de/adv/{id}/");
+                (id, Some(url))
+            }
+            "cert-bund" => {
+                let id = id.to_uppercase();
+                if !is_yearly_id(&id, "WID-SEC-", 4, '-') && !is_yearly_id(&id, "CB-K", 2, '/') {
+                    return None;
+                }
+                let url = format!(
+                    "https://wid.cert-bund.de/portal/wid/securityadvisory?name={}",
+                    urlencoding::encode(&id)
+                );
+                (id, Some(url))
+            }
+            "cwe" => {
+                let id = id.to_uppercase();
+                let number = id.strip_prefix("CWE-").filter(|x| is_number(x))?;
+                let url = format!("https://cwe.mitre.org/data/definitions/{number}.html");
+                (id, Some(url))
+            }
+            "bid" => {
+                if !is_number(id) {
+                    return None;
+                }
+                let url = format!("https://www.securityfocus.com/bid/{id}");
+                (id.to_string(), Some(url))
+            }
+            "url" => {
+                let url = reqwest::Url::parse(id).ok()?;
+                if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+                    return None;
+                }
+                (id.to_string(), Some(id.to_string()))
+            }
+            "" => return None,
+            _ => (id.to_string(), None),
+        };
+        Some(Self { r_type, id, url })
+    }
+
+    /// Resolves raw references given as type and ID into normalized references.
+    ///
+    /// IDs may contain several comma separated references, URLs are only split at a comma
+    /// followed by a whitespace as a URL may contain commas itself. Invalid references are
+    /// omitted and duplicates removed, the order is kept.
+    pub fn resolve<'a>(raw: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<Self> {
+        let mut seen = HashSet::new();
+        let mut references = vec![];
+        for (r_type, ids) in raw {
+            let ids: Vec<&str> = if r_type.trim().eq_ignore_ascii_case("url") {
+                ids.split(", ").collect()
+            } else {
+                ids.split(',').collect()
+            };
+            for reference in ids.into_iter().filter_map(|id| Self::new(r_type, id)) {
+                if seen.insert((reference.r_type.clone(), reference.id.clone())) {
+                    references.push(reference);
+                }
+            }
+        }
+        references
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let cve = Reference::new("CVE", " cve-2021-44228 ").unwrap();
+        assert_eq!(cve.r_type, "cve");
+        assert_eq!(cve.id, "CVE-2021-44228");
+        assert_eq!(
+            cve.url.as_deref(),
+            Some("https://nvd.nist.gov/vuln/detail/CVE-2021-44228")
+        );
+        assert_eq!(Reference::new("cve", "CVE-21-1"), None);
+        assert!(Reference::new("DFN-CERT", "DFN-CERT-2021-2598").is_some());
+        assert!(Reference::new("CERT-Bund", "WID-SEC-2023-1234").is_some());
+        assert!(Reference::new("CERT-Bund", "CB-K21/1234").is_some());
+        assert_eq!(Reference::new("cert-bund", "2021-1234"), None);
+        assert_eq!(
+            Reference::new("cwe", "CWE-79").unwrap().url.as_deref(),
+            Some("https://cwe.mitre.org/data/definitions/79.html")
+        );
+        assert_eq!(Reference::new("url", "javascript:alert(1)"), None);
+        assert_eq!(Reference::new("url", "not a url"), None);
+        let vendor = Reference::new("Vendor", "ADV-1").unwrap();
+        assert_eq!((vendor.r_type.as_str(), vendor.url), ("vendor", None));
+    }
+
+    #[test]
+    fn resolve() {
+        let references = Reference::resolve([
+            ("cve", "CVE-2021-44228, CVE-2021-45046"),
+            ("cve", "cve-2021-44228"),
+            ("URL", "https://example.com/a,b, https://example.com/c"),
+            ("bid", "invalid"),
+        ]);
+        let ids: Vec<_> = references.iter().map(|x| x.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "CVE-2021-44228",
+                "CVE-2021-45046",
+                "https://example.com/a,b",
+                "https://example.com/c"
+            ]
+        );
+    }
+}
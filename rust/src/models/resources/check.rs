@@ -1,21 +1,33 @@
+use std::path::PathBuf;
+
 use crate::models::scanner::ObservableResources;
 
 /// Checks for relative resource availability.
 ///
 /// When e.g. min_free_memory is set to 10 then 10% of the memory must be free so that `verify`
 /// does not return the memory observation as an unfullfiled check.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Checker {
     /// Memory in bytes that must be free
     pub memory: Option<u64>,
     /// Percentage of CPU until that must be free.
     pub cpu: Option<f32>,
+    /// Disk space in bytes that must be free on each of `disk_paths`
+    pub disk: Option<u64>,
+    /// Paths whose file systems are checked for free disk space
+    pub disk_paths: Vec<PathBuf>,
+    /// File descriptors that must still be available to the process
+    pub file_descriptors: Option<u64>,
 }
 
 impl Checker {
     /// Returns a instance based on given absolute memory values and relative cpu usage.
     pub fn new(memory: Option<u64>, cpu: Option<f32>) -> Self {
-        Self { memory, cpu }
+        Self {
+            memory,
+            cpu,
+            ..Default::default()
+        }
     }
     /// Returns a instance based on relative memory instead of absolute.
     ///
@@ -26,21 +38,50 @@ impl Checker {
             let system = sysinfo::System::new_all();
             Some((system.total_memory() as f32 * memory) as u64)
         };
-        Self { memory, cpu }
+        Self::new(memory, cpu)
+    }
+
+    /// Requires disk bytes to be free on the file system of each path.
+    pub fn with_disk(mut self, disk: Option<u64>, paths: Vec<PathBuf>) -> Self {
+        self.disk = disk;
+        self.disk_paths = paths;
+        self
+    }
+
+    /// Requires file_descriptors to be still available to the process.
+    pub fn with_file_descriptors(mut self, file_descriptors: Option<u64>) -> Self {
+        self.file_descriptors = file_descriptors;
+        self
     }
 
     /// Returns a list of resource observables that are not within the threshold.
     pub fn breakaways(&self) -> Vec<ObservableResources> {
-        let mut results = Vec::with_capacity(2);
-        let available = super::available();
-        if let Some(free) = self.memory {
-            if available.memory < free {
-                results.push(ObservableResources::Memory);
+        let mut results = Vec::with_capacity(4);
+        if self.memory.is_some() || self.cpu.is_some() {
+            let available = super::available();
+            if let Some(free) = self.memory {
+                if available.memory < free {
+                    results.push(ObservableResources::Memory);
+                }
+            }
+            if let Some(workload) = self.cpu {
+                if available.cpu > workload {
+                    results.push(ObservableResources::CPU);
+                }
             }
         }
-        if let Some(workload) = self.cpu {
-            if available.cpu > workload {
-                results.push(ObservableResources::CPU);
+        if let Some(free) = self.disk {
+            let exhausted = self
+                .disk_paths
+                .iter()
+                .any(|path| super::available_disk(path).is_some_and(|available| available < free));
+            if exhausted {
+                results.push(ObservableResources::Disk);
+            }
+        }
+        if let Some(free) = self.file_descriptors {
+            if super::available_file_descriptors().is_some_and(|available| available < free) {
+                results.push(ObservableResources::FileDescriptors);
             }
         }
 
@@ -61,15 +102,24 @@ mod tests {
 
     #[test]
     fn in_boundaries() {
-        let checker = Checker::new(Some(u64::MIN), Some(f32::MAX));
+        let checker = Checker::new(Some(u64::MIN), Some(f32::MAX))
+            .with_disk(Some(u64::MIN), vec!["/".into()])
+            .with_file_descriptors(Some(u64::MIN));
         assert_eq!(checker.breakaways(), vec![]);
         assert!(checker.in_boundaries());
     }
     #[test]
     fn not_in_boundaries() {
-        let checker = Checker::new(Some(u64::MAX), Some(f32::MIN));
+        let checker = Checker::new(Some(u64::MAX), Some(f32::MIN))
+            .with_disk(Some(u64::MAX), vec!["/".into()])
+            .with_file_descriptors(Some(u64::MAX));
         use ObservableResources::*;
-        assert_eq!(checker.breakaways(), vec![Memory, CPU]);
+        let expected = if super::super::available_file_descriptors() == Some(u64::MAX) {
+            vec![Memory, CPU, Disk]
+        } else {
+            vec![Memory, CPU, Disk, FileDescriptors]
+        };
+        assert_eq!(checker.breakaways(), expected);
         assert!(!checker.in_boundaries());
     }
 }
@@ -1,5 +1,7 @@
 pub mod check;
 
+use std::path::Path;
+
 #[derive(Debug)]
 pub struct AvailableResources {
     /// Available memory in bytes
@@ -16,6 +18,47 @@ pub fn available() -> AvailableResources {
     AvailableResources { memory, cpu }
 }
 
+/// Returns the bytes available to unprivileged users on the file system containing path.
+///
+/// Returns None when the file system cannot be queried, e.g. because path does not exist.
+pub fn available_disk<P: AsRef<Path>>(path: P) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_ref().as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is a valid C string and stat is only read when statvfs succeeded.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Returns how many file descriptors can still be opened by this process.
+///
+/// Returns None when the limit or the open file descriptors are unknown.
+pub fn available_file_descriptors() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: limit is a valid rlimit that getrlimit writes into.
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    if limit.rlim_cur == libc::RLIM_INFINITY {
+        return Some(u64::MAX);
+    }
+    let open = std::fs::read_dir("/proc/self/fd")
+        .or_else(|_| std::fs::read_dir("/dev/fd"))
+        .ok()?
+        .count() as u64;
+    #[allow(clippy::unnecessary_cast)]
+    Some((limit.rlim_cur as u64).saturating_sub(open))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -25,4 +68,15 @@ mod tests {
         assert!(result.memory > 0);
         assert!(result.cpu > 0.0);
     }
+
+    #[test]
+    fn available_disk() {
+        assert!(super::available_disk("/").is_some());
+        assert_eq!(super::available_disk("/does/not/exist"), None);
+    }
+
+    #[test]
+    fn available_file_descriptors() {
+        assert!(super::available_file_descriptors().unwrap() > 0);
+    }
 }
@@ -4,7 +4,7 @@
 
 use std::collections::HashMap;
 
-use crate::models::Specifier;
+use crate::models::{Labels, Specifier};
 
 use super::port::Protocol;
 
@@ -42,6 +42,12 @@ pub struct Result {
         feature = "serde_support",
         serde(skip_serializing_if = "Option::is_none", default)
     )]
+    /// Quality of detection of the VT, which generated the result, from 0 to 100
+    pub qod: Option<u8>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
     /// Port
     pub port: Option<i16>,
     #[cfg_attr(
@@ -63,6 +69,110 @@ pub struct Result {
     )]
     /// Details are only set on status and can be ignored
     pub detail: Option<Detail>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Packet capture file containing the traffic of the script when the result was created
+    pub pcap: Option<String>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Bytes the script transferred to and from the host when the result was created
+    pub traffic: Option<Traffic>,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Labels::is_empty", default)
+    )]
+    /// Labels of the host given within the target of the scan
+    pub labels: Labels,
+
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Outcome of the auto-triage rules that matched the result when it was stored
+    pub triage: Option<Triage>,
+}
+
+/// Action of an auto-triage rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TriageAction {
+    /// Hides the result unless suppressed results are requested
+    Suppress,
+    /// Marks the result as requiring attention
+    Escalate,
+}
+
+/// Auto-triage rule that matched a result
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct TriageStep {
+    /// Name of the rule
+    pub rule: String,
+    /// Conditions of the rule the result matched
+    pub matched: Vec<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Action of the rule
+    pub action: Option<TriageAction>,
+}
+
+/// Outcome of the auto-triage of a result
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Triage {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Labels::is_empty", default)
+    )]
+    /// Tags added by the matched rules
+    pub tags: Labels,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Whether the last matched rule with an action suppressed the result
+    pub suppressed: bool,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Whether the last matched rule with an action escalated the result
+    pub escalated: bool,
+    /// Matched rules in the order they were evaluated
+    pub trace: Vec<TriageStep>,
+}
+
+/// Bytes a script sent to and received from a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Traffic {
+    /// Bytes sent to the host
+    pub sent: u64,
+    /// Bytes received from the host
+    pub received: u64,
+}
+
+impl Traffic {
+    /// Returns the sum of sent and received bytes
+    pub fn total(&self) -> u64 {
+        self.sent + self.received
+    }
 }
 
 /// Host Details information
@@ -2,7 +2,10 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use super::{scanner_preference::ScanPreference, target::Target, vt::VT};
+use super::{
+    normalize_host, scanner_preference::ScanPreference, target::Host, target::Target, vt::VT,
+    Result,
+};
 
 pub type ScanId = String;
 
@@ -27,4 +30,100 @@ pub struct Scan {
     pub scan_preferences: Vec<ScanPreference>,
     /// List of VTs to execute for the target
     pub vts: Vec<VT>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The scan this scan was cloned from, when it is a re-run
+    pub lineage: Option<Lineage>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The origin of a scan restored from a bundle of another instance, such a scan is read-only
+    pub imported: Option<Imported>,
+}
+
+/// Relation of a cloned scan to the scans it was cloned from
+///
+/// All runs cloned from the same scan, directly or via another clone, share the same root and
+/// are numbered in the order of their creation, the root itself being run 1.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Lineage {
+    /// ID of the scan this scan was cloned from
+    pub parent: ScanId,
+    /// ID of the first scan of the series
+    pub root: ScanId,
+    /// Number of the run within the series
+    pub run: usize,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The hosts and VTs scanned again when the run is a rescan of a subset of its parent
+    pub rescan: Option<Rescan>,
+}
+
+/// Hosts and VTs of a finished scan that are scanned again, e.g. to verify a fix
+///
+/// The results of the parent outside of the subset are carried over into the rescan, so that it
+/// contains the results of all hosts and VTs of its parent.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Rescan {
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Hosts scanned again, all hosts of the parent when empty
+    pub hosts: Vec<Host>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// OIDs of the VTs run again, all VTs of the parent when empty
+    pub vts: Vec<String>,
+}
+
+impl Rescan {
+    /// Returns true when the result of the parent is replaced by the results of the rescan.
+    ///
+    /// Results without an OID, e.g. the start and end of a host, are replaced when their host is
+    /// scanned again.
+    pub fn covers(&self, result: &Result) -> bool {
+        let host = self.hosts.is_empty()
+            || [&result.ip_address, &result.hostname]
+                .into_iter()
+                .flatten()
+                .filter_map(|x| normalize_host(Some(x), None))
+                .any(|x| {
+                    self.hosts
+                        .iter()
+                        .any(|y| normalize_host(Some(y), None).as_ref() == Some(&x))
+                });
+        let vt = match &result.oid {
+            Some(oid) => self.vts.is_empty() || self.vts.contains(oid),
+            None => true,
+        };
+        host && vt
+    }
+}
+
+/// Origin of a scan restored from an exported bundle
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Imported {
+    /// ID of the scan on the exporting instance
+    pub scan_id: ScanId,
+    /// Version of the feed the exporting instance used
+    pub feed_version: String,
+    /// Unix timestamp of the export
+    pub exported: u64,
 }
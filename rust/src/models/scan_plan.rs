@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use crate::models::Host;
+
+/// Decision of a dry run whether a VT is executed on a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+#[cfg_attr(feature = "serde_support", serde(rename_all = "snake_case"))]
+pub enum PlannedStatus {
+    /// All keys and ports the VT depends on are known
+    Run,
+    /// The VT runs only when a previously executed VT sets a missing key or port
+    Conditional,
+    /// The VT does not run
+    Skipped,
+}
+
+/// A VT within the plan of a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct PlannedVT {
+    /// OID of the VT
+    pub oid: String,
+    /// Name of the VT
+    pub name: String,
+    /// Stage the VT is executed in
+    pub stage: String,
+    /// Whether the VT is executed
+    pub status: PlannedStatus,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// The first key or port that prevents the VT from running
+    pub reason: Option<String>,
+    /// Maximum execution time of the VT in seconds
+    pub timeout: u64,
+}
+
+/// The VTs of a single host in the order they are executed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct HostPlan {
+    pub host: Host,
+    pub vts: Vec<PlannedVT>,
+}
+
+impl HostPlan {
+    /// Returns the number of VTs with the given status.
+    pub fn count(&self, status: PlannedStatus) -> usize {
+        self.vts.iter().filter(|x| x.status == status).count()
+    }
+
+    /// Returns the sum of the timeouts of all VTs that are not skipped.
+    pub fn max_duration(&self) -> u64 {
+        self.vts
+            .iter()
+            .filter(|x| x.status != PlannedStatus::Skipped)
+            .map(|x| x.timeout)
+            .sum()
+    }
+}
+
+/// Result of a dry run of a scan
+///
+/// Contains the VTs that would be executed on each host without executing any of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ScanPlan {
+    pub scan_id: String,
+    pub hosts: Vec<HostPlan>,
+    /// Number of VTs that run for sure
+    pub run: usize,
+    /// Number of VTs that depend on keys or ports set during the scan
+    pub conditional: usize,
+    /// Number of VTs that are skipped
+    pub skipped: usize,
+    /// Upper bound of the scan duration in seconds
+    ///
+    /// Hosts are scanned one after another, so this is the sum of the timeouts of all VTs that are
+    /// not skipped.
+    pub max_duration: u64,
+}
+
+impl ScanPlan {
+    /// Creates a plan out of the plans of each host and calculates the totals.
+    pub fn new(scan_id: String, hosts: Vec<HostPlan>) -> Self {
+        let sum = |status| hosts.iter().map(|x| x.count(status)).sum();
+        Self {
+            scan_id,
+            run: sum(PlannedStatus::Run),
+            conditional: sum(PlannedStatus::Conditional),
+            skipped: sum(PlannedStatus::Skipped),
+            max_duration: hosts.iter().map(|x| x.max_duration()).sum(),
+            hosts,
+        }
+    }
+}
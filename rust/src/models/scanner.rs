@@ -5,7 +5,7 @@
 use async_trait::async_trait;
 use thiserror::Error;
 
-use super::{Scan, Status};
+use super::{Scan, ScanPlan, Status};
 
 /// Contains results of a scan as well as identification factors and statuses.
 ///
@@ -63,6 +63,15 @@ pub trait ScanResultFetcher {
     }
 }
 
+/// Resolves what a scan would execute without running it
+#[async_trait]
+pub trait ScanPlanner {
+    /// Returns the VTs that would be executed on each host of the scan.
+    async fn plan_scan(&self, _: &Scan) -> Result<ScanPlan, Error> {
+        Err(Error::NotSupported("dry run".to_string()))
+    }
+}
+
 /// Is a scanner implementation primarily for testing purposes.
 ///
 /// It is holding call back functions so that it is easier to implement a scanner for testing
@@ -189,17 +198,27 @@ impl ScanResultFetcher for Lambda {
     }
 }
 
+impl ScanPlanner for Lambda {}
+
 /// Combines all traits needed for a scanner.
 #[async_trait]
-pub trait Scanner: ScanStarter + ScanStopper + ScanDeleter + ScanResultFetcher {}
+pub trait Scanner:
+    ScanStarter + ScanStopper + ScanDeleter + ScanResultFetcher + ScanPlanner
+{
+}
 
-impl<T> Scanner for T where T: ScanStarter + ScanStopper + ScanDeleter + ScanResultFetcher {}
+impl<T> Scanner for T where
+    T: ScanStarter + ScanStopper + ScanDeleter + ScanResultFetcher + ScanPlanner
+{
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum ObservableResources {
     CPU,
     Memory,
     IO,
+    Disk,
+    FileDescriptors,
 }
 
 impl std::fmt::Display for ObservableResources {
@@ -208,6 +227,8 @@ impl std::fmt::Display for ObservableResources {
             Self::CPU => write!(f, "CPU"),
             Self::Memory => write!(f, "Memory"),
             Self::IO => write!(f, "IO"),
+            Self::Disk => write!(f, "Disk"),
+            Self::FileDescriptors => write!(f, "File descriptors"),
         }
     }
 }
@@ -226,6 +247,8 @@ pub enum Error {
     ScanNotFound(String),
     #[error("Unable to schedule scan {id}: {reason}")]
     SchedulingError { id: String, reason: String },
+    #[error("Not supported by this scanner: {0}")]
+    NotSupported(String),
 }
 
 fn display_resources(v: &[ObservableResources]) -> String {
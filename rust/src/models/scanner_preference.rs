@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use thiserror::Error;
+
 /// Configuration preference for the scanner
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(
@@ -50,3 +52,719 @@ pub struct ScanPreferenceInformation {
     /// Description of the scan preference
     pub description: &'static str,
 }
+
+/// A scan preference value that does not match the type of the default value
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid value '{value}' for scan preference {id}, expected {expected}.")]
+pub struct InvalidPreference {
+    /// The ID of the scan preference
+    pub id: String,
+    /// The given value
+    pub value: String,
+    /// Description of the expected values
+    pub expected: &'static str,
+}
+
+impl ScanPreferenceInformation {
+    /// Returns the information of a known scan preference.
+    pub fn find(id: &str) -> Option<&'static Self> {
+        PREFERENCES.iter().find(|x| x.id == id)
+    }
+
+    /// Verifies that the value matches the type of the default value.
+    ///
+    /// Returns the value as it is expected by openvas: booleans are either `yes` or `no`,
+    /// numbers must be integers and timeouts must not be negative.
+    pub fn normalize(&self, value: &str) -> Result<String, InvalidPreference> {
+        let invalid = |expected| InvalidPreference {
+            id: self.id.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+        let trimmed = value.trim();
+        match self.default {
+            PreferenceValue::Bool(_) => match parse_bool(value) {
+                Some(true) => Ok("yes".to_string()),
+                Some(false) => Ok("no".to_string()),
+                None => Err(invalid("yes or no")),
+            },
+            PreferenceValue::Int(_) => match trimmed.parse::<i64>() {
+                Ok(x) if x < 0 && self.id.ends_with("timeout") => {
+                    Err(invalid("a non-negative amount of seconds"))
+                }
+                Ok(x) => Ok(x.to_string()),
+                Err(_) => Err(invalid("an integer")),
+            },
+            PreferenceValue::String(_) => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Returns the value of a boolean scan preference.
+///
+/// `yes`, `true` and `1` are true, `no`, `false` and `0` are false, regardless of their case and
+/// surrounding whitespace. Other values are None.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "yes" | "true" | "1" => Some(true),
+        "no" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the value of the boolean scan preference with the id, None when it is not set or not
+/// a boolean.
+pub fn bool_preference(preferences: &[ScanPreference], id: &str) -> Option<bool> {
+    preferences
+        .iter()
+        .find(|x| x.id == id)
+        .and_then(|x| parse_bool(&x.value))
+}
+
+/// Verifies the values of the known preferences of a scan.
+///
+/// Unknown preferences are not verified as they may be settings of the used scanner.
+pub fn validate_scan_preferences(preferences: &[ScanPreference]) -> Result<(), InvalidPreference> {
+    preferences
+        .iter()
+        .filter_map(|x| ScanPreferenceInformation::find(&x.id).map(|i| i.normalize(&x.value)))
+        .try_for_each(|x| x.map(|_| ()))
+}
+
+/// The scan preferences known by the scanners with their default values.
+///
+/// Preferences that are not set within a scan use the default of the scanner configuration which
+/// usually equals the listed default.
+pub const PREFERENCES: &[ScanPreferenceInformation] = &[
+    ScanPreferenceInformation {
+        id: "auto_enable_dependencies",
+        name: "Automatic Enable Dependencies",
+        default: PreferenceValue::Bool(true),
+        description: "OpenVAS plugins use the result of each other to execute their job. For \
+        instance, a plugin which logs into the remote SMB registry will need the results of the \
+        plugin which finds the SMB name of the remote host and the results of the plugin which \
+        attempts to log into the remote host. If you want to only select a subset of the plugins \
+        available, tracking the dependencies can quickly become tiresome. If you set this option \
+        to 'yes', openvas will automatically enable the plugins that are depended on.",
+    },
+    ScanPreferenceInformation {
+        id: "cgi_path",
+        name: "CGI Path",
+        default: PreferenceValue::String("/cgi-bin:/scripts"),
+        description: "By default, openvas looks for default CGIs in /cgi-bin and /scripts. \
+        You may change these to something else to reflect the policy of your \
+        site. The syntax of this option is the same as the shell $PATH \
+        variable: path1:path2:...",
+    },
+    ScanPreferenceInformation {
+        id: "checks_read_timeout",
+        name: "Checks Read Timeout",
+        default: PreferenceValue::Int(5),
+        description: "Number of seconds that the security checks will wait for when doing \
+        a recv(). You should increase this value if you are running openvas \
+        across a slow network slink (testing a host via a dialup connection \
+        for instance)",
+    },
+    ScanPreferenceInformation {
+        id: "non_simult_ports",
+        name: "Non simultaneous ports",
+        default: PreferenceValue::String("139, 445, 3389, Services/irc"),
+        description: "Some services (in particular SMB) do not appreciate multiple \
+        connections at the same time coming from the same host. This option \
+        allows you to prevent openvas to make two connections on the same \
+        given ports at the same time. The syntax of this option is \
+        'port1[, port2...]'. Note that you can use the KB notation of \
+        openvas to designate a service formally. Ex: '139, Services/www', \
+        will prevent openvas from making two connections at the same time on \
+        port 139 and on every port which hosts a web server.",
+    },
+    ScanPreferenceInformation {
+        id: "open_sock_max_attempts",
+        name: "Maximum Attempts to open Sockets",
+        default: PreferenceValue::Int(5),
+        description: "When a port is found as opened at the beginning of the scan, and for \
+        some reason the status changes to filtered/closed, it will not be \
+        possible to open a socket. This is the number of unsuccessful \
+        retries to open the socket before to set the port as closed. This \
+        avoids to launch plugins which need the opened port as a mandatory \
+        key, therefore it avoids an overlong scan duration. If the set value \
+        is 0 or a negative value, this option is disabled. It should be take \
+        in account that one unsuccessful attempt needs the number of retries \
+        set in 'Socket timeout retry'.",
+    },
+    ScanPreferenceInformation {
+        id: "timeout_retry",
+        name: "Socket timeout retry",
+        default: PreferenceValue::Int(5),
+        description: "Number of retries when a socket connection attempt times out. This option \
+        is different from 'Maximum Attempts to open Sockets', as after the number of retries \
+        here is reached it counts as a single attempt for open the socket.",
+    },
+    ScanPreferenceInformation {
+        id: "optimize_test",
+        name: "Optimize Test",
+        default: PreferenceValue::Bool(true),
+        description: "By default, optimize_test is enabled which means openvas does trust \
+        the remote host banners and is only launching plugins against the \
+        services they have been designed to check. For example it will check \
+        a web server claiming to be IIS only for IIS related flaws but will \
+        skip plugins testing for Apache flaws, and so on. This default \
+        behavior is used to optimize the scanning performance and to avoid \
+        false positives. If you are not sure that the banners of the remote \
+        host have been tampered with, you can disable this option.",
+    },
+    ScanPreferenceInformation {
+        id: "nasl_no_signature_check",
+        name: "Disable Signature Check",
+        default: PreferenceValue::Bool(false),
+        description: "Loads VTs and include files whose hash sum or signature cannot be \
+        verified. Only enable it for development or when the feed is verified otherwise.",
+    },
+    ScanPreferenceInformation {
+        id: "plugins_timeout",
+        name: "Plugins Timeout",
+        default: PreferenceValue::Int(5),
+        description: "This is the maximum lifetime, in seconds of a plugin. It may happen \
+        that some plugins are slow because of the way they are written or \
+        the way the remote server behaves. This option allows you to make \
+        sure your scan is never caught in an endless loop because of a \
+        non-finishing plugin. Doesn't affect ACT_SCANNER plugins, use \
+        'ACT_SCANNER plugins timeout' for them instead. The script_timeout of a plugin takes \
+        precedence. When it is not set the plugin_timeout configured for openvasd applies.",
+    },
+    ScanPreferenceInformation {
+        id: "report_host_details",
+        name: "Report Host Details",
+        default: PreferenceValue::Bool(true),
+        description: "Host Details are general Information about a Host collected during a scan. \
+        These are used internally for plugins, but it is also possible to report these \
+        as results. In order for this option to work the Plugin 'Host Details' with the OID \
+        1.3.6.1.4.1.25623.1.0.103997 must also be in the VTs list, as this plugin is responsible \
+        for doing the actual reporting.",
+    },
+    ScanPreferenceInformation {
+        id: "safe_checks",
+        name: "Safe Checks",
+        default: PreferenceValue::Bool(true),
+        description: "Most of the time, openvas attempts to reproduce an exceptional \
+        condition to determine if the remote services are vulnerable to \
+        certain flaws. This includes the reproduction of buffer overflows or \
+        format strings, which may make the remote server crash. If you set \
+        this option to 'true', openvas will disable the plugins which have \
+        the potential to crash the remote services, and will at the same \
+        time make several checks rely on the banner of the service tested \
+        instead of its behavior towards a certain input. This reduces false \
+        positives and makes openvas nicer towards your network, however this \
+        may make you miss important vulnerabilities (as a vulnerability \
+        affecting a given service may also affect another one). Builtin functions \
+        annotated as intrusive or destructive are refused while it is enabled, the \
+        refusal is reported as log result of the calling plugin.",
+    },
+    ScanPreferenceInformation {
+        id: "scanner_plugins_timeout",
+        name: "ACT_SCANNER plugins timeout",
+        default: PreferenceValue::Int(36000),
+        description: "Like 'Plugins Timeout', but for ACT_SCANNER plugins.",
+    },
+    ScanPreferenceInformation {
+        id: "time_between_request",
+        name: "Time between Requests",
+        default: PreferenceValue::Int(0),
+        description: "Some devices do not appreciate quick connection establishment and \
+        termination neither quick request. This option allows you to set a \
+        wait time between two actions like to open a tcp socket, to send a \
+        request through the open tcp socket, and to close the tcp socket. \
+        This value should be given in milliseconds. If the set value is 0 \
+        (default value), this option is disabled and there is no wait time \
+        between requests.",
+    },
+    ScanPreferenceInformation {
+        id: "unscanned_closed",
+        name: "Close unscanned Port TCP",
+        default: PreferenceValue::Bool(true),
+        description: "This defines whether TCP ports that were not scanned should be treated like closed ports.",
+    },
+    ScanPreferenceInformation {
+        id: "unscanned_closed_udp",
+        name: "Close unscanned Port UDP",
+        default: PreferenceValue::Bool(true),
+        description: "This defines whether UDP ports that were not scanned should be treated as closed ports.",
+    },
+    ScanPreferenceInformation {
+        id: "expand_vhosts",
+        name: "Expand VHosts",
+        default: PreferenceValue::Bool(true),
+        description: "Whether to expand the target host's list of vhosts with values \
+        gathered from sources such as reverse-lookup queries and VT checks \
+        for SSL/TLS certificates.",
+    },
+    ScanPreferenceInformation {
+        id: "test_empty_vhost",
+        name: "Test Empty VHost",
+        default: PreferenceValue::Bool(false),
+        description: "If set to yes, the scanner will also test the target by using empty \
+        vhost value in addition to the target's associated vhost values.",
+    },
+    ScanPreferenceInformation {
+        id: "alive_test_ports",
+        name: "Alive Test Ports",
+        default: PreferenceValue::String(
+            "21-23,25,53,80,110-111,135,139,143,443,445,993,995,1723,3306,3389,5900,8080",
+        ),
+        description: "Preference to set the port list for the TCP SYN and TCP ACK alive test \
+        methods.",
+    },
+    ScanPreferenceInformation {
+        id: "test_alive_hosts_only",
+        name: "Test Alive Hosts Only",
+        default: PreferenceValue::Bool(false),
+        description: "If this option is set to 'true', openvas will scan the target list \
+        for alive hosts in a separate process while only testing those hosts \
+        which are identified as alive. This boosts the scan speed of target \
+        ranges with a high amount of dead hosts significantly.",
+    },
+    ScanPreferenceInformation {
+        id: "test_alive_wait_timeout",
+        name: "Alive Test Timeout",
+        default: PreferenceValue::Int(1),
+        description: "This option is to set how long (in sec) Boreas (alive test) waits for \
+        replies after last packet was sent.",
+    },
+    ScanPreferenceInformation {
+        id: "table_driven_lsc",
+        name: "Table Driven LSC",
+        default: PreferenceValue::Bool(true),
+        description: "This option will enable table driven local security Checks (LSC). This means \
+        gathered packages are sent to an specialized scanner. This is far more efficient than doing \
+        checks via NASL.",
+    },
+    ScanPreferenceInformation {
+        id: "dry_run",
+        name: "Dry Run",
+        default: PreferenceValue::Bool(false),
+        description: "A dry run is a simulated scan, with no actual host scanned. This mode \
+        is useful for automated testing and also to check up, if the setup is actually working.",
+    },
+    ScanPreferenceInformation {
+        id: "results_per_host",
+        name: "Results per Host",
+        default: PreferenceValue::Int(10),
+        description: "Amount of fake results generated per each host in the target \
+        list for a dry run scan.",
+    },
+    ScanPreferenceInformation {
+        id: "dns_cache_ttl",
+        name: "DNS Cache TTL",
+        default: PreferenceValue::Int(300),
+        description: "Time in seconds a resolved host name is cached and shared between all \
+        scripts of a scan.",
+    },
+    ScanPreferenceInformation {
+        id: "dns_cache_negative_ttl",
+        name: "DNS Cache Negative TTL",
+        default: PreferenceValue::Int(30),
+        description: "Time in seconds a failed host name resolution is cached before it is \
+        retried.",
+    },
+    ScanPreferenceInformation {
+        id: "max_connections_per_second",
+        name: "Maximum Connections per Second",
+        default: PreferenceValue::Int(0),
+        description: "Maximum number of new connections opened per second over all hosts of a \
+        scan. This helps to not trigger IDS thresholds. 0 disables the limit.",
+    },
+    ScanPreferenceInformation {
+        id: "max_sockets_per_host",
+        name: "Maximum Sockets per Host",
+        default: PreferenceValue::Int(0),
+        description: "Maximum number of concurrently open sockets to a single host. This helps \
+        to not exhaust NAT tables. 0 disables the limit. With adaptive concurrency it is the \
+        upper bound of the concurrency, 0 uses 16.",
+    },
+    ScanPreferenceInformation {
+        id: "adaptive_concurrency",
+        name: "Adaptive Concurrency",
+        default: PreferenceValue::Bool(false),
+        description: "Tracks the round trip time and the rate of timed out connections of each \
+        host. The amount of concurrently open sockets to a host grows while it answers and is \
+        halved on each timeout, socket timeouts are shortened for fast hosts and extended for \
+        slow ones. Only used by the openvasd scanner type.",
+    },
+    ScanPreferenceInformation {
+        id: "network_namespace",
+        name: "Network Namespace",
+        default: PreferenceValue::String(""),
+        description: "Name of the Linux network namespace the network operations of the scan \
+        are executed in. The namespace must be listed in the network_namespaces setting of the \
+        scanner configuration. Empty uses the namespace of openvasd.",
+    },
+    ScanPreferenceInformation {
+        id: "severity_profile",
+        name: "Severity Profile",
+        default: PreferenceValue::String(""),
+        description: "Name of the profile mapping the severity of results to levels when the \
+        results and reports of the scan are served. The profile must be configured within the \
+        severity settings of openvasd. Empty uses the profile of the tenant or the default \
+        profile.",
+    },
+    ScanPreferenceInformation {
+        id: "pcap_max_file_size",
+        name: "Maximum Packet Capture File Size",
+        default: PreferenceValue::Int(10485760),
+        description: "Size in bytes after which a new pcap file is started. 0 disables the \
+        rotation.",
+    },
+    ScanPreferenceInformation {
+        id: "pcap_max_files",
+        name: "Maximum Packet Capture Files",
+        default: PreferenceValue::Int(5),
+        description: "Number of pcap files that are kept per VT and host, older files are \
+        removed. 0 keeps all files.",
+    },
+    ScanPreferenceInformation {
+        id: "pcap_oids",
+        name: "Packet Capture OIDs",
+        default: PreferenceValue::String(""),
+        description: "Comma separated OIDs of the VTs to record. Empty records all VTs.",
+    },
+    ScanPreferenceInformation {
+        id: "regex_strict_compat",
+        name: "Strict Regular Expression Compatibility",
+        default: PreferenceValue::Bool(false),
+        description: "Interpret the patterns of ereg, egrep, ereg_replace and eregmatch like the \
+        POSIX implementation of openvas-scanner. Per default PCRE constructs commonly used within \
+        the feed are accepted as well.",
+    },
+    ScanPreferenceInformation {
+        id: "builtins_allowed",
+        name: "Allowed Builtin Functions",
+        default: PreferenceValue::String(""),
+        description: "Comma separated builtin functions or modules of them, e.g. `string`, the VTs \
+        may call when the openvasd scanner type is used. Every other builtin function is disabled. \
+        Empty allows all builtin functions.",
+    },
+    ScanPreferenceInformation {
+        id: "builtins_disabled",
+        name: "Disabled Builtin Functions",
+        default: PreferenceValue::String(""),
+        description: "Comma separated builtin functions or modules of them, e.g. `raw_ip`, the VTs \
+        must not call when the openvasd scanner type is used. A VT calling a disabled function is \
+        aborted and an error result is reported for it.",
+    },
+    ScanPreferenceInformation {
+        id: "report_script_timing",
+        name: "Report Script Timing",
+        default: PreferenceValue::Bool(false),
+        description: "Adds a log result containing the wall time of each executed VT.",
+    },
+    ScanPreferenceInformation {
+        id: "include_deprecated",
+        name: "Include Deprecated VTs",
+        default: PreferenceValue::Bool(false),
+        description: "Schedules VTs marked as deprecated when the scan requests them. Per \
+        default deprecated VTs are skipped.",
+    },
+    ScanPreferenceInformation {
+        id: "scan_budget",
+        name: "Scan Budget",
+        default: PreferenceValue::Int(0),
+        description: "Wall-clock time in seconds the scan may take when the openvasd scanner \
+        type is used. Once it is exhausted no further VT is launched, the scan ends as partial \
+        and a log result per host lists the VTs that were skipped. 0 disables the budget.",
+    },
+    ScanPreferenceInformation {
+        id: "scan_budget_grace",
+        name: "Scan Budget Grace Period",
+        default: PreferenceValue::Int(60),
+        description: "Time in seconds VTs still running when the scan budget is exhausted may \
+        continue before they are cancelled.",
+    },
+    ScanPreferenceInformation {
+        id: "max_script_bytes",
+        name: "Maximum Script Bytes",
+        default: PreferenceValue::Int(0),
+        description: "Maximum of bytes a VT may send to and receive from a host via sockets and \
+        http. A VT exceeding it is stopped and reported with an error, 0 disables the cap. Only \
+        used by the openvasd scanner type.",
+    },
+    ScanPreferenceInformation {
+        id: "abuse_signal_rate",
+        name: "Abuse Signal Rate",
+        default: PreferenceValue::String("0"),
+        description: "Share between 0 and 1 of the recent connections to a host that may be reset \
+        or rejected as administratively prohibited before scanning of the host is stopped. The \
+        remaining VTs of the host are skipped and the reason is reported with an error, 0 \
+        disables it. Only used by the openvasd scanner type.",
+    },
+    ScanPreferenceInformation {
+        id: "abuse_signal_window",
+        name: "Abuse Signal Window",
+        default: PreferenceValue::Int(50),
+        description: "Amount of recent connections to a host the share of abuse_signal_rate is \
+        calculated from.",
+    },
+    ScanPreferenceInformation {
+        id: "stop_scan_markers",
+        name: "Stop Scan Markers",
+        default: PreferenceValue::String(""),
+        description: "Comma separated markers that stop the scan of a host when it sends one of \
+        them, e.g. a header or banner asking not to be scanned. Only used by the openvasd \
+        scanner type.",
+    },
+    ScanPreferenceInformation {
+        id: "http_cache",
+        name: "HTTP Response Cache",
+        default: PreferenceValue::Bool(false),
+        description: "Caches the responses of HTTP GET requests of a scan so that VTs fetching \
+        the same URL of a host do not request it again. Only used by the openvasd scanner type.",
+    },
+    ScanPreferenceInformation {
+        id: "http_cache_ttl",
+        name: "HTTP Response Cache TTL",
+        default: PreferenceValue::Int(300),
+        description: "Seconds a cached HTTP response is used.",
+    },
+    ScanPreferenceInformation {
+        id: "http_cache_max_bytes",
+        name: "HTTP Response Cache Size",
+        default: PreferenceValue::Int(10485760),
+        description: "Maximum of bytes of all cached HTTP responses of a scan, the least \
+        recently used responses are dropped first.",
+    },
+    ScanPreferenceInformation {
+        id: "http_user_agent",
+        name: "HTTP User-Agent",
+        default: PreferenceValue::String(""),
+        description: "User-Agent sent with the HTTP requests of the built-in HTTP functions. Empty \
+        sends none unless a VT sets it.",
+    },
+    ScanPreferenceInformation {
+        id: "http_extra_headers",
+        name: "HTTP Extra Headers",
+        default: PreferenceValue::String(""),
+        description: "Headers sent with every HTTP request of the built-in HTTP functions, one \
+        `Name: value` per line, e.g. a header identifying the scan.",
+    },
+    ScanPreferenceInformation {
+        id: "http_target_headers",
+        name: "HTTP Target Headers",
+        default: PreferenceValue::String(""),
+        description: "Headers sent with the HTTP requests to a single target or virtual host, one \
+        `<target> Name: value` per line. They replace extra headers of the same name.",
+    },
+    ScanPreferenceInformation {
+        id: "http_max_redirects",
+        name: "HTTP Maximum Redirects",
+        default: PreferenceValue::Int(0),
+        description: "Maximum of redirects a request of the built-in HTTP functions follows. 0 \
+        follows none.",
+    },
+    ScanPreferenceInformation {
+        id: "http_redirect_same_host",
+        name: "HTTP Redirects To Same Host Only",
+        default: PreferenceValue::Bool(true),
+        description: "Only follows redirects to the host of the request. When disabled redirects \
+        to other host names are requested from the same target address.",
+    },
+    ScanPreferenceInformation {
+        id: "http_record_redirects",
+        name: "HTTP Record Redirects",
+        default: PreferenceValue::Bool(false),
+        description: "Stores each followed redirect within the knowledge base as \
+        `www/<port>/redirects<item>`.",
+    },
+    ScanPreferenceInformation {
+        id: "http_version",
+        name: "HTTP Version",
+        default: PreferenceValue::String("auto"),
+        description: "HTTP version of the requests of the built-in HTTP functions: auto, 1.0, 1.1 \
+        or 2. Auto uses HTTP/2 when the server selects it via ALPN and HTTP/1.1 otherwise.",
+    },
+    ScanPreferenceInformation {
+        id: "kb_seed_scan_id",
+        name: "Knowledge Base Seed Scan",
+        default: PreferenceValue::String(""),
+        description: "ID of a previous scan of the same targets. The detection results of that \
+        scan are imported into the knowledge base before the first VT runs. Empty disables the \
+        import.",
+    },
+    ScanPreferenceInformation {
+        id: "kb_seed_namespaces",
+        name: "Knowledge Base Seed Namespaces",
+        default: PreferenceValue::String("Host/OS/,HostDetails/OS/,Services/,Known/"),
+        description: "Comma separated prefixes of the KB items that are imported from the \
+        previous scan.",
+    },
+    ScanPreferenceInformation {
+        id: "kb_seed_max_age",
+        name: "Knowledge Base Seed Maximum Age",
+        default: PreferenceValue::Int(86400),
+        description: "Seconds since the last modification after which the knowledge base of the \
+        previous scan is not imported anymore. 0 disables the limit.",
+    },
+    ScanPreferenceInformation {
+        id: "kb_seed_skip_families",
+        name: "Knowledge Base Seed Skipped Families",
+        default: PreferenceValue::String(""),
+        description: "Comma separated VT families that are not run on hosts whose knowledge base \
+        was imported.",
+    },
+    ScanPreferenceInformation {
+        id: "nmap_import",
+        name: "Nmap Import",
+        default: PreferenceValue::String(""),
+        description: "Nmap XML report whose open ports and services of the hosts are imported \
+        into the knowledge base, the port scanners are not run on them. The report must be given \
+        inline, paths are refused. Empty disables the import.",
+    },
+    ScanPreferenceInformation {
+        id: "host_name_lookup",
+        name: "Host Name Lookup",
+        default: PreferenceValue::Bool(true),
+        description: "Resolves the host name, FQDN and reverse DNS name of each host before its \
+        first VT runs. Disable it for stealth scans, host names are then only known for targets \
+        that are not IP addresses.",
+    },
+    ScanPreferenceInformation {
+        id: "max_additional_hosts",
+        name: "Maximum Additional Hosts",
+        default: PreferenceValue::Int(0),
+        description: "Maximum of hosts scripts may add to a running scan, e.g. virtual hosts \
+        or neighbours found during discovery. Added hosts are scanned after the target hosts. \
+        0 disables adding hosts.",
+    },
+    ScanPreferenceInformation {
+        id: "additional_hosts_scope",
+        name: "Additional Hosts Scope",
+        default: PreferenceValue::String(""),
+        description: "Comma separated networks, addresses, host names and domains (starting \
+        with a dot) added hosts must be within. Empty allows every host that is not excluded.",
+    },
+    ScanPreferenceInformation {
+        id: "ssh_host_key_policy",
+        name: "SSH Host Key Policy",
+        default: PreferenceValue::String("record-only"),
+        description: "Verification of the host keys of SSH servers. record-only accepts every \
+        key and reports keys that differ from a known or previously seen key, accept-new \
+        additionally rejects those keys and strict only accepts keys listed in ssh_known_hosts.",
+    },
+    ScanPreferenceInformation {
+        id: "ssh_known_hosts",
+        name: "SSH Known Hosts",
+        default: PreferenceValue::String(""),
+        description: "Known host keys in the OpenSSH known_hosts format. Hashed host names, \
+        wildcards and @revoked markers are supported.",
+    },
+    ScanPreferenceInformation {
+        id: "ssh_session_pool_size",
+        name: "SSH Session Pool Size",
+        default: PreferenceValue::Int(8),
+        description: "Maximum number of idle authenticated SSH sessions that are kept to be \
+        reused by later VTs connecting to the same host with the same credentials. 0 disables \
+        the reuse.",
+    },
+    ScanPreferenceInformation {
+        id: "ssh_session_idle_timeout",
+        name: "SSH Session Idle Timeout",
+        default: PreferenceValue::Int(60),
+        description: "Time in seconds an idle SSH session is kept before it is disconnected.",
+    },
+    ScanPreferenceInformation {
+        id: "disable_credential_checks",
+        name: "Disable Default Credential Checks",
+        default: PreferenceValue::Bool(false),
+        description: "Refuses all login attempts with guessed credentials, i.e. disables \
+        default credential and brute-force style checks.",
+    },
+    ScanPreferenceInformation {
+        id: "max_credential_attempts",
+        name: "Maximum Credential Attempts",
+        default: PreferenceValue::Int(25),
+        description: "Maximum number of login attempts with guessed credentials per service of \
+        a host over all VTs of a scan. Attempts to a service that reported a locked account are \
+        refused as well. 0 disables the limit.",
+    },
+    ScanPreferenceInformation {
+        id: "credential_attempt_delay",
+        name: "Credential Attempt Delay",
+        default: PreferenceValue::Int(500),
+        description: "Delay in milliseconds between the login attempts with guessed \
+        credentials to a service of a host.",
+    },
+    ScanPreferenceInformation {
+        id: "include_once",
+        name: "Include Once",
+        default: PreferenceValue::Bool(true),
+        description: "Interprets each file included by a VT only once per VT run, further \
+        includes of the same file are skipped. Disable it to interpret a file on each include.",
+    },
+    ScanPreferenceInformation {
+        id: "preload_includes",
+        name: "Preload Includes",
+        default: PreferenceValue::String(""),
+        description: "Comma separated include files that are interpreted once before the first \
+        VT runs. Their functions and global variables are shared by all VTs of the scan, which \
+        skip including them again. Their top-level code must not depend on the scanned host. \
+        Empty preloads no file.",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(id: &str, value: &str) -> Result<String, InvalidPreference> {
+        ScanPreferenceInformation::find(id)
+            .unwrap()
+            .normalize(value)
+    }
+
+    #[test]
+    fn normalize_values() {
+        assert_eq!(normalize("optimize_test", "true"), Ok("yes".to_string()));
+        assert_eq!(normalize("optimize_test", "0"), Ok("no".to_string()));
+        assert_eq!(
+            normalize("nasl_no_signature_check", "No"),
+            Ok("no".to_string())
+        );
+        assert!(normalize("safe_checks", "maybe").is_err());
+        assert_eq!(normalize("plugins_timeout", " 320"), Ok("320".to_string()));
+        assert!(normalize("plugins_timeout", "-1").is_err());
+        assert!(normalize("scanner_plugins_timeout", "ten").is_err());
+        assert_eq!(
+            normalize("open_sock_max_attempts", "-1"),
+            Ok("-1".to_string())
+        );
+        assert_eq!(normalize("cgi_path", "/cgi"), Ok("/cgi".to_string()));
+    }
+
+    #[test]
+    fn bool_preferences() {
+        for value in ["1", "yes", "True", " YES "] {
+            assert_eq!(parse_bool(value), Some(true), "{value}");
+        }
+        for value in ["0", "no", "FALSE", "No\n"] {
+            assert_eq!(parse_bool(value), Some(false), "{value}");
+        }
+        assert_eq!(parse_bool("maybe"), None);
+        let preferences = [ScanPreference {
+            id: "include_once".to_string(),
+            value: "No".to_string(),
+        }];
+        assert_eq!(bool_preference(&preferences, "include_once"), Some(false));
+        assert_eq!(bool_preference(&preferences, "safe_checks"), None);
+    }
+
+    #[test]
+    fn validate_preferences() {
+        let pref = |id: &str, value: &str| ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        };
+        assert!(
+            validate_scan_preferences(&[pref("unknown", "x"), pref("safe_checks", "no")]).is_ok()
+        );
+        let err = validate_scan_preferences(&[pref("checks_read_timeout", "x")]).unwrap_err();
+        assert_eq!(err.id, "checks_read_timeout");
+    }
+}
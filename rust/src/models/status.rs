@@ -21,6 +21,84 @@ pub struct Status {
     pub status: Phase,
     /// Information about the hosts of a running scan
     pub host_info: Option<HostInfo>,
+    /// Latest signs of progress of a running scan
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    pub heartbeat: Option<Heartbeat>,
+    /// True when a running scan did not make any progress for longer than allowed
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "std::ops::Not::not", default)
+    )]
+    pub stalled: bool,
+}
+
+/// Signs of progress of a running scan
+///
+/// All timestamps are seconds since the unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Heartbeat {
+    /// Timestamp of the latest progress, e.g. a started or finished script or a new result
+    pub time: u64,
+    /// Timestamp of the latest result
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    pub last_result: Option<u64>,
+    /// OID of the latest started script
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    pub last_script: Option<String>,
+    /// Timestamp the latest script was started at
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    pub last_script_started: Option<u64>,
+}
+
+impl Heartbeat {
+    /// Creates a heartbeat for progress at the given time.
+    pub fn at(time: u64) -> Self {
+        Self {
+            time,
+            ..Default::default()
+        }
+    }
+
+    /// Marks a new result at the given time.
+    pub fn result(&mut self, time: u64) {
+        self.time = self.time.max(time);
+        self.last_result = Some(time);
+    }
+
+    /// Marks the start of a script at the given time.
+    pub fn script_started(&mut self, oid: &str, time: u64) {
+        self.time = self.time.max(time);
+        self.last_script = Some(oid.to_string());
+        self.last_script_started = Some(time);
+    }
+
+    /// Keeps the latest information of both heartbeats.
+    pub fn merge(&mut self, other: &Heartbeat) {
+        self.time = self.time.max(other.time);
+        if other.last_result > self.last_result {
+            self.last_result = other.last_result;
+        }
+        if other.last_script_started > self.last_script_started {
+            self.last_script_started = other.last_script_started;
+            self.last_script.clone_from(&other.last_script);
+        }
+    }
 }
 
 impl Status {
@@ -50,6 +128,22 @@ impl Status {
         if status.end_time.is_some() {
             self.end_time = status.end_time;
         }
+
+        if let Some(ref heartbeat) = status.heartbeat {
+            self.heartbeat
+                .get_or_insert_with(Default::default)
+                .merge(heartbeat);
+        }
+    }
+
+    /// Returns the time of the latest progress of the scan, the start time when there was no
+    /// progress yet.
+    pub fn last_progress(&self) -> Option<u64> {
+        match (&self.heartbeat, self.start_time) {
+            (Some(x), Some(start)) => Some(x.time.max(start)),
+            (Some(x), None) => Some(x.time),
+            (None, start) => start,
+        }
     }
 }
 
@@ -74,11 +168,15 @@ pub enum Phase {
     Failed,
     /// A scan has been successfully finished
     Succeeded,
+    /// A scan is held back by a blackout period and is resumed once it is over
+    Paused,
+    /// A scan has been finished, but some scripts were skipped as its time budget was exhausted
+    Partial,
 }
 
 impl Phase {
     pub fn is_running(&self) -> bool {
-        matches!(self, Self::Running | Self::Requested)
+        matches!(self, Self::Running | Self::Requested | Self::Paused)
     }
 }
 
@@ -93,6 +191,8 @@ impl FromStr for Phase {
             "failed" => Ok(Phase::Failed),
             "succeeded" => Ok(Phase::Succeeded),
             "stored" => Ok(Phase::Stored),
+            "paused" => Ok(Phase::Paused),
+            "partial" => Ok(Phase::Partial),
             _ => Err(()),
         }
     }
@@ -107,6 +207,8 @@ impl Display for Phase {
             Self::Failed => write!(f, "failed"),
             Self::Succeeded => write!(f, "succeeded"),
             Self::Stored => write!(f, "stored"),
+            Self::Paused => write!(f, "paused"),
+            Self::Partial => write!(f, "partial"),
         }
     }
 }
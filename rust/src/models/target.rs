@@ -2,10 +2,18 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+};
+
 use super::{credential::Credential, port::Port};
 
 pub type Host = String;
 
+/// User supplied labels of a host, e.g. an asset id or the environment
+pub type Labels = BTreeMap<String, String>;
+
 /// Information about a target of a scan
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(
@@ -36,6 +44,143 @@ pub struct Target {
     #[cfg_attr(feature = "serde_support", serde(default))]
     /// Only scan IP addresses that can be resolved into a DNS name.
     pub reverse_lookup_only: Option<bool>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Network interface the scan traffic is sent from. When set the addresses of this interface
+    /// are used as source addresses.
+    pub source_interface: Option<String>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// Source address of the scan traffic. Takes precedence over the addresses of
+    /// source_interface for its address family.
+    pub source_address: Option<IpAddr>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// Groups of hosts that are scanned with their own ports and credentials, e.g. a DMZ and
+    /// internal subnets within the same scan.
+    pub groups: Vec<TargetGroup>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
+    /// Labels of the hosts, they are added to each result of the host.
+    pub labels: HashMap<Host, Labels>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
+    /// Host names served by a host, e.g. the name based virtual hosts of a web server. Scripts
+    /// asking for the host name are run once per virtual host.
+    pub vhosts: HashMap<Host, Vec<String>>,
+}
+
+/// Hosts of a target that are scanned with their own ports and credentials
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct TargetGroup {
+    /// Name of the group, the hosts of each group are reported separately
+    pub name: String,
+    /// List of hosts to scan
+    pub hosts: Vec<Host>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// List of ports used for scanning, when empty the ports of the target are used
+    pub ports: Vec<Port>,
+    #[cfg_attr(feature = "serde_support", serde(default))]
+    /// List of credentials used to get access to a system, when empty the credentials of the
+    /// target are used
+    pub credentials: Vec<Credential>,
+}
+
+impl Target {
+    /// Returns the hosts of the target followed by the hosts of each group.
+    pub fn all_hosts(&self) -> impl Iterator<Item = &Host> {
+        self.hosts
+            .iter()
+            .chain(self.groups.iter().flat_map(|x| x.hosts.iter()))
+    }
+
+    /// Returns the group of a host or None when it is a host of the target itself.
+    pub fn group(&self, host: &str) -> Option<&TargetGroup> {
+        if self.hosts.iter().any(|x| x == host) {
+            return None;
+        }
+        self.groups
+            .iter()
+            .find(|x| x.hosts.iter().any(|x| x == host))
+    }
+
+    /// Returns the ports used for the given host.
+    pub fn ports_of(&self, host: &str) -> &[Port] {
+        match self.group(host) {
+            Some(group) if !group.ports.is_empty() => &group.ports,
+            _ => &self.ports,
+        }
+    }
+
+    /// Returns the credentials used for the given host.
+    pub fn credentials_of(&self, host: &str) -> &[Credential] {
+        match self.group(host) {
+            Some(group) if !group.credentials.is_empty() => &group.credentials,
+            _ => &self.credentials,
+        }
+    }
+
+    /// Returns the labels of a host.
+    pub fn labels_of(&self, host: &str) -> Option<&Labels> {
+        self.labels.get(host)
+    }
+
+    /// Returns the virtual hosts of a host.
+    pub fn vhosts_of(&self, host: &str) -> &[String] {
+        self.vhosts
+            .get(host)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Adds the labels of the host of a result.
+    ///
+    /// The host is looked up by the IP address and then by the host name of the result.
+    pub fn label_result(&self, result: &mut super::Result) {
+        let labels = [&result.ip_address, &result.hostname]
+            .into_iter()
+            .flatten()
+            .find_map(|x| self.labels_of(x));
+        if let Some(labels) = labels {
+            result
+                .labels
+                .extend(labels.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+
+    /// Returns true when a group uses other ports or credentials than the target.
+    pub fn has_group_settings(&self) -> bool {
+        self.groups
+            .iter()
+            .any(|x| !x.ports.is_empty() || !x.credentials.is_empty())
+    }
+
+    /// Maps each credential of the target and its groups using the given closure.
+    pub fn map_credentials<F, E>(mut self, f: F) -> Result<Self, E>
+    where
+        F: Fn(Credential) -> Result<Credential, E>,
+    {
+        self.credentials = self
+            .credentials
+            .into_iter()
+            .map(&f)
+            .collect::<Result<_, _>>()?;
+        for group in self.groups.iter_mut() {
+            group.credentials = std::mem::take(&mut group.credentials)
+                .into_iter()
+                .map(&f)
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(self)
+    }
 }
 
 /// Enum of possible alive test methods
@@ -52,3 +197,79 @@ pub enum AliveTestMethods {
     ConsiderAlive = 0x08,
     TcpSyn = 0x10,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PortRange, Protocol};
+
+    fn port(port: usize) -> Vec<Port> {
+        vec![Port {
+            protocol: Some(Protocol::TCP),
+            range: vec![PortRange {
+                start: port,
+                end: None,
+            }],
+        }]
+    }
+
+    #[test]
+    fn groups() {
+        let target = Target {
+            hosts: vec!["10.0.0.1".into()],
+            ports: port(22),
+            credentials: vec![Credential::default()],
+            groups: vec![
+                TargetGroup {
+                    name: "dmz".into(),
+                    hosts: vec!["192.0.2.1".into()],
+                    ports: port(443),
+                    ..Default::default()
+                },
+                TargetGroup {
+                    name: "internal".into(),
+                    hosts: vec!["10.0.1.1".into()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            target.all_hosts().collect::<Vec<_>>(),
+            vec!["10.0.0.1", "192.0.2.1", "10.0.1.1"]
+        );
+        assert!(target.group("10.0.0.1").is_none());
+        assert_eq!(target.group("192.0.2.1").unwrap().name, "dmz");
+        assert_eq!(target.ports_of("192.0.2.1"), port(443));
+        assert_eq!(target.ports_of("10.0.1.1"), port(22));
+        assert_eq!(target.credentials_of("10.0.1.1").len(), 1);
+        assert!(target.has_group_settings());
+        let target = target
+            .map_credentials::<_, ()>(|c| c.map_password(|_| Ok("***".to_string())))
+            .unwrap();
+        assert_eq!(target.credentials[0].password(), "***");
+    }
+
+    #[test]
+    fn label_result() {
+        let labels: Labels = [("asset-id".to_string(), "A-1".to_string())].into();
+        let target = Target {
+            hosts: vec!["www.example.com".into()],
+            labels: [("www.example.com".to_string(), labels.clone())].into(),
+            ..Default::default()
+        };
+        let mut result = crate::models::Result {
+            ip_address: Some("192.0.2.1".into()),
+            hostname: Some("www.example.com".into()),
+            ..Default::default()
+        };
+        target.label_result(&mut result);
+        assert_eq!(result.labels, labels);
+        let mut result = crate::models::Result {
+            ip_address: Some("192.0.2.2".into()),
+            ..Default::default()
+        };
+        target.label_result(&mut result);
+        assert!(result.labels.is_empty());
+    }
+}
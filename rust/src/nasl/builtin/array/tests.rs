@@ -59,8 +59,8 @@ mod tests {
                 NaslValue::Number(1),
                 NaslValue::Number(6),
                 NaslValue::Number(8),
-                NaslValue::String("aaaa".to_string()),
-                NaslValue::String("abbb".to_string()),
+                NaslValue::String("aaaa".into()),
+                NaslValue::String("abbb".into()),
             ]),
         );
     }
@@ -73,7 +73,7 @@ mod tests {
         t.ok(
             r#"keys(a,l);"#,
             NaslValue::Array(vec![
-                NaslValue::String("a".to_string()),
+                NaslValue::String("a".into()),
                 NaslValue::Number(0),
                 NaslValue::Number(1),
             ]),
@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines NASL functions to register detected products by their CPE.
+//!
+//! These are native implementations of the helpers of `cpe.inc` and `host_details.inc` and take
+//! precedence over them.
+
+#[cfg(test)]
+mod tests;
+
+use crate::cpe::Cpe;
+use crate::models::{self, Detail, ResultType, Source};
+use crate::nasl::prelude::*;
+use crate::storage::{types::Primitive, Field, Kb};
+
+use super::regex::make_regex;
+
+fn parse_cpe(cpe: &str) -> Result<Cpe, FunctionErrorKind> {
+    cpe.parse()
+        .map_err(|e: crate::cpe::CpeError| FunctionErrorKind::Diagnostic(e.to_string(), None))
+}
+
+fn set_kb_item(ctx: &Context, key: String, value: Primitive) -> Result<(), FunctionErrorKind> {
+    ctx.dispatcher().dispatch(
+        ctx.key(),
+        Field::KB(Kb {
+            key,
+            value,
+            expire: None,
+        }),
+    )?;
+    Ok(())
+}
+
+/// *string* **build_cpe**(value: *string*, exp: *string*, base: *string*);
+///
+/// Builds the CPE of a detected product from its version string.
+/// - value, the detected version string, e.g. `Apache/2.4.58 (Debian)`
+/// - exp, a regular expression whose first group is the version and the optional second group
+///   the update, e.g. `^([0-9.]+)`
+/// - base, the CPE of the product without version, e.g. `cpe:/a:apache:http_server:`
+///
+/// Returns the normalized CPE in the binding of base or NULL when exp does not match.
+#[nasl_function(named(value, exp, base))]
+fn build_cpe(
+    context: &Context,
+    value: &str,
+    exp: &str,
+    base: &str,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let re = make_regex(exp, true, false, context.regex_mode())?;
+    let Some(version) = re
+        .captures(value)
+        .and_then(|x| x.get(1).map(|v| (v, x.get(2))))
+    else {
+        return Ok(NaslValue::Null);
+    };
+    let mut cpe = parse_cpe(base.trim_end_matches(':'))?.with_version(version.0.as_str());
+    if let Some(update) = version.1 {
+        cpe = cpe.with_update(update.as_str());
+    }
+    let result = if base.starts_with("cpe:2.3:") {
+        cpe.to_string()
+    } else {
+        cpe.to_uri()
+    };
+    Ok(NaslValue::String(result.into()))
+}
+
+/// *void* **register_product**(cpe: *string*, location: *string*, port: *int*, proto: *string*, service: *string*, desc: *string*);
+///
+/// Registers a detected product.
+/// - cpe, the CPE of the product
+/// - location, where the product was found, e.g. a path or an URL
+/// - port, the port of the service, 0 for local detections
+/// - proto, the protocol of the port, "tcp" by default
+/// - service, the name of the service, e.g. `www`
+/// - desc, the description of the host detail
+///
+/// The product is stored within the KB:
+/// - `get_app/ports/<base>` the ports of the product
+/// - `get_app/<base>/<port>/<proto>/cpe` the CPE of the product
+/// - `get_app/<base>/<port>/<proto>/location` the location
+/// - `get_app/<base>/<port>/<proto>/service` the service, if given
+///
+/// with `<base>` being the CPE 2.2 URI without version, e.g. `cpe:/a:apache:http_server`.
+/// Additionally a host detail result with the CPE is created.
+#[nasl_function(named(cpe, location, port, proto, service, desc))]
+fn register_product(
+    context: &Context,
+    cpe: &str,
+    location: &str,
+    port: Option<i64>,
+    proto: Option<&str>,
+    service: Option<&str>,
+    desc: Option<&str>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let cpe = parse_cpe(cpe)?;
+    let base = cpe.base().to_uri();
+    let port = port.unwrap_or_default();
+    let proto = proto.unwrap_or("tcp");
+    let prefix = format!("get_app/{base}/{port}/{proto}");
+    set_kb_item(
+        context,
+        format!("get_app/ports/{base}"),
+        Primitive::Number(port),
+    )?;
+    set_kb_item(
+        context,
+        format!("{prefix}/cpe"),
+        Primitive::String(cpe.to_uri()),
+    )?;
+    set_kb_item(
+        context,
+        format!("{prefix}/location"),
+        Primitive::String(location.to_string()),
+    )?;
+    if let Some(service) = service {
+        set_kb_item(
+            context,
+            format!("{prefix}/service"),
+            Primitive::String(service.to_string()),
+        )?;
+    }
+
+    let oid = context.key().value();
+    let result = models::Result {
+        id: 0,
+        r_type: ResultType::HostDetail,
+        ip_address: Some(context.target().to_string()),
+        hostname: None,
+        oid: Some(oid.clone()),
+        qod: None,
+        port: (port > 0).then_some(port as i16),
+        protocol: models::Protocol::try_from(proto).ok(),
+        message: None,
+        detail: Some(Detail {
+            name: models::HOST_DETAIL_APP.to_string(),
+            value: cpe.to_uri(),
+            source: Source {
+                s_type: "nvt".to_string(),
+                name: oid,
+                description: desc.unwrap_or("Register Product").to_string(),
+            },
+        }),
+        pcap: None,
+        traffic: None,
+        labels: context.labels().cloned().unwrap_or_default(),
+        triage: None,
+    };
+    context
+        .dispatcher()
+        .retry_dispatch(5, context.key(), Field::Result(result.into()))?;
+    Ok(NaslValue::Null)
+}
+
+pub struct NaslCpe;
+
+function_set! {
+    NaslCpe,
+    sync_stateless,
+    (
+        build_cpe,
+        register_product
+    )
+}
@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        models::{Protocol, ResultType},
+        nasl::test_prelude::*,
+    };
+
+    #[test]
+    fn build_cpe() {
+        check_code_result(
+            r#"build_cpe(value: "2.4.58", exp: "^([0-9.]+)", base: "cpe:/a:apache:http_server:");"#,
+            "cpe:/a:apache:http_server:2.4.58",
+        );
+        check_code_result(
+            r#"build_cpe(value: "8.0 Update 5", exp: "^([0-9.]+) update ([0-9]+)", base: "cpe:/a:Oracle:JRE:");"#,
+            "cpe:/a:oracle:jre:8.0:5",
+        );
+        check_code_result(
+            r#"build_cpe(value: "1.2", exp: "^([0-9.]+)", base: "cpe:2.3:a:vendor:product");"#,
+            "cpe:2.3:a:vendor:product:1.2:*:*:*:*:*:*:*",
+        );
+        check_code_result(
+            r#"build_cpe(value: "unknown", exp: "^([0-9.]+)", base: "cpe:/a:vendor:product:");"#,
+            NaslValue::Null,
+        );
+        check_err_matches!(
+            r#"build_cpe(value: "1.2", exp: "^([0-9.]+)", base: "vendor:product");"#,
+            FunctionErrorKind::Diagnostic { .. }
+        );
+    }
+
+    #[test]
+    fn register_product() {
+        let mut t = TestBuilder::default();
+        t.run_all(
+            r#"
+        register_product(cpe: "cpe:/a:apache:http_server:2.4.58", location: "/", port: 443, service: "www");
+        get_kb_item("get_app/ports/cpe:/a:apache:http_server");
+        get_kb_item("get_app/cpe:/a:apache:http_server/443/tcp/cpe");
+        get_kb_item("get_app/cpe:/a:apache:http_server/443/tcp/location");
+        get_kb_item("get_app/cpe:/a:apache:http_server/443/tcp/service");
+        "#,
+        );
+        t.check_no_errors();
+        let results = t.results();
+        let value = |i: usize| results[i].as_ref().unwrap().clone();
+        assert_eq!(value(1), NaslValue::Number(443));
+        assert_eq!(
+            value(2),
+            NaslValue::String("cpe:/a:apache:http_server:2.4.58".into())
+        );
+        assert_eq!(value(3), NaslValue::String("/".into()));
+        assert_eq!(value(4), NaslValue::String("www".into()));
+
+        let context = t.context();
+        let result = context
+            .retriever()
+            .result(context.key(), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.r_type, ResultType::HostDetail);
+        assert_eq!(result.port, Some(443));
+        assert_eq!(result.protocol, Some(Protocol::TCP));
+        let detail = result.detail.unwrap();
+        assert_eq!(detail.name, "App");
+        assert_eq!(detail.value, "cpe:/a:apache:http_server:2.4.58");
+        assert_eq!(detail.source.name, context.key().value());
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Guards the login attempts of default credential checks against locking out accounts.
+//!
+//! The attempts are tracked per target, service and port over all scripts of a scan. Before
+//! each login a script or builtin asks for an attempt, which waits for the configured delay
+//! since the previous attempt and is refused once the cap is reached or the service reported a
+//! lockout. The guard is configured by scan preferences:
+//! - `disable_credential_checks` refuses all attempts, i.e. disables brute-force style checks,
+//! - `max_credential_attempts` is the maximum of attempts per service, 0 allows any number,
+//! - `credential_attempt_delay` is the delay between the attempts of a service in milliseconds.
+//!
+//! The guard only applies to attempts with guessed credentials, logins with the credentials of
+//! an authenticated scan are not tracked.
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::PluginConfig;
+
+use super::network::verify_port;
+
+/// Scan preference id disabling all default credential checks
+pub const DISABLE_CREDENTIAL_CHECKS: &str = "disable_credential_checks";
+/// Scan preference id of the maximum of login attempts per service
+pub const MAX_CREDENTIAL_ATTEMPTS: &str = "max_credential_attempts";
+/// Scan preference id of the delay between the login attempts of a service in milliseconds
+pub const CREDENTIAL_ATTEMPT_DELAY: &str = "credential_attempt_delay";
+
+const DEFAULT_MAX_ATTEMPTS: usize = 25;
+const DEFAULT_DELAY: Duration = Duration::from_millis(500);
+
+/// Phrases of login failures of locked accounts or blocked clients, compared in lowercase
+const LOCKOUT_PHRASES: [&str; 10] = [
+    "account is locked",
+    "account has been locked",
+    "account locked",
+    "locked out",
+    "too many authentication failures",
+    "too many failed",
+    "too many login attempts",
+    "maximum number of login attempts",
+    "temporarily blocked",
+    "blocked because of many connection errors",
+];
+
+/// Returns whether the message of a failed login indicates a locked account or blocked client.
+pub fn is_lockout(message: &str) -> bool {
+    let message = message.to_lowercase();
+    LOCKOUT_PHRASES.iter().any(|x| message.contains(x))
+}
+
+/// The limits of the login attempts of a scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialPolicy {
+    /// All attempts are refused
+    pub disabled: bool,
+    /// The maximum of attempts per service, 0 allows any number
+    pub max_attempts: usize,
+    /// The delay between the attempts of a service
+    pub delay: Duration,
+}
+
+impl Default for CredentialPolicy {
+    fn default() -> Self {
+        Self {
+            disabled: false,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            delay: DEFAULT_DELAY,
+        }
+    }
+}
+
+impl CredentialPolicy {
+    /// Creates the policy out of the scan preferences
+    pub fn from_config(config: &PluginConfig) -> Result<Self, FunctionErrorKind> {
+        let default = Self::default();
+        Ok(Self {
+            disabled: config.flag(DISABLE_CREDENTIAL_CHECKS),
+            max_attempts: config
+                .get(MAX_CREDENTIAL_ATTEMPTS)?
+                .unwrap_or(default.max_attempts),
+            delay: config
+                .get(CREDENTIAL_ATTEMPT_DELAY)?
+                .map(Duration::from_millis)
+                .unwrap_or(default.delay),
+        })
+    }
+}
+
+/// Why a login attempt is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// Default credential checks are disabled for the scan
+    Disabled,
+    /// The maximum of attempts of the service is reached
+    Exhausted,
+    /// The service reported a lockout
+    LockedOut,
+}
+
+impl Refusal {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Disabled => "disabled",
+            Self::Exhausted => "exhausted",
+            Self::LockedOut => "locked_out",
+        }
+    }
+}
+
+/// The outcome of a login attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// The account was locked or the client blocked
+    LockedOut,
+}
+
+#[derive(Debug, Default)]
+struct Service {
+    attempts: usize,
+    failures: usize,
+    locked: bool,
+    last: Option<Instant>,
+}
+
+type ServiceKey = (String, String, u16);
+
+/// The login attempts of each service of a scan
+#[derive(Debug)]
+pub struct CredentialGuard {
+    policy: CredentialPolicy,
+    services: Mutex<HashMap<ServiceKey, Service>>,
+}
+
+impl CredentialGuard {
+    pub fn new(policy: CredentialPolicy) -> Self {
+        Self {
+            policy,
+            services: Default::default(),
+        }
+    }
+
+    /// Returns the guard of the scan the context belongs to
+    pub fn of(context: &Context) -> Result<Arc<Self>, FunctionErrorKind> {
+        if let Some(x) = context.extensions().get() {
+            return Ok(x);
+        }
+        let config = context
+            .extensions()
+            .get::<PluginConfig>()
+            .unwrap_or_default();
+        let policy = CredentialPolicy::from_config(&config)?;
+        Ok(context
+            .extensions()
+            .get_or_insert_with(|| Self::new(policy)))
+    }
+
+    fn key(target: &str, service: &str, port: u16) -> ServiceKey {
+        (target.to_owned(), service.to_lowercase(), port)
+    }
+
+    /// Requests a login attempt to the service of the target.
+    ///
+    /// Waits until the delay since the previous attempt of the service passed and counts the
+    /// attempt. Returns the reason when the attempt is refused.
+    pub async fn attempt(&self, target: &str, service: &str, port: u16) -> Result<(), Refusal> {
+        if self.policy.disabled {
+            return Err(Refusal::Disabled);
+        }
+        let wait = {
+            let mut services = self.services.lock().unwrap_or_else(|e| e.into_inner());
+            let state = services
+                .entry(Self::key(target, service, port))
+                .or_default();
+            if state.locked {
+                return Err(Refusal::LockedOut);
+            }
+            if self.policy.max_attempts > 0 && state.attempts >= self.policy.max_attempts {
+                return Err(Refusal::Exhausted);
+            }
+            state.attempts += 1;
+            // the slot of this attempt is reserved, concurrent scripts wait for the next one
+            let now = Instant::now();
+            let slot = state
+                .last
+                .map(|x| x + self.policy.delay)
+                .filter(|x| *x > now)
+                .unwrap_or(now);
+            state.last = Some(slot);
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Records the outcome of a login attempt, a lockout refuses all further attempts of the
+    /// service.
+    pub fn record(&self, target: &str, service: &str, port: u16, outcome: Outcome) {
+        let mut services = self.services.lock().unwrap_or_else(|e| e.into_inner());
+        let state = services
+            .entry(Self::key(target, service, port))
+            .or_default();
+        match outcome {
+            Outcome::Success => {}
+            Outcome::Failure => state.failures += 1,
+            Outcome::LockedOut => {
+                state.failures += 1;
+                state.locked = true;
+                tracing::warn!(
+                    target,
+                    service,
+                    port,
+                    attempts = state.attempts,
+                    failures = state.failures,
+                    "lockout detected, stopping login attempts"
+                );
+            }
+        }
+    }
+}
+
+/// *bool* **credential_checks_enabled**();
+///
+/// Returns whether default credential checks are enabled for the scan, they are disabled by the
+/// `disable_credential_checks` scan preference.
+#[nasl_function]
+async fn credential_checks_enabled(context: &Context<'_>) -> Result<bool, FunctionErrorKind> {
+    Ok(!CredentialGuard::of(context)?.policy.disabled)
+}
+
+/// *bool* **credential_attempt**(service: *string*, port: *int*);
+///
+/// Requests a login attempt with guessed credentials to the service, e.g. `ssh`, `http` or
+/// `mysql`, on the port of the target. Must be called before each attempt.
+///
+/// Waits for the delay since the previous attempt of the service and returns TRUE when the
+/// attempt may be made. Returns FALSE when default credential checks are disabled, the maximum of
+/// attempts of the service is reached or the service reported a lockout.
+#[nasl_function(named(service, port))]
+async fn credential_attempt(
+    context: &Context<'_>,
+    service: &str,
+    port: i64,
+) -> Result<bool, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let guard = CredentialGuard::of(context)?;
+    match guard.attempt(context.target(), service, port).await {
+        Ok(()) => Ok(true),
+        Err(refusal) => {
+            tracing::debug!(
+                service,
+                port,
+                reason = refusal.as_str(),
+                "login attempt refused"
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// *bool* **credential_result**(service: *string*, port: *int*, success: *bool*, message: *string*);
+///
+/// Records the outcome of a login attempt to the service on the port of the target. The message
+/// of a failed login, e.g. the answer of the server, is checked for signs of a locked account or
+/// a blocked client, `lockout` reports a detected lockout explicitly.
+///
+/// Returns TRUE when a lockout was detected, all further attempts of the service are refused
+/// then.
+#[nasl_function(named(service, port, success, message, lockout))]
+async fn credential_result(
+    context: &Context<'_>,
+    service: &str,
+    port: i64,
+    success: bool,
+    message: Option<&str>,
+    lockout: Option<bool>,
+) -> Result<bool, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let locked = !success && (lockout.unwrap_or_default() || message.is_some_and(is_lockout));
+    let outcome = match (success, locked) {
+        (true, _) => Outcome::Success,
+        (false, true) => Outcome::LockedOut,
+        (false, false) => Outcome::Failure,
+    };
+    CredentialGuard::of(context)?.record(context.target(), service, port, outcome);
+    Ok(locked)
+}
+
+pub struct CredentialTesting;
+
+function_set! {
+    CredentialTesting,
+    async_stateless,
+    (credential_checks_enabled, credential_attempt, credential_result)
+}
@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::super::{
+        is_lockout, CredentialGuard, CredentialPolicy, Outcome, Refusal, CREDENTIAL_ATTEMPT_DELAY,
+        DISABLE_CREDENTIAL_CHECKS, MAX_CREDENTIAL_ATTEMPTS,
+    };
+    use crate::models::ScanPreference;
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::utils::PluginConfig;
+    use crate::storage::ContextKey;
+
+    fn config(preferences: &[(&str, &str)]) -> PluginConfig {
+        let preferences: Vec<_> = preferences
+            .iter()
+            .map(|(id, value)| ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            })
+            .collect();
+        PluginConfig::new("sid", &preferences)
+    }
+
+    #[test]
+    fn policy() {
+        assert_eq!(
+            CredentialPolicy::from_config(&PluginConfig::default()).unwrap(),
+            CredentialPolicy::default()
+        );
+        assert_eq!(
+            CredentialPolicy::from_config(&config(&[
+                (DISABLE_CREDENTIAL_CHECKS, "yes"),
+                (MAX_CREDENTIAL_ATTEMPTS, "3"),
+                (CREDENTIAL_ATTEMPT_DELAY, "100"),
+            ]))
+            .unwrap(),
+            CredentialPolicy {
+                disabled: true,
+                max_attempts: 3,
+                delay: Duration::from_millis(100),
+            }
+        );
+        assert!(
+            CredentialPolicy::from_config(&config(&[(MAX_CREDENTIAL_ATTEMPTS, "many")])).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn attempts() {
+        let guard = CredentialGuard::new(CredentialPolicy {
+            disabled: false,
+            max_attempts: 2,
+            delay: Duration::from_millis(50),
+        });
+        let start = Instant::now();
+        assert_eq!(guard.attempt("a", "ssh", 22).await, Ok(()));
+        guard.record("a", "ssh", 22, Outcome::Failure);
+        assert_eq!(guard.attempt("a", "SSH", 22).await, Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(guard.attempt("a", "ssh", 22).await, Err(Refusal::Exhausted));
+        // other ports and targets are tracked separately
+        assert_eq!(guard.attempt("a", "ssh", 2222).await, Ok(()));
+        assert_eq!(guard.attempt("b", "ssh", 22).await, Ok(()));
+
+        guard.record("b", "ssh", 22, Outcome::LockedOut);
+        assert_eq!(guard.attempt("b", "ssh", 22).await, Err(Refusal::LockedOut));
+
+        let disabled = CredentialGuard::new(CredentialPolicy {
+            disabled: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            disabled.attempt("a", "ssh", 22).await,
+            Err(Refusal::Disabled)
+        );
+    }
+
+    #[test]
+    fn lockout_messages() {
+        assert!(is_lockout("Account is locked. Contact your administrator."));
+        assert!(is_lockout(
+            "Host '10.0.0.1' is blocked because of many connection errors"
+        ));
+        assert!(is_lockout(
+            "Received disconnect: Too many authentication failures"
+        ));
+        assert!(!is_lockout("Permission denied (publickey,password)."));
+    }
+
+    #[test]
+    fn builtins() {
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.ok("credential_checks_enabled();", true);
+        t.ok(r#"credential_attempt(service: "mysql", port: 3306);"#, true);
+        t.ok(
+            r#"credential_result(service: "mysql", port: 3306, success: FALSE, message: "Access denied for user 'root'");"#,
+            false,
+        );
+        t.ok(
+            r#"credential_result(service: "mysql", port: 3306, success: FALSE, message: "Host is blocked because of many connection errors");"#,
+            true,
+        );
+        t.ok(
+            r#"credential_attempt(service: "mysql", port: 3306);"#,
+            false,
+        );
+        t.ok(r#"credential_attempt(service: "ftp", port: 21);"#, true);
+        check_err_matches!(
+            t,
+            r#"credential_attempt(service: "ssh", port: 70000);"#,
+            FunctionErrorKind::WrongArgument { .. }
+        );
+    }
+}
@@ -32,16 +32,18 @@ where
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     let key = match register.named("key") {
-        Some(ContextType::Value(NaslValue::String(x))) => x,
+        Some(ContextType::Value(NaslValue::String(x))) => x.as_bytes(),
+        Some(ContextType::Value(NaslValue::Data(x))) => x.as_slice(),
         Some(ContextType::Value(NaslValue::Null)) => return Ok(NaslValue::Null),
         x => return Err(("key", "string", x).into()),
     };
     let data = match register.named("data") {
-        Some(ContextType::Value(NaslValue::String(x))) => x,
+        Some(ContextType::Value(NaslValue::String(x))) => x.as_bytes(),
+        Some(ContextType::Value(NaslValue::Data(x))) => x.as_slice(),
         Some(ContextType::Value(NaslValue::Null)) => return Ok(NaslValue::Null),
         x => return Err(("data", "string", x).into()),
     };
-    let mut hmac = match Hmac::<D>::new_from_slice(key.as_bytes()) {
+    let mut hmac = match Hmac::<D>::new_from_slice(key) {
         Ok(x) => x,
         Err(InvalidLength) => {
             return Err(FunctionErrorKind::wrong_unnamed_argument(
@@ -50,10 +52,10 @@ where
             ))
         }
     };
-    hmac.update(data.as_bytes());
-    Ok(NaslValue::String(encode(
-        hmac.finalize().into_bytes().as_slice(),
-    )))
+    hmac.update(data);
+    Ok(NaslValue::String(
+        encode(hmac.finalize().into_bytes().as_slice()).into(),
+    ))
 }
 
 /// NASL function to get HMAC MD2 string
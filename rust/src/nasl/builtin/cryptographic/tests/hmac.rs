@@ -20,6 +20,33 @@ fn hmac_md5() {
     );
 }
 
+#[test]
+fn hmac_data() {
+    check_code_result(
+        r#"HMAC_MD5(key: 'my_shared?key', data: 'so much wow');"#,
+        "815292959633f0e63666d90d6f47cb79",
+    );
+}
+
+#[test]
+fn hmac_binary_data() {
+    // RFC 2202 test case 3, the key and data consist of bytes >= 0x80
+    check_code_result(
+        r#"HMAC_MD5(key: crap(data: raw_string(0xaa), length: 16), data: crap(data: raw_string(0xdd), length: 50));"#,
+        "56be34521d144c88dbb8c733f0e8b3f6",
+    );
+    // RFC 4231 test case 3
+    check_code_result(
+        r#"HMAC_SHA256(key: crap(data: raw_string(0xaa), length: 20), data: crap(data: raw_string(0xdd), length: 50));"#,
+        "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+    );
+    // NUL bytes are part of the key and data
+    check_code_result(
+        r#"HMAC_SHA1(key: raw_string(0x6b, 0x00, 0xff), data: raw_string(0x00, 0x80, 0xff, 0x00));"#,
+        "2bff171240adc99349353da01b51ad56e3984f74",
+    );
+}
+
 #[test]
 fn hmac_ripemd160() {
     check_code_result(
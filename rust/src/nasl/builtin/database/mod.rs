@@ -0,0 +1,274 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins parsing the initial handshake of database servers.
+//!
+//! No credentials are sent, the builtins only read what a server reveals before the
+//! authentication:
+//! - `mysql_handshake` reads the initial handshake of a MySQL or MariaDB server,
+//! - `postgres_probe` requests TLS and starts up a PostgreSQL session up to the authentication
+//!   request,
+//! - `mssql_prelogin` exchanges the TDS PRELOGIN with a Microsoft SQL Server.
+
+mod mssql;
+mod mysql;
+mod postgres;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use crate::nasl::prelude::*;
+
+use super::network::network_utils::{connect_target, timeout_arg};
+use super::network::verify_port;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connects, sends the request if any and reads the answer. Returns None when the connection
+/// fails or the server does not answer as expected.
+async fn exchange<T>(
+    context: &Context<'_>,
+    port: i64,
+    timeout: Option<i64>,
+    request: Option<&[u8]>,
+    read: impl FnOnce(&mut TcpStream) -> std::io::Result<(T, usize)>,
+) -> Result<Option<T>, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, _permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(None);
+    };
+    let result = (|| -> Result<_, FunctionErrorKind> {
+        if let Some(request) = request {
+            stream.write_all(request)?;
+            if let Some(traffic) = context.traffic() {
+                traffic.sent(request.len())?;
+            }
+        }
+        let (answer, len) = read(&mut stream)?;
+        if let Some(traffic) = context.traffic() {
+            traffic.received(len)?;
+        }
+        Ok(answer)
+    })();
+    match result {
+        Ok(x) => Ok(Some(x)),
+        Err(FunctionErrorKind::IOError(error)) => {
+            tracing::debug!(port, %error, "no handshake received");
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn string(value: impl Into<String>) -> NaslValue {
+    NaslValue::String(value.into().into())
+}
+
+/// *array* **mysql_handshake**(port: *int*, timeout: *int*);
+///
+/// Reads the initial handshake of the MySQL or MariaDB server on the port of the target, 3306 by
+/// default.
+///
+/// Returns an array with the `protocol` version 10, the server `version`, the `connection_id`,
+/// the `capabilities` flags, whether the server supports `tls` and its default `auth_plugin`.
+/// A server refusing the client answers with an error instead, then the array contains the
+/// `error_code` and the `error` message. NULL is returned when the port does not answer as MySQL
+/// server.
+#[nasl_function(named(port, timeout))]
+async fn mysql_handshake(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = port.unwrap_or(3306);
+    let Some(greeting) = exchange(context, port, timeout, None, mysql::Greeting::read).await?
+    else {
+        return Ok(NaslValue::Null);
+    };
+    let tls = greeting.tls();
+    let dict = match greeting {
+        mysql::Greeting::Handshake {
+            version,
+            connection_id,
+            capabilities,
+            auth_plugin,
+        } => HashMap::from([
+            ("protocol".to_string(), NaslValue::Number(10)),
+            ("version".to_string(), string(version)),
+            (
+                "connection_id".to_string(),
+                NaslValue::Number(connection_id as i64),
+            ),
+            (
+                "capabilities".to_string(),
+                NaslValue::Number(capabilities as i64),
+            ),
+            ("tls".to_string(), NaslValue::Boolean(tls)),
+            ("auth_plugin".to_string(), string(auth_plugin)),
+        ]),
+        mysql::Greeting::Error { code, message } => HashMap::from([
+            ("error_code".to_string(), NaslValue::Number(code as i64)),
+            ("error".to_string(), string(message)),
+        ]),
+    };
+    Ok(NaslValue::Dict(dict))
+}
+
+/// *array* **postgres_probe**(port: *int*, user: *string*, timeout: *int*);
+///
+/// Probes the PostgreSQL server on the port of the target, 5432 by default. An SSLRequest is sent
+/// on a first connection, a StartupMessage for the user, `postgres` by default, and the database
+/// of the same name on a second one.
+///
+/// Returns an array with whether the server supports `tls`. When the server requests
+/// authentication the array contains the `authentication` code, the `auth_method` like `trust`,
+/// `password`, `md5` or `sasl` and the SASL `mechanisms`. A server not requiring authentication
+/// reports its `parameters`, the `server_version` is returned as well then. When the server
+/// refuses the startup the array contains the `sqlstate` and the `error` message. NULL is returned
+/// when the port does not answer as PostgreSQL server.
+#[nasl_function(named(port, user, timeout))]
+async fn postgres_probe(
+    context: &Context<'_>,
+    port: Option<i64>,
+    user: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = port.unwrap_or(5432);
+    let read_answer = |stream: &mut TcpStream| {
+        let mut answer = [0; 1];
+        stream.read_exact(&mut answer)?;
+        Ok((answer[0], 1))
+    };
+    let tls = match exchange(
+        context,
+        port,
+        timeout,
+        Some(&postgres::SSL_REQUEST),
+        read_answer,
+    )
+    .await?
+    {
+        Some(b'S') => true,
+        // servers without TLS support answer N, very old servers with an ErrorResponse
+        Some(b'N' | b'E') => false,
+        _ => return Ok(NaslValue::Null),
+    };
+    let mut dict = HashMap::from([("tls".to_string(), NaslValue::Boolean(tls))]);
+    let request = postgres::startup_message(user.unwrap_or("postgres"));
+    let Some(startup) = exchange(
+        context,
+        port,
+        timeout,
+        Some(&request),
+        postgres::Startup::read,
+    )
+    .await?
+    else {
+        return Ok(NaslValue::Dict(dict));
+    };
+    if let Some(code) = startup.authentication {
+        dict.insert("authentication".to_string(), NaslValue::Number(code as i64));
+        dict.insert(
+            "auth_method".to_string(),
+            string(postgres::auth_method(code)),
+        );
+        dict.insert(
+            "mechanisms".to_string(),
+            NaslValue::Array(startup.mechanisms.into_iter().map(string).collect()),
+        );
+    }
+    if let Some((_, version)) = startup
+        .parameters
+        .iter()
+        .find(|(name, _)| name == "server_version")
+    {
+        dict.insert("server_version".to_string(), string(version.clone()));
+    }
+    if !startup.parameters.is_empty() {
+        dict.insert(
+            "parameters".to_string(),
+            NaslValue::Dict(
+                startup
+                    .parameters
+                    .into_iter()
+                    .map(|(name, value)| (name, string(value)))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some((code, message)) = startup.error {
+        dict.insert("sqlstate".to_string(), string(code));
+        dict.insert("error".to_string(), string(message));
+    }
+    Ok(NaslValue::Dict(dict))
+}
+
+/// *array* **mssql_prelogin**(port: *int*, timeout: *int*);
+///
+/// Sends a TDS PRELOGIN to the Microsoft SQL Server on the port of the target, 1433 by default.
+///
+/// Returns an array with the `version` of the server as `major.minor.build`, the `encryption`
+/// option, whether the server supports `tls`, the `instance` and whether it supports `mars`, as
+/// far as the server answers the options. The encryption option is 0 when the server supports
+/// encryption of the login only, 1 when it is enabled, 2 when it is not supported and 3 when
+/// it is required. NULL is returned when the port does not answer as SQL Server.
+#[nasl_function(named(port, timeout))]
+async fn mssql_prelogin(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = port.unwrap_or(1433);
+    let request = mssql::prelogin();
+    let Some(prelogin) = exchange(
+        context,
+        port,
+        timeout,
+        Some(&request),
+        mssql::PreLogin::read,
+    )
+    .await?
+    else {
+        return Ok(NaslValue::Null);
+    };
+    let mut dict = HashMap::new();
+    if let Some((major, minor, build)) = prelogin.version {
+        dict.insert(
+            "version".to_string(),
+            string(format!("{major}.{minor}.{build}")),
+        );
+    }
+    if let Some(encryption) = prelogin.encryption {
+        dict.insert(
+            "encryption".to_string(),
+            NaslValue::Number(encryption as i64),
+        );
+        dict.insert(
+            "tls".to_string(),
+            NaslValue::Boolean(encryption != mssql::ENCRYPT_NOT_SUP),
+        );
+    }
+    if let Some(instance) = prelogin.instance {
+        dict.insert("instance".to_string(), string(instance));
+    }
+    if let Some(mars) = prelogin.mars {
+        dict.insert("mars".to_string(), NaslValue::Boolean(mars));
+    }
+    Ok(NaslValue::Dict(dict))
+}
+
+pub struct Database;
+
+function_set! {
+    Database,
+    async_stateless,
+    (mysql_handshake, postgres_probe, mssql_prelogin)
+}
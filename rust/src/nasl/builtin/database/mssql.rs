@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! The TDS PRELOGIN exchange of Microsoft SQL Server ([MS-TDS] 2.2.6.5).
+
+use std::io::{self, Read};
+
+const PACKET_PRELOGIN: u8 = 0x12;
+const PACKET_RESPONSE: u8 = 0x04;
+const STATUS_EOM: u8 = 0x01;
+const MAX_PACKET: usize = 4096;
+
+const OPTION_VERSION: u8 = 0x00;
+const OPTION_ENCRYPTION: u8 = 0x01;
+const OPTION_INSTANCE: u8 = 0x02;
+const OPTION_THREAD_ID: u8 = 0x03;
+const OPTION_MARS: u8 = 0x04;
+const TERMINATOR: u8 = 0xff;
+
+/// The encryption of a server not supporting TLS
+pub const ENCRYPT_NOT_SUP: u8 = 0x02;
+
+/// Returns a PRELOGIN of a client supporting encryption without requiring it.
+pub fn prelogin() -> Vec<u8> {
+    let options: [(u8, &[u8]); 5] = [
+        (OPTION_VERSION, &[0; 6]),
+        (OPTION_ENCRYPTION, &[0]),
+        (OPTION_INSTANCE, &[0]),
+        (OPTION_THREAD_ID, &[0; 4]),
+        (OPTION_MARS, &[0]),
+    ];
+    let mut offset = options.len() * 5 + 1;
+    let mut header = vec![];
+    let mut data = vec![];
+    for (token, value) in options {
+        header.push(token);
+        header.extend_from_slice(&(offset as u16).to_be_bytes());
+        header.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        data.extend_from_slice(value);
+        offset += value.len();
+    }
+    header.push(TERMINATOR);
+    let mut packet = vec![PACKET_PRELOGIN, STATUS_EOM];
+    packet.extend_from_slice(&((header.len() + data.len() + 8) as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0]);
+    packet.extend(header);
+    packet.extend(data);
+    packet
+}
+
+/// The PRELOGIN response of a server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreLogin {
+    /// The major, minor and build number
+    pub version: Option<(u8, u8, u16)>,
+    pub encryption: Option<u8>,
+    pub instance: Option<String>,
+    pub mars: Option<bool>,
+}
+
+impl PreLogin {
+    /// Reads the response and returns it with its length.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no PRELOGIN response");
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if header[0] != PACKET_RESPONSE || !(9..=MAX_PACKET).contains(&len) {
+            return Err(invalid());
+        }
+        let mut payload = vec![0; len - 8];
+        reader.read_exact(&mut payload)?;
+        Ok((Self::parse(&payload).ok_or_else(invalid)?, len))
+    }
+
+    /// Parses the options of the payload of a response.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let mut result = Self::default();
+        let mut options = payload;
+        loop {
+            match options {
+                [TERMINATOR, ..] => return Some(result),
+                [token, a, b, c, d, rest @ ..] => {
+                    let offset = u16::from_be_bytes([*a, *b]) as usize;
+                    let len = u16::from_be_bytes([*c, *d]) as usize;
+                    let value = payload.get(offset..offset + len)?;
+                    match (*token, value) {
+                        (OPTION_VERSION, [major, minor, x, y, ..]) => {
+                            result.version = Some((*major, *minor, u16::from_be_bytes([*x, *y])))
+                        }
+                        (OPTION_ENCRYPTION, [x, ..]) => result.encryption = Some(*x),
+                        (OPTION_INSTANCE, x) => {
+                            let end = x.iter().position(|x| *x == 0).unwrap_or(x.len());
+                            result.instance = Some(String::from_utf8_lossy(&x[..end]).into_owned())
+                        }
+                        (OPTION_MARS, [x, ..]) => result.mars = Some(*x != 0),
+                        _ => {}
+                    }
+                    options = rest;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
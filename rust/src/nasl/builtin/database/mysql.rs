@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! The initial handshake packet a MySQL or MariaDB server sends after the connection.
+
+use std::io::{self, Read};
+
+const PROTOCOL_V10: u8 = 10;
+const ERR_PACKET: u8 = 0xff;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+const MAX_PACKET: usize = 4096;
+
+/// The first packet of a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Greeting {
+    Handshake {
+        version: String,
+        connection_id: u32,
+        capabilities: u32,
+        /// The authentication plugin of the server, `mysql_native_password` when the server
+        /// does not name one
+        auth_plugin: String,
+    },
+    /// An error of a server refusing the client, e.g. because the host is not allowed to connect
+    Error { code: u16, message: String },
+}
+
+impl Greeting {
+    /// Returns whether the server supports TLS.
+    pub fn tls(&self) -> bool {
+        matches!(self, Self::Handshake { capabilities, .. } if capabilities & CLIENT_SSL != 0)
+    }
+
+    /// Reads the first packet and returns it with its length.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no MySQL handshake");
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        if header[3] != 0 || !(3..=MAX_PACKET).contains(&len) {
+            return Err(invalid());
+        }
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+        let greeting = Self::parse(&payload).ok_or_else(invalid)?;
+        Ok((greeting, len + 4))
+    }
+
+    /// Parses the payload of the first packet.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match payload {
+            [ERR_PACKET, a, b, message @ ..] => Some(Self::Error {
+                code: u16::from_le_bytes([*a, *b]),
+                message: String::from_utf8_lossy(message).into_owned(),
+            }),
+            [PROTOCOL_V10, rest @ ..] => {
+                let end = rest.iter().position(|x| *x == 0)?;
+                let version = String::from_utf8_lossy(&rest[..end]).into_owned();
+                let rest = &rest[end + 1..];
+                // the connection id, the first part of the scramble and a filler
+                let connection_id = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?);
+                let lower = u16::from_le_bytes([*rest.get(13)?, *rest.get(14)?]);
+                // servers may end the packet after the lower capabilities
+                let upper = match rest.get(18..20) {
+                    Some(x) => u16::from_le_bytes([x[0], x[1]]),
+                    None => 0,
+                };
+                let capabilities = (upper as u32) << 16 | lower as u32;
+                let auth_plugin = (capabilities & CLIENT_PLUGIN_AUTH != 0)
+                    .then(|| {
+                        let scramble = (*rest.get(20)? as usize).saturating_sub(8).max(13);
+                        let name = rest.get(31 + scramble..)?;
+                        let end = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+                        Some(String::from_utf8_lossy(&name[..end]).into_owned())
+                    })
+                    .flatten()
+                    .unwrap_or_else(|| "mysql_native_password".to_string());
+                Some(Self::Handshake {
+                    version,
+                    connection_id,
+                    capabilities,
+                    auth_plugin,
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! The SSLRequest and the StartupMessage of the PostgreSQL frontend/backend protocol 3.0.
+
+use std::io::{self, Read};
+
+/// The SSLRequest, answered with `S` or `N` by the server
+pub const SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+const PROTOCOL_3_0: [u8; 4] = [0, 3, 0, 0];
+const MAX_MESSAGE: usize = 8192;
+/// Maximum of messages read after the StartupMessage
+const MAX_MESSAGES: usize = 32;
+
+/// Returns the StartupMessage for the user and the database of the same name.
+pub fn startup_message(user: &str) -> Vec<u8> {
+    let mut body = PROTOCOL_3_0.to_vec();
+    for field in ["user", user, "database", user, ""] {
+        body.extend_from_slice(field.as_bytes());
+        body.push(0);
+    }
+    let mut message = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+    message.extend(body);
+    message
+}
+
+/// Returns the name of an authentication request code.
+pub fn auth_method(code: u32) -> &'static str {
+    match code {
+        0 => "trust",
+        2 => "kerberos",
+        3 => "password",
+        5 => "md5",
+        7 => "gss",
+        9 => "sspi",
+        10 => "sasl",
+        _ => "unknown",
+    }
+}
+
+/// The answer of a server to the StartupMessage
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Startup {
+    /// The code of the authentication request
+    pub authentication: Option<u32>,
+    /// The SASL mechanisms of a SASL authentication request
+    pub mechanisms: Vec<String>,
+    /// The parameters a server reports after a successful authentication
+    pub parameters: Vec<(String, String)>,
+    /// The SQLSTATE code and the message of an ErrorResponse
+    pub error: Option<(String, String)>,
+}
+
+fn strings(data: &[u8]) -> impl Iterator<Item = String> + '_ {
+    data.split(|x| *x == 0)
+        .take_while(|x| !x.is_empty())
+        .map(|x| String::from_utf8_lossy(x).into_owned())
+}
+
+impl Startup {
+    /// Reads the messages until the server requests authentication, reports an error or is
+    /// ready for queries and returns the answer with the length read.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no PostgreSQL answer");
+        let mut result = Self::default();
+        let mut received = 0;
+        for _ in 0..MAX_MESSAGES {
+            let mut header = [0; 5];
+            reader.read_exact(&mut header)?;
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+            if !(4..=MAX_MESSAGE).contains(&len) {
+                return Err(invalid());
+            }
+            let mut body = vec![0; len - 4];
+            reader.read_exact(&mut body)?;
+            received += len + 1;
+            match header[0] {
+                b'R' if body.len() >= 4 => {
+                    let code = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+                    result.mechanisms = strings(&body[4..]).collect();
+                    result.authentication = Some(code);
+                    // a server without authentication continues with its parameters
+                    if code != 0 {
+                        return Ok((result, received));
+                    }
+                }
+                b'S' => {
+                    let mut fields = strings(&body);
+                    if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+                        result.parameters.push((name, value));
+                    }
+                }
+                b'E' => {
+                    let (mut code, mut message) = (String::new(), String::new());
+                    for field in strings(&body) {
+                        if let Some(x) = field.strip_prefix('C') {
+                            code = x.to_owned();
+                        } else if let Some(x) = field.strip_prefix('M') {
+                            message = x.to_owned();
+                        }
+                    }
+                    result.error = Some((code, message));
+                    return Ok((result, received));
+                }
+                b'Z' => return Ok((result, received)),
+                // the BackendKeyData and notices
+                b'K' | b'N' => {}
+                _ => return Err(invalid()),
+            }
+        }
+        Err(invalid())
+    }
+}
@@ -0,0 +1,227 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::super::{mssql, mysql, postgres};
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::NoOpLoader;
+    use crate::storage::{ContextKey, DefaultDispatcher};
+
+    fn builder() -> TestBuilder<NoOpLoader, DefaultDispatcher> {
+        TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())))
+    }
+
+    /// Returns the initial handshake packet of a MySQL 8 server.
+    fn mysql_greeting() -> Vec<u8> {
+        let mut payload = vec![10];
+        payload.extend_from_slice(b"8.0.36\0");
+        payload.extend_from_slice(&42u32.to_le_bytes());
+        payload.extend_from_slice(b"abcdefgh\0");
+        // the lower capabilities with CLIENT_SSL, charset and status
+        payload.extend_from_slice(&[0xff, 0xff, 0xff, 0x02, 0x00]);
+        // the upper capabilities with CLIENT_PLUGIN_AUTH and the length of the scramble
+        payload.extend_from_slice(&[0xff, 0xdf, 21]);
+        payload.extend_from_slice(&[0; 10]);
+        payload.extend_from_slice(b"ijklmnopqrst\0");
+        payload.extend_from_slice(b"caching_sha2_password\0");
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(0);
+        packet.extend(payload);
+        packet
+    }
+
+    #[test]
+    fn mysql_parse() {
+        let packet = mysql_greeting();
+        let (greeting, len) = mysql::Greeting::read(&mut packet.as_slice()).unwrap();
+        assert_eq!(len, packet.len());
+        assert!(greeting.tls());
+        assert_eq!(
+            greeting,
+            mysql::Greeting::Handshake {
+                version: "8.0.36".into(),
+                connection_id: 42,
+                capabilities: 0xdfff_ffff,
+                auth_plugin: "caching_sha2_password".into(),
+            }
+        );
+        let error = b"\xff\x6a\x04Host '10.0.0.1' is not allowed to connect to this MySQL server";
+        assert_eq!(
+            mysql::Greeting::parse(error),
+            Some(mysql::Greeting::Error {
+                code: 1130,
+                message: "Host '10.0.0.1' is not allowed to connect to this MySQL server".into(),
+            })
+        );
+        // a server without CLIENT_PLUGIN_AUTH
+        let mut old = b"\x0a5.0.96\0".to_vec();
+        old.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x2c, 0xa2, 8, 2, 0]);
+        assert_eq!(
+            mysql::Greeting::parse(&old),
+            Some(mysql::Greeting::Handshake {
+                version: "5.0.96".into(),
+                connection_id: 1,
+                capabilities: 0xa22c,
+                auth_plugin: "mysql_native_password".into(),
+            })
+        );
+        assert_eq!(mysql::Greeting::parse(b"\x0a5.0.96"), None);
+        assert!(mysql::Greeting::read(&mut &b"SSH-2.0-OpenSSH_9.6\r\n"[..]).is_err());
+    }
+
+    fn postgres_message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&((body.len() + 4) as u32).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    #[test]
+    fn postgres_parse() {
+        let message = postgres::startup_message("postgres");
+        assert_eq!(&message[..8], &[0, 0, 0, 41, 0, 3, 0, 0]);
+        assert_eq!(&message[8..], b"user\0postgres\0database\0postgres\0\0");
+
+        let sasl = postgres_message(b'R', b"\0\0\0\x0aSCRAM-SHA-256\0\0");
+        let (startup, len) = postgres::Startup::read(&mut sasl.as_slice()).unwrap();
+        assert_eq!(len, sasl.len());
+        assert_eq!(startup.authentication, Some(10));
+        assert_eq!(startup.mechanisms, vec!["SCRAM-SHA-256".to_string()]);
+
+        let mut trust = postgres_message(b'R', &[0, 0, 0, 0]);
+        trust.extend(postgres_message(b'S', b"server_version\x0016.2\0"));
+        trust.extend(postgres_message(b'K', &[0; 8]));
+        trust.extend(postgres_message(b'Z', b"I"));
+        let (startup, _) = postgres::Startup::read(&mut trust.as_slice()).unwrap();
+        assert_eq!(startup.authentication, Some(0));
+        assert_eq!(
+            startup.parameters,
+            vec![("server_version".to_string(), "16.2".to_string())]
+        );
+
+        let error = postgres_message(
+            b'E',
+            b"SFATAL\0C28000\0Mno pg_hba.conf entry for host \"10.0.0.1\"\0\0",
+        );
+        let (startup, _) = postgres::Startup::read(&mut error.as_slice()).unwrap();
+        assert_eq!(
+            startup.error,
+            Some((
+                "28000".to_string(),
+                "no pg_hba.conf entry for host \"10.0.0.1\"".to_string()
+            ))
+        );
+        assert!(postgres::Startup::read(&mut &b"HTTP/1.1 400"[..]).is_err());
+        assert_eq!(postgres::auth_method(5), "md5");
+    }
+
+    /// Returns the PRELOGIN response of an SQL Server 2019.
+    fn mssql_response() -> Vec<u8> {
+        let mut payload = vec![
+            0x00, 0, 16, 0, 6, 0x01, 0, 22, 0, 1, 0x04, 0, 23, 0, 1, 0xff,
+        ];
+        payload.extend_from_slice(&[15, 0, 0x07, 0xd0, 0, 0, 1, 0]);
+        let mut packet = vec![0x04, 0x01];
+        packet.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 1, 0]);
+        packet.extend(payload);
+        packet
+    }
+
+    #[test]
+    fn mssql_parse() {
+        let request = mssql::prelogin();
+        assert_eq!(request.len(), 47);
+        assert_eq!(&request[..4], &[0x12, 0x01, 0, 47]);
+        assert_eq!(request[8 + 25], 0xff);
+
+        let response = mssql_response();
+        let (prelogin, len) = mssql::PreLogin::read(&mut response.as_slice()).unwrap();
+        assert_eq!(len, response.len());
+        assert_eq!(
+            prelogin,
+            mssql::PreLogin {
+                version: Some((15, 0, 2000)),
+                encryption: Some(1),
+                instance: None,
+                mars: Some(false),
+            }
+        );
+        // an option pointing beyond the payload
+        assert_eq!(mssql::PreLogin::parse(&[0x00, 0, 6, 0, 6, 0xff]), None);
+        assert_eq!(mssql::PreLogin::parse(&[0x00, 0, 6]), None);
+    }
+
+    #[test]
+    fn mysql_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&mysql_greeting()).unwrap();
+        });
+        let mut t = builder();
+        t.run(&format!("a = mysql_handshake(port: {port});"));
+        t.ok(r#"a["version"];"#, "8.0.36");
+        t.ok(r#"a["tls"];"#, true);
+        t.ok(r#"a["auth_plugin"];"#, "caching_sha2_password");
+    }
+
+    #[test]
+    fn postgres_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 8];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request, postgres::SSL_REQUEST);
+            stream.write_all(b"N").unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut len = [0; 4];
+            stream.read_exact(&mut len).unwrap();
+            let mut body = vec![0; u32::from_be_bytes(len) as usize - 4];
+            stream.read_exact(&mut body).unwrap();
+            assert!(body.ends_with(b"user\0scanner\0database\0scanner\0\0"));
+            stream
+                .write_all(&postgres_message(b'R', &[0, 0, 0, 5, 1, 2, 3, 4]))
+                .unwrap();
+        });
+        let mut t = builder();
+        t.run(&format!(
+            r#"a = postgres_probe(port: {port}, user: "scanner");"#
+        ));
+        t.ok(r#"a["tls"];"#, false);
+        t.ok(r#"a["authentication"];"#, 5);
+        t.ok(r#"a["auth_method"];"#, "md5");
+    }
+
+    #[test]
+    fn mssql_prelogin() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; mssql::prelogin().len()];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request, mssql::prelogin());
+            stream.write_all(&mssql_response()).unwrap();
+        });
+        let mut t = builder();
+        t.run(&format!("a = mssql_prelogin(port: {port});"));
+        t.ok(r#"a["version"];"#, "15.0.2000");
+        t.ok(r#"a["encryption"];"#, 1);
+        t.ok(r#"a["tls"];"#, true);
+        t.ok(r#"a["mars"];"#, false);
+    }
+}
@@ -0,0 +1,180 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! The DNS message format shared by mDNS and the NetBIOS name service.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Maximum of compression pointers followed within a name
+const MAX_POINTERS: usize = 16;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+
+/// Returns the record type of its name, e.g. `PTR`, or parses a number.
+pub fn record_type(name: &str) -> Option<u16> {
+    match name.to_uppercase().as_str() {
+        "A" => Some(TYPE_A),
+        "PTR" => Some(TYPE_PTR),
+        "TXT" => Some(TYPE_TXT),
+        "AAAA" => Some(TYPE_AAAA),
+        "SRV" => Some(TYPE_SRV),
+        "ANY" => Some(255),
+        x => x.parse().ok(),
+    }
+}
+
+/// Appends the labels of a dotted name.
+pub fn push_name(buffer: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|x| !x.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        buffer.push(label.len() as u8);
+        buffer.extend_from_slice(label);
+    }
+    buffer.push(0);
+}
+
+/// Returns a query header followed by a single question.
+pub fn query(id: u16, flags: u16, question: &[u8], qtype: u16, qclass: u16) -> Vec<u8> {
+    let mut message = vec![];
+    for x in [id, flags, 1, 0, 0, 0] {
+        message.extend_from_slice(&x.to_be_bytes());
+    }
+    message.extend_from_slice(question);
+    message.extend_from_slice(&qtype.to_be_bytes());
+    message.extend_from_slice(&qclass.to_be_bytes());
+    message
+}
+
+/// A resource record of an answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    /// The data in the presentation format for A, AAAA, PTR, SRV and TXT records
+    pub text: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Reads a DNS message
+pub struct Message<'a> {
+    message: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Message<'a> {
+    pub fn new(message: &'a [u8]) -> Self {
+        Self { message, pos: 0 }
+    }
+
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let data = self.message.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(data)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|x| u16::from_be_bytes([x[0], x[1]]))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+    }
+
+    /// Reads a possibly compressed name.
+    pub fn name(&mut self) -> Option<String> {
+        let mut labels = vec![];
+        let mut pos = self.pos;
+        let mut end = None;
+        for _ in 0..MAX_POINTERS {
+            loop {
+                let len = *self.message.get(pos)? as usize;
+                match len {
+                    0 => {
+                        self.pos = end.unwrap_or(pos + 1);
+                        return Some(labels.join("."));
+                    }
+                    0xc0.. => break,
+                    _ => {
+                        let label = self.message.get(pos + 1..pos + 1 + len)?;
+                        labels.push(String::from_utf8_lossy(label).into_owned());
+                        pos += 1 + len;
+                    }
+                }
+            }
+            let pointer = self.message.get(pos..pos + 2)?;
+            end.get_or_insert(pos + 2);
+            pos = (u16::from_be_bytes([pointer[0], pointer[1]]) & 0x3fff) as usize;
+        }
+        None
+    }
+
+    /// Skips the header and the questions, returns the number of answer, authority and
+    /// additional records.
+    pub fn header(&mut self) -> Option<usize> {
+        self.take(4)?;
+        let questions = self.u16()?;
+        let records = (0..3).map(|_| self.u16()).sum::<Option<u16>>()?;
+        for _ in 0..questions {
+            self.name()?;
+            self.take(4)?;
+        }
+        Some(records as usize)
+    }
+
+    /// Reads a resource record.
+    pub fn record(&mut self) -> Option<Record> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let len = self.u16()? as usize;
+        let start = self.pos;
+        let data = self.take(len)?;
+        let mut rdata = Message {
+            message: self.message,
+            pos: start,
+        };
+        let text = match rtype {
+            TYPE_A if len == 4 => {
+                Some(Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string())
+            }
+            TYPE_AAAA if len == 16 => {
+                Some(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?).to_string())
+            }
+            TYPE_PTR => rdata.name(),
+            TYPE_SRV => {
+                let priority = rdata.u16()?;
+                let weight = rdata.u16()?;
+                let port = rdata.u16()?;
+                Some(format!("{priority} {weight} {port} {}", rdata.name()?))
+            }
+            TYPE_TXT => {
+                let mut strings = vec![];
+                let mut rest = data;
+                while let Some((len, tail)) = rest.split_first() {
+                    let string = tail.get(..*len as usize)?;
+                    strings.push(String::from_utf8_lossy(string).into_owned());
+                    rest = &tail[*len as usize..];
+                }
+                Some(strings.join("\n"))
+            }
+            _ => None,
+        };
+        Some(Record {
+            name,
+            rtype,
+            class,
+            ttl,
+            text,
+            data: data.to_vec(),
+        })
+    }
+}
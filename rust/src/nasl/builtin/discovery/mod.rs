@@ -0,0 +1,323 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins probing UDP discovery services of the target.
+//!
+//! Each builtin sends a single request to a port of the target and parses the datagrams it
+//! answers with:
+//! - `ntp_version` sends an NTP client request and returns the header of the response,
+//! - `ntp_monlist` sends a mode 7 monlist request and returns the size of the answer, an
+//!   amplification vector,
+//! - `netbios_name_query` sends a NetBIOS node status request and returns the registered names,
+//! - `mdns_query` sends a unicast mDNS question and returns the records of the answers,
+//! - `ssdp_search` sends an SSDP M-SEARCH and returns the headers of the responses.
+//!
+//! Requests are sent unicast to the target, multicast groups are never joined.
+
+mod dns;
+mod netbios;
+mod ntp;
+mod ssdp;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io,
+    net::UdpSocket,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+use crate::nasl::prelude::*;
+
+use super::network::network_utils::{bind_source_socket, resolve_ipaddr, source_addrs};
+use super::network::verify_port;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Time further datagrams are waited for after a datagram of a multi-datagram answer
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Maximum of datagrams of an answer
+const MAX_DATAGRAMS: usize = 100;
+
+/// Whether all datagrams of an answer are received or only the first one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    First,
+    All,
+}
+
+fn timeout_arg(timeout: Option<i64>) -> Duration {
+    timeout
+        .filter(|x| *x > 0)
+        .map(|x| Duration::from_secs(x as u64))
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+/// Receives the datagrams answering a request until the timeout.
+fn receive(socket: &UdpSocket, timeout: Duration, answer: Answer) -> io::Result<Vec<Vec<u8>>> {
+    let deadline = Instant::now() + timeout;
+    let mut datagrams = vec![];
+    let mut buffer = vec![0; 65535];
+    while datagrams.len() < MAX_DATAGRAMS {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let wait = if datagrams.is_empty() {
+            remaining
+        } else {
+            remaining.min(IDLE_TIMEOUT)
+        };
+        if wait.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(wait))?;
+        match socket.recv(&mut buffer) {
+            Ok(n) => datagrams.push(buffer[..n].to_vec()),
+            // a timeout or an ICMP port unreachable
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::ConnectionRefused
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        }
+        if answer == Answer::First {
+            break;
+        }
+    }
+    Ok(datagrams)
+}
+
+/// Sends the request to the port of the target and returns the datagrams it answers with.
+async fn exchange(
+    context: &Context<'_>,
+    port: i64,
+    request: &[u8],
+    timeout: Option<i64>,
+    answer: Answer,
+) -> Result<Vec<Vec<u8>>, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let addr = resolve_ipaddr(context, context.target())?;
+    let source = source_addrs(context)?;
+    let _permit = context
+        .connection_limiter()
+        .acquire(context.target())
+        .await?;
+    let dst = source.socket_addr(addr, port);
+    let socket = bind_source_socket(&dst, &source)?;
+    socket.connect(dst)?;
+    socket.send(request)?;
+    if let Some(traffic) = context.traffic() {
+        traffic.sent(request.len())?;
+    }
+    let datagrams = receive(&socket, timeout_arg(timeout), answer)?;
+    if let Some(traffic) = context.traffic() {
+        traffic.received(datagrams.iter().map(Vec::len).sum())?;
+    }
+    Ok(datagrams)
+}
+
+/// *array* **ntp_version**(port: *int*, timeout: *int*);
+///
+/// Sends an NTPv4 client request to the port of the target, 123 by default, and returns the
+/// header of the response as array with the `leap` indicator, the `version`, the `mode`, the
+/// `stratum`, the `poll` interval, the `precision`, the `reference_id`, the `reference_time` and
+/// the `transmit_time` as Unix time. NULL is returned when the server does not answer.
+#[nasl_function(named(port, timeout))]
+async fn ntp_version(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let answer = exchange(
+        context,
+        port.unwrap_or(123),
+        &ntp::client_request(),
+        timeout,
+        Answer::First,
+    )
+    .await?;
+    Ok(answer
+        .first()
+        .and_then(|x| ntp::ServerResponse::parse(x))
+        .map(NaslValue::from)
+        .unwrap_or(NaslValue::Null))
+}
+
+/// *array* **ntp_monlist**(port: *int*, timeout: *int*);
+///
+/// Sends a mode 7 monlist request to the port of the target, 123 by default. Returns an array
+/// with the `request_bytes`, the number of response `datagrams`, the `response_bytes` and the
+/// number of monitor `entries` of the answer. NULL is returned when the server does not answer
+/// the request.
+#[nasl_function(named(port, timeout))]
+async fn ntp_monlist(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let request = ntp::monlist_request();
+    let answer = exchange(context, port.unwrap_or(123), &request, timeout, Answer::All).await?;
+    let responses: Vec<_> = answer
+        .iter()
+        .filter_map(|x| ntp::monlist_entries(x).map(|entries| (x.len(), entries)))
+        .collect();
+    if responses.is_empty() {
+        return Ok(NaslValue::Null);
+    }
+    let number = |x: usize| NaslValue::Number(x as i64);
+    Ok(NaslValue::Dict(HashMap::from([
+        ("request_bytes".to_string(), number(request.len())),
+        ("datagrams".to_string(), number(responses.len())),
+        (
+            "response_bytes".to_string(),
+            number(responses.iter().map(|x| x.0).sum()),
+        ),
+        (
+            "entries".to_string(),
+            number(responses.iter().map(|x| x.1).sum()),
+        ),
+    ])))
+}
+
+/// *array* **netbios_name_query**(port: *int*, timeout: *int*);
+///
+/// Sends a NetBIOS node status request to the port of the target, 137 by default. Returns an
+/// array with the registered `names`, each an array of the `name`, the `suffix` and whether it is
+/// a `group` name, and the `mac` address of the node. NULL is returned when the node does not
+/// answer.
+#[nasl_function(named(port, timeout))]
+async fn netbios_name_query(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let request = netbios::node_status_request(rand::thread_rng().gen());
+    let answer = exchange(
+        context,
+        port.unwrap_or(137),
+        &request,
+        timeout,
+        Answer::First,
+    )
+    .await?;
+    Ok(answer
+        .first()
+        .and_then(|x| netbios::NodeStatus::parse(x))
+        .map(NaslValue::from)
+        .unwrap_or(NaslValue::Null))
+}
+
+/// *array* **mdns_query**(name: *string*, qtype: *string*, port: *int*, timeout: *int*);
+///
+/// Sends a unicast mDNS question for the name and the record type qtype to the port of the target,
+/// 5353 by default. The name defaults to `_services._dns-sd._udp.local`, listing the services
+/// of the device, and the qtype to `PTR`.
+///
+/// Returns the answer, authority and additional records of the responses as array of arrays
+/// with the `name`, the `type`, the `ttl`, the `data` and, for A, AAAA, PTR, SRV and TXT records,
+/// the `text` of the record. An empty array is returned when the device does not answer.
+#[nasl_function(named(name, qtype, port, timeout))]
+async fn mdns_query(
+    context: &Context<'_>,
+    name: Option<&str>,
+    qtype: Option<&str>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let rtype = qtype.unwrap_or("PTR");
+    let rtype = dns::record_type(rtype)
+        .ok_or_else(|| FunctionErrorKind::wrong_argument("qtype", "DNS record type", rtype))?;
+    let mut question = vec![];
+    dns::push_name(
+        &mut question,
+        name.unwrap_or("_services._dns-sd._udp.local"),
+    );
+    // the unicast response bit within the class
+    let request = dns::query(0, 0, &question, rtype, 0x8001);
+    let answer = exchange(
+        context,
+        port.unwrap_or(5353),
+        &request,
+        timeout,
+        Answer::All,
+    )
+    .await?;
+    let mut records = vec![];
+    for datagram in answer {
+        let mut message = dns::Message::new(&datagram);
+        let Some(count) = message.header() else {
+            continue;
+        };
+        for record in (0..count).map_while(|_| message.record()) {
+            let mut dict = HashMap::from([
+                ("name".to_string(), NaslValue::String(record.name.into())),
+                ("type".to_string(), NaslValue::Number(record.rtype as i64)),
+                ("ttl".to_string(), NaslValue::Number(record.ttl as i64)),
+                ("data".to_string(), NaslValue::Data(record.data)),
+            ]);
+            if let Some(text) = record.text {
+                dict.insert("text".to_string(), NaslValue::String(text.into()));
+            }
+            records.push(NaslValue::Dict(dict));
+        }
+    }
+    Ok(NaslValue::Array(records))
+}
+
+/// *array* **ssdp_search**(st: *string*, port: *int*, timeout: *int*);
+///
+/// Sends an SSDP M-SEARCH for the search target, `ssdp:all` by default, to the port of the
+/// target, 1900 by default. Returns the responses as array of arrays with the `status` line and
+/// the headers by their lowercase names, e.g. `server`, `location` and `usn`. An empty array is
+/// returned when the device does not answer.
+#[nasl_function(named(st, port, timeout))]
+async fn ssdp_search(
+    context: &Context<'_>,
+    st: Option<&str>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let request = ssdp::search_request(st.unwrap_or("ssdp:all"));
+    let answer = exchange(
+        context,
+        port.unwrap_or(1900),
+        &request,
+        timeout,
+        Answer::All,
+    )
+    .await?;
+    let responses = answer
+        .iter()
+        .filter_map(|x| ssdp::parse_response(x))
+        .map(|(status, headers)| {
+            let mut dict: HashMap<String, NaslValue> = headers
+                .into_iter()
+                .map(|(name, value)| (name, NaslValue::String(value.into())))
+                .collect();
+            dict.insert("status".to_string(), NaslValue::String(status.into()));
+            NaslValue::Dict(dict)
+        })
+        .collect();
+    Ok(NaslValue::Array(responses))
+}
+
+pub struct Discovery;
+
+function_set! {
+    Discovery,
+    async_stateless,
+    (
+        ntp_version,
+        ntp_monlist,
+        netbios_name_query,
+        mdns_query,
+        ssdp_search
+    )
+}
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! NetBIOS node status requests of RFC 1002.
+
+use std::collections::HashMap;
+
+use crate::nasl::prelude::*;
+
+use super::dns::{self, Message};
+
+const TYPE_NBSTAT: u16 = 0x21;
+const CLASS_IN: u16 = 1;
+const GROUP_NAME: u16 = 0x8000;
+
+/// Returns a node status request for the wildcard name.
+pub fn node_status_request(id: u16) -> Vec<u8> {
+    let mut name = [0u8; 16];
+    name[0] = b'*';
+    // first-level encoding, each half byte as a letter
+    let mut question = vec![32];
+    for x in name {
+        question.push(b'A' + (x >> 4));
+        question.push(b'A' + (x & 0xf));
+    }
+    question.push(0);
+    dns::query(id, 0, &question, TYPE_NBSTAT, CLASS_IN)
+}
+
+/// A name registered by a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name {
+    pub name: String,
+    /// The type of the name, e.g. 0x20 for the file server service
+    pub suffix: u8,
+    pub group: bool,
+}
+
+/// The node status response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub names: Vec<Name>,
+    /// The unit id, usually the MAC address
+    pub mac: String,
+}
+
+impl NodeStatus {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut message = Message::new(data);
+        if message.header()? == 0 {
+            return None;
+        }
+        message.name()?;
+        if message.u16()? != TYPE_NBSTAT {
+            return None;
+        }
+        // class, ttl and length of the data
+        message.take(8)?;
+        let count = message.take(1)?[0];
+        let names = (0..count)
+            .map(|_| {
+                let entry = message.take(18)?;
+                Some(Name {
+                    name: String::from_utf8_lossy(&entry[..15]).trim_end().to_owned(),
+                    suffix: entry[15],
+                    group: u16::from_be_bytes([entry[16], entry[17]]) & GROUP_NAME != 0,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let mac = message
+            .take(6)
+            .map(|x| {
+                x.iter()
+                    .map(|x| format!("{x:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+            .unwrap_or_default();
+        Some(Self { names, mac })
+    }
+}
+
+impl From<NodeStatus> for NaslValue {
+    fn from(value: NodeStatus) -> Self {
+        let names = value
+            .names
+            .into_iter()
+            .map(|x| {
+                NaslValue::Dict(HashMap::from([
+                    ("name".to_string(), NaslValue::String(x.name.into())),
+                    ("suffix".to_string(), NaslValue::Number(x.suffix as i64)),
+                    ("group".to_string(), NaslValue::Boolean(x.group)),
+                ]))
+            })
+            .collect();
+        NaslValue::Dict(HashMap::from([
+            ("names".to_string(), NaslValue::Array(names)),
+            ("mac".to_string(), NaslValue::String(value.mac.into())),
+        ]))
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! NTP client requests and mode 7 monlist requests.
+
+use std::{collections::HashMap, net::Ipv4Addr};
+
+use crate::nasl::prelude::*;
+
+/// Seconds between the NTP and the Unix epoch
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+const MODE_CLIENT: u8 = 3;
+const MODE_PRIVATE: u8 = 7;
+const IMPL_XNTPD: u8 = 3;
+const REQ_MON_GETLIST_1: u8 = 42;
+
+/// Returns an NTPv4 client request.
+pub fn client_request() -> Vec<u8> {
+    let mut request = vec![0; 48];
+    request[0] = (4 << 3) | MODE_CLIENT;
+    request
+}
+
+/// Returns a mode 7 MON_GETLIST_1 request as sent by ntpdc.
+pub fn monlist_request() -> Vec<u8> {
+    let mut request = vec![0; 48];
+    request[0] = (2 << 3) | MODE_PRIVATE;
+    request[2] = IMPL_XNTPD;
+    request[3] = REQ_MON_GETLIST_1;
+    request
+}
+
+/// The header of an NTP server response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerResponse {
+    pub leap: u8,
+    pub version: u8,
+    pub mode: u8,
+    pub stratum: u8,
+    pub poll: i8,
+    pub precision: i8,
+    /// The reference clock for stratum 0 and 1 servers, otherwise the IPv4 address or hash of
+    /// the upstream server
+    pub reference_id: String,
+    /// Unix time the clock was last set
+    pub reference_time: i64,
+    /// Unix time the response was sent
+    pub transmit_time: i64,
+}
+
+fn unix_time(data: &[u8]) -> i64 {
+    let seconds = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as i64;
+    if seconds == 0 {
+        0
+    } else {
+        seconds - NTP_UNIX_OFFSET
+    }
+}
+
+impl ServerResponse {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 48 {
+            return None;
+        }
+        let stratum = data[1];
+        let reference = &data[12..16];
+        let reference_id = if stratum <= 1 {
+            String::from_utf8_lossy(reference)
+                .trim_end_matches('\0')
+                .to_owned()
+        } else {
+            Ipv4Addr::new(reference[0], reference[1], reference[2], reference[3]).to_string()
+        };
+        Some(Self {
+            leap: data[0] >> 6,
+            version: (data[0] >> 3) & 0x7,
+            mode: data[0] & 0x7,
+            stratum,
+            poll: data[2] as i8,
+            precision: data[3] as i8,
+            reference_id,
+            reference_time: unix_time(&data[16..20]),
+            transmit_time: unix_time(&data[40..44]),
+        })
+    }
+}
+
+impl From<ServerResponse> for NaslValue {
+    fn from(value: ServerResponse) -> Self {
+        let number = |x: i64| NaslValue::Number(x);
+        NaslValue::Dict(HashMap::from([
+            ("leap".to_string(), number(value.leap as i64)),
+            ("version".to_string(), number(value.version as i64)),
+            ("mode".to_string(), number(value.mode as i64)),
+            ("stratum".to_string(), number(value.stratum as i64)),
+            ("poll".to_string(), number(value.poll as i64)),
+            ("precision".to_string(), number(value.precision as i64)),
+            (
+                "reference_id".to_string(),
+                NaslValue::String(value.reference_id.into()),
+            ),
+            ("reference_time".to_string(), number(value.reference_time)),
+            ("transmit_time".to_string(), number(value.transmit_time)),
+        ]))
+    }
+}
+
+/// Returns the number of monitor entries within a mode 7 response to MON_GETLIST_1, None when
+/// the datagram is no such response.
+pub fn monlist_entries(data: &[u8]) -> Option<usize> {
+    // the response bit, the mode and the request code
+    if data.len() < 8
+        || data[0] & 0x80 == 0
+        || data[0] & 0x7 != MODE_PRIVATE
+        || data[3] != REQ_MON_GETLIST_1
+    {
+        return None;
+    }
+    // an error code within the upper nibble
+    if data[4] >> 4 != 0 {
+        return Some(0);
+    }
+    Some((u16::from_be_bytes([data[4], data[5]]) & 0x0fff) as usize)
+}
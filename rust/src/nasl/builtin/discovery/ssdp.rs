@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! SSDP M-SEARCH requests of UPnP.
+
+use std::collections::HashMap;
+
+/// The multicast group of SSDP, devices expect it as host even for unicast requests
+const MULTICAST_HOST: &str = "239.255.255.250:1900";
+
+/// Returns a M-SEARCH request for the search target.
+pub fn search_request(target: &str) -> Vec<u8> {
+    format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {MULTICAST_HOST}\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: {target}\r\n\r\n"
+    )
+    .into_bytes()
+}
+
+/// Parses a search response into its status line and its headers with lowercase names.
+pub fn parse_response(data: &[u8]) -> Option<(String, HashMap<String, String>)> {
+    let response = String::from_utf8_lossy(data);
+    let mut lines = response.lines();
+    let status = lines.next()?.trim();
+    if !status.starts_with("HTTP/") {
+        return None;
+    }
+    let headers = lines
+        .filter_map(|x| x.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_owned()))
+        .collect();
+    Some((status.to_owned(), headers))
+}
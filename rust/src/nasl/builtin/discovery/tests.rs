@@ -0,0 +1,254 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, thread};
+
+    use super::super::{dns, netbios, ntp};
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    /// Answers the first request with the datagrams returned for it.
+    fn server(answer: impl FnOnce(&[u8]) -> Vec<Vec<u8>> + Send + 'static) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buffer = [0; 1500];
+            let (n, peer) = socket.recv_from(&mut buffer).unwrap();
+            for datagram in answer(&buffer[..n]) {
+                socket.send_to(&datagram, peer).unwrap();
+            }
+        });
+        port
+    }
+
+    fn builder() -> TestBuilder<crate::nasl::NoOpLoader, crate::storage::DefaultDispatcher> {
+        TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())))
+    }
+
+    fn ntp_response() -> Vec<u8> {
+        let mut response = vec![0; 48];
+        response[0] = (4 << 3) | 4;
+        response[1] = 1;
+        response[2] = 6;
+        response[3] = 0xe9;
+        response[12..16].copy_from_slice(b"GPS\0");
+        // 2024-01-01T00:00:00Z
+        response[40..44].copy_from_slice(&(1_704_067_200u32 + 2_208_988_800).to_be_bytes());
+        response
+    }
+
+    #[test]
+    fn ntp_parse() {
+        let response = ntp::ServerResponse::parse(&ntp_response()).unwrap();
+        assert_eq!(response.version, 4);
+        assert_eq!(response.mode, 4);
+        assert_eq!(response.stratum, 1);
+        assert_eq!(response.precision, -23);
+        assert_eq!(response.reference_id, "GPS");
+        assert_eq!(response.reference_time, 0);
+        assert_eq!(response.transmit_time, 1_704_067_200);
+        assert_eq!(ntp::ServerResponse::parse(&[0; 47]), None);
+
+        let request = ntp::monlist_request();
+        assert_eq!(&request[..4], &[0x17, 0, 3, 42]);
+        assert_eq!(
+            ntp::monlist_entries(&[0x97, 0, 3, 42, 0, 6, 0, 72]),
+            Some(6)
+        );
+        assert_eq!(
+            ntp::monlist_entries(&[0x97, 0, 3, 42, 0x40, 0, 0, 0]),
+            Some(0)
+        );
+        assert_eq!(ntp::monlist_entries(&request), None);
+    }
+
+    #[test]
+    fn ntp_version() {
+        let port = server(|request| {
+            assert_eq!(request[0], 0x23);
+            vec![ntp_response()]
+        });
+        let mut t = builder();
+        t.run(&format!("a = ntp_version(port: {port});"));
+        t.ok(r#"a["version"];"#, 4);
+        t.ok(r#"a["stratum"];"#, 1);
+        t.ok(r#"a["reference_id"];"#, "GPS");
+    }
+
+    #[test]
+    fn ntp_monlist() {
+        let port = server(|request| {
+            assert_eq!(request[3], 42);
+            (0..3)
+                .map(|_| {
+                    let mut response = vec![0x97, 0, 3, 42, 0, 6, 0, 72];
+                    response.resize(8 + 6 * 72, 0);
+                    response
+                })
+                .collect()
+        });
+        let mut t = builder();
+        t.run(&format!("a = ntp_monlist(port: {port}, timeout: 1);"));
+        t.ok(r#"a["request_bytes"];"#, 48);
+        t.ok(r#"a["datagrams"];"#, 3);
+        t.ok(r#"a["response_bytes"];"#, 3 * 440);
+        t.ok(r#"a["entries"];"#, 18);
+    }
+
+    fn node_status(request: &[u8]) -> Vec<u8> {
+        // the request without its counts, followed by the answer
+        let mut response = request[..2].to_vec();
+        response.extend_from_slice(&[0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        response.extend_from_slice(&request[12..46]);
+        response.extend_from_slice(&[0, 0x21, 0, 1, 0, 0, 0, 0, 0, 65]);
+        response.push(2);
+        for (name, suffix, flags) in [("WORKSTATION", 0x20, 0x0400), ("WORKGROUP", 0, 0x8400)] {
+            response.extend_from_slice(format!("{name:<15}").as_bytes());
+            response.push(suffix);
+            response.extend_from_slice(&(flags as u16).to_be_bytes());
+        }
+        response.extend_from_slice(&[0, 0x1b, 0x21, 0xaa, 0xbb, 0xcc]);
+        response.resize(response.len() + 46 - 6, 0);
+        response
+    }
+
+    #[test]
+    fn netbios_parse() {
+        let request = netbios::node_status_request(0x1234);
+        assert_eq!(request.len(), 50);
+        assert_eq!(&request[12..15], &[32, b'C', b'K']);
+        assert_eq!(&request[46..], &[0, 0x21, 0, 1]);
+        let status = netbios::NodeStatus::parse(&node_status(&request)).unwrap();
+        assert_eq!(
+            status.names,
+            vec![
+                netbios::Name {
+                    name: "WORKSTATION".into(),
+                    suffix: 0x20,
+                    group: false,
+                },
+                netbios::Name {
+                    name: "WORKGROUP".into(),
+                    suffix: 0,
+                    group: true,
+                },
+            ]
+        );
+        assert_eq!(status.mac, "00:1b:21:aa:bb:cc");
+    }
+
+    #[test]
+    fn netbios_name_query() {
+        let port = server(|request| vec![node_status(request)]);
+        let mut t = builder();
+        t.run(&format!("a = netbios_name_query(port: {port});"));
+        t.ok(r#"a["mac"];"#, "00:1b:21:aa:bb:cc");
+        t.run(r#"names = a["names"];"#);
+        t.run("n = names[1];");
+        t.ok(r#"n["name"];"#, "WORKGROUP");
+        t.ok(r#"n["group"];"#, true);
+    }
+
+    /// Returns an mDNS response with compressed names.
+    fn mdns_response() -> Vec<u8> {
+        let mut response = vec![0, 0, 0x84, 0, 0, 0, 0, 3, 0, 0, 0, 0];
+        // _http._tcp.local PTR printer._http._tcp.local
+        dns::push_name(&mut response, "_http._tcp.local");
+        response.extend_from_slice(&[0, 12, 0, 1, 0, 0, 0, 120, 0, 10]);
+        response.extend_from_slice(&[7]);
+        response.extend_from_slice(b"printer");
+        response.extend_from_slice(&[0xc0, 12]);
+        // printer._http._tcp.local SRV 0 0 631 printer.local
+        response.extend_from_slice(&[0xc0, 40]);
+        response.extend_from_slice(&[0, 33, 0x80, 1, 0, 0, 0, 120, 0, 21]);
+        response.extend_from_slice(&[0, 0, 0, 0, 2, 0x77, 7]);
+        response.extend_from_slice(b"printer");
+        response.extend_from_slice(&[5]);
+        response.extend_from_slice(b"local");
+        response.push(0);
+        // printer._http._tcp.local TXT
+        response.extend_from_slice(&[0xc0, 40]);
+        response.extend_from_slice(&[0, 16, 0x80, 1, 0, 0, 0, 120, 0, 12]);
+        response.extend_from_slice(&[5]);
+        response.extend_from_slice(b"rp=ip");
+        response.extend_from_slice(&[5]);
+        response.extend_from_slice(b"ty=HP");
+        response
+    }
+
+    #[test]
+    fn dns_parse() {
+        let response = mdns_response();
+        let mut message = dns::Message::new(&response);
+        assert_eq!(message.header(), Some(3));
+        let records: Vec<_> = (0..3).map_while(|_| message.record()).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].name, "_http._tcp.local");
+        assert_eq!(records[0].text.as_deref(), Some("printer._http._tcp.local"));
+        assert_eq!(records[1].name, "printer._http._tcp.local");
+        assert_eq!(records[1].text.as_deref(), Some("0 0 631 printer.local"));
+        assert_eq!(records[2].text.as_deref(), Some("rp=ip\nty=HP"));
+
+        // a compression pointer to itself
+        let mut looping = vec![0; 12];
+        looping.extend_from_slice(&[0xc0, 12]);
+        assert_eq!(dns::Message::new(&looping[12..]).name(), None);
+        assert_eq!(dns::record_type("srv"), Some(33));
+    }
+
+    #[test]
+    fn mdns_query() {
+        let port = server(|request| {
+            // the unicast response bit
+            assert!(request.ends_with(&[0, 12, 0x80, 1]));
+            vec![mdns_response()]
+        });
+        let mut t = builder();
+        t.run(&format!(
+            r#"a = mdns_query(name: "_http._tcp.local", port: {port}, timeout: 1);"#
+        ));
+        t.ok("max_index(a);", 3);
+        t.run("r = a[1];");
+        t.ok(r#"r["type"];"#, 33);
+        t.ok(r#"r["text"];"#, "0 0 631 printer.local");
+    }
+
+    #[test]
+    fn ssdp_search() {
+        let port = server(|request| {
+            assert!(request.starts_with(b"M-SEARCH * HTTP/1.1\r\n"));
+            vec![b"HTTP/1.1 200 OK\r\nSERVER: Linux/3.4 UPnP/1.0 MiniUPnPd/1.8\r\nLOCATION: http://127.0.0.1:5000/rootDesc.xml\r\nST: upnp:rootdevice\r\n\r\n".to_vec()]
+        });
+        let mut t = builder();
+        t.run(&format!(
+            r#"a = ssdp_search(st: "upnp:rootdevice", port: {port}, timeout: 1);"#
+        ));
+        t.ok("max_index(a);", 1);
+        t.run("r = a[0];");
+        t.ok(r#"r["status"];"#, "HTTP/1.1 200 OK");
+        t.ok(r#"r["server"];"#, "Linux/3.4 UPnP/1.0 MiniUPnPd/1.8");
+    }
+
+    #[test]
+    fn no_answer() {
+        // nothing listens on the port of a closed socket
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut t = builder();
+        t.ok(
+            &format!("ntp_version(port: {port}, timeout: 1);"),
+            NaslValue::Null,
+        );
+        t.ok(
+            &format!("max_index(ssdp_search(port: {port}, timeout: 1));"),
+            0,
+        );
+    }
+}
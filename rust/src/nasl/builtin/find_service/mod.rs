@@ -0,0 +1,312 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the built-in service detection `find_service.nasl` runs via `plugin_run_find_service`.
+//!
+//! Each open TCP port found by the port scanners (`Ports/tcp/*`) that is not known yet is probed
+//! once:
+//! - When the service greets first, the greeting identifies ftp, smtp, pop3, imap or ssh. For
+//!   ftp, smtp, pop3 and imap the STARTTLS command of the protocol is sent and on a positive
+//!   answer a TLS handshake is tried, a successful handshake sets `<service>/<port>/starttls`.
+//! - When the service stays silent a TLS ClientHello is sent. A successful handshake sets
+//!   `Transports/TCP/<port>` to the encapsulation of the negotiated version and the negotiated
+//!   ALPN protocol as `TLS/<port>/alpn`. The service is then identified by its greeting within
+//!   TLS or by the ALPN protocol.
+//!
+//! Identified services are stored as `Services/<service>` and `Known/tcp/<port>` like the
+//! service detection of the feed does.
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    time::Duration,
+};
+
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, ProtocolVersion};
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::zone;
+use crate::storage::{Field, Kb, Retrieve};
+
+use super::http::NoVerifier;
+use super::network::{network_utils::connect_target, transport_key, OpenvasEncaps};
+
+/// Time to wait for a greeting or a reply of the service
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Replies are not read beyond this size
+const MAX_REPLY: usize = 8192;
+/// ALPN protocols offered within the ClientHello
+const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// Service identified on a port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    Ftp,
+    Smtp,
+    Pop3,
+    Imap,
+    Ssh,
+    Www,
+}
+
+impl Service {
+    /// Identifies a service by its greeting.
+    fn from_greeting(greeting: &str) -> Option<Self> {
+        let upper = greeting.to_uppercase();
+        if upper.starts_with("SSH-") {
+            Some(Self::Ssh)
+        } else if upper.starts_with("+OK") {
+            Some(Self::Pop3)
+        } else if upper.starts_with("* OK") {
+            Some(Self::Imap)
+        } else if upper.starts_with("220") && upper.contains("FTP") {
+            Some(Self::Ftp)
+        } else if upper.starts_with("220") {
+            Some(Self::Smtp)
+        } else {
+            None
+        }
+    }
+
+    /// Identifies a service by the ALPN protocol negotiated within the TLS handshake.
+    fn from_alpn(alpn: &str) -> Option<Self> {
+        match alpn {
+            "h2" | "http/1.1" => Some(Self::Www),
+            _ => None,
+        }
+    }
+
+    /// Name as used within the KB by the service detection of the feed
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Ftp => "ftp",
+            Self::Smtp => "smtp",
+            Self::Pop3 => "pop3",
+            Self::Imap => "imap",
+            Self::Ssh => "ssh",
+            Self::Www => "www",
+        }
+    }
+
+    /// Returns the commands upgrading the connection to TLS and the start of a positive reply
+    /// to the last one.
+    fn starttls(&self) -> Option<(&'static [&'static str], &'static str)> {
+        match self {
+            Self::Smtp => Some((&["EHLO localhost\r\n", "STARTTLS\r\n"], "220")),
+            Self::Ftp => Some((&["AUTH TLS\r\n"], "234")),
+            Self::Pop3 => Some((&["STLS\r\n"], "+OK")),
+            Self::Imap => Some((&["A001 STARTTLS\r\n"], "A001 OK")),
+            Self::Ssh | Self::Www => None,
+        }
+    }
+}
+
+/// Negotiated parameters of a TLS handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tls {
+    encaps: i64,
+    alpn: Option<String>,
+}
+
+impl Tls {
+    fn of(connection: &ClientConnection) -> Self {
+        let encaps = match connection.protocol_version() {
+            Some(ProtocolVersion::TLSv1_3) => OpenvasEncaps::Tls13,
+            Some(ProtocolVersion::TLSv1_2) => OpenvasEncaps::Tls12,
+            _ => OpenvasEncaps::Ssl23,
+        };
+        Self {
+            encaps: encaps as i64,
+            alpn: connection
+                .alpn_protocol()
+                .map(|x| String::from_utf8_lossy(x).into_owned()),
+        }
+    }
+}
+
+/// What the service detection found on a port
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Detection {
+    service: Option<Service>,
+    tls: Option<Tls>,
+    starttls: bool,
+}
+
+impl Detection {
+    fn kb_items(&self, port: u16) -> Vec<Kb> {
+        let mut items = vec![];
+        if let Some(tls) = &self.tls {
+            items.push(Kb::from((transport_key(port), tls.encaps)));
+            if let Some(alpn) = &tls.alpn {
+                items.push(Kb::from((format!("TLS/{port}/alpn"), alpn.as_str())));
+            }
+        }
+        if let Some(service) = self.service {
+            items.push(Kb::from((
+                format!("Services/{}", service.name()),
+                port as i64,
+            )));
+            items.push(Kb::from((format!("Known/tcp/{port}"), service.name())));
+            if self.starttls {
+                items.push(Kb::from((format!("{}/{port}/starttls", service.name()), 1)));
+            }
+        }
+        items
+    }
+}
+
+/// Returns true for the last line of a reply, multi-line replies of ftp and smtp continue with
+/// a `-` after the status code.
+fn is_last_line(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    !(bytes.len() > 3 && bytes[..3].iter().all(u8::is_ascii_digit) && bytes[3] == b'-')
+}
+
+/// Reads a reply until its last line is complete, the connection is closed or the read timeout
+/// elapses.
+fn read_reply<S: Read>(stream: &mut S) -> String {
+    let mut reply = vec![];
+    let mut buffer = [0; 1024];
+    while reply.len() < MAX_REPLY {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => reply.extend_from_slice(&buffer[..n]),
+        }
+        let text = String::from_utf8_lossy(&reply);
+        if text.ends_with('\n') && text.lines().last().is_some_and(is_last_line) {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&reply).into_owned()
+}
+
+fn tls_config() -> ClientConfig {
+    let mut config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoVerifier))
+        .with_no_client_auth();
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|x| x.to_vec()).collect();
+    config
+}
+
+/// Sends a ClientHello and completes the handshake.
+///
+/// Returns None when the service does not speak TLS.
+fn handshake<S: Read + Write>(stream: &mut S, server: &str) -> Option<ClientConnection> {
+    let name = ServerName::try_from(server.to_owned()).ok()?;
+    let mut connection = ClientConnection::new(Arc::new(tls_config()), name).ok()?;
+    while connection.is_handshaking() {
+        connection.complete_io(stream).ok()?;
+    }
+    Some(connection)
+}
+
+/// Sends the STARTTLS commands of the service and upgrades the connection.
+fn starttls(stream: &mut TcpStream, service: Service, server: &str) -> io::Result<bool> {
+    let Some((commands, positive)) = service.starttls() else {
+        return Ok(false);
+    };
+    let mut reply = String::new();
+    for command in commands {
+        stream.write_all(command.as_bytes())?;
+        reply = read_reply(stream);
+    }
+    let last = reply.lines().last().unwrap_or_default();
+    Ok(last.starts_with(positive) && handshake(stream, server).is_some())
+}
+
+/// Probes a connected port.
+fn detect(stream: &mut TcpStream, server: &str) -> io::Result<Detection> {
+    let greeting = read_reply(stream);
+    if let Some(service) = Service::from_greeting(&greeting) {
+        return Ok(Detection {
+            service: Some(service),
+            tls: None,
+            starttls: starttls(stream, service, server)?,
+        });
+    }
+    if !greeting.is_empty() {
+        return Ok(Detection::default());
+    }
+    let Some(mut connection) = handshake(stream, server) else {
+        return Ok(Detection::default());
+    };
+    let tls = Tls::of(&connection);
+    let greeting = read_reply(&mut rustls::Stream::new(&mut connection, stream));
+    let service = Service::from_greeting(&greeting)
+        .or_else(|| tls.alpn.as_deref().and_then(Service::from_alpn));
+    Ok(Detection {
+        service,
+        tls: Some(tls),
+        starttls: false,
+    })
+}
+
+fn kb_keys(context: &Context<'_>, pattern: &str) -> Result<Vec<String>, FunctionErrorKind> {
+    Ok(context
+        .retriever()
+        .retrieve(context.key(), Retrieve::KB(pattern.to_owned()))?
+        .filter_map(|x| match x {
+            Field::KB(kb) => Some(kb.key),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Returns the open TCP ports whose service is not known yet.
+fn unknown_ports(context: &Context<'_>) -> Result<Vec<u16>, FunctionErrorKind> {
+    let known = kb_keys(context, "Known/tcp/*")?;
+    let mut ports: Vec<u16> = kb_keys(context, "Ports/tcp/*")?
+        .iter()
+        .filter_map(|x| x.strip_prefix("Ports/tcp/")?.parse().ok())
+        .filter(|x| !known.contains(&format!("Known/tcp/{x}")))
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
+}
+
+/// *void* **plugin_run_find_service**();
+///
+/// Detects the services and the TLS support of the open TCP ports of the target and stores them
+/// within the KB.
+#[nasl_function]
+async fn plugin_run_find_service(context: &Context<'_>) -> Result<NaslValue, FunctionErrorKind> {
+    let ports = unknown_ports(context)?;
+    if ports.is_empty() {
+        return Ok(NaslValue::Null);
+    }
+    let server = zone::strip(context.target());
+    for port in ports {
+        let Some((mut stream, _permit)) = connect_target(context, port, REPLY_TIMEOUT).await?
+        else {
+            continue;
+        };
+        let detection = match detect(&mut stream, server) {
+            Ok(x) => x,
+            Err(error) => {
+                tracing::debug!(port, %error, "unable to detect service");
+                continue;
+            }
+        };
+        for kb in detection.kb_items(port) {
+            context
+                .dispatcher()
+                .dispatch(context.key(), Field::KB(kb))?;
+        }
+    }
+    Ok(NaslValue::Null)
+}
+
+pub struct FindService;
+
+function_set! {
+    FindService,
+    async_stateless,
+    (plugin_run_find_service)
+}
@@ -0,0 +1,157 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use mock_target::{MockServer, Service as MockService};
+
+    use super::super::{is_last_line, Detection, Service, Tls};
+    use crate::nasl::test_prelude::*;
+    use crate::storage::{ContextKey, Kb};
+
+    #[test]
+    fn greeting() {
+        assert_eq!(
+            Service::from_greeting("220 mail.example.com ESMTP Postfix\r\n"),
+            Some(Service::Smtp)
+        );
+        assert_eq!(
+            Service::from_greeting("220 (vsFTPd 3.0.3)\r\n"),
+            Some(Service::Ftp)
+        );
+        assert_eq!(
+            Service::from_greeting("+OK Dovecot ready.\r\n"),
+            Some(Service::Pop3)
+        );
+        assert_eq!(
+            Service::from_greeting("* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n"),
+            Some(Service::Imap)
+        );
+        assert_eq!(
+            Service::from_greeting("SSH-2.0-OpenSSH_9.2\r\n"),
+            Some(Service::Ssh)
+        );
+        assert_eq!(Service::from_greeting("HTTP/1.1 400 Bad Request"), None);
+        assert_eq!(Service::from_alpn("h2"), Some(Service::Www));
+        assert!(!is_last_line("250-mail.example.com"));
+        assert!(is_last_line("250 STARTTLS"));
+        assert!(is_last_line("* OK ready"));
+    }
+
+    #[test]
+    fn kb_items() {
+        let detection = Detection {
+            service: Some(Service::Www),
+            tls: Some(Tls {
+                encaps: 8,
+                alpn: Some("h2".into()),
+            }),
+            starttls: false,
+        };
+        assert_eq!(
+            detection.kb_items(8443),
+            vec![
+                Kb::from(("Transports/TCP/8443", 8)),
+                Kb::from(("TLS/8443/alpn", "h2")),
+                Kb::from(("Services/www", 8443)),
+                Kb::from(("Known/tcp/8443", "www")),
+            ]
+        );
+        let detection = Detection {
+            service: Some(Service::Smtp),
+            tls: None,
+            starttls: true,
+        };
+        assert_eq!(
+            detection.kb_items(25),
+            vec![
+                Kb::from(("Services/smtp", 25)),
+                Kb::from(("Known/tcp/25", "smtp")),
+                Kb::from(("smtp/25/starttls", 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn smtp_without_starttls() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(b"220-mail.example.com\r\n220 ESMTP\r\n")
+                .unwrap();
+            let mut buffer = [0; 256];
+            for reply in [
+                &b"250-mail.example.com\r\n250 SIZE\r\n"[..],
+                b"454 TLS not available\r\n",
+            ] {
+                if stream.read(&mut buffer).unwrap_or_default() == 0 {
+                    break;
+                }
+                stream.write_all(reply).unwrap();
+            }
+        });
+
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.ok(
+            &format!(r#"set_kb_item(name: "Ports/tcp/{port}", value: 1);"#),
+            NaslValue::Null,
+        );
+        t.ok("plugin_run_find_service();", NaslValue::Null);
+        t.ok(&format!(r#"get_kb_item("Known/tcp/{port}");"#), "smtp");
+        t.ok(r#"get_kb_item("Services/smtp");"#, port as i64);
+        t.ok(
+            &format!(r#"get_kb_item("smtp/{port}/starttls");"#),
+            NaslValue::Null,
+        );
+    }
+
+    #[test]
+    fn mock_services() {
+        let ssh = MockServer::start(MockService::ssh("OpenSSH_9.2")).unwrap();
+        let ftp = MockServer::start(MockService::ftp("(vsFTPd 3.0.3)")).unwrap();
+        let smtps = MockServer::start_tls(MockService::smtp("mail.example.com", "ESMTP")).unwrap();
+
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        for port in [ssh.port(), ftp.port(), smtps.port()] {
+            t.ok(
+                &format!(r#"set_kb_item(name: "Ports/tcp/{port}", value: 1);"#),
+                NaslValue::Null,
+            );
+        }
+        t.ok("plugin_run_find_service();", NaslValue::Null);
+        t.ok(
+            &format!(r#"get_kb_item("Known/tcp/{}");"#, ssh.port()),
+            "ssh",
+        );
+        t.ok(
+            &format!(r#"get_kb_item("Known/tcp/{}");"#, ftp.port()),
+            "ftp",
+        );
+        t.ok(
+            &format!(r#"get_kb_item("ftp/{}/starttls");"#, ftp.port()),
+            NaslValue::Null,
+        );
+        t.ok(
+            &format!(r#"get_kb_item("Known/tcp/{}");"#, smtps.port()),
+            "smtp",
+        );
+        t.ok(
+            &format!(r#"get_kb_item("Transports/TCP/{}");"#, smtps.port()),
+            8,
+        );
+        // the code is run when the builder is dropped
+        drop(t);
+        assert_eq!(ftp.received(), vec![b"AUTH TLS\r\n".to_vec()]);
+    }
+}
@@ -8,64 +8,104 @@ mod tests;
 use std::{net::IpAddr, str::FromStr};
 
 use crate::function_set;
-use crate::nasl::utils::{error::FunctionErrorKind, lookup_keys::TARGET};
+use crate::nasl::prelude::nasl_function;
+use crate::nasl::utils::dns::{KB_FQDN, KB_HOSTNAME, KB_RDNS};
+use crate::nasl::utils::error::FunctionErrorKind;
+use crate::storage::{Field, Retrieve};
 
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::{Context, ContextType, Register};
+use crate::nasl::utils::lookup_keys::VHOST;
+use crate::nasl::utils::{zone, Context, ContextType, Register};
 
-/// Resolves IP address of target to hostname
-///
-/// It does lookup TARGET and when not found falls back to 127.0.0.1 to resolve.
-/// If the TARGET is not a IP address than we assume that it already is a fqdn or a hostname and will return that instead.
-fn resolve_hostname(register: &Register) -> Result<String, FunctionErrorKind> {
-    use std::net::ToSocketAddrs;
-
-    let default_ip = "127.0.0.1";
-    // currently we use shadow variables as _FC_ANON_ARGS; the original openvas uses redis for that purpose.
-    let target = register.named(TARGET).map_or_else(
-        || default_ip.to_owned(),
-        |x| match x {
-            ContextType::Value(NaslValue::String(x)) => x.clone(),
-            _ => default_ip.to_owned(),
-        },
-    );
+/// Returns the first value of a host name KB item that is populated at the start of each host
+fn kb_name(context: &Context, key: &str) -> Result<Option<String>, FunctionErrorKind> {
+    Ok(context
+        .retriever()
+        .retrieve(context.key(), Retrieve::KB(key.to_owned()))?
+        .find_map(|x| match x {
+            Field::KB(kb) => Some(kb.value.to_string()),
+            _ => None,
+        })
+        .filter(|x| !x.is_empty()))
+}
 
-    match target.to_socket_addrs() {
-        Ok(mut addr) => Ok(addr.next().map_or_else(String::new, |x| x.to_string())),
-        // assumes that target is already a hostname
-        Err(_) => Ok(target),
-    }
+/// Returns the host name of the target or the target itself when there is none.
+///
+/// Falls back to 127.0.0.1 when there is no target.
+fn host_name(context: &Context) -> Result<String, FunctionErrorKind> {
+    Ok(
+        kb_name(context, KB_HOSTNAME)?.unwrap_or_else(|| match context.target() {
+            "" => "127.0.0.1".to_owned(),
+            x => x.to_owned(),
+        }),
+    )
 }
 
-/// NASL function to get all stored vhosts
+/// NASL function to get all known names of the target
 ///
-/// As of now (2023-01-20) there is no vhost handling.
-/// Therefore this function does load the registered TARGET and if it is an IP Address resolves it via DNS instead.
-fn get_host_names(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
-    resolve_hostname(register).map(|x| NaslValue::Array(vec![NaslValue::String(x)]))
+/// Returns the host name, the FQDN and the name the address resolves to as stored in the KB at
+/// the start of the host, without duplicates.
+#[nasl_function]
+fn get_host_names(context: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    let mut names = vec![host_name(context)?];
+    for key in [KB_FQDN, KB_RDNS] {
+        if let Some(name) = kb_name(context, key)?.filter(|x| !names.contains(x)) {
+            names.push(name);
+        }
+    }
+    Ok(NaslValue::Array(
+        names
+            .into_iter()
+            .map(|x| NaslValue::String(x.into()))
+            .collect(),
+    ))
 }
 
 /// NASL function to get the current hostname
 ///
-/// As of now (2023-01-20) there is no vhost handling.
-/// Therefore this function does load the registered TARGET and if it is an IP Address resolves it via DNS instead.
-fn get_host_name(register: &Register, _: &Context) -> Result<NaslValue, FunctionErrorKind> {
-    resolve_hostname(register).map(NaslValue::String)
+/// Returns the host name stored in the KB at the start of the host. When the lookups are
+/// disabled and the target is an IP address, the address is returned instead.
+///
+/// When the target has virtual hosts the script forks, each instance continues with one of the
+/// names and keeps it for further calls, its results are reported for that name.
+#[nasl_function]
+fn get_host_name(register: &Register, context: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    if let Some(ContextType::Value(vhost)) = register.named(VHOST) {
+        return Ok(vhost.clone());
+    }
+    let mut names = vec![host_name(context)?];
+    for vhost in context.vhosts() {
+        if !names.iter().any(|x| x.eq_ignore_ascii_case(vhost)) {
+            names.push(vhost.clone());
+        }
+    }
+    let mut names: Vec<_> = names
+        .into_iter()
+        .map(|x| NaslValue::String(x.into()))
+        .collect();
+    Ok(match names.len() {
+        1 => names.remove(0),
+        _ => NaslValue::Fork(names),
+    })
 }
 
 /// Return the target's IP address as IpAddr.
+///
+/// When the target is a hostname it is resolved via the resolver cache of the context.
 pub fn get_host_ip(context: &Context) -> Result<IpAddr, FunctionErrorKind> {
     let default_ip = "127.0.0.1";
     let r_sock_addr = match context.target() {
-        x if !x.is_empty() => IpAddr::from_str(x),
-        _ => IpAddr::from_str(default_ip),
+        x if !x.is_empty() => IpAddr::from_str(zone::strip(x))
+            .map_err(|e| e.to_string())
+            .or_else(|e| context.dns_cache().lookup_first(x).ok_or(e)),
+        _ => IpAddr::from_str(default_ip).map_err(|e| e.to_string()),
     };
 
     match r_sock_addr {
         Ok(x) => Ok(x),
         Err(e) => Err(FunctionErrorKind::wrong_unnamed_argument(
             "IP address",
-            e.to_string().as_str(),
+            e.as_str(),
         )),
     }
 }
@@ -76,7 +116,23 @@ fn nasl_get_host_ip(
     context: &Context,
 ) -> Result<NaslValue, FunctionErrorKind> {
     let ip = get_host_ip(context)?;
-    Ok(NaslValue::String(ip.to_string()))
+    Ok(NaslValue::String(ip.to_string().into()))
+}
+
+/// Adds a host found by the script to the running scan.
+///
+/// The host is scanned with all VTs of the scan after the current hosts. Returns TRUE when the host
+/// is added, FALSE when it is already part of the scan and NULL when it is rejected by the scope
+/// or the maximum of additional hosts of the scan.
+#[nasl_function(named(host))]
+fn add_scan_target(host: &str, context: &Context) -> Option<bool> {
+    match context.target_queue().add(host, context.target()) {
+        Ok(added) => Some(added),
+        Err(error) => {
+            tracing::warn!(%error, host, origin = context.target(), "unable to add host to scan");
+            None
+        }
+    }
 }
 
 pub struct Host;
@@ -87,6 +143,7 @@ function_set! {
     (
         get_host_name,
         get_host_names,
-        (nasl_get_host_ip, "get_host_ip")
+        (nasl_get_host_ip, "get_host_ip"),
+        add_scan_target,
     )
 }
@@ -4,11 +4,37 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::{check_code_result_matches, nasl::prelude::*};
+    use crate::{check_code_result_matches, nasl::prelude::*, nasl::test_prelude::TestBuilder};
 
     #[test]
     fn get_host_name() {
         check_code_result_matches!("get_host_name();", NaslValue::String(_));
         check_code_result_matches!("get_host_names();", NaslValue::Array(_));
     }
+
+    #[test]
+    fn host_names_from_kb() {
+        let mut t = TestBuilder::default();
+        t.ok(r#"get_host_name();"#, "127.0.0.1");
+        t.ok(
+            r#"set_kb_item(name: "Host/hostname", value: "www");"#,
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"set_kb_item(name: "Host/FQDN", value: "www.example.com");"#,
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"set_kb_item(name: "Host/rDNS", value: "www.example.com");"#,
+            NaslValue::Null,
+        );
+        t.ok(r#"get_host_name();"#, "www");
+        t.ok(r#"get_host_names();"#, vec!["www", "www.example.com"]);
+    }
+
+    #[test]
+    fn add_scan_target() {
+        // adding hosts is disabled without a scan
+        check_code_result_matches!("add_scan_target(host: \"10.0.0.2\");", NaslValue::Null);
+    }
 }
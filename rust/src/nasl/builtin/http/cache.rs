@@ -0,0 +1,265 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Caches the responses of HTTP GET requests within a scan.
+//!
+//! Many VTs fetch the same URLs of a host, e.g. `/` or `/robots.txt`. With the scan preference
+//! `http_cache` enabled the response of a GET request is kept for `http_cache_ttl` seconds and
+//! returned to later requests of the same URL with the same custom headers. The cache holds at
+//! most `http_cache_max_bytes` of responses, the least recently used ones are dropped first.
+//!
+//! A request bypasses the cache when the script asks for it via `no_cache` or a `Cache-Control:
+//! no-cache` or `Pragma: no-cache` header. Any other method than GET and HEAD may change the state
+//! of the host, so it drops the cached responses of the host.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::nasl::utils::{Context, FunctionErrorKind, PluginConfig};
+
+/// Scan preference id enabling the cache
+pub const HTTP_CACHE: &str = "http_cache";
+/// Scan preference id of the seconds a response is kept
+pub const HTTP_CACHE_TTL: &str = "http_cache_ttl";
+/// Scan preference id of the maximum of bytes of all cached responses
+pub const HTTP_CACHE_MAX_BYTES: &str = "http_cache_max_bytes";
+
+const DEFAULT_TTL: u64 = 300;
+const DEFAULT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// Identifies a cached response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    /// The target the request was sent to
+    pub host: String,
+    /// The requested URI including the authority
+    pub uri: String,
+    /// The custom headers of the request
+    pub headers: Vec<(String, String)>,
+}
+
+impl CacheKey {
+    /// Returns true when the headers ask to bypass caches.
+    pub fn is_no_cache(&self) -> bool {
+        self.headers.iter().any(|(k, v)| {
+            let v = v.to_lowercase();
+            (k.eq_ignore_ascii_case("cache-control")
+                && (v.contains("no-cache") || v.contains("no-store")))
+                || (k.eq_ignore_ascii_case("pragma") && v.contains("no-cache"))
+        })
+    }
+}
+
+/// A cached response, the status code and the headers followed by the body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub code: u16,
+    pub version: http::Version,
+    pub response: String,
+    /// The location header of a redirect
+    pub location: Option<String>,
+}
+
+struct Entry {
+    key: CacheKey,
+    value: CachedResponse,
+    stored: Instant,
+}
+
+#[derive(Default)]
+struct Entries {
+    // the least recently used entries are at the front
+    entries: VecDeque<Entry>,
+    bytes: usize,
+}
+
+impl Entries {
+    fn remove(&mut self, i: usize) -> Option<Entry> {
+        let entry = self.entries.remove(i)?;
+        self.bytes -= entry.value.response.len();
+        Some(entry)
+    }
+}
+
+/// Cache of the HTTP responses of a scan, disabled by default.
+#[derive(Default)]
+pub struct ResponseCache {
+    enabled: bool,
+    ttl: Duration,
+    max_bytes: usize,
+    entries: Mutex<Entries>,
+}
+
+impl ResponseCache {
+    /// Creates an enabled cache
+    pub fn new(ttl: Duration, max_bytes: usize) -> Self {
+        Self {
+            enabled: max_bytes > 0 && !ttl.is_zero(),
+            ttl,
+            max_bytes,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Creates a cache configured by the scan preferences
+    pub fn from_config(config: &PluginConfig) -> Result<Self, FunctionErrorKind> {
+        if !config.flag(HTTP_CACHE) {
+            return Ok(Self::default());
+        }
+        let ttl = config.get(HTTP_CACHE_TTL)?.unwrap_or(DEFAULT_TTL);
+        let max_bytes = config
+            .get(HTTP_CACHE_MAX_BYTES)?
+            .unwrap_or(DEFAULT_MAX_BYTES);
+        Ok(Self::new(Duration::from_secs(ttl), max_bytes))
+    }
+
+    /// Returns the cache of the scan the context belongs to
+    pub fn of(context: &Context) -> Result<Arc<Self>, FunctionErrorKind> {
+        if let Some(x) = context.extensions().get() {
+            return Ok(x);
+        }
+        let config = context
+            .extensions()
+            .get::<PluginConfig>()
+            .unwrap_or_default();
+        let cache = Self::from_config(&config)?;
+        Ok(context.extensions().get_or_insert_with(|| cache))
+    }
+
+    /// Returns true when responses are cached
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the cached response of key when it is not expired.
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        if !self.enabled || key.is_no_cache() {
+            return None;
+        }
+        let mut entries = self.lock();
+        let i = entries.entries.iter().position(|x| &x.key == key)?;
+        let entry = entries.remove(i)?;
+        if entry.stored.elapsed() >= self.ttl {
+            return None;
+        }
+        let value = entry.value.clone();
+        entries.bytes += value.response.len();
+        entries.entries.push_back(entry);
+        Some(value)
+    }
+
+    /// Caches the response of key, the least recently used responses are dropped when the
+    /// maximum of bytes is exceeded.
+    pub fn insert(&self, key: CacheKey, value: CachedResponse) {
+        let size = value.response.len();
+        if !self.enabled || key.is_no_cache() || size > self.max_bytes {
+            return;
+        }
+        let mut entries = self.lock();
+        if let Some(i) = entries.entries.iter().position(|x| x.key == key) {
+            entries.remove(i);
+        }
+        while entries.bytes + size > self.max_bytes {
+            if entries.remove(0).is_none() {
+                break;
+            }
+        }
+        entries.bytes += size;
+        entries.entries.push_back(Entry {
+            key,
+            value,
+            stored: Instant::now(),
+        });
+    }
+
+    /// Drops the cached responses of the host.
+    pub fn invalidate(&self, host: &str) {
+        if !self.enabled {
+            return;
+        }
+        let mut entries = self.lock();
+        let mut i = 0;
+        while i < entries.entries.len() {
+            if entries.entries[i].key.host == host {
+                entries.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn key(host: &str, uri: &str) -> CacheKey {
+        CacheKey {
+            host: host.to_string(),
+            uri: uri.to_string(),
+            headers: vec![],
+        }
+    }
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            code: 200,
+            version: http::Version::HTTP_2,
+            response: body.to_string(),
+            location: None,
+        }
+    }
+
+    #[test]
+    fn hit_and_invalidate() {
+        let cache = ResponseCache::new(Duration::from_secs(60), 1024);
+        assert_eq!(cache.get(&key("a", "https://a/")), None);
+        cache.insert(key("a", "https://a/"), response("index"));
+        cache.insert(key("b", "https://b/"), response("index"));
+        assert_eq!(cache.get(&key("a", "https://a/")), Some(response("index")));
+        assert_eq!(cache.get(&key("a", "https://a/robots.txt")), None);
+
+        let mut no_cache = key("a", "https://a/");
+        no_cache
+            .headers
+            .push(("Cache-Control".into(), "no-cache".into()));
+        assert_eq!(cache.get(&no_cache), None);
+
+        cache.invalidate("a");
+        assert_eq!(cache.get(&key("a", "https://a/")), None);
+        assert!(cache.get(&key("b", "https://b/")).is_some());
+
+        let disabled = ResponseCache::from_config(&PluginConfig::default()).unwrap();
+        disabled.insert(key("a", "https://a/"), response("index"));
+        assert_eq!(disabled.get(&key("a", "https://a/")), None);
+    }
+
+    #[test]
+    fn bounds() {
+        let cache = ResponseCache::new(Duration::from_secs(60), 10);
+        cache.insert(key("a", "1"), response("12345"));
+        cache.insert(key("a", "2"), response("12345"));
+        // the recently used first response is kept
+        assert!(cache.get(&key("a", "1")).is_some());
+        cache.insert(key("a", "3"), response("123"));
+        assert!(cache.get(&key("a", "1")).is_some());
+        assert_eq!(cache.get(&key("a", "2")), None);
+        cache.insert(key("a", "4"), response("12345678901"));
+        assert_eq!(cache.get(&key("a", "4")), None);
+
+        let cache = ResponseCache::new(Duration::from_millis(10), 10);
+        cache.insert(key("a", "1"), response("1"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(&key("a", "1")), None);
+    }
+}
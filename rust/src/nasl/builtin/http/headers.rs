@@ -0,0 +1,196 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Headers added to every HTTP request of a scan.
+//!
+//! The headers are configured by scan preferences:
+//! - `http_user_agent` sets the `User-Agent`,
+//! - `http_extra_headers` contains one `Name: value` header per line, e.g. a header identifying
+//!   the scan,
+//! - `http_target_headers` contains one `<target> Name: value` header per line that is only added
+//!   to the requests to the given target or virtual host and replaces a global header of the same
+//!   name.
+//!
+//! A custom header set by the script via `http2_set_custom_header` replaces a configured header of
+//! the same name.
+
+use std::sync::Arc;
+
+use http::{HeaderName, HeaderValue};
+
+use crate::nasl::utils::{Context, FunctionErrorKind, PluginConfig};
+
+/// Scan preference id of the User-Agent
+pub const HTTP_USER_AGENT: &str = "http_user_agent";
+/// Scan preference id of the headers added to all requests
+pub const HTTP_EXTRA_HEADERS: &str = "http_extra_headers";
+/// Scan preference id of the headers added to the requests of a target
+pub const HTTP_TARGET_HEADERS: &str = "http_target_headers";
+
+type Header = (String, String);
+
+/// Parses a `Name: value` header.
+fn parse_header(id: &str, line: &str) -> Result<Header, FunctionErrorKind> {
+    let invalid = || FunctionErrorKind::wrong_argument(id, "Name: value", line);
+    let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+    let (name, value) = (name.trim(), value.trim());
+    if HeaderName::try_from(name).is_err() || HeaderValue::try_from(value).is_err() {
+        return Err(invalid());
+    }
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+fn lines(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+}
+
+/// Replaces the header of the same name or appends it.
+fn set(headers: &mut Vec<Header>, header: &Header) {
+    headers.retain(|(name, _)| !name.eq_ignore_ascii_case(&header.0));
+    headers.push(header.clone());
+}
+
+/// The headers configured for the HTTP requests of a scan
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeaderPolicy {
+    headers: Vec<Header>,
+    targets: Vec<(String, Header)>,
+}
+
+impl HeaderPolicy {
+    /// Creates the policy out of the scan preferences.
+    ///
+    /// Returns an error when a header is malformed.
+    pub fn from_config(config: &PluginConfig) -> Result<Self, FunctionErrorKind> {
+        let mut headers = vec![];
+        if let Some(agent) = config.value(HTTP_USER_AGENT).map(str::trim) {
+            if !agent.is_empty() {
+                set(
+                    &mut headers,
+                    &parse_header(HTTP_USER_AGENT, &format!("User-Agent: {agent}"))?,
+                );
+            }
+        }
+        for line in lines(config.value(HTTP_EXTRA_HEADERS)) {
+            set(&mut headers, &parse_header(HTTP_EXTRA_HEADERS, line)?);
+        }
+        let targets = lines(config.value(HTTP_TARGET_HEADERS))
+            .map(|line| {
+                let (target, header) = line.split_once(char::is_whitespace).ok_or_else(|| {
+                    FunctionErrorKind::wrong_argument(
+                        HTTP_TARGET_HEADERS,
+                        "<target> Name: value",
+                        line,
+                    )
+                })?;
+                Ok((
+                    target.to_owned(),
+                    parse_header(HTTP_TARGET_HEADERS, header)?,
+                ))
+            })
+            .collect::<Result<_, FunctionErrorKind>>()?;
+        Ok(Self { headers, targets })
+    }
+
+    /// Returns the policy of the scan the context belongs to
+    pub fn of(context: &Context) -> Result<Arc<Self>, FunctionErrorKind> {
+        if let Some(x) = context.extensions().get() {
+            return Ok(x);
+        }
+        let config = context
+            .extensions()
+            .get::<PluginConfig>()
+            .unwrap_or_default();
+        let policy = Self::from_config(&config)?;
+        Ok(context.extensions().get_or_insert_with(|| policy))
+    }
+
+    /// Returns the headers of a request to the target or virtual host, the custom headers of the
+    /// script replace configured ones.
+    pub fn headers(&self, target: &str, vhost: Option<&str>, custom: &[Header]) -> Vec<Header> {
+        let mut headers = self.headers.clone();
+        self.targets
+            .iter()
+            .filter(|(x, _)| x == target || Some(x.as_str()) == vhost)
+            .for_each(|(_, header)| set(&mut headers, header));
+        custom.iter().for_each(|header| set(&mut headers, header));
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ScanPreference;
+    use crate::nasl::utils::PluginConfig;
+
+    use super::*;
+
+    fn config(preferences: &[(&str, &str)]) -> PluginConfig {
+        let preferences: Vec<_> = preferences
+            .iter()
+            .map(|(id, value)| ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            })
+            .collect();
+        PluginConfig::new("sid", &preferences)
+    }
+
+    fn header(name: &str, value: &str) -> Header {
+        (name.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn headers() {
+        let policy = HeaderPolicy::from_config(&config(&[
+            (HTTP_USER_AGENT, "Scanner/1.0"),
+            (HTTP_EXTRA_HEADERS, "X-Scan-Id: 42\n\nX-Team: sec"),
+            (
+                HTTP_TARGET_HEADERS,
+                "10.0.0.1 X-Scan-Id: 43\nwww.example.com Authorization: Basic Zm9v",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(
+            policy.headers("10.0.0.2", None, &[]),
+            vec![
+                header("User-Agent", "Scanner/1.0"),
+                header("X-Scan-Id", "42"),
+                header("X-Team", "sec"),
+            ]
+        );
+        assert_eq!(
+            policy.headers(
+                "10.0.0.1",
+                Some("www.example.com"),
+                &[header("x-team", "web")]
+            ),
+            vec![
+                header("User-Agent", "Scanner/1.0"),
+                header("X-Scan-Id", "43"),
+                header("Authorization", "Basic Zm9v"),
+                header("x-team", "web"),
+            ]
+        );
+        assert!(HeaderPolicy::from_config(&PluginConfig::default())
+            .unwrap()
+            .headers("10.0.0.1", None, &[])
+            .is_empty());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(HeaderPolicy::from_config(&config(&[(HTTP_EXTRA_HEADERS, "X-Scan-Id")])).is_err());
+        assert!(
+            HeaderPolicy::from_config(&config(&[(HTTP_EXTRA_HEADERS, "Bad Name: 1")])).is_err()
+        );
+        assert!(
+            HeaderPolicy::from_config(&config(&[(HTTP_TARGET_HEADERS, "X-Scan-Id: 1")])).is_err()
+        );
+    }
+}
@@ -5,40 +5,128 @@
 //! Defines NASL functions to perform HTTP/2 request.
 // TODO: implement http functions once socket handling is available
 
+mod cache;
+mod headers;
+mod redirect;
+mod version;
+
+#[cfg(test)]
+mod tests;
+
+pub use cache::ResponseCache;
+pub use headers::HeaderPolicy;
+pub use redirect::RedirectPolicy;
+
+use cache::{CacheKey, CachedResponse};
+use redirect::Redirect;
+use version::HttpVersion;
+
+use crate::nasl::builtin::network::network_utils::{resolve_ipaddr, source_addrs, SourceAddrs};
 use crate::nasl::prelude::*;
-use crate::nasl::utils::ContextType;
+use crate::nasl::utils::lookup_keys::VHOST;
+use crate::nasl::utils::{zone, ContextType, Plugin, TrafficCounter};
+use crate::storage::{ContextKey, Field, Kb};
 
+use async_trait::async_trait;
 use h2::client;
 
-use core::convert::AsRef;
-use http::{response::Parts, Method, Request};
+use http::{response::Parts, Method, Request, Uri, Version};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
-use std::sync::Arc;
+use std::{io, net::SocketAddr, sync::Arc};
 
-use rustls::ClientConfig;
+use rustls::{pki_types::ServerName, ClientConfig};
 use tokio::{
-    net::TcpStream,
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpSocket, TcpStream},
     sync::{Mutex, MutexGuard},
 };
 use tokio_rustls::TlsConnector;
 
+/// Opens a TCP connection to addr from the given source
+async fn connect(addr: SocketAddr, source: &SourceAddrs) -> io::Result<TcpStream> {
+    let socket = {
+        let _ns = source.enter_namespace()?;
+        match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        }
+    };
+    if let Some(source) = source.for_dst(&addr.ip()) {
+        socket.bind(SocketAddr::new(source, 0))?;
+    }
+    socket.connect(addr).await
+}
+
 pub struct Handle {
     pub handle_id: i32,
     pub header_items: Vec<(String, String)>,
     pub http_code: u16,
+    /// The HTTP version of the last response
+    pub http_version: Option<Version>,
+    /// The script that created the handle
+    pub owner: ContextKey,
 }
 
-#[derive(Default)]
-pub struct NaslHttp {
-    handles: Arc<Mutex<Vec<Handle>>>,
+/// The handles and the TLS connectors shared by all scripts of a scan.
+///
+/// It is stored within the extensions of the context and dropped at the end of a scan.
+pub struct HttpState {
+    handles: Mutex<Vec<Handle>>,
+    connectors: Vec<(HttpVersion, TlsConnector)>,
 }
 
-async fn lock_handles(
-    handles: &Arc<Mutex<Vec<Handle>>>,
-) -> Result<MutexGuard<Vec<Handle>>, FunctionErrorKind> {
-    // we actually need to panic as a lock error is fatal
-    // alternatively we need to add a poison error on FunctionErrorKind
-    Ok(Arc::as_ref(handles).lock().await)
+impl Default for HttpState {
+    fn default() -> Self {
+        Self {
+            handles: Mutex::default(),
+            connectors: [
+                HttpVersion::Auto,
+                HttpVersion::Http10,
+                HttpVersion::Http11,
+                HttpVersion::Http2,
+            ]
+            .into_iter()
+            .map(|x| (x, tls_connector(x)))
+            .collect(),
+        }
+    }
+}
+
+impl HttpState {
+    /// Returns the state of the scan the context belongs to
+    fn of(context: &Context) -> Arc<Self> {
+        context.extensions().get_or_default()
+    }
+
+    async fn lock_handles(&self) -> MutexGuard<'_, Vec<Handle>> {
+        self.handles.lock().await
+    }
+
+    /// Returns the connector offering the protocols of the version via ALPN
+    fn connector(&self, version: HttpVersion) -> &TlsConnector {
+        self.connectors
+            .iter()
+            .find(|(x, _)| *x == version)
+            .map(|(_, x)| x)
+            .unwrap_or(&self.connectors[0].1)
+    }
+}
+
+#[derive(Default)]
+pub struct NaslHttp;
+
+/// Creates a TLS connector for the HTTP version that accepts every certificate
+fn tls_connector(version: HttpVersion) -> TlsConnector {
+    let mut config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoVerifier))
+        .with_no_client_auth();
+
+    config.alpn_protocols = version.alpn();
+    TlsConnector::from(Arc::new(config))
 }
 
 /// Return the next available handle ID
@@ -122,117 +210,211 @@ impl ServerCertVerifier for NoVerifier {
     }
 }
 
-impl NaslHttp {
-    async fn request(
-        &self,
-        ip_str: &String,
-        port: u16,
-        uri: String,
-        data: String,
-        method: Method,
-        handle: &mut Handle,
-    ) -> Result<(Parts, String), FunctionErrorKind> {
-        // Establish TCP connection to the server.
-
-        let mut config = ClientConfig::builder()
-            .dangerous()
-            .with_custom_certificate_verifier(Arc::new(NoVerifier))
-            .with_no_client_auth();
+/// Returns the status line and the headers followed by the body of a response.
+fn response_of(head: &Parts, body: &str) -> CachedResponse {
+    let mut response = String::new();
+    response.push_str(format!("{:?} ", head.version).as_str());
+    response.push_str(format!("{:?}\n", head.status).as_str());
+    for (k, v) in head.headers.iter() {
+        response.push_str(&format!(
+            "{}: {}\n",
+            k.as_str(),
+            String::from_utf8_lossy(v.as_bytes())
+        ))
+    }
+    response.push_str(body);
+    CachedResponse {
+        code: head.status.as_u16(),
+        version: head.version,
+        response,
+        location: head
+            .headers
+            .get(http::header::LOCATION)
+            .map(|x| String::from_utf8_lossy(x.as_bytes()).into_owned()),
+    }
+}
 
-        // For HTTP/2. For older HTTP versions should not be set,
-        config.alpn_protocols = vec![b"h2".to_vec()];
+fn diagnostic(e: impl ToString) -> FunctionErrorKind {
+    FunctionErrorKind::Diagnostic(e.to_string(), Some(NaslValue::Null))
+}
 
-        let server_name = ip_str.clone().to_owned().try_into().unwrap();
+/// Sends a request via HTTP/2 and reads the response.
+async fn send_h2<S>(
+    stream: S,
+    uri: String,
+    data: String,
+    method: Method,
+    headers: &[(String, String)],
+    traffic: Option<&TrafficCounter>,
+) -> Result<(Parts, String), FunctionErrorKind>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (h2, connection) = client::handshake(stream).await.map_err(diagnostic)?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!(%e, "HTTP/2 connection closed");
+        }
+    });
 
-        let connector = TlsConnector::from(Arc::new(config));
-        let stream = match TcpStream::connect(format!("{}:{}", ip_str, port)).await {
-            Ok(a) => a,
-            Err(e) => {
-                return Err(FunctionErrorKind::Diagnostic(
-                    e.to_string(),
-                    Some(NaslValue::Null),
-                ));
-            }
-        };
+    let mut h2 = h2.ready().await.map_err(diagnostic)?;
 
-        let stream = match connector.connect(server_name, stream).await {
-            Ok(a) => a,
-            Err(e) => {
-                return Err(FunctionErrorKind::Diagnostic(
-                    e.to_string(),
-                    Some(NaslValue::Null),
-                ));
-            }
-        };
+    // Prepare the HTTP request to send to the server.
+    let mut request = Request::builder();
 
-        let (h2, connection) = match client::handshake(stream).await {
-            Ok((x, y)) => (x, y),
-            Err(e) => {
-                return Err(FunctionErrorKind::Diagnostic(
-                    e.to_string(),
-                    Some(NaslValue::Null),
-                ))
-            }
-        };
+    // add the configured and the custom headers
+    for (k, v) in headers {
+        request = request.header(k, v);
+    }
+    let request = request
+        .method(method)
+        .uri(uri)
+        .body(())
+        .map_err(diagnostic)?;
+
+    // Send the request. The second tuple item allows the caller
+    // to stream a request body.
+    let (response, mut send_stream) = h2.send_request(request, false).map_err(diagnostic)?;
+    if let Some(traffic) = traffic {
+        traffic.sent(data.len())?;
+    }
+    send_stream
+        .send_data(data.into(), true)
+        .map_err(diagnostic)?;
+    let (head, mut body) = response.await.map_err(diagnostic)?.into_parts();
+
+    // The `flow_control` handle allows the caller to manage
+    // flow control.
+    //
+    // Whenever data is received, the caller is responsible for
+    // releasing capacity back to the server once it has freed
+    // the data from memory.
+    let mut flow_control = body.flow_control().clone();
+
+    let mut resp = String::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(diagnostic)?;
+
+        // stops downloading once the byte cap of the script is exceeded
+        if let Some(traffic) = traffic {
+            traffic.received(chunk.len())?;
+        }
+        resp.push_str(&String::from_utf8_lossy(&chunk));
+        // Let the server send more data.
+        let _ = flow_control.release_capacity(chunk.len());
+    }
 
-        tokio::spawn(async move {
-            connection.await.unwrap();
-        });
+    Ok((head, resp))
+}
 
-        let mut h2 = match h2.ready().await {
-            Ok(x) => x,
-            Err(e) => {
-                return Err(FunctionErrorKind::Diagnostic(
-                    e.to_string(),
-                    Some(NaslValue::Null),
-                ))
+/// Sends a request via HTTP/1.0 or HTTP/1.1 and reads the response.
+async fn send_http1<S>(
+    stream: S,
+    version: Version,
+    uri: String,
+    data: String,
+    method: Method,
+    headers: &[(String, String)],
+    traffic: Option<&TrafficCounter>,
+) -> Result<(Parts, String), FunctionErrorKind>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(diagnostic)?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!(%e, "HTTP/1 connection closed");
+        }
+    });
+
+    // HTTP/1 requests contain the path, the authority is sent as Host header
+    let uri: Uri = uri.parse().map_err(diagnostic)?;
+    let mut request = Request::builder()
+        .method(method)
+        .version(version)
+        .uri(uri.path_and_query().map(|x| x.as_str()).unwrap_or("/"));
+    if !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("host")) {
+        if let Some(authority) = uri.authority() {
+            request = request.header(http::header::HOST, authority.as_str());
+        }
+    }
+    for (k, v) in headers {
+        request = request.header(k, v);
+    }
+    if let Some(traffic) = traffic {
+        traffic.sent(data.len())?;
+    }
+    let request = request
+        .body(Full::new(Bytes::from(data)))
+        .map_err(diagnostic)?;
+    let (head, mut body) = sender
+        .send_request(request)
+        .await
+        .map_err(diagnostic)?
+        .into_parts();
+
+    let mut resp = String::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(diagnostic)?;
+        if let Some(chunk) = frame.data_ref() {
+            // stops downloading once the byte cap of the script is exceeded
+            if let Some(traffic) = traffic {
+                traffic.received(chunk.len())?;
             }
-        };
+            resp.push_str(&String::from_utf8_lossy(chunk));
+        }
+    }
 
-        // Prepare the HTTP request to send to the server.
-        let mut request = Request::builder();
+    Ok((head, resp))
+}
 
-        // add custom headers
-        for (k, v) in handle.header_items.iter() {
-            request = request.header(k, v);
-        }
-        let request = request.method(method).uri(uri).body(()).unwrap();
-
-        // Send the request. The second tuple item allows the caller
-        // to stream a request body.
-        let (response, mut send_stream) = h2.send_request(request, false).unwrap();
-        send_stream.send_data(data.into(), true).unwrap();
-        let (head, mut body) = response.await.expect("some response").into_parts();
-
-        // The `flow_control` handle allows the caller to manage
-        // flow control.
-        //
-        // Whenever data is received, the caller is responsible for
-        // releasing capacity back to the server once it has freed
-        // the data from memory.
-        let mut flow_control = body.flow_control().clone();
-
-        let mut resp = String::new();
-        while let Some(chunk) = body.data().await {
-            let chunk = match chunk {
-                Ok(byte_chunk) => byte_chunk,
-                Err(e) => {
-                    return Err(FunctionErrorKind::Diagnostic(
-                        e.to_string(),
-                        Some(NaslValue::Null),
-                    ))
-                }
-            };
+impl NaslHttp {
+    /// Sends the request with the given version, https URIs are sent via TLS.
+    #[allow(clippy::too_many_arguments)]
+    async fn request(
+        &self,
+        ip_str: &str,
+        addr: SocketAddr,
+        source: &SourceAddrs,
+        uri: String,
+        data: String,
+        method: Method,
+        state: &HttpState,
+        version: HttpVersion,
+        headers: &[(String, String)],
+        traffic: Option<&TrafficCounter>,
+    ) -> Result<(Parts, String), FunctionErrorKind> {
+        // Establish TCP connection to the server.
+        let stream = connect(addr, source).await.map_err(diagnostic)?;
 
-            resp.push_str(&String::from_utf8_lossy(&chunk));
-            // Let the server send more data.
-            let _ = flow_control.release_capacity(chunk.len());
+        if !uri.starts_with("https://") {
+            return match version.negotiate(false, false)? {
+                Version::HTTP_2 => send_h2(stream, uri, data, method, headers, traffic).await,
+                v => send_http1(stream, v, uri, data, method, headers, traffic).await,
+            };
         }
 
-        Ok((head, resp))
+        let server_name = ServerName::try_from(ip_str.to_owned()).map_err(diagnostic)?;
+        let stream = state
+            .connector(version)
+            .connect(server_name, stream)
+            .await
+            .map_err(diagnostic)?;
+        let h2 = stream.get_ref().1.alpn_protocol() == Some(b"h2");
+        match version.negotiate(true, h2)? {
+            Version::HTTP_2 => send_h2(stream, uri, data, method, headers, traffic).await,
+            v => send_http1(stream, v, uri, data, method, headers, traffic).await,
+        }
     }
 
     /// Perform request with the given method.
+    ///
+    /// Redirects are followed as configured by the scan preferences, the named argument
+    /// `follow_redirects` sets the maximum of redirects to follow for this request. The named
+    /// argument `version` forces the HTTP version of this request, see [`HttpVersion`].
     async fn http2_req<'a>(
         &self,
         register: &Register,
@@ -248,7 +430,8 @@ impl NaslHttp {
             }
         };
 
-        let mut handles = lock_handles(&self.handles).await?;
+        let state = HttpState::of(ctx);
+        let mut handles = state.lock_handles().await;
         let handle = match handles
             .iter_mut()
             .enumerate()
@@ -299,37 +482,141 @@ impl NaslHttp {
             _ => "127.0.0.1".to_string(),
         };
 
+        // the virtual host of the instance is used as authority and as TLS server name
+        let vhost = match register.named(VHOST) {
+            Some(ContextType::Value(x)) => Some(x.to_string()),
+            _ => None,
+        };
+        // IPv6 addresses are enclosed in brackets, the `%` of a zone is percent encoded
+        let authority = match (&vhost, zone::split(&ip_str)) {
+            (Some(vhost), _) => vhost.clone(),
+            (None, (addr, Some(zone))) => format!("[{addr}%25{zone}]"),
+            (None, (addr, None)) if addr.contains(':') => format!("[{addr}]"),
+            (None, (addr, None)) => addr.to_string(),
+        };
         let mut uri: String;
         if port != 80 && port != 443 {
-            uri = format!("{}://{}:{}", schema, ip_str, port);
+            uri = format!("{}://{}:{}", schema, authority, port);
         } else {
-            uri = format!("{}://{}", schema, ip_str)
+            uri = format!("{}://{}", schema, authority)
         }
 
         uri = format!("{}{}", uri, item);
 
-        match self.request(&ip_str, port, uri, data, method, handle).await {
-            Ok((head, body)) => {
-                handle.http_code = head.status.as_u16();
-                let mut header_str = String::new();
-                header_str.push_str(format!("{:?} ", head.version).as_str());
-                header_str.push_str(format!("{:?}\n", head.status).as_str());
-                for (k, v) in head.headers.iter() {
-                    header_str.push_str(&format!(
-                        "{}: {}\n",
-                        k.as_str(),
-                        String::from_utf8_lossy(v.as_bytes())
-                    ))
+        let headers =
+            HeaderPolicy::of(ctx)?.headers(&ip_str, vhost.as_deref(), &handle.header_items);
+
+        let redirects = RedirectPolicy::of(ctx)?;
+        let max_hops = match register.named("follow_redirects") {
+            Some(ContextType::Value(NaslValue::Number(x))) => (*x).max(0) as usize,
+            _ => redirects.max_hops,
+        };
+        let cache = ResponseCache::of(ctx)?;
+        let no_cache = match register.named("no_cache") {
+            Some(ContextType::Value(x)) => bool::from(x.clone()),
+            _ => false,
+        };
+        // a version forced by the script bypasses the cache
+        let (version, no_cache) = match register.named("version") {
+            Some(ContextType::Value(x)) => (
+                x.to_string().parse().map_err(|_| {
+                    FunctionErrorKind::wrong_argument(
+                        "version",
+                        "auto, 1.0, 1.1 or 2",
+                        &x.to_string(),
+                    )
+                })?,
+                true,
+            ),
+            _ => (HttpVersion::of(ctx)?, no_cache),
+        };
+
+        let ip = resolve_ipaddr(ctx, &ip_str)?;
+        let source = source_addrs(ctx)?;
+        let mut server = vhost.unwrap_or_else(|| zone::strip(&ip_str).to_owned());
+        let (mut uri, mut req_port, mut method, mut data) = (uri, port, method, data);
+        let mut chain: Vec<Redirect> = vec![];
+        let response = loop {
+            // only GET responses are cached, other methods than HEAD may change the state of the
+            // host
+            let key = CacheKey {
+                host: ip_str.clone(),
+                uri: uri.clone(),
+                headers: headers.clone(),
+            };
+            let cacheable = method == Method::GET && !no_cache && cache.is_enabled();
+            if !cacheable && method != Method::GET && method != Method::HEAD {
+                cache.invalidate(&ip_str);
+            }
+            let response = match cacheable.then(|| cache.get(&key)).flatten() {
+                Some(cached) => cached,
+                None => {
+                    let _permit = ctx.connection_limiter().acquire(&ip_str).await?;
+                    let (head, body) = self
+                        .request(
+                            &server,
+                            source.socket_addr(ip, req_port),
+                            &source,
+                            uri.clone(),
+                            data.clone(),
+                            method.clone(),
+                            &state,
+                            version,
+                            &headers,
+                            ctx.traffic(),
+                        )
+                        .await?;
+                    let response = response_of(&head, &body);
+                    ctx.connection_limiter()
+                        .record_received(&ip_str, response.response.as_bytes());
+                    if cacheable && !head.status.is_server_error() {
+                        cache.insert(key, response.clone());
+                    }
+                    response
                 }
-                //let _ = head.headers.iter().map(|(k,v)| header_str.push_str(&format!("{}: {}\n", k.as_str(), String::from_utf8_lossy(v.as_bytes()))));
-                header_str.push_str(&body);
-                Ok(NaslValue::String(header_str))
+            };
+            if chain.len() >= max_hops {
+                break response;
+            }
+            let Some(redirect) = redirects.next(
+                response.code,
+                response.location.as_deref(),
+                &uri,
+                req_port,
+                &method,
+            ) else {
+                break response;
+            };
+            if redirect.method != method {
+                data = String::new();
+            }
+            uri = redirect.uri.clone();
+            req_port = redirect.port;
+            method = redirect.method.clone();
+            server = redirect.host.clone();
+            chain.push(redirect);
+        };
+
+        if redirects.record {
+            for redirect in chain {
+                ctx.dispatcher().dispatch(
+                    ctx.key(),
+                    Field::KB(Kb::from((
+                        format!("www/{port}/redirects{item}"),
+                        format!("{} {}", redirect.status, redirect.uri),
+                    ))),
+                )?;
             }
-            Err(e) => Err(e),
         }
+        handle.http_code = response.code;
+        handle.http_version = Some(response.version);
+        Ok(NaslValue::String(response.response.into()))
     }
 
     /// Wrapper function for GET request. See http2_req
+    ///
+    /// When the `http_cache` scan preference is enabled the response is cached, the named
+    /// argument `no_cache: TRUE` bypasses the cache.
     async fn get<'a>(
         &self,
         register: &Register,
@@ -381,13 +668,16 @@ impl NaslHttp {
     /// On success the function returns a and integer with the handle
     /// identifier. Null on error.
     #[nasl_function]
-    async fn handle(&self) -> Result<NaslValue, FunctionErrorKind> {
-        let mut handles = lock_handles(&self.handles).await?;
+    async fn handle(&self, context: &Context<'_>) -> Result<NaslValue, FunctionErrorKind> {
+        let state = HttpState::of(context);
+        let mut handles = state.lock_handles().await;
         let handle_id = next_handle_id(&handles);
         let h = Handle {
             handle_id,
             header_items: Vec::default(),
             http_code: 0,
+            http_version: None,
+            owner: context.key().clone(),
         };
         handles.push(h);
 
@@ -401,8 +691,13 @@ impl NaslHttp {
     /// The function returns an integer.
     /// O on success, -1 on error.
     #[nasl_function(named(handle))]
-    async fn close_handle(&self, handle: i32) -> Result<NaslValue, FunctionErrorKind> {
-        let mut handles = lock_handles(&self.handles).await?;
+    async fn close_handle(
+        &self,
+        context: &Context<'_>,
+        handle: i32,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let state = HttpState::of(context);
+        let mut handles = state.lock_handles().await;
         match handles
             .iter_mut()
             .enumerate()
@@ -428,7 +723,7 @@ impl NaslHttp {
     async fn get_response_code(
         &self,
         register: &Register,
-        _: &Context<'_>,
+        context: &Context<'_>,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let handle_id = match register.named("handle") {
             Some(ContextType::Value(NaslValue::Number(x))) => *x as i32,
@@ -439,7 +734,8 @@ impl NaslHttp {
             }
         };
 
-        let mut handles = lock_handles(&self.handles).await?;
+        let state = HttpState::of(context);
+        let mut handles = state.lock_handles().await;
         match handles
             .iter_mut()
             .enumerate()
@@ -453,6 +749,31 @@ impl NaslHttp {
         }
     }
 
+    /// Get the HTTP version of the last response, e.g. `HTTP/2.0`.
+    /// nasl named param
+    ///   - handle The handle identifier
+    ///
+    /// Returns NULL when no request was performed yet.
+    #[nasl_function(named(handle))]
+    async fn get_version(
+        &self,
+        context: &Context<'_>,
+        handle: i32,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let state = HttpState::of(context);
+        let handles = state.lock_handles().await;
+        match handles.iter().find(|h| h.handle_id == handle) {
+            Some(h) => Ok(h
+                .http_version
+                .map(|x| NaslValue::String(format!("{x:?}").into()))
+                .unwrap_or(NaslValue::Null)),
+            _ => Err(FunctionErrorKind::Diagnostic(
+                format!("Handle ID {} not found", handle),
+                Some(NaslValue::Null),
+            )),
+        }
+    }
+
     /// Set a custom header element in the header
     /// nasl named param
     ///   - handle The handle identifier
@@ -462,7 +783,7 @@ impl NaslHttp {
     async fn set_custom_header(
         &self,
         register: &Register,
-        _: &Context<'_>,
+        context: &Context<'_>,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let header_item = match register.named("header_item") {
             Some(ContextType::Value(NaslValue::String(x))) => x,
@@ -480,7 +801,8 @@ impl NaslHttp {
             }
         };
 
-        let mut handles = lock_handles(&self.handles).await?;
+        let state = HttpState::of(context);
+        let mut handles = state.lock_handles().await;
         match handles
             .iter_mut()
             .enumerate()
@@ -505,6 +827,7 @@ function_set! {
         (NaslHttp::handle, "http2_handle"),
         (NaslHttp::close_handle, "http2_close_handle"),
         (NaslHttp::get_response_code, "http2_get_response_code"),
+        (NaslHttp::get_version, "http2_get_version"),
         (NaslHttp::set_custom_header, "http2_set_custom_header"),
         (NaslHttp::get, "http2_get"),
         (NaslHttp::head, "http2_head"),
@@ -513,3 +836,16 @@ function_set! {
         (NaslHttp::put, "http2_put"),
     )
 }
+
+#[async_trait]
+impl Plugin for NaslHttp {
+    /// Closes the handles the script did not close itself.
+    async fn finish_script(&mut self, context: &Context<'_>) {
+        if let Some(state) = context.extensions().get::<HttpState>() {
+            state
+                .lock_handles()
+                .await
+                .retain(|x| &x.owner != context.key());
+        }
+    }
+}
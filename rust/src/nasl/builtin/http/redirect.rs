@@ -0,0 +1,306 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Follows the redirects of HTTP responses.
+//!
+//! The scan preference `http_max_redirects` sets how many redirects a request follows, the named
+//! argument `follow_redirects` of the http2 functions overrides it for a single request. By
+//! default no redirect is followed.
+//!
+//! With `http_redirect_same_host` enabled, which is the default, only redirects to the host of the
+//! request are followed. Otherwise redirects to other host names are followed as well, they are
+//! requested from the target with the other host name as virtual host, another address is never
+//! connected to.
+//!
+//! With `http_record_redirects` enabled each followed redirect is stored within the KB as
+//! `www/<port>/redirects<item>` with the status code and the location as value.
+
+use std::sync::Arc;
+
+use http::{Method, Uri};
+
+use crate::nasl::utils::{Context, FunctionErrorKind, PluginConfig};
+
+/// Scan preference id of the maximum of redirects a request follows
+pub const HTTP_MAX_REDIRECTS: &str = "http_max_redirects";
+/// Scan preference id restricting redirects to the host of the request
+pub const HTTP_REDIRECT_SAME_HOST: &str = "http_redirect_same_host";
+/// Scan preference id enabling the KB items of followed redirects
+pub const HTTP_RECORD_REDIRECTS: &str = "http_record_redirects";
+
+/// A redirect to follow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// Status code of the redirecting response
+    pub status: u16,
+    /// The absolute URI of the location
+    pub uri: String,
+    /// The host of the location
+    pub host: String,
+    /// The port of the location
+    pub port: u16,
+    /// The method the location is requested with
+    pub method: Method,
+}
+
+/// Returns the method a redirect with the status is followed with.
+///
+/// 303 and, like browsers do, 301 and 302 change a POST into a GET, 307 and 308 keep the method.
+fn redirect_method(status: u16, method: &Method) -> Option<Method> {
+    match status {
+        301 | 302 if method == Method::POST => Some(Method::GET),
+        303 if method != Method::HEAD => Some(Method::GET),
+        301 | 302 | 303 | 307 | 308 => Some(method.clone()),
+        _ => None,
+    }
+}
+
+fn uri_host(uri: &Uri) -> Option<String> {
+    let host = uri.host()?;
+    Some(
+        host.trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_lowercase(),
+    )
+}
+
+/// Resolves a location against the URI and the port it was received from.
+///
+/// Returns the absolute URI, its host and its port.
+pub fn resolve(base: &str, base_port: u16, location: &str) -> Option<(String, String, u16)> {
+    let location = location.trim();
+    let base: Uri = base.parse().ok()?;
+    let scheme = base.scheme_str()?;
+    let authority = base.authority()?;
+    let is_absolute = location.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|x| x.is_ascii_alphanumeric() || "+-.".contains(x))
+    });
+    let absolute = if location.starts_with("//") {
+        format!("{scheme}:{location}")
+    } else if location.starts_with('/') {
+        format!("{scheme}://{authority}{location}")
+    } else if is_absolute {
+        location.to_owned()
+    } else {
+        let path = base.path();
+        let dir = &path[..=path.rfind('/')?];
+        format!("{scheme}://{authority}{dir}{location}")
+    };
+    let uri: Uri = absolute.parse().ok()?;
+    let port = match (uri.port_u16(), uri.authority() == Some(authority)) {
+        (Some(port), _) => port,
+        (None, true) => base_port,
+        (None, false) if uri.scheme_str() == Some("http") => 80,
+        (None, false) => 443,
+    };
+    Some((absolute, uri_host(&uri)?, port))
+}
+
+/// How the redirects of the requests of a scan are followed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    /// The maximum of redirects a request follows
+    pub max_hops: usize,
+    /// Only redirects to the host of the request are followed
+    pub same_host: bool,
+    /// The followed redirects are stored within the KB
+    pub record: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_hops: 0,
+            same_host: true,
+            record: false,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Creates the policy out of the scan preferences
+    pub fn from_config(config: &PluginConfig) -> Result<Self, FunctionErrorKind> {
+        let default = Self::default();
+        Ok(Self {
+            max_hops: config.get(HTTP_MAX_REDIRECTS)?.unwrap_or(default.max_hops),
+            same_host: config
+                .value(HTTP_REDIRECT_SAME_HOST)
+                .map(|_| config.flag(HTTP_REDIRECT_SAME_HOST))
+                .unwrap_or(default.same_host),
+            record: config.flag(HTTP_RECORD_REDIRECTS),
+        })
+    }
+
+    /// Returns the policy of the scan the context belongs to
+    pub fn of(context: &Context) -> Result<Arc<Self>, FunctionErrorKind> {
+        if let Some(x) = context.extensions().get() {
+            return Ok(x);
+        }
+        let config = context
+            .extensions()
+            .get::<PluginConfig>()
+            .unwrap_or_default();
+        let policy = Self::from_config(&config)?;
+        Ok(context.extensions().get_or_insert_with(|| policy))
+    }
+
+    /// Returns the redirect to follow for a response to a request of uri.
+    ///
+    /// Returns None when the response is no redirect or the location is not allowed.
+    pub fn next(
+        &self,
+        status: u16,
+        location: Option<&str>,
+        uri: &str,
+        port: u16,
+        method: &Method,
+    ) -> Option<Redirect> {
+        let method = redirect_method(status, method)?;
+        let (next, host, next_port) = resolve(uri, port, location?)?;
+        if self.same_host && Some(&host) != uri.parse().ok().and_then(|x| uri_host(&x)).as_ref() {
+            tracing::debug!(
+                uri,
+                location = next,
+                "redirect to another host not followed"
+            );
+            return None;
+        }
+        Some(Redirect {
+            status,
+            uri: next,
+            host,
+            port: next_port,
+            method,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+
+    #[test]
+    fn locations() {
+        let base = "https://www.example.com:8443/app/login?x=1";
+        assert_eq!(
+            resolve(base, 8443, "/home"),
+            Some((
+                "https://www.example.com:8443/home".into(),
+                "www.example.com".into(),
+                8443
+            ))
+        );
+        assert_eq!(
+            resolve(base, 8443, "start"),
+            Some((
+                "https://www.example.com:8443/app/start".into(),
+                "www.example.com".into(),
+                8443
+            ))
+        );
+        assert_eq!(
+            resolve(base, 8443, "http://Other.example.com/"),
+            Some((
+                "http://Other.example.com/".into(),
+                "other.example.com".into(),
+                80
+            ))
+        );
+        assert_eq!(
+            resolve(base, 8443, "//cdn.example.com/x"),
+            Some((
+                "https://cdn.example.com/x".into(),
+                "cdn.example.com".into(),
+                443
+            ))
+        );
+        // the authority of the base keeps the port it was requested on
+        assert_eq!(
+            resolve("https://10.0.0.1/", 80, "/next?to=http://a/").map(|x| x.2),
+            Some(80)
+        );
+    }
+
+    #[test]
+    fn follow() {
+        let policy = RedirectPolicy {
+            max_hops: 5,
+            ..Default::default()
+        };
+        let uri = "https://www.example.com/login";
+        let next = policy
+            .next(302, Some("/home"), uri, 443, &Method::POST)
+            .unwrap();
+        assert_eq!(next.uri, "https://www.example.com/home");
+        assert_eq!(next.method, Method::GET);
+        assert_eq!(
+            policy
+                .next(307, Some("/home"), uri, 443, &Method::POST)
+                .map(|x| x.method),
+            Some(Method::POST)
+        );
+        assert_eq!(
+            policy.next(200, Some("/home"), uri, 443, &Method::GET),
+            None
+        );
+        assert_eq!(policy.next(301, None, uri, 443, &Method::GET), None);
+        assert_eq!(
+            policy.next(
+                301,
+                Some("https://evil.example.org/"),
+                uri,
+                443,
+                &Method::GET
+            ),
+            None
+        );
+        let policy = RedirectPolicy {
+            same_host: false,
+            ..policy
+        };
+        assert_eq!(
+            policy
+                .next(
+                    301,
+                    Some("https://evil.example.org/"),
+                    uri,
+                    443,
+                    &Method::GET
+                )
+                .map(|x| x.host),
+            Some("evil.example.org".into())
+        );
+    }
+
+    #[test]
+    fn config() {
+        use crate::models::ScanPreference;
+        let preferences = [
+            (HTTP_MAX_REDIRECTS, "3"),
+            (HTTP_REDIRECT_SAME_HOST, "no"),
+            (HTTP_RECORD_REDIRECTS, "yes"),
+        ]
+        .map(|(id, value)| ScanPreference {
+            id: id.into(),
+            value: value.into(),
+        });
+        assert_eq!(
+            RedirectPolicy::from_config(&PluginConfig::new("sid", &preferences)).unwrap(),
+            RedirectPolicy {
+                max_hops: 3,
+                same_host: false,
+                record: true,
+            }
+        );
+        assert_eq!(
+            RedirectPolicy::from_config(&PluginConfig::default()).unwrap(),
+            RedirectPolicy::default()
+        );
+    }
+}
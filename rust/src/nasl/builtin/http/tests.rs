@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    // the request functions need a tokio runtime driving the connections while the interpreter
+    // blocks
+    #[tokio::test(flavor = "multi_thread")]
+    async fn http1_versions() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    )
+                    .unwrap();
+                sender
+                    .send(String::from_utf8_lossy(&request).to_lowercase())
+                    .unwrap();
+            }
+        });
+
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.run_all(format!(
+            r#"
+            h = http2_handle();
+            http2_get_version(handle: h);
+            http2_get(handle: h, port: {port}, item: "/x", schema: "http");
+            http2_get_response_code(handle: h);
+            http2_get_version(handle: h);
+            http2_get(handle: h, port: {port}, item: "/x", schema: "http", version: "1.0");
+            "#
+        ));
+        let results: Vec<_> = t.results().into_iter().map(|x| x.unwrap()).collect();
+        assert_eq!(results[1], NaslValue::Null);
+        assert_eq!(
+            results[2],
+            NaslValue::String("HTTP/1.1 200\ncontent-length: 2\nconnection: close\nok".into())
+        );
+        assert_eq!(results[3], NaslValue::Number(200));
+        assert_eq!(results[4], NaslValue::String("HTTP/1.1".into()));
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("get /x http/1.1\r\n"));
+        assert!(request.contains(&format!("host: 127.0.0.1:{port}\r\n")));
+        assert!(requests.recv().unwrap().starts_with("get /x http/1.0\r\n"));
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Selects the HTTP version of a request.
+//!
+//! The scan preference `http_version` sets the version of all requests, the named argument
+//! `version` of the http2 request functions overrides it for a single request. Both accept `auto`,
+//! `1.0`, `1.1` and `2`.
+//!
+//! With `auto` HTTP/2 is offered via ALPN on TLS connections and used when the server selects it,
+//! otherwise and on plain connections HTTP/1.1 is used. A forced HTTP/2 fails on TLS connections
+//! whose server does not select it and is sent with prior knowledge on plain connections.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::nasl::utils::{Context, FunctionErrorKind, PluginConfig};
+use crate::nasl::NaslValue;
+
+/// Scan preference id of the HTTP version
+pub const HTTP_VERSION: &str = "http_version";
+
+/// The HTTP version requests are sent with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/2 when the server selects it via ALPN, otherwise HTTP/1.1
+    #[default]
+    Auto,
+    Http10,
+    Http11,
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.strip_prefix("http/").unwrap_or(&s) {
+            "" | "auto" => Ok(Self::Auto),
+            "1.0" => Ok(Self::Http10),
+            "1.1" => Ok(Self::Http11),
+            "2" | "2.0" => Ok(Self::Http2),
+            _ => Err(format!("unknown HTTP version {s}")),
+        }
+    }
+}
+
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Http10 => write!(f, "1.0"),
+            Self::Http11 => write!(f, "1.1"),
+            Self::Http2 => write!(f, "2"),
+        }
+    }
+}
+
+impl HttpVersion {
+    /// Returns the version configured for the scan the context belongs to.
+    pub fn of(context: &Context) -> Result<Self, FunctionErrorKind> {
+        Ok(context
+            .extensions()
+            .get::<PluginConfig>()
+            .map(|x| x.get(HTTP_VERSION))
+            .transpose()?
+            .flatten()
+            .unwrap_or_default())
+    }
+
+    /// Returns the protocols offered via ALPN.
+    pub fn alpn(&self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            Self::Http2 => vec![b"h2".to_vec()],
+            Self::Http11 => vec![b"http/1.1".to_vec()],
+            // there is no widely supported protocol id for HTTP/1.0
+            Self::Http10 => vec![],
+        }
+    }
+
+    /// Returns the version a request is sent with, h2 is true when the server of a TLS connection
+    /// selected HTTP/2 via ALPN.
+    pub fn negotiate(&self, tls: bool, h2: bool) -> Result<http::Version, FunctionErrorKind> {
+        match (self, tls, h2) {
+            (Self::Http10, _, _) => Ok(http::Version::HTTP_10),
+            (Self::Http11, _, _) | (Self::Auto, false, _) | (Self::Auto, true, false) => {
+                Ok(http::Version::HTTP_11)
+            }
+            (Self::Http2, false, _) | (Self::Http2, true, true) | (Self::Auto, true, true) => {
+                Ok(http::Version::HTTP_2)
+            }
+            (Self::Http2, true, false) => Err(FunctionErrorKind::Diagnostic(
+                "The server does not support HTTP/2".to_string(),
+                Some(NaslValue::Null),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Version;
+
+    use super::HttpVersion;
+
+    #[test]
+    fn parse() {
+        assert_eq!("auto".parse(), Ok(HttpVersion::Auto));
+        assert_eq!("HTTP/1.0".parse(), Ok(HttpVersion::Http10));
+        assert_eq!(" 1.1".parse(), Ok(HttpVersion::Http11));
+        assert_eq!("2".parse(), Ok(HttpVersion::Http2));
+        assert!("3".parse::<HttpVersion>().is_err());
+        assert_eq!(HttpVersion::Http2.to_string(), "2");
+    }
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(
+            HttpVersion::Auto.negotiate(true, true).unwrap(),
+            Version::HTTP_2
+        );
+        assert_eq!(
+            HttpVersion::Auto.negotiate(true, false).unwrap(),
+            Version::HTTP_11
+        );
+        assert_eq!(
+            HttpVersion::Auto.negotiate(false, true).unwrap(),
+            Version::HTTP_11
+        );
+        assert_eq!(
+            HttpVersion::Http10.negotiate(true, true).unwrap(),
+            Version::HTTP_10
+        );
+        assert_eq!(
+            HttpVersion::Http2.negotiate(false, false).unwrap(),
+            Version::HTTP_2
+        );
+        assert!(HttpVersion::Http2.negotiate(true, false).is_err());
+    }
+}
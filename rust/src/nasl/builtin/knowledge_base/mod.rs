@@ -92,19 +92,26 @@ fn replace_kb_item(
 }
 
 /// NASL function to retrieve an item in a KB.
+///
+/// When the key contains a `*` wildcard, e.g. `Services/*`, all matching items are returned as
+/// an array indexed by their KB key.
 #[nasl_function(named(name, value, expires))]
 fn get_kb_list(key: NaslValue, c: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    let key = key.to_string();
+    let is_pattern = key.contains('*');
     c.retriever()
-        .retrieve(c.key(), Retrieve::KB(key.to_string()))
+        .retrieve(c.key(), Retrieve::KB(key))
         .map(|r| {
-            r.into_iter()
-                .filter_map(|x| match x {
-                    Field::NVT(_) | Field::NotusAdvisory(_) | Field::Result(_) => None,
-                    Field::KB(kb) => Some(kb.value.into()),
-                })
-                .collect::<Vec<_>>()
+            let kbs = r.into_iter().filter_map(|x| match x {
+                Field::NVT(_) | Field::NotusAdvisory(_) | Field::Result(_) => None,
+                Field::KB(kb) => Some(kb),
+            });
+            if is_pattern {
+                NaslValue::Dict(kbs.map(|kb| (kb.key, kb.value.into())).collect())
+            } else {
+                NaslValue::Array(kbs.map(|kb| kb.value.into()).collect())
+            }
         })
-        .map(NaslValue::Array)
         .map_err(|e| e.into())
 }
 
@@ -4,6 +4,8 @@
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use crate::nasl::test_prelude::*;
     use FunctionErrorKind::*;
 
@@ -39,6 +41,35 @@ mod tests {
         t.ok(r#"get_kb_list("test");"#, vec![1, 2]);
     }
 
+    #[test]
+    fn get_kb_list_pattern() {
+        let mut t = TestBuilder::default();
+        t.ok(
+            r#"set_kb_item(name: "Services/www", value: 80);"#,
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"set_kb_item(name: "Services/ssh", value: 22);"#,
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"set_kb_item(name: "Ports/tcp/22", value: 1);"#,
+            NaslValue::Null,
+        );
+        t.ok(
+            r#"get_kb_list("Services/*");"#,
+            NaslValue::Dict(HashMap::from([
+                ("Services/www".to_string(), NaslValue::Number(80)),
+                ("Services/ssh".to_string(), NaslValue::Number(22)),
+            ])),
+        );
+        t.ok(r#"max_index(keys(get_kb_list("*/*")));"#, 3);
+        t.ok(
+            r#"get_kb_list("Services/ftp*");"#,
+            NaslValue::Dict(HashMap::new()),
+        );
+    }
+
     #[test]
     fn replace_kb_item() {
         let mut t = TestBuilder::default();
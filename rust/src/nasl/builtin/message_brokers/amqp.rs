@@ -0,0 +1,235 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! AMQP protocol header exchange.
+//!
+//! An AMQP 0-9-1 broker answers the protocol header with a Connection.Start listing its server
+//! properties and SASL mechanisms. Brokers of other versions answer with the protocol header they
+//! support instead and close the connection, AMQP 1.0 brokers with a SASL layer list their
+//! mechanisms in a sasl-mechanisms frame after the SASL protocol header.
+
+use std::io::{self, Read};
+
+pub const HEADER_0_9_1: [u8; 8] = *b"AMQP\x00\x00\x09\x01";
+pub const HEADER_1_0_SASL: [u8; 8] = *b"AMQP\x03\x01\x00\x00";
+/// The protocol id of the AMQP 1.0 SASL layer
+pub const PROTOCOL_SASL: u8 = 3;
+
+const FRAME_METHOD: u8 = 1;
+const FRAME_END: u8 = 0xce;
+const MAX_FRAME: usize = 65536;
+/// The class and method id of Connection.Start
+const CONNECTION_START: [u8; 4] = [0, 10, 0, 10];
+/// The descriptor of the sasl-mechanisms performative
+const SASL_MECHANISMS: u8 = 0x40;
+
+/// A reader of big endian values
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|x| x[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+    }
+
+    fn short_string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    fn long_string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+fn symbol(cursor: &mut Cursor, constructor: u8) -> Option<String> {
+    match constructor {
+        0xa3 => cursor.short_string().map(text),
+        0xb3 => cursor.long_string().map(text),
+        _ => None,
+    }
+}
+
+/// The Connection.Start of an AMQP 0-9-1 broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Start {
+    pub major: u8,
+    pub minor: u8,
+    /// The string server properties, e.g. `product`, `version` and `platform`
+    pub properties: Vec<(String, String)>,
+    pub mechanisms: Vec<String>,
+    pub locales: Vec<String>,
+}
+
+/// The answer of a broker to the AMQP 0-9-1 protocol header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Start(Start),
+    /// The protocol header of the protocol id and version the broker supports instead
+    Header {
+        protocol: u8,
+        version: [u8; 3],
+    },
+}
+
+impl Answer {
+    /// Reads the answer and returns it with its length.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no AMQP broker");
+        let mut header = [0; 8];
+        reader.read_exact(&mut header[..7])?;
+        if header.starts_with(b"AMQP") {
+            reader.read_exact(&mut header[7..])?;
+            return Ok((
+                Self::Header {
+                    protocol: header[4],
+                    version: [header[5], header[6], header[7]],
+                },
+                8,
+            ));
+        }
+        let len = u32::from_be_bytes([header[3], header[4], header[5], header[6]]) as usize;
+        if header[0] != FRAME_METHOD || len > MAX_FRAME {
+            return Err(invalid());
+        }
+        let mut payload = vec![0; len + 1];
+        reader.read_exact(&mut payload)?;
+        if payload.pop() != Some(FRAME_END) {
+            return Err(invalid());
+        }
+        let start = parse_start(&payload).ok_or_else(invalid)?;
+        Ok((Self::Start(start), len + 8))
+    }
+}
+
+/// Parses the payload of a Connection.Start method frame.
+pub fn parse_start(payload: &[u8]) -> Option<Start> {
+    let mut cursor = Cursor(payload);
+    if cursor.take(4)? != CONNECTION_START {
+        return None;
+    }
+    let major = cursor.u8()?;
+    let minor = cursor.u8()?;
+    let properties = field_table(cursor.long_string()?);
+    let words = |x: &[u8]| text(x).split_whitespace().map(str::to_owned).collect();
+    let mechanisms = words(cursor.long_string()?);
+    let locales = words(cursor.long_string()?);
+    Some(Start {
+        major,
+        minor,
+        properties,
+        mechanisms,
+        locales,
+    })
+}
+
+/// Returns the string fields of a field table, the fields following a field of an unknown type
+/// are ignored.
+fn field_table(table: &[u8]) -> Vec<(String, String)> {
+    let mut cursor = Cursor(table);
+    let mut fields = vec![];
+    while let Some(name) = cursor.short_string() {
+        let skipped = match cursor.u8() {
+            Some(b'S') => cursor.long_string().map(|value| {
+                fields.push((text(name), text(value)));
+            }),
+            // tables, arrays and byte arrays
+            Some(b'F' | b'A' | b'x') => cursor.long_string().map(|_| ()),
+            Some(b't' | b'b' | b'B') => cursor.take(1).map(|_| ()),
+            Some(b's' | b'u') => cursor.take(2).map(|_| ()),
+            Some(b'I' | b'i' | b'f') => cursor.take(4).map(|_| ()),
+            Some(b'D') => cursor.take(5).map(|_| ()),
+            Some(b'l' | b'T' | b'd') => cursor.take(8).map(|_| ()),
+            Some(b'V') => Some(()),
+            _ => None,
+        };
+        if skipped.is_none() {
+            break;
+        }
+    }
+    fields
+}
+
+/// Reads the SASL protocol header and the sasl-mechanisms frame of an AMQP 1.0 broker and
+/// returns the mechanisms with the length read.
+pub fn read_sasl_mechanisms<R: Read>(reader: &mut R) -> io::Result<(Vec<String>, usize)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no sasl-mechanisms");
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    if header != HEADER_1_0_SASL {
+        return Err(invalid());
+    }
+    reader.read_exact(&mut header)?;
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let offset = header[4] as usize * 4;
+    if !(offset.max(8)..=MAX_FRAME).contains(&len) {
+        return Err(invalid());
+    }
+    let mut frame = vec![0; len - 8];
+    reader.read_exact(&mut frame)?;
+    let mechanisms = frame
+        .get(offset - 8..)
+        .and_then(sasl_mechanisms)
+        .ok_or_else(invalid)?;
+    Ok((mechanisms, 8 + len))
+}
+
+/// Parses the body of a sasl-mechanisms frame.
+pub fn sasl_mechanisms(body: &[u8]) -> Option<Vec<String>> {
+    let mut cursor = Cursor(body);
+    // the described list with the ulong descriptor
+    let descriptor = match cursor.take(2)? {
+        [0x00, 0x53] => cursor.u8()?,
+        [0x00, 0x80] => *cursor.take(8)?.last()?,
+        _ => return None,
+    };
+    if descriptor != SASL_MECHANISMS {
+        return None;
+    }
+    match cursor.u8()? {
+        0xc0 => {
+            cursor.take(2)?;
+        }
+        0xd0 => {
+            cursor.take(8)?;
+        }
+        _ => return None,
+    }
+    // the mechanisms are either a single symbol or an array of symbols
+    match cursor.u8()? {
+        x @ (0xa3 | 0xb3) => Some(vec![symbol(&mut cursor, x)?]),
+        x @ (0xe0 | 0xf0) => {
+            let count = if x == 0xe0 {
+                cursor.take(1)?;
+                cursor.u8()? as usize
+            } else {
+                cursor.take(4)?;
+                cursor.u32()? as usize
+            };
+            let constructor = cursor.u8()?;
+            (0..count)
+                .map(|_| symbol(&mut cursor, constructor))
+                .collect()
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,293 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins detecting MQTT and AMQP brokers and their authentication.
+//!
+//! - `mqtt_connect` sends an MQTT CONNECT and returns the CONNACK of the broker,
+//! - `mqtt_auth_required` returns whether a broker refuses anonymous clients,
+//! - `amqp_probe` exchanges the AMQP protocol header and returns the version, the server
+//!   properties and the SASL mechanisms of the broker.
+
+mod amqp;
+mod mqtt;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io::Write, net::TcpStream, time::Duration};
+
+use crate::nasl::prelude::*;
+
+use super::network::network_utils::{connect_target, timeout_arg};
+use super::network::verify_port;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The client id of MQTT connections by default
+const CLIENT_ID: &str = "OpenVAS";
+
+fn send(
+    context: &Context<'_>,
+    stream: &mut TcpStream,
+    data: &[u8],
+) -> Result<(), FunctionErrorKind> {
+    stream.write_all(data)?;
+    if let Some(traffic) = context.traffic() {
+        traffic.sent(data.len())?;
+    }
+    Ok(())
+}
+
+fn received(context: &Context<'_>, len: usize) -> Result<(), FunctionErrorKind> {
+    if let Some(traffic) = context.traffic() {
+        traffic.received(len)?;
+    }
+    Ok(())
+}
+
+/// Turns failures of the connection into None.
+fn or_none<T>(
+    port: u16,
+    result: Result<T, FunctionErrorKind>,
+) -> Result<Option<T>, FunctionErrorKind> {
+    match result {
+        Ok(x) => Ok(Some(x)),
+        Err(FunctionErrorKind::IOError(error)) => {
+            tracing::debug!(port, %error, "no broker answer");
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn level_arg(version: Option<i64>) -> Result<u8, FunctionErrorKind> {
+    match version {
+        None => Ok(4),
+        Some(x) => u8::try_from(x)
+            .ok()
+            .filter(|x| mqtt::LEVELS.contains(x))
+            .ok_or_else(|| {
+                FunctionErrorKind::wrong_argument("version", "3, 4 or 5", &x.to_string())
+            }),
+    }
+}
+
+/// Connects to the MQTT broker and returns its CONNACK, None when it does not answer as broker.
+async fn mqtt_exchange(
+    context: &Context<'_>,
+    port: i64,
+    level: u8,
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<Option<mqtt::ConnAck>, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, _permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(None);
+    };
+    let result = (|| -> Result<_, FunctionErrorKind> {
+        send(
+            context,
+            &mut stream,
+            &mqtt::connect(level, client_id, username, password),
+        )?;
+        let (ack, len) = mqtt::ConnAck::read(&mut stream)?;
+        received(context, len)?;
+        if ack.accepted() {
+            send(context, &mut stream, &mqtt::DISCONNECT)?;
+        }
+        Ok(ack)
+    })();
+    or_none(port, result)
+}
+
+/// *array* **mqtt_connect**(port: *int*, version: *int*, client_id: *string*, username: *string*, password: *string*, timeout: *int*);
+///
+/// Sends an MQTT CONNECT with a clean session to the broker on the port of the target, 1883 by
+/// default. The version is the protocol level, 3 for MQTT 3.1, 4 for MQTT 3.1.1 (default) and 5
+/// for MQTT 5. The client id defaults to `OpenVAS`, the username and password are only sent when
+/// given. An accepted connection is closed with a DISCONNECT.
+///
+/// Returns an array with the `return_code` of the CONNACK, the reason code for MQTT 5, whether a
+/// `session_present` and whether the connection was `accepted`. NULL is returned when the port
+/// does not answer as MQTT broker.
+#[nasl_function(named(port, version, client_id, username, password, timeout))]
+async fn mqtt_connect(
+    context: &Context<'_>,
+    port: Option<i64>,
+    version: Option<i64>,
+    client_id: Option<&str>,
+    username: Option<&str>,
+    password: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let level = level_arg(version)?;
+    let port = port.unwrap_or(1883);
+    let client_id = client_id.unwrap_or(CLIENT_ID);
+    let Some(ack) =
+        mqtt_exchange(context, port, level, client_id, username, password, timeout).await?
+    else {
+        return Ok(NaslValue::Null);
+    };
+    Ok(NaslValue::Dict(HashMap::from([
+        (
+            "return_code".to_string(),
+            NaslValue::Number(ack.code as i64),
+        ),
+        (
+            "session_present".to_string(),
+            NaslValue::Boolean(ack.session_present),
+        ),
+        ("accepted".to_string(), NaslValue::Boolean(ack.accepted())),
+    ])))
+}
+
+/// *bool* **mqtt_auth_required**(port: *int*, version: *int*, timeout: *int*);
+///
+/// Returns whether the MQTT broker on the port of the target, 1883 by default, refuses a
+/// connection without credentials as not authorized or with a bad username or password. The
+/// version is the protocol level as for `mqtt_connect`.
+///
+/// FALSE is returned when the broker accepts anonymous clients or refuses them for other reasons
+/// and NULL when the port does not answer as MQTT broker.
+#[nasl_function(named(port, version, timeout))]
+async fn mqtt_auth_required(
+    context: &Context<'_>,
+    port: Option<i64>,
+    version: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let level = level_arg(version)?;
+    let port = port.unwrap_or(1883);
+    Ok(
+        match mqtt_exchange(context, port, level, CLIENT_ID, None, None, timeout).await? {
+            Some(ack) => NaslValue::Boolean(ack.auth_refused()),
+            None => NaslValue::Null,
+        },
+    )
+}
+
+fn strings(values: Vec<String>) -> NaslValue {
+    NaslValue::Array(
+        values
+            .into_iter()
+            .map(|x| NaslValue::String(x.into()))
+            .collect(),
+    )
+}
+
+fn version_string(version: [u8; 3]) -> String {
+    let [major, minor, revision] = version;
+    if major == 0 {
+        format!("{major}-{minor}-{revision}")
+    } else {
+        format!("{major}.{minor}.{revision}")
+    }
+}
+
+/// Exchanges the protocol headers with the AMQP broker, None when it does not answer as broker.
+async fn amqp_exchange(
+    context: &Context<'_>,
+    port: i64,
+    timeout: Option<i64>,
+) -> Result<Option<HashMap<String, NaslValue>>, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(None);
+    };
+    let result = (|| -> Result<_, FunctionErrorKind> {
+        send(context, &mut stream, &amqp::HEADER_0_9_1)?;
+        let (answer, len) = amqp::Answer::read(&mut stream)?;
+        received(context, len)?;
+        Ok(answer)
+    })();
+    let Some(answer) = or_none(port, result)? else {
+        return Ok(None);
+    };
+    let mut result = HashMap::new();
+    let mechanisms = match answer {
+        amqp::Answer::Start(start) => {
+            result.insert(
+                "version".to_string(),
+                NaslValue::String(format!("{}-{}-1", start.major, start.minor).into()),
+            );
+            result.insert(
+                "properties".to_string(),
+                NaslValue::Dict(
+                    start
+                        .properties
+                        .into_iter()
+                        .map(|(name, value)| (name, NaslValue::String(value.into())))
+                        .collect(),
+                ),
+            );
+            result.insert("locales".to_string(), strings(start.locales));
+            start.mechanisms
+        }
+        amqp::Answer::Header { protocol, version } => {
+            result.insert(
+                "version".to_string(),
+                NaslValue::String(version_string(version).into()),
+            );
+            result.insert(
+                "protocol_id".to_string(),
+                NaslValue::Number(protocol as i64),
+            );
+            if protocol != amqp::PROTOCOL_SASL || version[0] != 1 {
+                // a broker without a SASL layer
+                vec![]
+            } else {
+                // the broker closes the connection after its header, the SASL layer needs another
+                drop(stream);
+                drop(permit);
+                let Some((mut stream, _permit)) = connect_target(context, port, timeout).await?
+                else {
+                    return Ok(Some(result));
+                };
+                let mechanisms = (|| -> Result<_, FunctionErrorKind> {
+                    send(context, &mut stream, &amqp::HEADER_1_0_SASL)?;
+                    let (mechanisms, len) = amqp::read_sasl_mechanisms(&mut stream)?;
+                    received(context, len)?;
+                    Ok(mechanisms)
+                })();
+                or_none(port, mechanisms)?.unwrap_or_default()
+            }
+        }
+    };
+    let anonymous = mechanisms.is_empty() || mechanisms.iter().any(|x| x == "ANONYMOUS");
+    result.insert("anonymous".to_string(), NaslValue::Boolean(anonymous));
+    result.insert("mechanisms".to_string(), strings(mechanisms));
+    Ok(Some(result))
+}
+
+/// *array* **amqp_probe**(port: *int*, timeout: *int*);
+///
+/// Sends the AMQP 0-9-1 protocol header to the broker on the port of the target, 5672 by default.
+///
+/// Returns an array with the AMQP `version` of the broker, its SASL `mechanisms` and whether it
+/// accepts `anonymous` clients, i.e. offers the ANONYMOUS mechanism or no SASL layer at all. An
+/// AMQP 0-9-1 broker additionally returns its server `properties` like `product`, `version` and
+/// `platform` and its `locales`. A broker answering with the header of another version returns
+/// the `protocol_id` of that header, for AMQP 1.0 with a SASL layer its mechanisms are read via a
+/// second connection. NULL is returned when the port does not answer as AMQP broker.
+#[nasl_function(named(port, timeout))]
+async fn amqp_probe(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    Ok(amqp_exchange(context, port.unwrap_or(5672), timeout)
+        .await?
+        .map(NaslValue::Dict)
+        .unwrap_or(NaslValue::Null))
+}
+
+pub struct MessageBrokers;
+
+function_set! {
+    MessageBrokers,
+    async_stateless,
+    (mqtt_connect, mqtt_auth_required, amqp_probe)
+}
@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! MQTT CONNECT and CONNACK of the protocol levels 3 (3.1), 4 (3.1.1) and 5.
+
+use std::io::{self, Read};
+
+const PACKET_CONNECT: u8 = 0x10;
+const PACKET_CONNACK: u8 = 0x20;
+/// The DISCONNECT sent after an accepted connection
+pub const DISCONNECT: [u8; 2] = [0xe0, 0x00];
+
+const FLAG_USERNAME: u8 = 0x80;
+const FLAG_PASSWORD: u8 = 0x40;
+const FLAG_CLEAN_SESSION: u8 = 0x02;
+const KEEP_ALIVE: u16 = 60;
+
+/// The return codes of brokers refusing the credentials before level 5
+const BAD_USERNAME_OR_PASSWORD: u8 = 0x04;
+const NOT_AUTHORIZED: u8 = 0x05;
+/// The reason codes of brokers refusing the credentials of level 5
+const BAD_USERNAME_OR_PASSWORD_V5: u8 = 0x86;
+const NOT_AUTHORIZED_V5: u8 = 0x87;
+
+/// The supported protocol levels
+pub const LEVELS: [u8; 3] = [3, 4, 5];
+
+fn push_string(packet: &mut Vec<u8>, value: &[u8]) {
+    packet.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    packet.extend_from_slice(value);
+}
+
+/// Appends the variable byte integer of the remaining length.
+fn push_length(packet: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            return;
+        }
+        packet.push(byte | 0x80);
+    }
+}
+
+/// Returns a CONNECT of the protocol level with a clean session.
+pub fn connect(
+    level: u8,
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Vec<u8> {
+    let mut body = vec![];
+    push_string(&mut body, if level == 3 { b"MQIsdp" } else { b"MQTT" });
+    let mut flags = FLAG_CLEAN_SESSION;
+    if username.is_some() {
+        flags |= FLAG_USERNAME;
+    }
+    if password.is_some() {
+        flags |= FLAG_PASSWORD;
+    }
+    body.extend_from_slice(&[level, flags]);
+    body.extend_from_slice(&KEEP_ALIVE.to_be_bytes());
+    if level >= 5 {
+        // no properties
+        body.push(0);
+    }
+    for field in [Some(client_id), username, password].into_iter().flatten() {
+        push_string(&mut body, field.as_bytes());
+    }
+    let mut packet = vec![PACKET_CONNECT];
+    push_length(&mut packet, body.len());
+    packet.extend(body);
+    packet
+}
+
+/// The CONNACK of a broker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnAck {
+    pub session_present: bool,
+    /// The return code, the reason code for level 5
+    pub code: u8,
+}
+
+impl ConnAck {
+    /// Reads a CONNACK and returns it with its length.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<(Self, usize)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid CONNACK");
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        if byte[0] != PACKET_CONNACK {
+            return Err(invalid());
+        }
+        let mut len = 0;
+        let mut header = 1;
+        for shift in [0, 7, 14, 21] {
+            reader.read_exact(&mut byte)?;
+            header += 1;
+            len |= ((byte[0] & 0x7f) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        // level 5 brokers may append properties to the two bytes
+        if !(2..=4096).contains(&len) {
+            return Err(invalid());
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        Ok((
+            Self {
+                session_present: body[0] & 0x01 != 0,
+                code: body[1],
+            },
+            header + len,
+        ))
+    }
+
+    pub fn accepted(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Returns whether the broker refused the connection because of missing or wrong
+    /// credentials.
+    pub fn auth_refused(&self) -> bool {
+        matches!(
+            self.code,
+            BAD_USERNAME_OR_PASSWORD
+                | NOT_AUTHORIZED
+                | BAD_USERNAME_OR_PASSWORD_V5
+                | NOT_AUTHORIZED_V5
+        )
+    }
+}
@@ -0,0 +1,240 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::super::{amqp, mqtt};
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::NoOpLoader;
+    use crate::storage::{ContextKey, DefaultDispatcher};
+
+    fn builder() -> TestBuilder<NoOpLoader, DefaultDispatcher> {
+        TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())))
+    }
+
+    fn long_string(data: &mut Vec<u8>, value: &[u8]) {
+        data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        data.extend_from_slice(value);
+    }
+
+    /// Returns the Connection.Start method frame of a RabbitMQ broker.
+    fn connection_start() -> Vec<u8> {
+        let mut table = vec![];
+        table.push(12);
+        table.extend_from_slice(b"capabilities");
+        table.push(b'F');
+        long_string(&mut table, b"\x0cbasic.nack\x74\x01");
+        for (name, value) in [("product", "RabbitMQ"), ("version", "3.12.4")] {
+            table.push(name.len() as u8);
+            table.extend_from_slice(name.as_bytes());
+            table.push(b'S');
+            long_string(&mut table, value.as_bytes());
+        }
+        let mut payload = vec![0, 10, 0, 10, 0, 9];
+        long_string(&mut payload, &table);
+        long_string(&mut payload, b"PLAIN AMQPLAIN");
+        long_string(&mut payload, b"en_US");
+        let mut frame = vec![1, 0, 0];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend(payload);
+        frame.push(0xce);
+        frame
+    }
+
+    /// Returns a sasl-mechanisms frame with an array of the mechanisms.
+    fn sasl_mechanisms(mechanisms: &[&str]) -> Vec<u8> {
+        let mut array = vec![mechanisms.len() as u8, 0xa3];
+        for mechanism in mechanisms {
+            array.push(mechanism.len() as u8);
+            array.extend_from_slice(mechanism.as_bytes());
+        }
+        let mut list = vec![1, 0xe0, array.len() as u8];
+        list.extend(array);
+        let mut body = vec![0x00, 0x53, 0x40, 0xc0, list.len() as u8];
+        body.extend(list);
+        let mut frame = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&[2, 1, 0, 0]);
+        frame.extend(body);
+        frame
+    }
+
+    #[test]
+    fn mqtt_packets() {
+        let mut expected = vec![0x10, 19, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 60, 0, 7];
+        expected.extend_from_slice(b"OpenVAS");
+        assert_eq!(mqtt::connect(4, "OpenVAS", None, None), expected);
+        let packet = mqtt::connect(5, "x", Some("user"), Some("pass"));
+        assert_eq!(&packet[..2], &[0x10, 26]);
+        assert_eq!(&packet[8..13], &[5, 0xc2, 0, 60, 0]);
+        assert_eq!(&mqtt::connect(3, "x", None, None)[2..10], b"\0\x06MQIsdp");
+
+        let (ack, len) = mqtt::ConnAck::read(&mut &[0x20, 2, 1, 5][..]).unwrap();
+        assert_eq!(len, 4);
+        assert!(ack.session_present && ack.auth_refused() && !ack.accepted());
+        let (ack, len) = mqtt::ConnAck::read(&mut &[0x20, 3, 0, 0x87, 0][..]).unwrap();
+        assert_eq!(len, 5);
+        assert!(ack.auth_refused());
+        assert!(mqtt::ConnAck::read(&mut &[0x30, 2, 0, 0][..]).is_err());
+        assert!(mqtt::ConnAck::read(&mut &[0x20, 2, 0][..]).is_err());
+    }
+
+    #[test]
+    fn amqp_parse() {
+        let frame = connection_start();
+        let (answer, len) = amqp::Answer::read(&mut frame.as_slice()).unwrap();
+        assert_eq!(len, frame.len());
+        assert_eq!(
+            answer,
+            amqp::Answer::Start(amqp::Start {
+                major: 0,
+                minor: 9,
+                properties: vec![
+                    ("product".into(), "RabbitMQ".into()),
+                    ("version".into(), "3.12.4".into())
+                ],
+                mechanisms: vec!["PLAIN".into(), "AMQPLAIN".into()],
+                locales: vec!["en_US".into()],
+            })
+        );
+        assert_eq!(
+            amqp::Answer::read(&mut &b"AMQP\x03\x01\x00\x00"[..]).unwrap(),
+            (
+                amqp::Answer::Header {
+                    protocol: 3,
+                    version: [1, 0, 0]
+                },
+                8
+            )
+        );
+        assert!(amqp::Answer::read(&mut &b"HTTP/1.1 400"[..]).is_err());
+
+        let frame = sasl_mechanisms(&["ANONYMOUS", "PLAIN"]);
+        assert_eq!(
+            amqp::sasl_mechanisms(&frame[8..]),
+            Some(vec!["ANONYMOUS".into(), "PLAIN".into()])
+        );
+        assert_eq!(
+            amqp::sasl_mechanisms(&[0x00, 0x53, 0x40, 0xc0, 7, 1, 0xa3, 5, b'P', b'L', b'A', b'I']),
+            None
+        );
+        assert_eq!(
+            amqp::sasl_mechanisms(&[
+                0x00, 0x53, 0x40, 0xc0, 7, 1, 0xa3, 5, b'P', b'L', b'A', b'I', b'N'
+            ]),
+            Some(vec!["PLAIN".into()])
+        );
+        let mut stream = amqp::HEADER_1_0_SASL.to_vec();
+        stream.extend(frame);
+        let (mechanisms, len) = amqp::read_sasl_mechanisms(&mut stream.as_slice()).unwrap();
+        assert_eq!(mechanisms, vec!["ANONYMOUS".to_string(), "PLAIN".into()]);
+        assert_eq!(len, stream.len());
+    }
+
+    /// Reads the CONNECT of an MQTT client and returns its variable header and payload.
+    fn read_connect(stream: &mut TcpStream) -> Vec<u8> {
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 0x10);
+        let mut body = vec![0; header[1] as usize];
+        stream.read_exact(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn mqtt() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            // an anonymous client is refused, the client with credentials accepted
+            let (mut stream, _) = listener.accept().unwrap();
+            let connect = read_connect(&mut stream);
+            assert_eq!(connect[7], 0x02);
+            stream.write_all(&[0x20, 2, 0, 5]).unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let connect = read_connect(&mut stream);
+            assert_eq!(connect[7], 0xc2);
+            stream.write_all(&[0x20, 2, 0, 0]).unwrap();
+            let mut disconnect = [0; 2];
+            stream.read_exact(&mut disconnect).unwrap();
+            disconnect
+        });
+        let mut t = builder();
+        t.ok(&format!("mqtt_auth_required(port: {port});"), true);
+        t.run(&format!(
+            r#"a = mqtt_connect(port: {port}, username: "admin", password: "secret");"#
+        ));
+        t.ok(r#"a["accepted"];"#, true);
+        t.ok(r#"a["return_code"];"#, 0);
+        t.ok(r#"a["session_present"];"#, false);
+        check_err_matches!(
+            t,
+            "mqtt_connect(port: 1, version: 6);",
+            FunctionErrorKind::WrongArgument { .. }
+        );
+        drop(t);
+        assert_eq!(server.join().unwrap(), mqtt::DISCONNECT);
+    }
+
+    #[test]
+    fn amqp_0_9_1() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0; 8];
+            stream.read_exact(&mut header).unwrap();
+            assert_eq!(header, amqp::HEADER_0_9_1);
+            stream.write_all(&connection_start()).unwrap();
+        });
+        let mut t = builder();
+        t.run(&format!("a = amqp_probe(port: {port});"));
+        t.ok(r#"a["version"];"#, "0-9-1");
+        t.run(r#"p = a["properties"];"#);
+        t.ok(r#"p["product"];"#, "RabbitMQ");
+        t.ok(
+            r#"a["mechanisms"];"#,
+            vec![
+                NaslValue::String("PLAIN".into()),
+                NaslValue::String("AMQPLAIN".into()),
+            ],
+        );
+        t.ok(r#"a["anonymous"];"#, false);
+    }
+
+    #[test]
+    fn amqp_1_0() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            // the broker answers with its header and closes the connection
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0; 8];
+            stream.read_exact(&mut header).unwrap();
+            stream.write_all(&amqp::HEADER_1_0_SASL).unwrap();
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut header).unwrap();
+            assert_eq!(header, amqp::HEADER_1_0_SASL);
+            stream.write_all(&amqp::HEADER_1_0_SASL).unwrap();
+            stream
+                .write_all(&sasl_mechanisms(&["ANONYMOUS", "PLAIN"]))
+                .unwrap();
+        });
+        let mut t = builder();
+        t.run(&format!("a = amqp_probe(port: {port});"));
+        t.ok(r#"a["version"];"#, "1.0.0");
+        t.ok(r#"a["protocol_id"];"#, 3);
+        t.ok(r#"a["anonymous"];"#, true);
+        t.ok(r#"max_index(a["mechanisms"]);"#, 2);
+    }
+}
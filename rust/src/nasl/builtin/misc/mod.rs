@@ -21,10 +21,14 @@ use chrono::{
 use nasl_function_proc_macro::nasl_function;
 
 use crate::nasl::{prelude::*, utils::function::Maybe};
+use crate::storage::{types::Primitive, Field, Kb};
 use flate2::{
     read::GzDecoder, read::ZlibDecoder, write::GzEncoder, write::ZlibEncoder, Compression,
 };
 
+/// Prefix of the KB items created by set_timing_kb_item
+pub const TIMING_KB_PREFIX: &str = "Timing/";
+
 #[inline]
 #[cfg(unix)]
 /// Reads 8 bytes from /dev/urandom and parses it to an i64
@@ -227,15 +231,77 @@ fn defined_func(fn_name: Option<Maybe<&str>>, ctx: &Context, register: &Register
 /// For example: “1067352015.030757” means 1067352015 seconds and 30757 microseconds.
 #[nasl_function]
 fn gettimeofday() -> Result<String, FunctionErrorKind> {
+    let time = now_micros()?;
+    Ok(format!("{}.{:06}", time / 1000000, time % 1000000))
+}
+
+/// Returns the microseconds since 1st January 1970.
+fn now_micros() -> Result<u128, FunctionErrorKind> {
     match time::SystemTime::now().duration_since(time::SystemTime::UNIX_EPOCH) {
-        Ok(time) => {
-            let time = time.as_micros();
-            Ok(format!("{}.{:06}", time / 1000000, time % 1000000))
-        }
+        Ok(time) => Ok(time.as_micros()),
         Err(e) => Err(FunctionErrorKind::Dirty(format!("{e}"))),
     }
 }
 
+/// Parses a value returned by gettimeofday into microseconds since 1st January 1970.
+fn parse_timeofday(value: &str) -> Option<u128> {
+    let (secs, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+    if fraction.len() > 6 || !fraction.chars().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let micros = format!("{fraction:0<6}").parse::<u128>().ok()?;
+    Some(secs.parse::<u128>().ok()? * 1000000 + micros)
+}
+
+/// Returns the microseconds between start and end, without end the current time is used.
+fn elapsed(start: &str, end: Option<&str>) -> Result<i64, FunctionErrorKind> {
+    let parse = |key, value| {
+        parse_timeofday(value).ok_or_else(|| {
+            FunctionErrorKind::wrong_argument(key, "value returned by gettimeofday", value)
+        })
+    };
+    let start = parse("start", start)?;
+    let end = match end {
+        Some(end) => parse("end", end)?,
+        None => now_micros()?,
+    };
+    Ok(end.saturating_sub(start).try_into().unwrap_or(i64::MAX))
+}
+
+/// Returns the elapsed microseconds between two values returned by gettimeofday.
+///
+/// - start, value of gettimeofday at the beginning of the measurement
+/// - end, optional value of gettimeofday at the end of the measurement, the current time is used
+///   when it is omitted
+///
+/// When end is before start 0 is returned.
+#[nasl_function(named(start, end))]
+fn elapsed_usec(start: &str, end: Option<&str>) -> Result<i64, FunctionErrorKind> {
+    elapsed(start, end)
+}
+
+/// Stores the elapsed microseconds since start as KB item `Timing/<name>` and returns them.
+///
+/// - name, name of the measurement
+/// - start, value of gettimeofday at the beginning of the measurement
+#[nasl_function(named(name, start))]
+fn set_timing_kb_item(
+    name: &str,
+    start: &str,
+    context: &Context,
+) -> Result<i64, FunctionErrorKind> {
+    let elapsed = elapsed(start, None)?;
+    context.dispatcher().dispatch(
+        context.key(),
+        Field::KB(Kb {
+            key: format!("{TIMING_KB_PREFIX}{name}"),
+            value: Primitive::Number(elapsed),
+            expire: None,
+        }),
+    )?;
+    Ok(elapsed)
+}
+
 /// Is a debug function to print the keys available within the called context. It does not take any
 /// nor returns any arguments.
 #[nasl_function]
@@ -263,6 +329,8 @@ function_set! {
         gunzip,
         defined_func,
         gettimeofday,
+        elapsed_usec,
+        set_timing_kb_item,
         dump_ctxt,
     )
 }
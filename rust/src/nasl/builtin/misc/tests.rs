@@ -198,4 +198,36 @@ mod tests {
         t.ok(r#"defined_func("a");"#, false);
         t.ok("defined_func(a);", false);
     }
+
+    #[test]
+    fn elapsed_usec() {
+        check_code_result(
+            r#"elapsed_usec(start: "1067352015.030757", end: "1067352016.000001");"#,
+            969244,
+        );
+        check_code_result(
+            r#"elapsed_usec(start: "1067352016.5", end: "1067352015.0");"#,
+            0,
+        );
+        check_code_result_matches!(
+            r#"elapsed_usec(start: gettimeofday());"#,
+            NaslValue::Number(0..)
+        );
+        check_err_matches!(
+            r#"elapsed_usec(start: "yesterday");"#,
+            FunctionErrorKind::WrongArgument(_)
+        );
+    }
+
+    #[test]
+    fn set_timing_kb_item() {
+        let mut t = TestBuilder::default();
+        t.run("start = gettimeofday();");
+        t.check(r#"set_timing_kb_item(name: "login", start: start);"#, |x| {
+            matches!(x, Ok(NaslValue::Number(0..)))
+        });
+        t.check(r#"get_kb_item("Timing/login");"#, |x| {
+            matches!(x, Ok(NaslValue::Number(0..)))
+        });
+    }
 }
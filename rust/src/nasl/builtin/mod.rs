@@ -5,26 +5,50 @@
 #![doc = include_str!("README.md")]
 
 mod array;
+mod cpe;
+mod credential_testing;
 mod cryptographic;
+mod database;
 mod description;
+mod discovery;
+mod find_service;
 mod host;
 mod http;
 mod isotime;
 mod knowledge_base;
+mod message_brokers;
 mod misc;
 mod network;
+#[cfg(feature = "nasl-builtin-ot-protocols")]
+mod ot_protocols;
 #[cfg(feature = "nasl-builtin-raw-ip")]
 mod raw_ip;
+mod rdp;
 mod regex;
 mod report_functions;
 #[cfg(feature = "nasl-builtin-ssh")]
 mod ssh;
+mod ssh_kex;
 mod string;
+mod tls_probe;
+
+use std::sync::Arc;
 
 use crate::nasl::syntax::{Loader, NoOpLoader};
-use crate::nasl::utils::{Context, Executor, NaslVarRegister, NaslVarRegisterBuilder, Register};
+use crate::nasl::utils::{
+    Context, Executor, Extensions, NaslVarRegister, NaslVarRegisterBuilder, Register, Risk,
+};
 use crate::storage::{ContextKey, DefaultDispatcher, Storage};
 
+/// Builtin functions that may affect the target, every other function is safe.
+///
+/// They are refused while the `safe_checks` preference is enabled.
+pub const RISKS: &[(&str, Risk)] = &[
+    ("credential_attempt", Risk::Intrusive),
+    ("http2_put", Risk::Intrusive),
+    ("http2_delete", Risk::Destructive),
+];
+
 /// Creates a new Executor and adds all the functions to it.
 ///
 /// When you have a function that is considered experimental due to either dependencies on
@@ -40,19 +64,72 @@ pub fn nasl_std_functions() -> Executor {
         .add_set(misc::Misc)
         .add_set(string::NaslString)
         .add_set(host::Host)
-        .add_set(http::NaslHttp::default())
+        .add_set(find_service::FindService)
+        .add_set(tls_probe::TlsProbe)
+        .add_set(ssh_kex::SshKex)
+        .add_set(rdp::Rdp)
+        .add_set(discovery::Discovery)
+        .add_set(message_brokers::MessageBrokers)
+        .add_set(database::Database)
+        .add_set(credential_testing::CredentialTesting)
+        .add_plugin(http::NaslHttp)
         .add_set(network::socket::NaslSockets::default())
         .add_set(network::network::Network)
         .add_set(regex::RegularExpressions)
         .add_set(cryptographic::Cryptographic)
         .add_set(description::Description)
-        .add_set(isotime::NaslIsotime);
+        .add_set(isotime::NaslIsotime)
+        .add_set(cpe::NaslCpe);
 
     #[cfg(feature = "nasl-builtin-ssh")]
-    executor.add_set(ssh::Ssh::default());
+    executor.add_plugin(ssh::Ssh);
     #[cfg(feature = "nasl-builtin-raw-ip")]
     executor.add_set(raw_ip::RawIp);
+    #[cfg(feature = "nasl-builtin-ot-protocols")]
+    executor.add_set(ot_protocols::OtProtocols);
+
+    for (function, risk) in RISKS {
+        executor.with_risk(function, *risk);
+    }
+    executor
+}
+
+/// Returns the names of the optional builtin function sets enabled in this build.
+#[allow(clippy::vec_init_then_push)]
+pub fn enabled_builtins() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut builtins = vec![];
+    #[cfg(feature = "nasl-builtin-ssh")]
+    builtins.push("ssh");
+    #[cfg(feature = "nasl-builtin-raw-ip")]
+    builtins.push("raw_ip");
+    #[cfg(feature = "nasl-builtin-ot-protocols")]
+    builtins.push("ot_protocols");
+    builtins
+}
 
+/// Returns true when the process is allowed to open raw sockets.
+pub fn raw_sockets_available() -> bool {
+    use network::capabilities::{has, Capability};
+    has(Capability::NetRaw)
+}
+
+/// Creates a new Executor with the functions that neither access the network nor block the
+/// execution.
+///
+/// This is used to interpret untrusted scripts, e.g. when fuzzing the interpreter.
+pub fn nasl_offline_functions() -> Executor {
+    let mut executor = Executor::default();
+    executor
+        .add_set(array::Array)
+        .add_set(report_functions::Reporting::default())
+        .add_set(knowledge_base::KnowledgeBase)
+        .add_set(string::NaslString)
+        .add_set(regex::RegularExpressions)
+        .add_set(cryptographic::Cryptographic)
+        .add_set(description::Description)
+        .add_set(isotime::NaslIsotime)
+        .add_set(cpe::NaslCpe);
     executor
 }
 
@@ -68,7 +145,7 @@ pub fn nasl_std_functions() -> Executor {
 ///
 /// This way the user can decide on compile if the functionality, and therefore the variables, are enabled or not.
 pub fn nasl_std_variables() -> NaslVarRegister {
-    let mut builder = NaslVarRegisterBuilder::new();
+    let mut builder = NaslVarRegisterBuilder::new().push_register(network::Transports);
     builder = add_raw_ip_vars(builder);
     builder.build()
 }
@@ -95,6 +172,8 @@ pub struct ContextFactory<Loader, Storage> {
     pub loader: Loader,
     /// The functions available to the nasl script.
     pub functions: Executor,
+    /// The state of the builtin functions shared by all built contexts.
+    pub extensions: Arc<Extensions>,
 }
 
 impl Default for ContextFactory<NoOpLoader, DefaultDispatcher> {
@@ -103,6 +182,7 @@ impl Default for ContextFactory<NoOpLoader, DefaultDispatcher> {
             loader: NoOpLoader::default(),
             functions: nasl_std_functions(),
             storage: DefaultDispatcher::default(),
+            extensions: Arc::default(),
         }
     }
 }
@@ -120,6 +200,7 @@ where
             storage,
             loader,
             functions: nasl_std_functions(),
+            extensions: Arc::default(),
         }
     }
 
@@ -144,6 +225,7 @@ where
             &self.loader,
             &self.functions,
         )
+        .with_extensions(self.extensions.clone())
     }
 }
 
@@ -0,0 +1,76 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Detection of the Linux capabilities of the scanner process.
+
+use std::fs;
+
+/// Capabilities relevant for the network functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Allows binding to ports below 1024
+    NetBindService = 10,
+    /// Allows using raw sockets
+    NetRaw = 13,
+}
+
+impl Capability {
+    /// Parses the name of a capability, e.g. `CAP_NET_RAW`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().trim_start_matches("CAP_") {
+            "NET_BIND_SERVICE" => Some(Self::NetBindService),
+            "NET_RAW" => Some(Self::NetRaw),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the effective capability set out of the content of `/proc/self/status`
+fn parse_effective(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|x| x.strip_prefix("CapEff:"))
+        .and_then(|x| u64::from_str_radix(x.trim(), 16).ok())
+}
+
+/// Returns the effective capability set of the current process.
+///
+/// When the set cannot be read root is assumed to have every capability.
+pub fn effective() -> u64 {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|x| parse_effective(&x))
+        .unwrap_or_else(|| match unsafe { libc::geteuid() } {
+            0 => u64::MAX,
+            _ => 0,
+        })
+}
+
+/// Returns true when the current process has the given capability
+pub fn has(capability: Capability) -> bool {
+    effective() & (1 << capability as u64) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_set() {
+        let status = "Name:\topenvasd\nCapInh:\t0000000000000000\nCapEff:\t0000000000002400\n";
+        let set = parse_effective(status).unwrap();
+        assert_ne!(set & (1 << Capability::NetBindService as u64), 0);
+        assert_ne!(set & (1 << Capability::NetRaw as u64), 0);
+        assert_eq!(parse_effective("Name:\topenvasd\n"), None);
+        assert_eq!(
+            Capability::from_name("cap_net_raw"),
+            Some(Capability::NetRaw)
+        );
+        assert_eq!(
+            Capability::from_name("NET_BIND_SERVICE"),
+            Some(Capability::NetBindService)
+        );
+        assert_eq!(Capability::from_name("CAP_SYS_ADMIN"), None);
+    }
+}
@@ -0,0 +1,169 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Dual stack connection establishment based on happy eyeballs (RFC 8305).
+//!
+//! When a host name resolves to IPv6 and IPv4 addresses the connection attempts are started
+//! alternating between both families, preferring IPv6. Each attempt gets a head start of
+//! [CONNECTION_ATTEMPT_DELAY] before the next address is tried, a failed attempt starts the next
+//! one immediately. The first established connection wins.
+
+use std::{
+    io,
+    net::{IpAddr, TcpStream},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::network_utils::{connect_tcp, SourceAddrs};
+
+/// Time a connection attempt may take before the next address is tried in parallel.
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// KB key containing the address family of the last established dual stack connection.
+pub const KB_FAMILY: &str = "Host/happy_eyeballs/family";
+
+/// Returns the name of the address family as it is stored within the KB.
+pub fn family(addr: &IpAddr) -> &'static str {
+    match addr {
+        IpAddr::V4(_) => "ipv4",
+        IpAddr::V6(_) => "ipv6",
+    }
+}
+
+/// Returns true when the given addresses contain IPv4 and IPv6 addresses.
+pub fn is_dual_stack(addrs: &[IpAddr]) -> bool {
+    addrs.iter().any(IpAddr::is_ipv4) && addrs.iter().any(IpAddr::is_ipv6)
+}
+
+/// Orders the addresses alternating by family, starting with IPv6.
+///
+/// The order within a family is kept.
+pub fn interleave(addrs: &[IpAddr]) -> Vec<IpAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.iter().partition(|x| x.is_ipv6());
+    let mut v6 = v6.into_iter();
+    let mut v4 = v4.into_iter();
+    let mut result = Vec::with_capacity(addrs.len());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return result,
+            (a, b) => result.extend(a.into_iter().chain(b).copied()),
+        }
+    }
+}
+
+/// Connects to the first reachable address.
+///
+/// The addresses are raced as described in the module documentation. Each connection is bound to
+/// the source address of its family when one is given. When none of the addresses is reachable
+/// within the timeout the last error is returned.
+pub fn connect(
+    addrs: &[IpAddr],
+    port: u16,
+    timeout: Duration,
+    source: SourceAddrs,
+) -> io::Result<TcpStream> {
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = mpsc::channel();
+    let mut candidates = interleave(addrs).into_iter();
+    let mut pending = 0;
+    let mut last_error = None;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let remaining = deadline - now;
+        let has_next = match candidates.next() {
+            Some(addr) => {
+                let tx = tx.clone();
+                let source = source.clone();
+                thread::spawn(move || {
+                    let result = connect_tcp(source.socket_addr(addr, port), &source, remaining);
+                    // the receiver is gone when another attempt already succeeded
+                    let _ = tx.send(result);
+                });
+                pending += 1;
+                candidates.len() > 0
+            }
+            None if pending == 0 => {
+                return Err(
+                    last_error.unwrap_or_else(|| io::Error::other("no address to connect to"))
+                )
+            }
+            None => false,
+        };
+        let wait = if has_next {
+            CONNECTION_ATTEMPT_DELAY.min(remaining)
+        } else {
+            remaining
+        };
+        match rx.recv_timeout(wait) {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => {
+                pending -= 1;
+                last_error = Some(e);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    use super::*;
+
+    #[test]
+    fn interleave_families() {
+        let v4 = |x| IpAddr::V4(Ipv4Addr::new(192, 0, 2, x));
+        let v6 = |x| IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, x));
+        assert_eq!(
+            interleave(&[v4(1), v4(2), v4(3), v6(1), v6(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v4(3)]
+        );
+        assert!(is_dual_stack(&[v4(1), v6(1)]));
+        assert!(!is_dual_stack(&[v4(1), v4(2)]));
+    }
+
+    #[test]
+    fn falls_back_to_reachable_family() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = connect(
+            &[
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ],
+            port,
+            Duration::from_secs(5),
+            SourceAddrs::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            stream.peer_addr().unwrap().ip(),
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        );
+    }
+
+    #[test]
+    fn binds_source_address() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let source = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+        let stream = connect(
+            &[IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            port,
+            Duration::from_secs(5),
+            SourceAddrs {
+                v4: Some(source),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), source);
+    }
+}
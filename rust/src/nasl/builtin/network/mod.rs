@@ -5,13 +5,17 @@
 use std::{fmt::Display, net::IpAddr};
 
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::{Context, FunctionErrorKind};
-use crate::storage::{Field, Retrieve};
+use crate::nasl::utils::{Context, FunctionErrorKind, NaslVarDefiner, NaslVars};
+use crate::storage::{Field, Kb, Retrieve};
 
+pub mod capabilities;
+pub mod happy_eyeballs;
+pub mod netns;
 #[allow(clippy::module_inception)]
 pub mod network;
 pub mod network_utils;
 pub mod socket;
+pub mod websocket;
 
 // 512 Bytes are typically supported by network devices. The ip header maximum size is 60 and a UDP
 // header contains 8 bytes, which must be subtracted from the max size for UDP packages.
@@ -26,6 +30,7 @@ pub fn mtu(_: IpAddr) -> usize {
     MTU
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpenvasEncaps {
     Auto = 0, /* Request auto detection.  */
     Ip,
@@ -59,6 +64,30 @@ impl OpenvasEncaps {
     }
 }
 
+/// Defines the ENCAPS_* constants of the transports.
+pub struct Transports;
+
+impl NaslVarDefiner for Transports {
+    fn nasl_var_define(&self) -> NaslVars<'_> {
+        [
+            ("ENCAPS_AUTO", OpenvasEncaps::Auto),
+            ("ENCAPS_IP", OpenvasEncaps::Ip),
+            ("ENCAPS_SSLv23", OpenvasEncaps::Ssl23),
+            ("ENCAPS_SSLv2", OpenvasEncaps::Ssl2),
+            ("ENCAPS_SSLv3", OpenvasEncaps::Ssl3),
+            ("ENCAPS_TLSv1", OpenvasEncaps::Tls1),
+            ("ENCAPS_TLSv11", OpenvasEncaps::Tls11),
+            ("ENCAPS_TLSv12", OpenvasEncaps::Tls12),
+            ("ENCAPS_TLSv13", OpenvasEncaps::Tls13),
+            ("ENCAPS_TLScustom", OpenvasEncaps::TlsCustom),
+            ("ENCAPS_MAX", OpenvasEncaps::Max),
+        ]
+        .into_iter()
+        .map(|(name, encaps)| (name, NaslValue::Number(encaps as i64)))
+        .collect()
+    }
+}
+
 impl Display for OpenvasEncaps {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -89,6 +118,41 @@ pub fn get_kb_item(context: &Context, name: &str) -> Result<Option<NaslValue>, F
         .map_err(|e| e.into())
 }
 
+/// Returns the KB key of the transport of a TCP port.
+pub fn transport_key(port: u16) -> String {
+    format!("Transports/TCP/{port}")
+}
+
+/// Returns the transport of a TCP port as stored within the KB, `OpenvasEncaps::Ip` when it is
+/// unknown.
+pub fn get_port_transport(
+    context: &Context,
+    port: u16,
+) -> Result<OpenvasEncaps, FunctionErrorKind> {
+    let encaps = match get_kb_item(context, &transport_key(port))? {
+        Some(NaslValue::Number(x)) => OpenvasEncaps::from_i64(x),
+        Some(NaslValue::String(x)) => x.parse().ok().and_then(OpenvasEncaps::from_i64),
+        _ => None,
+    };
+    Ok(match encaps {
+        None | Some(OpenvasEncaps::Auto | OpenvasEncaps::Max) => OpenvasEncaps::Ip,
+        Some(encaps) => encaps,
+    })
+}
+
+/// Stores the transport of a TCP port within the KB, a previously stored one is replaced.
+pub fn set_port_transport(
+    context: &Context,
+    port: u16,
+    encaps: OpenvasEncaps,
+) -> Result<(), FunctionErrorKind> {
+    context.dispatcher().dispatch_replace(
+        context.key(),
+        Field::KB(Kb::from((transport_key(port), encaps as i64))),
+    )?;
+    Ok(())
+}
+
 pub fn verify_port(port: i64) -> Result<u16, FunctionErrorKind> {
     if !(0..=65535).contains(&port) {
         return Err(FunctionErrorKind::WrongArgument(format!(
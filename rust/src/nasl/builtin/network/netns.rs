@@ -0,0 +1,78 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Executes network operations within a named Linux network namespace.
+//!
+//! The namespaces are the ones created via `ip netns add`. Entering a namespace only affects the
+//! calling thread, sockets created while the namespace is entered stay within it after the
+//! thread switched back.
+
+use std::{fs::File, io, os::fd::AsRawFd, path::Path};
+
+/// Directory containing the named network namespaces.
+pub const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+/// Returns true when the name can be used as a namespace name.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+/// Switches the network namespace of the current thread back on drop.
+pub struct NamespaceGuard {
+    original: Option<File>,
+}
+
+impl Drop for NamespaceGuard {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            if let Err(e) = setns(&original) {
+                tracing::warn!(error=%e, "unable to switch back to the original network namespace");
+            }
+        }
+    }
+}
+
+fn setns(file: &File) -> io::Result<()> {
+    match unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Enters the network namespace with the given name for the current thread.
+///
+/// Without a name the current namespace is kept. The original namespace is restored when the
+/// returned guard is dropped, therefore the guard must be dropped on the same thread.
+pub fn enter(name: Option<&str>) -> io::Result<NamespaceGuard> {
+    let Some(name) = name else {
+        return Ok(NamespaceGuard { original: None });
+    };
+    if !is_valid_name(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid network namespace name: {name}"),
+        ));
+    }
+    let original = File::open("/proc/thread-self/ns/net")?;
+    let target = File::open(Path::new(NETNS_RUN_DIR).join(name))?;
+    setns(&target)?;
+    Ok(NamespaceGuard {
+        original: Some(original),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("customer-a"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name(".."));
+        assert!(!is_valid_name("../../proc/1/ns/net"));
+        assert!(enter(Some("../escape")).is_err());
+        assert!(enter(None).is_ok());
+    }
+}
@@ -4,9 +4,12 @@
 
 use std::{net::IpAddr, process::Command};
 
+use super::capabilities::{self, Capability};
 use super::mtu;
 use super::{
-    network_utils::{get_netmask_by_local_ip, get_source_ip, ipstr2ipaddr, islocalhost},
+    network_utils::{
+        get_netmask_by_local_ip, get_source_ip, islocalhost, resolve_ipaddr, source_addrs,
+    },
     verify_port, DEFAULT_PORT,
 };
 use crate::function_set;
@@ -23,11 +26,27 @@ fn get_host_ip(context: &Context) -> String {
 /// Get the IP address of the current (attacking) machine depending on which network device is used
 #[nasl_function]
 fn this_host(context: &Context) -> Result<String, FunctionErrorKind> {
-    let dst = ipstr2ipaddr(context.target())?;
+    let dst = resolve_ipaddr(context, context.target())?;
+    let source = source_addrs(context)?;
+    if let Some(source) = source.for_dst(&dst) {
+        return Ok(source.to_string());
+    }
 
     let port: u16 = DEFAULT_PORT;
+    let _ns = source.enter_namespace()?;
+
+    get_source_ip(source.socket_addr(dst, port)).map(|ip| ip.to_string())
+}
 
-    get_source_ip(dst, port).map(|ip| ip.to_string())
+/// Returns TRUE when the scanner has the given Linux capability.
+///
+/// Supported are `CAP_NET_RAW`, required for raw sockets, and `CAP_NET_BIND_SERVICE`, required
+/// by `open_priv_sock_tcp` and `open_priv_sock_udp`.
+#[nasl_function]
+fn has_capability(name: &str) -> Result<bool, FunctionErrorKind> {
+    Capability::from_name(name)
+        .map(capabilities::has)
+        .ok_or_else(|| FunctionErrorKind::WrongArgument(format!("unknown capability: {name}")))
 }
 
 /// Get the host name of the current (attacking) machine
@@ -43,22 +62,22 @@ fn this_host_name() -> String {
 /// get the maximum transition unit for the scanned host
 #[nasl_function]
 fn get_mtu(context: &Context) -> Result<i64, FunctionErrorKind> {
-    let target = ipstr2ipaddr(context.target())?;
+    let target = resolve_ipaddr(context, context.target())?;
     Ok(mtu(target) as i64)
 }
 
 /// check if the currently scanned host is the localhost
 #[nasl_function]
 fn nasl_islocalhost(context: &Context) -> Result<bool, FunctionErrorKind> {
-    let host_ip = ipstr2ipaddr(context.target())?;
+    let host_ip = resolve_ipaddr(context, context.target())?;
     Ok(islocalhost(host_ip))
 }
 
 /// Check if the target host is on the same network as the attacking host
 #[nasl_function]
 fn islocalnet(context: &Context) -> Result<bool, FunctionErrorKind> {
-    let dst = ipstr2ipaddr(context.target())?;
-    let src = get_source_ip(dst, DEFAULT_PORT)?;
+    let dst = resolve_ipaddr(context, context.target())?;
+    let src = get_source_ip(source_addrs(context)?.socket_addr(dst, DEFAULT_PORT))?;
     let netmask = match get_netmask_by_local_ip(src)? {
         Some(netmask) => netmask,
         None => return Ok(false),
@@ -164,5 +183,6 @@ function_set! {
         this_host_name,
         get_mtu,
         get_host_ip,
+        has_capability,
     )
 }
@@ -4,43 +4,231 @@
 
 //! This module provides utility functions for IP handling.
 use std::{
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    ffi::CStr,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
     ptr,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
 use crate::nasl::prelude::*;
 
-/// Convert a string in a IpAddr
-pub fn ipstr2ipaddr(ip_addr: &str) -> Result<IpAddr, FunctionErrorKind> {
-    match IpAddr::from_str(ip_addr) {
-        Ok(ip) => Ok(ip),
-        Err(_) => Err(FunctionErrorKind::Diagnostic(
-            format!("Invalid IP address ({})", ip_addr),
+use crate::nasl::utils::{zone, ConnectionPermit};
+
+use super::netns;
+
+/// Resolves an IP address or a hostname via the resolver cache of the context
+pub fn resolve_ipaddr(context: &Context, addr: &str) -> Result<IpAddr, FunctionErrorKind> {
+    resolve_ipaddrs(context, addr).map(|x| x[0])
+}
+
+/// Resolves all addresses of an IP address or a hostname via the resolver cache of the context
+pub fn resolve_ipaddrs(context: &Context, addr: &str) -> Result<Vec<IpAddr>, FunctionErrorKind> {
+    context.dns_cache().lookup(addr).ok_or_else(|| {
+        FunctionErrorKind::Diagnostic(
+            format!("Unable to resolve ({})", addr),
             Some(NaslValue::Null),
-        )),
+        )
+    })
+}
+
+/// Source addresses per address family and network namespace of the scan traffic
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceAddrs {
+    /// Source address for IPv4 destinations
+    pub v4: Option<IpAddr>,
+    /// Source address for IPv6 destinations
+    pub v6: Option<IpAddr>,
+    /// Network namespace the sockets are created in
+    pub namespace: Option<Arc<str>>,
+    /// Scope id of link-local destinations, taken from the zone of the target
+    pub scope_id: u32,
+}
+
+impl SourceAddrs {
+    /// Returns the source address matching the family of the destination
+    pub fn for_dst(&self, dst: &IpAddr) -> Option<IpAddr> {
+        match dst {
+            IpAddr::V4(_) => self.v4,
+            IpAddr::V6(_) => self.v6,
+        }
+    }
+
+    /// Returns the socket address of the destination, link-local destinations get the scope id
+    pub fn socket_addr(&self, dst: IpAddr, port: u16) -> SocketAddr {
+        zone::socket_addr(dst, port, self.scope_id)
+    }
+
+    /// Enters the network namespace for the current thread until the guard is dropped
+    pub fn enter_namespace(&self) -> io::Result<netns::NamespaceGuard> {
+        netns::enter(self.namespace.as_deref())
+    }
+
+    fn set_if_missing(&mut self, addr: IpAddr) {
+        let slot = match addr {
+            IpAddr::V4(_) => &mut self.v4,
+            IpAddr::V6(_) => &mut self.v6,
+        };
+        if slot.is_none() {
+            *slot = Some(addr);
+        }
     }
 }
 
-/// Bind a local UDP socket to a V4 or V6 address depending on the given destination address
-pub fn bind_local_socket(dst: &SocketAddr) -> Result<UdpSocket, FunctionErrorKind> {
-    let fe = Err(FunctionErrorKind::Diagnostic(
-        "Error binding".to_string(),
-        None,
-    ));
+/// Returns the source addresses configured for the scan.
+///
+/// The configured source address takes precedence, the addresses of the configured source
+/// interface are used for the remaining family.
+pub fn source_addrs(context: &Context) -> Result<SourceAddrs, FunctionErrorKind> {
+    let source = context.network_source();
+    let mut result = SourceAddrs {
+        namespace: source.namespace.as_deref().map(Arc::from),
+        scope_id: context.target_scope_id(),
+        ..Default::default()
+    };
+    if let Some(addr) = source.address {
+        result.set_if_missing(addr);
+    }
+    if let Some(interface) = &source.interface {
+        let addrs = {
+            let _ns = result.enter_namespace()?;
+            get_interface_addrs(interface)?
+        };
+        if addrs.is_empty() {
+            return Err(FunctionErrorKind::Diagnostic(
+                format!("No address found for source interface {interface}"),
+                None,
+            ));
+        }
+        for addr in addrs {
+            result.set_if_missing(addr);
+        }
+    }
+    Ok(result)
+}
+
+/// Opens a TCP connection to dst from the given source
+pub fn connect_tcp(
+    dst: SocketAddr,
+    source: &SourceAddrs,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    connect_tcp_from_port(dst, source, 0, timeout)
+}
+
+/// Opens a TCP connection to dst from the given source and source port.
+///
+/// A source port of 0 lets the system choose the port.
+pub fn connect_tcp_from_port(
+    dst: SocketAddr,
+    source: &SourceAddrs,
+    sport: u16,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let socket = {
+        let _ns = source.enter_namespace()?;
+        Socket::new(Domain::for_address(dst), Type::STREAM, Some(Protocol::TCP))?
+    };
+    match (source.for_dst(&dst.ip()), sport) {
+        (Some(source), _) => socket.bind(&SockAddr::from(SocketAddr::new(source, sport)))?,
+        (None, 0) => {}
+        (None, _) => socket.bind(&SockAddr::from(SocketAddr::new(unspecified(&dst), sport)))?,
+    }
+    socket.connect_timeout(&SockAddr::from(dst), timeout)?;
+    Ok(socket.into())
+}
+
+/// Time to wait for the connection to a port of the target
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns the timeout in seconds given to a builtin, the default when it is missing or not
+/// positive.
+pub fn timeout_arg(timeout: Option<i64>, default: Duration) -> Duration {
+    timeout
+        .filter(|x| *x > 0)
+        .map(|x| Duration::from_secs(x as u64))
+        .unwrap_or(default)
+}
+
+/// Connects to the port of the target and sets the read and write timeout of the stream.
+///
+/// The connection counts against the open sockets per host of the connection limiter as long as
+/// the returned permit is kept, so it must live as long as the stream. Returns None when the
+/// connection fails.
+pub async fn connect_target(
+    context: &Context<'_>,
+    port: u16,
+    timeout: Duration,
+) -> Result<Option<(TcpStream, ConnectionPermit)>, FunctionErrorKind> {
+    let addr = resolve_ipaddr(context, context.target())?;
+    let source = source_addrs(context)?;
+    let permit = context
+        .connection_limiter()
+        .acquire(context.target())
+        .await?;
+    let stream = connect_tcp(
+        source.socket_addr(addr, port),
+        &source,
+        permit.timeout(CONNECT_TIMEOUT),
+    );
+    permit.record_connect(&stream);
+    let stream = match stream {
+        Ok(x) => x,
+        Err(error) => {
+            tracing::debug!(port, %error, "unable to connect");
+            return Ok(None);
+        }
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(Some((stream, permit)))
+}
+
+fn unspecified(dst: &SocketAddr) -> IpAddr {
     match dst {
-        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0").or(fe),
-        SocketAddr::V6(_) => UdpSocket::bind("[::]:0").or(fe),
+        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
     }
 }
 
-/// Return the source IP address given the destination IP address
-pub fn get_source_ip(dst: IpAddr, port: u16) -> Result<IpAddr, FunctionErrorKind> {
-    let socket = SocketAddr::new(dst, port);
-    let sd = format!("{}:{}", dst, port);
-    let local_socket = bind_local_socket(&socket)?;
+/// Bind a local UDP socket to a V4 or V6 address depending on the given destination address
+pub fn bind_local_socket(dst: &SocketAddr) -> Result<UdpSocket, FunctionErrorKind> {
+    bind_source_socket(dst, &SourceAddrs::default())
+}
+
+/// Bind a local UDP socket to the source address or to the unspecified address of the family of
+/// the given destination address
+pub fn bind_source_socket(
+    dst: &SocketAddr,
+    source: &SourceAddrs,
+) -> Result<UdpSocket, FunctionErrorKind> {
+    bind_source_socket_port(dst, source, 0)
+        .map_err(|_| FunctionErrorKind::Diagnostic("Error binding".to_string(), None))
+}
+
+/// Bind a local UDP socket to the given port of the source address or of the unspecified address
+/// of the family of the given destination address
+pub fn bind_source_socket_port(
+    dst: &SocketAddr,
+    source: &SourceAddrs,
+    port: u16,
+) -> io::Result<UdpSocket> {
+    let _ns = source.enter_namespace()?;
+    let addr = source
+        .for_dst(&dst.ip())
+        .unwrap_or_else(|| unspecified(dst));
+    UdpSocket::bind(SocketAddr::new(addr, port))
+}
+
+/// Return the source IP address given the destination socket address
+pub fn get_source_ip(dst: SocketAddr) -> Result<IpAddr, FunctionErrorKind> {
+    let local_socket = bind_local_socket(&dst)?;
     local_socket
-        .connect(sd)
+        .connect(dst)
         .ok()
         .and_then(|_| local_socket.local_addr().ok())
         .and_then(|l_addr| IpAddr::from_str(&l_addr.ip().to_string()).ok())
@@ -59,6 +247,54 @@ pub fn islocalhost(addr: IpAddr) -> bool {
     get_netmask_by_local_ip(addr).is_ok()
 }
 
+/// Get the addresses of the interface with the given name
+pub fn get_interface_addrs(name: &str) -> Result<Vec<IpAddr>, FunctionErrorKind> {
+    let mut interfaces: *mut libc::ifaddrs = ptr::null_mut();
+
+    let ret = unsafe { libc::getifaddrs(&mut interfaces) };
+
+    if ret < 0 {
+        return Err(FunctionErrorKind::Diagnostic(
+            "Error getting interfaces".to_string(),
+            None,
+        ));
+    }
+
+    let mut result = vec![];
+    let mut interface_iter = interfaces;
+    while !interface_iter.is_null() {
+        let interface = unsafe { &*interface_iter };
+        interface_iter = interface.ifa_next;
+        if interface.ifa_addr.is_null() || interface.ifa_name.is_null() {
+            continue;
+        }
+        // Dereferencing raw pointers is unsafe
+        unsafe {
+            if CStr::from_ptr(interface.ifa_name).to_bytes() != name.as_bytes() {
+                continue;
+            }
+            match (*interface.ifa_addr).sa_family as i32 {
+                libc::AF_INET => {
+                    let addr = &*(interface.ifa_addr as *const libc::sockaddr_in);
+                    result.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        addr.sin_addr.s_addr,
+                    ))));
+                }
+                libc::AF_INET6 => {
+                    let addr = &*(interface.ifa_addr as *const libc::sockaddr_in6);
+                    result.push(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    unsafe {
+        libc::freeifaddrs(interfaces);
+    }
+    Ok(result)
+}
+
 /// Get the interface from the local ip
 pub fn get_netmask_by_local_ip(local_address: IpAddr) -> Result<Option<IpAddr>, FunctionErrorKind> {
     let mut interfaces: *mut libc::ifaddrs = ptr::null_mut();
@@ -131,3 +367,31 @@ pub fn get_netmask_by_local_ip(local_address: IpAddr) -> Result<Option<IpAddr>,
         None,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::nasl::test_prelude::TestBuilder;
+    use crate::nasl::utils::ConnectionLimiter;
+    use crate::nasl::NoOpLoader;
+    use crate::storage::{ContextKey, DefaultDispatcher};
+
+    #[tokio::test]
+    async fn target_stream_keeps_permit() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let t = TestBuilder::<NoOpLoader, DefaultDispatcher>::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        let limiter = Arc::new(ConnectionLimiter::new(0, 1));
+        let context = t.context().with_connection_limiter(limiter.clone());
+        let connection = connect_target(&context, port, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(connection.is_some());
+        assert_eq!(limiter.open_sockets("127.0.0.1"), 1);
+        drop(connection);
+        assert_eq!(limiter.open_sockets("127.0.0.1"), 0);
+    }
+}
@@ -5,7 +5,7 @@
 use std::{
     fs,
     io::{self, BufReader, Read, Write},
-    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    net::{IpAddr, SocketAddr, TcpStream, UdpSocket},
     os::fd::AsRawFd,
     sync::{Arc, RwLock},
     thread::sleep,
@@ -14,24 +14,41 @@ use std::{
 
 use crate::function_set;
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::{error::FunctionErrorKind, Context};
-use dns_lookup::lookup_host;
+use crate::nasl::utils::{
+    error::FunctionErrorKind, lookup_keys::VHOST, zone, ConnectionPermit, Context, ContextType,
+    Register,
+};
+use crate::storage::{types::Primitive, Field, Kb};
 use nasl_function_proc_macro::nasl_function;
 use pkcs8::der::Decode;
 use rustls::{
     pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
-    ClientConfig, ClientConnection, RootCertStore, Stream,
+    ClientConfig, ClientConnection, RootCertStore, Stream, SupportedProtocolVersion,
 };
 
 use super::{
-    get_kb_item, mtu,
-    network_utils::{bind_local_socket, ipstr2ipaddr},
-    verify_port, OpenvasEncaps,
+    get_kb_item, get_port_transport, happy_eyeballs, mtu,
+    network_utils::{
+        bind_source_socket, bind_source_socket_port, connect_tcp_from_port, resolve_ipaddr,
+        resolve_ipaddrs, source_addrs, SourceAddrs,
+    },
+    set_port_transport, verify_port,
+    websocket::{self, Frame, Message, Opcode},
+    OpenvasEncaps,
 };
+use crate::nasl::builtin::http::NoVerifier;
 
 // Number of times to resend a UDP packet, when no response is received
 const NUM_TIMES_TO_RESEND: usize = 5;
 
+// Range of the privileged source ports tried by open_priv_sock_*, starting with the highest
+const PRIV_PORT_MIN: u16 = 512;
+const PRIV_PORT_MAX: u16 = 1023;
+
+// Protocol versions of the TLS encapsulations pinned to a single version
+const TLS12_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS12];
+const TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
 pub struct Interval {
     interval: Duration,
     last_tick: SystemTime,
@@ -59,9 +76,23 @@ struct TCPConnection {
     // Those values are currently unused, but needed for functions currently not implemented
     tls_connection: Option<ClientConnection>,
     _buffer: Option<Vec<u8>>,
+    _permit: ConnectionPermit,
 }
 
+/// A stream data is read from and written to
+trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
+
 impl TCPConnection {
+    /// Returns the stream of the connection, TLS is applied when it is set up.
+    fn stream(&mut self) -> Box<dyn ReadWrite + '_> {
+        match self.tls_connection.as_mut() {
+            Some(tls) => Box::new(Stream::new(tls, &mut self.socket)),
+            None => Box::new(&mut self.socket),
+        }
+    }
+
     /// Send data on a TCP connection using the libc send function.
     /// To ensure safety of the function, the caller must ensure, that the given length does not
     /// exceed the length of the given data data.
@@ -89,6 +120,7 @@ impl TCPConnection {
 struct UDPConnection {
     socket: UdpSocket,
     buffer: Vec<u8>,
+    _permit: ConnectionPermit,
 }
 
 impl UDPConnection {
@@ -145,38 +177,96 @@ pub struct NaslSockets {
 }
 
 impl NaslSockets {
-    fn resolve_socket_addr(addr: IpAddr, port: u16) -> Result<SocketAddr, FunctionErrorKind> {
-        (addr, port)
-            .to_socket_addrs()?
-            .next()
-            .ok_or(FunctionErrorKind::Diagnostic(
-                format!(
-                    "the given address and port do not correspond to a valid address: {addr}:{port}"
-                ),
-                None,
-            ))
+    fn open_udp(
+        addr: IpAddr,
+        port: u16,
+        source: SourceAddrs,
+        permit: ConnectionPermit,
+    ) -> Result<NaslSocket, FunctionErrorKind> {
+        let sock_addr = source.socket_addr(addr, port);
+        let socket = bind_source_socket(&sock_addr, &source)?;
+        Self::udp_connection(socket, sock_addr, permit)
     }
 
-    fn open_udp(addr: IpAddr, port: u16) -> Result<NaslSocket, FunctionErrorKind> {
-        let sock_addr = Self::resolve_socket_addr(addr, port)?;
-        let socket = bind_local_socket(&sock_addr)?;
-        socket.connect(sock_addr)?;
+    fn udp_connection(
+        socket: UdpSocket,
+        dst: SocketAddr,
+        permit: ConnectionPermit,
+    ) -> Result<NaslSocket, FunctionErrorKind> {
+        socket.connect(dst)?;
         socket.set_read_timeout(Some(Duration::from_secs(1)))?;
         Ok(NaslSocket::Udp(UDPConnection {
             socket,
             buffer: vec![],
+            _permit: permit,
         }))
     }
 
+    /// Returns the source ports tried by open_priv_sock_*, either the given one or all
+    /// privileged ports starting with the highest.
+    fn priv_ports(sport: Option<i64>) -> Result<Vec<u16>, FunctionErrorKind> {
+        match sport {
+            Some(sport) => Ok(vec![verify_port(sport)?]),
+            None => Ok((PRIV_PORT_MIN..=PRIV_PORT_MAX).rev().collect()),
+        }
+    }
+
+    /// Opens a socket from the first usable source port.
+    ///
+    /// A connection slot of the target is acquired for each attempt. Ports that are already in
+    /// use are skipped. Returns None when no socket could be opened, e.g. because the scanner
+    /// lacks the privilege to bind to privileged ports.
+    async fn open_priv<F>(context: &Context<'_>, ports: &[u16], mut open: F) -> Option<NaslSocket>
+    where
+        F: FnMut(u16, ConnectionPermit) -> Result<NaslSocket, FunctionErrorKind>,
+    {
+        for sport in ports {
+            let opened = match context.connection_limiter().acquire(context.target()).await {
+                Ok(permit) => open(*sport, permit),
+                Err(e) => Err(e.into()),
+            };
+            match opened {
+                Ok(socket) => return Some(socket),
+                Err(FunctionErrorKind::IOError(
+                    io::ErrorKind::AddrInUse | io::ErrorKind::AddrNotAvailable,
+                )) => {}
+                Err(FunctionErrorKind::IOError(io::ErrorKind::PermissionDenied)) => {
+                    tracing::warn!(
+                        sport,
+                        "unable to bind to a privileged port, CAP_NET_BIND_SERVICE is required"
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    tracing::debug!(sport, error=%e, "unable to open privileged socket");
+                    return None;
+                }
+            }
+        }
+        tracing::debug!("no free privileged source port");
+        None
+    }
+
     fn open_tcp(
-        addr: IpAddr,
+        addrs: &[IpAddr],
         port: u16,
         bufsz: Option<i64>,
         timeout: Duration,
         tls_config: Option<&TLSConfig>,
+        source: SourceAddrs,
+        permit: ConnectionPermit,
+    ) -> Result<NaslSocket, FunctionErrorKind> {
+        let socket = happy_eyeballs::connect(addrs, port, permit.timeout(timeout), source);
+        permit.record_connect(&socket);
+        Self::tcp_connection(socket?, bufsz, tls_config, permit)
+    }
+
+    fn tcp_connection(
+        mut socket: TcpStream,
+        bufsz: Option<i64>,
+        tls_config: Option<&TLSConfig>,
+        permit: ConnectionPermit,
     ) -> Result<NaslSocket, FunctionErrorKind> {
-        // Resolve Address and Port to SocketAddr
-        let sock_addr = Self::resolve_socket_addr(addr, port)?;
         // Create Vec depending of buffer size
         let buffer = if let Some(bufsz) = bufsz {
             if bufsz > 0 {
@@ -188,27 +278,31 @@ impl NaslSockets {
             None
         };
 
-        let socket = TcpStream::connect_timeout(&sock_addr, timeout)?;
-
         // Unwrap, because it cannot fail
-        socket
-            .set_read_timeout(Some(Duration::from_secs(20)))
-            .unwrap();
-        socket
-            .set_write_timeout(Some(Duration::from_secs(20)))
-            .unwrap();
-
-        // Create TLS Connection if requested
+        let timeout = permit.timeout(Duration::from_secs(20));
+        socket.set_read_timeout(Some(timeout)).unwrap();
+        socket.set_write_timeout(Some(timeout)).unwrap();
+
+        // Create TLS Connection if requested, the handshake is completed right away so that a
+        // service not speaking TLS is noticed when opening the socket
         let tls_connection = match tls_config {
-            Some(config) => Some(
-                ClientConnection::new(Arc::new(config.config.clone()), config.server.clone())
-                    .map_err(|e| {
-                        FunctionErrorKind::Diagnostic(
-                            format!("Unable to establish TLS connection: {e}"),
-                            None,
-                        )
-                    })?,
-            ),
+            Some(config) => {
+                let tls_error = |e: &dyn std::fmt::Display| {
+                    FunctionErrorKind::Diagnostic(
+                        format!("Unable to establish TLS connection: {e}"),
+                        None,
+                    )
+                };
+                let mut connection =
+                    ClientConnection::new(Arc::new(config.config.clone()), config.server.clone())
+                        .map_err(|e| tls_error(&e))?;
+                while connection.is_handshaking() {
+                    connection
+                        .complete_io(&mut socket)
+                        .map_err(|e| tls_error(&e))?;
+                }
+                Some(connection)
+            }
             None => None,
         };
 
@@ -216,9 +310,34 @@ impl NaslSockets {
             socket,
             tls_connection,
             _buffer: buffer,
+            _permit: permit,
         })))
     }
 
+    /// Stores the address family of an established connection in the KB when the target is
+    /// reachable via IPv4 and IPv6.
+    fn record_family(
+        context: &Context<'_>,
+        addrs: &[IpAddr],
+        socket: &NaslSocket,
+    ) -> Result<(), FunctionErrorKind> {
+        if !happy_eyeballs::is_dual_stack(addrs) {
+            return Ok(());
+        }
+        if let NaslSocket::Tcp(conn) = socket {
+            let peer = conn.socket.peer_addr()?;
+            context.dispatcher().dispatch(
+                context.key(),
+                Field::KB(Kb {
+                    key: happy_eyeballs::KB_FAMILY.to_owned(),
+                    value: Primitive::String(happy_eyeballs::family(&peer.ip()).to_owned()),
+                    expire: None,
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
     fn add(&self, socket: NaslSocket) -> usize {
         let mut handles = self
             .handles
@@ -235,7 +354,7 @@ impl NaslSockets {
 
     /// Close a given file descriptor taken as an unnamed argument.
     #[nasl_function]
-    fn close(&self, socket_fd: usize) -> Result<NaslValue, FunctionErrorKind> {
+    async fn close(&self, socket_fd: usize) -> Result<NaslValue, FunctionErrorKind> {
         let mut handles = self.handles.write().unwrap();
         match handles.handles.get_mut(socket_fd) {
             Some(NaslSocket::Close) => {
@@ -274,8 +393,9 @@ impl NaslSockets {
     ///
     /// On success the number of sent bytes is returned.
     #[nasl_function(named(socket, data, flags, len))]
-    fn send(
+    async fn send(
         &self,
+        context: &Context<'_>,
         socket: usize,
         data: &[u8],
         flags: Option<i64>,
@@ -293,7 +413,7 @@ impl NaslSockets {
 
         let mut data = &data[0..len];
 
-        match self
+        let sent = match self
             .handles
             .write()
             .unwrap()
@@ -324,15 +444,20 @@ impl NaslSockets {
             NaslSocket::Close => Err(FunctionErrorKind::WrongArgument(
                 "the given socket FD is already closed".to_string(),
             )),
+        }?;
+        if let (NaslValue::Number(n), Some(traffic)) = (&sent, context.traffic()) {
+            traffic.sent((*n).max(0) as usize)?;
         }
+        Ok(sent)
     }
 
+    /// Returns the amount of received bytes
     fn socket_recv<S: Read>(
         socket: &mut S,
         data: &mut [u8],
         len: usize,
         min: usize,
-    ) -> Result<(), FunctionErrorKind> {
+    ) -> Result<usize, FunctionErrorKind> {
         let mut ret = 0;
         while ret < len && ret < min {
             let n = socket.read(&mut data[ret..]).or_else(|e| match e.kind() {
@@ -344,7 +469,7 @@ impl NaslSockets {
             }
             ret += n;
         }
-        Ok(())
+        Ok(ret)
     }
 
     /// Receives data from a TCP or UDP socket. For a UDP socket, if it cannot read data, NASL will
@@ -355,8 +480,9 @@ impl NaslSockets {
     /// - min is the minimum number of data that must be read in case the “magic read function” is activated and the timeout is lowered. By default this is 0. It works together with length. More info https://lists.archive.carbon60.com/nessus/devel/13796
     /// - timeout can be changed from the default.
     #[nasl_function(named(socket, len, min, timeout))]
-    fn recv(
+    async fn recv(
         &self,
+        context: &Context<'_>,
         socket: usize,
         len: usize,
         min: Option<i64>,
@@ -385,12 +511,18 @@ impl NaslSockets {
                     conn.socket
                         .set_read_timeout(Some(Duration::from_secs(timeout as u64)))?;
                 }
-                if let Some(tls) = conn.tls_connection.as_mut() {
+                let received = if let Some(tls) = conn.tls_connection.as_mut() {
                     let mut socket = Stream::new(tls, &mut conn.socket);
-                    Self::socket_recv(&mut socket, &mut data, len, min)?;
+                    Self::socket_recv(&mut socket, &mut data, len, min)?
                 } else {
-                    Self::socket_recv(&mut conn.socket, &mut data, len, min)?;
+                    Self::socket_recv(&mut conn.socket, &mut data, len, min)?
+                };
+                if let Some(traffic) = context.traffic() {
+                    traffic.received(received)?;
                 }
+                context
+                    .connection_limiter()
+                    .record_received(context.target(), &data[..received]);
 
                 if let Some(timeout) = old {
                     conn.socket.set_read_timeout(Some(timeout))?;
@@ -412,7 +544,13 @@ impl NaslSockets {
                     match result {
                         Ok((size, origin)) => {
                             if conn.socket.peer_addr()? == origin {
+                                if let Some(traffic) = context.traffic() {
+                                    traffic.received(size)?;
+                                }
                                 data.truncate(size);
+                                context
+                                    .connection_limiter()
+                                    .record_received(context.target(), &data);
                                 ret = Ok(NaslValue::Data(data));
                                 break;
                             }
@@ -441,12 +579,194 @@ impl NaslSockets {
         }
     }
 
+    /// Calls f with the TCP connection of the socket.
+    fn with_tcp<T>(
+        &self,
+        socket: usize,
+        f: impl FnOnce(&mut TCPConnection) -> Result<T, FunctionErrorKind>,
+    ) -> Result<T, FunctionErrorKind> {
+        match self
+            .handles
+            .write()
+            .unwrap()
+            .handles
+            .get_mut(socket)
+            .ok_or(FunctionErrorKind::WrongArgument(format!(
+                "the given socket FD {socket} does not exist"
+            )))? {
+            NaslSocket::Tcp(conn) => f(conn),
+            NaslSocket::Udp(_) => Err(FunctionErrorKind::WrongArgument(
+                "the given socket FD is no TCP socket".to_string(),
+            )),
+            NaslSocket::Close => Err(FunctionErrorKind::WrongArgument(
+                "the given socket FD is already closed".to_string(),
+            )),
+        }
+    }
+
+    /// Upgrades a TCP or TLS socket to a WebSocket connection.
+    /// Args:
+    /// - socket: the socket returned by an open sock function
+    /// - path: the requested path, `/` by default
+    /// - host: the Host header, the virtual host or the target by default
+    /// - protocol: comma separated subprotocols offered to the server
+    /// - origin: the Origin header, none by default
+    ///
+    /// Returns the subprotocol the server selected, an empty string when it selected none and NULL
+    /// when the server refused the upgrade.
+    #[nasl_function(named(socket, path, host, protocol, origin))]
+    #[allow(clippy::too_many_arguments)]
+    async fn ws_connect(
+        &self,
+        register: &Register,
+        context: &Context<'_>,
+        socket: usize,
+        path: Option<&str>,
+        host: Option<&str>,
+        protocol: Option<&str>,
+        origin: Option<&str>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let protocols: Vec<String> = protocol
+            .unwrap_or_default()
+            .split(',')
+            .map(|x| x.trim().to_owned())
+            .filter(|x| !x.is_empty())
+            .collect();
+        let headers: Vec<_> = origin
+            .map(|x| ("Origin".to_owned(), x.to_owned()))
+            .into_iter()
+            .collect();
+        let key = websocket::handshake_key();
+        let response = self.with_tcp(socket, |conn| {
+            let host = match (host, register.named(VHOST)) {
+                (Some(host), _) => host.to_owned(),
+                (None, Some(ContextType::Value(x))) => x.to_string(),
+                (None, _) => zone::strip(context.target()).to_owned(),
+            };
+            let host = match conn.socket.peer_addr()?.port() {
+                80 | 443 => host,
+                port => format!("{host}:{port}"),
+            };
+            let request = websocket::handshake_request(
+                path.unwrap_or("/"),
+                &host,
+                &key,
+                &protocols,
+                &headers,
+            );
+            let mut stream = conn.stream();
+            stream.write_all(request.as_bytes())?;
+            stream.flush()?;
+            let response = websocket::read_handshake_response(&mut stream)?;
+            if let Some(traffic) = context.traffic() {
+                traffic.sent(request.len())?;
+                traffic.received(response.len())?;
+            }
+            context
+                .connection_limiter()
+                .record_received(context.target(), response.as_bytes());
+            Ok(response)
+        })?;
+        match websocket::verify_handshake(&response, &key, &protocols) {
+            Ok(protocol) => Ok(protocol.unwrap_or_default().into()),
+            Err(e) => Err(FunctionErrorKind::Diagnostic(
+                e.to_string(),
+                Some(NaslValue::Null),
+            )),
+        }
+    }
+
+    /// Sends a message on a socket upgraded by ws_connect.
+    /// Args:
+    /// - socket: the upgraded socket
+    /// - data: the payload
+    /// - opcode: 1 for a text message, which is the default, 2 for a binary message, 8 for a
+    ///   close, 9 for a ping and 10 for a pong
+    ///
+    /// On success the number of sent bytes is returned.
+    #[nasl_function(named(socket, data, opcode))]
+    async fn ws_send(
+        &self,
+        context: &Context<'_>,
+        socket: usize,
+        data: &[u8],
+        opcode: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let opcode = match opcode.unwrap_or(Opcode::Text as i64) {
+            x @ (1 | 2 | 8 | 9 | 10) => Opcode::try_from(x as u8)?,
+            x => {
+                return Err(FunctionErrorKind::wrong_argument(
+                    "opcode",
+                    "1, 2, 8, 9 or 10",
+                    &x.to_string(),
+                ))
+            }
+        };
+        let frame = Frame {
+            fin: true,
+            opcode,
+            payload: data.to_vec(),
+        };
+        self.wait_before_next_probe();
+        let sent = self.with_tcp(socket, |conn| Ok(frame.write(&mut conn.stream())?))?;
+        if let Some(traffic) = context.traffic() {
+            traffic.sent(sent)?;
+        }
+        Ok(NaslValue::Number(sent as i64))
+    }
+
+    /// Receives the next message on a socket upgraded by ws_connect. Pings of the server are
+    /// answered meanwhile.
+    /// Args:
+    /// - socket: the upgraded socket
+    /// - timeout: the seconds to wait for a message, the timeout of the socket by default
+    ///
+    /// Returns a text message as string and a binary message as data. NULL is returned when the
+    /// server closed the connection.
+    #[nasl_function(named(socket, timeout))]
+    async fn ws_recv(
+        &self,
+        context: &Context<'_>,
+        socket: usize,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let (message, received, sent) = self.with_tcp(socket, |conn| {
+            let old = conn.socket.read_timeout()?;
+            if let Some(timeout) = timeout {
+                conn.socket
+                    .set_read_timeout(Some(Duration::from_secs(timeout.max(1) as u64)))?;
+            }
+            let result = websocket::read_message(&mut conn.stream());
+            conn.socket.set_read_timeout(old)?;
+            Ok(result?)
+        })?;
+        if let Some(traffic) = context.traffic() {
+            traffic.sent(sent)?;
+            traffic.received(received)?;
+        }
+        Ok(match message {
+            Message::Text(x) => {
+                context
+                    .connection_limiter()
+                    .record_received(context.target(), &x);
+                NaslValue::String(String::from_utf8_lossy(&x).into_owned().into())
+            }
+            Message::Binary(x) => {
+                context
+                    .connection_limiter()
+                    .record_received(context.target(), &x);
+                NaslValue::Data(x)
+            }
+            Message::Close(_) => NaslValue::Null,
+        })
+    }
+
     /// Open a KDC socket. This function takes no arguments, but it is mandatory that keys are set. The following keys are required:
     /// - Secret/kdc_hostname
     /// - Secret/kdc_port
     /// - Secret/kdc_use_tcp
     #[nasl_function]
-    fn open_sock_kdc(&self, context: &Context) -> Result<NaslValue, FunctionErrorKind> {
+    async fn open_sock_kdc(&self, context: &Context<'_>) -> Result<NaslValue, FunctionErrorKind> {
         let hostname = match get_kb_item(context, "Secret/kdc_hostname")? {
             Some(x) => Ok(x.to_string()),
             None => Err(FunctionErrorKind::Diagnostic(
@@ -455,12 +775,9 @@ impl NaslSockets {
             )),
         }?;
 
-        let ip = lookup_host(&hostname)
-            .map_err(|_| {
-                FunctionErrorKind::Diagnostic(format!("unable to lookup hostname {hostname}"), None)
-            })?
-            .into_iter()
-            .next()
+        let ips = context
+            .dns_cache()
+            .lookup(&hostname)
             .ok_or(FunctionErrorKind::Diagnostic(
                 format!("No IP found for hostname {hostname}"),
                 None,
@@ -493,10 +810,17 @@ impl NaslSockets {
             .map(|x| x.into())
             .unwrap_or(false);
 
+        let source = source_addrs(context)?;
+        let permit = context.connection_limiter().acquire(&hostname).await?;
         let socket = match use_tcp {
-            true => Self::open_tcp(ip, port, None, Duration::from_secs(30), None),
-            false => Self::open_udp(ip, port),
-        }?;
+            true => {
+                let timeout = Duration::from_secs(30);
+                let socket = Self::open_tcp(&ips, port, None, timeout, None, source, permit)?;
+                Self::record_family(context, &ips, &socket)?;
+                socket
+            }
+            false => Self::open_udp(ips[0], port, source, permit)?,
+        };
 
         let ret = self.add(socket);
 
@@ -515,14 +839,17 @@ impl NaslSockets {
     /// - transport: One of the ENCAPS_* constants to force a specific encapsulation mode or force
     ///   trying of all modes (ENCAPS_AUTO). This is for example useful to select a specific TLS or
     ///   SSL version or use specific TLS connection setup priorities.  See *get_port_transport for
-    ///   a description of the ENCAPS constants.
+    ///   a description of the ENCAPS constants. Without it the transport the service detection
+    ///   stored for the port is used, so that the connection is wrapped in TLS for encrypted
+    ///   services.
     /// - priority A string value with priorities for an TLS encapsulation. For the syntax of the
     ///   priority string see the GNUTLS manual. This argument is only used in ENCAPS_TLScustom
     ///   encapsulation.
     #[nasl_function(named(timeout, transport, bufsz))]
-    fn open_sock_tcp(
+    async fn open_sock_tcp(
         &self,
-        context: &Context,
+        register: &Register,
+        context: &Context<'_>,
         port: i64,
         timeout: Option<i64>,
         transport: Option<i64>,
@@ -532,7 +859,6 @@ impl NaslSockets {
     ) -> Result<NaslValue, FunctionErrorKind> {
         // Get port
         let port = verify_port(port)?;
-        let transport = transport.unwrap_or(-1);
 
         let addr = context.target();
         if addr.is_empty() {
@@ -556,58 +882,64 @@ impl NaslSockets {
             Duration::from_secs(10)
         };
 
-        // TODO: for every vhost
-        let vhost = "localhost";
-        if transport < 0 {
-            // TODO: Get port transport and open connection depending on it
-            todo!()
-        } else {
-            match OpenvasEncaps::from_i64(transport) {
-                // Auto Detection
-                Some(OpenvasEncaps::Auto) => {
-                    // Try SSL/TLS first
-                    if let Ok(fd) =
-                        self.open_sock_tcp_tls(context, addr, port, bufsz, timeout, vhost)
-                    {
-                        fds.push(self.add(fd))
-                        // TODO: Set port transport
-                    } else {
-                        // Then try IP
-                        if let Ok(fd) =
-                            self.open_sock_tcp_ip(context, addr, port, bufsz, timeout, None)
-                        {
-                            fds.push(self.add(fd))
-                            // TODO: Set port transport
-                        }
-                    }
-                }
-                // IP
-                Some(OpenvasEncaps::Ip) => {
-                    if let Ok(fd) = self.open_sock_tcp_ip(context, addr, port, bufsz, timeout, None)
-                    {
-                        fds.push(self.add(fd))
-                        // TODO: Set port transport
-                    }
+        // the virtual host of the instance is used as TLS server name
+        let hostname = match register.named(VHOST) {
+            Some(ContextType::Value(x)) => x.to_string(),
+            _ => zone::strip(addr).to_owned(),
+        };
+        // a negative transport uses the one known for the port
+        let transport = match transport {
+            Some(x) if x >= 0 => x,
+            _ => get_port_transport(context, port)? as i64,
+        };
+        match OpenvasEncaps::from_i64(transport) {
+            // Auto Detection
+            Some(OpenvasEncaps::Auto) => {
+                // Try SSL/TLS first, then IP
+                let socket = match self
+                    .open_sock_tcp_tls(
+                        context,
+                        addr,
+                        port,
+                        bufsz,
+                        timeout,
+                        &hostname,
+                        OpenvasEncaps::Ssl23,
+                    )
+                    .await
+                {
+                    Ok(fd) => Ok((fd, OpenvasEncaps::Ssl23)),
+                    Err(_) => self
+                        .open_sock_tcp_ip(context, addr, port, bufsz, timeout, None)
+                        .await
+                        .map(|fd| (fd, OpenvasEncaps::Ip)),
+                };
+                if let Ok((fd, encaps)) = socket {
+                    set_port_transport(context, port, encaps)?;
+                    fds.push(self.add(fd))
                 }
-                // Unsupported transport layer
-                None | Some(OpenvasEncaps::Max) => {
-                    return Err(FunctionErrorKind::WrongArgument(format!(
-                        "unsupported transport layer: {transport}(unknown)"
-                    )))
+            }
+            // IP
+            Some(OpenvasEncaps::Ip) => {
+                if let Ok(fd) = self
+                    .open_sock_tcp_ip(context, addr, port, bufsz, timeout, None)
+                    .await
+                {
+                    fds.push(self.add(fd))
                 }
-                // TLS/SSL
-                Some(tls_version) => match tls_version {
-                    OpenvasEncaps::Tls12 | OpenvasEncaps::Tls13 => {
-                        let fd =
-                            self.open_sock_tcp_tls(context, addr, port, bufsz, timeout, vhost)?;
-                        fds.push(self.add(fd))
-                    }
-                    _ => {
-                        return Err(FunctionErrorKind::WrongArgument(format!(
-                            "unsupported transport layer: {transport}{tls_version}"
-                        )))
-                    }
-                },
+            }
+            // Unsupported transport layer
+            None | Some(OpenvasEncaps::Max) => {
+                return Err(FunctionErrorKind::WrongArgument(format!(
+                    "unsupported transport layer: {transport}(unknown)"
+                )))
+            }
+            // TLS/SSL
+            Some(encaps) => {
+                let fd = self
+                    .open_sock_tcp_tls(context, addr, port, bufsz, timeout, &hostname, encaps)
+                    .await?;
+                fds.push(self.add(fd))
             }
         }
 
@@ -618,16 +950,17 @@ impl NaslSockets {
         ))
     }
 
-    fn open_sock_tcp_ip(
+    async fn open_sock_tcp_ip(
         &self,
-        context: &Context,
+        context: &Context<'_>,
         addr: &str,
         port: u16,
         bufsz: Option<i64>,
         timeout: Duration,
         tls_config: Option<TLSConfig>,
     ) -> Result<NaslSocket, FunctionErrorKind> {
-        let addr = ipstr2ipaddr(addr)?;
+        let addrs = resolve_ipaddrs(context, addr)?;
+        let source = source_addrs(context)?;
         let mut retry = super::get_kb_item(context, "timeout_retry")?
             .map(|val| match val {
                 NaslValue::String(val) => val.parse::<i64>().unwrap_or_default(),
@@ -637,8 +970,14 @@ impl NaslSockets {
             .unwrap_or(2);
 
         while retry >= 0 {
-            match Self::open_tcp(addr, port, bufsz, timeout, tls_config.as_ref()) {
-                Ok(socket) => return Ok(socket),
+            let permit = context.connection_limiter().acquire(addr).await?;
+            let tls_config = tls_config.as_ref();
+            let source = source.clone();
+            match Self::open_tcp(&addrs, port, bufsz, timeout, tls_config, source, permit) {
+                Ok(socket) => {
+                    Self::record_family(context, &addrs, &socket)?;
+                    return Ok(socket);
+                }
                 Err(err) => {
                     if !matches!(err, FunctionErrorKind::IOError(io::ErrorKind::TimedOut)) {
                         return Err(err);
@@ -678,80 +1017,102 @@ impl NaslSockets {
         ))
     }
 
-    fn open_sock_tcp_tls(
+    /// Returns the protocol versions of a TLS encapsulation, None for the ones not supported.
+    fn tls_versions(encaps: OpenvasEncaps) -> Option<&'static [&'static SupportedProtocolVersion]> {
+        match encaps {
+            OpenvasEncaps::Tls12 => Some(TLS12_ONLY),
+            OpenvasEncaps::Tls13 => Some(TLS13_ONLY),
+            OpenvasEncaps::Ssl23 | OpenvasEncaps::TlsCustom => Some(rustls::ALL_VERSIONS),
+            _ => None,
+        }
+    }
+
+    /// Opens a TCP connection wrapped in TLS.
+    ///
+    /// The client certificate `SSL/cert` and its key `SSL/key` are only presented when both are
+    /// set. The certificate of the server is verified against `SSL/CA` when it is set, otherwise it
+    /// is accepted as is, like the C scanner does.
+    #[allow(clippy::too_many_arguments)]
+    async fn open_sock_tcp_tls(
         &self,
-        context: &Context,
+        context: &Context<'_>,
         addr: &str,
         port: u16,
         bufsz: Option<i64>,
         timeout: Duration,
         hostname: &str,
+        encaps: OpenvasEncaps,
     ) -> Result<NaslSocket, FunctionErrorKind> {
-        let cert_path = get_kb_item(context, "SSL/cert")?
-            .ok_or(FunctionErrorKind::Diagnostic(
-                "unable to open TLS connection: kes 'SSL/cert' is missing".to_string(),
-                None,
-            ))?
-            .to_string();
-        let key_path = get_kb_item(context, "SSL/key")?
-            .ok_or(FunctionErrorKind::Diagnostic(
-                "unable to open TLS connection: kes 'SSL/key' is missing".to_string(),
-                None,
-            ))?
-            .to_string();
+        let versions = Self::tls_versions(encaps).ok_or_else(|| {
+            FunctionErrorKind::WrongArgument(format!(
+                "unsupported transport layer: {}({encaps})",
+                encaps as i64
+            ))
+        })?;
+        let cert_path = get_kb_item(context, "SSL/cert")?.map(|x| x.to_string());
+        let key_path = get_kb_item(context, "SSL/key")?.map(|x| x.to_string());
         let password = get_kb_item(context, "SSL/password")?
             .unwrap_or(NaslValue::Null)
             .to_string();
-        let cafile_path = get_kb_item(context, "SSL/CA")?
-            .ok_or(FunctionErrorKind::Diagnostic(
-                "unable to open TLS connection: kes 'SSL/CA' is missing".to_string(),
-                None,
-            ))?
-            .to_string();
+        let cafile_path = get_kb_item(context, "SSL/CA")?.map(|x| x.to_string());
 
-        // TODO: From vhost name
         let server = ServerName::try_from(hostname.to_owned()).map_err(|_| {
             FunctionErrorKind::Dirty(format!("Given vHost Name {hostname} is not valid"))
         })?;
 
-        let mut root_store = RootCertStore::empty();
-        let ca_file = fs::File::open(cafile_path)?;
-        let mut reader = BufReader::new(ca_file);
-        root_store.add_parsable_certificates(
-            rustls_pemfile::certs(&mut reader).map(|result| result.unwrap()),
-        );
-
-        let cert_file = fs::File::open(cert_path)?;
-        let mut reader = BufReader::new(cert_file);
-        let cert = rustls_pemfile::certs(&mut reader)
-            .map(|result| result.unwrap())
-            .collect();
-
-        let mut key = Self::load_private_key(&key_path)?;
+        let builder = ClientConfig::builder_with_protocol_versions(versions);
+        let builder = match cafile_path {
+            Some(cafile_path) => {
+                let mut root_store = RootCertStore::empty();
+                let ca_file = fs::File::open(cafile_path)?;
+                let mut reader = BufReader::new(ca_file);
+                root_store.add_parsable_certificates(
+                    rustls_pemfile::certs(&mut reader).map(|result| result.unwrap()),
+                );
+                builder.with_root_certificates(root_store)
+            }
+            None => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerifier)),
+        };
 
-        if !password.is_empty() {
-            let encrypted_key = pkcs8::EncryptedPrivateKeyInfo::from_der(key.secret_der())
-                .map_err(|_| {
-                    FunctionErrorKind::Diagnostic(
-                        format!("Unable to decrypt private key {key_path} with given password"),
-                        None,
-                    )
-                })?;
-            let decrypted_key = encrypted_key.decrypt(password).map_err(|_| {
-                FunctionErrorKind::Diagnostic(
-                    format!("Unable to decrypt private key {key_path} with given password"),
-                    None,
-                )
-            })?;
+        let config = match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let cert_file = fs::File::open(cert_path)?;
+                let mut reader = BufReader::new(cert_file);
+                let cert = rustls_pemfile::certs(&mut reader)
+                    .map(|result| result.unwrap())
+                    .collect();
+
+                let mut key = Self::load_private_key(&key_path)?;
+
+                if !password.is_empty() {
+                    let encrypted_key = pkcs8::EncryptedPrivateKeyInfo::from_der(key.secret_der())
+                        .map_err(|_| {
+                            FunctionErrorKind::Diagnostic(
+                                format!(
+                                    "Unable to decrypt private key {key_path} with given password"
+                                ),
+                                None,
+                            )
+                        })?;
+                    let decrypted_key = encrypted_key.decrypt(password).map_err(|_| {
+                        FunctionErrorKind::Diagnostic(
+                            format!("Unable to decrypt private key {key_path} with given password"),
+                            None,
+                        )
+                    })?;
 
-            key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
-                decrypted_key.as_bytes().to_owned(),
-            ));
-        }
-        let config = ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_client_auth_cert(cert, key)
-            .map_err(|_| FunctionErrorKind::WrongArgument("Invalid Key".to_string()))?;
+                    key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                        decrypted_key.as_bytes().to_owned(),
+                    ));
+                }
+                builder
+                    .with_client_auth_cert(cert, key)
+                    .map_err(|_| FunctionErrorKind::WrongArgument("Invalid Key".to_string()))?
+            }
+            _ => builder.with_no_client_auth(),
+        };
 
         self.open_sock_tcp_ip(
             context,
@@ -761,15 +1122,107 @@ impl NaslSockets {
             timeout,
             Some(TLSConfig { config, server }),
         )
+        .await
+    }
+
+    /// *int* **get_port_transport**(*int* port, *bool* asstring);
+    ///
+    /// Returns the transport of a TCP port as one of the ENCAPS_* constants, ENCAPS_IP when the
+    /// port is not encrypted or unknown. With `asstring` set its name is returned instead.
+    #[nasl_function(named(asstring))]
+    async fn get_port_transport(
+        &self,
+        context: &Context<'_>,
+        port: i64,
+        asstring: Option<bool>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let encaps = get_port_transport(context, verify_port(port)?)?;
+        Ok(match asstring {
+            Some(true) => NaslValue::String(encaps.to_string().into()),
+            _ => NaslValue::Number(encaps as i64),
+        })
+    }
+
+    /// Open a TCP socket from a privileged source port to the target host.
+    ///
+    /// - dport: the destination port
+    /// - sport: the source port, by default the privileged ports are tried starting from 1023
+    /// - timeout: the connection timeout in seconds
+    ///
+    /// Returns NULL when the connection cannot be established or the scanner is not allowed to
+    /// bind privileged ports, see `has_capability`.
+    #[nasl_function(named(dport, sport, timeout))]
+    async fn open_priv_sock_tcp(
+        &self,
+        context: &Context<'_>,
+        dport: i64,
+        sport: Option<i64>,
+        timeout: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let source = source_addrs(context)?;
+        let dst = source.socket_addr(
+            resolve_ipaddr(context, context.target())?,
+            verify_port(dport)?,
+        );
+        let ports = Self::priv_ports(sport)?;
+        let timeout = match timeout {
+            Some(sec) if sec > 0 => Duration::from_secs(sec as u64),
+            _ => Duration::from_secs(10),
+        };
+
+        self.wait_before_next_probe();
+        let socket = Self::open_priv(context, &ports, |sport, permit| {
+            let socket = connect_tcp_from_port(dst, &source, sport, permit.timeout(timeout));
+            permit.record_connect(&socket);
+            Self::tcp_connection(socket?, None, None, permit)
+        })
+        .await;
+        Ok(socket.map_or(NaslValue::Null, |x| NaslValue::Number(self.add(x) as i64)))
+    }
+
+    /// Open a UDP socket from a privileged source port to the target host.
+    ///
+    /// - dport: the destination port
+    /// - sport: the source port, by default the privileged ports are tried starting from 1023
+    ///
+    /// Returns NULL when the scanner is not allowed to bind privileged ports, see `has_capability`.
+    #[nasl_function(named(dport, sport))]
+    async fn open_priv_sock_udp(
+        &self,
+        context: &Context<'_>,
+        dport: i64,
+        sport: Option<i64>,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let source = source_addrs(context)?;
+        let dst = source.socket_addr(
+            resolve_ipaddr(context, context.target())?,
+            verify_port(dport)?,
+        );
+        let ports = Self::priv_ports(sport)?;
+
+        let socket = Self::open_priv(context, &ports, |sport, permit| {
+            let socket = bind_source_socket_port(&dst, &source, sport)?;
+            Self::udp_connection(socket, dst, permit)
+        })
+        .await;
+        Ok(socket.map_or(NaslValue::Null, |x| NaslValue::Number(self.add(x) as i64)))
     }
 
     /// Open a UDP socket to the target host
     #[nasl_function]
-    fn open_sock_udp(&self, context: &Context, port: i64) -> Result<NaslValue, FunctionErrorKind> {
+    async fn open_sock_udp(
+        &self,
+        context: &Context<'_>,
+        port: i64,
+    ) -> Result<NaslValue, FunctionErrorKind> {
         let port = verify_port(port)?;
-        let addr = ipstr2ipaddr(context.target())?;
+        let addr = resolve_ipaddr(context, context.target())?;
 
-        let socket = Self::open_udp(addr, port)?;
+        let permit = context
+            .connection_limiter()
+            .acquire(context.target())
+            .await?;
+        let socket = Self::open_udp(addr, port, source_addrs(context)?, permit)?;
         let fd = self.add(socket);
 
         Ok(NaslValue::Number(fd as i64))
@@ -778,13 +1231,133 @@ impl NaslSockets {
 
 function_set! {
     NaslSockets,
-    sync_stateful,
+    async_stateful,
     (
         (NaslSockets::open_sock_kdc, "open_sock_kdc"),
         (NaslSockets::open_sock_tcp, "open_sock_tcp"),
         (NaslSockets::open_sock_udp, "open_sock_udp"),
+        (NaslSockets::open_priv_sock_tcp, "open_priv_sock_tcp"),
+        (NaslSockets::open_priv_sock_udp, "open_priv_sock_udp"),
+        (NaslSockets::get_port_transport, "get_port_transport"),
         (NaslSockets::close, "close"),
         (NaslSockets::send, "send"),
         (NaslSockets::recv, "recv"),
+        (NaslSockets::ws_connect, "ws_connect"),
+        (NaslSockets::ws_send, "ws_send"),
+        (NaslSockets::ws_recv, "ws_recv"),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::super::websocket::{self, Frame, Opcode};
+    use crate::nasl::{test_prelude::*, NoOpLoader};
+    use crate::storage::{ContextKey, DefaultDispatcher};
+
+    fn builder() -> TestBuilder<NoOpLoader, DefaultDispatcher> {
+        TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())))
+    }
+
+    /// Echoes everything received on each accepted connection.
+    fn echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buffer = [0; 1024];
+                while let Ok(n) = stream.read(&mut buffer) {
+                    if n == 0 || stream.write_all(&buffer[..n]).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn port_transport() {
+        let mut t = builder();
+        t.ok(
+            r#"set_kb_item(name: "Transports/TCP/443", value: 7);"#,
+            NaslValue::Null,
+        );
+        t.ok("get_port_transport(443);", 7);
+        t.ok("get_port_transport(443, asstring: TRUE);", "TLS 1.2");
+        t.ok("get_port_transport(80);", 1);
+        t.ok("get_port_transport(80, asstring: TRUE);", "None");
+    }
+
+    #[test]
+    fn auto_transport_falls_back_to_ip() {
+        let port = echo_server();
+        let mut t = builder();
+        t.ok(&format!("soc = open_sock_tcp({port}, transport: 0);"), 0);
+        t.ok(&format!("get_port_transport({port});"), 1);
+        t.ok(r#"send(socket: soc, data: "ping");"#, 4);
+        t.ok(
+            "recv(socket: soc, len: 4);",
+            NaslValue::Data(b"ping".to_vec()),
+        );
+        t.ok(&format!("soc = open_sock_tcp({port});"), 1);
+    }
+
+    #[test]
+    fn websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = websocket::read_handshake_response(&mut stream).unwrap();
+            let key = request
+                .lines()
+                .find_map(|x| x.strip_prefix("Sec-WebSocket-Key: "))
+                .unwrap();
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\
+                 Sec-WebSocket-Protocol: chat\r\n\r\n",
+                websocket::accept_key(key)
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            let frame = |opcode, payload: &[u8]| Frame {
+                fin: true,
+                opcode,
+                payload: payload.to_vec(),
+            };
+            frame(Opcode::Ping, b"").write(&mut stream).unwrap();
+            frame(Opcode::Text, b"hello").write(&mut stream).unwrap();
+            // echoes the first text message as binary message and closes
+            loop {
+                let received = Frame::read(&mut stream).unwrap();
+                if received.opcode == Opcode::Text {
+                    frame(Opcode::Binary, &received.payload)
+                        .write(&mut stream)
+                        .unwrap();
+                    frame(Opcode::Close, &1000u16.to_be_bytes())
+                        .write(&mut stream)
+                        .unwrap();
+                    break;
+                }
+            }
+        });
+
+        let mut t = builder();
+        t.ok(&format!("soc = open_sock_tcp({port});"), 0);
+        t.ok(
+            r#"ws_connect(socket: soc, protocol: "superchat, chat");"#,
+            "chat",
+        );
+        t.ok("ws_recv(socket: soc);", "hello");
+        t.ok(r#"ws_send(socket: soc, data: "hi");"#, 8);
+        t.ok("ws_recv(socket: soc);", NaslValue::Data(b"hi".to_vec()));
+        t.ok("ws_recv(socket: soc);", NaslValue::Null);
+    }
+}
@@ -0,0 +1,406 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Implements the client side of the WebSocket protocol (RFC 6455) on top of the NASL sockets.
+//!
+//! `ws_connect` upgrades an open TCP or TLS socket by the opening handshake, `ws_send` and
+//! `ws_recv` exchange messages afterwards. Pings of the server are answered while receiving, a
+//! close frame is confirmed and ends the receiving.
+
+use std::io::{self, Read, Write};
+
+use base64::{engine::general_purpose, Engine};
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+
+/// Appended to the key of the client to calculate the accept value of the server
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The opening handshake response is not read beyond this size
+const MAX_HANDSHAKE: usize = 16 * 1024;
+/// Messages larger than this are rejected
+pub const MAX_MESSAGE: u64 = 16 * 1024 * 1024;
+
+/// Opcodes of the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xA,
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = io::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            x => return Err(invalid(format!("unknown websocket opcode {x}"))),
+        })
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A single frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Encodes the frame, frames of a client are always masked.
+    pub fn encode(&self, mask: [u8; 4]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(self.payload.len() + 14);
+        frame.push((self.fin as u8) << 7 | self.opcode as u8);
+        match self.payload.len() {
+            n if n < 126 => frame.push(0x80 | n as u8),
+            n if n <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            self.payload
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    /// Writes the frame masked with a random key.
+    pub fn write<W: Write>(&self, stream: &mut W) -> io::Result<usize> {
+        let mut mask = [0; 4];
+        rand::thread_rng().fill_bytes(&mut mask);
+        let frame = self.encode(mask);
+        stream.write_all(&frame)?;
+        stream.flush()?;
+        Ok(frame.len())
+    }
+
+    /// Reads a frame, frames of a server may be masked.
+    pub fn read<R: Read>(stream: &mut R) -> io::Result<Self> {
+        let mut head = [0; 2];
+        stream.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = Opcode::try_from(head[0] & 0x0F)?;
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                stream.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0; 8];
+                stream.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            n => n as u64,
+        };
+        if len > MAX_MESSAGE {
+            return Err(invalid(format!(
+                "websocket frame of {len} bytes is too large"
+            )));
+        }
+        let mask = if head[1] & 0x80 != 0 {
+            let mut mask = [0; 4];
+            stream.read_exact(&mut mask)?;
+            Some(mask)
+        } else {
+            None
+        };
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload)?;
+        if let Some(mask) = mask {
+            payload
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, x)| *x ^= mask[i % 4]);
+        }
+        Ok(Self {
+            fin,
+            opcode,
+            payload,
+        })
+    }
+}
+
+/// A received message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(Vec<u8>),
+    Binary(Vec<u8>),
+    /// The server closed the connection with the contained status code
+    Close(Option<u16>),
+}
+
+/// Reads the next message, pings are answered and pongs are skipped.
+///
+/// Returns the message and the amount of bytes received and sent.
+pub fn read_message<S: Read + Write>(stream: &mut S) -> io::Result<(Message, usize, usize)> {
+    let (mut received, mut sent) = (0, 0);
+    let mut message: Option<(Opcode, Vec<u8>)> = None;
+    loop {
+        let frame = Frame::read(stream)?;
+        received += frame.payload.len() + 2;
+        match frame.opcode {
+            Opcode::Ping => {
+                sent += Frame {
+                    fin: true,
+                    opcode: Opcode::Pong,
+                    payload: frame.payload,
+                }
+                .write(stream)?;
+                continue;
+            }
+            Opcode::Pong => continue,
+            Opcode::Close => {
+                let code = (frame.payload.len() >= 2)
+                    .then(|| u16::from_be_bytes([frame.payload[0], frame.payload[1]]));
+                // confirms the close, the server closes the connection afterwards
+                sent += Frame {
+                    fin: true,
+                    opcode: Opcode::Close,
+                    payload: frame.payload.get(..2).unwrap_or_default().to_vec(),
+                }
+                .write(stream)
+                .unwrap_or_default();
+                return Ok((Message::Close(code), received, sent));
+            }
+            Opcode::Text | Opcode::Binary if message.is_none() => {
+                message = Some((frame.opcode, frame.payload));
+            }
+            Opcode::Continuation if message.is_some() => {
+                let (_, payload) = message.as_mut().expect("checked before");
+                if (payload.len() + frame.payload.len()) as u64 > MAX_MESSAGE {
+                    return Err(invalid("websocket message is too large"));
+                }
+                payload.extend(frame.payload);
+            }
+            opcode => return Err(invalid(format!("unexpected websocket frame {opcode:?}"))),
+        }
+        if frame.fin {
+            match message.take() {
+                Some((Opcode::Text, payload)) => {
+                    return Ok((Message::Text(payload), received, sent))
+                }
+                Some((_, payload)) => return Ok((Message::Binary(payload), received, sent)),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Returns a random key of the opening handshake.
+pub fn handshake_key() -> String {
+    let mut key = [0; 16];
+    rand::thread_rng().fill_bytes(&mut key);
+    general_purpose::STANDARD.encode(key)
+}
+
+/// Returns the accept value the server answers the key with.
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(GUID.as_bytes());
+    general_purpose::STANDARD.encode(sha1.finalize())
+}
+
+/// Returns the opening handshake request.
+pub fn handshake_request(
+    path: &str,
+    host: &str,
+    key: &str,
+    protocols: &[String],
+    headers: &[(String, String)],
+) -> String {
+    let mut request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n"
+    );
+    if !protocols.is_empty() {
+        request.push_str(&format!(
+            "Sec-WebSocket-Protocol: {}\r\n",
+            protocols.join(", ")
+        ));
+    }
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// Reads the handshake response up to the empty line, the frames following it are not consumed.
+pub fn read_handshake_response<R: Read>(stream: &mut R) -> io::Result<String> {
+    let mut response = vec![];
+    let mut byte = [0; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HANDSHAKE {
+            return Err(invalid("websocket handshake response is too large"));
+        }
+        match stream.read(&mut byte)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => response.push(byte[0]),
+        }
+    }
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Verifies the handshake response and returns the subprotocol the server selected.
+pub fn verify_handshake(
+    response: &str,
+    key: &str,
+    protocols: &[String],
+) -> io::Result<Option<String>> {
+    let mut lines = response.lines();
+    let status = lines.next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(invalid(format!("websocket upgrade rejected: {status}")));
+    }
+    let header = |name: &str| {
+        response
+            .lines()
+            .skip(1)
+            .filter_map(|x| x.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim().to_owned())
+    };
+    if header("Sec-WebSocket-Accept").as_deref() != Some(accept_key(key).as_str()) {
+        return Err(invalid("invalid Sec-WebSocket-Accept"));
+    }
+    let protocol = header("Sec-WebSocket-Protocol");
+    if let Some(protocol) = &protocol {
+        if !protocols.contains(protocol) {
+            return Err(invalid(format!(
+                "server selected unoffered subprotocol {protocol}"
+            )));
+        }
+    }
+    Ok(protocol)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn accept() {
+        // the example of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        let response = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+            Sec-WebSocket-Protocol: chat\r\n\r\n";
+        let protocols = vec!["superchat".to_string(), "chat".to_string()];
+        assert_eq!(
+            verify_handshake(response, "dGhlIHNhbXBsZSBub25jZQ==", &protocols).unwrap(),
+            Some("chat".into())
+        );
+        assert!(verify_handshake(response, "dGhlIHNhbXBsZSBub25jZQ==", &[]).is_err());
+        assert!(verify_handshake(response, "other", &protocols).is_err());
+        assert!(verify_handshake("HTTP/1.1 200 OK\r\n\r\n", "x", &[]).is_err());
+    }
+
+    #[test]
+    fn frames() {
+        let frame = Frame {
+            fin: true,
+            opcode: Opcode::Text,
+            payload: b"Hello".to_vec(),
+        };
+        // the masked example of RFC 6455
+        let encoded = frame.encode([0x37, 0xfa, 0x21, 0x3d]);
+        assert_eq!(
+            encoded,
+            [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+        );
+        assert_eq!(Frame::read(&mut Cursor::new(encoded)).unwrap(), frame);
+
+        let long = Frame {
+            fin: false,
+            opcode: Opcode::Binary,
+            payload: vec![7; 70000],
+        };
+        assert_eq!(
+            Frame::read(&mut Cursor::new(long.encode([1, 2, 3, 4]))).unwrap(),
+            long
+        );
+    }
+
+    /// A stream reading from input and writing into output
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn messages() {
+        // a ping between two fragments of a text message and a close
+        let input = [
+            &[0x01, 0x03][..],
+            b"Hel",
+            &[0x89, 0x01],
+            b"p",
+            &[0x80, 0x02],
+            b"lo",
+            &[0x88, 0x02, 0x03, 0xe8],
+        ]
+        .concat();
+        let mut stream = Duplex {
+            input: Cursor::new(input),
+            output: vec![],
+        };
+        let (message, _, sent) = read_message(&mut stream).unwrap();
+        assert_eq!(message, Message::Text(b"Hello".to_vec()));
+        assert_eq!(
+            Frame::read(&mut Cursor::new(&stream.output)).unwrap(),
+            Frame {
+                fin: true,
+                opcode: Opcode::Pong,
+                payload: b"p".to_vec()
+            }
+        );
+        assert_eq!(sent, stream.output.len());
+        let (message, _, _) = read_message(&mut stream).unwrap();
+        assert_eq!(message, Message::Close(Some(1000)));
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! BACnet/IP ReadProperty requests of the properties of the device object.
+
+/// The device object with the wildcard instance, answered by every device
+const WILDCARD_DEVICE: u32 = (8 << 22) | 0x3f_ffff;
+const SERVICE_READ_PROPERTY: u8 = 0x0c;
+const PDU_COMPLEX_ACK: u8 = 0x3;
+
+/// The properties read and the names they are returned with
+pub const PROPERTIES: [(u8, &str); 9] = [
+    (75, "instance"),
+    (120, "vendor_id"),
+    (121, "vendor_name"),
+    (70, "model_name"),
+    (44, "firmware_revision"),
+    (12, "application_software_version"),
+    (77, "object_name"),
+    (28, "description"),
+    (58, "location"),
+];
+
+/// A decoded property value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(u64),
+    Text(String),
+}
+
+/// Returns a ReadProperty request of the property of the device object.
+pub fn read_property(invoke_id: u8, property: u8) -> Vec<u8> {
+    // BVLC Original-Unicast-NPDU and an NPDU expecting a reply
+    let mut request = vec![0x81, 0x0a, 0, 17, 0x01, 0x04];
+    // a confirmed request accepting segmented responses up to 1476 bytes
+    request.extend_from_slice(&[0x00, 0x05, invoke_id, SERVICE_READ_PROPERTY]);
+    request.push(0x0c);
+    request.extend_from_slice(&WILDCARD_DEVICE.to_be_bytes());
+    request.extend_from_slice(&[0x19, property]);
+    request
+}
+
+/// Parses the ComplexACK of a ReadProperty request, None for errors, rejects and anything else.
+pub fn parse_response(data: &[u8], invoke_id: u8) -> Option<Value> {
+    if data.len() < 4 || data[0] != 0x81 {
+        return None;
+    }
+    let npdu = &data[4..];
+    let control = *npdu.get(1)?;
+    let mut pos = 2;
+    // the destination specifier
+    if control & 0x20 != 0 {
+        pos += 3 + *npdu.get(pos + 2)? as usize;
+    }
+    // the source specifier
+    if control & 0x08 != 0 {
+        pos += 3 + *npdu.get(pos + 2)? as usize;
+    }
+    // the hop count
+    if control & 0x20 != 0 {
+        pos += 1;
+    }
+    let apdu = npdu.get(pos..)?;
+    if apdu.len() < 3
+        || apdu[0] >> 4 != PDU_COMPLEX_ACK
+        || apdu[1] != invoke_id
+        || apdu[2] != SERVICE_READ_PROPERTY
+    {
+        return None;
+    }
+    // the context tags of the object id, the property and the optional array index
+    let mut pos = 3;
+    while let Some(tag) = apdu.get(pos).filter(|x| *x & 0x0f != 0x0e) {
+        if tag & 0x08 == 0 || tag & 0x07 > 4 {
+            return None;
+        }
+        pos += 1 + (tag & 0x07) as usize;
+    }
+    // the value follows the opening tag 3
+    if apdu.get(pos) != Some(&0x3e) {
+        return None;
+    }
+    decode(apdu.get(pos + 1..)?)
+}
+
+/// Decodes the first application tagged value.
+fn decode(data: &[u8]) -> Option<Value> {
+    let tag = *data.first()?;
+    // context specific tags are not decoded
+    if tag & 0x08 != 0 {
+        return None;
+    }
+    let (len, start) = match tag & 0x07 {
+        5 => match *data.get(1)? {
+            254 => (
+                u16::from_be_bytes([*data.get(2)?, *data.get(3)?]) as usize,
+                4,
+            ),
+            255 => return None,
+            x => (x as usize, 2),
+        },
+        x => (x as usize, 1),
+    };
+    let value = data.get(start..start + len)?;
+    match tag >> 4 {
+        // unsigned and enumerated
+        2 | 9 if len <= 8 => Some(Value::Number(
+            value.iter().fold(0, |acc, x| (acc << 8) | *x as u64),
+        )),
+        // character string with its encoding
+        7 => Some(Value::Text(
+            String::from_utf8_lossy(value.get(1..)?)
+                .trim_end_matches('\0')
+                .to_owned(),
+        )),
+        // object identifier, the instance
+        12 if len == 4 => Some(Value::Number(
+            (u32::from_be_bytes([value[0], value[1], value[2], value[3]]) & 0x3f_ffff) as u64,
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,231 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins identifying devices speaking operational technology protocols.
+//!
+//! The probes are read-only, they only send identification requests and never write registers,
+//! change the state of a PLC or send broadcasts:
+//! - `modbus_device_identification` reads the device identification of a Modbus/TCP device,
+//! - `s7_identify` reads the module and component identification of a Siemens S7 PLC,
+//! - `bacnet_device_info` reads the properties of the device object of a BACnet/IP device.
+//!
+//! The builtins are only available with the `nasl-builtin-ot-protocols` feature.
+
+mod bacnet;
+mod modbus;
+mod s7;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, io, time::Duration};
+
+use crate::nasl::prelude::*;
+
+use super::network::network_utils::{
+    bind_source_socket, connect_target, resolve_ipaddr, source_addrs, timeout_arg,
+};
+use super::network::verify_port;
+
+/// The result of a probe with the number of sent and received bytes
+type Probe<T> = io::Result<(T, usize, usize)>;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counts the traffic of a probe and turns failures of the connection into NULL.
+fn finish(
+    context: &Context<'_>,
+    port: i64,
+    result: Probe<NaslValue>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    match result {
+        Ok((value, sent, received)) => {
+            if let Some(traffic) = context.traffic() {
+                traffic.sent(sent)?;
+                traffic.received(received)?;
+            }
+            Ok(value)
+        }
+        Err(error) => {
+            tracing::debug!(port, %error, "no identification received");
+            Ok(NaslValue::Null)
+        }
+    }
+}
+
+fn dict(entries: Vec<(String, String)>) -> NaslValue {
+    NaslValue::Dict(
+        entries
+            .into_iter()
+            .map(|(name, value)| (name, NaslValue::String(value.into())))
+            .collect(),
+    )
+}
+
+/// *array* **modbus_device_identification**(port: *int*, unit: *int*, timeout: *int*);
+///
+/// Reads the basic and regular device identification objects of the Modbus/TCP device on the
+/// port of the target, 502 by default, with the unit id, 0 by default.
+///
+/// Returns an array of the objects by their names `vendor_name`, `product_code`, `revision`,
+/// `vendor_url`, `product_name`, `model_name` and `user_application_name`. When the device
+/// refuses the request an array with the `exception` code is returned. NULL is returned when the
+/// port does not answer as Modbus/TCP device.
+#[nasl_function(named(port, unit, timeout))]
+async fn modbus_device_identification(
+    context: &Context<'_>,
+    port: Option<i64>,
+    unit: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = port.unwrap_or(502);
+    let unit = match unit.map(u8::try_from).transpose() {
+        Ok(x) => x.unwrap_or(0),
+        Err(_) => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "unit",
+                "0 to 255",
+                &unit.unwrap_or_default().to_string(),
+            ))
+        }
+    };
+    let Some((mut stream, _permit)) = connect_target(
+        context,
+        verify_port(port)?,
+        timeout_arg(timeout, DEFAULT_TIMEOUT),
+    )
+    .await?
+    else {
+        return Ok(NaslValue::Null);
+    };
+    let result = modbus::identify(&mut stream, unit).map(|(result, sent, received)| {
+        let value = match result {
+            Ok(objects) => dict(
+                objects
+                    .into_iter()
+                    .map(|(id, value)| (modbus::object_name(id), value))
+                    .collect(),
+            ),
+            Err(code) => NaslValue::Dict(HashMap::from([(
+                "exception".to_string(),
+                NaslValue::Number(code as i64),
+            )])),
+        };
+        (value, sent, received)
+    });
+    finish(context, port, result)
+}
+
+/// *array* **s7_identify**(port: *int*, timeout: *int*);
+///
+/// Reads the identification of the Siemens S7 PLC on the port of the target, 102 by default.
+///
+/// Returns an array with the `order_number`, the `hardware` and the `firmware` version of the
+/// module identification and the `system_name`, `module_name`, `plant_id`, `copyright`,
+/// `serial_number` and `module_type` of the component identification, as far as the PLC provides
+/// them. NULL is returned when the port does not answer as S7 PLC.
+#[nasl_function(named(port, timeout))]
+async fn s7_identify(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = port.unwrap_or(102);
+    for tsap in s7::TSAPS {
+        let Some((mut stream, _permit)) = connect_target(
+            context,
+            verify_port(port)?,
+            timeout_arg(timeout, DEFAULT_TIMEOUT),
+        )
+        .await?
+        else {
+            return Ok(NaslValue::Null);
+        };
+        let request = s7::connection_request(tsap);
+        let confirm = match io::Write::write_all(&mut stream, &request)
+            .and_then(|_| s7::read_tpkt(&mut stream))
+        {
+            Ok(x) if s7::is_connection_confirm(&x) => x,
+            _ => {
+                tracing::debug!(port, tsap, "COTP connection not confirmed");
+                continue;
+            }
+        };
+        let result = s7::identify(&mut stream).map(|(entries, sent, received)| {
+            (
+                dict(entries),
+                sent + request.len(),
+                received + confirm.len() + 4,
+            )
+        });
+        return finish(context, port, result);
+    }
+    Ok(NaslValue::Null)
+}
+
+/// *array* **bacnet_device_info**(port: *int*, timeout: *int*);
+///
+/// Reads the properties of the device object of the BACnet/IP device on the port of the target,
+/// 47808 by default, via unicast ReadProperty requests.
+///
+/// Returns an array with the `instance` number, the `vendor_id`, the `vendor_name`, the
+/// `model_name`, the `firmware_revision`, the `application_software_version`, the `object_name`,
+/// the `description` and the `location` of the device, as far as it provides them. NULL is
+/// returned when the device does not answer.
+#[nasl_function(named(port, timeout))]
+async fn bacnet_device_info(
+    context: &Context<'_>,
+    port: Option<i64>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = verify_port(port.unwrap_or(47808))?;
+    let addr = resolve_ipaddr(context, context.target())?;
+    let source = source_addrs(context)?;
+    let _permit = context
+        .connection_limiter()
+        .acquire(context.target())
+        .await?;
+    let dst = source.socket_addr(addr, port);
+    let socket = bind_source_socket(&dst, &source)?;
+    socket.connect(dst)?;
+    socket.set_read_timeout(Some(timeout_arg(timeout, DEFAULT_TIMEOUT)))?;
+    let mut properties = HashMap::new();
+    let mut buffer = vec![0; 1500];
+    for (invoke_id, (property, name)) in bacnet::PROPERTIES.into_iter().enumerate() {
+        let request = bacnet::read_property(invoke_id as u8, property);
+        socket.send(&request)?;
+        if let Some(traffic) = context.traffic() {
+            traffic.sent(request.len())?;
+        }
+        let n = match socket.recv(&mut buffer) {
+            Ok(n) => n,
+            // a device not answering the first property is no BACnet device
+            Err(error) if invoke_id == 0 => {
+                tracing::debug!(port, %error, "no BACnet response");
+                return Ok(NaslValue::Null);
+            }
+            Err(_) => continue,
+        };
+        if let Some(traffic) = context.traffic() {
+            traffic.received(n)?;
+        }
+        let value = match bacnet::parse_response(&buffer[..n], invoke_id as u8) {
+            Some(bacnet::Value::Number(x)) => NaslValue::Number(x as i64),
+            Some(bacnet::Value::Text(x)) => NaslValue::String(x.into()),
+            None => continue,
+        };
+        properties.insert(name.to_string(), value);
+    }
+    if properties.is_empty() {
+        return Ok(NaslValue::Null);
+    }
+    Ok(NaslValue::Dict(properties))
+}
+
+pub struct OtProtocols;
+
+function_set! {
+    OtProtocols,
+    async_stateless,
+    (modbus_device_identification, s7_identify, bacnet_device_info)
+}
@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Modbus/TCP Read Device Identification (function 43, MEI type 14).
+
+use std::io::{self, Read, Write};
+
+use super::Probe;
+
+const FUNCTION_MEI: u8 = 0x2b;
+const MEI_READ_DEVICE_ID: u8 = 0x0e;
+/// Read code of the basic and regular objects
+const READ_REGULAR: u8 = 0x02;
+/// Maximum of requests following a response with more objects
+const MAX_REQUESTS: usize = 8;
+
+/// The names of the basic and regular objects by their id
+const OBJECTS: [&str; 7] = [
+    "vendor_name",
+    "product_code",
+    "revision",
+    "vendor_url",
+    "product_name",
+    "model_name",
+    "user_application_name",
+];
+
+/// Returns the name of an object, `object_<id>` for ids without a standard name.
+pub fn object_name(id: u8) -> String {
+    OBJECTS
+        .get(id as usize)
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| format!("object_{id}"))
+}
+
+/// Returns a request for the objects starting with the object id.
+pub fn request(transaction: u16, unit: u8, object: u8) -> Vec<u8> {
+    let mut frame = transaction.to_be_bytes().to_vec();
+    frame.extend_from_slice(&[0, 0, 0, 5, unit]);
+    frame.extend_from_slice(&[FUNCTION_MEI, MEI_READ_DEVICE_ID, READ_REGULAR, object]);
+    frame
+}
+
+/// The answer to a Read Device Identification request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The objects and the id of the next object if more objects follow
+    Objects {
+        objects: Vec<(u8, String)>,
+        next: Option<u8>,
+    },
+    /// The exception code of a device refusing the request
+    Exception(u8),
+}
+
+impl Response {
+    /// Parses the PDU of a response.
+    pub fn parse(pdu: &[u8]) -> Option<Self> {
+        match pdu {
+            [x, code, ..] if *x == FUNCTION_MEI | 0x80 => Some(Self::Exception(*code)),
+            [FUNCTION_MEI, MEI_READ_DEVICE_ID, _, _, more, next, count, rest @ ..] => {
+                let mut rest = rest;
+                let mut objects = vec![];
+                for _ in 0..*count {
+                    let (&[id, len], tail) = rest.split_first_chunk::<2>()?;
+                    let value = tail.get(..len as usize)?;
+                    objects.push((id, String::from_utf8_lossy(value).trim().to_owned()));
+                    rest = &tail[len as usize..];
+                }
+                Some(Self::Objects {
+                    objects,
+                    next: (*more == 0xff).then_some(*next),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads a Modbus/TCP frame and returns its PDU.
+fn read_frame<R: Read>(reader: &mut R, transaction: u16) -> io::Result<Vec<u8>> {
+    let invalid = |x: &str| io::Error::new(io::ErrorKind::InvalidData, x.to_owned());
+    let mut header = [0; 7];
+    reader.read_exact(&mut header)?;
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    if header[2..4] != [0, 0] || !(2..=254).contains(&len) {
+        return Err(invalid("no Modbus/TCP frame"));
+    }
+    if header[..2] != transaction.to_be_bytes() {
+        return Err(invalid("unexpected Modbus transaction"));
+    }
+    let mut pdu = vec![0; len - 1];
+    reader.read_exact(&mut pdu)?;
+    Ok(pdu)
+}
+
+/// Requests the identification objects until all are read and returns the objects or the
+/// exception code. Returns the number of sent and received bytes as well.
+pub fn identify<S: Read + Write>(stream: &mut S, unit: u8) -> Probe<Result<Vec<(u8, String)>, u8>> {
+    let mut objects = vec![];
+    let (mut sent, mut received) = (0, 0);
+    let mut next = 0;
+    for transaction in 1..=MAX_REQUESTS as u16 {
+        let frame = request(transaction, unit, next);
+        stream.write_all(&frame)?;
+        sent += frame.len();
+        let pdu = read_frame(stream, transaction)?;
+        received += pdu.len() + 7;
+        match Response::parse(&pdu) {
+            Some(Response::Exception(code)) if objects.is_empty() => {
+                return Ok((Err(code), sent, received))
+            }
+            Some(Response::Objects {
+                objects: mut x,
+                next: Some(id),
+            }) if id > next => {
+                objects.append(&mut x);
+                next = id;
+            }
+            Some(Response::Objects { objects: mut x, .. }) => {
+                objects.append(&mut x);
+                break;
+            }
+            Some(Response::Exception(_)) => break,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid Read Device Identification response",
+                ))
+            }
+        }
+    }
+    Ok((Ok(objects), sent, received))
+}
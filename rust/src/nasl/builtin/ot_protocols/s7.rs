@@ -0,0 +1,169 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! S7comm identification via the system status lists of Siemens PLCs.
+//!
+//! A COTP connection is requested, an S7 communication is set up and the system status lists
+//! 0x0011 (module identification) and 0x001c (component identification) are read.
+
+use std::io::{self, Read, Write};
+
+use super::Probe;
+
+/// The destination TSAPs tried, rack 0 slot 2 and the TSAP of S7-1200 and LOGO! PLCs
+pub const TSAPS: [u16; 2] = [0x0102, 0x0200];
+const COTP_CONNECTION_CONFIRM: u8 = 0xd0;
+const SZL_MODULE_IDENTIFICATION: u16 = 0x0011;
+const SZL_COMPONENT_IDENTIFICATION: u16 = 0x001c;
+
+/// The names of the records of the component identification by their index
+const COMPONENTS: [(u16, &str); 6] = [
+    (1, "system_name"),
+    (2, "module_name"),
+    (3, "plant_id"),
+    (4, "copyright"),
+    (5, "serial_number"),
+    (7, "module_type"),
+];
+
+fn tpkt(payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![3, 0];
+    packet.extend_from_slice(&((payload.len() + 4) as u16).to_be_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Returns the COTP Connection Request for the destination TSAP.
+pub fn connection_request(tsap: u16) -> Vec<u8> {
+    let mut cotp = vec![0x11, 0xe0, 0, 0, 0, 1, 0, 0xc1, 2, 1, 0, 0xc2, 2];
+    cotp.extend_from_slice(&tsap.to_be_bytes());
+    // TPDU size of 1024 bytes
+    cotp.extend_from_slice(&[0xc0, 1, 0x0a]);
+    tpkt(&cotp)
+}
+
+/// Returns the S7 Setup Communication job.
+pub fn setup_communication() -> Vec<u8> {
+    tpkt(&[
+        0x02, 0xf0, 0x80, 0x32, 0x01, 0, 0, 0, 0, 0, 0x08, 0, 0, 0xf0, 0, 0, 1, 0, 1, 0x01, 0xe0,
+    ])
+}
+
+/// Returns the userdata request reading the system status list.
+pub fn read_szl(id: u16) -> Vec<u8> {
+    let mut request = vec![
+        0x02, 0xf0, 0x80, 0x32, 0x07, 0, 0, 0, 0, 0, 0x08, 0, 0x08, 0, 0x01, 0x12, 0x04, 0x11,
+        0x44, 0x01, 0, 0xff, 0x09, 0, 0x04,
+    ];
+    request.extend_from_slice(&id.to_be_bytes());
+    request.extend_from_slice(&[0, 0]);
+    tpkt(&request)
+}
+
+/// Reads a TPKT and returns its payload.
+pub fn read_tpkt<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header)?;
+    let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if header[0] != 3 || !(7..=4096).contains(&len) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid TPKT"));
+    }
+    let mut payload = vec![0; len - 4];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// Returns the records of a system status list response.
+pub fn szl_records(payload: &[u8]) -> Option<Vec<&[u8]>> {
+    // the COTP data header followed by the S7 header
+    let s7 = payload.get(3..)?;
+    if s7.first() != Some(&0x32) {
+        return None;
+    }
+    let params = u16::from_be_bytes([*s7.get(6)?, *s7.get(7)?]) as usize;
+    let data = s7.get(10 + params..)?;
+    if data.first() != Some(&0xff) {
+        return None;
+    }
+    let record_len = u16::from_be_bytes([*data.get(8)?, *data.get(9)?]) as usize;
+    let count = u16::from_be_bytes([*data.get(10)?, *data.get(11)?]) as usize;
+    if record_len < 2 {
+        return None;
+    }
+    Some(
+        data.get(12..)?
+            .chunks_exact(record_len)
+            .take(count)
+            .collect(),
+    )
+}
+
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_matches(|x: char| x == '\0' || x.is_whitespace())
+        .to_owned()
+}
+
+/// Returns the order number, the hardware and the firmware version of the module
+/// identification.
+pub fn module_identification(records: &[&[u8]]) -> Vec<(String, String)> {
+    let mut result = vec![];
+    for record in records.iter().filter(|x| x.len() >= 28) {
+        match u16::from_be_bytes([record[0], record[1]]) {
+            1 => result.push(("order_number".to_string(), text(&record[2..22]))),
+            6 => result.push(("hardware".to_string(), text(&record[2..22]))),
+            7 => result.push((
+                "firmware".to_string(),
+                format!("V{}.{}.{}", record[25], record[26], record[27]),
+            )),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Returns the named records of the component identification.
+pub fn component_identification(records: &[&[u8]]) -> Vec<(String, String)> {
+    records
+        .iter()
+        .filter(|x| x.len() >= 34)
+        .filter_map(|record| {
+            let index = u16::from_be_bytes([record[0], record[1]]);
+            let (_, name) = COMPONENTS.iter().find(|(x, _)| *x == index)?;
+            Some((name.to_string(), text(&record[2..34])))
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// Reads the identification of the PLC after the connection is confirmed. Returns the number of
+/// sent and received bytes as well.
+pub fn identify<S: Read + Write>(stream: &mut S) -> Probe<Vec<(String, String)>> {
+    let (mut sent, mut received) = (0, 0);
+    let mut exchange = |request: Vec<u8>| -> io::Result<Vec<u8>> {
+        stream.write_all(&request)?;
+        sent += request.len();
+        let response = read_tpkt(stream)?;
+        received += response.len() + 4;
+        Ok(response)
+    };
+    exchange(setup_communication())?;
+    let mut result = vec![];
+    let modules = exchange(read_szl(SZL_MODULE_IDENTIFICATION))?;
+    if let Some(records) = szl_records(&modules) {
+        result.extend(module_identification(&records));
+    }
+    let components = exchange(read_szl(SZL_COMPONENT_IDENTIFICATION))?;
+    if let Some(records) = szl_records(&components) {
+        result.extend(component_identification(&records));
+    }
+    Ok((result, sent, received))
+}
+
+/// Returns whether the payload is a COTP Connection Confirm.
+pub fn is_connection_confirm(payload: &[u8]) -> bool {
+    payload
+        .get(1)
+        .is_some_and(|x| x & 0xf0 == COTP_CONNECTION_CONFIRM)
+}
@@ -0,0 +1,296 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, UdpSocket},
+        thread,
+    };
+
+    use super::super::{bacnet, modbus, s7};
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::NoOpLoader;
+    use crate::storage::{ContextKey, DefaultDispatcher};
+
+    fn builder() -> TestBuilder<NoOpLoader, DefaultDispatcher> {
+        TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())))
+    }
+
+    /// Returns a Read Device Identification response PDU.
+    fn identification(more: Option<u8>, objects: &[(u8, &str)]) -> Vec<u8> {
+        let (more, next) = more.map(|x| (0xff, x)).unwrap_or((0, 0));
+        let mut pdu = vec![0x2b, 0x0e, 0x02, 0x02, more, next, objects.len() as u8];
+        for (id, value) in objects {
+            pdu.extend_from_slice(&[*id, value.len() as u8]);
+            pdu.extend_from_slice(value.as_bytes());
+        }
+        pdu
+    }
+
+    fn modbus_frame(transaction: u16, pdu: &[u8]) -> Vec<u8> {
+        let mut frame = transaction.to_be_bytes().to_vec();
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&((pdu.len() + 1) as u16).to_be_bytes());
+        frame.push(0);
+        frame.extend_from_slice(pdu);
+        frame
+    }
+
+    #[test]
+    fn modbus_parse() {
+        assert_eq!(
+            modbus::request(7, 1, 0),
+            vec![0, 7, 0, 0, 0, 5, 1, 0x2b, 0x0e, 0x02, 0]
+        );
+        assert_eq!(
+            modbus::Response::parse(&identification(Some(2), &[(0, "Schneider"), (1, "BMX")])),
+            Some(modbus::Response::Objects {
+                objects: vec![(0, "Schneider".into()), (1, "BMX".into())],
+                next: Some(2),
+            })
+        );
+        assert_eq!(
+            modbus::Response::parse(&[0xab, 0x01]),
+            Some(modbus::Response::Exception(1))
+        );
+        assert_eq!(
+            modbus::Response::parse(&identification(None, &[(0, "x")])[..9]),
+            None
+        );
+        assert_eq!(modbus::object_name(5), "model_name");
+        assert_eq!(modbus::object_name(0x80), "object_128");
+    }
+
+    #[test]
+    fn modbus_device_identification() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let answers = [
+                identification(Some(2), &[(0, "Schneider Electric"), (1, "BMX P34 2020")]),
+                identification(None, &[(2, "v2.70")]),
+            ];
+            for (transaction, answer) in (1..).zip(answers) {
+                let mut request = [0; 11];
+                stream.read_exact(&mut request).unwrap();
+                // the continuation starts with the next object
+                assert_eq!(request[10], if transaction == 1 { 0 } else { 2 });
+                stream
+                    .write_all(&modbus_frame(transaction, &answer))
+                    .unwrap();
+            }
+        });
+        let mut t = builder();
+        t.run(&format!("a = modbus_device_identification(port: {port});"));
+        t.ok(r#"a["vendor_name"];"#, "Schneider Electric");
+        t.ok(r#"a["product_code"];"#, "BMX P34 2020");
+        t.ok(r#"a["revision"];"#, "v2.70");
+        check_err_matches!(
+            t,
+            r#"modbus_device_identification(port: 1, unit: 256);"#,
+            FunctionErrorKind::WrongArgument { .. }
+        );
+    }
+
+    /// Returns the payload of a system status list response with the records.
+    fn szl_response(id: u16, records: &[Vec<u8>]) -> Vec<u8> {
+        let record_len = records.first().map(Vec::len).unwrap_or_default();
+        let mut data = vec![0xff, 0x09];
+        data.extend_from_slice(&((8 + record_len * records.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&(record_len as u16).to_be_bytes());
+        data.extend_from_slice(&(records.len() as u16).to_be_bytes());
+        for record in records {
+            data.extend_from_slice(record);
+        }
+        let params = [0, 1, 0x12, 0x08, 0x12, 0x84, 0x01, 0x01, 0, 0, 0, 0];
+        let mut payload = vec![0x02, 0xf0, 0x80, 0x32, 0x07, 0, 0, 0, 0];
+        payload.extend_from_slice(&(params.len() as u16).to_be_bytes());
+        payload.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        payload.extend_from_slice(&params);
+        payload.extend_from_slice(&data);
+        payload
+    }
+
+    fn record(index: u16, text: &str, len: usize) -> Vec<u8> {
+        let mut record = index.to_be_bytes().to_vec();
+        record.extend_from_slice(text.as_bytes());
+        record.resize(len, 0);
+        record
+    }
+
+    fn s7_records() -> (Vec<u8>, Vec<u8>) {
+        let mut firmware = record(7, "", 28);
+        firmware[25..28].copy_from_slice(&[4, 2, 1]);
+        let modules = szl_response(0x11, &[record(1, "6ES7 214-1AG40-0XB0 ", 28), firmware]);
+        let components = szl_response(
+            0x1c,
+            &[
+                record(1, "S71200/ET200MP station_1", 34),
+                record(5, "S C-X4U421302009", 34),
+                record(6, "", 34),
+            ],
+        );
+        (modules, components)
+    }
+
+    fn tpkt(payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![3, 0];
+        packet.extend_from_slice(&((payload.len() + 4) as u16).to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn s7_parse() {
+        let request = s7::connection_request(0x0102);
+        assert_eq!(request.len(), 22);
+        assert_eq!(u16::from_be_bytes([request[2], request[3]]), 22);
+        assert_eq!(s7::setup_communication().len(), 25);
+        assert_eq!(s7::read_szl(0x11).len(), 33);
+
+        let (modules, components) = s7_records();
+        let records = s7::szl_records(&modules).unwrap();
+        assert_eq!(
+            s7::module_identification(&records),
+            vec![
+                ("order_number".into(), "6ES7 214-1AG40-0XB0".into()),
+                ("firmware".into(), "V4.2.1".into()),
+            ]
+        );
+        let records = s7::szl_records(&components).unwrap();
+        assert_eq!(
+            s7::component_identification(&records),
+            vec![
+                ("system_name".into(), "S71200/ET200MP station_1".into()),
+                ("serial_number".into(), "S C-X4U421302009".into()),
+            ]
+        );
+        assert_eq!(s7::szl_records(&[0x02, 0xf0, 0x80, 0x33]), None);
+    }
+
+    #[test]
+    fn s7_identify() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            // the first TSAP is refused
+            let (mut stream, _) = listener.accept().unwrap();
+            s7::read_tpkt(&mut stream).unwrap();
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = s7::read_tpkt(&mut stream).unwrap();
+            assert_eq!(&request[13..15], &[2, 0]);
+            let (modules, components) = s7_records();
+            let setup = [
+                0x02, 0xf0, 0x80, 0x32, 0x03, 0, 0, 0, 0, 0, 0x08, 0, 0, 0, 0, 0xf0, 0, 0, 1, 0, 1,
+                0x00, 0xf0,
+            ];
+            for answer in [
+                vec![0x11, 0xd0, 0, 1, 0, 1, 0, 0xc0, 1, 0x0a],
+                setup.to_vec(),
+                modules,
+                components,
+            ] {
+                if answer[1] != 0xd0 {
+                    s7::read_tpkt(&mut stream).unwrap();
+                }
+                stream.write_all(&tpkt(&answer)).unwrap();
+            }
+        });
+        let mut t = builder();
+        t.run(&format!("a = s7_identify(port: {port});"));
+        t.ok(r#"a["order_number"];"#, "6ES7 214-1AG40-0XB0");
+        t.ok(r#"a["firmware"];"#, "V4.2.1");
+        t.ok(r#"a["system_name"];"#, "S71200/ET200MP station_1");
+    }
+
+    /// Returns a ComplexACK of a ReadProperty request with the value.
+    fn bacnet_ack(invoke_id: u8, property: u8, value: &[u8]) -> Vec<u8> {
+        // an NPDU with a source specifier of a routed device
+        let mut ack = vec![0x81, 0x0a, 0, 0, 0x01, 0x08, 0, 5, 1, 9];
+        ack.extend_from_slice(&[0x30, invoke_id, 0x0c, 0x0c, 0x02, 0x00, 0x3e, 0x3e]);
+        ack.extend_from_slice(&[0x19, property, 0x3e]);
+        ack.extend_from_slice(value);
+        ack.push(0x3f);
+        let len = (ack.len() as u16).to_be_bytes();
+        ack[2..4].copy_from_slice(&len);
+        ack
+    }
+
+    #[test]
+    fn bacnet_parse() {
+        let request = bacnet::read_property(3, 77);
+        assert_eq!(request.len(), 17);
+        assert_eq!(&request[..4], &[0x81, 0x0a, 0, 17]);
+        assert_eq!(&request[10..15], &[0x0c, 0x02, 0x3f, 0xff, 0xff]);
+        let name = [0x75, 0x06, 0x00, b'A', b'H', b'U', b'-', b'1'];
+        assert_eq!(
+            bacnet::parse_response(&bacnet_ack(3, 77, &name), 3),
+            Some(bacnet::Value::Text("AHU-1".into()))
+        );
+        // the instance 15934 within the object id
+        assert_eq!(
+            bacnet::parse_response(&bacnet_ack(0, 75, &[0xc4, 0x02, 0x00, 0x3e, 0x3e]), 0),
+            Some(bacnet::Value::Number(15934))
+        );
+        assert_eq!(
+            bacnet::parse_response(&bacnet_ack(1, 120, &[0x21, 0x05]), 1),
+            Some(bacnet::Value::Number(5))
+        );
+        assert_eq!(
+            bacnet::parse_response(&bacnet_ack(1, 120, &[0x21, 0x05]), 2),
+            None
+        );
+        // an error PDU
+        assert_eq!(
+            bacnet::parse_response(
+                &[0x81, 0x0a, 0, 13, 0x01, 0x00, 0x50, 4, 0x0c, 0x91, 2, 0x91, 32],
+                4
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn bacnet_device_info() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buffer = [0; 1500];
+            for _ in bacnet::PROPERTIES {
+                let (_, peer) = socket.recv_from(&mut buffer).unwrap();
+                let (invoke_id, property) = (buffer[8], buffer[16]);
+                let value: &[u8] = match property {
+                    75 => &[0xc4, 0x02, 0x00, 0x3e, 0x3e],
+                    121 => b"\x75\x0a\x00Honeywell",
+                    // unsupported properties are answered with an error
+                    _ => {
+                        let error = [
+                            0x81, 0x0a, 0, 13, 0x01, 0x00, 0x50, invoke_id, 0x0c, 0x91, 2, 0x91, 32,
+                        ];
+                        socket.send_to(&error, peer).unwrap();
+                        continue;
+                    }
+                };
+                socket
+                    .send_to(&bacnet_ack(invoke_id, property, value), peer)
+                    .unwrap();
+            }
+        });
+        let mut t = builder();
+        t.run(&format!(
+            "a = bacnet_device_info(port: {port}, timeout: 1);"
+        ));
+        t.ok(r#"a["instance"];"#, 15934);
+        t.ok(r#"a["vendor_name"];"#, "Honeywell");
+        t.ok(r#"a["model_name"];"#, NaslValue::Null);
+    }
+}
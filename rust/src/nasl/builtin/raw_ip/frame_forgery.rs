@@ -13,7 +13,7 @@ use pcap::{Capture, Device};
 
 use super::super::host::get_host_ip;
 
-use super::raw_ip_utils::{get_interface_by_local_ip, get_source_ip, ipstr2ipaddr};
+use super::raw_ip_utils::{get_interface_by_local_ip, get_scan_source_ip, ipstr2ipaddr};
 
 use tracing::info;
 
@@ -311,7 +311,7 @@ fn send_frame(
     frame: &[u8],
     iface: &Device,
     pcap_active: &bool,
-    filter: Option<&String>,
+    filter: Option<&str>,
     timeout: i32,
 ) -> Result<Option<Frame>, FunctionErrorKind> {
     let mut capture_dev = match Capture::from_device(iface.clone()) {
@@ -369,7 +369,7 @@ fn nasl_send_arp_request(
             "IPv6 does not support ARP protocol.",
         ));
     }
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
+    let local_ip = get_scan_source_ip(context, target_ip)?;
     let iface = get_interface_by_local_ip(local_ip)?;
     let local_mac_address = match get_local_mac_address(&iface.name) {
         Some(x) => x,
@@ -402,7 +402,7 @@ fn nasl_send_arp_request(
     let filter = format!("arp and src host {}", target_ip);
     // send the frame and get a response if pcap_active enabled
     match send_frame(&arp_frame, &iface, &true, Some(&filter), timeout)? {
-        Some(f) => Ok(NaslValue::String(format!("{}", f.srchaddr))),
+        Some(f) => Ok(NaslValue::String(format!("{}", f.srchaddr).into())),
         None => Ok(NaslValue::Null),
     }
 }
@@ -426,7 +426,7 @@ fn nasl_get_local_mac_address_from_ip(
             let ip = ipstr2ipaddr(x)?;
             let iface = get_interface_by_local_ip(ip)?;
             match get_local_mac_address(&iface.name) {
-                Some(mac) => Ok(NaslValue::String(mac.to_string())),
+                Some(mac) => Ok(NaslValue::String(mac.to_string().into())),
                 _ => Err(FunctionErrorKind::Diagnostic(
                     "Not possible to get the local mac address".to_string(),
                     Some(NaslValue::Null),
@@ -454,7 +454,7 @@ fn nasl_forge_frame(register: &Register, _: &Context) -> Result<NaslValue, Funct
     };
 
     let payload: Vec<u8> = match register.named("payload") {
-        Some(ContextType::Value(NaslValue::String(x))) => x.clone().into_bytes(),
+        Some(ContextType::Value(NaslValue::String(x))) => x.as_bytes().to_vec(),
         Some(ContextType::Value(NaslValue::Data(x))) => x.clone(),
         _ => vec![],
     };
@@ -496,7 +496,7 @@ fn nasl_send_frame(register: &Register, context: &Context) -> Result<NaslValue,
     };
 
     let filter = match register.named("pcap_filter") {
-        Some(ContextType::Value(NaslValue::String(x))) => Some(x),
+        Some(ContextType::Value(NaslValue::String(x))) => Some(x.as_str()),
         None => None,
         _ => {
             return Err(FunctionErrorKind::wrong_unnamed_argument(
@@ -519,7 +519,7 @@ fn nasl_send_frame(register: &Register, context: &Context) -> Result<NaslValue,
 
     let target_ip = get_host_ip(context)?;
 
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
+    let local_ip = get_scan_source_ip(context, target_ip)?;
     let iface = get_interface_by_local_ip(local_ip)?;
 
     // send the frame and get a response if pcap_active enabled
@@ -9,7 +9,7 @@ use std::{
     str::FromStr,
 };
 
-use super::raw_ip_utils::{get_interface_by_local_ip, get_source_ip, islocalhost};
+use super::raw_ip_utils::{get_interface_by_local_ip, get_scan_source_ip, islocalhost};
 
 use super::super::host::get_host_ip;
 use crate::nasl::builtin::misc::random_impl;
@@ -390,8 +390,8 @@ fn get_ip_element(register: &Register, _configs: &Context) -> Result<NaslValue,
             "ip_ttl" => Ok(NaslValue::Number(pkt.get_ttl() as i64)),
             "ip_p" => Ok(NaslValue::Number(pkt.get_next_level_protocol().0 as i64)),
             "ip_sum" => Ok(NaslValue::Number(pkt.get_checksum() as i64)),
-            "ip_src" => Ok(NaslValue::String(pkt.get_source().to_string())),
-            "ip_dst" => Ok(NaslValue::String(pkt.get_destination().to_string())),
+            "ip_src" => Ok(NaslValue::String(pkt.get_source().to_string().into())),
+            "ip_dst" => Ok(NaslValue::String(pkt.get_destination().to_string().into())),
             _ => Err(FunctionErrorKind::WrongArgument(
                 "Invalid element".to_string(),
             )),
@@ -1956,7 +1956,7 @@ fn nasl_tcp_ping(register: &Register, configs: &Context) -> Result<NaslValue, Fu
 
     // Get the iface name, to set the capture device.
     let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
+    let local_ip = get_scan_source_ip(configs, target_ip)?;
     let iface = get_interface_by_local_ip(local_ip)?;
 
     let port = match register.named("port") {
@@ -2142,7 +2142,7 @@ fn nasl_send_packet(
 
     // Get the iface name, to set the capture device.
     let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
+    let local_ip = get_scan_source_ip(configs, target_ip)?;
     let iface = get_interface_by_local_ip(local_ip)?;
 
     let mut capture_dev = match Capture::from_device(iface) {
@@ -2275,7 +2275,7 @@ fn nasl_send_capture(
 
     // Get the iface name, to set the capture device.
     let target_ip = get_host_ip(configs)?;
-    let local_ip = get_source_ip(target_ip, 50000u16)?;
+    let local_ip = get_scan_source_ip(configs, target_ip)?;
     let mut iface = get_interface_by_local_ip(local_ip)?;
     if !interface.is_empty() {
         iface = pcap::Device::from(interface.as_str());
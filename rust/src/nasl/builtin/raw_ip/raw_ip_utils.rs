@@ -7,13 +7,16 @@ use std::{
     str::FromStr,
 };
 
+use crate::nasl::builtin::network::network_utils::source_addrs;
 use crate::nasl::syntax::NaslValue;
-use crate::nasl::utils::FunctionErrorKind;
+use crate::nasl::utils::{zone, Context, FunctionErrorKind};
 use pcap::{Address, Device};
 
 /// Convert a string in a IpAddr
+///
+/// The zone of a link-local address, e.g. `%eth0` of `fe80::1%eth0`, is ignored.
 pub fn ipstr2ipaddr(ip_addr: &str) -> Result<IpAddr, FunctionErrorKind> {
-    match IpAddr::from_str(ip_addr) {
+    match IpAddr::from_str(zone::strip(ip_addr)) {
         Ok(ip) => Ok(ip),
         Err(_) => Err(FunctionErrorKind::Diagnostic(
             "Invalid IP address".to_string(),
@@ -88,12 +91,21 @@ pub fn bind_local_socket(dst: &SocketAddr) -> Result<UdpSocket, FunctionErrorKin
     }
 }
 
-/// Return the source IP address given the destination IP address
-pub fn get_source_ip(dst: IpAddr, port: u16) -> Result<IpAddr, FunctionErrorKind> {
-    let socket = SocketAddr::new(dst, port);
-    let sd = format!("{}:{}", dst, port);
-    let local_socket = bind_local_socket(&socket)?;
-    match local_socket.connect(sd) {
+/// Return the source IP address of the scan traffic to the destination IP address
+///
+/// The configured source of the scan is preferred over the address of the routing interface.
+pub fn get_scan_source_ip(context: &Context, dst: IpAddr) -> Result<IpAddr, FunctionErrorKind> {
+    let source = source_addrs(context)?;
+    match source.for_dst(&dst) {
+        Some(x) => Ok(x),
+        None => get_source_ip(source.socket_addr(dst, 50000u16)),
+    }
+}
+
+/// Return the source IP address given the destination socket address
+pub fn get_source_ip(dst: SocketAddr) -> Result<IpAddr, FunctionErrorKind> {
+    let local_socket = bind_local_socket(&dst)?;
+    match local_socket.connect(dst) {
         Ok(_) => match local_socket.local_addr() {
             Ok(l_addr) => match IpAddr::from_str(&l_addr.ip().to_string()) {
                 Ok(x) => Ok(x),
@@ -0,0 +1,235 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins negotiating the security protocol of an RDP server.
+//!
+//! Only the X.224 Connection Request with an RDP Negotiation Request and the Connection Confirm
+//! of the server are exchanged ([MS-RDPBCGR] 2.2.1.1 and 2.2.1.2), no TLS or CredSSP handshake
+//! follows. The protocols are given and returned as the flags of [MS-RDPBCGR]:
+//! - `0` standard RDP security
+//! - `1` TLS
+//! - `2` CredSSP, i.e. Network Level Authentication
+//! - `4` RDSTLS
+//! - `8` CredSSP with early user authorization result
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::TrafficCounter;
+
+use super::network::network_utils::{connect_target, timeout_arg};
+use super::network::verify_port;
+
+const PROTOCOL_SSL: u32 = 0x1;
+const PROTOCOL_HYBRID: u32 = 0x2;
+const PROTOCOL_HYBRID_EX: u32 = 0x8;
+/// Protocols requested by default, all but RDSTLS
+const DEFAULT_PROTOCOLS: u32 = PROTOCOL_SSL | PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX;
+
+const TYPE_RDP_NEG_REQ: u8 = 0x01;
+const TYPE_RDP_NEG_RSP: u8 = 0x02;
+const TYPE_RDP_NEG_FAILURE: u8 = 0x03;
+const X224_CONNECTION_REQUEST: u8 = 0xe0;
+const X224_CONNECTION_CONFIRM: u8 = 0xd0;
+
+/// The failure code of a server requiring CredSSP
+const HYBRID_REQUIRED_BY_SERVER: u32 = 0x5;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns the X.224 Connection Request within a TPKT.
+fn connection_request(protocols: u32, cookie: Option<&str>) -> Vec<u8> {
+    let cookie = cookie
+        .map(|x| format!("Cookie: mstshash={x}\r\n"))
+        .unwrap_or_default();
+    let mut tpdu = vec![X224_CONNECTION_REQUEST, 0, 0, 0, 0, 0];
+    tpdu.extend_from_slice(cookie.as_bytes());
+    tpdu.extend_from_slice(&[TYPE_RDP_NEG_REQ, 0, 8, 0]);
+    tpdu.extend_from_slice(&protocols.to_le_bytes());
+    let mut packet = vec![3, 0];
+    packet.extend_from_slice(&((tpdu.len() + 5) as u16).to_be_bytes());
+    packet.push(tpdu.len() as u8);
+    packet.extend(tpdu);
+    packet
+}
+
+/// The Connection Confirm of a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Negotiation {
+    /// The server selected a protocol
+    Response { protocol: u32, flags: u8 },
+    /// The server refused the requested protocols
+    Failure { code: u32 },
+    /// The server does not support the negotiation and uses standard RDP security
+    Legacy,
+}
+
+impl Negotiation {
+    /// Parses the TPKT of a Connection Confirm.
+    fn parse(packet: &[u8]) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid Connection Confirm");
+        if packet.len() < 11 || packet[0] != 3 || packet[5] & 0xf0 != X224_CONNECTION_CONFIRM {
+            return Err(invalid());
+        }
+        let li = packet[4] as usize;
+        let tpdu = packet.get(5..5 + li).ok_or_else(invalid)?;
+        let Some(data) = tpdu.get(6..).filter(|x| x.len() >= 8) else {
+            return Ok(Self::Legacy);
+        };
+        let value = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        match data[0] {
+            TYPE_RDP_NEG_RSP => Ok(Self::Response {
+                protocol: value,
+                flags: data[1],
+            }),
+            TYPE_RDP_NEG_FAILURE => Ok(Self::Failure { code: value }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl From<Negotiation> for NaslValue {
+    fn from(value: Negotiation) -> Self {
+        let number = |x: u32| NaslValue::Number(x as i64);
+        let string = |x: &str| NaslValue::String(x.into());
+        let dict = match value {
+            Negotiation::Response { protocol, flags } => HashMap::from([
+                ("type".to_string(), string("response")),
+                ("protocol".to_string(), number(protocol)),
+                ("flags".to_string(), number(flags as u32)),
+            ]),
+            Negotiation::Failure { code } => HashMap::from([
+                ("type".to_string(), string("failure")),
+                ("failure".to_string(), number(code)),
+            ]),
+            Negotiation::Legacy => HashMap::from([
+                ("type".to_string(), string("legacy")),
+                ("protocol".to_string(), number(0)),
+            ]),
+        };
+        NaslValue::Dict(dict)
+    }
+}
+
+/// Sends the Connection Request and reads the Connection Confirm.
+fn negotiate<S: Read + Write>(
+    stream: &mut S,
+    protocols: u32,
+    cookie: Option<&str>,
+    traffic: Option<&TrafficCounter>,
+) -> Result<Negotiation, FunctionErrorKind> {
+    let request = connection_request(protocols, cookie);
+    stream.write_all(&request)?;
+    if let Some(traffic) = traffic {
+        traffic.sent(request.len())?;
+    }
+    let mut packet = vec![0; 4];
+    stream.read_exact(&mut packet)?;
+    let len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if packet[0] != 3 || !(11..=512).contains(&len) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid TPKT").into());
+    }
+    packet.resize(len, 0);
+    stream.read_exact(&mut packet[4..])?;
+    if let Some(traffic) = traffic {
+        traffic.received(len)?;
+    }
+    Ok(Negotiation::parse(&packet)?)
+}
+
+/// Connects to the port of the target and negotiates, returns None when the server does not
+/// answer with a Connection Confirm.
+async fn probe(
+    context: &Context<'_>,
+    port: i64,
+    protocols: u32,
+    cookie: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<Option<Negotiation>, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, _permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(None);
+    };
+    match negotiate(&mut stream, protocols, cookie, context.traffic()) {
+        Ok(x) => Ok(Some(x)),
+        Err(FunctionErrorKind::IOError(error)) => {
+            tracing::debug!(port, %error, "no RDP Connection Confirm");
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn protocols_arg(protocols: Option<i64>) -> Result<u32, FunctionErrorKind> {
+    match protocols {
+        None => Ok(DEFAULT_PROTOCOLS),
+        Some(x) => u32::try_from(x).map_err(|_| {
+            FunctionErrorKind::wrong_argument("protocols", "32 bit flags", &x.to_string())
+        }),
+    }
+}
+
+/// *array* **rdp_negotiate**(port: *int*, protocols: *int*, cookie: *string*, timeout: *int*);
+///
+/// Sends an RDP Negotiation Request for the protocols to the port of the target. The protocols
+/// default to TLS and CredSSP, the cookie is sent as `mstshash` when given.
+///
+/// Returns an array with the `type` `response`, the selected `protocol` and the `flags` of the
+/// server when it selected a protocol, with the `type` `failure` and the `failure` code when it
+/// refused the requested protocols and with the `type` `legacy` and the `protocol` 0 when it does
+/// not support the negotiation. NULL is returned when the server does not answer as RDP server.
+#[nasl_function(named(port, protocols, cookie, timeout))]
+async fn rdp_negotiate(
+    context: &Context<'_>,
+    port: i64,
+    protocols: Option<i64>,
+    cookie: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let protocols = protocols_arg(protocols)?;
+    Ok(probe(context, port, protocols, cookie, timeout)
+        .await?
+        .map(NaslValue::from)
+        .unwrap_or(NaslValue::Null))
+}
+
+/// *bool* **rdp_nla_required**(port: *int*, timeout: *int*);
+///
+/// Returns whether the RDP server on the port of the target enforces Network Level
+/// Authentication. A connection requesting standard RDP security and TLS but not CredSSP is
+/// negotiated, the server enforces NLA when it refuses it.
+///
+/// NULL is returned when the server does not answer as RDP server.
+#[nasl_function(named(port, timeout))]
+async fn rdp_nla_required(
+    context: &Context<'_>,
+    port: i64,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    Ok(
+        match probe(context, port, PROTOCOL_SSL, None, timeout).await? {
+            Some(Negotiation::Failure { code }) => {
+                NaslValue::Boolean(code == HYBRID_REQUIRED_BY_SERVER)
+            }
+            Some(_) => NaslValue::Boolean(false),
+            None => NaslValue::Null,
+        },
+    )
+}
+
+pub struct Rdp;
+
+function_set! {
+    Rdp,
+    async_stateless,
+    (rdp_negotiate, rdp_nla_required)
+}
@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::super::{connection_request, Negotiation};
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    /// Returns a Connection Confirm with the negotiation data.
+    fn confirm(data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![3, 0, 0, (11 + data.len()) as u8, (6 + data.len()) as u8];
+        packet.extend_from_slice(&[0xd0, 0, 0, 0x12, 0x34, 0]);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn request() {
+        assert_eq!(
+            connection_request(3, None),
+            vec![3, 0, 0, 19, 14, 0xe0, 0, 0, 0, 0, 0, 1, 0, 8, 0, 3, 0, 0, 0]
+        );
+        let request = connection_request(0, Some("scan"));
+        assert_eq!(request.len(), 19 + "Cookie: mstshash=scan\r\n".len());
+        assert_eq!(request[4] as usize, request.len() - 5);
+        assert_eq!(&request[11..34], b"Cookie: mstshash=scan\r\n");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Negotiation::parse(&confirm(&[2, 0x1f, 8, 0, 2, 0, 0, 0])).unwrap(),
+            Negotiation::Response {
+                protocol: 2,
+                flags: 0x1f
+            }
+        );
+        assert_eq!(
+            Negotiation::parse(&confirm(&[3, 0, 8, 0, 5, 0, 0, 0])).unwrap(),
+            Negotiation::Failure { code: 5 }
+        );
+        assert_eq!(
+            Negotiation::parse(&confirm(&[])).unwrap(),
+            Negotiation::Legacy
+        );
+        assert!(Negotiation::parse(b"HTTP/1.1 400 Bad Request").is_err());
+    }
+
+    /// Answers each connection with the next of the answers and returns the requested
+    /// protocols.
+    fn server(answers: Vec<Vec<u8>>) -> (u16, std::sync::mpsc::Receiver<u8>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, requested) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for (stream, answer) in listener.incoming().zip(answers) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 19];
+                stream.read_exact(&mut request).unwrap();
+                sender.send(request[15]).unwrap();
+                stream.write_all(&answer).unwrap();
+            }
+        });
+        (port, requested)
+    }
+
+    #[test]
+    fn negotiate() {
+        let (port, requested) = server(vec![confirm(&[2, 0, 8, 0, 1, 0, 0, 0]), confirm(&[])]);
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.run(&format!("a = rdp_negotiate(port: {port});"));
+        t.ok(r#"a["type"];"#, "response");
+        t.ok(r#"a["protocol"];"#, 1);
+        t.run(&format!("a = rdp_negotiate(port: {port}, protocols: 0);"));
+        t.ok(r#"a["type"];"#, "legacy");
+        drop(t);
+        assert_eq!(requested.recv().unwrap(), 11);
+        assert_eq!(requested.recv().unwrap(), 0);
+    }
+
+    #[test]
+    fn nla() {
+        let (port, requested) = server(vec![
+            confirm(&[3, 0, 8, 0, 5, 0, 0, 0]),
+            confirm(&[2, 0, 8, 0, 1, 0, 0, 0]),
+        ]);
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.ok(&format!("rdp_nla_required(port: {port});"), true);
+        t.ok(&format!("rdp_nla_required(port: {port});"), false);
+        drop(t);
+        assert_eq!(requested.recv().unwrap(), 1);
+    }
+}
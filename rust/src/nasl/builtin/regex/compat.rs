@@ -0,0 +1,320 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Translates POSIX and PCRE patterns into the syntax of the regex crate.
+//!
+//! The C implementation uses the POSIX extended regular expressions of the system library, while
+//! many scripts are written with PCRE in mind. Constructs the regex crate rejects although they
+//! are valid in either dialect are rewritten:
+//!
+//! - `{` not starting a repetition is a literal and `{,n}` is the same as `{0,n}`
+//! - `\e`, `\<`, `\>`, `` \` ``, `\'`, `\Z` and `\Q...\E` are replaced by their equivalent
+//! - comments `(?#...)` are removed
+//!
+//! In the compatible mode atomic groups and possessive quantifiers are approximated by their
+//! ordinary counterparts. The strict mode follows the POSIX implementation of the C scanner
+//! instead: backslashes within brackets are literals, unknown escapes match the escaped
+//! character, the pattern ends at the first NUL and constructs without an exact equivalent are
+//! rejected. Backreferences and lookarounds are rejected in both modes.
+
+use crate::nasl::utils::RegexMode;
+
+/// Escapes of the GNU regex library, every other escaped letter matches itself
+const GNU_ESCAPES: &str = "wWsSbB<>`'";
+
+fn unsupported(construct: &str) -> String {
+    format!("{construct} are not supported")
+}
+
+/// Returns the length of a repetition like `{2}`, `{2,}`, `{2,5}` or `{,5}` at the start of the
+/// given characters and the repetition in the syntax of the regex crate.
+fn repetition(chars: &[char]) -> Option<(usize, String)> {
+    let end = chars.iter().position(|x| *x == '}')?;
+    let inner: String = chars[1..end].iter().collect();
+    let valid = |x: &str| x.chars().all(|x| x.is_ascii_digit());
+    let translated = match inner.split_once(',') {
+        None if !inner.is_empty() && valid(&inner) => inner,
+        Some(("", max)) if !max.is_empty() && valid(max) => format!("0,{max}"),
+        Some((min, max)) if !min.is_empty() && valid(min) && valid(max) => inner,
+        _ => return None,
+    };
+    Some((end + 1, format!("{{{translated}}}")))
+}
+
+/// Returns the content of a POSIX bracket expression element like `[:alpha:]`, `[=a=]` or
+/// `[.a.]` at the start of the given characters and its length.
+fn bracket_element(chars: &[char]) -> Option<(char, String, usize)> {
+    let kind = *chars.get(1)?;
+    if chars.first() != Some(&'[') || !matches!(kind, ':' | '=' | '.') {
+        return None;
+    }
+    let end =
+        (2..chars.len().saturating_sub(1)).find(|i| chars[*i] == kind && chars[*i + 1] == ']')?;
+    Some((kind, chars[2..end].iter().collect(), end + 2))
+}
+
+/// Translates a bracket expression and returns its length.
+fn bracket(chars: &[char], mode: RegexMode, out: &mut String) -> Result<usize, String> {
+    let strict = mode == RegexMode::Strict;
+    let mut i = 1;
+    out.push('[');
+    if chars.get(i) == Some(&'^') {
+        out.push('^');
+        i += 1;
+    }
+    // a leading ] is a literal
+    if chars.get(i) == Some(&']') {
+        out.push_str("\\]");
+        i += 1;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            ']' => {
+                out.push(']');
+                return Ok(i + 1);
+            }
+            '[' => match bracket_element(&chars[i..]) {
+                Some((':', class, len)) => {
+                    out.push_str(&format!("[:{class}:]"));
+                    i += len;
+                }
+                Some((_, element, len)) => {
+                    let mut element = element.chars();
+                    match (element.next(), element.next()) {
+                        (Some(x), None) if !strict => out.push_str(&regex::escape(&x.to_string())),
+                        _ => return Err(unsupported("Collating elements and equivalence classes")),
+                    }
+                    i += len;
+                }
+                None => {
+                    out.push_str("\\[");
+                    i += 1;
+                }
+            },
+            '\\' if strict => {
+                out.push_str("\\\\");
+                i += 1;
+            }
+            '\\' => {
+                out.push('\\');
+                if let Some(x) = chars.get(i + 1) {
+                    out.push(*x);
+                }
+                i += 2;
+            }
+            x @ ('&' | '~') if chars.get(i + 1) == Some(&x) => {
+                // set operations of the regex crate are literals in POSIX and PCRE
+                out.push('\\');
+                out.push(x);
+                i += 1;
+            }
+            x => {
+                out.push(x);
+                i += 1;
+            }
+        }
+    }
+    Err("Unclosed bracket expression".to_string())
+}
+
+/// Translates the pattern into the syntax of the regex crate.
+pub fn translate(pattern: &str, mode: RegexMode) -> Result<String, String> {
+    let strict = mode == RegexMode::Strict;
+    let pattern = match strict {
+        true => pattern.split('\0').next().unwrap_or_default(),
+        false => pattern,
+    };
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len());
+    // true when the previous token can be repeated
+    let mut atom = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => {
+                let Some(&next) = chars.get(i + 1) else {
+                    return Err("Trailing backslash".to_string());
+                };
+                i += 2;
+                atom = true;
+                match next {
+                    '1'..='9' => return Err(unsupported("Backreferences")),
+                    '<' | '>' | '`' | '\'' => {
+                        out.push_str(match next {
+                            '<' => "\\b{start}",
+                            '>' => "\\b{end}",
+                            '`' => "\\A",
+                            _ => "\\z",
+                        });
+                        atom = false;
+                    }
+                    // unknown escapes match the character itself in POSIX
+                    x if strict && x.is_ascii_alphanumeric() && !GNU_ESCAPES.contains(x) => {
+                        out.push(x)
+                    }
+                    'e' => out.push_str("\\x1B"),
+                    'Z' => {
+                        out.push_str("(?:\\n?\\z)");
+                        atom = false;
+                    }
+                    'Q' => {
+                        let end = (i..chars.len())
+                            .find(|x| chars[*x] == '\\' && chars.get(x + 1) == Some(&'E'));
+                        let quoted: String = chars[i..end.unwrap_or(chars.len())].iter().collect();
+                        out.push_str(&regex::escape(&quoted));
+                        i = end.map_or(chars.len(), |x| x + 2);
+                    }
+                    x => {
+                        out.push('\\');
+                        out.push(x);
+                    }
+                }
+            }
+            '(' if chars.get(i + 1) == Some(&'?') => {
+                let rest: String = chars[i..chars.len().min(i + 4)].iter().collect();
+                if rest.starts_with("(?=")
+                    || rest.starts_with("(?!")
+                    || rest.starts_with("(?<=")
+                    || rest.starts_with("(?<!")
+                {
+                    return Err(unsupported("Lookarounds"));
+                } else if rest.starts_with("(?#") {
+                    match chars[i..].iter().position(|x| *x == ')') {
+                        Some(end) => i += end + 1,
+                        None => return Err("Unclosed comment".to_string()),
+                    }
+                    continue;
+                } else if rest.starts_with("(?>") {
+                    if strict {
+                        return Err(unsupported("Atomic groups"));
+                    }
+                    out.push_str("(?:");
+                    i += 3;
+                } else {
+                    out.push_str("(?");
+                    i += 2;
+                }
+                atom = false;
+            }
+            '(' | '|' => {
+                out.push(c);
+                i += 1;
+                atom = false;
+            }
+            '[' => {
+                i += bracket(&chars[i..], mode, &mut out)?;
+                atom = true;
+            }
+            '{' => match repetition(&chars[i..]).filter(|_| atom) {
+                Some((len, translated)) => {
+                    out.push_str(&translated);
+                    i += len;
+                    i = possessive(&chars, i, strict)?;
+                    atom = false;
+                }
+                None => {
+                    out.push_str("\\{");
+                    i += 1;
+                    atom = true;
+                }
+            },
+            '*' | '+' | '?' => {
+                out.push(c);
+                i += 1;
+                if c != '?' && chars.get(i) == Some(&'?') {
+                    // lazy quantifier
+                    out.push('?');
+                    i += 1;
+                } else {
+                    i = possessive(&chars, i, strict)?;
+                }
+                atom = false;
+            }
+            '^' => {
+                out.push(c);
+                i += 1;
+                atom = false;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+                atom = true;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Skips the `+` of a possessive quantifier at position i and returns the new position.
+fn possessive(chars: &[char], i: usize, strict: bool) -> Result<usize, String> {
+    match chars.get(i) {
+        Some('+') if strict => Err(unsupported("Possessive quantifiers")),
+        Some('+') => Ok(i + 1),
+        _ => Ok(i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compat(pattern: &str) -> Result<String, String> {
+        translate(pattern, RegexMode::Compat)
+    }
+
+    fn strict(pattern: &str) -> Result<String, String> {
+        translate(pattern, RegexMode::Strict)
+    }
+
+    #[test]
+    fn repetitions() {
+        assert_eq!(compat("a{2,}b{,3}c{4}").unwrap(), "a{2,}b{0,3}c{4}");
+        assert_eq!(compat("{foo}").unwrap(), "\\{foo}");
+        assert_eq!(compat("a{b").unwrap(), "a\\{b");
+        assert_eq!(compat("(a){2}").unwrap(), "(a){2}");
+        assert_eq!(compat("a++b*+").unwrap(), "a+b*");
+        assert_eq!(compat("a+?").unwrap(), "a+?");
+        assert!(strict("a++").is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(compat("\\<admin\\>").unwrap(), "\\b{start}admin\\b{end}");
+        assert_eq!(compat("\\e\\d").unwrap(), "\\x1B\\d");
+        assert_eq!(compat("\\Qa.b*\\Ec").unwrap(), "a\\.b\\*c");
+        assert_eq!(compat("a\\Z").unwrap(), "a(?:\\n?\\z)");
+        assert_eq!(strict("\\d\\w\\.").unwrap(), "d\\w\\.");
+        assert!(compat("(a)\\1").is_err());
+        assert!(compat("a\\").is_err());
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(compat("(?>ab)(?#comment)c").unwrap(), "(?:ab)c");
+        assert_eq!(compat("(?i)a(?:b)").unwrap(), "(?i)a(?:b)");
+        assert!(compat("a(?=b)").is_err());
+        assert!(compat("(?<!a)b").is_err());
+        assert!(strict("(?>ab)").is_err());
+    }
+
+    #[test]
+    fn brackets() {
+        assert_eq!(compat("[]a]").unwrap(), "[\\]a]");
+        assert_eq!(compat("[^]a]").unwrap(), "[^\\]a]");
+        assert_eq!(compat("[[:alpha:]_]").unwrap(), "[[:alpha:]_]");
+        assert_eq!(compat("[a&&b]").unwrap(), "[a\\&&b]");
+        assert_eq!(compat("[[=a=]]").unwrap(), "[a]");
+        assert_eq!(compat("[\\d.]").unwrap(), "[\\d.]");
+        assert_eq!(strict("[\\d.]").unwrap(), "[\\\\d.]");
+        assert!(strict("[[=a=]]").is_err());
+        assert!(compat("[ab").is_err());
+    }
+
+    #[test]
+    fn nul() {
+        assert_eq!(strict("ab\0cd").unwrap(), "ab");
+        assert_eq!(compat("ab\0cd").unwrap(), "ab\0cd");
+    }
+}
@@ -2,11 +2,13 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+mod compat;
 #[cfg(test)]
 mod tests;
 
 use crate::nasl::prelude::*;
-use regex::{Regex, RegexBuilder};
+use crate::nasl::utils::RegexMode;
+use regex::{Captures, Regex, RegexBuilder};
 
 fn parse_search_string(mut s: &str, rnul: bool, multiline: bool) -> &str {
     if !rnul {
@@ -19,10 +21,24 @@ fn parse_search_string(mut s: &str, rnul: bool, multiline: bool) -> &str {
     s
 }
 
-fn make_regex(pattern: &str, icase: bool, multiline: bool) -> Result<Regex, FunctionErrorKind> {
-    match RegexBuilder::new(pattern.to_string().as_str())
+/// Builds the regular expression after translating the pattern.
+///
+/// In the strict mode `.` matches a newline and `^` and `$` only match at the start and end of
+/// the string, as the C implementation does not use `REG_NEWLINE`.
+pub(super) fn make_regex(
+    pattern: &str,
+    icase: bool,
+    multiline: bool,
+    mode: RegexMode,
+) -> Result<Regex, FunctionErrorKind> {
+    let translated = compat::translate(pattern, mode).map_err(|e| {
+        FunctionErrorKind::Dirty(format!(" Error building regular expression pattern: {}", e))
+    })?;
+    let strict = mode == RegexMode::Strict;
+    match RegexBuilder::new(&translated)
         .case_insensitive(icase)
-        .multi_line(multiline)
+        .multi_line(multiline && !strict)
+        .dot_matches_new_line(strict)
         .build()
     {
         Ok(re) => Ok(re),
@@ -43,6 +59,7 @@ fn make_regex(pattern: &str, icase: bool, multiline: bool) -> Result<Regex, Func
 /// Return true if matches, false otherwise
 #[nasl_function(named(string, pattern, icase, rnul, multiline))]
 fn ereg(
+    context: &Context,
     string: NaslValue,
     pattern: NaslValue,
     icase: Option<bool>,
@@ -56,7 +73,7 @@ fn ereg(
     let string = string.to_string();
     let string = parse_search_string(&string, rnul, multiline);
 
-    let re = make_regex(&pattern.to_string(), icase, multiline)?;
+    let re = make_regex(&pattern.to_string(), icase, multiline, context.regex_mode())?;
     Ok(re.is_match(string))
 }
 
@@ -70,6 +87,7 @@ fn ereg(
 /// Return the new string with the pattern replaced with replace.
 #[nasl_function(named(string, pattern, replace, icase, rnul))]
 fn ereg_replace(
+    context: &Context,
     string: NaslValue,
     pattern: NaslValue,
     replace: NaslValue,
@@ -81,7 +99,7 @@ fn ereg_replace(
 
     let string = string.to_string();
     let string = parse_search_string(&string, rnul, true);
-    let re = make_regex(&pattern.to_string(), icase, false)?;
+    let re = make_regex(&pattern.to_string(), icase, false, context.regex_mode())?;
 
     let out = re
         .replace_all(string, replace.to_string().as_str())
@@ -99,6 +117,7 @@ fn ereg_replace(
 /// Returns the concatenation of all lines that match. Null otherwise.
 #[nasl_function(named(string, pattern, replace, icase, rnul))]
 fn egrep(
+    context: &Context,
     string: NaslValue,
     pattern: NaslValue,
     icase: Option<bool>,
@@ -109,7 +128,7 @@ fn egrep(
 
     let string = string.to_string();
     let string = parse_search_string(&string, rnul, true);
-    let re = make_regex(&pattern.to_string(), icase, true)?;
+    let re = make_regex(&pattern.to_string(), icase, true, context.regex_mode())?;
 
     let lines: Vec<&str> = string
         .split_inclusive('\n')
@@ -127,11 +146,13 @@ fn egrep(
 /// - find_all Boolean, to find all matches
 /// - rnul replace the null char in the string. Default TRUE.
 ///
-/// Return an array with the first match (find_all: False)
-/// or an array with all matches (find_all: TRUE).
+/// Return an array with the first match followed by its groups (find_all: False)
+/// or an array with all matches and their groups (find_all: TRUE).
+/// Groups that did not participate in the first match are NULL.
 /// NULL or empty if no match was found.
 #[nasl_function(named(string, pattern, find_all, icase, rnul))]
 fn eregmatch(
+    context: &Context,
     string: NaslValue,
     pattern: NaslValue,
     find_all: Option<bool>,
@@ -144,15 +165,19 @@ fn eregmatch(
 
     let string = string.to_string();
     let string = parse_search_string(&string, rnul, true);
-    let re = make_regex(&pattern.to_string(), icase, true)?;
+    let re = make_regex(&pattern.to_string(), icase, true, context.regex_mode())?;
 
+    let group = |x: Option<regex::Match>| x.map(|x| NaslValue::String(x.as_str().into()));
     let matches = match find_all {
         true => re
-            .find_iter(string)
-            .map(|m| NaslValue::String(m.as_str().to_string()))
+            .captures_iter(string)
+            .flat_map(|x: Captures| x.iter().filter_map(group).collect::<Vec<_>>())
             .collect(),
-        false => match re.find(string) {
-            Some(s) => vec![NaslValue::String(s.as_str().to_string())],
+        false => match re.captures(string) {
+            Some(x) => x
+                .iter()
+                .map(|x| group(x).unwrap_or(NaslValue::Null))
+                .collect(),
             None => vec![],
         },
     };
@@ -4,7 +4,9 @@
 
 #[cfg(test)]
 mod tests {
+    use super::super::make_regex;
     use crate::nasl::test_prelude::*;
+    use crate::nasl::utils::RegexMode;
 
     #[test]
     fn ereg_rnul_true_success() {
@@ -108,7 +110,7 @@ mod tests {
         );
         assert_eq!(
             t.results()[1],
-            Ok(NaslValue::String("Pair 0\n        Pair 2\n".to_string()))
+            Ok(NaslValue::String("Pair 0\n        Pair 2\n".into()))
         );
     }
 
@@ -125,7 +127,7 @@ mod tests {
         );
         assert_eq!(
             t.results()[1],
-            Ok(NaslValue::String("Pair 0\n        Pair 2\n".to_string()))
+            Ok(NaslValue::String("Pair 0\n        Pair 2\n".into()))
         );
     }
 
@@ -148,4 +150,118 @@ mod tests {
             vec!["Bar".to_string()],
         );
     }
+
+    #[test]
+    fn eregmatch_groups() {
+        check_code_result(
+            r#"eregmatch(string: "Server: Apache/2.4.57", pattern: "Apache/([0-9]+)\.([0-9]+)");"#,
+            vec!["Apache/2.4".to_string(), "2".to_string(), "4".to_string()],
+        );
+        check_code_result(
+            r#"eregmatch(string: "a1 b2", pattern: "([a-z])([0-9])", find_all: TRUE);"#,
+            vec!["a1", "a", "1", "b2", "b", "2"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        );
+        check_code_result(
+            r#"eregmatch(string: "ab", pattern: "a(x)?b");"#,
+            NaslValue::Array(vec![NaslValue::String("ab".into()), NaslValue::Null]),
+        );
+    }
+
+    #[test]
+    fn translated_patterns() {
+        check_code_result(r#"ereg(string: '{"a": 1}', pattern: "^{");"#, true);
+        check_code_result(r#"ereg(string: "aab", pattern: "^a{,2}b");"#, true);
+        check_code_result(
+            r#"ereg(string: "administrator", pattern: "\<admin\>");"#,
+            false,
+        );
+        check_err_matches!(
+            r#"ereg(string: "aa", pattern: "(a)\1");"#,
+            FunctionErrorKind::Dirty(_)
+        );
+    }
+
+    /// Patterns as they are used within the feed with a matching and a not matching subject
+    const FEED_PATTERNS: &[(&str, &str, &str)] = &[
+        ("^SSH-([0-9.]+)-", "SSH-2.0-OpenSSH_8.9p1", "220 ProFTPD"),
+        (
+            "Server: Apache/([0-9.]+)",
+            "Server: Apache/2.4.57 (Debian)",
+            "Server: nginx/1.25",
+        ),
+        (
+            "^HTTP/1\\.[01] +200",
+            "HTTP/1.1 200 OK",
+            "HTTP/1.1 404 Not Found",
+        ),
+        (
+            "<title>([^<]+)</title>",
+            "<title>Login</title>",
+            "<h1>Login</h1>",
+        ),
+        (
+            "(Microsoft|MS)-IIS/([0-9.]+)",
+            "Server: Microsoft-IIS/10.0",
+            "Server: lighttpd",
+        ),
+        (
+            "[0-9]{1,3}\\.[0-9]{1,3}\\.[0-9]{1,3}\\.[0-9]{1,3}",
+            "inet 192.168.1.10",
+            "inet6 ::1",
+        ),
+        (
+            "[[:space:]]+version[[:space:]]*=",
+            "  version = 1",
+            "version=1",
+        ),
+        ("\\<admin\\>", "user admin logged in", "administrator"),
+        ("^220[ -].*FTP", "220 ProFTPD FTP Server", "421 FTP Service"),
+        (
+            "\"version\": ?\"([0-9.]+)\"",
+            "{\"version\": \"1.2.3\"}",
+            "{}",
+        ),
+        ("[]a-z[]+", "[abc]", "123"),
+        ("{\"status\":", "{\"status\":\"ok\"}", "[]"),
+        (
+            "^[0-9a-f]{32}$",
+            "d41d8cd98f00b204e9800998ecf8427e",
+            "d41d8",
+        ),
+        (
+            "(?i)x-powered-by: php/([0-9.]+)",
+            "X-Powered-By: PHP/8.2.1",
+            "X-Powered-By: ASP.NET",
+        ),
+        ("^(\\+OK|-ERR) ", "+OK POP3 ready", "* OK IMAP4rev1"),
+    ];
+
+    #[test]
+    fn feed_patterns() {
+        for mode in [RegexMode::Compat, RegexMode::Strict] {
+            for (pattern, matching, not_matching) in FEED_PATTERNS {
+                let re = make_regex(pattern, false, true, mode)
+                    .unwrap_or_else(|e| panic!("{pattern} in {mode:?}: {e}"));
+                assert!(re.is_match(matching), "{pattern} in {mode:?}");
+                assert!(!re.is_match(not_matching), "{pattern} in {mode:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn strict_mode() {
+        let strict = |pattern: &str| make_regex(pattern, false, true, RegexMode::Strict).unwrap();
+        let compat = |pattern: &str| make_regex(pattern, false, true, RegexMode::Compat).unwrap();
+        assert!(strict("a.b").is_match("a\nb"));
+        assert!(!compat("a.b").is_match("a\nb"));
+        assert!(!strict("^b").is_match("a\nb"));
+        assert!(compat("^b").is_match("a\nb"));
+        assert!(strict("[\\.]").is_match("\\"));
+        assert!(!compat("[\\.]").is_match("\\"));
+        assert!(strict("\\r\\n").is_match("rn"));
+        assert!(compat("\\r\\n").is_match("\r\n"));
+    }
 }
@@ -2,7 +2,10 @@ use std::sync::{Arc, RwLock};
 
 use crate::models::{self, Protocol, ResultType};
 
-use crate::{nasl::prelude::*, storage::Field};
+use crate::{
+    nasl::{prelude::*, utils::lookup_keys::VHOST},
+    storage::Field,
+};
 
 #[cfg(test)]
 mod tests;
@@ -45,18 +48,29 @@ impl Reporting {
             Some("udp") => Protocol::UDP,
             _ => Protocol::TCP,
         };
-        let result = models::Result {
+        let mut result = models::Result {
             id: self.id(),
             r_type: typus,
             ip_address: Some(context.target().to_string()),
-            // TODO: where to get hostname? is it only vhost relevant?
-            hostname: None,
-            oid: Some(context.key().value()),
+            // results of an instance forked by get_host_name belong to its virtual host
+            hostname: register.named(VHOST).map(|x| x.to_string()),
+            // results of a scan belong to the VT, the key is the scan id
+            oid: context
+                .nvt()
+                .map(|x| x.oid.clone())
+                .or_else(|| Some(context.key().value())),
+            qod: context.nvt().map(|x| x.qod()),
             port,
             protocol: Some(protocol),
             message: data,
             detail: None,
+            pcap: context.recording().and_then(|x| x.current_file()),
+            traffic: context.traffic().map(|x| x.totals()),
+            labels: context.labels().cloned().unwrap_or_default(),
+            triage: None,
         };
+        // scripts may report what they sent to a host, e.g. a password
+        crate::redact::redact_result(&mut result);
         context
             .dispatcher()
             .retry_dispatch(5, context.key(), Field::Result(result.into()))?;
@@ -4,9 +4,12 @@
 
 #[cfg(test)]
 mod tests {
+    use futures::StreamExt;
+
     use crate::{
         models::{self, Protocol, ResultType},
-        nasl::test_prelude::*,
+        nasl::{interpreter::CodeInterpreter, test_prelude::*},
+        storage::item::Nvt,
     };
 
     fn verify(function: &str, result_type: ResultType) {
@@ -41,10 +44,15 @@ mod tests {
             ip_address: Some(context.target().to_string()),
             hostname: None,
             oid: Some(context.key().value()),
+            qod: None,
             port,
             protocol: Some(protocol),
             message: Some(format!("test{id}")),
             detail: None,
+            pcap: None,
+            traffic: None,
+            labels: Default::default(),
+            triage: None,
         };
 
         let udp = get_result(0);
@@ -75,4 +83,33 @@ mod tests {
     fn error_message() {
         verify("error_message", ResultType::Error)
     }
+
+    #[test]
+    fn result_of_vt() {
+        let t = TestBuilder::default();
+        let nvt = Nvt {
+            oid: "1.3.6.1.4.1.25623.1.0.1".to_string(),
+            ..Default::default()
+        };
+        let context = t.context().with_nvt(Some(&nvt));
+        let mut results = Box::pin(
+            CodeInterpreter::new(
+                r#"log_message(data: "test");"#,
+                Register::default(),
+                &context,
+            )
+            .stream(),
+        );
+        futures::executor::block_on(async {
+            while let Some(x) = results.next().await {
+                x.unwrap();
+            }
+        });
+        let result = context
+            .retriever()
+            .result(context.key(), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.oid.as_deref(), Some("1.3.6.1.4.1.25623.1.0.1"));
+    }
 }
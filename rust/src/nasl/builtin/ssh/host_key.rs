@@ -0,0 +1,496 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Verifies the host keys of the SSH servers connected to during a scan.
+//!
+//! The policy is configured via the scan preference `ssh_host_key_policy`:
+//! - `record-only` accepts every key. The keys are remembered for the scan, so that a key that
+//!   differs from a known or previously seen key is reported.
+//! - `accept-new` accepts unknown keys and remembers them, a differing key is rejected.
+//! - `strict` only accepts keys listed within `ssh_known_hosts`.
+//!
+//! `ssh_known_hosts` contains entries in the OpenSSH known_hosts format, including hashed host
+//! names, wildcards and `@revoked` markers. Keys are compared by their SHA256 fingerprint.
+
+use std::{collections::HashMap, fmt, str::FromStr, sync::Mutex};
+
+use base64::{engine::general_purpose, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::nasl::utils::{FunctionErrorKind, PluginConfig};
+
+/// Scan preference id of the host key policy
+pub const HOST_KEY_POLICY: &str = "ssh_host_key_policy";
+/// Scan preference id of the known hosts in OpenSSH known_hosts format
+pub const KNOWN_HOSTS: &str = "ssh_known_hosts";
+
+/// Decides which host keys are accepted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostKeyPolicy {
+    /// Accepts unknown keys, rejects keys that differ from a known key
+    AcceptNew,
+    /// Only accepts known keys
+    Strict,
+    /// Accepts every key and reports keys that differ from a known key
+    #[default]
+    RecordOnly,
+}
+
+impl FromStr for HostKeyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accept-new" => Ok(Self::AcceptNew),
+            "strict" => Ok(Self::Strict),
+            "record-only" | "" => Ok(Self::RecordOnly),
+            _ => Err(format!("unknown ssh host key policy {s}")),
+        }
+    }
+}
+
+/// The SHA256 hash of a public key blob
+#[derive(Clone, PartialEq, Eq)]
+pub struct Fingerprint(Vec<u8>);
+
+impl Fingerprint {
+    /// Creates a fingerprint out of a SHA256 hash
+    pub fn new(hash: Vec<u8>) -> Self {
+        Self(hash)
+    }
+
+    fn of_blob(blob: &[u8]) -> Self {
+        Self(Sha256::digest(blob).to_vec())
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SHA256:{}",
+            general_purpose::STANDARD_NO_PAD.encode(&self.0)
+        )
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+/// The result of verifying a host key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// The key is listed or was seen before
+    Known,
+    /// Neither the host is listed nor a key was seen before
+    New,
+    /// The key differs from the listed or previously seen keys
+    Changed(Vec<Fingerprint>),
+    /// The key is marked as revoked
+    Revoked,
+}
+
+/// A verified host key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKeyCheck {
+    /// The name of the host as used within known_hosts
+    pub host: String,
+    /// The fingerprint of the key of the server
+    pub fingerprint: Fingerprint,
+    /// The result of the verification
+    pub status: HostKeyStatus,
+    /// True when the connection may be used
+    pub accepted: bool,
+}
+
+impl HostKeyCheck {
+    /// Returns a message describing a key that must be reported, None for unremarkable keys.
+    pub fn finding(&self) -> Option<String> {
+        let action = if self.accepted {
+            "The connection was accepted"
+        } else {
+            "The connection was rejected"
+        };
+        let (host, fingerprint) = (&self.host, &self.fingerprint);
+        match &self.status {
+            HostKeyStatus::Known => None,
+            HostKeyStatus::New if self.accepted => None,
+            HostKeyStatus::New => Some(format!(
+                "The SSH host key {fingerprint} of {host} is not known. {action}."
+            )),
+            HostKeyStatus::Changed(expected) => {
+                let expected: Vec<_> = expected.iter().map(|x| x.to_string()).collect();
+                Some(format!(
+                    "The SSH host key of {host} changed. Expected {} but got {fingerprint}. \
+                     {action}.",
+                    expected.join(" or ")
+                ))
+            }
+            HostKeyStatus::Revoked => Some(format!(
+                "The SSH host key {fingerprint} of {host} is revoked. {action}."
+            )),
+        }
+    }
+}
+
+enum HostPattern {
+    /// Comma separated names, may contain wildcards and negations
+    Plain(Vec<String>),
+    /// `|1|salt|hash` as written by HashKnownHosts
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// Matches name against a pattern containing `*` and `?`
+fn wildcard(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            wildcard(rest, name) || (!name.is_empty() && wildcard(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name))) => wildcard(rest, name),
+        (Some((p, rest)), Some((n, name))) => p.eq_ignore_ascii_case(n) && wildcard(rest, name),
+        _ => false,
+    }
+}
+
+impl HostPattern {
+    fn parse(hosts: &str) -> Result<Self, String> {
+        if let Some(hashed) = hosts.strip_prefix("|1|") {
+            let decode = |x: &str| {
+                general_purpose::STANDARD
+                    .decode(x)
+                    .map_err(|e| format!("invalid hashed host: {e}"))
+            };
+            let (salt, hash) = hashed
+                .split_once('|')
+                .ok_or_else(|| "invalid hashed host".to_string())?;
+            return Ok(Self::Hashed {
+                salt: decode(salt)?,
+                hash: decode(hash)?,
+            });
+        }
+        Ok(Self::Plain(
+            hosts.split(',').map(|x| x.to_string()).collect(),
+        ))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            HostPattern::Plain(patterns) => {
+                let mut matched = false;
+                for pattern in patterns {
+                    match pattern.strip_prefix('!') {
+                        Some(negated) if wildcard(negated.as_bytes(), name.as_bytes()) => {
+                            return false
+                        }
+                        Some(_) => {}
+                        None => matched |= wildcard(pattern.as_bytes(), name.as_bytes()),
+                    }
+                }
+                matched
+            }
+            HostPattern::Hashed { salt, hash } => {
+                let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(salt) else {
+                    return false;
+                };
+                mac.update(name.as_bytes());
+                mac.verify_slice(hash).is_ok()
+            }
+        }
+    }
+}
+
+struct KnownHost {
+    hosts: HostPattern,
+    fingerprint: Fingerprint,
+    revoked: bool,
+}
+
+/// The parsed entries of a known_hosts file
+#[derive(Default)]
+pub struct KnownHosts {
+    entries: Vec<KnownHost>,
+}
+
+impl KnownHosts {
+    /// Parses the content of a known_hosts file.
+    ///
+    /// `@cert-authority` entries are ignored as certificates are not supported.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut entries = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| format!("{KNOWN_HOSTS}: line {}: {reason}", i + 1);
+            let mut fields = line.split_whitespace();
+            let mut hosts = fields.next().unwrap_or_default();
+            let mut revoked = false;
+            match hosts {
+                "@revoked" => {
+                    revoked = true;
+                    hosts = fields.next().ok_or_else(|| invalid("missing hosts"))?;
+                }
+                "@cert-authority" => continue,
+                x if x.starts_with('@') => return Err(invalid("unknown marker")),
+                _ => {}
+            }
+            let (Some(_key_type), Some(key)) = (fields.next(), fields.next()) else {
+                return Err(invalid("missing key"));
+            };
+            let blob = general_purpose::STANDARD
+                .decode(key)
+                .map_err(|e| invalid(&e.to_string()))?;
+            entries.push(KnownHost {
+                hosts: HostPattern::parse(hosts).map_err(|e| invalid(&e))?,
+                fingerprint: Fingerprint::of_blob(&blob),
+                revoked,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Returns true when there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn lookup<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a KnownHost> {
+        self.entries.iter().filter(move |x| x.hosts.matches(name))
+    }
+}
+
+/// Verifies host keys based on the policy of a scan.
+///
+/// It is shared by all scripts of a scan, so that keys seen by one script are known to the others.
+#[derive(Default)]
+pub struct HostKeyVerifier {
+    policy: HostKeyPolicy,
+    known_hosts: KnownHosts,
+    seen: Mutex<HashMap<String, Fingerprint>>,
+}
+
+impl HostKeyVerifier {
+    /// Creates a new verifier
+    pub fn new(policy: HostKeyPolicy, known_hosts: KnownHosts) -> Self {
+        Self {
+            policy,
+            known_hosts,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Creates a verifier out of the scan preferences.
+    ///
+    /// Returns an error when the preferences are invalid or when the strict policy is used
+    /// without known hosts.
+    pub fn from_config(config: &PluginConfig) -> Result<Self, FunctionErrorKind> {
+        let policy = config
+            .get::<HostKeyPolicy>(HOST_KEY_POLICY)?
+            .unwrap_or_default();
+        let known_hosts = KnownHosts::parse(config.value(KNOWN_HOSTS).unwrap_or_default())
+            .map_err(FunctionErrorKind::Dirty)?;
+        if policy == HostKeyPolicy::Strict && known_hosts.is_empty() {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "{HOST_KEY_POLICY} strict requires {KNOWN_HOSTS}"
+            )));
+        }
+        Ok(Self::new(policy, known_hosts))
+    }
+
+    /// Returns the policy
+    pub fn policy(&self) -> HostKeyPolicy {
+        self.policy
+    }
+
+    /// Verifies the key of the server on host and port
+    pub fn verify(&self, host: &str, port: u16, fingerprint: Fingerprint) -> HostKeyCheck {
+        let name = match port {
+            0 | 22 => host.to_string(),
+            port => format!("[{host}]:{port}"),
+        };
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let mut expected = vec![];
+        let mut status = None;
+        for entry in self.known_hosts.lookup(&name) {
+            match (entry.fingerprint == fingerprint, entry.revoked) {
+                (true, true) => {
+                    status = Some(HostKeyStatus::Revoked);
+                    break;
+                }
+                (true, false) => status = status.or(Some(HostKeyStatus::Known)),
+                (false, false) => expected.push(entry.fingerprint.clone()),
+                (false, true) => {}
+            }
+        }
+        let status = status.unwrap_or_else(|| {
+            if self.policy == HostKeyPolicy::Strict {
+                // keys seen during the scan are not trusted
+                return match expected.is_empty() {
+                    true => HostKeyStatus::New,
+                    false => HostKeyStatus::Changed(expected),
+                };
+            }
+            match seen.get(&name) {
+                Some(x) if x == &fingerprint => HostKeyStatus::Known,
+                Some(x) => {
+                    expected.push(x.clone());
+                    HostKeyStatus::Changed(expected)
+                }
+                None if expected.is_empty() => HostKeyStatus::New,
+                None => HostKeyStatus::Changed(expected),
+            }
+        });
+        let accepted = match (&status, self.policy) {
+            (_, HostKeyPolicy::RecordOnly) => true,
+            (HostKeyStatus::Known, _) => true,
+            (HostKeyStatus::New, HostKeyPolicy::AcceptNew) => true,
+            _ => false,
+        };
+        // the first seen key of a host is kept so that each later change is reported
+        if self.policy != HostKeyPolicy::Strict && status != HostKeyStatus::Revoked {
+            seen.entry(name.clone())
+                .or_insert_with(|| fingerprint.clone());
+        }
+        HostKeyCheck {
+            host: name,
+            fingerprint,
+            status,
+            accepted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ScanPreference;
+
+    use super::*;
+
+    const KEY_A: &[u8] = b"ssh-ed25519 key a";
+    const KEY_B: &[u8] = b"ssh-ed25519 key b";
+
+    fn line(hosts: &str, key: &[u8]) -> String {
+        format!(
+            "{hosts} ssh-ed25519 {}\n",
+            general_purpose::STANDARD.encode(key)
+        )
+    }
+
+    fn fp(key: &[u8]) -> Fingerprint {
+        Fingerprint::of_blob(key)
+    }
+
+    fn verifier(policy: HostKeyPolicy, known_hosts: &str) -> HostKeyVerifier {
+        HostKeyVerifier::new(policy, KnownHosts::parse(known_hosts).unwrap())
+    }
+
+    #[test]
+    fn known_hosts() {
+        let salt = b"0123456789abcdef0123";
+        let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+        mac.update(b"10.0.0.2");
+        let hashed = format!(
+            "|1|{}|{}",
+            general_purpose::STANDARD.encode(salt),
+            general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+        );
+        let content = [
+            "# comment\n".to_string(),
+            line("10.0.0.1,[10.0.0.1]:2222", KEY_A),
+            line(&hashed, KEY_A),
+            line("*.example.com,!bad.example.com", KEY_A),
+            line("@cert-authority *", KEY_B),
+        ]
+        .concat();
+        let known = KnownHosts::parse(&content).unwrap();
+        let matches = |name: &str| known.lookup(name).count();
+        assert_eq!(matches("10.0.0.1"), 1);
+        assert_eq!(matches("[10.0.0.1]:2222"), 1);
+        assert_eq!(matches("10.0.0.2"), 1);
+        assert_eq!(matches("10.0.0.3"), 0);
+        assert_eq!(matches("host.EXAMPLE.com"), 1);
+        assert_eq!(matches("bad.example.com"), 0);
+
+        assert!(KnownHosts::parse("10.0.0.1 ssh-ed25519").is_err());
+        assert!(KnownHosts::parse("10.0.0.1 ssh-ed25519 !!!").is_err());
+        assert!(KnownHosts::parse("@unknown 10.0.0.1 ssh-ed25519 AAAA").is_err());
+    }
+
+    #[test]
+    fn policies() {
+        let known = [line("10.0.0.1", KEY_A), line("@revoked 10.0.0.3", KEY_B)].concat();
+
+        let strict = verifier(HostKeyPolicy::Strict, &known);
+        assert_eq!(
+            strict.verify("10.0.0.1", 22, fp(KEY_A)).status,
+            HostKeyStatus::Known
+        );
+        let changed = strict.verify("10.0.0.1", 22, fp(KEY_B));
+        assert_eq!(changed.status, HostKeyStatus::Changed(vec![fp(KEY_A)]));
+        assert!(!changed.accepted);
+        assert!(changed.finding().unwrap().contains("rejected"));
+        assert!(!strict.verify("10.0.0.2", 22, fp(KEY_A)).accepted);
+        assert!(!strict.verify("10.0.0.2", 22, fp(KEY_A)).accepted);
+        assert!(!strict.verify("10.0.0.1", 2222, fp(KEY_A)).accepted);
+
+        let accept_new = verifier(HostKeyPolicy::AcceptNew, &known);
+        let new = accept_new.verify("10.0.0.2", 22, fp(KEY_A));
+        assert_eq!(
+            (new.status.clone(), new.accepted),
+            (HostKeyStatus::New, true)
+        );
+        assert_eq!(new.finding(), None);
+        assert!(accept_new.verify("10.0.0.2", 22, fp(KEY_A)).accepted);
+        assert!(!accept_new.verify("10.0.0.2", 22, fp(KEY_B)).accepted);
+        assert!(!accept_new.verify("10.0.0.1", 22, fp(KEY_B)).accepted);
+        let revoked = accept_new.verify("10.0.0.3", 22, fp(KEY_B));
+        assert_eq!(
+            (revoked.status, revoked.accepted),
+            (HostKeyStatus::Revoked, false)
+        );
+
+        let record = verifier(HostKeyPolicy::RecordOnly, &known);
+        assert_eq!(record.verify("10.0.0.2", 22, fp(KEY_A)).finding(), None);
+        let changed = record.verify("10.0.0.2", 22, fp(KEY_B));
+        assert!(changed.accepted);
+        assert_eq!(changed.status, HostKeyStatus::Changed(vec![fp(KEY_A)]));
+        assert!(changed.finding().unwrap().contains("accepted"));
+        // the first seen key stays the expected one
+        assert_eq!(
+            record.verify("10.0.0.2", 22, fp(KEY_A)).status,
+            HostKeyStatus::Known
+        );
+    }
+
+    #[test]
+    fn from_config() {
+        let config = |values: &[(&str, &str)]| {
+            let preferences: Vec<_> = values
+                .iter()
+                .map(|(id, value)| ScanPreference {
+                    id: id.to_string(),
+                    value: value.to_string(),
+                })
+                .collect();
+            HostKeyVerifier::from_config(&PluginConfig::new("scan", &preferences))
+        };
+        assert_eq!(config(&[]).unwrap().policy(), HostKeyPolicy::RecordOnly);
+        assert!(config(&[(HOST_KEY_POLICY, "strict")]).is_err());
+        assert!(config(&[(HOST_KEY_POLICY, "sometimes")]).is_err());
+        let known = line("10.0.0.1", KEY_A);
+        assert_eq!(
+            config(&[(HOST_KEY_POLICY, "strict"), (KNOWN_HOSTS, &known)])
+                .unwrap()
+                .policy(),
+            HostKeyPolicy::Strict
+        );
+    }
+}
@@ -6,13 +6,24 @@
 // TODO clean up and maybe split as 2000 lines is a bit much
 //! Defines NASL ssh and sftp functions
 //!
+mod host_key;
+mod pool;
 mod sessions;
 
+use crate::models::{self, Protocol, ResultType};
 use crate::nasl::prelude::*;
-use crate::nasl::syntax::NaslValue;
+use crate::nasl::syntax::{NaslString, NaslValue};
+use crate::nasl::utils::{Plugin, PluginConfig};
+use crate::notus::gather::{self, OsRelease};
+use crate::notus::PackageScanner;
+use crate::storage::{types::Primitive, Field, Kb};
+use async_trait::async_trait;
 use core::str;
+use host_key::{Fingerprint, HostKeyVerifier};
 use libssh_rs::{AuthMethods, AuthStatus, Channel, LogLevel, Session, SshKey, SshOption};
-use sessions::SshSession;
+use pool::Pool;
+use sessions::{ConnectOptions, SshSession};
+use sha2::{Digest, Sha256};
 use std::io::Write;
 use std::net::UdpSocket;
 use std::os::fd::AsRawFd;
@@ -170,12 +181,310 @@ fn next_session_id(sessions: &MutexGuard<Vec<SshSession>>) -> i32 {
     new_val
 }
 
-fn lock_sessions(
-    sessions: &Arc<Mutex<Vec<SshSession>>>,
-) -> Result<MutexGuard<Vec<SshSession>>, FunctionErrorKind> {
-    // we actually need to panic as a lock error is fatal
-    // alternatively we need to add a poison error on FunctionErrorKind
-    Ok(Arc::as_ref(sessions).lock().unwrap())
+/// Creates a new session with the given options, it is not connected yet.
+fn open_session(options: &ConnectOptions) -> Result<Session, FunctionErrorKind> {
+    let (ip_str, port, timeout) = (&options.host, options.port, options.timeout);
+    let ConnectOptions {
+        key_type,
+        csciphers,
+        scciphers,
+        ..
+    } = options;
+
+    let session = match Session::new() {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "Function called from ssh_connect: {}",
+                e
+            )));
+        }
+    };
+
+    let option = SshOption::Timeout(Duration::from_secs(timeout as u64));
+
+    if let Err(err) = session.set_option(option) {
+        return Err(FunctionErrorKind::Dirty(
+        format!(
+            "Function {} called from {}: Failed to set the SSH connection timeout to {} seconds: {}", "func", "key", timeout, err)));
+    }
+
+    let verbose = env::var("OPENVAS_LIBSSH_DEBUG")
+        .map(|x| x.parse::<i32>().unwrap_or_default())
+        .unwrap_or(0);
+
+    let log_level = match verbose {
+        0 => LogLevel::NoLogging,
+        1 => LogLevel::Warning,
+        2 => LogLevel::Protocol,
+        3 => LogLevel::Packet,
+        _ => LogLevel::Functions,
+    };
+    let option = SshOption::LogLevel(log_level);
+    if session.set_option(option).is_err() {
+        return Err(FunctionErrorKind::Dirty(format!(
+            "Function {} called from {}: Failed to set the SSH connection log level",
+            "func", "key"
+        )));
+    }
+
+    let option = SshOption::Hostname(ip_str.to_owned());
+    match session.set_option(option) {
+        Ok(_) => (),
+        Err(e) => {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "Function {} (calling internal function {}): Failed to set SSH hostname '{}': {}",
+                "func", "nasl_ssh_connect", ip_str, e
+            )));
+        }
+    };
+
+    let option = SshOption::KnownHosts(Some("/dev/null".to_owned()));
+    if let Err(err) = session.set_option(option) {
+        return Err(FunctionErrorKind::Dirty(format!(
+            "Function {} (calling internal function {}): Failed to disable known_hosts: {}",
+            "func", "nasl_ssh_connect", err
+        )));
+    }
+
+    if !key_type.is_empty() {
+        let option = SshOption::HostKeys(key_type.to_owned());
+        if let Err(err) = session.set_option(option) {
+            return Err(FunctionErrorKind::Dirty(format!(
+                "Function {} (calling internal function {}): Failed to set SSH key type '{}': {}",
+                "func", "nasl_ssh_connect", key_type, err
+            )));
+        }
+    }
+
+    if !csciphers.is_empty() {
+        let option = SshOption::CiphersCS(csciphers.to_owned());
+        if let Err(err) = session.set_option(option) {
+            return Err(FunctionErrorKind::Dirty(
+            format!(
+                "Function {} (calling internal function {}): Failed to set SSH client to server ciphers '{}': {}", "func", "nasl_ssh_connect", csciphers, err)
+        ));
+        }
+    }
+
+    if !scciphers.is_empty() {
+        let option = SshOption::CiphersSC(scciphers.to_owned());
+        if let Err(err) = session.set_option(option) {
+            return Err(FunctionErrorKind::Dirty(
+            format!(
+                "Function {} (calling internal function {}): Failed to set SSH server to client ciphers '{}': {}", "func", "nasl_ssh_connect", scciphers, err)
+        ));
+        }
+    }
+
+    let valid_ports = 1..65535;
+    if valid_ports.contains(&port) {
+        let option = SshOption::Port(port);
+        if let Err(err) = session.set_option(option) {
+            return Err(FunctionErrorKind::Dirty(
+            format!(
+                "Function {} (calling internal function {}) called from {}: Failed to set SSH port '{}': {}", "func", "nasl_ssh_connect", "key", port, err)
+        ));
+        }
+    }
+    Ok(session)
+}
+
+/// Reports a finding about the host key of the server on port
+fn report_host_key(ctx: &Context, port: u16, message: String) -> Result<(), FunctionErrorKind> {
+    let result = models::Result {
+        id: 0,
+        r_type: ResultType::Log,
+        ip_address: Some(ctx.target().to_string()),
+        hostname: None,
+        oid: Some(ctx.key().value()),
+        port: Some(port as i16),
+        protocol: Some(Protocol::TCP),
+        message: Some(message),
+        detail: None,
+        pcap: ctx.recording().and_then(|x| x.current_file()),
+        traffic: ctx.traffic().map(|x| x.totals()),
+        labels: ctx.labels().cloned().unwrap_or_default(),
+    };
+    ctx.dispatcher()
+        .retry_dispatch(5, ctx.key(), Field::Result(result.into()))?;
+    Ok(())
+}
+
+/// Verifies the key of the connected server with the host key policy of the scan.
+///
+/// Changed, revoked and rejected keys are reported as log results.
+fn verify_host_key(
+    ctx: &Context,
+    session: &Session,
+    host: &str,
+    port: u16,
+) -> Result<(), FunctionErrorKind> {
+    let verifier = match ctx.extensions().get::<HostKeyVerifier>() {
+        Some(x) => x,
+        None => {
+            let config = ctx.extensions().get::<PluginConfig>().unwrap_or_default();
+            let verifier = HostKeyVerifier::from_config(&config)?;
+            ctx.extensions().get_or_insert_with(|| verifier)
+        }
+    };
+    let fingerprint = session
+        .get_server_public_key()
+        .and_then(|x| x.get_public_key_hash(libssh_rs::PublicKeyHashType::Sha256))
+        .map_err(|e| {
+            FunctionErrorKind::Dirty(format!("Failed to get the SSH host key of {host}: {e}"))
+        })?;
+    let port = if port == 0 { 22 } else { port };
+    let check = verifier.verify(host, port, Fingerprint::new(fingerprint));
+    if let Some(message) = check.finding() {
+        report_host_key(ctx, port, message)?;
+    }
+    if check.accepted {
+        Ok(())
+    } else {
+        Err(FunctionErrorKind::Diagnostic(
+            format!(
+                "SSH host key {} of {} rejected by the {:?} policy",
+                check.fingerprint,
+                check.host,
+                verifier.policy()
+            ),
+            Some(NaslValue::Number(0)),
+        ))
+    }
+}
+
+/// Returns a hash identifying the credentials of an authentication
+fn credential_hash(parts: &[Option<&str>]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        match part {
+            Some(x) => {
+                hasher.update([1]);
+                hasher.update((x.len() as u64).to_le_bytes());
+                hasher.update(x.as_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Idle authenticated sessions of a scan, see [pool].
+type SessionPool = Pool<ConnectOptions, SshSession>;
+
+impl SessionPool {
+    /// Returns the pool of the scan the context belongs to
+    fn of(context: &Context) -> Result<Arc<Self>, FunctionErrorKind> {
+        if let Some(x) = context.extensions().get() {
+            return Ok(x);
+        }
+        let config = context
+            .extensions()
+            .get::<PluginConfig>()
+            .unwrap_or_default();
+        let pool = Self::from_config(&config, |x: SshSession| x.session.disconnect())?;
+        Ok(context.extensions().get_or_insert_with(|| pool))
+    }
+
+    /// Keeps an authenticated session for later scripts, other sessions are disconnected.
+    fn release_session(&self, mut session: SshSession) {
+        match (&session.options, &session.credential, &session.channel) {
+            (Some(options), Some(_), None) => {
+                let options = options.clone();
+                session.owner = None;
+                self.release(options, session);
+            }
+            _ => session.session.disconnect(),
+        }
+    }
+}
+
+/// Replaces a reused session that is authenticated with other credentials.
+///
+/// An idle session authenticated with credential is taken from the pool when available,
+/// otherwise a new connection is opened. The replaced session is kept within the pool.
+fn switch_session(
+    ctx: &Context,
+    session: &mut SshSession,
+    credential: &[u8],
+) -> Result<(), FunctionErrorKind> {
+    let (Some(options), Some(current)) = (&session.options, &session.credential) else {
+        return Ok(());
+    };
+    if current == credential {
+        return Ok(());
+    }
+    let options = options.clone();
+    let pool = SessionPool::of(ctx)?;
+    let replacement = pool.take(
+        &options,
+        |x| x.credential.as_deref() == Some(credential),
+        |x| x.session.is_connected(),
+    );
+    let mut replacement = match replacement {
+        Some(x) => x,
+        None => {
+            let new = open_session(&options)?;
+            if let Err(e) = new.connect() {
+                new.disconnect();
+                return Err(FunctionErrorKind::Dirty(format!(
+                    "Failed to connect to SSH server '{}' (port {}): {}",
+                    options.host, options.port, e
+                )));
+            }
+            if let Err(e) = verify_host_key(ctx, &new, &options.host, options.port) {
+                new.disconnect();
+                return Err(e);
+            }
+            SshSession {
+                session_id: session.session_id,
+                session: new,
+                authmethods: AuthMethods::NONE,
+                authmethods_valid: false,
+                user_set: false,
+                channel: None,
+                owner: None,
+                options: Some(options),
+                credential: None,
+            }
+        }
+    };
+    replacement.session_id = session.session_id;
+    replacement.owner = session.owner.clone();
+    let previous = std::mem::replace(session, replacement);
+    pool.release_session(previous);
+    Ok(())
+}
+
+/// The sessions shared by all scripts of a scan.
+///
+/// It is stored within the extensions of the context, the remaining sessions are disconnected
+/// when the scan is finished.
+#[derive(Default)]
+pub struct SshSessions(Mutex<Vec<SshSession>>);
+
+impl SshSessions {
+    /// Returns the sessions of the scan the context belongs to
+    fn of(context: &Context) -> Arc<Self> {
+        context.extensions().get_or_default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Vec<SshSession>>, FunctionErrorKind> {
+        // we actually need to panic as a lock error is fatal
+        // alternatively we need to add a poison error on FunctionErrorKind
+        Ok(self.0.lock().unwrap())
+    }
+}
+
+impl Drop for SshSessions {
+    fn drop(&mut self) {
+        if let Ok(sessions) = self.0.get_mut() {
+            for x in sessions.drain(..) {
+                x.session.disconnect();
+            }
+        }
+    }
 }
 
 fn set_opt_user(
@@ -366,11 +675,61 @@ fn exec_ssh_cmd(
     Ok((response, compat_buf))
 }
 
-#[derive(Default)]
-pub struct Ssh {
-    sessions: Arc<Mutex<Vec<SshSession>>>,
+/// Stores a gathered value within the KB of the target
+fn set_gathered(ctx: &Context, key: &str, value: String) -> Result<(), FunctionErrorKind> {
+    ctx.dispatcher().dispatch(
+        ctx.key(),
+        Field::KB(Kb {
+            key: key.to_string(),
+            value: Primitive::String(value),
+            expire: None,
+        }),
+    )?;
+    Ok(())
 }
 
+/// Checks the gathered packages with the notus instance of the scan.
+///
+/// Each notus VT with vulnerable packages is reported as an alarm.
+fn report_vulnerable_packages(
+    ctx: &Context,
+    scanner: &dyn PackageScanner,
+    product: &str,
+    packages: &[String],
+) -> Result<(), FunctionErrorKind> {
+    let results = match scanner.scan_packages(product, packages) {
+        Ok(x) => x,
+        Err(e) => {
+            // e.g. products that are not covered by the notus feed
+            debug!(product, error=%e, "Unable to check gathered packages with notus");
+            return Ok(());
+        }
+    };
+    for (oid, vulnerable) in results {
+        let result = models::Result {
+            id: 0,
+            r_type: ResultType::Alarm,
+            ip_address: Some(ctx.target().to_string()),
+            hostname: None,
+            oid: Some(oid),
+            port: None,
+            protocol: None,
+            message: Some(gather::finding(&vulnerable)),
+            detail: None,
+            pcap: None,
+            traffic: None,
+            labels: ctx.labels().cloned().unwrap_or_default(),
+            triage: None,
+        };
+        ctx.dispatcher()
+            .retry_dispatch(5, ctx.key(), Field::Result(result.into()))?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct Ssh;
+
 impl Ssh {
     /// Connect to the target host via TCP and setup an ssh
     ///        connection.
@@ -434,114 +793,51 @@ impl Ssh {
             .into();
         let key_type: String = register
             .named("keytype")
-            .unwrap_or(&ContextType::Value(NaslValue::String(String::default())))
+            .unwrap_or(&ContextType::Value(
+                NaslValue::String(NaslString::default()),
+            ))
             .into();
         let csciphers: String = register
             .named("csciphers")
-            .unwrap_or(&ContextType::Value(NaslValue::String(String::default())))
+            .unwrap_or(&ContextType::Value(
+                NaslValue::String(NaslString::default()),
+            ))
             .into();
 
         let scciphers: String = register
             .named("scciphers")
-            .unwrap_or(&ContextType::Value(NaslValue::String(String::default())))
+            .unwrap_or(&ContextType::Value(
+                NaslValue::String(NaslString::default()),
+            ))
             .into();
 
-        let session = match Session::new() {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(FunctionErrorKind::Dirty(format!(
-                    "Function called from ssh_connect: {}",
-                    e
-                )));
-            }
-        };
-
-        let option = SshOption::Timeout(Duration::from_secs(timeout as u64));
-
-        if let Err(err) = session.set_option(option) {
-            return Err(FunctionErrorKind::Dirty(
-            format!(
-                "Function {} called from {}: Failed to set the SSH connection timeout to {} seconds: {}", "func", "key", timeout, err)));
-        }
-
-        let verbose = env::var("OPENVAS_LIBSSH_DEBUG")
-            .map(|x| x.parse::<i32>().unwrap_or_default())
-            .unwrap_or(0);
-
-        let log_level = match verbose {
-            0 => LogLevel::NoLogging,
-            1 => LogLevel::Warning,
-            2 => LogLevel::Protocol,
-            3 => LogLevel::Packet,
-            _ => LogLevel::Functions,
-        };
-        let option = SshOption::LogLevel(log_level);
-        if session.set_option(option).is_err() {
-            return Err(FunctionErrorKind::Dirty(format!(
-                "Function {} called from {}: Failed to set the SSH connection log level",
-                "func", "key"
-            )));
-        }
-
-        let option = SshOption::Hostname(ip_str.to_owned());
-        match session.set_option(option) {
-            Ok(_) => (),
-            Err(e) => {
-                return Err(FunctionErrorKind::Dirty(
-                format!(
-                    "Function {} (calling internal function {}): Failed to set SSH hostname '{}': {}", "func", "nasl_ssh_connect", ip_str, e)
-            ));
-            }
+        let options = ConnectOptions {
+            host: ip_str.clone(),
+            port,
+            timeout,
+            key_type,
+            csciphers,
+            scciphers,
         };
-
-        let option = SshOption::KnownHosts(Some("/dev/null".to_owned()));
-        if let Err(err) = session.set_option(option) {
-            return Err(FunctionErrorKind::Dirty(format!(
-                "Function {} (calling internal function {}): Failed to disable known_hosts: {}",
-                "func", "nasl_ssh_connect", err
-            )));
-        }
-
-        if !key_type.is_empty() {
-            let option = SshOption::HostKeys(key_type.to_owned());
-            if let Err(err) = session.set_option(option) {
-                return Err(FunctionErrorKind::Dirty(
-                format!(
-                    "Function {} (calling internal function {}): Failed to set SSH key type '{}': {}", "func", "nasl_ssh_connect", key_type, err)
-                ));
-            }
-        }
-
-        if !csciphers.is_empty() {
-            let option = SshOption::CiphersCS(csciphers.to_owned());
-            if let Err(err) = session.set_option(option) {
-                return Err(FunctionErrorKind::Dirty(
-                format!(
-                    "Function {} (calling internal function {}): Failed to set SSH client to server ciphers '{}': {}", "func", "nasl_ssh_connect", csciphers, err)
-            ));
+        // sessions using the socket of a script are neither pooled nor reused
+        if sock <= 0 {
+            let pool = SessionPool::of(ctx)?;
+            if let Some(mut session) = pool.take(&options, |_| true, |x| x.session.is_connected()) {
+                let state = SshSessions::of(ctx);
+                let mut sessions = state.lock()?;
+                session.session_id = next_session_id(&sessions);
+                session.owner = Some(ctx.key().clone());
+                debug!(
+                    session_id = session.session_id,
+                    "Reusing SSH session to '{}' (port {})", ip_str, port
+                );
+                let session_id = session.session_id;
+                sessions.push(session);
+                return Ok(NaslValue::Number(session_id as i64));
             }
         }
 
-        if !scciphers.is_empty() {
-            let option = SshOption::CiphersSC(scciphers.to_owned());
-            if let Err(err) = session.set_option(option) {
-                return Err(FunctionErrorKind::Dirty(
-                format!(
-                    "Function {} (calling internal function {}): Failed to set SSH server to client ciphers '{}': {}", "func", "nasl_ssh_connect", scciphers, err)
-            ));
-            }
-        }
-
-        let valid_ports = 1..65535;
-        if valid_ports.contains(&port) {
-            let option = SshOption::Port(port);
-            if let Err(err) = session.set_option(option) {
-                return Err(FunctionErrorKind::Dirty(
-                format!(
-                    "Function {} (calling internal function {}) called from {}: Failed to set SSH port '{}': {}", "func", "nasl_ssh_connect", "key", port, err)
-            ));
-            }
-        }
+        let session = open_session(&options)?;
 
         let mut forced_sock = -1;
         if sock > 0 {
@@ -574,7 +870,12 @@ impl Ssh {
 
         match session.connect() {
             Ok(_) => {
-                let mut sessions = lock_sessions(&self.sessions)?;
+                if let Err(e) = verify_host_key(ctx, &session, &ip_str, port) {
+                    session.disconnect();
+                    return Err(e);
+                }
+                let state = SshSessions::of(ctx);
+                let mut sessions = state.lock()?;
 
                 let session_id = next_session_id(&sessions);
 
@@ -585,6 +886,9 @@ impl Ssh {
                     authmethods_valid: false,
                     user_set: false,
                     channel: None,
+                    owner: Some(ctx.key().clone()),
+                    options: (sock <= 0).then_some(options),
+                    credential: None,
                 };
 
                 sessions.push(s);
@@ -614,7 +918,7 @@ impl Ssh {
     fn nasl_ssh_disconnect(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -626,15 +930,16 @@ impl Ssh {
 
         match &positional[0] {
             NaslValue::Number(session_id) => {
-                let mut sessions = lock_sessions(&self.sessions)?;
+                let state = SshSessions::of(ctx);
+                let mut sessions = state.lock()?;
                 match sessions
                     .iter()
                     .enumerate()
                     .find(|(_i, s)| s.session_id == *session_id as i32)
                 {
-                    Some((i, s)) => {
-                        s.session.disconnect();
-                        sessions.remove(i);
+                    Some((i, _)) => {
+                        let session = sessions.remove(i);
+                        SessionPool::of(ctx)?.release_session(session);
                         Ok(NaslValue::Null)
                     }
                     _ => Err(FunctionErrorKind::Diagnostic(
@@ -727,7 +1032,7 @@ impl Ssh {
     fn nasl_ssh_set_login(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -747,11 +1052,12 @@ impl Ssh {
         };
 
         let login = match register.named("login") {
-            Some(ContextType::Value(NaslValue::String(x))) => Some(x.to_owned()),
+            Some(ContextType::Value(NaslValue::String(x))) => Some(x.to_string()),
             _ => return Err(FunctionErrorKind::missing_argument("login")),
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -816,7 +1122,7 @@ impl Ssh {
     fn nasl_ssh_userauth(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -859,13 +1165,21 @@ impl Ssh {
             )));
         }
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let credential = credential_hash(&[login.as_deref(), password, privatekey, passphrase]);
+
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
             .find(|(_i, s)| s.session_id == session_id)
         {
             Some((_i, session)) => {
+                // a reused session may already be authenticated
+                switch_session(ctx, session, &credential)?;
+                if session.credential.as_ref() == Some(&credential) {
+                    return Ok(NaslValue::Number(0));
+                }
                 if !session.user_set {
                     set_opt_user(session, login, session_id)?;
                 }
@@ -893,6 +1207,7 @@ impl Ssh {
                 if password.is_some() && methods.contains(AuthMethods::PASSWORD) {
                     match session.session.userauth_password(None, password) {
                         Ok(AuthStatus::Success) => {
+                            session.credential = Some(credential);
                             return Ok(NaslValue::Number(0));
                         }
                         Ok(_) => {
@@ -945,6 +1260,7 @@ impl Ssh {
                                     .userauth_keyboard_interactive_set_answers(&answers)
                                 {
                                     Ok(_) => {
+                                        session.credential = Some(credential);
                                         return Ok(NaslValue::Number(0));
                                     }
                                     Err(_) => break,
@@ -974,6 +1290,7 @@ impl Ssh {
                             Ok(AuthStatus::Success) => {
                                 match session.session.userauth_publickey(None, &k) {
                                     Ok(AuthStatus::Success) => {
+                                        session.credential = Some(credential);
                                         return Ok(NaslValue::Number(0));
                                     }
                                     _ => {
@@ -1045,7 +1362,7 @@ impl Ssh {
     fn nasl_ssh_request_exec(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1079,7 +1396,8 @@ impl Ssh {
             _ => -1,
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1113,7 +1431,7 @@ impl Ssh {
                 if compat_mode {
                     response.push_str(&compat_buf)
                 }
-                Ok(NaslValue::String(response))
+                Ok(NaslValue::String(response.into()))
             }
             _ => Err(FunctionErrorKind::Diagnostic(
                 format!("Session ID {} not found", session_id),
@@ -1136,7 +1454,7 @@ impl Ssh {
     fn nasl_ssh_shell_open(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1160,7 +1478,8 @@ impl Ssh {
             _ => false,
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1212,7 +1531,7 @@ impl Ssh {
     fn nasl_ssh_shell_read(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1236,7 +1555,8 @@ impl Ssh {
             _ => Duration::from_secs(0),
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1266,7 +1586,7 @@ impl Ssh {
                     return Ok(NaslValue::Null);
                 }
 
-                Ok(NaslValue::String(response))
+                Ok(NaslValue::String(response.into()))
             }
             _ => Err(FunctionErrorKind::Dirty(format!(
                 "Session ID {} not found",
@@ -1288,7 +1608,7 @@ impl Ssh {
     fn nasl_ssh_shell_write(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1309,7 +1629,7 @@ impl Ssh {
 
         let binding;
         let cmd = match register.named("cmd") {
-            Some(ContextType::Value(NaslValue::String(x))) => x,
+            Some(ContextType::Value(NaslValue::String(x))) => x.as_str(),
             Some(ContextType::Value(NaslValue::Data(x))) => {
                 binding = x.iter().map(|x| *x as char).collect::<String>();
                 &binding
@@ -1317,7 +1637,8 @@ impl Ssh {
             _ => return Err(FunctionErrorKind::missing_argument("cmd")),
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1357,7 +1678,7 @@ impl Ssh {
     fn nasl_ssh_shell_close(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1376,7 +1697,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1418,7 +1740,7 @@ impl Ssh {
     fn nasl_ssh_login_interactive(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1438,11 +1760,12 @@ impl Ssh {
         };
 
         let login = match register.named("login") {
-            Some(ContextType::Value(NaslValue::String(x))) => Some(x.to_owned()),
+            Some(ContextType::Value(NaslValue::String(x))) => Some(x.to_string()),
             _ => return Err(FunctionErrorKind::missing_argument("login")),
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1506,7 +1829,7 @@ impl Ssh {
                             }
                         }
                     }
-                    return Ok(NaslValue::String(prompt));
+                    return Ok(NaslValue::String(prompt.into()));
                 }
                 Ok(NaslValue::Null)
             }
@@ -1539,7 +1862,7 @@ impl Ssh {
     fn nasl_ssh_login_interactive_pass(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1563,7 +1886,8 @@ impl Ssh {
             _ => return Err(FunctionErrorKind::missing_argument("pass")),
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1651,7 +1975,7 @@ impl Ssh {
     fn nasl_ssh_get_issue_banner(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1670,7 +1994,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1687,7 +2012,7 @@ impl Ssh {
                 }
 
                 match session.session.get_issue_banner() {
-                    Ok(b) => Ok(NaslValue::String(b)),
+                    Ok(b) => Ok(NaslValue::String(b.into())),
                     Err(_) => Ok(NaslValue::Null),
                 }
             }
@@ -1711,7 +2036,7 @@ impl Ssh {
     fn nasl_ssh_get_server_banner(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1730,7 +2055,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1738,7 +2064,7 @@ impl Ssh {
         {
             // TODO: Check with openvas-nasl why the outputs doesn't match
             Some((_i, session)) => match session.session.get_server_banner() {
-                Ok(b) => Ok(NaslValue::String(b)),
+                Ok(b) => Ok(NaslValue::String(b.into())),
                 Err(_) => Ok(NaslValue::Null),
             },
             _ => Err(FunctionErrorKind::Dirty(format!(
@@ -1763,7 +2089,7 @@ impl Ssh {
     fn nasl_ssh_get_auth_methods(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1782,7 +2108,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1818,7 +2145,7 @@ impl Ssh {
                 if methods.is_empty() {
                     return Ok(NaslValue::Null);
                 }
-                Ok(NaslValue::String(methods.join(",")))
+                Ok(NaslValue::String(methods.join(",").into()))
             }
             _ => Err(FunctionErrorKind::Dirty(format!(
                 "Session ID {} not found",
@@ -1839,7 +2166,7 @@ impl Ssh {
     fn nasl_ssh_get_host_key(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1858,7 +2185,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1866,7 +2194,7 @@ impl Ssh {
         {
             Some((_i, session)) => match session.session.get_server_public_key() {
                 Ok(s) => match s.get_public_key_hash_hexa(libssh_rs::PublicKeyHashType::Md5) {
-                    Ok(hash) => Ok(NaslValue::String(hash)),
+                    Ok(hash) => Ok(NaslValue::String(hash.into())),
                     Err(_) => Ok(NaslValue::Null),
                 },
                 Err(_) => Err(FunctionErrorKind::Diagnostic(
@@ -1893,7 +2221,7 @@ impl Ssh {
     fn nasl_sftp_enabled_check(
         &self,
         register: &Register,
-        _: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1912,7 +2240,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -1948,7 +2277,7 @@ impl Ssh {
     fn nasl_ssh_execute_netconf_subsystem(
         &self,
         register: &Register,
-        _ctx: &Context,
+        ctx: &Context,
     ) -> Result<NaslValue, FunctionErrorKind> {
         let positional = register.positional();
         if positional.is_empty() {
@@ -1967,7 +2296,8 @@ impl Ssh {
             }
         };
 
-        let mut sessions = lock_sessions(&self.sessions)?;
+        let state = SshSessions::of(ctx);
+        let mut sessions = state.lock()?;
         match sessions
             .iter_mut()
             .enumerate()
@@ -2014,6 +2344,89 @@ impl Ssh {
             ))),
         }
     }
+
+    /// Gathers the installed packages of the target as input of notus.
+    ///
+    /// The distribution is detected via /etc/os-release and the packages are listed by dpkg, rpm
+    /// or apk. The notus product, the package manager and the packages separated by new lines
+    /// are stored within the KB as ssh/login/release_notus, ssh/login/package_manager_notus and
+    /// ssh/login/package_list_notus.
+    ///
+    /// nasl params
+    ///
+    /// - An SSH session id.
+    ///
+    /// nasl named params
+    ///
+    /// - notus: When 1, the default, and notus is available to the scan, the packages are
+    ///   checked directly and the vulnerable packages are reported.
+    ///
+    /// @naslret The notus product of the target or NULL when the distribution is not supported.
+    fn nasl_ssh_gather_package_list(
+        &self,
+        register: &Register,
+        ctx: &Context,
+    ) -> Result<NaslValue, FunctionErrorKind> {
+        let positional = register.positional();
+        let session_id = match positional.first() {
+            Some(NaslValue::Number(x)) => *x as i32,
+            Some(_) => {
+                return Err(FunctionErrorKind::WrongArgument(
+                    ("Invalid session ID").to_string(),
+                ))
+            }
+            None => {
+                return Err(FunctionErrorKind::MissingPositionalArguments {
+                    expected: 1,
+                    got: 0,
+                })
+            }
+        };
+        let notus = match register.named("notus") {
+            Some(ContextType::Value(NaslValue::Number(x))) => *x != 0,
+            _ => true,
+        };
+
+        let state = SshSessions::of(ctx);
+        let (release, manager, packages) = {
+            let mut sessions = state.lock()?;
+            let session = sessions
+                .iter_mut()
+                .find(|s| s.session_id == session_id)
+                .ok_or_else(|| {
+                    FunctionErrorKind::Diagnostic(
+                        format!("Session ID {} not found", session_id),
+                        Some(NaslValue::Null),
+                    )
+                })?;
+            let (output, _) = exec_ssh_cmd(session, gather::OS_RELEASE_COMMAND, false, 1, 0)?;
+            let Some(release) = OsRelease::parse(&output) else {
+                debug!(session_id, "Unable to detect the distribution");
+                return Ok(NaslValue::Null);
+            };
+            let Some(manager) = release.package_manager() else {
+                debug!(session_id, id = %release.id, "Unsupported distribution");
+                return Ok(NaslValue::Null);
+            };
+            let (output, _) = exec_ssh_cmd(session, manager.command(), false, 1, 0)?;
+            (release, manager, manager.parse(&output))
+        };
+
+        let product = release.product();
+        set_gathered(ctx, gather::KB_RELEASE, product.clone())?;
+        set_gathered(
+            ctx,
+            gather::KB_PACKAGE_MANAGER,
+            manager.as_str().to_string(),
+        )?;
+        set_gathered(ctx, gather::KB_PACKAGE_LIST, packages.join("\n"))?;
+        if notus {
+            if let Some(scanner) = ctx.extensions().get::<Arc<dyn PackageScanner>>() {
+                report_vulnerable_packages(ctx, &**scanner, &product, &packages)?;
+            }
+        }
+        Ok(NaslValue::String(product.into()))
+    }
 }
 
 function_set! {
@@ -2039,5 +2452,37 @@ function_set! {
         Ssh::nasl_ssh_get_host_key,
         Ssh::nasl_sftp_enabled_check,
         Ssh::nasl_ssh_execute_netconf_subsystem,
+        Ssh::nasl_ssh_gather_package_list,
     )
 }
+
+#[async_trait]
+impl Plugin for Ssh {
+    /// Verifies the host key policy and the session pool configuration of the scan.
+    async fn init_scan(&mut self, config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        HostKeyVerifier::from_config(config)?;
+        SessionPool::from_config(config, drop)?;
+        Ok(())
+    }
+
+    /// Releases the sessions the script did not disconnect itself into the pool.
+    async fn finish_script(&mut self, context: &Context<'_>) {
+        let Some(state) = context.extensions().get::<SshSessions>() else {
+            return;
+        };
+        let owned: Vec<_> = match state.lock() {
+            Ok(mut sessions) => {
+                let (owned, others) = sessions
+                    .drain(..)
+                    .partition(|x| x.owner.as_ref() == Some(context.key()));
+                *sessions = others;
+                owned
+            }
+            Err(_) => return,
+        };
+        match SessionPool::of(context) {
+            Ok(pool) => owned.into_iter().for_each(|x| pool.release_session(x)),
+            Err(_) => owned.into_iter().for_each(|x| x.session.disconnect()),
+        }
+    }
+}
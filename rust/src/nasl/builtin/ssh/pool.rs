@@ -0,0 +1,223 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Keeps the authenticated SSH sessions of a scan to reuse them within later scripts.
+//!
+//! Connecting and authenticating dominates the runtime of authenticated scans, as most VTs open
+//! a session just to run a few commands. Instead of disconnecting a session at the end of a
+//! script it is kept idle within the pool of the scan, and a later script connecting to the same
+//! host with the same options gets the idle session instead of a new connection.
+//!
+//! The pool is configured via the scan preferences `ssh_session_pool_size`, the maximum of idle
+//! sessions of a scan, and `ssh_session_idle_timeout`, the seconds after which an idle session is
+//! disconnected. A size of 0 disables the pool.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::nasl::utils::{FunctionErrorKind, PluginConfig};
+
+/// Scan preference id of the maximum of idle sessions
+pub const POOL_SIZE: &str = "ssh_session_pool_size";
+/// Scan preference id of the seconds an idle session is kept
+pub const POOL_IDLE_TIMEOUT: &str = "ssh_session_idle_timeout";
+
+const DEFAULT_POOL_SIZE: usize = 8;
+const DEFAULT_IDLE_TIMEOUT: u64 = 60;
+
+struct Idle<K, T> {
+    key: K,
+    value: T,
+    since: Instant,
+}
+
+/// Holds idle values by key until they are taken again, expire or exceed the size of the pool.
+///
+/// Values that are not taken anymore are handed to close.
+pub struct Pool<K, T> {
+    idle: Mutex<VecDeque<Idle<K, T>>>,
+    size: usize,
+    idle_timeout: Duration,
+    close: Box<dyn Fn(T) + Send + Sync>,
+}
+
+impl<K: PartialEq, T> Pool<K, T> {
+    /// Creates a new pool
+    pub fn new<F>(size: usize, idle_timeout: Duration, close: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        Self {
+            idle: Mutex::default(),
+            size,
+            idle_timeout,
+            close: Box::new(close),
+        }
+    }
+
+    /// Creates a pool configured by the scan preferences
+    pub fn from_config<F>(config: &PluginConfig, close: F) -> Result<Self, FunctionErrorKind>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let size = config.get(POOL_SIZE)?.unwrap_or(DEFAULT_POOL_SIZE);
+        let idle_timeout = config
+            .get(POOL_IDLE_TIMEOUT)?
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT);
+        Ok(Self::new(size, Duration::from_secs(idle_timeout), close))
+    }
+
+    /// Returns true when values are kept
+    pub fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    /// Closes the expired values
+    fn expire(&self, idle: &mut VecDeque<Idle<K, T>>) {
+        let now = Instant::now();
+        // the oldest values are at the front
+        while idle
+            .front()
+            .is_some_and(|x| now.duration_since(x.since) >= self.idle_timeout)
+        {
+            if let Some(x) = idle.pop_front() {
+                (self.close)(x.value);
+            }
+        }
+    }
+
+    /// Takes the most recently released value of key that is accepted.
+    ///
+    /// Values of key that are not usable anymore, e.g. closed connections, are closed on the way.
+    pub fn take<A, U>(&self, key: &K, accept: A, usable: U) -> Option<T>
+    where
+        A: Fn(&T) -> bool,
+        U: Fn(&T) -> bool,
+    {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        self.expire(&mut idle);
+        let mut i = idle.len();
+        while i > 0 {
+            i -= 1;
+            if &idle[i].key != key {
+                continue;
+            }
+            if !usable(&idle[i].value) {
+                if let Some(x) = idle.remove(i) {
+                    (self.close)(x.value);
+                }
+                continue;
+            }
+            if accept(&idle[i].value) {
+                return idle.remove(i).map(|x| x.value);
+            }
+        }
+        None
+    }
+
+    /// Keeps value until it is taken again, the oldest value is closed when the pool is full.
+    pub fn release(&self, key: K, value: T) {
+        if !self.is_enabled() {
+            (self.close)(value);
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        self.expire(&mut idle);
+        while idle.len() >= self.size {
+            if let Some(x) = idle.pop_front() {
+                (self.close)(x.value);
+            }
+        }
+        idle.push_back(Idle {
+            key,
+            value,
+            since: Instant::now(),
+        });
+    }
+}
+
+impl<K, T> Drop for Pool<K, T> {
+    fn drop(&mut self) {
+        if let Ok(idle) = self.idle.get_mut() {
+            for x in idle.drain(..) {
+                (self.close)(x.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn new_pool(
+        size: usize,
+        idle_timeout: Duration,
+    ) -> (Pool<&'static str, u32>, Arc<Mutex<Vec<u32>>>) {
+        let closed = Arc::new(Mutex::new(vec![]));
+        let c = closed.clone();
+        let pool = Pool::new(size, idle_timeout, move |x| c.lock().unwrap().push(x));
+        (pool, closed)
+    }
+
+    #[test]
+    fn reuse() {
+        let (pool, closed) = new_pool(3, Duration::from_secs(60));
+        pool.release("a", 1);
+        pool.release("a", 2);
+        pool.release("b", 3);
+        assert_eq!(pool.take(&"a", |_| true, |_| true), Some(2));
+        assert_eq!(pool.take(&"a", |x| *x != 1, |_| true), None);
+        assert_eq!(pool.take(&"c", |_| true, |_| true), None);
+        // unusable values are closed
+        assert_eq!(pool.take(&"b", |_| true, |x| *x != 3), None);
+        assert_eq!(*closed.lock().unwrap(), vec![3]);
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+
+        // the oldest value is closed when the pool is full
+        pool.release("b", 4);
+        pool.release("b", 5);
+        pool.release("b", 6);
+        assert_eq!(*closed.lock().unwrap(), vec![3, 1]);
+        drop(pool);
+        assert_eq!(*closed.lock().unwrap(), vec![3, 1, 4, 5, 6]);
+    }
+
+    #[test]
+    fn expire_and_disable() {
+        let (pool, closed) = new_pool(3, Duration::ZERO);
+        pool.release("a", 1);
+        assert_eq!(pool.take(&"a", |_| true, |_| true), None);
+        assert_eq!(*closed.lock().unwrap(), vec![1]);
+
+        let (pool, closed) = new_pool(0, Duration::from_secs(60));
+        assert!(!pool.is_enabled());
+        pool.release("a", 1);
+        assert!(pool.idle.lock().unwrap().is_empty());
+        assert_eq!(*closed.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn from_config() {
+        let config = |values: &[(&str, &str)]| {
+            let preferences: Vec<_> = values
+                .iter()
+                .map(|(id, value)| crate::models::ScanPreference {
+                    id: id.to_string(),
+                    value: value.to_string(),
+                })
+                .collect();
+            Pool::<u32, u32>::from_config(&PluginConfig::new("scan", &preferences), drop)
+        };
+        assert_eq!(config(&[]).unwrap().size, DEFAULT_POOL_SIZE);
+        let pool = config(&[(POOL_SIZE, "0"), (POOL_IDLE_TIMEOUT, "5")]).unwrap();
+        assert_eq!((pool.size, pool.idle_timeout), (0, Duration::from_secs(5)));
+        assert!(config(&[(POOL_SIZE, "-1")]).is_err());
+    }
+}
@@ -6,6 +6,25 @@
 
 use libssh_rs::{AuthMethods, Channel, Session};
 
+use crate::storage::ContextKey;
+
+/// The options a session is connected with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectOptions {
+    /// Address of the server
+    pub host: String,
+    /// Port of the server, 0 is the default port
+    pub port: u16,
+    /// Connection timeout in seconds, 0 is the default of libssh
+    pub timeout: i64,
+    /// Preferred host key types
+    pub key_type: String,
+    /// Client to server ciphers
+    pub csciphers: String,
+    /// Server to client ciphers
+    pub scciphers: String,
+}
+
 /// Structure to hold an SSH Session
 pub struct SshSession {
     /// Session ID
@@ -20,6 +39,12 @@ pub struct SshSession {
     pub user_set: bool,
     /// Channel
     pub channel: Option<Channel>,
+    /// The script that opened the session
+    pub owner: Option<ContextKey>,
+    /// The options of the connection, None when the connection of a script is used
+    pub options: Option<ConnectOptions>,
+    /// Hash of the credentials the session is authenticated with
+    pub credential: Option<Vec<u8>>,
 }
 
 impl Default for SshSession {
@@ -32,6 +57,9 @@ impl Default for SshSession {
                 authmethods_valid: false,
                 user_set: false,
                 channel: None,
+                owner: None,
+                options: None,
+                credential: None,
             }
         }
     }
@@ -0,0 +1,216 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines a builtin enumerating the algorithms an SSH server offers.
+//!
+//! `ssh_kex_algorithms` only exchanges the identification strings and receives the KEXINIT of the
+//! server, no key exchange is performed and no authentication is attempted. Unlike the ssh
+//! session functions it does not depend on libssh and works for servers libssh refuses to
+//! negotiate with, e.g. servers offering only deprecated algorithms.
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    time::Duration,
+};
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::TrafficCounter;
+
+use super::network::network_utils::{connect_target, timeout_arg};
+use super::network::verify_port;
+
+/// The identification string sent to the server
+const IDENTIFICATION: &str = "SSH-2.0-OpenVAS\r\n";
+const SSH_MSG_KEXINIT: u8 = 20;
+/// Maximum length of a packet RFC 4253 requires implementations to handle
+const MAX_PACKET: usize = 35000;
+/// Maximum of lines a server may send before its identification string
+const MAX_PRE_BANNER_LINES: usize = 50;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The name-lists of a KEXINIT in the order of RFC 4253
+const NAME_LISTS: [&str; 10] = [
+    "kex_algorithms",
+    "server_host_key_algorithms",
+    "encryption_algorithms_client_to_server",
+    "encryption_algorithms_server_to_client",
+    "mac_algorithms_client_to_server",
+    "mac_algorithms_server_to_client",
+    "compression_algorithms_client_to_server",
+    "compression_algorithms_server_to_client",
+    "languages_client_to_server",
+    "languages_server_to_client",
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// The algorithms offered by a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KexInit {
+    /// The identification string of the server without the line break
+    banner: String,
+    /// The name-lists in the order of `NAME_LISTS`
+    lists: Vec<Vec<String>>,
+    first_kex_packet_follows: bool,
+}
+
+impl KexInit {
+    /// Parses the payload of a KEXINIT message.
+    fn parse(banner: String, payload: &[u8]) -> io::Result<Self> {
+        let truncated = || invalid("truncated KEXINIT");
+        // message id and cookie
+        let mut rest = payload.get(17..).ok_or_else(truncated)?;
+        let mut lists = Vec::with_capacity(NAME_LISTS.len());
+        for _ in NAME_LISTS {
+            let len = rest.get(..4).ok_or_else(truncated)?;
+            let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            let list = rest.get(4..4 + len).ok_or_else(truncated)?;
+            let list = String::from_utf8_lossy(list);
+            lists.push(
+                list.split(',')
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            );
+            rest = &rest[4 + len..];
+        }
+        // first_kex_packet_follows and the reserved uint32
+        let rest = rest.get(..5).ok_or_else(truncated)?;
+        Ok(Self {
+            banner,
+            lists,
+            first_kex_packet_follows: rest[0] != 0,
+        })
+    }
+}
+
+impl From<KexInit> for NaslValue {
+    fn from(value: KexInit) -> Self {
+        let mut dict: HashMap<String, NaslValue> = NAME_LISTS
+            .iter()
+            .zip(value.lists)
+            .map(|(name, list)| {
+                (
+                    name.to_string(),
+                    NaslValue::Array(
+                        list.into_iter()
+                            .map(|x| NaslValue::String(x.into()))
+                            .collect(),
+                    ),
+                )
+            })
+            .collect();
+        dict.insert("banner".to_string(), NaslValue::String(value.banner.into()));
+        dict.insert(
+            "first_kex_packet_follows".to_string(),
+            NaslValue::Boolean(value.first_kex_packet_follows),
+        );
+        NaslValue::Dict(dict)
+    }
+}
+
+/// Reads the identification string, skipping the lines a server may send before it.
+fn read_identification<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    for _ in 0..MAX_PRE_BANNER_LINES {
+        let mut line = vec![];
+        if reader.by_ref().take(256).read_until(b'\n', &mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.starts_with("SSH-") {
+            return Ok(line.to_owned());
+        }
+    }
+    Err(invalid("no SSH identification string"))
+}
+
+/// Reads unencrypted binary packets until the KEXINIT and returns its payload.
+fn read_kexinit<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    loop {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let padding = header[4] as usize;
+        if len > MAX_PACKET || padding + 1 > len {
+            return Err(invalid("invalid SSH packet length"));
+        }
+        let mut packet = vec![0; len - 1];
+        reader.read_exact(&mut packet)?;
+        packet.truncate(len - 1 - padding);
+        // servers may send SSH_MSG_IGNORE or SSH_MSG_DEBUG before the KEXINIT
+        if packet.first() == Some(&SSH_MSG_KEXINIT) {
+            return Ok(packet);
+        }
+    }
+}
+
+/// Exchanges the identification strings and returns the KEXINIT of the server.
+fn exchange<S: Read + Write>(
+    stream: &mut S,
+    traffic: Option<&TrafficCounter>,
+) -> Result<KexInit, FunctionErrorKind> {
+    stream.write_all(IDENTIFICATION.as_bytes())?;
+    if let Some(traffic) = traffic {
+        traffic.sent(IDENTIFICATION.len())?;
+    }
+    let mut reader = BufReader::new(stream);
+    let banner = read_identification(&mut reader)?;
+    if !banner.starts_with("SSH-2.0-") && !banner.starts_with("SSH-1.99-") {
+        return Err(invalid("the server does not support SSH 2").into());
+    }
+    let payload = read_kexinit(&mut reader)?;
+    if let Some(traffic) = traffic {
+        traffic.received(banner.len() + 2 + payload.len())?;
+    }
+    Ok(KexInit::parse(banner, &payload)?)
+}
+
+/// *array* **ssh_kex_algorithms**(port: *int*, timeout: *int*);
+///
+/// Connects to the SSH server on the port of the target and returns the algorithms it offers
+/// within its KEXINIT, without performing a key exchange or authenticating.
+///
+/// The returned array contains the identification string of the server as `banner`, the
+/// name-lists `kex_algorithms`, `server_host_key_algorithms`,
+/// `encryption_algorithms_client_to_server`, `encryption_algorithms_server_to_client`,
+/// `mac_algorithms_client_to_server`, `mac_algorithms_server_to_client`,
+/// `compression_algorithms_client_to_server`, `compression_algorithms_server_to_client`,
+/// `languages_client_to_server` and `languages_server_to_client` as arrays of algorithm names and
+/// `first_kex_packet_follows`. NULL is returned when the connection fails or the server does not
+/// speak SSH 2.
+#[nasl_function(named(port, timeout))]
+async fn ssh_kex_algorithms(
+    context: &Context<'_>,
+    port: i64,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, _permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(NaslValue::Null);
+    };
+    match exchange(&mut stream, context.traffic()) {
+        Ok(x) => Ok(x.into()),
+        Err(FunctionErrorKind::IOError(error)) => {
+            tracing::debug!(port, %error, "unable to receive the SSH KEXINIT");
+            Ok(NaslValue::Null)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub struct SshKex;
+
+function_set! {
+    SshKex,
+    async_stateless,
+    (ssh_kex_algorithms)
+}
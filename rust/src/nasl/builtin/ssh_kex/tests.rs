@@ -0,0 +1,101 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::super::{read_identification, read_kexinit, KexInit, NAME_LISTS};
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+
+    /// Builds an unencrypted packet with the payload.
+    fn packet(payload: &[u8]) -> Vec<u8> {
+        let padding = 8 - (payload.len() + 5) % 8 + 4;
+        let mut packet = ((payload.len() + padding + 1) as u32)
+            .to_be_bytes()
+            .to_vec();
+        packet.push(padding as u8);
+        packet.extend_from_slice(payload);
+        packet.resize(packet.len() + padding, 0);
+        packet
+    }
+
+    fn kexinit(lists: &[&str]) -> Vec<u8> {
+        let mut payload = vec![20];
+        payload.extend_from_slice(&[0x42; 16]);
+        for list in lists {
+            payload.extend_from_slice(&(list.len() as u32).to_be_bytes());
+            payload.extend_from_slice(list.as_bytes());
+        }
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+        payload
+    }
+
+    const LISTS: [&str; 10] = [
+        "curve25519-sha256,diffie-hellman-group1-sha1",
+        "ssh-ed25519,ssh-dss",
+        "aes128-ctr,3des-cbc",
+        "aes128-ctr",
+        "hmac-sha2-256,hmac-md5",
+        "hmac-sha2-256",
+        "none,zlib@openssh.com",
+        "none",
+        "",
+        "",
+    ];
+
+    #[test]
+    fn parse() {
+        let mut data = b"Welcome\r\nSSH-2.0-OpenSSH_9.2 Debian\r\n".to_vec();
+        // SSH_MSG_IGNORE before the KEXINIT
+        data.extend(packet(&[2, 0, 0, 0, 0]));
+        data.extend(packet(&kexinit(&LISTS)));
+        let mut reader = &data[..];
+        let banner = read_identification(&mut reader).unwrap();
+        assert_eq!(banner, "SSH-2.0-OpenSSH_9.2 Debian");
+        let kex = KexInit::parse(banner, &read_kexinit(&mut reader).unwrap()).unwrap();
+        assert_eq!(kex.lists.len(), NAME_LISTS.len());
+        assert_eq!(
+            kex.lists[0],
+            vec!["curve25519-sha256", "diffie-hellman-group1-sha1"]
+        );
+        assert_eq!(kex.lists[6], vec!["none", "zlib@openssh.com"]);
+        assert!(kex.lists[8].is_empty());
+        assert!(!kex.first_kex_packet_follows);
+
+        let payload = kexinit(&LISTS);
+        assert!(KexInit::parse(String::new(), &payload[..payload.len() - 1]).is_err());
+        assert!(read_kexinit(&mut &[0xff, 0, 0, 0, 4][..]).is_err());
+    }
+
+    #[test]
+    fn algorithms() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut answer = b"SSH-2.0-dropbear_2020.81\r\n".to_vec();
+            answer.extend(packet(&kexinit(&LISTS)));
+            stream.write_all(&answer).unwrap();
+            let mut identification = [0; 17];
+            stream.read_exact(&mut identification).unwrap();
+            assert_eq!(&identification, b"SSH-2.0-OpenVAS\r\n");
+        });
+
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.run(&format!("a = ssh_kex_algorithms(port: {port});"));
+        t.ok(r#"a["banner"];"#, "SSH-2.0-dropbear_2020.81");
+        t.run(r#"ciphers = a["encryption_algorithms_client_to_server"];"#);
+        t.ok("ciphers[1];", "3des-cbc");
+        t.ok(r#"max_index(a["server_host_key_algorithms"]);"#, 2);
+        t.ok(r#"max_index(a["languages_server_to_client"]);"#, 0);
+        t.ok(r#"a["first_kex_packet_follows"];"#, false);
+    }
+}
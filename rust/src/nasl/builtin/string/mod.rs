@@ -14,14 +14,21 @@ use crate::nasl::utils::{
 use core::fmt::Write;
 use glob::{MatchOptions, Pattern};
 use nasl_function_proc_macro::nasl_function;
-use std::num::ParseIntError;
 
 use crate::function_set;
 use crate::nasl::syntax::NaslValue;
 
-/// `Some(string)` if constructed from either a `NaslValue::String`
-/// or `NaslValue::Data`.
-struct StringOrData(String);
+/// The bytes of either a `NaslValue::String` or a `NaslValue::Data`.
+struct StringOrData(Vec<u8>);
+
+/// Returns the bytes as a string when they are valid utf8, otherwise as data.
+///
+/// This keeps binary data intact while text results stay strings.
+fn bytes_to_value(bytes: Vec<u8>) -> NaslValue {
+    String::from_utf8(bytes)
+        .map(NaslValue::from)
+        .unwrap_or_else(|e| NaslValue::Data(e.into_bytes()))
+}
 
 fn bytes_to_str(bytes: &[u8]) -> String {
     bytes.iter().map(|x| *x as char).collect::<String>()
@@ -30,8 +37,8 @@ fn bytes_to_str(bytes: &[u8]) -> String {
 impl<'a> FromNaslValue<'a> for StringOrData {
     fn from_nasl_value(value: &'a NaslValue) -> Result<Self, FunctionErrorKind> {
         match value {
-            NaslValue::String(string) => Ok(Self(string.clone())),
-            NaslValue::Data(buffer) => Ok(Self(bytes_to_str(buffer))),
+            NaslValue::String(string) => Ok(Self(string.as_bytes().to_vec())),
+            NaslValue::Data(buffer) => Ok(Self(buffer.clone())),
             _ => Err(FunctionErrorKind::WrongArgument(
                 "Expected string or byte buffer.".to_string(),
             )),
@@ -40,10 +47,19 @@ impl<'a> FromNaslValue<'a> for StringOrData {
 }
 
 /// Decodes given string as hex and returns the result as a byte array
-pub fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
-    (0..s.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+///
+/// Returns None when the string has an odd length or contains a non hex character.
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|x| {
+            std::str::from_utf8(x)
+                .ok()
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+        })
         .collect()
 }
 
@@ -56,6 +72,22 @@ pub fn encode_hex(bytes: &[u8]) -> String {
         .join("")
 }
 
+/// Returns the bytes of a string or data value, other values are converted into a string.
+fn value_bytes(value: &NaslValue) -> Vec<u8> {
+    match value.as_data() {
+        Some(x) => x.to_vec(),
+        None => value.to_string().into_bytes(),
+    }
+}
+
+/// Returns the index of the first occurrence of needle within haystack.
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
+
 fn append_nasl_value_as_u8(data: &mut Vec<u8>, p: &NaslValue) {
     match p {
         NaslValue::String(s) => {
@@ -85,6 +117,9 @@ fn append_nasl_value_as_u8(data: &mut Vec<u8>, p: &NaslValue) {
 }
 
 /// NASL function to parse numeric values into characters and combine with additional values
+///
+/// Numbers are truncated to their lowest byte, e.g. 0x1FF becomes 0xFF and -1 becomes 0xFF.
+/// Strings and data are appended as they are.
 #[nasl_function]
 fn raw_string(positional: CheckedPositionals<&NaslValue>) -> Vec<u8> {
     let mut data: Vec<u8> = vec![];
@@ -165,23 +200,25 @@ fn write_nasl_string_value(s: &mut String, value: &NaslValue) -> Result<(), Func
 
 /// NASL function to return uppercase equivalent of a given string
 ///
+/// Only ASCII letters are converted, all other bytes are kept.
 /// If this function retrieves anything but a string it returns NULL
 #[nasl_function]
-fn toupper(s: Option<Maybe<StringOrData>>) -> Option<String> {
+fn toupper(s: Option<Maybe<StringOrData>>) -> Option<NaslValue> {
     s.and_then(Maybe::as_option)
-        .map(|inner| inner.0.to_uppercase())
+        .map(|inner| bytes_to_value(inner.0.to_ascii_uppercase()))
 }
 
 /// NASL function to return lowercase equivalent of a given string
 ///
+/// Only ASCII letters are converted, all other bytes are kept.
 /// If this function retrieves anything but a string it returns NULL
 #[nasl_function]
-fn tolower(s: Option<Maybe<StringOrData>>) -> Option<String> {
+fn tolower(s: Option<Maybe<StringOrData>>) -> Option<NaslValue> {
     s.and_then(Maybe::as_option)
-        .map(|inner| inner.0.to_lowercase())
+        .map(|inner| bytes_to_value(inner.0.to_ascii_lowercase()))
 }
 
-/// NASL function to return the length of string
+/// NASL function to return the length of string in bytes
 ///
 /// If this function retrieves anything but a string it returns 0
 #[nasl_function]
@@ -196,20 +233,16 @@ fn strlen(s: Option<Maybe<StringOrData>>) -> usize {
 /// The first positional argument is the *string* to get the slice from.
 /// As a second positional argument an *int* that contains the start index for the slice is required.
 /// The optional third positional argument is an *int* and contains the end index for the slice.
-/// If not given it is set to the end of the string.
-/// If the start integer is higher than the value of the string NULL is returned.
+/// If not given or higher than the length it is set to the end of the string.
+/// If the start integer is higher than the length of the string or the end NULL is returned.
+/// The indices are byte offsets.
 #[nasl_function]
-fn substr(s: StringOrData, start: usize, end: Option<usize>) -> Option<String> {
-    if start > s.0.len() {
+fn substr(s: StringOrData, start: usize, end: Option<usize>) -> Option<NaslValue> {
+    let end = end.unwrap_or(s.0.len()).min(s.0.len());
+    if start > end {
         None
     } else {
-        Some(
-            match end {
-                Some(end) => &s.0[start..end],
-                None => &s.0[start..],
-            }
-            .into(),
-        )
+        Some(bytes_to_value(s.0[start..end].to_vec()))
     }
 }
 
@@ -242,7 +275,7 @@ fn hex(s: i64) -> String {
 fn hexstr_to_data(s: NaslValue) -> Result<Vec<u8>, FunctionErrorKind> {
     let s = s.to_string();
     let s = s.as_str();
-    decode_hex(s).map_err(|_| {
+    decode_hex(s).ok_or_else(|| {
         FunctionErrorKind::WrongArgument(format!(
             "Expected an even-length string containing only 0-9a-fA-F, found '{}'",
             s
@@ -263,21 +296,20 @@ fn data_to_hexstr(bytes: Maybe<&[u8]>) -> Option<String> {
 /// Length argument is required and can be a named argument or a positional argument.
 /// Data argument is an optional named argument and is taken to be "X" if not provided.
 #[nasl_function(maybe_named(length), named(data))]
-fn crap(length: usize, data: Option<NaslValue>) -> String {
-    let data = match data {
-        Some(x) => x.to_string(),
-        None => "X".to_string(),
-    };
-
-    data.as_str().repeat(length)
+fn crap(length: usize, data: Option<NaslValue>) -> NaslValue {
+    match data {
+        Some(NaslValue::Data(x)) => bytes_to_value(x.repeat(length)),
+        Some(x) => x.to_string().repeat(length).into(),
+        None => "X".repeat(length).into(),
+    }
 }
 
 /// NASL function to remove trailing whitespaces from a string
 ///
 /// Takes one required positional argument of string type.
 #[nasl_function]
-fn chomp(s: StringOrData) -> String {
-    s.0.trim_end().into()
+fn chomp(s: StringOrData) -> NaslValue {
+    bytes_to_value(s.0.trim_ascii_end().to_vec())
 }
 
 /// NASL function to lookup position of a substring within a string
@@ -285,18 +317,18 @@ fn chomp(s: StringOrData) -> String {
 /// The first positional argument is the *string* to search through.
 /// The second positional argument is the *string* to search for.
 /// The optional third positional argument is an *int* containing an offset from where to start the search.
+/// The returned index is a byte offset relative to the given offset.
 #[nasl_function]
 fn stridx(haystack: NaslValue, needle: NaslValue, offset: Option<usize>) -> i64 {
-    let h = haystack.to_string();
-    let haystack = h.as_str();
-    let n = needle.to_string();
-    let needle = n.as_str();
+    let haystack = value_bytes(&haystack);
+    let needle = value_bytes(&needle);
 
     let offset = offset.unwrap_or(0);
-    match &haystack[offset..].find(needle) {
-        Some(index) => *index as i64,
-        None => -1,
-    }
+    haystack
+        .get(offset..)
+        .and_then(|x| find_bytes(x, &needle))
+        .map(|x| x as i64)
+        .unwrap_or(-1)
 }
 
 /// NASL function to display any number of NASL values
@@ -309,12 +341,11 @@ fn display(register: &Register, configs: &Context) -> Result<NaslValue, Function
 
 /// NASL function that returns the ASCII code of the first character of a given string.
 ///
-/// Takes a single positional argument.
+/// Takes a single positional argument. Data returns its first byte, other values are converted
+/// into a string first.
 #[nasl_function]
 fn ord(s: NaslValue) -> Option<u8> {
-    let s = s.to_string();
-    let s = s.as_str();
-    s.chars().next().map(|c| c as u8)
+    value_bytes(&s).first().copied()
 }
 
 /// NASL function to convert a string to an integer.  This function
@@ -480,16 +511,13 @@ fn str_replace(
 /// 1st positional argument: string to search in.
 /// 2nd positional argument: substring to search for.
 fn strstr(string: NaslValue, find: NaslValue) -> NaslValue {
-    let strb = string.to_string();
-    let string = strb.as_str();
-
-    let findb = find.to_string();
-    let find = findb.as_str();
+    let bytes = value_bytes(&string);
+    let find = value_bytes(&find);
 
-    if let Some(i) = string.find(find) {
-        return NaslValue::String(string[i..].to_string());
+    match find_bytes(&bytes, &find) {
+        Some(i) => bytes_to_value(bytes[i..].to_vec()),
+        None => NaslValue::Null,
     }
-    NaslValue::Null
 }
 /// The description builtin function
 pub struct NaslString;
@@ -239,4 +239,36 @@ mod tests {
         check_err_matches!(r#"strstr();"#, MissingPositionalArguments { .. });
         check_err_matches!(r#"strstr("a");"#, MissingPositionalArguments { .. });
     }
+
+    #[test]
+    fn binary_safe() {
+        check_code_result(
+            "raw_string(0x1FF, -1, 256, 0x80);",
+            vec![0xffu8, 0xff, 0, 0x80],
+        );
+        check_code_result(r#"hexstr(hexstr_to_data("00ff80c3"));"#, "00ff80c3");
+        check_code_result(r#"data_to_hexstr(hexstr_to_data("00FF80"));"#, "00ff80");
+        check_err_matches!(r#"hexstr_to_data("abc");"#, WrongArgument { .. });
+        check_err_matches!(r#"hexstr_to_data("zz");"#, WrongArgument { .. });
+        check_err_matches!(r#"hexstr_to_data("äa");"#, WrongArgument { .. });
+        check_code_result("ord(raw_string(0xff, 0x41));", 255);
+        check_code_result(r#"ord("ä");"#, 0xc3);
+        check_code_result("strlen(raw_string(0xff, 0x80, 0));", 3i64);
+        check_code_result("substr(raw_string(0, 0xff, 1), 1);", vec![0xffu8, 1]);
+        check_code_result("substr('hello', 1, 99);", "ello");
+        check_code_result("substr('hello', 3, 2);", Null);
+        check_code_result("toupper(raw_string(0xe4, 0x61));", vec![0xe4u8, 0x41]);
+        check_code_result("tolower(raw_string(0xc4, 0x41));", vec![0xc4u8, 0x61]);
+        check_code_result(
+            "crap(data: raw_string(0xff), length: 2);",
+            vec![0xffu8, 0xff],
+        );
+        check_code_result("chomp(raw_string(0xff, 0x20, 0x0a));", vec![0xffu8]);
+        check_code_result("stridx(raw_string(0xff, 1, 2), raw_string(2));", 2i64);
+        check_code_result("stridx('abc', 'c', 10);", -1i64);
+        check_code_result(
+            "strstr(raw_string(1, 0xff, 2), raw_string(0xff));",
+            vec![0xffu8, 2],
+        );
+    }
 }
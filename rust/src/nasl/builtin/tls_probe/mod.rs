@@ -0,0 +1,473 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines builtins sending crafted TLS ClientHellos and parsing the answer of the server.
+//!
+//! A TLS library refuses to offer deprecated protocol versions and ciphers, so VTs enumerating
+//! the capabilities of a server build the ClientHello themselves:
+//! - `tls_client_hello` returns a ClientHello record offering exactly the given version, cipher
+//!   suites and named groups,
+//! - `tls_parse_server_hello` parses the ServerHello or the alert a server answers with,
+//! - `tls_probe` connects to a port of the target, sends a ClientHello and returns the parsed
+//!   answer.
+//!
+//! Versions are given by their number on the wire, e.g. `0x0300` for SSLv3 and `0x0304` for TLS
+//! 1.3. A TLS 1.3 ClientHello contains no key share, a server supporting it answers with a
+//! HelloRetryRequest naming the cipher suite and group it selected. No handshake is completed.
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use rand::RngCore;
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::{lookup_keys::VHOST, zone};
+
+use super::network::network_utils::{connect_target, timeout_arg};
+use super::network::verify_port;
+
+const SSL3: u16 = 0x0300;
+const TLS10: u16 = 0x0301;
+const TLS12: u16 = 0x0303;
+const TLS13: u16 = 0x0304;
+
+const HANDSHAKE: u8 = 22;
+const ALERT: u8 = 21;
+const CLIENT_HELLO: u8 = 1;
+const SERVER_HELLO: u8 = 2;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_KEY_SHARE: u16 = 51;
+
+/// x25519, secp256r1 and secp384r1
+const DEFAULT_GROUPS: &[u16] = &[0x001d, 0x0017, 0x0018];
+/// The common ECDSA, RSA-PSS and RSA PKCS#1 signature schemes
+const SIGNATURE_SCHEMES: &[u16] = &[
+    0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0203, 0x0201,
+];
+/// The random of a ServerHello that is a HelloRetryRequest
+const HELLO_RETRY_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+/// Maximum length of a record
+const MAX_RECORD: usize = 16384 + 2048;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Appends the data prefixed by its length encoded in len bytes.
+fn push_vec(buffer: &mut Vec<u8>, len: usize, data: &[u8]) {
+    buffer.extend_from_slice(&(data.len() as u32).to_be_bytes()[4 - len..]);
+    buffer.extend_from_slice(data);
+}
+
+fn u16_list(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_be_bytes()).collect()
+}
+
+/// A ClientHello offering exactly the configured parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClientHello {
+    version: u16,
+    ciphers: Vec<u16>,
+    groups: Vec<u16>,
+    sni: Option<String>,
+}
+
+impl ClientHello {
+    /// Encodes the ClientHello as handshake record.
+    fn encode(&self) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let mut body = vec![];
+        push_u16(&mut body, self.version.min(TLS12));
+        let mut random = [0; 32];
+        rng.fill_bytes(&mut random);
+        body.extend_from_slice(&random);
+        // TLS 1.3 servers expect a session id for the middlebox compatibility mode
+        let mut session_id = vec![];
+        if self.version >= TLS13 {
+            session_id = vec![0; 32];
+            rng.fill_bytes(&mut session_id);
+        }
+        push_vec(&mut body, 1, &session_id);
+        push_vec(&mut body, 2, &u16_list(&self.ciphers));
+        // no compression
+        push_vec(&mut body, 1, &[0]);
+        if self.version >= TLS10 {
+            push_vec(&mut body, 2, &self.extensions());
+        }
+
+        let mut handshake = vec![CLIENT_HELLO];
+        push_vec(&mut handshake, 3, &body);
+        let mut record = vec![HANDSHAKE];
+        push_u16(&mut record, self.version.min(TLS10));
+        push_vec(&mut record, 2, &handshake);
+        record
+    }
+
+    fn extensions(&self) -> Vec<u8> {
+        let mut extensions = vec![];
+        let mut push = |id, data: &[u8]| {
+            push_u16(&mut extensions, id);
+            push_vec(&mut extensions, 2, data);
+        };
+        if let Some(sni) = &self.sni {
+            let mut name = vec![0];
+            push_vec(&mut name, 2, sni.as_bytes());
+            let mut list = vec![];
+            push_vec(&mut list, 2, &name);
+            push(EXT_SERVER_NAME, &list);
+        }
+        let mut groups = vec![];
+        push_vec(&mut groups, 2, &u16_list(&self.groups));
+        push(EXT_SUPPORTED_GROUPS, &groups);
+        // uncompressed points
+        push(EXT_EC_POINT_FORMATS, &[1, 0]);
+        if self.version >= TLS12 {
+            let mut schemes = vec![];
+            push_vec(&mut schemes, 2, &u16_list(SIGNATURE_SCHEMES));
+            push(EXT_SIGNATURE_ALGORITHMS, &schemes);
+        }
+        if self.version >= TLS13 {
+            let mut versions = vec![];
+            push_vec(&mut versions, 1, &u16_list(&[TLS13]));
+            push(EXT_SUPPORTED_VERSIONS, &versions);
+            // an empty key share makes the server send a HelloRetryRequest
+            push(EXT_KEY_SHARE, &[0, 0]);
+        }
+        extensions
+    }
+}
+
+/// The answer of a server to a ClientHello
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ServerResponse {
+    ServerHello {
+        record_version: u16,
+        /// The negotiated version, the selected supported version for TLS 1.3
+        version: u16,
+        cipher: u16,
+        compression: u8,
+        session_id: Vec<u8>,
+        extensions: Vec<u16>,
+        /// The group of the key share
+        group: Option<u16>,
+        hello_retry: bool,
+    },
+    Alert {
+        record_version: u16,
+        level: u8,
+        description: u8,
+    },
+}
+
+/// Reads big endian numbers of a slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|x| x[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|x| u16::from_be_bytes([x[0], x[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|x| u32::from_be_bytes([0, x[0], x[1], x[2]]) as usize)
+    }
+}
+
+impl ServerResponse {
+    /// Parses the first record of the answer, None when it is neither a ServerHello nor an
+    /// alert or when it is incomplete.
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut record = Reader(data);
+        let content_type = record.u8()?;
+        let record_version = record.u16()?;
+        let len = record.u16()? as usize;
+        let mut body = Reader(record.take(len)?);
+        match content_type {
+            ALERT => Some(Self::Alert {
+                record_version,
+                level: body.u8()?,
+                description: body.u8()?,
+            }),
+            HANDSHAKE if body.u8()? == SERVER_HELLO => {
+                let len = body.u24()?;
+                Self::parse_server_hello(record_version, Reader(body.take(len)?))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_server_hello(record_version: u16, mut hello: Reader) -> Option<Self> {
+        let mut version = hello.u16()?;
+        let hello_retry = hello.take(32)? == HELLO_RETRY_RANDOM;
+        let session_len = hello.u8()? as usize;
+        let session_id = hello.take(session_len)?.to_vec();
+        let cipher = hello.u16()?;
+        let compression = hello.u8()?;
+        let mut extensions = vec![];
+        let mut group = None;
+        // extensions are optional before TLS 1.2
+        if let Some(len) = hello.u16() {
+            let mut list = Reader(hello.take(len as usize)?);
+            while let Some(id) = list.u16() {
+                let len = list.u16()? as usize;
+                let mut data = Reader(list.take(len)?);
+                match id {
+                    EXT_SUPPORTED_VERSIONS => version = data.u16()?,
+                    EXT_KEY_SHARE => group = data.u16(),
+                    _ => {}
+                }
+                extensions.push(id);
+            }
+        }
+        Some(Self::ServerHello {
+            record_version,
+            version,
+            cipher,
+            compression,
+            session_id,
+            extensions,
+            group,
+            hello_retry,
+        })
+    }
+}
+
+impl From<ServerResponse> for NaslValue {
+    fn from(value: ServerResponse) -> Self {
+        let number = |x: u16| NaslValue::Number(x as i64);
+        let dict = match value {
+            ServerResponse::ServerHello {
+                record_version,
+                version,
+                cipher,
+                compression,
+                session_id,
+                extensions,
+                group,
+                hello_retry,
+            } => {
+                let mut dict = HashMap::from([
+                    ("type".to_string(), NaslValue::String("server_hello".into())),
+                    ("record_version".to_string(), number(record_version)),
+                    ("version".to_string(), number(version)),
+                    ("cipher".to_string(), number(cipher)),
+                    ("compression".to_string(), number(compression as u16)),
+                    ("session_id".to_string(), NaslValue::Data(session_id)),
+                    (
+                        "extensions".to_string(),
+                        NaslValue::Array(extensions.into_iter().map(number).collect()),
+                    ),
+                    ("hello_retry".to_string(), NaslValue::Boolean(hello_retry)),
+                ]);
+                if let Some(group) = group {
+                    dict.insert("group".to_string(), number(group));
+                }
+                dict
+            }
+            ServerResponse::Alert {
+                record_version,
+                level,
+                description,
+            } => HashMap::from([
+                ("type".to_string(), NaslValue::String("alert".into())),
+                ("record_version".to_string(), number(record_version)),
+                ("level".to_string(), number(level as u16)),
+                ("description".to_string(), number(description as u16)),
+            ]),
+        };
+        NaslValue::Dict(dict)
+    }
+}
+
+/// Reads a single record.
+fn read_record<R: Read>(stream: &mut R) -> io::Result<Vec<u8>> {
+    let mut record = vec![0; 5];
+    stream.read_exact(&mut record)?;
+    let len = u16::from_be_bytes([record[3], record[4]]) as usize;
+    if len > MAX_RECORD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "TLS record is too large",
+        ));
+    }
+    record.resize(5 + len, 0);
+    stream.read_exact(&mut record[5..])?;
+    Ok(record)
+}
+
+/// Returns the server name sent by default, the virtual host or the target when it is no IP
+/// address.
+fn default_sni(register: &Register, context: &Context<'_>) -> Option<String> {
+    let name = match register.named(VHOST) {
+        Some(ContextType::Value(x)) => x.to_string(),
+        _ => zone::strip(context.target()).to_owned(),
+    };
+    (!name.is_empty() && name.parse::<std::net::IpAddr>().is_err()).then_some(name)
+}
+
+fn client_hello(
+    register: &Register,
+    context: &Context<'_>,
+    version: i64,
+    ciphers: Vec<i64>,
+    curves: Option<Vec<i64>>,
+    sni: Option<&str>,
+) -> Result<ClientHello, FunctionErrorKind> {
+    let version = match u16::try_from(version) {
+        Ok(x @ SSL3..=TLS13) => x,
+        _ => {
+            return Err(FunctionErrorKind::wrong_argument(
+                "version",
+                "0x0300 to 0x0304",
+                &format!("{version:#06x}"),
+            ))
+        }
+    };
+    let u16s = |name: &str, values: Vec<i64>| {
+        values
+            .into_iter()
+            .map(|x| {
+                u16::try_from(x).map_err(|_| {
+                    FunctionErrorKind::wrong_argument(name, "16 bit number", &x.to_string())
+                })
+            })
+            .collect::<Result<Vec<u16>, FunctionErrorKind>>()
+    };
+    let ciphers = u16s("ciphers", ciphers)?;
+    if ciphers.is_empty() {
+        return Err(FunctionErrorKind::missing_argument("ciphers"));
+    }
+    let groups = match curves {
+        Some(x) => u16s("curves", x)?,
+        None => DEFAULT_GROUPS.to_vec(),
+    };
+    let sni = match sni {
+        Some("") => None,
+        Some(x) => Some(x.to_owned()),
+        None => default_sni(register, context),
+    };
+    Ok(ClientHello {
+        version,
+        ciphers,
+        groups,
+        sni,
+    })
+}
+
+/// *data* **tls_client_hello**(version: *int*, ciphers: *array*, curves: *array*, sni: *string*);
+///
+/// Returns a ClientHello record offering the version, the cipher suites and the named groups. The
+/// groups default to x25519, secp256r1 and secp384r1, the server name defaults to the virtual host
+/// or the hostname of the target, an empty `sni` sends none.
+#[nasl_function(named(version, ciphers, curves, sni))]
+async fn tls_client_hello(
+    register: &Register,
+    context: &Context<'_>,
+    version: i64,
+    ciphers: Vec<i64>,
+    curves: Option<Vec<i64>>,
+    sni: Option<&str>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let hello = client_hello(register, context, version, ciphers, curves, sni)?;
+    Ok(NaslValue::Data(hello.encode()))
+}
+
+/// *array* **tls_parse_server_hello**(*data*);
+///
+/// Parses the first record of the answer to a ClientHello.
+///
+/// A ServerHello is returned as array with the `type` `server_hello`, the `record_version`, the
+/// negotiated `version`, the selected `cipher`, the `compression`, the `session_id`, the ids of
+/// the `extensions`, the `group` of the key share if any and `hello_retry` for a
+/// HelloRetryRequest. An alert is returned with the `type` `alert`, the `record_version`, the
+/// `level` and the `description`. NULL is returned for anything else.
+#[nasl_function]
+async fn tls_parse_server_hello(data: &[u8]) -> NaslValue {
+    ServerResponse::parse(data)
+        .map(NaslValue::from)
+        .unwrap_or(NaslValue::Null)
+}
+
+/// *array* **tls_probe**(port: *int*, version: *int*, ciphers: *array*, curves: *array*, sni: *string*, timeout: *int*);
+///
+/// Sends a ClientHello like `tls_client_hello` to the port of the target and returns the answer
+/// parsed like `tls_parse_server_hello`. NULL is returned when the connection fails or is closed
+/// without an answer.
+#[nasl_function(named(port, version, ciphers, curves, sni, timeout))]
+#[allow(clippy::too_many_arguments)]
+async fn tls_probe(
+    register: &Register,
+    context: &Context<'_>,
+    port: i64,
+    version: i64,
+    ciphers: Vec<i64>,
+    curves: Option<Vec<i64>>,
+    sni: Option<&str>,
+    timeout: Option<i64>,
+) -> Result<NaslValue, FunctionErrorKind> {
+    let port = verify_port(port)?;
+    let hello = client_hello(register, context, version, ciphers, curves, sni)?.encode();
+    let timeout = timeout_arg(timeout, DEFAULT_TIMEOUT);
+    let Some((mut stream, _permit)) = connect_target(context, port, timeout).await? else {
+        return Ok(NaslValue::Null);
+    };
+    stream.write_all(&hello)?;
+    if let Some(traffic) = context.traffic() {
+        traffic.sent(hello.len())?;
+    }
+    let record = match read_record(&mut stream) {
+        Ok(x) => x,
+        Err(error) => {
+            tracing::debug!(port, %error, "no answer to the ClientHello");
+            return Ok(NaslValue::Null);
+        }
+    };
+    if let Some(traffic) = context.traffic() {
+        traffic.received(record.len())?;
+    }
+    Ok(ServerResponse::parse(&record)
+        .map(NaslValue::from)
+        .unwrap_or(NaslValue::Null))
+}
+
+pub struct TlsProbe;
+
+function_set! {
+    TlsProbe,
+    async_stateless,
+    (
+        tls_client_hello,
+        tls_parse_server_hello,
+        tls_probe
+    )
+}
@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::super::{read_record, ClientHello, ServerResponse, HELLO_RETRY_RANDOM};
+    use crate::nasl::test_prelude::*;
+    use crate::storage::ContextKey;
+    use FunctionErrorKind::*;
+
+    /// Builds a ServerHello record with the extensions given as id and data.
+    fn server_hello(
+        version: u16,
+        random: [u8; 32],
+        cipher: u16,
+        extensions: &[(u16, &[u8])],
+    ) -> Vec<u8> {
+        let mut body = version.to_be_bytes().to_vec();
+        body.extend_from_slice(&random);
+        body.extend_from_slice(&[2, 0xab, 0xcd]);
+        body.extend_from_slice(&cipher.to_be_bytes());
+        body.push(0);
+        let mut list = vec![];
+        for (id, data) in extensions {
+            list.extend_from_slice(&id.to_be_bytes());
+            list.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            list.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(list.len() as u16).to_be_bytes());
+        body.extend_from_slice(&list);
+        let mut handshake = vec![2, 0];
+        handshake.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&body);
+        let mut record = vec![22, 3, 3];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn client_hello() {
+        let hello = ClientHello {
+            version: 0x0301,
+            ciphers: vec![0x002f, 0x0035],
+            groups: vec![0x0017],
+            sni: Some("www.example.com".into()),
+        }
+        .encode();
+        assert_eq!(&hello[..3], &[22, 3, 1]);
+        assert_eq!(
+            u16::from_be_bytes([hello[3], hello[4]]) as usize,
+            hello.len() - 5
+        );
+        // handshake type and client version
+        assert_eq!(hello[5], 1);
+        assert_eq!(&hello[9..11], &[3, 1]);
+        // empty session id followed by the cipher suites
+        assert_eq!(&hello[43..50], &[0, 0, 4, 0, 0x2f, 0, 0x35]);
+        assert!(hello.windows(15).any(|x| x == b"www.example.com"));
+        // no signature algorithms before TLS 1.2
+        assert!(!hello.windows(2).any(|x| x == [0, 13]));
+
+        let hello = ClientHello {
+            version: 0x0304,
+            ciphers: vec![0x1301],
+            groups: vec![0x001d],
+            sni: None,
+        }
+        .encode();
+        assert_eq!(&hello[1..3], &[3, 1]);
+        assert_eq!(&hello[9..11], &[3, 3]);
+        // a session id for the middlebox compatibility mode
+        assert_eq!(hello[43], 32);
+        // supported versions offering only TLS 1.3 and an empty key share
+        assert!(hello.windows(7).any(|x| x == [0, 43, 0, 3, 2, 3, 4]));
+        assert!(hello.ends_with(&[0, 51, 0, 2, 0, 0]));
+
+        let ssl3 = ClientHello {
+            version: 0x0300,
+            ciphers: vec![0x000a],
+            groups: vec![],
+            sni: None,
+        }
+        .encode();
+        assert_eq!(&ssl3[1..3], &[3, 0]);
+        // SSLv3 has no extensions
+        assert_eq!(ssl3.len(), 5 + 4 + 2 + 32 + 1 + 4 + 2);
+    }
+
+    #[test]
+    fn parse() {
+        let hello = server_hello(0x0303, [1; 32], 0xc02f, &[(0xff01, &[0]), (11, &[1, 0])]);
+        assert_eq!(
+            ServerResponse::parse(&hello),
+            Some(ServerResponse::ServerHello {
+                record_version: 0x0303,
+                version: 0x0303,
+                cipher: 0xc02f,
+                compression: 0,
+                session_id: vec![0xab, 0xcd],
+                extensions: vec![0xff01, 11],
+                group: None,
+                hello_retry: false,
+            })
+        );
+        let retry = server_hello(
+            0x0303,
+            HELLO_RETRY_RANDOM,
+            0x1301,
+            &[(43, &[3, 4]), (51, &[0, 0x1d])],
+        );
+        assert_eq!(
+            ServerResponse::parse(&retry),
+            Some(ServerResponse::ServerHello {
+                record_version: 0x0303,
+                version: 0x0304,
+                cipher: 0x1301,
+                compression: 0,
+                session_id: vec![0xab, 0xcd],
+                extensions: vec![43, 51],
+                group: Some(0x1d),
+                hello_retry: true,
+            })
+        );
+        assert_eq!(
+            ServerResponse::parse(&[21, 3, 1, 0, 2, 2, 70]),
+            Some(ServerResponse::Alert {
+                record_version: 0x0301,
+                level: 2,
+                description: 70,
+            })
+        );
+        assert_eq!(ServerResponse::parse(&hello[..hello.len() - 1]), None);
+        assert_eq!(ServerResponse::parse(b"HTTP/1.1 400 Bad Request"), None);
+        assert_eq!(
+            read_record(&mut &hello[..]).unwrap(),
+            hello,
+            "a record is read completely"
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let mut t = TestBuilder::default();
+        t.run(r#"tls_client_hello(version: 0x0303, ciphers: make_list(0xc02f), sni: "");"#);
+        check_err_matches!(
+            t,
+            r#"tls_client_hello(version: 0x0200, ciphers: make_list(0xc02f));"#,
+            WrongArgument { .. }
+        );
+        check_err_matches!(
+            t,
+            r#"tls_client_hello(version: 0x0303, ciphers: make_list(0x10000));"#,
+            WrongArgument { .. }
+        );
+        t.ok(r#"tls_parse_server_hello("foo");"#, NaslValue::Null);
+        t.run(r#"a = tls_parse_server_hello(raw_string(21, 3, 3, 0, 2, 2, 40));"#);
+        t.ok(r#"a["type"];"#, "alert");
+        t.ok(r#"a["description"];"#, 40);
+    }
+
+    #[test]
+    fn probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for (stream, answer) in listener.incoming().zip([
+                server_hello(0x0303, [7; 32], 0xc02f, &[]),
+                vec![21, 3, 3, 0, 2, 2, 40],
+            ]) {
+                let mut stream = stream.unwrap();
+                let hello = read_record(&mut stream).unwrap();
+                // the server offers only the first of the cipher suites
+                assert_eq!(&hello[46..48], &[0xc0, 0x2f]);
+                stream.write_all(&answer).unwrap();
+                let _ = stream.read(&mut [0; 16]);
+            }
+        });
+
+        let mut t = TestBuilder::default()
+            .with_context_key(ContextKey::Scan("sid".into(), Some("127.0.0.1".into())));
+        t.run_all(format!(
+            r#"
+            a = tls_probe(port: {port}, version: 0x0303, ciphers: make_list(0xc02f, 0x009c));
+            a["type"];
+            a["cipher"];
+            a = tls_probe(port: {port}, version: 0x0303, ciphers: make_list(0xc02f));
+            a["type"];
+            a["description"];
+            "#
+        ));
+        let results: Vec<_> = t.results().into_iter().map(|x| x.unwrap()).collect();
+        assert_eq!(results[1], NaslValue::String("server_hello".into()));
+        assert_eq!(results[2], NaslValue::Number(0xc02f));
+        assert_eq!(results[4], NaslValue::String("alert".into()));
+        assert_eq!(results[5], NaslValue::Number(40));
+    }
+}
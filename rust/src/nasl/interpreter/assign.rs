@@ -6,7 +6,12 @@ use std::collections::HashMap;
 
 use crate::nasl::syntax::{AssignOrder, Statement, TokenCategory};
 
-use super::{error::InterpretError, interpreter::InterpretResult, Interpreter};
+use super::{
+    error::InterpretError,
+    interpreter::InterpretResult,
+    operator::{divide, modulo, shl, shr},
+    Interpreter,
+};
 use crate::nasl::syntax::NaslValue;
 use crate::nasl::syntax::StatementKind::*;
 use crate::nasl::utils::ContextType;
@@ -144,7 +149,7 @@ impl<'a> Interpreter<'a> {
             }
             Some(idx) => match idx {
                 NaslValue::String(idx) => {
-                    self.handle_dict(ridx, key, idx, left, right, order, result)
+                    self.handle_dict(ridx, key, idx.into(), left, right, order, result)
                 }
                 NaslValue::Data(idx) => {
                     let idx = idx.into_iter().map(|x| x as char).collect();
@@ -196,41 +201,41 @@ impl<'a> Interpreter<'a> {
         match category {
             TokenCategory::Equal => self.store_return(&key, lookup, &val, |_, right| right.clone()),
             TokenCategory::PlusEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) + i64::from(right))
+                NaslValue::Number(i64::from(left).wrapping_add(i64::from(right)))
             }),
             TokenCategory::MinusEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) - i64::from(right))
+                NaslValue::Number(i64::from(left).wrapping_sub(i64::from(right)))
             }),
             TokenCategory::SlashEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) / i64::from(right))
+                NaslValue::Number(divide(i64::from(left), i64::from(right)))
             }),
             TokenCategory::StarEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) * i64::from(right))
+                NaslValue::Number(i64::from(left).wrapping_mul(i64::from(right)))
             }),
             TokenCategory::GreaterGreaterEqual => {
                 self.store_return(&key, lookup, &val, |left, right| {
-                    NaslValue::Number(i64::from(left) >> i64::from(right))
+                    NaslValue::Number(shr(i64::from(left), i64::from(right)))
                 })
             }
             TokenCategory::LessLessEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) << i64::from(right))
+                NaslValue::Number(shl(i64::from(left), i64::from(right)))
             }),
             TokenCategory::GreaterGreaterGreaterEqual => {
                 self.store_return(&key, lookup, &val, |left, right| {
                     // get rid of minus sign
                     let left = i64::from(left) as u32;
                     let right = i64::from(right) as u32;
-                    NaslValue::Number((left << right) as i64)
+                    NaslValue::Number(left.wrapping_shl(right) as i64)
                 })
             }
             TokenCategory::PercentEqual => self.store_return(&key, lookup, &val, |left, right| {
-                NaslValue::Number(i64::from(left) % i64::from(right))
+                NaslValue::Number(modulo(i64::from(left), i64::from(right)))
             }),
             TokenCategory::PlusPlus => self.without_right(order, &key, lookup, |left, _| {
-                NaslValue::Number(i64::from(left) + 1)
+                NaslValue::Number(i64::from(left).wrapping_add(1))
             }),
             TokenCategory::MinusMinus => self.without_right(order, &key, lookup, |left, _| {
-                NaslValue::Number(i64::from(left) - 1)
+                NaslValue::Number(i64::from(left).wrapping_sub(1))
             }),
 
             cat => Err(InterpretError::wrong_category(cat)),
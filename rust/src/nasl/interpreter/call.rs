@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use crate::nasl::syntax::{Statement, StatementKind::*, Token};
-use crate::nasl::utils::lookup_keys::FC_ANON_ARGS;
+use crate::nasl::utils::lookup_keys::{FC_ANON_ARGS, VHOST};
 
 use crate::nasl::interpreter::{
     error::{FunctionError, InterpretError},
@@ -15,6 +15,14 @@ use crate::nasl::syntax::NaslValue;
 use crate::nasl::utils::ContextType;
 use std::collections::HashMap;
 
+/// Returns the register key keeping the value a forking function returned to an instance
+fn fork_key(name: &str) -> Option<&'static str> {
+    match name {
+        "get_host_name" => Some(VHOST),
+        _ => None,
+    }
+}
+
 impl<'a> Interpreter<'a> {
     pub async fn call(&mut self, name: &Token, arguments: &[Statement]) -> InterpretResult {
         let name = &Self::identifier(name)?;
@@ -43,6 +51,7 @@ impl<'a> Interpreter<'a> {
         let result = match self.ctxconfigs.nasl_fn_execute(name, self.register()).await {
             Some(r) => {
                 if let Ok(NaslValue::Fork(mut x)) = r {
+                    let key = fork_key(name);
                     Ok(if let Some(r) = x.pop() {
                         // this is a proposal for the case that the caller is immediately executing
                         // if not the position needs to be reset
@@ -50,10 +59,15 @@ impl<'a> Interpreter<'a> {
                             let position = self.position().current_init_statement();
                             for i in x {
                                 tracing::trace!(return_value=?i, return_position=?self.position(), interpreter_position=?position, "creating interpreter instance" );
+                                let mut register = self.register().clone();
+                                if let Some(key) = key {
+                                    register.add_global(key, ContextType::Value(i.clone()));
+                                }
                                 self.run_specific.push(RunSpecific {
-                                    register: self.register().clone(),
+                                    register,
                                     position: position.clone(),
                                     skip_until_return: Some((self.position().clone(), i)),
+                                    included: self.run_specific[self.index].included.clone(),
                                 });
                             }
                         } else {
@@ -63,6 +77,10 @@ impl<'a> Interpreter<'a> {
                             );
                         }
                         tracing::trace!(return_value=?r, "returning interpreter instance" );
+                        if let Some(key) = key {
+                            self.register_mut()
+                                .add_global(key, ContextType::Value(r.clone()));
+                        }
                         r
                     } else {
                         NaslValue::Null
@@ -106,6 +124,7 @@ impl<'a> Interpreter<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::nasl::interpreter::{CodeInterpreter, InterpretError, InterpretErrorKind};
     use crate::nasl::test_prelude::*;
 
     #[test]
@@ -120,4 +139,46 @@ mod tests {
         t.ok("test(a: 1);", 1);
         t.ok("test();", 0);
     }
+
+    #[test]
+    fn endless_recursion() {
+        // unoptimized builds need more stack than a test thread has by default
+        let run = || {
+            let binding = ContextFactory::default();
+            let context = binding.build(Default::default());
+            let code = "function test() { return test(); } test();";
+            let result = CodeInterpreter::new(code, Register::default(), &context)
+                .iter_blocking()
+                .last();
+            assert!(matches!(
+                result,
+                Some(Err(InterpretError {
+                    kind: InterpretErrorKind::MaxRecursionDepth(_),
+                    ..
+                }))
+            ));
+        };
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn named_parameter_without_call() {
+        let binding = ContextFactory::default();
+        let context = binding.build(Default::default());
+        let result = CodeInterpreter::new("a: 1;", Register::default(), &context)
+            .iter_blocking()
+            .last();
+        assert!(matches!(
+            result,
+            Some(Err(InterpretError {
+                kind: InterpretErrorKind::WrongType(_),
+                ..
+            }))
+        ));
+    }
 }
@@ -1,18 +1,47 @@
 //! Contains implementations of Interpreter that handle the simulation of forking methods for the
 //! caller.
 
-use futures::{stream, Stream};
+use std::{collections::VecDeque, sync::Arc};
 
-use crate::nasl::syntax::{Lexer, Statement, Tokenizer};
+use futures::{future, stream, Stream};
+
+use crate::nasl::syntax::{optimize, Lexer, Statement, SyntaxError, Tokenizer};
 
 use crate::nasl::interpreter::interpreter::{InterpretResult, Interpreter};
+use crate::nasl::interpreter::parallel::Footprint;
+use crate::nasl::interpreter::Trace;
 use crate::nasl::prelude::*;
 
+/// Is the source of statements of a CodeInterpreter
+enum Statements<'b> {
+    /// Statements are parsed lazily from code
+    Lexer(Lexer<'b>),
+    /// Statements are already compiled
+    Compiled(std::vec::IntoIter<Statement>),
+}
+
+impl Iterator for Statements<'_> {
+    type Item = Result<Statement, SyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Statements::Lexer(x) => x.next(),
+            Statements::Compiled(x) => x.next().map(Ok),
+        }
+    }
+}
+
 /// Uses given code to return results based on that.
 pub struct CodeInterpreter<'a, 'b> {
-    lexer: Lexer<'b>,
+    lexer: Statements<'b>,
     interpreter: Interpreter<'a>,
     statement: Option<Statement>,
+    optimize: bool,
+    parallel: bool,
+    /// Statements that are already parsed, the flag is false when they must not be batched
+    queued: VecDeque<Result<(Statement, bool), SyntaxError>>,
+    /// Results of a concurrently interpreted batch
+    pending: VecDeque<InterpretResult>,
 }
 
 impl<'a, 'b> CodeInterpreter<'a, 'b> {
@@ -26,23 +55,166 @@ impl<'a, 'b> CodeInterpreter<'a, 'b> {
         let lexer = Lexer::new(token);
         let interpreter = Interpreter::new(register, context);
         Self {
-            lexer,
+            lexer: Statements::Lexer(lexer),
             interpreter,
             statement: None,
+            optimize: false,
+            parallel: false,
+            queued: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Creates a new code interpreter based on already compiled statements
+    ///
+    /// This is used to skip the parsing of scripts that are cached via `nasl::syntax::CompileCache`.
+    pub fn from_statements(
+        statements: Vec<Statement>,
+        register: Register,
+        context: &'a Context<'a>,
+    ) -> CodeInterpreter<'a, 'b> {
+        let interpreter = Interpreter::new(register, context);
+        Self {
+            lexer: Statements::Compiled(statements.into_iter()),
+            interpreter,
+            statement: None,
+            optimize: false,
+            parallel: false,
+            queued: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Runs each parsed statement through `nasl::syntax::optimize` before interpreting it.
+    pub fn optimized(mut self) -> Self {
+        self.optimize = true;
+        self
+    }
+
+    /// Interprets independent top-level statements concurrently.
+    ///
+    /// This is an experimental mode for scripts with many independent statements, e.g. heavy
+    /// enumerations. Consecutive statements that do not share variables and only call pure
+    /// builtin functions are run concurrently on a copy of the register, afterwards the written
+    /// variables are merged back and the results are returned in order. All other statements,
+    /// e.g. ones accessing the KB or the network, are interpreted sequentially.
+    ///
+    /// When a statement of a batch forks the interpreter, the batch is discarded and interpreted
+    /// sequentially instead. As a batch is free of side effects, nothing is repeated by that.
+    pub fn parallel(mut self) -> Self {
+        self.parallel = true;
+        self
+    }
+
+    /// Records each executed statement into the trace.
+    pub fn traced(mut self, trace: Arc<Trace>) -> Self {
+        self.interpreter.trace = Some(trace);
+        self
+    }
+
+    fn next_parsed(&mut self) -> Option<Result<(Statement, bool), SyntaxError>> {
+        if let Some(x) = self.queued.pop_front() {
+            return Some(x);
+        }
+        let stmt = match self.lexer.next()? {
+            Ok(x) if self.optimize => optimize(x),
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(Ok((stmt, self.parallel)))
+    }
+
+    /// Collects the following statements that can be interpreted concurrently with first.
+    fn collect_batch(&mut self, first: Statement) -> Vec<(Statement, Footprint)> {
+        let context = self.interpreter.ctxconfigs;
+        let Some(footprint) = Footprint::of(&first, context) else {
+            return vec![(first, Footprint::default())];
+        };
+        let mut combined = footprint.clone();
+        let mut batch = vec![(first, footprint)];
+        while let Some(next) = self.next_parsed() {
+            let footprint = match &next {
+                Ok((stmt, true)) => Footprint::of(stmt, context),
+                _ => None,
+            };
+            match (next, footprint) {
+                (Ok((stmt, _)), Some(x)) if combined.is_disjoint(&x) => {
+                    combined.extend(&x);
+                    batch.push((stmt, x));
+                }
+                (next, _) => {
+                    // e.g. a syntax error is returned after the batch
+                    self.queued.push_front(next);
+                    break;
+                }
+            }
+        }
+        batch
+    }
+
+    /// Interprets the batch concurrently and stores the results in pending.
+    async fn run_batch(&mut self, batch: Vec<(Statement, Footprint)>) {
+        let context = self.interpreter.ctxconfigs;
+        let mut interpreters: Vec<_> = batch
+            .iter()
+            .map(|_| {
+                let mut inter = Interpreter::new(self.interpreter.register().clone(), context);
+                inter.trace = self.interpreter.trace.clone();
+                inter
+            })
+            .collect();
+        let results = future::join_all(
+            interpreters
+                .iter_mut()
+                .zip(batch.iter())
+                .map(|(inter, (stmt, _))| inter.retry_resolve_next(stmt, 5)),
+        )
+        .await;
+        if interpreters.iter().any(|x| x.run_specific.len() > 1) {
+            tracing::debug!(
+                statements = batch.len(),
+                "statement forked within a batch, interpreting sequentially"
+            );
+            for (stmt, _) in batch.into_iter().rev() {
+                self.queued.push_front(Ok((stmt, false)));
+            }
+            return;
+        }
+        for ((inter, (_, footprint)), result) in interpreters.iter().zip(batch.iter()).zip(results)
+        {
+            for name in &footprint.writes {
+                if let Some(value) = inter.register().named(name) {
+                    let value = value.clone();
+                    self.interpreter.register_mut().add_global(name, value);
+                }
+            }
+            self.interpreter.skip_next();
+            self.pending.push_back(result);
         }
     }
 
     /// Evaluates the next statement
     pub async fn next_statement(&mut self) -> Option<InterpretResult> {
         self.statement = None;
-        match self.lexer.next() {
-            Some(Ok(nstmt)) => {
-                let results = Some(self.interpreter.retry_resolve_next(&nstmt, 5).await);
-                self.statement = Some(nstmt);
-                results
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
             }
-            Some(Err(err)) => Some(Err(err.into())),
-            None => None,
+            let nstmt = match self.next_parsed()? {
+                Ok((nstmt, true)) => {
+                    let mut batch = self.collect_batch(nstmt);
+                    if batch.len() > 1 {
+                        self.run_batch(batch).await;
+                        continue;
+                    }
+                    batch.remove(0).0
+                }
+                Ok((nstmt, false)) => nstmt,
+                Err(err) => return Some(Err(err.into())),
+            };
+            let results = Some(self.interpreter.retry_resolve_next(&nstmt, 5).await);
+            self.statement = Some(nstmt);
+            return results;
         }
     }
 
@@ -86,6 +258,7 @@ impl<'a, 'b> CodeInterpreter<'a, 'b> {
 
 #[cfg(test)]
 mod tests {
+    use crate::nasl::interpreter::CodeInterpreter;
     use crate::nasl::test_prelude::*;
 
     #[test]
@@ -94,4 +267,44 @@ mod tests {
         check_code_result(r#"set_kb_item(name: "test", value: 2);"#, NaslValue::Null);
         check_code_result(r#"display(get_kb_item("test"));"#, NaslValue::Null);
     }
+
+    #[test]
+    fn parallel() {
+        let code = r#"
+        a = 1;
+        b = 2;
+        c = a + b;
+        d = hexstr('a');
+        foreach i (make_list(1, 2)) l[i] = i;
+        set_kb_item(name: "x", value: c);
+        e = c * 2;
+        f = e + l[2];
+        function g() { return 1; }
+        h = g();
+        "#;
+        let run = |parallel: bool| {
+            let factory = ContextFactory::default();
+            let context = factory.build(Default::default());
+            let mut interpreter = CodeInterpreter::new(code, Register::default(), &context);
+            if parallel {
+                interpreter = interpreter.parallel();
+            }
+            futures::executor::block_on(async {
+                let mut results = vec![];
+                while let Some(result) = interpreter.next_statement().await {
+                    results.push(result.unwrap());
+                }
+                let register = interpreter.register();
+                let variables: Vec<_> = ["a", "b", "c", "d", "e", "f", "h", "i"]
+                    .iter()
+                    .map(|x| NaslValue::from(register.named(x).unwrap()))
+                    .collect();
+                (results, variables)
+            })
+        };
+        let (results, variables) = run(true);
+        assert_eq!((results.clone(), variables.clone()), run(false));
+        assert_eq!(results.len(), 10);
+        assert_eq!(variables[5], NaslValue::Number(8));
+    }
 }
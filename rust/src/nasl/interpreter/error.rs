@@ -90,6 +90,9 @@ pub enum InterpretErrorKind {
     /// An error occurred while calling a built-in function.
     #[error("{0}")]
     FunctionCallError(FunctionError),
+    /// Statements are nested too deeply, e.g. by an endless recursion
+    #[error("Maximal recursion depth of {0} reached.")]
+    MaxRecursionDepth(usize),
 }
 
 impl InterpretError {
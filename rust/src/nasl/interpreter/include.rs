@@ -6,7 +6,9 @@
 mod tests {
     use std::{collections::HashMap, string::String};
 
-    use crate::nasl::interpreter::CodeInterpreter;
+    use futures::StreamExt;
+
+    use crate::nasl::interpreter::{CodeInterpreter, InterpretError, InterpretErrorKind};
     use crate::nasl::{syntax::LoadError, Loader};
 
     use crate::nasl::{nasl_std_functions, prelude::*};
@@ -50,6 +52,7 @@ mod tests {
             loader,
             functions: nasl_std_functions(),
             storage: DefaultDispatcher::default(),
+            extensions: Default::default(),
         };
         let ctx = context.build(Default::default());
         let mut interpreter = CodeInterpreter::new(code, register, &ctx);
@@ -66,4 +69,77 @@ mod tests {
             )]))))
         );
     }
+
+    async fn loaded(code: &str, include_once: bool) -> NaslValue {
+        let plugins = HashMap::from([
+            ("common.inc".to_string(), "loaded++;".to_string()),
+            (
+                "a.inc".to_string(),
+                r#"include("common.inc"); include("b.inc");"#.to_string(),
+            ),
+            (
+                "b.inc".to_string(),
+                r#"include("common.inc"); include("a.inc");"#.to_string(),
+            ),
+        ]);
+        let context = ContextFactory {
+            loader: FakeInclude { plugins },
+            functions: nasl_std_functions(),
+            storage: DefaultDispatcher::default(),
+            extensions: Default::default(),
+        };
+        let ctx = context
+            .build(Default::default())
+            .with_include_once(include_once);
+        let code = format!("loaded = 0; {code} loaded;");
+        let results: Vec<_> = CodeInterpreter::new(&code, Register::default(), &ctx)
+            .stream()
+            .collect()
+            .await;
+        results.into_iter().next_back().unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn include_once() {
+        // a.inc and b.inc include each other as well as common.inc
+        let code = r#"include("common.inc"); include("a.inc");"#;
+        assert_eq!(loaded(code, true).await, NaslValue::Number(1));
+        let code = r#"include("common.inc"); include("common.inc");"#;
+        assert_eq!(loaded(code, false).await, NaslValue::Number(2));
+    }
+
+    #[test]
+    fn cyclic_include() {
+        // unoptimized builds need more stack than a test thread has by default
+        let run = || {
+            let plugins = HashMap::from([(
+                "cyclic.inc".to_string(),
+                r#"include("cyclic.inc");"#.to_string(),
+            )]);
+            let context = ContextFactory {
+                loader: FakeInclude { plugins },
+                functions: nasl_std_functions(),
+                storage: DefaultDispatcher::default(),
+                extensions: Default::default(),
+            };
+            let ctx = context.build(Default::default()).with_include_once(false);
+            let code = r#"include("cyclic.inc");"#;
+            let result = CodeInterpreter::new(code, Register::default(), &ctx)
+                .iter_blocking()
+                .last();
+            assert!(matches!(
+                result,
+                Some(Err(InterpretError {
+                    kind: InterpretErrorKind::MaxRecursionDepth(_),
+                    ..
+                }))
+            ));
+        };
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
 }
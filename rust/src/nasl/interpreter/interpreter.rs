@@ -2,7 +2,11 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::{collections::HashMap, io};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::Arc,
+};
 
 use crate::nasl::syntax::{
     IdentifierType, LoadError, NaslValue, Statement, StatementKind::*, SyntaxError, Token,
@@ -12,11 +16,16 @@ use crate::storage::StorageError;
 
 use crate::nasl::interpreter::{
     declare::{DeclareFunctionExtension, DeclareVariableExtension},
+    trace::{self, Trace},
     InterpretError, InterpretErrorKind,
 };
 
 use crate::nasl::utils::{Context, ContextType, Register};
 
+/// Is the maximum of nested statements that are interpreted at once, e.g. by recursive function
+/// calls. Deeper nesting is an error instead of a stack overflow.
+const MAX_DEPTH: usize = 100;
+
 /// Is used to identify the depth of the current statement
 ///
 /// Initial call of retry_resolce sets the first element all others are only
@@ -47,6 +56,10 @@ impl Position {
     fn root_index(&self) -> usize {
         *self.index.first().unwrap_or(&0)
     }
+
+    fn depth(&self) -> usize {
+        self.index.len()
+    }
 }
 
 /// Contains data that is specific for a single run
@@ -58,6 +71,8 @@ pub(crate) struct RunSpecific {
     pub(crate) register: Register,
     pub(crate) position: Position,
     pub(crate) skip_until_return: Option<(Position, NaslValue)>,
+    /// Files included by the run so far
+    pub(crate) included: HashSet<String>,
 }
 
 /// Used to interpret a Statement
@@ -65,6 +80,8 @@ pub struct Interpreter<'a> {
     pub(crate) run_specific: Vec<RunSpecific>,
     pub(crate) ctxconfigs: &'a Context<'a>,
     pub(crate) index: usize,
+    /// Records the executed statements when set
+    pub(crate) trace: Option<Arc<Trace>>,
 }
 
 /// Interpreter always returns a NaslValue or an InterpretError
@@ -75,18 +92,31 @@ pub type InterpretResult = Result<NaslValue, InterpretError>;
 impl<'a> Interpreter<'a> {
     /// Creates a new Interpreter
     pub fn new(register: Register, ctxconfigs: &'a Context) -> Self {
+        // the files of the base are already included
+        let included = register
+            .base()
+            .map(|x| x.included().iter().cloned().collect())
+            .unwrap_or_default();
         let root_run = RunSpecific {
             register,
             position: Position::new(0),
             skip_until_return: None,
+            included,
         };
         Interpreter {
             run_specific: vec![root_run],
             ctxconfigs,
             index: 0,
+            trace: None,
         }
     }
 
+    /// Records each executed statement into the trace.
+    pub fn traced(mut self, trace: Arc<Trace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
     pub(crate) fn identifier(token: &Token) -> Result<String, InterpretError> {
         match token.category() {
             TokenCategory::Identifier(IdentifierType::Undefined(x)) => Ok(x.to_owned()),
@@ -129,24 +159,68 @@ impl<'a> Interpreter<'a> {
     /// Includes a script into to the current runtime by executing it and share the register as
     /// well as DB of the current runtime.
     ///
+    /// Unless disabled via [Context::with_include_once] a file that was already included by the
+    /// run is skipped, so that the shared libraries of transitive includes are executed once. The
+    /// file is marked before it is executed, which also ends cyclic includes.
+    ///
     // NOTE: This is currently optimized for interpreting runs, but it is very inefficient if we want to
     // switch to a jitc approach or do parallelization of statements within a script. For that it
     // would be necessary to include the statements within a statement list of a script prior of
     // execution. In the current usage (2024-04-02) it would be overkill, but I'm writing a note as
     // I think this can be easily overlooked.
+    // The experimental `CodeInterpreter::parallel` mode therefore only batches top-level
+    // statements and treats an include as a dependency of everything that follows.
     async fn include(&mut self, name: &Statement) -> InterpretResult {
         match self.resolve(name).await? {
-            NaslValue::String(key) => {
-                let code = self.ctxconfigs.loader().load(&key)?;
+            NaslValue::String(key) => self.include_key(&key).await,
+            _ => Err(InterpretError::unsupported(name, "string")),
+        }
+    }
 
-                let mut inter = Interpreter::new(self.register().clone(), self.ctxconfigs);
-                for stmt in crate::nasl::syntax::parse(&code) {
-                    self.execute_statements(&key, &mut inter, stmt).await?;
-                }
-                self.set_register(inter.register().clone());
-                Ok(NaslValue::Null)
+    /// Includes the file of the given key, see [Interpreter::include].
+    pub(crate) async fn include_key(&mut self, key: &str) -> InterpretResult {
+        let include_once = self.ctxconfigs.include_once();
+        if include_once && self.included().contains(key) {
+            tracing::trace!(key, "skipping already included file");
+            return Ok(NaslValue::Null);
+        }
+        let ctx = self.ctxconfigs;
+        let code;
+        let statements = match ctx.parsed_include(key) {
+            Some(x) => Box::new(x.iter().cloned().map(Ok)),
+            None => {
+                code = ctx.loader().load(key)?;
+                ctx.parse(&code)
             }
-            _ => Err(InterpretError::unsupported(name, "string")),
+        };
+
+        let mut inter = Interpreter::new(self.register().clone(), self.ctxconfigs);
+        inter.trace = self.trace.clone();
+        if include_once {
+            let mut included = self.included().clone();
+            included.insert(key.to_string());
+            inter.run_specific[0].included = included;
+        }
+        // the included statements are nested within the include statement, otherwise a
+        // cyclic include would never reach the maximum depth
+        *inter.position_mut() = self.position().clone();
+        for stmt in statements {
+            self.execute_statements(key, &mut inter, stmt).await?;
+        }
+        self.set_register(inter.register().clone());
+        if include_once {
+            self.run_specific[self.index].included =
+                std::mem::take(&mut inter.run_specific[inter.index].included);
+        }
+        Ok(NaslValue::Null)
+    }
+
+    /// Advances the position as if the next statement was interpreted.
+    ///
+    /// Is used when the statement got interpreted by another interpreter.
+    pub(crate) fn skip_next(&mut self) {
+        if let Some(last) = self.position_mut().index.last_mut() {
+            *last += 1;
         }
     }
 
@@ -208,6 +282,12 @@ impl<'a> Interpreter<'a> {
 
     /// Interprets a Statement
     pub(crate) async fn resolve(&mut self, statement: &Statement) -> InterpretResult {
+        if self.position().depth() >= MAX_DEPTH {
+            return Err(InterpretError::from_statement(
+                statement,
+                InterpretErrorKind::MaxRecursionDepth(MAX_DEPTH),
+            ));
+        }
         self.position_mut().up();
         tracing::trace!(position=?self.position(), statement=statement.to_string(), "executing");
         // On a fork statement run we skip until the root index is reached. Between the root index
@@ -229,15 +309,22 @@ impl<'a> Interpreter<'a> {
             }
         }
 
+        let trace = self
+            .trace
+            .clone()
+            .filter(|_| trace::is_traced(statement))
+            .map(|x| {
+                let step = x.begin(self.position().depth(), statement);
+                (x, step, self.register().variables())
+            });
         let results = {
             match statement.kind() {
                 Include(inc) => Box::pin(self.include(inc)).await,
                 Array(position) => self.resolve_array(statement, position.clone()).await,
                 Exit(stmt) => self.resolve_exit(stmt).await,
                 Return(stmt) => self.resolve_return(stmt).await,
-                NamedParameter(..) => {
-                    unreachable!("named parameter should not be an executable statement.")
-                }
+                // a named parameter outside of a function call, e.g. `a: 1;`
+                NamedParameter(..) => Err(InterpretError::unsupported(statement, "statement")),
                 For(assignment, condition, update, body) => {
                     Box::pin(self.for_loop(assignment, condition, update, body)).await
                 }
@@ -279,6 +366,9 @@ impl<'a> Interpreter<'a> {
                 }
             })
         };
+        if let Some((trace, step, before)) = trace {
+            trace.end(step, &before, self.register(), &results);
+        }
         self.position_mut().down();
         results
     }
@@ -326,6 +416,10 @@ impl<'a> Interpreter<'a> {
         &mut self.run_specific[self.index].register
     }
 
+    pub(crate) fn included(&self) -> &HashSet<String> {
+        &self.run_specific[self.index].included
+    }
+
     pub(crate) fn position_mut(&mut self) -> &mut Position {
         &mut self.run_specific[self.index].position
     }
@@ -32,6 +32,8 @@ impl<'a> Interpreter<'a> {
         self.resolve(assignment).await?;
 
         loop {
+            // Gives the runtime the chance to abort a script that never leaves the loop
+            tokio::task::consume_budget().await;
             // Check condition statement
             if !bool::from(self.resolve(condition).await?) {
                 break;
@@ -101,6 +103,7 @@ impl<'a> Interpreter<'a> {
     /// it and resolve the body for every value in the array.
     pub async fn while_loop(&mut self, condition: &Statement, body: &Statement) -> InterpretResult {
         while bool::from(self.resolve(condition).await?) {
+            tokio::task::consume_budget().await;
             // Execute loop body
             let ret = self.resolve(body).await?;
             // Catch special values
@@ -128,6 +131,7 @@ impl<'a> Interpreter<'a> {
         condition: &Statement,
     ) -> InterpretResult {
         loop {
+            tokio::task::consume_budget().await;
             // Execute loop body
             let ret = self.resolve(body).await?;
             // Catch special values
@@ -15,6 +15,9 @@ mod include;
 mod interpreter;
 mod loop_extension;
 mod operator;
+mod parallel;
+mod preload;
+mod trace;
 
 #[cfg(test)]
 mod tests;
@@ -24,3 +27,5 @@ pub use error::FunctionError;
 pub use error::InterpretError;
 pub use error::InterpretErrorKind;
 pub use interpreter::Interpreter;
+pub use preload::preload;
+pub use trace::{Step as TraceStep, Trace};
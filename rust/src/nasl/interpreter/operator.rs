@@ -59,6 +59,22 @@ macro_rules! num_expr {
     };
 }
 
+pub(crate) fn divide(left: i64, right: i64) -> i64 {
+    left.checked_div(right).unwrap_or_default()
+}
+
+pub(crate) fn modulo(left: i64, right: i64) -> i64 {
+    left.checked_rem(right).unwrap_or_default()
+}
+
+pub(crate) fn shl(left: i64, right: i64) -> i64 {
+    left.wrapping_shl(right as u32)
+}
+
+pub(crate) fn shr(left: i64, right: i64) -> i64 {
+    left.wrapping_shr(right as u32)
+}
+
 fn match_regex(a: NaslValue, matches: Option<NaslValue>) -> InterpretResult {
     let right = matches.map(|x| x.to_string()).unwrap_or_default();
     match Regex::new(&right) {
@@ -76,7 +92,7 @@ macro_rules! add_left_right_string {
     ($left: ident, $right:ident) => {{
         let right = $right.map(|x| x.to_string()).unwrap_or_default();
         let x = $left;
-        Ok(NaslValue::String(format!("{x}{right}")))
+        Ok(NaslValue::String(format!("{x}{right}").into()))
     }};
 }
 
@@ -84,25 +100,40 @@ macro_rules! minus_left_right_string {
     ($left: ident, $right:ident) => {{
         let right = $right.map(|x| x.to_string()).unwrap_or_default();
         let x = $left.to_string();
-        Ok(NaslValue::String(x.replacen(&right, "", 1)))
+        Ok(NaslValue::String(x.replacen(&right, "", 1).into()))
     }};
 }
 
+/// Returns the bytes of the right side of a data operation.
+///
+/// Unlike `to_string` this keeps each byte of a Data value as it is.
+fn right_data(right: Option<NaslValue>) -> Vec<u8> {
+    match right {
+        Some(NaslValue::Data(x)) => x,
+        Some(x) => x.to_string().into_bytes(),
+        None => vec![],
+    }
+}
+
 macro_rules! add_left_right_data {
     ($left: ident, $right:ident) => {{
-        let right = $right.map(|x| x.to_string()).unwrap_or_default();
-        let x: Vec<u8> = $left.into();
-        let x: String = x.into_iter().map(|b| b as char).collect();
-        Ok(NaslValue::Data(format!("{x}{right}").into()))
+        let mut x: Vec<u8> = $left.into();
+        x.extend(right_data($right));
+        Ok(NaslValue::Data(x))
     }};
 }
 
 macro_rules! minus_left_right_data {
     ($left: ident, $right:ident) => {{
-        let right = $right.map(|x| x.to_string()).unwrap_or_default();
-        let x: Vec<u8> = $left.into();
-        let x: String = x.into_iter().map(|b| b as char).collect();
-        Ok(NaslValue::Data(x.replacen(&right, "", 1).into()))
+        let right = right_data($right);
+        let mut x: Vec<u8> = $left.into();
+        if let Some(i) = (!right.is_empty())
+            .then(|| x.windows(right.len()).position(|w| w == right))
+            .flatten()
+        {
+            x.drain(i..i + right.len());
+        }
+        Ok(NaslValue::Data(x))
     }};
 }
 
@@ -124,7 +155,7 @@ impl<'a> Interpreter<'a> {
                         Some(NaslValue::Data(_)) => add_left_right_data!(left, b),
                         _ => {
                             let right = b.map(|x| i64::from(&x)).unwrap_or_default();
-                            Ok(NaslValue::Number(i64::from(&left) + right))
+                            Ok(NaslValue::Number(i64::from(&left).wrapping_add(right)))
                         }
                     },
                 })
@@ -139,8 +170,8 @@ impl<'a> Interpreter<'a> {
                         Some(NaslValue::Data(_)) => minus_left_right_data!(left, b),
                         _ => {
                             let result = match b {
-                                Some(right) => i64::from(&left) - i64::from(&right),
-                                None => -i64::from(&left),
+                                Some(right) => i64::from(&left).wrapping_sub(i64::from(&right)),
+                                None => i64::from(&left).wrapping_neg(),
                             };
                             Ok(NaslValue::Number(result))
                         }
@@ -149,11 +180,17 @@ impl<'a> Interpreter<'a> {
                 .await
             }
             // number
-            TokenCategory::Star => self.execute(stmts, |a, b| num_expr!(* a b)).await,
-            TokenCategory::Slash => self.execute(stmts, |a, b| num_expr!(/ a b)).await,
-            TokenCategory::Percent => self.execute(stmts, |a, b| num_expr!(% a b)).await,
-            TokenCategory::LessLess => self.execute(stmts, |a, b| num_expr!(<< a b)).await,
-            TokenCategory::GreaterGreater => self.execute(stmts, |a, b| num_expr!(>> a b)).await,
+            TokenCategory::Star => {
+                self.execute(stmts, |a, b| num_expr!(i64::wrapping_mul => a b))
+                    .await
+            }
+            // like openvas-nasl a division by zero results in 0
+            TokenCategory::Slash => self.execute(stmts, |a, b| num_expr!(divide => a b)).await,
+            TokenCategory::Percent => self.execute(stmts, |a, b| num_expr!(modulo => a b)).await,
+            TokenCategory::LessLess => self.execute(stmts, |a, b| num_expr!(shl => a b)).await,
+            TokenCategory::GreaterGreater => {
+                self.execute(stmts, |a, b| num_expr!(shr => a b)).await
+            }
             // let left_casted = left as u32; (left_casted >> right) as i64
             TokenCategory::GreaterGreaterGreater => {
                 self.execute(
@@ -161,7 +198,7 @@ impl<'a> Interpreter<'a> {
                     //|a, b| num_expr!(|a, b| ((a as u32) >> b) as i32 => a b),
                     |a, b| {
                         let (left, right) = as_i64(a, b);
-                        let result = ((left as u32) >> right) as i32;
+                        let result = (left as u32).wrapping_shr(right as u32) as i32;
                         Ok(NaslValue::Number(result as i64))
                     },
                 )
@@ -173,7 +210,7 @@ impl<'a> Interpreter<'a> {
             TokenCategory::StarStar => {
                 self.execute(stmts, |a, b| {
                     let (a, b) = as_i64(a, b);
-                    let result = (a as u32).pow(b as u32);
+                    let result = (a as u32).wrapping_pow(b as u32);
                     Ok(NaslValue::Number(result as i64))
                 })
                 .await
@@ -273,7 +310,7 @@ impl<'a> Interpreter<'a> {
                     return Ok(NaslValue::Null);
                 }
                 let repeatable = &stmts[0];
-                for _ in 1..repeat - 1 {
+                for _ in 1..repeat.saturating_sub(1) {
                     self.resolve(repeatable).await?;
                 }
                 self.resolve(repeatable).await
@@ -286,6 +323,30 @@ impl<'a> Interpreter<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::nasl::test_prelude::*;
+
+    #[test]
+    fn data_keeps_bytes() {
+        check_code_result(
+            "raw_string(0xff) + raw_string(0x80, 0);",
+            vec![0xffu8, 0x80, 0],
+        );
+        check_code_result("raw_string(0xc3) + 1;", vec![0xc3u8, b'1']);
+        check_code_result("raw_string(1, 0xff, 2) - raw_string(0xff);", vec![1u8, 2]);
+    }
+
+    #[test]
+    fn numbers_do_not_overflow() {
+        check_code_result("1 / 0;", 0);
+        check_code_result("1 % 0;", 0);
+        check_code_result("9223372036854775807 + 1;", i64::MIN);
+        check_code_result("1 << 65;", 2);
+        check_code_result("2 ** 32;", 0);
+        let mut t = TestBuilder::default();
+        t.run("a = 1;");
+        t.ok("a /= 0;", 0);
+        t.ok("a %= 0;", 0);
+    }
 
     // use crate::*;
 
@@ -0,0 +1,247 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Finds top-level statements that can be interpreted concurrently.
+//!
+//! This is an experimental research mode used by [super::CodeInterpreter::parallel]. A statement
+//! is considered to be independent when:
+//! - it only calls pure builtin functions, which neither access the KB, the network or results
+//!   nor fork the interpreter, see [PURE_FUNCTIONS],
+//! - it does not call user defined functions, as they may access any global variable,
+//! - it contains no include, exit, return, break, continue, variable or function declaration.
+//!
+//! Consecutive independent statements are grouped into a batch as long as no statement writes a
+//! variable that another statement of the batch reads or writes.
+
+use std::collections::HashSet;
+
+use crate::nasl::syntax::{Statement, StatementKind};
+use crate::nasl::utils::Context;
+
+use super::Interpreter;
+
+/// Builtin functions without side effects that only depend on their arguments.
+///
+/// A statement calling any other function is interpreted sequentially, so that a batch can be
+/// interpreted again without repeating a side effect.
+const PURE_FUNCTIONS: &[&str] = &[
+    // strings
+    "chomp",
+    "crap",
+    "data_to_hexstr",
+    "hex",
+    "hexstr",
+    "hexstr_to_data",
+    "insstr",
+    "int",
+    "match",
+    "ord",
+    "raw_string",
+    "split",
+    "str_replace",
+    "strcat",
+    "stridx",
+    "string",
+    "strlen",
+    "substr",
+    "tolower",
+    "toupper",
+    // regular expressions
+    "egrep",
+    "ereg",
+    "ereg_replace",
+    "eregmatch",
+    // arrays
+    "keys",
+    "make_array",
+    "make_list",
+    "max_index",
+    "sort",
+    // values
+    "dec2str",
+    "isnull",
+    "typeof",
+    // hashes
+    "HMAC_MD2",
+    "HMAC_MD5",
+    "HMAC_RIPEMD160",
+    "HMAC_SHA1",
+    "HMAC_SHA256",
+    "HMAC_SHA384",
+    "HMAC_SHA512",
+    "MD2",
+    "MD4",
+    "MD5",
+    "RIPEMD160",
+    "SHA1",
+    "SHA256",
+    "SHA512",
+];
+
+/// The variables a statement reads and writes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Footprint {
+    pub(crate) reads: HashSet<String>,
+    pub(crate) writes: HashSet<String>,
+}
+
+impl Footprint {
+    /// Returns the footprint of an independent statement or None when the statement may depend on
+    /// shared state.
+    pub(crate) fn of(statement: &Statement, context: &Context) -> Option<Self> {
+        let mut result = Self::default();
+        result.visit(statement, context).then_some(result)
+    }
+
+    /// Returns true when both statements can run concurrently.
+    pub(crate) fn is_disjoint(&self, other: &Self) -> bool {
+        self.writes.is_disjoint(&other.writes)
+            && self.writes.is_disjoint(&other.reads)
+            && self.reads.is_disjoint(&other.writes)
+    }
+
+    /// Adds the variables of other
+    pub(crate) fn extend(&mut self, other: &Self) {
+        self.reads.extend(other.reads.iter().cloned());
+        self.writes.extend(other.writes.iter().cloned());
+    }
+
+    fn name(statement: &Statement) -> Option<String> {
+        Interpreter::identifier(statement.as_token()).ok()
+    }
+
+    fn visit_all(&mut self, statements: &[Statement], context: &Context) -> bool {
+        statements.iter().all(|x| self.visit(x, context))
+    }
+
+    fn visit(&mut self, statement: &Statement, context: &Context) -> bool {
+        use StatementKind::*;
+        match statement.kind() {
+            Primitive | AttackCategory | NoOp | EoF => true,
+            Variable => Self::name(statement)
+                .map(|x| self.reads.insert(x))
+                .is_some(),
+            Array(lookup) => {
+                Self::name(statement)
+                    .map(|x| self.reads.insert(x))
+                    .is_some()
+                    && lookup.as_ref().is_none_or(|x| self.visit(x, context))
+            }
+            Assign(_, _, target, value) => {
+                let lookup = match target.kind() {
+                    Variable | Array(None) => None,
+                    Array(Some(lookup)) => Some(lookup),
+                    _ => return false,
+                };
+                let Some(name) = Self::name(target) else {
+                    return false;
+                };
+                // e.g. `a += 1` or `a[1] = 1` depend on the previous value as well
+                self.reads.insert(name.clone());
+                self.writes.insert(name);
+                lookup.is_none_or(|x| self.visit(x, context)) && self.visit(value, context)
+            }
+            Call(parameter) => {
+                let Some(name) = Self::name(statement) else {
+                    return false;
+                };
+                // user defined functions are stored in the register and not known to the context
+                context.nasl_fn_defined(&name)
+                    && PURE_FUNCTIONS.contains(&name.as_str())
+                    && self.visit(parameter, context)
+            }
+            NamedParameter(value) => self.visit(value, context),
+            Parameter(x) | Operator(_, x) | Block(x) => self.visit_all(x, context),
+            If(condition, then, _, otherwise) => {
+                self.visit(condition, context)
+                    && self.visit(then, context)
+                    && otherwise.as_ref().is_none_or(|x| self.visit(x, context))
+            }
+            For(assignment, condition, update, body) => {
+                self.visit(assignment, context)
+                    && self.visit(condition, context)
+                    && self.visit(update, context)
+                    && self.visit(body, context)
+            }
+            While(a, b) | Repeat(a, b) => self.visit(a, context) && self.visit(b, context),
+            ForEach(variable, iterable, body) => {
+                let Ok(name) = Interpreter::identifier(variable) else {
+                    return false;
+                };
+                self.writes.insert(name);
+                self.visit(iterable, context) && self.visit(body, context)
+            }
+            Exit(_)
+            | Return(_)
+            | Break
+            | Continue
+            | Include(_)
+            | Declare(_)
+            | FunctionDeclaration(..) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nasl::prelude::*;
+    use crate::nasl::syntax::parse;
+
+    use super::Footprint;
+
+    fn footprint(code: &str) -> Option<(Vec<String>, Vec<String>)> {
+        let factory = ContextFactory::default();
+        let context = factory.build(Default::default());
+        let stmt = parse(code).next().unwrap().unwrap();
+        Footprint::of(&stmt, &context).map(|x| {
+            let mut reads: Vec<_> = x.reads.into_iter().collect();
+            let mut writes: Vec<_> = x.writes.into_iter().collect();
+            reads.sort();
+            writes.sort();
+            (reads, writes)
+        })
+    }
+
+    fn names(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn footprints() {
+        assert_eq!(
+            footprint("a = b + c[d];"),
+            Some((names(&["a", "b", "c", "d"]), names(&["a"])))
+        );
+        assert_eq!(
+            footprint("foreach x (l) { y[x] = hexstr(x); }"),
+            Some((names(&["l", "x", "y"]), names(&["x", "y"])))
+        );
+        assert_eq!(
+            footprint("for (i = 0; i < 3; i++) l[i] = hexstr(i);"),
+            Some((names(&["i", "l"]), names(&["i", "l"])))
+        );
+        // only pure builtins are batched
+        assert_eq!(footprint("display(1);"), None);
+        assert_eq!(footprint("a = rand();"), None);
+        assert_eq!(footprint("a = recv(socket: s);"), None);
+        assert_eq!(footprint("set_kb_item(name: 'a', value: 1);"), None);
+        assert_eq!(footprint("a = get_kb_item('a');"), None);
+        assert_eq!(footprint("a = unknown_function();"), None);
+        assert_eq!(footprint("include('a.inc');"), None);
+        assert_eq!(footprint("exit(0);"), None);
+        assert_eq!(footprint("local_var a;"), None);
+    }
+
+    #[test]
+    fn disjoint() {
+        let factory = ContextFactory::default();
+        let context = factory.build(Default::default());
+        let fp = |code: &str| Footprint::of(&parse(code).next().unwrap().unwrap(), &context);
+        let a = fp("a = x;").unwrap();
+        assert!(a.is_disjoint(&fp("b = x;").unwrap()));
+        assert!(!a.is_disjoint(&fp("b = a;").unwrap()));
+        assert!(!a.is_disjoint(&fp("x = 1;").unwrap()));
+        assert!(!a.is_disjoint(&fp("a = 1;").unwrap()));
+    }
+}
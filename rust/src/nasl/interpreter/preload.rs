@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Preloads libraries into a base that the registers of scripts are layered on.
+//!
+//! Instead of interpreting the same include files for each script on each host, the files are
+//! interpreted once and the defined functions and global variables are shared via an immutable
+//! [RegisterBase]. Scripts using the base skip including the preloaded files again.
+
+use crate::nasl::prelude::*;
+use crate::nasl::utils::RegisterBase;
+
+use super::{interpreter::Interpreter, InterpretError};
+
+/// Interprets the given include files in order and returns their definitions as base.
+///
+/// The files are interpreted without a target, so their top-level code must not depend on the
+/// scanned host.
+pub async fn preload(
+    files: &[String],
+    context: &Context<'_>,
+) -> Result<RegisterBase, InterpretError> {
+    let mut interpreter = Interpreter::new(Register::default(), context);
+    for file in files {
+        interpreter.include_key(file).await?;
+    }
+    let included = interpreter
+        .included()
+        .iter()
+        .chain(files)
+        .cloned()
+        .collect();
+    Ok(interpreter.register().clone().into_base(included))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use futures::StreamExt;
+
+    use crate::nasl::{interpreter::CodeInterpreter, nasl_std_functions, syntax::LoadError};
+    use crate::storage::DefaultDispatcher;
+
+    use super::*;
+
+    struct Files(HashMap<String, String>);
+
+    impl Loader for Files {
+        fn load(&self, key: &str) -> Result<String, LoadError> {
+            self.0
+                .get(key)
+                .cloned()
+                .ok_or_else(|| LoadError::NotFound(key.to_string()))
+        }
+        fn root_path(&self) -> Result<String, LoadError> {
+            Ok(String::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn layered() {
+        let files = Files(HashMap::from([
+            (
+                "base.inc".to_string(),
+                "loaded = 1; function double(x) { return x * 2; }".to_string(),
+            ),
+            (
+                "lib.inc".to_string(),
+                r#"include("base.inc"); loaded++; function quad(x) { return double(x: double(x: x)); }"#
+                    .to_string(),
+            ),
+        ]));
+        let context = ContextFactory {
+            loader: files,
+            functions: nasl_std_functions(),
+            storage: DefaultDispatcher::default(),
+            extensions: Default::default(),
+        };
+        let ctx = context.build(Default::default());
+        let base = Arc::new(preload(&["lib.inc".to_string()], &ctx).await.unwrap());
+        assert_eq!(base.included(), ["base.inc", "lib.inc"]);
+
+        let run = |code: &'static str| {
+            let register = Register::layered(base.clone());
+            let ctx = &ctx;
+            async move {
+                let results: Vec<_> = CodeInterpreter::new(code, register, ctx)
+                    .stream()
+                    .collect()
+                    .await;
+                results.into_iter().next_back().unwrap().unwrap()
+            }
+        };
+        // the preloaded files are not included again
+        let code = r#"include("lib.inc"); quad(x: loaded);"#;
+        assert_eq!(run(code).await, NaslValue::Number(8));
+        // assigning shadows the definition of the base without changing it
+        assert_eq!(run("loaded = 5; loaded;").await, NaslValue::Number(5));
+        assert_eq!(run("loaded;").await, NaslValue::Number(2));
+    }
+
+    #[tokio::test]
+    async fn missing() {
+        let context = ContextFactory {
+            loader: Files(HashMap::new()),
+            functions: nasl_std_functions(),
+            storage: DefaultDispatcher::default(),
+            extensions: Default::default(),
+        };
+        let ctx = context.build(Default::default());
+        assert!(preload(&["missing.inc".to_string()], &ctx).await.is_err());
+    }
+}
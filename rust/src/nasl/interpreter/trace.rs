@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Records the statements executed by an interpreter to debug a misbehaving script.
+//!
+//! Each executed assignment, declaration, call, return, exit and include is stored as a [Step]
+//! together with the variables it changed and the value it returned or the error it failed with.
+//! Steps are stored in the order the statements start, nested statements, e.g. the statements of
+//! a called function, follow the statement containing them with a higher depth.
+//!
+//! A trace is written as json lines, one step per line, so that it can be filtered with common
+//! tools as well.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::nasl::{
+    syntax::{NaslValue, Statement, StatementKind},
+    utils::{cassette::Value, Register},
+};
+
+use super::interpreter::InterpretResult;
+
+/// An executed statement
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Step {
+    /// Nesting of the statement within the script and called functions
+    pub depth: usize,
+    /// Line of the statement within the script or include file
+    pub line: usize,
+    pub statement: String,
+    /// Variables changed by the statement, a variable that went out of scope is null
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub changed: BTreeMap<String, Value>,
+    /// The returned value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error the statement failed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Returns true when the statement is recorded.
+///
+/// Expressions are part of the recorded statement, the statements within blocks and loops are
+/// recorded on their own.
+pub(crate) fn is_traced(statement: &Statement) -> bool {
+    matches!(
+        statement.kind(),
+        StatementKind::Assign(..)
+            | StatementKind::Call(_)
+            | StatementKind::Declare(_)
+            | StatementKind::Return(_)
+            | StatementKind::Exit(_)
+            | StatementKind::Include(_)
+    )
+}
+
+/// The steps of an interpreter run
+#[derive(Debug, Default)]
+pub struct Trace {
+    steps: Mutex<Vec<Step>>,
+}
+
+impl Trace {
+    /// Creates an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn locked(&self) -> MutexGuard<'_, Vec<Step>> {
+        // a panic while holding the lock does not leave the steps in an inconsistent state
+        self.steps.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the start of a statement and returns the index of its step.
+    pub(crate) fn begin(&self, depth: usize, statement: &Statement) -> usize {
+        let mut steps = self.locked();
+        steps.push(Step {
+            depth,
+            line: statement.as_token().line_column.0,
+            statement: statement.to_string().trim_end_matches(';').to_string(),
+            changed: BTreeMap::new(),
+            result: None,
+            error: None,
+        });
+        steps.len() - 1
+    }
+
+    /// Records the outcome of a statement and the variables changed compared to before.
+    pub(crate) fn end(
+        &self,
+        index: usize,
+        before: &BTreeMap<String, NaslValue>,
+        register: &Register,
+        result: &InterpretResult,
+    ) {
+        let after = register.variables();
+        let mut changed: BTreeMap<_, _> = after
+            .iter()
+            .filter(|(k, v)| before.get(*k) != Some(v))
+            .map(|(k, v)| (k.clone(), Value::from(v)))
+            .collect();
+        changed.extend(
+            before
+                .keys()
+                .filter(|x| !after.contains_key(*x))
+                .map(|x| (x.clone(), Value::Null)),
+        );
+        if let Some(step) = self.locked().get_mut(index) {
+            step.changed = changed;
+            match result {
+                Ok(x) => step.result = Some(x.into()),
+                Err(e) => step.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// Returns the recorded steps.
+    pub fn steps(&self) -> Vec<Step> {
+        self.locked().clone()
+    }
+
+    /// Writes the steps as json lines.
+    pub fn write<W>(&self, mut w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        for step in self.locked().iter() {
+            serde_json::to_writer(&mut w, step).map_err(io::Error::from)?;
+            w.write_all(b"\n")?;
+        }
+        w.flush()
+    }
+
+    /// Reads the steps written by [Trace::write].
+    pub fn read<R>(r: R) -> io::Result<Vec<Step>>
+    where
+        R: io::BufRead,
+    {
+        r.lines()
+            .filter(|x| !matches!(x, Ok(x) if x.trim().is_empty()))
+            .map(|x| serde_json::from_str(&x?).map_err(io::Error::from))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::nasl::{interpreter::CodeInterpreter, prelude::*};
+
+    #[test]
+    fn records_steps() {
+        let code = r#"
+        function add(a, b) {
+          return a + b;
+        }
+        x = 1;
+        if (x) {
+          y = add(a: x, b: 2);
+        }
+        z = undefined();
+        "#;
+        let trace = Arc::new(Trace::new());
+        let factory = ContextFactory::default();
+        let context = factory.build(Default::default());
+        let interpreter =
+            CodeInterpreter::new(code, Register::default(), &context).traced(trace.clone());
+        futures::executor::block_on(async {
+            let _: Vec<_> = futures::StreamExt::collect(interpreter.stream()).await;
+        });
+        let steps = trace.steps();
+        let summary: Vec<_> = steps
+            .iter()
+            .map(|x| (x.line, x.statement.as_str(), x.changed.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (5, "x = 1", 1),
+                (7, "y = add(a: x, b: 2)", 1),
+                (7, "add(a: x, b: 2)", 0),
+                (3, "return a + b", 0),
+                (9, "z = undefined()", 0),
+                (9, "undefined()", 0),
+            ]
+        );
+        assert_eq!(steps[1].changed["y"], Value::Number(3));
+        assert!(steps[1].depth < steps[2].depth && steps[2].depth < steps[3].depth);
+        assert_eq!(steps[3].result, Some(Value::Number(3)));
+        assert!(steps[5].error.is_some());
+
+        let mut buffer = vec![];
+        trace.write(&mut buffer).unwrap();
+        assert_eq!(buffer.iter().filter(|x| **x == b'\n').count(), 6);
+        assert_eq!(Trace::read(buffer.as_slice()).unwrap(), steps);
+    }
+}
@@ -36,6 +36,9 @@ pub mod test_prelude {
     pub use crate::check_err_matches;
 }
 
+pub use builtin::enabled_builtins;
+pub use builtin::nasl_offline_functions;
 pub use builtin::nasl_std_functions;
+pub use builtin::raw_sockets_available;
 
 pub use syntax::NoOpLoader;
@@ -0,0 +1,465 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Loads NASL code from a single archive of the feed instead of a directory.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+
+use super::{ContentReader, LoadError, Loader};
+
+const BLOCK_SIZE: u64 = 512;
+const ZIP_LOCAL_HEADER: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const ZIP_CENTRAL_HEADER: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+const ZIP_END_OF_CENTRAL_DIRECTORY: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+
+#[derive(Debug, Clone)]
+enum Entry {
+    /// Uncompressed content at the offset of the archive
+    Stored { offset: u64, size: u64 },
+    /// Content of a zip archive described by the local header at offset
+    Zip {
+        header: u64,
+        compressed: u64,
+        method: u16,
+    },
+    /// Content of a compressed tar archive
+    Memory(Vec<u8>),
+}
+
+/// Is a plugin loader based on a tar or zip archive of the feed.
+///
+/// Supported are uncompressed (`.tar`) and gzip compressed (`.tar.gz`, `.tgz`) tar archives as
+/// well as zip archives (`.zip`) using no or deflate compression. The keys are the paths within
+/// the archive without a leading `./`.
+///
+/// On creation only an index of the archive is built, the content is read on each load. As a
+/// compressed tar archive cannot be read at an offset its content is kept in memory.
+#[derive(Debug)]
+pub struct ArchiveLoader {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn c_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+fn octal(bytes: &[u8]) -> io::Result<u64> {
+    let value = c_str(bytes);
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(value, 8).map_err(|e| invalid(format!("invalid number {value}: {e}")))
+}
+
+/// Returns the path of pax extended header records (`<length> <key>=<value>\n`).
+fn pax_path(data: &[u8]) -> Option<String> {
+    let data = String::from_utf8_lossy(data);
+    data.lines()
+        .filter_map(|x| x.split_once(' ').map(|(_, record)| record))
+        .find_map(|x| x.strip_prefix("path=").map(|x| x.to_string()))
+}
+
+fn skip<R: Read>(reader: &mut R, amount: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.by_ref().take(amount), &mut io::sink())?;
+    if skipped != amount {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_tar<R: Read>(mut reader: R, in_memory: bool) -> io::Result<HashMap<String, Entry>> {
+    let mut entries = HashMap::new();
+    let mut offset = 0;
+    let mut long_name = None;
+    let mut header = [0; BLOCK_SIZE as usize];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // some archives are missing the closing empty blocks
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        offset += BLOCK_SIZE;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = octal(&header[124..136])?;
+        let padded = size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        match header[156] {
+            // GNU long name and pax extended header of the following entry
+            kind @ (b'L' | b'x') => {
+                let mut data = vec![0; padded as usize];
+                reader.read_exact(&mut data)?;
+                data.truncate(size as usize);
+                let name = if kind == b'L' {
+                    Some(c_str(&data))
+                } else {
+                    pax_path(&data)
+                };
+                long_name = name.or(long_name);
+            }
+            b'0' | 0 => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = c_str(&header[0..100]);
+                    let prefix = c_str(&header[345..500]);
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{prefix}/{name}")
+                    } else {
+                        name
+                    }
+                });
+                let entry = if in_memory {
+                    let mut data = vec![0; size as usize];
+                    reader.read_exact(&mut data)?;
+                    skip(&mut reader, padded - size)?;
+                    Entry::Memory(data)
+                } else {
+                    skip(&mut reader, padded)?;
+                    Entry::Stored { offset, size }
+                };
+                insert(&mut entries, &name, entry);
+            }
+            _ => {
+                long_name = None;
+                skip(&mut reader, padded)?;
+            }
+        }
+        offset += padded;
+    }
+    Ok(entries)
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn read_zip(file: &mut File) -> io::Result<HashMap<String, Entry>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    // the end of central directory record is 22 bytes followed by a comment of up to 64KiB
+    let tail_len = len.min(22 + u16::MAX as u64);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let end = tail
+        .windows(4)
+        .rposition(|x| x == ZIP_END_OF_CENTRAL_DIRECTORY)
+        .filter(|x| tail.len() - x >= 22)
+        .ok_or_else(|| invalid("missing end of central directory"))?;
+    let end = &tail[end..];
+    let count = u16_le(end, 10);
+    let (size, offset) = (u32_le(end, 12), u32_le(end, 16));
+    if count == u16::MAX || offset == u32::MAX {
+        return Err(invalid("zip64 archives are not supported"));
+    }
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut directory = vec![0; size as usize];
+    file.read_exact(&mut directory)?;
+
+    let mut entries = HashMap::new();
+    let mut pos = 0;
+    for _ in 0..count {
+        let header = directory
+            .get(pos..pos + 46)
+            .filter(|x| x[0..4] == ZIP_CENTRAL_HEADER)
+            .ok_or_else(|| invalid("invalid central directory"))?;
+        let name_len = u16_le(header, 28) as usize;
+        let extra_len = u16_le(header, 30) as usize;
+        let comment_len = u16_le(header, 32) as usize;
+        let entry = Entry::Zip {
+            header: u32_le(header, 42) as u64,
+            compressed: u32_le(header, 20) as u64,
+            method: u16_le(header, 10),
+        };
+        let name = directory
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(|| invalid("invalid central directory"))?;
+        insert(&mut entries, &String::from_utf8_lossy(name), entry);
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+fn insert(entries: &mut HashMap<String, Entry>, name: &str, entry: Entry) {
+    let mut name = name;
+    while let Some(x) = name.strip_prefix("./") {
+        name = x;
+    }
+    if !name.is_empty() && !name.ends_with('/') {
+        entries.insert(name.to_string(), entry);
+    }
+}
+
+impl ArchiveLoader {
+    /// Creates a loader by reading the index of the archive in path.
+    ///
+    /// The format is chosen based on the file extension.
+    pub fn open<P>(path: P) -> Result<Self, LoadError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let name = path.to_str().unwrap_or_default();
+        let load_error = |e: io::Error| match e.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                LoadError::Dirty(format!("{name}: {e}"))
+            }
+            _ => LoadError::from((name, e)),
+        };
+        let mut file = File::open(path).map_err(load_error)?;
+        let entries = if name.ends_with(".tar") {
+            read_tar(io::BufReader::new(file), false)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            read_tar(GzDecoder::new(io::BufReader::new(file)), true)
+        } else if name.ends_with(".zip") {
+            read_zip(&mut file)
+        } else {
+            return Err(LoadError::Dirty(format!(
+                "{name}: unsupported archive, expected .tar, .tar.gz, .tgz or .zip"
+            )));
+        }
+        .map_err(load_error)?;
+        Ok(Self {
+            path: path.to_owned(),
+            entries,
+        })
+    }
+
+    /// Returns the path of the archive
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true when the given path has the file extension of a supported archive.
+    pub fn is_archive<P>(path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        let name = path.as_ref().to_str().unwrap_or_default();
+        [".tar", ".tar.gz", ".tgz", ".zip"]
+            .iter()
+            .any(|x| name.ends_with(x))
+    }
+
+    /// Returns the keys of all files within the archive
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|x| x.as_str())
+    }
+
+    fn open_at(&self, key: &str, offset: u64) -> Result<File, LoadError> {
+        let mut file = File::open(&self.path).map_err(|e| LoadError::from((key, e)))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| LoadError::from((key, e)))?;
+        Ok(file)
+    }
+}
+
+impl ContentReader for ArchiveLoader {
+    fn open(&self, key: &str) -> Result<Box<dyn Read + Send + Sync + '_>, LoadError> {
+        match self.entries.get(key) {
+            None => Err(LoadError::NotFound(key.to_string())),
+            Some(Entry::Memory(data)) => Ok(Box::new(&data[..])),
+            Some(Entry::Stored { offset, size }) => {
+                Ok(Box::new(self.open_at(key, *offset)?.take(*size)))
+            }
+            Some(Entry::Zip {
+                header,
+                compressed,
+                method,
+            }) => {
+                let mut file = self.open_at(key, *header)?;
+                let mut local = [0; 30];
+                file.read_exact(&mut local)
+                    .map_err(|e| LoadError::from((key, e)))?;
+                if local[0..4] != ZIP_LOCAL_HEADER {
+                    return Err(LoadError::Dirty(format!("{key}: invalid local header")));
+                }
+                let data = 30 + u16_le(&local, 26) as u64 + u16_le(&local, 28) as u64;
+                let file = self.open_at(key, header + data)?.take(*compressed);
+                match method {
+                    0 => Ok(Box::new(file)),
+                    8 => Ok(Box::new(DeflateDecoder::new(file))),
+                    x => Err(LoadError::Dirty(format!(
+                        "{key}: unsupported compression method {x}"
+                    ))),
+                }
+            }
+        }
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+}
+
+impl Loader for ArchiveLoader {
+    fn load(&self, key: &str) -> Result<String, LoadError> {
+        let mut content = vec![];
+        self.open(key)?
+            .read_to_end(&mut content)
+            .map_err(|e| LoadError::from((key, e)))?;
+        // unfortunately nasl is still in iso-8859-1
+        Ok(content.iter().map(|&b| b as char).collect())
+    }
+
+    fn root_path(&self) -> Result<String, LoadError> {
+        Ok(self.path.to_str().unwrap_or_default().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    const FILES: [(&str, &str); 3] = [
+        ("./plugin_feed_info.inc", "PLUGIN_SET = \"1\";"),
+        ("2024/test.nasl", "exit(0);"),
+        ("includes/ä.inc", "display('ä');"),
+    ];
+
+    fn tar() -> Vec<u8> {
+        let mut archive = vec![];
+        let mut add = |name: &str, kind: u8, data: &[u8]| {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+            header[156] = kind;
+            header[257..263].copy_from_slice(b"ustar\0");
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(data);
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        };
+        add("2024/", b'5', &[]);
+        for (name, code) in FILES {
+            if name.len() > 10 {
+                add("././@LongLink", b'L', name.as_bytes());
+                add("truncated", b'0', code.as_bytes());
+            } else {
+                add(name, b'0', code.as_bytes());
+            }
+        }
+        archive.extend_from_slice(&[0; 1024]);
+        archive
+    }
+
+    fn zip() -> Vec<u8> {
+        let mut archive = vec![];
+        let mut directory = vec![];
+        for (i, (name, code)) in FILES.iter().enumerate() {
+            // alternate between stored and deflate
+            let (method, data) = if i % 2 == 0 {
+                (0u16, code.as_bytes().to_vec())
+            } else {
+                let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+                encoder.write_all(code.as_bytes()).unwrap();
+                (8, encoder.finish().unwrap())
+            };
+            let offset = archive.len() as u32;
+            let mut local = ZIP_LOCAL_HEADER.to_vec();
+            local.extend_from_slice(&[20, 0, 0, 0]);
+            local.extend_from_slice(&method.to_le_bytes());
+            local.extend_from_slice(&[0; 8]);
+            local.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            local.extend_from_slice(&(code.len() as u32).to_le_bytes());
+            local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            local.extend_from_slice(&[0, 0]);
+            archive.extend_from_slice(&local);
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(&data);
+
+            let mut central = ZIP_CENTRAL_HEADER.to_vec();
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(code.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+            directory.extend_from_slice(&central);
+        }
+        let offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        archive.extend_from_slice(&ZIP_END_OF_CENTRAL_DIRECTORY);
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&(FILES.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(FILES.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&offset.to_le_bytes());
+        archive.extend_from_slice(&[0, 0]);
+        archive
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn archives() {
+        let dir = std::env::temp_dir().join(format!("archive-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archives = [
+            ("feed.tar", tar()),
+            ("feed.tar.gz", gzip(&tar())),
+            ("feed.zip", zip()),
+        ];
+        for (name, data) in archives {
+            let path = dir.join(name);
+            std::fs::write(&path, data).unwrap();
+            assert!(ArchiveLoader::is_archive(&path));
+            let loader = ArchiveLoader::open(&path).unwrap();
+            let mut keys: Vec<_> = loader.keys().collect();
+            keys.sort();
+            assert_eq!(
+                keys,
+                ["2024/test.nasl", "includes/ä.inc", "plugin_feed_info.inc"],
+                "{name}"
+            );
+            for (key, code) in FILES {
+                let key = key.trim_start_matches("./");
+                let expected: String = code.bytes().map(|b| b as char).collect();
+                assert_eq!(loader.load(key).unwrap(), expected, "{name}: {key}");
+            }
+            assert!(loader.contains("2024/test.nasl"));
+            assert_eq!(
+                loader.load("missing.nasl"),
+                Err(LoadError::NotFound("missing.nasl".to_string()))
+            );
+        }
+        std::fs::write(dir.join("feed.zip"), b"no zip").unwrap();
+        assert!(matches!(
+            ArchiveLoader::open(dir.join("feed.zip")),
+            Err(LoadError::Dirty(_))
+        ));
+        assert!(!ArchiveLoader::is_archive(dir.join("feed.7z")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
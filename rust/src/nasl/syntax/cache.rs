@@ -0,0 +1,285 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Pre-compiled representation of a script with an on-disk cache.
+//!
+//! A script is compiled by parsing it into statements and running them through `optimize`. The
+//! result is stored as CBOR in a cache directory using the sha256 sum of the code as the file name
+//! so that a changed script is recompiled automatically. Each file contains a format version, on a
+//! mismatch the script is recompiled and the file overwritten.
+//!
+//! [ParsedIncludes] keeps the statements of the include files of a scan in memory, so that they
+//! are loaded and parsed once per scan instead of by each script including them.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{optimize, parse, Loader, Statement, SyntaxError};
+
+/// Version of the compiled format.
+///
+/// Must be increased whenever Statement or Token change in a way that breaks deserialization.
+pub const COMPILED_FORMAT_VERSION: u32 = 1;
+
+/// Calculates the key used to identify code within the cache.
+pub fn code_hash(code: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(code.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Is a compiled script.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CompiledScript {
+    version: u32,
+    hash: String,
+    statements: Vec<Statement>,
+}
+
+impl CompiledScript {
+    /// Parses and optimizes the given code.
+    ///
+    /// Returns the first syntax error, in that case nothing can be cached.
+    pub fn compile(code: &str) -> Result<Self, SyntaxError> {
+        let statements = parse(code)
+            .map(|x| x.map(optimize))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            version: COMPILED_FORMAT_VERSION,
+            hash: code_hash(code),
+            statements,
+        })
+    }
+
+    /// Returns the sha256 sum of the code this script was compiled from.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Returns the compiled statements.
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    /// Returns the compiled statements.
+    pub fn into_statements(self) -> Vec<Statement> {
+        self.statements
+    }
+
+    /// Writes the compiled script as CBOR.
+    pub fn write<W>(&self, w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        ciborium::into_writer(self, w).map_err(io::Error::other)
+    }
+
+    /// Reads a compiled script previously written via `write`.
+    ///
+    /// Returns an error of kind `InvalidData` when the data was written by a different version.
+    pub fn read<R>(r: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let result: Self =
+            ciborium::from_reader(r).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if result.version != COMPILED_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported compiled format version {} expected {}",
+                    result.version, COMPILED_FORMAT_VERSION
+                ),
+            ));
+        }
+        Ok(result)
+    }
+}
+
+/// Caches compiled scripts within a directory.
+#[derive(Clone, Debug)]
+pub struct CompileCache {
+    root: PathBuf,
+}
+
+impl CompileCache {
+    /// Creates a new cache within the given directory.
+    ///
+    /// The directory is created when missing.
+    pub fn new<P>(root: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref().to_owned();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        self.root.join(format!("{hash}.ir"))
+    }
+
+    /// Returns the cached compiled script of the given hash.
+    ///
+    /// Returns None when there is no usable entry.
+    pub fn get(&self, hash: &str) -> Option<CompiledScript> {
+        let file = File::open(self.path(hash)).ok()?;
+        match CompiledScript::read(BufReader::new(file)) {
+            Ok(x) if x.hash == hash => Some(x),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::debug!(hash, error=%e, "ignoring cached script");
+                None
+            }
+        }
+    }
+
+    /// Stores the compiled script.
+    pub fn store(&self, script: &CompiledScript) -> io::Result<()> {
+        // write to a temporary file first so that a concurrent reader never sees a partial file
+        let path = self.path(&script.hash);
+        let tmp = path.with_extension(format!("ir.{}", std::process::id()));
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        script.write(&mut w)?;
+        w.flush()?;
+        drop(w);
+        fs::rename(tmp, path)
+    }
+
+    /// Returns the compiled statements of the given code.
+    ///
+    /// The code is compiled and stored when it is not cached yet. A failure to store a compiled
+    /// script is logged but not returned as the statements are still usable.
+    pub fn compile(&self, code: &str) -> Result<Vec<Statement>, SyntaxError> {
+        let hash = code_hash(code);
+        if let Some(x) = self.get(&hash) {
+            return Ok(x.into_statements());
+        }
+        let script = CompiledScript::compile(code)?;
+        if let Err(e) = self.store(&script) {
+            tracing::warn!(hash, error=%e, "unable to store compiled script");
+        }
+        Ok(script.into_statements())
+    }
+}
+
+/// Contains the parsed statements of include files by their key.
+///
+/// The files are loaded when the scan starts, changes of the files while it is running are not
+/// seen by its scripts.
+#[derive(Clone, Debug, Default)]
+pub struct ParsedIncludes {
+    files: HashMap<String, Vec<Statement>>,
+}
+
+impl ParsedIncludes {
+    /// Loads and parses the given include files, via the compile cache when one is given.
+    ///
+    /// Files that cannot be loaded or parsed are skipped. The scripts including them load them
+    /// themselves and report the error.
+    pub fn load<L, I>(loader: &L, keys: I, cache: Option<&CompileCache>) -> Self
+    where
+        L: Loader + ?Sized,
+        I: IntoIterator<Item = String>,
+    {
+        let mut files = HashMap::new();
+        for key in keys {
+            if files.contains_key(&key) {
+                continue;
+            }
+            let statements = loader
+                .load(&key)
+                .map_err(|e| e.to_string())
+                .and_then(|code| {
+                    match cache {
+                        Some(cache) => cache.compile(&code),
+                        None => parse(&code).collect(),
+                    }
+                    .map_err(|e| e.to_string())
+                });
+            match statements {
+                Ok(x) => {
+                    files.insert(key, x);
+                }
+                Err(error) => tracing::debug!(key, error, "unable to parse include in advance"),
+            }
+        }
+        Self { files }
+    }
+
+    /// Returns the statements of the include file, None when it was not parsed in advance.
+    pub fn get(&self, key: &str) -> Option<&[Statement]> {
+        self.files.get(key).map(|x| x.as_slice())
+    }
+
+    /// Returns the amount of parsed files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true when no file was parsed.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let script = CompiledScript::compile("a = 1 + 2; if (a) display(a);").unwrap();
+        assert_eq!(script.statements().len(), 2);
+        assert_eq!(script.statements()[0].to_string(), "a = 3");
+        let mut buf = vec![];
+        script.write(&mut buf).unwrap();
+        assert_eq!(CompiledScript::read(buf.as_slice()).unwrap(), script);
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let mut script = CompiledScript::compile("a = 1;").unwrap();
+        script.version = COMPILED_FORMAT_VERSION + 1;
+        let mut buf = vec![];
+        script.write(&mut buf).unwrap();
+        let err = CompiledScript::read(buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn cache_dir() {
+        let root = std::env::temp_dir().join(format!("nasl-compile-cache-{}", std::process::id()));
+        let cache = CompileCache::new(&root).unwrap();
+        let code = "a = 2 * 21;";
+        assert!(cache.get(&code_hash(code)).is_none());
+        let statements = cache.compile(code).unwrap();
+        assert_eq!(
+            cache.get(&code_hash(code)).unwrap().statements(),
+            statements
+        );
+        assert!(cache.compile("local_var 1;").is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn parsed_includes() {
+        let loader = |key: &str| match key {
+            "a.inc" => "a = 1; b = 2;".to_string(),
+            "broken.inc" => "local_var 1;".to_string(),
+            _ => String::new(),
+        };
+        let keys = ["a.inc", "broken.inc", "a.inc"].map(String::from);
+        let includes = ParsedIncludes::load(&loader, keys, None);
+        assert_eq!(includes.len(), 1);
+        assert_eq!(includes.get("a.inc").unwrap().len(), 2);
+        assert!(includes.get("broken.inc").is_none());
+    }
+}
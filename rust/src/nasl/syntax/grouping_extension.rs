@@ -25,25 +25,20 @@ impl<'a> Grouping for Lexer<'a> {
         let (end, right) = self.statement(0, &|cat| cat == &Category::RightParen)?;
 
         match end {
-            End::Done(end) => {
-                self.depth = 0;
-                Ok(match right.kind() {
-                    StatementKind::Assign(cat, _, first, second) => {
-                        Statement::with_start_end_token(
-                            token,
-                            end,
-                            StatementKind::Assign(
-                                cat.clone(),
-                                AssignOrder::AssignReturn,
-                                first.clone(),
-                                second.clone(),
-                            ),
-                        )
-                    }
+            End::Done(end) => Ok(match right.kind() {
+                StatementKind::Assign(cat, _, first, second) => Statement::with_start_end_token(
+                    token,
+                    end,
+                    StatementKind::Assign(
+                        cat.clone(),
+                        AssignOrder::AssignReturn,
+                        first.clone(),
+                        second.clone(),
+                    ),
+                ),
 
-                    _ => right,
-                })
-            }
+                _ => right,
+            }),
 
             End::Continue => Err(unclosed_token!(token)),
         }
@@ -55,7 +50,6 @@ impl<'a> Grouping for Lexer<'a> {
             if token.category() == &Category::RightCurlyBracket {
                 let _ = self.token();
 
-                self.depth = 0;
                 let stmt = Statement::with_start_end_token(
                     kw,
                     token.clone(),
@@ -6,7 +6,7 @@
 use std::ops::Not;
 
 use super::{
-    error::SyntaxError,
+    error::{ErrorKind, SyntaxError},
     operation::Operation,
     prefix_extension::Prefix,
     token::{Category, Token, Tokenizer},
@@ -21,10 +21,8 @@ pub struct Lexer<'a> {
     // to allopw statements of a Vec
     tokenizer: Tokenizer<'a>,
 
-    // is the current depth call within a statement call. The current
-    // implementation relies that the iterator implementation resets depth to 0
-    // after a statement, or error, has been returned.
-    pub(crate) depth: u8,
+    // is the amount of statement calls that are currently on the stack
+    depth: u8,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -34,7 +32,8 @@ pub enum End {
     Continue,
 }
 
-/// Is the maximum depth allowed within one continuous statement call.
+/// Is the maximum of nested statement calls, deeper nesting is an error instead of a stack
+/// overflow.
 const MAX_DEPTH: u8 = 42;
 
 impl End {
@@ -244,10 +243,7 @@ impl<'a> Lexer<'a> {
                 self.token();
                 let (end, nl) = self.infix_statement(op, y, token, left, abort)?;
                 match end {
-                    End::Done(cat) => {
-                        self.depth = 0;
-                        InFixState::ReturnEnd(cat, nl)
-                    }
+                    End::Done(cat) => InFixState::ReturnEnd(cat, nl),
                     End::Continue => InFixState::Unfinished(nl),
                 }
             }
@@ -268,10 +264,20 @@ impl<'a> Lexer<'a> {
         min_binding_power: u8,
         abort: &impl Fn(&Category) -> bool,
     ) -> Result<(End, Statement), SyntaxError> {
-        self.depth += 1;
         if self.depth >= MAX_DEPTH {
             return Err(max_recursion!(MAX_DEPTH));
         }
+        self.depth += 1;
+        let result = self.nested_statement(min_binding_power, abort);
+        self.depth -= 1;
+        result
+    }
+
+    fn nested_statement(
+        &mut self,
+        min_binding_power: u8,
+        abort: &impl Fn(&Category) -> bool,
+    ) -> Result<(End, Statement), SyntaxError> {
         fn done(token: Token, mut left: Statement) -> Result<(End, Statement), SyntaxError> {
             left.set_end(token.clone());
             Ok((End::Done(token), left))
@@ -307,7 +313,6 @@ impl<'a> Lexer<'a> {
         while let Some(token) = self.peek() {
             if abort(token.category()) {
                 self.token();
-                self.depth = 0;
                 return done(token, left);
             }
             let op = Operation::new(&token).ok_or_else(|| unexpected_token!(token.clone()))?;
@@ -353,11 +358,6 @@ impl<'a> Iterator for Lexer<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let result = self.statement(0, &|cat| cat == &Category::Semicolon);
-        // simulate eof if end::continue is stuck in a recursive loop
-        if self.depth >= MAX_DEPTH {
-            return None;
-        }
-
         match result {
             Ok((end, stmt)) => {
                 if matches!(stmt.kind(), &StatementKind::EoF) {
@@ -373,7 +373,13 @@ impl<'a> Iterator for Lexer<'a> {
                     End::Continue => Some(Err(unexpected_statement!(stmt))),
                 }
             }
-            Err(x) => Some(Err(x)),
+            Err(x) => {
+                if matches!(x.kind(), ErrorKind::MaxRecursionDepth(_)) {
+                    // the remaining tokens belong to the statement that is too deeply nested
+                    self.tokenizer.by_ref().for_each(drop);
+                }
+                Some(Err(x))
+            }
         }
     }
 }
@@ -584,3 +590,34 @@ mod postfix {
         expected(result("a[1]--;"), MinusMinus);
     }
 }
+
+#[cfg(test)]
+mod depth {
+    use super::super::{parse, ErrorKind};
+
+    #[test]
+    fn nesting_is_limited() {
+        let code = format!("{}1{}; a = 1;", "(".repeat(100), ")".repeat(100));
+        let result = parse(&code).collect::<Vec<_>>();
+        assert_eq!(result.len(), 1);
+        assert!(matches!(
+            result[0].as_ref().map_err(|e| e.kind()),
+            Err(ErrorKind::MaxRecursionDepth(_))
+        ));
+    }
+
+    #[test]
+    fn depth_is_reset_after_statement() {
+        let code = format!("{}1{};", "(".repeat(30), ")".repeat(30)).repeat(3);
+        assert!(parse(&code).all(|x| x.is_ok()));
+    }
+
+    #[test]
+    fn long_operator_chain() {
+        // the statement is as deep as the chain is long
+        let code = format!("a = 1{};", " + 1".repeat(100_000));
+        let result = parse(&code).collect::<Vec<_>>();
+        assert_eq!(result.len(), 1);
+        assert!(result[0].is_ok());
+    }
+}
@@ -52,6 +52,16 @@ pub trait Loader: Sync + Send {
     fn root_path(&self) -> Result<String, LoadError>;
 }
 
+/// Gives access to the raw content of a key
+///
+/// Is used to verify the files of a feed independent of where they are stored.
+pub trait ContentReader: Loader {
+    /// Opens the content of the given key
+    fn open(&self, key: &str) -> Result<Box<dyn io::Read + Send + Sync + '_>, LoadError>;
+    /// Returns true when the given key exists
+    fn contains(&self, key: &str) -> bool;
+}
+
 /// Returns given key as BufReader
 pub trait AsBufReader<P> {
     /// Returns given key as BufReader
@@ -85,9 +95,14 @@ impl Default for Box<dyn Loader> {
 ///
 /// So when the root path is `/var/lib/openvas/plugins` than it will be extended to
 /// `/var/lib/openvas/plugins/plugin_feed_info.inc`.
+///
+/// Additional directories (e.g. add-ons or local overrides) can be stacked on top of the root as
+/// overlays. A key is loaded from the last overlay containing it and from the root when no
+/// overlay contains it.
 #[derive(Debug, Clone)]
 pub struct FSPluginLoader {
     root: PathBuf,
+    overlays: Vec<PathBuf>,
 }
 
 impl From<(&Path, std::io::Error)> for LoadError {
@@ -118,18 +133,55 @@ impl FSPluginLoader {
     {
         Self {
             root: root.as_ref().to_owned(),
+            overlays: vec![],
         }
     }
 
+    /// Stacks the given directories on top of the root, a later one takes precedence.
+    pub fn with_overlays<I, P>(mut self, overlays: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.overlays
+            .extend(overlays.into_iter().map(|x| x.as_ref().to_owned()));
+        self
+    }
+
     /// Returns the used path
     pub fn root(&self) -> &Path {
         self.root.as_ref()
     }
+
+    /// Returns the overlay directories
+    pub fn overlays(&self) -> &[PathBuf] {
+        &self.overlays
+    }
+
+    /// Returns a loader without overlays for the root and each overlay ordered from the lowest
+    /// to the highest precedence.
+    pub fn layers(&self) -> Vec<FSPluginLoader> {
+        std::iter::once(&self.root)
+            .chain(&self.overlays)
+            .map(FSPluginLoader::new)
+            .collect()
+    }
+
+    /// Returns the path of the key within the directory with the highest precedence containing
+    /// it, or within the root when no directory contains it.
+    pub fn resolve(&self, key: &str) -> PathBuf {
+        self.overlays
+            .iter()
+            .rev()
+            .map(|x| x.join(key))
+            .find(|x| x.is_file())
+            .unwrap_or_else(|| self.root.join(key))
+    }
 }
 
 impl AsBufReader<File> for FSPluginLoader {
     fn as_bufreader(&self, key: &str) -> Result<io::BufReader<File>, LoadError> {
-        let path = self.root.join(key);
+        let path = self.resolve(key);
         match File::open(path).map_err(|e| LoadError::from((key, e))) {
             Ok(file) => Ok(io::BufReader::new(file)),
             Err(e) => Err(e),
@@ -139,7 +191,7 @@ impl AsBufReader<File> for FSPluginLoader {
 
 impl Loader for FSPluginLoader {
     fn load(&self, key: &str) -> Result<String, LoadError> {
-        let path = self.root.join(key);
+        let path = self.resolve(key);
         if !path.is_file() {
             return Err(LoadError::NotFound(format!(
                 "{} does not exist or is not accessible.",
@@ -156,6 +208,17 @@ impl Loader for FSPluginLoader {
     }
 }
 
+impl ContentReader for FSPluginLoader {
+    fn open(&self, key: &str) -> Result<Box<dyn io::Read + Send + Sync + '_>, LoadError> {
+        let file = File::open(self.resolve(key)).map_err(|e| LoadError::from((key, e)))?;
+        Ok(Box::new(file))
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.resolve(key).is_file()
+    }
+}
+
 impl<S> Loader for S
 where
     S: Fn(&str) -> String + Sync + Send,
@@ -168,3 +231,38 @@ where
         Ok(String::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{FSPluginLoader, Loader};
+
+    #[test]
+    fn overlays() {
+        let dir = std::env::temp_dir().join(format!("fs-plugin-loader-{}", std::process::id()));
+        let layers = ["feed", "addons", "local"].map(|x| dir.join(x));
+        for l in &layers {
+            fs::create_dir_all(l).unwrap();
+        }
+        fs::write(layers[0].join("a.inc"), "feed").unwrap();
+        fs::write(layers[0].join("b.inc"), "feed").unwrap();
+        fs::write(layers[1].join("b.inc"), "addons").unwrap();
+        fs::write(layers[1].join("c.inc"), "addons").unwrap();
+        fs::write(layers[2].join("c.inc"), "local").unwrap();
+
+        let loader = FSPluginLoader::new(&layers[0]).with_overlays(&layers[1..]);
+        assert_eq!(loader.load("a.inc").unwrap(), "feed");
+        assert_eq!(loader.load("b.inc").unwrap(), "addons");
+        assert_eq!(loader.load("c.inc").unwrap(), "local");
+        assert!(loader.load("d.inc").is_err());
+        assert_eq!(loader.resolve("d.inc"), layers[0].join("d.inc"));
+        let roots: Vec<_> = loader
+            .layers()
+            .iter()
+            .map(|x| x.root().to_owned())
+            .collect();
+        assert_eq!(roots, layers);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Loads NASL code from a memory mapped index of the feed.
+//!
+//! Opening and reading each include separately dominates the description run on slow disks and
+//! network filesystems. Instead the files of the feed are packed once into a single index file
+//! that is mapped into memory, so that a load is a lookup followed by a copy.
+//!
+//! The index starts with the magic `NASLIDX\x01` followed by the amount of entries as u64. Each
+//! entry consists of the length of the key as u16, the key, the offset and the length of the
+//! content as u64. All numbers are little endian, the offsets are absolute within the index.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use super::{ContentReader, LoadError, Loader};
+
+const MAGIC: &[u8; 8] = b"NASLIDX\x01";
+
+/// A read only memory mapping of a whole file
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read only and never changed after creation.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    fn new(file: &File) -> io::Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty index"));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Is a plugin loader serving the files of a feed from a memory mapped index.
+///
+/// The index is created by [MappedLoader::build] and must not be changed while it is in use.
+/// As [MappedLoader::build] replaces an existing index atomically, a loader keeps serving the
+/// previous version until it is opened again.
+pub struct MappedLoader {
+    path: PathBuf,
+    map: Mmap,
+    entries: HashMap<String, (usize, usize)>,
+}
+
+impl std::fmt::Debug for MappedLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedLoader")
+            .field("path", &self.path)
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+/// Reads the entries of an index, returns None when the index is corrupt.
+fn read_entries(data: &[u8]) -> Option<HashMap<String, (usize, usize)>> {
+    fn u64_at(data: &[u8], pos: &mut usize) -> Option<usize> {
+        let bytes = data.get(*pos..*pos + 8)?;
+        *pos += 8;
+        usize::try_from(u64::from_le_bytes(bytes.try_into().ok()?)).ok()
+    }
+    if data.get(..MAGIC.len())? != MAGIC {
+        return None;
+    }
+    let mut pos = MAGIC.len();
+    let count = u64_at(data, &mut pos)?;
+    let mut entries = HashMap::with_capacity(count.min(data.len()));
+    for _ in 0..count {
+        let len = u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        pos += 2;
+        let key = std::str::from_utf8(data.get(pos..pos + len)?).ok()?;
+        pos += len;
+        let offset = u64_at(data, &mut pos)?;
+        let size = u64_at(data, &mut pos)?;
+        if offset.checked_add(size)? > data.len() {
+            return None;
+        }
+        entries.insert(key.to_string(), (offset, size));
+    }
+    Some(entries)
+}
+
+impl MappedLoader {
+    /// Packs each file within root into a new index at path.
+    ///
+    /// The keys are the paths relative to root. The index is written into a temporary file first
+    /// and then renamed to path. Returns the amount of packed files.
+    pub fn build<R, P>(root: R, path: P) -> Result<usize, LoadError>
+    where
+        R: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        let (root, path) = (root.as_ref(), path.as_ref());
+        let io_error = |p: &Path, e: io::Error| LoadError::from((p, e));
+        let mut files = vec![];
+        for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+            let entry = entry.map_err(|e| LoadError::Dirty(e.to_string()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let key = entry
+                .path()
+                .strip_prefix(root)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace(std::path::MAIN_SEPARATOR, "/");
+            if key.len() > u16::MAX as usize {
+                return Err(LoadError::Dirty(format!("{key}: path is too long")));
+            }
+            let size = entry
+                .metadata()
+                .map_err(|e| LoadError::Dirty(e.to_string()))?
+                .len();
+            files.push((entry.into_path(), key, size));
+        }
+
+        let header: usize = MAGIC.len()
+            + 8
+            + files
+                .iter()
+                .map(|(_, key, _)| 2 + key.len() + 16)
+                .sum::<usize>();
+        let tmp = path.with_extension(format!("{}.part", std::process::id()));
+        let write = || -> io::Result<()> {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            out.write_all(MAGIC)?;
+            out.write_all(&(files.len() as u64).to_le_bytes())?;
+            let mut offset = header as u64;
+            for (_, key, size) in &files {
+                out.write_all(&(key.len() as u16).to_le_bytes())?;
+                out.write_all(key.as_bytes())?;
+                out.write_all(&offset.to_le_bytes())?;
+                out.write_all(&size.to_le_bytes())?;
+                offset += size;
+            }
+            for (file, _, size) in &files {
+                // a file changing in between would corrupt all following offsets
+                let copied = io::copy(&mut File::open(file)?.take(*size), &mut out)?;
+                if copied != *size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("{} changed while building the index", file.display()),
+                    ));
+                }
+            }
+            out.into_inner()?.sync_all()
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&tmp);
+            return Err(io_error(path, e));
+        }
+        fs::rename(&tmp, path).map_err(|e| io_error(path, e))?;
+        Ok(files.len())
+    }
+
+    /// Maps the index at path into memory.
+    pub fn open<P>(path: P) -> Result<Self, LoadError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let name = path.to_string_lossy();
+        let file = File::open(path).map_err(|e| LoadError::from((path, e)))?;
+        let map = Mmap::new(&file).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => LoadError::Dirty(format!("{name}: {e}")),
+            _ => LoadError::from((path, e)),
+        })?;
+        let entries = read_entries(map.as_slice())
+            .ok_or_else(|| LoadError::Dirty(format!("{name}: corrupt index")))?;
+        Ok(Self {
+            path: path.to_owned(),
+            map,
+            entries,
+        })
+    }
+
+    /// Returns the path of the index
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the amount of files within the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true when the index contains no files
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn get(&self, key: &str) -> Result<&[u8], LoadError> {
+        let (offset, size) = self
+            .entries
+            .get(key)
+            .ok_or_else(|| LoadError::NotFound(key.to_string()))?;
+        Ok(&self.map.as_slice()[*offset..*offset + *size])
+    }
+}
+
+impl ContentReader for MappedLoader {
+    fn open(&self, key: &str) -> Result<Box<dyn Read + Send + Sync + '_>, LoadError> {
+        Ok(Box::new(self.get(key)?))
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+}
+
+impl Loader for MappedLoader {
+    fn load(&self, key: &str) -> Result<String, LoadError> {
+        // unfortunately nasl is still in iso-8859-1
+        Ok(self.get(key)?.iter().map(|&b| b as char).collect())
+    }
+
+    fn root_path(&self) -> Result<String, LoadError> {
+        Ok(self.path.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_and_load() {
+        let dir = std::env::temp_dir().join(format!("mapped-loader-{}", std::process::id()));
+        let root = dir.join("feed");
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("plugin_feed_info.inc"), "PLUGIN_SET = \"1\";").unwrap();
+        fs::write(root.join("2024/test.nasl"), b"display('\xe4');").unwrap();
+        fs::write(root.join("empty.inc"), "").unwrap();
+        let index = dir.join("feed.idx");
+
+        assert_eq!(MappedLoader::build(&root, &index).unwrap(), 3);
+        let loader = MappedLoader::open(&index).unwrap();
+        assert_eq!(loader.len(), 3);
+        assert_eq!(
+            loader.load("plugin_feed_info.inc").unwrap(),
+            "PLUGIN_SET = \"1\";"
+        );
+        assert_eq!(loader.load("2024/test.nasl").unwrap(), "display('ä');");
+        assert_eq!(loader.load("empty.inc").unwrap(), "");
+        assert!(loader.contains("2024/test.nasl"));
+        assert_eq!(
+            loader.load("missing.nasl"),
+            Err(LoadError::NotFound("missing.nasl".to_string()))
+        );
+
+        // rebuilding does not affect an already opened index
+        fs::write(root.join("2024/test.nasl"), "exit(0);").unwrap();
+        MappedLoader::build(&root, &index).unwrap();
+        assert_eq!(loader.load("2024/test.nasl").unwrap(), "display('ä');");
+        let reopened = MappedLoader::open(&index).unwrap();
+        assert_eq!(reopened.load("2024/test.nasl").unwrap(), "exit(0);");
+
+        fs::write(&index, b"NASLIDX\x01\xff").unwrap();
+        assert!(matches!(
+            MappedLoader::open(&index),
+            Err(LoadError::Dirty(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,24 +3,38 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+mod archive;
+#[cfg(feature = "serde_support")]
+mod cache;
 mod cursor;
 mod error;
 mod grouping_extension;
 mod keyword_extension;
 mod lexer;
 mod loader;
+mod mapped;
+mod naslstring;
 mod naslvalue;
+mod object_store;
 mod operation;
+mod optimize;
 mod prefix_extension;
 mod statement;
 mod token;
 mod variable_extension;
 
 pub use crate::storage::item::ACT;
+pub use archive::ArchiveLoader;
+#[cfg(feature = "serde_support")]
+pub use cache::{code_hash, CompileCache, CompiledScript, ParsedIncludes, COMPILED_FORMAT_VERSION};
 pub use error::{ErrorKind, SyntaxError};
 pub use lexer::Lexer;
 pub use loader::*;
+pub use mapped::MappedLoader;
+pub use naslstring::NaslString;
 pub use naslvalue::*;
+pub use object_store::ObjectStoreLoader;
+pub use optimize::optimize;
 pub use statement::*;
 pub use token::Base as NumberBase;
 pub use token::Category as TokenCategory;
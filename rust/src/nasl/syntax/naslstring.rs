@@ -0,0 +1,127 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{borrow::Borrow, fmt, ops::Deref, sync::Arc};
+
+/// Is the immutable string representation of NASL.
+///
+/// The content is shared between clones so that passing strings around, e.g. within loops or
+/// between the interpreter and the storage, does not allocate. Manipulating a string creates a new
+/// one.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NaslString(Arc<str>);
+
+impl NaslString {
+    /// Returns the string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns true when both instances share the same allocation.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for NaslString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for NaslString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for NaslString {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl Borrow<str> for NaslString {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for NaslString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for NaslString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl From<String> for NaslString {
+    fn from(value: String) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<&String> for NaslString {
+    fn from(value: &String) -> Self {
+        Self(value.as_str().into())
+    }
+}
+
+impl From<&str> for NaslString {
+    fn from(value: &str) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<NaslString> for String {
+    fn from(value: NaslString) -> Self {
+        value.0.to_string()
+    }
+}
+
+impl From<&NaslString> for String {
+    fn from(value: &NaslString) -> Self {
+        value.0.to_string()
+    }
+}
+
+impl PartialEq<str> for NaslString {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for NaslString {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for NaslString {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NaslString;
+
+    #[test]
+    fn shared() {
+        let a = NaslString::from("hello");
+        let b = a.clone();
+        assert!(a.ptr_eq(&b));
+        assert_eq!(b, "hello");
+        assert_eq!(format!("{b}"), "hello");
+        assert_eq!(format!("{b:?}"), "\"hello\"");
+        assert_eq!(String::from(b), "hello".to_string());
+    }
+}
@@ -6,13 +6,13 @@ use std::{cmp::Ordering, collections::HashMap, fmt::Display};
 
 use crate::storage::types::Primitive;
 
-use super::{IdentifierType, Token, TokenCategory, ACT};
+use super::{IdentifierType, NaslString, Token, TokenCategory, ACT};
 
 /// Represents a valid Value of NASL
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub enum NaslValue {
     /// String value
-    String(String),
+    String(NaslString),
     /// Data value
     Data(Vec<u8>),
     /// Number value
@@ -59,7 +59,7 @@ impl NaslValue {
     pub fn as_primitive(self) -> Primitive {
         use Primitive::*;
         match self {
-            Self::String(s) => String(s),
+            Self::String(s) => String(s.into()),
             Self::Data(x) => Data(x),
             Self::Number(x) => Number(x),
             Self::Array(x) => Array(x.into_iter().map(|x| x.as_primitive()).collect()),
@@ -68,6 +68,18 @@ impl NaslValue {
             _ => Null,
         }
     }
+
+    /// Returns the bytes of a Data or String value without copying them.
+    ///
+    /// Data is returned as is, a String is returned as its utf8 representation. Every other
+    /// value returns None.
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Data(x) => Some(x),
+            Self::String(x) => Some(x.as_bytes()),
+            _ => None,
+        }
+    }
 }
 
 impl PartialOrd for NaslValue {
@@ -150,13 +162,13 @@ impl From<bool> for NaslValue {
 
 impl From<&str> for NaslValue {
     fn from(s: &str) -> Self {
-        Self::String(s.to_owned())
+        Self::String(s.into())
     }
 }
 
 impl From<String> for NaslValue {
     fn from(s: String) -> Self {
-        Self::String(s)
+        Self::String(s.into())
     }
 }
 
@@ -187,7 +199,7 @@ impl From<HashMap<String, NaslValue>> for NaslValue {
 impl From<NaslValue> for Vec<u8> {
     fn from(value: NaslValue) -> Self {
         match value {
-            NaslValue::String(x) => x.into(),
+            NaslValue::String(x) => x.as_bytes().to_vec(),
             NaslValue::Data(x) => x,
             NaslValue::Array(x) => x
                 .iter()
@@ -270,11 +282,11 @@ impl TryFrom<&Token> for NaslValue {
     fn try_from(token: &Token) -> Result<Self, Self::Error> {
         match token.category() {
             TokenCategory::String(category) | TokenCategory::IPv4Address(category) => {
-                Ok(NaslValue::String(category.clone()))
+                Ok(NaslValue::String(category.into()))
             }
             TokenCategory::Data(data) => Ok(NaslValue::Data(data.clone())),
             TokenCategory::Identifier(IdentifierType::Undefined(id)) => {
-                Ok(NaslValue::String(id.clone()))
+                Ok(NaslValue::String(id.into()))
             }
             TokenCategory::Number(num) => Ok(NaslValue::Number(*num)),
             TokenCategory::Identifier(IdentifierType::Null) => Ok(NaslValue::Null),
@@ -295,7 +307,7 @@ impl From<NaslValue> for Vec<NaslValue> {
             NaslValue::Data(ret) => ret.into_iter().map(|x| NaslValue::Data(vec![x])).collect(),
             NaslValue::String(ret) => ret
                 .chars()
-                .map(|x| NaslValue::String(x.to_string()))
+                .map(|x| NaslValue::String(x.to_string().into()))
                 .collect(),
             _ => vec![],
         }
@@ -306,7 +318,7 @@ impl From<Primitive> for NaslValue {
     fn from(value: Primitive) -> Self {
         use Primitive::*;
         match value {
-            String(x) => Self::String(x),
+            String(x) => Self::String(x.into()),
             Data(x) => Self::Data(x),
             Number(x) => Self::Number(x),
             Array(x) => Self::Array(x.into_iter().map(Self::from).collect()),
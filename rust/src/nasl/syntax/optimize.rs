@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Optional optimization pass over parsed statements.
+//!
+//! It folds operators whose operands are number or boolean literals into a single primitive and
+//! removes branches of `if` and `while` statements whose condition is a literal. Only operations
+//! that cannot fail or cause side effects while interpreting are folded, everything else is left
+//! untouched.
+
+use super::{IdentifierType, NaslValue, Statement, StatementKind, Token, TokenCategory};
+
+/// Folds constant expressions and removes dead branches of the given statement.
+///
+/// # Examples
+/// ```
+/// use scannerlib::nasl::syntax::{optimize, parse};
+/// let stmt = parse("if (0) display(1); else a = 2 * 3 + 1;")
+///     .next()
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(optimize(stmt).to_string(), "a = 7");
+/// ```
+pub fn optimize(stmt: Statement) -> Statement {
+    let (kind, start, end) = stmt.into_parts();
+    let opt = |x: Box<Statement>| Box::new(optimize(*x));
+    let opt_all = |x: Vec<Statement>| x.into_iter().map(optimize).collect::<Vec<_>>();
+    let kind = match kind {
+        StatementKind::Array(Some(x)) => StatementKind::Array(Some(opt(x))),
+        StatementKind::Call(x) => StatementKind::Call(opt(x)),
+        StatementKind::Exit(x) => StatementKind::Exit(opt(x)),
+        StatementKind::Return(x) => StatementKind::Return(opt(x)),
+        StatementKind::Include(x) => StatementKind::Include(opt(x)),
+        StatementKind::NamedParameter(x) => StatementKind::NamedParameter(opt(x)),
+        StatementKind::Declare(x) => StatementKind::Declare(x),
+        StatementKind::Parameter(x) => StatementKind::Parameter(opt_all(x)),
+        StatementKind::Block(x) => StatementKind::Block(opt_all(x)),
+        StatementKind::Assign(c, o, l, r) => StatementKind::Assign(c, o, l, opt(r)),
+        StatementKind::Operator(c, x) => {
+            let x = opt_all(x);
+            match fold_operator(&c, &x) {
+                Some(category) => {
+                    let token = Token {
+                        category,
+                        line_column: start.line_column,
+                        position: (start.position.0, end.as_ref().unwrap_or(&start).position.1),
+                    };
+                    return Statement::with_start_token(token, StatementKind::Primitive);
+                }
+                None => StatementKind::Operator(c, x),
+            }
+        }
+        StatementKind::If(c, x, et, e) => {
+            let c = optimize(*c);
+            match literal(&c).map(bool::from) {
+                Some(true) => return optimize(*x),
+                Some(false) => match e {
+                    Some(e) => return optimize(*e),
+                    None => StatementKind::NoOp,
+                },
+                None => StatementKind::If(Box::new(c), opt(x), et, e.map(opt)),
+            }
+        }
+        StatementKind::While(c, x) => {
+            let c = optimize(*c);
+            match literal(&c).map(bool::from) {
+                Some(false) => StatementKind::NoOp,
+                _ => StatementKind::While(Box::new(c), opt(x)),
+            }
+        }
+        StatementKind::For(i, c, u, x) => StatementKind::For(opt(i), opt(c), opt(u), opt(x)),
+        StatementKind::Repeat(x, c) => StatementKind::Repeat(opt(x), opt(c)),
+        StatementKind::ForEach(v, a, x) => StatementKind::ForEach(v, opt(a), opt(x)),
+        StatementKind::FunctionDeclaration(n, p, x) => {
+            StatementKind::FunctionDeclaration(n, p, opt(x))
+        }
+        kind @ (StatementKind::Primitive
+        | StatementKind::AttackCategory
+        | StatementKind::Variable
+        | StatementKind::Array(None)
+        | StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::NoOp
+        | StatementKind::EoF) => kind,
+    };
+    Statement::from_parts(kind, start, end)
+}
+
+/// Returns the value of a number, string or boolean literal.
+fn literal(stmt: &Statement) -> Option<NaslValue> {
+    match (stmt.kind(), stmt.as_token().category()) {
+        (
+            StatementKind::Primitive,
+            TokenCategory::Number(_)
+            | TokenCategory::String(_)
+            | TokenCategory::Data(_)
+            | TokenCategory::Identifier(
+                IdentifierType::True | IdentifierType::False | IdentifierType::Null,
+            ),
+        ) => NaslValue::try_from(stmt.as_token()).ok(),
+        _ => None,
+    }
+}
+
+fn number(stmt: &Statement) -> Option<i64> {
+    match (stmt.kind(), stmt.as_token().category()) {
+        (StatementKind::Primitive, TokenCategory::Number(x)) => Some(*x),
+        _ => None,
+    }
+}
+
+fn boolean(stmt: &Statement) -> Option<bool> {
+    match (stmt.kind(), stmt.as_token().category()) {
+        (StatementKind::Primitive, TokenCategory::Number(x)) => Some(*x != 0),
+        (StatementKind::Primitive, TokenCategory::Identifier(IdentifierType::True)) => Some(true),
+        (StatementKind::Primitive, TokenCategory::Identifier(IdentifierType::False)) => Some(false),
+        _ => None,
+    }
+}
+
+fn as_category(value: bool) -> TokenCategory {
+    if value {
+        TokenCategory::Identifier(IdentifierType::True)
+    } else {
+        TokenCategory::Identifier(IdentifierType::False)
+    }
+}
+
+fn fold_operator(category: &TokenCategory, stmts: &[Statement]) -> Option<TokenCategory> {
+    use TokenCategory::*;
+    match stmts {
+        [x] => match category {
+            Minus => number(x)?.checked_neg().map(Number),
+            Tilde => number(x).map(|x| Number(!x)),
+            Bang => boolean(x).map(|x| as_category(!x)),
+            _ => None,
+        },
+        [l, r] => {
+            if let (Some(l), Some(r)) = (number(l), number(r)) {
+                return match category {
+                    Plus => l.checked_add(r).map(Number),
+                    Minus => l.checked_sub(r).map(Number),
+                    Star => l.checked_mul(r).map(Number),
+                    Slash => l.checked_div(r).map(Number),
+                    Percent => l.checked_rem(r).map(Number),
+                    Ampersand => Some(Number(l & r)),
+                    Pipe => Some(Number(l | r)),
+                    Caret => Some(Number(l ^ r)),
+                    EqualEqual => Some(as_category(l == r)),
+                    BangEqual => Some(as_category(l != r)),
+                    Greater => Some(as_category(l > r)),
+                    Less => Some(as_category(l < r)),
+                    GreaterEqual => Some(as_category(l >= r)),
+                    LessEqual => Some(as_category(l <= r)),
+                    AmpersandAmpersand => Some(as_category(l != 0 && r != 0)),
+                    PipePipe => Some(as_category(l != 0 || r != 0)),
+                    _ => None,
+                };
+            }
+            match (category, boolean(l), boolean(r)) {
+                (AmpersandAmpersand, Some(l), Some(r)) => Some(as_category(l && r)),
+                (PipePipe, Some(l), Some(r)) => Some(as_category(l || r)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::optimize;
+    use crate::nasl::syntax::parse;
+
+    fn optimized(code: &str) -> Vec<String> {
+        parse(code)
+            .map(|x| optimize(x.unwrap()).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fold_numbers() {
+        assert_eq!(
+            optimized("a = 1 + 2 * 3; b = -(4 - 5); c = 7 / 0; d = x + 1;"),
+            vec!["a = 7", "b = 1", "c = 7 / 0", "d = x + 1"]
+        );
+    }
+
+    #[test]
+    fn fold_booleans() {
+        assert_eq!(
+            optimized("a = 1 < 2; b = !TRUE; c = FALSE || 1 == 1;"),
+            vec!["a = TRUE", "b = FALSE", "c = TRUE"]
+        );
+    }
+
+    #[test]
+    fn remove_dead_branches() {
+        assert_eq!(
+            optimized(
+                r#"
+            if (0) display(1);
+            if (1 - 1) display(2); else display(3);
+            if ("a") display(4);
+            while (FALSE) display(5);
+            if (description) display(6);
+            "#
+            ),
+            vec![
+                "NoOp",
+                "display(3);",
+                "display(4);",
+                "NoOp",
+                "if (description) display(6);"
+            ]
+        );
+    }
+}
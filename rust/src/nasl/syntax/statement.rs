@@ -11,6 +11,10 @@ use super::{SyntaxError, Token, TokenCategory};
 
 /// Specifies the order of assignment
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum AssignOrder {
     /// Assign first than return
     AssignReturn,
@@ -20,6 +24,10 @@ pub enum AssignOrder {
 
 /// Is a executable step.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum StatementKind {
     /// Either a Number, String, Boolean or Null
     Primitive,
@@ -84,6 +92,10 @@ pub enum StatementKind {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 /// Is the definition of a Statement
 ///
 /// start returns a token of the beginning of that statement while end contains
@@ -380,6 +392,82 @@ impl Statement {
     pub(crate) fn set_end(&mut self, cat: Token) {
         self.end = Some(cat)
     }
+
+    /// Splits the statement into its kind, start and end token.
+    pub(crate) fn into_parts(mut self) -> (StatementKind, Token, Option<Token>) {
+        (
+            std::mem::replace(&mut self.kind, StatementKind::NoOp),
+            std::mem::take(&mut self.start),
+            self.end.take(),
+        )
+    }
+
+    /// Creates a statement based on the parts returned by `into_parts`.
+    pub(crate) fn from_parts(kind: StatementKind, start: Token, end: Option<Token>) -> Self {
+        Self { kind, start, end }
+    }
+}
+
+/// Moves the kinds of the direct children of a statement onto the stack.
+fn take_children(kind: StatementKind, stack: &mut Vec<StatementKind>) {
+    let mut push =
+        |mut x: Statement| stack.push(std::mem::replace(&mut x.kind, StatementKind::NoOp));
+    match kind {
+        StatementKind::Array(Some(x))
+        | StatementKind::Call(x)
+        | StatementKind::Exit(x)
+        | StatementKind::Return(x)
+        | StatementKind::Include(x)
+        | StatementKind::NamedParameter(x) => push(*x),
+        StatementKind::Declare(x)
+        | StatementKind::Parameter(x)
+        | StatementKind::Operator(_, x)
+        | StatementKind::Block(x) => x.into_iter().for_each(push),
+        StatementKind::Assign(_, _, x, y)
+        | StatementKind::While(x, y)
+        | StatementKind::Repeat(x, y)
+        | StatementKind::ForEach(_, x, y)
+        | StatementKind::FunctionDeclaration(_, x, y) => {
+            push(*x);
+            push(*y);
+        }
+        StatementKind::If(x, y, _, z) => {
+            push(*x);
+            push(*y);
+            if let Some(z) = z {
+                push(*z);
+            }
+        }
+        StatementKind::For(w, x, y, z) => {
+            push(*w);
+            push(*x);
+            push(*y);
+            push(*z);
+        }
+        StatementKind::Primitive
+        | StatementKind::AttackCategory
+        | StatementKind::Variable
+        | StatementKind::Array(None)
+        | StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::NoOp
+        | StatementKind::EoF => {}
+    }
+}
+
+impl Drop for Statement {
+    // A long operator chain like `1 + 1 + ...` results in a statement that is as deep as the
+    // chain is long, dropping it recursively would overflow the stack.
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        take_children(
+            std::mem::replace(&mut self.kind, StatementKind::NoOp),
+            &mut stack,
+        );
+        while let Some(kind) = stack.pop() {
+            take_children(kind, &mut stack);
+        }
+    }
 }
 
 impl std::fmt::Display for Statement {
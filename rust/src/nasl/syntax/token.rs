@@ -11,6 +11,10 @@ use crate::storage::item::ACT;
 
 /// Identifies if number is base10, base 8, hex or binary
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum Base {
     /// Base 2: contains 01 is defined by 0b e.g.: `0b010101`
     Binary,
@@ -60,6 +64,10 @@ impl Base {
 
 /// Is used to identify which Category type is unclosed
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum UnclosedCategory {
     /// Is a unclosed String.
     String,
@@ -121,6 +129,10 @@ impl Display for IdentifierType {
 
 /// Unless Dynamic those are reserved words that cannot be reused otherwise.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum IdentifierType {
     /// function declaration
     Function,
@@ -201,6 +213,10 @@ make_keyword_matcher! {
 
 /// Is used to identify a Token
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum Category {
     /// `(`
     LeftParen,
@@ -395,6 +411,10 @@ impl Display for Category {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 /// Contains the TokenType as well as the position.
 pub struct Token {
     /// The category or kind of a token
@@ -0,0 +1,275 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Learns during a scan which hosts ask not to be scanned and stops scanning them.
+//!
+//! Two kinds of abuse signals are taken into account:
+//! - the share of the recent connections to a host that were reset or rejected as
+//!   administratively prohibited, e.g. by a firewall or an intrusion prevention system reacting to
+//!   the scan,
+//! - a configured "stop scanning me" marker contained in data received from the host.
+//!
+//! Once a host is blocked no further connection to it is allowed and its remaining VTs are not
+//! launched, the other hosts of the scan continue.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io,
+    sync::Mutex,
+};
+
+use crate::models::ScanPreference;
+
+/// Preference id of the share of reset or prohibited connections within the window above which a
+/// host is blocked, 0 disables it.
+pub const ABUSE_SIGNAL_RATE: &str = "abuse_signal_rate";
+/// Preference id of the amount of recent connections the share is calculated from.
+pub const ABUSE_SIGNAL_WINDOW: &str = "abuse_signal_window";
+/// Preference id of the comma separated markers blocking a host when it sends one of them.
+pub const STOP_SCAN_MARKERS: &str = "stop_scan_markers";
+
+/// Window when it is not set
+pub const DEFAULT_WINDOW: usize = 50;
+
+/// Outcome of a connection attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Answered,
+    Reset,
+    Prohibited,
+}
+
+impl Signal {
+    /// Classifies the outcome of a connection attempt, None for outcomes that say nothing about
+    /// the host, e.g. a timeout.
+    fn of<T>(result: &io::Result<T>) -> Option<Self> {
+        match result {
+            Ok(_) => Some(Self::Answered),
+            Err(e) => match e.kind() {
+                io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {
+                    Some(Self::Reset)
+                }
+                // an ICMP administratively prohibited is reported as unreachable host or network
+                io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => {
+                    Some(Self::Prohibited)
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Reason a host was blocked
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockReason {
+    /// The share of reset connections exceeded the configured rate
+    Resets(f64),
+    /// The share of administratively prohibited connections exceeded the configured rate
+    Prohibited(f64),
+    /// The host sent the contained marker
+    Marker(String),
+}
+
+impl Display for BlockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Resets(rate) => write!(
+                f,
+                "{:.0}% of the recent connections were reset",
+                rate * 100.0
+            ),
+            Self::Prohibited(rate) => write!(
+                f,
+                "{:.0}% of the recent connections were administratively prohibited",
+                rate * 100.0
+            ),
+            Self::Marker(marker) => write!(f, "the host sent the stop marker \"{marker}\""),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    signals: HashMap<String, VecDeque<Signal>>,
+    blocked: HashMap<String, BlockReason>,
+}
+
+/// Blocks hosts of a scan based on abuse signals.
+///
+/// Without a rate and markers nothing is tracked.
+#[derive(Default)]
+pub struct HostBlocklist {
+    rate: Option<f64>,
+    window: usize,
+    markers: Vec<String>,
+    state: Mutex<State>,
+}
+
+impl HostBlocklist {
+    /// Creates a blocklist, a rate of none or 0 disables the blocking by connection outcomes.
+    pub fn new(rate: Option<f64>, window: usize, markers: Vec<String>) -> Self {
+        Self {
+            rate: rate.filter(|x| *x > 0.0),
+            window: window.max(1),
+            markers: markers.into_iter().filter(|x| !x.is_empty()).collect(),
+            state: Default::default(),
+        }
+    }
+
+    /// Creates a blocklist based on the `abuse_signal_rate`, `abuse_signal_window` and
+    /// `stop_scan_markers` scan preferences.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let value = |id: &str| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+        };
+        let rate = value(ABUSE_SIGNAL_RATE).and_then(|x| x.parse().ok());
+        let window = value(ABUSE_SIGNAL_WINDOW)
+            .and_then(|x| x.parse().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(DEFAULT_WINDOW);
+        let markers = value(STOP_SCAN_MARKERS)
+            .map(|x| x.split(',').map(|x| x.trim().to_owned()).collect())
+            .unwrap_or_default();
+        Self::new(rate, window, markers)
+    }
+
+    /// Returns the reason when the host is blocked.
+    pub fn reason(&self, host: &str) -> Option<BlockReason> {
+        self.state
+            .lock()
+            .ok()
+            .and_then(|x| x.blocked.get(host).cloned())
+    }
+
+    /// Returns all blocked hosts and the reason.
+    pub fn blocked(&self) -> HashMap<String, BlockReason> {
+        self.state
+            .lock()
+            .map(|x| x.blocked.clone())
+            .unwrap_or_default()
+    }
+
+    /// Records the outcome of establishing a connection to the host.
+    ///
+    /// The host is blocked once the window is filled and the share of reset or prohibited
+    /// connections within it reaches the rate.
+    pub fn record_connect<T>(&self, host: &str, result: &io::Result<T>) {
+        let (Some(rate), Some(signal)) = (self.rate, Signal::of(result)) else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.blocked.contains_key(host) {
+            return;
+        }
+        let signals = state.signals.entry(host.to_owned()).or_default();
+        signals.push_back(signal);
+        if signals.len() > self.window {
+            signals.pop_front();
+        }
+        if signals.len() < self.window {
+            return;
+        }
+        let share =
+            |kind| signals.iter().filter(|x| **x == kind).count() as f64 / signals.len() as f64;
+        let (resets, prohibited) = (share(Signal::Reset), share(Signal::Prohibited));
+        if resets + prohibited < rate {
+            return;
+        }
+        let reason = if prohibited > resets {
+            BlockReason::Prohibited(resets + prohibited)
+        } else {
+            BlockReason::Resets(resets + prohibited)
+        };
+        Self::block(&mut state, host, reason);
+    }
+
+    /// Records data received from the host, the host is blocked when it contains a marker.
+    pub fn record_received(&self, host: &str, data: &[u8]) {
+        if self.markers.is_empty() {
+            return;
+        }
+        let data = String::from_utf8_lossy(data);
+        if let Some(marker) = self.markers.iter().find(|x| data.contains(x.as_str())) {
+            if let Ok(mut state) = self.state.lock() {
+                Self::block(&mut state, host, BlockReason::Marker(marker.clone()));
+            }
+        }
+    }
+
+    fn block(state: &mut State, host: &str, reason: BlockReason) {
+        if state.blocked.contains_key(host) {
+            return;
+        }
+        tracing::warn!(host, %reason, "stop scanning host");
+        state.signals.remove(host);
+        state.blocked.insert(host.to_owned(), reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{BlockReason, HostBlocklist, ABUSE_SIGNAL_RATE, STOP_SCAN_MARKERS};
+    use crate::models::ScanPreference;
+
+    fn preference(id: &str, value: &str) -> ScanPreference {
+        ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn signal_rate() {
+        let refused: io::Result<()> = Err(io::ErrorKind::ConnectionRefused.into());
+        let prohibited: io::Result<()> = Err(io::ErrorKind::HostUnreachable.into());
+        let timed_out: io::Result<()> = Err(io::ErrorKind::TimedOut.into());
+        let blocklist = HostBlocklist::new(Some(0.5), 4, vec![]);
+        blocklist.record_connect("a", &Ok(()));
+        blocklist.record_connect("a", &refused);
+        blocklist.record_connect("a", &timed_out);
+        blocklist.record_connect("a", &Ok(()));
+        assert_eq!(blocklist.reason("a"), None);
+        blocklist.record_connect("a", &refused);
+        assert_eq!(blocklist.reason("a"), Some(BlockReason::Resets(0.5)));
+
+        for _ in 0..4 {
+            blocklist.record_connect("b", &prohibited);
+        }
+        assert_eq!(blocklist.reason("b"), Some(BlockReason::Prohibited(1.0)));
+        assert_eq!(blocklist.blocked().len(), 2);
+
+        let disabled = HostBlocklist::from_preferences(&[]);
+        for _ in 0..100 {
+            disabled.record_connect("a", &refused);
+        }
+        assert_eq!(disabled.reason("a"), None);
+    }
+
+    #[test]
+    fn markers() {
+        let blocklist = HostBlocklist::from_preferences(&[
+            preference(ABUSE_SIGNAL_RATE, "0"),
+            preference(STOP_SCAN_MARKERS, "X-No-Scan, please stop"),
+        ]);
+        blocklist.record_received("a", b"HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(blocklist.reason("a"), None);
+        blocklist.record_received("a", b"HTTP/1.1 200 OK\r\nX-No-Scan: 1\r\n\r\n");
+        assert_eq!(
+            blocklist.reason("a"),
+            Some(BlockReason::Marker("X-No-Scan".into()))
+        );
+        assert_eq!(
+            blocklist.reason("a").unwrap().to_string(),
+            "the host sent the stop marker \"X-No-Scan\""
+        );
+    }
+}
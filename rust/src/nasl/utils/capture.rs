@@ -0,0 +1,471 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Records the traffic of a script run against a host into rotating pcap files.
+//!
+//! The recording is disabled unless openvasd is configured with a pcap directory. The live capture
+//! requires the `nasl-builtin-raw-ip` feature as well as the privileges to capture packets.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Preference id of the maximum size in bytes of a pcap file before it is rotated.
+pub const PCAP_MAX_FILE_SIZE: &str = "pcap_max_file_size";
+/// Preference id of the maximum of kept pcap files per host and script.
+pub const PCAP_MAX_FILES: &str = "pcap_max_files";
+/// Preference id of the comma separated OIDs of the scripts to record, empty records all.
+pub const PCAP_OIDS: &str = "pcap_oids";
+
+/// Link type of ethernet frames.
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const SNAPLEN: u32 = 65535;
+const FILE_HEADER_LEN: u64 = 24;
+const RECORD_HEADER_LEN: u64 = 16;
+
+struct PcapFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl PcapFile {
+    fn create(path: PathBuf, linktype: u32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        // timezone offset and accuracy of the timestamps
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&SNAPLEN.to_le_bytes())?;
+        writer.write_all(&linktype.to_le_bytes())?;
+        Ok(Self {
+            path,
+            writer,
+            size: FILE_HEADER_LEN,
+        })
+    }
+
+    fn write(&mut self, timestamp: Duration, original_len: u32, data: &[u8]) -> io::Result<()> {
+        let data = &data[..data.len().min(SNAPLEN as usize)];
+        self.writer
+            .write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&timestamp.subsec_micros().to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&original_len.max(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.size += RECORD_HEADER_LEN + data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Writes packets into pcap files, starting a new file when the maximum size is reached.
+///
+/// The files are named `<prefix>-<n>.pcap`, only the newest `max_files` files are kept.
+pub struct RotatingPcapWriter {
+    prefix: PathBuf,
+    linktype: u32,
+    max_size: u64,
+    max_files: usize,
+    index: usize,
+    current: Option<PcapFile>,
+}
+
+impl RotatingPcapWriter {
+    /// Creates a new RotatingPcapWriter
+    ///
+    /// A max_size or max_files of 0 disables the corresponding limit.
+    pub fn new(prefix: PathBuf, linktype: u32, max_size: u64, max_files: usize) -> Self {
+        Self {
+            prefix,
+            linktype,
+            max_size,
+            max_files,
+            index: 0,
+            current: None,
+        }
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        let mut name = self.prefix.as_os_str().to_owned();
+        name.push(format!("-{index}.pcap"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.writer.flush()?;
+            self.index += 1;
+        }
+        if self.max_files > 0 && self.index >= self.max_files {
+            let _ = fs::remove_file(self.path(self.index - self.max_files));
+        }
+        self.current = Some(PcapFile::create(self.path(self.index), self.linktype)?);
+        Ok(())
+    }
+
+    /// Writes a packet captured at the given time.
+    pub fn write(&mut self, time: SystemTime, original_len: u32, data: &[u8]) -> io::Result<()> {
+        let required = RECORD_HEADER_LEN + data.len() as u64;
+        let full = match &self.current {
+            None => true,
+            Some(x) => {
+                self.max_size > 0 && x.size > FILE_HEADER_LEN && x.size + required > self.max_size
+            }
+        };
+        if full {
+            self.rotate()?;
+        }
+        let timestamp = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        match &mut self.current {
+            Some(x) => x.write(timestamp, original_len, data),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the buffered packets into the current file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(x) => x.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the file currently written to.
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|x| x.path.as_path())
+    }
+
+    /// Returns all kept files, oldest first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let first = match self.max_files {
+            0 => 0,
+            max => (self.index + 1).saturating_sub(max),
+        };
+        (first..=self.index)
+            .map(|x| self.path(x))
+            .filter(|x| x.exists())
+            .collect()
+    }
+}
+
+/// Starts the recordings of a scan based on the scan preferences.
+#[derive(Debug, Default, Clone)]
+pub struct PacketRecorder {
+    directory: Option<PathBuf>,
+    max_size: u64,
+    max_files: usize,
+    oids: Vec<String>,
+    interface: Option<String>,
+}
+
+impl PacketRecorder {
+    /// Creates a new PacketRecorder based on the `pcap_max_file_size`, `pcap_max_files` and
+    /// `pcap_oids` scan preferences.
+    ///
+    /// Nothing is recorded until a directory is set via [PacketRecorder::with_directory].
+    pub fn from_preferences(preferences: &[crate::models::ScanPreference]) -> Self {
+        let value = |id: &str| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+                .filter(|x| !x.is_empty())
+        };
+        let number = |id: &str, default: u64| {
+            value(id)
+                .and_then(|x| x.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            directory: None,
+            max_size: number(PCAP_MAX_FILE_SIZE, 10 * 1024 * 1024),
+            max_files: number(PCAP_MAX_FILES, 5) as usize,
+            oids: value(PCAP_OIDS)
+                .map(|x| x.split(',').map(|x| x.trim().to_owned()).collect())
+                .unwrap_or_default(),
+            interface: None,
+        }
+    }
+
+    /// Records into the given directory, it is configured by the scanner and not by the scan as
+    /// the files are written with the privileges of the scanner.
+    pub fn with_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.directory = directory;
+        self
+    }
+
+    /// Captures on the given interface instead of all interfaces.
+    pub fn with_interface(mut self, interface: Option<String>) -> Self {
+        self.interface = interface;
+        self
+    }
+
+    /// Returns true when the script with the given OID is recorded.
+    pub fn is_enabled(&self, oid: &str) -> bool {
+        self.directory.is_some() && (self.oids.is_empty() || self.oids.iter().any(|x| x == oid))
+    }
+
+    fn prefix(&self, host: &str, oid: &str) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        let name: String = format!("{host}_{oid}")
+            .chars()
+            .map(|x| match x {
+                '/' | '\\' | ':' => '_',
+                x => x,
+            })
+            .collect();
+        Some(directory.join(name))
+    }
+
+    /// Starts recording the traffic to the given addresses of a host for a script.
+    ///
+    /// Returns None when recording is disabled for the script or the capture cannot be started.
+    pub fn start(&self, host: &str, oid: &str, addrs: &[IpAddr]) -> Option<Recording> {
+        if !self.is_enabled(oid) || addrs.is_empty() {
+            return None;
+        }
+        let prefix = self.prefix(host, oid)?;
+        if let Some(directory) = &self.directory {
+            if let Err(e) = fs::create_dir_all(directory) {
+                tracing::warn!(directory=?directory, error=%e, "unable to create pcap directory");
+                return None;
+            }
+        }
+        let (capture, linktype) = match live::open(self.interface.as_deref(), addrs) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::warn!(host, oid, error=%e, "unable to start packet capture");
+                return None;
+            }
+        };
+        let writer = Arc::new(Mutex::new(RotatingPcapWriter::new(
+            prefix,
+            linktype,
+            self.max_size,
+            self.max_files,
+        )));
+        Some(Recording {
+            stop: Some(live::spawn(capture, writer.clone())),
+            writer,
+        })
+    }
+}
+
+/// A running capture, it is stopped on drop.
+pub struct Recording {
+    writer: Arc<Mutex<RotatingPcapWriter>>,
+    stop: Option<live::Stop>,
+}
+
+impl Recording {
+    /// Returns the file the packets are currently written to.
+    pub fn current_file(&self) -> Option<String> {
+        self.writer
+            .lock()
+            .ok()
+            .and_then(|x| x.current_file().map(|x| x.to_string_lossy().to_string()))
+    }
+
+    /// Returns all files of the recording, oldest first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.writer.lock().map(|x| x.files()).unwrap_or_default()
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        // stop the capture before the remaining packets are flushed
+        self.stop = None;
+        if let Ok(mut writer) = self.writer.lock() {
+            if let Err(e) = writer.flush() {
+                tracing::warn!(error=%e, "unable to write pcap file");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nasl-builtin-raw-ip")]
+mod live {
+    use std::{
+        io,
+        net::IpAddr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, SystemTime},
+    };
+
+    use super::RotatingPcapWriter;
+
+    pub type Capture = pcap::Capture<pcap::Active>;
+
+    /// Stops the capture thread on drop.
+    pub struct Stop {
+        stop: Arc<AtomicBool>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl Drop for Stop {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    fn timestamp(ts: &libc::timeval) -> SystemTime {
+        SystemTime::UNIX_EPOCH
+            + Duration::from_secs(ts.tv_sec as u64)
+            + Duration::from_micros(ts.tv_usec as u64)
+    }
+
+    /// Opens a capture of the traffic to the addresses and returns it with its link type.
+    pub fn open(interface: Option<&str>, addrs: &[IpAddr]) -> io::Result<(Capture, u32)> {
+        let mut capture = pcap::Capture::from_device(interface.unwrap_or("any"))
+            .and_then(|x| {
+                x.snaplen(super::SNAPLEN as i32)
+                    .timeout(100)
+                    .immediate_mode(true)
+                    .open()
+            })
+            .map_err(io::Error::other)?;
+        let filter = addrs
+            .iter()
+            .map(|x| format!("host {x}"))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        capture.filter(&filter, true).map_err(io::Error::other)?;
+        let linktype = capture.get_datalink().0 as u32;
+        Ok((capture, linktype))
+    }
+
+    /// Writes the captured packets within a thread until the returned Stop is dropped.
+    pub fn spawn(mut capture: Capture, writer: Arc<Mutex<RotatingPcapWriter>>) -> Stop {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let packet = match capture.next_packet() {
+                        Ok(x) => x,
+                        Err(pcap::Error::TimeoutExpired) => continue,
+                        Err(e) => {
+                            tracing::warn!(error=%e, "packet capture stopped");
+                            return;
+                        }
+                    };
+                    let Ok(mut writer) = writer.lock() else {
+                        return;
+                    };
+                    let time = timestamp(&packet.header.ts);
+                    if let Err(e) = writer.write(time, packet.header.len, packet.data) {
+                        tracing::warn!(error=%e, "unable to write pcap file");
+                        return;
+                    }
+                }
+            })
+        };
+        Stop {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+#[cfg(not(feature = "nasl-builtin-raw-ip"))]
+mod live {
+    use std::{
+        io,
+        net::IpAddr,
+        sync::{Arc, Mutex},
+    };
+
+    use super::RotatingPcapWriter;
+
+    pub type Capture = ();
+
+    pub struct Stop;
+
+    pub fn open(_interface: Option<&str>, _addrs: &[IpAddr]) -> io::Result<(Capture, u32)> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "packet capture requires the nasl-builtin-raw-ip feature",
+        ))
+    }
+
+    pub fn spawn(_capture: Capture, _writer: Arc<Mutex<RotatingPcapWriter>>) -> Stop {
+        Stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_files() {
+        let directory = std::env::temp_dir().join(format!("pcap-rotation-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let packet = [0u8; 100];
+        // header and exactly two packets fit into a file
+        let max_size = FILE_HEADER_LEN + 2 * (RECORD_HEADER_LEN + packet.len() as u64);
+        let mut writer =
+            RotatingPcapWriter::new(directory.join("host_oid"), LINKTYPE_ETHERNET, max_size, 2);
+        for _ in 0..7 {
+            writer.write(SystemTime::now(), 100, &packet).unwrap();
+        }
+        writer.flush().unwrap();
+        let files = writer.files();
+        assert_eq!(
+            files,
+            vec![
+                directory.join("host_oid-2.pcap"),
+                directory.join("host_oid-3.pcap")
+            ]
+        );
+        assert_eq!(writer.current_file(), Some(files[1].as_path()));
+        assert!(!directory.join("host_oid-0.pcap").exists());
+        let content = fs::read(&files[0]).unwrap();
+        assert_eq!(content.len() as u64, max_size);
+        assert_eq!(content[..4], PCAP_MAGIC.to_le_bytes());
+        assert_eq!(content[20..24], LINKTYPE_ETHERNET.to_le_bytes());
+        assert_eq!(
+            fs::read(&files[1]).unwrap().len() as u64,
+            FILE_HEADER_LEN + RECORD_HEADER_LEN + packet.len() as u64
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn preferences() {
+        use crate::models::ScanPreference;
+        let preference = |id: &str, value: &str| ScanPreference {
+            id: id.to_owned(),
+            value: value.to_owned(),
+        };
+        assert!(!PacketRecorder::from_preferences(&[]).is_enabled("1.2.3"));
+        let recorder = PacketRecorder::from_preferences(&[preference(PCAP_OIDS, "1.2.3, 1.2.4")]);
+        // a directory is only set by the scanner
+        assert!(!recorder.is_enabled("1.2.4"));
+        let recorder = recorder.with_directory(Some(PathBuf::from("/tmp/pcap")));
+        assert!(recorder.is_enabled("1.2.4"));
+        assert!(!recorder.is_enabled("1.2.5"));
+        assert_eq!(recorder.max_files, 5);
+    }
+}
@@ -0,0 +1,446 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Records the calls of network builtin functions into a cassette and replays them.
+//!
+//! While recording each call of a function within [NETWORK_FUNCTIONS] is executed as usual and
+//! its arguments and result are appended to the cassette. While replaying the functions are not
+//! executed, instead the result of the next recorded call is returned. This allows running a
+//! script offline against the responses of a real host, e.g. for regression tests of the
+//! detection logic.
+//!
+//! A replayed call must match the recorded one by function name and arguments, otherwise the
+//! script changed its behaviour and the call fails. Errors are replayed as
+//! [FunctionErrorKind::Diagnostic] with the recorded message.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+
+use crate::nasl::syntax::NaslValue;
+
+use super::{
+    executor::FunctionSet, lookup_keys::FC_ANON_ARGS, Context, ContextType, FunctionErrorKind,
+    NaslResult, PluginConfig, Register,
+};
+
+/// The builtin functions exchanging data with a host.
+pub const NETWORK_FUNCTIONS: &[&str] = &[
+    "open_sock_kdc",
+    "open_sock_tcp",
+    "open_sock_udp",
+    "open_priv_sock_tcp",
+    "open_priv_sock_udp",
+    "close",
+    "send",
+    "recv",
+    "ws_connect",
+    "ws_send",
+    "ws_recv",
+    "http2_handle",
+    "http2_close_handle",
+    "http2_get_response_code",
+    "http2_get_version",
+    "http2_set_custom_header",
+    "http2_get",
+    "http2_head",
+    "http2_post",
+    "http2_delete",
+    "http2_put",
+    "tls_probe",
+    "ssh_kex_algorithms",
+    "rdp_negotiate",
+    "rdp_nla_required",
+    "ntp_version",
+    "ntp_monlist",
+    "netbios_name_query",
+    "mdns_query",
+    "ssdp_search",
+    "mqtt_connect",
+    "mqtt_auth_required",
+    "amqp_probe",
+    "mysql_handshake",
+    "postgres_probe",
+    "mssql_prelogin",
+];
+
+/// A serializable representation of a NaslValue
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Value {
+    String(String),
+    Data(Vec<u8>),
+    Number(i64),
+    Array(Vec<Value>),
+    Dict(BTreeMap<String, Value>),
+    Boolean(bool),
+    Fork(Vec<Value>),
+    Null,
+}
+
+impl From<&NaslValue> for Value {
+    fn from(value: &NaslValue) -> Self {
+        match value {
+            NaslValue::String(x) => Self::String(x.to_string()),
+            NaslValue::Data(x) => Self::Data(x.clone()),
+            NaslValue::Number(x) => Self::Number(*x),
+            NaslValue::Array(x) => Self::Array(x.iter().map(Self::from).collect()),
+            NaslValue::Fork(x) => Self::Fork(x.iter().map(Self::from).collect()),
+            NaslValue::Dict(x) => {
+                Self::Dict(x.iter().map(|(k, v)| (k.clone(), Self::from(v))).collect())
+            }
+            NaslValue::Boolean(x) => Self::Boolean(*x),
+            NaslValue::AttackCategory(x) => Self::Number(*x as i64),
+            NaslValue::Return(x) => Self::from(x.as_ref()),
+            NaslValue::Exit(x) => Self::Number(*x),
+            NaslValue::Null | NaslValue::Continue | NaslValue::Break => Self::Null,
+        }
+    }
+}
+
+impl From<Value> for NaslValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::String(x) => Self::String(x.into()),
+            Value::Data(x) => Self::Data(x),
+            Value::Number(x) => Self::Number(x),
+            Value::Array(x) => Self::Array(x.into_iter().map(Self::from).collect()),
+            Value::Dict(x) => Self::Dict(x.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            Value::Boolean(x) => Self::Boolean(x),
+            Value::Fork(x) => Self::Fork(x.into_iter().map(Self::from).collect()),
+            Value::Null => Self::Null,
+        }
+    }
+}
+
+/// The outcome of a recorded call
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The returned value
+    Ok(Value),
+    /// The message and the optional return value of a failed call
+    Err {
+        message: String,
+        value: Option<Value>,
+    },
+}
+
+impl From<&NaslResult> for Outcome {
+    fn from(value: &NaslResult) -> Self {
+        match value {
+            Ok(x) => Self::Ok(x.into()),
+            Err(FunctionErrorKind::Diagnostic(message, value)) => Self::Err {
+                message: message.clone(),
+                value: value.as_ref().map(Value::from),
+            },
+            Err(e) => Self::Err {
+                message: e.to_string(),
+                value: None,
+            },
+        }
+    }
+}
+
+impl From<Outcome> for NaslResult {
+    fn from(value: Outcome) -> Self {
+        match value {
+            Outcome::Ok(x) => Ok(x.into()),
+            Outcome::Err { message, value } => Err(FunctionErrorKind::Diagnostic(
+                message,
+                value.map(NaslValue::from),
+            )),
+        }
+    }
+}
+
+/// A single call of a network function
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Interaction {
+    /// Name of the called function
+    pub function: String,
+    /// The positional arguments
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positional: Vec<Value>,
+    /// The named arguments
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub named: BTreeMap<String, Value>,
+    /// The result of the call
+    pub outcome: Outcome,
+}
+
+impl Interaction {
+    fn new(function: &str, register: &Register, outcome: Outcome) -> Self {
+        let named = register
+            .iter_named_args()
+            .into_iter()
+            .flatten()
+            .filter(|x| *x != FC_ANON_ARGS)
+            .filter_map(|x| match register.named(x) {
+                Some(ContextType::Value(v)) => Some((x.to_string(), v.into())),
+                _ => None,
+            })
+            .collect();
+        Self {
+            function: function.to_string(),
+            positional: register.positional().iter().map(Value::from).collect(),
+            named,
+            outcome,
+        }
+    }
+
+    /// Returns the function name and arguments of the call.
+    fn signature(&self) -> String {
+        format!("{}({:?}, {:?})", self.function, self.positional, self.named)
+    }
+
+    fn matches(&self, other: &Self) -> bool {
+        self.function == other.function
+            && self.positional == other.positional
+            && self.named == other.named
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    #[serde(skip)]
+    position: usize,
+}
+
+/// Recorded calls of network functions
+#[derive(Debug)]
+pub struct Cassette {
+    mode: Mode,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    /// Creates an empty cassette recording each network call.
+    pub fn record() -> Self {
+        Self {
+            mode: Mode::Record,
+            tape: Mutex::default(),
+        }
+    }
+
+    /// Creates a cassette replaying the given calls in order.
+    pub fn replay(interactions: Vec<Interaction>) -> Self {
+        Self {
+            mode: Mode::Replay,
+            tape: Mutex::new(Tape {
+                interactions,
+                position: 0,
+            }),
+        }
+    }
+
+    /// Reads a cassette previously written via `write` for replaying.
+    pub fn read<R>(r: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let tape: Tape = serde_json::from_reader(r).map_err(io::Error::from)?;
+        Ok(Self::replay(tape.interactions))
+    }
+
+    /// Writes the recorded calls as json.
+    pub fn write<W>(&self, w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        serde_json::to_writer_pretty(w, &*self.tape()).map_err(io::Error::from)
+    }
+
+    /// Returns true when the calls are replayed instead of executed.
+    pub fn is_replaying(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// Returns the recorded calls.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.tape().interactions.clone()
+    }
+
+    /// Returns the amount of calls that are not replayed yet.
+    pub fn remaining(&self) -> usize {
+        let tape = self.tape();
+        tape.interactions.len() - tape.position
+    }
+
+    fn tape(&self) -> MutexGuard<'_, Tape> {
+        // a panic while holding the lock does not leave the tape in an inconsistent state
+        self.tape.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, interaction: Interaction) {
+        self.tape().interactions.push(interaction);
+    }
+
+    fn next(&self, call: &Interaction) -> NaslResult {
+        let mut tape = self.tape();
+        let position = tape.position;
+        let recorded = match tape.interactions.get(position) {
+            Some(x) if x.matches(call) => x.outcome.clone(),
+            Some(x) => {
+                return Err(FunctionErrorKind::Dirty(format!(
+                    "cassette mismatch at call {position}: expected {}, got {}",
+                    x.signature(),
+                    call.signature()
+                )))
+            }
+            None => {
+                return Err(FunctionErrorKind::Dirty(format!(
+                    "cassette exhausted: unexpected call of {}",
+                    call.function
+                )))
+            }
+        };
+        tape.position += 1;
+        recorded.into()
+    }
+}
+
+/// A function set recording or replaying the network calls of the inner set.
+pub(super) struct CassetteSet {
+    inner: Box<dyn FunctionSet + Send + Sync>,
+    cassette: std::sync::Arc<Cassette>,
+}
+
+impl CassetteSet {
+    pub(super) fn new(
+        inner: Box<dyn FunctionSet + Send + Sync>,
+        cassette: std::sync::Arc<Cassette>,
+    ) -> Self {
+        Self { inner, cassette }
+    }
+}
+
+#[async_trait]
+impl FunctionSet for CassetteSet {
+    async fn exec<'a>(
+        &'a self,
+        k: &'a str,
+        register: &'a Register,
+        context: &'a Context<'_>,
+    ) -> NaslResult {
+        if !NETWORK_FUNCTIONS.contains(&k) {
+            return self.inner.exec(k, register, context).await;
+        }
+        match self.cassette.mode {
+            Mode::Replay => {
+                let call = Interaction::new(k, register, Outcome::Ok(Value::Null));
+                self.cassette.next(&call)
+            }
+            Mode::Record => {
+                let result = self.inner.exec(k, register, context).await;
+                self.cassette
+                    .push(Interaction::new(k, register, Outcome::from(&result)));
+                result
+            }
+        }
+    }
+
+    fn contains(&self, k: &str) -> bool {
+        self.inner.contains(k)
+    }
+
+    async fn init_scan(&self, config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        self.inner.init_scan(config).await
+    }
+
+    async fn init_script(&self, context: &Context<'_>) -> Result<(), FunctionErrorKind> {
+        self.inner.init_script(context).await
+    }
+
+    async fn finish_script(&self, context: &Context<'_>) {
+        self.inner.finish_script(context).await
+    }
+
+    async fn shutdown(&self) {
+        self.inner.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+
+    use crate::nasl::interpreter::{CodeInterpreter, InterpretErrorKind};
+    use crate::nasl::test_prelude::*;
+    use crate::nasl::utils::Executor;
+    use crate::nasl::ContextFactory;
+    use crate::storage::ContextKey;
+
+    use super::*;
+
+    struct Fake;
+
+    #[nasl_function(named(data))]
+    fn send(data: &str) -> usize {
+        data.len()
+    }
+
+    #[nasl_function]
+    fn recv() -> NaslValue {
+        NaslValue::Data(b"SSH-2.0-OpenSSH_9.6".to_vec())
+    }
+
+    crate::function_set! {
+        Fake,
+        sync_stateless,
+        (send, recv)
+    }
+
+    fn run(cassette: Arc<Cassette>, code: &str) -> Vec<NaslResult> {
+        let mut executor = Executor::single(Fake);
+        executor.with_cassette(cassette);
+        let mut factory = ContextFactory::default();
+        factory.functions = executor;
+        let context = factory.build(ContextKey::default());
+        let interpreter = CodeInterpreter::new(code, Register::default(), &context);
+        futures::executor::block_on(
+            interpreter
+                .stream()
+                .map(|x| {
+                    x.map_err(|e| match e.kind {
+                        InterpretErrorKind::FunctionCallError(f) => f.kind,
+                        e => panic!("unexpected error: {e}"),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let code = r#"send(data: "hi"); recv();"#;
+        let cassette = Arc::new(Cassette::record());
+        let recorded = run(cassette.clone(), code);
+        assert_eq!(cassette.interactions().len(), 2);
+
+        let mut buf = vec![];
+        cassette.write(&mut buf).unwrap();
+        let replay = Arc::new(Cassette::read(buf.as_slice()).unwrap());
+        assert!(replay.is_replaying());
+        assert_eq!(run(replay.clone(), code), recorded);
+        assert_eq!(replay.remaining(), 0);
+
+        let replay = Arc::new(Cassette::read(buf.as_slice()).unwrap());
+        let results = run(replay, r#"send(data: "changed");"#);
+        assert!(matches!(&results[0], Err(FunctionErrorKind::Dirty(_))));
+    }
+}
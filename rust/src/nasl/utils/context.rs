@@ -4,10 +4,27 @@
 
 //! Defines the context used within the interpreter and utilized by the builtin functions
 
-use crate::nasl::syntax::{Loader, NaslValue, Statement};
-use crate::storage::{ContextKey, Dispatcher, Retriever};
-
-use super::{executor::Executor, lookup_keys::FC_ANON_ARGS};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+
+use crate::models::{self, Labels};
+use crate::nasl::syntax::{
+    CompileCache, Loader, NaslValue, ParsedIncludes, Statement, SyntaxError,
+};
+use crate::storage::{item::Nvt, ContextKey, Dispatcher, Field, Retriever};
+
+use super::{
+    capture::Recording,
+    dns::DnsCache,
+    error::FunctionErrorKind,
+    executor::Executor,
+    extensions::Extensions,
+    kb_cache::{CachedStorage, KbCache},
+    limiter::ConnectionLimiter,
+    lookup_keys::FC_ANON_ARGS,
+    policy::BuiltinPolicy,
+    targets::TargetQueue,
+    traffic::TrafficCounter,
+};
 
 /// Contexts are responsible to locate, add and delete everything that is declared within a NASL plugin
 
@@ -117,6 +134,8 @@ impl From<HashMap<String, NaslValue>> for ContextType {
 #[derive(Clone)]
 pub struct Register {
     blocks: Vec<NaslContext>,
+    /// Immutable definitions looked up after the root context
+    base: Option<Arc<RegisterBase>>,
 }
 
 impl Register {
@@ -124,6 +143,43 @@ impl Register {
     pub fn new() -> Self {
         Self {
             blocks: vec![NaslContext::default()],
+            base: None,
+        }
+    }
+
+    /// Creates an empty register layered on top of the given base.
+    ///
+    /// Definitions of the base are visible as if they were defined within the root context,
+    /// assigning to them defines the name within the root context instead of changing the base.
+    pub fn layered(base: Arc<RegisterBase>) -> Self {
+        Self {
+            blocks: vec![NaslContext::default()],
+            base: Some(base),
+        }
+    }
+
+    /// Returns the base the register is layered on
+    pub fn base(&self) -> Option<&RegisterBase> {
+        self.base.as_deref()
+    }
+
+    /// Turns the root context into a base for other registers.
+    ///
+    /// The included files are recorded so that scripts using the base skip including them again.
+    pub fn into_base(mut self, included: Vec<String>) -> RegisterBase {
+        let mut defined = std::mem::take(&mut self.blocks[0].defined);
+        let mut all_included = included;
+        if let Some(base) = self.base {
+            for (k, v) in &base.defined {
+                defined.entry(k.clone()).or_insert_with(|| v.clone());
+            }
+            all_included.extend(base.included.iter().cloned());
+        }
+        all_included.sort();
+        all_included.dedup();
+        RegisterBase {
+            defined,
+            included: all_included,
         }
     }
 
@@ -137,7 +193,10 @@ impl Register {
             defined,
             ..Default::default()
         };
-        Self { blocks: vec![root] }
+        Self {
+            blocks: vec![root],
+            base: None,
+        }
     }
 
     /// Returns the next index
@@ -171,15 +230,17 @@ impl Register {
 
     /// Finds a named ContextType
     pub fn named<'a>(&'a self, name: &'a str) -> Option<&ContextType> {
-        self.blocks
-            .last()
-            .and_then(|x| x.named(self, name))
-            .map(|(_, val)| val)
+        self.index_named(name).map(|(_, val)| val)
     }
 
     /// Finds a named ContextType with index
+    ///
+    /// Definitions of the base are returned with the index of the root context.
     pub fn index_named<'a>(&'a self, name: &'a str) -> Option<(usize, &ContextType)> {
-        self.blocks.last().and_then(|x| x.named(self, name))
+        self.blocks
+            .last()
+            .and_then(|x| x.named(self, name))
+            .or_else(|| Some((0, self.base.as_ref()?.defined.get(name)?)))
     }
 
     /// Return an iterator over the names of the named arguments.
@@ -219,6 +280,28 @@ impl Register {
         }
     }
 
+    /// Returns the values of the variables visible within the current context.
+    pub fn variables(&self) -> BTreeMap<String, NaslValue> {
+        let mut result = BTreeMap::new();
+        let mut current = self.blocks.last();
+        while let Some(context) = current {
+            for (name, value) in &context.defined {
+                if let (ContextType::Value(value), false) = (value, name == FC_ANON_ARGS) {
+                    result.entry(name.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            current = context.parent.map(|x| &self.blocks[x]);
+        }
+        if let Some(base) = &self.base {
+            for (name, value) in &base.defined {
+                if let ContextType::Value(value) = value {
+                    result.entry(name.clone()).or_insert_with(|| value.clone());
+                }
+            }
+        }
+        result
+    }
+
     /// Destroys the current context.
     ///
     /// This must be called when a context vanishes.
@@ -291,6 +374,32 @@ impl Default for Register {
 use std::collections::HashMap;
 type Named = HashMap<String, ContextType>;
 
+/// Immutable definitions shared by registers, e.g. libraries preloaded once per scan
+///
+/// Is created via [Register::into_base] and used via [Register::layered].
+#[derive(Default)]
+pub struct RegisterBase {
+    defined: Named,
+    included: Vec<String>,
+}
+
+impl RegisterBase {
+    /// Returns the files that were included to create the base
+    pub fn included(&self) -> &[String] {
+        &self.included
+    }
+
+    /// Returns the number of defined functions and variables
+    pub fn len(&self) -> usize {
+        self.defined.len()
+    }
+
+    /// Returns true when nothing is defined
+    pub fn is_empty(&self) -> bool {
+        self.defined.is_empty()
+    }
+}
+
 /// NaslContext is a struct to contain variables and if root declared functions
 ///
 /// A context should never be created directly but via a Register.
@@ -328,6 +437,65 @@ impl NaslContext {
     }
 }
 
+/// Scan preference id enabling the strict regular expression compatibility mode
+pub const REGEX_STRICT_COMPAT: &str = "regex_strict_compat";
+
+/// Selects how the regular expression functions interpret patterns
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RegexMode {
+    /// POSIX and PCRE constructs are translated, matching follows the regex crate
+    #[default]
+    Compat,
+    /// Follows the POSIX implementation of the C scanner as close as possible
+    Strict,
+}
+
+impl From<&crate::models::Scan> for RegexMode {
+    fn from(scan: &crate::models::Scan) -> Self {
+        let strict = crate::models::bool_preference(&scan.scan_preferences, REGEX_STRICT_COMPAT);
+        match strict.unwrap_or_default() {
+            true => Self::Strict,
+            false => Self::Compat,
+        }
+    }
+}
+
+/// Scan preference id disabling the include-once semantics of `include` when set to `no`
+pub const INCLUDE_ONCE: &str = "include_once";
+
+/// Returns false when the preferences disable the include-once semantics of `include`.
+pub fn include_once_from_preferences(preferences: &[crate::models::ScanPreference]) -> bool {
+    crate::models::bool_preference(preferences, INCLUDE_ONCE).unwrap_or(true)
+}
+
+/// Scan preference id selecting the network namespace of a scan
+pub const NETWORK_NAMESPACE: &str = "network_namespace";
+
+/// Source of the network traffic of a scan
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NetworkSource {
+    /// Interface the traffic is sent from
+    pub interface: Option<String>,
+    /// Address the traffic is sent from
+    pub address: Option<IpAddr>,
+    /// Network namespace the sockets are created in
+    pub namespace: Option<String>,
+}
+
+impl From<&crate::models::Scan> for NetworkSource {
+    fn from(scan: &crate::models::Scan) -> Self {
+        Self {
+            interface: scan.target.source_interface.clone(),
+            address: scan.target.source_address,
+            namespace: scan
+                .scan_preferences
+                .iter()
+                .find(|x| x.id == NETWORK_NAMESPACE && !x.value.is_empty())
+                .map(|x| x.value.clone()),
+        }
+    }
+}
+
 /// Configurations
 ///
 /// This struct includes all objects that a nasl function requires.
@@ -337,14 +505,48 @@ pub struct Context<'a> {
     key: ContextKey,
     /// target to run a scan against
     target: String,
+    /// Scope id of the zone of a link-local target, 0 when there is none
+    scope_id: u32,
     /// Default Dispatcher
     dispatcher: &'a dyn Dispatcher,
     /// Default Retriever
     retriever: &'a dyn Retriever,
     /// Default Loader
     loader: &'a dyn Loader,
+    /// Dispatcher and retriever using the KB cache of the host
+    cached: Option<CachedStorage<'a>>,
     /// Default function executor.
     executor: &'a Executor,
+    /// Resolver cache shared between the contexts of a scan
+    dns: Arc<DnsCache>,
+    /// Outbound connection limiter shared between the contexts of a scan
+    limiter: Arc<ConnectionLimiter>,
+    /// Hosts added to the running scan
+    targets: Arc<TargetQueue>,
+    /// State of the builtin functions shared between the contexts of a scan
+    extensions: Arc<Extensions>,
+    /// Source interface and address of the network traffic
+    source: NetworkSource,
+    /// Packet capture of the script run
+    recording: Option<&'a Recording>,
+    /// Bytes transferred by the script run
+    traffic: Option<&'a TrafficCounter>,
+    /// Interpretation of regular expression patterns
+    regex: RegexMode,
+    /// Whether each file is only included once per script run
+    include_once: bool,
+    /// Compiled scripts and includes shared between the contexts of a scan
+    compile_cache: Option<Arc<CompileCache>>,
+    /// Include files parsed in advance for the scripts of a scan
+    parsed_includes: Option<Arc<ParsedIncludes>>,
+    /// Labels of the target added to each result
+    labels: Option<&'a Labels>,
+    /// Virtual hosts of the target
+    vhosts: &'a [String],
+    /// VT of the running script, None when the script does not run within a scan
+    nvt: Option<&'a Nvt>,
+    /// Builtin functions the script may call
+    policy: Arc<BuiltinPolicy>,
 }
 
 impl<'a> Context<'a> {
@@ -357,16 +559,183 @@ impl<'a> Context<'a> {
         loader: &'a dyn Loader,
         executor: &'a Executor,
     ) -> Self {
+        let scope_id = super::ScopedAddr::parse(&target)
+            .map(|x| x.scope_id)
+            .unwrap_or_default();
         Self {
             key,
             target,
+            scope_id,
             dispatcher,
             retriever,
             loader,
+            cached: None,
             executor,
+            dns: Arc::new(DnsCache::default()),
+            limiter: Arc::new(ConnectionLimiter::default()),
+            targets: Arc::new(TargetQueue::default()),
+            extensions: Arc::new(Extensions::default()),
+            source: NetworkSource::default(),
+            recording: None,
+            traffic: None,
+            regex: RegexMode::default(),
+            include_once: true,
+            compile_cache: None,
+            parsed_includes: None,
+            labels: None,
+            vhosts: &[],
+            nvt: None,
+            policy: Arc::default(),
         }
     }
 
+    /// Replaces the resolver cache.
+    ///
+    /// This is used to share a cache between all scripts of a scan.
+    pub fn with_dns_cache(mut self, dns: Arc<DnsCache>) -> Self {
+        self.dns = dns;
+        self
+    }
+
+    /// Replaces the connection limiter.
+    ///
+    /// This is used to enforce the limits over all scripts of a scan.
+    pub fn with_connection_limiter(mut self, limiter: Arc<ConnectionLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Replaces the queue of added hosts.
+    ///
+    /// This is used to allow scripts to add hosts to the running scan.
+    pub fn with_target_queue(mut self, targets: Arc<TargetQueue>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Replaces the state of the builtin functions.
+    ///
+    /// This is used to share e.g. connections of builtin functions between all scripts of a scan.
+    pub fn with_extensions(mut self, extensions: Arc<Extensions>) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Reads and writes the `Services/` and `Ports/` KB items via the cache of the host.
+    pub fn with_kb_cache(mut self, cache: Option<Arc<KbCache>>) -> Self {
+        self.cached = cache.map(|cache| CachedStorage {
+            dispatcher: self.dispatcher,
+            retriever: self.retriever,
+            cache,
+        });
+        self
+    }
+
+    /// Sets the source interface and address used by the network functions.
+    pub fn with_network_source(mut self, source: NetworkSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets how regular expression patterns are interpreted.
+    pub fn with_regex_mode(mut self, regex: RegexMode) -> Self {
+        self.regex = regex;
+        self
+    }
+
+    /// Sets whether `include` skips files that were already included by the script run.
+    ///
+    /// Disabling it executes the top-level code of a file on each include.
+    pub fn with_include_once(mut self, include_once: bool) -> Self {
+        self.include_once = include_once;
+        self
+    }
+
+    /// Sets the cache the script and its includes are compiled with instead of parsing them.
+    pub fn with_compile_cache(mut self, cache: Option<Arc<CompileCache>>) -> Self {
+        self.compile_cache = cache;
+        self
+    }
+
+    /// Sets the include files parsed in advance, they are neither loaded nor parsed on include.
+    pub fn with_parsed_includes(mut self, includes: Option<Arc<ParsedIncludes>>) -> Self {
+        self.parsed_includes = includes;
+        self
+    }
+
+    /// Sets the packet capture the results of the script refer to.
+    pub fn with_recording(mut self, recording: Option<&'a Recording>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    /// Counts the bytes transferred by the script run.
+    pub fn with_traffic(mut self, traffic: Option<&'a TrafficCounter>) -> Self {
+        self.traffic = traffic;
+        self
+    }
+
+    /// Sets the labels of the target that are added to each result.
+    pub fn with_labels(mut self, labels: Option<&'a Labels>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the virtual hosts of the target, `get_host_name` forks over them.
+    pub fn with_vhosts(mut self, vhosts: &'a [String]) -> Self {
+        self.vhosts = vhosts;
+        self
+    }
+
+    /// Sets the VT of the running script, e.g. to add its QoD to each result.
+    pub fn with_nvt(mut self, nvt: Option<&'a Nvt>) -> Self {
+        self.nvt = nvt;
+        self
+    }
+
+    /// Restricts the builtin functions the script may call.
+    pub fn with_builtin_policy(mut self, policy: Arc<BuiltinPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns true when the builtin function is disabled by the policy.
+    fn is_disabled(&self, name: &str) -> bool {
+        !self.policy.allows_all() && self.policy.is_disabled(name, self.executor.module_of(name))
+    }
+
+    /// Returns true when the builtin function is refused by the safe checks.
+    fn is_refused(&self, name: &str) -> bool {
+        self.policy.refuses(self.executor.risk_of(name))
+    }
+
+    /// Logs the refusal of a builtin function as log result of the script.
+    fn refuse(&self, name: &str) -> super::NaslResult {
+        let risk = self.executor.risk_of(name);
+        tracing::debug!(function = name, %risk, key = self.key.value(), "refused builtin function");
+        let result = models::Result {
+            id: 0,
+            r_type: models::ResultType::Log,
+            ip_address: Some(self.target.clone()),
+            hostname: None,
+            oid: Some(self.key.value()),
+            qod: self.nvt.map(|x| x.qod()),
+            port: None,
+            protocol: None,
+            message: Some(format!(
+                "Refused to call the {risk} builtin function {name} as safe_checks is enabled."
+            )),
+            detail: None,
+            pcap: None,
+            traffic: self.traffic.map(|x| x.totals()),
+            labels: self.labels.cloned().unwrap_or_default(),
+            triage: None,
+        };
+        self.dispatcher
+            .retry_dispatch(5, &self.key, Field::Result(result.into()))?;
+        Ok(NaslValue::Null)
+    }
+
     /// Executes a function by name
     ///
     /// Returns None when the function was not found.
@@ -375,12 +744,18 @@ impl<'a> Context<'a> {
         name: &str,
         register: &Register,
     ) -> Option<super::NaslResult> {
+        if self.is_disabled(name) && self.executor.contains(name) {
+            return Some(Err(FunctionErrorKind::Disabled(name.to_string())));
+        }
+        if self.is_refused(name) && self.executor.contains(name) {
+            return Some(self.refuse(name));
+        }
         self.executor.exec(name, self, register).await
     }
 
-    /// Checks if a function is defined
+    /// Checks if a function is defined and neither disabled nor refused
     pub fn nasl_fn_defined(&self, name: &str) -> bool {
-        self.executor.contains(name)
+        self.executor.contains(name) && !self.is_disabled(name) && !self.is_refused(name)
     }
 
     /// Get the executor
@@ -398,20 +773,115 @@ impl<'a> Context<'a> {
         &self.target
     }
 
+    /// Get the scope id of the zone of the target, e.g. the index of `eth0` for `fe80::1%eth0`
+    pub fn target_scope_id(&self) -> u32 {
+        self.scope_id
+    }
+
     /// Get the storage
     pub fn dispatcher(&self) -> &dyn Dispatcher {
-        self.dispatcher
+        match &self.cached {
+            Some(x) => x,
+            None => self.dispatcher,
+        }
     }
 
     /// Get the storage
     pub fn retriever(&self) -> &dyn Retriever {
-        self.retriever
+        match &self.cached {
+            Some(x) => x,
+            None => self.retriever,
+        }
     }
 
     /// Get the loader
     pub fn loader(&self) -> &dyn Loader {
         self.loader
     }
+
+    /// Get the resolver cache
+    pub fn dns_cache(&self) -> &DnsCache {
+        &self.dns
+    }
+
+    /// Get the connection limiter
+    pub fn connection_limiter(&self) -> &ConnectionLimiter {
+        &self.limiter
+    }
+
+    /// Get the queue of hosts added to the running scan
+    pub fn target_queue(&self) -> &TargetQueue {
+        &self.targets
+    }
+
+    /// Get the state of the builtin functions shared within the scan
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get the source of the network traffic
+    pub fn network_source(&self) -> &NetworkSource {
+        &self.source
+    }
+
+    /// Get the interpretation of regular expression patterns
+    pub fn regex_mode(&self) -> RegexMode {
+        self.regex
+    }
+
+    /// Get whether each file is only included once per script run
+    pub fn include_once(&self) -> bool {
+        self.include_once
+    }
+
+    /// Get the cache scripts are compiled with, if any
+    pub fn compile_cache(&self) -> Option<&CompileCache> {
+        self.compile_cache.as_deref()
+    }
+
+    /// Get the statements of an include file parsed in advance, if any
+    pub fn parsed_include(&self, key: &str) -> Option<&[Statement]> {
+        self.parsed_includes.as_deref().and_then(|x| x.get(key))
+    }
+
+    /// Parses the given code, the statements are taken from the compile cache when it is set.
+    pub fn parse<'c>(
+        &self,
+        code: &'c str,
+    ) -> Box<dyn Iterator<Item = Result<Statement, SyntaxError>> + Send + 'c> {
+        match self.compile_cache() {
+            Some(cache) => match cache.compile(code) {
+                Ok(statements) => Box::new(statements.into_iter().map(Ok)),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            },
+            None => Box::new(crate::nasl::syntax::parse(code)),
+        }
+    }
+
+    /// Get the packet capture of the script run
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording
+    }
+
+    /// Get the virtual hosts of the target
+    pub fn vhosts(&self) -> &[String] {
+        self.vhosts
+    }
+
+    /// Get the labels of the target
+    pub fn labels(&self) -> Option<&Labels> {
+        self.labels
+    }
+
+    /// Get the traffic counter of the script run
+    pub fn traffic(&self) -> Option<&TrafficCounter> {
+        self.traffic
+    }
+
+    /// Get the VT of the running script
+    pub fn nvt(&self) -> Option<&Nvt> {
+        self.nvt
+    }
 }
 
 impl From<&ContextType> for NaslValue {
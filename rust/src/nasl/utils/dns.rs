@@ -0,0 +1,391 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines a resolver cache that is shared between all scripts of a scan as well as the host
+//! names of a target that are stored within the knowledge base.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io,
+    net::IpAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use super::zone;
+
+/// Preference id of the ttl in seconds of successful lookups.
+pub const DNS_CACHE_TTL: &str = "dns_cache_ttl";
+/// Preference id of the ttl in seconds of failed lookups.
+pub const DNS_CACHE_NEGATIVE_TTL: &str = "dns_cache_negative_ttl";
+/// Preference id enabling the host name lookups at the start of each host, disable it for
+/// stealth scans.
+pub const HOST_NAME_LOOKUP: &str = "host_name_lookup";
+
+/// KB item containing the host name of the target
+pub const KB_HOSTNAME: &str = "Host/hostname";
+/// KB item containing the fully qualified domain name of the target
+pub const KB_FQDN: &str = "Host/FQDN";
+/// KB item containing the name the address of the target resolves to
+pub const KB_RDNS: &str = "Host/rDNS";
+
+type Resolver = Box<dyn Fn(&str) -> io::Result<Vec<IpAddr>> + Send + Sync>;
+type ReverseResolver = Box<dyn Fn(&IpAddr) -> io::Result<String> + Send + Sync>;
+
+struct Entry<T> {
+    value: Option<T>,
+    expires: Instant,
+}
+
+/// Hit and miss counter of a DnsCache
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DnsCacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that required a resolution
+    pub misses: u64,
+}
+
+impl DnsCacheStats {
+    /// Returns the ratio of hits to all lookups
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            all => self.hits as f64 / all as f64,
+        }
+    }
+}
+
+/// Caches positive and negative name resolutions.
+///
+/// Each entry is kept for the configured time to live, afterwards the name is resolved again.
+/// IP addresses are returned as they are without being cached.
+pub struct DnsCache {
+    ttl: Duration,
+    negative_ttl: Duration,
+    entries: RwLock<HashMap<String, Entry<Vec<IpAddr>>>>,
+    names: RwLock<HashMap<IpAddr, Entry<String>>>,
+    resolver: Resolver,
+    reverse_resolver: ReverseResolver,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), Duration::from_secs(30))
+    }
+}
+
+impl DnsCache {
+    /// Creates a new DnsCache using the system resolver
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            ttl,
+            negative_ttl,
+            entries: RwLock::new(HashMap::new()),
+            names: RwLock::new(HashMap::new()),
+            resolver: Box::new(dns_lookup::lookup_host),
+            reverse_resolver: Box::new(dns_lookup::lookup_addr),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a new DnsCache based on the `dns_cache_ttl` and `dns_cache_negative_ttl` scan
+    /// preferences.
+    ///
+    /// Missing or invalid preferences fall back to the defaults.
+    pub fn from_preferences(preferences: &[crate::models::ScanPreference]) -> Self {
+        let default = Self::default();
+        let seconds = |id: &str, default: Duration| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .and_then(|x| x.value.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+        Self::new(
+            seconds(DNS_CACHE_TTL, default.ttl),
+            seconds(DNS_CACHE_NEGATIVE_TTL, default.negative_ttl),
+        )
+    }
+
+    /// Replaces the resolver used on a cache miss
+    pub fn with_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&str) -> io::Result<Vec<IpAddr>> + Send + Sync + 'static,
+    {
+        self.resolver = Box::new(resolver);
+        self
+    }
+
+    /// Replaces the resolver of addresses to names used on a cache miss
+    pub fn with_reverse_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&IpAddr) -> io::Result<String> + Send + Sync + 'static,
+    {
+        self.reverse_resolver = Box::new(resolver);
+        self
+    }
+
+    fn cached<K, T, F>(
+        &self,
+        entries: &RwLock<HashMap<K, Entry<T>>>,
+        key: K,
+        resolve: F,
+    ) -> Option<T>
+    where
+        K: Hash + Eq,
+        T: Clone,
+        F: FnOnce(&K) -> Option<T>,
+    {
+        let now = Instant::now();
+        if let Ok(entries) = entries.read() {
+            if let Some(entry) = entries.get(&key).filter(|x| x.expires > now) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return entry.value.clone();
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = resolve(&key);
+        let ttl = if value.is_some() {
+            self.ttl
+        } else {
+            self.negative_ttl
+        };
+        if let Ok(mut entries) = entries.write() {
+            entries.insert(
+                key,
+                Entry {
+                    value: value.clone(),
+                    expires: now + ttl,
+                },
+            );
+        }
+        value
+    }
+
+    /// Returns the addresses of the given name.
+    ///
+    /// Returns None when the name cannot be resolved.
+    pub fn lookup(&self, name: &str) -> Option<Vec<IpAddr>> {
+        if let Ok(ip) = IpAddr::from_str(zone::strip(name)) {
+            return Some(vec![ip]);
+        }
+        self.cached(&self.entries, name.to_owned(), |name| {
+            match (self.resolver)(name) {
+                Ok(x) if !x.is_empty() => Some(x),
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::debug!(name, error=%e, "unable to resolve");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Returns the name the given address resolves to.
+    ///
+    /// Returns None when there is no name or when the resolver returns the address itself.
+    pub fn reverse_lookup(&self, addr: IpAddr) -> Option<String> {
+        self.cached(&self.names, addr, |addr| {
+            match (self.reverse_resolver)(addr) {
+                Ok(x) if !x.is_empty() && IpAddr::from_str(&x).is_err() => {
+                    Some(x.trim_end_matches('.').to_owned())
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::debug!(%addr, error=%e, "unable to resolve address");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Returns the first address of the given name.
+    pub fn lookup_first(&self, name: &str) -> Option<IpAddr> {
+        self.lookup(name).and_then(|x| x.into_iter().next())
+    }
+
+    /// Returns the hit and miss counter
+    pub fn stats(&self) -> DnsCacheStats {
+        DnsCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The names of a target as stored in the knowledge base at the start of each host
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostNames {
+    /// The host name, either the target itself or the name its address resolves to
+    pub hostname: Option<String>,
+    /// The fully qualified domain name
+    pub fqdn: Option<String>,
+    /// The name the address of the target resolves to
+    pub rdns: Option<String>,
+}
+
+impl HostNames {
+    /// Returns the names of a target without any lookup.
+    ///
+    /// Only a target that is not an IP address is used as host name.
+    pub fn from_target(target: &str) -> Self {
+        let hostname = Some(target.to_owned())
+            .filter(|x| !x.is_empty() && IpAddr::from_str(zone::strip(x)).is_err());
+        Self {
+            fqdn: hostname.clone().filter(|x| x.contains('.')),
+            hostname,
+            rdns: None,
+        }
+    }
+
+    /// Resolves the names of a target by a reverse lookup of its first address.
+    pub fn resolve(target: &str, dns: &DnsCache) -> Self {
+        let names = Self::from_target(target);
+        let rdns = dns.lookup_first(target).and_then(|x| dns.reverse_lookup(x));
+        Self {
+            hostname: names.hostname.or_else(|| rdns.clone()),
+            fqdn: names
+                .fqdn
+                .or_else(|| rdns.clone().filter(|x| x.contains('.'))),
+            rdns,
+        }
+    }
+
+    /// Returns the KB keys and values of the known names.
+    pub fn kb_items(&self) -> Vec<(&'static str, &str)> {
+        [
+            (KB_HOSTNAME, &self.hostname),
+            (KB_FQDN, &self.fqdn),
+            (KB_RDNS, &self.rdns),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.as_deref().map(|x| (key, x)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn counting(calls: Arc<AtomicUsize>) -> impl Fn(&str) -> io::Result<Vec<IpAddr>> {
+        move |name| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            match name {
+                "example.com" => Ok(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]),
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "unknown")),
+            }
+        }
+    }
+
+    #[test]
+    fn positive_and_negative() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = DnsCache::new(Duration::from_secs(60), Duration::from_secs(60))
+            .with_resolver(counting(calls.clone()));
+        for _ in 0..3 {
+            assert_eq!(
+                cache.lookup_first("example.com"),
+                Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+            );
+            assert_eq!(cache.lookup("unknown.example"), None);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats(), DnsCacheStats { hits: 4, misses: 2 });
+        assert_eq!(
+            cache.lookup_first("127.0.0.1"),
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        );
+        assert_eq!(
+            cache.lookup_first("fe80::1%eth0"),
+            Some("fe80::1".parse().unwrap())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn expires() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache =
+            DnsCache::new(Duration::ZERO, Duration::ZERO).with_resolver(counting(calls.clone()));
+        cache.lookup("example.com");
+        cache.lookup("example.com");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn preferences() {
+        use crate::models::ScanPreference;
+        let cache = DnsCache::from_preferences(&[
+            ScanPreference {
+                id: DNS_CACHE_TTL.to_owned(),
+                value: "10".to_owned(),
+            },
+            ScanPreference {
+                id: DNS_CACHE_NEGATIVE_TTL.to_owned(),
+                value: "invalid".to_owned(),
+            },
+        ]);
+        assert_eq!(cache.ttl, Duration::from_secs(10));
+        assert_eq!(cache.negative_ttl, DnsCache::default().negative_ttl);
+    }
+
+    #[test]
+    fn host_names() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let reverse = calls.clone();
+        let cache = DnsCache::new(Duration::from_secs(60), Duration::from_secs(60))
+            .with_resolver(counting(calls.clone()))
+            .with_reverse_resolver(move |addr| {
+                reverse.fetch_add(1, Ordering::SeqCst);
+                match addr {
+                    IpAddr::V4(x) if x.octets() == [192, 0, 2, 1] => Ok("www.example.com.".into()),
+                    x => Ok(x.to_string()),
+                }
+            });
+        assert_eq!(
+            HostNames::resolve("192.0.2.1", &cache),
+            HostNames {
+                hostname: Some("www.example.com".into()),
+                fqdn: Some("www.example.com".into()),
+                rdns: Some("www.example.com".into()),
+            }
+        );
+        assert_eq!(
+            HostNames::resolve("example.com", &cache),
+            HostNames {
+                hostname: Some("example.com".into()),
+                fqdn: Some("example.com".into()),
+                rdns: Some("www.example.com".into()),
+            }
+        );
+        assert_eq!(HostNames::resolve("192.0.2.2", &cache).kb_items(), vec![]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            HostNames::from_target("localhost").kb_items(),
+            vec![(KB_HOSTNAME, "localhost")]
+        );
+        assert_eq!(HostNames::from_target("fe80::1%eth0"), HostNames::default());
+    }
+}
@@ -58,6 +58,9 @@ pub enum FunctionErrorKind {
     /// Generic error
     #[error("Generic error: {0}")]
     GeneralError(#[from] GeneralErrorType),
+    /// The function is disabled for the running scan
+    #[error("Builtin function {0} is disabled")]
+    Disabled(String),
     /// There is a deeper problem
     /// An example would be that there is no free memory left in the system
     #[error("{0}")]
@@ -74,6 +77,18 @@ impl From<io::Error> for FunctionErrorKind {
     }
 }
 
+impl From<super::LimitError> for FunctionErrorKind {
+    fn from(e: super::LimitError) -> Self {
+        Self::Diagnostic(e.to_string(), None)
+    }
+}
+
+impl From<super::TrafficError> for FunctionErrorKind {
+    fn from(e: super::TrafficError) -> Self {
+        Self::Diagnostic(e.to_string(), None)
+    }
+}
+
 impl FunctionErrorKind {
     /// Helper function to quickly construct a `WrongArgument` variant
     /// containing the name of the argument, the expected value and
@@ -0,0 +1,558 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Loads additional builtin functions from shared objects at runtime.
+//!
+//! A shared object exports the symbol `nasl_plugin_register` returning a pointer to a
+//! [NaslPluginDescriptor] that stays valid as long as the library is loaded. See
+//! `examples/nasl-plugin` for the C header and an example plugin.
+//!
+//! Each function is called with a JSON object containing the `target`, the `positional` and the
+//! `named` arguments and returns a JSON value. NASL values map to their JSON counterpart, data is
+//! encoded as `{"$data": "<hex>"}`.
+//!
+//! Before a library is opened it has to pass the following checks:
+//! - it must not be writable by group or others
+//! - when an allow list is set, its file name must be listed
+//! - when the signature check is enabled, it must be listed with a valid hash in the `sha256sums`
+//!   file of the directory, which itself must be signed by a key of the keyring in `GNUPGHOME`
+//!
+//! The checks and `dlopen` use the same file descriptor, so that the library cannot be replaced
+//! after it got checked.
+//!
+//! A library must not define a function that is already known to the executor.
+//!
+//! WASM modules are out of scope: they would require a runtime like wasmtime and a host
+//! interface for sockets and the KB. Plugins needing a sandbox have to be shipped as shared
+//! objects until then.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr, CString},
+    fs, io,
+    io::BufReader,
+    os::{fd::AsRawFd, unix::fs::PermissionsExt},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::feed::{check_signature, HashSumNameLoader, Hasher, VerifyError};
+use crate::nasl::prelude::*;
+use crate::nasl::utils::lookup_keys::FC_ANON_ARGS;
+
+use super::{Executor, FunctionSet};
+
+/// Version of the interface a plugin has to implement
+pub const ABI_VERSION: u32 = 1;
+
+/// Name of the symbol returning the [NaslPluginDescriptor]
+pub const REGISTER_SYMBOL: &str = "nasl_plugin_register";
+
+/// Calls a function of a plugin.
+///
+/// `arguments` is a JSON object. On success 0 is returned and `result` is set to a JSON value or
+/// left NULL to return NULL. Any other return value signals an error and `result` may point to an
+/// error message.
+pub type NaslPluginCall =
+    unsafe extern "C" fn(arguments: *const c_char, result: *mut *mut c_char) -> i32;
+
+/// A function defined by a plugin
+#[repr(C)]
+pub struct NaslPluginFunction {
+    /// Name of the function within NASL
+    pub name: *const c_char,
+    pub call: Option<NaslPluginCall>,
+}
+
+/// Describes the functions of a plugin
+#[repr(C)]
+pub struct NaslPluginDescriptor {
+    /// Must be [ABI_VERSION]
+    pub abi_version: u32,
+    /// Name of the plugin used for logging
+    pub name: *const c_char,
+    pub functions: *const NaslPluginFunction,
+    pub functions_len: usize,
+    /// Frees a string set as result by a function
+    pub free_result: Option<unsafe extern "C" fn(result: *mut c_char)>,
+}
+
+#[derive(Debug, Error)]
+/// Errors while loading a plugin
+pub enum DynamicError {
+    #[error("Unable to read {0}: {1}")]
+    /// The plugin directory or library cannot be read
+    IO(PathBuf, io::ErrorKind),
+    #[error("{0} is writable by group or others.")]
+    /// The library may have been modified by someone else
+    Permission(PathBuf),
+    #[error("Signature check of {0} failed: {1}")]
+    /// The sums file or the hash of a library is invalid
+    Signature(PathBuf, VerifyError),
+    #[error("Unable to load {0}: {1}")]
+    /// The library cannot be opened or does not export the register symbol
+    Open(PathBuf, String),
+    #[error("{0} implements ABI version {1} but {ABI_VERSION} is required.")]
+    /// The library implements a different interface version
+    AbiVersion(PathBuf, u32),
+    #[error("{0} is invalid: {1}")]
+    /// The descriptor returned by the library is malformed
+    Invalid(PathBuf, String),
+    #[error("{0} defines the already defined function {1}.")]
+    /// The library would shadow a function
+    AlreadyDefined(PathBuf, String),
+}
+
+/// Loads the plugins of a directory into an executor
+#[derive(Debug, Clone)]
+pub struct DynamicLoader {
+    path: PathBuf,
+    signature_check: bool,
+    allowed: Option<Vec<String>>,
+}
+
+impl DynamicLoader {
+    /// Creates a loader for the shared objects (`*.so`) within the given directory.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            signature_check: false,
+            allowed: None,
+        }
+    }
+
+    /// Requires each library to be listed in the signed sums file of the directory.
+    pub fn with_signature_check(mut self, signature_check: bool) -> Self {
+        self.signature_check = signature_check;
+        self
+    }
+
+    /// Only loads the libraries with the given file names.
+    pub fn with_allowed(mut self, allowed: Vec<String>) -> Self {
+        self.allowed = Some(allowed);
+        self
+    }
+
+    fn is_allowed(&self, file_name: &str) -> bool {
+        self.allowed
+            .as_ref()
+            .map(|x| x.iter().any(|x| x == file_name))
+            .unwrap_or(true)
+    }
+
+    /// Returns the signed libraries with their expected hash.
+    fn signed_libraries(&self) -> Result<Vec<(String, Option<String>)>, DynamicError> {
+        let signature_error = |e| DynamicError::Signature(self.path.clone(), e);
+        // check_signature panics on missing files
+        if !self.path.join("sha256sums.asc").is_file() {
+            return Err(signature_error(VerifyError::BadSignature(
+                "missing sha256sums.asc".to_string(),
+            )));
+        }
+        check_signature(&self.path).map_err(signature_error)?;
+        let loader = FSPluginLoader::new(&self.path);
+        let mut result = vec![];
+        for item in HashSumNameLoader::sha256(&loader).map_err(signature_error)? {
+            let item = item.map_err(signature_error)?;
+            if item.get_filename().ends_with(".so") {
+                result.push((item.get_filename(), Some(item.get_hashsum())));
+            }
+        }
+        Ok(result)
+    }
+
+    fn libraries(&self) -> Result<Vec<(String, Option<String>)>, DynamicError> {
+        if self.signature_check {
+            return self.signed_libraries();
+        }
+        let entries =
+            fs::read_dir(&self.path).map_err(|e| DynamicError::IO(self.path.clone(), e.kind()))?;
+        let mut result: Vec<String> = entries
+            .filter_map(|x| x.ok())
+            .map(|x| x.file_name().to_string_lossy().to_string())
+            .filter(|x| x.ends_with(".so"))
+            .collect();
+        result.sort();
+        Ok(result.into_iter().map(|x| (x, None)).collect())
+    }
+
+    /// Loads all permitted libraries and returns their file names.
+    ///
+    /// Libraries that are not on the allow list are skipped, every other failing check is an
+    /// error.
+    pub fn load(&self, executor: &mut Executor) -> Result<Vec<String>, DynamicError> {
+        let mut loaded = vec![];
+        for (file_name, hashsum) in self.libraries()? {
+            if !self.is_allowed(&file_name) {
+                tracing::warn!(file_name, "plugin is not allowed, skipping");
+                continue;
+            }
+            let path = self.path.join(&file_name);
+            let io_error = |e: io::Error| DynamicError::IO(path.clone(), e.kind());
+            let file = fs::File::open(&path).map_err(io_error)?;
+            let metadata = file.metadata().map_err(io_error)?;
+            if metadata.permissions().mode() & 0o022 != 0 {
+                return Err(DynamicError::Permission(path));
+            }
+            if let Some(expected) = hashsum {
+                let actual = Hasher::Sha256
+                    .hash(&mut BufReader::new(&file), &file_name)
+                    .map_err(|e| DynamicError::Signature(path.clone(), e))?;
+                if expected != actual {
+                    let key = file_name.clone();
+                    let e = VerifyError::HashInvalid {
+                        expected,
+                        actual,
+                        key,
+                    };
+                    return Err(DynamicError::Signature(path, e));
+                }
+            }
+            let set = DynamicFunctionSet::open(&file, &path)?;
+            if let Some(name) = set.functions.keys().find(|x| executor.contains(x)) {
+                return Err(DynamicError::AlreadyDefined(path, name.clone()));
+            }
+            tracing::info!(file_name, plugin = set.name, "loaded plugin");
+            executor.push_set(Box::new(set));
+            loaded.push(file_name);
+        }
+        Ok(loaded)
+    }
+}
+
+/// Handle of an opened shared object
+struct Library(*mut c_void);
+
+// The handle is only passed to dlsym and dlclose which are thread safe.
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    fn open(path: &Path) -> Result<Self, String> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes()).map_err(|e| e.to_string())?;
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            Err(dl_error())
+        } else {
+            Ok(Self(handle))
+        }
+    }
+
+    fn symbol(&self, name: &str) -> Result<*mut c_void, String> {
+        let c_name = CString::new(name).map_err(|e| e.to_string())?;
+        let symbol = unsafe { libc::dlsym(self.0, c_name.as_ptr()) };
+        if symbol.is_null() {
+            Err(dl_error())
+        } else {
+            Ok(symbol)
+        }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.0) };
+    }
+}
+
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// The functions of a loaded plugin
+struct DynamicFunctionSet {
+    name: String,
+    functions: HashMap<String, NaslPluginCall>,
+    free_result: Option<unsafe extern "C" fn(*mut c_char)>,
+    // must be dropped after the function pointers are not used anymore
+    _library: Option<Library>,
+}
+
+impl DynamicFunctionSet {
+    /// Opens the library of the given file, the path is only used for errors.
+    fn open(file: &fs::File, path: &Path) -> Result<Self, DynamicError> {
+        let open_error = |e| DynamicError::Open(path.to_path_buf(), e);
+        let fd_path = PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()));
+        let library = Library::open(&fd_path).map_err(open_error)?;
+        let register = library.symbol(REGISTER_SYMBOL).map_err(open_error)?;
+        let register: unsafe extern "C" fn() -> *const NaslPluginDescriptor =
+            unsafe { std::mem::transmute(register) };
+        let descriptor = unsafe { register() };
+        unsafe { Self::from_descriptor(descriptor, Some(library)) }.map_err(|e| match e {
+            DescriptorError::AbiVersion(x) => DynamicError::AbiVersion(path.to_path_buf(), x),
+            DescriptorError::Invalid(x) => DynamicError::Invalid(path.to_path_buf(), x),
+        })
+    }
+
+    /// # Safety
+    ///
+    /// The descriptor must either be NULL or point to a descriptor that lives as long as the
+    /// library.
+    unsafe fn from_descriptor(
+        descriptor: *const NaslPluginDescriptor,
+        library: Option<Library>,
+    ) -> Result<Self, DescriptorError> {
+        let invalid = |x: &str| DescriptorError::Invalid(x.to_string());
+        let descriptor = descriptor
+            .as_ref()
+            .ok_or_else(|| invalid("no descriptor"))?;
+        if descriptor.abi_version != ABI_VERSION {
+            return Err(DescriptorError::AbiVersion(descriptor.abi_version));
+        }
+        let string = |x: *const c_char| {
+            (!x.is_null())
+                .then(|| CStr::from_ptr(x).to_string_lossy().to_string())
+                .filter(|x| !x.is_empty())
+        };
+        let name = string(descriptor.name).ok_or_else(|| invalid("missing name"))?;
+        let functions = if descriptor.functions_len == 0 {
+            &[]
+        } else if descriptor.functions.is_null() {
+            return Err(invalid("missing functions"));
+        } else {
+            std::slice::from_raw_parts(descriptor.functions, descriptor.functions_len)
+        };
+        let functions = functions
+            .iter()
+            .map(|x| match (string(x.name), x.call) {
+                (Some(name), Some(call)) => Ok((name, call)),
+                _ => Err(invalid("function without name or call")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name,
+            functions,
+            free_result: descriptor.free_result,
+            _library: library,
+        })
+    }
+
+    fn call(&self, k: &str, arguments: Value) -> NaslResult {
+        let call = self.functions[k];
+        let arguments = CString::new(arguments.to_string())
+            .expect("JSON does not contain unescaped nul characters");
+        let mut result = ptr::null_mut();
+        let code = unsafe { call(arguments.as_ptr(), &mut result) };
+        let result = (!result.is_null()).then(|| {
+            let text = unsafe { CStr::from_ptr(result) }
+                .to_string_lossy()
+                .to_string();
+            if let Some(free) = self.free_result {
+                unsafe { free(result) };
+            }
+            text
+        });
+        let diagnostic =
+            |x: String| FunctionErrorKind::Diagnostic(format!("{}: {k}: {x}", self.name), None);
+        if code != 0 {
+            return Err(diagnostic(
+                result.unwrap_or_else(|| format!("failed with {code}")),
+            ));
+        }
+        match result {
+            Some(x) => serde_json::from_str(&x)
+                .map(from_json)
+                .map_err(|e| diagnostic(e.to_string())),
+            None => Ok(NaslValue::Null),
+        }
+    }
+}
+
+enum DescriptorError {
+    AbiVersion(u32),
+    Invalid(String),
+}
+
+#[async_trait]
+impl FunctionSet for DynamicFunctionSet {
+    async fn exec<'a>(
+        &'a self,
+        k: &'a str,
+        register: &'a Register,
+        context: &'a Context<'_>,
+    ) -> NaslResult {
+        self.call(k, arguments(register, context))
+    }
+
+    fn contains(&self, k: &str) -> bool {
+        self.functions.contains_key(k)
+    }
+}
+
+fn arguments(register: &Register, context: &Context) -> Value {
+    let named: Map<String, Value> = register
+        .iter_named_args()
+        .into_iter()
+        .flatten()
+        .filter(|x| *x != FC_ANON_ARGS)
+        .filter_map(|x| match register.named(x) {
+            Some(ContextType::Value(v)) => Some((x.to_string(), to_json(v))),
+            _ => None,
+        })
+        .collect();
+    json!({
+        "target": context.target(),
+        "positional": register.positional().iter().map(to_json).collect::<Vec<_>>(),
+        "named": named,
+    })
+}
+
+fn to_json(value: &NaslValue) -> Value {
+    match value {
+        NaslValue::String(x) => Value::String(x.to_string()),
+        NaslValue::Data(x) => json!({ "$data": hex::encode(x) }),
+        NaslValue::Number(x) => json!(x),
+        NaslValue::Boolean(x) => Value::Bool(*x),
+        NaslValue::Array(x) => Value::Array(x.iter().map(to_json).collect()),
+        NaslValue::Dict(x) => {
+            Value::Object(x.iter().map(|(k, v)| (k.to_string(), to_json(v))).collect())
+        }
+        _ => Value::Null,
+    }
+}
+
+fn from_json(value: Value) -> NaslValue {
+    match value {
+        Value::Null => NaslValue::Null,
+        Value::Bool(x) => NaslValue::Boolean(x),
+        Value::Number(x) => NaslValue::Number(
+            x.as_i64()
+                .unwrap_or_else(|| x.as_f64().unwrap_or_default() as i64),
+        ),
+        Value::String(x) => NaslValue::String(x.into()),
+        Value::Array(x) => NaslValue::Array(x.into_iter().map(from_json).collect()),
+        Value::Object(x) => match x.get("$data").and_then(|x| x.as_str()).map(hex::decode) {
+            Some(Ok(data)) if x.len() == 1 => NaslValue::Data(data),
+            _ => NaslValue::Dict(x.into_iter().map(|(k, v)| (k, from_json(v))).collect()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::nasl::nasl_std_functions;
+    use crate::nasl::test_prelude::*;
+
+    use super::*;
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn echo(arguments: *const c_char, result: *mut *mut c_char) -> i32 {
+        *result = CString::from(CStr::from_ptr(arguments)).into_raw();
+        0
+    }
+
+    unsafe extern "C" fn fail(_: *const c_char, result: *mut *mut c_char) -> i32 {
+        *result = CString::new("no route").unwrap().into_raw();
+        1
+    }
+
+    unsafe extern "C" fn free_result(result: *mut c_char) {
+        FREED.fetch_add(1, Ordering::SeqCst);
+        drop(CString::from_raw(result));
+    }
+
+    fn plugin(abi_version: u32) -> Result<DynamicFunctionSet, DescriptorError> {
+        let c = |x: &str| CString::new(x).unwrap().into_raw() as *const c_char;
+        let functions = Box::leak(Box::new([
+            NaslPluginFunction {
+                name: c("plugin_echo"),
+                call: Some(echo),
+            },
+            NaslPluginFunction {
+                name: c("plugin_fail"),
+                call: Some(fail),
+            },
+        ]));
+        let descriptor = Box::leak(Box::new(NaslPluginDescriptor {
+            abi_version,
+            name: c("test"),
+            functions: functions.as_ptr(),
+            functions_len: functions.len(),
+            free_result: Some(free_result),
+        }));
+        unsafe { DynamicFunctionSet::from_descriptor(descriptor, None) }
+    }
+
+    #[test]
+    fn calls_plugin_functions() {
+        assert!(matches!(plugin(2), Err(DescriptorError::AbiVersion(2))));
+        let mut executor = nasl_std_functions();
+        executor.push_set(Box::new(plugin(ABI_VERSION).ok().unwrap()));
+        let mut t = TestBuilder::default().with_context(ContextFactory {
+            functions: executor,
+            ..Default::default()
+        });
+        t.ok(
+            r#"x = plugin_echo(1, "a", raw_string(0x00), port: 80);"#,
+            NaslValue::Dict(HashMap::from([
+                ("target".to_string(), NaslValue::String("".into())),
+                (
+                    "positional".to_string(),
+                    NaslValue::Array(vec![
+                        NaslValue::Number(1),
+                        NaslValue::String("a".into()),
+                        NaslValue::Data(vec![0]),
+                    ]),
+                ),
+                (
+                    "named".to_string(),
+                    NaslValue::Dict(HashMap::from([("port".to_string(), NaslValue::Number(80))])),
+                ),
+            ])),
+        );
+        t.check("plugin_fail();", |e| {
+            matches!(e, Err(FunctionErrorKind::Diagnostic(x, None)) if x == "test: plugin_fail: no route")
+        });
+        drop(t);
+        assert_eq!(FREED.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn checks_libraries_before_loading() {
+        let directory = std::env::temp_dir().join(format!("nasl-plugins-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let library = directory.join("broken.so");
+        fs::write(&library, b"not a shared object").unwrap();
+        fs::write(directory.join("ignored.txt"), b"").unwrap();
+        let mut executor = Executor::default();
+
+        fs::set_permissions(&library, fs::Permissions::from_mode(0o666)).unwrap();
+        let loader = DynamicLoader::new(&directory);
+        assert!(matches!(
+            loader.load(&mut executor),
+            Err(DynamicError::Permission(_))
+        ));
+        fs::set_permissions(&library, fs::Permissions::from_mode(0o644)).unwrap();
+        // opened via the descriptor but reported with the path of the library
+        assert!(matches!(
+            loader.load(&mut executor),
+            Err(DynamicError::Open(path, _)) if path == library
+        ));
+        let loaded = loader
+            .clone()
+            .with_allowed(vec!["other.so".to_string()])
+            .load(&mut executor)
+            .unwrap();
+        assert!(loaded.is_empty());
+        assert!(matches!(
+            loader.with_signature_check(true).load(&mut executor),
+            Err(DynamicError::Signature(_, _))
+        ));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -10,9 +10,14 @@
 //!    Typically, stateful functions are implemented as methods on the state struct.
 //!
 //! In order to create new sets of NASL functions, the `function_set!` macro is provided.
+//! Sets whose state implements [Plugin] can be registered via `Executor::add_plugin` to be
+//! notified when a scan or script starts and ends. Additional functions can be loaded at runtime
+//! from shared objects via [DynamicLoader].
+mod dynamic;
 mod nasl_function;
+mod plugin;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
 use nasl_function::{AsyncDoubleArgFn, AsyncTripleArgFn, NaslFunction};
@@ -20,6 +25,15 @@ use tokio::sync::RwLock;
 
 use crate::nasl::prelude::*;
 
+use super::cassette::{Cassette, CassetteSet};
+use super::policy::Risk;
+
+pub use dynamic::{
+    DynamicError, DynamicLoader, NaslPluginCall, NaslPluginDescriptor, NaslPluginFunction,
+    ABI_VERSION, REGISTER_SYMBOL,
+};
+pub use plugin::{Plugin, PluginConfig};
+
 #[derive(Default)]
 /// The executor. This is the main outward facing type of this module
 /// and fulfills two main roles:
@@ -28,6 +42,16 @@ use crate::nasl::prelude::*;
 ///    includes things such as open SSH or HTTP connections, mutexes, etc.
 pub struct Executor {
     sets: Vec<Box<dyn FunctionSet + Send + Sync>>,
+    /// Module of the builtin functions of each set
+    modules: Vec<Option<&'static str>>,
+    /// Functions that are not safe
+    risks: HashMap<String, Risk>,
+}
+
+/// Returns the module of [crate::nasl::builtin] the type is defined in, e.g. `raw_ip`.
+fn builtin_module<S>() -> Option<&'static str> {
+    let (_, path) = std::any::type_name::<S>().split_once("::builtin::")?;
+    path.split("::").next()
 }
 
 impl Executor {
@@ -46,9 +70,88 @@ impl Executor {
         <S as IntoFunctionSet>::State: Send + Sync,
     {
         self.sets.push(Box::new(S::into_function_set(s)));
+        self.modules.push(builtin_module::<S>());
+        self
+    }
+
+    /// Adds a set whose state gets notified about the lifecycle of scans and scripts.
+    pub fn add_plugin<S: IntoFunctionSet + 'static>(&mut self, s: S) -> &mut Self
+    where
+        <S as IntoFunctionSet>::State: Plugin,
+    {
+        self.sets
+            .push(Box::new(PluginFunctionSet(S::into_function_set(s))));
+        self.modules.push(builtin_module::<S>());
         self
     }
 
+    pub(crate) fn push_set(&mut self, set: Box<dyn FunctionSet + Send + Sync>) -> &mut Self {
+        self.sets.push(set);
+        self.modules.push(None);
+        self
+    }
+
+    /// Records the calls of network functions into the cassette or replays them from it.
+    ///
+    /// This must be called after all sets are added.
+    pub fn with_cassette(&mut self, cassette: Arc<Cassette>) -> &mut Self {
+        self.sets = std::mem::take(&mut self.sets)
+            .into_iter()
+            .map(|x| {
+                Box::new(CassetteSet::new(x, cassette.clone()))
+                    as Box<dyn FunctionSet + Send + Sync>
+            })
+            .collect();
+        self
+    }
+
+    /// Annotates how the function may affect the target, functions are safe unless annotated.
+    pub fn with_risk(&mut self, k: &str, risk: Risk) -> &mut Self {
+        self.risks.insert(k.to_string(), risk);
+        self
+    }
+
+    /// Returns how the function may affect the target.
+    pub fn risk_of(&self, k: &str) -> Risk {
+        self.risks.get(k).copied().unwrap_or_default()
+    }
+
+    /// Initializes all plugins for a new scan.
+    ///
+    /// Every plugin is initialized even when one of them fails, the first error is returned.
+    pub async fn init_scan(&self, config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        let mut result = Ok(());
+        for set in self.sets.iter() {
+            let r = set.init_scan(config).await;
+            result = result.and(r);
+        }
+        result
+    }
+
+    /// Prepares all plugins for the script of the given context.
+    pub async fn init_script(&self, context: &Context<'_>) -> Result<(), FunctionErrorKind> {
+        let mut result = Ok(());
+        for set in self.sets.iter() {
+            let r = set.init_script(context).await;
+            result = result.and(r);
+        }
+        result
+    }
+
+    /// Notifies all plugins that the script of the given context finished.
+    pub async fn finish_script(&self, context: &Context<'_>) {
+        for set in self.sets.iter() {
+            set.finish_script(context).await;
+        }
+    }
+
+    /// Shuts all plugins down.
+    pub async fn shutdown(&self) {
+        for set in self.sets.iter() {
+            set.shutdown().await;
+        }
+    }
+
     pub async fn exec(
         &self,
         k: &str,
@@ -66,6 +169,14 @@ impl Executor {
     pub fn contains(&self, k: &str) -> bool {
         self.sets.iter().any(|set| set.contains(k))
     }
+
+    /// Returns the module of the builtin function, none for functions loaded at runtime.
+    pub fn module_of(&self, k: &str) -> Option<&'static str> {
+        self.sets
+            .iter()
+            .position(|set| set.contains(k))
+            .and_then(|i| self.modules[i])
+    }
 }
 
 pub struct StoredFunctionSet<State> {
@@ -180,6 +291,18 @@ pub trait FunctionSet {
     ) -> NaslResult;
 
     fn contains(&self, k: &str) -> bool;
+
+    async fn init_scan(&self, _config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        Ok(())
+    }
+
+    async fn init_script(&self, _context: &Context<'_>) -> Result<(), FunctionErrorKind> {
+        Ok(())
+    }
+
+    async fn finish_script(&self, _context: &Context<'_>) {}
+
+    async fn shutdown(&self) {}
 }
 
 #[async_trait]
@@ -218,6 +341,41 @@ impl<State: Sync + Send> FunctionSet for StoredFunctionSet<State> {
     }
 }
 
+/// A `StoredFunctionSet` whose state receives the lifecycle hooks of [Plugin].
+struct PluginFunctionSet<State>(StoredFunctionSet<State>);
+
+#[async_trait]
+impl<State: Plugin> FunctionSet for PluginFunctionSet<State> {
+    async fn exec<'a>(
+        &'a self,
+        k: &'a str,
+        register: &'a Register,
+        context: &'a Context<'_>,
+    ) -> NaslResult {
+        self.0.exec(k, register, context).await
+    }
+
+    fn contains(&self, k: &str) -> bool {
+        self.0.contains(k)
+    }
+
+    async fn init_scan(&self, config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        self.0.state.write().await.init_scan(config).await
+    }
+
+    async fn init_script(&self, context: &Context<'_>) -> Result<(), FunctionErrorKind> {
+        self.0.state.write().await.init_script(context).await
+    }
+
+    async fn finish_script(&self, context: &Context<'_>) {
+        self.0.state.write().await.finish_script(context).await
+    }
+
+    async fn shutdown(&self) {
+        self.0.state.write().await.shutdown().await
+    }
+}
+
 /// Anything that can be converted into a `StoredFunctionSet`.
 pub trait IntoFunctionSet {
     /// The state associated with the function set.
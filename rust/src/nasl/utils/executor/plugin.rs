@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Lifecycle hooks for function sets that need to set up or tear down their state, e.g. to open
+//! clients shared by all scripts of a scan or to close the connections a script left open.
+
+use std::{any::type_name, collections::HashMap, str::FromStr};
+
+use async_trait::async_trait;
+
+use crate::models::{Scan, ScanPreference};
+use crate::nasl::prelude::*;
+
+/// The configuration of a scan as seen by a plugin
+#[derive(Debug, Clone, Default)]
+pub struct PluginConfig {
+    scan_id: String,
+    preferences: HashMap<String, String>,
+}
+
+impl From<&Scan> for PluginConfig {
+    fn from(scan: &Scan) -> Self {
+        Self::new(&scan.scan_id, &scan.scan_preferences)
+    }
+}
+
+impl PluginConfig {
+    /// Creates a configuration out of the scan preferences.
+    pub fn new(scan_id: &str, preferences: &[ScanPreference]) -> Self {
+        Self {
+            scan_id: scan_id.to_owned(),
+            preferences: preferences
+                .iter()
+                .map(|x| (x.id.clone(), x.value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns the id of the scan
+    pub fn scan_id(&self) -> &str {
+        &self.scan_id
+    }
+
+    /// Returns the raw value of a scan preference
+    pub fn value(&self, id: &str) -> Option<&str> {
+        self.preferences.get(id).map(|x| x.as_str())
+    }
+
+    /// Parses the value of a scan preference.
+    ///
+    /// Returns None when the preference is not set and an error when it cannot be parsed.
+    pub fn get<T: FromStr>(&self, id: &str) -> Result<Option<T>, FunctionErrorKind> {
+        self.value(id)
+            .map(|x| {
+                x.trim()
+                    .parse()
+                    .map_err(|_| FunctionErrorKind::wrong_argument(id, type_name::<T>(), x))
+            })
+            .transpose()
+    }
+
+    /// Returns true when a scan preference is set to `1`, `yes` or `true`.
+    pub fn flag(&self, id: &str) -> bool {
+        self.value(id)
+            .and_then(crate::models::parse_bool)
+            .unwrap_or_default()
+    }
+}
+
+/// Lifecycle hooks of the state of a function set.
+///
+/// The hooks are only called for sets that are registered via `Executor::add_plugin`. All of them
+/// default to doing nothing.
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    /// Is called once before the first script of a scan runs.
+    async fn init_scan(&mut self, _config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+        Ok(())
+    }
+
+    /// Is called before a script runs, a failure prevents the script from running.
+    async fn init_script(&mut self, _context: &Context<'_>) -> Result<(), FunctionErrorKind> {
+        Ok(())
+    }
+
+    /// Is called after a script finished, regardless of its result.
+    async fn finish_script(&mut self, _context: &Context<'_>) {}
+
+    /// Is called once when the executor is not used anymore.
+    async fn shutdown(&mut self) {}
+}
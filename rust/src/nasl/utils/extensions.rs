@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines a typed map of state that is shared between all scripts of a scan.
+//!
+//! Builtin function sets like http or ssh need state that outlives a single script, e.g. open
+//! connections or caches. Instead of keeping such state within the function set, which is shared
+//! by every scan using the same executor, it is stored in the [Extensions] of the
+//! [super::Context]. Each scan creates its own instance, so that the state is dropped when the
+//! scan is finished.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+type Entry = Arc<dyn Any + Send + Sync>;
+
+/// Holds one value per type, shared between all contexts of a scan.
+#[derive(Default)]
+pub struct Extensions {
+    entries: RwLock<HashMap<TypeId, Entry>>,
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+fn downcast<T>(entry: Entry) -> Arc<T>
+where
+    T: Any + Send + Sync,
+{
+    entry
+        .downcast()
+        .unwrap_or_else(|_| unreachable!("entries are stored by their TypeId"))
+}
+
+impl Extensions {
+    /// Returns the value of type T if it is set.
+    pub fn get<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries.get(&TypeId::of::<T>()).cloned().map(downcast)
+    }
+
+    /// Returns the value of type T, it is created by init when it is not set yet.
+    pub fn get_or_insert_with<T, F>(&self, init: F) -> Arc<T>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> T,
+    {
+        if let Some(x) = self.get() {
+            return x;
+        }
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        // another script may have set it in between
+        let entry = entries
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(init()));
+        downcast(entry.clone())
+    }
+
+    /// Returns the value of type T, the default is used when it is not set yet.
+    pub fn get_or_default<T>(&self) -> Arc<T>
+    where
+        T: Any + Send + Sync + Default,
+    {
+        self.get_or_insert_with(T::default)
+    }
+
+    /// Sets the value of type T and returns the previous one.
+    pub fn insert<T>(&self, value: T) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .map(downcast)
+    }
+
+    /// Removes the value of type T and returns it.
+    pub fn remove<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&TypeId::of::<T>()).map(downcast)
+    }
+
+    /// Returns the amount of set values
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true when no value is set
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    #[test]
+    fn typed_entries() {
+        let extensions = Extensions::default();
+        assert!(extensions.get::<Counter>().is_none());
+        extensions
+            .get_or_default::<Counter>()
+            .0
+            .fetch_add(1, Ordering::SeqCst);
+        extensions
+            .get_or_default::<Counter>()
+            .0
+            .fetch_add(1, Ordering::SeqCst);
+        assert_eq!(
+            extensions
+                .get::<Counter>()
+                .unwrap()
+                .0
+                .load(Ordering::SeqCst),
+            2
+        );
+
+        assert!(extensions.insert(String::from("a")).is_none());
+        assert_eq!(
+            extensions.insert(String::from("b")).as_deref(),
+            Some(&String::from("a"))
+        );
+        assert_eq!(
+            *extensions.get_or_insert_with(|| String::from("c")),
+            String::from("b")
+        );
+        assert_eq!(extensions.len(), 2);
+        assert!(extensions.remove::<Counter>().is_some());
+        assert!(extensions.get::<Counter>().is_none());
+        assert_eq!(extensions.len(), 1);
+    }
+}
@@ -45,12 +45,9 @@ impl<'a> FromNaslValue<'a> for &'a str {
 
 impl<'a> FromNaslValue<'a> for &'a [u8] {
     fn from_nasl_value(value: &'a NaslValue) -> Result<Self, FunctionErrorKind> {
-        match value {
-            NaslValue::Data(bytes) => Ok(bytes),
-            _ => Err(FunctionErrorKind::WrongArgument(
-                "Expected byte data.".to_string(),
-            )),
-        }
+        value
+            .as_data()
+            .ok_or_else(|| FunctionErrorKind::WrongArgument("Expected byte data.".to_string()))
     }
 }
 
@@ -40,13 +40,13 @@ impl ToNaslResult for () {
 
 impl ToNaslResult for String {
     fn to_nasl_result(self) -> NaslResult {
-        Ok(NaslValue::String(self))
+        Ok(NaslValue::String(self.into()))
     }
 }
 
 impl ToNaslResult for &str {
     fn to_nasl_result(self) -> NaslResult {
-        Ok(NaslValue::String(self.to_owned()))
+        Ok(NaslValue::String(self.into()))
     }
 }
 
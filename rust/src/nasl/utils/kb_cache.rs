@@ -0,0 +1,327 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines a per host cache of the KB items written by the service detection.
+//!
+//! Most scripts check the `Services/` and `Ports/` items of a host, often thousands of times per
+//! host. While the discovery stage of a host runs, items with these prefixes are collected in
+//! memory instead of being written one by one; reads merge them with the stored items. When the
+//! discovery stage finished the collected items are written within a single batch and all items
+//! with these prefixes are read once. Afterwards reads of these prefixes are served from memory
+//! and writes go to the storage as well as the cache.
+//!
+//! Until the discovery stage finished the collected items are only visible via the cache.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::storage::{
+    matches_kb_key, ContextKey, Dispatcher, Field, FieldKeyResult, Kb, KbSnapshot, Retrieve,
+    Retriever, StorageError,
+};
+
+/// Prefixes of the KB items that are cached
+pub const PREWARMED_PREFIXES: [&str; 2] = ["Services/", "Ports/"];
+
+/// Returns true when all keys matching the pattern are cached.
+fn is_cached(pattern: &str) -> bool {
+    let literal = pattern.split('*').next().unwrap_or_default();
+    PREWARMED_PREFIXES.iter().any(|x| literal.starts_with(x))
+}
+
+/// Returns true when a key matching the pattern may be cached.
+fn may_be_cached(pattern: &str) -> bool {
+    let literal = pattern.split('*').next().unwrap_or_default();
+    PREWARMED_PREFIXES
+        .iter()
+        .any(|x| literal.starts_with(x) || x.starts_with(literal))
+}
+
+fn push_unique(kbs: &mut Vec<Kb>, kb: Kb) {
+    if !kbs.iter().any(|x| x.key == kb.key && x.value == kb.value) {
+        kbs.push(kb);
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// Items written during the discovery stage that are not yet stored
+    Collecting(Vec<Kb>),
+    /// Stored items by key
+    Warm(HashMap<String, Vec<Kb>>),
+}
+
+/// Caches the `Services/` and `Ports/` KB items of a single host
+#[derive(Debug)]
+pub struct KbCache {
+    state: RwLock<State>,
+}
+
+impl Default for KbCache {
+    fn default() -> Self {
+        Self {
+            state: RwLock::new(State::Collecting(vec![])),
+        }
+    }
+}
+
+impl KbCache {
+    /// Returns true when the items were read into memory.
+    pub fn is_warm(&self) -> bool {
+        self.state
+            .read()
+            .map(|x| matches!(*x, State::Warm(_)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the items matching the pattern, see [Retrieve::KB].
+    pub fn retrieve(
+        &self,
+        retriever: &dyn Retriever,
+        key: &ContextKey,
+        pattern: &str,
+    ) -> Result<Vec<Kb>, StorageError> {
+        let stored = |pattern: &str| -> Result<Vec<Kb>, StorageError> {
+            Ok(retriever
+                .retrieve(key, Retrieve::KB(pattern.to_string()))?
+                .filter_map(|x| match x {
+                    Field::KB(kb) => Some(kb),
+                    _ => None,
+                })
+                .collect())
+        };
+        let state = self.state.read()?;
+        match &*state {
+            State::Collecting(pending) if may_be_cached(pattern) => {
+                let mut kbs = stored(pattern)?;
+                for kb in pending.iter().filter(|x| matches_kb_key(pattern, &x.key)) {
+                    push_unique(&mut kbs, kb.clone());
+                }
+                Ok(kbs)
+            }
+            State::Warm(items) if is_cached(pattern) => {
+                if !pattern.contains('*') {
+                    return Ok(items.get(pattern).cloned().unwrap_or_default());
+                }
+                let mut matching: Vec<_> = items
+                    .iter()
+                    .filter(|(k, _)| matches_kb_key(pattern, k))
+                    .collect();
+                matching.sort_by(|a, b| a.0.cmp(b.0));
+                Ok(matching.into_iter().flat_map(|(_, x)| x.clone()).collect())
+            }
+            _ => stored(pattern),
+        }
+    }
+
+    /// Writes an item, a replaced item replaces all items of the same key.
+    pub fn dispatch(
+        &self,
+        dispatcher: &dyn Dispatcher,
+        key: &ContextKey,
+        kb: Kb,
+        replace: bool,
+    ) -> Result<(), StorageError> {
+        if !PREWARMED_PREFIXES.iter().any(|x| kb.key.starts_with(x)) {
+            return match replace {
+                true => dispatcher.dispatch_replace(key, Field::KB(kb)),
+                false => dispatcher.dispatch(key, Field::KB(kb)),
+            };
+        }
+        let mut state = self.state.write()?;
+        match &mut *state {
+            State::Collecting(pending) if !replace => {
+                push_unique(pending, kb);
+                Ok(())
+            }
+            State::Collecting(pending) => {
+                // the collected items are stored first so that they are replaced as well
+                let collected = std::mem::take(pending);
+                dispatcher.dispatch_batch(key, collected.into_iter().map(Field::KB).collect())?;
+                dispatcher.dispatch_replace(key, Field::KB(kb))
+            }
+            State::Warm(items) => {
+                let entry = items.entry(kb.key.clone()).or_default();
+                if replace {
+                    dispatcher.dispatch_replace(key, Field::KB(kb.clone()))?;
+                    *entry = vec![kb];
+                } else {
+                    dispatcher.dispatch(key, Field::KB(kb.clone()))?;
+                    push_unique(entry, kb);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores the collected items within a single batch and reads all cached items into memory.
+    ///
+    /// Returns the amount of cached items. Does nothing when the cache is already warm.
+    pub fn prewarm(
+        &self,
+        dispatcher: &dyn Dispatcher,
+        retriever: &dyn Retriever,
+        key: &ContextKey,
+    ) -> Result<usize, StorageError> {
+        let mut state = self.state.write()?;
+        let State::Collecting(pending) = &mut *state else {
+            return Ok(0);
+        };
+        let collected = std::mem::take(pending);
+        dispatcher.dispatch_batch(key, collected.into_iter().map(Field::KB).collect())?;
+        let mut items: HashMap<String, Vec<Kb>> = HashMap::new();
+        let mut count = 0;
+        for prefix in PREWARMED_PREFIXES {
+            for field in retriever.retrieve(key, Retrieve::KB(format!("{prefix}*")))? {
+                if let Field::KB(kb) = field {
+                    count += 1;
+                    items.entry(kb.key.clone()).or_default().push(kb);
+                }
+            }
+        }
+        *state = State::Warm(items);
+        Ok(count)
+    }
+}
+
+/// Storage of a script run that reads and writes the cached KB items via a [KbCache]
+pub struct CachedStorage<'a> {
+    pub(crate) dispatcher: &'a dyn Dispatcher,
+    pub(crate) retriever: &'a dyn Retriever,
+    pub(crate) cache: Arc<KbCache>,
+}
+
+impl Dispatcher for CachedStorage<'_> {
+    fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        match scope {
+            Field::KB(kb) => self.cache.dispatch(self.dispatcher, key, kb, false),
+            scope => self.dispatcher.dispatch(key, scope),
+        }
+    }
+
+    fn dispatch_replace(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
+        match scope {
+            Field::KB(kb) => self.cache.dispatch(self.dispatcher, key, kb, true),
+            scope => self.dispatcher.dispatch_replace(key, scope),
+        }
+    }
+
+    fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError> {
+        self.dispatcher.on_exit(key)
+    }
+}
+
+impl Retriever for CachedStorage<'_> {
+    fn retrieve(
+        &self,
+        key: &ContextKey,
+        scope: Retrieve,
+    ) -> Result<Box<dyn Iterator<Item = Field>>, StorageError> {
+        match scope {
+            Retrieve::KB(pattern) => {
+                let kbs = self.cache.retrieve(self.retriever, key, &pattern)?;
+                Ok(Box::new(kbs.into_iter().map(Field::KB)))
+            }
+            scope => self.retriever.retrieve(key, scope),
+        }
+    }
+
+    fn retrieve_kb_snapshot(&self, key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        self.retriever.retrieve_kb_snapshot(key)
+    }
+
+    fn retrieve_by_field(&self, field: Field, scope: Retrieve) -> FieldKeyResult {
+        self.retriever.retrieve_by_field(field, scope)
+    }
+
+    fn retrieve_by_fields(&self, field: Vec<Field>, scope: Retrieve) -> FieldKeyResult {
+        self.retriever.retrieve_by_fields(field, scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::DefaultDispatcher;
+
+    use super::*;
+
+    fn values(kbs: Vec<Kb>) -> Vec<String> {
+        kbs.into_iter().map(|x| x.value.to_string()).collect()
+    }
+
+    #[test]
+    fn prewarm() {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::Scan("1".into(), Some("127.0.0.1".into()));
+        let cache = Arc::new(KbCache::default());
+        let cached = CachedStorage {
+            dispatcher: &storage,
+            retriever: &storage,
+            cache: cache.clone(),
+        };
+        storage
+            .dispatch(&key, Field::KB(("Ports/tcp/22", 1).into()))
+            .unwrap();
+        cached
+            .dispatch(&key, Field::KB(("Ports/tcp/80", 1).into()))
+            .unwrap();
+        cached
+            .dispatch(&key, Field::KB(("Host/dead", 0).into()))
+            .unwrap();
+        // collected items are only visible via the cache until the cache is warm
+        let stored = |pattern: &str| {
+            storage
+                .retrieve(&key, Retrieve::KB(pattern.into()))
+                .unwrap()
+                .count()
+        };
+        assert_eq!(stored("Ports/*"), 1);
+        assert_eq!(stored("Host/dead"), 1);
+        assert_eq!(cache.retrieve(&storage, &key, "Ports/*").unwrap().len(), 2);
+        assert_eq!(cache.retrieve(&storage, &key, "*").unwrap().len(), 3);
+
+        assert_eq!(cache.prewarm(&storage, &storage, &key).unwrap(), 2);
+        assert!(cache.is_warm());
+        assert_eq!(stored("Ports/*"), 2);
+
+        cached
+            .dispatch_replace(&key, Field::KB(("Services/www", 80).into()))
+            .unwrap();
+        cached
+            .dispatch(&key, Field::KB(("Services/www", 8080).into()))
+            .unwrap();
+        assert_eq!(
+            values(cache.retrieve(&storage, &key, "Services/www").unwrap()),
+            vec!["80", "8080"]
+        );
+        assert_eq!(stored("Services/www"), 2);
+        // items written to the storage directly are not seen by the warm cache
+        storage
+            .dispatch(&key, Field::KB(("Ports/tcp/443", 1).into()))
+            .unwrap();
+        assert_eq!(
+            values(cache.retrieve(&storage, &key, "Ports/*").unwrap()),
+            vec!["1", "1"]
+        );
+    }
+
+    #[test]
+    fn replace_while_collecting() {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::Scan("1".into(), Some("127.0.0.1".into()));
+        let cache = KbCache::default();
+        cache
+            .dispatch(&storage, &key, ("Services/www", 80).into(), false)
+            .unwrap();
+        cache
+            .dispatch(&storage, &key, ("Services/www", 443).into(), true)
+            .unwrap();
+        assert_eq!(
+            values(cache.retrieve(&storage, &key, "Services/www").unwrap()),
+            vec!["443"]
+        );
+    }
+}
@@ -0,0 +1,446 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines a limiter for outbound connections that is shared between all scripts of a scan.
+//!
+//! With adaptive concurrency the limiter additionally tracks the [Responsiveness] of each host and
+//! adapts the amount of concurrently open sockets and the socket timeouts to it. Connections to
+//! hosts blocked by the [HostBlocklist] are refused.
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::blocklist::{BlockReason, HostBlocklist};
+use super::responsiveness::{Responsiveness, ADAPTIVE_CONCURRENCY, DEFAULT_MAX_CONCURRENCY};
+
+/// Preference id of the maximum of new connections per second of a scan.
+pub const MAX_CONNECTIONS_PER_SECOND: &str = "max_connections_per_second";
+/// Preference id of the maximum of concurrently open sockets per host.
+pub const MAX_SOCKETS_PER_HOST: &str = "max_sockets_per_host";
+
+/// Time to wait for a free socket slot of a host before giving up.
+const SOCKET_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+/// Errors returned by the ConnectionLimiter
+pub enum LimitError {
+    /// The maximum of concurrently open sockets to the host is reached
+    #[error("Too many open sockets to {0}")]
+    TooManySockets(String),
+    /// Scanning the host was stopped because of abuse signals
+    #[error("Scanning {0} was stopped: {1}")]
+    Blocked(String, String),
+}
+
+/// Socket slots of a host, each permit of the semaphore is an open socket.
+struct Slots {
+    semaphore: Arc<Semaphore>,
+    /// Permits of the semaphore, follows the concurrency of the host when adaptive
+    permits: usize,
+    /// Permits that are forgotten when they are released, as the concurrency got reduced while
+    /// they were in use
+    debt: usize,
+}
+
+impl Slots {
+    fn new(permits: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            permits,
+            debt: 0,
+        }
+    }
+
+    fn open(&self) -> usize {
+        (self.permits + self.debt).saturating_sub(self.semaphore.available_permits())
+    }
+
+    /// Grows or shrinks the permits to the given concurrency.
+    fn resize(&mut self, permits: usize) {
+        if permits > self.permits {
+            let grow = permits - self.permits;
+            let paid = grow.min(self.debt);
+            self.debt -= paid;
+            self.semaphore.add_permits(grow - paid);
+        } else {
+            let shrink = self.permits - permits;
+            self.debt += shrink - self.semaphore.forget_permits(shrink);
+        }
+        self.permits = permits;
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_slot: Option<Instant>,
+    slots: HashMap<String, Slots>,
+    hosts: HashMap<String, Responsiveness>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    blocklist: HostBlocklist,
+}
+
+/// Limits the rate of new connections and the amount of concurrently open sockets per host.
+///
+/// A limit of 0 disables the corresponding restriction. When adaptive, the limit of open sockets
+/// per host is the upper bound of the concurrency of the host.
+#[derive(Default)]
+pub struct ConnectionLimiter {
+    per_second: u32,
+    per_host: usize,
+    adaptive: bool,
+    inner: Arc<Inner>,
+}
+
+/// Represents an open socket to a host, the slot is released on drop.
+pub struct ConnectionPermit {
+    slot: Option<OwnedSemaphorePermit>,
+    target: String,
+    adaptive: bool,
+    acquired: Instant,
+    inner: Arc<Inner>,
+}
+
+impl ConnectionPermit {
+    /// Records the outcome of establishing the connection for the responsiveness of the host.
+    ///
+    /// A refused connection was answered by the host, a timed out connection is an error. Other
+    /// errors are not recorded.
+    pub fn record_connect<T>(&self, result: &io::Result<T>) {
+        self.inner.blocklist.record_connect(&self.target, result);
+        if !self.adaptive {
+            return;
+        }
+        let rtt = self.acquired.elapsed();
+        self.update(|x| match result {
+            Ok(_) => x.record_answer(rtt),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => x.record_answer(rtt),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => x.record_error(),
+            Err(_) => {}
+        });
+    }
+
+    /// Adapts a socket timeout to the responsiveness of the host.
+    pub fn timeout(&self, requested: Duration) -> Duration {
+        if !self.adaptive {
+            return requested;
+        }
+        self.inner
+            .state
+            .lock()
+            .ok()
+            .and_then(|x| x.hosts.get(&self.target).map(|x| x.timeout(requested)))
+            .unwrap_or(requested)
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Responsiveness),
+    {
+        // the limiter has to be adaptive for a permit to be adaptive
+        if let Ok(mut state) = self.inner.state.lock() {
+            let Some(host) = state.hosts.get_mut(&self.target) else {
+                return;
+            };
+            f(host);
+            let concurrency = host.concurrency();
+            // a grown concurrency allows waiting connections
+            if let Some(slots) = state.slots.get_mut(&self.target) {
+                slots.resize(concurrency);
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        if let Ok(mut state) = self.inner.state.lock() {
+            if let Some(slots) = state.slots.get_mut(&self.target) {
+                if slots.debt > 0 {
+                    slots.debt -= 1;
+                    slot.forget();
+                }
+            }
+        }
+    }
+}
+
+impl ConnectionLimiter {
+    /// Creates a new ConnectionLimiter
+    pub fn new(per_second: u32, per_host: usize) -> Self {
+        Self {
+            per_second,
+            per_host,
+            adaptive: false,
+            inner: Default::default(),
+        }
+    }
+
+    /// Creates a new ConnectionLimiter adapting the concurrency and socket timeouts to the
+    /// responsiveness of each host.
+    ///
+    /// The concurrency of a host never exceeds `max_per_host`, 0 uses
+    /// [DEFAULT_MAX_CONCURRENCY].
+    pub fn adaptive(per_second: u32, max_per_host: usize) -> Self {
+        let per_host = match max_per_host {
+            0 => DEFAULT_MAX_CONCURRENCY,
+            x => x,
+        };
+        Self {
+            adaptive: true,
+            ..Self::new(per_second, per_host)
+        }
+    }
+
+    /// Refuses connections to the hosts blocked by the given blocklist.
+    pub fn with_blocklist(mut self, blocklist: HostBlocklist) -> Self {
+        self.inner = Arc::new(Inner {
+            blocklist,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Creates a new ConnectionLimiter based on the `max_connections_per_second`,
+    /// `max_sockets_per_host` and `adaptive_concurrency` scan preferences, the blocklist is based
+    /// on the abuse signal preferences.
+    ///
+    /// Missing or invalid preferences disable the corresponding limit.
+    pub fn from_preferences(preferences: &[crate::models::ScanPreference]) -> Self {
+        let find = |id: &str| preferences.iter().find(|x| x.id == id);
+        let value = |id: &str| {
+            find(id)
+                .and_then(|x| x.value.parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        let per_second = value(MAX_CONNECTIONS_PER_SECOND) as u32;
+        let per_host = value(MAX_SOCKETS_PER_HOST) as usize;
+        let adaptive =
+            crate::models::bool_preference(preferences, ADAPTIVE_CONCURRENCY).unwrap_or_default();
+        let limiter = if adaptive {
+            Self::adaptive(per_second, per_host)
+        } else {
+            Self::new(per_second, per_host)
+        };
+        limiter.with_blocklist(HostBlocklist::from_preferences(preferences))
+    }
+
+    /// Returns the reason when scanning the host was stopped.
+    pub fn blocked(&self, host: &str) -> Option<BlockReason> {
+        self.inner.blocklist.reason(host)
+    }
+
+    /// Returns the hosts scanning was stopped for and the reason.
+    pub fn blocked_hosts(&self) -> HashMap<String, BlockReason> {
+        self.inner.blocklist.blocked()
+    }
+
+    /// Records data received from the host, see [HostBlocklist::record_received].
+    pub fn record_received(&self, host: &str, data: &[u8]) {
+        self.inner.blocklist.record_received(host, data);
+    }
+
+    /// Returns the tracked responsiveness of each host, empty when not adaptive
+    pub fn responsiveness(&self) -> HashMap<String, Responsiveness> {
+        self.inner
+            .state
+            .lock()
+            .map(|x| x.hosts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of currently open sockets to the given host
+    pub fn open_sockets(&self, host: &str) -> usize {
+        self.inner
+            .state
+            .lock()
+            .map(|x| x.slots.get(host).map(Slots::open).unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Waits until a new connection to the host is allowed.
+    ///
+    /// Waits until the rate limit allows a new connection. When the maximum of open sockets to
+    /// the host is reached it waits for a released socket and returns an error when none is
+    /// released in time. Returns an error right away when scanning the host was stopped.
+    pub async fn acquire(&self, host: &str) -> Result<ConnectionPermit, LimitError> {
+        if let Some(reason) = self.blocked(host) {
+            return Err(LimitError::Blocked(host.to_owned(), reason.to_string()));
+        }
+        let slot = self.reserve_socket(host).await?;
+        self.wait_for_slot().await;
+        Ok(ConnectionPermit {
+            slot,
+            target: host.to_owned(),
+            adaptive: self.adaptive,
+            acquired: Instant::now(),
+            inner: self.inner.clone(),
+        })
+    }
+
+    async fn reserve_socket(&self, host: &str) -> Result<Option<OwnedSemaphorePermit>, LimitError> {
+        if self.per_host == 0 {
+            return Ok(None);
+        }
+        let too_many = || LimitError::TooManySockets(host.to_owned());
+        let semaphore = {
+            let mut state = self.inner.state.lock().map_err(|_| too_many())?;
+            let permits = if self.adaptive {
+                state
+                    .hosts
+                    .entry(host.to_owned())
+                    .or_insert_with(|| Responsiveness::new(self.per_host))
+                    .concurrency()
+            } else {
+                self.per_host
+            };
+            state
+                .slots
+                .entry(host.to_owned())
+                .or_insert_with(|| Slots::new(permits))
+                .semaphore
+                .clone()
+        };
+        match tokio::time::timeout(SOCKET_WAIT_TIMEOUT, semaphore.acquire_owned()).await {
+            Ok(Ok(slot)) => Ok(Some(slot)),
+            _ => Err(too_many()),
+        }
+    }
+
+    async fn wait_for_slot(&self) {
+        if self.per_second == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(1) / self.per_second;
+        let slot = match self.inner.state.lock() {
+            Ok(mut state) => {
+                let now = Instant::now();
+                let slot = state.next_slot.filter(|x| *x > now).unwrap_or(now);
+                state.next_slot = Some(slot + interval);
+                slot
+            }
+            Err(_) => return,
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn sockets_per_host() {
+        let limiter = ConnectionLimiter::new(0, 2);
+        let a = limiter.acquire("a").await.unwrap();
+        let _b = limiter.acquire("a").await.unwrap();
+        let _c = limiter.acquire("b").await.unwrap();
+        assert_eq!(limiter.open_sockets("a"), 2);
+        drop(a);
+        assert_eq!(limiter.open_sockets("a"), 1);
+        let _d = limiter.acquire("a").await.unwrap();
+        assert_eq!(limiter.open_sockets("a"), 2);
+    }
+
+    #[tokio::test]
+    async fn released_socket() {
+        let limiter = ConnectionLimiter::new(0, 1);
+        let a = limiter.acquire("a").await.unwrap();
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(a);
+        };
+        let (_, b) = tokio::join!(release, limiter.acquire("a"));
+        assert!(b.is_ok());
+        assert_eq!(limiter.open_sockets("a"), 1);
+    }
+
+    #[tokio::test]
+    async fn adaptive() {
+        use std::io;
+
+        let timed_out: io::Result<()> = Err(io::ErrorKind::TimedOut.into());
+        let limiter = ConnectionLimiter::from_preferences(&[crate::models::ScanPreference {
+            id: ADAPTIVE_CONCURRENCY.to_string(),
+            value: "true".to_string(),
+        }]);
+        let requested = Duration::from_secs(10);
+        let a = limiter.acquire("a").await.unwrap();
+        assert_eq!(a.timeout(requested), requested);
+        a.record_connect(&Ok(()));
+        // a fast host gets shorter timeouts
+        assert!(a.timeout(requested) < requested);
+        a.record_connect(&timed_out);
+        a.record_connect(&timed_out);
+        drop(a);
+        let hosts = limiter.responsiveness();
+        assert_eq!(hosts["a"].concurrency(), 1);
+        assert_eq!(hosts["a"].samples(), 3);
+
+        let _b = limiter.acquire("b").await.unwrap();
+        let _c = limiter.acquire("b").await.unwrap();
+        assert_eq!(limiter.open_sockets("b"), 2);
+        assert!(!limiter.responsiveness().contains_key("c"));
+    }
+
+    #[tokio::test]
+    async fn blocked() {
+        use super::super::blocklist::{ABUSE_SIGNAL_RATE, ABUSE_SIGNAL_WINDOW};
+        use std::io;
+
+        let preference = |id: &str, value: &str| crate::models::ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        };
+        let refused: io::Result<()> = Err(io::ErrorKind::ConnectionRefused.into());
+        let limiter = ConnectionLimiter::from_preferences(&[
+            preference(ABUSE_SIGNAL_RATE, "0.5"),
+            preference(ABUSE_SIGNAL_WINDOW, "2"),
+        ]);
+        let a = limiter.acquire("a").await.unwrap();
+        a.record_connect(&refused);
+        a.record_connect(&refused);
+        assert!(limiter.blocked("a").is_some());
+        assert!(matches!(
+            limiter.acquire("a").await,
+            Err(LimitError::Blocked(host, _)) if host == "a"
+        ));
+        assert!(limiter.acquire("b").await.is_ok());
+        assert_eq!(limiter.blocked_hosts().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn not_adaptive() {
+        let limiter = ConnectionLimiter::new(0, 0);
+        let a = limiter.acquire("a").await.unwrap();
+        a.record_connect(&Ok(()));
+        assert_eq!(a.timeout(Duration::from_secs(10)), Duration::from_secs(10));
+        assert!(limiter.responsiveness().is_empty());
+    }
+
+    #[tokio::test]
+    async fn connections_per_second() {
+        let limiter = ConnectionLimiter::new(20, 0);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire("a").await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(limiter.open_sockets("a"), 0);
+    }
+}
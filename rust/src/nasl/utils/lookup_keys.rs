@@ -15,3 +15,8 @@ pub const FC_ANON_ARGS: &str = "_FCT_ANON_ARGS";
 /// In the current state of the rust implementation (2023-01-23) we don't have the interface to store data between different script runs.
 // Therefore it is currently stored within the register with this key.
 pub const TARGET: &str = "_OPENVAS_TARGET";
+/// _OPENVAS_VHOST is set to the virtual host an interpreter instance runs for
+///
+/// It is set when `get_host_name` forks over the virtual hosts of the target and used for the
+/// Host header of http requests and as host name of the results.
+pub const VHOST: &str = "_OPENVAS_VHOST";
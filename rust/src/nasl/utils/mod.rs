@@ -3,18 +3,45 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+pub mod blocklist;
+pub mod capture;
+pub mod cassette;
 pub mod context;
+pub mod dns;
 pub mod error;
 mod executor;
+pub mod extensions;
 pub mod function;
+pub mod kb_cache;
+pub mod limiter;
 pub mod lookup_keys;
+pub mod policy;
+pub mod responsiveness;
+pub mod targets;
+pub mod traffic;
+pub mod zone;
 
 use std::collections::HashMap;
 
-pub use context::{Context, ContextType, Register};
+pub use blocklist::{BlockReason, HostBlocklist};
+pub use capture::{PacketRecorder, Recording};
+pub use cassette::Cassette;
+pub use context::{Context, ContextType, NetworkSource, RegexMode, Register, RegisterBase};
+pub use dns::{DnsCache, DnsCacheStats, HostNames};
 pub use error::FunctionErrorKind;
+pub use extensions::Extensions;
+pub use kb_cache::KbCache;
+pub use limiter::{ConnectionLimiter, ConnectionPermit, LimitError};
+pub use policy::{BuiltinPolicy, Risk};
+pub use responsiveness::Responsiveness;
+pub use targets::{AddedHost, TargetError, TargetQueue};
+pub use traffic::{TrafficCounter, TrafficError};
+pub use zone::ScopedAddr;
 
-pub use executor::{Executor, IntoFunctionSet, StoredFunctionSet};
+pub use executor::{
+    DynamicError, DynamicLoader, Executor, IntoFunctionSet, NaslPluginCall, NaslPluginDescriptor,
+    NaslPluginFunction, Plugin, PluginConfig, StoredFunctionSet, ABI_VERSION, REGISTER_SYMBOL,
+};
 
 /// The result of a function call.
 pub type NaslResult = Result<crate::nasl::syntax::NaslValue, FunctionErrorKind>;
@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Restricts the builtin functions a script may call.
+//!
+//! An entry names either a single function, e.g. `send_packet`, or a module of builtin functions,
+//! e.g. `raw_ip`, `ssh` or `network`. The module of a function is the module of
+//! [crate::nasl::builtin] defining it. A call of a disabled function fails with
+//! [FunctionErrorKind::Disabled](super::FunctionErrorKind::Disabled) instead of being executed
+//! and `defined_func` returns false for it.
+//!
+//! Independent of the lists each builtin function is annotated with a [Risk] when it is
+//! registered. While `safe_checks` is enabled intrusive and destructive functions are refused:
+//! they are not executed, return NULL and the refusal is logged as a log result of the script.
+
+use std::{collections::HashSet, fmt::Display};
+
+use crate::models::{parse_bool, ScanPreference};
+
+/// Preference id of the comma separated functions and modules a script may call, all others are
+/// disabled.
+pub const BUILTINS_ALLOWED: &str = "builtins_allowed";
+
+/// Preference id of the comma separated functions and modules that are disabled.
+pub const BUILTINS_DISABLED: &str = "builtins_disabled";
+
+/// Preference id of the safe checks, intrusive and destructive functions are refused when it is
+/// enabled. It is enabled when not set.
+pub const SAFE_CHECKS: &str = "safe_checks";
+
+/// How a builtin function may affect the target
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Risk {
+    /// Only observes the target
+    #[default]
+    Safe,
+    /// May change the state of the target, e.g. lock an account
+    Intrusive,
+    /// May damage the target or its data
+    Destructive,
+}
+
+impl Display for Risk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let x = match self {
+            Self::Safe => "safe",
+            Self::Intrusive => "intrusive",
+            Self::Destructive => "destructive",
+        };
+        write!(f, "{x}")
+    }
+}
+
+/// Allow and deny list of builtin functions
+///
+/// The deny list takes precedence over the allow list. Without an allow list every function that
+/// is not denied is allowed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BuiltinPolicy {
+    allowed: Option<HashSet<String>>,
+    disabled: HashSet<String>,
+    safe_checks: bool,
+}
+
+fn entries<'a>(list: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+    list.into_iter()
+        .flat_map(|x| x.split(','))
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+impl BuiltinPolicy {
+    /// Creates a policy of the given allow and deny lists.
+    ///
+    /// Each entry may contain multiple comma separated names.
+    pub fn new<'a>(
+        allowed: Option<impl IntoIterator<Item = &'a str>>,
+        disabled: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        Self {
+            allowed: allowed.map(entries),
+            disabled: entries(disabled),
+            safe_checks: false,
+        }
+    }
+
+    /// Refuses intrusive and destructive functions when safe checks are enabled.
+    pub fn with_safe_checks(mut self, enabled: bool) -> Self {
+        self.safe_checks = enabled;
+        self
+    }
+
+    /// Returns the policy of the `builtins_allowed`, `builtins_disabled` and `safe_checks` scan
+    /// preferences.
+    ///
+    /// An empty `builtins_allowed` is treated as not set.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let list = |id: &'static str| {
+            preferences
+                .iter()
+                .filter(move |x| x.id == id)
+                .map(|x| x.value.as_str())
+        };
+        let allowed = entries(list(BUILTINS_ALLOWED));
+        Self {
+            allowed: (!allowed.is_empty()).then_some(allowed),
+            disabled: entries(list(BUILTINS_DISABLED)),
+            safe_checks: list(SAFE_CHECKS)
+                .next_back()
+                .and_then(parse_bool)
+                .unwrap_or(true),
+        }
+    }
+
+    /// Returns true when neither an allow nor a deny list is set.
+    pub fn allows_all(&self) -> bool {
+        self.allowed.is_none() && self.disabled.is_empty()
+    }
+
+    /// Returns true when a function of the given risk must not be executed.
+    pub fn refuses(&self, risk: Risk) -> bool {
+        self.safe_checks && risk != Risk::Safe
+    }
+
+    /// Returns true when the function of the given module must not be called.
+    pub fn is_disabled(&self, function: &str, module: Option<&str>) -> bool {
+        let listed = |x: &HashSet<String>| {
+            x.contains(function) || module.is_some_and(|module| x.contains(module))
+        };
+        listed(&self.disabled) || self.allowed.as_ref().is_some_and(|x| !listed(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preference(id: &str, value: &str) -> ScanPreference {
+        ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn deny_list() {
+        let policy = BuiltinPolicy::from_preferences(&[preference(
+            BUILTINS_DISABLED,
+            "raw_ip, ssh_connect",
+        )]);
+        assert!(policy.is_disabled("send_packet", Some("raw_ip")));
+        assert!(policy.is_disabled("ssh_connect", Some("ssh")));
+        assert!(!policy.is_disabled("ssh_login", Some("ssh")));
+        assert!(!policy.is_disabled("user_defined", None));
+    }
+
+    #[test]
+    fn allow_list() {
+        let policy = BuiltinPolicy::new(Some(["string", "display"]), ["strlen"]);
+        assert!(!policy.is_disabled("display", Some("misc")));
+        assert!(!policy.is_disabled("substr", Some("string")));
+        assert!(policy.is_disabled("strlen", Some("string")));
+        assert!(policy.is_disabled("open_sock_tcp", Some("network")));
+    }
+
+    #[test]
+    fn safe_checks() {
+        let policy = BuiltinPolicy::from_preferences(&[]);
+        assert!(policy.refuses(Risk::Intrusive));
+        assert!(policy.refuses(Risk::Destructive));
+        assert!(!policy.refuses(Risk::Safe));
+        let policy = BuiltinPolicy::from_preferences(&[preference(SAFE_CHECKS, "no")]);
+        assert!(!policy.refuses(Risk::Destructive));
+        assert!(!BuiltinPolicy::default().refuses(Risk::Destructive));
+    }
+
+    #[test]
+    fn empty() {
+        let policy = BuiltinPolicy::from_preferences(&[preference(BUILTINS_ALLOWED, "")]);
+        assert!(policy.allows_all());
+        assert!(!policy.is_disabled("send_packet", Some("raw_ip")));
+    }
+}
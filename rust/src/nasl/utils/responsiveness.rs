@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Tracks how responsive a host is to adapt the concurrency and the socket timeouts of a scan.
+//!
+//! Each connection attempt to a host is recorded either with its round trip time, the time until
+//! the host answered, or as error when it timed out. The smoothed round trip time and its
+//! variation are estimated like the retransmission timeout of TCP (RFC 6298), the error rate is an
+//! exponentially weighted moving average.
+//!
+//! The concurrency, the amount of sockets that may be open to the host at the same time, grows by
+//! one after as many answered connections as the current concurrency and is halved on each error.
+//! Socket timeouts follow the round trip time: they are shortened for fast hosts and extended for
+//! slow ones, for a host with a high error rate they are never shortened.
+
+use std::time::Duration;
+
+/// Preference id enabling the adaptive concurrency and socket timeouts.
+pub const ADAPTIVE_CONCURRENCY: &str = "adaptive_concurrency";
+
+/// Upper bound of the concurrency when `max_sockets_per_host` is not set.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 16;
+
+/// Concurrency of a host without any recorded connection
+const INITIAL_CONCURRENCY: usize = 4;
+
+/// Error rate above which the socket timeouts of a host are not shortened anymore
+pub const HIGH_ERROR_RATE: f64 = 0.2;
+
+/// Shortest adapted socket timeout
+pub const MIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An adapted socket timeout is at most the requested timeout times this factor
+const MAX_TIMEOUT_SCALE: u32 = 4;
+
+/// Adapted socket timeouts are the retransmission timeout times this factor
+const TIMEOUT_FACTOR: f64 = 4.0;
+
+/// Gain of the smoothed round trip time, see RFC 6298
+const ALPHA: f64 = 0.125;
+/// Gain of the round trip time variation, see RFC 6298
+const BETA: f64 = 0.25;
+/// Gain of the error rate
+const ERROR_GAIN: f64 = 0.1;
+
+/// Responsiveness of a single host
+#[derive(Debug, Clone, PartialEq)]
+pub struct Responsiveness {
+    srtt: Option<f64>,
+    rttvar: f64,
+    error_rate: f64,
+    concurrency: usize,
+    max_concurrency: usize,
+    answered: usize,
+    samples: usize,
+}
+
+impl Responsiveness {
+    /// Creates the responsiveness of a host whose concurrency never exceeds the given maximum.
+    pub fn new(max_concurrency: usize) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+        Self {
+            srtt: None,
+            rttvar: 0.0,
+            error_rate: 0.0,
+            concurrency: INITIAL_CONCURRENCY.min(max_concurrency),
+            max_concurrency,
+            answered: 0,
+            samples: 0,
+        }
+    }
+
+    /// Records a connection the host answered after the given round trip time.
+    pub fn record_answer(&mut self, rtt: Duration) {
+        let rtt = rtt.as_secs_f64();
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar = (1.0 - BETA) * self.rttvar + BETA * (srtt - rtt).abs();
+                self.srtt = Some((1.0 - ALPHA) * srtt + ALPHA * rtt);
+            }
+        }
+        self.error_rate *= 1.0 - ERROR_GAIN;
+        self.samples += 1;
+        self.answered += 1;
+        if self.answered >= self.concurrency && self.concurrency < self.max_concurrency {
+            self.concurrency += 1;
+            self.answered = 0;
+        }
+    }
+
+    /// Records a connection the host did not answer in time.
+    pub fn record_error(&mut self) {
+        self.error_rate = (1.0 - ERROR_GAIN) * self.error_rate + ERROR_GAIN;
+        self.samples += 1;
+        self.answered = 0;
+        self.concurrency = (self.concurrency / 2).max(1);
+    }
+
+    /// Returns the amount of sockets that may be open to the host at the same time.
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Returns the smoothed round trip time, none before the host answered the first time.
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt.map(Duration::from_secs_f64)
+    }
+
+    /// Returns the share of recent connections the host did not answer in time.
+    pub fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// Returns the amount of recorded connections.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Adapts the requested socket timeout to the round trip time of the host.
+    pub fn timeout(&self, requested: Duration) -> Duration {
+        let Some(srtt) = self.srtt else {
+            return requested;
+        };
+        let lower = if self.error_rate > HIGH_ERROR_RATE {
+            requested
+        } else {
+            MIN_TIMEOUT.min(requested)
+        };
+        let upper = requested * MAX_TIMEOUT_SCALE;
+        let rto = srtt + 4.0 * self.rttvar;
+        Duration::from_secs_f64(rto * TIMEOUT_FACTOR).clamp(lower, upper)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Responsiveness, MIN_TIMEOUT};
+
+    #[test]
+    fn concurrency() {
+        let mut host = Responsiveness::new(6);
+        assert_eq!(host.concurrency(), 4);
+        for _ in 0..4 {
+            host.record_answer(Duration::from_millis(5));
+        }
+        assert_eq!(host.concurrency(), 5);
+        for _ in 0..20 {
+            host.record_answer(Duration::from_millis(5));
+        }
+        assert_eq!(host.concurrency(), 6);
+        host.record_error();
+        assert_eq!(host.concurrency(), 3);
+        host.record_error();
+        host.record_error();
+        assert_eq!(host.concurrency(), 1);
+        assert_eq!(host.samples(), 27);
+    }
+
+    #[test]
+    fn timeout() {
+        let requested = Duration::from_secs(10);
+        let mut fast = Responsiveness::new(4);
+        assert_eq!(fast.timeout(requested), requested);
+        fast.record_answer(Duration::from_millis(2));
+        assert_eq!(fast.timeout(requested), MIN_TIMEOUT);
+
+        let mut slow = Responsiveness::new(4);
+        slow.record_answer(Duration::from_secs(5));
+        slow.record_answer(Duration::from_secs(8));
+        assert_eq!(slow.timeout(requested), requested * 4);
+
+        let mut lossy = Responsiveness::new(4);
+        lossy.record_answer(Duration::from_millis(2));
+        for _ in 0..3 {
+            lossy.record_error();
+        }
+        assert!(lossy.error_rate() > super::HIGH_ERROR_RATE);
+        assert_eq!(lossy.timeout(requested), requested);
+    }
+}
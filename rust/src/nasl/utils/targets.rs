@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the queue of hosts that are added to a running scan, e.g. by discovery scripts.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+};
+
+use super::zone;
+use crate::models::{Host, Scan, ScanPreference};
+
+/// Preference id of the maximum of hosts that may be added to a running scan.
+pub const MAX_ADDITIONAL_HOSTS: &str = "max_additional_hosts";
+/// Preference id of the comma separated networks, addresses and host names added hosts must be
+/// within.
+pub const ADDITIONAL_HOSTS_SCOPE: &str = "additional_hosts_scope";
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+/// Reasons why a host cannot be added to a scan
+pub enum TargetError {
+    /// The scan does not allow additional hosts
+    #[error("Adding hosts is disabled, set {MAX_ADDITIONAL_HOSTS} to enable it")]
+    Disabled,
+    /// The maximum of additional hosts is reached
+    #[error("The maximum of {0} additional hosts is reached")]
+    LimitReached(usize),
+    /// The host is excluded from the scan
+    #[error("{0} is excluded")]
+    Excluded(String),
+    /// The host is not within the scope
+    #[error("{0} is not within {ADDITIONAL_HOSTS_SCOPE}")]
+    OutOfScope(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scope {
+    Network(IpAddr, u8),
+    Domain(String),
+    Name(String),
+}
+
+impl Scope {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        if let Some((addr, prefix)) = value.split_once('/') {
+            let addr: IpAddr = addr.parse().ok()?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            let prefix = prefix.parse().ok().filter(|x| *x <= max)?;
+            return Some(Self::Network(addr, prefix));
+        }
+        if let Ok(addr) = value.parse::<IpAddr>() {
+            let prefix = if addr.is_ipv4() { 32 } else { 128 };
+            return Some(Self::Network(addr, prefix));
+        }
+        match value.strip_prefix('.') {
+            Some(domain) => Some(Self::Domain(domain.to_lowercase())),
+            None => Some(Self::Name(value.to_lowercase())),
+        }
+    }
+
+    fn contains(&self, host: &str) -> bool {
+        match (self, zone::strip(host).parse::<IpAddr>()) {
+            (Self::Network(IpAddr::V4(net), prefix), Ok(IpAddr::V4(addr))) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - *prefix as u32)
+                    .unwrap_or_default();
+                u32::from(*net) & mask == u32::from(addr) & mask
+            }
+            (Self::Network(IpAddr::V6(net), prefix), Ok(IpAddr::V6(addr))) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - *prefix as u32)
+                    .unwrap_or_default();
+                u128::from(*net) & mask == u128::from(addr) & mask
+            }
+            (Self::Domain(domain), Err(_)) => {
+                let host = host.to_lowercase();
+                host.strip_suffix(domain.as_str())
+                    .is_some_and(|x| x.ends_with('.'))
+            }
+            (Self::Name(name), Err(_)) => name.eq_ignore_ascii_case(host),
+            _ => false,
+        }
+    }
+}
+
+/// A host that was added to a running scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddedHost {
+    /// The added host
+    pub host: Host,
+    /// The host whose script added it
+    pub origin: Host,
+}
+
+#[derive(Default)]
+struct State {
+    known: HashSet<Host>,
+    pending: VecDeque<AddedHost>,
+    added: usize,
+}
+
+/// Collects the hosts that are added while a scan is running.
+///
+/// Hosts are only accepted when they are not part of the scan yet, not excluded, within the
+/// scope and as long as the maximum of additional hosts is not reached. A maximum of 0 disables
+/// adding hosts.
+#[derive(Default)]
+pub struct TargetQueue {
+    max: usize,
+    scope: Vec<Scope>,
+    excluded: HashSet<Host>,
+    state: Mutex<State>,
+}
+
+impl TargetQueue {
+    /// Creates a new TargetQueue for a scan of the given hosts
+    pub fn new(hosts: &[Host], excluded: &[Host], max: usize) -> Self {
+        Self {
+            max,
+            scope: vec![],
+            excluded: excluded.iter().cloned().collect(),
+            state: Mutex::new(State {
+                known: hosts.iter().cloned().collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Restricts added hosts to the given networks (`10.0.0.0/24`), addresses, host names and
+    /// domains (`.example.com`).
+    ///
+    /// An empty scope allows every host.
+    pub fn with_scope<S: AsRef<str>>(mut self, scope: &[S]) -> Self {
+        self.scope = scope
+            .iter()
+            .filter_map(|x| Scope::parse(x.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Creates a new TargetQueue based on the target and the `max_additional_hosts` and
+    /// `additional_hosts_scope` scan preferences.
+    pub fn from_scan(scan: &Scan) -> Self {
+        let value = |id: &str| {
+            scan.scan_preferences
+                .iter()
+                .find(|x: &&ScanPreference| x.id == id)
+                .map(|x| x.value.as_str())
+        };
+        let max = value(MAX_ADDITIONAL_HOSTS)
+            .and_then(|x| x.trim().parse().ok())
+            .unwrap_or_default();
+        let scope: Vec<&str> = value(ADDITIONAL_HOSTS_SCOPE)
+            .map(|x| x.split(',').collect())
+            .unwrap_or_default();
+        let hosts: Vec<_> = scan.target.all_hosts().cloned().collect();
+        Self::new(&hosts, &scan.target.excluded_hosts, max).with_scope(&scope)
+    }
+
+    /// Enqueues a host found by a script running against origin.
+    ///
+    /// Returns false when the host is already part of the scan.
+    pub fn add(&self, host: &str, origin: &str) -> Result<bool, TargetError> {
+        let host = host.trim();
+        if self.max == 0 {
+            return Err(TargetError::Disabled);
+        }
+        if self.excluded.contains(host) {
+            return Err(TargetError::Excluded(host.to_string()));
+        }
+        if !self.scope.is_empty() && !self.scope.iter().any(|x| x.contains(host)) {
+            return Err(TargetError::OutOfScope(host.to_string()));
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.known.contains(host) {
+            return Ok(false);
+        }
+        if state.added >= self.max {
+            return Err(TargetError::LimitReached(self.max));
+        }
+        state.added += 1;
+        state.known.insert(host.to_string());
+        state.pending.push_back(AddedHost {
+            host: host.to_string(),
+            origin: origin.to_string(),
+        });
+        Ok(true)
+    }
+
+    /// Takes the next added host that is not scanned yet.
+    pub fn next(&self) -> Option<AddedHost> {
+        self.state.lock().unwrap().pending.pop_front()
+    }
+
+    /// Returns the amount of hosts that were added.
+    pub fn added(&self) -> usize {
+        self.state.lock().unwrap().added
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope() {
+        let queue = TargetQueue::new(&["10.0.0.1".into()], &["10.0.0.9".into()], 2).with_scope(&[
+            "10.0.0.0/24",
+            "2001:db8::/32",
+            ".example.com",
+            "other.test",
+        ]);
+        assert_eq!(queue.add("10.0.0.1", "10.0.0.1"), Ok(false));
+        assert_eq!(
+            queue.add("10.0.1.1", "10.0.0.1"),
+            Err(TargetError::OutOfScope("10.0.1.1".into()))
+        );
+        assert_eq!(
+            queue.add("10.0.0.9", "10.0.0.1"),
+            Err(TargetError::Excluded("10.0.0.9".into()))
+        );
+        assert_eq!(
+            queue.add("example.com.evil", "10.0.0.1"),
+            Err(TargetError::OutOfScope("example.com.evil".into()))
+        );
+        assert_eq!(queue.add("www.Example.com", "10.0.0.1"), Ok(true));
+        assert_eq!(queue.add("2001:db8::1", "10.0.0.1"), Ok(true));
+        assert_eq!(
+            queue.add("other.test", "10.0.0.1"),
+            Err(TargetError::LimitReached(2))
+        );
+        assert_eq!(queue.added(), 2);
+        assert_eq!(queue.next().map(|x| x.host), Some("www.Example.com".into()));
+        assert_eq!(queue.next().map(|x| x.origin), Some("10.0.0.1".into()));
+        assert_eq!(queue.next(), None);
+        assert_eq!(
+            TargetQueue::default().add("10.0.0.2", "10.0.0.1"),
+            Err(TargetError::Disabled)
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Accounts the bytes a script sends to and receives from a host.
+//!
+//! Each run of a script on a host gets its own counter. The socket and http builtin functions add
+//! the transferred bytes, an optional cap stops a script e.g. accidentally downloading a huge file
+//! once the sum of sent and received bytes exceeds it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::models::{ScanPreference, Traffic};
+
+/// Preference id of the maximum of bytes a script may send and receive per host.
+pub const MAX_SCRIPT_BYTES: &str = "max_script_bytes";
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+/// Errors returned by the TrafficCounter
+pub enum TrafficError {
+    /// The script transferred more bytes than allowed
+    #[error("Script exceeded the cap of {0} bytes")]
+    CapExceeded(u64),
+}
+
+/// Counts the bytes sent and received by a script run.
+#[derive(Debug, Default)]
+pub struct TrafficCounter {
+    sent: AtomicU64,
+    received: AtomicU64,
+    cap: Option<u64>,
+}
+
+impl TrafficCounter {
+    /// Creates a counter, a cap of none or 0 disables the cap.
+    pub fn new(cap: Option<u64>) -> Self {
+        Self {
+            cap: cap.filter(|x| *x > 0),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the cap of the `max_script_bytes` scan preference, 0 or an invalid value is
+    /// treated as not set.
+    pub fn cap_from_preferences(preferences: &[ScanPreference]) -> Option<u64> {
+        preferences
+            .iter()
+            .find(|x| x.id == MAX_SCRIPT_BYTES)
+            .and_then(|x| x.value.trim().parse().ok())
+            .filter(|x| *x > 0)
+    }
+
+    /// Adds sent bytes, returns an error when the cap is exceeded afterwards.
+    pub fn sent(&self, bytes: usize) -> Result<(), TrafficError> {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.check()
+    }
+
+    /// Adds received bytes, returns an error when the cap is exceeded afterwards.
+    pub fn received(&self, bytes: usize) -> Result<(), TrafficError> {
+        self.received.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.check()
+    }
+
+    /// Returns the bytes transferred so far.
+    pub fn totals(&self) -> Traffic {
+        Traffic {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the cap when it is exceeded.
+    pub fn exceeded(&self) -> Option<u64> {
+        self.cap.filter(|x| self.totals().total() > *x)
+    }
+
+    fn check(&self) -> Result<(), TrafficError> {
+        match self.exceeded() {
+            Some(cap) => Err(TrafficError::CapExceeded(cap)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrafficCounter, TrafficError, MAX_SCRIPT_BYTES};
+    use crate::models::{ScanPreference, Traffic};
+
+    #[test]
+    fn count() {
+        let counter = TrafficCounter::new(None);
+        counter.sent(10).unwrap();
+        counter.received(32).unwrap();
+        counter.received(8).unwrap();
+        assert_eq!(
+            counter.totals(),
+            Traffic {
+                sent: 10,
+                received: 40
+            }
+        );
+        assert_eq!(counter.exceeded(), None);
+    }
+
+    #[test]
+    fn cap() {
+        let cap = TrafficCounter::cap_from_preferences(&[ScanPreference {
+            id: MAX_SCRIPT_BYTES.to_string(),
+            value: "100".to_string(),
+        }]);
+        assert_eq!(cap, Some(100));
+        let counter = TrafficCounter::new(cap);
+        counter.sent(50).unwrap();
+        counter.received(50).unwrap();
+        assert_eq!(counter.received(1), Err(TrafficError::CapExceeded(100)));
+        assert_eq!(counter.exceeded(), Some(100));
+        assert_eq!(counter.totals().total(), 101);
+
+        assert_eq!(TrafficCounter::cap_from_preferences(&[]), None);
+        assert_eq!(TrafficCounter::new(Some(0)).exceeded(), None);
+    }
+}
@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Handles IPv6 targets with a zone id, e.g. `fe80::1%eth0` or `[fe80::1%2]`.
+//!
+//! A link-local address is only unique on a single link, the zone names the interface, either by
+//! name or by index, the address is reached through. The zone is kept in the target so that it is
+//! part of the knowledge base key and of the results, the socket functions resolve it to the
+//! scope id of the destination address.
+
+use std::{
+    ffi::CString,
+    net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6},
+};
+
+/// An IP address and the scope id of its zone, 0 when there is none
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopedAddr {
+    /// The address without the zone
+    pub addr: IpAddr,
+    /// The index of the interface named by the zone
+    pub scope_id: u32,
+}
+
+impl ScopedAddr {
+    /// Parses an IP address with an optional zone.
+    ///
+    /// Returns None when the target is no IP address, when a zone is given for an IPv4 address
+    /// or when the zone names an unknown interface.
+    pub fn parse(target: &str) -> Option<Self> {
+        let (addr, zone) = split(target);
+        let addr: IpAddr = addr.parse().ok()?;
+        let scope_id = match (addr, zone) {
+            (_, None) => 0,
+            (IpAddr::V6(_), Some(zone)) => scope_id(zone)?,
+            (IpAddr::V4(_), Some(_)) => return None,
+        };
+        Some(Self { addr, scope_id })
+    }
+
+    /// Returns the socket address of the given port.
+    pub fn socket_addr(&self, port: u16) -> SocketAddr {
+        socket_addr(self.addr, port, self.scope_id)
+    }
+}
+
+/// Splits a target into the address and the zone, surrounding brackets are removed.
+///
+/// Only targets whose part before the `%` is an IPv6 address are split.
+pub fn split(target: &str) -> (&str, Option<&str>) {
+    let target = target
+        .strip_prefix('[')
+        .and_then(|x| x.strip_suffix(']'))
+        .unwrap_or(target);
+    match target.split_once('%') {
+        Some((addr, zone)) if !zone.is_empty() && addr.parse::<Ipv6Addr>().is_ok() => {
+            (addr, Some(zone))
+        }
+        _ => (target, None),
+    }
+}
+
+/// Returns the target without its zone.
+pub fn strip(target: &str) -> &str {
+    split(target).0
+}
+
+/// Returns the scope id of a zone, either its index or the index of the interface it names.
+pub fn scope_id(zone: &str) -> Option<u32> {
+    if let Ok(index) = zone.parse() {
+        return Some(index);
+    }
+    let name = CString::new(zone).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
+/// Returns true for addresses that require a scope id, e.g. `fe80::/10`.
+pub fn is_link_local(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(_) => false,
+        IpAddr::V6(addr) => {
+            addr.segments()[0] & 0xffc0 == 0xfe80
+                || (addr.is_multicast() && addr.segments()[0] & 0x000f == 0x2)
+        }
+    }
+}
+
+/// Returns the socket address of an address and port, the scope id is only set for link-local
+/// addresses.
+pub fn socket_addr(addr: IpAddr, port: u16, scope_id: u32) -> SocketAddr {
+    match addr {
+        IpAddr::V6(v6) if is_link_local(&addr) => {
+            SocketAddr::V6(SocketAddrV6::new(v6, port, 0, scope_id))
+        }
+        _ => SocketAddr::new(addr, port),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use super::{is_link_local, socket_addr, split, strip, ScopedAddr};
+
+    #[test]
+    fn parse() {
+        assert_eq!(split("fe80::1%eth0"), ("fe80::1", Some("eth0")));
+        assert_eq!(split("[fe80::1%2]"), ("fe80::1", Some("2")));
+        assert_eq!(split("fe80::1%"), ("fe80::1%", None));
+        assert_eq!(split("example.com"), ("example.com", None));
+        assert_eq!(split("50%off"), ("50%off", None));
+        assert_eq!(strip("fe80::1%lo"), "fe80::1");
+
+        let addr = ScopedAddr::parse("fe80::1%3").unwrap();
+        assert_eq!(addr.addr, "fe80::1".parse::<IpAddr>().unwrap());
+        assert_eq!(addr.scope_id, 3);
+        assert!(ScopedAddr::parse("fe80::1%lo").unwrap().scope_id > 0);
+        assert_eq!(ScopedAddr::parse("10.0.0.1").unwrap().scope_id, 0);
+        assert_eq!(ScopedAddr::parse("fe80::1%no-such-interface"), None);
+        assert_eq!(ScopedAddr::parse("example.com"), None);
+    }
+
+    #[test]
+    fn scope() {
+        assert!(is_link_local(&"fe80::1".parse().unwrap()));
+        assert!(is_link_local(&"ff02::1".parse().unwrap()));
+        assert!(!is_link_local(&"2001:db8::1".parse().unwrap()));
+        assert!(!is_link_local(&"10.0.0.1".parse().unwrap()));
+
+        let addr = ScopedAddr::parse("fe80::1%3").unwrap().socket_addr(22);
+        assert_eq!(addr.to_string(), "[fe80::1%3]:22");
+        let SocketAddr::V6(addr) = socket_addr("2001:db8::1".parse().unwrap(), 22, 3) else {
+            unreachable!()
+        };
+        assert_eq!(addr.scope_id(), 0);
+    }
+}
@@ -0,0 +1,294 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Gathers the input of a notus scan from the output of commands run on a target.
+//!
+//! This is the native counterpart of the `gather-package-list.nasl` script: the distribution is
+//! detected via `/etc/os-release` and the installed packages are listed by the package manager
+//! of the distribution. The result is a product name, e.g. `debian_12`, and a list of full
+//! package names as expected by [super::Notus::scan].
+//!
+//! Running the commands is up to the caller, e.g. the ssh builtin functions, so that this module
+//! does not depend on a specific connection.
+
+use std::sync::Mutex;
+
+use crate::models::{FixedVersion, NotusResults, Specifier, VulnerablePackage};
+
+use super::{error::Error, loader::ProductLoader, Notus};
+
+/// Command to read the release information of the target
+pub const OS_RELEASE_COMMAND: &str = "cat /etc/os-release";
+
+/// KB key of the detected product
+pub const KB_RELEASE: &str = "ssh/login/release_notus";
+/// KB key of the installed packages, separated by new lines
+pub const KB_PACKAGE_LIST: &str = "ssh/login/package_list_notus";
+/// KB key of the package manager used to list the packages
+pub const KB_PACKAGE_MANAGER: &str = "ssh/login/package_manager_notus";
+
+/// Release information of a target as found in `/etc/os-release`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsRelease {
+    /// The lower case identifier of the distribution, e.g. `debian`
+    pub id: String,
+    /// Identifiers of related distributions, e.g. `rhel fedora` for rocky
+    pub id_like: Vec<String>,
+    /// The version of the distribution, e.g. `12` or `22.04`
+    pub version_id: String,
+}
+
+impl OsRelease {
+    /// Parses the content of an os-release file.
+    ///
+    /// Returns None when the file does not contain an ID.
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut result = Self::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            match key {
+                "ID" => result.id = value.to_lowercase(),
+                "ID_LIKE" => {
+                    result.id_like = value.split_whitespace().map(|x| x.to_lowercase()).collect()
+                }
+                "VERSION_ID" => result.version_id = value.to_string(),
+                _ => {}
+            }
+        }
+        (!result.id.is_empty()).then_some(result)
+    }
+
+    fn is_like(&self, id: &str) -> bool {
+        self.id == id || self.id_like.iter().any(|x| x == id)
+    }
+
+    /// Returns the name of the notus product of the release, e.g. `debian_12`.
+    ///
+    /// Distributions based on rhel only use their major version, as minor releases share the
+    /// same product.
+    pub fn product(&self) -> String {
+        let major_only = self.id != "fedora" && (self.is_like("rhel") || self.is_like("fedora"));
+        let version = if major_only {
+            self.version_id
+                .split('.')
+                .next()
+                .unwrap_or(&self.version_id)
+        } else {
+            &self.version_id
+        };
+        if version.is_empty() {
+            self.id.clone()
+        } else {
+            format!("{}_{}", self.id, version)
+        }
+    }
+
+    /// Returns the package manager of the release
+    pub fn package_manager(&self) -> Option<PackageManager> {
+        if self.is_like("debian") || self.is_like("ubuntu") {
+            Some(PackageManager::Dpkg)
+        } else if self.is_like("rhel")
+            || self.is_like("fedora")
+            || self.is_like("suse")
+            || self.is_like("opensuse")
+            || self.is_like("sles")
+        {
+            Some(PackageManager::Rpm)
+        } else if self.is_like("alpine") {
+            Some(PackageManager::Apk)
+        } else {
+            None
+        }
+    }
+}
+
+/// Package managers whose installed packages can be gathered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    /// Debian based distributions
+    Dpkg,
+    /// Red Hat and SUSE based distributions
+    Rpm,
+    /// Alpine Linux
+    Apk,
+}
+
+impl PackageManager {
+    /// Returns the name of the package manager
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dpkg => "dpkg",
+            Self::Rpm => "rpm",
+            Self::Apk => "apk",
+        }
+    }
+
+    /// Returns the command listing the installed packages
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::Dpkg => r"dpkg-query -W -f='${Status} ${Package}-${Version}\n'",
+            Self::Rpm => r"rpm -qa --qf '%{NAME}-%{VERSION}-%{RELEASE}.%{ARCH}\n'",
+            Self::Apk => "apk info -v",
+        }
+    }
+
+    /// Parses the output of [Self::command] into full package names.
+    pub fn parse(&self, output: &str) -> Vec<String> {
+        let mut result: Vec<String> = output
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .filter_map(|line| match self {
+                // only packages that are installed and not just configured or removed
+                Self::Dpkg => line
+                    .strip_prefix("install ok installed ")
+                    .or_else(|| line.strip_prefix("hold ok installed "))
+                    .map(str::trim),
+                // e.g. gpg-pubkey does not have an architecture
+                Self::Rpm => Some(line.strip_suffix(".(none)").unwrap_or(line)),
+                Self::Apk => (!line.starts_with("WARNING:")).then_some(line),
+            })
+            .map(|x| x.to_string())
+            .collect();
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+/// Returns the message of a result reporting the vulnerable packages of a notus VT
+pub fn finding(packages: &[VulnerablePackage]) -> String {
+    packages
+        .iter()
+        .map(|x| {
+            let fixed = match &x.fixed_version {
+                FixedVersion::Single { version, specifier } => {
+                    let specifier = match specifier {
+                        Specifier::GT => ">",
+                        Specifier::LT => "<",
+                        Specifier::GE => ">=",
+                        Specifier::LE => "<=",
+                        Specifier::EQ => "=",
+                    };
+                    format!("{specifier}{version}")
+                }
+                FixedVersion::Range { start, end } => format!("{start} - {end}"),
+            };
+            format!(
+                "Vulnerable package: {}\nInstalled version:  {}\nFixed version:      {}\n",
+                x.name, x.installed_version, fixed
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Scans gathered packages for vulnerabilities.
+///
+/// This allows builtin functions to run notus without knowing the loader of the products.
+pub trait PackageScanner: Send + Sync {
+    /// Returns the vulnerable packages of the product
+    fn scan_packages(&self, os: &str, packages: &[String]) -> Result<NotusResults, Error>;
+}
+
+impl<L> PackageScanner for Mutex<Notus<L>>
+where
+    L: ProductLoader + Send,
+{
+    fn scan_packages(&self, os: &str, packages: &[String]) -> Result<NotusResults, Error> {
+        self.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .scan(os, packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_release() {
+        let debian = OsRelease::parse(
+            r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+ID=debian
+"#,
+        )
+        .unwrap();
+        assert_eq!(debian.product(), "debian_12");
+        assert_eq!(debian.package_manager(), Some(PackageManager::Dpkg));
+
+        let ubuntu = OsRelease::parse("ID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"22.04\"").unwrap();
+        assert_eq!(ubuntu.product(), "ubuntu_22.04");
+        assert_eq!(ubuntu.package_manager(), Some(PackageManager::Dpkg));
+
+        let rocky =
+            OsRelease::parse("ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=\"9.3\"")
+                .unwrap();
+        assert_eq!(rocky.product(), "rocky_9");
+        assert_eq!(rocky.package_manager(), Some(PackageManager::Rpm));
+
+        let fedora = OsRelease::parse("ID=fedora\nVERSION_ID=39").unwrap();
+        assert_eq!(fedora.product(), "fedora_39");
+
+        let alpine = OsRelease::parse("ID=alpine\nVERSION_ID=3.19.1").unwrap();
+        assert_eq!(alpine.product(), "alpine_3.19.1");
+        assert_eq!(alpine.package_manager(), Some(PackageManager::Apk));
+
+        assert_eq!(OsRelease::parse("ID=arch").unwrap().package_manager(), None);
+        assert_eq!(OsRelease::parse("NAME=unknown"), None);
+    }
+
+    #[test]
+    fn findings() {
+        let packages = vec![
+            VulnerablePackage {
+                name: "openssl".to_string(),
+                installed_version: "3.0.11-1".to_string(),
+                fixed_version: FixedVersion::Single {
+                    version: "3.0.13-1".to_string(),
+                    specifier: Specifier::GE,
+                },
+            },
+            VulnerablePackage {
+                name: "curl".to_string(),
+                installed_version: "7.88.1".to_string(),
+                fixed_version: FixedVersion::Range {
+                    start: "7.80.0".to_string(),
+                    end: "7.88.2".to_string(),
+                },
+            },
+        ];
+        assert_eq!(
+            finding(&packages),
+            "Vulnerable package: openssl\nInstalled version:  3.0.11-1\nFixed version:      >=3.0.13-1\n\n\
+             Vulnerable package: curl\nInstalled version:  7.88.1\nFixed version:      7.80.0 - 7.88.2\n"
+        );
+    }
+
+    #[test]
+    fn package_lists() {
+        assert_eq!(
+            PackageManager::Dpkg.parse(
+                "install ok installed zlib1g-1:1.2.13.dfsg-1\n\
+                 deinstall ok config-files old-1.0\n\
+                 install ok installed apt-2.6.1\n"
+            ),
+            vec!["apt-2.6.1", "zlib1g-1:1.2.13.dfsg-1"]
+        );
+        assert_eq!(
+            PackageManager::Rpm
+                .parse("bash-5.1.8-6.el9.x86_64\ngpg-pubkey-5a6340b3-6229229e.(none)\n\n"),
+            vec!["bash-5.1.8-6.el9.x86_64", "gpg-pubkey-5a6340b3-6229229e"]
+        );
+        assert_eq!(
+            PackageManager::Apk.parse("WARNING: opening cache\nmusl-1.2.4-r2\nbusybox-1.36.1-r15"),
+            vec!["busybox-1.36.1-r15", "musl-1.2.4-r2"]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Gathers the input of a notus scan from a container image without running it.
+//!
+//! Two kinds of archives are supported, both optionally compressed with gzip:
+//! - an exported filesystem, e.g. created by `docker export`,
+//! - an image archive, e.g. created by `docker save` or `podman save`, whose layers are applied
+//!   in the order of its `manifest.json` including their whiteouts.
+//!
+//! Images are not pulled from a registry, they must be saved into an archive first.
+//!
+//! The distribution is detected via `os-release` and the installed packages are read from the
+//! package database of dpkg or apk. The binary databases of rpm are not supported.
+
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read},
+};
+
+use thiserror::Error;
+
+use super::gather::{OsRelease, PackageManager};
+
+const BLOCK_SIZE: usize = 512;
+
+const OS_RELEASE: &[&str] = &["etc/os-release", "usr/lib/os-release"];
+const DPKG_STATUS: &str = "var/lib/dpkg/status";
+const APK_INSTALLED: &str = "lib/apk/db/installed";
+const MANIFEST: &str = "manifest.json";
+
+/// Errors that might occur while reading an image
+#[derive(Debug, Error)]
+pub enum ImageError {
+    /// Unable to read the archive
+    #[error("Unable to read the image archive: {0}")]
+    Io(#[from] io::Error),
+    /// The manifest of an image archive is invalid
+    #[error("Invalid manifest of the image archive: {0}")]
+    Manifest(String),
+    /// The image does not contain an os-release file
+    #[error("The image does not contain an os-release file")]
+    MissingOsRelease,
+    /// The distribution of the image has no supported package manager
+    #[error("The distribution {0} is not supported")]
+    UnsupportedDistribution(String),
+    /// The package database of the image is missing or not supported, e.g. rpm
+    #[error("The {0} package database of the image is missing or not supported")]
+    PackageDatabase(&'static str),
+}
+
+/// The product and packages of an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInventory {
+    /// The release of the image
+    pub release: OsRelease,
+    /// The package manager the packages are read from
+    pub package_manager: PackageManager,
+    /// The installed packages as full names
+    pub packages: Vec<String>,
+}
+
+impl ImageInventory {
+    /// Reads the inventory of an exported filesystem or an image archive
+    pub fn read<R: Read>(reader: R) -> Result<Self, ImageError> {
+        let files = ImageFiles::read(reader)?;
+        let release = OS_RELEASE
+            .iter()
+            .find_map(|x| files.text(x))
+            .and_then(|x| OsRelease::parse(&x))
+            .ok_or(ImageError::MissingOsRelease)?;
+        let package_manager = release
+            .package_manager()
+            .ok_or_else(|| ImageError::UnsupportedDistribution(release.id.clone()))?;
+        let packages = match package_manager {
+            PackageManager::Dpkg => files.text(DPKG_STATUS).map(|x| parse_dpkg_status(&x)),
+            PackageManager::Apk => files.text(APK_INSTALLED).map(|x| parse_apk_installed(&x)),
+            PackageManager::Rpm => None,
+        }
+        .ok_or(ImageError::PackageDatabase(package_manager.as_str()))?;
+        Ok(Self {
+            release,
+            package_manager,
+            packages,
+        })
+    }
+
+    /// Returns the name of the notus product of the image
+    pub fn product(&self) -> String {
+        self.release.product()
+    }
+}
+
+/// Parses the status file of dpkg into full package names of the installed packages
+pub fn parse_dpkg_status(status: &str) -> Vec<String> {
+    let mut result: Vec<String> = status
+        .split("\n\n")
+        .filter_map(|paragraph| {
+            let mut name = None;
+            let mut version = None;
+            let mut installed = false;
+            for line in paragraph.lines() {
+                match line.split_once(':') {
+                    Some(("Package", x)) => name = Some(x.trim()),
+                    Some(("Version", x)) => version = Some(x.trim()),
+                    Some(("Status", x)) => installed = x.trim().ends_with(" installed"),
+                    _ => {}
+                }
+            }
+            match (name, version, installed) {
+                (Some(name), Some(version), true) => Some(format!("{name}-{version}")),
+                _ => None,
+            }
+        })
+        .collect();
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Parses the installed database of apk into full package names
+pub fn parse_apk_installed(installed: &str) -> Vec<String> {
+    let mut result: Vec<String> = installed
+        .split("\n\n")
+        .filter_map(|paragraph| {
+            let mut name = None;
+            let mut version = None;
+            for line in paragraph.lines() {
+                match line.split_once(':') {
+                    Some(("P", x)) => name = Some(x),
+                    Some(("V", x)) => version = Some(x),
+                    _ => {}
+                }
+            }
+            Some(format!("{}-{}", name?, version?))
+        })
+        .collect();
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// The files of a filesystem that are relevant for the inventory
+#[derive(Debug, Default)]
+struct Layer {
+    files: HashMap<String, Vec<u8>>,
+    /// Paths removed by a whiteout
+    whiteouts: Vec<String>,
+    /// Directories whose content of lower layers is hidden
+    opaque: Vec<String>,
+}
+
+impl Layer {
+    fn is_relevant(path: &str) -> bool {
+        OS_RELEASE.contains(&path) || path == DPKG_STATUS || path == APK_INSTALLED
+    }
+
+    /// Reads the relevant files of a filesystem archive
+    fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let mut result = Self::default();
+        walk(decompress(reader)?, |path, data| {
+            let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+            if name == ".wh..wh..opq" {
+                result.opaque.push(dir.to_string());
+                return Ok(());
+            }
+            if let Some(name) = name.strip_prefix(".wh.") {
+                result.whiteouts.push(join(dir, name));
+                return Ok(());
+            }
+            if Self::is_relevant(path) {
+                let mut content = vec![];
+                data.read_to_end(&mut content)?;
+                result.files.insert(path.to_string(), content);
+            }
+            Ok(())
+        })?;
+        Ok(result)
+    }
+
+    /// Applies upper onto this layer
+    fn apply(&mut self, upper: Layer) {
+        let hidden = |path: &str| {
+            upper
+                .whiteouts
+                .iter()
+                .any(|x| path == x || path.starts_with(&format!("{x}/")))
+                || upper
+                    .opaque
+                    .iter()
+                    .any(|x| x.is_empty() || path.starts_with(&format!("{x}/")))
+        };
+        self.files.retain(|path, _| !hidden(path));
+        self.files.extend(upper.files);
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// The relevant files of an image
+#[derive(Debug, Default)]
+struct ImageFiles {
+    root: Layer,
+}
+
+impl ImageFiles {
+    fn read<R: Read>(reader: R) -> Result<Self, ImageError> {
+        let mut root = Layer::default();
+        let mut manifest = None;
+        let mut layers = HashMap::new();
+        walk(decompress(reader)?, |path, data| {
+            if path == MANIFEST {
+                let mut content = vec![];
+                data.read_to_end(&mut content)?;
+                manifest = Some(content);
+            } else if path.ends_with("layer.tar") || path.starts_with("blobs/") {
+                // blobs also contain configurations and manifests, they are no archives
+                if let Ok(layer) = Layer::read(data) {
+                    layers.insert(path.to_string(), layer);
+                }
+            } else if Layer::is_relevant(path) {
+                let mut content = vec![];
+                data.read_to_end(&mut content)?;
+                root.files.insert(path.to_string(), content);
+            }
+            Ok(())
+        })?;
+        let Some(manifest) = manifest else {
+            return Ok(Self { root });
+        };
+        for path in manifest_layers(&manifest)? {
+            let layer = layers
+                .remove(&path)
+                .ok_or_else(|| ImageError::Manifest(format!("missing layer {path}")))?;
+            root.apply(layer);
+        }
+        Ok(Self { root })
+    }
+
+    fn text(&self, path: &str) -> Option<String> {
+        self.root
+            .files
+            .get(path)
+            .map(|x| String::from_utf8_lossy(x).to_string())
+    }
+}
+
+/// Returns the layers of the first image of a manifest from the lowest to the upper most
+fn manifest_layers(manifest: &[u8]) -> Result<Vec<String>, ImageError> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Entry {
+        layers: Vec<String>,
+    }
+    let entries: Vec<Entry> =
+        serde_json::from_slice(manifest).map_err(|e| ImageError::Manifest(e.to_string()))?;
+    entries
+        .into_iter()
+        .next()
+        .map(|x| x.layers)
+        .ok_or_else(|| ImageError::Manifest("no image".to_string()))
+}
+
+/// Returns a reader that decompresses gzip compressed data
+fn decompress<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = [0u8; 2];
+    let mut read = 0;
+    while read < magic.len() {
+        match reader.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    let reader = Cursor::new(magic[..read].to_vec()).chain(reader);
+    if magic[..read] == [0x1f, 0x8b] {
+        Ok(Box::new(flate2::read::GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn octal(field: &[u8]) -> io::Result<u64> {
+    // large sizes are stored as big endian number with the highest bit set
+    if field.first().is_some_and(|x| x & 0x80 != 0) {
+        return Ok(field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7f), |acc, x| {
+                acc << 8 | u64::from(*x)
+            }));
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid number in tar header"))
+}
+
+fn header_text(field: &[u8]) -> String {
+    let end = field.iter().position(|x| *x == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Removes leading `./` and `/` of a path within an archive
+fn normalize(path: &str) -> &str {
+    let mut path = path;
+    loop {
+        if let Some(x) = path.strip_prefix("./") {
+            path = x;
+        } else if let Some(x) = path.strip_prefix('/') {
+            path = x;
+        } else {
+            return path.trim_end_matches('/');
+        }
+    }
+}
+
+/// Calls visit with the path and content of each regular file of a tar archive
+fn walk<R, F>(mut reader: R, mut visit: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(&str, &mut dyn Read) -> io::Result<()>,
+{
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut header = [0u8; BLOCK_SIZE];
+    let mut long_name: Option<String> = None;
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(_) => {}
+            // some archives are not terminated by empty blocks
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        if header.iter().all(|x| *x == 0) {
+            return Ok(());
+        }
+        let checksum = octal(&header[148..156])?;
+        let actual: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    u64::from(*x)
+                }
+            })
+            .sum();
+        if checksum != actual {
+            return Err(invalid("invalid tar header checksum"));
+        }
+        let size = octal(&header[124..136])?;
+        let kind = header[156];
+        let name = match long_name.take() {
+            Some(x) => x,
+            None if &header[257..262] == b"ustar" && header[345] != 0 => format!(
+                "{}/{}",
+                header_text(&header[345..500]),
+                header_text(&header[..100])
+            ),
+            None => header_text(&header[..100]),
+        };
+        let mut data = (&mut reader).take(size);
+        match kind {
+            // GNU long name of the next entry
+            b'L' => {
+                let mut x = vec![];
+                data.read_to_end(&mut x)?;
+                long_name = Some(header_text(&x));
+            }
+            // pax header, only the path is of interest
+            b'x' => {
+                let mut x = vec![];
+                data.read_to_end(&mut x)?;
+                long_name = String::from_utf8_lossy(&x).lines().find_map(|line| {
+                    let (_, record) = line.split_once(' ')?;
+                    record.strip_prefix("path=").map(|x| x.to_string())
+                });
+            }
+            b'0' | 0 | b'7' => visit(normalize(&name), &mut data)?,
+            _ => {}
+        }
+        io::copy(&mut data, &mut io::sink())?;
+        let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+        io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const OS_RELEASE_DEBIAN: &str = "ID=debian\nVERSION_ID=\"12\"\n";
+    const DPKG: &str = "Package: apt\nStatus: install ok installed\nVersion: 2.6.1\n\n\
+                        Package: old\nStatus: deinstall ok config-files\nVersion: 1.0\n\n\
+                        Package: libc6\nStatus: install ok installed\nMulti-Arch: same\n\
+                        Description: GNU C Library\n multiple lines\nVersion: 2.36-9+deb12u3\n";
+
+    fn entry(path: &str, content: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+        header[156] = b'0';
+        header[257..262].copy_from_slice(b"ustar");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u64 = header.iter().map(|x| u64::from(*x)).sum();
+        header[148..154].copy_from_slice(format!("{:06o}", checksum).as_bytes());
+        header[154] = 0;
+        let mut result = header.to_vec();
+        result.extend_from_slice(content);
+        result.resize(result.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        result
+    }
+
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut result: Vec<u8> = entries.iter().flat_map(|(p, c)| entry(p, c)).collect();
+        result.extend_from_slice(&[0u8; BLOCK_SIZE * 2]);
+        result
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn package_databases() {
+        assert_eq!(
+            parse_dpkg_status(DPKG),
+            vec!["apt-2.6.1", "libc6-2.36-9+deb12u3"]
+        );
+        assert_eq!(
+            parse_apk_installed("C:Q1\nP:musl\nV:1.2.4-r2\nA:x86_64\n\nP:busybox\nV:1.36.1-r15\n"),
+            vec!["busybox-1.36.1-r15", "musl-1.2.4-r2"]
+        );
+    }
+
+    #[test]
+    fn exported_filesystem() {
+        let tar = archive(&[
+            ("./etc/os-release", OS_RELEASE_DEBIAN.as_bytes()),
+            ("./usr/bin/true", b"binary"),
+            ("./var/lib/dpkg/status", DPKG.as_bytes()),
+        ]);
+        let inventory = ImageInventory::read(Cursor::new(gzip(&tar))).unwrap();
+        assert_eq!(inventory.product(), "debian_12");
+        assert_eq!(inventory.package_manager, PackageManager::Dpkg);
+        assert_eq!(
+            inventory.packages,
+            vec!["apt-2.6.1", "libc6-2.36-9+deb12u3"]
+        );
+    }
+
+    #[test]
+    fn image_archive() {
+        let base = archive(&[
+            ("etc/os-release", b"ID=alpine\nVERSION_ID=3.18.4\n"),
+            ("lib/apk/db/installed", b"P:musl\nV:1.2.4-r1\n"),
+        ]);
+        let upper = archive(&[
+            ("etc/.wh.os-release", b""),
+            ("usr/lib/os-release", b"ID=alpine\nVERSION_ID=3.19.0\n"),
+            ("lib/apk/db/installed", b"P:musl\nV:1.2.4-r2\n"),
+        ]);
+        let manifest = br#"[{"Config":"config.json","Layers":["a/layer.tar","blobs/sha256/b"]}]"#;
+        let image = archive(&[
+            ("blobs/sha256/b", &gzip(&upper)),
+            ("config.json", b"{}"),
+            ("a/layer.tar", &base),
+            ("manifest.json", manifest),
+        ]);
+        let inventory = ImageInventory::read(Cursor::new(image)).unwrap();
+        assert_eq!(inventory.product(), "alpine_3.19.0");
+        assert_eq!(inventory.packages, vec!["musl-1.2.4-r2"]);
+
+        let missing = archive(&[("manifest.json", manifest)]);
+        assert!(matches!(
+            ImageInventory::read(Cursor::new(missing)),
+            Err(ImageError::Manifest(_))
+        ));
+    }
+
+    #[test]
+    fn unsupported() {
+        let rpm = archive(&[
+            (
+                "etc/os-release",
+                b"ID=rocky\nID_LIKE=rhel\nVERSION_ID=9.3\n",
+            ),
+            ("var/lib/rpm/rpmdb.sqlite", b"SQLite"),
+        ]);
+        assert!(matches!(
+            ImageInventory::read(Cursor::new(rpm)),
+            Err(ImageError::PackageDatabase("rpm"))
+        ));
+        assert!(matches!(
+            ImageInventory::read(Cursor::new(archive(&[("bin/sh", b"")]))),
+            Err(ImageError::MissingOsRelease)
+        ));
+    }
+}
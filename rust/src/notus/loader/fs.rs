@@ -6,14 +6,15 @@ use std::{
     fs::{self, File},
     io::{self, Read},
     path::Path,
+    sync::Arc,
     time::SystemTime,
 };
 
 use crate::models::Product;
 
-use crate::feed::SignatureChecker;
+use crate::feed::{SignatureChecker, TrustStore};
 use crate::{
-    feed::{check_signature, VerifyError},
+    feed::{check_file_signature, check_signature, VerifyError},
     notus::error::{Error, LoadProductErrorKind},
 };
 
@@ -25,6 +26,7 @@ where
     P: AsRef<Path>,
 {
     root: P,
+    trust_store: Option<Arc<dyn TrustStore>>,
 }
 
 impl<P> FSProductLoader<P>
@@ -44,7 +46,18 @@ where
             ));
         }
 
-        Ok(Self { root: path })
+        Ok(Self {
+            root: path,
+            trust_store: None,
+        })
+    }
+
+    /// Verifies each product file with its detached signature `<os>.notus.asc` before loading it.
+    ///
+    /// This allows to use products that are not part of a signed sha256sums file.
+    pub fn with_trust_store(mut self, trust_store: Arc<dyn TrustStore>) -> Self {
+        self.trust_store = Some(trust_store);
+        self
     }
 }
 
@@ -57,6 +70,12 @@ where
     fn load_product(&self, os: &str) -> Result<(Product, FeedStamp), Error> {
         let notus_file = self.root.as_ref().join(format!("{os}.notus"));
         let notus_file_str = notus_file.to_string_lossy().to_string();
+        if let Some(store) = &self.trust_store {
+            if notus_file.is_file() {
+                check_file_signature(store.as_ref(), &notus_file)
+                    .map_err(Error::SignatureCheckError)?;
+            }
+        }
         let mut file = match File::open(notus_file) {
             Ok(file) => file,
             Err(err) => {
@@ -142,7 +161,9 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
 
+    use crate::feed::VerifyError;
     use crate::notus::{
         error::Error,
         loader::ProductLoader,
@@ -174,6 +195,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_err_missing_detached_signature() {
+        let loader = setup_loader().with_trust_store(Arc::new(Vec::new()));
+        assert!(matches!(
+            loader.load_product("debian_10").expect_err("Should fail"),
+            Error::SignatureCheckError(VerifyError::MissingSignature(_))
+        ));
+        assert!(matches!(
+            loader.load_product("foo").expect_err("Should fail"),
+            Error::UnknownProduct(_)
+        ));
+    }
+
     #[test]
     fn test_err_unknown_os() {
         let loader = setup_loader();
@@ -5,17 +5,23 @@
 #![doc = include_str!("README.md")]
 
 mod loader;
-mod packages;
+pub(crate) mod packages;
 
 mod error;
+pub mod gather;
+pub mod image;
 #[allow(clippy::module_inception)]
 mod notus;
+pub mod sbom;
+pub mod vex;
 mod vts;
+pub mod windows_inventory;
 
 #[cfg(test)]
 mod tests;
 
 pub use error::Error as NotusError;
+pub use gather::PackageScanner;
 pub use loader::fs::FSProductLoader;
 pub use loader::hashsum::HashsumAdvisoryLoader;
 pub use loader::hashsum::HashsumProductLoader;
@@ -0,0 +1,403 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Gathers the input of a notus scan from a software bill of materials.
+//!
+//! CycloneDX and SPDX documents in JSON are supported. Components are mapped to distribution
+//! packages via their package URL (purl), e.g.
+//! `pkg:deb/debian/openssl@3.0.11-1~deb12u2?arch=amd64&distro=debian-12`. Only deb, rpm and apk
+//! packages can be checked by notus, every other component is reported as unmatched.
+//!
+//! The product is derived from the `distro` qualifier of the purls or, for CycloneDX, from a
+//! component of type `operating-system`. When the components belong to several distributions
+//! the most common one is used and the others are unmatched.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::gather::OsRelease;
+
+/// Distributions using the product of their major version
+const RHEL_LIKE: &[&str] = &["rhel", "centos", "rocky", "almalinux", "ol"];
+
+/// Errors that might occur while reading a SBOM
+#[derive(Debug, Error)]
+pub enum SbomError {
+    /// The document is not valid JSON
+    #[error("Invalid SBOM: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The document is neither CycloneDX nor SPDX
+    #[error("Unsupported SBOM format, expected CycloneDX or SPDX in JSON")]
+    UnsupportedFormat,
+}
+
+/// The packages of a SBOM
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SbomInventory {
+    /// The notus product of the distribution, if it is described by the SBOM
+    pub product: Option<String>,
+    /// The distribution packages as full names
+    pub packages: Vec<String>,
+    /// Components that cannot be checked by notus, as purl or name and version
+    pub unmatched: Vec<String>,
+    /// The purls of the packages by package name, to reference them in reports
+    pub purls: HashMap<String, String>,
+}
+
+/// A component of a SBOM
+struct Component {
+    name: String,
+    version: Option<String>,
+    purl: Option<String>,
+}
+
+impl Component {
+    fn from_cyclonedx(value: &Value) -> Self {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Self {
+            name: text("name").unwrap_or_default(),
+            version: text("version"),
+            purl: text("purl"),
+        }
+    }
+
+    fn from_spdx(value: &Value) -> Self {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let purl = value
+            .get("externalRefs")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|x| x.get("referenceType").and_then(Value::as_str) == Some("purl"))
+            .and_then(|x| x.get("referenceLocator"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        Self {
+            name: text("name").unwrap_or_default(),
+            version: text("versionInfo"),
+            purl,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match (&self.purl, &self.version) {
+            (Some(purl), _) => purl.clone(),
+            (None, Some(version)) => format!("{}@{}", self.name, version),
+            (None, None) => self.name.clone(),
+        }
+    }
+}
+
+/// A parsed package URL
+#[derive(Debug, PartialEq, Eq)]
+struct Purl {
+    kind: String,
+    namespace: Option<String>,
+    name: String,
+    version: String,
+    qualifiers: HashMap<String, String>,
+}
+
+/// Decodes the percent encoded characters of a purl
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(x)) => {
+                result.push(x);
+                i += 3;
+            }
+            (x, _) => {
+                result.push(x);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+impl Purl {
+    fn parse(purl: &str) -> Option<Self> {
+        let rest = purl.strip_prefix("pkg:")?;
+        let rest = rest.split_once('#').map_or(rest, |(x, _)| x);
+        let (rest, qualifiers) = match rest.split_once('?') {
+            Some((rest, qualifiers)) => (rest, qualifiers),
+            None => (rest, ""),
+        };
+        let (path, version) = rest.rsplit_once('@')?;
+        let mut parts = path.trim_matches('/').split('/');
+        let kind = parts.next()?.to_lowercase();
+        let mut parts: Vec<_> = parts.collect();
+        let name = decode(parts.pop()?);
+        let namespace = (!parts.is_empty()).then(|| decode(&parts.join("/")).to_lowercase());
+        let qualifiers = qualifiers
+            .split('&')
+            .filter_map(|x| x.split_once('='))
+            .map(|(k, v)| (k.to_lowercase(), decode(v)))
+            .collect();
+        Some(Self {
+            kind,
+            namespace,
+            name,
+            version: decode(version),
+            qualifiers,
+        })
+    }
+
+    /// Returns the distribution and its version, e.g. `("debian", "12")`
+    fn distro(&self) -> Option<(String, String)> {
+        let distro = self.qualifiers.get("distro")?;
+        // e.g. debian-12, alpine-3.19.0 or rhel-9.3
+        let (id, version) = distro.rsplit_once('-')?;
+        let id = match id {
+            "redhat" => "rhel",
+            x => x,
+        };
+        Some((id.to_lowercase(), version.to_string()))
+    }
+
+    /// Returns the full package name as expected by notus
+    fn package(&self) -> Option<String> {
+        match self.kind.as_str() {
+            "deb" | "apk" => Some(format!("{}-{}", self.name, self.version)),
+            "rpm" => {
+                // version contains the release, the architecture is required by notus
+                if !self.version.contains('-') {
+                    return None;
+                }
+                let arch = self.qualifiers.get("arch")?;
+                let epoch = self
+                    .qualifiers
+                    .get("epoch")
+                    .filter(|x| *x != "0")
+                    .map(|x| format!("{x}:"))
+                    .unwrap_or_default();
+                Some(format!("{}-{}{}.{}", self.name, epoch, self.version, arch))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the notus product of a distribution
+fn product(id: &str, version: &str) -> String {
+    let release = OsRelease {
+        id: id.to_string(),
+        id_like: if RHEL_LIKE.contains(&id) {
+            vec!["rhel".to_string()]
+        } else {
+            vec![]
+        },
+        version_id: version.to_string(),
+    };
+    release.product()
+}
+
+impl SbomInventory {
+    /// Parses a CycloneDX or SPDX document in JSON
+    pub fn parse(document: &[u8]) -> Result<Self, SbomError> {
+        let document: Value = serde_json::from_slice(document)?;
+        let mut operating_system = None;
+        let components: Vec<Component> =
+            if document.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX") {
+                let mut components = vec![];
+                let mut pending: Vec<&Value> = document
+                    .get("components")
+                    .and_then(Value::as_array)
+                    .map(|x| x.iter().collect())
+                    .unwrap_or_default();
+                // the described component, e.g. a container, may also contain the os
+                if let Some(nested) = document
+                    .pointer("/metadata/component/components")
+                    .and_then(Value::as_array)
+                {
+                    pending.extend(nested);
+                }
+                // components may be nested
+                while let Some(x) = pending.pop() {
+                    if let Some(nested) = x.get("components").and_then(Value::as_array) {
+                        pending.extend(nested);
+                    }
+                    if x.get("type").and_then(Value::as_str) == Some("operating-system") {
+                        let os = Component::from_cyclonedx(x);
+                        operating_system = os.version.map(|v| (os.name.to_lowercase(), v));
+                        continue;
+                    }
+                    components.push(Component::from_cyclonedx(x));
+                }
+                components
+            } else if document.get("spdxVersion").is_some() {
+                document
+                    .get("packages")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .map(Component::from_spdx)
+                    .collect()
+            } else {
+                return Err(SbomError::UnsupportedFormat);
+            };
+        Ok(Self::from_components(components, operating_system))
+    }
+
+    fn from_components(
+        components: Vec<Component>,
+        operating_system: Option<(String, String)>,
+    ) -> Self {
+        let mut result = Self::default();
+        let parsed: Vec<_> = components
+            .into_iter()
+            .map(|x| {
+                let purl = x.purl.as_deref().and_then(Purl::parse);
+                (x, purl)
+            })
+            .collect();
+        let mut counts: HashMap<(String, String), usize> = HashMap::new();
+        for distro in parsed.iter().filter_map(|(_, x)| x.as_ref()?.distro()) {
+            *counts.entry(distro).or_default() += 1;
+        }
+        let distro = counts
+            .into_iter()
+            .max_by(|(a, x), (b, y)| x.cmp(y).then_with(|| b.cmp(a)))
+            .map(|(x, _)| x)
+            .or(operating_system);
+
+        for (component, purl) in parsed {
+            let package = purl.as_ref().and_then(|purl| {
+                // packages of another distribution would be compared with the wrong advisories
+                let same = match (purl.distro(), &distro) {
+                    (Some(x), Some(distro)) => &x == distro,
+                    _ => true,
+                };
+                same.then(|| purl.package()).flatten()
+            });
+            match (package, purl, &component.purl) {
+                (Some(x), Some(purl), Some(reference)) => {
+                    result.purls.insert(purl.name, reference.clone());
+                    result.packages.push(x);
+                }
+                _ => result.unmatched.push(component.describe()),
+            }
+        }
+        result.product = distro.map(|(id, version)| product(&id, &version));
+        result.packages.sort();
+        result.packages.dedup();
+        result.unmatched.sort();
+        result.unmatched.dedup();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purls() {
+        let purl =
+            Purl::parse("pkg:deb/debian/zlib1g@1%3A1.2.13.dfsg-1?arch=amd64&distro=debian-12")
+                .unwrap();
+        assert_eq!(purl.namespace.as_deref(), Some("debian"));
+        assert_eq!(purl.package().unwrap(), "zlib1g-1:1.2.13.dfsg-1");
+        assert_eq!(
+            purl.distro(),
+            Some(("debian".to_string(), "12".to_string()))
+        );
+
+        let rpm =
+            Purl::parse("pkg:rpm/redhat/bash@5.1.8-6.el9?arch=x86_64&epoch=1&distro=redhat-9.3")
+                .unwrap();
+        assert_eq!(rpm.package().unwrap(), "bash-1:5.1.8-6.el9.x86_64");
+        assert_eq!(rpm.distro(), Some(("rhel".to_string(), "9.3".to_string())));
+        assert_eq!(
+            Purl::parse("pkg:rpm/redhat/bash@5.1.8-6.el9")
+                .unwrap()
+                .package(),
+            None
+        );
+        assert_eq!(
+            Purl::parse("pkg:npm/%40angular/core@16.0.0")
+                .unwrap()
+                .package(),
+            None
+        );
+        assert!(Purl::parse("npm:core").is_none());
+    }
+
+    #[test]
+    fn cyclonedx() {
+        let sbom = br#"{
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "components": [
+                {"type": "operating-system", "name": "Alpine", "version": "3.19.0"},
+                {"type": "library", "name": "musl", "version": "1.2.4-r2",
+                 "purl": "pkg:apk/alpine/musl@1.2.4-r2?arch=x86_64"},
+                {"type": "library", "name": "lodash", "version": "4.17.21",
+                 "purl": "pkg:npm/lodash@4.17.21",
+                 "components": [
+                    {"type": "library", "name": "busybox", "version": "1.36.1-r15",
+                     "purl": "pkg:apk/alpine/busybox@1.36.1-r15?arch=x86_64"}
+                 ]},
+                {"type": "library", "name": "internal", "version": "1.0"}
+            ]
+        }"#;
+        let inventory = SbomInventory::parse(sbom).unwrap();
+        assert_eq!(inventory.product.as_deref(), Some("alpine_3.19.0"));
+        assert_eq!(
+            inventory.packages,
+            vec!["busybox-1.36.1-r15", "musl-1.2.4-r2"]
+        );
+        assert_eq!(
+            inventory.unmatched,
+            vec!["internal@1.0", "pkg:npm/lodash@4.17.21"]
+        );
+        assert_eq!(
+            inventory.purls.get("musl").map(|x| x as &str),
+            Some("pkg:apk/alpine/musl@1.2.4-r2?arch=x86_64")
+        );
+    }
+
+    #[test]
+    fn spdx() {
+        let purl = |x: &str| {
+            format!(
+                r#"{{"name": "x", "externalRefs": [{{"referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl", "referenceLocator": "{x}"}}]}}"#
+            )
+        };
+        let sbom = format!(
+            r#"{{"spdxVersion": "SPDX-2.3", "packages": [{}, {}, {}, {{"name": "app", "versionInfo": "2"}}]}}"#,
+            purl("pkg:rpm/rocky/openssl@3.0.7-25.el9?arch=x86_64&distro=rocky-9.3"),
+            purl("pkg:rpm/rocky/bash@5.1.8-6.el9?arch=x86_64&distro=rocky-9.3"),
+            purl("pkg:rpm/fedora/bash@5.2.26-3.fc40?arch=x86_64&distro=fedora-40"),
+        );
+        let inventory = SbomInventory::parse(sbom.as_bytes()).unwrap();
+        assert_eq!(inventory.product.as_deref(), Some("rocky_9"));
+        assert_eq!(
+            inventory.packages,
+            vec!["bash-5.1.8-6.el9.x86_64", "openssl-3.0.7-25.el9.x86_64"]
+        );
+        assert_eq!(
+            inventory.unmatched,
+            vec![
+                "app@2",
+                "pkg:rpm/fedora/bash@5.2.26-3.fc40?arch=x86_64&distro=fedora-40"
+            ]
+        );
+
+        assert!(matches!(
+            SbomInventory::parse(b"{}"),
+            Err(SbomError::UnsupportedFormat)
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Renders the results of a notus scan as VEX document.
+//!
+//! Two formats are supported:
+//! - [OpenVEX](https://openvex.dev), a statement per notus VT listing the affected packages,
+//! - CycloneDX VEX, a vulnerability per notus VT referencing the affected packages.
+//!
+//! Packages are referenced by their package URL. The purls of a SBOM are used when the scan was
+//! based on one, see [super::sbom::SbomInventory::purls], otherwise a purl is derived from the
+//! product and the package.
+//!
+//! Notus identifies vulnerabilities by the OID of its VTs, so the OID is used as name of the
+//! vulnerability.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::models::{NotusResults, VulnerablePackage};
+
+use super::gather::{self, OsRelease, PackageManager};
+
+const OPENVEX_CONTEXT: &str = "https://openvex.dev/ns/v0.2.0";
+const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+
+/// The format of a VEX document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VexFormat {
+    /// OpenVEX
+    OpenVex,
+    /// CycloneDX VEX
+    CycloneDx,
+}
+
+impl FromStr for VexFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "openvex" => Ok(Self::OpenVex),
+            "cyclonedx" | "cyclonedx-vex" => Ok(Self::CycloneDx),
+            x => Err(format!(
+                "unknown VEX format {x}, expected openvex or cyclonedx"
+            )),
+        }
+    }
+}
+
+impl Display for VexFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OpenVex => write!(f, "openvex"),
+            Self::CycloneDx => write!(f, "cyclonedx"),
+        }
+    }
+}
+
+/// A VEX document of the results of a notus scan of a product
+#[derive(Debug, Clone)]
+pub struct VexDocument {
+    /// The scanned notus product, e.g. `debian_12`
+    pub product: String,
+    /// The purls of packages by package name
+    pub purls: HashMap<String, String>,
+    /// The author of the document
+    pub author: String,
+    /// The time of the document in RFC 3339
+    pub timestamp: String,
+    /// The unique id of the document
+    pub id: String,
+}
+
+impl VexDocument {
+    /// Creates a new document of product
+    pub fn new(product: &str) -> Self {
+        let id = uuid::Uuid::new_v4();
+        Self {
+            product: product.to_string(),
+            purls: HashMap::new(),
+            author: "Greenbone Notus".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            id: format!("urn:uuid:{id}"),
+        }
+    }
+
+    /// Sets the purls of the scanned packages, e.g. of a SBOM
+    pub fn with_purls(mut self, purls: HashMap<String, String>) -> Self {
+        self.purls = purls;
+        self
+    }
+
+    /// Sets the author of the document
+    pub fn with_author(mut self, author: &str) -> Self {
+        self.author = author.to_string();
+        self
+    }
+
+    /// Returns the purl of a vulnerable package
+    fn purl(&self, package: &VulnerablePackage) -> String {
+        if let Some(x) = self.purls.get(&package.name) {
+            return x.clone();
+        }
+        let (id, version) = self.product.rsplit_once('_').unwrap_or((&self.product, ""));
+        let release = OsRelease {
+            id: id.to_string(),
+            id_like: vec![],
+            version_id: version.to_string(),
+        };
+        let kind = match release.package_manager() {
+            Some(PackageManager::Dpkg) => "deb",
+            Some(PackageManager::Apk) => "apk",
+            Some(PackageManager::Rpm) | None => "rpm",
+        };
+        let mut purl = format!(
+            "pkg:{kind}/{id}/{}@{}",
+            package.name,
+            package.installed_version.replace(':', "%3A")
+        );
+        if !version.is_empty() {
+            purl.push_str(&format!("?distro={id}-{version}"));
+        }
+        purl
+    }
+
+    /// Returns the results sorted by OID for stable documents
+    fn sorted(results: &NotusResults) -> Vec<(&String, &Vec<VulnerablePackage>)> {
+        let mut result: Vec<_> = results.iter().collect();
+        result.sort_by(|a, b| a.0.cmp(b.0));
+        result
+    }
+
+    /// Renders the results in format
+    pub fn render(&self, format: VexFormat, results: &NotusResults) -> Value {
+        match format {
+            VexFormat::OpenVex => self.openvex(results),
+            VexFormat::CycloneDx => self.cyclonedx(results),
+        }
+    }
+
+    fn openvex(&self, results: &NotusResults) -> Value {
+        let statements: Vec<Value> = Self::sorted(results)
+            .into_iter()
+            .map(|(oid, packages)| {
+                let products: Vec<Value> = packages
+                    .iter()
+                    .map(|x| json!({ "@id": self.purl(x) }))
+                    .collect();
+                json!({
+                    "vulnerability": { "name": oid },
+                    "products": products,
+                    "status": "affected",
+                    "action_statement": gather::finding(packages),
+                })
+            })
+            .collect();
+        json!({
+            "@context": OPENVEX_CONTEXT,
+            "@id": self.id,
+            "author": self.author,
+            "timestamp": self.timestamp,
+            "version": 1,
+            "statements": statements,
+        })
+    }
+
+    fn cyclonedx(&self, results: &NotusResults) -> Value {
+        let vulnerabilities: Vec<Value> = Self::sorted(results)
+            .into_iter()
+            .map(|(oid, packages)| {
+                let affects: Vec<Value> = packages
+                    .iter()
+                    .map(|x| {
+                        json!({
+                            "ref": self.purl(x),
+                            "versions": [{ "version": x.installed_version, "status": "affected" }],
+                        })
+                    })
+                    .collect();
+                json!({
+                    "id": oid,
+                    "source": { "name": self.author },
+                    "analysis": { "state": "exploitable" },
+                    "recommendation": gather::finding(packages),
+                    "affects": affects,
+                })
+            })
+            .collect();
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": CYCLONEDX_SPEC_VERSION,
+            "serialNumber": self.id,
+            "version": 1,
+            "metadata": {
+                "timestamp": self.timestamp,
+                "tools": { "components": [{ "type": "application", "name": self.author }] },
+            },
+            "vulnerabilities": vulnerabilities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{FixedVersion, Specifier};
+
+    use super::*;
+
+    fn results() -> NotusResults {
+        let package = |name: &str, installed: &str, fixed: &str| VulnerablePackage {
+            name: name.to_string(),
+            installed_version: installed.to_string(),
+            fixed_version: FixedVersion::Single {
+                version: fixed.to_string(),
+                specifier: Specifier::GE,
+            },
+        };
+        NotusResults::from([
+            (
+                "1.3.6.1.4.1.25623.1.1.2".to_string(),
+                vec![package("zlib1g", "1:1.2.13", "1:1.2.14")],
+            ),
+            (
+                "1.3.6.1.4.1.25623.1.1.1".to_string(),
+                vec![package("openssl", "3.0.11-1", "3.0.13-1")],
+            ),
+        ])
+    }
+
+    fn document() -> VexDocument {
+        let mut document = VexDocument::new("debian_12").with_purls(HashMap::from([(
+            "openssl".to_string(),
+            "pkg:deb/debian/openssl@3.0.11-1?arch=amd64".to_string(),
+        )]));
+        document.id = "urn:uuid:1".to_string();
+        document.timestamp = "2024-01-01T00:00:00Z".to_string();
+        document
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!("OpenVEX".parse(), Ok(VexFormat::OpenVex));
+        assert_eq!("cyclonedx-vex".parse(), Ok(VexFormat::CycloneDx));
+        assert!("spdx".parse::<VexFormat>().is_err());
+    }
+
+    #[test]
+    fn openvex() {
+        let vex = document().render(VexFormat::OpenVex, &results());
+        assert_eq!(vex["@id"], "urn:uuid:1");
+        let statements = vex["statements"].as_array().unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0]["vulnerability"]["name"],
+            "1.3.6.1.4.1.25623.1.1.1"
+        );
+        assert_eq!(
+            statements[0]["products"][0]["@id"],
+            "pkg:deb/debian/openssl@3.0.11-1?arch=amd64"
+        );
+        assert_eq!(statements[0]["status"], "affected");
+        assert_eq!(
+            statements[1]["products"][0]["@id"],
+            "pkg:deb/debian/zlib1g@1%3A1.2.13?distro=debian-12"
+        );
+    }
+
+    #[test]
+    fn cyclonedx() {
+        let vex = document()
+            .with_author("test")
+            .render(VexFormat::CycloneDx, &results());
+        assert_eq!(vex["bomFormat"], "CycloneDX");
+        assert_eq!(vex["serialNumber"], "urn:uuid:1");
+        let vulnerabilities = vex["vulnerabilities"].as_array().unwrap();
+        assert_eq!(vulnerabilities[1]["id"], "1.3.6.1.4.1.25623.1.1.2");
+        assert_eq!(vulnerabilities[1]["source"]["name"], "test");
+        assert_eq!(
+            vulnerabilities[1]["affects"][0]["versions"][0]["version"],
+            "1:1.2.13"
+        );
+        assert_eq!(
+            vulnerabilities[0]["affects"][0]["ref"],
+            "pkg:deb/debian/openssl@3.0.11-1?arch=amd64"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Builds the input of a notus scan of a Windows target from its inventory.
+//!
+//! The inventory consists of the values of the registry key
+//! `HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion` and the installed hotfixes. Notus compares
+//! Windows targets by their build and update revision, e.g. `10.0.19045.3570`, so the inventory
+//! results in a product like `windows_10` and a single package of that form.
+//!
+//! Collecting the values is up to the caller, e.g. via `reg query` and `wmic qfe` on a remote
+//! shell, as there are no SMB or WMI builtin functions yet.
+
+use super::gather::{KB_PACKAGE_LIST, KB_RELEASE};
+
+/// Registry key containing the version of the target
+pub const CURRENT_VERSION_KEY: &str = r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+
+/// KB key of the installed hotfixes, separated by new lines
+pub const KB_HOTFIXES: &str = "WMI/notus/hotfixes";
+
+/// The first build of Windows 11, which still reports itself as Windows 10 in the registry
+const FIRST_WINDOWS_11_BUILD: u32 = 22000;
+
+/// Version and hotfixes of a Windows target
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowsInventory {
+    /// The product name, e.g. `Windows Server 2022 Datacenter`
+    pub product_name: String,
+    /// The build number, e.g. `20348`
+    pub current_build: u32,
+    /// The update build revision, e.g. `2031`
+    pub ubr: u32,
+    /// The installed hotfixes, e.g. `KB5031364`
+    pub hotfixes: Vec<String>,
+}
+
+/// Parses a registry value of type REG_SZ or REG_DWORD
+fn registry_value(kind: &str, value: &str) -> Option<String> {
+    match kind {
+        "REG_DWORD" => {
+            let hex = value.trim_start_matches("0x");
+            u32::from_str_radix(hex, 16).ok().map(|x| x.to_string())
+        }
+        _ => Some(value.to_string()),
+    }
+}
+
+impl WindowsInventory {
+    /// Parses the output of `reg query` of [CURRENT_VERSION_KEY].
+    ///
+    /// Returns None when the product name or build is missing.
+    pub fn from_registry(output: &str) -> Option<Self> {
+        let mut result = Self::default();
+        for line in output.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(kind)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let value = fields.collect::<Vec<_>>().join(" ");
+            let Some(value) = registry_value(kind, &value) else {
+                continue;
+            };
+            match name {
+                "ProductName" => result.product_name = value,
+                "CurrentBuild" | "CurrentBuildNumber" => {
+                    result.current_build = value.parse().unwrap_or_default()
+                }
+                "UBR" => result.ubr = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+        }
+        (!result.product_name.is_empty() && result.current_build > 0).then_some(result)
+    }
+
+    /// Adds the hotfixes of the output of `wmic qfe get HotFixID`
+    pub fn with_hotfixes(mut self, output: &str) -> Self {
+        self.hotfixes = output
+            .lines()
+            .map(str::trim)
+            .filter(|x| x.starts_with("KB"))
+            .map(|x| x.to_string())
+            .collect();
+        self.hotfixes.sort();
+        self.hotfixes.dedup();
+        self
+    }
+
+    /// Returns the name of the notus product, e.g. `windows_server_2022`
+    pub fn product(&self) -> String {
+        let name = self.product_name.to_lowercase();
+        let mut words = name.split_whitespace().filter(|x| *x != "microsoft");
+        let mut result = vec![];
+        if let Some(x) = words.next() {
+            result.push(x.to_string());
+        }
+        for word in words {
+            if word == "server" {
+                result.push(word.to_string());
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                result.push(word.to_string());
+                break;
+            } else {
+                break;
+            }
+        }
+        if result.last().is_some_and(|x| x == "10") && self.current_build >= FIRST_WINDOWS_11_BUILD
+        {
+            result.pop();
+            result.push("11".to_string());
+        }
+        result.join("_")
+    }
+
+    /// Returns the package compared by notus, e.g. `10.0.20348.2031`
+    pub fn package(&self) -> String {
+        format!("10.0.{}.{}", self.current_build, self.ubr)
+    }
+
+    /// Returns the KB items describing the inventory
+    pub fn kb_items(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (KB_RELEASE, self.product()),
+            (KB_PACKAGE_LIST, self.package()),
+            (KB_HOTFIXES, self.hotfixes.join("\n")),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let inventory = WindowsInventory::from_registry(
+            r"
+HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows NT\CurrentVersion
+    ProductName    REG_SZ    Windows Server 2022 Datacenter
+    CurrentBuild    REG_SZ    20348
+    UBR    REG_DWORD    0x7ef
+",
+        )
+        .unwrap()
+        .with_hotfixes("HotFixID\r\nKB5031364\r\nKB5030216\r\n\r\n");
+        assert_eq!(inventory.product(), "windows_server_2022");
+        assert_eq!(inventory.package(), "10.0.20348.2031");
+        assert_eq!(inventory.hotfixes, vec!["KB5030216", "KB5031364"]);
+        assert_eq!(
+            inventory.kb_items()[2],
+            (KB_HOTFIXES, "KB5030216\nKB5031364".to_string())
+        );
+
+        let client = WindowsInventory::from_registry(
+            "ProductName REG_SZ Windows 10 Pro\nCurrentBuild REG_SZ 22631\nUBR REG_DWORD 0xb2c",
+        )
+        .unwrap();
+        assert_eq!(client.product(), "windows_11");
+        assert_eq!(client.package(), "10.0.22631.2860");
+
+        assert_eq!(WindowsInventory::from_registry("UBR REG_DWORD 0x1"), None);
+    }
+}
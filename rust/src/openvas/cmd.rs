@@ -5,7 +5,8 @@
 use configparser::ini::Ini;
 use std::{
     io::Result,
-    process::{Child, Command},
+    process::{Child, Command, ExitStatus},
+    time::{Duration, Instant},
 };
 /// This module provides functions to call the openvas executable for different
 /// purposes, e.g. start or stopping a scan.
@@ -30,7 +31,7 @@ pub fn read_openvas_config() -> Result<Ini> {
     let oconfig = oconfig.stdout.iter().map(|x| *x as char).collect();
     config
         .read(oconfig)
-        .expect("Error reading openvas configuration");
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     Ok(config)
 }
 
@@ -45,33 +46,29 @@ pub fn get_redis_socket() -> String {
     String::new()
 }
 
+/// Returns the command line to start a scan.
+///
+/// When a network namespace is given openvas is executed within it via `ip netns exec`.
+fn start_args(id: &str, sudo: bool, nice: Option<i8>, netns: Option<&str>) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(niceness) = nice {
+        args.extend(["nice".to_string(), "-n".to_string(), niceness.to_string()]);
+    }
+    if sudo {
+        args.extend(["sudo".to_string(), "-n".to_string()]);
+    }
+    if let Some(netns) = netns {
+        args.extend(["ip", "netns", "exec", netns].map(String::from));
+    }
+    args.extend(["openvas", "--scan-start", id].map(String::from));
+    args
+}
+
 /// Start a new scan with the openvas executable with the given string. Before a scan can be
 /// started all data needed for the scan must be put into redis before.
-pub fn start(id: &str, sudo: bool, nice: Option<i8>) -> Result<Child> {
-    match nice {
-        Some(niceness) => match sudo {
-            true => Command::new("nice")
-                .args([
-                    "-n",
-                    &niceness.to_string(),
-                    "sudo",
-                    "-n",
-                    "openvas",
-                    "--scan-start",
-                    id,
-                ])
-                .spawn(),
-            false => Command::new("nice")
-                .args(["-n", &niceness.to_string(), "openvas", "--scan-start", id])
-                .spawn(),
-        },
-        None => match sudo {
-            true => Command::new("sudo")
-                .args(["-n", "openvas", "--scan-start", id])
-                .spawn(),
-            false => Command::new("openvas").args(["--scan-start", id]).spawn(),
-        },
-    }
+pub fn start(id: &str, sudo: bool, nice: Option<i8>, netns: Option<&str>) -> Result<Child> {
+    let args = start_args(id, sudo, nice, netns);
+    Command::new(&args[0]).args(&args[1..]).spawn()
 }
 
 /// Stops a running scan. Openvas internally sends an SIGUSR1 to the running
@@ -84,3 +81,65 @@ pub fn stop(id: &str, sudo: bool) -> Result<Child> {
         false => Command::new("openvas").args(["--scan-stop", id]).spawn(),
     }
 }
+
+/// Waits for the process to exit without blocking the runtime.
+///
+/// When the process did not exit within the timeout it is killed and `None` is returned.
+pub async fn wait(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Command, time::Duration};
+
+    use super::{start_args, wait};
+
+    #[tokio::test]
+    async fn wait_with_timeout() {
+        let mut exits = Command::new("true").spawn().unwrap();
+        let status = wait(&mut exits, Duration::from_secs(5)).await.unwrap();
+        assert!(status.is_some_and(|x| x.success()));
+        let mut hangs = Command::new("sleep").arg("10").spawn().unwrap();
+        let status = wait(&mut hangs, Duration::from_millis(100)).await.unwrap();
+        assert_eq!(status, None);
+        // the killed process is reaped
+        assert!(hangs.try_wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn start_in_namespace() {
+        assert_eq!(
+            start_args("1", true, Some(10), Some("customer-a")),
+            [
+                "nice",
+                "-n",
+                "10",
+                "sudo",
+                "-n",
+                "ip",
+                "netns",
+                "exec",
+                "customer-a",
+                "openvas",
+                "--scan-start",
+                "1"
+            ]
+        );
+        assert_eq!(
+            start_args("1", false, None, None),
+            ["openvas", "--scan-start", "1"]
+        );
+    }
+}
@@ -11,4 +11,4 @@ mod openvas_redis;
 mod pref_handler;
 mod result_collector;
 
-pub use openvas::Scanner;
+pub use openvas::{Scanner, DEFAULT_TIMEOUT};
@@ -7,34 +7,40 @@ use super::{
     error::OpenvasError,
     openvas_redis::{KbAccess, RedisHelper},
     pref_handler::PreferenceHandler,
-    result_collector::ResultHelper,
+    result_collector::{ResultHelper, Results},
 };
 use crate::models::{
     scanner::{
-        Error as ScanError, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
+        Error as ScanError, ScanDeleter, ScanPlanner, ScanResultFetcher, ScanResults, ScanStarter,
+        ScanStopper,
     },
-    HostInfoBuilder, Phase, Status,
+    Heartbeat, HostInfoBuilder, Phase, Status,
 };
 use crate::{
     models::{self, resources::check::Checker, Scan},
+    nasl::utils::NetworkSource,
     storage::redis::{NameSpaceSelector, RedisCtx},
 };
 use async_trait::async_trait;
 use std::{
     collections::HashMap,
     fmt::Display,
-    process::Child,
+    process::{Child, ExitStatus},
     str::FromStr,
     sync::{Arc, Mutex},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
+/// Default time a redis command or an openvas process may take before it is given up
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct Scanner {
     running: Mutex<HashMap<String, (Child, u32)>>,
     sudo: bool,
     redis_socket: String,
     resource_checker: Option<Checker>,
+    timeout: Duration,
 }
 
 impl From<OpenvasError> for ScanError {
@@ -98,6 +104,7 @@ impl Scanner {
             sudo,
             redis_socket: url,
             resource_checker: Some(Checker::new_relative_memory(memory, None)),
+            timeout: DEFAULT_TIMEOUT,
         }
     }
 
@@ -107,12 +114,27 @@ impl Scanner {
             sudo,
             redis_socket: url,
             resource_checker: Some(Checker::new(memory, cpu)),
+            timeout: DEFAULT_TIMEOUT,
         }
     }
 
+    /// Sets the time a redis command or waiting for an openvas process may take.
+    ///
+    /// Collecting the status of a scan fails when redis does not respond in time, an openvas
+    /// process that does not exit in time is killed.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     /// Removes a scan from init and add it to the list of running scans
-    fn add_running(&self, id: String, dbid: u32) -> Result<bool, OpenvasError> {
-        let openvas = cmd::start(&id, self.sudo, None).map_err(OpenvasError::CmdError)?;
+    fn add_running(
+        &self,
+        id: String,
+        dbid: u32,
+        netns: Option<&str>,
+    ) -> Result<bool, OpenvasError> {
+        let openvas = cmd::start(&id, self.sudo, None, netns).map_err(OpenvasError::CmdError)?;
         self.running.lock().unwrap().insert(id, (openvas, dbid));
         Ok(true)
     }
@@ -126,28 +148,105 @@ impl Scanner {
         &self,
         dbid: Option<u32>,
     ) -> Result<RedisHelper<RedisCtx>, ScanError> {
-        let namespace = match dbid {
-            Some(id) => [NameSpaceSelector::Fix(id)],
-            None => [NameSpaceSelector::Free],
-        };
+        create_redis_connector(&self.redis_socket, self.timeout, dbid)
+    }
 
-        tracing::trace!(url = &self.redis_socket, "connecting to redis");
-        let kbctx = Arc::new(Mutex::new(
-            match RedisCtx::open(&self.redis_socket, &namespace) {
-                Ok(x) => x,
-                Err(e) => return Err(ScanError::Connection(format!("{e}"))),
-            },
-        ));
-        let nvtcache = Arc::new(Mutex::new(
-            match RedisCtx::open(&self.redis_socket, &[NameSpaceSelector::Key("nvticache")]) {
-                Ok(x) => x,
-                Err(e) => return Err(ScanError::Connection(format!("{e}"))),
-            },
-        ));
-        Ok(RedisHelper::<RedisCtx>::new(nvtcache, kbctx))
+    /// Collects the status and optionally the results of a scan from its kb.
+    ///
+    /// The redis commands run on a blocking thread, when they do not finish within the timeout
+    /// a connection error is returned instead of blocking the caller. The commands themselves
+    /// fail at the latest after the timeout of the connection, so that the thread is not
+    /// blocked forever either.
+    async fn collect(
+        &self,
+        dbid: u32,
+        scan_id: &str,
+        with_results: bool,
+    ) -> Result<(RedisHelper<RedisCtx>, Results), ScanError> {
+        let url = self.redis_socket.clone();
+        let timeout = self.timeout;
+        let scan_id = scan_id.to_string();
+        let collect = tokio::task::spawn_blocking(move || {
+            let mut redis_help = create_redis_connector(&url, timeout, Some(dbid))?;
+            let mut ov_results = ResultHelper::init(&mut redis_help);
+            // the collect functions do not await anything, they only block on redis
+            futures::executor::block_on(async {
+                if with_results {
+                    ov_results.collect_results().await?;
+                    ov_results.collect_host_status().await?;
+                }
+                ov_results.collect_scan_status(scan_id).await
+            })
+            .map_err(|e| ScanError::Unexpected(e.to_string()))?;
+            let results = std::mem::take(
+                &mut *Arc::as_ref(&ov_results.results)
+                    .lock()
+                    .map_err(|_| ScanError::Poisoned)?,
+            );
+            Ok((redis_help, results))
+        });
+        match tokio::time::timeout(timeout, collect).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(ScanError::Unexpected(e.to_string())),
+            Err(_) => Err(ScanError::Connection(format!(
+                "redis did not respond within {timeout:?}"
+            ))),
+        }
+    }
+
+    /// Waits for an openvas process to exit, returns `None` when it was killed after the timeout.
+    async fn wait(
+        &self,
+        child: &mut Child,
+        scan_id: &str,
+    ) -> Result<Option<ExitStatus>, ScanError> {
+        match cmd::wait(child, self.timeout)
+            .await
+            .map_err(OpenvasError::CmdError)?
+        {
+            Some(status) => Ok(Some(status)),
+            None => {
+                tracing::warn!(
+                    scan_id,
+                    timeout = ?self.timeout,
+                    "openvas did not exit in time and was killed"
+                );
+                Ok(None)
+            }
+        }
     }
 }
 
+fn create_redis_connector(
+    url: &str,
+    timeout: Duration,
+    dbid: Option<u32>,
+) -> Result<RedisHelper<RedisCtx>, ScanError> {
+    let namespace = match dbid {
+        Some(id) => [NameSpaceSelector::Fix(id)],
+        None => [NameSpaceSelector::Free],
+    };
+
+    tracing::trace!(url, "connecting to redis");
+    let kbctx = Arc::new(Mutex::new(
+        match RedisCtx::open_with_timeout(url, &namespace, Some(timeout)) {
+            Ok(x) => x,
+            Err(e) => return Err(ScanError::Connection(format!("{e}"))),
+        },
+    ));
+    let nvtcache = Arc::new(Mutex::new(
+        match RedisCtx::open_with_timeout(
+            url,
+            &[NameSpaceSelector::Key("nvticache")],
+            Some(timeout),
+        ) {
+            Ok(x) => x,
+            Err(e) => return Err(ScanError::Connection(format!("{e}"))),
+        },
+    ));
+    Ok(RedisHelper::<RedisCtx>::new(nvtcache, kbctx))
+}
+
 impl Default for Scanner {
     fn default() -> Self {
         Self {
@@ -155,12 +254,21 @@ impl Default for Scanner {
             sudo: cmd::check_sudo(),
             redis_socket: cmd::get_redis_socket(),
             resource_checker: None,
+            timeout: DEFAULT_TIMEOUT,
         }
     }
 }
+impl ScanPlanner for Scanner {}
+
 #[async_trait]
 impl ScanStarter for Scanner {
     async fn start_scan(&self, scan: Scan) -> Result<(), ScanError> {
+        // openvas uses the same ports and credentials for all hosts of a scan
+        if scan.target.has_group_settings() {
+            return Err(ScanError::NotSupported(
+                "target groups with their own ports or credentials".to_string(),
+            ));
+        }
         // Prepare the connections to redis for communication with openvas.
         let mut redis_help = self.create_redis_connector(None)?;
 
@@ -173,10 +281,11 @@ impl ScanStarter for Scanner {
             }
         }
 
-        self.add_running(
-            scan.scan_id,
-            redis_help.kb_id().expect("Valid Redis context"),
-        )?;
+        let dbid = redis_help
+            .kb_id()
+            .map_err(|e| ScanError::Connection(e.to_string()))?;
+        let netns = NetworkSource::from(&scan).namespace;
+        self.add_running(scan.scan_id, dbid, netns.as_deref())?;
 
         return Ok(());
     }
@@ -204,12 +313,12 @@ impl ScanStopper for Scanner {
             None => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
         };
 
-        cmd::stop(scan_id, self.sudo)
-            .map_err(OpenvasError::CmdError)?
-            .wait()
-            .map_err(OpenvasError::CmdError)?;
-
-        scan.wait().map_err(OpenvasError::CmdError)?;
+        let mut stop = cmd::stop(scan_id, self.sudo).map_err(OpenvasError::CmdError)?;
+        if self.wait(&mut stop, scan_id).await?.is_none() {
+            // the scan is killed below when it does not stop in time
+            tracing::warn!(scan_id, "unable to stop the scan gracefully");
+        }
+        self.wait(&mut scan, scan_id).await?;
 
         // Release the task kb
         let mut redis_help = self.create_redis_connector(Some(dbid))?;
@@ -240,21 +349,12 @@ impl ScanDeleter for Scanner {
             None => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
         };
 
-        let mut redis_help = self.create_redis_connector(Some(dbid))?;
-        let mut ov_results = ResultHelper::init(&mut redis_help);
-        ov_results
-            .collect_scan_status(scan_id.to_string())
-            .await
-            .map_err(|e| ScanError::Unexpected(e.to_string()))?;
-
-        let mut scan_status = Phase::Running;
-        if let Ok(res) = Arc::as_ref(&ov_results.results).lock() {
-            scan_status = OpenvasPhase::from_str(&res.scan_status)
-                .map_err(|_| {
-                    ScanError::Unexpected(format!("Invalid Phase status {}", res.scan_status))
-                })?
-                .into();
-        }
+        let (mut redis_help, res) = self.collect(dbid, scan_id, false).await?;
+        let scan_status: Phase = OpenvasPhase::from_str(&res.scan_status)
+            .map_err(|_| {
+                ScanError::Unexpected(format!("Invalid Phase status {}", res.scan_status))
+            })?
+            .into();
 
         match scan_status {
             Phase::Running => {
@@ -296,106 +396,102 @@ impl ScanResultFetcher for Scanner {
             None => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
         };
 
-        let mut redis_help = self.create_redis_connector(Some(dbid))?;
-        let mut ov_results = ResultHelper::init(&mut redis_help);
+        let (mut redis_help, all_results) = self.collect(dbid, scan_id, true).await?;
+        let hosts_info = HostInfoBuilder {
+            all: all_results.count_total as u64,
+            excluded: all_results.count_excluded as u64,
+            dead: all_results.count_dead as u64,
+            alive: all_results.count_alive as u64,
+            queued: 0,
+            finished: all_results.count_alive as u64,
+            scanning: Some(all_results.host_status.clone()),
+        }
+        .build();
+
+        let status: Phase = OpenvasPhase::from_str(&all_results.scan_status)
+            .map_err(|_| {
+                ScanError::Unexpected(format!("Invalid Phase status {}", all_results.scan_status))
+            })?
+            .into();
+        let start_time = match status {
+            Phase::Running => Some(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|x| x.as_secs())
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        };
+        let end_time = match status {
+            Phase::Failed | Phase::Stopped | Phase::Succeeded | Phase::Partial => Some(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|x| x.as_secs())
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        };
 
-        ov_results
-            .collect_results()
-            .await
-            .map_err(|e| ScanError::Unexpected(e.to_string()))?;
-        ov_results
-            .collect_host_status()
-            .await
-            .map_err(|e| ScanError::Unexpected(e.to_string()))?;
-        ov_results
-            .collect_scan_status(scan_id.to_string())
-            .await
-            .map_err(|e| ScanError::Unexpected(e.to_string()))?;
+        // openvas does not tell which script it started, new results and a changed host
+        // progress are the only signs of progress
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        let has_results = !all_results.results.is_empty();
+        let heartbeat = (has_results || !all_results.host_status.is_empty()).then(|| {
+            let mut heartbeat = Heartbeat::at(now);
+            if has_results {
+                heartbeat.result(now);
+            }
+            heartbeat
+        });
+
+        let st = Status {
+            start_time,
+            end_time,
+            status: status.clone(),
+            host_info: Some(hosts_info),
+            heartbeat,
+            stalled: false,
+        };
 
-        match Arc::as_ref(&ov_results.results).lock() {
-            Ok(all_results) => {
-                let hosts_info = HostInfoBuilder {
-                    all: all_results.count_total as u64,
-                    excluded: all_results.count_excluded as u64,
-                    dead: all_results.count_dead as u64,
-                    alive: all_results.count_alive as u64,
-                    queued: 0,
-                    finished: all_results.count_alive as u64,
-                    scanning: Some(all_results.host_status.clone()),
-                }
-                .build();
-
-                let status: Phase = OpenvasPhase::from_str(&all_results.scan_status)
-                    .map_err(|_| {
-                        ScanError::Unexpected(format!(
-                            "Invalid Phase status {}",
-                            all_results.scan_status
-                        ))
-                    })?
-                    .into();
-                let start_time = match status {
-                    Phase::Running => Some(
-                        SystemTime::now()
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .expect("Valid timestamp for start scan")
-                            .as_secs(),
-                    ),
-                    _ => None,
-                };
-                let end_time = match status {
-                    Phase::Failed | Phase::Stopped | Phase::Succeeded => Some(
-                        SystemTime::now()
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .expect("Valid timestamp for start scan")
-                            .as_secs(),
-                    ),
-                    _ => None,
-                };
-
-                let st = Status {
-                    start_time,
-                    end_time,
-                    status: status.clone(),
-                    host_info: Some(hosts_info),
-                };
-
-                let mut scan_res = ScanResults {
-                    id: scan_id.to_string(),
-                    status: st,
-                    results: all_results
-                        .results
-                        .iter()
-                        .map(|r| models::Result::from(r).clone())
-                        .collect(),
-                };
-                // If the scan finished, release. Openvas "finished" status is translated todo
-                // Succeeded. It is necessary to read the exit code to know if it failed.
-                if status == Phase::Succeeded {
-                    let mut scan = match self.remove_running(scan_id) {
-                        Some(scan) => scan.0,
-                        None => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
-                    };
-
-                    // Read openvas scanner exit code and if failed, reset the status to Failed.
-                    let exit_status = scan.wait().map_err(OpenvasError::CmdError)?;
-                    if let Some(code) = exit_status.code() {
-                        if code != 0 {
-                            scan_res.status.status = Phase::Failed;
-                            scan_res.status.start_time = scan_res.status.end_time;
-                            scan_res.status.host_info = None;
-                        }
-                    }
+        let mut scan_res = ScanResults {
+            id: scan_id.to_string(),
+            status: st,
+            results: all_results
+                .results
+                .iter()
+                .map(|r| models::Result::from(r).clone())
+                .collect(),
+        };
+        // If the scan finished, release. Openvas "finished" status is translated todo
+        // Succeeded. It is necessary to read the exit code to know if it failed.
+        if status == Phase::Succeeded {
+            let mut scan = match self.remove_running(scan_id) {
+                Some(scan) => scan.0,
+                None => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
+            };
+
+            // Read openvas scanner exit code and if failed, reset the status to Failed.
+            // A scanner that did not exit in time is killed and failed as well.
+            let failed = match self.wait(&mut scan, scan_id).await? {
+                Some(status) => status.code().is_some_and(|x| x != 0),
+                None => true,
+            };
+            if failed {
+                scan_res.status.status = Phase::Failed;
+                scan_res.status.start_time = scan_res.status.end_time;
+                scan_res.status.host_info = None;
+            }
 
-                    redis_help
-                        .release()
-                        .map_err(|e| ScanError::Unexpected(e.to_string()))?;
-                    self.running.lock().unwrap().remove(scan_id);
-                }
+            redis_help
+                .release()
+                .map_err(|e| ScanError::Unexpected(e.to_string()))?;
+            self.running.lock().unwrap().remove(scan_id);
+        }
 
-                return Ok(scan_res);
-            }
-            Err(_) => return Err(OpenvasError::ScanNotFound(scan_id.to_string()).into()),
-        };
+        Ok(scan_res)
     }
 
     fn do_addition(&self) -> bool {
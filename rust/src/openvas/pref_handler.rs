@@ -5,7 +5,8 @@
 use std::collections::HashMap;
 
 use crate::models::{
-    ports_to_openvas_port_list, AliveTestMethods, CredentialType, Scan, Service, VT,
+    ports_to_openvas_port_list, AliveTestMethods, CredentialType, Scan, ScanPreferenceInformation,
+    Service, VT,
 };
 use crate::storage::redis::RedisStorageResult;
 
@@ -56,6 +57,7 @@ where
         self.prepare_plugins_for_openvas().await?;
         self.prepare_main_kbindex_for_openvas().await?;
         self.prepare_host_options_for_openvas().await?;
+        self.prepare_source_iface_for_openvas().await?;
         self.prepare_scan_params_for_openvas().await?;
         self.prepare_reverse_lookup_opt_for_openvas().await?;
         self.prepare_alive_test_option_for_openvas().await?;
@@ -202,7 +204,7 @@ where
         //Preference 7
         value = "no";
         if (alive_test & AliveTestMethods::TcpSyn as u8) != 0
-            && !(alive_test & AliveTestMethods::TcpAck as u8) != 0
+            && (alive_test & AliveTestMethods::TcpAck as u8) == 0
         {
             value = "yes"
         }
@@ -305,7 +307,7 @@ where
             if reverse_lookup_unify {
                 lookup_opts.push("reverse_lookup_unify|||yes".to_string());
             } else {
-                lookup_opts.push("reverse_lookup_unify|||no ".to_string());
+                lookup_opts.push("reverse_lookup_unify|||no".to_string());
             }
         }
 
@@ -320,7 +322,13 @@ where
     }
 
     async fn prepare_target_for_openvas(&mut self) -> RedisStorageResult<()> {
-        let target = self.scan_config.target.hosts.join(",");
+        let target = self
+            .scan_config
+            .target
+            .all_hosts()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(",");
         self.redis_connector.push_kb_item(
             format!("internal/{}/scanprefs", self.scan_config.scan_id.clone()).as_str(),
             format!("TARGET|||{}", target),
@@ -351,13 +359,36 @@ where
         )
     }
 
+    async fn prepare_source_iface_for_openvas(&mut self) -> RedisStorageResult<()> {
+        let iface = match &self.scan_config.target.source_interface {
+            Some(x) if !x.is_empty() => x.clone(),
+            _ => return Ok(()),
+        };
+
+        self.redis_connector.push_kb_item(
+            format!("internal/{}/scanprefs", self.scan_config.scan_id.clone()).as_str(),
+            format!("source_iface|||{}", iface),
+        )
+    }
+
     async fn prepare_scan_params_for_openvas(&mut self) -> RedisStorageResult<()> {
         let options = self
             .scan_config
             .scan_preferences
             .clone()
             .iter()
-            .map(|x| format!("{}|||{}", x.id, x.value))
+            .map(|x| {
+                let value =
+                    match ScanPreferenceInformation::find(&x.id).map(|i| i.normalize(&x.value)) {
+                        Some(Ok(value)) => value,
+                        Some(Err(e)) => {
+                            tracing::warn!(%e, "passing invalid scan preference to openvas");
+                            x.value.clone()
+                        }
+                        None => x.value.clone(),
+                    };
+                format!("{}|||{}", x.id, value)
+            })
             .collect::<Vec<String>>();
 
         if options.is_empty() {
@@ -560,6 +591,7 @@ mod tests {
             },
         }];
         scan.target.excluded_hosts = vec!["127.0.0.1".to_string()];
+        scan.target.source_interface = Some("eth1".to_string());
         scan.target.ports = vec![Port {
             protocol: Some(Protocol::TCP),
             range: vec![
@@ -601,6 +633,11 @@ mod tests {
             .redis_connector
             .item_exists("internal/123-456/scanprefs", "exclude_hosts|||127.0.0.1"));
 
+        assert!(prefh.prepare_source_iface_for_openvas().await.is_ok());
+        assert!(prefh
+            .redis_connector
+            .item_exists("internal/123-456/scanprefs", "source_iface|||eth1"));
+
         assert!(prefh.prepare_credentials_for_openvas().await.is_ok());
         assert!(prefh.redis_connector.item_exists(
             "internal/123-456/scanprefs",
@@ -98,6 +98,7 @@ where
             };
 
             let mut rname = String::new();
+            let mut qod = String::new();
             if !host_is_dead && !host_deny && !start_end_msg && !host_count && !excluded_hosts {
                 if roid.is_empty() && !error_msg {
                     tracing::warn!("Missing VT oid for a result");
@@ -107,6 +108,7 @@ where
                 match vt_aux {
                     None => tracing::warn!("Invalid oid"),
                     Some(vt) => {
+                        qod = vt.qod().to_string();
                         rname = vt.name;
                     }
                 };
@@ -122,6 +124,7 @@ where
                     description: value,
                     severity: StringF32::from(0.0),
                     name: rname,
+                    qod,
                 });
             } else if result_type == "LOG" {
                 scan_results.push(OspScanResult {
@@ -133,6 +136,7 @@ where
                     description: value,
                     severity: StringF32::from(0.0),
                     name: rname,
+                    qod,
                 });
             } else if result_type == "HOST_START" {
                 scan_results.push(OspScanResult {
@@ -144,6 +148,7 @@ where
                     description: value,
                     severity: StringF32::from(0.0),
                     name: rname,
+                    qod,
                 });
             } else if result_type == "HOST_END" {
                 scan_results.push(OspScanResult {
@@ -155,6 +160,7 @@ where
                     description: value,
                     severity: StringF32::from(0.0),
                     name: rname,
+                    qod,
                 });
             } else if result_type == "ALARM" {
                 scan_results.push(OspScanResult {
@@ -166,13 +172,14 @@ where
                     description: value,
                     severity: StringF32::from(0.0),
                     name: rname,
+                    qod,
                 });
             } else if result_type == "DEADHOST" {
-                new_dead += i64::from_str(&value).expect("Valid amount of dead hosts");
+                new_dead += amount_of_hosts(&value, "dead hosts");
             } else if host_count {
-                count_total = i64::from_str(&value).expect("Valid amount of dead hosts");
+                count_total = amount_of_hosts(&value, "hosts");
             } else if excluded_hosts {
-                count_excluded = i64::from_str(&value).expect("Valid amount of excluded hosts");
+                count_excluded = amount_of_hosts(&value, "excluded hosts");
             }
         }
         if let Ok(mut results) = Arc::as_ref(&self.results).lock() {
@@ -201,9 +208,12 @@ where
         let mut all_hosts: HashMap<String, i32> = HashMap::new();
         for res in redis_status {
             let mut fields = res.splitn(3, '/');
-            let current_host = fields.next().expect("Valid status value");
-            let launched = fields.next().expect("Valid status value");
-            let total = fields.next().expect("Valid status value");
+            let (Some(current_host), Some(launched), Some(total)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                tracing::warn!("Invalid status value {res}");
+                continue;
+            };
 
             let host_progress: i32 = match i32::from_str(total) {
                 // No plugins
@@ -212,7 +222,13 @@ where
                 }
                 // Host Dead
                 Ok(-1) => ScanProgress::DeadHost as i32,
-                Ok(n) => ((f32::from_str(launched).expect("Integer") / n as f32) * 100.0) as i32,
+                Ok(n) => match f32::from_str(launched) {
+                    Ok(launched) => ((launched / n as f32) * 100.0) as i32,
+                    Err(_) => {
+                        tracing::warn!("Invalid amount of launched plugins {launched}");
+                        continue;
+                    }
+                },
                 _ => {
                     continue;
                 }
@@ -253,6 +269,14 @@ where
     }
 }
 
+/// Parses an amount of hosts sent by openvas, an invalid amount is logged and counted as 0.
+fn amount_of_hosts(value: &str, kind: &str) -> i64 {
+    i64::from_str(value).unwrap_or_else(|_| {
+        tracing::warn!("Invalid amount of {kind} {value}");
+        0
+    })
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -288,10 +312,15 @@ mod tests {
             ip_address: Some("127.0.0.1".to_string()),
             hostname: Some("localhost".to_string()),
             oid: Some("".to_string()),
+            qod: None,
             port: None,
             protocol: None,
             message: Some("HOST_START".to_string()),
             detail: None,
+            pcap: None,
+            traffic: None,
+            labels: Default::default(),
+            triage: None,
         };
         assert_eq!(
             models::Result::from(
@@ -312,10 +341,15 @@ mod tests {
             ip_address: Some("127.0.0.1".to_string()),
             hostname: Some("localhost".to_string()),
             oid: Some("1.2.3.4.5.6".to_string()),
+            qod: None,
             port: None,
             protocol: None,
             message: Some("NVT timeout".to_string()),
             detail: None,
+            pcap: None,
+            traffic: None,
+            labels: Default::default(),
+            triage: None,
         };
         assert_eq!(
             models::Result::from(
@@ -336,10 +370,15 @@ mod tests {
             ip_address: Some("127.0.0.1".to_string()),
             hostname: Some("example.com".to_string()),
             oid: Some("12.11.10.9.8.7".to_string()),
+            qod: None,
             port: Some(22i16),
             protocol: Some(Protocol::TCP),
             message: Some("Something wrong".to_string()),
             detail: None,
+            pcap: None,
+            traffic: None,
+            labels: Default::default(),
+            triage: None,
         };
         assert_eq!(
             models::Result::from(
@@ -389,4 +428,23 @@ mod tests {
         assert_eq!(resh.results.as_ref().lock().unwrap().count_alive, 1);
         assert_eq!(resh.results.as_ref().lock().unwrap().count_dead, 2);
     }
+
+    #[test]
+    fn invalid_status() {
+        let status = vec![
+            "127.0.0.1".to_string(),
+            "127.0.0.2/a/1000".to_string(),
+            "127.0.0.3/1000/1000".to_string(),
+        ];
+
+        let mut rc = FakeRedis {
+            data: HashMap::new(),
+        };
+
+        let resh = ResultHelper::init(&mut rc);
+        resh.process_status(status).unwrap();
+
+        let r = HashMap::from([("127.0.0.3".to_string(), 100)]);
+        assert_eq!(resh.results.as_ref().lock().unwrap().host_status, r);
+    }
 }
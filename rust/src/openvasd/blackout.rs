@@ -0,0 +1,314 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Time windows during which no scan may run.
+//!
+//! A blackout starts on each of its days at `start` and lasts until `end`, both in the timezone
+//! of the blackout. When `end` is not after `start` the blackout lasts until `end` of the next
+//! day, so `22:00` to `06:00` covers the night and `00:00` to `00:00` the whole day. A blackout
+//! without hosts applies to every scan, otherwise only to scans targeting one of its hosts or
+//! networks.
+
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use scannerlib::{models::Scan, nasl::utils::zone};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Day of the week, given by its English name or abbreviation, e.g. `monday` or `Mon`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Day(pub Weekday);
+
+impl TryFrom<String> for Day {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .trim()
+            .parse()
+            .map(Self)
+            .map_err(|_| ParseError(format!("`{value}` is not a day of the week")))
+    }
+}
+
+impl From<Day> for String {
+    fn from(value: Day) -> Self {
+        value.0.to_string()
+    }
+}
+
+/// Time of the day, either on a 24-hour clock (`22:30`) or on a 12-hour clock (`10:30 pm`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let trimmed = value.trim();
+        ["%H:%M", "%I:%M %p", "%I:%M%p", "%I %p", "%I%p"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(trimmed, format).ok())
+            .map(Self)
+            .ok_or_else(|| ParseError(format!("`{value}` is not a time of the day")))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(value: TimeOfDay) -> Self {
+        value.0.format("%H:%M").to_string()
+    }
+}
+
+/// Timezone the times of a blackout are given in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Timezone {
+    #[default]
+    Utc,
+    /// Timezone of the host openvasd runs on, including its daylight saving time
+    Local,
+    /// Fixed offset to UTC, e.g. `+02:00`
+    Fixed(FixedOffset),
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.trim() {
+            x if x.eq_ignore_ascii_case("utc") || x == "Z" => Ok(Self::Utc),
+            x if x.eq_ignore_ascii_case("local") => Ok(Self::Local),
+            x => FixedOffset::from_str(x)
+                .map(Self::Fixed)
+                .map_err(|_| ParseError(format!("`{value}` is not a timezone"))),
+        }
+    }
+}
+
+impl From<Timezone> for String {
+    fn from(value: Timezone) -> Self {
+        match value {
+            Timezone::Utc => "UTC".to_string(),
+            Timezone::Local => "local".to_string(),
+            Timezone::Fixed(offset) => offset.to_string(),
+        }
+    }
+}
+
+impl Timezone {
+    fn local(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Utc => now.naive_utc(),
+            Self::Local => now.with_timezone(&Local).naive_local(),
+            Self::Fixed(offset) => now.with_timezone(offset).naive_local(),
+        }
+    }
+}
+
+/// A recurring time window during which scans must not run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blackout {
+    /// Name of the blackout used in logs
+    #[serde(default)]
+    pub name: String,
+    /// Days on which the blackout starts, every day when empty
+    #[serde(default)]
+    pub days: Vec<Day>,
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+    #[serde(default)]
+    pub timezone: Timezone,
+    /// Hosts or networks in CIDR notation the blackout is limited to, all scans when empty
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+impl Blackout {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|x| x.0 == day)
+    }
+
+    /// Returns true when now is within the blackout.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let local = self.timezone.local(now);
+        let (day, time) = (local.weekday(), local.time());
+        let (start, end) = (self.start.0, self.end.0);
+        if start < end {
+            self.starts_on(day) && start <= time && time < end
+        } else {
+            (self.starts_on(day) && start <= time) || (self.starts_on(day.pred()) && time < end)
+        }
+    }
+
+    /// Returns true when the blackout applies to the targets of the scan.
+    pub fn applies_to(&self, scan: &Scan) -> bool {
+        self.hosts.is_empty()
+            || scan
+                .target
+                .all_hosts()
+                .any(|host| self.hosts.iter().any(|x| matches_host(x, host)))
+    }
+}
+
+/// Returns the first blackout that is active for scan.
+pub fn active<'a>(
+    blackouts: &'a [Blackout],
+    scan: &Scan,
+    now: DateTime<Utc>,
+) -> Option<&'a Blackout> {
+    blackouts
+        .iter()
+        .find(|x| x.applies_to(scan) && x.is_active(now))
+}
+
+fn parse_network(value: &str) -> Option<(IpAddr, u32)> {
+    match value.split_once('/') {
+        Some((ip, prefix)) => Some((ip.parse().ok()?, prefix.parse().ok()?)),
+        None => {
+            let ip: IpAddr = zone::strip(value).parse().ok()?;
+            Some((ip, if ip.is_ipv4() { 32 } else { 128 }))
+        }
+    }
+}
+
+fn network_bits(ip: IpAddr) -> (u128, u32) {
+    match ip {
+        IpAddr::V4(ip) => (u32::from(ip) as u128, 32),
+        IpAddr::V6(ip) => (u128::from(ip), 128),
+    }
+}
+
+/// Returns true when host equals pattern or is an address within the network of pattern.
+///
+/// A host given as network matches when it lies completely within the network of pattern.
+pub fn matches_host(pattern: &str, host: &str) -> bool {
+    if pattern.eq_ignore_ascii_case(host) {
+        return true;
+    }
+    let (Some((network, prefix)), Some((ip, host_prefix))) =
+        (parse_network(pattern), parse_network(host))
+    else {
+        return false;
+    };
+    let ((network, bits), (ip, host_bits)) = (network_bits(network), network_bits(ip));
+    if bits != host_bits || prefix > bits || host_prefix < prefix {
+        return false;
+    }
+    let mask =
+        u128::MAX.checked_shl(bits - prefix).unwrap_or_default() & (u128::MAX >> (128 - bits));
+    network & mask == ip & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use scannerlib::models::Scan;
+
+    use super::{matches_host, Blackout};
+
+    fn blackout(toml: &str) -> Blackout {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let b = blackout(
+            r#"
+            days = ["sat", "Sunday"]
+            start = "10:00 pm"
+            end = "06:30"
+            timezone = "+02:00"
+            "#,
+        );
+        assert_eq!(b.days.len(), 2);
+        assert_eq!(String::from(b.start), "22:00");
+        assert_eq!(String::from(b.timezone), "+02:00");
+        assert!(toml::from_str::<Blackout>(r#"start = "25:00""#).is_err());
+        assert!(toml::from_str::<Blackout>(
+            r#"
+            days = ["someday"]
+            start = "10:00"
+            end = "11:00"
+            "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn active() {
+        let b = blackout(
+            r#"
+            days = ["sat"]
+            start = "22:00"
+            end = "06:00"
+            timezone = "+02:00"
+            "#,
+        );
+        // 2024-06-01 is a Saturday
+        let at = |d, h, m| Utc.with_ymd_and_hms(2024, 6, d, h, m, 0).unwrap();
+        assert!(!b.is_active(at(1, 19, 59)));
+        assert!(b.is_active(at(1, 20, 0)));
+        assert!(b.is_active(at(2, 3, 59)));
+        assert!(!b.is_active(at(2, 4, 0)));
+        assert!(!b.is_active(at(2, 20, 0)));
+
+        let b = blackout(
+            r#"
+            start = "09:00"
+            end = "17:00"
+            "#,
+        );
+        assert!(b.is_active(at(3, 9, 0)));
+        assert!(!b.is_active(at(3, 17, 0)));
+
+        let b = blackout(
+            r#"
+            days = ["mon"]
+            start = "00:00"
+            end = "00:00"
+            "#,
+        );
+        assert!(b.is_active(at(3, 0, 0)));
+        assert!(b.is_active(at(3, 23, 59)));
+        assert!(!b.is_active(at(4, 0, 0)));
+    }
+
+    #[test]
+    fn hosts() {
+        assert!(matches_host("example.com", "Example.com"));
+        assert!(matches_host("10.0.0.0/8", "10.1.2.3"));
+        assert!(matches_host("10.0.0.0/8", "10.1.0.0/16"));
+        assert!(!matches_host("10.0.0.0/16", "10.0.0.0/8"));
+        assert!(!matches_host("10.0.0.0/8", "11.0.0.1"));
+        assert!(matches_host("0.0.0.0/0", "192.168.0.1"));
+        assert!(matches_host("2001:db8::/32", "2001:db8::1"));
+        assert!(!matches_host("2001:db8::/32", "10.0.0.1"));
+        assert!(matches_host("fe80::/10", "fe80::1%eth0"));
+
+        let b = blackout(
+            r#"
+            start = "09:00"
+            end = "17:00"
+            hosts = ["192.168.0.0/24"]
+            "#,
+        );
+        let mut scan = Scan::default();
+        scan.target.hosts = vec!["10.0.0.1".to_string()];
+        assert!(!b.applies_to(&scan));
+        scan.target.hosts.push("192.168.0.10".to_string());
+        assert!(b.applies_to(&scan));
+    }
+}
@@ -0,0 +1,194 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Signed archive of an entire scan to hand it over to another instance.
+//!
+//! A bundle contains the definition of a scan with masked credentials, its status, findings,
+//! log messages, annotations, the knowledge bases of its hosts and the feed version it ran with.
+//! It is a gzip compressed file consisting of the [FORMAT] line, the hex encoded HMAC-SHA256 of
+//! the JSON content as second line and the JSON content itself. Bundles are signed and verified
+//! with the configured bundle key, so that only instances sharing the key accept each other's
+//! bundles.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use hmac::{Hmac, Mac};
+use scannerlib::{
+    models::{self, Annotation, Scan, Status},
+    storage::KbSnapshot,
+};
+use sha2::Sha256;
+
+/// First line of each bundle
+pub const FORMAT: &str = "openvasd-bundle/1";
+/// Bundles are not decompressed beyond this size
+pub const MAX_BUNDLE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("not a scan bundle")]
+    Format,
+    #[error("invalid signature")]
+    Signature,
+    #[error("unable to read bundle: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid bundle content: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Everything known about a scan
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Content {
+    /// Definition of the scan, the passwords of its credentials are masked
+    pub scan: Scan,
+    pub status: Status,
+    /// Alarms of the scan
+    pub results: Vec<models::Result>,
+    /// All other results, e.g. log and error messages
+    pub logs: Vec<models::Result>,
+    pub annotations: Vec<Annotation>,
+    /// Knowledge bases by host
+    pub kb: BTreeMap<String, KbSnapshot>,
+    /// Version of the feed of the exporting instance
+    pub feed_version: String,
+    /// Unix timestamp of the export
+    pub exported: u64,
+}
+
+impl Content {
+    /// Returns the findings and log messages ordered by their id.
+    pub fn all_results(&self) -> Vec<models::Result> {
+        let mut results: Vec<_> = self.results.iter().chain(&self.logs).cloned().collect();
+        results.sort_by_key(|x| x.id);
+        results
+    }
+}
+
+fn signature(key: &str, content: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("any key length");
+    mac.update(content);
+    mac
+}
+
+/// Signs the content with the key and returns the bundle.
+pub fn seal(content: &Content, key: &str) -> Result<Vec<u8>, Error> {
+    let json = serde_json::to_vec(content)?;
+    let signature = hex::encode(signature(key, &json).finalize().into_bytes());
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    writeln!(encoder, "{FORMAT}")?;
+    writeln!(encoder, "{signature}")?;
+    encoder.write_all(&json)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses the bundle, bundles exceeding the limit when decompressed are refused.
+fn decode(bundle: &[u8], limit: u64) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(bundle)
+        .take(limit + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|_| Error::Format)?;
+    if decoded.len() as u64 > limit {
+        return Err(Error::Format);
+    }
+    Ok(decoded)
+}
+
+/// Verifies the signature of the bundle with the key and returns its content.
+///
+/// The bundle is decompressed up to [MAX_BUNDLE_SIZE] before the signature is verified.
+pub fn open(bundle: &[u8], key: &str) -> Result<Content, Error> {
+    let decoded = decode(bundle, MAX_BUNDLE_SIZE)?;
+    let mut parts = decoded.splitn(3, |x| *x == b'\n');
+    if parts.next() != Some(FORMAT.as_bytes()) {
+        return Err(Error::Format);
+    }
+    let expected = parts
+        .next()
+        .and_then(|x| hex::decode(x).ok())
+        .ok_or(Error::Format)?;
+    let json = parts.next().ok_or(Error::Format)?;
+    signature(key, json)
+        .verify_slice(&expected)
+        .map_err(|_| Error::Signature)?;
+    Ok(serde_json::from_slice(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use scannerlib::models::{self, Phase, ResultType, Scan, Status};
+
+    use super::{decode, open, seal, Content, Error};
+
+    fn content() -> Content {
+        Content {
+            scan: Scan {
+                scan_id: "exported".to_string(),
+                ..Default::default()
+            },
+            status: Status {
+                status: Phase::Succeeded,
+                ..Default::default()
+            },
+            results: vec![models::Result {
+                id: 1,
+                r_type: ResultType::Alarm,
+                ..Default::default()
+            }],
+            logs: vec![models::Result {
+                id: 0,
+                r_type: ResultType::Log,
+                ..Default::default()
+            }],
+            feed_version: "202401010000".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bundle = seal(&content(), "bundle-key").unwrap();
+        let opened = open(&bundle, "bundle-key").unwrap();
+        assert_eq!(opened, content());
+        let ids: Vec<_> = opened.all_results().iter().map(|x| x.id).collect();
+        assert_eq!(ids, vec![0, 1]);
+    }
+
+    #[test]
+    fn rejects_other_keys_and_tampering() {
+        let bundle = seal(&content(), "bundle-key").unwrap();
+        assert!(matches!(open(&bundle, "other"), Err(Error::Signature)));
+        assert!(matches!(
+            open(b"no bundle", "bundle-key"),
+            Err(Error::Format)
+        ));
+
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&bundle[..]), &mut decoded)
+            .unwrap();
+        let tampered = String::from_utf8(decoded)
+            .unwrap()
+            .replace("202401010000", "202501010000");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(tampered.as_bytes()).unwrap();
+        let tampered = encoder.finish().unwrap();
+        assert!(matches!(
+            open(&tampered, "bundle-key"),
+            Err(Error::Signature)
+        ));
+    }
+
+    #[test]
+    fn decompression_limit() {
+        let bundle = seal(&content(), "bundle-key").unwrap();
+        let size = decode(&bundle, u64::MAX - 1).unwrap().len() as u64;
+        assert!(decode(&bundle, size).is_ok());
+        assert!(matches!(decode(&bundle, size - 1), Err(Error::Format)));
+    }
+}
@@ -0,0 +1,930 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Distributes scans to worker openvasd instances.
+//!
+//! The cluster scanner type accepts scans via the API as usual and starts each of them on the
+//! live and compatible worker with the lowest load relative to the maximum of scans it may run. A
+//! scan stays queued while no such worker is able to run another scan. The status and new results
+//! of a running scan are fetched from its worker, once the scan is done it is removed from the
+//! worker.
+//!
+//! Workers are either configured or register themselves at `/cluster/register` with the
+//! registration token of the controller. A configured worker is live while its `/health/ready`
+//! endpoint succeeds; it is checked again after the health check interval. A registered worker
+//! is live while its heartbeats, which are repeated registrations, arrive within the heartbeat
+//! timeout. Any worker is considered dead as soon as a request to it fails.
+//!
+//! Registered workers advertise their capabilities. They are compatible when their feed version
+//! matches the one of the controller, they provide the required builtins and, for scans using
+//! the ARP alive test, they are able to open raw sockets.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use reqwest::{Method, StatusCode};
+use scannerlib::{
+    models::{
+        self,
+        scanner::{
+            Error, ScanDeleter, ScanPlanner, ScanResultFetcher, ScanResults, ScanStarter,
+            ScanStopper,
+        },
+        Action, AliveTestMethods, Phase, Scan, ScanAction, ScanPlan, Status,
+    },
+    redact,
+};
+
+use crate::{config, controller::Context};
+
+/// Capabilities a worker advertises on registration
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Version of the loaded feed
+    pub feed_version: Option<String>,
+    /// Optional builtin function sets, e.g. `ssh`
+    pub builtins: Vec<String>,
+    /// Whether the worker is allowed to open raw sockets
+    pub raw_sockets: bool,
+}
+
+/// Registration and heartbeat of a worker
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Registration {
+    /// Base URL the controller reaches the worker at
+    pub url: String,
+    /// API key of the worker
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_max_scans")]
+    pub max_scans: usize,
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+fn default_max_scans() -> usize {
+    1
+}
+
+/// State of a worker as listed by `/cluster/workers`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WorkerStatus {
+    pub url: String,
+    pub max_scans: usize,
+    /// Amount of scans running on the worker
+    pub running: usize,
+    pub live: bool,
+    /// Whether the worker registered itself
+    pub registered: bool,
+    /// Seconds since the last heartbeat of a registered worker
+    pub last_heartbeat: Option<u64>,
+    /// Advertised capabilities of a registered worker
+    pub capabilities: Option<Capabilities>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    healthy: bool,
+    checked: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Worker {
+    config: config::Worker,
+    health: Health,
+    /// Capabilities of a registered worker
+    capabilities: Option<Capabilities>,
+    /// Last heartbeat of a registered worker
+    heartbeat: Option<Instant>,
+    /// Masks the API key of a registered worker
+    _redaction: redact::Registration,
+}
+
+impl Worker {
+    fn new(config: config::Worker) -> Self {
+        Self {
+            config,
+            health: Health::default(),
+            capabilities: None,
+            heartbeat: None,
+            _redaction: redact::Registration::default(),
+        }
+    }
+
+    fn url(&self) -> &str {
+        self.config.url.trim_end_matches('/')
+    }
+}
+
+/// Scan running on a worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Assignment {
+    worker: usize,
+    /// Amount of results already fetched from the worker
+    fetched: usize,
+}
+
+fn connection_error(e: impl ToString) -> Error {
+    Error::Connection(e.to_string())
+}
+
+/// Returns the response when its status is successful.
+fn successful(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(Error::Unexpected(format!(
+            "worker responded to {} with {status}",
+            response.url().path()
+        )))
+    }
+}
+
+/// Workers of the cluster and the scans running on them
+///
+/// It is shared between the scanner and the endpoints workers register at.
+#[derive(Debug)]
+pub struct Registry {
+    config: config::Cluster,
+    // workers are never removed, so that their index stays valid for assignments
+    workers: RwLock<Vec<Worker>>,
+    assigned: RwLock<HashMap<String, Assignment>>,
+    feed_version: OnceLock<Arc<RwLock<String>>>,
+}
+
+impl Registry {
+    fn new(config: config::Cluster) -> Self {
+        Self {
+            workers: RwLock::new(config.workers.iter().cloned().map(Worker::new).collect()),
+            config,
+            assigned: RwLock::new(HashMap::new()),
+            feed_version: OnceLock::new(),
+        }
+    }
+
+    /// Sets the feed version of the controller workers have to match.
+    pub fn set_feed_version(&self, version: Arc<RwLock<String>>) {
+        let _ = self.feed_version.set(version);
+    }
+
+    /// Returns the token workers have to present to register.
+    pub fn registration_token(&self) -> Option<&str> {
+        self.config.registration_token.as_deref()
+    }
+
+    /// Adds a new worker or renews the heartbeat of a known one.
+    pub fn register(&self, registration: Registration) {
+        let Registration {
+            url,
+            api_key,
+            max_scans,
+            capabilities,
+        } = registration;
+        let url = url.trim_end_matches('/');
+        let mut workers = self.workers.write().unwrap();
+        let index = match workers.iter().position(|x| x.url() == url) {
+            Some(index) => index,
+            None => {
+                tracing::info!(url, ?capabilities, "worker registered");
+                workers.push(Worker::new(config::Worker {
+                    url: url.to_string(),
+                    api_key: None,
+                    max_scans,
+                }));
+                workers.len() - 1
+            }
+        };
+        let worker = &mut workers[index];
+        if worker.config.api_key != api_key {
+            worker._redaction = redact::register(api_key.as_deref());
+            worker.config.api_key = api_key;
+        }
+        if !worker.health.healthy && worker.health.checked.is_some() {
+            tracing::info!(url, "worker is live");
+        }
+        worker.config.max_scans = max_scans;
+        worker.capabilities = Some(capabilities);
+        worker.heartbeat = Some(Instant::now());
+        worker.health = Health {
+            healthy: true,
+            checked: worker.heartbeat,
+        };
+    }
+
+    /// Returns the state of each worker.
+    pub fn workers(&self) -> Vec<WorkerStatus> {
+        let running = self.running();
+        self.workers
+            .read()
+            .unwrap()
+            .iter()
+            .zip(running)
+            .map(|(worker, running)| WorkerStatus {
+                url: worker.url().to_string(),
+                max_scans: worker.config.max_scans,
+                running,
+                live: self.live(worker),
+                registered: worker.heartbeat.is_some(),
+                last_heartbeat: worker.heartbeat.map(|x| x.elapsed().as_secs()),
+                capabilities: worker.capabilities.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the amount of scans assigned to each worker.
+    fn running(&self) -> Vec<usize> {
+        let assigned = self.assigned.read().unwrap();
+        self.running_of(&assigned)
+    }
+
+    fn running_of(&self, assigned: &HashMap<String, Assignment>) -> Vec<usize> {
+        let mut running = vec![0; self.workers.read().unwrap().len()];
+        for assignment in assigned.values() {
+            running[assignment.worker] += 1;
+        }
+        running
+    }
+
+    fn live(&self, worker: &Worker) -> bool {
+        worker.health.healthy
+            && worker
+                .heartbeat
+                .is_none_or(|x| x.elapsed() < self.config.heartbeat_timeout)
+    }
+
+    /// Returns true when the worker is able to run the scan.
+    ///
+    /// Configured workers do not advertise capabilities and are always compatible.
+    fn compatible(&self, worker: &Worker, scan: &Scan) -> bool {
+        let Some(capabilities) = &worker.capabilities else {
+            return true;
+        };
+        let controller_feed = self
+            .feed_version
+            .get()
+            .map(|x| x.read().unwrap().clone())
+            .filter(|x| x != "UNDEFINED");
+        if let Some(feed_version) = controller_feed {
+            if capabilities.feed_version.as_ref() != Some(&feed_version) {
+                return false;
+            }
+        }
+        if !self
+            .config
+            .required_builtins
+            .iter()
+            .all(|x| capabilities.builtins.contains(x))
+        {
+            return false;
+        }
+        let arp = scan
+            .target
+            .alive_test_methods
+            .contains(&AliveTestMethods::Arp);
+        !arp || capabilities.raw_sockets
+    }
+
+    fn url(&self, worker: usize) -> String {
+        self.workers.read().unwrap()[worker].url().to_string()
+    }
+
+    fn set_health(&self, worker: usize, healthy: bool) {
+        let mut workers = self.workers.write().unwrap();
+        let worker = &mut workers[worker];
+        if worker.health.healthy != healthy {
+            let url = worker.url();
+            if healthy {
+                tracing::info!(url, "worker is healthy");
+            } else if worker.health.checked.is_some() {
+                tracing::warn!(url, "worker is unhealthy");
+            }
+        }
+        worker.health.healthy = healthy;
+        worker.health.checked = Some(Instant::now());
+    }
+
+    /// Returns the live and compatible worker with the lowest load that is able to run another
+    /// scan.
+    ///
+    /// The load is the amount of assigned scans relative to the maximum of the worker, on equal
+    /// load the worker known first is used.
+    fn place(&self, assigned: &HashMap<String, Assignment>, scan: &Scan) -> Option<usize> {
+        let running = self.running_of(assigned);
+        let workers = self.workers.read().unwrap();
+        (0..workers.len())
+            .filter(|&x| {
+                let worker = &workers[x];
+                self.live(worker)
+                    && running[x] < worker.config.max_scans
+                    && self.compatible(worker, scan)
+            })
+            .min_by(|&a, &b| {
+                (running[a] * workers[b].config.max_scans)
+                    .cmp(&(running[b] * workers[a].config.max_scans))
+            })
+    }
+
+    fn assignment(&self, id: &str) -> Option<Assignment> {
+        self.assigned.read().unwrap().get(id).copied()
+    }
+}
+
+/// Scanner running the scans on worker openvasd instances
+pub struct Scanner {
+    client: reqwest::Client,
+    registry: Arc<Registry>,
+}
+
+impl Scanner {
+    pub fn new(config: config::Cluster) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            registry: Arc::new(Registry::new(config)),
+        }
+    }
+
+    /// Returns the registry of the workers.
+    pub fn registry(&self) -> Arc<Registry> {
+        self.registry.clone()
+    }
+
+    fn url(&self, worker: usize) -> String {
+        self.registry.url(worker)
+    }
+
+    async fn send<T>(
+        &self,
+        worker: usize,
+        method: Method,
+        path: &str,
+        body: Option<&T>,
+    ) -> Result<reqwest::Response, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let (url, api_key) = {
+            let workers = self.registry.workers.read().unwrap();
+            let worker = &workers[worker];
+            (worker.url().to_string(), worker.config.api_key.clone())
+        };
+        let mut request = self.client.request(method, format!("{url}{path}"));
+        if let Some(key) = &api_key {
+            request = request.header("x-api-key", key);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        request.send().await.map_err(|e| {
+            self.registry.set_health(worker, false);
+            connection_error(e)
+        })
+    }
+
+    /// Checks the health of each configured worker whose last check is older than the health
+    /// check interval.
+    ///
+    /// Registered workers are not polled, their heartbeats renew their health.
+    async fn check_health(&self) {
+        let interval = self.registry.config.health_check_interval;
+        let due: Vec<usize> = {
+            let workers = self.registry.workers.read().unwrap();
+            (0..workers.len())
+                .filter(|&x| {
+                    workers[x].heartbeat.is_none()
+                        && workers[x]
+                            .health
+                            .checked
+                            .is_none_or(|x| x.elapsed() >= interval)
+                })
+                .collect()
+        };
+        let checks = due.into_iter().map(|worker| async move {
+            let healthy = self
+                .send::<()>(worker, Method::GET, "/health/ready", None)
+                .await
+                .is_ok_and(|x| x.status().is_success());
+            self.registry.set_health(worker, healthy);
+        });
+        futures::future::join_all(checks).await;
+    }
+
+    /// Removes the scan from its worker, failures are only logged as the scan is not needed
+    /// anymore.
+    async fn remove_from_worker(&self, worker: usize, id: &str) {
+        let path = format!("/scans/{id}");
+        match self.send::<()>(worker, Method::DELETE, &path, None).await {
+            Ok(x) if x.status().is_success() || x.status() == StatusCode::NOT_FOUND => {}
+            Ok(x) => {
+                tracing::warn!(scan_id = id, url = self.url(worker), status = %x.status(), "unable to remove scan from worker")
+            }
+            Err(e) => {
+                tracing::warn!(scan_id = id, url = self.url(worker), %e, "unable to remove scan from worker")
+            }
+        }
+    }
+
+    async fn start_on(&self, worker: usize, scan: &Scan) -> Result<(), Error> {
+        successful(
+            self.send(worker, Method::POST, "/scans", Some(scan))
+                .await?,
+        )?;
+        let action = ScanAction {
+            action: Action::Start,
+        };
+        let path = format!("/scans/{}", scan.scan_id);
+        let started = match self.send(worker, Method::POST, &path, Some(&action)).await {
+            Ok(x) => successful(x).map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if started.is_err() {
+            self.remove_from_worker(worker, &scan.scan_id).await;
+        }
+        started
+    }
+}
+
+#[async_trait]
+impl ScanStarter for Scanner {
+    async fn start_scan(&self, scan: Scan) -> Result<(), Error> {
+        self.check_health().await;
+        let id = scan.scan_id.clone();
+        let worker = {
+            let mut assigned = self.registry.assigned.write().unwrap();
+            let Some(worker) = self.registry.place(&assigned, &scan) else {
+                return Err(Error::SchedulingError {
+                    id,
+                    reason: "no compatible worker is able to run another scan".to_string(),
+                });
+            };
+            assigned.insert(id.clone(), Assignment { worker, fetched: 0 });
+            worker
+        };
+        match self.start_on(worker, &scan).await {
+            Ok(()) => {
+                tracing::info!(
+                    scan_id = id,
+                    url = self.url(worker),
+                    "started scan on worker"
+                );
+                Ok(())
+            }
+            Err(e) => {
+                self.registry.assigned.write().unwrap().remove(&id);
+                Err(e)
+            }
+        }
+    }
+
+    async fn can_start_scan(&self, scan: &Scan) -> bool {
+        self.check_health().await;
+        let assigned = self.registry.assigned.read().unwrap();
+        self.registry.place(&assigned, scan).is_some()
+    }
+}
+
+#[async_trait]
+impl ScanStopper for Scanner {
+    async fn stop_scan<I>(&self, id: I) -> Result<(), Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        let id = id.as_ref();
+        let Some(assignment) = self.registry.assignment(id) else {
+            return Ok(());
+        };
+        let action = ScanAction {
+            action: Action::Stop,
+        };
+        let path = format!("/scans/{id}");
+        let response = self
+            .send(assignment.worker, Method::POST, &path, Some(&action))
+            .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            successful(response)?;
+        }
+        self.registry.assigned.write().unwrap().remove(id);
+        self.remove_from_worker(assignment.worker, id).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScanDeleter for Scanner {
+    async fn delete_scan<I>(&self, id: I) -> Result<(), Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        let id = id.as_ref();
+        let assignment = self.registry.assigned.write().unwrap().remove(id);
+        if let Some(assignment) = assignment {
+            self.remove_from_worker(assignment.worker, id).await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScanResultFetcher for Scanner {
+    async fn fetch_results<I>(&self, id: I) -> Result<ScanResults, Error>
+    where
+        I: AsRef<str> + Send + 'static,
+    {
+        let id = id.as_ref().to_string();
+        let Some(assignment) = self.registry.assignment(&id) else {
+            return Err(Error::ScanNotFound(id));
+        };
+        let worker = assignment.worker;
+        // the worker stores the results before the status, so that all results of a finished
+        // scan are fetched
+        let path = format!("/scans/{id}/status");
+        let response = self.send::<()>(worker, Method::GET, &path, None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            tracing::warn!(scan_id = id, url = self.url(worker), "worker lost the scan");
+            self.registry.assigned.write().unwrap().remove(&id);
+            return Ok(ScanResults {
+                id,
+                status: Status {
+                    status: Phase::Failed,
+                    ..Default::default()
+                },
+                results: vec![],
+            });
+        }
+        let status: Status = successful(response)?
+            .json()
+            .await
+            .map_err(connection_error)?;
+        let path = format!("/scans/{id}/results?range={}-", assignment.fetched);
+        let results: Vec<models::Result> =
+            successful(self.send::<()>(worker, Method::GET, &path, None).await?)?
+                .json()
+                .await
+                .map_err(connection_error)?;
+        if status.is_done() {
+            self.registry.assigned.write().unwrap().remove(&id);
+            self.remove_from_worker(worker, &id).await;
+        } else if let Some(x) = self.registry.assigned.write().unwrap().get_mut(&id) {
+            x.fetched += results.len();
+        }
+        Ok(ScanResults {
+            id,
+            status,
+            results,
+        })
+    }
+}
+
+#[async_trait]
+impl ScanPlanner for Scanner {
+    async fn plan_scan(&self, scan: &Scan) -> Result<ScanPlan, Error> {
+        self.check_health().await;
+        let worker = {
+            let workers = self.registry.workers.read().unwrap();
+            (0..workers.len()).find(|&x| {
+                self.registry.live(&workers[x]) && self.registry.compatible(&workers[x], scan)
+            })
+        }
+        .ok_or_else(|| Error::Connection("no compatible worker is live".to_string()))?;
+        // a temporary scan, so that a scan running on the worker is not affected
+        let mut temporary = scan.clone();
+        temporary.scan_id = uuid::Uuid::new_v4().to_string();
+        successful(
+            self.send(worker, Method::POST, "/scans", Some(&temporary))
+                .await?,
+        )?;
+        let path = format!("/scans/{}/plan", temporary.scan_id);
+        let plan = match self.send::<()>(worker, Method::GET, &path, None).await {
+            Ok(x) => match successful(x) {
+                Ok(x) => x.json::<ScanPlan>().await.map_err(connection_error),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        self.remove_from_worker(worker, &temporary.scan_id).await;
+        let mut plan = plan?;
+        plan.scan_id.clone_from(&scan.scan_id);
+        Ok(plan)
+    }
+}
+
+/// Registers this openvasd as worker at the controller and repeats it as heartbeat.
+///
+/// This loop should be run as background task when a controller is configured.
+pub async fn heartbeat<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    config: config::Controller,
+    api_key: Option<String>,
+) {
+    let Some(controller) = config.url.as_deref() else {
+        return;
+    };
+    let Some(url) = config.advertise_url.clone() else {
+        tracing::warn!(
+            controller,
+            "No advertise URL configured, unable to register at the controller"
+        );
+        return;
+    };
+    let endpoint = format!("{}/cluster/register", controller.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(config.heartbeat_interval)
+        .build()
+        .unwrap_or_default();
+    let mut registered = false;
+    tracing::debug!(controller, "Starting heartbeats");
+    let mut interval = tokio::time::interval(config.heartbeat_interval);
+    loop {
+        interval.tick().await;
+        if *ctx.abort.read().unwrap() {
+            tracing::trace!("aborting");
+            break;
+        }
+        let feed_version = ctx.scheduler.feed_version().read().unwrap().clone();
+        let registration = Registration {
+            url: url.clone(),
+            api_key: api_key.clone(),
+            max_scans: config.max_scans,
+            capabilities: Capabilities {
+                feed_version: Some(feed_version).filter(|x| x != "UNDEFINED"),
+                builtins: scannerlib::nasl::enabled_builtins()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                raw_sockets: scannerlib::nasl::raw_sockets_available(),
+            },
+        };
+        let mut request = client.post(&endpoint).json(&registration);
+        if let Some(token) = &config.registration_token {
+            request = request.header("x-registration-token", token);
+        }
+        match request.send().await.map(|x| x.status()) {
+            Ok(status) if status.is_success() => {
+                if !registered {
+                    tracing::info!(controller, "Registered at the controller");
+                }
+                registered = true;
+            }
+            Ok(status) => {
+                tracing::warn!(controller, %status, "Unable to register at the controller");
+                registered = false;
+            }
+            Err(e) => {
+                tracing::warn!(controller, %e, "Unable to register at the controller");
+                registered = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use scannerlib::models::{
+        scanner::{ScanResultFetcher, ScanStarter, ScanStopper},
+        AliveTestMethods, Phase, Scan,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{Capabilities, Registration, Registry, Scanner};
+    use crate::config;
+
+    /// Requests received by a worker as method and path
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Serves a minimal worker answering with the responses of its routes.
+    ///
+    /// Routes are given as `METHOD /path` and answered with the status and JSON body, unknown
+    /// routes are answered with 404.
+    async fn worker(routes: HashMap<String, (u16, String)>) -> (String, Requests) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = vec![0; 64 * 1024];
+                let mut read = 0;
+                // reads the head and the body announced by content-length
+                loop {
+                    let n = stream.read(&mut buffer[read..]).await.unwrap();
+                    read += n;
+                    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                    if let Some((head, body)) = request.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|x| {
+                                let (key, value) = x.split_once(':')?;
+                                key.eq_ignore_ascii_case("content-length")
+                                    .then(|| value.trim().parse::<usize>().ok())?
+                            })
+                            .unwrap_or_default();
+                        if body.len() >= length || n == 0 {
+                            let route = head
+                                .lines()
+                                .next()
+                                .unwrap_or_default()
+                                .rsplit_once(' ')
+                                .map(|x| x.0.to_string())
+                                .unwrap_or_default();
+                            received.lock().unwrap().push(route.clone());
+                            let (status, body) =
+                                routes.get(&route).cloned().unwrap_or((404, String::new()));
+                            let response = format!(
+                                "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                                body.len()
+                            );
+                            stream.write_all(response.as_bytes()).await.unwrap();
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    fn cluster(workers: &[(&str, usize)]) -> Scanner {
+        Scanner::new(config::Cluster {
+            workers: workers
+                .iter()
+                .map(|(url, max_scans)| config::Worker {
+                    url: url.to_string(),
+                    api_key: None,
+                    max_scans: *max_scans,
+                })
+                .collect(),
+            health_check_interval: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(5),
+            ..Default::default()
+        })
+    }
+
+    fn place(registry: &Registry, scan: &Scan) -> Option<usize> {
+        registry.place(&registry.assigned.read().unwrap(), scan)
+    }
+
+    fn scan(id: &str) -> Scan {
+        Scan {
+            scan_id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn routes(routes: &[(&str, u16, &str)]) -> HashMap<String, (u16, String)> {
+        routes
+            .iter()
+            .map(|(route, status, body)| (route.to_string(), (*status, body.to_string())))
+            .collect()
+    }
+
+    /// Routes of a worker running the scan with the status
+    fn running(id: &str, status: &str) -> HashMap<String, (u16, String)> {
+        let mut routes = routes(&[
+            ("GET /health/ready", 200, ""),
+            ("POST /scans", 201, &format!("\"{id}\"")),
+            (
+                &format!("GET /scans/{id}/results?range=0-"),
+                200,
+                r#"[{"id":0,"type":"alarm","message":"found"}]"#,
+            ),
+        ]);
+        routes.insert(format!("POST /scans/{id}"), (204, String::new()));
+        routes.insert(format!("DELETE /scans/{id}"), (204, String::new()));
+        routes.insert(format!("GET /scans/{id}/status"), (200, status.to_string()));
+        routes
+    }
+
+    #[tokio::test]
+    async fn places_scans_by_load_and_health() {
+        let (first, _) = worker(running("a", "{}")).await;
+        let (second, _) = worker(running("b", "{}")).await;
+        let scanner = cluster(&[
+            (&first, 2),
+            (&second, 1),
+            // nothing is listening, so it is never healthy
+            ("http://127.0.0.1:1", 10),
+        ]);
+        assert!(scanner.can_start_scan(&scan("a")).await);
+        scanner.start_scan(scan("a")).await.unwrap();
+        scanner.start_scan(scan("b")).await.unwrap();
+        let registry = scanner.registry();
+        let worker_of = |id: &str| registry.assignment(id).unwrap().worker;
+        assert_eq!((worker_of("a"), worker_of("b")), (0, 1));
+        // the first worker is only half loaded
+        assert_eq!(place(&registry, &scan("c")), Some(0));
+        registry.assigned.write().unwrap().insert(
+            "c".to_string(),
+            super::Assignment {
+                worker: 0,
+                fetched: 0,
+            },
+        );
+        assert!(!scanner.can_start_scan(&scan("d")).await);
+        assert!(scanner.start_scan(scan("d")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fetches_results_until_done() {
+        let (url, requests) = worker(running(
+            "a",
+            r#"{"start_time":1,"end_time":2,"status":"succeeded"}"#,
+        ))
+        .await;
+        let scanner = cluster(&[(&url, 1)]);
+        scanner.start_scan(scan("a")).await.unwrap();
+        let results = scanner.fetch_results("a").await.unwrap();
+        assert_eq!(results.status.status, Phase::Succeeded);
+        assert_eq!(results.results.len(), 1);
+        // the finished scan is removed from the worker and frees its capacity
+        assert!(scanner.registry().assignment("a").is_none());
+        assert!(requests
+            .lock()
+            .unwrap()
+            .contains(&"DELETE /scans/a".to_string()));
+        assert!(scanner.fetch_results("a").await.is_err());
+        // unknown scans are stopped on no worker
+        scanner.stop_scan("unknown").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lost_scan_fails() {
+        let (url, _) = worker(routes(&[
+            ("GET /health/ready", 200, ""),
+            ("POST /scans", 201, "\"a\""),
+            ("POST /scans/a", 204, ""),
+        ]))
+        .await;
+        let scanner = cluster(&[(&url, 1)]);
+        scanner.start_scan(scan("a")).await.unwrap();
+        let results = scanner.fetch_results("a").await.unwrap();
+        assert_eq!(results.status.status, Phase::Failed);
+        assert!(scanner.can_start_scan(&scan("b")).await);
+    }
+
+    fn registration(url: &str, feed_version: &str, builtins: &[&str], raw: bool) -> Registration {
+        Registration {
+            url: url.to_string(),
+            api_key: Some("w0rker-key".to_string()),
+            max_scans: 2,
+            capabilities: Capabilities {
+                feed_version: Some(feed_version.to_string()),
+                builtins: builtins.iter().map(|x| x.to_string()).collect(),
+                raw_sockets: raw,
+            },
+        }
+    }
+
+    #[test]
+    fn places_scans_on_compatible_workers() {
+        let registry = Registry::new(config::Cluster {
+            required_builtins: vec!["ssh".to_string()],
+            ..Default::default()
+        });
+        registry.set_feed_version(Arc::new(std::sync::RwLock::new("202401".to_string())));
+        registry.register(registration("http://outdated", "202312", &["ssh"], true));
+        registry.register(registration("http://no-ssh", "202401", &[], true));
+        registry.register(registration("http://no-raw", "202401", &["ssh"], false));
+        registry.register(registration("http://raw", "202401", &["ssh"], true));
+        assert_eq!(place(&registry, &scan("a")), Some(2));
+        let mut arp = scan("b");
+        arp.target.alive_test_methods = vec![AliveTestMethods::Arp];
+        assert_eq!(place(&registry, &arp), Some(3));
+
+        // heartbeats update known workers
+        let mut heartbeat = registration("http://raw/", "202401", &["ssh"], true);
+        heartbeat.max_scans = 5;
+        registry.register(heartbeat);
+        let workers = registry.workers();
+        assert_eq!(workers.len(), 4);
+        assert_eq!(workers[3].max_scans, 5);
+        assert!(workers.iter().all(|x| x.live && x.registered));
+        assert_eq!(workers[3].last_heartbeat, Some(0));
+    }
+
+    #[test]
+    fn missing_heartbeats() {
+        let registry = Registry::new(config::Cluster {
+            heartbeat_timeout: Duration::ZERO,
+            ..Default::default()
+        });
+        registry.register(registration("http://late", "202401", &[], false));
+        assert_eq!(place(&registry, &scan("a")), None);
+        assert!(!registry.workers()[0].live);
+    }
+}
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     net::SocketAddr,
     path::PathBuf,
@@ -15,8 +16,138 @@ use serde::{Deserialize, Serialize};
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Feed {
     pub path: PathBuf,
+    /// Directories stacked on top of the feed path, files of a later overlay take precedence
+    #[serde(default)]
+    pub overlays: Vec<PathBuf>,
     pub check_interval: Duration,
     pub signature_check: bool,
+    /// When a detected feed change is applied while scans are running
+    #[serde(default)]
+    pub update_policy: FeedUpdatePolicy,
+    /// Minimum time between two feed updates applied while scans are running when using the
+    /// window policy
+    #[serde(default = "default_update_window")]
+    pub update_window: Duration,
+}
+
+fn default_update_window() -> Duration {
+    Duration::from_secs(6 * 3600)
+}
+
+/// Decides when a detected feed change is applied.
+///
+/// A feed update blocks new scans until the running ones are finished and reloads the whole
+/// storage, deferring it keeps a busy scanner from stalling.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeedUpdatePolicy {
+    /// Applies the update as soon as it is detected
+    #[default]
+    #[serde(rename = "immediate")]
+    Immediate,
+    /// Defers the update until no scan is running
+    #[serde(rename = "idle_only")]
+    IdleOnly,
+    /// Defers the update while scans are running unless the last update is longer ago than
+    /// the update window
+    #[serde(rename = "window")]
+    Window,
+}
+
+impl TypedValueParser for FeedUpdatePolicy {
+    type Value = Self;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        Ok(match value.to_str().unwrap_or_default() {
+            "immediate" => Self::Immediate,
+            "idle_only" => Self::IdleOnly,
+            "window" => Self::Window,
+            x => {
+                let mut cmd = cmd.clone();
+                let err = cmd.error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("`{x}` is not a feed update policy."),
+                );
+                return Err(err);
+            }
+        })
+    }
+}
+
+/// Decides what happens with new scans while resources are below their thresholds.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResourcePolicy {
+    /// Accepts the scan but keeps it queued until the resources are available again
+    #[default]
+    #[serde(rename = "queue")]
+    Queue,
+    /// Refuses to start the scan with an error
+    #[serde(rename = "refuse")]
+    Refuse,
+}
+
+impl TypedValueParser for ResourcePolicy {
+    type Value = Self;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        Ok(match value.to_str().unwrap_or_default() {
+            "queue" => Self::Queue,
+            "refuse" => Self::Refuse,
+            x => {
+                let mut cmd = cmd.clone();
+                let err = cmd.error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("`{x}` is not a resource policy."),
+                );
+                return Err(err);
+            }
+        })
+    }
+}
+
+/// Decides what happens with running scans that do not make any progress.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StallPolicy {
+    /// Flags the scan as stalled within its status but keeps it running
+    #[default]
+    #[serde(rename = "flag")]
+    Flag,
+    /// Flags the scan as stalled, stops it and sets it to failed
+    #[serde(rename = "stop")]
+    Stop,
+}
+
+impl TypedValueParser for StallPolicy {
+    type Value = Self;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        Ok(match value.to_str().unwrap_or_default() {
+            "flag" => Self::Flag,
+            "stop" => Self::Stop,
+            x => {
+                let mut cmd = cmd.clone();
+                let err = cmd.error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("`{x}` is not a stall policy."),
+                );
+                return Err(err);
+            }
+        })
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -25,6 +156,276 @@ pub struct Notus {
     pub advisories_path: PathBuf,
 }
 
+/// Rule based auto-triage of results as they are stored
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Triage {
+    /// TOML file containing the rules, results are not triaged when it is not set
+    pub rules: Option<PathBuf>,
+}
+
+/// Enrichment of results with information about the CVEs referenced by their VT
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Enrichment {
+    /// Directory containing the NVD and EPSS data, results are not enriched when it is not set
+    pub path: Option<PathBuf>,
+    /// How often the directory is synced and reloaded
+    pub sync_interval: Duration,
+    /// Url of the NVD CVE API, the directory is synced externally when it is not set
+    #[serde(default)]
+    pub nvd_url: Option<String>,
+    /// Api key of the NVD CVE API allowing a faster sync
+    #[serde(default)]
+    pub nvd_api_key: Option<String>,
+    /// Url of the EPSS scores as CSV file, may be gzip compressed
+    #[serde(default)]
+    pub epss_url: Option<String>,
+}
+
+impl Default for Enrichment {
+    fn default() -> Self {
+        Self {
+            path: None,
+            sync_interval: Duration::from_secs(24 * 3600),
+            nvd_url: None,
+            nvd_api_key: None,
+            epss_url: None,
+        }
+    }
+}
+
+/// Rendering of reports via /scans/{id}/report
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Report {
+    /// The tool printing PDF reports
+    #[serde(default)]
+    pub pdf: scannerlib::report::PdfConverter,
+    /// Organization, logo, color and footer shown in HTML and PDF reports
+    #[serde(default)]
+    pub branding: scannerlib::report::Branding,
+}
+
+/// Profiles mapping the severity of results to levels within served results and reports
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Severity {
+    /// Profile of scans that neither select a profile nor belong to a tenant with a profile, the
+    /// CVSS classes are used when it is not set
+    pub default_profile: Option<String>,
+    /// Profiles by name
+    pub profiles: BTreeMap<String, scannerlib::models::cvss::SeverityProfile>,
+}
+
+/// Export and import of scans as signed bundles
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Bundle {
+    /// Key bundles are signed and verified with, scans are neither exported nor imported when it
+    /// is not set
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// VTs a tenant may see and scan
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Tenant {
+    /// Families whose VTs are visible
+    pub families: Vec<String>,
+    /// OIDs of visible VTs in addition to the ones of the families
+    pub oids: Vec<String>,
+    /// Severity profile of the scans of the tenant
+    pub severity_profile: Option<String>,
+}
+
+/// Restricts the feed visible to the clients of a tenant
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Tenancy {
+    /// Restricts the VTs of clients belonging to a tenant when set
+    pub enabled: bool,
+    /// Tenant of clients whose token has no tenant claim and of clients authenticated by API key
+    /// or certificate, they see the whole feed when it is not set
+    pub default_tenant: Option<String>,
+    /// Feed subsets by tenant name, clients of a tenant that is not listed see no VT
+    pub tenants: BTreeMap<String, Tenant>,
+}
+
+/// Authentication via bearer tokens issued by an OpenID Connect provider
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Oidc {
+    /// Issuer of the tokens, bearer tokens are not accepted when it is not set
+    pub issuer: Option<String>,
+    /// Audience a token must be issued for
+    pub audience: Option<String>,
+    /// Url of the JSON web key set, it is discovered via the issuer when it is not set
+    pub jwks_url: Option<String>,
+    /// How often the JSON web key set is fetched
+    pub jwks_refresh_interval: Duration,
+    /// Scopes a token must contain
+    pub scopes: Vec<String>,
+    /// Claim containing the roles or groups of a client, nested claims are separated by dots
+    pub roles_claim: String,
+    /// Maps values of the roles claim to roles of openvasd, the highest role wins
+    pub roles: BTreeMap<String, crate::oidc::Role>,
+    /// Role of clients without a mapped role, their tokens are rejected when it is not set
+    pub default_role: Option<crate::oidc::Role>,
+    /// Claim containing the tenant of a client, nested claims are separated by dots
+    pub tenant_claim: String,
+}
+
+impl Default for Oidc {
+    fn default() -> Self {
+        Self {
+            issuer: None,
+            audience: None,
+            jwks_url: None,
+            jwks_refresh_interval: Duration::from_secs(3600),
+            scopes: vec![],
+            roles_claim: "roles".to_string(),
+            roles: BTreeMap::new(),
+            default_role: None,
+            tenant_claim: "tenant".to_string(),
+        }
+    }
+}
+
+/// Export of traces to an OpenTelemetry collector
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Telemetry {
+    /// Url of the OTLP/HTTP collector, traces are not exported when it is not set
+    pub otlp_endpoint: Option<String>,
+    /// Headers sent with each export, e.g. to authenticate at the collector
+    pub headers: BTreeMap<String, String>,
+    /// Name of the service the traces are reported for
+    pub service_name: String,
+    /// Filter of the exported spans in the format of RUST_LOG
+    pub filter: String,
+    /// Ratio of traces that are exported, traces continued from a client keep its decision
+    pub sample_ratio: f64,
+    /// How often closed spans are exported
+    pub export_interval: Duration,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            headers: BTreeMap::new(),
+            service_name: "openvasd".to_string(),
+            filter: "openvasd=info,scannerlib=info".to_string(),
+            sample_ratio: 1.0,
+            export_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Message queue the results and scan events are published to
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Broker {
+    /// NATS server, the url is in the format nats://[user:password@]host[:port]
+    #[default]
+    #[serde(rename = "nats")]
+    Nats,
+    /// Kafka via the REST proxy of Confluent, the url is the one of the proxy
+    #[serde(rename = "kafka")]
+    Kafka,
+}
+
+/// Serialization of the published messages
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PublishFormat {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    /// Avro single object encoding
+    #[serde(rename = "avro")]
+    Avro,
+}
+
+/// Publishing of results and scan events to a message queue
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Publisher {
+    /// Url of the message queue, nothing is published when it is not set
+    pub url: Option<String>,
+    pub broker: Broker,
+    pub format: PublishFormat,
+    /// Prefix of the subjects or topics, results are published to `<prefix>.results` and
+    /// events to `<prefix>.events`
+    pub prefix: String,
+    /// Headers sent with each request to the Kafka REST proxy
+    pub headers: BTreeMap<String, String>,
+    /// How often the scans are checked for changes of their status
+    pub interval: Duration,
+}
+
+impl Default for Publisher {
+    fn default() -> Self {
+        Self {
+            url: None,
+            broker: Broker::default(),
+            format: PublishFormat::default(),
+            prefix: "openvasd".to_string(),
+            headers: BTreeMap::new(),
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Vault of HashiCorp, secrets are read from its key value engine
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Vault {
+    /// Url of the vault, references to the vault are rejected when it is not set
+    pub address: Option<String>,
+    /// Token used to authenticate at the vault
+    pub token: Option<String>,
+    /// Namespace of the secrets, only supported by Vault Enterprise
+    pub namespace: Option<String>,
+    /// Path the key value engine is mounted at
+    pub mount: String,
+    /// Version of the key value engine, either 1 or 2
+    pub kv_version: u8,
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self {
+            address: None,
+            token: None,
+            namespace: None,
+            mount: "secret".to_string(),
+            kv_version: 2,
+        }
+    }
+}
+
+/// Resolution of credential references to secrets of a vault or a local secrets file
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Secrets {
+    /// How long a fetched secret is reused before it is fetched again
+    pub cache_ttl: Duration,
+    /// Path of the encrypted secrets file, references to it are rejected when it is not set
+    pub file: Option<PathBuf>,
+    /// Key the secrets file is encrypted with
+    pub file_key: Option<String>,
+    pub vault: Vault,
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Self {
+            cache_ttl: Duration::from_secs(300),
+            file: None,
+            file_key: None,
+            vault: Vault::default(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Redis {
     pub url: String,
@@ -38,6 +439,26 @@ pub struct Scheduler {
     pub max_running_scans: Option<usize>,
     #[serde(default)]
     pub min_free_mem: Option<u64>,
+    /// Disk space in bytes that must be free on each of the disk paths
+    #[serde(default)]
+    pub min_free_disk: Option<u64>,
+    /// Paths checked for free disk space, defaults to the storage path
+    #[serde(default)]
+    pub disk_paths: Vec<PathBuf>,
+    /// File descriptors that must be available to openvasd
+    #[serde(default)]
+    pub min_free_file_descriptors: Option<u64>,
+    #[serde(default)]
+    pub resource_policy: ResourcePolicy,
+    /// Time windows during which scans are held back
+    #[serde(default)]
+    pub blackouts: Vec<crate::blackout::Blackout>,
+    /// Time without any progress after which a running scan is considered stalled, stalled
+    /// scans are not detected when it is not set
+    #[serde(default)]
+    pub stall_timeout: Option<Duration>,
+    #[serde(default)]
+    pub stall_policy: StallPolicy,
     pub check_interval: Duration,
 }
 
@@ -48,6 +469,13 @@ impl Default for Scheduler {
             max_queued_scans: None,
             max_running_scans: None,
             min_free_mem: None,
+            min_free_disk: None,
+            disk_paths: vec![],
+            min_free_file_descriptors: None,
+            resource_policy: ResourcePolicy::Queue,
+            blackouts: vec![],
+            stall_timeout: None,
+            stall_policy: StallPolicy::Flag,
         }
     }
 }
@@ -58,6 +486,126 @@ pub struct Scanner {
     pub scanner_type: ScannerType,
     #[serde(default)]
     pub ospd: OspdWrapper,
+    #[serde(default)]
+    pub openvas: OpenvasWrapper,
+    /// Network namespaces a scan may select via the `network_namespace` scan preference
+    #[serde(default)]
+    pub network_namespaces: Vec<String>,
+    /// Shared objects defining additional builtin functions for the openvasd scanner type
+    #[serde(default)]
+    pub plugins: Plugins,
+    /// Timeout in seconds of each VT without script_timeout for scans not setting the
+    /// `plugins_timeout` preference
+    #[serde(default)]
+    pub plugin_timeout: Option<u64>,
+    /// Builtin functions or modules of them, e.g. `raw_ip`, that are disabled for every scan of
+    /// the openvasd scanner type
+    #[serde(default)]
+    pub disabled_builtins: Vec<String>,
+    /// Directory the VTs and their includes are compiled into by the openvasd scanner type, they
+    /// are parsed on each run when it is not set
+    #[serde(default)]
+    pub compile_cache: Option<PathBuf>,
+    /// Include graph written by `scannerctl feed update --include-graph`, the includes of the VTs
+    /// of each scan of the openvasd scanner type are loaded and parsed once when it starts
+    #[serde(default)]
+    pub include_graph: Option<PathBuf>,
+    /// Directory the traffic of the VTs is recorded to as pcap files by the openvasd scanner
+    /// type, nothing is recorded when it is not set
+    #[serde(default)]
+    pub pcap_directory: Option<PathBuf>,
+    /// Workers the scans are distributed to by the cluster scanner type
+    #[serde(default)]
+    pub cluster: Cluster,
+}
+
+/// Worker openvasd instance of the cluster scanner type
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Worker {
+    /// Base URL of the worker, e.g. `https://worker-1:3000`
+    pub url: String,
+    /// API key of the worker
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Maximum of scans running on the worker at the same time
+    #[serde(default = "default_worker_max_scans")]
+    pub max_scans: usize,
+}
+
+fn default_worker_max_scans() -> usize {
+    1
+}
+
+/// Controller a worker registers at and sends its heartbeats to
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Controller {
+    /// Base URL of the controller, the worker does not register when it is not set
+    pub url: Option<String>,
+    /// Token authenticating the worker at the controller
+    pub registration_token: Option<String>,
+    /// Base URL the controller reaches this worker at
+    pub advertise_url: Option<String>,
+    /// Maximum of scans the controller runs on this worker at the same time
+    pub max_scans: usize,
+    /// Interval of the heartbeats
+    pub heartbeat_interval: Duration,
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        Self {
+            url: None,
+            registration_token: None,
+            advertise_url: None,
+            max_scans: default_worker_max_scans(),
+            heartbeat_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Distribution of scans to worker openvasd instances
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Cluster {
+    pub workers: Vec<Worker>,
+    /// How long the health of a worker is trusted before it is checked again
+    pub health_check_interval: Duration,
+    /// Timeout of each request to a worker
+    pub request_timeout: Duration,
+    /// Token workers have to present to register, registration is disabled when it is not set
+    pub registration_token: Option<String>,
+    /// Time without heartbeat after which a registered worker is considered dead
+    pub heartbeat_timeout: Duration,
+    /// Optional builtin function sets, e.g. `ssh`, registered workers have to provide
+    pub required_builtins: Vec<String>,
+    /// Registration of this openvasd as worker of a controller
+    pub controller: Controller,
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Self {
+            workers: vec![],
+            health_check_interval: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(30),
+            registration_token: None,
+            heartbeat_timeout: Duration::from_secs(30),
+            required_builtins: vec![],
+            controller: Controller::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Plugins {
+    /// Directory containing the shared objects, no plugin is loaded when it is not set
+    pub path: Option<PathBuf>,
+    /// Requires each shared object to be listed in the signed sha256sums file of the directory
+    #[serde(default)]
+    pub signature_check: bool,
+    /// File names of the shared objects that may be loaded, every one when it is not set
+    pub allowed: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -68,6 +616,8 @@ pub enum ScannerType {
     Openvas,
     #[serde(rename = "openvasd")]
     Openvasd,
+    #[serde(rename = "cluster")]
+    Cluster,
 }
 
 impl Default for ScannerType {
@@ -89,6 +639,7 @@ impl TypedValueParser for ScannerType {
             "ospd" => ScannerType::OSPD,
             "openvas" => ScannerType::Openvas,
             "openvasd" => ScannerType::Openvasd,
+            "cluster" => ScannerType::Cluster,
             x => {
                 let mut cmd = cmd.clone();
                 let err = cmd.error(
@@ -107,6 +658,22 @@ pub struct OspdWrapper {
     pub read_timeout: Option<Duration>,
 }
 
+/// Settings of the openvas scanner type
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct OpenvasWrapper {
+    /// Time a redis command or an openvas process may take before it is given up
+    pub timeout: Duration,
+}
+
+impl Default for OpenvasWrapper {
+    fn default() -> Self {
+        OpenvasWrapper {
+            timeout: scannerlib::openvas::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
 impl Default for OspdWrapper {
     fn default() -> Self {
         OspdWrapper {
@@ -120,8 +687,11 @@ impl Default for Feed {
     fn default() -> Self {
         Feed {
             path: PathBuf::from("/var/lib/openvas/plugins"),
+            overlays: vec![],
             check_interval: Duration::from_secs(3600),
             signature_check: false,
+            update_policy: FeedUpdatePolicy::default(),
+            update_window: default_update_window(),
         }
     }
 }
@@ -146,12 +716,20 @@ impl Default for Redis {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Listener {
     pub address: SocketAddr,
+    /// Address of the OSP listener for gvmd, disabled when not set
+    #[serde(default)]
+    pub osp: Option<SocketAddr>,
+    /// Address of the gRPC API, disabled when not set or built without the grpc feature
+    #[serde(default)]
+    pub grpc: Option<SocketAddr>,
 }
 
 impl Default for Listener {
     fn default() -> Self {
         Self {
             address: ([127, 0, 0, 1], 3000).into(),
+            osp: None,
+            grpc: None,
         }
     }
 }
@@ -213,16 +791,56 @@ pub struct Tls {
 pub struct Logging {
     #[serde(default)]
     pub level: String,
+    /// Additional secrets masked in logs and results
+    #[serde(default)]
+    pub redact: Vec<String>,
 }
 
 impl Default for Logging {
     fn default() -> Self {
         Self {
             level: "INFO".to_string(),
+            redact: vec![],
         }
     }
 }
 
+impl Config {
+    /// Returns the secrets of the configuration that must not be logged.
+    pub fn secrets(&self) -> Vec<&str> {
+        [
+            self.endpoints.key.as_deref(),
+            self.storage.fs.key.as_deref(),
+            self.secrets.file_key.as_deref(),
+            self.secrets.vault.token.as_deref(),
+            self.bundle.key.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(
+            self.scanner
+                .cluster
+                .workers
+                .iter()
+                .filter_map(|x| x.api_key.as_deref()),
+        )
+        .chain(
+            [
+                self.scanner.cluster.registration_token.as_deref(),
+                self.scanner
+                    .cluster
+                    .controller
+                    .registration_token
+                    .as_deref(),
+            ]
+            .into_iter()
+            .flatten(),
+        )
+        .chain(self.log.redact.iter().map(|x| x.as_str()))
+        .collect()
+    }
+}
+
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Eq)]
 pub enum StorageType {
     #[default]
@@ -274,6 +892,20 @@ impl Default for FileStorage {
     }
 }
 
+/// Automatic maintenance of the storage, only storages keeping files have something to reclaim
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct StorageMaintenance {
+    /// How often the storage is compacted, it is only compacted on request when it is not set
+    #[serde(default)]
+    pub interval: Option<Duration>,
+    /// Maximum amount of running scans at which an automatic compaction still takes place
+    #[serde(default)]
+    pub max_running_scans: usize,
+    /// Maximum CPU usage in percent at which an automatic compaction still takes place
+    #[serde(default)]
+    pub max_cpu: Option<f32>,
+}
+
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct Storage {
     #[serde(default, rename = "type")]
@@ -282,6 +914,8 @@ pub struct Storage {
     pub fs: FileStorage,
     #[serde(default)]
     pub redis: Redis,
+    #[serde(default)]
+    pub maintenance: StorageMaintenance,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -306,6 +940,29 @@ pub struct Config {
     pub storage: Storage,
     #[serde(default)]
     pub scheduler: Scheduler,
+    #[serde(default)]
+    pub enrichment: Enrichment,
+    #[serde(default)]
+    pub triage: Triage,
+    #[serde(default)]
+    pub report: Report,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub oidc: Oidc,
+    #[serde(default)]
+    pub telemetry: Telemetry,
+    #[serde(default)]
+    pub publisher: Publisher,
+    #[serde(default)]
+    pub secrets: Secrets,
+    #[serde(default)]
+    pub bundle: Bundle,
+    #[serde(default)]
+    pub tenancy: Tenancy,
+    /// Plain secrets file that is encrypted and printed instead of starting openvasd
+    #[serde(skip)]
+    pub encrypt_secrets: Option<PathBuf>,
 }
 
 impl Display for Config {
@@ -357,6 +1014,15 @@ impl Config {
                     .action(ArgAction::Set)
                     .help("path to openvas feed"),
             )
+            .arg(
+                clap::Arg::new("feed-overlay")
+                    .env("FEED_OVERLAYS")
+                    .long("feed-overlay")
+                    .value_delimiter(',')
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Append)
+                    .help("directory stacked on top of the openvas feed, files of a later overlay take precedence"),
+            )
             .arg(
                 clap::Arg::new("feed-signature-check")
                     .long("feed-signature-check")
@@ -373,6 +1039,22 @@ impl Config {
                     .value_name("SECONDS")
                     .help("interval to check for feed updates in seconds"),
             )
+            .arg(
+                clap::Arg::new("feed-update-policy")
+                    .env("FEED_UPDATE_POLICY")
+                    .long("feed-update-policy")
+                    .value_name("immediate,idle_only,window")
+                    .value_parser(FeedUpdatePolicy::Immediate)
+                    .help("when a detected feed change is applied while scans are running"),
+            )
+            .arg(
+                clap::Arg::new("feed-update-window")
+                    .env("FEED_UPDATE_WINDOW")
+                    .long("feed-update-window")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("minimum time between feed updates while scans are running using the window policy"),
+            )
             .arg(
                 clap::Arg::new("notus-advisories")
                     .env("NOTUS_ADVISORIES")
@@ -387,6 +1069,39 @@ impl Config {
                     .value_parser(clap::builder::PathBufValueParser::new())
                     .action(ArgAction::Set)
                     .help("Path containing the Notus products directory"))
+            .arg(
+                clap::Arg::new("enrichment-path")
+                    .env("ENRICHMENT_PATH")
+                    .long("enrichment-path")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Set)
+                    .help("Path containing the NVD and EPSS data to enrich results with"))
+            .arg(
+                clap::Arg::new("triage-rules")
+                    .env("TRIAGE_RULES")
+                    .long("triage-rules")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Set)
+                    .help("TOML file containing the auto-triage rules applied to stored results"))
+            .arg(
+                clap::Arg::new("enrichment-sync-interval")
+                    .env("ENRICHMENT_SYNC_INTERVAL")
+                    .long("enrichment-sync-interval")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("interval to sync and reload the enrichment data in seconds"))
+            .arg(
+                clap::Arg::new("otlp-endpoint")
+                    .env("OTLP_ENDPOINT")
+                    .long("otlp-endpoint")
+                    .action(ArgAction::Set)
+                    .help("Url of the OTLP/HTTP collector traces are exported to"))
+            .arg(
+                clap::Arg::new("publisher-url")
+                    .env("PUBLISHER_URL")
+                    .long("publisher-url")
+                    .action(ArgAction::Set)
+                    .help("Url of the NATS server or Kafka REST proxy results and scan events are published to"))
             .arg(
                 clap::Arg::new("redis-url")
                     .long("redis-url")
@@ -434,11 +1149,68 @@ impl Config {
                     .action(ArgAction::Set)
                     .help("API key that must be set as X-API-KEY header to gain access"),
             )
+            .arg(
+                clap::Arg::new("oidc-issuer")
+                    .env("OIDC_ISSUER")
+                    .long("oidc-issuer")
+                    .action(ArgAction::Set)
+                    .help("Issuer of bearer tokens that grant access. Enables OIDC authentication."),
+            )
+            .arg(
+                clap::Arg::new("oidc-audience")
+                    .env("OIDC_AUDIENCE")
+                    .long("oidc-audience")
+                    .action(ArgAction::Set)
+                    .help("Audience a bearer token must be issued for"),
+            )
+            .arg(
+                clap::Arg::new("vault-address")
+                    .env("VAULT_ADDR")
+                    .long("vault-address")
+                    .action(ArgAction::Set)
+                    .help("Url of the HashiCorp Vault credential references are resolved from"),
+            )
+            .arg(
+                clap::Arg::new("vault-token")
+                    .env("VAULT_TOKEN")
+                    .long("vault-token")
+                    .action(ArgAction::Set)
+                    .help("Token used to authenticate at the vault"),
+            )
+            .arg(
+                clap::Arg::new("secrets-file")
+                    .env("SECRETS_FILE")
+                    .long("secrets-file")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Set)
+                    .help("Path of the encrypted file credential references are resolved from"),
+            )
+            .arg(
+                clap::Arg::new("secrets-file-key")
+                    .env("SECRETS_FILE_KEY")
+                    .long("secrets-file-key")
+                    .action(ArgAction::Set)
+                    .help("Key the secrets file is encrypted with"),
+            )
+            .arg(
+                clap::Arg::new("bundle-key")
+                    .env("BUNDLE_KEY")
+                    .long("bundle-key")
+                    .action(ArgAction::Set)
+                    .help("Key exported scan bundles are signed and imported ones are verified with"),
+            )
+            .arg(
+                clap::Arg::new("encrypt-secrets")
+                    .long("encrypt-secrets")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .action(ArgAction::Set)
+                    .help("Encrypts the given plain secrets file with the secrets file key, prints it and exits"),
+            )
             .arg(
                 clap::Arg::new("scanner-type")
                     .env("SCANNER_TYPE")
                     .long("scanner-type")
-                    .value_name("ospd,openvas")
+                    .value_name("ospd,openvas,openvasd,cluster")
                     .value_parser(ScannerType::OSPD)
                     .help("Type of scanner used to manage scans")
             )
@@ -464,6 +1236,46 @@ impl Config {
                     .action(ArgAction::Set)
                     .help("Minimum memory available to start a new scan")
             )
+            .arg(
+                clap::Arg::new("min-free-disk")
+                    .env("MIN_FREE_DISK")
+                    .long("min-free-disk")
+                    .value_parser(clap::value_parser!(u64))
+                    .action(ArgAction::Set)
+                    .help("Minimum disk space in bytes available to start a new scan or feed update")
+            )
+            .arg(
+                clap::Arg::new("min-free-file-descriptors")
+                    .env("MIN_FREE_FILE_DESCRIPTORS")
+                    .long("min-free-file-descriptors")
+                    .value_parser(clap::value_parser!(u64))
+                    .action(ArgAction::Set)
+                    .help("Minimum file descriptors available to start a new scan or feed update")
+            )
+            .arg(
+                clap::Arg::new("resource-policy")
+                    .env("RESOURCE_POLICY")
+                    .long("resource-policy")
+                    .value_name("queue,refuse")
+                    .value_parser(ResourcePolicy::Queue)
+                    .help("whether scans are queued or refused while resources are insufficient"),
+            )
+            .arg(
+                clap::Arg::new("stall-timeout")
+                    .env("STALL_TIMEOUT")
+                    .long("stall-timeout")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("Time without any progress after which a running scan is considered stalled")
+            )
+            .arg(
+                clap::Arg::new("stall-policy")
+                    .env("STALL_POLICY")
+                    .long("stall-policy")
+                    .value_name("flag,stop")
+                    .value_parser(StallPolicy::Flag)
+                    .help("whether stalled scans are only flagged or flagged and stopped"),
+            )
             .arg(
                 clap::Arg::new("check-interval")
                     .env("SCHEDULER_CHECK_INTERVAL")
@@ -487,6 +1299,54 @@ impl Config {
                     .value_name("SECONDS")
                     .help("read timeout in seconds on the ospd-openvas socket"),
             )
+            .arg(
+                clap::Arg::new("openvas-timeout")
+                    .env("OPENVAS_TIMEOUT")
+                    .long("openvas-timeout")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("timeout in seconds of redis commands and openvas processes of the openvas scanner type"),
+            )
+            .arg(
+                clap::Arg::new("plugin-timeout")
+                    .env("PLUGIN_TIMEOUT")
+                    .long("plugin-timeout")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("default timeout in seconds of each VT run by the openvasd scanner type"),
+            )
+            .arg(
+                clap::Arg::new("disabled-builtins")
+                    .env("DISABLED_BUILTINS")
+                    .long("disabled-builtins")
+                    .value_delimiter(',')
+                    .value_name("FUNCTIONS")
+                    .help("comma separated builtin functions or modules disabled for each scan of the openvasd scanner type"),
+            )
+            .arg(
+                clap::Arg::new("compile-cache")
+                    .env("COMPILE_CACHE")
+                    .long("compile-cache")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .value_name("DIR")
+                    .help("directory the VTs and their includes are compiled into by the openvasd scanner type"),
+            )
+            .arg(
+                clap::Arg::new("include-graph")
+                    .env("INCLUDE_GRAPH")
+                    .long("include-graph")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .value_name("FILE")
+                    .help("include graph of the feed, the includes of the VTs of each scan of the openvasd scanner type are parsed once when it starts"),
+            )
+            .arg(
+                clap::Arg::new("pcap-directory")
+                    .env("PCAP_DIRECTORY")
+                    .long("pcap-directory")
+                    .value_parser(clap::builder::PathBufValueParser::new())
+                    .value_name("DIR")
+                    .help("directory the traffic of the VTs is recorded to as pcap files by the openvasd scanner type"),
+            )
             .arg(
                 clap::Arg::new("result-check-interval")
                     .env("RESULT_CHECK_INTERVAL")
@@ -504,6 +1364,22 @@ impl Config {
                     .value_parser(clap::value_parser!(SocketAddr))
                     .help("the address to listen to (e.g. 127.0.0.1:3000 or 0.0.0.0:3000)."),
             )
+            .arg(
+                clap::Arg::new("osp-listening")
+                    .env("OSP_LISTENING")
+                    .long("osp-listening")
+                    .value_name("IP:PORT")
+                    .value_parser(clap::value_parser!(SocketAddr))
+                    .help("the address of the OSP listener for gvmd (e.g. 0.0.0.0:3001), requires TLS."),
+            )
+            .arg(
+                clap::Arg::new("grpc-listening")
+                    .env("GRPC_LISTENING")
+                    .long("grpc-listening")
+                    .value_name("IP:PORT")
+                    .value_parser(clap::value_parser!(SocketAddr))
+                    .help("the address of the gRPC API (e.g. 0.0.0.0:3002), requires the grpc feature."),
+            )
             .arg(
                 clap::Arg::new("storage_type")
                     .env("STORAGE_TYPE")
@@ -527,6 +1403,14 @@ impl Config {
                     .value_name("KEY")
                     .help("the password to use for encryption when type is set to fs. If not set the files are not encrypted."),
             )
+            .arg(
+                clap::Arg::new("storage-maintenance-interval")
+                    .env("STORAGE_MAINTENANCE_INTERVAL")
+                    .long("storage-maintenance-interval")
+                    .value_parser(clap::value_parser!(u64))
+                    .value_name("SECONDS")
+                    .help("interval to compact the storage in seconds, 0 disables the automatic compaction"),
+            )
             .arg(
                 clap::Arg::new("log-level")
                     .env("OPENVASD_LOG")
@@ -556,6 +1440,12 @@ impl Config {
         if let Some(interval) = cmds.get_one::<u64>("feed-check-interval") {
             config.feed.check_interval = Duration::from_secs(*interval);
         }
+        if let Some(policy) = cmds.get_one::<FeedUpdatePolicy>("feed-update-policy") {
+            config.feed.update_policy = *policy;
+        }
+        if let Some(window) = cmds.get_one::<u64>("feed-update-window") {
+            config.feed.update_window = Duration::from_secs(*window);
+        }
         if let Some(scanner_type) = cmds.get_one::<ScannerType>("scanner-type") {
             config.scanner.scanner_type = scanner_type.clone()
         }
@@ -568,6 +1458,21 @@ impl Config {
         if let Some(min_free_mem) = cmds.get_one::<u64>("min-free-mem") {
             config.scheduler.min_free_mem = Some(*min_free_mem)
         }
+        if let Some(min_free_disk) = cmds.get_one::<u64>("min-free-disk") {
+            config.scheduler.min_free_disk = Some(*min_free_disk)
+        }
+        if let Some(fds) = cmds.get_one::<u64>("min-free-file-descriptors") {
+            config.scheduler.min_free_file_descriptors = Some(*fds)
+        }
+        if let Some(policy) = cmds.get_one::<ResourcePolicy>("resource-policy") {
+            config.scheduler.resource_policy = *policy;
+        }
+        if let Some(timeout) = cmds.get_one::<u64>("stall-timeout") {
+            config.scheduler.stall_timeout = Some(Duration::from_secs(*timeout));
+        }
+        if let Some(policy) = cmds.get_one::<StallPolicy>("stall-policy") {
+            config.scheduler.stall_policy = *policy;
+        }
         if let Some(check_interval) = cmds.get_one::<u64>("check-interval") {
             config.scheduler.check_interval = Duration::from_millis(*check_interval)
         }
@@ -577,16 +1482,52 @@ impl Config {
         if let Some(interval) = cmds.get_one::<u64>("read-timeout") {
             config.scanner.ospd.read_timeout = Some(Duration::from_secs(*interval));
         }
+        if let Some(timeout) = cmds.get_one::<u64>("openvas-timeout") {
+            config.scanner.openvas.timeout = Duration::from_secs(*timeout);
+        }
+        if let Some(timeout) = cmds.get_one::<u64>("plugin-timeout") {
+            config.scanner.plugin_timeout = Some(*timeout);
+        }
+        if let Some(disabled) = cmds.get_many::<String>("disabled-builtins") {
+            config.scanner.disabled_builtins = disabled.cloned().collect();
+        }
+        if let Some(path) = cmds.get_one::<PathBuf>("compile-cache") {
+            config.scanner.compile_cache = Some(path.clone());
+        }
+        if let Some(path) = cmds.get_one::<PathBuf>("include-graph") {
+            config.scanner.include_graph = Some(path.clone());
+        }
+        if let Some(path) = cmds.get_one::<PathBuf>("pcap-directory") {
+            config.scanner.pcap_directory = Some(path.clone());
+        }
 
         if let Some(path) = cmds.get_one::<PathBuf>("feed-path") {
             config.feed.path.clone_from(path);
         }
+        if let Some(overlays) = cmds.get_many::<PathBuf>("feed-overlay") {
+            config.feed.overlays = overlays.cloned().collect();
+        }
         if let Some(path) = cmds.get_one::<PathBuf>("notus-products") {
             config.notus.products_path.clone_from(path);
         }
         if let Some(path) = cmds.get_one::<PathBuf>("notus-advisories") {
             config.notus.advisories_path.clone_from(path);
         }
+        if let Some(path) = cmds.get_one::<PathBuf>("enrichment-path") {
+            config.enrichment.path = Some(path.clone());
+        }
+        if let Some(path) = cmds.get_one::<PathBuf>("triage-rules") {
+            config.triage.rules = Some(path.clone());
+        }
+        if let Some(interval) = cmds.get_one::<u64>("enrichment-sync-interval") {
+            config.enrichment.sync_interval = Duration::from_secs(*interval);
+        }
+        if let Some(endpoint) = cmds.get_one::<String>("otlp-endpoint") {
+            config.telemetry.otlp_endpoint = Some(endpoint.clone());
+        }
+        if let Some(url) = cmds.get_one::<String>("publisher-url") {
+            config.publisher.url = Some(url.clone());
+        }
         if let Some(path) = cmds.get_one::<String>("redis-url") {
             config.storage.redis.url.clone_from(path);
         }
@@ -605,9 +1546,37 @@ impl Config {
         if let Some(api_key) = cmds.get_one::<String>("api-key") {
             config.endpoints.key = Some(api_key.clone());
         }
+        if let Some(issuer) = cmds.get_one::<String>("oidc-issuer") {
+            config.oidc.issuer = Some(issuer.clone());
+        }
+        if let Some(audience) = cmds.get_one::<String>("oidc-audience") {
+            config.oidc.audience = Some(audience.clone());
+        }
+        if let Some(address) = cmds.get_one::<String>("vault-address") {
+            config.secrets.vault.address = Some(address.clone());
+        }
+        if let Some(token) = cmds.get_one::<String>("vault-token") {
+            config.secrets.vault.token = Some(token.clone());
+        }
+        if let Some(path) = cmds.get_one::<PathBuf>("secrets-file") {
+            config.secrets.file = Some(path.clone());
+        }
+        if let Some(key) = cmds.get_one::<String>("secrets-file-key") {
+            config.secrets.file_key = Some(key.clone());
+        }
+        if let Some(key) = cmds.get_one::<String>("bundle-key") {
+            config.bundle.key = Some(key.clone());
+        }
+        config.encrypt_secrets = cmds.get_one::<PathBuf>("encrypt-secrets").cloned();
         if let Some(ip) = cmds.get_one::<SocketAddr>("listening") {
             config.listener.address = *ip;
         }
+        if let Some(ip) = cmds.get_one::<SocketAddr>("osp-listening") {
+            config.listener.osp = Some(*ip);
+        }
+        if let Some(ip) = cmds.get_one::<SocketAddr>("grpc-listening") {
+            config.listener.grpc = Some(*ip);
+        }
         if let Some(log_level) = cmds.get_one::<String>("log-level") {
             config.log.level.clone_from(log_level);
         }
@@ -617,6 +1586,10 @@ impl Config {
         if let Some(path) = cmds.get_one::<PathBuf>("storage_path") {
             config.storage.fs.path.clone_from(path);
         }
+        if let Some(interval) = cmds.get_one::<u64>("storage-maintenance-interval") {
+            config.storage.maintenance.interval =
+                Some(Duration::from_secs(*interval)).filter(|x| !x.is_zero());
+        }
         if let Some(mode) = cmds.get_one::<Mode>("mode") {
             config.mode = mode.clone();
         }
@@ -644,6 +1617,10 @@ mod tests {
             std::path::PathBuf::from("/var/lib/openvas/plugins")
         );
         assert_eq!(config.feed.check_interval, Duration::from_secs(3600));
+        assert_eq!(
+            config.feed.update_policy,
+            crate::config::FeedUpdatePolicy::Immediate
+        );
 
         assert!(!config.endpoints.enable_get_scans);
         assert!(config.endpoints.key.is_none());
@@ -653,11 +1630,28 @@ mod tests {
         assert!(config.tls.client_certs.is_none());
 
         assert_eq!(config.scheduler.check_interval, Duration::from_millis(500));
+        assert!(config.scheduler.stall_timeout.is_none());
+        assert_eq!(
+            config.scheduler.stall_policy,
+            crate::config::StallPolicy::Flag
+        );
         assert_eq!(
             config.scanner.ospd.socket,
             PathBuf::from("/var/run/ospd/ospd.sock")
         );
         assert!(config.scanner.ospd.read_timeout.is_none());
+        assert_eq!(config.scanner.openvas.timeout, Duration::from_secs(10));
+        assert!(config.scanner.plugin_timeout.is_none());
+        assert!(config.scanner.disabled_builtins.is_empty());
+
+        assert!(config.enrichment.path.is_none());
+        assert_eq!(
+            config.enrichment.sync_interval,
+            Duration::from_secs(24 * 3600)
+        );
+
+        assert!(config.storage.maintenance.interval.is_none());
+        assert_eq!(config.storage.maintenance.max_running_scans, 0);
 
         assert_eq!(config.listener.address, ([127, 0, 0, 1], 3000).into());
 
@@ -681,6 +1675,18 @@ mod tests {
         [storage.fs]
         path = "/var/lib/openvasd/storage/test"
         key = "changeme"
+        [scanner]
+        type = "cluster"
+        [[scanner.cluster.workers]]
+        url = "https://worker-1:3000"
+        api_key = "worker-key"
+        max_scans = 4
+        [[scanner.cluster.workers]]
+        url = "https://worker-2:3000"
+        [scanner.cluster]
+        registration_token = "registration-token"
+        [scanner.cluster.controller]
+        url = "https://controller:3000"
         "#;
         let config: super::Config = toml::from_str(cfg).unwrap();
         assert_eq!(config.log.level, "DEBUG");
@@ -690,5 +1696,19 @@ mod tests {
         );
         assert_eq!(config.storage.fs.key, Some("changeme".to_string()));
         assert_eq!(config.storage.storage_type, StorageType::FileSystem);
+        let workers = &config.scanner.cluster.workers;
+        assert_eq!(
+            workers.iter().map(|x| x.max_scans).collect::<Vec<_>>(),
+            vec![4, 1]
+        );
+        assert_eq!(
+            config.scanner.cluster.health_check_interval,
+            Duration::from_secs(30)
+        );
+        assert!(config.secrets().contains(&"worker-key"));
+        assert!(config.secrets().contains(&"registration-token"));
+        let controller = &config.scanner.cluster.controller;
+        assert_eq!(controller.url.as_deref(), Some("https://controller:3000"));
+        assert_eq!(controller.heartbeat_interval, Duration::from_secs(10));
     }
 }
@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the checks a scan has to pass before it is stored.
+//!
+//! The checks are the same for scans created via HTTP, OSP and gRPC.
+
+use std::fmt::Display;
+
+use scannerlib::{
+    models::{scanner::Scanner, validate_scan_preferences, InvalidPreference, Scan},
+    nasl::utils::NetworkSource,
+};
+
+use super::context::Context;
+use crate::{scheduling, tenancy::FeedSubset};
+
+/// Reason a scan is refused
+#[derive(Debug)]
+pub enum Refusal {
+    /// A scan preference contains an invalid value
+    Preference(InvalidPreference),
+    /// The network namespace is not part of the configured namespaces
+    NetworkNamespace(String),
+    /// The severity profile is not configured
+    SeverityProfile(String),
+    /// The VTs are unknown or not part of the feed subset of the client
+    Feed(scheduling::Error),
+}
+
+impl Display for Refusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Refusal::Preference(e) => write!(f, "{e}"),
+            Refusal::NetworkNamespace(netns) => {
+                write!(f, "Network namespace {netns} is not allowed")
+            }
+            Refusal::SeverityProfile(name) => {
+                write!(f, "Severity profile {name} is not configured")
+            }
+            Refusal::Feed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Refusal {}
+
+impl<S, DB> Context<S, DB>
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Returns the reason when a scan of a client restricted to the feed subset must not be
+    /// stored.
+    pub async fn admit(&self, scan: &Scan, subset: Option<&FeedSubset>) -> Result<(), Refusal> {
+        validate_scan_preferences(&scan.scan_preferences).map_err(Refusal::Preference)?;
+        if let Some(netns) = NetworkSource::from(scan).namespace {
+            if !self.network_namespaces.contains(&netns) {
+                return Err(Refusal::NetworkNamespace(netns));
+            }
+        }
+        if let Some(name) = crate::severity::requested(scan) {
+            if !self.severity.profiles.contains_key(name) {
+                return Err(Refusal::SeverityProfile(name.to_string()));
+            }
+        }
+        if let Some(subset) = subset {
+            self.scheduler
+                .check_feed_subset(&scan.vts, subset)
+                .await
+                .map_err(Refusal::Feed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::{
+        models::{ScanPreference, VT},
+        nasl::utils::context::NETWORK_NAMESPACE,
+    };
+
+    use super::*;
+
+    fn context() -> Context<
+        super::super::NoOpScanner,
+        crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
+    > {
+        crate::controller::ContextBuilder::new()
+            .scanner(super::super::NoOpScanner)
+            .network_namespaces(vec!["allowed".to_string()])
+            .build()
+    }
+
+    fn scan(id: &str, value: &str) -> Scan {
+        Scan {
+            scan_preferences: vec![ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn refusals() {
+        let ctx = context();
+        assert!(ctx.admit(&Scan::default(), None).await.is_ok());
+        assert!(matches!(
+            ctx.admit(&scan("plugins_timeout", "forever"), None).await,
+            Err(Refusal::Preference(_))
+        ));
+        assert!(matches!(
+            ctx.admit(&scan(NETWORK_NAMESPACE, "other"), None).await,
+            Err(Refusal::NetworkNamespace(x)) if x == "other"
+        ));
+        assert!(ctx
+            .admit(&scan(NETWORK_NAMESPACE, "allowed"), None)
+            .await
+            .is_ok());
+        assert!(matches!(
+            ctx.admit(&scan(crate::severity::SEVERITY_PROFILE, "strict"), None).await,
+            Err(Refusal::SeverityProfile(x)) if x == "strict"
+        ));
+        let mut vts = Scan::default();
+        vts.vts.push(VT {
+            oid: "1.2.3".to_string(),
+            parameters: vec![],
+        });
+        assert!(ctx.admit(&vts, None).await.is_ok());
+        assert!(matches!(
+            ctx.admit(&vts, Some(&FeedSubset::default())).await,
+            Err(Refusal::Feed(scheduling::Error::VtsNotAvailable(oids))) if oids == ["1.2.3"]
+        ));
+    }
+}
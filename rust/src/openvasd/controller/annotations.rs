@@ -0,0 +1,100 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Attaches the annotations of results to served results.
+
+use std::collections::HashMap;
+
+use scannerlib::models::Annotation;
+
+/// Returns the ID of a serialized result
+fn result_id(result: &[u8]) -> Option<usize> {
+    #[derive(serde::Deserialize)]
+    struct Id {
+        id: usize,
+    }
+    serde_json::from_slice::<Id>(result).ok().map(|x| x.id)
+}
+
+/// Adds the annotations of each result to serialized results as `annotations`.
+///
+/// Annotations of the scan itself are not added, results without annotations are unchanged.
+pub fn annotate(
+    annotations: &[Annotation],
+    results: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+    let mut by_result: HashMap<usize, Vec<&Annotation>> = HashMap::new();
+    for annotation in annotations {
+        if let Some(id) = annotation.result_id {
+            by_result.entry(id).or_default().push(annotation);
+        }
+    }
+    if by_result.is_empty() {
+        return results;
+    }
+    let results: Vec<Vec<u8>> = results
+        .map(|x| {
+            let Some(annotations) = result_id(&x).and_then(|id| by_result.get(&id)) else {
+                return x;
+            };
+            let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&x) else {
+                return x;
+            };
+            if let (Some(object), Ok(annotations)) =
+                (value.as_object_mut(), serde_json::to_value(annotations))
+            {
+                object.insert("annotations".to_string(), annotations);
+            }
+            serde_json::to_vec(&value).unwrap_or(x)
+        })
+        .collect();
+    Box::new(results.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{self, Annotation};
+
+    #[test]
+    fn annotate() {
+        let results: Vec<Vec<u8>> = (0..3)
+            .map(|id| {
+                serde_json::to_vec(&models::Result {
+                    id,
+                    ..Default::default()
+                })
+                .unwrap()
+            })
+            .collect();
+        let annotations = vec![
+            Annotation {
+                id: 0,
+                note: Some("scan note".to_string()),
+                ..Default::default()
+            },
+            Annotation {
+                id: 1,
+                result_id: Some(1),
+                labels: [("triage".to_string(), "false_positive".to_string())].into(),
+                ..Default::default()
+            },
+        ];
+        let annotated: Vec<serde_json::Value> =
+            super::annotate(&annotations, Box::new(results.clone().into_iter()))
+                .map(|x| serde_json::from_slice(&x).unwrap())
+                .collect();
+        assert!(annotated[0].get("annotations").is_none());
+        assert_eq!(
+            annotated[1]["annotations"][0]["labels"]["triage"],
+            "false_positive"
+        );
+        assert!(annotated[2].get("annotations").is_none());
+        let parsed: models::Result = serde_json::from_value(annotated[1].clone()).unwrap();
+        assert_eq!(parsed.id, 1);
+
+        let unchanged: Vec<Vec<u8>> =
+            super::annotate(&annotations[..1], Box::new(results.clone().into_iter())).collect();
+        assert_eq!(unchanged, results);
+    }
+}
@@ -7,10 +7,13 @@ use scannerlib::storage::DefaultDispatcher;
 use scannerlib::{feed, nasl::FSPluginLoader};
 use std::sync::{Arc, RwLock};
 
-use crate::{config, notus::NotusWrapper, response, scheduling, tls::TlsConfig};
+use crate::{
+    cluster, config, enrichment::Enrichment, notus::NotusWrapper, oidc::Oidc, response, scheduling,
+    secrets::Secrets, tls::TlsConfig, triage::Triage,
+};
 
 use scannerlib::models::scanner::{
-    Error, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
+    Error, ScanDeleter, ScanPlanner, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
 };
 
 #[derive(Debug, Clone)]
@@ -25,13 +28,23 @@ pub struct ContextBuilder<S, DB, T> {
     storage: DB,
     feed_config: Option<crate::config::Feed>,
     api_key: Option<String>,
+    oidc: Option<Oidc>,
     tls_config: Option<TlsConfig>,
     enable_get_scans: bool,
     marker: std::marker::PhantomData<S>,
     response: response::Response,
     notus: Option<NotusWrapper>,
+    enrichment: Option<Enrichment>,
+    report: config::Report,
+    severity: config::Severity,
+    bundle: config::Bundle,
+    tenancy: config::Tenancy,
     scheduler_config: Option<config::Scheduler>,
+    secrets: Option<Secrets>,
+    triage: Option<Triage>,
     mode: config::Mode,
+    network_namespaces: Vec<String>,
+    cluster: Option<Arc<cluster::Registry>>,
 }
 
 impl<S>
@@ -44,13 +57,23 @@ impl<S>
             storage: crate::storage::inmemory::Storage::default(),
             feed_config: None,
             api_key: None,
+            oidc: None,
             tls_config: None,
             marker: std::marker::PhantomData,
             enable_get_scans: false,
             response: response::Response::default(),
             notus: None,
+            enrichment: None,
+            report: config::Report::default(),
+            severity: config::Severity::default(),
+            bundle: config::Bundle::default(),
+            tenancy: config::Tenancy::default(),
             scheduler_config: None,
+            secrets: None,
+            triage: None,
             mode: config::Mode::default(),
+            network_namespaces: vec![],
+            cluster: None,
         }
     }
 }
@@ -65,7 +88,7 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
     pub async fn feed_config(mut self, config: config::Feed) -> Self {
         self.feed_config = Some(config);
         if let Some(fp) = self.feed_config.as_ref() {
-            let loader = FSPluginLoader::new(fp.path.clone());
+            let loader = FSPluginLoader::new(fp.path.clone()).with_overlays(&fp.overlays);
             let dispatcher: DefaultDispatcher = DefaultDispatcher::default();
             let version = feed::version(&loader, &dispatcher)
                 .await
@@ -81,6 +104,12 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
         self
     }
 
+    /// Accepts bearer tokens of an OpenID Connect issuer
+    pub fn oidc(mut self, oidc: Oidc) -> Self {
+        self.oidc = Some(oidc);
+        self
+    }
+
     /// Set the TLS config
     pub fn tls_config(mut self, tls_config: Option<TlsConfig>) -> Self {
         self.tls_config = tls_config;
@@ -99,11 +128,65 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
         self
     }
 
+    /// Enriches results with the CVE dataset
+    pub fn enrichment(mut self, enrichment: Enrichment) -> Self {
+        self.enrichment = Some(enrichment);
+        self
+    }
+
+    /// Sets the PDF converter and branding of reports
+    pub fn report(mut self, report: config::Report) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Sets the profiles mapping the severity of results to levels
+    pub fn severity(mut self, severity: config::Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Sets the key scan bundles are signed and verified with
+    pub fn bundle(mut self, bundle: config::Bundle) -> Self {
+        self.bundle = bundle;
+        self
+    }
+
+    /// Sets the feed subsets of the tenants
+    pub fn tenancy(mut self, tenancy: config::Tenancy) -> Self {
+        self.tenancy = tenancy;
+        self
+    }
+
     pub fn scheduler_config(mut self, scheduler_config: config::Scheduler) -> Self {
         self.scheduler_config = Some(scheduler_config);
         self
     }
 
+    /// Sets the resolver of secret references within the credentials of scans.
+    pub fn secrets(mut self, secrets: Secrets) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Sets the auto-triage rules applied to stored results.
+    pub fn triage(mut self, triage: Triage) -> Self {
+        self.triage = Some(triage);
+        self
+    }
+
+    /// Sets the network namespaces scans are allowed to select.
+    pub fn network_namespaces(mut self, network_namespaces: Vec<String>) -> Self {
+        self.network_namespaces = network_namespaces;
+        self
+    }
+
+    /// Sets the registry of the workers of the cluster scanner type.
+    pub fn cluster(mut self, cluster: Arc<cluster::Registry>) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
     /// Sets the storage.
     #[allow(dead_code)]
     pub fn storage<NDB>(self, storage: NDB) -> ContextBuilder<S, NDB, T> {
@@ -112,26 +195,46 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
             storage: _,
             feed_config,
             api_key,
+            oidc,
             tls_config,
             enable_get_scans,
             marker,
             response,
             notus,
+            enrichment,
+            report,
+            severity,
+            bundle,
+            tenancy,
             scheduler_config,
+            secrets,
+            triage,
             mode,
+            network_namespaces,
+            cluster,
         } = self;
         ContextBuilder {
             scanner,
             storage,
             feed_config,
             api_key,
+            oidc,
             tls_config,
             enable_get_scans,
             marker,
             response,
             notus,
+            enrichment,
+            report,
+            severity,
+            bundle,
+            tenancy,
             scheduler_config,
+            secrets,
+            triage,
             mode,
+            network_namespaces,
+            cluster,
         }
     }
 }
@@ -145,6 +248,7 @@ impl<S, DB> ContextBuilder<S, DB, NoScanner> {
         let Self {
             feed_config,
             api_key,
+            oidc,
             tls_config,
             enable_get_scans,
             scanner: _,
@@ -152,8 +256,17 @@ impl<S, DB> ContextBuilder<S, DB, NoScanner> {
             response,
             storage,
             notus,
+            enrichment,
+            report,
+            severity,
+            bundle,
+            tenancy,
             scheduler_config,
+            secrets,
+            triage,
             mode,
+            network_namespaces,
+            cluster,
         } = self;
         ContextBuilder {
             scanner: Scanner(scanner),
@@ -161,12 +274,22 @@ impl<S, DB> ContextBuilder<S, DB, NoScanner> {
             feed_config,
             marker: std::marker::PhantomData,
             api_key,
+            oidc,
             tls_config,
             enable_get_scans,
             response,
             notus,
+            enrichment,
+            report,
+            severity,
+            bundle,
+            tenancy,
             scheduler_config,
+            secrets,
+            triage,
             mode,
+            network_namespaces,
+            cluster,
         }
     }
 }
@@ -187,22 +310,35 @@ impl<S, DB> ContextBuilder<S, DB, Scanner<S>> {
             (true, true) => unreachable!(),
             (true, false) => self.response.add_authentication("mTLS"),
             (false, true) => self.response.add_authentication("x-api-key"),
-            (false, false) => {
+            (false, false) if self.oidc.is_none() => {
                 tracing::warn!(
                     "Neither mTLS nor an API key are set. /scans endpoint is unsecured."
                 );
             }
+            (false, false) => {}
+        }
+        if self.oidc.is_some() {
+            self.response.add_authentication("bearer");
         }
     }
 
     pub fn build(mut self) -> Context<S, DB> {
         self.configure_authentication_methods();
-        let scheduler = scheduling::Scheduler::new(
+        let mut scheduler = scheduling::Scheduler::new(
             self.scheduler_config.unwrap_or_default(),
             self.scanner.0,
             self.storage,
         );
+        if let Some(secrets) = self.secrets {
+            scheduler = scheduler.with_secrets(secrets);
+        }
+        if let Some(triage) = self.triage {
+            scheduler = scheduler.with_triage(triage);
+        }
         let shared_feed = Arc::clone(&scheduler.feed_version());
+        if let Some(cluster) = &self.cluster {
+            cluster.set_feed_version(Arc::clone(&shared_feed));
+        }
         self.response.add_feed_version(shared_feed);
         Context {
             response: self.response,
@@ -210,10 +346,18 @@ impl<S, DB> ContextBuilder<S, DB, Scanner<S>> {
             feed_config: self.feed_config,
             abort: Default::default(),
             api_key: self.api_key,
+            oidc: self.oidc,
             tls_config: self.tls_config,
             enable_get_scans: self.enable_get_scans,
             notus: self.notus,
+            enrichment: self.enrichment,
+            report: self.report,
+            severity: self.severity,
+            bundle: self.bundle,
+            tenancy: self.tenancy,
             mode: self.mode,
+            network_namespaces: self.network_namespaces,
+            cluster: self.cluster,
         }
     }
 }
@@ -229,14 +373,30 @@ pub struct Context<S, DB> {
     ///
     /// When none api key is set, no authentication is required.
     pub api_key: Option<String>,
+    /// Validates bearer tokens, they are not accepted when it is not set
+    pub oidc: Option<Oidc>,
     pub tls_config: Option<TlsConfig>,
     /// Whether to enable the GET /scans endpoint
     pub enable_get_scans: bool,
     pub mode: config::Mode,
+    /// Network namespaces a scan may select via the network_namespace preference
+    pub network_namespaces: Vec<String>,
+    /// Workers of the cluster scanner type, workers are not able to register when it is not set
+    pub cluster: Option<Arc<cluster::Registry>>,
     /// Aborts the background loops
     pub abort: RwLock<bool>,
     /// Notus Scanner
     pub notus: Option<NotusWrapper>,
+    /// Enriches results with information about their CVEs
+    pub enrichment: Option<Enrichment>,
+    /// PDF converter and branding of reports
+    pub report: config::Report,
+    /// Severity profiles of served results and reports
+    pub severity: config::Severity,
+    /// Key scan bundles are signed and verified with
+    pub bundle: config::Bundle,
+    /// Feed subsets of the tenants
+    pub tenancy: config::Tenancy,
     /// All scanner and db operations must go through a scheduler.
     ///
     /// This allows us to throttle requests per need and gives us control when to start/stop/delete
@@ -289,6 +449,8 @@ impl ScanResultFetcher for NoOpScanner {
     }
 }
 
+impl ScanPlanner for NoOpScanner {}
+
 impl Default
     for Context<NoOpScanner, crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>>
 {
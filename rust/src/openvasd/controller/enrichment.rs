@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the sync loop of the enrichment dataset and the enrichment of served results with
+//! the references of their VT and the information about the referenced CVEs.
+
+use std::{collections::HashMap, sync::Arc};
+
+use scannerlib::models::{scanner::Scanner, Reference};
+
+use super::context::Context;
+use crate::{enrichment::CveDataset, storage::NVTStorer as _};
+
+/// Syncs and reloads the enrichment dataset.
+///
+/// This loop should be run as background task when an enrichment is configured.
+pub async fn sync<S, DB>(ctx: Arc<Context<S, DB>>)
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let Some(enrichment) = &ctx.enrichment else {
+        return;
+    };
+    let Some(path) = enrichment.config.path.clone() else {
+        return;
+    };
+    tracing::debug!("Starting enrichment synchronization loop");
+    let mut interval = tokio::time::interval(enrichment.config.sync_interval);
+    loop {
+        interval.tick().await;
+        if *ctx.abort.read().unwrap() {
+            tracing::trace!("aborting");
+            break;
+        }
+        if let Err(e) = crate::enrichment::sync(&enrichment.config).await {
+            tracing::warn!(%e, "Unable to sync the enrichment dataset");
+        }
+        let p = path.clone();
+        match tokio::task::spawn_blocking(move || CveDataset::load(&p)).await {
+            Ok(Ok(dataset)) => {
+                tracing::info!(cves = dataset.len(), "Loaded enrichment dataset");
+                *enrichment.dataset.write().unwrap() = dataset;
+            }
+            Ok(Err(e)) => tracing::warn!(%e, "Unable to load the enrichment dataset"),
+            Err(e) => tracing::warn!(%e, "Unable to load the enrichment dataset"),
+        }
+    }
+}
+
+/// Returns the OID of a serialized result
+fn oid(result: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Oid {
+        oid: Option<String>,
+    }
+    serde_json::from_slice::<Oid>(result).ok()?.oid
+}
+
+/// Adds the references to a serialized result as `references`.
+fn with_references(result: Vec<u8>, references: &[Reference]) -> Vec<u8> {
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&result) else {
+        return result;
+    };
+    if let (Some(object), Ok(references)) =
+        (value.as_object_mut(), serde_json::to_value(references))
+    {
+        object.insert("references".to_string(), references);
+    }
+    serde_json::to_vec(&value).unwrap_or(result)
+}
+
+/// Adds the resolved references of their VT to serialized results and, when an enrichment is
+/// configured, the information about the referenced CVEs.
+///
+/// Results without an OID or whose VT has no valid references are returned unchanged.
+pub async fn enrich<S, DB>(
+    ctx: &Context<S, DB>,
+    results: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+) -> Box<dyn Iterator<Item = Vec<u8>> + Send>
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let results: Vec<Vec<u8>> = results.collect();
+    let mut references: HashMap<String, Vec<Reference>> = HashMap::new();
+    for oid in results.iter().filter_map(|x| oid(x)) {
+        if references.contains_key(&oid) {
+            continue;
+        }
+        let resolved = match ctx.scheduler.vt_by_oid(&oid).await {
+            Ok(Some(vt)) => Reference::resolve(vt.references.iter().map(|x| (x.class(), x.id()))),
+            Ok(None) => vec![],
+            Err(e) => {
+                tracing::debug!(oid, %e, "Unable to get VT of result");
+                vec![]
+            }
+        };
+        references.insert(oid, resolved);
+    }
+    let dataset = ctx.enrichment.as_ref().map(|x| x.dataset.read().unwrap());
+    let results: Vec<Vec<u8>> = results
+        .into_iter()
+        .map(|x| {
+            let Some(references) = oid(&x)
+                .and_then(|o| references.get(&o))
+                .filter(|x| !x.is_empty())
+            else {
+                return x;
+            };
+            let x = with_references(x, references);
+            let cves: Vec<String> = references
+                .iter()
+                .filter(|x| x.r_type == "cve")
+                .map(|x| x.id.clone())
+                .collect();
+            match &dataset {
+                Some(dataset) if !cves.is_empty() => dataset.enrich(x, &cves),
+                _ => x,
+            }
+        })
+        .collect();
+    Box::new(results.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::Reference;
+
+    #[test]
+    fn with_references() {
+        let result = br#"{"id":0,"type":"alarm","oid":"1.2.3"}"#.to_vec();
+        let references = Reference::resolve([("cve", "CVE-2021-44228"), ("url", "invalid")]);
+        let value: serde_json::Value =
+            serde_json::from_slice(&super::with_references(result, &references)).unwrap();
+        assert_eq!(value["oid"], "1.2.3");
+        assert_eq!(value["references"].as_array().unwrap().len(), 1);
+        assert_eq!(value["references"][0]["type"], "cve");
+        assert_eq!(
+            value["references"][0]["url"],
+            "https://nvd.nist.gov/vuln/detail/CVE-2021-44228"
+        );
+    }
+}
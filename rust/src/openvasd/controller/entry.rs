@@ -6,22 +6,34 @@
 //!
 //! All known paths must be handled in the entrypoint function.
 
-use std::{fmt::Display, marker::PhantomData, sync::Arc};
+use std::{collections::HashSet, fmt::Display, marker::PhantomData, sync::Arc};
 
-use super::{context::Context, ClientIdentifier};
+use super::{context::Context, Caller, ClientIdentifier};
 
 use hyper::{Method, Request};
-use scannerlib::models::scanner::{ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper};
-use scannerlib::models::{scanner::*, Action, Phase, Scan, ScanAction};
-use scannerlib::notus::NotusError;
+use scannerlib::alive::AliveTest;
+use scannerlib::models::scanner::{
+    ScanDeleter, ScanPlanner, ScanResultFetcher, ScanStarter, ScanStopper,
+};
+use scannerlib::models::{
+    self, scanner::*, Action, Annotation, NotusResults, Phase, Scan, ScanAction, Status, Target,
+};
+use scannerlib::notus::{image::ImageInventory, sbom::SbomInventory, NotusError};
+use scannerlib::report::{Report, ReportFormat, ReportKind};
+use scannerlib::storage::item::Nvt;
 
 use crate::{
-    config,
-    controller::ClientHash,
+    cluster, config,
     notus::NotusScanner,
+    oidc::Role,
+    response::Validators,
     scheduling,
-    storage::{NVTStorer as _, ProgressGetter as _, ScanIDClientMapper as _, ScanStorer as _},
+    storage::{
+        AnnotationStorer as _, FamilySummary, NVTStorer as _, ProgressGetter as _,
+        ScanIDClientMapper as _, ScanStorer as _, Storage as _, VtFilter,
+    },
 };
+use tracing::Instrument as _;
 
 #[derive(PartialEq, Eq)]
 enum HealthOpts {
@@ -42,14 +54,56 @@ enum KnownPaths {
     ScanPreferences,
     /// /scans/{id}/results/{result_id}
     ScanResults(String, Option<String>),
+    /// /scans/{id}/results/stream
+    ScanResultsStream(String),
+    /// /scans/{id}/results/download
+    ScanResultsDownload(String),
     /// /scans/{id}/status
     ScanStatus(String),
+    /// /scans/{id}/report
+    ScanReport(String),
+    /// /scans/{id}/hosts
+    ScanHosts(String),
+    /// /scans/{id}/annotations/{annotation_id}
+    ScanAnnotations(String, Option<String>),
+    /// /scans/{id}/plan
+    ScanPlan(String),
+    /// /scans/{id}/clone
+    ScanClone(String),
+    /// /scans/{id}/rescan
+    ScanRescan(String),
+    /// /scans/{id}/runs
+    ScanRuns(String),
+    /// /scans/{id}/trend
+    ScanTrend(String),
+    /// /scans/{id}/bundle
+    ScanBundle(String),
+    /// /scans/import
+    ScanImport,
     /// /vts
     Vts(Option<String>),
+    /// /vts/{oid}/preferences
+    VtPreferences(String),
+    /// /feed/report
+    FeedReport,
+    /// /feed/families
+    FeedFamilies,
     /// /health
     Health(HealthOpts),
     /// /notus/{os}
     Notus(Option<String>),
+    /// /notus/image
+    NotusImage,
+    /// /notus/sbom
+    NotusSbom,
+    /// /alive
+    Alive,
+    /// /cluster/workers
+    ClusterWorkers,
+    /// /cluster/register
+    ClusterRegister,
+    /// /storage/compact
+    StorageCompact,
     /// Not supported
     Unknown,
 }
@@ -58,7 +112,16 @@ impl KnownPaths {
     pub fn requires_id(&self) -> bool {
         !matches!(
             self,
-            Self::Unknown | Self::Health(_) | Self::Vts(_) | Self::Notus(_)
+            Self::Unknown
+                | Self::Health(_)
+                | Self::Vts(_)
+                | Self::VtPreferences(_)
+                | Self::FeedReport
+                | Self::FeedFamilies
+                | Self::Notus(_)
+                | Self::NotusImage
+                | Self::NotusSbom
+                | Self::ClusterRegister
         )
     }
 
@@ -72,15 +135,33 @@ impl KnownPaths {
                     tracing::debug!(?mode, ?path);
                     match parts.next() {
                         Some(id) => match parts.next() {
-                            Some("results") => KnownPaths::ScanResults(
+                            Some("results") => match parts.next() {
+                                Some("stream") => KnownPaths::ScanResultsStream(id.to_string()),
+                                Some("download") => KnownPaths::ScanResultsDownload(id.to_string()),
+                                rid => KnownPaths::ScanResults(
+                                    id.to_string(),
+                                    rid.map(|s| s.to_string()),
+                                ),
+                            },
+                            Some("status") => KnownPaths::ScanStatus(id.to_string()),
+                            Some("report") => KnownPaths::ScanReport(id.to_string()),
+                            Some("hosts") => KnownPaths::ScanHosts(id.to_string()),
+                            Some("annotations") => KnownPaths::ScanAnnotations(
                                 id.to_string(),
                                 parts.next().map(|s| s.to_string()),
                             ),
-                            Some("status") => KnownPaths::ScanStatus(id.to_string()),
+                            Some("plan") => KnownPaths::ScanPlan(id.to_string()),
+                            Some("clone") => KnownPaths::ScanClone(id.to_string()),
+                            Some("rescan") => KnownPaths::ScanRescan(id.to_string()),
+                            Some("runs") => KnownPaths::ScanRuns(id.to_string()),
+                            Some("trend") => KnownPaths::ScanTrend(id.to_string()),
+                            Some("bundle") => KnownPaths::ScanBundle(id.to_string()),
                             Some(_) => KnownPaths::Unknown,
                             None => {
                                 if id == "preferences" {
                                     KnownPaths::ScanPreferences
+                                } else if id == "import" {
+                                    KnownPaths::ScanImport
                                 } else {
                                     KnownPaths::Scans(Some(id.to_string()))
                                 }
@@ -94,14 +175,36 @@ impl KnownPaths {
                     KnownPaths::Unknown
                 }
             },
-            Some("vts") => match parts.next() {
-                Some(oid) => KnownPaths::Vts(Some(oid.to_string())),
-                None => KnownPaths::Vts(None),
+            Some("alive") => match mode {
+                config::Mode::Service if parts.next().is_none() => KnownPaths::Alive,
+                _ => KnownPaths::Unknown,
+            },
+            Some("cluster") => match (mode, parts.next(), parts.next()) {
+                (config::Mode::Service, Some("workers"), None) => KnownPaths::ClusterWorkers,
+                (config::Mode::Service, Some("register"), None) => KnownPaths::ClusterRegister,
+                _ => KnownPaths::Unknown,
+            },
+            Some("vts") => match (parts.next(), parts.next()) {
+                (Some(oid), None) => KnownPaths::Vts(Some(oid.to_string())),
+                (Some(oid), Some("preferences")) => KnownPaths::VtPreferences(oid.to_string()),
+                (None, _) => KnownPaths::Vts(None),
+                _ => KnownPaths::Unknown,
+            },
+            Some("feed") => match (parts.next(), parts.next()) {
+                (Some("report"), None) => KnownPaths::FeedReport,
+                (Some("families"), None) => KnownPaths::FeedFamilies,
+                _ => KnownPaths::Unknown,
             },
             Some("notus") => match parts.next() {
+                Some("image") => KnownPaths::NotusImage,
+                Some("sbom") => KnownPaths::NotusSbom,
                 Some(os) => KnownPaths::Notus(Some(os.to_string())),
                 None => KnownPaths::Notus(None),
             },
+            Some("storage") => match (parts.next(), parts.next()) {
+                (Some("compact"), None) => KnownPaths::StorageCompact,
+                _ => KnownPaths::Unknown,
+            },
             Some("health") => match parts.next() {
                 Some("ready") => KnownPaths::Health(HealthOpts::Ready),
                 Some("alive") => KnownPaths::Health(HealthOpts::Alive),
@@ -117,12 +220,39 @@ impl KnownPaths {
 
     fn scan_id(&self) -> Option<&str> {
         match self {
-            Self::Scans(Some(id)) | Self::ScanResults(id, _) | Self::ScanStatus(id) => Some(id),
+            Self::Scans(Some(id))
+            | Self::ScanResults(id, _)
+            | Self::ScanResultsStream(id)
+            | Self::ScanResultsDownload(id)
+            | Self::ScanStatus(id)
+            | Self::ScanReport(id)
+            | Self::ScanHosts(id)
+            | Self::ScanAnnotations(id, _)
+            | Self::ScanPlan(id)
+            | Self::ScanClone(id)
+            | Self::ScanRescan(id)
+            | Self::ScanRuns(id)
+            | Self::ScanTrend(id)
+            | Self::ScanBundle(id) => Some(id),
             _ => None,
         }
     }
 }
 
+/// Response of a notus scan of a container image or SBOM
+#[derive(serde::Serialize, Debug)]
+struct InventoryResults {
+    /// The detected product
+    os: String,
+    /// The installed packages
+    packages: Vec<String>,
+    /// Components of a SBOM that are not checked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unmatched: Vec<String>,
+    /// The vulnerable packages by OID of the notus VT
+    results: NotusResults,
+}
+
 impl Display for KnownPaths {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -132,18 +262,239 @@ impl Display for KnownPaths {
                 write!(f, "/scans/{}/results/{}", id, result_id)
             }
             KnownPaths::ScanResults(id, None) => write!(f, "/scans/{}/results", id),
+            KnownPaths::ScanResultsStream(id) => write!(f, "/scans/{}/results/stream", id),
+            KnownPaths::ScanResultsDownload(id) => write!(f, "/scans/{}/results/download", id),
             KnownPaths::ScanStatus(id) => write!(f, "/scans/{}/status", id),
+            KnownPaths::ScanReport(id) => write!(f, "/scans/{}/report", id),
+            KnownPaths::ScanHosts(id) => write!(f, "/scans/{}/hosts", id),
+            KnownPaths::ScanAnnotations(id, Some(annotation_id)) => {
+                write!(f, "/scans/{}/annotations/{}", id, annotation_id)
+            }
+            KnownPaths::ScanAnnotations(id, None) => write!(f, "/scans/{}/annotations", id),
+            KnownPaths::ScanPlan(id) => write!(f, "/scans/{}/plan", id),
+            KnownPaths::ScanClone(id) => write!(f, "/scans/{}/clone", id),
+            KnownPaths::ScanRescan(id) => write!(f, "/scans/{}/rescan", id),
+            KnownPaths::ScanRuns(id) => write!(f, "/scans/{}/runs", id),
+            KnownPaths::ScanTrend(id) => write!(f, "/scans/{}/trend", id),
+            KnownPaths::ScanBundle(id) => write!(f, "/scans/{}/bundle", id),
+            KnownPaths::ScanImport => write!(f, "/scans/import"),
             KnownPaths::Unknown => write!(f, "Unknown"),
             KnownPaths::Vts(None) => write!(f, "/vts"),
             KnownPaths::Vts(Some(oid)) => write!(f, "/vts/{oid}"),
+            KnownPaths::VtPreferences(oid) => write!(f, "/vts/{oid}/preferences"),
+            KnownPaths::FeedReport => write!(f, "/feed/report"),
+            KnownPaths::FeedFamilies => write!(f, "/feed/families"),
             KnownPaths::Notus(Some(os)) => write!(f, "/notus/{}", os),
             KnownPaths::Notus(None) => write!(f, "/notus"),
+            KnownPaths::NotusImage => write!(f, "/notus/image"),
+            KnownPaths::NotusSbom => write!(f, "/notus/sbom"),
             KnownPaths::Health(HealthOpts::Alive) => write!(f, "/health/alive"),
             KnownPaths::Health(HealthOpts::Ready) => write!(f, "/health/ready"),
             KnownPaths::Health(HealthOpts::Started) => write!(f, "/health/started"),
             KnownPaths::ScanPreferences => write!(f, "/scans/preferences"),
+            KnownPaths::Alive => write!(f, "/alive"),
+            KnownPaths::ClusterWorkers => write!(f, "/cluster/workers"),
+            KnownPaths::ClusterRegister => write!(f, "/cluster/register"),
+            KnownPaths::StorageCompact => write!(f, "/storage/compact"),
+        }
+    }
+}
+
+/// Parses the query of /vts into whether the meta information is requested and the filter.
+///
+/// Supported are `information`, `family`, `category`, `name`, `range` and `deprecated`. The range
+/// is given as `begin-end` of the matching VTs, like the range of results, both are included.
+/// Deprecated VTs are only listed when `deprecated` is set. Unknown parameters are ignored.
+fn vts_query(query: Option<&str>) -> Result<(bool, VtFilter), String> {
+    let mut meta = false;
+    let mut filter = VtFilter::default();
+    for pair in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|x| !x.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = urlencoding::decode(&value.replace('+', " "))
+            .map_err(|e| format!("{key}: {e}"))?
+            .into_owned();
+        match key {
+            "information" => meta = matches!(value.as_str(), "true" | "1"),
+            "deprecated" => filter.deprecated = matches!(value.as_str(), "true" | "1"),
+            "family" => filter.family = Some(value),
+            "name" => filter.name = Some(value),
+            "category" => {
+                let category = serde_json::from_value(serde_json::Value::String(value.clone()))
+                    .or_else(|_| value.parse())
+                    .map_err(|_| format!("unknown category {value}"))?;
+                filter.category = Some(category);
+            }
+            "range" => {
+                let mut range = value.split('-');
+                let begin = range.next().unwrap_or_default().parse::<usize>();
+                let end = range.next().unwrap_or_default().parse::<usize>();
+                match (begin, end) {
+                    (Ok(begin), Ok(end)) => {
+                        filter.begin = begin;
+                        filter.end = Some(end + 1);
+                    }
+                    (Ok(begin), Err(_)) => filter.begin = begin,
+                    _ => return Err(format!("invalid range {value}")),
+                }
+            }
+            _ => tracing::debug!(key, "ignoring unknown query parameter"),
+        }
+    }
+    Ok((meta, filter))
+}
+
+/// Query of /scans/{id}/results
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ResultsQuery {
+    begin: Option<usize>,
+    /// Exclusive end of the range
+    end: Option<usize>,
+    min_qod: Option<u8>,
+    /// Includes the results suppressed by the auto-triage
+    suppressed: bool,
+}
+
+/// Parses the query of /scans/{id}/results.
+///
+/// An invalid range returns all results.
+fn results_query(query: Option<&str>) -> Result<ResultsQuery, String> {
+    let mut result = ResultsQuery::default();
+    for pair in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|x| !x.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key {
+            "range" => {
+                let mut parts = value.split('-');
+                let begin = parts.next().unwrap_or_default().parse::<usize>();
+                let end = parts.next().unwrap_or_default().parse::<usize>();
+                (result.begin, result.end) = match (begin, end) {
+                    (Ok(begin), Ok(end)) => (Some(begin), Some(end + 1)),
+                    (Ok(begin), Err(_)) => (Some(begin), None),
+                    _ => (None, None),
+                };
+            }
+            "min_qod" => result.min_qod = Some(min_qod_value(value)?),
+            "suppressed" => result.suppressed = matches!(value, "true" | "1"),
+            _ => tracing::debug!(key, "ignoring unknown query parameter"),
+        }
+    }
+    Ok(result)
+}
+
+/// Parses a minimum QoD from 0 to 100
+fn min_qod_value(value: &str) -> Result<u8, String> {
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|x| *x <= 100)
+        .ok_or_else(|| format!("invalid min_qod {value}, expected a number from 0 to 100"))
+}
+
+/// Returns the response of a scan that could not be started.
+fn start_failed(
+    response: &crate::response::Response,
+    id: &str,
+    e: scheduling::Error,
+) -> crate::response::Result {
+    match e {
+        scheduling::Error::ScanRunning | scheduling::Error::ScanAlreadyQueued => {
+            use Phase::*;
+            let expected = &[Stored, Stopped, Failed, Succeeded];
+            response.not_accepted(&Requested, expected)
+        }
+        scheduling::Error::NotFound => response.not_found("scan", id),
+        e @ scheduling::Error::VtsNotAvailable(_) => response.bad_request(&e.to_string()),
+        scheduling::Error::QueueFull => {
+            response.service_unavailable("Queue is already full. Try again later.")
+        }
+        scheduling::Error::InsufficientResources(resources) => {
+            let resources: Vec<String> = resources.iter().map(|x| x.to_string()).collect();
+            response.service_unavailable(&format!(
+                "Not enough resources of types: {}. Try again later.",
+                resources.join(",")
+            ))
+        }
+        scheduling::Error::UnsupportedResume => {
+            response.not_implemented("Resuming task is currently not possible, please create a new scan excluding the finished hosts.")
+        }
+        e => response.internal_server_error(&e),
+    }
+}
+
+/// Returns true when the scan was imported from a bundle and is therefore read-only.
+async fn is_imported<DB, S>(scheduler: &scheduling::Scheduler<DB, S>, id: &str) -> bool
+where
+    DB: crate::storage::Storage + Send + Sync + 'static,
+    S: Send + Sync,
+{
+    scheduler
+        .get_scan(id)
+        .await
+        .is_ok_and(|(scan, _)| scan.imported.is_some())
+}
+
+/// Parses the query of /scans/{id}/report into the kind, format and minimum QoD of the report.
+///
+/// Without parameters a technical HTML report is rendered. Unknown parameters are ignored.
+fn report_query(query: Option<&str>) -> Result<(ReportKind, ReportFormat, Option<u8>), String> {
+    let mut kind = ReportKind::default();
+    let mut format = ReportFormat::default();
+    let mut min_qod = None;
+    for pair in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|x| !x.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key {
+            "kind" => kind = value.parse()?,
+            "format" => format = value.parse()?,
+            "min_qod" => min_qod = Some(min_qod_value(value)?),
+            _ => tracing::debug!(key, "ignoring unknown query parameter"),
         }
     }
+    Ok((kind, format, min_qod))
+}
+
+/// Returns the validators of the status of a scan.
+///
+/// A finished scan cannot be started again, its end time is therefore the last modification.
+fn status_validators(status: &Status) -> Validators {
+    Validators::new(serde_json::to_vec(status).unwrap_or_default())
+        .last_modified(status.end_time.filter(|_| status.is_done()))
+}
+
+/// Returns the validators of the results of a scan.
+///
+/// Results are only appended, so their amount is the version of the results.
+///
+/// Annotations change the served results without changing their amount, they are therefore part
+/// of the version. As removing an annotation doesn't leave a timestamp there is no last
+/// modification of annotated results.
+fn results_validators(status: &Status, count: usize, annotations: &[Annotation]) -> Validators {
+    Validators::new((status.start_time, count, annotations)).last_modified(
+        status
+            .end_time
+            .filter(|_| status.is_done() && annotations.is_empty()),
+    )
+}
+
+/// Adds the validators to the response when there are any.
+fn with_validators(
+    validators: Option<&Validators>,
+    response: crate::response::Result,
+) -> crate::response::Result {
+    match validators {
+        Some(validators) => validators.apply(response),
+        None => response,
+    }
 }
 
 pub struct EntryPoint<S, DB, R> {
@@ -168,6 +519,7 @@ where
         + ScanStopper
         + ScanDeleter
         + ScanResultFetcher
+        + ScanPlanner
         + std::marker::Send
         + std::marker::Sync
         + 'static,
@@ -187,55 +539,39 @@ where
     fn call(&self, req: Request<R>) -> Self::Future {
         let ctx = self.ctx.clone();
         let cid = self.cid.clone();
-        Box::pin(async move {
+        let span = tracing::info_span!(
+            "request",
+            otel.kind = "server",
+            http.request.method = %req.method(),
+            url.path = req.uri().path(),
+            http.response.status_code = tracing::field::Empty,
+            traceparent = req.headers().get("traceparent").and_then(|x| x.to_str().ok()),
+        );
+        let handle = async move {
             use KnownPaths::*;
             let kp = KnownPaths::from_path(req.uri().path(), &ctx.mode);
             // on head requests we just return an empty response, except for /scans
             if req.method() == Method::HEAD && kp != KnownPaths::Scans(None) {
                 return Ok(ctx.response.empty(hyper::StatusCode::OK));
             }
-            let cid: Option<ClientHash> = {
-                match &*cid {
-                    ClientIdentifier::Disabled => {
-                        if let Some(key) = ctx.api_key.as_ref() {
-                            match req.headers().get("x-api-key") {
-                                Some(v) if v == key => ctx.api_key.as_ref().map(|x| x.into()),
-                                Some(v) => {
-                                    tracing::debug!("{} {} invalid key: {:?}", req.method(), kp, v);
-                                    None
-                                }
-                                None => None,
-                            }
-                        } else {
-                            Some("disabled".into())
-                        }
-                    }
-                    ClientIdentifier::Known(cid) => Some(cid.clone()),
-                    ClientIdentifier::Unknown => {
-                        if let Some(key) = ctx.api_key.as_ref() {
-                            match req.headers().get("x-api-key") {
-                                Some(v) if v == key => ctx.api_key.as_ref().map(|x| x.into()),
-                                Some(v) => {
-                                    tracing::debug!("{} {} invalid key: {:?}", req.method(), kp, v);
-                                    None
-                                }
-                                None => None,
-                            }
-                        } else {
-                            // We don't allow no api key and no client certs when we have a server
-                            // certificate to prevent accidental misconfiguration.
-                            None
-                        }
-                    }
-                }
-            };
+            // clients authenticated by a token are restricted to their role
+            let Caller { cid, role, tenant } = Caller::identify(&ctx, &cid, req.headers()).await;
 
-            if kp.requires_id() && cid.is_none() {
+            // uploading VTs replaces the feed and is never anonymous
+            let uploads_vts = kp == Vts(None) && req.method() == Method::POST;
+            if (kp.requires_id() || uploads_vts) && cid.is_none() {
                 tracing::debug!("{} {} unauthorized", req.method(), kp);
                 return Ok(ctx.response.unauthorized());
             }
+            if role.is_some_and(|x| !x.allows(req.method())) {
+                tracing::debug!("{} {} forbidden for role {:?}", req.method(), kp, role);
+                return Ok(ctx.response.forbidden());
+            }
             let cid = cid.unwrap_or_default();
-            if let Some(scan_id) = kp.scan_id() {
+            // clients of a tenant only see and scan the VTs of its feed subset
+            let subset = crate::tenancy::subset(&ctx.tenancy, tenant.as_deref(), role);
+            // admins may access the scans of every client
+            if let Some(scan_id) = kp.scan_id().filter(|_| role != Some(Role::Admin)) {
                 if !ctx
                     .scheduler
                     .is_client_allowed(scan_id.to_owned(), &cid)
@@ -258,6 +594,19 @@ where
                 query=req.uri().query(),
                 "process call",
             );
+            // VT metadata only changes with the feed
+            let vts_validators = match kp {
+                Vts(_) | VtPreferences(_) | FeedFamilies if req.method() == Method::GET => {
+                    ctx.scheduler.vts_version().await.map(Validators::new)
+                }
+                _ => None,
+            };
+            if let Some(validators) = vts_validators
+                .as_ref()
+                .filter(|x| x.is_fresh(req.headers()))
+            {
+                return Ok(ctx.response.not_modified(validators));
+            }
             match (req.method(), kp) {
                 (&Method::HEAD, Scans(None)) => {
                     Ok(ctx.response.empty(hyper::StatusCode::NO_CONTENT))
@@ -302,9 +651,120 @@ where
                         Err(resp) => Ok(resp),
                     }
                 }
+                (&Method::POST, NotusImage) => match &ctx.notus {
+                    Some(notus) => {
+                        let bytes = match crate::request::bytes_request(&ctx.response, req).await {
+                            Ok(x) => x,
+                            Err(resp) => return Ok(resp),
+                        };
+                        let inventory = tokio::task::spawn_blocking(move || {
+                            ImageInventory::read(std::io::Cursor::new(bytes))
+                        })
+                        .await;
+                        let inventory = match inventory {
+                            Ok(Ok(x)) => x,
+                            Ok(Err(err)) => return Ok(ctx.response.bad_request(&format!("{err}"))),
+                            Err(err) => return Ok(ctx.response.internal_server_error(&err)),
+                        };
+                        let os = inventory.product();
+                        match notus.scan(&os, &inventory.packages).await {
+                            Ok(results) => Ok(ctx.response.ok(&InventoryResults {
+                                os,
+                                packages: inventory.packages,
+                                unmatched: vec![],
+                                results,
+                            })),
+                            Err(NotusError::UnknownProduct(_)) => {
+                                Ok(ctx.response.not_found("advisories", &os))
+                            }
+                            Err(err) => Ok(ctx.response.internal_server_error(&err)),
+                        }
+                    }
+                    None => Ok(ctx.response.empty(hyper::StatusCode::SERVICE_UNAVAILABLE)),
+                },
+                (&Method::POST, NotusSbom) => match &ctx.notus {
+                    Some(notus) => {
+                        let bytes = match crate::request::bytes_request(&ctx.response, req).await {
+                            Ok(x) => x,
+                            Err(resp) => return Ok(resp),
+                        };
+                        let inventory = match SbomInventory::parse(&bytes) {
+                            Ok(x) => x,
+                            Err(err) => return Ok(ctx.response.bad_request(&format!("{err}"))),
+                        };
+                        let Some(os) = inventory.product else {
+                            return Ok(ctx
+                                .response
+                                .bad_request(&"The SBOM does not describe a distribution"));
+                        };
+                        match notus.scan(&os, &inventory.packages).await {
+                            Ok(results) => Ok(ctx.response.ok(&InventoryResults {
+                                os,
+                                packages: inventory.packages,
+                                unmatched: inventory.unmatched,
+                                results,
+                            })),
+                            Err(NotusError::UnknownProduct(_)) => {
+                                Ok(ctx.response.not_found("advisories", &os))
+                            }
+                            Err(err) => Ok(ctx.response.internal_server_error(&err)),
+                        }
+                    }
+                    None => Ok(ctx.response.empty(hyper::StatusCode::SERVICE_UNAVAILABLE)),
+                },
+                (&Method::POST, Alive) => {
+                    match crate::request::json_request::<Target, _>(&ctx.response, req).await {
+                        Ok(target) => {
+                            let test = AliveTest::from(&target);
+                            match tokio::task::spawn_blocking(move || test.check_target(&target))
+                                .await
+                            {
+                                Ok(status) => Ok(ctx.response.ok(&status)),
+                                Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                            }
+                        }
+                        Err(resp) => Ok(resp),
+                    }
+                }
+                (&Method::GET, ClusterWorkers) => match &ctx.cluster {
+                    Some(cluster) => Ok(ctx.response.ok(&cluster.workers())),
+                    None => Ok(ctx.response.not_found("cluster", "workers")),
+                },
+                (&Method::POST, ClusterRegister) => {
+                    let Some((cluster, token)) = ctx
+                        .cluster
+                        .as_ref()
+                        .and_then(|x| Some((x, x.registration_token()?)))
+                    else {
+                        return Ok(ctx.response.not_found("cluster", "register"));
+                    };
+                    if req
+                        .headers()
+                        .get("x-registration-token")
+                        .is_none_or(|x| x != token)
+                    {
+                        tracing::debug!("{} /cluster/register invalid token", req.method());
+                        return Ok(ctx.response.unauthorized());
+                    }
+                    match crate::request::json_request::<cluster::Registration, _>(
+                        &ctx.response,
+                        req,
+                    )
+                    .await
+                    {
+                        Ok(registration) => {
+                            cluster.register(registration);
+                            Ok(ctx.response.no_content())
+                        }
+                        Err(resp) => Ok(resp),
+                    }
+                }
                 (&Method::POST, Scans(None)) => {
                     match crate::request::json_request::<Scan, _>(&ctx.response, req).await {
                         Ok(mut scan) => {
+                            if let Err(e) = ctx.admit(&scan, subset.as_ref()).await {
+                                return Ok(ctx.response.bad_request(&e.to_string()));
+                            }
                             let id = if !scan.scan_id.is_empty() {
                                 scan.scan_id.to_string()
                             } else {
@@ -326,26 +786,9 @@ where
                         .map(|a| a.action)
                     {
                         Ok(Action::Start) => {
-                            match ctx.scheduler.start_scan_by_id(&id).await {
+                            match ctx.scheduler.start_scan_within(&id, subset.as_ref()).await {
                                 Ok(_) => Ok(ctx.response.no_content()),
-                                Err(scheduling::Error::ScanRunning)
-                                | Err(scheduling::Error::ScanAlreadyQueued) => {
-                                    use Phase::*;
-                                    let expected = &[Stored, Stopped, Failed, Succeeded];
-                                    Ok(ctx.response.not_accepted(&Requested, expected))
-                                }
-                                Err(scheduling::Error::NotFound) => {
-                                    Ok(ctx.response.not_found("scan", &id))
-                                }
-                                Err(scheduling::Error::QueueFull) => {
-                                    Ok(ctx.response.service_unavailable(
-                                        "Queue is already full. Try again later.",
-                                    ))
-                                }
-                                Err(scheduling::Error::UnsupportedResume) => {
-                                    Ok(ctx.response.not_implemented("Resuming task is currently not possible, please create a new scan excluding the finished hosts."))
-                                }
-                                Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                                Err(e) => Ok(start_failed(&ctx.response, &id, e)),
                             }
                         }
                         Ok(Action::Stop) => match ctx.scheduler.stop_scan(id).await {
@@ -355,6 +798,142 @@ where
                         Err(resp) => Ok(resp),
                     }
                 }
+                (&Method::POST, ScanClone(id)) => {
+                    let start = req
+                        .uri()
+                        .query()
+                        .unwrap_or_default()
+                        .split('&')
+                        .any(|x| matches!(x, "start" | "start=true" | "start=1"));
+                    let clone = match ctx.scheduler.clone_scan_by_id(&id, &cid).await {
+                        Ok(clone) => clone,
+                        Err(scheduling::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scan", &id));
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    tracing::debug!(%id, %clone, "Scan cloned");
+                    if start {
+                        if let Err(e) = ctx
+                            .scheduler
+                            .start_scan_within(&clone, subset.as_ref())
+                            .await
+                        {
+                            // the client does not learn about a clone that could not be started
+                            if let Err(e) = ctx.scheduler.delete_scan_by_id(&clone).await {
+                                tracing::warn!(%clone, %e, "unable to remove clone");
+                            }
+                            return Ok(start_failed(&ctx.response, &clone, e));
+                        }
+                    }
+                    Ok(ctx.response.created(&clone))
+                }
+                (&Method::POST, ScanRescan(id)) => {
+                    let start = req
+                        .uri()
+                        .query()
+                        .unwrap_or_default()
+                        .split('&')
+                        .any(|x| matches!(x, "start" | "start=true" | "start=1"));
+                    let rescan =
+                        match crate::request::json_request::<models::Rescan, _>(&ctx.response, req)
+                            .await
+                        {
+                            Ok(x) => x,
+                            Err(resp) => return Ok(resp),
+                        };
+                    let rescan = match ctx.scheduler.rescan_scan_by_id(&id, rescan, &cid).await {
+                        Ok(rescan) => rescan,
+                        Err(scheduling::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scan", &id));
+                        }
+                        Err(
+                            e @ (scheduling::Error::NotFinished
+                            | scheduling::Error::InvalidRescan(_)),
+                        ) => return Ok(ctx.response.bad_request(&e.to_string())),
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    tracing::debug!(%id, %rescan, "Scan subset rescheduled");
+                    if start {
+                        if let Err(e) = ctx
+                            .scheduler
+                            .start_scan_within(&rescan, subset.as_ref())
+                            .await
+                        {
+                            if let Err(e) = ctx.scheduler.delete_scan_by_id(&rescan).await {
+                                tracing::warn!(%rescan, %e, "unable to remove rescan");
+                            }
+                            return Ok(start_failed(&ctx.response, &rescan, e));
+                        }
+                    }
+                    Ok(ctx.response.created(&rescan))
+                }
+                (&Method::GET, ScanBundle(id)) => {
+                    let Some(key) = ctx.bundle.key.as_deref() else {
+                        return Ok(ctx.response.not_implemented("No bundle key configured"));
+                    };
+                    match ctx.scheduler.export_scan_by_id(&id).await {
+                        Ok(content) => match crate::bundle::seal(&content, key) {
+                            Ok(bundle) => Ok(ctx.response.ok_document("application/gzip", bundle)),
+                            Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                        },
+                        Err(scheduling::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans", &id))
+                        }
+                        Err(e @ scheduling::Error::NotFinished) => {
+                            Ok(ctx.response.bad_request(&e.to_string()))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::POST, ScanImport) => {
+                    let Some(key) = ctx.bundle.key.clone() else {
+                        return Ok(ctx.response.not_implemented("No bundle key configured"));
+                    };
+                    let bytes = match crate::request::bytes_request(&ctx.response, req).await {
+                        Ok(x) => x,
+                        Err(resp) => return Ok(resp),
+                    };
+                    let content =
+                        tokio::task::spawn_blocking(move || crate::bundle::open(&bytes, &key))
+                            .await;
+                    let content = match content {
+                        Ok(Ok(x)) => x,
+                        Ok(Err(e)) => return Ok(ctx.response.bad_request(&e.to_string())),
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    match ctx.scheduler.import_scan(content, &cid).await {
+                        Ok(id) => {
+                            tracing::debug!(%id, "Scan imported");
+                            Ok(ctx.response.created(&id))
+                        }
+                        Err(e @ scheduling::Error::NotFinished) => {
+                            Ok(ctx.response.bad_request(&e.to_string()))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanRuns(id)) => match ctx.scheduler.series_of(&id, &cid).await {
+                    Ok(runs) => {
+                        let ids: Vec<String> = runs.into_iter().map(|(_, id)| id).collect();
+                        Ok(ctx.response.ok(&ids))
+                    }
+                    Err(scheduling::Error::NotFound) => Ok(ctx.response.not_found("scan", &id)),
+                    Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                },
+                (&Method::GET, ScanTrend(id)) => {
+                    let runs = match ctx.scheduler.series_of(&id, &cid).await {
+                        Ok(runs) => runs,
+                        Err(scheduling::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scan", &id));
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    match ctx.scheduler.trend(&runs).await {
+                        Ok(trend) => Ok(ctx.response.ok(&trend)),
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
                 (&Method::GET, Scans(None)) => {
                     if ctx.enable_get_scans {
                         match ctx.scheduler.get_scans_of_client_id(&cid).await {
@@ -370,16 +949,12 @@ where
                     .ok_static(crate::preference::PREFERENCES_JSON.as_bytes())),
                 (&Method::GET, Scans(Some(id))) => match ctx.scheduler.get_scan(&id).await {
                     Ok((mut scan, _)) => {
-                        let credentials = scan
+                        scan.target = scan
                             .target
-                            .credentials
-                            .into_iter()
-                            .map(move |c| {
-                                let c = c.map_password::<_, Error>(|_| Ok("***".to_string()));
-                                c.unwrap()
+                            .map_credentials::<_, Error>(|c| {
+                                c.map_password(|_| Ok("***".to_string()))
                             })
-                            .collect::<Vec<_>>();
-                        scan.target.credentials = credentials;
+                            .unwrap();
                         Ok(ctx.response.ok(&scan))
                     }
                     Err(crate::storage::Error::NotFound) => {
@@ -388,12 +963,31 @@ where
                     Err(e) => Ok(ctx.response.internal_server_error(&e)),
                 },
                 (&Method::GET, ScanStatus(id)) => match ctx.scheduler.get_scan(&id).await {
-                    Ok((_, status)) => Ok(ctx.response.ok(&status)),
+                    Ok((_, status)) => {
+                        let validators = status_validators(&status);
+                        if validators.is_fresh(req.headers()) {
+                            Ok(ctx.response.not_modified(&validators))
+                        } else {
+                            Ok(validators.apply(ctx.response.ok(&status)))
+                        }
+                    }
                     Err(crate::storage::Error::NotFound) => {
                         Ok(ctx.response.not_found("scans/status", &id))
                     }
                     Err(e) => Ok(ctx.response.internal_server_error(&e)),
                 },
+                (&Method::GET, ScanPlan(id)) => match ctx.scheduler.plan_scan_by_id(&id).await {
+                    Ok(plan) => Ok(ctx.response.ok(&plan)),
+                    Err(scheduling::Error::NotFound) => {
+                        Ok(ctx.response.not_found("scans/plan", &id))
+                    }
+                    Err(scheduling::Error::Scan(
+                        scannerlib::models::scanner::Error::NotSupported(x),
+                    )) => Ok(ctx
+                        .response
+                        .not_implemented(&format!("{x} is not supported"))),
+                    Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                },
                 (&Method::DELETE, Scans(Some(id))) => {
                     match ctx.scheduler.delete_scan_by_id(&id).await {
                         Ok(_) => Ok(ctx.response.no_content()),
@@ -404,64 +998,344 @@ where
                     }
                 }
                 (&Method::GET, ScanResults(id, rid)) => {
-                    let (begin, end) = {
-                        if let Some(id) = rid {
-                            match id.parse::<usize>() {
-                                Ok(id) => (Some(id), Some(id + 1)),
-                                Err(_) => (None, None),
-                            }
-                        } else {
-                            let query = req.uri().query().unwrap_or_default();
-                            let mut parts = query.split('=');
-                            if parts.next() == Some("range") {
-                                let mut range = parts.next().unwrap_or_default().split('-');
-                                let begin = range.next().unwrap_or_default().parse::<usize>();
-                                let end = range.next().unwrap_or_default().parse::<usize>();
-                                match (begin, end) {
-                                    (Ok(begin), Ok(end)) => (Some(begin), Some(end + 1)),
-                                    (Ok(begin), Err(_)) => (Some(begin), None),
-                                    _ => (None, None),
-                                }
-                            } else {
-                                (None, None)
-                            }
-                        }
+                    let query = match results_query(req.uri().query()) {
+                        Ok(x) => x,
+                        Err(e) => return Ok(ctx.response.bad_request(&e)),
+                    };
+                    let (begin, end) = match rid {
+                        Some(id) => match id.parse::<usize>() {
+                            Ok(id) => (Some(id), Some(id + 1)),
+                            Err(_) => (None, None),
+                        },
+                        None => (query.begin, query.end),
                     };
 
+                    let annotations = match ctx.scheduler.get_annotations(&id).await {
+                        Ok(annotations) => annotations,
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/results", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    let (profile, validators) = match ctx.scheduler.get_scan(&id).await {
+                        Ok((scan, status)) => (
+                            crate::severity::profile(
+                                &ctx.severity,
+                                &ctx.tenancy,
+                                tenant.as_deref(),
+                                &scan,
+                            ),
+                            results_validators(
+                                &status,
+                                ctx.scheduler.count_results(&id).await?,
+                                &annotations,
+                            ),
+                        ),
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/results", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    if validators.is_fresh(req.headers()) {
+                        return Ok(ctx.response.not_modified(&validators));
+                    }
                     match ctx.scheduler.get_results(&id, begin, end).await {
-                        Ok(results) => Ok(ctx.response.ok_byte_stream(results).await),
+                        Ok(results) => {
+                            let results = super::results::filter_qod(query.min_qod, results);
+                            let results =
+                                super::results::filter_suppressed(query.suppressed, results);
+                            let results = super::annotations::annotate(&annotations, results);
+                            let results = super::enrichment::enrich(&ctx, results).await;
+                            let results = super::severity::classify(&ctx, &profile, results).await;
+                            Ok(validators.apply(ctx.response.ok_byte_stream(results).await))
+                        }
                         Err(crate::storage::Error::NotFound) => {
                             Ok(ctx.response.not_found("scans/results", &id))
                         }
                         Err(e) => Ok(ctx.response.internal_server_error(&e)),
                     }
                 }
-
-                (&Method::GET, Vts(oid)) => {
-                    let query = req.uri().query();
-
-                    let meta = match query {
-                        Some("information=true") => true,
-                        Some("information=1") => true,
-                        Some(_) | None => false,
+                (&Method::POST, ScanAnnotations(id, _))
+                | (&Method::DELETE, ScanAnnotations(id, _))
+                    if is_imported(&ctx.scheduler, &id).await =>
+                {
+                    Ok(ctx.response.bad_request(&"Imported scans are read-only"))
+                }
+                (&Method::POST, ScanAnnotations(id, None)) => {
+                    let annotation =
+                        match crate::request::json_request::<Annotation, _>(&ctx.response, req)
+                            .await
+                        {
+                            Ok(annotation) => annotation,
+                            Err(resp) => return Ok(resp),
+                        };
+                    if annotation.is_empty() {
+                        return Ok(ctx
+                            .response
+                            .bad_request(&"An annotation requires a note or labels"));
+                    }
+                    if let Some(rid) = annotation.result_id {
+                        match ctx.scheduler.count_results(&id).await {
+                            Ok(count) if rid < count => {}
+                            Ok(_) => {
+                                return Ok(ctx
+                                    .response
+                                    .not_found("scans/results", &format!("{id}/{rid}")))
+                            }
+                            Err(crate::storage::Error::NotFound) => {
+                                return Ok(ctx.response.not_found("scans", &id))
+                            }
+                            Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                        }
+                    }
+                    match ctx.scheduler.add_annotation(&id, annotation).await {
+                        Ok(annotation) => Ok(ctx.response.created(&annotation)),
+                        Err(crate::storage::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans", &id))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanAnnotations(id, aid)) => {
+                    let annotations = match ctx.scheduler.get_annotations(&id).await {
+                        Ok(annotations) => annotations,
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
                     };
-                    match oid {
-                        Some(oid) => match ctx.scheduler.vt_by_oid(&oid).await? {
-                            Some(nvt) => Ok(ctx.response.ok(&nvt)),
-                            None => Ok(ctx.response.not_found("nvt", &oid)),
+                    match aid {
+                        None => Ok(ctx.response.ok(&annotations)),
+                        Some(aid) => match annotations
+                            .iter()
+                            .find(|x| aid.parse::<usize>() == Ok(x.id))
+                        {
+                            Some(annotation) => Ok(ctx.response.ok(annotation)),
+                            None => Ok(ctx.response.not_found("scans/annotations", &aid)),
                         },
-                        None if meta => Ok(ctx
-                            .response
-                            .ok_json_stream(ctx.scheduler.vts().await?)
-                            .await),
-                        None => Ok(ctx
-                            .response
-                            .ok_json_stream(ctx.scheduler.oids().await?)
-                            .await),
                     }
                 }
+                (&Method::DELETE, ScanAnnotations(id, Some(aid))) => {
+                    let Ok(annotation_id) = aid.parse::<usize>() else {
+                        return Ok(ctx.response.not_found("scans/annotations", &aid));
+                    };
+                    match ctx.scheduler.remove_annotation(&id, annotation_id).await {
+                        Ok(_) => Ok(ctx.response.no_content()),
+                        Err(crate::storage::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans/annotations", &aid))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanReport(id)) => {
+                    let (kind, format, min_qod) = match report_query(req.uri().query()) {
+                        Ok(x) => x,
+                        Err(e) => return Ok(ctx.response.bad_request(&e)),
+                    };
+                    let profile = match ctx.scheduler.get_scan(&id).await {
+                        Ok((scan, _)) => crate::severity::profile(
+                            &ctx.severity,
+                            &ctx.tenancy,
+                            tenant.as_deref(),
+                            &scan,
+                        ),
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/report", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    let results = match ctx.scheduler.get_results(&id, None, None).await {
+                        Ok(results) => results,
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/report", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    let results: Vec<models::Result> = results
+                        .filter_map(|x| serde_json::from_slice(&x).ok())
+                        .collect();
+                    let mut vts = Vec::new();
+                    let oids: HashSet<&String> =
+                        results.iter().filter_map(|x| x.oid.as_ref()).collect();
+                    for oid in oids {
+                        if let Some(vt) = ctx.scheduler.vt_by_oid(oid).await? {
+                            vts.push(vt);
+                        }
+                    }
+                    let report = Report::new(&id, results)
+                        .with_vts(vts)
+                        .with_branding(ctx.report.branding.clone())
+                        .with_severity_profile(profile)
+                        .with_min_qod(min_qod);
+                    let pdf = ctx.report.pdf.clone();
+                    // printing to PDF waits for an external process
+                    match tokio::task::spawn_blocking(move || report.export(kind, format, &pdf))
+                        .await
+                    {
+                        Ok(Ok(report)) => {
+                            Ok(ctx.response.ok_document(format.content_type(), report))
+                        }
+                        Ok(Err(e)) => Ok(ctx.response.internal_server_error(&e)),
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanHosts(id)) => {
+                    match ctx.scheduler.get_results(&id, None, None).await {
+                        Ok(results) => {
+                            let results: Vec<models::Result> = results
+                                .filter_map(|x| serde_json::from_slice(&x).ok())
+                                .collect();
+                            Ok(ctx.response.ok(&models::Asset::from_results(&results)))
+                        }
+                        Err(crate::storage::Error::NotFound) => {
+                            Ok(ctx.response.not_found("scans/hosts", &id))
+                        }
+                        Err(e) => Ok(ctx.response.internal_server_error(&e)),
+                    }
+                }
+                (&Method::GET, ScanResultsStream(id)) => {
+                    match ctx.scheduler.get_status(&id).await {
+                        Ok(_) => {}
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/results", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    // a reconnecting client continues after the last received result
+                    let next = req
+                        .headers()
+                        .get("last-event-id")
+                        .and_then(|x| x.to_str().ok())
+                        .and_then(|x| x.parse::<usize>().ok())
+                        .map_or(0, |x| x + 1);
+                    let (tx, rx) = tokio::sync::mpsc::channel(16);
+                    tokio::spawn(super::results::stream(ctx.clone(), id, next, tx));
+                    Ok(ctx.response.ok_event_stream(rx))
+                }
+                (&Method::GET, ScanResultsDownload(id)) => {
+                    let query = match results_query(req.uri().query()) {
+                        Ok(x) => x,
+                        Err(e) => return Ok(ctx.response.bad_request(&e)),
+                    };
+                    let (status, count) = match ctx.scheduler.get_status(&id).await {
+                        Ok(status) => (status, ctx.scheduler.count_results(&id).await?),
+                        Err(crate::storage::Error::NotFound) => {
+                            return Ok(ctx.response.not_found("scans/results", &id))
+                        }
+                        Err(e) => return Ok(ctx.response.internal_server_error(&e)),
+                    };
+                    let annotations = ctx.scheduler.get_annotations(&id).await?;
+                    let validators = results_validators(&status, count, &annotations);
+                    if validators.is_fresh(req.headers()) {
+                        return Ok(ctx.response.not_modified(&validators));
+                    }
+                    // results fetched during the download are left for the next download
+                    let end = query.end.map_or(count, |x| x.min(count));
+                    let range = (query.begin.unwrap_or_default(), end);
+                    let (tx, rx) = tokio::sync::mpsc::channel(4);
+                    tokio::spawn(super::results::download(
+                        ctx.clone(),
+                        id,
+                        range,
+                        query.min_qod,
+                        query.suppressed,
+                        super::results::DOWNLOAD_CHUNK,
+                        tx,
+                    ));
+                    let mut response = ctx.response.ok_stream("application/gzip", rx);
+                    response.headers_mut().insert("x-results-end", end.into());
+                    Ok(validators.apply(response))
+                }
+
+                (&Method::GET, Vts(Some(oid))) => {
+                    let response = match ctx.scheduler.vt_by_oid(&oid).await? {
+                        Some(nvt) if subset.as_ref().is_none_or(|x| x.allows(&nvt)) => {
+                            ctx.response.ok(&nvt)
+                        }
+                        _ => ctx.response.not_found("nvt", &oid),
+                    };
+                    Ok(with_validators(vts_validators.as_ref(), response))
+                }
+                (&Method::GET, VtPreferences(oid)) => {
+                    let response = match ctx.scheduler.vt_by_oid(&oid).await? {
+                        Some(nvt) if subset.as_ref().is_none_or(|x| x.allows(&nvt)) => {
+                            ctx.response.ok(&nvt.preferences)
+                        }
+                        _ => ctx.response.not_found("nvt", &oid),
+                    };
+                    Ok(with_validators(vts_validators.as_ref(), response))
+                }
+                (&Method::GET, Vts(None)) => {
+                    let (meta, mut filter) = match vts_query(req.uri().query()) {
+                        Ok(x) => x,
+                        Err(e) => return Ok(ctx.response.bad_request(&e)),
+                    };
+                    filter.subset = subset;
+                    let response = match (meta, filter.is_empty()) {
+                        (true, _) => {
+                            ctx.response
+                                .ok_json_stream(ctx.scheduler.vts_filtered(filter).await?)
+                                .await
+                        }
+                        (false, true) => {
+                            ctx.response
+                                .ok_json_stream(ctx.scheduler.oids().await?)
+                                .await
+                        }
+                        (false, false) => {
+                            ctx.response
+                                .ok_json_stream(
+                                    ctx.scheduler.vts_filtered(filter).await?.map(|x| x.oid),
+                                )
+                                .await
+                        }
+                    };
+                    Ok(with_validators(vts_validators.as_ref(), response))
+                }
+                // the uploaded VTs replace the VTs of every client
+                (&Method::POST, Vts(None)) if role.is_none_or(|x| x == Role::Admin) => {
+                    match crate::request::json_request::<Vec<Nvt>, _>(&ctx.response, req).await {
+                        Ok(vts) => {
+                            ctx.scheduler.replace_vts(vts).await?;
+                            Ok(ctx.response.no_content())
+                        }
+                        Err(resp) => Ok(resp),
+                    }
+                }
+                (&Method::POST, Vts(None)) => Ok(ctx.response.forbidden()),
+                (&Method::GET, FeedFamilies) => {
+                    let families = match subset {
+                        Some(subset) => {
+                            let filter = VtFilter {
+                                deprecated: true,
+                                subset: Some(subset),
+                                ..Default::default()
+                            };
+                            FamilySummary::aggregate(ctx.scheduler.vts_filtered(filter).await?)
+                        }
+                        None => ctx.scheduler.families().await?,
+                    };
+                    let response = ctx.response.ok(&families);
+                    Ok(with_validators(vts_validators.as_ref(), response))
+                }
+                // maintenance affects the scans of every client
+                (&Method::POST, StorageCompact) if role.is_none_or(|x| x == Role::Admin) => {
+                    Ok(ctx.response.ok(&ctx.scheduler.compact().await?))
+                }
+                (&Method::POST, StorageCompact) => Ok(ctx.response.forbidden()),
+                (&Method::GET, FeedReport) => match ctx.scheduler.feed_report().await? {
+                    Some(report) => Ok(ctx.response.ok(&report)),
+                    None => Ok(ctx.response.not_found("feed", "report")),
+                },
                 _ => Ok(ctx.response.not_found("path", req.uri().path())),
             }
+        };
+        Box::pin(async move {
+            let result = handle.instrument(span.clone()).await;
+            if let Ok(response) = &result {
+                span.record("http.response.status_code", response.status().as_u16());
+            }
+            result
         })
     }
 }
@@ -472,19 +1346,24 @@ pub mod client {
 
     use http_body_util::{BodyExt, Empty, Full};
     use hyper::{
-        body::Bytes, header::HeaderValue, service::HttpService, HeaderMap, Method, Request,
+        body::Bytes,
+        header::{HeaderName, HeaderValue},
+        service::HttpService,
+        HeaderMap, Method, Request,
     };
+    use scannerlib::alive::AliveStatus;
     use scannerlib::models::scanner::{self, Scanner};
     use scannerlib::models::{self, Action, Scan, ScanAction, Status};
     use scannerlib::nasl::FSPluginLoader;
     use scannerlib::storage::infisto::{
         CachedIndexFileStorer, ChaCha20IndexFileStorer, IndexedFileStorer,
     };
+    use scannerlib::storage::item::NvtPreference;
     use serde::Deserialize;
 
     use crate::{
         controller::{ClientIdentifier, Context},
-        storage::{file::Storage, NVTStorer, UserNASLStorageForKBandVT},
+        storage::{file::Storage, FamilySummary, NVTStorer, UserNASLStorageForKBandVT},
     };
 
     use super::KnownPaths;
@@ -493,25 +1372,20 @@ pub mod client {
     type TypeResult<T> = Result<T, scanner::Error>;
 
     pub struct Client<S, DB> {
-        ctx: Arc<Context<S, DB>>,
+        pub(super) ctx: Arc<Context<S, DB>>,
         cid: Arc<ClientIdentifier>,
     }
 
-    pub async fn in_memory_example_feed() -> Client<
-        scannerlib::scanner::Scanner<(
-            Arc<
-                UserNASLStorageForKBandVT<
-                    crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
-                >,
-            >,
-            FSPluginLoader,
-        )>,
-        Arc<
-            UserNASLStorageForKBandVT<
-                crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
-            >,
-        >,
-    > {
+    type InMemoryStorage = Arc<
+        UserNASLStorageForKBandVT<crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>>,
+    >;
+    type InMemoryClient =
+        Client<scannerlib::scanner::Scanner<(InMemoryStorage, FSPluginLoader)>, InMemoryStorage>;
+
+    async fn in_memory_example_scanner() -> (
+        scannerlib::scanner::Scanner<(InMemoryStorage, FSPluginLoader)>,
+        InMemoryStorage,
+    ) {
         use crate::file::tests::{example_feeds, nasl_root};
         let storage = crate::storage::inmemory::Storage::default();
 
@@ -523,8 +1397,84 @@ pub mod client {
             .unwrap();
         let nasl_feed_path = nasl_root().await;
         let scanner = scannerlib::scanner::Scanner::with_storage(storage.clone(), &nasl_feed_path);
+        (scanner, storage)
+    }
+
+    pub async fn in_memory_example_feed() -> InMemoryClient {
+        let (scanner, storage) = in_memory_example_scanner().await;
         Client::authenticated(scanner, storage)
     }
+
+    /// Creates a client without certificates accepting bearer tokens of oidc
+    pub async fn in_memory_example_feed_with_oidc(oidc: crate::oidc::Oidc) -> InMemoryClient {
+        let (scanner, storage) = in_memory_example_scanner().await;
+        let ctx = Arc::new(
+            crate::controller::ContextBuilder::new()
+                .oidc(oidc)
+                .scanner(scanner)
+                .storage(storage)
+                .enable_get_scans(true)
+                .build(),
+        );
+        let cid = Arc::new(ClientIdentifier::Disabled);
+        Client { ctx, cid }
+    }
+    /// Creates a client belonging to the default tenant of the given tenancy
+    pub async fn in_memory_example_feed_with_tenancy(
+        tenancy: crate::config::Tenancy,
+    ) -> InMemoryClient {
+        let (scanner, storage) = in_memory_example_scanner().await;
+        let ctx = Arc::new(
+            crate::controller::ContextBuilder::new()
+                .tenancy(tenancy)
+                .scanner(scanner)
+                .storage(storage)
+                .enable_get_scans(true)
+                .build(),
+        );
+        let cid = Arc::new(ClientIdentifier::Disabled);
+        Client { ctx, cid }
+    }
+    /// Creates a client signing and verifying scan bundles with the given key
+    pub async fn in_memory_example_feed_with_bundle(key: &str) -> InMemoryClient {
+        let (scanner, storage) = in_memory_example_scanner().await;
+        let ns = crate::config::Scheduler {
+            check_interval: std::time::Duration::from_nanos(10),
+            ..Default::default()
+        };
+        let ctx = Arc::new(
+            crate::controller::ContextBuilder::new()
+                .api_key(Some("mtls_is_preferred".to_string()))
+                .scheduler_config(ns)
+                .bundle(crate::config::Bundle {
+                    key: Some(key.to_string()),
+                })
+                .scanner(scanner)
+                .storage(storage)
+                .enable_get_scans(true)
+                .build(),
+        );
+        let cid = Arc::new(ClientIdentifier::Known("42".into()));
+        Client { ctx, cid }
+    }
+    /// Client of a controller distributing scans to the workers of the cluster scanner
+    pub fn cluster(
+        scanner: crate::cluster::Scanner,
+    ) -> Client<
+        crate::cluster::Scanner,
+        crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
+    > {
+        let ctx = Arc::new(
+            crate::controller::ContextBuilder::new()
+                .api_key(Some("mtls_is_preferred".to_string()))
+                .cluster(scanner.registry())
+                .scanner(scanner)
+                .build(),
+        );
+        let cid = Arc::new(ClientIdentifier::Disabled);
+        Client { ctx, cid }
+    }
+
     pub async fn encrypted_file_based_example_feed(
         prefix: &str,
     ) -> Client<
@@ -629,13 +1579,27 @@ pub mod client {
             url: KnownPaths,
             body: B,
         ) -> Result<crate::response::Result, scanner::Error>
+        where
+            B: Sync + Send + http_body::Body + 'static,
+            <B as http_body::Body>::Data: Send,
+            <B as http_body::Body>::Error: std::error::Error,
+        {
+            self.request_uri(method, url.to_string(), body).await
+        }
+
+        async fn request_uri<B>(
+            &self,
+            method: Method,
+            uri: String,
+            body: B,
+        ) -> Result<crate::response::Result, scanner::Error>
         where
             B: Sync + Send + http_body::Body + 'static,
             <B as http_body::Body>::Data: Send,
             <B as http_body::Body>::Error: std::error::Error,
         {
             let req = Request::builder()
-                .uri(url.to_string())
+                .uri(uri)
                 .method(method)
                 .body(body)
                 .map_err(|x| {
@@ -644,6 +1608,34 @@ pub mod client {
             self.entrypoint(req).await
         }
 
+        /// Sends a GET request with the given headers, e.g. to make a conditional request.
+        pub(super) async fn get_with_headers(
+            &self,
+            url: KnownPaths,
+            headers: &[(HeaderName, &str)],
+        ) -> HttpResult {
+            self.request_with_headers(Method::GET, url, headers, Bytes::new())
+                .await
+        }
+
+        /// Sends a request with the given headers, e.g. to authenticate by a bearer token.
+        pub(super) async fn request_with_headers(
+            &self,
+            method: Method,
+            url: KnownPaths,
+            headers: &[(HeaderName, &str)],
+            body: Bytes,
+        ) -> HttpResult {
+            let mut req = Request::builder().uri(url.to_string()).method(method);
+            for (name, value) in headers {
+                req = req.header(name, *value);
+            }
+            let req = req.body(Full::new(body)).map_err(|x| {
+                scanner::Error::Unexpected(format!("Unable to create request: {x}"))
+            })?;
+            self.entrypoint(req).await
+        }
+
         pub async fn scan_status(&self, id: &str) -> TypeResult<Status> {
             let result = self
                 .request_empty(Method::GET, KnownPaths::ScanStatus(id.to_string()))
@@ -671,6 +1663,176 @@ pub mod client {
                 .await;
             self.parsed(result).await
         }
+        /// Returns the content type and the rendered report of a scan
+        pub async fn scan_report(&self, id: &str, query: &str) -> TypeResult<(String, String)> {
+            let uri = format!("{}?{query}", KnownPaths::ScanReport(id.to_string()));
+            let resp = self
+                .request_uri(Method::GET, uri, Empty::<Bytes>::new())
+                .await?;
+            if resp.status() != 200 {
+                return Err(scanner::Error::Unexpected(format!(
+                    "Expected 200 for a report but got {}",
+                    resp.status()
+                )));
+            }
+            let content_type = resp.headers()["Content-Type"]
+                .to_str()
+                .unwrap_or_default()
+                .to_string();
+            // infallible
+            let resp = resp.into_body().collect().await.unwrap().to_bytes();
+            String::from_utf8(resp.to_vec())
+                .map(|x| (content_type, x))
+                .map_err(|x| scanner::Error::Unexpected(format!("Invalid report: {x}")))
+        }
+
+        /// Returns the end of the downloaded range and the decompressed results
+        pub async fn scan_results_download(
+            &self,
+            id: &str,
+            query: &str,
+        ) -> TypeResult<(usize, String)> {
+            use std::io::Read as _;
+
+            let uri = format!(
+                "{}?{query}",
+                KnownPaths::ScanResultsDownload(id.to_string())
+            );
+            let resp = self
+                .request_uri(Method::GET, uri, Empty::<Bytes>::new())
+                .await?;
+            if resp.status() != 200 {
+                return Err(scanner::Error::Unexpected(format!(
+                    "Expected 200 for a download but got {}",
+                    resp.status()
+                )));
+            }
+            let end = resp.headers()["x-results-end"]
+                .to_str()
+                .ok()
+                .and_then(|x| x.parse().ok())
+                .unwrap_or_default();
+            // infallible
+            let resp = resp.into_body().collect().await.unwrap().to_bytes();
+            let mut results = String::new();
+            flate2::read::MultiGzDecoder::new(resp.as_ref())
+                .read_to_string(&mut results)
+                .map_err(|x| scanner::Error::Unexpected(format!("Invalid download: {x}")))?;
+            Ok((end, results))
+        }
+
+        pub async fn scan_bundle(&self, id: &str) -> TypeResult<Vec<u8>> {
+            let resp = self
+                .request_empty(Method::GET, KnownPaths::ScanBundle(id.to_string()))
+                .await?;
+            if resp.status() != 200 {
+                return Err(scanner::Error::Unexpected(format!(
+                    "Expected 200 for a bundle but got {}",
+                    resp.status()
+                )));
+            }
+            // infallible
+            let resp = resp.into_body().collect().await.unwrap().to_bytes();
+            Ok(resp.to_vec())
+        }
+
+        pub async fn scan_import(&self, bundle: Vec<u8>) -> TypeResult<String> {
+            let result = self
+                .request_body(
+                    Method::POST,
+                    KnownPaths::ScanImport,
+                    Full::<Bytes>::from(bundle),
+                )
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_clone(&self, id: &str, start: bool) -> TypeResult<String> {
+            let mut uri = KnownPaths::ScanClone(id.to_string()).to_string();
+            if start {
+                uri.push_str("?start=true");
+            }
+            let result = self
+                .request_uri(Method::POST, uri, Empty::<Bytes>::new())
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_rescan(
+            &self,
+            id: &str,
+            rescan: &models::Rescan,
+            start: bool,
+        ) -> TypeResult<String> {
+            let mut uri = KnownPaths::ScanRescan(id.to_string()).to_string();
+            if start {
+                uri.push_str("?start=true");
+            }
+            let body = serde_json::to_vec(rescan).map_err(|x| {
+                scanner::Error::Unexpected(format!("Unable to transform {rescan:?}: {x}"))
+            })?;
+            let result = self
+                .request_uri(Method::POST, uri, Full::<Bytes>::from(body))
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_runs(&self, id: &str) -> TypeResult<Vec<String>> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::ScanRuns(id.to_string()))
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_hosts(&self, id: &str) -> TypeResult<Vec<models::Asset>> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::ScanHosts(id.to_string()))
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_trend(&self, id: &str) -> TypeResult<crate::storage::trend::Trend> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::ScanTrend(id.to_string()))
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn annotation_create(
+            &self,
+            id: &str,
+            annotation: &models::Annotation,
+        ) -> TypeResult<models::Annotation> {
+            let result = self
+                .request_json(
+                    Method::POST,
+                    KnownPaths::ScanAnnotations(id.to_string(), None),
+                    annotation,
+                )
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn annotations(&self, id: &str) -> TypeResult<Vec<models::Annotation>> {
+            let result = self
+                .request_empty(
+                    Method::GET,
+                    KnownPaths::ScanAnnotations(id.to_string(), None),
+                )
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn annotation_delete(&self, id: &str, annotation_id: usize) -> TypeResult<()> {
+            let result = self
+                .request_empty(
+                    Method::DELETE,
+                    KnownPaths::ScanAnnotations(id.to_string(), Some(annotation_id.to_string())),
+                )
+                .await;
+            self.no_content(result).await
+        }
+
         pub async fn scan_delete(&self, id: &str) -> TypeResult<()> {
             let result = self
                 .request_empty(Method::DELETE, KnownPaths::Scans(Some(id.to_string())))
@@ -734,11 +1896,50 @@ pub mod client {
             self.parsed(result).await
         }
 
+        pub async fn alive(&self, target: &models::Target) -> TypeResult<Vec<AliveStatus>> {
+            let result = self
+                .request_json(Method::POST, KnownPaths::Alive, target)
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn scan_plan(&self, id: &str) -> TypeResult<models::ScanPlan> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::ScanPlan(id.to_string()))
+                .await;
+            self.parsed(result).await
+        }
+
         pub async fn vts(&self) -> TypeResult<Vec<String>> {
             let result = self.request_empty(Method::GET, KnownPaths::Vts(None)).await;
             self.parsed(result).await
         }
 
+        pub async fn vts_query<T>(&self, query: &str) -> TypeResult<T>
+        where
+            T: for<'de> Deserialize<'de>,
+        {
+            let uri = format!("{}?{query}", KnownPaths::Vts(None));
+            let result = self
+                .request_uri(Method::GET, uri, Empty::<Bytes>::new())
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn families(&self) -> TypeResult<Vec<FamilySummary>> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::FeedFamilies)
+                .await;
+            self.parsed(result).await
+        }
+
+        pub async fn vt_preferences(&self, oid: &str) -> TypeResult<Vec<NvtPreference>> {
+            let result = self
+                .request_empty(Method::GET, KnownPaths::VtPreferences(oid.to_string()))
+                .await;
+            self.parsed(result).await
+        }
+
         /// Starts a scan and wait until is finished and returns it status and results
         ///
         pub async fn scan_finish(&self, scan: &Scan) -> TypeResult<(String, Status)> {
@@ -756,6 +1957,8 @@ pub mod client {
                     *abort = true;
                     return Ok((id, response));
                 }
+                // fetches the status of scans finishing after the last sync
+                self.ctx.scheduler.sync_scans().await?;
 
                 if let Ok(has_run) = std::time::SystemTime::now().duration_since(start) {
                     let mut abort = Arc::as_ref(&self.ctx).abort.write().unwrap();
@@ -792,7 +1995,238 @@ pub mod client {
 
 #[cfg(test)]
 pub(super) mod tests {
-    use scannerlib::models::{Scan, VT};
+    use scannerlib::models::{self, Scan, VT};
+    use scannerlib::report::{ReportFormat, ReportKind};
+    use scannerlib::storage::item::{Nvt, ACT};
+
+    use crate::storage::VtFilter;
+
+    #[test]
+    fn vts_query() {
+        assert_eq!(super::vts_query(None), Ok((false, VtFilter::default())));
+        assert_eq!(
+            super::vts_query(Some(
+                "information=1&family=Product%20detection&category=gather_info&name=http+server&range=10-19"
+            )),
+            Ok((
+                true,
+                VtFilter {
+                    family: Some("Product detection".to_string()),
+                    category: Some(ACT::GatherInfo),
+                    name: Some("http server".to_string()),
+                    begin: 10,
+                    end: Some(20),
+                    deprecated: false,
+                    subset: None,
+                }
+            ))
+        );
+        assert_eq!(
+            super::vts_query(Some("category=3")).map(|(_, x)| x.category),
+            Ok(Some(ACT::GatherInfo))
+        );
+        assert!(super::vts_query(Some("deprecated=true")).is_ok_and(|(_, x)| x.is_empty()));
+        assert!(super::vts_query(Some("category=unknown")).is_err());
+        assert!(super::vts_query(Some("range=a-b")).is_err());
+    }
+
+    #[test]
+    fn report_query() {
+        assert_eq!(
+            super::report_query(None),
+            Ok((ReportKind::Technical, ReportFormat::Html, None))
+        );
+        assert_eq!(
+            super::report_query(Some("format=markdown&kind=executive&unknown=1")),
+            Ok((ReportKind::Executive, ReportFormat::Markdown, None))
+        );
+        assert_eq!(
+            super::report_query(Some("format=pdf&min_qod=70")),
+            Ok((ReportKind::Technical, ReportFormat::Pdf, Some(70)))
+        );
+        assert!(super::report_query(Some("format=docx")).is_err());
+        assert!(super::report_query(Some("kind=marketing")).is_err());
+        assert!(super::report_query(Some("min_qod=101")).is_err());
+    }
+
+    #[test]
+    fn results_query() {
+        use super::ResultsQuery;
+        assert_eq!(super::results_query(None), Ok(ResultsQuery::default()));
+        assert_eq!(
+            super::results_query(Some("range=2-4")),
+            Ok(ResultsQuery {
+                begin: Some(2),
+                end: Some(5),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            super::results_query(Some("range=3&min_qod=70&suppressed=true")),
+            Ok(ResultsQuery {
+                begin: Some(3),
+                end: None,
+                min_qod: Some(70),
+                suppressed: true,
+            })
+        );
+        assert_eq!(
+            super::results_query(Some("range=a-b&min_qod=0")),
+            Ok(ResultsQuery {
+                min_qod: Some(0),
+                ..Default::default()
+            })
+        );
+        assert!(super::results_query(Some("min_qod=high")).is_err());
+    }
+
+    #[tokio::test]
+    async fn filter_vts() {
+        let client = super::client::in_memory_example_feed().await;
+        let all: Vec<Nvt> = client.vts_query("information=1").await.unwrap();
+        let expected = |f: &dyn Fn(&Nvt) -> bool| {
+            let mut oids = all
+                .iter()
+                .filter(|x| f(x))
+                .map(|x| x.oid.clone())
+                .collect::<Vec<_>>();
+            oids.sort();
+            oids
+        };
+        let sorted = |mut x: Vec<String>| {
+            x.sort();
+            x
+        };
+
+        let attacks: Vec<String> = client.vts_query("category=attack").await.unwrap();
+        assert!(!attacks.is_empty());
+        assert_eq!(sorted(attacks), expected(&|x| x.category == ACT::Attack));
+
+        let detection: Vec<Nvt> = client
+            .vts_query("information=true&family=Product+detection&name=SERVER")
+            .await
+            .unwrap();
+        assert_eq!(
+            sorted(detection.into_iter().map(|x| x.oid).collect()),
+            expected(&|x| x.family == "Product detection")
+        );
+
+        let page: Vec<String> = client.vts_query("range=1-2").await.unwrap();
+        assert_eq!(page, expected(&|_| true)[1..3]);
+    }
+
+    #[tokio::test]
+    async fn families() {
+        let client = super::client::in_memory_example_feed().await;
+        let all: Vec<Nvt> = client.vts_query("information=1").await.unwrap();
+        let families = client.families().await.unwrap();
+        assert_eq!(families.iter().map(|x| x.count).sum::<usize>(), all.len());
+        let detection = families
+            .iter()
+            .find(|x| x.family == "Product detection")
+            .unwrap();
+        let vts = all
+            .iter()
+            .filter(|x| x.family == "Product detection")
+            .collect::<Vec<_>>();
+        assert_eq!(detection.count, vts.len());
+        assert_eq!(
+            detection
+                .categories
+                .get(&ACT::Attack)
+                .cloned()
+                .unwrap_or_default(),
+            vts.iter().filter(|x| x.category == ACT::Attack).count()
+        );
+        assert!(detection.last_modification.is_some());
+    }
+
+    #[tokio::test]
+    async fn tenant_feed_subset() {
+        use crate::storage::ScanStorer as _;
+
+        let all: Vec<Nvt> = super::client::in_memory_example_feed()
+            .await
+            .vts_query("information=1")
+            .await
+            .unwrap();
+        let other = all
+            .iter()
+            .find(|x| x.family != "Product detection")
+            .unwrap();
+        let tenancy = crate::config::Tenancy {
+            enabled: true,
+            default_tenant: Some("basic".to_string()),
+            tenants: [(
+                "basic".to_string(),
+                crate::config::Tenant {
+                    families: vec!["Product detection".to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        };
+        let client = super::client::in_memory_example_feed_with_tenancy(tenancy).await;
+        let visible: Vec<Nvt> = client.vts_query("information=1").await.unwrap();
+        assert!(!visible.is_empty());
+        assert!(visible.iter().all(|x| x.family == "Product detection"));
+        assert_eq!(client.vts().await.unwrap().len(), visible.len());
+        let families = client.families().await.unwrap();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].count, visible.len());
+        assert!(client.vt_preferences(&other.oid).await.is_err());
+
+        let vt = |oid: &str| VT {
+            oid: oid.to_string(),
+            parameters: vec![],
+        };
+        let mut scan = Scan::default();
+        scan.vts = vec![vt(&visible[0].oid), vt(&other.oid)];
+        assert!(client.scan_create(&scan).await.is_err());
+        scan.vts.pop();
+        let id = client.scan_create(&scan).await.unwrap();
+
+        // scans stored before the subset changed are refused on start
+        scan.scan_id = id.clone();
+        scan.vts.push(vt(&other.oid));
+        client.ctx.scheduler.insert_scan(scan).await.unwrap();
+        assert!(client
+            .scan_action(&id, models::Action::Start)
+            .await
+            .is_err());
+        assert_eq!(
+            client.scan_status(&id).await.unwrap().status,
+            models::Phase::Stored
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_scan_preferences() {
+        use scannerlib::models::ScanPreference;
+
+        let client = super::client::in_memory_example_feed().await;
+        let mut scan = Scan::default();
+        scan.scan_preferences = vec![ScanPreference {
+            id: "plugins_timeout".to_string(),
+            value: "forever".to_string(),
+        }];
+        assert!(client.scan_create(&scan).await.is_err());
+        assert!(client.scans().await.unwrap().is_empty());
+        scan.scan_preferences[0].value = "320".to_string();
+        client.scan_create(&scan).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn vt_preferences() {
+        let client = super::client::in_memory_example_feed().await;
+        let all: Vec<Nvt> = client.vts_query("information=1").await.unwrap();
+        let nvt = &all[0];
+        assert_eq!(
+            client.vt_preferences(&nvt.oid).await.unwrap(),
+            nvt.preferences
+        );
+        assert!(client.vt_preferences("0.0.0.0").await.is_err());
+    }
 
     #[tokio::test]
     #[tracing_test::traced_test]
@@ -824,4 +2258,575 @@ pub(super) mod tests {
         assert_eq!(3, results.len());
         client.scan_delete(&id).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn scan_report() {
+        let client = super::client::encrypted_file_based_example_feed("scan_report").await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let (content_type, report) = client.scan_report(&id, "").await.unwrap();
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(report.contains(&format!("Technical report of scan {id}")));
+        let (content_type, report) = client
+            .scan_report(&id, "kind=executive&format=markdown")
+            .await
+            .unwrap();
+        assert_eq!(content_type, "text/markdown; charset=utf-8");
+        assert!(report.starts_with(&format!("# Executive summary of scan {id}")));
+        assert!(client.scan_report(&id, "format=docx").await.is_err());
+        assert!(client.scan_report("unknown", "").await.is_err());
+        client.scan_delete(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rescan() {
+        let client = super::client::in_memory_example_feed().await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let subset = models::Rescan {
+            hosts: vec!["localhost".to_string()],
+            vts: vec![],
+        };
+        let rescan = client.scan_rescan(&id, &subset, false).await.unwrap();
+        let lineage = client.scan(&rescan).await.unwrap().lineage.unwrap();
+        assert_eq!(lineage.parent, id);
+        assert_eq!(lineage.rescan, Some(subset));
+        let unknown = models::Rescan {
+            hosts: vec!["192.0.2.1".to_string()],
+            vts: vec![],
+        };
+        assert!(client.scan_rescan(&id, &unknown, false).await.is_err());
+        assert!(client
+            .scan_rescan("unknown", &models::Rescan::default(), false)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn scan_hosts() {
+        let client = super::client::in_memory_example_feed().await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let results = client.scan_results(&id).await.unwrap();
+        let hosts = client.scan_hosts(&id).await.unwrap();
+        assert_eq!(hosts, models::Asset::from_results(&results));
+        assert!(client.scan_hosts("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn clone() {
+        let client = super::client::in_memory_example_feed().await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let id = client.scan_create(&scan).await.unwrap();
+        let second = client.scan_clone(&id, false).await.unwrap();
+        assert_ne!(id, second);
+        let cloned = client.scan(&second).await.unwrap();
+        assert_eq!(cloned.target, scan.target);
+        assert_eq!(cloned.vts, scan.vts);
+        let lineage = cloned.lineage.unwrap();
+        assert_eq!(
+            (lineage.parent.as_str(), lineage.root.as_str()),
+            (id.as_str(), id.as_str())
+        );
+        assert_eq!(lineage.run, 2);
+        assert_eq!(
+            client.scan_status(&second).await.unwrap().status,
+            models::Phase::Stored
+        );
+
+        let third = client.scan_clone(&second, true).await.unwrap();
+        let lineage = client.scan(&third).await.unwrap().lineage.unwrap();
+        assert_eq!(
+            (lineage.parent, lineage.root, lineage.run),
+            (second.clone(), id.clone(), 3)
+        );
+        assert_eq!(
+            client.scan_status(&third).await.unwrap().status,
+            models::Phase::Requested
+        );
+
+        let runs = vec![id.clone(), second.clone(), third.clone()];
+        assert_eq!(client.scan_runs(&id).await.unwrap(), runs);
+        assert_eq!(client.scan_runs(&third).await.unwrap(), runs);
+        // none of the runs succeeded yet
+        let trend = client.scan_trend(&second).await.unwrap();
+        assert!(trend.runs.is_empty());
+        assert_eq!(trend.mean_time_to_fix, None);
+        assert!(client.scan_trend("unknown").await.is_err());
+        assert!(client.scan_clone("unknown", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn bundle_export_import() {
+        let client = super::client::in_memory_example_feed_with_bundle("bundle-key").await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, status) = client.scan_finish(&scan).await.unwrap();
+        let bundle = client.scan_bundle(&id).await.unwrap();
+        let imported = client.scan_import(bundle.clone()).await.unwrap();
+        assert_ne!(id, imported);
+        let origin = client.scan(&imported).await.unwrap().imported.unwrap();
+        assert_eq!(origin.scan_id, id);
+        assert_eq!(
+            client.scan_status(&imported).await.unwrap().status,
+            status.status
+        );
+        assert_eq!(
+            client.scan_results(&imported).await.unwrap().len(),
+            client.scan_results(&id).await.unwrap().len()
+        );
+        // imported scans are read-only
+        let annotation = models::Annotation {
+            note: Some("handed over".to_string()),
+            ..Default::default()
+        };
+        assert!(client
+            .annotation_create(&imported, &annotation)
+            .await
+            .is_err());
+        assert!(client
+            .scan_action(&imported, models::Action::Start)
+            .await
+            .is_err());
+
+        let mut tampered = bundle;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        assert!(client.scan_import(tampered).await.is_err());
+        let other = super::client::in_memory_example_feed_with_bundle("other-key").await;
+        let bundle = client.scan_bundle(&id).await.unwrap();
+        assert!(other.scan_import(bundle).await.is_err());
+
+        // only finished scans are exported
+        let stored = client.scan_create(&scan).await.unwrap();
+        assert!(client.scan_bundle(&stored).await.is_err());
+        assert!(client.scan_bundle("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn annotations() {
+        use http_body_util::BodyExt;
+
+        use super::KnownPaths;
+
+        let client = super::client::encrypted_file_based_example_feed("annotations").await;
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let annotation = models::Annotation {
+            result_id: Some(0),
+            note: Some("false positive".to_string()),
+            author: Some("admin".to_string()),
+            ..Default::default()
+        };
+        let created = client.annotation_create(&id, &annotation).await.unwrap();
+        assert_eq!(created.id, 0);
+        assert_eq!(created.note, annotation.note);
+        assert!(client
+            .annotation_create(&id, &models::Annotation::default())
+            .await
+            .is_err());
+        let out_of_range = models::Annotation {
+            result_id: Some(1000),
+            ..annotation.clone()
+        };
+        assert!(client.annotation_create(&id, &out_of_range).await.is_err());
+        assert_eq!(client.annotations(&id).await.unwrap(), vec![created]);
+
+        let resp = client
+            .get_with_headers(KnownPaths::ScanResults(id.clone(), None), &[])
+            .await
+            .unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let results: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(results[0]["annotations"][0]["note"], "false positive");
+
+        client.annotation_delete(&id, 0).await.unwrap();
+        assert!(client.annotation_delete(&id, 0).await.is_err());
+        assert!(client.annotations(&id).await.unwrap().is_empty());
+        client.scan_delete(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn oidc() {
+        use http_body_util::BodyExt;
+        use hyper::{body::Bytes, header::AUTHORIZATION, Method, StatusCode};
+
+        use super::KnownPaths;
+        use crate::oidc::{tests as tokens, Oidc};
+
+        let signer = tokens::Signer::new();
+        let oidc = Oidc::new(tokens::config()).with_keys(signer.jwks("k1"));
+        let client = super::client::in_memory_example_feed_with_oidc(oidc).await;
+        let bearer = |subject: &str, role: &str| {
+            let token = signer.sign("k1", tokens::claims(subject, &[role]));
+            format!("Bearer {token}")
+        };
+        let request = |method: Method, url: KnownPaths, token: Option<String>, body: Bytes| {
+            let client = &client;
+            async move {
+                let headers: Vec<_> = token.iter().map(|x| (AUTHORIZATION, x.as_str())).collect();
+                client
+                    .request_with_headers(method, url, &headers, body)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let resp = request(Method::GET, KnownPaths::Scans(None), None, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let invalid = Some("Bearer a.b.c".to_string());
+        let resp = request(Method::GET, KnownPaths::Scans(None), invalid, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let scan = Bytes::from(serde_json::to_vec(&Scan::default()).unwrap());
+        let operator = Some(bearer("alice", "scan-user"));
+        let resp = request(
+            Method::POST,
+            KnownPaths::Scans(None),
+            operator,
+            scan.clone(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let id: String = serde_json::from_slice(&body).unwrap();
+        let scan_path = || KnownPaths::Scans(Some(id.clone()));
+
+        let viewer = Some(bearer("bob", "auditor"));
+        let resp = request(Method::POST, KnownPaths::Scans(None), viewer, scan).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        // the scan belongs to another client
+        let viewer = Some(bearer("bob", "auditor"));
+        let resp = request(Method::GET, scan_path(), viewer, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let admin = Some(bearer("carol", "scan-admin"));
+        let resp = request(Method::GET, scan_path(), admin, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let operator = Some(bearer("alice", "scan-user"));
+        let resp = request(Method::DELETE, scan_path(), operator, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let operator = Some(bearer("alice", "scan-user"));
+        let compact = || KnownPaths::StorageCompact;
+        let resp = request(Method::POST, compact(), operator, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let admin = Some(bearer("carol", "scan-admin"));
+        let resp = request(Method::POST, compact(), admin, Bytes::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let report: crate::storage::Maintenance = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report, Default::default());
+    }
+
+    #[tokio::test]
+    async fn upload_vts() {
+        use http_body_util::BodyExt;
+        use hyper::{body::Bytes, header::AUTHORIZATION, header::ETAG, Method, StatusCode};
+        use scannerlib::storage::item::Nvt;
+
+        use super::KnownPaths;
+        use crate::oidc::{tests as tokens, Oidc};
+
+        let signer = tokens::Signer::new();
+        let oidc = Oidc::new(tokens::config()).with_keys(signer.jwks("k1"));
+        let client = super::client::in_memory_example_feed_with_oidc(oidc).await;
+        let bearer = |subject: &str, role: &str| {
+            let token = signer.sign("k1", tokens::claims(subject, &[role]));
+            format!("Bearer {token}")
+        };
+        let request = |method: Method, token: Option<String>, body: Bytes| {
+            let client = &client;
+            async move {
+                let headers: Vec<_> = token.iter().map(|x| (AUTHORIZATION, x.as_str())).collect();
+                client
+                    .request_with_headers(method, KnownPaths::Vts(None), &headers, body)
+                    .await
+                    .unwrap()
+            }
+        };
+        let vts: Vec<_> = ["1.2.3.4", "1.2.3.5"]
+            .map(|oid| Nvt {
+                oid: oid.to_string(),
+                filename: format!("{oid}.nasl"),
+                ..Default::default()
+            })
+            .into();
+        let vts = Bytes::from(serde_json::to_vec(&vts).unwrap());
+
+        let resp = request(Method::POST, None, vts.clone()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let operator = Some(bearer("alice", "scan-user"));
+        let resp = request(Method::POST, operator, vts.clone()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let viewer = || Some(bearer("bob", "auditor"));
+        let before = request(Method::GET, viewer(), Bytes::new()).await;
+        let etag = before.headers()[ETAG].clone();
+        let admin = Some(bearer("carol", "scan-admin"));
+        let resp = request(Method::POST, admin, vts).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let after = request(Method::GET, viewer(), Bytes::new()).await;
+        assert_eq!(after.status(), StatusCode::OK);
+        assert_ne!(after.headers()[ETAG], etag);
+        let body = after.into_body().collect().await.unwrap().to_bytes();
+        let mut oids: Vec<String> = serde_json::from_slice(&body).unwrap();
+        // the notus advisories are not part of the upload
+        oids.retain(|x| !x.starts_with("1.3.6.1.4.1.25623.1.1."));
+        oids.sort();
+        assert_eq!(oids, vec!["1.2.3.4", "1.2.3.5"]);
+    }
+
+    #[tokio::test]
+    async fn conditional_requests() {
+        use hyper::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+        use hyper::StatusCode;
+
+        use super::KnownPaths;
+
+        let client = super::client::encrypted_file_based_example_feed("conditional_requests").await;
+        let header = |resp: &crate::response::Result, name| {
+            resp.headers()[name].to_str().unwrap().to_string()
+        };
+
+        let vts = client.get_with_headers(KnownPaths::Vts(None), &[]).await;
+        let vts = vts.unwrap();
+        assert_eq!(vts.status(), StatusCode::OK);
+        let etag = header(&vts, ETAG);
+        let cached = client
+            .get_with_headers(KnownPaths::Vts(None), &[(IF_NONE_MATCH, &etag)])
+            .await
+            .unwrap();
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(header(&cached, ETAG), etag);
+        let outdated = client
+            .get_with_headers(KnownPaths::Vts(None), &[(IF_NONE_MATCH, "W/\"0\"")])
+            .await
+            .unwrap();
+        assert_eq!(outdated.status(), StatusCode::OK);
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let path = |results: bool| match results {
+            true => KnownPaths::ScanResults(id.clone(), None),
+            false => KnownPaths::ScanStatus(id.clone()),
+        };
+        for results in [false, true] {
+            let resp = client.get_with_headers(path(results), &[]).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let etag = header(&resp, ETAG);
+            let last_modified = header(&resp, LAST_MODIFIED);
+            for conditional in [(IF_NONE_MATCH, &etag), (IF_MODIFIED_SINCE, &last_modified)] {
+                let resp = client
+                    .get_with_headers(path(results), &[(conditional.0, conditional.1)])
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            }
+        }
+        let since = "Thu, 01 Jan 1970 00:00:00 GMT";
+        let resp = client
+            .get_with_headers(
+                KnownPaths::ScanResults(id.clone(), None),
+                &[(IF_MODIFIED_SINCE, since)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        client.scan_delete(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stream_results() {
+        use http_body_util::BodyExt;
+        use hyper::{header::HeaderName, StatusCode};
+
+        use super::KnownPaths;
+
+        let client = super::client::encrypted_file_based_example_feed("stream_results").await;
+        let resp = client
+            .get_with_headers(KnownPaths::ScanResultsStream("unknown".to_string()), &[])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = ["3", "4"]
+            .iter()
+            .map(|x| VT {
+                oid: format!("0.0.0.0.0.0.0.0.0.{x}"),
+                parameters: vec![],
+            })
+            .collect();
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let events = |last_event_id: Option<&'static str>| {
+            let client = &client;
+            let id = id.clone();
+            async move {
+                let headers: Vec<_> = last_event_id
+                    .map(|x| (HeaderName::from_static("last-event-id"), x))
+                    .into_iter()
+                    .collect();
+                let resp = client
+                    .get_with_headers(KnownPaths::ScanResultsStream(id), &headers)
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(resp.headers()["content-type"], "text/event-stream");
+                let body = resp.into_body().collect().await.unwrap().to_bytes();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+        let all = events(None).await;
+        assert_eq!(all.matches("event: result\n").count(), 2, "{all}");
+        assert!(all.starts_with("id: 0\nevent: result\ndata: {"), "{all}");
+        let (_, status) = all.split_once("event: status\ndata: ").unwrap();
+        assert!(status.contains("\"status\":\"succeeded\""), "{status}");
+        let resumed = events(Some("0")).await;
+        assert!(resumed.starts_with("id: 1\nevent: result\n"), "{resumed}");
+        assert_eq!(resumed.matches("event: result\n").count(), 1, "{resumed}");
+        client.scan_delete(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn download_results() {
+        let client = super::client::encrypted_file_based_example_feed("download_results").await;
+        assert!(client.scan_results_download("unknown", "").await.is_err());
+
+        let mut scan: Scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = ["3", "4"]
+            .iter()
+            .map(|x| VT {
+                oid: format!("0.0.0.0.0.0.0.0.0.{x}"),
+                parameters: vec![],
+            })
+            .collect();
+        let (id, _) = client.scan_finish(&scan).await.unwrap();
+        let expected = client.scan_results(&id).await.unwrap();
+        let parse = |x: &str| {
+            x.lines()
+                .map(|x| serde_json::from_str::<models::Result>(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let (end, all) = client.scan_results_download(&id, "").await.unwrap();
+        assert_eq!(end, expected.len());
+        assert_eq!(parse(&all), expected);
+        // resumes after the last received result
+        let (end, rest) = client.scan_results_download(&id, "range=1").await.unwrap();
+        assert_eq!(end, expected.len());
+        assert_eq!(parse(&rest), expected[1..]);
+        let (end, first) = client
+            .scan_results_download(&id, "range=0-0")
+            .await
+            .unwrap();
+        assert_eq!(end, 1);
+        assert_eq!(parse(&first), expected[..1]);
+        client.scan_delete(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cluster_registration() {
+        use http_body_util::BodyExt;
+        use hyper::{body::Bytes, header::HeaderName, Method, StatusCode};
+
+        use super::KnownPaths;
+        use crate::cluster::{Registration, WorkerStatus};
+
+        let scanner = crate::cluster::Scanner::new(crate::config::Cluster {
+            registration_token: Some("t0ken-registration".to_string()),
+            ..Default::default()
+        });
+        let client = super::client::cluster(scanner);
+        let registration = Bytes::from(
+            serde_json::to_vec(&Registration {
+                url: "http://worker:3000".to_string(),
+                api_key: None,
+                max_scans: 3,
+                capabilities: Default::default(),
+            })
+            .unwrap(),
+        );
+        let token = HeaderName::from_static("x-registration-token");
+        let api_key = HeaderName::from_static("x-api-key");
+        let register = |value: &'static str| {
+            let (client, token, registration) = (&client, token.clone(), registration.clone());
+            async move {
+                client
+                    .request_with_headers(
+                        Method::POST,
+                        KnownPaths::ClusterRegister,
+                        &[(token, value)],
+                        registration,
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+        let resp = register("wrong").await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = register("t0ken-registration").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let resp = client
+            .get_with_headers(KnownPaths::ClusterWorkers, &[])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = client
+            .get_with_headers(
+                KnownPaths::ClusterWorkers,
+                &[(api_key, "mtls_is_preferred")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let workers: Vec<WorkerStatus> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].url, "http://worker:3000");
+        assert_eq!(workers[0].max_scans, 3);
+        assert!(workers[0].live && workers[0].registered);
+    }
 }
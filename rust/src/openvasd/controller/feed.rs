@@ -2,11 +2,15 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use scannerlib::models::scanner::Scanner;
 
 use crate::{
+    config::{self, FeedUpdatePolicy},
     feed::FeedIdentifier,
     storage::{FeedHash, NVTStorer as _},
 };
@@ -17,24 +21,29 @@ async fn changed_hash(signature_check: bool, feeds: &[FeedHash]) -> Result<Vec<F
     let mut result = Vec::with_capacity(feeds.len());
     for h in feeds {
         if signature_check {
-            if let Err(err) = scannerlib::feed::check_signature(&h.path) {
-                tracing::warn!(
-                    sumsfile=%h.path.display(),
-                    error=%err,
-                    "Signature is incorrect, skipping",
-                );
-                return Err(());
+            for path in std::iter::once(&h.path).chain(&h.overlays) {
+                if let Err(err) = scannerlib::feed::check_signature(path) {
+                    tracing::warn!(
+                        sumsfile=%path.display(),
+                        error=%err,
+                        "Signature is incorrect, skipping",
+                    );
+                    return Err(());
+                }
             }
         }
 
         let path = h.path.clone();
-        let hash = tokio::task::spawn_blocking(move || match FeedIdentifier::sumfile_hash(&path) {
-            Ok(h) => h,
-            Err(mut e) => {
-                e.key = path.to_str().unwrap_or_default().to_string();
+        let overlays = h.overlays.clone();
+        let hash = tokio::task::spawn_blocking(move || {
+            match FeedIdentifier::sumfile_hash(&path, &overlays) {
+                Ok(h) => h,
+                Err(mut e) => {
+                    e.key = path.to_str().unwrap_or_default().to_string();
 
-                tracing::warn!(%e, "Failed to compute sumfile hash");
-                "".to_string()
+                    tracing::warn!(%e, "Failed to compute sumfile hash");
+                    "".to_string()
+                }
             }
         })
         .await
@@ -48,6 +57,42 @@ async fn changed_hash(signature_check: bool, feeds: &[FeedHash]) -> Result<Vec<F
     Ok(result)
 }
 
+/// Decides if a detected feed change can be applied based on the running scans.
+#[derive(Debug)]
+struct UpdateCoordinator {
+    policy: FeedUpdatePolicy,
+    window: Duration,
+    last_update: Option<Instant>,
+}
+
+impl UpdateCoordinator {
+    fn new(cfg: &config::Feed) -> Self {
+        Self {
+            policy: cfg.update_policy,
+            window: cfg.update_window,
+            last_update: None,
+        }
+    }
+
+    fn may_update(&self, scans_running: bool, now: Instant) -> bool {
+        match self.policy {
+            FeedUpdatePolicy::Immediate => true,
+            FeedUpdatePolicy::IdleOnly => !scans_running,
+            FeedUpdatePolicy::Window => {
+                !scans_running
+                    || self
+                        .last_update
+                        .map(|x| now.duration_since(x) >= self.window)
+                        .unwrap_or(true)
+            }
+        }
+    }
+
+    fn updated(&mut self, now: Instant) {
+        self.last_update = Some(now);
+    }
+}
+
 pub async fn fetch<S, DB>(ctx: Arc<Context<S, DB>>)
 where
     S: Scanner + 'static + std::marker::Send + std::marker::Sync,
@@ -57,21 +102,98 @@ where
     if let Some(cfg) = &ctx.feed_config {
         let interval = cfg.check_interval;
         let signature_check = cfg.signature_check;
+        let mut coordinator = UpdateCoordinator::new(cfg);
+        // a detected change that got deferred, it is checked with the scheduler interval until
+        // it can be applied
+        let mut pending: Option<Vec<FeedHash>> = None;
         loop {
             if *ctx.abort.read().unwrap() {
                 tracing::trace!("aborting");
                 break;
             };
-            let last_hash = ctx.scheduler.feed_hash().await;
-            if let Ok(nh) = changed_hash(signature_check, &last_hash).await {
-                if !nh.is_empty() {
+            let detected = pending.is_none();
+            if detected {
+                let last_hash = ctx.scheduler.feed_hash().await;
+                if let Ok(nh) = changed_hash(signature_check, &last_hash).await {
+                    if !nh.is_empty() {
+                        pending = Some(nh);
+                    }
+                }
+            }
+            if let Some(nh) = pending.take() {
+                let scans_running = ctx.scheduler.has_running_scans().await;
+                let insufficient = ctx.scheduler.insufficient_resources();
+                if !insufficient.is_empty() {
+                    if detected {
+                        tracing::warn!(
+                            ?insufficient,
+                            "deferring feed update until resources are available"
+                        );
+                    }
+                    pending = Some(nh);
+                } else if coordinator.may_update(scans_running, Instant::now()) {
                     if let Err(err) = ctx.scheduler.synchronize_feeds(nh).await {
                         tracing::warn!(%err, "Unable to sync feed")
                     }
+                    coordinator.updated(Instant::now());
+                } else {
+                    if detected {
+                        tracing::debug!(
+                            policy = ?coordinator.policy,
+                            "deferring feed update while scans are running"
+                        );
+                    }
+                    pending = Some(nh);
                 }
             }
 
-            tokio::time::sleep(interval).await;
+            let sleep = if pending.is_some() {
+                ctx.scheduler.config().check_interval
+            } else {
+                interval
+            };
+            tokio::time::sleep(sleep).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::UpdateCoordinator;
+    use crate::config::{Feed, FeedUpdatePolicy};
+
+    fn coordinator(policy: FeedUpdatePolicy) -> UpdateCoordinator {
+        UpdateCoordinator::new(&Feed {
+            update_policy: policy,
+            update_window: Duration::from_secs(60),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn immediate() {
+        let c = coordinator(FeedUpdatePolicy::Immediate);
+        assert!(c.may_update(false, Instant::now()));
+        assert!(c.may_update(true, Instant::now()));
+    }
+
+    #[test]
+    fn idle_only() {
+        let c = coordinator(FeedUpdatePolicy::IdleOnly);
+        assert!(c.may_update(false, Instant::now()));
+        assert!(!c.may_update(true, Instant::now()));
+    }
+
+    #[test]
+    fn window() {
+        let mut c = coordinator(FeedUpdatePolicy::Window);
+        let now = Instant::now();
+        assert!(c.may_update(true, now));
+        c.updated(now);
+        assert!(!c.may_update(true, now + Duration::from_secs(59)));
+        assert!(c.may_update(false, now + Duration::from_secs(59)));
+        assert!(c.may_update(true, now + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,78 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the loop compacting the storage.
+//!
+//! A compaction locks the storage for its duration, it is therefore postponed to the next
+//! interval while the scanner is busier than configured.
+
+use std::sync::Arc;
+
+use scannerlib::models::{resources::check::Checker, scanner::Scanner};
+
+use super::context::Context;
+use crate::{config, storage::ScanStorer as _};
+
+/// Returns true when an automatic compaction may take place with the amount of running scans.
+fn is_idle(config: &config::StorageMaintenance, running_scans: usize) -> bool {
+    running_scans <= config.max_running_scans
+        && config
+            .max_cpu
+            .is_none_or(|cpu| Checker::new(None, Some(cpu)).in_boundaries())
+}
+
+/// Compacts the storage in the configured interval.
+///
+/// This loop should be run as background task, it returns immediately when no interval is set.
+pub async fn compact<S, DB>(ctx: Arc<Context<S, DB>>, config: config::StorageMaintenance)
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let Some(period) = config.interval else {
+        return;
+    };
+    tracing::debug!(?period, "Starting storage maintenance loop");
+    let mut interval = tokio::time::interval(period);
+    // the first tick completes immediately, there is nothing to reclaim at start up
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if *ctx.abort.read().unwrap() {
+            tracing::trace!("aborting");
+            break;
+        }
+        let running_scans = ctx.scheduler.running_scans().await;
+        if !is_idle(&config, running_scans) {
+            tracing::debug!(running_scans, "Postponing storage maintenance");
+            continue;
+        }
+        match ctx.scheduler.compact().await {
+            Ok(report) => tracing::info!(
+                compacted = report.compacted,
+                removed = report.removed,
+                reclaimed = report.reclaimed,
+                size = report.size,
+                "Compacted storage"
+            ),
+            Err(e) => tracing::warn!(%e, "Unable to compact storage"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle() {
+        let mut config = config::StorageMaintenance::default();
+        assert!(is_idle(&config, 0));
+        assert!(!is_idle(&config, 1));
+        config.max_running_scans = 2;
+        assert!(is_idle(&config, 2));
+        config.max_cpu = Some(f32::MIN);
+        assert!(!is_idle(&config, 0));
+    }
+}
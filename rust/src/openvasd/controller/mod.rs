@@ -2,17 +2,24 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+pub mod admission;
+pub mod annotations;
 mod context;
+pub mod enrichment;
 pub mod entry;
 pub mod feed;
+pub mod maintenance;
+pub mod osp;
+pub mod publisher;
 pub mod results;
+pub mod severity;
 
 use std::{
     net::SocketAddr,
     sync::{Arc, RwLock},
 };
 
-use crate::config;
+use crate::{config, oidc::Role, tenancy::FeedSubset};
 pub use context::{Context, ContextBuilder, NoOpScanner};
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use scannerlib::models;
@@ -49,7 +56,7 @@ pub enum ClientIdentifier {
     Known(ClientHash),
 }
 
-fn retrieve_and_reset(id: Arc<RwLock<ClientIdentifier>>) -> ClientIdentifier {
+pub(crate) fn retrieve_and_reset(id: Arc<RwLock<ClientIdentifier>>) -> ClientIdentifier {
     // get client information
     let mut ci = id.write().unwrap();
     let cci = ci.clone();
@@ -58,6 +65,74 @@ fn retrieve_and_reset(id: Arc<RwLock<ClientIdentifier>>) -> ClientIdentifier {
     cci
 }
 
+/// A client identified by its certificate, api key or bearer token
+#[derive(Default, Debug, Clone)]
+pub struct Caller {
+    /// None when the client could not be identified
+    pub cid: Option<ClientHash>,
+    /// Role of a client authenticated by a token, other clients are not restricted
+    pub role: Option<Role>,
+    /// Tenant of a client authenticated by a token
+    pub tenant: Option<String>,
+}
+
+impl Caller {
+    /// Identifies the client of a request via HTTP or gRPC.
+    pub async fn identify<S, DB>(
+        ctx: &Context<S, DB>,
+        cid: &ClientIdentifier,
+        headers: &hyper::HeaderMap,
+    ) -> Self {
+        let api_key = || match (ctx.api_key.as_ref(), headers.get("x-api-key")) {
+            (Some(key), Some(v)) if v == key => Some(key.into()),
+            (Some(_), Some(v)) => {
+                tracing::debug!("invalid key: {:?}", v);
+                None
+            }
+            _ => None,
+        };
+        let bearer = ctx.oidc.as_ref().zip(crate::oidc::bearer_token(headers));
+        match (cid, bearer) {
+            (ClientIdentifier::Known(cid), _) => Self {
+                cid: Some(cid.clone()),
+                ..Default::default()
+            },
+            (_, Some((oidc, token))) => match oidc.authenticate(token).await {
+                Ok(identity) => Self {
+                    cid: Some(identity.client_hash()),
+                    role: Some(identity.role),
+                    tenant: identity.tenant,
+                },
+                Err(e) => {
+                    tracing::debug!("invalid token: {e}");
+                    Self::default()
+                }
+            },
+            (ClientIdentifier::Disabled, None) => Self {
+                cid: if ctx.api_key.is_some() {
+                    api_key()
+                } else if ctx.oidc.is_some() {
+                    None
+                } else {
+                    Some("disabled".into())
+                },
+                ..Default::default()
+            },
+            // We don't allow no api key and no client certs when we have a server
+            // certificate to prevent accidental misconfiguration.
+            (ClientIdentifier::Unknown, None) => Self {
+                cid: api_key(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns the feed subset of the client or None when it may access the whole feed.
+    pub fn subset(&self, tenancy: &config::Tenancy) -> Option<FeedSubset> {
+        crate::tenancy::subset(tenancy, self.tenant.as_deref(), self.role)
+    }
+}
+
 pub async fn run<'a, S, DB>(
     mut ctx: Context<S, DB>,
     config: &config::Config,
@@ -67,6 +142,7 @@ where
         + models::scanner::ScanStopper
         + models::scanner::ScanDeleter
         + models::scanner::ScanResultFetcher
+        + models::scanner::ScanPlanner
         + std::marker::Send
         + std::marker::Sync
         + 'static,
@@ -78,11 +154,50 @@ where
 
     let tls_config = ctx.tls_config.take();
     let controller = std::sync::Arc::new(ctx);
+    if let Some(address) = config.listener.grpc {
+        #[cfg(feature = "grpc")]
+        {
+            let ctx = Arc::clone(&controller);
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Err(e) = crate::grpc::listen(ctx, address, &config).await {
+                    tracing::error!(%e, "gRPC listener stopped");
+                }
+            });
+        }
+        #[cfg(not(feature = "grpc"))]
+        tracing::warn!(%address, "gRPC is not served as openvasd is built without the grpc feature");
+    }
+    if let Some(address) = config.listener.osp {
+        let ctx = Arc::clone(&controller);
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::controller::osp::listen(ctx, address, &config).await {
+                tracing::error!(%e, "OSP listener stopped");
+            }
+        });
+    }
     tracing::info!(?config.mode, "running in");
     if config.mode == config::Mode::Service {
         tokio::spawn(crate::controller::results::fetch(Arc::clone(&controller)));
     }
     tokio::spawn(crate::controller::feed::fetch(Arc::clone(&controller)));
+    tokio::spawn(crate::controller::enrichment::sync(Arc::clone(&controller)));
+    tokio::spawn(crate::controller::maintenance::compact(
+        Arc::clone(&controller),
+        config.storage.maintenance.clone(),
+    ));
+    tokio::spawn(crate::controller::publisher::publish(
+        Arc::clone(&controller),
+        config.publisher.clone(),
+    ));
+    if config.mode == config::Mode::Service && config.scanner.cluster.controller.url.is_some() {
+        tokio::spawn(crate::cluster::heartbeat(
+            Arc::clone(&controller),
+            config.scanner.cluster.controller.clone(),
+            config.endpoints.key.clone(),
+        ));
+    }
 
     if let Some(tls_config) = tls_config {
         use hyper::server::conn::http2::Builder;
@@ -137,7 +252,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use scannerlib::models::Scan;
+    use scannerlib::models::{AliveTestMethods, PlannedStatus, Scan, ScanPreference, Target, VT};
 
     use crate::controller::ClientIdentifier;
 
@@ -173,4 +288,64 @@ mod tests {
             "expected to not be allowed to get that scan without cid"
         );
     }
+
+    #[tokio::test]
+    async fn alive_test() {
+        let client = super::entry::client::in_memory_example_feed().await;
+        let target = Target {
+            hosts: vec!["127.0.0.1".into(), "192.0.2.1".into()],
+            excluded_hosts: vec!["192.0.2.1".into()],
+            alive_test_methods: vec![AliveTestMethods::ConsiderAlive],
+            ..Default::default()
+        };
+        let status = client.alive(&target).await.unwrap();
+        assert_eq!(status.len(), 1);
+        assert!(status[0].alive);
+    }
+
+    #[tokio::test]
+    async fn scan_plan() {
+        let client = super::entry::client::in_memory_example_feed().await;
+        let mut scan = Scan::default();
+        scan.target.hosts.push("localhost".to_string());
+        scan.vts = vec![VT {
+            oid: "0.0.0.0.0.0.0.0.0.3".to_string(),
+            parameters: vec![],
+        }];
+        let id = client.scan_create(&scan).await.unwrap();
+        let plan = client.scan_plan(&id).await.unwrap();
+        assert_eq!(plan.scan_id, id);
+        assert_eq!(plan.hosts.len(), 1);
+        assert!(plan.hosts[0]
+            .vts
+            .iter()
+            .any(|x| x.oid == "0.0.0.0.0.0.0.0.0.3" && x.status == PlannedStatus::Run));
+        assert!(client.scan_plan("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn network_namespace_must_be_allowed() {
+        let client = super::entry::client::in_memory_example_feed().await;
+        let scan = Scan {
+            scan_preferences: vec![ScanPreference {
+                id: "network_namespace".to_string(),
+                value: "customer-a".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(client.scan_create(&scan).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn severity_profile_must_be_configured() {
+        let client = super::entry::client::in_memory_example_feed().await;
+        let scan = Scan {
+            scan_preferences: vec![ScanPreference {
+                id: "severity_profile".to_string(),
+                value: "org".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(client.scan_create(&scan).await.is_err());
+    }
 }
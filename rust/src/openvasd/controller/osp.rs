@@ -0,0 +1,547 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! OSP listener so that gvmd can use openvasd as a drop-in for ospd-openvas.
+//!
+//! Each command is sent as a XML document over a TLS connection authenticated by a client
+//! certificate. The commands are mapped to the same scheduler operations as the HTTP API,
+//! therefore scans created via OSP are also visible via HTTP for the same client.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use scannerlib::{
+    models::{
+        self, cvss,
+        scanner::{ScanDeleter, ScanPlanner, ScanResultFetcher, ScanStarter, ScanStopper},
+    },
+    osp::{
+        osp_command_name, osp_failure, osp_is_complete, osp_scans, osp_started, osp_success,
+        osp_version, OspRequest, OspScan, OspScanResult,
+    },
+    storage::item::TagKey,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use super::{context::Context, retrieve_and_reset, ClientHash, ClientIdentifier};
+use crate::storage::{
+    NVTStorer as _, ProgressGetter as _, ScanIDClientMapper as _, ScanStorer as _,
+};
+
+/// Maximal size of a command
+const MAX_COMMAND_SIZE: usize = 16 * 1024 * 1024;
+/// Time a client has to send a complete command
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Failure = (u16, String);
+
+/// Handles the OSP commands of clients.
+pub struct Osp<S, DB> {
+    ctx: Arc<Context<S, DB>>,
+    /// Number of results already returned per scan for get_scans with pop_results
+    delivered: Mutex<HashMap<String, usize>>,
+}
+
+impl<S, DB> Osp<S, DB>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+{
+    pub fn new(ctx: Arc<Context<S, DB>>) -> Self {
+        Self {
+            ctx,
+            delivered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Handles a command and returns the response.
+    pub async fn handle(&self, cid: &ClientHash, xml: &[u8]) -> Vec<u8> {
+        let Some(command) = osp_command_name(xml) else {
+            return respond("osp", Err((400, "Invalid XML".to_string())));
+        };
+        let request = match OspRequest::from_xml(xml) {
+            Ok(x) => x,
+            Err(_) if !KNOWN_COMMANDS.contains(&command.as_str()) => {
+                return respond("osp", Err((400, "Bogus command name".to_string())));
+            }
+            Err(e) => return respond(&command, Err((400, format!("{e:?}")))),
+        };
+        tracing::debug!(command, "OSP command");
+        let response = match request {
+            OspRequest::StartScan(scan) => self.start_scan(cid, *scan).await,
+            OspRequest::StopScan(id) => self.stop_scan(cid, id).await,
+            OspRequest::DeleteScan(id) => self.delete_scan(cid, id).await,
+            OspRequest::GetScans {
+                scan_id,
+                pop_results,
+                details,
+            } => self.get_scans(cid, scan_id, pop_results, details).await,
+            OspRequest::GetVersion => osp_version("openvasd", env!("CARGO_PKG_VERSION"))
+                .map_err(|e| (500, format!("{e:?}"))),
+        };
+        respond(&command, response)
+    }
+
+    async fn check_client(&self, cid: &ClientHash, id: &str) -> Result<(), Failure> {
+        match self
+            .ctx
+            .scheduler
+            .is_client_allowed(id.to_string(), cid)
+            .await
+        {
+            Ok(true) => Ok(()),
+            // like the HTTP API unknown and foreign scans are not distinguished
+            Ok(false) | Err(crate::storage::Error::NotFound) => {
+                Err((404, format!("Failed to find scan '{id}'")))
+            }
+            Err(e) => Err((500, e.to_string())),
+        }
+    }
+
+    async fn start_scan(
+        &self,
+        cid: &ClientHash,
+        mut scan: models::Scan,
+    ) -> Result<Vec<u8>, Failure> {
+        // OSP clients are identified by their certificate and belong to the default tenant
+        let subset = crate::tenancy::subset(&self.ctx.tenancy, None, None);
+        self.ctx
+            .admit(&scan, subset.as_ref())
+            .await
+            .map_err(|e| (400, e.to_string()))?;
+        if scan.scan_id.is_empty() {
+            scan.scan_id = uuid::Uuid::new_v4().to_string();
+        }
+        let id = scan.scan_id.clone();
+        let scheduler = &self.ctx.scheduler;
+        if scheduler.get_status(&id).await.is_ok() {
+            return Err((400, format!("Scan '{id}' already exists")));
+        }
+        scheduler
+            .insert_scan(scan)
+            .await
+            .map_err(|e| (500, e.to_string()))?;
+        scheduler
+            .add_scan_client_id(id.clone(), cid.clone())
+            .await
+            .map_err(|e| (500, e.to_string()))?;
+        if let Err(e) = scheduler.start_scan_within(&id, subset.as_ref()).await {
+            // gvmd does not know the scan when it could not be started
+            if let Err(e) = scheduler.delete_scan_by_id(&id).await {
+                tracing::warn!(%id, %e, "unable to remove scan");
+            }
+            return Err((500, e.to_string()));
+        }
+        tracing::debug!(%id, "Scan started via OSP");
+        osp_started(&id).map_err(|e| (500, format!("{e:?}")))
+    }
+
+    async fn stop_scan(&self, cid: &ClientHash, id: String) -> Result<Vec<u8>, Failure> {
+        self.check_client(cid, &id).await?;
+        self.ctx
+            .scheduler
+            .stop_scan(id)
+            .await
+            .map_err(|e| (500, e.to_string()))?;
+        osp_success("stop_scan").map_err(|e| (500, format!("{e:?}")))
+    }
+
+    async fn delete_scan(&self, cid: &ClientHash, id: String) -> Result<Vec<u8>, Failure> {
+        self.check_client(cid, &id).await?;
+        self.ctx
+            .scheduler
+            .delete_scan_by_id(&id)
+            .await
+            .map_err(|e| (500, e.to_string()))?;
+        self.delivered.lock().unwrap().remove(&id);
+        osp_success("delete_scan").map_err(|e| (500, format!("{e:?}")))
+    }
+
+    async fn get_scans(
+        &self,
+        cid: &ClientHash,
+        id: Option<String>,
+        pop_results: bool,
+        details: bool,
+    ) -> Result<Vec<u8>, Failure> {
+        let ids = match id {
+            Some(id) => {
+                self.check_client(cid, &id).await?;
+                vec![id]
+            }
+            None => self
+                .ctx
+                .scheduler
+                .get_scans_of_client_id(cid)
+                .await
+                .map_err(|e| (500, e.to_string()))?,
+        };
+        let mut vts = HashMap::new();
+        let mut scans = Vec::with_capacity(ids.len());
+        for id in ids {
+            scans.push(self.scan(&id, pop_results, details, &mut vts).await?);
+        }
+        osp_scans(&scans).map_err(|e| (500, format!("{e:?}")))
+    }
+
+    async fn scan(
+        &self,
+        id: &str,
+        pop_results: bool,
+        details: bool,
+        vts: &mut HashMap<String, (String, f32)>,
+    ) -> Result<OspScan, Failure> {
+        let (scan, status) = self
+            .ctx
+            .scheduler
+            .get_scan(id)
+            .await
+            .map_err(|e| (500, e.to_string()))?;
+        let mut results = vec![];
+        if details {
+            let offset = self
+                .delivered
+                .lock()
+                .unwrap()
+                .get(id)
+                .copied()
+                .unwrap_or_default();
+            let stored: Vec<models::Result> = self
+                .ctx
+                .scheduler
+                .get_results(id, None, None)
+                .await
+                .map_err(|e| (500, e.to_string()))?
+                .skip(offset)
+                .filter_map(|x| serde_json::from_slice(&x).ok())
+                .collect();
+            if pop_results {
+                self.delivered
+                    .lock()
+                    .unwrap()
+                    .insert(id.to_string(), offset + stored.len());
+            }
+            for result in &stored {
+                let mut osp = OspScanResult::from(result);
+                if let Some(oid) = result.oid.as_ref().filter(|x| !x.is_empty()) {
+                    let (name, severity) = self.vt(oid, vts).await;
+                    if osp.name.is_empty() {
+                        osp.name = name;
+                    }
+                    if result.r_type == models::ResultType::Alarm {
+                        osp.severity = severity.into();
+                    }
+                }
+                results.push(osp);
+            }
+        }
+        Ok(OspScan::from_status(id, &scan.target, &status, results))
+    }
+
+    /// Returns the name and the severity of a VT.
+    async fn vt(&self, oid: &str, vts: &mut HashMap<String, (String, f32)>) -> (String, f32) {
+        if let Some(x) = vts.get(oid) {
+            return x.clone();
+        }
+        let vt = match self.ctx.scheduler.vt_by_oid(oid).await {
+            Ok(Some(vt)) => {
+                let severity = [TagKey::SeverityVector, TagKey::CvssBaseVector]
+                    .iter()
+                    .find_map(|key| cvss::base_score(&vt.tag.get(key)?.to_string()))
+                    .unwrap_or_default();
+                (vt.name, severity as f32)
+            }
+            _ => (String::new(), 0.0),
+        };
+        vts.insert(oid.to_string(), vt.clone());
+        vt
+    }
+}
+
+const KNOWN_COMMANDS: &[&str] = &[
+    "start_scan",
+    "stop_scan",
+    "delete_scan",
+    "get_scans",
+    "get_version",
+];
+
+fn respond(command: &str, response: Result<Vec<u8>, Failure>) -> Vec<u8> {
+    match response {
+        Ok(x) => x,
+        Err((code, text)) => {
+            tracing::debug!(command, code, text, "OSP command failed");
+            osp_failure(command, code, &text).unwrap_or_default()
+        }
+    }
+}
+
+/// Reads commands from a connection and writes the responses until the client closes it.
+pub async fn serve<S, DB, C>(
+    osp: Arc<Osp<S, DB>>,
+    cid: ClientHash,
+    mut connection: C,
+) -> std::io::Result<()>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+    C: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = match tokio::time::timeout(READ_TIMEOUT, connection.read(&mut chunk)).await {
+            Ok(read) => read?,
+            Err(_) => return Err(std::io::ErrorKind::TimedOut.into()),
+        };
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if buffer.len() > MAX_COMMAND_SIZE {
+            let response = respond("osp", Err((400, "Command too large".to_string())));
+            connection.write_all(&response).await?;
+            return Ok(());
+        }
+        if osp_is_complete(&buffer) {
+            let response = osp.handle(&cid, &buffer).await;
+            connection.write_all(&response).await?;
+            connection.flush().await?;
+            buffer.clear();
+        }
+    }
+}
+
+/// Accepts OSP connections on the given address.
+///
+/// Only clients with a known certificate are served as the OSP scan IDs are bound to them.
+pub async fn listen<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    address: SocketAddr,
+    config: &crate::config::Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+{
+    let Some(tls_config) = crate::tls::tls_config(config)? else {
+        return Err("the OSP listener requires TLS with client certificates".into());
+    };
+    if !tls_config.has_clients {
+        return Err("the OSP listener requires client certificates".into());
+    }
+    let mut server_config = tls_config.config;
+    // OSP is not negotiated via ALPN
+    server_config.alpn_protocols.clear();
+    let tls_acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+    let incoming = TcpListener::bind(&address).await?;
+    let osp = Arc::new(Osp::new(ctx));
+    tracing::info!("listening for OSP on {}", address);
+    loop {
+        let (tcp_stream, _remote_addr) = incoming.accept().await?;
+        let tls_acceptor = tls_acceptor.clone();
+        let identifier = tls_config.client_identifier.clone();
+        let osp = osp.clone();
+        tokio::spawn(async move {
+            let tls_stream = match tls_acceptor.accept(tcp_stream).await {
+                Ok(tls_stream) => tls_stream,
+                Err(err) => {
+                    tracing::debug!("failed to perform tls handshake: {err:#}");
+                    return;
+                }
+            };
+            let ClientIdentifier::Known(cid) = retrieve_and_reset(identifier) else {
+                tracing::debug!("OSP client without a known certificate");
+                return;
+            };
+            if let Err(err) = serve(osp, cid, tls_stream).await {
+                tracing::debug!("failed to serve OSP connection: {err:#}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use scannerlib::{
+        models::{Phase, Protocol, ResultType},
+        osp::{OspResponse, OspScanStatus},
+    };
+
+    use super::*;
+    use crate::storage::AppendFetchResult as _;
+
+    fn read(xml: Vec<u8>) -> OspResponse {
+        quick_xml::de::from_str(std::str::from_utf8(&xml).unwrap()).unwrap()
+    }
+
+    fn osp() -> Osp<
+        super::super::NoOpScanner,
+        crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
+    > {
+        let ctx = crate::controller::ContextBuilder::new()
+            .scanner(super::super::NoOpScanner)
+            .build();
+        Osp::new(Arc::new(ctx))
+    }
+
+    const START: &str = r#"<start_scan scan_id="42">
+  <scanner_params><max_checks>4</max_checks></scanner_params>
+  <vt_selection><vt_single id="1.3.6.1.4.1.25623.1.0.10330"/></vt_selection>
+  <targets><target><hosts>127.0.0.1</hosts><ports>T:22</ports></target></targets>
+</start_scan>"#;
+
+    #[tokio::test]
+    async fn start_get_and_delete() {
+        let osp = osp();
+        let cid: ClientHash = "gvmd".into();
+        let response = read(osp.handle(&cid, START.as_bytes()).await);
+        assert!(matches!(
+            response,
+            OspResponse::StartScan { id: Some(ref id), ref status } if id == "42" && status.is_ok()
+        ));
+        // the same id cannot be started twice
+        assert!(!read(osp.handle(&cid, START.as_bytes()).await)
+            .status()
+            .is_ok());
+
+        let results = [
+            models::Result {
+                r_type: ResultType::Alarm,
+                ip_address: Some("127.0.0.1".into()),
+                port: Some(22),
+                protocol: Some(Protocol::TCP),
+                oid: Some("1.2.3".into()),
+                message: Some("vulnerable".into()),
+                ..Default::default()
+            },
+            models::Result {
+                id: 1,
+                r_type: ResultType::HostEnd,
+                ip_address: Some("127.0.0.1".into()),
+                ..Default::default()
+            },
+        ];
+        let status = models::Status {
+            status: Phase::Succeeded,
+            ..Default::default()
+        };
+        osp.ctx
+            .scheduler
+            .append_fetched_result(vec![models::scanner::ScanResults {
+                id: "42".into(),
+                status,
+                results: results.to_vec(),
+            }])
+            .await
+            .unwrap();
+
+        let get = r#"<get_scans scan_id="42" pop_results="1"/>"#;
+        let OspResponse::GetScans {
+            scan: Some(scan), ..
+        } = read(osp.handle(&cid, get.as_bytes()).await)
+        else {
+            panic!("expected a scan");
+        };
+        assert_eq!(scan.status, OspScanStatus::Succeeded);
+        assert_eq!(u64::from(scan.progress), 100);
+        let read_results: Vec<models::Result> = scan.into();
+        assert_eq!(read_results.len(), 2);
+        assert_eq!(read_results[0].r_type, ResultType::Alarm);
+        assert_eq!(read_results[1].r_type, ResultType::HostEnd);
+        // popped results are not returned again
+        let OspResponse::GetScans {
+            scan: Some(scan), ..
+        } = read(osp.handle(&cid, get.as_bytes()).await)
+        else {
+            panic!("expected a scan");
+        };
+        assert!(scan.results.result.is_empty());
+
+        // other clients do not see the scan
+        let other: ClientHash = "other".into();
+        assert!(!read(osp.handle(&other, get.as_bytes()).await)
+            .status()
+            .is_ok());
+        let delete = r#"<delete_scan scan_id="42"/>"#;
+        assert!(!read(osp.handle(&other, delete.as_bytes()).await)
+            .status()
+            .is_ok());
+        assert!(read(osp.handle(&cid, delete.as_bytes()).await)
+            .status()
+            .is_ok());
+        assert!(osp.ctx.scheduler.get_scan("42").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn start_scan_admission() {
+        let osp = osp();
+        let cid: ClientHash = "gvmd".into();
+        let netns = START.replace(
+            "<max_checks>4</max_checks>",
+            "<network_namespace>other</network_namespace>",
+        );
+        let response = String::from_utf8(osp.handle(&cid, netns.as_bytes()).await).unwrap();
+        assert!(
+            response.contains("Network namespace other is not allowed"),
+            "{response}"
+        );
+        assert!(osp.ctx.scheduler.get_scan("42").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_command() {
+        let osp = osp();
+        let response = osp.handle(&"gvmd".into(), b"<help/>").await;
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("<osp_response"), "{response}");
+        assert!(response.contains("Bogus command name"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn serve_connection() {
+        let osp = Arc::new(osp());
+        let (mut client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(serve(osp, "gvmd".into(), server));
+        // a command may arrive in several parts
+        client.write_all(b"<get_ver").await.unwrap();
+        client.write_all(b"sion/>").await.unwrap();
+        let mut response = vec![0; 1024];
+        let read = client.read(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response[..read]).to_string();
+        assert!(response.contains("<name>OSP</name>"), "{response}");
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+}
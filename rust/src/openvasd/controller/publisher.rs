@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the loop publishing new results and status changes of scans to a message queue.
+//!
+//! Scans that exist on start up are considered as published, only their later changes are
+//! published. Unpublished messages are retried on the next run.
+
+use std::{collections::HashMap, sync::Arc};
+
+use scannerlib::models::{self, scanner::Scanner, Phase};
+
+use super::context::Context;
+use crate::{
+    config,
+    publisher::{self, Event, EventType, Publisher},
+    storage::ProgressGetter as _,
+};
+
+/// Maximum number of results published at once
+const BATCH_SIZE: usize = 500;
+
+/// What was published about a scan
+#[derive(Debug, Default)]
+struct Published {
+    phase: Option<Phase>,
+    results: usize,
+}
+
+/// Publishes the new results and status changes of all scans.
+async fn step<S, DB>(
+    ctx: &Context<S, DB>,
+    publisher: &mut Publisher,
+    scans: &mut HashMap<String, Published>,
+) -> Result<(), publisher::Error>
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let ids = match ctx.scheduler.get_scan_ids().await {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::warn!(%e, "Unable to get the scans to publish");
+            return Ok(());
+        }
+    };
+    let deleted: Vec<String> = scans.keys().filter(|x| !ids.contains(x)).cloned().collect();
+    for id in deleted {
+        publisher
+            .event(&Event::new(&id, EventType::Deleted, None))
+            .await?;
+        scans.remove(&id);
+    }
+    for id in ids {
+        let published = scans.entry(id.clone()).or_default();
+        // the status of the last batch covers all published results
+        let status = loop {
+            let (status, results) = match ctx
+                .scheduler
+                .get_status_and_results(
+                    &id,
+                    Some(published.results),
+                    Some(published.results + BATCH_SIZE),
+                )
+                .await
+            {
+                Ok((status, results)) => (status, results.collect::<Vec<_>>()),
+                Err(e) => {
+                    tracing::debug!(id, %e, "Unable to get status and results of scan to publish");
+                    break None;
+                }
+            };
+            let fetched = results.len();
+            let results: Vec<models::Result> = results
+                .into_iter()
+                .filter_map(|x| match serde_json::from_slice(&x) {
+                    Ok(x) => Some(x),
+                    Err(e) => {
+                        tracing::warn!(id, %e, "Skipping invalid result");
+                        None
+                    }
+                })
+                .collect();
+            publisher.results(&id, &results).await?;
+            published.results += fetched;
+            if fetched < BATCH_SIZE {
+                break Some(status);
+            }
+        };
+        let Some(status) = status else {
+            continue;
+        };
+        if published.phase.as_ref() != Some(&status.status) {
+            publisher
+                .event(&Event::new(&id, (&status.status).into(), Some(&status)))
+                .await?;
+            published.phase = Some(status.status);
+        }
+    }
+    Ok(())
+}
+
+/// Publishes new results and status changes of scans.
+///
+/// This loop should be run as background task, it returns immediately when no publisher is
+/// configured.
+pub async fn publish<S, DB>(ctx: Arc<Context<S, DB>>, config: config::Publisher)
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let Some(mut publisher) = Publisher::new(&config) else {
+        return;
+    };
+    tracing::debug!(url = config.url, "Starting publisher loop");
+    let mut appended = ctx.scheduler.subscribe_results();
+    let mut scans = HashMap::new();
+    for id in ctx.scheduler.get_scan_ids().await.unwrap_or_default() {
+        let phase = ctx.scheduler.get_status(&id).await.ok().map(|x| x.status);
+        let results = ctx.scheduler.count_results(&id).await.unwrap_or_default();
+        scans.insert(id, Published { phase, results });
+    }
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        tokio::select! {
+            _ = appended.changed() => {}
+            _ = interval.tick() => {}
+        }
+        if *ctx.abort.read().unwrap() {
+            tracing::trace!("aborting");
+            break;
+        }
+        if let Err(e) = step(&ctx, &mut publisher, &mut scans).await {
+            tracing::warn!(%e, "Unable to publish");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{scanner::ScanResults, ResultType, Status};
+
+    use super::*;
+    use crate::{
+        controller::{ContextBuilder, NoOpScanner},
+        publisher::nats_server,
+        storage::{AppendFetchResult as _, ScanStorer as _},
+    };
+
+    #[tokio::test]
+    async fn publishes_results_and_events() {
+        let (url, mut received) = nats_server().await;
+        let config = config::Publisher {
+            url: Some(url),
+            ..Default::default()
+        };
+        let mut publisher = Publisher::new(&config).unwrap();
+        let ctx = ContextBuilder::new().scanner(NoOpScanner).build();
+        let mut scans = HashMap::new();
+        let scan = models::Scan {
+            scan_id: "42".into(),
+            ..Default::default()
+        };
+        ctx.scheduler.insert_scan(scan).await.unwrap();
+        step(&ctx, &mut publisher, &mut scans).await.unwrap();
+        let (subject, payload) = received.recv().await.unwrap();
+        assert_eq!(subject, "openvasd.events");
+        let event: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(event["scan_id"], "42");
+        assert_eq!(event["event"], "stored");
+
+        let results = vec![
+            models::Result {
+                r_type: ResultType::Alarm,
+                oid: Some("1.2.3".into()),
+                ..Default::default()
+            },
+            models::Result {
+                r_type: ResultType::HostEnd,
+                ..Default::default()
+            },
+        ];
+        ctx.scheduler
+            .append_fetched_result(vec![ScanResults {
+                id: "42".into(),
+                status: Status {
+                    status: Phase::Succeeded,
+                    ..Default::default()
+                },
+                results,
+            }])
+            .await
+            .unwrap();
+        step(&ctx, &mut publisher, &mut scans).await.unwrap();
+        let mut messages = vec![];
+        for _ in 0..3 {
+            let (subject, payload) = received.recv().await.unwrap();
+            let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+            messages.push((subject, payload));
+        }
+        assert_eq!(messages[0].0, "openvasd.results");
+        assert_eq!(messages[0].1["scan_id"], "42");
+        assert_eq!(messages[0].1["oid"], "1.2.3");
+        assert_eq!(messages[1].1["type"], "host_end");
+        assert_eq!(messages[2].0, "openvasd.events");
+        assert_eq!(messages[2].1["event"], "succeeded");
+
+        // nothing changed, nothing is published
+        step(&ctx, &mut publisher, &mut scans).await.unwrap();
+        ctx.scheduler.delete_scan_by_id("42").await.unwrap();
+        step(&ctx, &mut publisher, &mut scans).await.unwrap();
+        let (subject, payload) = received.recv().await.unwrap();
+        assert_eq!(subject, "openvasd.events");
+        let event: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(event["event"], "deleted");
+        assert!(scans.is_empty());
+    }
+}
@@ -2,15 +2,21 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-//! Defines the result fetching loop.
+//! Defines the result fetching loop and the streaming of fetched results.
 //!
-//! This loop should be run as background task to fetch results from the scanner.
+//! The fetching loop should be run as background task to fetch results from the scanner.
 
+use flate2::{write::GzEncoder, Compression};
+use hyper::body::Bytes;
 use scannerlib::models::scanner::Scanner;
 
-use std::sync::Arc;
+use std::{io::Write, sync::Arc};
 
 use super::context::Context;
+use crate::{
+    response::event,
+    storage::{AnnotationStorer as _, ProgressGetter as _},
+};
 
 /// Defines the result fetching loop.
 ///
@@ -45,3 +51,168 @@ where
         }
     }
 }
+
+/// Returns the QoD of a serialized result
+fn qod(result: &[u8]) -> Option<u8> {
+    #[derive(serde::Deserialize)]
+    struct Qod {
+        qod: Option<u8>,
+    }
+    serde_json::from_slice::<Qod>(result).ok()?.qod
+}
+
+/// Removes the serialized results with a QoD below min_qod.
+///
+/// Results without a QoD, e.g. the start and end of a host, are kept.
+pub fn filter_qod(
+    min_qod: Option<u8>,
+    results: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+    match min_qod {
+        None => results,
+        Some(min_qod) => Box::new(results.filter(move |x| qod(x).is_none_or(|x| x >= min_qod))),
+    }
+}
+
+/// Returns true when the auto-triage suppressed a serialized result
+fn is_suppressed(result: &[u8]) -> bool {
+    #[derive(serde::Deserialize)]
+    struct Suppressed {
+        suppressed: bool,
+    }
+    #[derive(serde::Deserialize)]
+    struct Triage {
+        triage: Option<Suppressed>,
+    }
+    serde_json::from_slice::<Triage>(result)
+        .ok()
+        .and_then(|x| x.triage)
+        .is_some_and(|x| x.suppressed)
+}
+
+/// Removes the serialized results suppressed by the auto-triage unless they are included.
+pub fn filter_suppressed(
+    include: bool,
+    results: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+    if include {
+        results
+    } else {
+        Box::new(results.filter(|x| !is_suppressed(x)))
+    }
+}
+
+/// Amount of results compressed into one gzip member of a download
+pub const DOWNLOAD_CHUNK: usize = 1000;
+
+/// Compresses the results as newline delimited JSON into a single gzip member.
+fn compress(results: impl Iterator<Item = Vec<u8>>) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for result in results {
+        encoder.write_all(&result)?;
+        encoder.write_all(b"\n")?;
+    }
+    encoder.finish()
+}
+
+/// Sends the results of a scan from `begin` until `end` as gzip compressed newline delimited
+/// JSON.
+///
+/// Each `chunk` results are compressed into a gzip member of their own and sent as soon as they
+/// are compressed, so that only a chunk is kept in memory and an interrupted download can be
+/// decompressed until its last complete member. The download ends when the receiver is dropped.
+pub async fn download<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    id: String,
+    (begin, end): (usize, usize),
+    min_qod: Option<u8>,
+    suppressed: bool,
+    chunk: usize,
+    chunks: tokio::sync::mpsc::Sender<Bytes>,
+) where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let annotations = ctx.scheduler.get_annotations(&id).await.unwrap_or_default();
+    for from in (begin..end).step_by(chunk.max(1)) {
+        let to = end.min(from + chunk.max(1));
+        let results = match ctx.scheduler.get_results(&id, Some(from), Some(to)).await {
+            Ok(results) => {
+                let results = filter_qod(min_qod, results);
+                let results = filter_suppressed(suppressed, results);
+                let results = super::annotations::annotate(&annotations, results);
+                super::enrichment::enrich(&ctx, results).await
+            }
+            Err(e) => {
+                tracing::debug!(id, %e, "stopping result download");
+                return;
+            }
+        };
+        let compressed = match compress(results) {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::warn!(id, %e, "unable to compress results");
+                return;
+            }
+        };
+        if chunks.send(compressed.into()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Sends the results of a scan, beginning with the result id `next`, as server-sent events.
+///
+/// New results are sent as soon as they got fetched from the scanner. When the scan is finished a
+/// final `status` event is sent and the stream ends. It also ends when the receiver is dropped.
+pub async fn stream<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    id: String,
+    mut next: usize,
+    events: tokio::sync::mpsc::Sender<Bytes>,
+) where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let mut appended = ctx.scheduler.subscribe_results();
+    loop {
+        appended.borrow_and_update();
+        let (status, results) = match ctx
+            .scheduler
+            .get_status_and_results(&id, Some(next), None)
+            .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::debug!(id, %e, "stopping result stream");
+                return;
+            }
+        };
+        let annotations = ctx.scheduler.get_annotations(&id).await.unwrap_or_default();
+        let results = super::annotations::annotate(&annotations, results);
+        let results = super::enrichment::enrich(&ctx, results).await;
+        for result in results {
+            if events
+                .send(event(Some(next), "result", &result))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            next += 1;
+        }
+        if status.is_done() {
+            let status = serde_json::to_vec(&status).unwrap_or_default();
+            let _ = events.send(event(None, "status", &status)).await;
+            return;
+        }
+        tokio::select! {
+            changed = appended.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            _ = events.closed() => return,
+        }
+    }
+}
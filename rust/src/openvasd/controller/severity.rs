@@ -0,0 +1,96 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Defines the classification of served results by the severity profile of their scan.
+
+use std::collections::HashMap;
+
+use scannerlib::models::{
+    cvss::{self, SeverityProfile},
+    scanner::Scanner,
+};
+use scannerlib::storage::item::TagKey;
+
+use super::context::Context;
+use crate::storage::NVTStorer as _;
+
+/// Returns the OID of a serialized result
+fn oid(result: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Oid {
+        oid: Option<String>,
+    }
+    serde_json::from_slice::<Oid>(result).ok()?.oid
+}
+
+/// Adds the base score and its level to a serialized result as `severity`.
+fn with_severity(result: Vec<u8>, score: f64, level: Option<&str>) -> Vec<u8> {
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&result) else {
+        return result;
+    };
+    if let Some(object) = value.as_object_mut() {
+        let mut severity = serde_json::json!({ "score": score });
+        if let Some(level) = level {
+            severity["level"] = level.into();
+        }
+        object.insert("severity".to_string(), severity);
+    }
+    serde_json::to_vec(&value).unwrap_or(result)
+}
+
+/// Adds the base score of the severity vector of their VT and its level within the profile to
+/// serialized results.
+///
+/// Results without an OID or whose VT has no valid severity vector are returned unchanged.
+pub async fn classify<S, DB>(
+    ctx: &Context<S, DB>,
+    profile: &SeverityProfile,
+    results: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+) -> Box<dyn Iterator<Item = Vec<u8>> + Send>
+where
+    S: Scanner + 'static + std::marker::Send + std::marker::Sync,
+    DB: crate::storage::Storage + 'static + std::marker::Send + std::marker::Sync,
+{
+    let results: Vec<Vec<u8>> = results.collect();
+    let mut scores: HashMap<String, Option<f64>> = HashMap::new();
+    for oid in results.iter().filter_map(|x| oid(x)) {
+        if scores.contains_key(&oid) {
+            continue;
+        }
+        let score = match ctx.scheduler.vt_by_oid(&oid).await {
+            Ok(Some(vt)) => [TagKey::SeverityVector, TagKey::CvssBaseVector]
+                .iter()
+                .find_map(|key| cvss::base_score(&vt.tag.get(key)?.to_string())),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::debug!(oid, %e, "Unable to get VT of result");
+                None
+            }
+        };
+        scores.insert(oid, score);
+    }
+    let results: Vec<Vec<u8>> = results
+        .into_iter()
+        .map(
+            |x| match oid(&x).and_then(|o| scores.get(&o).copied().flatten()) {
+                Some(score) => with_severity(x, score, profile.level(score)),
+                None => x,
+            },
+        )
+        .collect();
+    Box::new(results.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn with_severity() {
+        let result = br#"{"id":0,"type":"alarm","oid":"1.2.3"}"#.to_vec();
+        let value: serde_json::Value =
+            serde_json::from_slice(&super::with_severity(result, 7.3, Some("high"))).unwrap();
+        assert_eq!(value["oid"], "1.2.3");
+        assert_eq!(value["severity"]["score"], 7.3);
+        assert_eq!(value["severity"]["level"], "high");
+    }
+}
@@ -56,6 +56,11 @@ pub struct ChaCha20Crypt {
 }
 
 impl ChaCha20Crypt {
+    /// Creates a crypt using the given key instead of a random one
+    pub fn new(key: impl Into<Key>) -> Self {
+        Self { key: key.into() }
+    }
+
     fn encrypt_sync(key: &Key, mut data: Vec<u8>) -> Encrypted {
         let mut nonce = [0u8; 12];
         let mut rng = rand::thread_rng();
@@ -0,0 +1,490 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Enriches results with information about the CVEs referenced by their VT.
+//!
+//! The information is read from a local directory containing
+//! - JSON files in the format of the NVD CVE API 2.0, files are applied in the order of their
+//!   names so that a later file updates the CVEs of an earlier one,
+//! - a CSV file of EPSS scores as published by FIRST, named `epss*.csv`.
+//!
+//! Files may be gzip compressed. The directory is either synced externally or by the daemon when
+//! the urls of the NVD CVE API or of the EPSS scores are configured.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// File containing the time of the last NVD sync
+const LAST_NVD_SYNC: &str = "nvd.last_sync";
+/// Prefix of the downloaded NVD pages
+const NVD_PREFIX: &str = "nvd-";
+/// File name of the downloaded EPSS scores
+const EPSS_FILE: &str = "epss.csv";
+/// Maximum number of CVEs per page allowed by the NVD API
+const NVD_PAGE_SIZE: usize = 2000;
+/// Maximum range of the last modified dates allowed by the NVD API
+const NVD_MAX_RANGE_DAYS: i64 = 120;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to access the dataset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unable to download the dataset: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid NVD data: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Information about a CVE
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CveInfo {
+    pub id: String,
+    /// The CVSS vector of the primary metric, preferring the latest CVSS version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvss_vector: Option<String>,
+    /// The CVSS base score of the vector
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvss_base_score: Option<f64>,
+    /// The weaknesses, e.g. `CWE-79`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cwes: Vec<String>,
+    /// The probability of exploitation within the next 30 days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epss: Option<f64>,
+    /// The percentile of the EPSS score
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epss_percentile: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdResponse {
+    #[serde(default)]
+    total_results: usize,
+    #[serde(default)]
+    vulnerabilities: Vec<NvdVulnerability>,
+}
+
+#[derive(Deserialize)]
+struct NvdVulnerability {
+    cve: NvdCve,
+}
+
+#[derive(Deserialize)]
+struct NvdCve {
+    id: String,
+    #[serde(default)]
+    weaknesses: Vec<NvdWeakness>,
+    #[serde(default)]
+    metrics: NvdMetrics,
+}
+
+#[derive(Deserialize)]
+struct NvdWeakness {
+    description: Vec<NvdDescription>,
+}
+
+#[derive(Deserialize)]
+struct NvdDescription {
+    value: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct NvdMetrics {
+    #[serde(default)]
+    cvss_metric_v40: Vec<NvdMetric>,
+    #[serde(default)]
+    cvss_metric_v31: Vec<NvdMetric>,
+    #[serde(default)]
+    cvss_metric_v30: Vec<NvdMetric>,
+    #[serde(default)]
+    cvss_metric_v2: Vec<NvdMetric>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdMetric {
+    #[serde(default, rename = "type")]
+    m_type: String,
+    cvss_data: NvdCvssData,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdCvssData {
+    vector_string: String,
+    base_score: f64,
+}
+
+impl NvdMetrics {
+    fn primary(&self) -> Option<&NvdCvssData> {
+        [
+            &self.cvss_metric_v40,
+            &self.cvss_metric_v31,
+            &self.cvss_metric_v30,
+            &self.cvss_metric_v2,
+        ]
+        .into_iter()
+        .find_map(|x| x.iter().find(|m| m.m_type == "Primary").or(x.first()))
+        .map(|x| &x.cvss_data)
+    }
+}
+
+/// The CVEs known to the enrichment
+#[derive(Debug, Default)]
+pub struct CveDataset {
+    cves: HashMap<String, CveInfo>,
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    let data = std::fs::read(path)?;
+    if path.extension().is_some_and(|x| x == "gz") {
+        let mut result = String::new();
+        flate2::read::GzDecoder::new(data.as_slice()).read_to_string(&mut result)?;
+        Ok(result)
+    } else {
+        Ok(String::from_utf8_lossy(&data).to_string())
+    }
+}
+
+impl CveDataset {
+    /// Loads the files of the dataset directory
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| x.is_file())
+            .collect();
+        files.sort();
+        let mut result = Self::default();
+        let mut epss = vec![];
+        for file in files {
+            let name = file
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name = name.trim_end_matches(".gz");
+            if name.starts_with("epss") && name.ends_with(".csv") {
+                // scores are applied after all CVEs are known
+                epss.push(file);
+            } else if name.ends_with(".json") {
+                if let Err(e) = read_file(&file).and_then(|x| result.add_nvd(&x)) {
+                    tracing::warn!(file=%file.display(), error=%e, "Skipping invalid NVD file");
+                }
+            }
+        }
+        for file in epss {
+            match read_file(&file) {
+                Ok(x) => result.add_epss(&x),
+                Err(e) => tracing::warn!(file=%file.display(), error=%e, "Skipping EPSS file"),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Adds or updates the CVEs of a response of the NVD CVE API
+    pub fn add_nvd(&mut self, data: &str) -> Result<(), Error> {
+        let response: NvdResponse = serde_json::from_str(data)?;
+        for x in response.vulnerabilities {
+            let cve = x.cve;
+            let metric = cve.metrics.primary();
+            let mut cwes: Vec<String> = cve
+                .weaknesses
+                .into_iter()
+                .flat_map(|w| w.description)
+                .map(|d| d.value)
+                .filter(|x| x.starts_with("CWE-"))
+                .collect();
+            cwes.sort();
+            cwes.dedup();
+            let previous = self.cves.remove(&cve.id).unwrap_or_default();
+            self.cves.insert(
+                cve.id.clone(),
+                CveInfo {
+                    id: cve.id,
+                    cvss_vector: metric.map(|x| x.vector_string.clone()),
+                    cvss_base_score: metric.map(|x| x.base_score),
+                    cwes,
+                    ..previous
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds the scores of an EPSS CSV file to the known CVEs
+    pub fn add_epss(&mut self, data: &str) {
+        for line in data.lines().filter(|x| !x.starts_with('#')) {
+            let mut fields = line.split(',');
+            let (Some(id), Some(epss), Some(percentile)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let (Ok(epss), Ok(percentile)) = (epss.trim().parse(), percentile.trim().parse())
+            else {
+                // e.g. the header
+                continue;
+            };
+            if let Some(cve) = self.cves.get_mut(id.trim()) {
+                cve.epss = Some(epss);
+                cve.epss_percentile = Some(percentile);
+            }
+        }
+    }
+
+    /// Returns the number of known CVEs
+    pub fn len(&self) -> usize {
+        self.cves.len()
+    }
+
+    /// Returns true when no CVE is known
+    pub fn is_empty(&self) -> bool {
+        self.cves.is_empty()
+    }
+
+    /// Returns the information about the known CVEs of cves
+    pub fn lookup(&self, cves: &[String]) -> Vec<CveInfo> {
+        cves.iter()
+            .filter_map(|x| self.cves.get(x))
+            .cloned()
+            .collect()
+    }
+
+    /// Adds the information about the known CVEs to a serialized result.
+    ///
+    /// Returns the result unchanged when no CVE is known.
+    pub fn enrich(&self, result: Vec<u8>, cves: &[String]) -> Vec<u8> {
+        let known = self.lookup(cves);
+        if known.is_empty() {
+            return result;
+        }
+        let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&result) else {
+            return result;
+        };
+        if let Some(x) = value.as_object_mut() {
+            x.insert(
+                "enrichment".to_string(),
+                serde_json::json!({ "cves": known }),
+            );
+        }
+        serde_json::to_vec(&value).unwrap_or(result)
+    }
+}
+
+/// The enrichment stage of the daemon
+#[derive(Debug)]
+pub struct Enrichment {
+    pub config: config::Enrichment,
+    pub dataset: RwLock<CveDataset>,
+}
+
+impl Enrichment {
+    /// Creates an enrichment with an empty dataset, it is loaded by the sync job
+    pub fn new(config: config::Enrichment) -> Self {
+        Self {
+            config,
+            dataset: Default::default(),
+        }
+    }
+}
+
+/// Downloads the CVEs modified since the last sync from the NVD CVE API.
+///
+/// The first sync and syncs after more than the allowed range of the API download all CVEs
+/// and remove the pages of previous syncs.
+async fn sync_nvd(config: &config::Enrichment, path: &Path, url: &str) -> Result<(), Error> {
+    let now = chrono::Utc::now();
+    let last_sync = std::fs::read_to_string(path.join(LAST_NVD_SYNC))
+        .ok()
+        .and_then(|x| chrono::DateTime::parse_from_rfc3339(x.trim()).ok())
+        .map(|x| x.with_timezone(&chrono::Utc))
+        .filter(|x| (now - *x).num_days() < NVD_MAX_RANGE_DAYS);
+    let client = reqwest::Client::new();
+    let prefix = format!("{NVD_PREFIX}{}", now.format("%Y%m%d%H%M%S"));
+    let mut index = 0;
+    loop {
+        let mut query = vec![
+            ("startIndex", index.to_string()),
+            ("resultsPerPage", NVD_PAGE_SIZE.to_string()),
+        ];
+        if let Some(last) = last_sync {
+            let format = "%Y-%m-%dT%H:%M:%S%.3fZ";
+            query.push(("lastModStartDate", last.format(format).to_string()));
+            query.push(("lastModEndDate", now.format(format).to_string()));
+        }
+        let mut request = client.get(url).query(&query);
+        if let Some(key) = &config.nvd_api_key {
+            request = request.header("apiKey", key);
+        }
+        let body = request.send().await?.error_for_status()?.text().await?;
+        let response: NvdResponse = serde_json::from_str(&body)?;
+        std::fs::write(path.join(format!("{prefix}-{index:07}.json")), &body)?;
+        index += NVD_PAGE_SIZE;
+        if index >= response.total_results {
+            break;
+        }
+        // the NVD API allows 5 requests within 30 seconds without api key
+        let pause = if config.nvd_api_key.is_some() { 1 } else { 6 };
+        tokio::time::sleep(std::time::Duration::from_secs(pause)).await;
+    }
+    if last_sync.is_none() {
+        for entry in std::fs::read_dir(path)?.filter_map(|x| x.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(NVD_PREFIX) && !name.starts_with(&prefix) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+    }
+    std::fs::write(path.join(LAST_NVD_SYNC), now.to_rfc3339())?;
+    Ok(())
+}
+
+/// Downloads the current EPSS scores
+async fn sync_epss(path: &Path, url: &str) -> Result<(), Error> {
+    let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    let name = if url.ends_with(".gz") {
+        format!("{EPSS_FILE}.gz")
+    } else {
+        EPSS_FILE.to_string()
+    };
+    std::fs::write(path.join(name), &body)?;
+    Ok(())
+}
+
+/// Syncs the dataset directory with the configured sources.
+///
+/// Does nothing when neither a NVD nor an EPSS url is configured.
+pub async fn sync(config: &config::Enrichment) -> Result<(), Error> {
+    let Some(path) = &config.path else {
+        return Ok(());
+    };
+    if config.nvd_url.is_some() || config.epss_url.is_some() {
+        std::fs::create_dir_all(path)?;
+    }
+    if let Some(url) = &config.nvd_url {
+        sync_nvd(config, path, url).await?;
+    }
+    if let Some(url) = &config.epss_url {
+        sync_epss(path, url).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NVD: &str = r#"{
+  "resultsPerPage": 2,
+  "startIndex": 0,
+  "totalResults": 2,
+  "vulnerabilities": [
+    {
+      "cve": {
+        "id": "CVE-2021-44228",
+        "weaknesses": [
+          { "source": "nvd@nist.gov", "type": "Primary", "description": [{ "lang": "en", "value": "CWE-917" }] },
+          { "source": "security@apache.org", "type": "Secondary", "description": [{ "lang": "en", "value": "CWE-20" }, { "lang": "en", "value": "NVD-CWE-noinfo" }] }
+        ],
+        "metrics": {
+          "cvssMetricV31": [
+            { "source": "nvd@nist.gov", "type": "Primary", "cvssData": { "version": "3.1", "vectorString": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H", "baseScore": 10.0 } }
+          ],
+          "cvssMetricV2": [
+            { "source": "nvd@nist.gov", "type": "Primary", "cvssData": { "version": "2.0", "vectorString": "AV:N/AC:M/Au:N/C:C/I:C/A:C", "baseScore": 9.3 } }
+          ]
+        }
+      }
+    },
+    {
+      "cve": {
+        "id": "CVE-1999-0001",
+        "metrics": {
+          "cvssMetricV2": [
+            { "source": "nvd@nist.gov", "type": "Primary", "cvssData": { "version": "2.0", "vectorString": "AV:N/AC:L/Au:N/C:N/I:N/A:P", "baseScore": 5.0 } }
+          ]
+        }
+      }
+    }
+  ]
+}"#;
+
+    const EPSS: &str = "#model_version:v2023.03.01,score_date:2024-01-01T00:00:00+0000\n\
+                        cve,epss,percentile\n\
+                        CVE-2021-44228,0.97565,0.99996\n\
+                        CVE-2000-0001,0.1,0.2\n";
+
+    fn dataset() -> CveDataset {
+        let mut dataset = CveDataset::default();
+        dataset.add_nvd(NVD).unwrap();
+        dataset.add_epss(EPSS);
+        dataset
+    }
+
+    #[test]
+    fn nvd_and_epss() {
+        let dataset = dataset();
+        assert_eq!(dataset.len(), 2);
+        let cves = dataset.lookup(&[
+            "CVE-2021-44228".to_string(),
+            "CVE-1999-0001".to_string(),
+            "CVE-2000-0001".to_string(),
+        ]);
+        assert_eq!(
+            cves[0],
+            CveInfo {
+                id: "CVE-2021-44228".to_string(),
+                cvss_vector: Some("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H".to_string()),
+                cvss_base_score: Some(10.0),
+                cwes: vec!["CWE-20".to_string(), "CWE-917".to_string()],
+                epss: Some(0.97565),
+                epss_percentile: Some(0.99996),
+            }
+        );
+        assert_eq!(
+            cves[1].cvss_vector.as_deref(),
+            Some("AV:N/AC:L/Au:N/C:N/I:N/A:P")
+        );
+        assert_eq!(cves[1].epss, None);
+        assert_eq!(cves.len(), 2);
+    }
+
+    #[test]
+    fn updates_keep_scores() {
+        let mut dataset = dataset();
+        dataset
+            .add_nvd(r#"{"vulnerabilities": [{"cve": {"id": "CVE-2021-44228"}}]}"#)
+            .unwrap();
+        let cve = &dataset.lookup(&["CVE-2021-44228".to_string()])[0];
+        assert_eq!(cve.cvss_vector, None);
+        assert_eq!(cve.epss, Some(0.97565));
+    }
+
+    #[test]
+    fn enrich() {
+        let dataset = dataset();
+        let result = br#"{"id":0,"type":"alarm","oid":"1.2.3"}"#.to_vec();
+        let unknown = dataset.enrich(result.clone(), &["CVE-2000-0002".to_string()]);
+        assert_eq!(unknown, result);
+
+        let enriched = dataset.enrich(result, &["CVE-2021-44228".to_string()]);
+        let value: serde_json::Value = serde_json::from_slice(&enriched).unwrap();
+        assert_eq!(value["oid"], "1.2.3");
+        let cve = &value["enrichment"]["cves"][0];
+        assert_eq!(cve["id"], "CVE-2021-44228");
+        assert_eq!(cve["cvss_base_score"], 10.0);
+        assert_eq!(cve["cwes"][1], "CWE-917");
+        assert_eq!(cve["epss"], 0.97565);
+    }
+}
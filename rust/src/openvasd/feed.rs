@@ -3,13 +3,13 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
 };
 
 use scannerlib::storage::{ContextKey, StorageError};
 use scannerlib::{
-    feed::{self, HashSumNameLoader},
+    feed::{self, HashSumNameLoader, LayeredNameLoader},
     nasl::FSPluginLoader,
     storage::{item::NVTField, Dispatcher, Field},
 };
@@ -79,12 +79,14 @@ impl FeedIdentifier {
         Ok(oids)
     }
 
-    pub fn sumfile_hash<S>(path: S) -> Result<String, feed::UpdateError>
+    /// Returns the hash of the sums file, or of the sums files of each layer when overlays are
+    /// given.
+    pub fn sumfile_hash<S>(path: S, overlays: &[PathBuf]) -> Result<String, feed::UpdateError>
     where
         S: AsRef<Path> + Clone + std::fmt::Debug + Sync + Send,
     {
-        let loader = FSPluginLoader::new(path);
-        let verifier = HashSumNameLoader::sha256(&loader)?;
+        let layers = FSPluginLoader::new(path).with_overlays(overlays).layers();
+        let verifier = LayeredNameLoader::sha256(&layers)?;
         verifier.sumfile_hash().map_err(|e| feed::UpdateError {
             kind: feed::UpdateErrorKind::VerifyError(e),
             key: "feed_oid poisoned".to_string(),
@@ -94,10 +96,12 @@ impl FeedIdentifier {
 
 impl Dispatcher for FeedIdentifier {
     fn dispatch(&self, _: &ContextKey, scope: Field) -> Result<(), StorageError> {
-        if let Field::NVT(NVTField::Oid(x)) = scope {
-            let mut oids = self.oids.write()?;
-            oids.push(x);
-        }
+        let oid = match scope {
+            Field::NVT(NVTField::Oid(x)) => x,
+            Field::NVT(NVTField::Nvt(x)) => x.oid,
+            _ => return Ok(()),
+        };
+        self.oids.write()?.push(oid);
         Ok(())
     }
 
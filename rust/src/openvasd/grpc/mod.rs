@@ -0,0 +1,831 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! gRPC API for scan management, see openvasd.proto.
+//!
+//! It is served via HTTP/2 on its own address and uses the same scheduler and authentication as
+//! the HTTP API, therefore scans created via gRPC are also available via HTTP for the same client.
+//! Clients authenticated by a bearer token within the metadata are restricted to their role and
+//! the feed subset of their tenant.
+
+pub mod proto;
+#[cfg(test)]
+mod proto_tests;
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+};
+
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Bytes, Frame},
+    HeaderMap, Request,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use scannerlib::models::{
+    self,
+    scanner::{ScanDeleter, ScanPlanner, ScanResultFetcher, ScanStarter, ScanStopper},
+};
+use tokio::{net::TcpListener, sync::mpsc};
+
+use crate::{
+    controller::{admission::Refusal, Caller, ClientHash, ClientIdentifier, Context},
+    oidc::Role,
+    scheduling,
+    storage::{NVTStorer as _, ProgressGetter as _, ScanIDClientMapper as _, ScanStorer as _},
+};
+use proto::Message;
+
+/// Path prefix of the methods of the Scans service
+const SERVICE: &str = "/openvasd.v1.Scans/";
+
+/// Status of a gRPC call as defined by the gRPC status codes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub code: u8,
+    pub message: String,
+}
+
+impl Status {
+    pub const OK: u8 = 0;
+    pub const INVALID_ARGUMENT: u8 = 3;
+    pub const NOT_FOUND: u8 = 5;
+    pub const ALREADY_EXISTS: u8 = 6;
+    pub const PERMISSION_DENIED: u8 = 7;
+    pub const FAILED_PRECONDITION: u8 = 9;
+    pub const UNIMPLEMENTED: u8 = 12;
+    pub const INTERNAL: u8 = 13;
+    pub const UNAVAILABLE: u8 = 14;
+    pub const UNAUTHENTICATED: u8 = 16;
+
+    fn new(code: u8, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    fn internal(e: impl ToString) -> Self {
+        Self::new(Self::INTERNAL, e)
+    }
+
+    fn not_found(id: &str) -> Self {
+        Self::new(Self::NOT_FOUND, format!("scan {id} not found"))
+    }
+
+    fn trailers(&self) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", u16::from(self.code).into());
+        if let Ok(message) = urlencoding::encode(&self.message).parse() {
+            trailers.insert("grpc-message", message);
+        }
+        trailers
+    }
+}
+
+impl From<scheduling::Error> for Status {
+    fn from(e: scheduling::Error) -> Self {
+        use scheduling::Error::*;
+        match e {
+            NotFound => Self::new(Self::NOT_FOUND, e),
+            ScanRunning | ScanAlreadyQueued | AlreadyFinished | NotFinished => {
+                Self::new(Self::FAILED_PRECONDITION, e)
+            }
+            VtsNotAvailable(_) => Self::new(Self::INVALID_ARGUMENT, e),
+            QueueFull | InsufficientResources(_) => Self::new(Self::UNAVAILABLE, e),
+            UnsupportedResume => Self::new(Self::UNIMPLEMENTED, e),
+            e => Self::internal(e),
+        }
+    }
+}
+
+impl From<Refusal> for Status {
+    fn from(e: Refusal) -> Self {
+        match e {
+            Refusal::Feed(e @ scheduling::Error::VtsNotAvailable(_)) => e.into(),
+            Refusal::Feed(e) => Self::internal(e),
+            e => Self::new(Self::INVALID_ARGUMENT, e),
+        }
+    }
+}
+
+impl From<crate::storage::Error> for Status {
+    fn from(e: crate::storage::Error) -> Self {
+        match e {
+            crate::storage::Error::NotFound => Self::new(Self::NOT_FOUND, e),
+            e => Self::internal(e),
+        }
+    }
+}
+
+/// Frames a message as a length-prefixed message without compression.
+fn frame<M: Message>(message: &M) -> Frame<Bytes> {
+    let message = message.encode_to_vec();
+    let mut framed = Vec::with_capacity(message.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(&message);
+    Frame::data(framed.into())
+}
+
+/// Returns the message of a length-prefixed request.
+fn unframe(body: &[u8]) -> Result<&[u8], Status> {
+    match body {
+        [0, a, b, c, d, message @ ..] => {
+            let len = u32::from_be_bytes([*a, *b, *c, *d]) as usize;
+            message
+                .get(..len)
+                .ok_or_else(|| Status::new(Status::INVALID_ARGUMENT, "truncated message"))
+        }
+        [_, ..] => Err(Status::new(
+            Status::UNIMPLEMENTED,
+            "compressed messages are not supported",
+        )),
+        [] => Err(Status::new(Status::INVALID_ARGUMENT, "missing message")),
+    }
+}
+
+fn decode<M: Message>(body: &[u8]) -> Result<M, Status> {
+    M::decode(unframe(body)?).map_err(|e| Status::new(Status::INVALID_ARGUMENT, e))
+}
+
+/// Body of a gRPC response, the frames are sent through the channel until it is closed.
+pub struct Body(mpsc::Receiver<Frame<Bytes>>);
+
+impl http_body::Body for Body {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        self.get_mut().0.poll_recv(cx).map(|x| x.map(Ok))
+    }
+}
+
+fn response(body: mpsc::Receiver<Frame<Bytes>>) -> hyper::Response<Body> {
+    hyper::Response::builder()
+        .header("content-type", "application/grpc+proto")
+        .body(Body(body))
+        .unwrap()
+}
+
+/// Returns a response containing the message or the failure of an unary call.
+fn unary<M: Message>(result: Result<M, Status>) -> hyper::Response<Body> {
+    let (tx, rx) = mpsc::channel(2);
+    let status = match result {
+        Ok(message) => {
+            let _ = tx.try_send(frame(&message));
+            Status::new(Status::OK, "")
+        }
+        Err(status) => {
+            tracing::debug!(code = status.code, status.message, "gRPC call failed");
+            status
+        }
+    };
+    let _ = tx.try_send(Frame::trailers(status.trailers()));
+    response(rx)
+}
+
+/// Serves the gRPC methods for a connection.
+pub struct Service<S, DB> {
+    ctx: Arc<Context<S, DB>>,
+    cid: Arc<ClientIdentifier>,
+}
+
+impl<S, DB> Clone for Service<S, DB> {
+    fn clone(&self) -> Self {
+        Self {
+            ctx: self.ctx.clone(),
+            cid: self.cid.clone(),
+        }
+    }
+}
+
+impl<S, DB> Service<S, DB>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+{
+    pub fn new(ctx: Arc<Context<S, DB>>, cid: Arc<ClientIdentifier>) -> Self {
+        Self { ctx, cid }
+    }
+
+    async fn check_client(&self, caller: &Caller, id: &str) -> Result<(), Status> {
+        // admins may access the scans of every client
+        if caller.role == Some(Role::Admin) {
+            return Ok(());
+        }
+        let cid = caller.cid.clone().unwrap_or_default();
+        match self
+            .ctx
+            .scheduler
+            .is_client_allowed(id.to_string(), &cid)
+            .await
+        {
+            Ok(true) => Ok(()),
+            // like the HTTP API unknown and foreign scans are not distinguished
+            Ok(false) | Err(crate::storage::Error::NotFound) => Err(Status::not_found(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Handles a call and returns its response.
+    pub async fn call(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> hyper::Response<Body> {
+        let Some(method) = path.strip_prefix(SERVICE) else {
+            return unary::<proto::Empty>(Err(Status::new(
+                Status::UNIMPLEMENTED,
+                format!("unknown service of {path}"),
+            )));
+        };
+        let caller = Caller::identify(&self.ctx, &self.cid, headers).await;
+        let Some(cid) = caller.cid.clone() else {
+            return unary::<proto::Empty>(Err(Status::new(
+                Status::UNAUTHENTICATED,
+                "unknown client",
+            )));
+        };
+        // calls that only read are treated like GET requests of the HTTP API
+        let reads = matches!(
+            method,
+            "ListScans" | "GetStatus" | "GetFeedStatus" | "StreamResults"
+        );
+        let as_http = if reads {
+            hyper::Method::GET
+        } else {
+            hyper::Method::POST
+        };
+        if caller.role.is_some_and(|x| !x.allows(&as_http)) {
+            return unary::<proto::Empty>(Err(Status::new(
+                Status::PERMISSION_DENIED,
+                format!("{method} is not allowed for role {:?}", caller.role),
+            )));
+        }
+        tracing::debug!(method, "gRPC call");
+        match method {
+            "CreateScan" => unary(self.create_scan(&caller, &cid, body).await),
+            "StartScan" => unary(self.start_scan(&caller, body).await),
+            "StopScan" => unary(self.stop_scan(&caller, body).await),
+            "DeleteScan" => unary(self.delete_scan(&caller, body).await),
+            "ListScans" => unary(self.list_scans(&cid).await),
+            "GetStatus" => unary(self.get_status(&caller, body).await),
+            "GetFeedStatus" => unary(self.feed_status().await),
+            "StreamResults" => self.stream_results(&caller, body).await,
+            _ => unary::<proto::Empty>(Err(Status::new(
+                Status::UNIMPLEMENTED,
+                format!("unknown method {method}"),
+            ))),
+        }
+    }
+
+    async fn create_scan(
+        &self,
+        caller: &Caller,
+        cid: &ClientHash,
+        body: &[u8],
+    ) -> Result<proto::ScanReference, Status> {
+        let request: proto::CreateScanRequest = decode(body)?;
+        let mut scan: models::Scan = serde_json::from_slice(&request.scan)
+            .map_err(|e| Status::new(Status::INVALID_ARGUMENT, e))?;
+        let subset = caller.subset(&self.ctx.tenancy);
+        self.ctx.admit(&scan, subset.as_ref()).await?;
+        if scan.scan_id.is_empty() {
+            scan.scan_id = uuid::Uuid::new_v4().to_string();
+        } else if self.ctx.scheduler.get_status(&scan.scan_id).await.is_ok() {
+            return Err(Status::new(
+                Status::ALREADY_EXISTS,
+                format!("scan {} already exists", scan.scan_id),
+            ));
+        }
+        let id = scan.scan_id.clone();
+        self.ctx.scheduler.insert_scan(scan).await?;
+        self.ctx
+            .scheduler
+            .add_scan_client_id(id.clone(), cid.clone())
+            .await?;
+        tracing::debug!(%id, "Scan created via gRPC");
+        Ok(proto::ScanReference { id })
+    }
+
+    async fn start_scan(&self, caller: &Caller, body: &[u8]) -> Result<proto::Empty, Status> {
+        let request: proto::ScanReference = decode(body)?;
+        self.check_client(caller, &request.id).await?;
+        let subset = caller.subset(&self.ctx.tenancy);
+        self.ctx
+            .scheduler
+            .start_scan_within(&request.id, subset.as_ref())
+            .await?;
+        Ok(proto::Empty)
+    }
+
+    async fn stop_scan(&self, caller: &Caller, body: &[u8]) -> Result<proto::Empty, Status> {
+        let request: proto::ScanReference = decode(body)?;
+        self.check_client(caller, &request.id).await?;
+        self.ctx
+            .scheduler
+            .stop_scan(request.id)
+            .await
+            .map_err(Status::internal)?;
+        Ok(proto::Empty)
+    }
+
+    async fn delete_scan(&self, caller: &Caller, body: &[u8]) -> Result<proto::Empty, Status> {
+        let request: proto::ScanReference = decode(body)?;
+        self.check_client(caller, &request.id).await?;
+        self.ctx.scheduler.delete_scan_by_id(&request.id).await?;
+        Ok(proto::Empty)
+    }
+
+    async fn list_scans(&self, cid: &ClientHash) -> Result<proto::ScanList, Status> {
+        let ids = self.ctx.scheduler.get_scans_of_client_id(cid).await?;
+        Ok(proto::ScanList { ids })
+    }
+
+    async fn get_status(&self, caller: &Caller, body: &[u8]) -> Result<proto::ScanStatus, Status> {
+        let request: proto::ScanReference = decode(body)?;
+        self.check_client(caller, &request.id).await?;
+        let status = self.ctx.scheduler.get_status(&request.id).await?;
+        Ok((&status).into())
+    }
+
+    async fn feed_status(&self) -> Result<proto::FeedStatus, Status> {
+        Ok(proto::FeedStatus {
+            version: self.ctx.scheduler.current_feed_version().await?,
+            vts: self.ctx.scheduler.oids().await?.count() as u64,
+        })
+    }
+
+    async fn stream_results(&self, caller: &Caller, body: &[u8]) -> hyper::Response<Body> {
+        let request: proto::StreamResultsRequest = match decode(body) {
+            Ok(x) => x,
+            Err(e) => return unary::<proto::Empty>(Err(e)),
+        };
+        if let Err(e) = self.check_client(caller, &request.id).await {
+            return unary::<proto::Empty>(Err(e));
+        }
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(stream(self.ctx.clone(), request, tx));
+        response(rx)
+    }
+}
+
+/// Sends the results of a scan and, when following, waits for new results until the scan is done.
+async fn stream<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    request: proto::StreamResultsRequest,
+    frames: mpsc::Sender<Frame<Bytes>>,
+) where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+{
+    let id = request.id;
+    let mut next = request.from as usize;
+    let mut appended = ctx.scheduler.subscribe_results();
+    let status = loop {
+        appended.borrow_and_update();
+        let (status, results) = match ctx
+            .scheduler
+            .get_status_and_results(&id, Some(next), None)
+            .await
+        {
+            Ok(x) => x,
+            Err(e) => break Status::from(e),
+        };
+        for result in results {
+            let result: models::Result = match serde_json::from_slice(&result) {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::warn!(id, %e, "skipping invalid result");
+                    next += 1;
+                    continue;
+                }
+            };
+            if frames
+                .send(frame(&proto::ScanResult::from(&result)))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            next += 1;
+        }
+        if !request.follow || status.is_done() {
+            break Status::new(Status::OK, "");
+        }
+        tokio::select! {
+            changed = appended.changed() => {
+                if changed.is_err() {
+                    break Status::new(Status::UNAVAILABLE, "openvasd is shutting down");
+                }
+            }
+            _ = frames.closed() => return,
+        }
+    };
+    let _ = frames.send(Frame::trailers(status.trailers())).await;
+}
+
+impl<S, DB, R> hyper::service::Service<Request<R>> for Service<S, DB>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+    R: hyper::body::Body + Send + 'static,
+    <R as hyper::body::Body>::Error: std::error::Error,
+    <R as hyper::body::Body>::Data: Send,
+{
+    type Response = hyper::Response<Body>;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn futures_util::Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+    fn call(&self, req: Request<R>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let is_grpc = req
+                .headers()
+                .get("content-type")
+                .and_then(|x| x.to_str().ok())
+                .is_some_and(|x| x.starts_with("application/grpc"));
+            if req.method() != hyper::Method::POST || !is_grpc {
+                return Ok(hyper::Response::builder()
+                    .status(hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    .body(Body(mpsc::channel(1).1))
+                    .unwrap());
+            }
+            let (parts, body) = req.into_parts();
+            let body = match body.collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => {
+                    return Ok(unary::<proto::Empty>(Err(Status::new(
+                        Status::INVALID_ARGUMENT,
+                        e,
+                    ))))
+                }
+            };
+            Ok(service.call(parts.uri.path(), &parts.headers, &body).await)
+        })
+    }
+}
+
+/// Serves the gRPC API on the given address via HTTP/2, with TLS when it is configured.
+pub async fn listen<S, DB>(
+    ctx: Arc<Context<S, DB>>,
+    address: SocketAddr,
+    config: &crate::config::Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + std::marker::Send
+        + std::marker::Sync
+        + 'static,
+    DB: crate::storage::Storage + std::marker::Send + 'static + std::marker::Sync,
+{
+    use hyper::server::conn::http2::Builder;
+    let incoming = TcpListener::bind(&address).await?;
+    let tls_config = crate::tls::tls_config(config)?;
+    tracing::info!(tls = tls_config.is_some(), "serving gRPC on {}", address);
+    let tls = tls_config.map(|x| {
+        (
+            tokio_rustls::TlsAcceptor::from(Arc::new(x.config)),
+            x.client_identifier,
+        )
+    });
+    loop {
+        let (tcp_stream, _remote_addr) = incoming.accept().await?;
+        let ctx = ctx.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let result = match tls {
+                Some((acceptor, identifier)) => {
+                    let tls_stream = match acceptor.accept(tcp_stream).await {
+                        Ok(tls_stream) => tls_stream,
+                        Err(err) => {
+                            tracing::debug!("failed to perform tls handshake: {err:#}");
+                            return;
+                        }
+                    };
+                    let cid = crate::controller::retrieve_and_reset(identifier);
+                    Builder::new(TokioExecutor::new())
+                        .serve_connection(
+                            TokioIo::new(tls_stream),
+                            Service::new(ctx, Arc::new(cid)),
+                        )
+                        .await
+                }
+                None => {
+                    Builder::new(TokioExecutor::new())
+                        .serve_connection(
+                            TokioIo::new(tcp_stream),
+                            Service::new(ctx, Arc::new(ClientIdentifier::Disabled)),
+                        )
+                        .await
+                }
+            };
+            if let Err(err) = result {
+                tracing::debug!("failed to serve gRPC connection: {err:#}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::NoOpScanner;
+
+    fn service(
+    ) -> Service<NoOpScanner, crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>> {
+        let ctx = crate::controller::ContextBuilder::new()
+            .scanner(NoOpScanner)
+            .build();
+        Service::new(
+            Arc::new(ctx),
+            Arc::new(ClientIdentifier::Known("42".into())),
+        )
+    }
+
+    fn request<M: Message>(message: &M) -> Vec<u8> {
+        match frame(message).into_data() {
+            Ok(data) => data.to_vec(),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the messages and the grpc-status of a response.
+    async fn read<M: Message>(response: hyper::Response<Body>) -> (Vec<M>, u8) {
+        let mut body = response.into_body();
+        let mut messages = vec![];
+        let mut status = None;
+        while let Some(Ok(frame)) = body.frame().await {
+            match frame.into_data() {
+                Ok(data) => messages.push(decode(&data).unwrap()),
+                Err(frame) => {
+                    let trailers = frame.into_trailers().unwrap();
+                    status = trailers
+                        .get("grpc-status")
+                        .and_then(|x| x.to_str().ok()?.parse().ok());
+                }
+            }
+        }
+        (messages, status.expect("grpc-status trailer"))
+    }
+
+    async fn call<M: Message>(
+        service: &Service<
+            NoOpScanner,
+            crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
+        >,
+        method: &str,
+        body: &[u8],
+    ) -> (Vec<M>, u8) {
+        call_with(service, method, &HeaderMap::new(), body).await
+    }
+
+    async fn call_with<M: Message>(
+        service: &Service<
+            NoOpScanner,
+            crate::storage::inmemory::Storage<crate::crypt::ChaCha20Crypt>,
+        >,
+        method: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> (Vec<M>, u8) {
+        read(
+            service
+                .call(&format!("{SERVICE}{method}"), headers, body)
+                .await,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn scan_lifecycle() {
+        let service = service();
+        let scan = models::Scan {
+            scan_id: "42".into(),
+            target: models::Target {
+                hosts: vec!["127.0.0.1".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let create = request(&proto::CreateScanRequest {
+            scan: serde_json::to_vec(&scan).unwrap(),
+        });
+        let (created, status) = call::<proto::ScanReference>(&service, "CreateScan", &create).await;
+        assert_eq!(status, Status::OK);
+        assert_eq!(created[0].id, "42");
+        let (_, status) = call::<proto::Empty>(&service, "CreateScan", &create).await;
+        assert_eq!(status, Status::ALREADY_EXISTS);
+
+        let (list, _) =
+            call::<proto::ScanList>(&service, "ListScans", &request(&proto::Empty)).await;
+        assert_eq!(list[0].ids, vec!["42"]);
+        let reference = request(&proto::ScanReference { id: "42".into() });
+        let (status, code) = call::<proto::ScanStatus>(&service, "GetStatus", &reference).await;
+        assert_eq!(code, Status::OK);
+        assert_eq!(status[0].phase, 0);
+
+        let results = vec![models::Result {
+            id: 0,
+            r_type: models::ResultType::Alarm,
+            ip_address: Some("127.0.0.1".into()),
+            oid: Some("1.2.3".into()),
+            ..Default::default()
+        }];
+        use crate::storage::AppendFetchResult as _;
+        service
+            .ctx
+            .scheduler
+            .append_fetched_result(vec![models::scanner::ScanResults {
+                id: "42".into(),
+                status: models::Status {
+                    status: models::Phase::Succeeded,
+                    ..Default::default()
+                },
+                results,
+            }])
+            .await
+            .unwrap();
+        let stream = request(&proto::StreamResultsRequest {
+            id: "42".into(),
+            from: 0,
+            follow: true,
+        });
+        let (results, code) = call::<proto::ScanResult>(&service, "StreamResults", &stream).await;
+        assert_eq!(code, Status::OK);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].oid, "1.2.3");
+
+        let other = Service::new(
+            service.ctx.clone(),
+            Arc::new(ClientIdentifier::Known("other".into())),
+        );
+        let (_, code) = call::<proto::Empty>(&other, "DeleteScan", &reference).await;
+        assert_eq!(code, Status::NOT_FOUND);
+        let (_, code) = call::<proto::Empty>(&service, "DeleteScan", &reference).await;
+        assert_eq!(code, Status::OK);
+        let (_, code) = call::<proto::Empty>(&service, "GetStatus", &reference).await;
+        assert_eq!(code, Status::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens() {
+        use crate::oidc::{tests as tokens, Oidc};
+
+        let signer = tokens::Signer::new();
+        let tenancy = crate::config::Tenancy {
+            enabled: true,
+            default_tenant: None,
+            tenants: [(
+                "basic".to_string(),
+                crate::config::Tenant {
+                    families: vec!["Product detection".to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        };
+        let ctx = crate::controller::ContextBuilder::new()
+            .oidc(Oidc::new(tokens::config()).with_keys(signer.jwks("k1")))
+            .tenancy(tenancy)
+            .scanner(NoOpScanner)
+            .build();
+        let service = Service::new(Arc::new(ctx), Arc::new(ClientIdentifier::Disabled));
+        let bearer = |subject: &str, role: &str| {
+            let mut claims = tokens::claims(subject, &[role]);
+            claims["tenant"] = "basic".into();
+            let token = signer.sign("k1", claims);
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                hyper::header::AUTHORIZATION,
+                format!("Bearer {token}").parse().unwrap(),
+            );
+            headers
+        };
+        let list = request(&proto::Empty);
+        let (_, code) = call::<proto::Empty>(&service, "ListScans", &list).await;
+        assert_eq!(code, Status::UNAUTHENTICATED);
+
+        let viewer = bearer("bob", "auditor");
+        let (_, code) = call_with::<proto::ScanList>(&service, "ListScans", &viewer, &list).await;
+        assert_eq!(code, Status::OK);
+        let mut scan = models::Scan {
+            scan_id: "42".into(),
+            ..Default::default()
+        };
+        let create = |scan: &models::Scan| {
+            request(&proto::CreateScanRequest {
+                scan: serde_json::to_vec(scan).unwrap(),
+            })
+        };
+        let (_, code) =
+            call_with::<proto::Empty>(&service, "CreateScan", &viewer, &create(&scan)).await;
+        assert_eq!(code, Status::PERMISSION_DENIED);
+
+        // VTs outside of the feed subset of the tenant are refused
+        let operator = bearer("alice", "scan-user");
+        scan.vts.push(models::VT {
+            oid: "1.2.3".into(),
+            parameters: vec![],
+        });
+        let (_, code) =
+            call_with::<proto::Empty>(&service, "CreateScan", &operator, &create(&scan)).await;
+        assert_eq!(code, Status::INVALID_ARGUMENT);
+        scan.vts.clear();
+        let (_, code) =
+            call_with::<proto::ScanReference>(&service, "CreateScan", &operator, &create(&scan))
+                .await;
+        assert_eq!(code, Status::OK);
+
+        let reference = request(&proto::ScanReference { id: "42".into() });
+        let (_, code) =
+            call_with::<proto::ScanStatus>(&service, "GetStatus", &viewer, &reference).await;
+        assert_eq!(code, Status::NOT_FOUND);
+        let admin = bearer("carol", "scan-admin");
+        let (_, code) =
+            call_with::<proto::ScanStatus>(&service, "GetStatus", &admin, &reference).await;
+        assert_eq!(code, Status::OK);
+    }
+
+    #[tokio::test]
+    async fn served_via_http2() {
+        let service = service();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(
+            hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(server), service),
+        );
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(client))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        let request = Request::post(format!("http://localhost{SERVICE}GetFeedStatus"))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(http_body_util::Full::new(Bytes::from(request(
+                &proto::Empty,
+            ))))
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "application/grpc+proto");
+        let body = response.into_body().collect().await.unwrap();
+        assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+        let status: proto::FeedStatus = decode(&body.to_bytes()).unwrap();
+        assert_eq!(status.vts, 0);
+    }
+
+    #[tokio::test]
+    async fn failures() {
+        let service = service();
+        let (_, code) = call::<proto::Empty>(&service, "Unknown", &request(&proto::Empty)).await;
+        assert_eq!(code, Status::UNIMPLEMENTED);
+        let (_, code) = call::<proto::Empty>(&service, "StartScan", &[1, 0, 0, 0, 0]).await;
+        assert_eq!(code, Status::UNIMPLEMENTED);
+        let invalid = request(&proto::CreateScanRequest {
+            scan: b"{".to_vec(),
+        });
+        let (_, code) = call::<proto::Empty>(&service, "CreateScan", &invalid).await;
+        assert_eq!(code, Status::INVALID_ARGUMENT);
+        let anonymous = Service::new(service.ctx.clone(), Arc::new(ClientIdentifier::Unknown));
+        let (_, code) =
+            call::<proto::Empty>(&anonymous, "ListScans", &request(&proto::Empty)).await;
+        assert_eq!(code, Status::UNAUTHENTICATED);
+    }
+}
@@ -0,0 +1,529 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Protocol buffer encoding of the messages defined in openvasd.proto.
+//!
+//! Only the wire format needed by the messages is implemented: varints and length delimited
+//! fields. Unknown fields are skipped as required by proto3.
+
+use scannerlib::models;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DecodeError(pub &'static str);
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid protobuf message: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Value of a field within the wire format
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl Value<'_> {
+    fn varint(&self) -> Result<u64, DecodeError> {
+        match self {
+            Value::Varint(x) => Ok(*x),
+            _ => Err(DecodeError("expected a varint")),
+        }
+    }
+
+    fn bytes(&self) -> Result<&[u8], DecodeError> {
+        match self {
+            Value::Bytes(x) => Ok(x),
+            _ => Err(DecodeError("expected a length delimited field")),
+        }
+    }
+
+    fn string(&self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| DecodeError("string is not UTF-8"))
+    }
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut result = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().ok_or(DecodeError("truncated varint"))?;
+        *buf = rest;
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(DecodeError("varint too long"))
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if buf.len() < len {
+        return Err(DecodeError("truncated field"));
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    put_varint(buf, (u64::from(field) << 3) | u64::from(wire_type));
+}
+
+/// Writes a varint field, default values are omitted as in proto3.
+fn put_uint(buf: &mut Vec<u8>, field: u32, value: u64) {
+    if value != 0 {
+        put_key(buf, field, 0);
+        put_varint(buf, value);
+    }
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    if !value.is_empty() {
+        put_key(buf, field, 2);
+        put_varint(buf, value.len() as u64);
+        buf.extend_from_slice(value);
+    }
+}
+
+fn put_message<M: Message>(buf: &mut Vec<u8>, field: u32, value: &M) {
+    let value = value.encode_to_vec();
+    put_key(buf, field, 2);
+    put_varint(buf, value.len() as u64);
+    buf.extend_from_slice(&value);
+}
+
+pub trait Message: Default {
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Sets a field, unknown fields are ignored.
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError>;
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    fn decode(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut message = Self::default();
+        while !buf.is_empty() {
+            let key = read_varint(&mut buf)?;
+            let field = u32::try_from(key >> 3).map_err(|_| DecodeError("invalid field"))?;
+            let value = match key & 7 {
+                0 => Value::Varint(read_varint(&mut buf)?),
+                1 => Value::Fixed64(u64::from_le_bytes(
+                    take(&mut buf, 8)?.try_into().unwrap_or_default(),
+                )),
+                2 => {
+                    let len = read_varint(&mut buf)? as usize;
+                    Value::Bytes(take(&mut buf, len)?)
+                }
+                5 => Value::Fixed32(u32::from_le_bytes(
+                    take(&mut buf, 4)?.try_into().unwrap_or_default(),
+                )),
+                _ => return Err(DecodeError("unsupported wire type")),
+            };
+            message.merge(field, value)?;
+        }
+        Ok(message)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Empty;
+
+impl Message for Empty {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn merge(&mut self, _: u32, _: Value<'_>) -> Result<(), DecodeError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CreateScanRequest {
+    /// JSON document of the scan
+    pub scan: Vec<u8>,
+}
+
+impl Message for CreateScanRequest {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_bytes(buf, 1, &self.scan);
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        if field == 1 {
+            self.scan = value.bytes()?.to_vec();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScanReference {
+    pub id: String,
+}
+
+impl Message for ScanReference {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_bytes(buf, 1, self.id.as_bytes());
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        if field == 1 {
+            self.id = value.string()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScanList {
+    pub ids: Vec<String>,
+}
+
+impl Message for ScanList {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for id in &self.ids {
+            // repeated strings keep empty entries
+            put_key(buf, 1, 2);
+            put_varint(buf, id.len() as u64);
+            buf.extend_from_slice(id.as_bytes());
+        }
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        if field == 1 {
+            self.ids.push(value.string()?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    pub all: u64,
+    pub excluded: u64,
+    pub dead: u64,
+    pub alive: u64,
+    pub queued: u64,
+    pub finished: u64,
+}
+
+impl Message for HostInfo {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_uint(buf, 1, self.all);
+        put_uint(buf, 2, self.excluded);
+        put_uint(buf, 3, self.dead);
+        put_uint(buf, 4, self.alive);
+        put_uint(buf, 5, self.queued);
+        put_uint(buf, 6, self.finished);
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.all = value.varint()?,
+            2 => self.excluded = value.varint()?,
+            3 => self.dead = value.varint()?,
+            4 => self.alive = value.varint()?,
+            5 => self.queued = value.varint()?,
+            6 => self.finished = value.varint()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScanStatus {
+    /// Phase as numbered in openvasd.proto
+    pub phase: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub hosts: Option<HostInfo>,
+}
+
+impl Message for ScanStatus {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_uint(buf, 1, self.phase);
+        put_uint(buf, 2, self.start_time);
+        put_uint(buf, 3, self.end_time);
+        if let Some(hosts) = &self.hosts {
+            put_message(buf, 4, hosts);
+        }
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.phase = value.varint()?,
+            2 => self.start_time = value.varint()?,
+            3 => self.end_time = value.varint()?,
+            4 => self.hosts = Some(HostInfo::decode(value.bytes()?)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl From<&models::Status> for ScanStatus {
+    fn from(status: &models::Status) -> Self {
+        use models::Phase::*;
+        let phase = match status.status {
+            Stored => 0,
+            Requested => 1,
+            Running => 2,
+            Stopped => 3,
+            Failed => 4,
+            Succeeded => 5,
+            Paused => 6,
+            Partial => 7,
+        };
+        ScanStatus {
+            phase,
+            start_time: status.start_time.unwrap_or_default(),
+            end_time: status.end_time.unwrap_or_default(),
+            hosts: status.host_info.as_ref().map(|x| HostInfo {
+                all: x.all(),
+                excluded: x.excluded(),
+                dead: x.dead(),
+                alive: x.alive(),
+                queued: x.queued(),
+                finished: x.finished(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamResultsRequest {
+    pub id: String,
+    pub from: u64,
+    pub follow: bool,
+}
+
+impl Message for StreamResultsRequest {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_bytes(buf, 1, self.id.as_bytes());
+        put_uint(buf, 2, self.from);
+        put_uint(buf, 3, self.follow.into());
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.id = value.string()?,
+            2 => self.from = value.varint()?,
+            3 => self.follow = value.varint()? != 0,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Detail {
+    pub name: String,
+    pub value: String,
+    pub source_type: String,
+    pub source_name: String,
+    pub source_description: String,
+}
+
+impl Message for Detail {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_bytes(buf, 1, self.name.as_bytes());
+        put_bytes(buf, 2, self.value.as_bytes());
+        put_bytes(buf, 3, self.source_type.as_bytes());
+        put_bytes(buf, 4, self.source_name.as_bytes());
+        put_bytes(buf, 5, self.source_description.as_bytes());
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.name = value.string()?,
+            2 => self.value = value.string()?,
+            3 => self.source_type = value.string()?,
+            4 => self.source_name = value.string()?,
+            5 => self.source_description = value.string()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Result message, named to not shadow [std::result::Result]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScanResult {
+    pub id: u64,
+    /// Result type as numbered in openvasd.proto
+    pub r_type: u64,
+    pub ip_address: String,
+    pub hostname: String,
+    pub oid: String,
+    pub port: u64,
+    pub protocol: String,
+    pub message: String,
+    pub qod: u64,
+    pub detail: Option<Detail>,
+}
+
+impl Message for ScanResult {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_uint(buf, 1, self.id);
+        put_uint(buf, 2, self.r_type);
+        put_bytes(buf, 3, self.ip_address.as_bytes());
+        put_bytes(buf, 4, self.hostname.as_bytes());
+        put_bytes(buf, 5, self.oid.as_bytes());
+        put_uint(buf, 6, self.port);
+        put_bytes(buf, 7, self.protocol.as_bytes());
+        put_bytes(buf, 8, self.message.as_bytes());
+        put_uint(buf, 9, self.qod);
+        if let Some(detail) = &self.detail {
+            put_message(buf, 10, detail);
+        }
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.id = value.varint()?,
+            2 => self.r_type = value.varint()?,
+            3 => self.ip_address = value.string()?,
+            4 => self.hostname = value.string()?,
+            5 => self.oid = value.string()?,
+            6 => self.port = value.varint()?,
+            7 => self.protocol = value.string()?,
+            8 => self.message = value.string()?,
+            9 => self.qod = value.varint()?,
+            10 => self.detail = Some(Detail::decode(value.bytes()?)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl From<&models::Result> for ScanResult {
+    fn from(result: &models::Result) -> Self {
+        use models::ResultType::*;
+        let r_type = match result.r_type {
+            Alarm => 0,
+            Log => 1,
+            Error => 2,
+            HostStart => 3,
+            HostEnd => 4,
+            DeadHost => 5,
+            HostDetail => 6,
+        };
+        ScanResult {
+            id: result.id as u64,
+            r_type,
+            ip_address: result.ip_address.clone().unwrap_or_default(),
+            hostname: result.hostname.clone().unwrap_or_default(),
+            oid: result.oid.clone().unwrap_or_default(),
+            port: result.port.map(|x| x as u64).unwrap_or_default(),
+            protocol: result
+                .protocol
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            message: result.message.clone().unwrap_or_default(),
+            qod: result.qod.map(u64::from).unwrap_or_default(),
+            detail: result.detail.as_ref().map(|x| Detail {
+                name: x.name.clone(),
+                value: x.value.clone(),
+                source_type: x.source.s_type.clone(),
+                source_name: x.source.name.clone(),
+                source_description: x.source.description.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FeedStatus {
+    pub version: String,
+    pub vts: u64,
+}
+
+impl Message for FeedStatus {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        put_bytes(buf, 1, self.version.as_bytes());
+        put_uint(buf, 2, self.vts);
+    }
+
+    fn merge(&mut self, field: u32, value: Value<'_>) -> Result<(), DecodeError> {
+        match field {
+            1 => self.version = value.string()?,
+            2 => self.vts = value.varint()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_format() {
+        // example of the protobuf encoding documentation: field 1 = 150
+        let mut buf = Vec::new();
+        put_uint(&mut buf, 1, 150);
+        assert_eq!(buf, [0x08, 0x96, 0x01]);
+        let reference = ScanReference {
+            id: "testing".into(),
+        };
+        assert_eq!(
+            reference.encode_to_vec(),
+            [0x0a, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g']
+        );
+        assert!(ScanReference::decode(&[0x0a, 0x07, b't']).is_err());
+        // unknown fields are skipped
+        let mut buf = reference.encode_to_vec();
+        put_uint(&mut buf, 15, 1);
+        assert_eq!(ScanReference::decode(&buf).unwrap(), reference);
+    }
+
+    #[test]
+    fn round_trip() {
+        let result = ScanResult {
+            id: 300,
+            r_type: 6,
+            ip_address: "127.0.0.1".into(),
+            port: 22,
+            protocol: "tcp".into(),
+            detail: Some(Detail {
+                name: "OS".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(ScanResult::decode(&result.encode_to_vec()).unwrap(), result);
+        let status = ScanStatus {
+            phase: 2,
+            start_time: 1_700_000_000,
+            hosts: Some(HostInfo {
+                all: 3,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(ScanStatus::decode(&status.encode_to_vec()).unwrap(), status);
+        let list = ScanList {
+            ids: vec!["a".into(), "".into()],
+        };
+        assert_eq!(ScanList::decode(&list.encode_to_vec()).unwrap(), list);
+    }
+}
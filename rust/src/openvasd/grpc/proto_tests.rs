@@ -0,0 +1,501 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Checks proto.rs and the served methods against the checked-in openvasd.proto.
+//!
+//! The schema is parsed from openvasd.proto and drives a generic codec that is independent of
+//! proto.rs, so that a field number, wire type or method diverging from the definition is found.
+
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::controller::NoOpScanner;
+
+const PROTO: &str = include_str!("openvasd.proto");
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    number: u32,
+    kind: String,
+    repeated: bool,
+}
+
+#[derive(Debug)]
+struct Rpc {
+    name: String,
+    input: String,
+    output: String,
+    stream: bool,
+}
+
+#[derive(Debug, Default)]
+struct Schema {
+    messages: BTreeMap<String, Vec<Field>>,
+    enums: BTreeMap<String, BTreeMap<String, u64>>,
+    rpcs: Vec<Rpc>,
+}
+
+impl Schema {
+    fn parse(proto: &str) -> Self {
+        let code: String = proto
+            .lines()
+            .map(|x| x.split("//").next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let code = ["{", "}", ";", "=", "(", ")"]
+            .iter()
+            .fold(code, |code, x| code.replace(x, &format!(" {x} ")));
+        let mut tokens = code.split_whitespace();
+        let mut next = || tokens.next().unwrap_or_default().to_string();
+        let mut schema = Self::default();
+        loop {
+            match next().as_str() {
+                "" => return schema,
+                "syntax" | "package" => while next() != ";" {},
+                "message" => {
+                    let name = next();
+                    assert_eq!(next(), "{");
+                    let mut fields = vec![];
+                    loop {
+                        let mut kind = next();
+                        if kind == "}" {
+                            break;
+                        }
+                        let repeated = kind == "repeated";
+                        if repeated {
+                            kind = next();
+                        }
+                        let name = next();
+                        assert_eq!(next(), "=");
+                        let number = next().parse().unwrap();
+                        assert_eq!(next(), ";");
+                        fields.push(Field {
+                            name,
+                            number,
+                            kind,
+                            repeated,
+                        });
+                    }
+                    schema.messages.insert(name, fields);
+                }
+                "enum" => {
+                    let name = next();
+                    assert_eq!(next(), "{");
+                    let mut values = BTreeMap::new();
+                    loop {
+                        let value = next();
+                        if value == "}" {
+                            break;
+                        }
+                        assert_eq!(next(), "=");
+                        values.insert(value, next().parse().unwrap());
+                        assert_eq!(next(), ";");
+                    }
+                    schema.enums.insert(name, values);
+                }
+                "service" => {
+                    assert_eq!(next(), "Scans");
+                    assert_eq!(next(), "{");
+                    while next() == "rpc" {
+                        let name = next();
+                        assert_eq!(next(), "(");
+                        let input = next();
+                        assert_eq!(next(), ")");
+                        assert_eq!(next(), "returns");
+                        assert_eq!(next(), "(");
+                        let mut output = next();
+                        let stream = output == "stream";
+                        if stream {
+                            output = next();
+                        }
+                        assert_eq!(next(), ")");
+                        assert_eq!(next(), ";");
+                        schema.rpcs.push(Rpc {
+                            name,
+                            input,
+                            output,
+                            stream,
+                        });
+                    }
+                }
+                x => panic!("unexpected {x} in openvasd.proto"),
+            }
+        }
+    }
+
+    fn field(&self, message: &str, number: u32) -> &Field {
+        self.messages[message]
+            .iter()
+            .find(|x| x.number == number)
+            .unwrap_or_else(|| panic!("{message} has no field {number}"))
+    }
+
+    /// Returns the wire type of a field kind.
+    fn wire_type(&self, kind: &str) -> u64 {
+        match kind {
+            "uint64" | "uint32" | "bool" => 0,
+            "string" | "bytes" => 2,
+            x if self.enums.contains_key(x) => 0,
+            x if self.messages.contains_key(x) => 2,
+            x => panic!("unsupported type {x}"),
+        }
+    }
+
+    fn decode(&self, message: &str, mut buf: &[u8]) -> Fields {
+        let mut result = Fields::new();
+        while !buf.is_empty() {
+            let key = varint(&mut buf);
+            let field = self.field(message, (key >> 3) as u32);
+            assert_eq!(
+                key & 7,
+                self.wire_type(&field.kind),
+                "wire type of {message}.{}",
+                field.name
+            );
+            let value = if key & 7 == 0 {
+                Dynamic::Varint(varint(&mut buf))
+            } else {
+                let len = varint(&mut buf) as usize;
+                let (value, rest) = buf.split_at(len);
+                buf = rest;
+                match field.kind.as_str() {
+                    "string" => Dynamic::Bytes(
+                        String::from_utf8(value.to_vec())
+                            .expect("UTF-8 string")
+                            .into_bytes(),
+                    ),
+                    "bytes" => Dynamic::Bytes(value.to_vec()),
+                    kind => Dynamic::Message(self.decode(kind, value)),
+                }
+            };
+            let values = result.entry(field.name.clone()).or_default();
+            assert!(
+                field.repeated || values.is_empty(),
+                "{message}.{} is set twice",
+                field.name
+            );
+            values.push(value);
+        }
+        result
+    }
+
+    fn encode(&self, message: &str, fields: &Fields) -> Vec<u8> {
+        let mut buf = vec![];
+        for (name, values) in fields {
+            let field = self.messages[message]
+                .iter()
+                .find(|x| &x.name == name)
+                .unwrap_or_else(|| panic!("{message} has no field {name}"));
+            for value in values {
+                let wire_type = self.wire_type(&field.kind);
+                put_varint(&mut buf, (u64::from(field.number) << 3) | wire_type);
+                match value {
+                    Dynamic::Varint(x) => put_varint(&mut buf, *x),
+                    Dynamic::Bytes(x) => {
+                        put_varint(&mut buf, x.len() as u64);
+                        buf.extend_from_slice(x);
+                    }
+                    Dynamic::Message(x) => {
+                        let x = self.encode(&field.kind, x);
+                        put_varint(&mut buf, x.len() as u64);
+                        buf.extend_from_slice(&x);
+                    }
+                }
+            }
+        }
+        buf
+    }
+}
+
+/// A field value decoded by the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Dynamic {
+    Varint(u64),
+    Bytes(Vec<u8>),
+    Message(Fields),
+}
+
+type Fields = BTreeMap<String, Vec<Dynamic>>;
+
+fn varint(buf: &mut &[u8]) -> u64 {
+    let mut result = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = buf[0];
+        *buf = &buf[1..];
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            break;
+        }
+    }
+    result
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn fields<const N: usize>(values: [(&str, Dynamic); N]) -> Fields {
+    values
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), vec![value]))
+        .collect()
+}
+
+fn text(x: &str) -> Dynamic {
+    Dynamic::Bytes(x.as_bytes().to_vec())
+}
+
+/// Checks that the message is encoded as the fields and vice versa.
+fn check<M: Message + PartialEq + std::fmt::Debug>(
+    schema: &Schema,
+    name: &str,
+    message: M,
+    expected: Fields,
+) {
+    assert_eq!(
+        schema.decode(name, &message.encode_to_vec()),
+        expected,
+        "{name}"
+    );
+    assert_eq!(
+        M::decode(&schema.encode(name, &expected)).unwrap(),
+        message,
+        "{name}"
+    );
+}
+
+#[test]
+fn messages() {
+    let schema = Schema::parse(PROTO);
+    let mut checked = vec![];
+    let mut named = |name: &'static str| {
+        checked.push(name);
+        name
+    };
+    check(&schema, named("Empty"), proto::Empty, Fields::new());
+    check(
+        &schema,
+        named("CreateScanRequest"),
+        proto::CreateScanRequest {
+            scan: b"{}".to_vec(),
+        },
+        fields([("scan", text("{}"))]),
+    );
+    check(
+        &schema,
+        named("ScanReference"),
+        proto::ScanReference { id: "42".into() },
+        fields([("id", text("42"))]),
+    );
+    check(
+        &schema,
+        named("ScanList"),
+        proto::ScanList {
+            ids: vec!["a".into(), "".into()],
+        },
+        [("ids".to_string(), vec![text("a"), text("")])].into(),
+    );
+    let hosts = fields([
+        ("all", Dynamic::Varint(1)),
+        ("excluded", Dynamic::Varint(2)),
+        ("dead", Dynamic::Varint(3)),
+        ("alive", Dynamic::Varint(4)),
+        ("queued", Dynamic::Varint(5)),
+        ("finished", Dynamic::Varint(6)),
+    ]);
+    let host_info = proto::HostInfo {
+        all: 1,
+        excluded: 2,
+        dead: 3,
+        alive: 4,
+        queued: 5,
+        finished: 6,
+    };
+    check(&schema, named("HostInfo"), host_info.clone(), hosts.clone());
+    check(
+        &schema,
+        named("ScanStatus"),
+        proto::ScanStatus {
+            phase: 5,
+            start_time: 1_700_000_000,
+            end_time: 1_700_000_300,
+            hosts: Some(host_info),
+        },
+        fields([
+            ("phase", Dynamic::Varint(5)),
+            ("start_time", Dynamic::Varint(1_700_000_000)),
+            ("end_time", Dynamic::Varint(1_700_000_300)),
+            ("hosts", Dynamic::Message(hosts)),
+        ]),
+    );
+    check(
+        &schema,
+        named("StreamResultsRequest"),
+        proto::StreamResultsRequest {
+            id: "42".into(),
+            from: 300,
+            follow: true,
+        },
+        fields([
+            ("id", text("42")),
+            ("from", Dynamic::Varint(300)),
+            ("follow", Dynamic::Varint(1)),
+        ]),
+    );
+    let detail = fields([
+        ("name", text("OS")),
+        ("value", text("cpe:/o:debian:debian_linux")),
+        ("source_type", text("nvt")),
+        ("source_name", text("1.2.3")),
+        ("source_description", text("OS Detection")),
+    ]);
+    check(
+        &schema,
+        named("Detail"),
+        proto::Detail {
+            name: "OS".into(),
+            value: "cpe:/o:debian:debian_linux".into(),
+            source_type: "nvt".into(),
+            source_name: "1.2.3".into(),
+            source_description: "OS Detection".into(),
+        },
+        detail.clone(),
+    );
+    check(
+        &schema,
+        named("Result"),
+        proto::ScanResult {
+            id: 7,
+            r_type: 6,
+            ip_address: "127.0.0.1".into(),
+            hostname: "localhost".into(),
+            oid: "1.2.3".into(),
+            port: 22,
+            protocol: "tcp".into(),
+            message: "found".into(),
+            qod: 80,
+            detail: Some(proto::Detail {
+                name: "OS".into(),
+                value: "cpe:/o:debian:debian_linux".into(),
+                source_type: "nvt".into(),
+                source_name: "1.2.3".into(),
+                source_description: "OS Detection".into(),
+            }),
+        },
+        fields([
+            ("id", Dynamic::Varint(7)),
+            ("type", Dynamic::Varint(6)),
+            ("ip_address", text("127.0.0.1")),
+            ("hostname", text("localhost")),
+            ("oid", text("1.2.3")),
+            ("port", Dynamic::Varint(22)),
+            ("protocol", text("tcp")),
+            ("message", text("found")),
+            ("qod", Dynamic::Varint(80)),
+            ("detail", Dynamic::Message(detail)),
+        ]),
+    );
+    check(
+        &schema,
+        named("FeedStatus"),
+        proto::FeedStatus {
+            version: "202401010000".into(),
+            vts: 100,
+        },
+        fields([
+            ("version", text("202401010000")),
+            ("vts", Dynamic::Varint(100)),
+        ]),
+    );
+    checked.sort();
+    assert_eq!(
+        checked,
+        schema
+            .messages
+            .keys()
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn enums() {
+    let schema = Schema::parse(PROTO);
+    for (name, value) in &schema.enums["Phase"] {
+        let status = models::Status {
+            status: serde_json::from_value(name.to_lowercase().into()).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(proto::ScanStatus::from(&status).phase, *value, "{name}");
+    }
+    for (name, value) in &schema.enums["ResultType"] {
+        let result = models::Result {
+            r_type: serde_json::from_value(name.to_lowercase().into()).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(proto::ScanResult::from(&result).r_type, *value, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn methods() {
+    let schema = Schema::parse(PROTO);
+    let ctx = crate::controller::ContextBuilder::new()
+        .scanner(NoOpScanner)
+        .build();
+    let service = Service::new(
+        Arc::new(ctx),
+        Arc::new(ClientIdentifier::Known("42".into())),
+    );
+    let streamed: Vec<_> = schema.rpcs.iter().filter(|x| x.stream).collect();
+    assert_eq!(streamed.len(), 1);
+    assert_eq!(streamed[0].name, "StreamResults");
+    for rpc in &schema.rpcs {
+        let request = match rpc.input.as_str() {
+            "Empty" => Fields::new(),
+            "CreateScanRequest" => fields([("scan", text(r#"{"scan_id": "1"}"#))]),
+            "ScanReference" | "StreamResultsRequest" => fields([("id", text("1"))]),
+            x => panic!("no request for {x}"),
+        };
+        let message = schema.encode(&rpc.input, &request);
+        let mut body = vec![0];
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(&message);
+        let response = service
+            .call(
+                &format!("/openvasd.v1.Scans/{}", rpc.name),
+                &HeaderMap::new(),
+                &body,
+            )
+            .await;
+        let mut body = response.into_body();
+        let mut code = None;
+        while let Some(Ok(frame)) = body.frame().await {
+            match frame.into_data() {
+                Ok(data) => {
+                    assert_eq!(data[0], 0);
+                    schema.decode(&rpc.output, &data[5..]);
+                }
+                Err(frame) => code = frame.into_trailers().unwrap().get("grpc-status").cloned(),
+            }
+        }
+        // the scan is deleted in between, so only the implementation is checked
+        let code = code.expect("grpc-status trailer");
+        assert_ne!(
+            code,
+            Status::UNIMPLEMENTED.to_string().as_str(),
+            "{}",
+            rpc.name
+        );
+    }
+}
@@ -5,53 +5,87 @@
 #![doc = include_str!("README.md")]
 
 use std::marker::{Send, Sync};
+use std::sync::{Arc, Mutex};
 
 use config::{Config, Mode, ScannerType};
 use controller::{Context, ContextBuilder};
+use enrichment::Enrichment;
 use notus::NotusWrapper;
+use oidc::Oidc;
 use scannerlib::models::scanner::{
-    ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper, Scanner,
+    ScanDeleter, ScanPlanner, ScanResultFetcher, ScanStarter, ScanStopper, Scanner,
 };
-use scannerlib::nasl::FSPluginLoader;
+use scannerlib::nasl::syntax::CompileCache;
+use scannerlib::nasl::utils::DynamicLoader;
+use scannerlib::nasl::{nasl_std_functions, FSPluginLoader};
 use scannerlib::notus::{HashsumProductLoader, Notus};
 use scannerlib::openvas::{self, cmd};
 use scannerlib::osp;
+use scannerlib::redact;
 use scannerlib::scanner::ScannerStackWithStorage;
 use scannerlib::storage::infisto::{ChaCha20IndexFileStorer, IndexedFileStorer};
 use storage::{FromConfigAndFeeds, Storage};
 use tls::tls_config;
 use tracing::{info, metadata::LevelFilter, warn};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{
+    layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter, Layer as _,
+};
 
 use crate::{
     config::StorageType,
     crypt::ChaCha20Crypt,
     storage::{file, inmemory, redis, FeedHash},
 };
+pub mod blackout;
+pub mod bundle;
+pub mod cluster;
 pub mod config;
 pub mod controller;
 pub mod crypt;
+pub mod enrichment;
 pub mod feed;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod notus;
+pub mod oidc;
 pub mod preference;
+pub mod publisher;
 pub mod request;
 pub mod response;
 mod scheduling;
+pub mod secrets;
+pub mod severity;
 pub mod storage;
+pub mod telemetry;
+pub mod tenancy;
 pub mod tls;
+pub mod triage;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 fn setup_log(config: &Config) {
     let filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .parse_lossy(format!("{},rustls=info,h2=info", &config.log.level));
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let telemetry = config.telemetry.otlp_endpoint.as_ref().map(|_| {
+        let (layer, spans) = telemetry::OtlpLayer::new(config.telemetry.sample_ratio);
+        tokio::spawn(telemetry::export(config.telemetry.clone(), spans));
+        layer.with_filter(EnvFilter::builder().parse_lossy(&config.telemetry.filter))
+    });
+    redact::register(config.secrets()).keep();
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(redact::MakeRedacting::new(std::io::stdout))
+                .with_filter(filter),
+        )
+        .with(telemetry)
+        .init();
 }
 
 fn get_feeds(config: &Config) -> Vec<FeedHash> {
     match config.mode {
         Mode::Service => vec![
-            FeedHash::nasl(&config.feed.path),
+            FeedHash::nasl(&config.feed.path).with_overlays(config.feed.overlays.clone()),
             FeedHash::advisories(&config.notus.advisories_path),
         ],
         Mode::ServiceNotus => vec![FeedHash::advisories(&config.notus.advisories_path)],
@@ -85,26 +119,62 @@ fn make_openvas_scanner(mut config: Config) -> openvas::Scanner {
         cmd::check_sudo(),
         redis_url,
     )
+    .with_timeout(config.scanner.openvas.timeout)
 }
 
 fn make_openvasd_scanner<S>(
     config: &Config,
     storage: S,
-) -> scannerlib::scanner::Scanner<ScannerStackWithStorage<S>>
+) -> Result<scannerlib::scanner::Scanner<ScannerStackWithStorage<S>>>
 where
     S: storage::NaslStorage + Send + 'static,
 {
-    scannerlib::scanner::Scanner::with_storage(storage, &config.feed.path)
+    let mut executor = nasl_std_functions();
+    let plugins = &config.scanner.plugins;
+    if let Some(path) = &plugins.path {
+        let mut loader = DynamicLoader::new(path).with_signature_check(plugins.signature_check);
+        if let Some(allowed) = &plugins.allowed {
+            loader = loader.with_allowed(allowed.clone());
+        }
+        let loaded = loader.load(&mut executor)?;
+        info!(path=%path.display(), ?loaded, "Loaded builtin function plugins");
+    }
+    let loader = FSPluginLoader::new(&config.feed.path).with_overlays(&config.feed.overlays);
+    let mut scanner = scannerlib::scanner::Scanner::new(storage, loader, executor)
+        .with_plugin_timeout(config.scanner.plugin_timeout)
+        .with_disabled_builtins(config.scanner.disabled_builtins.clone())
+        .with_pcap_directory(config.scanner.pcap_directory.clone())
+        .with_include_graph(config.scanner.include_graph.clone());
+    if let Some(path) = &config.scanner.compile_cache {
+        scanner = scanner.with_compile_cache(CompileCache::new(path)?);
+    }
+    // allows scripts to check the packages they gathered without another notus request
+    let products = FSPluginLoader::new(config.notus.products_path.to_string_lossy().to_string());
+    match HashsumProductLoader::new(products) {
+        Ok(loader) => {
+            let notus = Notus::new(loader, config.feed.signature_check);
+            scanner = scanner.with_package_scanner(Arc::new(Mutex::new(notus)));
+        }
+        Err(e) => warn!("Notus disabled for gathered packages: {e}"),
+    }
+    Ok(scanner)
 }
 
 async fn create_context<DB, ScanHandler>(
     db: DB,
     sh: ScanHandler,
     config: &Config,
+    cluster: Option<Arc<cluster::Registry>>,
 ) -> Context<ScanHandler, DB>
 where
-    ScanHandler:
-        ScanStarter + ScanStopper + ScanDeleter + ScanResultFetcher + Sync + Send + 'static,
+    ScanHandler: ScanStarter
+        + ScanStopper
+        + ScanDeleter
+        + ScanResultFetcher
+        + ScanPlanner
+        + Sync
+        + Send
+        + 'static,
 {
     let mut ctx_builder = ContextBuilder::new();
 
@@ -116,11 +186,40 @@ where
         }
         Err(e) => warn!("Notus Scanner disabled: {e}"),
     }
+    if config.oidc.issuer.is_some() {
+        ctx_builder = ctx_builder.oidc(Oidc::new(config.oidc.clone()));
+    }
+    if config.enrichment.path.is_some() {
+        ctx_builder = ctx_builder.enrichment(Enrichment::new(config.enrichment.clone()));
+    }
+    let mut report = config.report.clone();
+    match report.branding.clone().embed_logo() {
+        Ok(branding) => report.branding = branding,
+        Err(e) => warn!(
+            logo = report.branding.logo,
+            "Unable to embed logo into reports: {e}"
+        ),
+    }
+    ctx_builder = ctx_builder
+        .report(report)
+        .severity(config.severity.clone())
+        .bundle(config.bundle.clone());
+    ctx_builder = ctx_builder.tenancy(config.tenancy.clone());
+    if let Some(cluster) = cluster {
+        ctx_builder = ctx_builder.cluster(cluster);
+    }
+    let mut scheduler = config.scheduler.clone();
+    if scheduler.disk_paths.is_empty() && config.storage.storage_type == StorageType::FileSystem {
+        scheduler.disk_paths.push(config.storage.fs.path.clone());
+    }
     tracing::warn!(enable_get_scans = config.endpoints.enable_get_scans);
 
     ctx_builder
         .mode(config.mode.clone())
-        .scheduler_config(config.scheduler.clone())
+        .scheduler_config(scheduler)
+        .secrets(secrets::Secrets::new(config.secrets.clone()))
+        .triage(triage::Triage::new(config.triage.clone()))
+        .network_namespaces(config.scanner.network_namespaces.clone())
         .feed_config(config.feed.clone())
         .await
         .scanner(sh)
@@ -135,12 +234,13 @@ async fn run_with_scanner_and_storage<Sc, St>(
     scanner: Sc,
     storage: St,
     config: &Config,
+    cluster: Option<Arc<cluster::Registry>>,
 ) -> Result<()>
 where
     St: Storage + Send + Sync + 'static,
     Sc: Scanner + Send + Sync + 'static,
 {
-    let ctx = create_context(storage, scanner, config).await;
+    let ctx = create_context(storage, scanner, config, cluster).await;
     controller::run(ctx, config).await
 }
 
@@ -154,16 +254,25 @@ where
     match config.scanner.scanner_type {
         ScannerType::OSPD => {
             let scanner = make_osp_scanner(config);
-            run_with_scanner_and_storage(scanner, storage, config).await
+            run_with_scanner_and_storage(scanner, storage, config, None).await
         }
         ScannerType::Openvas => {
             let scanner = make_openvas_scanner(config.clone());
-            run_with_scanner_and_storage(scanner, storage, config).await
+            run_with_scanner_and_storage(scanner, storage, config, None).await
         }
         ScannerType::Openvasd => {
             let storage = std::sync::Arc::new(storage::UserNASLStorageForKBandVT::new(storage));
-            let scanner = make_openvasd_scanner(config, storage.clone());
-            run_with_scanner_and_storage(scanner, storage, config).await
+            let scanner = make_openvasd_scanner(config, storage.clone())?;
+            run_with_scanner_and_storage(scanner, storage, config, None).await
+        }
+        ScannerType::Cluster => {
+            let cluster = &config.scanner.cluster;
+            if cluster.workers.is_empty() && cluster.registration_token.is_none() {
+                warn!("Neither workers nor a registration token configured, scans of the cluster scanner type will stay queued");
+            }
+            let scanner = cluster::Scanner::new(cluster.clone());
+            let registry = scanner.registry();
+            run_with_scanner_and_storage(scanner, storage, config, Some(registry)).await
         }
     }
 }
@@ -199,6 +308,15 @@ async fn run(config: &Config) -> Result<()> {
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = Config::load();
+    if let Some(path) = &config.encrypt_secrets {
+        let key = config
+            .secrets
+            .file_key
+            .as_deref()
+            .ok_or("the secrets file key is required to encrypt secrets")?;
+        println!("{}", secrets::encrypt_file(path, key)?);
+        return Ok(());
+    }
     tracing::debug!(key = config.storage.fs.key);
     setup_log(&config);
     run(&config).await
@@ -0,0 +1,654 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Authenticates clients by bearer tokens issued by an OpenID Connect provider.
+//!
+//! Tokens are JSON web tokens signed by a key of the JSON web key set of the issuer. The key set
+//! is discovered via `/.well-known/openid-configuration` of the issuer unless its url is
+//! configured, it is fetched again after the refresh interval or when a token is signed by an
+//! unknown key.
+//!
+//! The role of a client is mapped from a claim of the token, e.g. the groups or roles assigned
+//! by the provider.
+
+use std::{
+    sync::RwLock,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hyper::{header::AUTHORIZATION, HeaderMap, Method};
+use ring::signature;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config, controller::ClientHash};
+
+/// Accepted difference between the clocks of the issuer and openvasd in seconds
+const LEEWAY: u64 = 60;
+/// Minimal time between two fetches of the key set in seconds
+const MIN_REFRESH: u64 = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to fetch the keys of the issuer: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("malformed token: {0}")]
+    Malformed(&'static str),
+    #[error("unsupported algorithm {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("the signing key of the token is unknown")]
+    UnknownKey,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("the token is expired")]
+    Expired,
+    #[error("the token is not yet valid")]
+    NotYetValid,
+    #[error("unexpected issuer {0}")]
+    Issuer(String),
+    #[error("the token is not issued for the audience {0}")]
+    Audience(String),
+    #[error("the token is missing the scope {0}")]
+    Scope(String),
+    #[error("no role is mapped to the token")]
+    Role,
+}
+
+/// Role of a client authenticated by a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May only send GET and HEAD requests
+    Viewer,
+    /// May create, start, stop and delete own scans
+    Operator,
+    /// May access the scans of every client
+    Admin,
+}
+
+impl Role {
+    /// Returns true when the role may send requests of the method
+    pub fn allows(&self, method: &Method) -> bool {
+        match self {
+            Self::Viewer => method == Method::GET || method == Method::HEAD,
+            Self::Operator | Self::Admin => true,
+        }
+    }
+}
+
+/// A client authenticated by a token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub issuer: String,
+    /// The `sub` claim of the token
+    pub subject: String,
+    pub role: Role,
+    /// Tenant the client belongs to, see [config::Oidc::tenant_claim]
+    pub tenant: Option<String>,
+}
+
+impl Identity {
+    /// Returns the hash the scans of the client are stored under
+    pub fn client_hash(&self) -> ClientHash {
+        format!("{}#{}", self.issuer, self.subject).into()
+    }
+}
+
+/// A key of a JSON web key set
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub alg: Option<String>,
+    #[serde(default, rename = "use")]
+    pub key_use: Option<String>,
+    /// Modulus of RSA keys
+    #[serde(default)]
+    pub n: Option<String>,
+    /// Exponent of RSA keys
+    #[serde(default)]
+    pub e: Option<String>,
+    /// Curve of EC keys
+    #[serde(default)]
+    pub crv: Option<String>,
+    #[serde(default)]
+    pub x: Option<String>,
+    #[serde(default)]
+    pub y: Option<String>,
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, Error> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| Error::Malformed("invalid base64"))
+}
+
+impl Jwk {
+    fn component(value: &Option<String>) -> Result<Vec<u8>, Error> {
+        value
+            .as_deref()
+            .ok_or(Error::Malformed("incomplete key"))
+            .and_then(decode)
+    }
+
+    /// Returns true when the key may verify signatures of the algorithm
+    fn accepts(&self, alg: &str) -> bool {
+        let kty = match alg {
+            "RS256" | "RS384" | "RS512" | "PS256" | "PS384" | "PS512" => "RSA",
+            "ES256" | "ES384" => "EC",
+            _ => return false,
+        };
+        self.kty == kty
+            && self.alg.as_deref().is_none_or(|x| x == alg)
+            && self.key_use.as_deref().is_none_or(|x| x == "sig")
+    }
+
+    fn verify(&self, alg: &str, message: &[u8], sig: &[u8]) -> Result<(), Error> {
+        let rsa: Option<&signature::RsaParameters> = match alg {
+            "RS256" => Some(&signature::RSA_PKCS1_2048_8192_SHA256),
+            "RS384" => Some(&signature::RSA_PKCS1_2048_8192_SHA384),
+            "RS512" => Some(&signature::RSA_PKCS1_2048_8192_SHA512),
+            "PS256" => Some(&signature::RSA_PSS_2048_8192_SHA256),
+            "PS384" => Some(&signature::RSA_PSS_2048_8192_SHA384),
+            "PS512" => Some(&signature::RSA_PSS_2048_8192_SHA512),
+            _ => None,
+        };
+        let verified = if let Some(params) = rsa {
+            signature::RsaPublicKeyComponents {
+                n: Self::component(&self.n)?,
+                e: Self::component(&self.e)?,
+            }
+            .verify(params, message, sig)
+        } else {
+            let params: &signature::EcdsaVerificationAlgorithm = match (alg, self.crv.as_deref()) {
+                ("ES256", Some("P-256")) => &signature::ECDSA_P256_SHA256_FIXED,
+                ("ES384", Some("P-384")) => &signature::ECDSA_P384_SHA384_FIXED,
+                _ => return Err(Error::UnsupportedAlgorithm(alg.to_string())),
+            };
+            // uncompressed point
+            let mut point = vec![4];
+            point.extend(Self::component(&self.x)?);
+            point.extend(Self::component(&self.y)?);
+            signature::UnparsedPublicKey::new(params, point).verify(message, sig)
+        };
+        verified.map_err(|_| Error::InvalidSignature)
+    }
+}
+
+/// A JSON web key set
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+impl JwkSet {
+    fn contains(&self, kid: Option<&str>) -> bool {
+        match kid {
+            Some(kid) => self.keys.iter().any(|x| x.kid.as_deref() == Some(kid)),
+            None => !self.keys.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// A decoded but not yet verified token
+#[derive(Debug)]
+struct Token {
+    header: Header,
+    claims: Value,
+    /// The signed part of the token
+    message: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Token {
+    fn parse(token: &str) -> Result<Self, Error> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::Malformed("expected three parts"));
+        };
+        Ok(Self {
+            header: serde_json::from_slice(&decode(header)?)?,
+            claims: serde_json::from_slice(&decode(claims)?)?,
+            message: format!("{header}.{claims}").into_bytes(),
+            signature: decode(signature)?,
+        })
+    }
+
+    fn claim(&self, name: &str) -> Option<&Value> {
+        self.claims.get(name)
+    }
+
+    /// Returns a claim of the token, nested claims are separated by dots
+    fn nested_claim(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(&self.claims, |x, name| x.get(name))
+    }
+
+    fn time(&self, name: &str) -> Option<u64> {
+        self.claim(name).and_then(|x| x.as_f64()).map(|x| x as u64)
+    }
+}
+
+/// Returns a string or the strings of an array
+fn strings(value: Option<&Value>) -> Vec<&str> {
+    match value {
+        Some(Value::String(x)) => vec![x.as_str()],
+        Some(Value::Array(x)) => x.iter().filter_map(|x| x.as_str()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Returns the token of an `Authorization: Bearer` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then_some(token.trim())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+struct Keys {
+    set: JwkSet,
+    fetched: Option<Instant>,
+    attempted: Option<Instant>,
+}
+
+/// Validates bearer tokens of an OpenID Connect issuer
+#[derive(Debug)]
+pub struct Oidc {
+    pub config: config::Oidc,
+    keys: RwLock<Keys>,
+}
+
+impl Oidc {
+    /// Creates a validator without keys, they are fetched with the first token
+    pub fn new(config: config::Oidc) -> Self {
+        Self {
+            config,
+            keys: Default::default(),
+        }
+    }
+
+    /// Uses the given keys until they are refreshed
+    #[cfg(test)]
+    pub fn with_keys(self, set: JwkSet) -> Self {
+        let now = Some(Instant::now());
+        *self.keys.write().unwrap() = Keys {
+            set,
+            fetched: now,
+            attempted: now,
+        };
+        self
+    }
+
+    fn needs_refresh(&self, kid: Option<&str>) -> bool {
+        let keys = self.keys.read().unwrap();
+        let elapsed = |x: Option<Instant>| x.map(|x| x.elapsed());
+        if elapsed(keys.attempted).is_some_and(|x| x.as_secs() < MIN_REFRESH) {
+            return false;
+        }
+        elapsed(keys.fetched).is_none_or(|x| x > self.config.jwks_refresh_interval)
+            || !keys.set.contains(kid)
+    }
+
+    /// Returns the url of the key set, discovers it via the issuer when it is not configured
+    async fn jwks_url(&self, client: &reqwest::Client) -> Result<String, Error> {
+        if let Some(url) = &self.config.jwks_url {
+            return Ok(url.clone());
+        }
+        #[derive(Deserialize)]
+        struct Discovery {
+            jwks_uri: String,
+        }
+        let issuer = self.config.issuer.as_deref().unwrap_or_default();
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let body = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice::<Discovery>(&body)?.jwks_uri)
+    }
+
+    /// Fetches the key set of the issuer
+    pub async fn refresh(&self) -> Result<(), Error> {
+        self.keys.write().unwrap().attempted = Some(Instant::now());
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?;
+        let url = self.jwks_url(&client).await?;
+        let body = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let set: JwkSet = serde_json::from_slice(&body)?;
+        tracing::debug!(keys = set.keys.len(), "Fetched keys of the OIDC issuer");
+        let mut keys = self.keys.write().unwrap();
+        keys.set = set;
+        keys.fetched = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Verifies a token and returns the identity of its client.
+    ///
+    /// The key set is fetched first when it is outdated or does not contain the key of the token.
+    pub async fn authenticate(&self, token: &str) -> Result<Identity, Error> {
+        let token = Token::parse(token)?;
+        if self.needs_refresh(token.header.kid.as_deref()) {
+            if let Err(e) = self.refresh().await {
+                tracing::warn!(%e, "Unable to fetch the keys of the OIDC issuer");
+            }
+        }
+        self.validate(&token, now())
+    }
+
+    fn verify_signature(&self, token: &Token) -> Result<(), Error> {
+        let alg = token.header.alg.as_str();
+        let keys = self.keys.read().unwrap();
+        let mut candidates = keys
+            .set
+            .keys
+            .iter()
+            .filter(|x| token.header.kid.is_none() || x.kid == token.header.kid)
+            .filter(|x| x.accepts(alg))
+            .peekable();
+        if candidates.peek().is_none() {
+            return match keys.set.keys.iter().any(|x| x.accepts(alg)) {
+                true => Err(Error::UnknownKey),
+                false => Err(Error::UnsupportedAlgorithm(alg.to_string())),
+            };
+        }
+        let mut result = Err(Error::InvalidSignature);
+        for key in candidates {
+            result = key.verify(alg, &token.message, &token.signature);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    fn role(&self, token: &Token) -> Result<Role, Error> {
+        strings(token.nested_claim(&self.config.roles_claim))
+            .into_iter()
+            .filter_map(|x| self.config.roles.get(x).copied())
+            .max()
+            .or(self.config.default_role)
+            .ok_or(Error::Role)
+    }
+
+    fn validate(&self, token: &Token, now: u64) -> Result<Identity, Error> {
+        self.verify_signature(token)?;
+        let issuer = token
+            .claim("iss")
+            .and_then(|x| x.as_str())
+            .ok_or(Error::Malformed("missing iss"))?;
+        if Some(issuer) != self.config.issuer.as_deref() {
+            return Err(Error::Issuer(issuer.to_string()));
+        }
+        let expires = token.time("exp").ok_or(Error::Malformed("missing exp"))?;
+        if now > expires.saturating_add(LEEWAY) {
+            return Err(Error::Expired);
+        }
+        if token
+            .time("nbf")
+            .is_some_and(|x| now.saturating_add(LEEWAY) < x)
+        {
+            return Err(Error::NotYetValid);
+        }
+        if let Some(audience) = &self.config.audience {
+            if !strings(token.claim("aud")).contains(&audience.as_str()) {
+                return Err(Error::Audience(audience.clone()));
+            }
+        }
+        let mut scopes: Vec<&str> = token
+            .claim("scope")
+            .and_then(|x| x.as_str())
+            .map(|x| x.split_whitespace().collect())
+            .unwrap_or_default();
+        scopes.extend(strings(token.claim("scp")));
+        if let Some(missing) = self
+            .config
+            .scopes
+            .iter()
+            .find(|x| !scopes.contains(&x.as_str()))
+        {
+            return Err(Error::Scope(missing.clone()));
+        }
+        let subject = token
+            .claim("sub")
+            .and_then(|x| x.as_str())
+            .ok_or(Error::Malformed("missing sub"))?;
+        Ok(Identity {
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            role: self.role(token)?,
+            tenant: strings(token.nested_claim(&self.config.tenant_claim))
+                .first()
+                .map(|x| x.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    pub const ISSUER: &str = "https://sso.example.com/realms/greenbone";
+
+    /// Signs tokens with a generated ES256 key
+    pub struct Signer {
+        pair: EcdsaKeyPair,
+        rng: SystemRandom,
+    }
+
+    impl Signer {
+        pub fn new() -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+            let pair =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            Self { pair, rng }
+        }
+
+        pub fn jwks(&self, kid: &str) -> JwkSet {
+            let point = self.pair.public_key().as_ref();
+            JwkSet {
+                keys: vec![Jwk {
+                    kty: "EC".to_string(),
+                    kid: Some(kid.to_string()),
+                    crv: Some("P-256".to_string()),
+                    x: Some(URL_SAFE_NO_PAD.encode(&point[1..33])),
+                    y: Some(URL_SAFE_NO_PAD.encode(&point[33..])),
+                    ..Default::default()
+                }],
+            }
+        }
+
+        pub fn sign(&self, kid: &str, claims: Value) -> String {
+            let header = json!({"alg": "ES256", "typ": "JWT", "kid": kid});
+            let message = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(header.to_string()),
+                URL_SAFE_NO_PAD.encode(claims.to_string())
+            );
+            let sig = self.pair.sign(&self.rng, message.as_bytes()).unwrap();
+            format!("{message}.{}", URL_SAFE_NO_PAD.encode(sig.as_ref()))
+        }
+    }
+
+    /// Claims of a valid token with the given roles
+    pub fn claims(subject: &str, roles: &[&str]) -> Value {
+        json!({
+            "iss": ISSUER,
+            "sub": subject,
+            "aud": ["account", "openvasd"],
+            "exp": now() + 300,
+            "scope": "openid openvasd",
+            "realm_access": { "roles": roles },
+        })
+    }
+
+    pub fn config() -> config::Oidc {
+        config::Oidc {
+            issuer: Some(ISSUER.to_string()),
+            audience: Some("openvasd".to_string()),
+            scopes: vec!["openvasd".to_string()],
+            roles_claim: "realm_access.roles".to_string(),
+            roles: [
+                ("scan-admin".to_string(), Role::Admin),
+                ("scan-user".to_string(), Role::Operator),
+                ("auditor".to_string(), Role::Viewer),
+            ]
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticate() {
+        let signer = Signer::new();
+        let oidc = Oidc::new(config()).with_keys(signer.jwks("k1"));
+        let identity = oidc
+            .authenticate(&signer.sign("k1", claims("alice", &["auditor", "scan-user"])))
+            .await
+            .unwrap();
+        assert_eq!(identity.subject, "alice");
+        assert_eq!(identity.role, Role::Operator);
+        assert!(!Role::Viewer.allows(&Method::POST));
+        assert!(Role::Viewer.allows(&Method::GET));
+
+        let mut claims = claims("alice", &["auditor"]);
+        let token = |claims: &Value| Token::parse(&signer.sign("k1", claims.clone())).unwrap();
+        assert!(oidc.validate(&token(&claims), now()).is_ok());
+        assert!(matches!(
+            oidc.validate(&token(&claims), now() + 3600),
+            Err(Error::Expired)
+        ));
+        // saturated to u64::MAX
+        claims["exp"] = json!(1e300);
+        claims["nbf"] = json!(1e300);
+        assert!(oidc.validate(&token(&claims), u64::MAX).is_ok());
+        claims["exp"] = json!(now() + 300);
+        claims.as_object_mut().unwrap().remove("nbf");
+        claims["scope"] = json!("openid");
+        assert!(matches!(
+            oidc.validate(&token(&claims), now()),
+            Err(Error::Scope(_))
+        ));
+        claims["scp"] = json!(["openvasd"]);
+        claims["aud"] = json!("account");
+        assert!(matches!(
+            oidc.validate(&token(&claims), now()),
+            Err(Error::Audience(_))
+        ));
+        claims["aud"] = json!("openvasd");
+        claims["iss"] = json!("https://evil.example.com");
+        assert!(matches!(
+            oidc.validate(&token(&claims), now()),
+            Err(Error::Issuer(_))
+        ));
+        claims["iss"] = json!(ISSUER);
+        claims["realm_access"]["roles"] = json!(["unknown"]);
+        assert!(matches!(
+            oidc.validate(&token(&claims), now()),
+            Err(Error::Role)
+        ));
+
+        // signed by another key with the same kid
+        let other = Signer::new().sign("k1", super::tests::claims("alice", &["auditor"]));
+        let other = Token::parse(&other).unwrap();
+        assert!(matches!(
+            oidc.validate(&other, now()),
+            Err(Error::InvalidSignature)
+        ));
+        let mut unsigned = token(&super::tests::claims("alice", &["auditor"]));
+        unsigned.header.alg = "none".to_string();
+        assert!(matches!(
+            oidc.validate(&unsigned, now()),
+            Err(Error::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(Token::parse("a.b"), Err(Error::Malformed(_))));
+    }
+
+    #[test]
+    fn default_role() {
+        let signer = Signer::new();
+        let config = config::Oidc {
+            default_role: Some(Role::Viewer),
+            ..config()
+        };
+        let oidc = Oidc::new(config).with_keys(signer.jwks("k1"));
+        let token = signer.sign("k1", claims("bob", &[]));
+        let identity = oidc
+            .validate(&Token::parse(&token).unwrap(), now())
+            .unwrap();
+        assert_eq!(identity.role, Role::Viewer);
+        assert_eq!(identity.tenant, None);
+        let mut claims = claims("bob", &[]);
+        claims["tenant"] = json!(["basic", "other"]);
+        let token = signer.sign("k1", claims);
+        let tenant = oidc
+            .validate(&Token::parse(&token).unwrap(), now())
+            .unwrap()
+            .tenant;
+        assert_eq!(tenant.as_deref(), Some("basic"));
+        assert_ne!(
+            identity.client_hash(),
+            Identity {
+                subject: "alice".to_string(),
+                ..identity.clone()
+            }
+            .client_hash()
+        );
+    }
+
+    #[test]
+    fn bearer() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(AUTHORIZATION, "Bearer abc.def.ghi".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("abc.def.ghi"));
+        headers.insert(AUTHORIZATION, "Basic YWxpY2U6c2VjcmV0".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+    }
+}
@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Avro single object encoding of the published messages.
+//!
+//! The schemas are stored in their parsing canonical form, the fingerprint of the header is
+//! calculated from them. Keep them in sync with the encoding below.
+
+use scannerlib::models;
+
+use super::Event;
+
+const RESULT_SCHEMA: &str = include_str!("result.avsc");
+const EVENT_SCHEMA: &str = include_str!("event.avsc");
+
+/// Marker of the single object encoding
+const MARKER: [u8; 2] = [0xc3, 0x01];
+const EMPTY: u64 = 0xc15d213aa4d7a795;
+
+/// Returns the CRC-64-AVRO fingerprint of a schema.
+fn fingerprint(schema: &str) -> u64 {
+    let mut table = [0u64; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut fp = i as u64;
+        for _ in 0..8 {
+            fp = (fp >> 1) ^ (EMPTY & (fp & 1).wrapping_neg());
+        }
+        *entry = fp;
+    }
+    schema.trim_end().bytes().fold(EMPTY, |fp, b| {
+        (fp >> 8) ^ table[((fp ^ b as u64) & 0xff) as usize]
+    })
+}
+
+/// Writes the body of a single object encoded message
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn new(schema: &str) -> Self {
+        let mut buf = MARKER.to_vec();
+        buf.extend_from_slice(&fingerprint(schema).to_le_bytes());
+        Self(buf)
+    }
+
+    /// Writes an int or long as zigzag encoded varint
+    fn long(&mut self, value: i64) {
+        let mut n = ((value << 1) ^ (value >> 63)) as u64;
+        while n >= 0x80 {
+            self.0.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.0.push(n as u8);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.long(value.len() as i64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    /// Writes the union of null and the value
+    fn optional<T>(&mut self, value: Option<T>, f: impl FnOnce(&mut Self, T)) {
+        match value {
+            None => self.long(0),
+            Some(x) => {
+                self.long(1);
+                f(self, x);
+            }
+        }
+    }
+}
+
+pub fn result(scan_id: &str, result: &models::Result) -> Vec<u8> {
+    use models::ResultType;
+    let mut w = Writer::new(RESULT_SCHEMA);
+    w.string(scan_id);
+    w.long(result.id as i64);
+    w.long(match result.r_type {
+        ResultType::Alarm => 0,
+        ResultType::Log => 1,
+        ResultType::Error => 2,
+        ResultType::HostStart => 3,
+        ResultType::HostEnd => 4,
+        ResultType::DeadHost => 5,
+        ResultType::HostDetail => 6,
+    });
+    w.optional(result.ip_address.as_deref(), Writer::string);
+    w.optional(result.hostname.as_deref(), Writer::string);
+    w.optional(result.oid.as_deref(), Writer::string);
+    w.optional(result.port, |w, x| w.long(x.into()));
+    w.optional(result.protocol, |w, x| {
+        w.string(match x {
+            models::Protocol::TCP => "tcp",
+            models::Protocol::UDP => "udp",
+        })
+    });
+    w.optional(result.message.as_deref(), Writer::string);
+    w.optional(result.qod, |w, x| w.long(x.into()));
+    w.optional(result.detail.as_ref(), |w, x| {
+        w.string(&x.name);
+        w.string(&x.value);
+        w.string(&x.source.s_type);
+        w.string(&x.source.name);
+        w.string(&x.source.description);
+    });
+    w.0
+}
+
+pub fn event(event: &Event) -> Vec<u8> {
+    let mut w = Writer::new(EVENT_SCHEMA);
+    w.string(&event.scan_id);
+    // the symbols are in the order of the variants
+    w.long(event.event as i64);
+    w.optional(event.start_time, |w, x| w.long(x as i64));
+    w.optional(event.end_time, |w, x| w.long(x as i64));
+    w.long(event.timestamp as i64);
+    w.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher::EventType;
+
+    #[test]
+    fn zigzag() {
+        let encode = |x| {
+            let mut w = Writer(vec![]);
+            w.long(x);
+            w.0
+        };
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(-1), [0x01]);
+        assert_eq!(encode(1), [0x02]);
+        assert_eq!(encode(-64), [0x7f]);
+        assert_eq!(encode(64), [0x80, 0x01]);
+        assert_eq!(encode(300), [0xd8, 0x04]);
+    }
+
+    #[test]
+    fn header() {
+        let encoded = event(&Event {
+            scan_id: "42".into(),
+            event: EventType::Succeeded,
+            start_time: Some(1),
+            end_time: None,
+            timestamp: 2,
+        });
+        assert_eq!(encoded[..2], MARKER);
+        assert_eq!(encoded[2..10], fingerprint(EVENT_SCHEMA).to_le_bytes());
+        assert_ne!(fingerprint(EVENT_SCHEMA), fingerprint(RESULT_SCHEMA));
+        assert_eq!(encoded[10..], [4, b'4', b'2', 10, 2, 2, 0, 4]);
+    }
+
+    #[test]
+    fn schemas_are_valid_json() {
+        for schema in [RESULT_SCHEMA, EVENT_SCHEMA] {
+            serde_json::from_str::<serde_json::Value>(schema).unwrap();
+        }
+    }
+}
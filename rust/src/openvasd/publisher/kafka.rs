@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Produces Kafka records via the v2 API of the Confluent REST proxy.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Error;
+
+const CONTENT_TYPE: &str = "application/vnd.kafka.binary.v2+json";
+
+#[derive(Deserialize)]
+struct Offset {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Produced {
+    #[serde(default)]
+    offsets: Vec<Offset>,
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+}
+
+impl Client {
+    pub fn new(url: &str, headers: &BTreeMap<String, String>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url: url.trim_end_matches('/').to_string(),
+            headers: headers.clone(),
+        }
+    }
+
+    /// Produces the payloads as records with the key to the topic.
+    pub async fn publish(&self, topic: &str, key: &str, payloads: &[Vec<u8>]) -> Result<(), Error> {
+        let key = STANDARD.encode(key);
+        let records: Vec<_> = payloads
+            .iter()
+            .map(|x| json!({"key": key, "value": STANDARD.encode(x)}))
+            .collect();
+        let body = json!({ "records": records });
+        let mut request = self
+            .http
+            .post(format!("{}/topics/{topic}", self.url))
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+            .body(body.to_string());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let produced: Produced = request.send().await?.error_for_status()?.json().await?;
+        match produced.offsets.into_iter().find_map(|x| x.error) {
+            Some(error) => Err(Error::Rejected(error)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Publishes results and scan lifecycle events to a message queue.
+//!
+//! Results are published to `<prefix>.results` and events to `<prefix>.events`, either as JSON or
+//! in the Avro single object encoding of the schemas [result.avsc](result.avsc) and
+//! [event.avsc](event.avsc). Kafka messages are keyed by the scan id.
+
+mod avro;
+mod kafka;
+mod nats;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use scannerlib::models::{self, Phase};
+use serde::Serialize;
+
+use crate::config::{self, Broker, PublishFormat};
+
+#[cfg(test)]
+pub use nats::tests::server as nats_server;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to connect to the message queue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unable to reach the message queue: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid url of the message queue: {0}")]
+    InvalidUrl(String),
+    #[error("message queue rejected the messages: {0}")]
+    Rejected(String),
+}
+
+/// Type of a scan lifecycle event, the phase the scan changed to or its deletion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    Stored,
+    Requested,
+    Running,
+    Stopped,
+    Failed,
+    Succeeded,
+    Paused,
+    Partial,
+    Deleted,
+}
+
+impl From<&Phase> for EventType {
+    fn from(value: &Phase) -> Self {
+        match value {
+            Phase::Stored => Self::Stored,
+            Phase::Requested => Self::Requested,
+            Phase::Running => Self::Running,
+            Phase::Stopped => Self::Stopped,
+            Phase::Failed => Self::Failed,
+            Phase::Succeeded => Self::Succeeded,
+            Phase::Paused => Self::Paused,
+            Phase::Partial => Self::Partial,
+        }
+    }
+}
+
+/// Lifecycle event of a scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    pub scan_id: String,
+    pub event: EventType,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Seconds since the epoch the event was published at
+    pub timestamp: u64,
+}
+
+impl Event {
+    pub fn new(scan_id: &str, event: EventType, status: Option<&models::Status>) -> Self {
+        Self {
+            scan_id: scan_id.to_string(),
+            event,
+            start_time: status.and_then(|x| x.start_time),
+            end_time: status.and_then(|x| x.end_time),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Result together with the scan it belongs to
+#[derive(Serialize)]
+struct ScanResult<'a> {
+    scan_id: &'a str,
+    #[serde(flatten)]
+    result: &'a models::Result,
+}
+
+enum Transport {
+    /// Connection to the NATS server, it is established on the next publish after a failure
+    Nats(Option<nats::Client>),
+    Kafka(kafka::Client),
+}
+
+/// Publisher of the configured message queue
+pub struct Publisher {
+    url: String,
+    format: PublishFormat,
+    results: String,
+    events: String,
+    transport: Transport,
+}
+
+impl Publisher {
+    /// Returns the publisher or none when no url is configured.
+    pub fn new(config: &config::Publisher) -> Option<Self> {
+        let url = config.url.clone()?;
+        let transport = match config.broker {
+            Broker::Nats => Transport::Nats(None),
+            Broker::Kafka => Transport::Kafka(kafka::Client::new(&url, &config.headers)),
+        };
+        Some(Self {
+            url,
+            format: config.format,
+            results: format!("{}.results", config.prefix),
+            events: format!("{}.events", config.prefix),
+            transport,
+        })
+    }
+
+    /// Publishes the results of a scan.
+    pub async fn results(
+        &mut self,
+        scan_id: &str,
+        results: &[models::Result],
+    ) -> Result<(), Error> {
+        if results.is_empty() {
+            return Ok(());
+        }
+        let payloads = results
+            .iter()
+            .map(|result| match self.format {
+                PublishFormat::Json => {
+                    serde_json::to_vec(&ScanResult { scan_id, result }).unwrap_or_default()
+                }
+                PublishFormat::Avro => avro::result(scan_id, result),
+            })
+            .collect();
+        let subject = self.results.clone();
+        self.send(&subject, scan_id, payloads).await
+    }
+
+    /// Publishes a lifecycle event of a scan.
+    pub async fn event(&mut self, event: &Event) -> Result<(), Error> {
+        let payload = match self.format {
+            PublishFormat::Json => serde_json::to_vec(event).unwrap_or_default(),
+            PublishFormat::Avro => avro::event(event),
+        };
+        let subject = self.events.clone();
+        self.send(&subject, &event.scan_id, vec![payload]).await
+    }
+
+    async fn send(
+        &mut self,
+        subject: &str,
+        key: &str,
+        payloads: Vec<Vec<u8>>,
+    ) -> Result<(), Error> {
+        match &mut self.transport {
+            Transport::Nats(connection) => {
+                let client = match connection {
+                    Some(client) => client,
+                    None => connection.insert(nats::Client::connect(&self.url).await?),
+                };
+                let published = client.publish(subject, &payloads).await;
+                if published.is_err() {
+                    *connection = None;
+                }
+                published
+            }
+            Transport::Kafka(client) => client.publish(subject, key, &payloads).await,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Minimal client of the NATS protocol that only publishes messages.
+//!
+//! Each batch of messages is followed by a PING, the batch is published once the server answered
+//! with PONG. TLS is not supported.
+
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    net::TcpStream,
+};
+
+use super::Error;
+
+const DEFAULT_PORT: u16 = 4222;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Client {
+    stream: BufReader<TcpStream>,
+}
+
+impl Client {
+    /// Connects to nats://[user:password@]host[:port].
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        if parsed.scheme() != "nats" {
+            return Err(Error::InvalidUrl(format!("{url} is not a nats:// url")));
+        }
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| Error::InvalidUrl(format!("{url} contains no host")))?;
+        let port = parsed.port().unwrap_or(DEFAULT_PORT);
+        let stream = tokio::time::timeout(TIMEOUT, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| timeout())??;
+        let mut client = Self {
+            stream: BufReader::new(stream),
+        };
+        let info = client.read_line().await?;
+        if !info.starts_with("INFO ") {
+            return Err(Error::Rejected(format!("unexpected greeting {info}")));
+        }
+        let mut connect = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": "openvasd",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol": 1,
+        });
+        if !parsed.username().is_empty() {
+            connect["user"] = urlencoding::decode(parsed.username())
+                .map(|x| x.into_owned())
+                .unwrap_or_default()
+                .into();
+            connect["pass"] = urlencoding::decode(parsed.password().unwrap_or_default())
+                .map(|x| x.into_owned())
+                .unwrap_or_default()
+                .into();
+        }
+        let command = format!("CONNECT {connect}\r\n");
+        client
+            .stream
+            .get_mut()
+            .write_all(command.as_bytes())
+            .await?;
+        client.flush().await?;
+        Ok(client)
+    }
+
+    /// Publishes the payloads to the subject.
+    pub async fn publish(&mut self, subject: &str, payloads: &[Vec<u8>]) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        for payload in payloads {
+            buf.extend_from_slice(format!("PUB {subject} {}\r\n", payload.len()).as_bytes());
+            buf.extend_from_slice(payload);
+            buf.extend_from_slice(b"\r\n");
+        }
+        self.stream.get_mut().write_all(&buf).await?;
+        self.flush().await
+    }
+
+    /// Sends a PING and waits for the PONG, errors of the server are returned.
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.stream.get_mut().write_all(b"PING\r\n").await?;
+        loop {
+            let line = self.read_line().await?;
+            match line.as_str() {
+                "PONG" => return Ok(()),
+                "PING" => self.stream.get_mut().write_all(b"PONG\r\n").await?,
+                x if x.starts_with("-ERR") => {
+                    return Err(Error::Rejected(x.trim_start_matches("-ERR").trim().into()))
+                }
+                // +OK and INFO updates of the cluster
+                _ => {}
+            }
+        }
+    }
+
+    async fn read_line(&mut self) -> Result<String, Error> {
+        let mut line = String::new();
+        let read = tokio::time::timeout(TIMEOUT, self.stream.read_line(&mut line))
+            .await
+            .map_err(|_| timeout())??;
+        if read == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+fn timeout() -> Error {
+    std::io::Error::from(std::io::ErrorKind::TimedOut).into()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::*;
+
+    /// Starts a NATS server accepting a single connection that sends each published subject and
+    /// payload to the returned receiver.
+    pub async fn server() -> (String, mpsc::UnboundedReceiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            stream
+                .get_mut()
+                .write_all(b"INFO {\"server_id\":\"test\"}\r\n")
+                .await
+                .unwrap();
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap_or_default() == 0 {
+                    return;
+                }
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                match parts.as_slice() {
+                    ["PING"] => stream.get_mut().write_all(b"PONG\r\n").await.unwrap(),
+                    ["PUB", subject, len] => {
+                        let mut payload = vec![0; len.parse::<usize>().unwrap() + 2];
+                        stream.read_exact(&mut payload).await.unwrap();
+                        payload.truncate(payload.len() - 2);
+                        let _ = sender.send((subject.to_string(), payload));
+                    }
+                    _ => {}
+                }
+            }
+        });
+        (url, receiver)
+    }
+
+    #[tokio::test]
+    async fn publish() {
+        let (url, mut received) = server().await;
+        let mut client = Client::connect(&url).await.unwrap();
+        client
+            .publish("openvasd.results", &[b"a".to_vec(), b"b\r\nc".to_vec()])
+            .await
+            .unwrap();
+        assert_eq!(
+            received.recv().await.unwrap(),
+            ("openvasd.results".to_string(), b"a".to_vec())
+        );
+        assert_eq!(received.recv().await.unwrap().1, b"b\r\nc");
+    }
+
+    #[tokio::test]
+    async fn invalid_url() {
+        assert!(matches!(
+            Client::connect("http://localhost").await,
+            Err(Error::InvalidUrl(_))
+        ));
+    }
+}
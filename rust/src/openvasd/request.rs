@@ -11,6 +11,21 @@ pub struct BadRequest {
     message: String,
 }
 
+/// Returns the raw body of a request
+pub async fn bytes_request<H>(
+    response: &crate::response::Response,
+    req: hyper::Request<H>,
+) -> Result<hyper::body::Bytes, crate::response::Result>
+where
+    H: hyper::body::Body,
+    <H as hyper::body::Body>::Error: std::error::Error,
+{
+    match req.into_body().collect().await {
+        Ok(x) => Ok(x.to_bytes()),
+        Err(e) => Err(response.internal_server_error(&e)),
+    }
+}
+
 pub async fn json_request<T, H>(
     response: &crate::response::Response,
     req: hyper::Request<H>,
@@ -20,13 +35,7 @@ where
     H: hyper::body::Body,
     <H as hyper::body::Body>::Error: std::error::Error,
 {
-    let body = req.into_body();
-    let bytes = match body.collect().await {
-        Ok(x) => x.to_bytes(),
-        Err(e) => {
-            return Err(response.internal_server_error(&e));
-        }
-    };
+    let bytes = bytes_request(response, req).await?;
     match serde_json::from_slice(&bytes) {
         Ok(json) => Ok(json),
         Err(e) => Err(response.bad_request(&BadRequest {
@@ -5,14 +5,20 @@
 use std::{
     convert::Infallible,
     error::Error,
+    hash::{DefaultHasher, Hash, Hasher},
     pin::Pin,
     sync::{mpsc::Receiver, Arc},
     task::Poll,
     thread,
+    time::{Duration, SystemTime},
 };
 
 use http_body::Body;
-use hyper::body::Bytes;
+use hyper::{
+    body::Bytes,
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    HeaderMap,
+};
 use serde::Serialize;
 pub type Result = hyper::Response<BodyKind>;
 
@@ -58,6 +64,12 @@ pub enum BodyKind {
     /// self.ok_json_response(BodyKind::BinaryStream(rx))
     /// ```
     BinaryStream(Receiver<SendState>),
+    /// Chunks, e.g. server-sent events, each received chunk is send as is until the sender is
+    /// dropped.
+    ///
+    /// Other than [BodyKind::BinaryStream] the receiver is polled asynchronously so that the
+    /// stream can wait for new data without blocking.
+    EventStream(tokio::sync::mpsc::Receiver<Bytes>),
 }
 
 #[derive(Debug)]
@@ -81,7 +93,7 @@ impl Body for BodyKind {
     fn is_end_stream(&self) -> bool {
         match self {
             BodyKind::Empty => true,
-            BodyKind::BinaryStream(..) | BodyKind::Binary(_) => false,
+            BodyKind::BinaryStream(..) | BodyKind::EventStream(..) | BodyKind::Binary(_) => false,
         }
     }
 
@@ -90,14 +102,16 @@ impl Body for BodyKind {
             BodyKind::Empty => http_body::SizeHint::with_exact(0),
             BodyKind::Binary(b) => http_body::SizeHint::with_exact(b.len() as u64),
             // we don't know
-            BodyKind::BinaryStream(..) => http_body::SizeHint::default(),
+            BodyKind::BinaryStream(..) | BodyKind::EventStream(..) => {
+                http_body::SizeHint::default()
+            }
         }
     }
 
     #[inline]
     fn poll_frame(
         self: Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<std::result::Result<http_body::Frame<Self::Data>, Self::Error>>> {
         let kind = self.get_mut();
 
@@ -133,10 +147,109 @@ impl Body for BodyKind {
                     }
                 }
             }),
+            BodyKind::EventStream(rec) => match rec.poll_recv(cx) {
+                Poll::Ready(Some(b)) => Poll::Ready(Some(Ok(http_body::Frame::data(b)))),
+                Poll::Ready(None) => {
+                    *kind = BodyKind::Empty;
+                    Poll::Ready(None)
+                }
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+/// Validators of a representation used to answer conditional requests.
+///
+/// The entity tag is weak as it is derived from the version of the stored data rather than from
+/// the bytes of the body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    etag: String,
+    last_modified: Option<u64>,
+}
+
+impl Validators {
+    /// Creates validators with an entity tag derived from the given version.
+    pub fn new<T: Hash>(version: T) -> Self {
+        let mut hasher = DefaultHasher::new();
+        version.hash(&mut hasher);
+        Self {
+            etag: format!("W/\"{:016x}\"", hasher.finish()),
+            last_modified: None,
+        }
+    }
+
+    /// Sets the last modification in seconds since epoch.
+    pub fn last_modified(mut self, last_modified: Option<u64>) -> Self {
+        self.last_modified = last_modified;
+        self
+    }
+
+    /// Returns the entity tag.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Returns true when the client already has the current representation.
+    ///
+    /// If-Modified-Since is ignored when If-None-Match is given.
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(tags) = headers.get(IF_NONE_MATCH) {
+            let opaque = |x: &str| x.trim().trim_start_matches("W/").to_string();
+            let etag = opaque(&self.etag);
+            return tags
+                .to_str()
+                .unwrap_or_default()
+                .split(',')
+                .map(opaque)
+                .any(|x| x == "*" || x == etag);
+        }
+        match (self.last_modified, headers.get(IF_MODIFIED_SINCE)) {
+            (Some(last_modified), Some(since)) => since
+                .to_str()
+                .ok()
+                .and_then(|x| httpdate::parse_http_date(x).ok())
+                .and_then(|x| x.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .is_some_and(|x| last_modified <= x.as_secs()),
+            _ => false,
+        }
+    }
+
+    /// Adds the ETag and Last-Modified header to a successful or not modified response.
+    pub fn apply(&self, mut response: Result) -> Result {
+        let status = response.status();
+        if !status.is_success() && status != hyper::StatusCode::NOT_MODIFIED {
+            return response;
         }
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            let date = SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified);
+            if let Ok(date) = HeaderValue::from_str(&httpdate::fmt_http_date(date)) {
+                headers.insert(LAST_MODIFIED, date);
+            }
+        }
+        response
     }
 }
 
+/// Formats a server-sent event.
+///
+/// The data must not contain line breaks, which is the case for serialized json.
+pub fn event(id: Option<usize>, name: &str, data: &[u8]) -> Bytes {
+    let mut event = Vec::with_capacity(data.len() + name.len() + 32);
+    if let Some(id) = id {
+        event.extend_from_slice(format!("id: {id}\n").as_bytes());
+    }
+    event.extend_from_slice(format!("event: {name}\ndata: ").as_bytes());
+    event.extend_from_slice(data);
+    event.extend_from_slice(b"\n\n");
+    event.into()
+}
+
 impl Response {
     /// Sets the version of the response header.
     pub fn set_feed_version(&mut self, feed_version: &str) {
@@ -172,9 +285,14 @@ impl Response {
 
     #[inline]
     fn ok_json_response(&self, body: BodyKind) -> Result {
+        self.ok_response("application/json", body)
+    }
+
+    #[inline]
+    fn ok_response(&self, content_type: &str, body: BodyKind) -> Result {
         match self
             .default_response_builder()
-            .header("Content-Type", "application/json")
+            .header("Content-Type", content_type)
             .status(hyper::StatusCode::OK)
             .body(body)
         {
@@ -230,6 +348,35 @@ impl Response {
         self.ok_json_response(BodyKind::BinaryStream(rx))
     }
 
+    /// Returns a stream of server-sent events, see [event], until the sender is dropped.
+    pub fn ok_event_stream(&self, events: tokio::sync::mpsc::Receiver<Bytes>) -> Result {
+        match self
+            .default_response_builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .status(hyper::StatusCode::OK)
+            .body(BodyKind::EventStream(events))
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::error!("Error creating response: {}", e);
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(BodyKind::Empty)
+                    .unwrap()
+            }
+        }
+    }
+
+    /// Sends the received chunks as is until the sender is dropped.
+    pub fn ok_stream(
+        &self,
+        content_type: &str,
+        chunks: tokio::sync::mpsc::Receiver<Bytes>,
+    ) -> Result {
+        self.ok_response(content_type, BodyKind::EventStream(chunks))
+    }
+
     #[inline]
     pub async fn ok_json_stream<T, S>(&self, value: T) -> Result
     where
@@ -284,6 +431,11 @@ impl Response {
         self.ok_json_response(BodyKind::Binary(value.to_vec().into()))
     }
 
+    /// Returns a document of the given media type, e.g. a rendered report
+    pub fn ok_document(&self, content_type: &str, value: Vec<u8>) -> Result {
+        self.ok_response(content_type, BodyKind::Binary(value.into()))
+    }
+
     pub fn created<T>(&self, value: &T) -> Result
     where
         T: ?Sized + Serialize + std::fmt::Debug,
@@ -301,10 +453,18 @@ impl Response {
         self.empty(hyper::StatusCode::NO_CONTENT)
     }
 
+    pub fn not_modified(&self, validators: &Validators) -> Result {
+        validators.apply(self.empty(hyper::StatusCode::NOT_MODIFIED))
+    }
+
     pub fn unauthorized(&self) -> Result {
         self.empty(hyper::StatusCode::UNAUTHORIZED)
     }
 
+    pub fn forbidden(&self) -> Result {
+        self.empty(hyper::StatusCode::FORBIDDEN)
+    }
+
     pub fn internal_server_error(&self, err: &dyn Error) -> Result {
         tracing::error!("Unexpected error: {}", err);
         self.empty(hyper::StatusCode::INTERNAL_SERVER_ERROR)
@@ -2,22 +2,38 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 
 use crate::storage::{Error as StorageError, FeedHash, Storage};
 use async_trait::async_trait;
+use scannerlib::models::cvss::SeverityClass;
+use scannerlib::models::resources::check::Checker;
 use scannerlib::models::scanner::Error as ScanError;
+use scannerlib::models::scanner::ObservableResources;
 use scannerlib::models::scanner::{ScanResultFetcher, ScanResults, ScanStopper};
-use scannerlib::models::{Phase, Scan, Status};
+use scannerlib::models::{
+    self, Annotation, Imported, Lineage, Phase, Rescan, ResultType, Scan, ScanPlan, Status,
+};
+use scannerlib::redact;
 use scannerlib::storage::item::Nvt;
-use tokio::sync::RwLock;
+use scannerlib::storage::KbSnapshot;
+use tokio::sync::{watch, RwLock};
+use tracing::Instrument as _;
 
 use crate::{
-    config,
+    blackout, bundle, config,
     controller::ClientHash,
-    storage::{AppendFetchResult, NVTStorer, ProgressGetter, ScanIDClientMapper, ScanStorer},
+    secrets::Secrets,
+    storage::{
+        AnnotationStorer, AppendFetchResult, NVTStorer, ProgressGetter, ScanIDClientMapper,
+        ScanStorer,
+    },
+    tenancy::FeedSubset,
+    triage::Triage,
 };
 
 #[derive(Debug)]
@@ -38,6 +54,14 @@ pub enum Error {
     UnsupportedResume,
     /// A scan ins already finished
     AlreadyFinished,
+    /// Resources are below their thresholds and the scan is refused
+    InsufficientResources(Vec<ObservableResources>),
+    /// Operation requires a finished scan
+    NotFinished,
+    /// The scan contains VTs that are unknown or not part of the feed subset of the client
+    VtsNotAvailable(Vec<String>),
+    /// The hosts or VTs to scan again are not part of the scan
+    InvalidRescan(String),
 }
 
 impl Display for Error {
@@ -53,6 +77,19 @@ impl Display for Error {
                 write!(f, "unable to resume scan: operation not supported")
             }
             Error::AlreadyFinished => write!(f, "unable to resume scan: scan already finished"),
+            Error::NotFinished => write!(f, "scan is not finished"),
+            Error::InvalidRescan(reason) => write!(f, "invalid rescan: {reason}"),
+            Error::VtsNotAvailable(oids) => {
+                write!(f, "VTs are not available: {}", oids.join(","))
+            }
+            Error::InsufficientResources(resources) => {
+                let resources: Vec<String> = resources.iter().map(|x| x.to_string()).collect();
+                write!(
+                    f,
+                    "unable to start scan: not enough resources of types: {}",
+                    resources.join(",")
+                )
+            }
         }
     }
 }
@@ -107,12 +144,31 @@ pub struct Scheduler<DB, Scanner> {
     config: config::Scheduler,
     /// Feed version shared with response.
     feed_version: Arc<std::sync::RwLock<String>>,
+    /// Counts the uploads of VTs, is part of the version of the VT metadata.
+    vts_uploads: AtomicUsize,
+    /// Notifies result streams that results may have been appended.
+    results_appended: watch::Sender<()>,
+    /// Trace contexts of the requests that queued a scan, used to link the start of a scan.
+    traceparents: RwLock<HashMap<String, String>>,
+    /// Verifies that resources are available before starting scans or feed updates.
+    resources: Checker,
+    /// Resolves secret references within the credentials of scans before they are started.
+    secrets: Secrets,
+    /// Keeps the secrets of the credentials of running scans masked in logs and results.
+    redactions: RwLock<HashMap<String, redact::Registration>>,
+    /// Auto-triage rules applied to results before they are stored.
+    triage: Triage,
+    /// Amount of stored results of running scans whose scanner stores the results itself.
+    result_counts: RwLock<HashMap<String, usize>>,
 }
 
 impl<DB, Scanner> Scheduler<DB, Scanner> {
     pub fn new(config: config::Scheduler, scanner: Scanner, db: DB) -> Self {
         let assumed_queued = config.max_queued_scans.unwrap_or(10);
         let assumed_running = config.max_running_scans.unwrap_or(10);
+        let resources = Checker::new(config.min_free_mem, None)
+            .with_disk(config.min_free_disk, config.disk_paths.clone())
+            .with_file_descriptors(config.min_free_file_descriptors);
         Self {
             queued: RwLock::new(Vec::with_capacity(assumed_queued)),
             running: RwLock::new(Vec::with_capacity(assumed_running)),
@@ -121,9 +177,29 @@ impl<DB, Scanner> Scheduler<DB, Scanner> {
             config,
             is_synchronizing_feed: RwLock::new(false),
             feed_version: Arc::new(std::sync::RwLock::new(String::from("UNDEFINED"))),
+            vts_uploads: AtomicUsize::new(0),
+            results_appended: watch::channel(()).0,
+            traceparents: RwLock::new(HashMap::new()),
+            resources,
+            secrets: Secrets::default(),
+            redactions: RwLock::new(HashMap::new()),
+            triage: Triage::default(),
+            result_counts: RwLock::new(HashMap::new()),
         }
     }
 
+    /// Sets the resolver of secret references.
+    pub fn with_secrets(mut self, secrets: Secrets) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Sets the auto-triage rules applied to results before they are stored.
+    pub fn with_triage(mut self, triage: Triage) -> Self {
+        self.triage = triage;
+        self
+    }
+
     pub fn config(&self) -> &config::Scheduler {
         &self.config
     }
@@ -131,6 +207,19 @@ impl<DB, Scanner> Scheduler<DB, Scanner> {
     pub fn feed_version(&self) -> Arc<std::sync::RwLock<String>> {
         self.feed_version.clone()
     }
+
+    /// Returns the resources that are below their configured thresholds.
+    ///
+    /// New scans and feed updates are only started when it is empty.
+    pub fn insufficient_resources(&self) -> Vec<ObservableResources> {
+        self.resources.breakaways()
+    }
+
+    /// Returns a receiver that is marked as changed each time results or status of scans got
+    /// fetched from the scanner.
+    pub fn subscribe_results(&self) -> watch::Receiver<()> {
+        self.results_appended.subscribe()
+    }
 }
 
 impl<DB, Scanner> Scheduler<DB, Scanner>
@@ -148,10 +237,10 @@ where
         let mut status = self.get_status(id).await?;
         match status.status {
             Phase::Stored => status.status = Phase::Requested,
-            Phase::Requested => return Err(Error::ScanAlreadyQueued),
+            Phase::Requested | Phase::Paused => return Err(Error::ScanAlreadyQueued),
             Phase::Running => return Err(Error::ScanRunning),
             Phase::Stopped | Phase::Failed => return Err(Error::UnsupportedResume),
-            Phase::Succeeded => return Err(Error::AlreadyFinished),
+            Phase::Succeeded | Phase::Partial => return Err(Error::AlreadyFinished),
         }
 
         let mut queued = self.queued.write().await;
@@ -163,11 +252,356 @@ where
         if queued.iter().any(|x| x == id) {
             return Err(Error::ScanAlreadyQueued);
         }
+        if self.config().resource_policy == config::ResourcePolicy::Refuse {
+            let insufficient = self.insufficient_resources();
+            if !insufficient.is_empty() {
+                return Err(Error::InsufficientResources(insufficient));
+            }
+        }
         self.update_status(id, status).await?;
         queued.push(id.to_string());
+        if let Some(traceparent) = crate::telemetry::traceparent() {
+            self.traceparents
+                .write()
+                .await
+                .insert(id.to_string(), traceparent);
+        }
         Ok(())
     }
 
+    /// Returns an error listing the VTs that are unknown or not part of the feed subset.
+    pub async fn check_feed_subset(
+        &self,
+        vts: &[models::VT],
+        subset: &FeedSubset,
+    ) -> Result<(), Error> {
+        let filter = crate::storage::VtFilter {
+            deprecated: true,
+            subset: Some(subset.clone()),
+            ..Default::default()
+        };
+        let allowed: HashSet<String> = self.db.vts_filtered(filter).await?.map(|x| x.oid).collect();
+        let denied: Vec<String> = vts
+            .iter()
+            .filter(|x| !allowed.contains(&x.oid))
+            .map(|x| x.oid.clone())
+            .collect();
+        if denied.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::VtsNotAvailable(denied))
+        }
+    }
+
+    /// Starts the scan when all of its VTs are part of the feed subset of the client.
+    pub async fn start_scan_within(
+        &self,
+        id: &str,
+        subset: Option<&FeedSubset>,
+    ) -> Result<(), Error> {
+        if let Some(subset) = subset {
+            let (scan, _) = self.get_scan(id).await?;
+            self.check_feed_subset(&scan.vts, subset).await?;
+        }
+        self.start_scan_by_id(id).await
+    }
+
+    /// Stores a copy of the scan under a new id and returns that id.
+    ///
+    /// The copy records the scan it was cloned from and continues its series of runs. Only the
+    /// definition of the scan is copied, neither its status nor its results.
+    pub async fn clone_scan_by_id(
+        &self,
+        id: &str,
+        client_id: &ClientHash,
+    ) -> Result<String, Error> {
+        let (scan, _) = self.get_decrypted_scan(id).await?;
+        self.store_clone(id, scan, None, client_id).await
+    }
+
+    /// Stores a copy of a finished scan limited to the given hosts and VTs and returns its id.
+    ///
+    /// The copy continues the series of runs like a clone. The results of the scan outside of the
+    /// subset are carried over, so that the rescan contains the results of the whole scan once it
+    /// finished. Hosts must be part of the target or have results within the scan, VTs must be
+    /// part of the scan.
+    pub async fn rescan_scan_by_id(
+        &self,
+        id: &str,
+        rescan: Rescan,
+        client_id: &ClientHash,
+    ) -> Result<String, Error> {
+        if rescan.hosts.is_empty() && rescan.vts.is_empty() {
+            return Err(Error::InvalidRescan("neither hosts nor VTs given".into()));
+        }
+        let (mut scan, status) = self.get_decrypted_scan(id).await?;
+        if !status.is_done() {
+            return Err(Error::NotFinished);
+        }
+        let results: Vec<models::Result> = self
+            .get_results(id, None, None)
+            .await?
+            .filter_map(|x| serde_json::from_slice(&x).ok())
+            .collect();
+        if !rescan.hosts.is_empty() {
+            let scanned: HashSet<&String> = results
+                .iter()
+                .flat_map(|x| [&x.ip_address, &x.hostname])
+                .flatten()
+                .collect();
+            let in_target = |patterns: &[String], host: &String| {
+                patterns.iter().any(|x| blackout::matches_host(x, host))
+            };
+            let unknown: Vec<&str> = rescan
+                .hosts
+                .iter()
+                .filter(|x| !scanned.contains(x) && !in_target(&scan.target.hosts, x))
+                .filter(|x| !scan.target.groups.iter().any(|g| in_target(&g.hosts, x)))
+                .map(|x| x.as_str())
+                .collect();
+            if !unknown.is_empty() {
+                return Err(Error::InvalidRescan(format!(
+                    "hosts are not part of the scan: {}",
+                    unknown.join(",")
+                )));
+            }
+            // hosts of a group keep the ports and credentials of their group
+            let (grouped, hosts): (Vec<_>, Vec<_>) = rescan.hosts.iter().cloned().partition(|x| {
+                !in_target(&scan.target.hosts, x)
+                    && scan.target.groups.iter().any(|g| in_target(&g.hosts, x))
+            });
+            for group in scan.target.groups.iter_mut() {
+                group.hosts = grouped
+                    .iter()
+                    .filter(|x| in_target(&group.hosts, x))
+                    .cloned()
+                    .collect();
+            }
+            scan.target.groups.retain(|x| !x.hosts.is_empty());
+            scan.target.hosts = hosts;
+        }
+        if !rescan.vts.is_empty() {
+            let unknown: Vec<&str> = rescan
+                .vts
+                .iter()
+                .filter(|x| !scan.vts.iter().any(|vt| &vt.oid == *x))
+                .map(|x| x.as_str())
+                .collect();
+            if !unknown.is_empty() {
+                return Err(Error::InvalidRescan(format!(
+                    "VTs are not part of the scan: {}",
+                    unknown.join(",")
+                )));
+            }
+            scan.vts.retain(|x| rescan.vts.contains(&x.oid));
+        }
+        let carried: Vec<models::Result> = results
+            .into_iter()
+            .filter(|x| !rescan.covers(x))
+            .map(|mut x| {
+                // the rules are applied again when the results are appended
+                x.triage = None;
+                x
+            })
+            .collect();
+        let rescan_id = self.store_clone(id, scan, Some(rescan), client_id).await?;
+        if !carried.is_empty() {
+            self.append_fetched_result(vec![ScanResults {
+                id: rescan_id.clone(),
+                status: Status::default(),
+                results: carried,
+            }])
+            .await?;
+        }
+        Ok(rescan_id)
+    }
+
+    async fn store_clone(
+        &self,
+        id: &str,
+        mut scan: Scan,
+        rescan: Option<Rescan>,
+        client_id: &ClientHash,
+    ) -> Result<String, Error> {
+        let root = match &scan.lineage {
+            Some(lineage) => lineage.root.clone(),
+            None => id.to_string(),
+        };
+        let run = self
+            .runs_of(&root, client_id)
+            .await?
+            .last()
+            .map(|(run, _)| run + 1)
+            .unwrap_or(2);
+        let clone_id = uuid::Uuid::new_v4().to_string();
+        scan.scan_id.clone_from(&clone_id);
+        // the clone is a new scan of this instance, even when the scan was imported
+        scan.imported = None;
+        scan.lineage = Some(Lineage {
+            parent: id.to_string(),
+            root,
+            run,
+            rescan,
+        });
+        self.insert_scan(scan).await?;
+        self.add_scan_client_id(clone_id.clone(), client_id.clone())
+            .await?;
+        Ok(clone_id)
+    }
+
+    /// Collects everything known about a finished scan to export it as bundle.
+    pub async fn export_scan_by_id(&self, id: &str) -> Result<bundle::Content, Error> {
+        let (mut scan, status) = self.get_scan(id).await?;
+        if !status.is_done() {
+            return Err(Error::NotFinished);
+        }
+        scan.target = scan
+            .target
+            .map_credentials::<_, StorageError>(|c| c.map_password(|_| Ok("***".to_string())))?;
+        let (logs, results) = self
+            .get_results(id, None, None)
+            .await?
+            .filter_map(|x| serde_json::from_slice::<models::Result>(&x).ok())
+            .partition::<Vec<_>, _>(|x| x.r_type != ResultType::Alarm);
+        let hosts: BTreeSet<String> = results
+            .iter()
+            .chain(&logs)
+            .filter_map(|x| x.ip_address.clone())
+            .chain(scan.target.hosts.iter().cloned())
+            .collect();
+        let mut kb = BTreeMap::new();
+        for host in hosts {
+            if let Some(snapshot) = self.get_kb_snapshot(id, &host).await? {
+                kb.insert(host, snapshot);
+            }
+        }
+        Ok(bundle::Content {
+            annotations: self.get_annotations(id).await?,
+            scan,
+            status,
+            results,
+            logs,
+            kb,
+            feed_version: self.feed_version.read().unwrap().clone(),
+            exported: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Stores the content of a bundle as a new scan of the client and returns its id.
+    ///
+    /// The scan keeps the finished status of the bundle so that it is not started again and
+    /// records its origin, which marks it as read-only.
+    pub async fn import_scan(
+        &self,
+        content: bundle::Content,
+        client_id: &ClientHash,
+    ) -> Result<String, Error> {
+        if !content.status.is_done() {
+            return Err(Error::NotFinished);
+        }
+        let results = content.all_results();
+        let bundle::Content {
+            mut scan,
+            status,
+            annotations,
+            kb,
+            feed_version,
+            exported,
+            ..
+        } = content;
+        let id = uuid::Uuid::new_v4().to_string();
+        scan.imported = Some(Imported {
+            scan_id: std::mem::replace(&mut scan.scan_id, id.clone()),
+            feed_version,
+            exported,
+        });
+        // the series of the exporting instance is unknown here
+        scan.lineage = None;
+        self.insert_scan(scan).await?;
+        self.add_scan_client_id(id.clone(), client_id.clone())
+            .await?;
+        self.append_fetched_result(vec![ScanResults {
+            id: id.clone(),
+            status,
+            results,
+        }])
+        .await?;
+        for annotation in annotations {
+            self.add_annotation(&id, annotation).await?;
+        }
+        for (host, snapshot) in kb {
+            self.store_kb_snapshot(&id, &host, snapshot).await?;
+        }
+        Ok(id)
+    }
+
+    /// Returns the runs and ids of the scans of the client that belong to the series of root,
+    /// ordered by their run.
+    pub async fn runs_of(
+        &self,
+        root: &str,
+        client_id: &ClientHash,
+    ) -> Result<Vec<(usize, String)>, Error> {
+        let mut runs = Vec::new();
+        for id in self.get_scans_of_client_id(client_id).await? {
+            let scan = match self.get_scan(&id).await {
+                Ok((scan, _)) => scan,
+                Err(StorageError::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            match scan.lineage {
+                Some(lineage) if lineage.root == root => runs.push((lineage.run, id)),
+                None if id == root => runs.push((1, id)),
+                _ => {}
+            }
+        }
+        runs.sort();
+        Ok(runs)
+    }
+
+    /// Returns the runs and ids of the series the scan belongs to, ordered by their run.
+    pub async fn series_of(
+        &self,
+        id: &str,
+        client_id: &ClientHash,
+    ) -> Result<Vec<(usize, String)>, Error> {
+        let (scan, _) = self.get_scan(id).await?;
+        let root = scan
+            .lineage
+            .map(|x| x.root)
+            .unwrap_or_else(|| id.to_string());
+        self.runs_of(&root, client_id).await
+    }
+
+    /// Resolves what the scan would execute without running it.
+    pub async fn plan_scan_by_id(&self, id: &str) -> Result<ScanPlan, Error> {
+        let (scan, _) = self.get_decrypted_scan(id).await?;
+        Ok(self.scanner.plan_scan(&scan).await?)
+    }
+
+    /// Returns the hashes of the loaded feeds as version of the VT metadata.
+    ///
+    /// Returns None while the feed is synchronized as the VTs may change until it is finished.
+    pub async fn vts_version(&self) -> Option<Vec<String>> {
+        let synchronizing = self
+            .is_synchronizing_feed
+            .try_read()
+            .map(|x| *x)
+            .unwrap_or(true);
+        if synchronizing {
+            return None;
+        }
+        let mut version: Vec<_> = self.feed_hash().await.into_iter().map(|x| x.hash).collect();
+        match self.vts_uploads.load(Ordering::Relaxed) {
+            0 => {}
+            uploads => version.push(format!("upload-{uploads}")),
+        }
+        Some(version)
+    }
+
     pub async fn delete_scan_by_id(&self, id: &str) -> Result<(), Error> {
         let mut queued = self.queued.write().await;
         if let Some(idx) = queued.iter().position(|x| x == id) {
@@ -180,6 +614,8 @@ where
             }
         }
 
+        self.traceparents.write().await.remove(id);
+        self.redactions.write().await.remove(id);
         self.db.remove_scan(id).await?;
         // TODO change from I to &str so that we don't have to clone everywhere
         self.db.remove_scan_id(id.to_string()).await?;
@@ -195,6 +631,17 @@ where
         }
         let config = self.config();
         let mut queued = self.queued.write().await;
+        if queued.is_empty() {
+            return Ok(());
+        }
+        let insufficient = self.insufficient_resources();
+        if !insufficient.is_empty() {
+            tracing::debug!(
+                ?insufficient,
+                "keeping scans queued until resources are available"
+            );
+            return Ok(());
+        }
         let mut running = self.running.write().await;
         let amount_to_start = if let Some(mrs) = config.max_running_scans {
             mrs - running.len()
@@ -203,15 +650,60 @@ where
         };
 
         tracing::trace!(%amount_to_start, "handling scans");
+        let now = chrono::Utc::now();
+        // scans within a blackout are put back in front so that they do not block others
+        let mut held = Vec::new();
         for _ in 0..amount_to_start {
             if let Some(scan_id) = queued.pop() {
-                let (scan, status) = self.db.get_decrypted_scan(&scan_id).await?;
+                let (mut scan, mut status) = self.db.get_decrypted_scan(&scan_id).await?;
+                if let Some(blackout) = blackout::active(&config.blackouts, &scan, now) {
+                    if status.status != Phase::Paused {
+                        tracing::info!(%scan_id, blackout = blackout.name, "holding back scan during blackout");
+                        status.status = Phase::Paused;
+                        self.db.update_status(&scan_id, status).await?;
+                    }
+                    held.push(scan_id);
+                    continue;
+                }
+                if status.status == Phase::Paused {
+                    self.exclude_finished_hosts(&mut scan).await?;
+                    status.status = Phase::Requested;
+                    self.db.update_status(&scan_id, status.clone()).await?;
+                }
+                // the resolved secrets are only handed to the scanner, never stored
+                let scan = match self.secrets.resolve_scan(scan).await {
+                    Ok(scan) => scan,
+                    Err(e) => {
+                        tracing::warn!(%scan_id, %e, "unable to resolve the secrets of the scan, set status to failed");
+                        status.status = Phase::Failed;
+                        self.db.update_status(&scan_id, status).await?;
+                        continue;
+                    }
+                };
+                let secrets = scan
+                    .target
+                    .credentials
+                    .iter()
+                    .chain(scan.target.groups.iter().flat_map(|x| x.credentials.iter()))
+                    .flat_map(|x| x.secrets());
+                let registration = redact::register(secrets);
+                self.redactions
+                    .write()
+                    .await
+                    .insert(scan_id.clone(), registration);
                 if !self.scanner.can_start_scan(&scan).await {
                     tracing::debug!(?status, %scan_id, "unable to start scan");
                     queued.push(scan_id);
                 } else {
                     tracing::debug!(?status, %scan_id, "starting scan");
-                    match self.scanner.start_scan(scan).await {
+                    let traceparent = self.traceparents.write().await.remove(&scan_id);
+                    let span = tracing::info_span!(
+                        "start_scan",
+                        otel.kind = "internal",
+                        scan_id = %scan_id,
+                        traceparent = traceparent.as_deref(),
+                    );
+                    match self.scanner.start_scan(scan).instrument(span).await {
                         Ok(_) => {
                             tracing::debug!(%scan_id, "started");
                             running.push(scan_id.clone());
@@ -226,10 +718,8 @@ where
                                 .update_status(
                                     &scan_id,
                                     Status {
-                                        start_time: None,
-                                        end_time: None,
                                         status: Phase::Failed,
-                                        host_info: None,
+                                        ..Default::default()
                                     },
                                 )
                                 .await?;
@@ -240,6 +730,62 @@ where
                 break;
             }
         }
+        queued.splice(0..0, held);
+        Ok(())
+    }
+
+    /// Stops running scans that entered a blackout and queues them again, they are resumed
+    /// once the blackout is over.
+    async fn park_scans(&self) -> Result<(), Error> {
+        let blackouts = &self.config().blackouts;
+        if blackouts.is_empty() {
+            return Ok(());
+        }
+        let now = chrono::Utc::now();
+        let mut queued = self.queued.write().await;
+        let mut running = self.running.write().await;
+        let mut parked = Vec::new();
+        for scan_id in running.iter() {
+            let (scan, _) = self.db.get_scan(scan_id).await?;
+            let Some(blackout) = blackout::active(blackouts, &scan, now) else {
+                continue;
+            };
+            // results that are not fetched yet may get lost when the scan is stopped
+            if let Ok(mut results) = self.fetch_results(scan_id.clone()).await {
+                for result in results.results.iter_mut() {
+                    scan.target.label_result(result);
+                }
+                self.db.append_fetched_result(vec![results]).await?;
+            }
+            if let Err(e) = self.scanner.stop_scan(scan_id.clone()).await {
+                tracing::warn!(%scan_id, %e, "unable to pause scan during blackout");
+                continue;
+            }
+            tracing::info!(%scan_id, blackout = blackout.name, "paused scan during blackout");
+            let mut status = self.db.get_status(scan_id).await?;
+            status.status = Phase::Paused;
+            self.db.update_status(scan_id, status).await?;
+            parked.push(scan_id.clone());
+        }
+        running.retain(|x| !parked.contains(x));
+        queued.splice(0..0, parked);
+        Ok(())
+    }
+
+    /// Excludes the hosts a paused scan already finished so that they are not scanned again.
+    async fn exclude_finished_hosts(&self, scan: &mut Scan) -> Result<(), Error> {
+        let finished = self
+            .db
+            .get_results(&scan.scan_id, None, None)
+            .await?
+            .filter_map(|x| serde_json::from_slice::<scannerlib::models::Result>(&x).ok())
+            .filter(|x| x.r_type == ResultType::HostEnd)
+            .filter_map(|x| x.ip_address);
+        for host in finished {
+            if !scan.target.excluded_hosts.contains(&host) {
+                scan.target.excluded_hosts.push(host);
+            }
+        }
         Ok(())
     }
 
@@ -247,13 +793,39 @@ where
         // we clone to drop the lock
         let running = self.running.read().await.clone();
         for scan_id in running {
-            match self.fetch_results(scan_id.clone()).await {
+            let span = tracing::debug_span!("fetch_results", scan_id = %scan_id);
+            match self.fetch_results(scan_id.clone()).instrument(span).await {
                 // using self.append_fetch_result instead of db to keep track of the status
                 // and may remove them from running.
                 Ok(mut results) => {
+                    let scan_status = self.db.get_status(&scan_id).await?;
                     if self.scanner.do_addition() {
-                        let scan_status = self.db.get_status(&scan_id).await?;
                         results.status.update_with(&scan_status);
+                    } else if let Some(heartbeat) = &scan_status.heartbeat {
+                        // keeps the results marked by previous runs
+                        results
+                            .status
+                            .heartbeat
+                            .get_or_insert_with(Default::default)
+                            .merge(heartbeat);
+                    }
+                    self.mark_results(&scan_id, &mut results).await?;
+                    let stalled = self.is_stalled(&results.status);
+                    if stalled && !scan_status.stalled {
+                        tracing::warn!(
+                            %scan_id,
+                            last_progress = results.status.last_progress(),
+                            "scan did not make any progress, flagging it as stalled"
+                        );
+                    }
+                    results.status.stalled = stalled;
+                    if !results.results.is_empty() {
+                        let (scan, _) = self.db.get_scan(&scan_id).await?;
+                        if !scan.target.labels.is_empty() {
+                            for result in results.results.iter_mut() {
+                                scan.target.label_result(result);
+                            }
+                        }
                     }
                     match self.append_fetched_result(vec![results]).await {
                         Ok(()) => {
@@ -263,6 +835,9 @@ where
                             tracing::warn!(%scan_id, %e, "unable to append results");
                         }
                     };
+                    if stalled && self.config.stall_policy == config::StallPolicy::Stop {
+                        self.stop_stalled(&scan_id).await?;
+                    }
                 }
                 Err(e) => {
                     tracing::warn!(%scan_id, %e, "unable to fetch results");
@@ -272,7 +847,71 @@ where
         Ok(())
     }
 
+    /// Marks new results within the heartbeat of the scan.
+    ///
+    /// Results of scanners storing them themselves are recognized by a changed amount of stored
+    /// results.
+    async fn mark_results(&self, scan_id: &str, results: &mut ScanResults) -> Result<(), Error> {
+        let mut new = !results.results.is_empty();
+        if !self.scanner.do_addition() {
+            let stored = self.db.count_results(scan_id).await?;
+            let mut counts = self.result_counts.write().await;
+            let previous = counts.insert(scan_id.to_string(), stored + results.results.len());
+            new |= previous.is_some_and(|x| x < stored);
+        }
+        if new {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default();
+            results
+                .status
+                .heartbeat
+                .get_or_insert_with(Default::default)
+                .result(now);
+        }
+        Ok(())
+    }
+
+    /// Returns true when a running scan did not make any progress within the stall timeout.
+    fn is_stalled(&self, status: &Status) -> bool {
+        let Some(timeout) = self.config.stall_timeout else {
+            return false;
+        };
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        status.status == Phase::Running
+            && status
+                .last_progress()
+                .is_some_and(|x| now.saturating_sub(x) > timeout.as_secs())
+    }
+
+    /// Stops a stalled scan and sets it to failed.
+    async fn stop_stalled(&self, scan_id: &str) -> Result<(), Error> {
+        if let Err(e) = self.scanner.stop_scan(scan_id.to_string()).await {
+            tracing::warn!(%scan_id, %e, "unable to stop stalled scan");
+            return Ok(());
+        }
+        tracing::warn!(%scan_id, "stopped stalled scan");
+        self.running.write().await.retain(|x| x != scan_id);
+        self.redactions.write().await.remove(scan_id);
+        self.result_counts.write().await.remove(scan_id);
+        let mut status = self.db.get_status(scan_id).await?;
+        status.status = Phase::Failed;
+        status.end_time = Some(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+        );
+        self.db.update_status(scan_id, status).await?;
+        Ok(())
+    }
+
     pub async fn sync_scans(&self) -> Result<(), Error> {
+        self.park_scans().await?;
         let coordination = self.coordinate_scans();
         let results = self.handle_results();
         let cr = coordination.await;
@@ -286,6 +925,11 @@ where
         let running = self.running.read().await;
         !running.is_empty()
     }
+
+    /// Returns the amount of currently running scans.
+    pub async fn running_scans(&self) -> usize {
+        self.running.read().await.len()
+    }
 }
 
 #[async_trait]
@@ -383,18 +1027,22 @@ where
     DB: Storage + Sync + Send + 'static,
     S: Sync + Send,
 {
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn get_scan(&self, id: &str) -> Result<(Scan, Status), StorageError> {
         self.db.get_scan(id).await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn get_decrypted_scan(&self, id: &str) -> Result<(Scan, Status), StorageError> {
         self.db.get_decrypted_scan(id).await
     }
     async fn get_scan_ids(&self) -> Result<Vec<String>, StorageError> {
         self.db.get_scan_ids().await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn get_status(&self, id: &str) -> Result<Status, StorageError> {
         self.db.get_status(id).await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn get_results(
         &self,
         id: &str,
@@ -403,6 +1051,20 @@ where
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, StorageError> {
         self.db.get_results(id, from, to).await
     }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
+    async fn count_results(&self, id: &str) -> Result<usize, StorageError> {
+        self.db.count_results(id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
+    async fn get_kb_snapshot(
+        &self,
+        id: &str,
+        host: &str,
+    ) -> Result<Option<KbSnapshot>, StorageError> {
+        self.db.get_kb_snapshot(id, host).await
+    }
 }
 
 #[async_trait]
@@ -440,6 +1102,23 @@ where
         result
     }
 
+    /// Like a feed synchronization it waits until all running scans are finished and prevents
+    /// starting new scans while the VTs are replaced.
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), StorageError> {
+        let mut sync_feed = self.is_synchronizing_feed.write().await;
+        *sync_feed = true;
+        let mut interval = tokio::time::interval(self.config().check_interval);
+        while !self.running.read().await.is_empty() {
+            tracing::trace!("blocking until all running scans are finished");
+            interval.tick().await;
+        }
+        let result = self.db.replace_vts(vts).await;
+        self.vts_uploads.fetch_add(1, Ordering::Relaxed);
+        *sync_feed = false;
+        tracing::debug!(?result, "replaced VTs, scans can be started again");
+        result
+    }
+
     async fn oids(&self) -> Result<Box<dyn Iterator<Item = String> + Send>, StorageError> {
         self.db.oids().await
     }
@@ -452,6 +1131,17 @@ where
         self.db.vt_by_oid(oid).await
     }
 
+    async fn vts_filtered<'a>(
+        &self,
+        filter: crate::storage::VtFilter,
+    ) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, StorageError> {
+        self.db.vts_filtered(filter).await
+    }
+
+    async fn families(&self) -> Result<Vec<crate::storage::FamilySummary>, StorageError> {
+        self.db.families().await
+    }
+
     async fn feed_hash(&self) -> Vec<FeedHash> {
         self.db.feed_hash().await.to_vec()
     }
@@ -459,6 +1149,10 @@ where
     async fn current_feed_version(&self) -> Result<String, StorageError> {
         self.db.current_feed_version().await
     }
+
+    async fn feed_report(&self) -> Result<Option<scannerlib::feed::UpdateReport>, StorageError> {
+        self.db.feed_report().await
+    }
 }
 
 #[async_trait]
@@ -467,16 +1161,19 @@ where
     DB: Storage + Sync + Send + 'static,
     S: Sync + Send,
 {
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = %t.scan_id))]
     async fn insert_scan(&self, t: Scan) -> Result<(), StorageError> {
         self.db.insert_scan(t).await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn remove_scan(&self, id: &str) -> Result<(), StorageError> {
         self.db.remove_scan(id).await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
     async fn update_status(&self, id: &str, status: Status) -> Result<(), StorageError> {
         match status.status {
-            Phase::Stored | Phase::Requested | Phase::Running => {}
-            Phase::Stopped | Phase::Failed | Phase::Succeeded => {
+            Phase::Stored | Phase::Requested | Phase::Running | Phase::Paused => {}
+            Phase::Stopped | Phase::Failed | Phase::Succeeded | Phase::Partial => {
                 let mut running = self.running.write().await;
                 if let Some(idx) = running.iter().position(|x| x == id) {
                     running.swap_remove(idx);
@@ -485,6 +1182,73 @@ where
         };
         self.db.update_status(id, status).await
     }
+    #[tracing::instrument(level = "debug", skip_all, fields(scan_id = id))]
+    async fn store_kb_snapshot(
+        &self,
+        id: &str,
+        host: &str,
+        snapshot: KbSnapshot,
+    ) -> Result<(), StorageError> {
+        self.db.store_kb_snapshot(id, host, snapshot).await
+    }
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn compact(&self) -> Result<crate::storage::Maintenance, StorageError> {
+        self.db.compact().await
+    }
+}
+
+#[async_trait]
+impl<DB, S> AnnotationStorer for Scheduler<DB, S>
+where
+    DB: Storage + Sync + Send + 'static,
+    S: Sync + Send,
+{
+    async fn add_annotation(
+        &self,
+        id: &str,
+        annotation: Annotation,
+    ) -> Result<Annotation, StorageError> {
+        self.db.add_annotation(id, annotation).await
+    }
+    async fn get_annotations(&self, id: &str) -> Result<Vec<Annotation>, StorageError> {
+        self.db.get_annotations(id).await
+    }
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), StorageError> {
+        self.db.remove_annotation(id, annotation_id).await
+    }
+}
+
+impl<DB, S> Scheduler<DB, S>
+where
+    DB: Storage + Sync + Send + 'static,
+    S: Sync + Send,
+{
+    /// Applies the auto-triage rules to the results.
+    ///
+    /// The severity of the VT of a result is only looked up when a rule requires it.
+    async fn triage_results(&self, results: &mut [ScanResults]) {
+        let rules = self.triage.rules();
+        if rules.is_empty() {
+            return;
+        }
+        let mut severities: HashMap<String, Option<SeverityClass>> = HashMap::new();
+        for result in results.iter_mut().flat_map(|x| x.results.iter_mut()) {
+            let severity = match &result.oid {
+                Some(oid) if rules.uses_severity() => {
+                    if !severities.contains_key(oid) {
+                        let severity = match self.db.vt_by_oid(oid).await {
+                            Ok(Some(vt)) => crate::storage::trend::severity(&vt),
+                            _ => None,
+                        };
+                        severities.insert(oid.clone(), severity);
+                    }
+                    severities[oid]
+                }
+                _ => None,
+            };
+            rules.apply(result, severity);
+        }
+    }
 }
 
 #[async_trait]
@@ -493,22 +1257,38 @@ where
     DB: Storage + Sync + Send + 'static,
     S: Sync + Send,
 {
-    async fn append_fetched_result(&self, results: Vec<ScanResults>) -> Result<(), StorageError> {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn append_fetched_result(
+        &self,
+        mut results: Vec<ScanResults>,
+    ) -> Result<(), StorageError> {
+        for result in results.iter_mut().flat_map(|x| x.results.iter_mut()) {
+            redact::redact_result(result);
+        }
+        self.triage_results(&mut results).await;
         let mut running = self.running.write().await;
+        let mut redactions = self.redactions.write().await;
+        let mut result_counts = self.result_counts.write().await;
         for x in results.iter() {
             match x.status.status {
-                Phase::Stored | Phase::Requested | Phase::Running => {}
-                Phase::Stopped | Phase::Failed | Phase::Succeeded => {
+                Phase::Stored | Phase::Requested | Phase::Running | Phase::Paused => {}
+                Phase::Stopped | Phase::Failed | Phase::Succeeded | Phase::Partial => {
                     if let Some(idx) = running.iter().position(|y| y == &x.id) {
                         running.swap_remove(idx);
                     }
+                    redactions.remove(&x.id);
+                    result_counts.remove(&x.id);
                 }
             };
         }
+        drop(result_counts);
+        drop(redactions);
         drop(running);
 
         tracing::trace!("appending results");
-        self.db.append_fetched_result(results).await
+        self.db.append_fetched_result(results).await?;
+        self.results_appended.send_replace(());
+        Ok(())
     }
 }
 
@@ -530,6 +1310,8 @@ mod tests {
             Phase, Status,
         };
 
+        use crate::storage::{AppendFetchResult as _, ProgressGetter as _};
+
         use super::*;
 
         #[traced_test]
@@ -621,6 +1403,384 @@ mod tests {
             assert_eq!(scheduler.running.read().await.len(), 0);
         }
 
+        #[traced_test]
+        #[tokio::test]
+        async fn not_move_from_queue_on_insufficient_disk() {
+            let config = config::Scheduler {
+                min_free_disk: Some(u64::MAX),
+                disk_paths: vec!["/".into()],
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            let scan = Scan::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config, Lambda::default(), db);
+            scheduler.start_scan_by_id(&scan.scan_id).await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(scheduler.queued.read().await.len(), 1);
+            assert_eq!(scheduler.running.read().await.len(), 0);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn resolve_secrets_on_start() {
+            use scannerlib::models::{Credential, CredentialType};
+
+            let dir = std::env::temp_dir().join(format!(
+                "openvasd-scheduler-secrets-{}",
+                uuid::Uuid::new_v4()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            let plain = dir.join("plain.toml");
+            std::fs::write(&plain, "[ssh]\npassword = \"hidden\"\n").unwrap();
+            let file = dir.join("secrets");
+            std::fs::write(&file, crate::secrets::encrypt_file(&plain, "key").unwrap()).unwrap();
+            let secrets = crate::secrets::Secrets::new(config::Secrets {
+                file: Some(file),
+                file_key: Some("key".to_string()),
+                ..Default::default()
+            });
+
+            let scan = |id: &str, password: &str| {
+                let mut scan = Scan {
+                    scan_id: id.to_string(),
+                    ..Default::default()
+                };
+                scan.target.credentials = vec![Credential {
+                    credential_type: CredentialType::UP {
+                        username: "root".to_string(),
+                        password: password.to_string(),
+                        privilege: None,
+                    },
+                    ..Default::default()
+                }];
+                scan
+            };
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan("resolved", "local://ssh#password"))
+                .await
+                .unwrap();
+            db.insert_scan(scan("unresolved", "local://other#password"))
+                .await
+                .unwrap();
+            let scanner = LambdaBuilder::new()
+                .with_start(|scan| {
+                    assert_eq!(scan.target.credentials[0].password(), "hidden");
+                    Ok(())
+                })
+                .build();
+            let scheduler =
+                Scheduler::new(config::Scheduler::default(), scanner, db).with_secrets(secrets);
+            scheduler.start_scan_by_id("resolved").await.unwrap();
+            scheduler.start_scan_by_id("unresolved").await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(
+                *scheduler.running.read().await,
+                vec!["resolved".to_string()]
+            );
+            assert!(scheduler.queued.read().await.is_empty());
+            let status = scheduler.get_status("unresolved").await.unwrap();
+            assert_eq!(status.status, Phase::Failed);
+            // only the reference is stored
+            let (stored, _) = scheduler.get_decrypted_scan("resolved").await.unwrap();
+            assert_eq!(
+                stored.target.credentials[0].password(),
+                "local://ssh#password"
+            );
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn redact_credentials_of_running_scans() {
+            use scannerlib::models::{Credential, CredentialType};
+            use scannerlib::redact;
+
+            let mut scan = Scan::default();
+            scan.target.credentials = vec![Credential {
+                credential_type: CredentialType::UP {
+                    username: "root".to_string(),
+                    password: "s3cr3t-scheduler".to_string(),
+                    privilege: None,
+                },
+                ..Default::default()
+            }];
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config::Scheduler::default(), Lambda::default(), db);
+            scheduler.start_scan_by_id(&scan.scan_id).await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            let results = |status| {
+                vec![ScanResults {
+                    id: scan.scan_id.clone(),
+                    status: Status {
+                        status,
+                        ..Default::default()
+                    },
+                    results: vec![scannerlib::models::Result {
+                        message: Some("login as root:s3cr3t-scheduler".to_string()),
+                        ..Default::default()
+                    }],
+                }]
+            };
+            scheduler
+                .append_fetched_result(results(Phase::Running))
+                .await
+                .unwrap();
+            let stored: Vec<scannerlib::models::Result> = scheduler
+                .get_results(&scan.scan_id, None, None)
+                .await
+                .unwrap()
+                .map(|x| serde_json::from_slice(&x).unwrap())
+                .collect();
+            assert_eq!(stored[0].message.as_deref(), Some("login as root:********"));
+            scheduler
+                .append_fetched_result(results(Phase::Succeeded))
+                .await
+                .unwrap();
+            // no longer masked once the scan is finished
+            assert_eq!(redact::redact("s3cr3t-scheduler"), "s3cr3t-scheduler");
+        }
+
+        #[tokio::test]
+        async fn rescan_subset() {
+            use scannerlib::models::{Rescan, ResultType, VT};
+
+            use crate::controller::ClientHash;
+
+            let mut scan = Scan {
+                scan_id: "original".into(),
+                vts: ["1.2.3", "1.2.4"]
+                    .iter()
+                    .map(|oid| VT {
+                        oid: oid.to_string(),
+                        parameters: vec![],
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            scan.target.hosts = vec!["192.168.0.0/30".into()];
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config::Scheduler::default(), Lambda::default(), db);
+            let client = ClientHash::from("test");
+            let result = |ip: &str, oid: Option<&str>| scannerlib::models::Result {
+                r_type: if oid.is_some() {
+                    ResultType::Alarm
+                } else {
+                    ResultType::HostEnd
+                },
+                ip_address: Some(ip.to_string()),
+                oid: oid.map(|x| x.to_string()),
+                ..Default::default()
+            };
+            let rescan = Rescan {
+                hosts: vec!["192.168.0.1".into()],
+                vts: vec!["1.2.3".into()],
+            };
+            assert!(matches!(
+                scheduler
+                    .rescan_scan_by_id("original", rescan.clone(), &client)
+                    .await,
+                Err(scheduling::Error::NotFinished)
+            ));
+            scheduler
+                .append_fetched_result(vec![ScanResults {
+                    id: "original".into(),
+                    status: Status {
+                        status: Phase::Succeeded,
+                        ..Default::default()
+                    },
+                    results: vec![
+                        result("192.168.0.1", Some("1.2.3")),
+                        result("192.168.0.1", Some("1.2.4")),
+                        result("192.168.0.1", None),
+                        result("192.168.0.2", Some("1.2.3")),
+                    ],
+                }])
+                .await
+                .unwrap();
+            for invalid in [
+                Rescan::default(),
+                Rescan {
+                    hosts: vec!["10.0.0.1".into()],
+                    vts: vec![],
+                },
+                Rescan {
+                    hosts: vec![],
+                    vts: vec!["1.2.5".into()],
+                },
+            ] {
+                assert!(matches!(
+                    scheduler
+                        .rescan_scan_by_id("original", invalid, &client)
+                        .await,
+                    Err(scheduling::Error::InvalidRescan(_))
+                ));
+            }
+
+            let id = scheduler
+                .rescan_scan_by_id("original", rescan.clone(), &client)
+                .await
+                .unwrap();
+            let (scan, _) = scheduler.get_scan(&id).await.unwrap();
+            assert_eq!(scan.target.hosts, vec!["192.168.0.1"]);
+            assert_eq!(scan.vts.len(), 1);
+            assert_eq!(scan.vts[0].oid, "1.2.3");
+            let lineage = scan.lineage.unwrap();
+            assert_eq!(lineage.parent, "original");
+            assert_eq!(lineage.rescan, Some(rescan));
+            let carried: Vec<(Option<String>, Option<String>)> = scheduler
+                .get_results(&id, None, None)
+                .await
+                .unwrap()
+                .map(|x| serde_json::from_slice::<scannerlib::models::Result>(&x).unwrap())
+                .map(|x| (x.ip_address, x.oid))
+                .collect();
+            assert_eq!(
+                carried,
+                vec![
+                    (Some("192.168.0.1".into()), Some("1.2.4".into())),
+                    (Some("192.168.0.2".into()), Some("1.2.3".into())),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn triage_appended_results() {
+            let path =
+                std::env::temp_dir().join(format!("scheduler-triage-{}.toml", std::process::id()));
+            std::fs::write(
+                &path,
+                "[[rule]]\nname = \"known\"\noids = [\"1.2.3\"]\naction = \"suppress\"",
+            )
+            .unwrap();
+            let scan = Scan::default();
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config::Scheduler::default(), Lambda::default(), db)
+                .with_triage(crate::triage::Triage::new(config::Triage {
+                    rules: Some(path.clone()),
+                }));
+            let results = ["1.2.3", "1.2.4"]
+                .iter()
+                .map(|oid| scannerlib::models::Result {
+                    oid: Some(oid.to_string()),
+                    ..Default::default()
+                })
+                .collect();
+            scheduler
+                .append_fetched_result(vec![ScanResults {
+                    id: scan.scan_id.clone(),
+                    status: Status::default(),
+                    results,
+                }])
+                .await
+                .unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let stored: Vec<scannerlib::models::Result> = scheduler
+                .get_results(&scan.scan_id, None, None)
+                .await
+                .unwrap()
+                .map(|x| serde_json::from_slice(&x).unwrap())
+                .collect();
+            let triage = stored[0].triage.as_ref().unwrap();
+            assert!(triage.suppressed);
+            assert_eq!(triage.trace[0].rule, "known");
+            assert_eq!(stored[1].triage, None);
+        }
+
+        fn blackout(hosts: &[&str]) -> crate::blackout::Blackout {
+            crate::blackout::Blackout {
+                name: "always".to_string(),
+                days: vec![],
+                start: "00:00".to_string().try_into().unwrap(),
+                end: "00:00".to_string().try_into().unwrap(),
+                timezone: Default::default(),
+                hosts: hosts.iter().map(|x| x.to_string()).collect(),
+            }
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn hold_back_queued_scans_during_blackout() {
+            let config = config::Scheduler {
+                blackouts: vec![blackout(&["10.0.0.0/8"])],
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            let mut affected = Scan::default();
+            affected.scan_id = "affected".to_string();
+            affected.target.hosts = vec!["10.0.0.1".to_string()];
+            let mut other = Scan::default();
+            other.scan_id = "other".to_string();
+            other.target.hosts = vec!["192.168.0.1".to_string()];
+            db.insert_scan(affected.clone()).await.unwrap();
+            db.insert_scan(other.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config, Lambda::default(), db);
+            scheduler.start_scan_by_id("affected").await.unwrap();
+            scheduler.start_scan_by_id("other").await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(*scheduler.queued.read().await, vec!["affected".to_string()]);
+            assert_eq!(*scheduler.running.read().await, vec!["other".to_string()]);
+            let status = scheduler.get_status("affected").await.unwrap();
+            assert_eq!(status.status, Phase::Paused);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn pause_running_scans_during_blackout() {
+            let config = config::Scheduler {
+                blackouts: vec![blackout(&[])],
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            let scan = Scan::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scheduler = Scheduler::new(config, Lambda::default(), db);
+            scheduler.running.write().await.push(scan.scan_id.clone());
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(scheduler.running.read().await.len(), 0);
+            assert_eq!(*scheduler.queued.read().await, vec![scan.scan_id.clone()]);
+            let status = scheduler.get_status(&scan.scan_id).await.unwrap();
+            assert_eq!(status.status, Phase::Paused);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn resume_paused_scans_without_finished_hosts() {
+            let config = config::Scheduler::default();
+            let db = inmemory::Storage::default();
+            let mut scan = Scan::default();
+            scan.target.hosts = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+            db.insert_scan(scan.clone()).await.unwrap();
+            let host_end = scannerlib::models::Result {
+                r_type: scannerlib::models::ResultType::HostEnd,
+                ip_address: Some("10.0.0.1".to_string()),
+                ..Default::default()
+            };
+            db.append_fetched_result(vec![ScanResults {
+                id: scan.scan_id.clone(),
+                status: Status {
+                    status: Phase::Paused,
+                    ..Default::default()
+                },
+                results: vec![host_end],
+            }])
+            .await
+            .unwrap();
+            let scanner = LambdaBuilder::new()
+                .with_start(|scan| {
+                    assert_eq!(scan.target.excluded_hosts, vec!["10.0.0.1".to_string()]);
+                    Ok(())
+                })
+                .build();
+            let scheduler = Scheduler::new(config, scanner, db);
+            scheduler.queued.write().await.push(scan.scan_id.clone());
+            scheduler.sync_scans().await.unwrap();
+            assert_eq!(*scheduler.running.read().await, vec![scan.scan_id.clone()]);
+        }
+
         #[traced_test]
         #[tokio::test]
         async fn not_move_from_queue_on_connection_error() {
@@ -738,6 +1898,7 @@ mod tests {
                             end_time: None,
                             status: Phase::Succeeded,
                             host_info: None,
+                            ..Default::default()
                         },
                         results: vec![],
                     })
@@ -756,10 +1917,118 @@ mod tests {
             assert_eq!(scheduler.queued.read().await.len(), 0);
             assert_eq!(scheduler.running.read().await.len(), 0);
         }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn detect_stalled_scans() {
+            use scannerlib::models::Heartbeat;
+
+            let run = |policy| async move {
+                let db = inmemory::Storage::default();
+                for id in ["stalled", "alive"] {
+                    let scan = Scan {
+                        scan_id: id.to_string(),
+                        ..Default::default()
+                    };
+                    db.insert_scan(scan).await.unwrap();
+                }
+                let scanner = LambdaBuilder::default()
+                    .with_fetch(|s| {
+                        let now = std::time::SystemTime::now()
+                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs();
+                        Ok(ScanResults {
+                            id: s.to_string(),
+                            status: Status {
+                                status: Phase::Running,
+                                start_time: Some(now - 120),
+                                heartbeat: (s == "alive").then(|| Heartbeat::at(now)),
+                                ..Default::default()
+                            },
+                            results: vec![],
+                        })
+                    })
+                    .build();
+                let config = config::Scheduler {
+                    stall_timeout: Some(std::time::Duration::from_secs(60)),
+                    stall_policy: policy,
+                    ..Default::default()
+                };
+                let scheduler = Scheduler::new(config, scanner, db);
+                for id in ["stalled", "alive"] {
+                    scheduler.start_scan_by_id(id).await.unwrap();
+                }
+                scheduler.coordinate_scans().await.unwrap();
+                scheduler.handle_results().await.unwrap();
+                let stalled = scheduler.get_status("stalled").await.unwrap();
+                let alive = scheduler.get_status("alive").await.unwrap();
+                assert!(stalled.stalled);
+                assert!(!alive.stalled);
+                assert!(alive.heartbeat.is_some());
+                let running = scheduler.running.read().await.clone();
+                (stalled, running)
+            };
+
+            let (status, running) = run(config::StallPolicy::Flag).await;
+            assert_eq!(status.status, Phase::Running);
+            assert_eq!(running.len(), 2);
+
+            let (status, running) = run(config::StallPolicy::Stop).await;
+            assert_eq!(status.status, Phase::Failed);
+            assert!(status.end_time.is_some());
+            assert_eq!(running, vec!["alive".to_string()]);
+        }
+
+        #[traced_test]
+        #[tokio::test]
+        async fn label_fetched_results() {
+            use crate::storage::ProgressGetter as _;
+
+            let mut scan = Scan::default();
+            scan.target.hosts = vec!["192.0.2.1".to_string()];
+            scan.target.labels = [(
+                "192.0.2.1".to_string(),
+                [("asset-id".to_string(), "A-1".to_string())].into(),
+            )]
+            .into();
+            let db = inmemory::Storage::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scanner = LambdaBuilder::default()
+                .with_fetch(|s| {
+                    Ok(ScanResults {
+                        id: s.to_string(),
+                        status: Status {
+                            status: Phase::Running,
+                            ..Default::default()
+                        },
+                        results: vec![scannerlib::models::Result {
+                            ip_address: Some("192.0.2.1".to_string()),
+                            ..Default::default()
+                        }],
+                    })
+                })
+                .build();
+            let scheduler = Scheduler::new(config::Scheduler::default(), scanner, db);
+            scheduler.start_scan_by_id(&scan.scan_id).await.unwrap();
+            scheduler.coordinate_scans().await.unwrap();
+            scheduler.handle_results().await.unwrap();
+            let results: Vec<scannerlib::models::Result> = scheduler
+                .get_results(&scan.scan_id, None, None)
+                .await
+                .unwrap()
+                .map(|x| serde_json::from_slice(&x).unwrap())
+                .collect();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].labels["asset-id"], "A-1");
+        }
     }
 
     mod start {
-        use scannerlib::models::{scanner::Lambda, Phase};
+        use scannerlib::models::{
+            scanner::{Lambda, ObservableResources},
+            Phase,
+        };
 
         use crate::storage::ProgressGetter;
 
@@ -780,6 +2049,30 @@ mod tests {
         }
         #[traced_test]
         #[tokio::test]
+        async fn error_vts_not_in_subset() {
+            let config = config::Scheduler::default();
+            let db = inmemory::Storage::default();
+            let mut scan = Scan::default();
+            scan.vts.push(scannerlib::models::VT {
+                oid: "1.3.6.1.4.1.25623.1.0.90022".to_string(),
+                parameters: vec![],
+            });
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scanner = Lambda::default();
+            let scheduler = Scheduler::new(config, scanner, db);
+            let subset = crate::tenancy::FeedSubset::default();
+            assert!(matches!(
+                scheduler.start_scan_within(&scan.scan_id, Some(&subset)).await,
+                Err(scheduling::Error::VtsNotAvailable(oids)) if oids == ["1.3.6.1.4.1.25623.1.0.90022"]
+            ));
+            assert_eq!(scheduler.queued.read().await.len(), 0);
+            scheduler
+                .start_scan_within(&scan.scan_id, None)
+                .await
+                .unwrap();
+            assert_eq!(scheduler.queued.read().await.len(), 1);
+        }
+        #[tokio::test]
         async fn error_starting_twice() {
             let config = config::Scheduler::default();
             let db = inmemory::Storage::default();
@@ -833,6 +2126,30 @@ mod tests {
         }
         #[traced_test]
         #[tokio::test]
+        async fn error_insufficient_resources() {
+            let config = config::Scheduler {
+                min_free_disk: Some(u64::MAX),
+                disk_paths: vec!["/".into()],
+                resource_policy: config::ResourcePolicy::Refuse,
+                ..Default::default()
+            };
+            let db = inmemory::Storage::default();
+            let scan = Scan::default();
+            db.insert_scan(scan.clone()).await.unwrap();
+            let scanner = Lambda::default();
+            let scheduler = Scheduler::new(config, scanner, db);
+            assert!(
+                match scheduler.start_scan_by_id(&scan.scan_id).await {
+                    Err(scheduling::Error::InsufficientResources(x)) =>
+                        x == vec![ObservableResources::Disk],
+                    Ok(_) | Err(_) => false,
+                },
+                "should return InsufficientResources"
+            );
+            assert_eq!(scheduler.queued.read().await.len(), 0);
+        }
+        #[traced_test]
+        #[tokio::test]
         async fn error_resume_unsupported() {
             let config = config::Scheduler {
                 max_queued_scans: Some(0),
@@ -0,0 +1,406 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Resolves references to secrets within the credentials of a scan.
+//!
+//! Instead of a password, private key or privacy password a credential may contain a reference
+//! to a secret:
+//! - `vault://<path>#<key>` reads the key of a secret of the key value engine of HashiCorp Vault,
+//! - `local://<name>#<key>` reads the key of a secret of the local encrypted secrets file.
+//!
+//! References are resolved when a scan is started, so that the stored scan only contains the
+//! references and the scanner gets the secrets. Fetched secrets are reused until the cache ttl
+//! elapsed. Each access of a secret is logged with the target `audit`, without its value.
+
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use scannerlib::models::Scan;
+use tokio::sync::RwLock;
+
+use crate::{
+    config,
+    crypt::{ChaCha20Crypt, Crypt as _, Encrypted},
+};
+
+const VAULT: &str = "vault://";
+const LOCAL: &str = "local://";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid secret reference {0}, expected <scheme>://<path>#<key>")]
+    InvalidReference(String),
+    #[error("unable to resolve {0}, {1} is not configured")]
+    NotConfigured(String, &'static str),
+    #[error("secret {0} not found")]
+    NotFound(String),
+    #[error("unable to fetch the secret from the vault: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid response of the vault: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unable to read the secrets file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid secrets file: {0}")]
+    File(String),
+}
+
+/// Source of a secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Vault,
+    Local,
+}
+
+impl Provider {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Vault => "vault",
+            Self::Local => "secrets file",
+        }
+    }
+}
+
+/// A reference to a key of a secret
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reference {
+    pub provider: Provider,
+    /// Path of the secret within the vault or name within the secrets file
+    pub path: String,
+    pub key: String,
+}
+
+impl Reference {
+    /// Parses a reference, returns None when value is not a reference.
+    pub fn parse(value: &str) -> Option<Result<Self, Error>> {
+        let (provider, rest) = if let Some(rest) = value.strip_prefix(VAULT) {
+            (Provider::Vault, rest)
+        } else if let Some(rest) = value.strip_prefix(LOCAL) {
+            (Provider::Local, rest)
+        } else {
+            return None;
+        };
+        Some(match rest.rsplit_once('#') {
+            Some((path, key)) if !path.is_empty() && !key.is_empty() => Ok(Self {
+                provider,
+                path: path.trim_matches('/').to_string(),
+                key: key.to_string(),
+            }),
+            _ => Err(Error::InvalidReference(value.to_string())),
+        })
+    }
+}
+
+/// Keys and values of a secret
+type Secret = BTreeMap<String, String>;
+
+/// Encrypts the secrets file with the given key.
+///
+/// The plain secrets file is a TOML file containing a table of keys and values per secret.
+pub fn encrypt_file(path: &Path, key: &str) -> Result<String, Error> {
+    let plain = std::fs::read_to_string(path)?;
+    toml::from_str::<BTreeMap<String, Secret>>(&plain).map_err(|e| Error::File(e.to_string()))?;
+    Ok(ChaCha20Crypt::new(key)
+        .encrypt_sync(plain.into_bytes())
+        .to_string())
+}
+
+/// Resolves secret references of scans
+#[derive(Debug)]
+pub struct Secrets {
+    config: config::Secrets,
+    cache: RwLock<HashMap<(Provider, String), (Instant, Secret)>>,
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Self::new(config::Secrets::default())
+    }
+}
+
+impl Secrets {
+    pub fn new(config: config::Secrets) -> Self {
+        Self {
+            config,
+            cache: Default::default(),
+        }
+    }
+
+    async fn fetch_vault(&self, reference: &Reference) -> Result<Secret, Error> {
+        let vault = &self.config.vault;
+        let address = vault
+            .address
+            .as_deref()
+            .ok_or_else(|| Error::NotConfigured(reference.path.clone(), Provider::Vault.name()))?;
+        let mount = vault.mount.trim_matches('/');
+        let url = match vault.kv_version {
+            1 => format!(
+                "{}/v1/{mount}/{}",
+                address.trim_end_matches('/'),
+                reference.path
+            ),
+            _ => format!(
+                "{}/v1/{mount}/data/{}",
+                address.trim_end_matches('/'),
+                reference.path
+            ),
+        };
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?;
+        let mut request = client.get(url);
+        if let Some(token) = &vault.token {
+            request = request.header("X-Vault-Token", token);
+        }
+        if let Some(namespace) = &vault.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::NotFound(reference.path.clone()));
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        let mut value: serde_json::Value = serde_json::from_slice(&body)?;
+        let mut data = value["data"].take();
+        if vault.kv_version != 1 {
+            data = data["data"].take();
+        }
+        let secret: BTreeMap<String, serde_json::Value> = serde_json::from_value(data)?;
+        Ok(secret
+            .into_iter()
+            .map(|(k, v)| match v {
+                serde_json::Value::String(v) => (k, v),
+                v => (k, v.to_string()),
+            })
+            .collect())
+    }
+
+    async fn fetch_local(&self, reference: &Reference) -> Result<Secret, Error> {
+        let (Some(path), Some(key)) = (&self.config.file, &self.config.file_key) else {
+            return Err(Error::NotConfigured(
+                reference.path.clone(),
+                Provider::Local.name(),
+            ));
+        };
+        let encrypted = tokio::fs::read_to_string(path).await?;
+        let encrypted =
+            Encrypted::try_from(encrypted.trim()).map_err(|e| Error::File(e.to_string()))?;
+        let plain = ChaCha20Crypt::new(key.as_str()).decrypt(encrypted).await;
+        let plain = String::from_utf8(plain)
+            .map_err(|_| Error::File("unable to decrypt, verify the key".to_string()))?;
+        let mut secrets: BTreeMap<String, Secret> =
+            toml::from_str(&plain).map_err(|e| Error::File(e.to_string()))?;
+        secrets
+            .remove(&reference.path)
+            .ok_or_else(|| Error::NotFound(reference.path.clone()))
+    }
+
+    /// Returns the value of a reference, the secret is fetched when it is not cached.
+    pub async fn resolve(&self, scan_id: &str, reference: &Reference) -> Result<String, Error> {
+        let id = (reference.provider, reference.path.clone());
+        let cached = self
+            .cache
+            .read()
+            .await
+            .get(&id)
+            .filter(|(fetched, _)| fetched.elapsed() < self.config.cache_ttl)
+            .map(|(_, secret)| secret.clone());
+        let was_cached = cached.is_some();
+        let secret = match cached {
+            Some(secret) => secret,
+            None => {
+                let fetched = match reference.provider {
+                    Provider::Vault => self.fetch_vault(reference).await,
+                    Provider::Local => self.fetch_local(reference).await,
+                };
+                let secret = match fetched {
+                    Ok(secret) => secret,
+                    Err(e) => {
+                        tracing::warn!(
+                            target: "audit",
+                            scan_id,
+                            provider = reference.provider.name(),
+                            path = reference.path,
+                            key = reference.key,
+                            %e,
+                            "Unable to access secret"
+                        );
+                        return Err(e);
+                    }
+                };
+                if !self.config.cache_ttl.is_zero() {
+                    self.cache
+                        .write()
+                        .await
+                        .insert(id, (Instant::now(), secret.clone()));
+                }
+                secret
+            }
+        };
+        tracing::info!(
+            target: "audit",
+            scan_id,
+            provider = reference.provider.name(),
+            path = reference.path,
+            key = reference.key,
+            cached = was_cached,
+            "Accessed secret"
+        );
+        secret
+            .get(&reference.key)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("{}#{}", reference.path, reference.key)))
+    }
+
+    /// Replaces the secret references within the credentials of the scan by their values.
+    pub async fn resolve_scan(&self, mut scan: Scan) -> Result<Scan, Error> {
+        let references = Mutex::new(Vec::new());
+        let target = scan.target.clone().map_credentials(|c| {
+            c.map_password(|x| {
+                if let Some(reference) = Reference::parse(&x) {
+                    references.lock().unwrap().push((x.clone(), reference?));
+                }
+                Ok::<_, Error>(x)
+            })
+        })?;
+        let references = references.into_inner().unwrap();
+        if references.is_empty() {
+            return Ok(scan);
+        }
+        let mut values = HashMap::with_capacity(references.len());
+        for (raw, reference) in references {
+            if let Entry::Vacant(entry) = values.entry(raw) {
+                entry.insert(self.resolve(&scan.scan_id, &reference).await?);
+            }
+        }
+        scan.target = target.map_credentials(|c| {
+            c.map_password(|x| Ok::<_, Error>(values.get(&x).cloned().unwrap_or(x)))
+        })?;
+        Ok(scan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use scannerlib::models::{Credential, CredentialType, Scan, Service};
+
+    use super::{encrypt_file, Error, Provider, Reference, Secrets};
+    use crate::config;
+
+    fn scan(password: &str) -> Scan {
+        let mut scan = Scan {
+            scan_id: "s1".to_string(),
+            ..Default::default()
+        };
+        scan.target.credentials = vec![Credential {
+            service: Service::SSH,
+            port: None,
+            credential_type: CredentialType::UP {
+                username: "root".to_string(),
+                password: password.to_string(),
+                privilege: None,
+            },
+        }];
+        scan
+    }
+
+    fn secrets_file(name: &str, content: &str) -> config::Secrets {
+        let dir =
+            std::env::temp_dir().join(format!("openvasd-secrets-{name}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plain = dir.join("plain.toml");
+        std::fs::write(&plain, content).unwrap();
+        let encrypted = dir.join("secrets");
+        std::fs::write(&encrypted, encrypt_file(&plain, "key").unwrap()).unwrap();
+        config::Secrets {
+            file: Some(encrypted),
+            file_key: Some("key".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert!(Reference::parse("secret").is_none());
+        let reference = Reference::parse("vault://ssh/prod/#password")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            reference,
+            Reference {
+                provider: Provider::Vault,
+                path: "ssh/prod".to_string(),
+                key: "password".to_string(),
+            }
+        );
+        assert!(Reference::parse("local://ssh").unwrap().is_err());
+        assert!(Reference::parse("local://#password").unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_local() {
+        let config = secrets_file(
+            "resolve",
+            r#"
+            [ssh-prod]
+            password = "hidden"
+            "#,
+        );
+        let secrets = Secrets::new(config);
+        let resolved = secrets
+            .resolve_scan(scan("local://ssh-prod#password"))
+            .await
+            .unwrap();
+        assert_eq!(resolved.target.credentials[0].password(), "hidden");
+
+        let plain = secrets.resolve_scan(scan("plain")).await.unwrap();
+        assert_eq!(plain.target.credentials[0].password(), "plain");
+        assert!(matches!(
+            secrets.resolve_scan(scan("local://ssh-prod#other")).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            secrets
+                .resolve_scan(scan("local://ssh-test#password"))
+                .await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let config = secrets_file("cache", "[a]\npassword = \"first\"\n");
+        let file = config.file.clone().unwrap();
+        let reference = Reference::parse("local://a#password").unwrap().unwrap();
+        let secrets = Secrets::new(config.clone());
+        assert_eq!(secrets.resolve("s1", &reference).await.unwrap(), "first");
+        std::fs::remove_file(&file).unwrap();
+        // served from the cache although the file is gone
+        assert_eq!(secrets.resolve("s1", &reference).await.unwrap(), "first");
+
+        let uncached = Secrets::new(config::Secrets {
+            cache_ttl: Duration::ZERO,
+            ..config
+        });
+        assert!(uncached.resolve("s1", &reference).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn not_configured() {
+        let secrets = Secrets::default();
+        assert!(matches!(
+            secrets.resolve_scan(scan("vault://ssh#password")).await,
+            Err(Error::NotConfigured(..))
+        ));
+        assert!(matches!(
+            secrets.resolve_scan(scan("local://ssh#password")).await,
+            Err(Error::NotConfigured(..))
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Selects the profile mapping the severity of results to levels.
+//!
+//! A scan selects a profile via the `severity_profile` scan preference. Otherwise the profile of
+//! the tenant of the client is used, then the default profile. Without any of them the CVSS
+//! classes of [SeverityProfile::default] are used.
+
+use scannerlib::models::{cvss::SeverityProfile, Scan};
+
+use crate::config;
+
+/// Scan preference selecting the severity profile of a scan
+pub const SEVERITY_PROFILE: &str = "severity_profile";
+
+/// Returns the name of the profile selected by the scan.
+pub fn requested(scan: &Scan) -> Option<&str> {
+    scan.scan_preferences
+        .iter()
+        .find(|x| x.id == SEVERITY_PROFILE)
+        .map(|x| x.value.as_str())
+        .filter(|x| !x.is_empty())
+}
+
+/// Returns the profile of a scan of a client belonging to the given tenant.
+///
+/// The tenant of a client defaults to the default tenant of the tenancy, regardless of whether
+/// the feed subsets of tenants are enabled. Profiles that are not configured are skipped.
+pub fn profile(
+    config: &config::Severity,
+    tenancy: &config::Tenancy,
+    tenant: Option<&str>,
+    scan: &Scan,
+) -> SeverityProfile {
+    let tenant = tenant
+        .or(tenancy.default_tenant.as_deref())
+        .and_then(|x| tenancy.tenants.get(x))
+        .and_then(|x| x.severity_profile.as_deref());
+    [requested(scan), tenant, config.default_profile.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(|name| match config.profiles.get(name) {
+            Some(profile) => Some(profile.clone()),
+            None => {
+                tracing::warn!(name, "Severity profile is not configured");
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::models::{cvss::SeverityLevel, ScanPreference};
+
+    use super::*;
+
+    fn single(name: &str) -> SeverityProfile {
+        SeverityProfile {
+            levels: vec![SeverityLevel {
+                name: name.to_string(),
+                min: 0.0,
+            }],
+        }
+    }
+
+    fn level(
+        config: &config::Severity,
+        tenancy: &config::Tenancy,
+        tenant: Option<&str>,
+        scan: &Scan,
+    ) -> Option<String> {
+        profile(config, tenancy, tenant, scan)
+            .level(5.0)
+            .map(|x| x.to_string())
+    }
+
+    #[test]
+    fn select_profile() {
+        let mut config = config::Severity {
+            default_profile: None,
+            profiles: ["org", "tenant", "scan"]
+                .into_iter()
+                .map(|x| (x.to_string(), single(x)))
+                .collect(),
+        };
+        let mut tenancy = config::Tenancy {
+            default_tenant: Some("basic".to_string()),
+            tenants: [(
+                "basic".to_string(),
+                config::Tenant {
+                    severity_profile: Some("tenant".to_string()),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let mut scan = Scan::default();
+        assert_eq!(level(&config, &tenancy, None, &scan).unwrap(), "tenant");
+        // tenants without a profile use the default profile
+        assert_eq!(
+            level(&config, &tenancy, Some("other"), &scan).unwrap(),
+            "medium"
+        );
+        config.default_profile = Some("org".to_string());
+        assert_eq!(
+            level(&config, &tenancy, Some("other"), &scan).unwrap(),
+            "org"
+        );
+        scan.scan_preferences.push(ScanPreference {
+            id: SEVERITY_PROFILE.to_string(),
+            value: "scan".to_string(),
+        });
+        assert_eq!(level(&config, &tenancy, None, &scan).unwrap(), "scan");
+        // unknown profiles are skipped
+        scan.scan_preferences[0].value = "unknown".to_string();
+        tenancy.default_tenant = None;
+        assert_eq!(level(&config, &tenancy, None, &scan).unwrap(), "org");
+    }
+}
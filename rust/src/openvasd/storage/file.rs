@@ -97,6 +97,131 @@ where
         let parsed = iter.filter_map(|x| x.ok());
         Ok(Box::new(parsed))
     }
+
+    fn get_annotations_sync(storage: &S, key: &str) -> Result<Vec<models::Annotation>, Error> {
+        let annotations: Vec<Serialization<Vec<models::Annotation>>> =
+            match storage.by_range(key, Range::All) {
+                Ok(x) => x,
+                // there are no annotations until the first one got stored
+                Err(scannerlib::storage::infisto::Error::IoError(
+                    scannerlib::storage::infisto::IoErrorKind::FileOpen,
+                    io::ErrorKind::NotFound,
+                )) => vec![],
+                Err(e) => return Err(e.into()),
+            };
+        match annotations.into_iter().next() {
+            Some(Serialization::Deserialized(x)) => Ok(x),
+            Some(_) => Err(Error::Serialization),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the stored ids of scans, they may be stored more than once.
+    fn get_stored_scan_ids_sync(storage: &S) -> Result<Vec<String>, Error> {
+        let scans: Vec<Serialization<String>> = match storage.by_range("scans", Range::All) {
+            Ok(s) => s,
+            Err(scannerlib::storage::infisto::Error::IoError(
+                scannerlib::storage::infisto::IoErrorKind::FileOpen,
+                io::ErrorKind::NotFound,
+            )) => {
+                vec![]
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(scans
+            .into_iter()
+            .filter_map(|x| match x {
+                Serialization::Serialized(_) => None,
+                Serialization::Deserialized(x) => Some(x),
+            })
+            .collect())
+    }
+
+    fn get_scan_ids_sync(storage: &S) -> Result<Vec<String>, Error> {
+        let mut scans = Self::get_stored_scan_ids_sync(storage)?;
+        scans.sort();
+        scans.dedup();
+        Ok(scans)
+    }
+
+    /// Compacts the files and removes the files of scans that are not stored anymore.
+    ///
+    /// The list of scans and the client mapping are rewritten when they contain duplicated or
+    /// removed scans.
+    fn compact_sync(storage: &mut S) -> Result<Maintenance, Error> {
+        let size = storage.size()?;
+        let mut report = Maintenance::default();
+        let stored = Self::get_stored_scan_ids_sync(storage)?;
+        let ids = Self::get_scan_ids_sync(storage)?;
+        if stored.len() != ids.len() {
+            let ids = ids
+                .iter()
+                .map(Serialization::serialize)
+                .collect::<Result<Vec<_>, _>>()?;
+            storage.remove("scans")?;
+            storage.append_all("scans", &ids)?;
+            report.compacted += 1;
+        }
+        let mapping: Vec<Serialization<(ClientHash, String)>> =
+            storage.by_range("idmap", Range::All).unwrap_or_default();
+        let mapped = mapping.len();
+        let retained: Vec<_> = mapping
+            .into_iter()
+            .filter_map(|x| x.deserialize().ok())
+            .filter(|(_, id)| ids.binary_search(id).is_ok())
+            .map(Serialization::serialize)
+            .collect::<Result<_, _>>()?;
+        if retained.len() != mapped {
+            storage.remove("idmap")?;
+            storage.append_all("idmap", &retained)?;
+            report.compacted += 1;
+        }
+        for key in storage.keys()? {
+            let owner = ["scan_", "status_", "results_", "annotations_"]
+                .iter()
+                .find_map(|x| key.strip_prefix(x));
+            match owner {
+                Some(id) if ids.binary_search(&id.to_string()).is_err() => {
+                    tracing::debug!(key, "removing leftover of a removed scan");
+                    // removes what is left when only a part of it got stored
+                    if storage.remove(&key).is_err() {
+                        storage.compact(&key)?;
+                    }
+                    report.removed += 1;
+                }
+                _ => {
+                    if storage.compact(&key)? > 0 {
+                        tracing::debug!(key, "compacted");
+                        report.compacted += 1;
+                    }
+                }
+            }
+        }
+        report.size = storage.size()?;
+        report.reclaimed = size.saturating_sub(report.size);
+        Ok(report)
+    }
+
+    /// Changes the annotations of a scan stored under key
+    async fn update_annotations<F, R>(&self, id: &str, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Vec<models::Annotation>) -> Result<R, Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        // fails when the scan does not exist
+        self.get_status(id).await?;
+        let key = format!("annotations_{id}");
+        let storage = Arc::clone(&self.storage);
+        spawn_blocking(move || {
+            let mut storage = storage.write().unwrap();
+            let mut annotations = Self::get_annotations_sync(&storage, &key)?;
+            let result = f(&mut annotations)?;
+            storage.put(&key, Serialization::serialize(annotations)?)?;
+            Ok(result)
+        })
+        .await
+        .unwrap()
+    }
 }
 
 #[async_trait]
@@ -118,6 +243,18 @@ where
         self.get_results_sync(id, from, to)
     }
 
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        let key = format!("results_{id}");
+        let storage = Arc::clone(&self.storage);
+        spawn_blocking(move || {
+            let storage = &storage.read().unwrap();
+            // there are no results until the first one got stored
+            Ok(storage.indices(&key).map(|x| x.len()).unwrap_or_default())
+        })
+        .await
+        .unwrap()
+    }
+
     async fn get_scan(&self, id: &str) -> Result<(Scan, Status), Error> {
         let key = format!("scan_{id}");
         let status_key = format!("status_{id}");
@@ -145,31 +282,9 @@ where
 
     async fn get_scan_ids(&self) -> Result<Vec<String>, Error> {
         let storage = Arc::clone(&self.storage);
-        spawn_blocking(move || {
-            let storage = &storage.read().unwrap();
-            let scans: Vec<Serialization<String>> = match storage.by_range("scans", Range::All) {
-                Ok(s) => s,
-                Err(scannerlib::storage::infisto::Error::IoError(
-                    scannerlib::storage::infisto::IoErrorKind::FileOpen,
-                    io::ErrorKind::NotFound,
-                )) => {
-                    vec![]
-                }
-                Err(e) => return Err(e.into()),
-            };
-            let mut scans: Vec<_> = scans
-                .into_iter()
-                .filter_map(|x| match x {
-                    Serialization::Serialized(_) => None,
-                    Serialization::Deserialized(x) => Some(x),
-                })
-                .collect();
-            scans.sort();
-            scans.dedup();
-            Ok(scans)
-        })
-        .await
-        .unwrap()
+        spawn_blocking(move || Self::get_scan_ids_sync(&storage.read().unwrap()))
+            .await
+            .unwrap()
     }
 
     async fn get_status(&self, id: &str) -> Result<Status, Error> {
@@ -190,6 +305,46 @@ where
     }
 }
 
+#[async_trait]
+impl<S> AnnotationStorer for Storage<S>
+where
+    S: IndexedByteStorage + Sync + Send + Clone + 'static,
+{
+    async fn add_annotation(
+        &self,
+        id: &str,
+        annotation: models::Annotation,
+    ) -> Result<models::Annotation, Error> {
+        self.update_annotations(id, move |annotations| {
+            let annotation = next_annotation(annotations, annotation);
+            annotations.push(annotation.clone());
+            Ok(annotation)
+        })
+        .await
+    }
+
+    async fn get_annotations(&self, id: &str) -> Result<Vec<models::Annotation>, Error> {
+        self.get_status(id).await?;
+        let key = format!("annotations_{id}");
+        let storage = Arc::clone(&self.storage);
+        spawn_blocking(move || Self::get_annotations_sync(&storage.read().unwrap(), &key))
+            .await
+            .unwrap()
+    }
+
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error> {
+        self.update_annotations(id, move |annotations| {
+            let idx = annotations
+                .iter()
+                .position(|x| x.id == annotation_id)
+                .ok_or(Error::NotFound)?;
+            annotations.remove(idx);
+            Ok(())
+        })
+        .await
+    }
+}
+
 #[async_trait]
 impl<S> AppendFetchResult for Storage<S>
 where
@@ -197,35 +352,33 @@ where
 {
     async fn append_fetched_result(&self, results: Vec<ScanResults>) -> Result<(), Error> {
         for r in results {
-            let id = &r.id;
-            let status = r.status;
-            self.update_status(id, status).await?;
-            if r.results.is_empty() {
-                continue;
-            }
-
-            let key = format!("results_{}", id);
-            let storage = Arc::clone(&self.storage);
-            tracing::trace!(key, results_len = r.results.len());
+            // the results are stored first so that a finished status implies that all results
+            // are available
+            if !r.results.is_empty() {
+                let key = format!("results_{}", r.id);
+                let storage = Arc::clone(&self.storage);
+                tracing::trace!(key, results_len = r.results.len());
 
-            spawn_blocking(move || {
-                let storage = &mut storage.write().unwrap();
                 let results = r.results;
-                let mut serialized_results = Vec::with_capacity(results.len());
-                let ilen = match storage.indices(&key) {
-                    Ok(x) => x.len(),
-                    Err(_) => 0,
-                };
-                for (i, mut result) in results.into_iter().enumerate() {
-                    result.id = ilen + i;
-                    let bytes = serde_json::to_vec(&result)?;
-                    serialized_results.push(bytes);
-                }
-                storage.append_all(&key, &serialized_results)?;
-                Ok::<_, Error>(())
-            })
-            .await
-            .unwrap()?
+                spawn_blocking(move || {
+                    let storage = &mut storage.write().unwrap();
+                    let mut serialized_results = Vec::with_capacity(results.len());
+                    let ilen = match storage.indices(&key) {
+                        Ok(x) => x.len(),
+                        Err(_) => 0,
+                    };
+                    for (i, mut result) in results.into_iter().enumerate() {
+                        result.id = ilen + i;
+                        let bytes = serde_json::to_vec(&result)?;
+                        serialized_results.push(bytes);
+                    }
+                    storage.append_all(&key, &serialized_results)?;
+                    Ok::<_, Error>(())
+                })
+                .await
+                .unwrap()?
+            }
+            self.update_status(&r.id, r.status).await?;
         }
         Ok(())
     }
@@ -260,6 +413,7 @@ where
         let key = format!("scan_{}", id);
         let status_key = format!("status_{}", id);
         let results_key = format!("results_{}", id);
+        let annotations_key = format!("annotations_{}", id);
         let storage = Arc::clone(&self.storage);
         let ids = self.get_scan_ids().await?;
         let ids: Vec<_> = ids
@@ -273,6 +427,7 @@ where
             let mut storage = storage.write().unwrap();
             tracing::debug!(results_key, "removing results");
             let _ = storage.remove(&results_key);
+            let _ = storage.remove(&annotations_key);
             tracing::debug!(key, "removing scan");
             storage.remove(&key)?;
             tracing::debug!(status_key, "removing status");
@@ -298,6 +453,14 @@ where
         .await
         .unwrap()
     }
+
+    async fn compact(&self) -> Result<Maintenance, Error> {
+        let storage = Arc::clone(&self.storage);
+        // the write lock prevents scans from being stored while their files are checked
+        spawn_blocking(move || Self::compact_sync(&mut storage.write().unwrap()))
+            .await
+            .unwrap()
+    }
 }
 
 #[async_trait]
@@ -389,6 +552,10 @@ where
         self.underlying.synchronize_feeds(hash).await
     }
 
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error> {
+        self.underlying.replace_vts(vts).await
+    }
+
     async fn oids(&self) -> Result<Box<dyn Iterator<Item = String> + Send>, Error> {
         self.underlying.oids().await
     }
@@ -397,6 +564,17 @@ where
         self.underlying.vts().await
     }
 
+    async fn vts_filtered<'a>(
+        &self,
+        filter: VtFilter,
+    ) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
+        self.underlying.vts_filtered(filter).await
+    }
+
+    async fn families(&self) -> Result<Vec<FamilySummary>, Error> {
+        self.underlying.families().await
+    }
+
     async fn feed_hash(&self) -> Vec<FeedHash> {
         self.underlying.feed_hash().await
     }
@@ -404,6 +582,10 @@ where
     async fn current_feed_version(&self) -> Result<String, Error> {
         todo!()
     }
+
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        self.underlying.feed_report().await
+    }
 }
 
 impl FromConfigAndFeeds for Storage<ChaCha20IndexFileStorer<IndexedFileStorer>> {
@@ -438,7 +620,14 @@ where
     where
         E: From<StorageError>,
     {
-        tracing::trace!(?key, ?result);
+        self.handle_results(key, vec![result])
+    }
+
+    fn handle_results<E>(&self, key: &ContextKey, results: Vec<models::Result>) -> Result<(), E>
+    where
+        E: From<StorageError>,
+    {
+        tracing::trace!(?key, ?results);
         let store = &mut self.storage.write().unwrap();
         let key = format!("results_{}", key.value());
 
@@ -446,7 +635,6 @@ where
             Ok(x) => x.len(),
             Err(_) => 0,
         };
-        let results = vec![result];
         let mut serialized_results = Vec::with_capacity(results.len());
         for (i, mut result) in results.into_iter().enumerate() {
             result.id = ilen + i;
@@ -729,4 +917,89 @@ pub(crate) mod tests {
         let key = "idmap";
         storage.remove(key).unwrap();
     }
+
+    #[tokio::test]
+    async fn annotations() {
+        let storage = example_feed_file_storage("/tmp/openvasd/file_storage_annotations").await;
+        let mut scan = Scan::default();
+        let id = uuid::Uuid::new_v4().to_string();
+        scan.scan_id.clone_from(&id);
+        storage.insert_scan(scan).await.unwrap();
+        let note = |x: &str| models::Annotation {
+            note: Some(x.to_string()),
+            ..Default::default()
+        };
+        assert!(storage.get_annotations(&id).await.unwrap().is_empty());
+        assert_eq!(storage.add_annotation(&id, note("a")).await.unwrap().id, 0);
+        assert_eq!(storage.add_annotation(&id, note("b")).await.unwrap().id, 1);
+        storage.remove_annotation(&id, 0).await.unwrap();
+        assert_eq!(storage.add_annotation(&id, note("c")).await.unwrap().id, 2);
+        let annotations = storage.get_annotations(&id).await.unwrap();
+        assert_eq!(
+            annotations.iter().map(|x| x.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(storage.add_annotation("unknown", note("d")).await.is_err());
+        storage.remove_scan(&id).await.unwrap();
+        assert!(storage.get_annotations(&id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn compact() {
+        use std::io::Write;
+
+        let path = "/tmp/openvasd/file_storage_compact";
+        let _ = fs::remove_dir_all(path);
+        let storage = example_feed_file_storage(path).await;
+        for id in ["kept", "removed", "kept"] {
+            let scan = Scan {
+                scan_id: id.to_string(),
+                ..Default::default()
+            };
+            storage.insert_scan(scan).await.unwrap();
+            storage
+                .add_scan_client_id(id.to_string(), "0".into())
+                .await
+                .unwrap();
+            if id == "removed" {
+                storage.remove_scan(id).await.unwrap();
+            }
+        }
+        let results = ScanResults {
+            id: "kept".to_string(),
+            status: Status::default(),
+            results: vec![models::Result::default(); 3],
+        };
+        storage.append_fetched_result(vec![results]).await.unwrap();
+        // results stored after their scan got removed and an interrupted append
+        let mut files = CachedIndexFileStorer::init(path).unwrap();
+        files.append("results_removed", b"result").unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(Path::new(path).join("results_kept.dat"))
+            .unwrap()
+            .write_all(b"interrupted")
+            .unwrap();
+
+        let report = storage.compact().await.unwrap();
+        assert_eq!(report.removed, 1);
+        // the scans list, the client mapping and the results
+        assert_eq!(report.compacted, 3);
+        assert!(report.reclaimed > 0);
+        assert_eq!(storage.get_scan_ids().await.unwrap(), vec!["kept"]);
+        assert_eq!(
+            storage.get_scans_of_client_id(&"0".into()).await.unwrap(),
+            vec!["kept", "kept"]
+        );
+        let results = storage.get_results("kept", None, None).await.unwrap();
+        assert_eq!(results.count(), 3);
+        assert_eq!(
+            storage.compact().await.unwrap(),
+            Maintenance {
+                size: report.size,
+                ..Default::default()
+            }
+        );
+        fs::remove_dir_all(path).unwrap();
+    }
 }
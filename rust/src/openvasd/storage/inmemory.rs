@@ -21,6 +21,8 @@ struct Progress {
     ///
     /// The reason that it is json is that we don't need it unless it is requested by the user.
     results: Vec<crypt::Encrypted>,
+    /// Annotations of the scan and its results
+    annotations: Vec<models::Annotation>,
 }
 
 #[derive(Debug)]
@@ -31,6 +33,7 @@ pub struct Storage<E> {
     underlying: Arc<DefaultDispatcher>,
     crypter: Arc<E>,
     feed_version: Arc<RwLock<String>>,
+    feed_report: Arc<RwLock<Option<feed::UpdateReport>>>,
 }
 
 impl<E> Storage<E>
@@ -45,27 +48,20 @@ where
             crypter: crypter.into(),
             underlying: DefaultDispatcher::default().into(),
             feed_version: Arc::new(RwLock::new(String::new())),
+            feed_report: Arc::new(RwLock::new(None)),
         }
     }
 
     fn new_progress(crypter: &E, mut scan: models::Scan) -> Result<Progress, Error> {
-        let credentials = scan
-            .target
-            .credentials
-            .into_iter()
-            .map(move |c| {
-                let c = c.map_password::<_, Error>(|p| {
-                    Ok(crypter.encrypt_sync(p.as_bytes().to_vec()).to_string())
-                });
-                c.unwrap()
-            })
-            .collect::<Vec<_>>();
-        scan.target.credentials = credentials;
+        scan.target = scan.target.map_credentials::<_, Error>(|c| {
+            c.map_password(|p| Ok(crypter.encrypt_sync(p.as_bytes().to_vec()).to_string()))
+        })?;
 
         Ok(Progress {
             scan,
             status: models::Status::default(),
             results: Vec::new(),
+            annotations: Vec::new(),
         })
     }
 
@@ -230,6 +226,42 @@ where
     }
 }
 
+#[async_trait]
+impl<E> AnnotationStorer for Storage<E>
+where
+    E: crate::crypt::Crypt + Send + Sync + 'static,
+{
+    async fn add_annotation(
+        &self,
+        id: &str,
+        annotation: models::Annotation,
+    ) -> Result<models::Annotation, Error> {
+        let mut scans = self.scans.write().unwrap();
+        let progress = scans.get_mut(id).ok_or(Error::NotFound)?;
+        let annotation = next_annotation(&progress.annotations, annotation);
+        progress.annotations.push(annotation.clone());
+        Ok(annotation)
+    }
+
+    async fn get_annotations(&self, id: &str) -> Result<Vec<models::Annotation>, Error> {
+        let scans = self.scans.read().unwrap();
+        let progress = scans.get(id).ok_or(Error::NotFound)?;
+        Ok(progress.annotations.clone())
+    }
+
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error> {
+        let mut scans = self.scans.write().unwrap();
+        let progress = scans.get_mut(id).ok_or(Error::NotFound)?;
+        let idx = progress
+            .annotations
+            .iter()
+            .position(|x| x.id == annotation_id)
+            .ok_or(Error::NotFound)?;
+        progress.annotations.remove(idx);
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl<E> ProgressGetter for Storage<E>
 where
@@ -252,16 +284,13 @@ where
 
     async fn get_decrypted_scan(&self, id: &str) -> Result<(models::Scan, models::Status), Error> {
         let (mut scan, status) = self.get_scan(id).await?;
-        let mut decrypted_credentials = Vec::with_capacity(scan.target.credentials.len());
-        for c in scan.target.credentials.into_iter() {
-            let c = c.map_password::<_, Error>(|p| {
+        scan.target = scan.target.map_credentials::<_, Error>(|c| {
+            c.map_password(|p| {
                 let enc = p.try_into()?;
                 let dec = self.crypter.decrypt_sync(&enc);
                 Ok(String::from_utf8(dec)?)
-            })?;
-            decrypted_credentials.push(c);
-        }
-        scan.target.credentials = decrypted_credentials;
+            })
+        })?;
 
         Ok((scan, status))
     }
@@ -281,6 +310,12 @@ where
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, Error> {
         self.get_results_sync(id, from, to)
     }
+
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        let scans = self.scans.read().unwrap();
+        let progress = scans.get(id).ok_or(Error::NotFound)?;
+        Ok(progress.results.len())
+    }
 }
 
 impl<E> FromConfigAndFeeds for Storage<E>
@@ -333,10 +368,17 @@ where
         let mut updates = JoinSet::new();
 
         for h in hash {
+            let loader = h.loader();
             let path = h.path;
             match h.typus {
                 FeedType::NASL => {
-                    _ = updates.spawn(super::update_nasl_feed(path, self.underlying.clone()))
+                    let underlying = self.underlying.clone();
+                    let feed_report = self.feed_report.clone();
+                    _ = updates.spawn(async move {
+                        let report = super::update_nasl_feed(loader, underlying).await?;
+                        *feed_report.write().unwrap() = Some(report);
+                        Ok(())
+                    })
                 }
                 FeedType::Advisories => {
                     _ = updates.spawn(super::update_notus_feed(path, self.underlying.clone()))
@@ -353,6 +395,20 @@ where
         Ok(())
     }
 
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error> {
+        let underlying = self.underlying.clone();
+        tokio::task::spawn_blocking(move || {
+            let vts = vts
+                .into_iter()
+                .map(|vt| (vt.filename.clone(), vt))
+                .collect();
+            underlying.replace_vts(vts)?;
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
     async fn vts<'a>(&self) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
         // TODO: change that setup to a channel based construct to get rid of collecting and
         // cloning, see: response.rs#ok_bytestream. This would effectively change the response to a
@@ -377,6 +433,10 @@ where
         let v = self.feed_version.read().unwrap();
         Ok(v.clone())
     }
+
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        Ok(self.feed_report.read().unwrap().clone())
+    }
 }
 
 impl<C> super::ResultHandler for Storage<C>
@@ -481,6 +541,20 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn replace_vts() {
+        let storage = Storage::default();
+        let vt = |oid: &str| Nvt {
+            oid: oid.to_owned(),
+            filename: format!("{oid}.nasl"),
+            ..Default::default()
+        };
+        storage.replace_vts(vec![vt("1"), vt("2")]).await.unwrap();
+        storage.replace_vts(vec![vt("3")]).await.unwrap();
+        let oids: Vec<_> = storage.oids().await.unwrap().collect();
+        assert_eq!(oids, vec!["3"]);
+    }
+
     #[tokio::test]
     async fn store_delete_scan() {
         let storage = Storage::default();
@@ -625,4 +699,25 @@ mod tests {
         let (_, status) = storage.get_scan(&id).await.unwrap();
         assert_eq!(status.status, models::Phase::Requested);
     }
+
+    #[tokio::test]
+    async fn annotations() {
+        let storage = Storage::default();
+        let id = store_scan(&storage).await;
+        let note = |x: &str| models::Annotation {
+            note: Some(x.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(storage.add_annotation(&id, note("a")).await.unwrap().id, 0);
+        assert_eq!(storage.add_annotation(&id, note("b")).await.unwrap().id, 1);
+        assert_eq!(storage.get_annotations(&id).await.unwrap().len(), 2);
+        storage.remove_annotation(&id, 0).await.unwrap();
+        assert!(storage.remove_annotation(&id, 0).await.is_err());
+        assert_eq!(storage.add_annotation(&id, note("c")).await.unwrap().id, 2);
+        let annotations = storage.get_annotations(&id).await.unwrap();
+        assert_eq!(annotations[0].note, Some("b".to_string()));
+        assert!(storage.add_annotation("unknown", note("d")).await.is_err());
+        storage.remove_scan(&id).await.unwrap();
+        assert!(storage.get_annotations(&id).await.is_err());
+    }
 }
@@ -5,17 +5,21 @@
 pub mod file;
 pub mod inmemory;
 pub mod redis;
+pub mod results;
+pub mod trend;
 pub use scannerlib::storage::Storage as NaslStorage;
 use scannerlib::{
-    models::{self, Scan, Status, VulnerabilityData},
+    models::{self, Annotation, Scan, Status, VulnerabilityData},
     storage::{
-        item::Nvt, ContextKey, DefaultDispatcher, Dispatcher, Field, FieldKeyResult, Kb, Remover,
+        item::{Nvt, TagKey, ACT},
+        types::Primitive,
+        ContextKey, DefaultDispatcher, Dispatcher, Field, FieldKeyResult, Kb, KbSnapshot, Remover,
         Retrieve, Retriever, StorageError,
     },
 };
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -25,7 +29,7 @@ use scannerlib::models::scanner::ScanResults;
 
 use crate::{config::Config, controller::ClientHash, crypt};
 use scannerlib::{
-    feed::{self, HashSumNameLoader, Update},
+    feed::{self, LayeredNameLoader, Update},
     nasl::FSPluginLoader,
     notus::{AdvisoryLoader, HashsumAdvisoryLoader},
 };
@@ -124,6 +128,32 @@ pub trait ProgressGetter {
         from: Option<usize>,
         to: Option<usize>,
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, Error>;
+    /// Returns the status of a scan together with its results like [Self::get_results].
+    ///
+    /// The status is read first as a finished status is only stored after all results, the
+    /// results of a done scan are therefore complete.
+    async fn get_status_and_results(
+        &self,
+        id: &str,
+        from: Option<usize>,
+        to: Option<usize>,
+    ) -> Result<(Status, Box<dyn Iterator<Item = Vec<u8>> + Send>), Error> {
+        let status = self.get_status(id).await?;
+        let results = self.get_results(id, from, to).await?;
+        Ok((status, results))
+    }
+    /// Returns the amount of stored results of a scan.
+    ///
+    /// As results are only appended it is used as the version of the results.
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        Ok(self.get_results(id, None, None).await?.count())
+    }
+    /// Returns the knowledge base of a host of the scan.
+    ///
+    /// Only storages running the scans themselves hold knowledge bases, all others return none.
+    async fn get_kb_snapshot(&self, _id: &str, _host: &str) -> Result<Option<KbSnapshot>, Error> {
+        Ok(None)
+    }
 }
 
 pub type Hash = String;
@@ -143,6 +173,8 @@ pub enum FeedType {
 pub struct FeedHash {
     pub hash: Hash,
     pub path: PathBuf,
+    /// Directories stacked on top of path, only used for NASL feeds
+    pub overlays: Vec<PathBuf>,
     pub typus: FeedType,
 }
 
@@ -154,6 +186,7 @@ impl FeedHash {
         FeedHash {
             hash: String::new(),
             path: p.as_ref().to_path_buf(),
+            overlays: vec![],
             typus: FeedType::Advisories,
         }
     }
@@ -165,9 +198,126 @@ impl FeedHash {
         FeedHash {
             hash: String::new(),
             path: p.as_ref().to_path_buf(),
+            overlays: vec![],
             typus: FeedType::NASL,
         }
     }
+
+    /// Sets the directories stacked on top of the feed path.
+    pub fn with_overlays(mut self, overlays: Vec<PathBuf>) -> Self {
+        self.overlays = overlays;
+        self
+    }
+
+    /// Returns a loader of the feed path including its overlays.
+    pub fn loader(&self) -> FSPluginLoader {
+        FSPluginLoader::new(&self.path).with_overlays(&self.overlays)
+    }
+}
+
+/// Filters NVTs by their metadata and selects a range of the matching NVTs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VtFilter {
+    /// Family of the NVT
+    pub family: Option<String>,
+    /// Category of the NVT
+    pub category: Option<ACT>,
+    /// Case insensitive part of the name of the NVT
+    pub name: Option<String>,
+    /// Index of the first matching NVT to return
+    pub begin: usize,
+    /// Index after the last matching NVT to return
+    pub end: Option<usize>,
+    /// Includes NVTs marked as deprecated
+    pub deprecated: bool,
+    /// Feed subset of the tenant of the client
+    pub subset: Option<crate::tenancy::FeedSubset>,
+}
+
+impl VtFilter {
+    /// Returns true if the filter neither restricts the metadata nor the range and includes
+    /// deprecated NVTs.
+    pub fn is_empty(&self) -> bool {
+        self == &Self {
+            deprecated: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true when the metadata of the nvt matches the filter.
+    pub fn matches(&self, nvt: &Nvt) -> bool {
+        if !self.deprecated && nvt.is_deprecated() {
+            return false;
+        }
+        if matches!(&self.family, Some(family) if family != &nvt.family) {
+            return false;
+        }
+        if matches!(&self.category, Some(category) if category != &nvt.category) {
+            return false;
+        }
+        if matches!(&self.subset, Some(subset) if !subset.allows(nvt)) {
+            return false;
+        }
+        match &self.name {
+            Some(name) => nvt.name.to_lowercase().contains(&name.to_lowercase()),
+            None => true,
+        }
+    }
+
+    /// Returns the matching NVTs within the range of the filter.
+    ///
+    /// As the storages do not guarantee an order the matching NVTs are sorted by their oid when a
+    /// range is set, so that the pages are stable.
+    pub fn apply<'a, I>(self, vts: I) -> Box<dyn Iterator<Item = Nvt> + Send + 'a>
+    where
+        I: Iterator<Item = Nvt> + Send + 'a,
+    {
+        if self.begin == 0 && self.end.is_none() {
+            return Box::new(vts.filter(move |x| self.matches(x)));
+        }
+        let mut vts = vts.filter(|x| self.matches(x)).collect::<Vec<_>>();
+        vts.sort_by(|a, b| a.oid.cmp(&b.oid));
+        let end = self.end.unwrap_or(vts.len()).min(vts.len());
+        let begin = self.begin.min(end);
+        Box::new(vts.into_iter().skip(begin).take(end - begin))
+    }
+}
+
+/// Summary of the VTs within a family
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FamilySummary {
+    /// Name of the family
+    pub family: String,
+    /// Amount of VTs within the family
+    pub count: usize,
+    /// Amount of VTs per category
+    pub categories: BTreeMap<ACT, usize>,
+    /// Newest last modification of the VTs as unix timestamp
+    pub last_modification: Option<i64>,
+}
+
+impl FamilySummary {
+    /// Summarizes the given VTs by their family, ordered by the name of the family.
+    pub fn aggregate<I>(vts: I) -> Vec<Self>
+    where
+        I: Iterator<Item = Nvt>,
+    {
+        let mut families: BTreeMap<String, Self> = BTreeMap::new();
+        for vt in vts {
+            let modified = match vt.tag.get(&TagKey::LastModification) {
+                Some(Primitive::Number(x)) => Some(*x),
+                _ => None,
+            };
+            let summary = families.entry(vt.family).or_insert_with_key(|family| Self {
+                family: family.clone(),
+                ..Default::default()
+            });
+            summary.count += 1;
+            *summary.categories.entry(vt.category).or_default() += 1;
+            summary.last_modification = summary.last_modification.max(modified);
+        }
+        families.into_values().collect()
+    }
 }
 
 #[async_trait]
@@ -181,6 +331,12 @@ pub trait NVTStorer {
     /// directories and update the meta information.
     async fn synchronize_feeds(&self, hash: Vec<FeedHash>) -> Result<(), Error>;
 
+    /// Replaces the stored NVTs by the given ones.
+    ///
+    /// This is used when the feed is processed centrally and the NVTs are uploaded instead of
+    /// being loaded from a local feed.
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error>;
+
     /// Retrieves just all oids.
     async fn oids(&self) -> Result<Box<dyn Iterator<Item = String> + Send>, Error> {
         let vts = self.vts().await?;
@@ -196,11 +352,29 @@ pub trait NVTStorer {
         Ok(self.vts().await?.find(|x| x.oid == oid))
     }
 
+    /// Retrieves the NVTs matching the given filter.
+    async fn vts_filtered<'a>(
+        &self,
+        filter: VtFilter,
+    ) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
+        Ok(filter.apply(self.vts().await?))
+    }
+
+    /// Summarizes the NVTs by their family.
+    async fn families(&self) -> Result<Vec<FamilySummary>, Error> {
+        Ok(FamilySummary::aggregate(self.vts().await?))
+    }
+
     /// Returns the currently stored feed hash.
     async fn feed_hash(&self) -> Vec<FeedHash>;
 
     /// Returns the current feed version, if any.
     async fn current_feed_version(&self) -> Result<String, Error>;
+
+    /// Returns the report of the last nasl feed update, if any.
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        Ok(None)
+    }
 }
 
 #[async_trait]
@@ -216,6 +390,62 @@ pub trait ScanStorer {
     ///
     /// This is required when a scan is started or stopped.
     async fn update_status(&self, id: &str, status: Status) -> Result<(), Error>;
+    /// Stores the knowledge base of a host of the scan, e.g. when it is imported.
+    ///
+    /// Only storages running the scans themselves hold knowledge bases, all others drop it.
+    async fn store_kb_snapshot(
+        &self,
+        _id: &str,
+        _host: &str,
+        _snapshot: KbSnapshot,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Compacts the stored data and removes the leftovers of removed scans.
+    ///
+    /// Only storages keeping their data in files have something to reclaim, all others return an
+    /// empty report.
+    async fn compact(&self) -> Result<Maintenance, Error> {
+        Ok(Maintenance::default())
+    }
+}
+
+/// Outcome of a storage maintenance
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Maintenance {
+    /// Amount of rewritten entries
+    pub compacted: usize,
+    /// Amount of removed entries not belonging to a stored scan
+    pub removed: usize,
+    /// Bytes used by the storage after the maintenance
+    pub size: u64,
+    /// Bytes freed by the maintenance
+    pub reclaimed: u64,
+}
+
+#[async_trait]
+/// A trait for storing annotations of scans and their results.
+///
+/// Annotations are removed together with their scan.
+pub trait AnnotationStorer {
+    /// Stores an annotation and returns it with its assigned id and creation time.
+    async fn add_annotation(&self, id: &str, annotation: Annotation) -> Result<Annotation, Error>;
+    /// Returns the annotations of a scan ordered by their id.
+    async fn get_annotations(&self, id: &str) -> Result<Vec<Annotation>, Error>;
+    /// Removes an annotation of a scan.
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error>;
+}
+
+/// Assigns the next id and the creation time to an annotation appended to annotations.
+///
+/// Ids of removed annotations are not reused.
+fn next_annotation(annotations: &[Annotation], mut annotation: Annotation) -> Annotation {
+    annotation.id = annotations.last().map_or(0, |x| x.id + 1);
+    annotation.created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    annotation
 }
 
 #[async_trait]
@@ -235,6 +465,24 @@ where
     }
 }
 
+#[async_trait]
+impl<T> AnnotationStorer for Arc<T>
+where
+    T: AnnotationStorer + Send + Sync,
+{
+    async fn add_annotation(&self, id: &str, annotation: Annotation) -> Result<Annotation, Error> {
+        self.as_ref().add_annotation(id, annotation).await
+    }
+
+    async fn get_annotations(&self, id: &str) -> Result<Vec<Annotation>, Error> {
+        self.as_ref().get_annotations(id).await
+    }
+
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error> {
+        self.as_ref().remove_annotation(id, annotation_id).await
+    }
+}
+
 #[async_trait]
 impl<T> ProgressGetter for Arc<T>
 where
@@ -264,6 +512,14 @@ where
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, Error> {
         self.as_ref().get_results(id, from, to).await
     }
+
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        self.as_ref().count_results(id).await
+    }
+
+    async fn get_kb_snapshot(&self, id: &str, host: &str) -> Result<Option<KbSnapshot>, Error> {
+        self.as_ref().get_kb_snapshot(id, host).await
+    }
 }
 
 #[async_trait]
@@ -281,6 +537,19 @@ where
     async fn update_status(&self, id: &str, status: Status) -> Result<(), Error> {
         self.as_ref().update_status(id, status).await
     }
+
+    async fn store_kb_snapshot(
+        &self,
+        id: &str,
+        host: &str,
+        snapshot: KbSnapshot,
+    ) -> Result<(), Error> {
+        self.as_ref().store_kb_snapshot(id, host, snapshot).await
+    }
+
+    async fn compact(&self) -> Result<Maintenance, Error> {
+        self.as_ref().compact().await
+    }
 }
 
 #[async_trait]
@@ -292,6 +561,10 @@ where
         self.as_ref().synchronize_feeds(hash).await
     }
 
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error> {
+        self.as_ref().replace_vts(vts).await
+    }
+
     async fn oids(&self) -> Result<Box<dyn Iterator<Item = String> + Send>, Error> {
         self.as_ref().oids().await
     }
@@ -304,6 +577,17 @@ where
         self.as_ref().vt_by_oid(oid).await
     }
 
+    async fn vts_filtered<'a>(
+        &self,
+        filter: VtFilter,
+    ) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
+        self.as_ref().vts_filtered(filter).await
+    }
+
+    async fn families(&self) -> Result<Vec<FamilySummary>, Error> {
+        self.as_ref().families().await
+    }
+
     async fn feed_hash(&self) -> Vec<FeedHash> {
         self.as_ref().feed_hash().await
     }
@@ -311,6 +595,10 @@ where
     async fn current_feed_version(&self) -> Result<String, Error> {
         self.as_ref().current_feed_version().await
     }
+
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        self.as_ref().feed_report().await
+    }
 }
 
 #[async_trait]
@@ -345,15 +633,52 @@ where
 }
 
 #[async_trait]
-/// Combines the traits `ProgressGetter`, `ScanStorer` and `AppendFetchResult`.
+/// Combines the traits `ProgressGetter`, `ScanStorer`, `AppendFetchResult` and `AnnotationStorer`.
 pub trait Storage:
-    ProgressGetter + ScanStorer + AppendFetchResult + NVTStorer + ScanIDClientMapper
+    ProgressGetter + ScanStorer + AppendFetchResult + NVTStorer + ScanIDClientMapper + AnnotationStorer
 {
+    /// Aggregates the findings of the runs of a scan series, given by their number and id.
+    async fn trend(&self, runs: &[(usize, String)]) -> Result<trend::Trend, Error>
+    where
+        Self: Sync,
+    {
+        let mut severities = HashMap::new();
+        let mut inputs = Vec::with_capacity(runs.len());
+        for (run, id) in runs {
+            let findings: std::collections::BTreeSet<_> = self
+                .get_results(id, None, None)
+                .await?
+                .filter_map(|x| serde_json::from_slice::<models::Result>(&x).ok())
+                .filter_map(|x| x.finding_key())
+                .collect();
+            for finding in &findings {
+                if !severities.contains_key(&finding.oid) {
+                    let severity = self
+                        .vt_by_oid(&finding.oid)
+                        .await?
+                        .and_then(|x| trend::severity(&x));
+                    severities.insert(finding.oid.clone(), severity);
+                }
+            }
+            inputs.push(trend::Run {
+                scan_id: id.clone(),
+                run: *run,
+                status: self.get_status(id).await?,
+                findings,
+            });
+        }
+        Ok(trend::Trend::aggregate(inputs, &severities))
+    }
 }
 
 #[async_trait]
 impl<T> Storage for T where
-    T: ProgressGetter + ScanStorer + AppendFetchResult + NVTStorer + ScanIDClientMapper
+    T: ProgressGetter
+        + ScanStorer
+        + AppendFetchResult
+        + NVTStorer
+        + ScanIDClientMapper
+        + AnnotationStorer
 {
 }
 
@@ -396,19 +721,28 @@ async fn update_notus_feed(p: PathBuf, store: Arc<DefaultDispatcher>) -> Result<
     .expect("notus handler to be executed.")
 }
 
-async fn update_nasl_feed(p: PathBuf, store: Arc<DefaultDispatcher>) -> Result<(), Error> {
-    let nasl_feed_path = p;
+async fn update_nasl_feed(
+    loader: FSPluginLoader,
+    store: Arc<DefaultDispatcher>,
+) -> Result<feed::UpdateReport, Error> {
     store.as_ref().clean_vts()?;
 
     tracing::debug!("starting nasl feed update");
     let oversion = "0.1";
-    let loader = FSPluginLoader::new(nasl_feed_path);
-    let verifier = HashSumNameLoader::sha256(&loader)?;
+    let layers = loader.layers();
+    let verifier = LayeredNameLoader::sha256(&layers)?;
 
     let fu = Update::init(oversion, 5, &loader, &store, verifier);
-    fu.perform_update().await?;
+    let report = fu.report().await?;
+    if report.failed() > 0 {
+        tracing::warn!(
+            failed = report.failed(),
+            total = report.total(),
+            "nasl feed update finished with failures, see /feed/report"
+        );
+    }
     tracing::debug!("finished nasl feed update");
-    Ok(())
+    Ok(report)
 }
 
 pub trait ResultHandler {
@@ -416,6 +750,15 @@ pub trait ResultHandler {
     fn handle_result<E>(&self, key: &ContextKey, result: models::Result) -> Result<(), E>
     where
         E: From<StorageError>;
+    /// Stores multiple results of the same scan at once.
+    fn handle_results<E>(&self, key: &ContextKey, results: Vec<models::Result>) -> Result<(), E>
+    where
+        E: From<StorageError>,
+    {
+        results
+            .into_iter()
+            .try_for_each(|result| self.handle_result(key, result))
+    }
     fn remove_result<E>(
         &self,
         key: &ContextKey,
@@ -429,16 +772,24 @@ pub trait ResultHandler {
 /// nasl-interpreter::Interpreter.
 ///
 /// This is used for file storage and inmemeory storage.
-pub struct UserNASLStorageForKBandVT<T>(T)
+///
+/// Results of scripts are stored in the background via a [results::ResultChannel].
+pub struct UserNASLStorageForKBandVT<T>
 where
-    T: Storage + ResultHandler + Sync + Send;
+    T: Storage + ResultHandler + Sync + Send,
+{
+    storage: Arc<T>,
+    results: results::ResultChannel,
+}
 
 impl<T> UserNASLStorageForKBandVT<T>
 where
-    T: Storage + ResultHandler + Sync + Send,
+    T: Storage + ResultHandler + Sync + Send + 'static,
 {
     pub fn new(underlying: T) -> Self {
-        Self(underlying)
+        let storage = Arc::new(underlying);
+        let results = results::ResultChannel::new(storage.clone());
+        Self { storage, results }
     }
 }
 
@@ -447,13 +798,20 @@ where
     T: Storage + ResultHandler + Sync + Send,
 {
     fn underlying_storage(&self) -> &Arc<DefaultDispatcher> {
-        self.0.underlying_storage()
+        self.storage.underlying_storage()
     }
     fn handle_result<E>(&self, key: &ContextKey, result: models::Result) -> Result<(), E>
     where
         E: From<StorageError>,
     {
-        self.0.handle_result(key, result)
+        self.storage.handle_result(key, result)
+    }
+
+    fn handle_results<E>(&self, key: &ContextKey, results: Vec<models::Result>) -> Result<(), E>
+    where
+        E: From<StorageError>,
+    {
+        self.storage.handle_results(key, results)
     }
 
     fn remove_result<E>(
@@ -464,7 +822,7 @@ where
     where
         E: From<StorageError>,
     {
-        self.0.remove_result(key, idx)
+        self.storage.remove_result(key, idx)
     }
 }
 
@@ -488,6 +846,10 @@ where
         self.underlying_storage().retrieve(key, scope)
     }
 
+    fn retrieve_kb_snapshot(&self, key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        self.underlying_storage().retrieve_kb_snapshot(key)
+    }
+
     fn retrieve_by_field(&self, field: Field, scope: Retrieve) -> FieldKeyResult {
         // We should never try to return results without an ID
         self.underlying_storage().retrieve_by_field(field, scope)
@@ -505,10 +867,7 @@ where
 {
     fn dispatch(&self, key: &ContextKey, scope: Field) -> Result<(), StorageError> {
         match scope {
-            Field::Result(result) => {
-                // we may already run in an specialized thread therefore we use current thread.
-                self.handle_result(key, *result)
-            }
+            Field::Result(result) => self.results.send(key, result),
             _ => self
                 .underlying_storage()
                 .as_dispatcher()
@@ -520,6 +879,18 @@ where
         self.underlying_storage().on_exit(key)
     }
 
+    fn dispatch_batch(&self, key: &ContextKey, fields: Vec<Field>) -> Result<(), StorageError> {
+        let (results, fields): (Vec<_>, Vec<_>) = fields
+            .into_iter()
+            .partition(|x| matches!(x, Field::Result(_)));
+        for result in results {
+            self.dispatch(key, result)?;
+        }
+        self.underlying_storage()
+            .as_dispatcher()
+            .dispatch_batch(key, fields)
+    }
+
     fn dispatch_replace(&self, _: &ContextKey, _scope: Field) -> Result<(), StorageError> {
         Ok(())
     }
@@ -552,18 +923,18 @@ where
     T: Storage + ResultHandler + Send + Sync,
 {
     async fn get_scan(&self, id: &str) -> Result<(Scan, Status), Error> {
-        self.0.get_scan(id).await
+        self.storage.get_scan(id).await
     }
     async fn get_decrypted_scan(&self, id: &str) -> Result<(Scan, Status), Error> {
-        self.0.get_decrypted_scan(id).await
+        self.storage.get_decrypted_scan(id).await
     }
 
     async fn get_scan_ids(&self) -> Result<Vec<String>, Error> {
-        self.0.get_scan_ids().await
+        self.storage.get_scan_ids().await
     }
     /// Returns the status of a scan.
     async fn get_status(&self, id: &str) -> Result<Status, Error> {
-        self.0.get_status(id).await
+        self.storage.get_status(id).await
     }
     /// Returns the results of a scan as json bytes.
     ///
@@ -575,7 +946,18 @@ where
         from: Option<usize>,
         to: Option<usize>,
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, Error> {
-        self.0.get_results(id, from, to).await
+        self.storage.get_results(id, from, to).await
+    }
+
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        self.storage.count_results(id).await
+    }
+
+    async fn get_kb_snapshot(&self, id: &str, host: &str) -> Result<Option<KbSnapshot>, Error> {
+        let key = ContextKey::Scan(id.to_string(), Some(host.to_string()));
+        self.underlying_storage()
+            .retrieve_kb_snapshot(&key)
+            .map_err(|e| Error::Storage(Box::new(e)))
     }
 }
 #[async_trait]
@@ -584,13 +966,52 @@ where
     T: Storage + ResultHandler + Send + Sync,
 {
     async fn insert_scan(&self, t: Scan) -> Result<(), Error> {
-        self.0.insert_scan(t).await
+        self.storage.insert_scan(t).await
     }
     async fn remove_scan(&self, id: &str) -> Result<(), Error> {
-        self.0.remove_scan(id).await
+        // otherwise pending results would be stored after the scan is removed
+        if let Err(e) = self.results.flush(id).await {
+            tracing::warn!(id, %e, "removing scan with unstored results");
+        }
+        self.storage.remove_scan(id).await
     }
     async fn update_status(&self, id: &str, status: Status) -> Result<(), Error> {
-        self.0.update_status(id, status).await
+        self.storage.update_status(id, status).await
+    }
+    async fn store_kb_snapshot(
+        &self,
+        id: &str,
+        host: &str,
+        snapshot: KbSnapshot,
+    ) -> Result<(), Error> {
+        let key = ContextKey::Scan(id.to_string(), Some(host.to_string()));
+        for kb in snapshot.items {
+            self.underlying_storage()
+                .dispatch(&key, Field::KB(kb))
+                .map_err(|e| Error::Storage(Box::new(e)))?;
+        }
+        Ok(())
+    }
+    async fn compact(&self) -> Result<Maintenance, Error> {
+        self.storage.compact().await
+    }
+}
+
+#[async_trait]
+impl<T> AnnotationStorer for UserNASLStorageForKBandVT<T>
+where
+    T: Storage + ResultHandler + Send + Sync,
+{
+    async fn add_annotation(&self, id: &str, annotation: Annotation) -> Result<Annotation, Error> {
+        self.storage.add_annotation(id, annotation).await
+    }
+
+    async fn get_annotations(&self, id: &str) -> Result<Vec<Annotation>, Error> {
+        self.storage.get_annotations(id).await
+    }
+
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error> {
+        self.storage.remove_annotation(id, annotation_id).await
     }
 }
 
@@ -600,7 +1021,14 @@ where
     T: Storage + ResultHandler + Send + Sync,
 {
     async fn append_fetched_result(&self, results: Vec<ScanResults>) -> Result<(), Error> {
-        self.0.append_fetched_result(results).await
+        // A finished status implies that all results are available.
+        for r in results.iter().filter(|r| r.status.is_done()) {
+            self.results
+                .flush(&r.id)
+                .await
+                .map_err(|e| Error::Storage(Box::new(e)))?;
+        }
+        self.storage.append_fetched_result(results).await
     }
 }
 
@@ -610,27 +1038,46 @@ where
     T: Storage + ResultHandler + Send + Sync,
 {
     async fn synchronize_feeds(&self, hash: Vec<FeedHash>) -> Result<(), Error> {
-        self.0.synchronize_feeds(hash).await
+        self.storage.synchronize_feeds(hash).await
+    }
+
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error> {
+        self.storage.replace_vts(vts).await
     }
 
     async fn oids(&self) -> Result<Box<dyn Iterator<Item = String> + Send>, Error> {
-        self.0.oids().await
+        self.storage.oids().await
     }
 
     async fn vts<'a>(&self) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
-        self.0.vts().await
+        self.storage.vts().await
     }
 
     async fn vt_by_oid(&self, oid: &str) -> Result<Option<Nvt>, Error> {
-        self.0.vt_by_oid(oid).await
+        self.storage.vt_by_oid(oid).await
+    }
+
+    async fn vts_filtered<'a>(
+        &self,
+        filter: VtFilter,
+    ) -> Result<Box<dyn Iterator<Item = Nvt> + Send + 'a>, Error> {
+        self.storage.vts_filtered(filter).await
+    }
+
+    async fn families(&self) -> Result<Vec<FamilySummary>, Error> {
+        self.storage.families().await
     }
 
     async fn feed_hash(&self) -> Vec<FeedHash> {
-        self.0.feed_hash().await
+        self.storage.feed_hash().await
     }
 
     async fn current_feed_version(&self) -> Result<String, Error> {
-        self.0.current_feed_version().await
+        self.storage.current_feed_version().await
+    }
+
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        self.storage.feed_report().await
     }
 }
 
@@ -644,23 +1091,23 @@ where
         scan_id: String,
         client_id: ClientHash,
     ) -> Result<(), Error> {
-        self.0.add_scan_client_id(scan_id, client_id).await
+        self.storage.add_scan_client_id(scan_id, client_id).await
     }
     async fn remove_scan_id<I>(&self, scan_id: I) -> Result<(), Error>
     where
         I: AsRef<str> + Send + 'static,
     {
-        self.0.remove_scan_id(scan_id).await
+        self.storage.remove_scan_id(scan_id).await
     }
 
     async fn get_scans_of_client_id(&self, client_id: &ClientHash) -> Result<Vec<String>, Error> {
-        self.0.get_scans_of_client_id(client_id).await
+        self.storage.get_scans_of_client_id(client_id).await
     }
 
     async fn is_client_allowed<I>(&self, scan_id: I, client_id: &ClientHash) -> Result<bool, Error>
     where
         I: AsRef<str> + Send + 'static,
     {
-        self.0.is_client_allowed(scan_id, client_id).await
+        self.storage.is_client_allowed(scan_id, client_id).await
     }
 }
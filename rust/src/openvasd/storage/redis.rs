@@ -5,7 +5,7 @@
 use std::{path::PathBuf, sync::Arc};
 
 use async_trait::async_trait;
-use scannerlib::models::{self, Scan, Status, VulnerabilityData};
+use scannerlib::models::{self, Annotation, Scan, Status, VulnerabilityData};
 use scannerlib::nasl::FSPluginLoader;
 use scannerlib::storage::item::Nvt;
 use scannerlib::storage::redis::{
@@ -24,12 +24,13 @@ use crate::{config::Config, controller::ClientHash, storage::FeedType};
 use scannerlib::models::scanner::ScanResults;
 
 use super::{
-    AppendFetchResult, Error, FeedHash, FromConfigAndFeeds, NVTStorer, ProgressGetter,
-    ScanIDClientMapper, ScanStorer,
+    AnnotationStorer, AppendFetchResult, Error, FeedHash, FromConfigAndFeeds, NVTStorer,
+    ProgressGetter, ScanIDClientMapper, ScanStorer,
 };
 
 pub struct Storage<T> {
     hash: RwLock<Vec<FeedHash>>,
+    feed_report: Arc<RwLock<Option<feed::UpdateReport>>>,
 
     url: Arc<String>,
     underlying: T,
@@ -39,6 +40,7 @@ impl<T> Storage<T> {
     pub fn new(underlying: T, url: String, feed: Vec<FeedHash>) -> Storage<T> {
         Storage {
             hash: RwLock::new(feed),
+            feed_report: Arc::new(RwLock::new(None)),
             url: Arc::new(url),
             underlying,
         }
@@ -79,13 +81,20 @@ impl<T> Storage<T> {
 
     async fn update_nasl(
         url: Arc<String>,
-        nasl_feed_path: PathBuf,
+        loader: FSPluginLoader,
         current_feed: String,
+        feed_report: Arc<RwLock<Option<feed::UpdateReport>>>,
     ) -> Result<(), Error> {
         tracing::debug!("starting nasl feed update");
         let oversion = "0.1";
-        let loader = FSPluginLoader::new(nasl_feed_path);
-        let verifier = feed::HashSumNameLoader::sha256(&loader)?;
+        let layers = loader.layers();
+        // only the nasl files are verified by the update, the includes when they are loaded
+        let verifier = feed::LayeredNameLoader::sha256(&layers)?.filter(|x| {
+            x.as_ref()
+                .map(|x| x.get_filename().ends_with(".nasl"))
+                .unwrap_or(true)
+        });
+        let verifier = feed::ParallelVerifier::new(verifier, num_cpus::get());
 
         let redis_cache: CacheDispatcher<RedisCtx> =
             redis::CacheDispatcher::init(&url, FEEDUPDATE_SELECTOR)?;
@@ -94,7 +103,15 @@ impl<T> Storage<T> {
         if !fu.feed_is_outdated(current_feed).await.unwrap() {
             return Ok(());
         }
-        fu.perform_update().await?;
+        let report = fu.report().await?;
+        if report.failed() > 0 {
+            tracing::warn!(
+                failed = report.failed(),
+                total = report.total(),
+                "nasl feed update finished with failures, see /feed/report"
+            );
+        }
+        *feed_report.write().await = Some(report);
         tracing::debug!("finished nasl feed update");
         Ok(())
     }
@@ -153,6 +170,10 @@ where
     ) -> Result<Box<dyn Iterator<Item = Vec<u8>> + Send>, Error> {
         self.underlying.get_results(id, from, to).await
     }
+
+    async fn count_results(&self, id: &str) -> Result<usize, Error> {
+        self.underlying.count_results(id).await
+    }
 }
 
 impl From<redis::DbError> for super::Error {
@@ -191,8 +212,9 @@ where
                     let current_feed = self.current_feed_version().await?;
                     _ = updates.spawn(Self::update_nasl(
                         self.url.clone(),
-                        h.path.clone(),
+                        h.loader(),
                         current_feed,
+                        self.feed_report.clone(),
                     ))
                 }
                 FeedType::Advisories => {
@@ -210,6 +232,16 @@ where
         Ok(())
     }
 
+    async fn replace_vts(&self, vts: Vec<Nvt>) -> Result<(), Error> {
+        let url = self.url.clone();
+        tokio::task::spawn_blocking(move || {
+            CacheDispatcher::replace_nvts(&url, vts)?;
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
     async fn vt_by_oid(&self, oid: &str) -> Result<Option<Nvt>, Error> {
         let url = self.url.to_string();
         let aoid = oid.to_owned();
@@ -311,6 +343,10 @@ where
         .unwrap()?;
         Ok(cache_version)
     }
+
+    async fn feed_report(&self) -> Result<Option<feed::UpdateReport>, Error> {
+        Ok(self.feed_report.read().await.clone())
+    }
 }
 
 #[async_trait]
@@ -329,6 +365,22 @@ where
     }
 }
 
+#[async_trait]
+impl<T> AnnotationStorer for Storage<T>
+where
+    T: super::Storage + std::marker::Sync,
+{
+    async fn add_annotation(&self, id: &str, annotation: Annotation) -> Result<Annotation, Error> {
+        self.underlying.add_annotation(id, annotation).await
+    }
+    async fn get_annotations(&self, id: &str) -> Result<Vec<Annotation>, Error> {
+        self.underlying.get_annotations(id).await
+    }
+    async fn remove_annotation(&self, id: &str, annotation_id: usize) -> Result<(), Error> {
+        self.underlying.remove_annotation(id, annotation_id).await
+    }
+}
+
 #[async_trait]
 impl<T> AppendFetchResult for Storage<T>
 where
@@ -370,6 +422,13 @@ where
         self.underlying.handle_result(key, result)
     }
 
+    fn handle_results<E>(&self, key: &ContextKey, results: Vec<models::Result>) -> Result<(), E>
+    where
+        E: From<StorageError>,
+    {
+        self.underlying.handle_results(key, results)
+    }
+
     fn remove_result<E>(
         &self,
         key: &ContextKey,
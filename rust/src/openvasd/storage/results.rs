@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Bounded channel between the scripts producing results and the storage persisting them.
+//!
+//! Instead of writing each result while the interpreter is running, results are sent to a
+//! [ResultChannel]. A background task collects them in batches and stores them via
+//! [ResultHandler::handle_results] so that a slow storage only stalls script execution when the
+//! buffer is full.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use scannerlib::{
+    models,
+    storage::{ContextKey, StorageError},
+};
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::{
+        mpsc::{self, error::SendTimeoutError, error::TrySendError},
+        oneshot,
+    },
+};
+
+use super::ResultHandler;
+
+/// Amount of results that can be buffered before a script has to wait.
+pub const DEFAULT_CAPACITY: usize = 1024;
+/// Maximum amount of results stored at once.
+pub const DEFAULT_BATCH_SIZE: usize = 64;
+/// How long a script waits for a free slot before the result is dropped.
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Message {
+    Result(ContextKey, Box<models::Result>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Results that could not be stored for a scan
+#[derive(Debug, Default)]
+struct Dropped {
+    amount: usize,
+    reason: String,
+}
+
+type DroppedResults = Arc<Mutex<HashMap<String, Dropped>>>;
+
+/// Sends results to a background task storing them in batches.
+///
+/// When the buffer is full, the sender waits up to the send timeout for the storage to catch up.
+/// Results that cannot be sent in time, or that the storage fails to store, are dropped and
+/// reported as errors.
+pub struct ResultChannel {
+    sender: mpsc::Sender<Message>,
+    dropped: DroppedResults,
+    send_timeout: Duration,
+}
+
+impl ResultChannel {
+    /// Creates a new channel with the default capacity, batch size and send timeout.
+    ///
+    /// Must be called within a tokio runtime as it spawns the storing task.
+    pub fn new<T>(storage: Arc<T>) -> Self
+    where
+        T: ResultHandler + Send + Sync + 'static,
+    {
+        Self::with_capacity(
+            storage,
+            DEFAULT_CAPACITY,
+            DEFAULT_BATCH_SIZE,
+            DEFAULT_SEND_TIMEOUT,
+        )
+    }
+
+    /// Creates a new channel buffering up to capacity results.
+    ///
+    /// Must be called within a tokio runtime as it spawns the storing task.
+    pub fn with_capacity<T>(
+        storage: Arc<T>,
+        capacity: usize,
+        batch_size: usize,
+        send_timeout: Duration,
+    ) -> Self
+    where
+        T: ResultHandler + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let dropped = DroppedResults::default();
+        tokio::spawn(store(storage, receiver, batch_size.max(1), dropped.clone()));
+        Self {
+            sender,
+            dropped,
+            send_timeout,
+        }
+    }
+
+    /// Sends a result to be stored.
+    ///
+    /// Returns an error when the result got dropped or when previous results of the same scan
+    /// could not be stored.
+    pub fn send(&self, key: &ContextKey, result: Box<models::Result>) -> Result<(), StorageError> {
+        match self.sender.try_send(Message::Result(key.clone(), result)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(msg)) => self.wait_for_capacity(key, msg)?,
+            Err(TrySendError::Closed(_)) => {
+                return Err(StorageError::Dirty(format!(
+                    "dropped result of {key}: result channel is closed"
+                )))
+            }
+        }
+        self.take_dropped(key.as_ref())
+    }
+
+    fn wait_for_capacity(&self, key: &ContextKey, msg: Message) -> Result<(), StorageError> {
+        let handle = match Handle::try_current() {
+            // Blocking is only possible when other workers can run the storing task meanwhile.
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => handle,
+            _ => {
+                tracing::warn!(%key, "result buffer is full, dropping result");
+                return Err(StorageError::Dirty(format!(
+                    "dropped result of {key}: result buffer is full"
+                )));
+            }
+        };
+        tokio::task::block_in_place(|| {
+            handle.block_on(self.sender.send_timeout(msg, self.send_timeout))
+        })
+        .map_err(|e| {
+            let reason = match e {
+                SendTimeoutError::Timeout(_) => format!(
+                    "result buffer is still full after {} seconds",
+                    self.send_timeout.as_secs()
+                ),
+                SendTimeoutError::Closed(_) => "result channel is closed".to_string(),
+            };
+            tracing::warn!(%key, reason, "dropping result");
+            StorageError::Dirty(format!("dropped result of {key}: {reason}"))
+        })
+    }
+
+    fn take_dropped(&self, id: &str) -> Result<(), StorageError> {
+        match self.dropped.lock()?.remove(id) {
+            Some(Dropped { amount, reason }) => Err(StorageError::Dirty(format!(
+                "{amount} results of {id} could not be stored: {reason}"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits until all previously sent results are stored.
+    ///
+    /// Returns an error when results of the given scan could not be stored.
+    pub async fn flush(&self, id: &str) -> Result<(), StorageError> {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(Message::Flush(tx)).await.is_ok() {
+            // an error means that the storing task is gone, there is nothing to wait for
+            let _ = rx.await;
+        }
+        self.take_dropped(id)
+    }
+}
+
+async fn store<T>(
+    storage: Arc<T>,
+    mut receiver: mpsc::Receiver<Message>,
+    batch_size: usize,
+    dropped: DroppedResults,
+) where
+    T: ResultHandler + Send + Sync + 'static,
+{
+    let mut messages = Vec::with_capacity(batch_size);
+    while receiver.recv_many(&mut messages, batch_size).await > 0 {
+        let mut batches: Vec<(ContextKey, Vec<models::Result>)> = vec![];
+        let mut flushes = vec![];
+        for msg in messages.drain(..) {
+            match msg {
+                Message::Result(key, result) => match batches.last_mut() {
+                    Some((k, results)) if k == &key => results.push(*result),
+                    _ => batches.push((key, vec![*result])),
+                },
+                Message::Flush(tx) => flushes.push(tx),
+            }
+        }
+        let amounts: Vec<(String, usize)> = batches
+            .iter()
+            .map(|(k, r)| (k.as_ref().to_string(), r.len()))
+            .collect();
+        let storage = storage.clone();
+        let failures = tokio::task::spawn_blocking(move || {
+            batches
+                .into_iter()
+                .filter_map(|(key, results)| {
+                    let amount = results.len();
+                    storage
+                        .handle_results::<StorageError>(&key, results)
+                        .err()
+                        .map(|e| (key.as_ref().to_string(), amount, e.to_string()))
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_else(|e| {
+            amounts
+                .into_iter()
+                .map(|(key, amount)| (key, amount, e.to_string()))
+                .collect()
+        });
+        if !failures.is_empty() {
+            let mut dropped = dropped.lock().unwrap_or_else(|e| e.into_inner());
+            for (key, amount, reason) in failures {
+                tracing::warn!(key, amount, reason, "unable to store results");
+                let entry = dropped.entry(key).or_default();
+                entry.amount += amount;
+                entry.reason = reason;
+            }
+        }
+        for tx in flushes {
+            let _ = tx.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use models::Scan;
+    use scannerlib::storage::ContextKey;
+
+    use super::*;
+    use crate::storage::{inmemory, ProgressGetter, ScanStorer};
+
+    fn result(id: usize) -> Box<models::Result> {
+        Box::new(models::Result {
+            id,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn stores_results_in_order() {
+        let storage = Arc::new(inmemory::Storage::default());
+        storage.insert_scan(Scan::default()).await.unwrap();
+        let channel = ResultChannel::with_capacity(storage.clone(), 10, 3, Duration::ZERO);
+        let key = ContextKey::Scan(String::default(), None);
+        for i in 0..10 {
+            channel.send(&key, result(i)).unwrap();
+        }
+        channel.flush("").await.unwrap();
+        let results: Vec<models::Result> = storage
+            .get_results("", None, None)
+            .await
+            .unwrap()
+            .map(|x| serde_json::from_slice(&x).unwrap())
+            .collect();
+        assert_eq!(results, (0..10).map(|x| *result(x)).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn full_buffer_drops_result() {
+        let storage = Arc::new(inmemory::Storage::default());
+        storage.insert_scan(Scan::default()).await.unwrap();
+        let channel = ResultChannel::with_capacity(storage.clone(), 1, 1, Duration::ZERO);
+        let key = ContextKey::Scan(String::default(), None);
+        // the storing task cannot run on a current thread runtime until we yield
+        channel.send(&key, result(0)).unwrap();
+        assert!(channel.send(&key, result(1)).is_err());
+        channel.flush("").await.unwrap();
+        assert_eq!(storage.count_results("").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn storage_failures_are_reported() {
+        let storage = Arc::new(inmemory::Storage::default());
+        let channel = ResultChannel::new(storage);
+        let key = ContextKey::Scan("unknown".to_string(), None);
+        channel.send(&key, result(0)).unwrap();
+        channel.send(&key, result(1)).unwrap();
+        let err = channel.flush("unknown").await.unwrap_err();
+        assert!(err.to_string().contains("2 results"), "{err}");
+        channel.flush("unknown").await.unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Trend of the findings across the runs of a scan series.
+//!
+//! A finding is an alarm identified by its [FindingKey]. Only succeeded runs are compared, as a
+//! stopped or failed run may just not have reached a finding. A finding is new in a run when no
+//! earlier run found it, recurring otherwise, and fixed when the previous run found it but the
+//! run does not. The time to fix is the time between the start of the run first finding it and
+//! the start of the run no longer finding it.
+
+use std::collections::{BTreeSet, HashMap};
+
+use scannerlib::models::{
+    cvss::{self, SeverityClass},
+    FindingKey, Phase, Status,
+};
+
+/// Amount of findings per severity
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SeverityCounts {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub none: usize,
+    /// Findings of VTs without a severity vector or with an unsupported CVSS version
+    pub unknown: usize,
+}
+
+impl SeverityCounts {
+    fn add(&mut self, severity: Option<SeverityClass>) {
+        let count = match severity {
+            Some(SeverityClass::Critical) => &mut self.critical,
+            Some(SeverityClass::High) => &mut self.high,
+            Some(SeverityClass::Medium) => &mut self.medium,
+            Some(SeverityClass::Low) => &mut self.low,
+            Some(SeverityClass::None) => &mut self.none,
+            None => &mut self.unknown,
+        };
+        *count += 1;
+    }
+}
+
+/// Findings of a single run
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RunTrend {
+    pub scan_id: String,
+    /// Number of the run within the series, starting at 1
+    pub run: usize,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Amount of findings
+    pub findings: usize,
+    pub severities: SeverityCounts,
+    /// Findings not found by an earlier run
+    pub new: usize,
+    /// Findings already found by an earlier run
+    pub recurring: usize,
+    /// Findings of the previous run that are no longer found
+    pub fixed: usize,
+}
+
+/// Trend of the findings of a scan series
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Trend {
+    /// Succeeded runs ordered by their number
+    pub runs: Vec<RunTrend>,
+    /// Mean time in seconds until a finding was fixed, none when nothing was fixed yet
+    pub mean_time_to_fix: Option<u64>,
+}
+
+/// Findings of a run as input of the aggregation
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub scan_id: String,
+    pub run: usize,
+    pub status: Status,
+    pub findings: BTreeSet<FindingKey>,
+}
+
+/// Returns the severity of a VT by its severity vector or its CVSS base vector.
+pub fn severity(vt: &scannerlib::storage::item::Nvt) -> Option<SeverityClass> {
+    use scannerlib::storage::item::TagKey;
+    [TagKey::SeverityVector, TagKey::CvssBaseVector]
+        .iter()
+        .find_map(|key| cvss::base_score(&vt.tag.get(key)?.to_string()))
+        .map(SeverityClass::from_score)
+}
+
+impl Trend {
+    /// Compares the succeeded runs ordered by their number.
+    ///
+    /// The severities map the OIDs of the findings to the severity of their VT.
+    pub fn aggregate<I>(runs: I, severities: &HashMap<String, Option<SeverityClass>>) -> Self
+    where
+        I: IntoIterator<Item = Run>,
+    {
+        let mut runs: Vec<Run> = runs
+            .into_iter()
+            .filter(|x| x.status.status == Phase::Succeeded)
+            .collect();
+        runs.sort_by_key(|x| x.run);
+
+        let mut trend = Self::default();
+        let mut seen = BTreeSet::new();
+        let mut first_found: HashMap<&FindingKey, Option<u64>> = HashMap::new();
+        let mut fix_times = vec![];
+        let mut previous: Option<&BTreeSet<FindingKey>> = None;
+        for run in &runs {
+            let started = run.status.start_time.or(run.status.end_time);
+            let mut result = RunTrend {
+                scan_id: run.scan_id.clone(),
+                run: run.run,
+                start_time: run.status.start_time,
+                end_time: run.status.end_time,
+                findings: run.findings.len(),
+                ..Default::default()
+            };
+            for finding in &run.findings {
+                result
+                    .severities
+                    .add(severities.get(&finding.oid).cloned().flatten());
+                if seen.insert(finding) {
+                    result.new += 1;
+                } else {
+                    result.recurring += 1;
+                }
+                first_found.entry(finding).or_insert(started);
+            }
+            for finding in previous.iter().flat_map(|x| x.difference(&run.findings)) {
+                result.fixed += 1;
+                if let (Some(Some(found)), Some(fixed)) = (first_found.remove(finding), started) {
+                    fix_times.push(fixed.saturating_sub(found));
+                }
+            }
+            previous = Some(&run.findings);
+            trend.runs.push(result);
+        }
+        if !fix_times.is_empty() {
+            trend.mean_time_to_fix = Some(fix_times.iter().sum::<u64>() / fix_times.len() as u64);
+        }
+        trend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use scannerlib::models::{cvss::SeverityClass, FindingKey, Phase, Protocol, Status};
+
+    use super::{Run, Trend};
+
+    fn finding(oid: &str, host: &str) -> FindingKey {
+        FindingKey {
+            oid: oid.to_string(),
+            target: host.to_string(),
+            port: Some(443),
+            protocol: Some(Protocol::TCP),
+            vhost: None,
+        }
+    }
+
+    fn run(run: usize, start_time: u64, phase: Phase, findings: &[FindingKey]) -> Run {
+        Run {
+            scan_id: format!("scan-{run}"),
+            run,
+            status: Status {
+                start_time: Some(start_time),
+                end_time: Some(start_time + 10),
+                status: phase,
+                host_info: None,
+                ..Default::default()
+            },
+            findings: findings.iter().cloned().collect::<BTreeSet<_>>(),
+        }
+    }
+
+    #[test]
+    fn aggregate() {
+        let (a, b, c) = (finding("1", "h1"), finding("2", "h1"), finding("1", "h2"));
+        let severities = HashMap::from([
+            ("1".to_string(), Some(SeverityClass::High)),
+            ("2".to_string(), None),
+        ]);
+        let trend = Trend::aggregate(
+            vec![
+                run(3, 300, Phase::Succeeded, &[b.clone(), c.clone()]),
+                run(1, 100, Phase::Succeeded, &[a.clone(), b.clone()]),
+                run(2, 200, Phase::Stopped, &[]),
+                run(4, 400, Phase::Succeeded, &[a]),
+            ],
+            &severities,
+        );
+        let runs: Vec<_> = trend
+            .runs
+            .iter()
+            .map(|x| (x.run, x.findings, x.new, x.recurring, x.fixed))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![(1, 2, 2, 0, 0), (3, 2, 1, 1, 1), (4, 1, 0, 1, 2)]
+        );
+        assert_eq!(trend.runs[0].severities.high, 1);
+        assert_eq!(trend.runs[0].severities.unknown, 1);
+        // a fixed after 200s, b after 300s and c after 100s
+        assert_eq!(trend.mean_time_to_fix, Some(200));
+    }
+
+    #[tokio::test]
+    async fn from_storage() {
+        use scannerlib::models::{scanner::ScanResults, ResultType, Scan};
+
+        use crate::storage::{inmemory, AppendFetchResult as _, ScanStorer as _, Storage as _};
+
+        let storage = inmemory::Storage::default();
+        let alarm = |host: &str| scannerlib::models::Result {
+            r_type: ResultType::Alarm,
+            ip_address: Some(host.to_string()),
+            oid: Some("1".to_string()),
+            ..Default::default()
+        };
+        for (id, start_time, hosts) in [("r1", 100, vec!["h1", "h2"]), ("r2", 250, vec!["h2"])] {
+            let scan = Scan {
+                scan_id: id.to_string(),
+                ..Default::default()
+            };
+            storage.insert_scan(scan).await.unwrap();
+            let mut results: Vec<_> = hosts.into_iter().map(alarm).collect();
+            results.push(scannerlib::models::Result {
+                r_type: ResultType::Log,
+                oid: Some("2".to_string()),
+                ..Default::default()
+            });
+            storage
+                .append_fetched_result(vec![ScanResults {
+                    id: id.to_string(),
+                    status: Status {
+                        start_time: Some(start_time),
+                        status: Phase::Succeeded,
+                        ..Default::default()
+                    },
+                    results,
+                }])
+                .await
+                .unwrap();
+        }
+        let trend = storage
+            .trend(&[(1, "r1".to_string()), (2, "r2".to_string())])
+            .await
+            .unwrap();
+        let runs: Vec<_> = trend
+            .runs
+            .iter()
+            .map(|x| {
+                (
+                    x.findings,
+                    x.new,
+                    x.recurring,
+                    x.fixed,
+                    x.severities.unknown,
+                )
+            })
+            .collect();
+        assert_eq!(runs, vec![(2, 2, 0, 0, 2), (1, 0, 1, 1, 1)]);
+        assert_eq!(trend.mean_time_to_fix, Some(150));
+    }
+}
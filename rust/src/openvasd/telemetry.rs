@@ -0,0 +1,453 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Exports the tracing spans of openvasd as OpenTelemetry traces.
+//!
+//! Closed spans are sent in batches to an OTLP/HTTP collector using the JSON encoding. Each span
+//! gets the trace of its parent, a span containing a `traceparent` field in the format of the
+//! W3C trace context continues the given trace instead. This is used to continue the trace of a
+//! client request as well as to continue the trace of the request that started a scan within the
+//! scheduler.
+//!
+//! The `otel.kind` field sets the kind of a span, events of the level error mark their span as
+//! failed.
+
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, Registry},
+    Layer,
+};
+
+use crate::config;
+
+/// Maximum number of spans waiting for the export, further spans are dropped
+const QUEUE_SIZE: usize = 4096;
+/// Maximum number of spans sent within one request
+const BATCH_SIZE: usize = 512;
+
+/// Identifies a span within a trace as defined by the W3C trace context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parses a `traceparent` header
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (Some("00"), Some(trace_id), Some(span_id), Some(flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let mut result = Self {
+            trace_id: [0; 16],
+            span_id: [0; 8],
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+        };
+        hex::decode_to_slice(trace_id, &mut result.trace_id).ok()?;
+        hex::decode_to_slice(span_id, &mut result.span_id).ok()?;
+        if result.trace_id == [0; 16] || result.span_id == [0; 8] {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+impl Display for TraceContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            hex::encode(self.trace_id),
+            hex::encode(self.span_id),
+            self.sampled as u8
+        )
+    }
+}
+
+/// Returns the `traceparent` of the current span.
+///
+/// Returns None when traces are not exported.
+pub fn traceparent() -> Option<String> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            let span = registry.span(id)?;
+            span.scope()
+                .find_map(|x| x.extensions().get::<SpanData>().map(|x| x.context))
+        })
+        .flatten()
+        .map(|x| x.to_string())
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Collects the fields of a span or event as OTLP attributes
+#[derive(Debug, Default)]
+struct Fields {
+    attributes: Vec<Value>,
+    /// The `message` of an event
+    message: Option<String>,
+    traceparent: Option<String>,
+    kind: Option<String>,
+}
+
+impl Fields {
+    fn add(&mut self, field: &Field, value: Value) {
+        self.attributes
+            .retain(|x| x["key"].as_str() != Some(field.name()));
+        self.attributes
+            .push(json!({"key": field.name(), "value": value}));
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = &*scannerlib::redact::redact(value);
+        match field.name() {
+            "message" => self.message = Some(value.to_string()),
+            "traceparent" => self.traceparent = Some(value.to_string()),
+            "otel.kind" => self.kind = Some(value.to_string()),
+            _ => self.add(field, json!({ "stringValue": value })),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"))
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.add(field, json!({ "intValue": value.to_string() }))
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.add(field, json!({ "intValue": value.to_string() }))
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.add(field, json!({ "doubleValue": value }))
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.add(field, json!({ "boolValue": value }))
+    }
+}
+
+/// A span as it is exported
+#[derive(Debug)]
+pub struct SpanData {
+    context: TraceContext,
+    parent_span_id: Option<[u8; 8]>,
+    name: &'static str,
+    fields: Fields,
+    start: u64,
+    end: u64,
+    events: Vec<Value>,
+    failed: bool,
+}
+
+impl SpanData {
+    /// Returns the span in the OTLP JSON encoding
+    fn encode(&self) -> Value {
+        let kind = match self.fields.kind.as_deref() {
+            Some("server") => 2,
+            Some("client") => 3,
+            Some("producer") => 4,
+            Some("consumer") => 5,
+            _ => 1,
+        };
+        let mut span = json!({
+            "traceId": hex::encode(self.context.trace_id),
+            "spanId": hex::encode(self.context.span_id),
+            "name": self.name,
+            "kind": kind,
+            "startTimeUnixNano": self.start.to_string(),
+            "endTimeUnixNano": self.end.to_string(),
+            "attributes": self.fields.attributes,
+            "events": self.events,
+            "status": { "code": if self.failed { 2 } else { 0 } },
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = json!(hex::encode(parent));
+        }
+        span
+    }
+}
+
+/// Returns the body of an OTLP export request containing the spans
+pub fn encode(service_name: &str, spans: &[SpanData]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                ]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "openvasd" },
+                "spans": spans.iter().map(|x| x.encode()).collect::<Vec<_>>(),
+            }]
+        }]
+    })
+}
+
+/// Records spans and hands the sampled ones to the exporter when they are closed
+pub struct OtlpLayer {
+    sender: mpsc::Sender<SpanData>,
+    sample_ratio: f64,
+}
+
+impl OtlpLayer {
+    /// Creates a layer and the receiver of its closed spans
+    pub fn new(sample_ratio: f64) -> (Self, mpsc::Receiver<SpanData>) {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        (
+            Self {
+                sender,
+                sample_ratio,
+            },
+            receiver,
+        )
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let parent = fields
+            .traceparent
+            .as_deref()
+            .and_then(TraceContext::parse)
+            .or_else(|| {
+                span.parent()
+                    .and_then(|x| x.extensions().get::<SpanData>().map(|x| x.context))
+            });
+        let context = TraceContext {
+            trace_id: parent.map(|x| x.trace_id).unwrap_or_else(rand::random),
+            span_id: rand::random(),
+            sampled: parent
+                .map(|x| x.sampled)
+                .unwrap_or_else(|| rand::random::<f64>() < self.sample_ratio),
+        };
+        span.extensions_mut().insert(SpanData {
+            context,
+            parent_span_id: parent.map(|x| x.span_id),
+            name: attrs.metadata().name(),
+            fields,
+            start: unix_nanos(),
+            end: 0,
+            events: vec![],
+            failed: false,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(&mut data.fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let level = *event.metadata().level();
+        fields.attributes.push(json!({
+            "key": "level",
+            "value": { "stringValue": level.as_str() }
+        }));
+        data.failed |= level == Level::ERROR;
+        data.events.push(json!({
+            "timeUnixNano": unix_nanos().to_string(),
+            "name": fields.message.unwrap_or_else(|| event.metadata().name().to_string()),
+            "attributes": fields.attributes,
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(mut data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        if data.context.sampled {
+            data.end = unix_nanos();
+            // spans are dropped rather than blocking when the collector is too slow
+            let _ = self.sender.try_send(data);
+        }
+    }
+}
+
+/// Sends the closed spans to the OTLP collector.
+///
+/// The spans are sent in batches each export interval or when a batch is full.
+pub async fn export(config: config::Telemetry, mut spans: mpsc::Receiver<SpanData>) {
+    let Some(endpoint) = config.otlp_endpoint.as_deref() else {
+        return;
+    };
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut interval = tokio::time::interval(config.export_interval);
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    loop {
+        let closed = tokio::select! {
+            span = spans.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                    false
+                }
+                None => true,
+            },
+            _ = interval.tick() => false,
+        };
+        if !batch.is_empty() {
+            let body = encode(&config.service_name, &batch);
+            batch.clear();
+            let mut request = client.post(&url).json(&body);
+            for (name, value) in &config.headers {
+                request = request.header(name, value);
+            }
+            if let Err(e) = request.send().await.and_then(|x| x.error_for_status()) {
+                tracing::debug!(%e, "Unable to export spans");
+            }
+        }
+        if closed {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use super::*;
+
+    #[test]
+    fn trace_context() {
+        let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let context = TraceContext::parse(traceparent).unwrap();
+        assert!(context.sampled);
+        assert_eq!(
+            context.span_id,
+            [0xb7, 0xad, 0x6b, 0x71, 0x69, 0x20, 0x33, 0x31]
+        );
+        assert_eq!(context.to_string(), traceparent);
+        assert!(TraceContext::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b71-01").is_none());
+        assert!(
+            TraceContext::parse("00-00000000000000000000000000000000-b7ad6b7169203331-01")
+                .is_none()
+        );
+        assert!(
+            TraceContext::parse("ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let (layer, mut spans) = OtlpLayer::new(1.0);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let remote = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let propagated = tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(
+                "request",
+                otel.kind = "server",
+                traceparent = remote,
+                status = tracing::field::Empty
+            );
+            let _entered = request.enter();
+            let propagated = traceparent();
+            tracing::info_span!("scan", scan_id = "aha").in_scope(|| {
+                tracing::error!(reason = "timeout", "unable to start");
+            });
+            request.record("status", 200);
+            propagated
+        });
+
+        let scan = spans.try_recv().unwrap();
+        let request = spans.try_recv().unwrap();
+        assert!(spans.try_recv().is_err());
+        assert_eq!(propagated, Some(request.context.to_string()));
+        assert_eq!(
+            hex::encode(request.context.trace_id),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert_eq!(scan.context.trace_id, request.context.trace_id);
+        assert_eq!(scan.parent_span_id, Some(request.context.span_id));
+        assert!(scan.failed);
+        assert!(!request.failed);
+
+        let body = encode("openvasd", &[request, scan]);
+        let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["name"], "request");
+        assert_eq!(spans[0]["kind"], 2);
+        assert_eq!(spans[0]["parentSpanId"], "b7ad6b7169203331");
+        assert_eq!(
+            spans[0]["attributes"],
+            json!([{ "key": "status", "value": { "intValue": "200" } }])
+        );
+        assert_eq!(spans[1]["kind"], 1);
+        assert_eq!(spans[1]["status"]["code"], 2);
+        assert_eq!(spans[1]["events"][0]["name"], "unable to start");
+    }
+
+    #[test]
+    fn sampling() {
+        let (layer, mut spans) = OtlpLayer::new(0.0);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("dropped").in_scope(|| {
+                tracing::info_span!("child").in_scope(|| {});
+            });
+            let sampled = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+            tracing::info_span!("continued", traceparent = sampled).in_scope(|| {});
+        });
+        assert_eq!(spans.try_recv().unwrap().name, "continued");
+        assert!(spans.try_recv().is_err());
+    }
+}
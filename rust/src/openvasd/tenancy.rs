@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Restricts the VTs visible to and schedulable by the clients of a tenant.
+//!
+//! The tenant of a client authenticated by a token is taken from the tenant claim of the token,
+//! other clients belong to the default tenant. Clients without a tenant and admins see the whole
+//! feed. A VT is part of the subset of a tenant when either its family or its OID is listed for
+//! the tenant.
+
+use std::collections::BTreeSet;
+
+use scannerlib::storage::item::Nvt;
+
+use crate::{config, oidc::Role};
+
+/// Families and OIDs of the VTs a client may see and scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedSubset {
+    pub families: BTreeSet<String>,
+    pub oids: BTreeSet<String>,
+}
+
+impl From<&config::Tenant> for FeedSubset {
+    fn from(value: &config::Tenant) -> Self {
+        Self {
+            families: value.families.iter().cloned().collect(),
+            oids: value.oids.iter().cloned().collect(),
+        }
+    }
+}
+
+impl FeedSubset {
+    /// Returns true when the nvt is part of the subset.
+    pub fn allows(&self, nvt: &Nvt) -> bool {
+        self.families.contains(&nvt.family) || self.oids.contains(&nvt.oid)
+    }
+}
+
+/// Returns the feed subset of a client or None when the client may access the whole feed.
+///
+/// A tenant that is not configured gets an empty subset.
+pub fn subset(
+    config: &config::Tenancy,
+    tenant: Option<&str>,
+    role: Option<Role>,
+) -> Option<FeedSubset> {
+    if !config.enabled || role == Some(Role::Admin) {
+        return None;
+    }
+    let tenant = tenant.or(config.default_tenant.as_deref())?;
+    Some(
+        config
+            .tenants
+            .get(tenant)
+            .map(FeedSubset::from)
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nvt(oid: &str, family: &str) -> Nvt {
+        Nvt {
+            oid: oid.to_string(),
+            family: family.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn subset_of_tenant() {
+        let mut config = config::Tenancy {
+            enabled: true,
+            default_tenant: None,
+            tenants: [(
+                "basic".to_string(),
+                config::Tenant {
+                    families: vec!["Product detection".to_string()],
+                    oids: vec!["1.2.3".to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        };
+        let basic = subset(&config, Some("basic"), Some(Role::Operator)).unwrap();
+        assert!(basic.allows(&nvt("1.2.4", "Product detection")));
+        assert!(basic.allows(&nvt("1.2.3", "Web application abuses")));
+        assert!(!basic.allows(&nvt("1.2.4", "Web application abuses")));
+
+        assert_eq!(subset(&config, Some("basic"), Some(Role::Admin)), None);
+        assert_eq!(subset(&config, None, Some(Role::Viewer)), None);
+        assert_eq!(
+            subset(&config, Some("unknown"), None),
+            Some(FeedSubset::default())
+        );
+        config.default_tenant = Some("basic".to_string());
+        assert_eq!(subset(&config, None, None), Some(basic));
+        config.enabled = false;
+        assert_eq!(subset(&config, Some("basic"), None), None);
+    }
+}
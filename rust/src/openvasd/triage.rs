@@ -0,0 +1,346 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Rule based auto-triage of results as they are stored.
+//!
+//! The rules are read from a TOML file, each `[[rule]]` matches results by
+//! - `oids`, the OID of the VT of the result is one of them,
+//! - `min_severity`, the severity class of the VT is at least the given one,
+//! - `labels`, the host of the result has each of the labels given within the target,
+//! - `message`, a regular expression matching the message of the result.
+//!
+//! A matching rule adds its `tags` to the result and, with `action`, either suppresses or
+//! escalates it. The rules are evaluated in the order of the file, the action of the last
+//! matching rule wins. Each matching rule is recorded in the trace of the result. Only results of
+//! a VT are triaged. The file is reloaded when it changed; an invalid file keeps the previous
+//! rules.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use regex::Regex;
+use scannerlib::models::{self, cvss::SeverityClass, Labels, TriageAction, TriageStep};
+use serde::Deserialize;
+
+use crate::config;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to read the rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid rules: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid message of rule {0}: {1}")]
+    Regex(String, regex::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Definition {
+    name: String,
+    #[serde(default)]
+    oids: Vec<String>,
+    #[serde(default)]
+    min_severity: Option<SeverityClass>,
+    #[serde(default)]
+    labels: Labels,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    tags: Labels,
+    #[serde(default)]
+    action: Option<TriageAction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    rule: Vec<Definition>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    definition: Definition,
+    message: Option<Regex>,
+}
+
+fn class(severity: SeverityClass) -> &'static str {
+    match severity {
+        SeverityClass::None => "none",
+        SeverityClass::Low => "low",
+        SeverityClass::Medium => "medium",
+        SeverityClass::High => "high",
+        SeverityClass::Critical => "critical",
+    }
+}
+
+impl Rule {
+    /// Returns the matched conditions or none when the result does not match.
+    fn matches(
+        &self,
+        result: &models::Result,
+        severity: Option<SeverityClass>,
+    ) -> Option<Vec<String>> {
+        let rule = &self.definition;
+        let mut matched = vec![];
+        if !rule.oids.is_empty() {
+            let oid = result.oid.as_ref().filter(|x| rule.oids.contains(x))?;
+            matched.push(format!("oid {oid}"));
+        }
+        if let Some(min) = rule.min_severity {
+            let severity = severity.filter(|x| *x >= min)?;
+            matched.push(format!("severity {}", class(severity)));
+        }
+        for (key, value) in &rule.labels {
+            if result.labels.get(key) != Some(value) {
+                return None;
+            }
+            matched.push(format!("label {key}={value}"));
+        }
+        if let Some(message) = &self.message {
+            if !message.is_match(result.message.as_deref()?) {
+                return None;
+            }
+            matched.push(format!("message {message}"));
+        }
+        Some(matched)
+    }
+}
+
+/// Triage rules in the order of their evaluation
+#[derive(Debug, Default)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let file: File = toml::from_str(content)?;
+        file.rule
+            .into_iter()
+            .map(|definition| {
+                let message = definition
+                    .message
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| Error::Regex(definition.name.clone(), e))?;
+                Ok(Rule {
+                    definition,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn load(path: &Path) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns true when a rule requires the severity of the VT of a result.
+    pub fn uses_severity(&self) -> bool {
+        self.0.iter().any(|x| x.definition.min_severity.is_some())
+    }
+
+    /// Applies the matching rules to a result.
+    ///
+    /// The severity is the one of the VT of the result, results without an OID are unchanged.
+    pub fn apply(&self, result: &mut models::Result, severity: Option<SeverityClass>) {
+        if result.oid.is_none() {
+            return;
+        }
+        for rule in &self.0 {
+            let Some(matched) = rule.matches(result, severity) else {
+                continue;
+            };
+            let definition = &rule.definition;
+            let triage = result.triage.get_or_insert_with(Default::default);
+            triage.tags.extend(definition.tags.clone());
+            match definition.action {
+                Some(TriageAction::Suppress) => {
+                    triage.suppressed = true;
+                    triage.escalated = false;
+                }
+                Some(TriageAction::Escalate) => {
+                    triage.suppressed = false;
+                    triage.escalated = true;
+                }
+                None => {}
+            }
+            triage.trace.push(TriageStep {
+                rule: definition.name.clone(),
+                matched,
+                action: definition.action,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Loaded {
+    /// Whether the file was read at all
+    checked: bool,
+    modified: Option<SystemTime>,
+    rules: Arc<Rules>,
+}
+
+/// Triage rules of the configured file
+#[derive(Debug, Default)]
+pub struct Triage {
+    path: Option<PathBuf>,
+    loaded: RwLock<Loaded>,
+}
+
+impl Triage {
+    pub fn new(config: config::Triage) -> Self {
+        let triage = Self {
+            path: config.rules,
+            loaded: Default::default(),
+        };
+        triage.rules();
+        triage
+    }
+
+    /// Returns the current rules, the file is reloaded when it was modified since the last call.
+    pub fn rules(&self) -> Arc<Rules> {
+        let Some(path) = &self.path else {
+            return Arc::default();
+        };
+        let modified = std::fs::metadata(path).and_then(|x| x.modified()).ok();
+        {
+            let loaded = self.loaded.read().unwrap();
+            if loaded.checked && loaded.modified == modified {
+                return loaded.rules.clone();
+            }
+        }
+        let mut loaded = self.loaded.write().unwrap();
+        match Rules::load(path) {
+            Ok(rules) => {
+                tracing::info!(path = %path.display(), rules = rules.0.len(), "Loaded triage rules");
+                loaded.rules = Arc::new(rules);
+            }
+            Err(e) => {
+                tracing::warn!(path = %path.display(), %e, "Unable to load triage rules, keeping the previous ones")
+            }
+        }
+        loaded.checked = true;
+        loaded.modified = modified;
+        loaded.rules.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const RULES: &str = r#"
+[[rule]]
+name = "banner"
+oids = ["1.2.3"]
+message = "OpenSSH_[0-9]"
+action = "suppress"
+
+[[rule]]
+name = "production"
+min_severity = "high"
+labels = { env = "prod" }
+tags = { team = "secops" }
+action = "escalate"
+"#;
+
+    fn result(oid: &str, message: &str, env: &str) -> models::Result {
+        models::Result {
+            oid: Some(oid.into()),
+            message: Some(message.into()),
+            labels: [("env".to_string(), env.to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let rules = Rules::parse(RULES).unwrap();
+        assert!(rules.uses_severity());
+
+        let mut banner = result("1.2.3", "SSH-2.0-OpenSSH_8.9", "dev");
+        rules.apply(&mut banner, Some(SeverityClass::Low));
+        let triage = banner.triage.unwrap();
+        assert!(triage.suppressed);
+        assert!(!triage.escalated);
+        assert_eq!(triage.trace.len(), 1);
+        assert_eq!(triage.trace[0].rule, "banner");
+        assert_eq!(
+            triage.trace[0].matched,
+            vec!["oid 1.2.3", "message OpenSSH_[0-9]"]
+        );
+
+        // the later rule overrides the action of an earlier one
+        let mut both = result("1.2.3", "OpenSSH_9", "prod");
+        rules.apply(&mut both, Some(SeverityClass::Critical));
+        let triage = both.triage.unwrap();
+        assert!(!triage.suppressed);
+        assert!(triage.escalated);
+        assert_eq!(triage.tags.get("team").unwrap(), "secops");
+        assert_eq!(triage.trace.len(), 2);
+        assert_eq!(
+            triage.trace[1].matched,
+            vec!["severity critical", "label env=prod"]
+        );
+
+        let mut unmatched = result("1.2.4", "OpenSSH_9", "prod");
+        rules.apply(&mut unmatched, Some(SeverityClass::Medium));
+        assert_eq!(unmatched.triage, None);
+
+        let mut host_end = models::Result::default();
+        rules.apply(&mut host_end, None);
+        assert_eq!(host_end.triage, None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Rules::parse("[[rule]]\nname = \"a\"\nmessage = \"(\""),
+            Err(Error::Regex(..))
+        ));
+        assert!(matches!(
+            Rules::parse("[[rule]]\nname = \"a\"\nunknown = 1"),
+            Err(Error::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn reload() {
+        let path = std::env::temp_dir().join(format!("triage-{}.toml", std::process::id()));
+        std::fs::write(&path, RULES).unwrap();
+        let triage = Triage::new(config::Triage {
+            rules: Some(path.clone()),
+        });
+        assert_eq!(triage.rules().0.len(), 2);
+
+        let touch = |content: &str, offset: u64| {
+            std::fs::write(&path, content).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::now() + Duration::from_secs(offset))
+                .unwrap();
+        };
+        touch("[[rule]]\nname = \"all\"\ntags = { seen = \"yes\" }", 10);
+        assert_eq!(triage.rules().0.len(), 1);
+        // invalid rules keep the previous ones
+        touch("[[rule]]", 20);
+        assert_eq!(triage.rules().0.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -25,7 +25,7 @@ pub enum ScanCommand<'a> {
 }
 
 type Result<T> = std::result::Result<T, Error>;
-type Writer = quick_xml::Writer<Cursor<Vec<u8>>>;
+pub(super) type Writer = quick_xml::Writer<Cursor<Vec<u8>>>;
 
 impl<'a> ScanCommand<'a> {
     fn as_byte_response(
@@ -71,7 +71,7 @@ impl<'a> ScanCommand<'a> {
     }
 }
 
-enum IDAttribute<'a> {
+pub(super) enum IDAttribute<'a> {
     VTId(&'a str),
     ScanID(&'a str),
 }
@@ -92,7 +92,7 @@ impl IDAttribute<'_> {
     }
 }
 
-trait WithinElement {
+pub(super) trait WithinElement {
     /// Writes an element with the given name and attributes.
     fn within_element<F>(&mut self, name: &str, f: &mut F) -> Result<()>
     where
@@ -196,7 +196,7 @@ fn write_scanner_prefs(scan: &Scan, writer: &mut Writer) -> Result<()> {
     Ok(())
 }
 
-fn write_str_element(writer: &mut Writer, name: &str, value: &str) -> Result<()> {
+pub(super) fn write_str_element(writer: &mut Writer, name: &str, value: &str) -> Result<()> {
     write_event(name, writer, Event::Text(BytesText::new(value)))
 }
 
@@ -7,6 +7,7 @@ mod commands;
 mod connection;
 mod response;
 mod scanner;
+mod server;
 
 #[cfg(test)]
 mod tests;
@@ -18,3 +19,8 @@ pub use response::ScanResult as OspScanResult;
 pub use response::ScanStatus as OspScanStatus;
 pub use response::StringF32;
 pub use scanner::Scanner;
+pub use server::{
+    command_name as osp_command_name, failure as osp_failure, is_complete as osp_is_complete,
+    scans as osp_scans, started as osp_started, success as osp_success, version as osp_version,
+    Request as OspRequest,
+};
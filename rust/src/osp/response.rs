@@ -321,6 +321,20 @@ pub enum ResultType {
     HostEnd,
 }
 
+impl ResultType {
+    /// Returns the name as used within the type attribute of a result
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultType::Alarm => "Alarm",
+            ResultType::Log => "Log Message",
+            ResultType::Error => "Error Message",
+            ResultType::HostDetail => "Host Detail",
+            ResultType::HostStart => "Host Start",
+            ResultType::HostEnd => "Host End",
+        }
+    }
+}
+
 /// Scan result within the get_scans response
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct ScanResult {
@@ -345,8 +359,11 @@ pub struct ScanResult {
     #[serde(rename = "@type")]
     /// Type
     pub result_type: ResultType,
-    /// Description
-    #[serde(rename = "$text")]
+    #[serde(rename = "@qod", default)]
+    /// Quality of detection, empty for results not created by a VT
+    pub qod: String,
+    /// Description, results without a message have none
+    #[serde(rename = "$text", default)]
     pub description: String,
 }
 
@@ -419,9 +436,59 @@ impl From<&ScanResult> for crate::models::Result {
             port,
             protocol,
             oid: Some(result.test_id.clone()),
+            qod: result.qod.parse().ok(),
             r_type,
             message,
             detail: detail.extract(),
+            pcap: None,
+            traffic: None,
+            labels: Default::default(),
+            triage: None,
+        }
+    }
+}
+
+impl From<&crate::models::Result> for ScanResult {
+    /// Creates the result as ospd-openvas would send it.
+    ///
+    /// The severity is not part of a result and must be set from the VT afterwards.
+    fn from(result: &crate::models::Result) -> Self {
+        use quick_xml::escape::escape;
+        let (result_type, name) = match result.r_type {
+            crate::models::ResultType::Alarm => (ResultType::Alarm, ""),
+            crate::models::ResultType::Log => (ResultType::Log, ""),
+            crate::models::ResultType::Error => (ResultType::Error, ""),
+            crate::models::ResultType::HostStart => (ResultType::Log, "HOST_START"),
+            crate::models::ResultType::HostEnd => (ResultType::Log, "HOST_END"),
+            crate::models::ResultType::DeadHost => (ResultType::Log, "DEADHOST"),
+            crate::models::ResultType::HostDetail => (ResultType::Log, "Host Details"),
+        };
+        let description = match &result.detail {
+            Some(d) => format!(
+                "<host><detail><name>{}</name><value>{}</value><source><type>{}</type><name>{}</name><description>{}</description></source></detail></host>",
+                escape(&d.name),
+                escape(&d.value),
+                escape(&d.source.s_type),
+                escape(&d.source.name),
+                escape(&d.source.description),
+            ),
+            None => result.message.clone().unwrap_or_default(),
+        };
+        let port = match (result.port, &result.protocol) {
+            (Some(port), Some(protocol)) => format!("{port}/{protocol}"),
+            (Some(port), None) => port.to_string(),
+            (None, _) => String::new(),
+        };
+        ScanResult {
+            host: result.ip_address.clone().unwrap_or_default(),
+            hostname: result.hostname.clone().unwrap_or_default(),
+            severity: StringF32(0.0),
+            port,
+            test_id: result.oid.clone().unwrap_or_default(),
+            name: name.to_string(),
+            result_type,
+            qod: result.qod.map(|x| x.to_string()).unwrap_or_default(),
+            description,
         }
     }
 }
@@ -495,6 +562,22 @@ impl From<&str> for ScanStatus {
     }
 }
 
+impl ScanStatus {
+    /// Returns the name as used within the status attribute of a scan
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanStatus::Queued => "queued",
+            ScanStatus::Requested => "requested",
+            ScanStatus::Running => "running",
+            ScanStatus::Stopped => "stopped",
+            ScanStatus::Failed => "failed",
+            ScanStatus::Finished => "finished",
+            ScanStatus::Succeeded => "succeeded",
+            ScanStatus::Interrupted => "interrupted",
+        }
+    }
+}
+
 /// Scan within the get_scans response
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Scan {
@@ -616,6 +699,69 @@ impl From<Scan> for crate::models::Status {
                 }
                 .build()
             }),
+            ..Default::default()
+        }
+    }
+}
+
+impl Scan {
+    /// Creates the scan as ospd-openvas would send it within a get_scans response.
+    pub fn from_status(
+        id: &str,
+        target: &crate::models::Target,
+        status: &crate::models::Status,
+        results: Vec<ScanResult>,
+    ) -> Self {
+        let scan_status = match status.status {
+            crate::models::Phase::Stored => ScanStatus::Queued,
+            crate::models::Phase::Requested => ScanStatus::Requested,
+            crate::models::Phase::Running | crate::models::Phase::Paused => ScanStatus::Running,
+            crate::models::Phase::Stopped => ScanStatus::Stopped,
+            crate::models::Phase::Failed => ScanStatus::Failed,
+            crate::models::Phase::Succeeded | crate::models::Phase::Partial => {
+                ScanStatus::Succeeded
+            }
+        };
+        let element = |x: u64| ElementU64 {
+            content: StringU64(x),
+        };
+        let host_info = status.host_info.as_ref().map(|x| {
+            let done = x.finished() + x.dead() + x.excluded();
+            let overall = match x.all() {
+                0 => 0,
+                all => (done * 100 / all).min(100),
+            };
+            HostInfo {
+                host: x
+                    .scanning()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, progress)| Host {
+                        name: name.clone(),
+                        progress: StringU64((*progress).max(0) as u64),
+                    })
+                    .collect(),
+                overall: element(overall),
+                count_alive: element(x.alive()),
+                count_dead: element(x.dead()),
+                count_excluded: element(x.excluded()),
+                count_total: element(x.all()),
+            }
+        });
+        let progress = match (&host_info, status.is_done()) {
+            (_, true) => 100,
+            (Some(x), false) => x.overall.content.0,
+            (None, false) => 0,
+        };
+        Scan {
+            id: id.to_string(),
+            target: target.hosts.join(","),
+            start_time: status.start_time.map(StringU64),
+            end_time: status.end_time.map(StringU64),
+            progress: StringU64(progress),
+            status: scan_status,
+            results: Results { result: results },
+            host_info,
         }
     }
 }
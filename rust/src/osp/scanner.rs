@@ -8,7 +8,9 @@
 use std::{path::PathBuf, time::Duration};
 
 use crate::models::{
-    scanner::{Error, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper},
+    scanner::{
+        Error, ScanDeleter, ScanPlanner, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
+    },
     Scan,
 };
 use async_trait::async_trait;
@@ -52,6 +54,8 @@ impl Scanner {
     }
 }
 
+impl ScanPlanner for Scanner {}
+
 #[async_trait]
 impl ScanStarter for Scanner {
     async fn start_scan(&self, scan: Scan) -> Result<(), Error> {
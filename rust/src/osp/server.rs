@@ -0,0 +1,689 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! # Server side of OSP
+//!
+//! Parses the commands sent by an OSP client, e.g. gvmd, and writes the responses to them so
+//! that a scanner can be offered as a drop-in for ospd-openvas.
+use std::{collections::HashMap, io::Cursor};
+
+use quick_xml::{
+    events::{BytesStart, BytesText, Event},
+    Reader,
+};
+use serde::Deserialize;
+
+use crate::models::{
+    ports_from_openvas_port_list, AliveTestMethods, Credential, CredentialType, Parameter,
+    PrivilegeInformation, Scan, ScanPreference, Service, Target, VT,
+};
+
+use super::{
+    commands::{write_str_element, Error, IDAttribute, WithinElement, Writer},
+    response::{Scan as OspScan, ScanResult},
+};
+
+/// Version of the protocol that is implemented
+pub const PROTOCOL_VERSION: &str = "22.04";
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Command sent by an OSP client
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Start a new scan.
+    StartScan(Box<Scan>),
+    /// Stop a scan.
+    StopScan(String),
+    /// Delete a scan.
+    DeleteScan(String),
+    /// Get the status and results of a scan or, without an ID, of all scans.
+    GetScans {
+        scan_id: Option<String>,
+        /// Results that are returned once are not returned again
+        pop_results: bool,
+        /// Results are only returned when set
+        details: bool,
+    },
+    /// Get the versions of the protocol and the scanner.
+    GetVersion,
+}
+
+/// Returns the name of the root element.
+pub fn command_name(xml: &[u8]) -> Option<String> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                return Some(String::from_utf8_lossy(e.name().as_ref()).to_string())
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => buf.clear(),
+        }
+    }
+}
+
+/// Returns true when the buffer contains a complete root element.
+///
+/// OSP clients do not close their side of the connection after sending a command, the end of a
+/// command is the end of its root element.
+pub fn is_complete(xml: &[u8]) -> bool {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut depth = 0usize;
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(_)) => depth += 1,
+            Ok(Event::Empty(_)) if depth == 0 => return true,
+            Ok(Event::End(_)) => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return true;
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return false,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+enum Command {
+    #[serde(rename = "start_scan")]
+    StartScan {
+        #[serde(rename = "@scan_id", default)]
+        scan_id: String,
+        #[serde(default)]
+        scanner_params: HashMap<String, String>,
+        #[serde(default)]
+        vt_selection: VtSelection,
+        targets: Targets,
+    },
+    #[serde(rename = "stop_scan")]
+    StopScan {
+        #[serde(rename = "@scan_id")]
+        scan_id: String,
+    },
+    #[serde(rename = "delete_scan")]
+    DeleteScan {
+        #[serde(rename = "@scan_id")]
+        scan_id: String,
+    },
+    #[serde(rename = "get_scans")]
+    GetScans {
+        #[serde(rename = "@scan_id")]
+        scan_id: Option<String>,
+        #[serde(rename = "@pop_results")]
+        pop_results: Option<String>,
+        #[serde(rename = "@details")]
+        details: Option<String>,
+    },
+    #[serde(rename = "get_version")]
+    GetVersion {},
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VtSelection {
+    #[serde(default)]
+    vt_single: Vec<VtSingle>,
+    #[serde(default)]
+    vt_group: Vec<VtGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VtSingle {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(default)]
+    vt_value: Vec<VtValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VtValue {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "$text", default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VtGroup {
+    #[serde(rename = "@filter")]
+    filter: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Targets {
+    target: Vec<TargetElement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetElement {
+    hosts: String,
+    #[serde(default)]
+    ports: String,
+    #[serde(default)]
+    exclude_hosts: String,
+    #[serde(default)]
+    alive_test_ports: String,
+    #[serde(default)]
+    alive_test_methods: HashMap<String, String>,
+    reverse_lookup_only: Option<String>,
+    reverse_lookup_unify: Option<String>,
+    #[serde(default)]
+    credentials: Credentials,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Credentials {
+    #[serde(default)]
+    credential: Vec<CredentialElement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialElement {
+    #[serde(rename = "@type")]
+    kind: String,
+    #[serde(rename = "@service")]
+    service: String,
+    #[serde(rename = "@port")]
+    port: Option<String>,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    private: String,
+    priv_username: Option<String>,
+    priv_password: Option<String>,
+    #[serde(default)]
+    community: String,
+    #[serde(default)]
+    auth_algorithm: String,
+    #[serde(default)]
+    privacy_password: String,
+    #[serde(default)]
+    privacy_algorithm: String,
+}
+
+fn flag(x: Option<&str>) -> bool {
+    x.and_then(crate::models::parse_bool).unwrap_or_default()
+}
+
+fn list(x: &str) -> Vec<String> {
+    x.split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+impl TryFrom<CredentialElement> for Credential {
+    type Error = Error;
+
+    fn try_from(c: CredentialElement) -> Result<Self> {
+        let privilege = match (c.priv_username, c.priv_password) {
+            (Some(username), password) => Some(PrivilegeInformation {
+                username,
+                password: password.unwrap_or_default(),
+            }),
+            _ => None,
+        };
+        let credential_type = match c.kind.as_str() {
+            "up" => CredentialType::UP {
+                username: c.username,
+                password: c.password,
+                privilege,
+            },
+            "usk" => CredentialType::USK {
+                username: c.username,
+                password: c.password,
+                private_key: c.private,
+                privilege,
+            },
+            "snmp" => CredentialType::SNMP {
+                username: c.username,
+                password: c.password,
+                community: c.community,
+                auth_algorithm: c.auth_algorithm,
+                privacy_password: c.privacy_password,
+                privacy_algorithm: c.privacy_algorithm,
+            },
+            x => return Err(Error::ReadXML(format!("Invalid credential type: {x}"))),
+        };
+        let port = match c.port.as_deref().map(|x| x.trim()) {
+            None | Some("") => None,
+            Some(x) => Some(
+                x.parse()
+                    .map_err(|_| Error::ReadXML(format!("Invalid credential port: {x}")))?,
+            ),
+        };
+        Ok(Credential {
+            service: Service::try_from(c.service.as_str()).map_err(Error::ReadXML)?,
+            port,
+            credential_type,
+        })
+    }
+}
+
+impl TryFrom<TargetElement> for Target {
+    type Error = Error;
+
+    fn try_from(t: TargetElement) -> Result<Self> {
+        let alive_test_methods = t
+            .alive_test_methods
+            .iter()
+            .filter(|(_, v)| flag(Some(v)))
+            .map(|(k, _)| match k.as_str() {
+                "icmp" => Ok(AliveTestMethods::Icmp),
+                "tcp_syn" => Ok(AliveTestMethods::TcpSyn),
+                "tcp_ack" => Ok(AliveTestMethods::TcpAck),
+                "arp" => Ok(AliveTestMethods::Arp),
+                "consider_alive" => Ok(AliveTestMethods::ConsiderAlive),
+                x => Err(Error::ReadXML(format!("Invalid alive test method: {x}"))),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Target {
+            hosts: list(&t.hosts),
+            ports: ports_from_openvas_port_list(&t.ports).map_err(Error::ReadXML)?,
+            excluded_hosts: list(&t.exclude_hosts),
+            credentials: t
+                .credentials
+                .credential
+                .into_iter()
+                .map(Credential::try_from)
+                .collect::<Result<_>>()?,
+            alive_test_ports: ports_from_openvas_port_list(&t.alive_test_ports)
+                .map_err(Error::ReadXML)?,
+            alive_test_methods,
+            reverse_lookup_unify: t.reverse_lookup_unify.map(|x| flag(Some(&x))),
+            reverse_lookup_only: t.reverse_lookup_only.map(|x| flag(Some(&x))),
+            ..Default::default()
+        })
+    }
+}
+
+impl Request {
+    /// Parses a command.
+    pub fn from_xml(xml: &[u8]) -> Result<Self> {
+        let xml = std::str::from_utf8(xml).map_err(|e| Error::ReadXML(e.to_string()))?;
+        Ok(match quick_xml::de::from_str::<Command>(xml)? {
+            Command::StartScan {
+                scan_id,
+                scanner_params,
+                vt_selection,
+                targets,
+            } => {
+                if let Some(group) = vt_selection.vt_group.first() {
+                    return Err(Error::ReadXML(format!(
+                        "VT groups are not supported: {}",
+                        group.filter
+                    )));
+                }
+                let mut targets = targets.target.into_iter();
+                let target = match (targets.next(), targets.next()) {
+                    (Some(target), None) => Target::try_from(target)?,
+                    _ => return Err(Error::ReadXML("Exactly one target is required".into())),
+                };
+                let vts = vt_selection
+                    .vt_single
+                    .into_iter()
+                    .map(|vt| {
+                        let parameters = vt
+                            .vt_value
+                            .into_iter()
+                            .map(|x| {
+                                let id = x.id.parse().map_err(|_| {
+                                    Error::ReadXML(format!("Invalid VT parameter: {}", x.id))
+                                })?;
+                                Ok(Parameter { id, value: x.value })
+                            })
+                            .collect::<Result<_>>()?;
+                        Ok(VT {
+                            oid: vt.id,
+                            parameters,
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                let mut scan_preferences: Vec<_> = scanner_params
+                    .into_iter()
+                    .map(|(id, value)| ScanPreference { id, value })
+                    .collect();
+                scan_preferences.sort_by(|a, b| a.id.cmp(&b.id));
+                Request::StartScan(Box::new(Scan {
+                    scan_id,
+                    target,
+                    scan_preferences,
+                    vts,
+                    ..Default::default()
+                }))
+            }
+            Command::StopScan { scan_id } => Request::StopScan(scan_id),
+            Command::DeleteScan { scan_id } => Request::DeleteScan(scan_id),
+            Command::GetScans {
+                scan_id,
+                pop_results,
+                details,
+            } => Request::GetScans {
+                scan_id,
+                pop_results: flag(pop_results.as_deref()),
+                // OSP returns the results unless asked not to
+                details: details.is_none() || flag(details.as_deref()),
+            },
+            Command::GetVersion {} => Request::GetVersion,
+        })
+    }
+}
+
+fn response(
+    command: &str,
+    code: u16,
+    text: &str,
+    f: &mut dyn FnMut(&mut Writer) -> Result<()>,
+) -> Result<Vec<u8>> {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let code = code.to_string();
+    writer.within_parameter_element(
+        &format!("{command}_response"),
+        vec![("status", code.as_str()), ("status_text", text)],
+        f,
+    )?;
+    Ok(writer.into_inner().into_inner())
+}
+
+/// Returns the response of a failed command.
+///
+/// Commands that are unknown are answered with the command `osp`.
+pub fn failure(command: &str, code: u16, text: &str) -> Result<Vec<u8>> {
+    response(command, code, text, &mut |_| Ok(()))
+}
+
+/// Returns the successful response of a command without content.
+pub fn success(command: &str) -> Result<Vec<u8>> {
+    response(command, 200, "OK", &mut |_| Ok(()))
+}
+
+/// Returns the response of a started scan.
+pub fn started(id: &str) -> Result<Vec<u8>> {
+    response("start_scan", 200, "OK", &mut |writer| {
+        write_str_element(writer, "id", id)
+    })
+}
+
+/// Returns the response of get_version.
+pub fn version(scanner: &str, scanner_version: &str) -> Result<Vec<u8>> {
+    response("get_version", 200, "OK", &mut |writer| {
+        for (element, name, version) in [
+            ("protocol", "OSP", PROTOCOL_VERSION),
+            ("daemon", scanner, scanner_version),
+            ("scanner", scanner, scanner_version),
+        ] {
+            writer.within_element(element, &mut |writer| {
+                write_str_element(writer, "name", name)?;
+                write_str_element(writer, "version", version)
+            })?;
+        }
+        Ok(())
+    })
+}
+
+fn write_result(writer: &mut Writer, result: &ScanResult) -> Result<()> {
+    let severity = format!("{:.1}", f32::from(result.severity.clone()));
+    let attributes = [
+        ("host", result.host.as_str()),
+        ("hostname", result.hostname.as_str()),
+        ("severity", severity.as_str()),
+        ("port", result.port.as_str()),
+        ("test_id", result.test_id.as_str()),
+        ("name", result.name.as_str()),
+        ("type", result.result_type.as_str()),
+        ("qod", result.qod.as_str()),
+    ];
+    let mut element = BytesStart::new("result");
+    for x in attributes {
+        element.push_attribute(x);
+    }
+    writer.write_event(Event::Start(element))?;
+    writer.write_event(Event::Text(BytesText::new(&result.description)))?;
+    writer.write_event(Event::End(BytesStart::new("result").to_end()))?;
+    Ok(())
+}
+
+fn write_scan(writer: &mut Writer, scan: &OspScan) -> Result<()> {
+    let time = |x: Option<super::response::StringU64>| x.map(u64::from).unwrap_or_default();
+    let start_time = time(scan.start_time).to_string();
+    let end_time = time(scan.end_time).to_string();
+    let progress = u64::from(scan.progress).to_string();
+    writer.within_id_element(
+        IDAttribute::VTId(&scan.id),
+        &[
+            ("target", scan.target.as_str()),
+            ("start_time", start_time.as_str()),
+            ("end_time", end_time.as_str()),
+            ("progress", progress.as_str()),
+            ("status", scan.status.as_str()),
+        ],
+        "scan",
+        &mut |writer| {
+            writer.within_element("results", &mut |writer| {
+                for result in &scan.results.result {
+                    write_result(writer, result)?;
+                }
+                Ok(())
+            })?;
+            if let Some(info) = &scan.host_info {
+                writer.within_element("progress", &mut |writer| {
+                    for host in &info.host {
+                        writer.within_parameter_element(
+                            "host",
+                            vec![("name", host.name.as_str())],
+                            &mut |writer| {
+                                let progress = u64::from(host.progress).to_string();
+                                writer.write_event(Event::Text(BytesText::new(&progress)))?;
+                                Ok(())
+                            },
+                        )?;
+                    }
+                    for (name, value) in [
+                        ("overall", &info.overall),
+                        ("count_alive", &info.count_alive),
+                        ("count_dead", &info.count_dead),
+                        ("count_excluded", &info.count_excluded),
+                        ("count_total", &info.count_total),
+                    ] {
+                        write_str_element(writer, name, &u64::from(value.content).to_string())?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Returns the response of get_scans.
+pub fn scans(scans: &[OspScan]) -> Result<Vec<u8>> {
+    response("get_scans", 200, "OK", &mut |writer| {
+        for scan in scans {
+            write_scan(writer, scan)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{self, Port, PortRange, Protocol};
+    use crate::osp::response::{Response, ScanStatus};
+
+    #[test]
+    fn start_scan() {
+        let xml = r#"
+<start_scan scan_id="42">
+  <scanner_params><max_checks>4</max_checks><safe_checks/></scanner_params>
+  <vt_selection>
+    <vt_single id="1.3.6.1.4.1.25623.1.0.10330"><vt_value id="1">yes</vt_value></vt_single>
+    <vt_single id="1.3.6.1.4.1.25623.1.0.10331"/>
+  </vt_selection>
+  <targets>
+    <target>
+      <hosts>127.0.0.1, 10.0.0.1</hosts>
+      <ports>T:22,80-81,U:53</ports>
+      <exclude_hosts>10.0.0.2</exclude_hosts>
+      <alive_test_methods><icmp>1</icmp><tcp_syn>0</tcp_syn></alive_test_methods>
+      <credentials>
+        <credential type="up" service="ssh" port="2222">
+          <username>scanner</username><password>secret</password>
+        </credential>
+      </credentials>
+    </target>
+  </targets>
+</start_scan>"#;
+        assert!(is_complete(xml.as_bytes()));
+        assert!(!is_complete(&xml.as_bytes()[..xml.len() - 3]));
+        assert_eq!(command_name(xml.as_bytes()).unwrap(), "start_scan");
+        let Request::StartScan(scan) = Request::from_xml(xml.as_bytes()).unwrap() else {
+            panic!("expected start_scan");
+        };
+        assert_eq!(scan.scan_id, "42");
+        assert_eq!(scan.target.hosts, vec!["127.0.0.1", "10.0.0.1"]);
+        assert_eq!(scan.target.excluded_hosts, vec!["10.0.0.2"]);
+        assert_eq!(
+            scan.target.ports,
+            vec![
+                Port {
+                    protocol: Some(Protocol::TCP),
+                    range: vec![
+                        PortRange {
+                            start: 22,
+                            end: None
+                        },
+                        PortRange {
+                            start: 80,
+                            end: Some(81)
+                        }
+                    ]
+                },
+                Port {
+                    protocol: Some(Protocol::UDP),
+                    range: vec![PortRange {
+                        start: 53,
+                        end: None
+                    }]
+                }
+            ]
+        );
+        assert_eq!(scan.target.alive_test_methods, vec![AliveTestMethods::Icmp]);
+        assert_eq!(scan.target.credentials[0].port, Some(2222));
+        assert_eq!(scan.target.credentials[0].password(), "secret");
+        assert_eq!(scan.vts.len(), 2);
+        assert_eq!(scan.vts[0].parameters[0].value, "yes");
+        assert_eq!(
+            scan.scan_preferences,
+            vec![
+                ScanPreference {
+                    id: "max_checks".into(),
+                    value: "4".into()
+                },
+                ScanPreference {
+                    id: "safe_checks".into(),
+                    value: "".into()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn commands() {
+        let parse = |x: &str| Request::from_xml(x.as_bytes());
+        assert_eq!(
+            parse(r#"<stop_scan scan_id="1"/>"#).unwrap(),
+            Request::StopScan("1".into())
+        );
+        assert_eq!(
+            parse(r#"<delete_scan scan_id="1"/>"#).unwrap(),
+            Request::DeleteScan("1".into())
+        );
+        assert_eq!(
+            parse(r#"<get_scans scan_id="1" pop_results="1" progress="1"/>"#).unwrap(),
+            Request::GetScans {
+                scan_id: Some("1".into()),
+                pop_results: true,
+                details: true
+            }
+        );
+        assert_eq!(parse("<get_version/>").unwrap(), Request::GetVersion);
+        assert!(parse("<help/>").is_err());
+        assert!(parse(
+            r#"<start_scan><vt_selection><vt_group filter="family=x"/></vt_selection><targets><target><hosts>a</hosts></target></targets></start_scan>"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn responses_are_readable_by_the_client() {
+        let read = |x: Vec<u8>| -> Response {
+            quick_xml::de::from_str(std::str::from_utf8(&x).unwrap()).unwrap()
+        };
+        assert!(matches!(
+            read(started("42").unwrap()),
+            Response::StartScan { id: Some(id), status } if id == "42" && status.is_ok()
+        ));
+        assert!(matches!(
+            read(success("stop_scan").unwrap()),
+            Response::StopScan { status } if status.is_ok()
+        ));
+        assert!(matches!(
+            read(failure("osp", 400, "Bogus command name").unwrap()),
+            Response::Failure { status } if !status.is_ok()
+        ));
+
+        let results = [
+            models::Result {
+                r_type: models::ResultType::Alarm,
+                ip_address: Some("127.0.0.1".into()),
+                port: Some(22),
+                protocol: Some(Protocol::TCP),
+                oid: Some("1.2.3".into()),
+                message: Some("a <vulnerability>".into()),
+                ..Default::default()
+            },
+            models::Result {
+                r_type: models::ResultType::HostStart,
+                ip_address: Some("127.0.0.1".into()),
+                message: Some("start".into()),
+                ..Default::default()
+            },
+        ];
+        let scan = OspScan {
+            id: "42".into(),
+            target: "127.0.0.1".into(),
+            status: ScanStatus::Running,
+            results: super::super::response::Results {
+                result: results.iter().map(ScanResult::from).collect(),
+            },
+            ..Default::default()
+        };
+        let Response::GetScans {
+            scan: Some(read_scan),
+            ..
+        } = read(self::scans(&[scan]).unwrap())
+        else {
+            panic!("expected a scan");
+        };
+        assert_eq!(read_scan.id, "42");
+        assert_eq!(read_scan.status, ScanStatus::Running);
+        let read_results: Vec<models::Result> = read_scan.into();
+        assert_eq!(read_results[0].r_type, models::ResultType::Alarm);
+        assert_eq!(read_results[0].port, Some(22));
+        assert_eq!(
+            read_results[0].message.as_deref(),
+            Some("a <vulnerability>")
+        );
+        assert_eq!(read_results[1].r_type, models::ResultType::HostStart);
+    }
+}
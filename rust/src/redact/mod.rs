@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Masks secrets, e.g. the passwords of scan credentials or API keys, before text leaves the
+//! process.
+//!
+//! Secrets are registered process wide for as long as their [Registration] lives. [redact]
+//! replaces every registered secret within a text by [MASK], it is applied to log output via
+//! [MakeRedacting] and to the messages of results before they are stored.
+//!
+//! ```
+//! use scannerlib::redact;
+//!
+//! let registration = redact::register(["hunter22"]);
+//! assert_eq!(redact::redact("password is hunter22"), "password is ********");
+//! drop(registration);
+//! assert_eq!(redact::redact("password is hunter22"), "password is hunter22");
+//! ```
+
+use std::{borrow::Cow, collections::BTreeMap, io::Write, sync::RwLock};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::models;
+
+/// Replaces a secret
+pub const MASK: &str = "********";
+
+/// Secrets shorter than this are not masked, as they would mask unrelated text as well.
+pub const MIN_LEN: usize = 4;
+
+/// Registered secrets and how often they are registered
+static SECRETS: RwLock<BTreeMap<String, usize>> = RwLock::new(BTreeMap::new());
+
+/// Keeps secrets registered until it is dropped
+#[derive(Debug, Default)]
+#[must_use = "the secrets are unregistered when the registration is dropped"]
+pub struct Registration {
+    secrets: Vec<String>,
+}
+
+impl Registration {
+    /// Keeps the secrets registered for the lifetime of the process.
+    pub fn keep(self) {
+        std::mem::forget(self)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if self.secrets.is_empty() {
+            return;
+        }
+        let mut registered = SECRETS.write().unwrap();
+        for secret in &self.secrets {
+            if let Some(count) = registered.get_mut(secret) {
+                *count -= 1;
+                if *count == 0 {
+                    registered.remove(secret);
+                }
+            }
+        }
+    }
+}
+
+/// Registers secrets to be masked, values shorter than [MIN_LEN] are ignored.
+pub fn register<I, S>(secrets: I) -> Registration
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let secrets: Vec<String> = secrets
+        .into_iter()
+        .map(|x| x.as_ref().trim().to_string())
+        .filter(|x| x.chars().count() >= MIN_LEN)
+        .collect();
+    if !secrets.is_empty() {
+        let mut registered = SECRETS.write().unwrap();
+        for secret in &secrets {
+            *registered.entry(secret.clone()).or_default() += 1;
+        }
+    }
+    Registration { secrets }
+}
+
+/// Replaces each registered secret within text by [MASK].
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let registered = SECRETS.read().unwrap();
+    let mut secrets: Vec<&String> = registered
+        .keys()
+        .filter(|x| text.contains(x.as_str()))
+        .collect();
+    if secrets.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    // longer secrets first so that a secret containing another one is masked completely
+    secrets.sort_by_key(|x| std::cmp::Reverse(x.len()));
+    let mut text = text.to_string();
+    for secret in secrets {
+        text = text.replace(secret.as_str(), MASK);
+    }
+    Cow::Owned(text)
+}
+
+/// Masks the registered secrets within the message and details of a result.
+pub fn redact_result(result: &mut models::Result) {
+    if let Some(message) = &mut result.message {
+        if let Cow::Owned(redacted) = redact(message) {
+            *message = redacted;
+        }
+    }
+    if let Some(detail) = &mut result.detail {
+        if let Cow::Owned(redacted) = redact(&detail.value) {
+            detail.value = redacted;
+        }
+    }
+}
+
+/// Writer masking registered secrets of the written text
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => {
+                self.0.write_all(redact(text).as_bytes())?;
+                Ok(buf.len())
+            }
+            Err(_) => self.0.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Creates writers masking registered secrets for the log output of tracing
+///
+/// ```
+/// use scannerlib::redact::MakeRedacting;
+///
+/// let subscriber = tracing_subscriber::fmt()
+///     .with_writer(MakeRedacting::new(std::io::stderr))
+///     .finish();
+/// ```
+pub struct MakeRedacting<M>(M);
+
+impl<M> MakeRedacting<M> {
+    pub fn new(make_writer: M) -> Self {
+        Self(make_writer)
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for MakeRedacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{redact, redact_result, register, RedactingWriter};
+    use crate::models;
+
+    // the registry is process wide, so each test uses its own secrets
+
+    #[test]
+    fn mask() {
+        let registration = register(["s3cr3t-mask", "s3cr3t-mask-longer", "abc", ""]);
+        assert_eq!(
+            redact("s3cr3t-mask-longer and s3cr3t-mask"),
+            "******** and ********"
+        );
+        // too short to be masked
+        assert_eq!(redact("abc"), "abc");
+        drop(registration);
+        assert_eq!(redact("s3cr3t-mask"), "s3cr3t-mask");
+    }
+
+    #[test]
+    fn registered_twice() {
+        let first = register(["s3cr3t-twice"]);
+        let second = register(["s3cr3t-twice"]);
+        drop(first);
+        assert_eq!(redact("s3cr3t-twice"), "********");
+        drop(second);
+        assert_eq!(redact("s3cr3t-twice"), "s3cr3t-twice");
+    }
+
+    #[test]
+    fn result() {
+        let _registration = register(["s3cr3t-result"]);
+        let mut result = models::Result {
+            message: Some("login with s3cr3t-result failed".to_string()),
+            ..Default::default()
+        };
+        redact_result(&mut result);
+        assert_eq!(result.message.unwrap(), "login with ******** failed");
+    }
+
+    #[test]
+    fn writer() {
+        let _registration = register(["s3cr3t-writer"]);
+        let mut writer = RedactingWriter(Vec::new());
+        writer.write_all(b"token s3cr3t-writer\n").unwrap();
+        assert_eq!(writer.0, b"token ********\n");
+    }
+}
@@ -0,0 +1,765 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+#![doc = include_str!("README.md")]
+
+mod pdf;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use tinytemplate::TinyTemplate;
+
+use crate::{
+    models::{
+        self,
+        cvss::{self, SeverityProfile},
+        ResultType,
+    },
+    storage::item::{Nvt, TagKey},
+};
+
+pub use pdf::{PdfBackend, PdfConverter};
+
+const EXECUTIVE_HTML: &str = include_str!("templates/executive.html");
+const EXECUTIVE_MARKDOWN: &str = include_str!("templates/executive.md");
+const TECHNICAL_HTML: &str = include_str!("templates/technical.html");
+const TECHNICAL_MARKDOWN: &str = include_str!("templates/technical.md");
+
+/// Errors that occur when rendering a report
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The template is invalid or does not match the context
+    #[error("unable to render report: {0}")]
+    Template(#[from] tinytemplate::error::Error),
+    /// The HTML report could not be printed to PDF
+    #[error("unable to create PDF: {0}")]
+    Pdf(String),
+}
+
+/// The output format of a report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// HTML, values are escaped
+    #[default]
+    Html,
+    /// Markdown, values are inserted as they are
+    Markdown,
+    /// PDF, printed from the HTML report
+    Pdf,
+}
+
+impl ReportFormat {
+    /// Returns the media type of the format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Html => "text/html; charset=utf-8",
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Pdf => "application/pdf",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "pdf" => Ok(Self::Pdf),
+            x => Err(format!(
+                "unknown report format {x}, expected html, markdown or pdf"
+            )),
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Html => write!(f, "html"),
+            Self::Markdown => write!(f, "markdown"),
+            Self::Pdf => write!(f, "pdf"),
+        }
+    }
+}
+
+/// The audience of a report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportKind {
+    /// An overview of the affected hosts and the most widespread findings
+    Executive,
+    /// Every finding with its affected hosts and messages
+    #[default]
+    Technical,
+}
+
+impl ReportKind {
+    fn template(&self, format: ReportFormat) -> &'static str {
+        match (self, format) {
+            (Self::Executive, ReportFormat::Html | ReportFormat::Pdf) => EXECUTIVE_HTML,
+            (Self::Executive, ReportFormat::Markdown) => EXECUTIVE_MARKDOWN,
+            (Self::Technical, ReportFormat::Html | ReportFormat::Pdf) => TECHNICAL_HTML,
+            (Self::Technical, ReportFormat::Markdown) => TECHNICAL_MARKDOWN,
+        }
+    }
+}
+
+impl FromStr for ReportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "executive" => Ok(Self::Executive),
+            "technical" => Ok(Self::Technical),
+            x => Err(format!(
+                "unknown report kind {x}, expected executive or technical"
+            )),
+        }
+    }
+}
+
+impl Display for ReportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Executive => write!(f, "executive"),
+            Self::Technical => write!(f, "technical"),
+        }
+    }
+}
+
+/// Customizes the appearance of a report
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Branding {
+    /// Name of the organization issuing the report
+    pub organization: Option<String>,
+    /// URL, data URI or path of a logo shown above the title
+    pub logo: Option<String>,
+    /// CSS color of the headings and table headers, e.g. `#1e7b34`
+    pub color: Option<String>,
+    /// Text shown at the end of the report, e.g. a confidentiality notice
+    pub footer: Option<String>,
+}
+
+impl Branding {
+    /// Replaces a logo given as path by a data URI containing the image.
+    ///
+    /// Reports are self-contained this way, which is required when printing them to PDF.
+    pub fn embed_logo(mut self) -> std::io::Result<Self> {
+        let Some(logo) = &self.logo else {
+            return Ok(self);
+        };
+        if logo.starts_with("data:") || logo.contains("://") {
+            return Ok(self);
+        }
+        let path = std::path::Path::new(logo);
+        let media_type = match path
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_lowercase())
+            .as_deref()
+        {
+            Some("svg") => "image/svg+xml",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => "image/png",
+        };
+        let image = std::fs::read(path)?;
+        self.logo = Some(format!("data:{media_type};base64,{}", BASE64.encode(image)));
+        Ok(self)
+    }
+}
+
+/// Counts of the results of a scan
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    /// Number of hosts with at least one result
+    pub hosts: usize,
+    /// Number of hosts with at least one alarm
+    pub vulnerable_hosts: usize,
+    /// Number of distinct VTs that raised an alarm
+    pub findings: usize,
+    pub alarms: usize,
+    pub logs: usize,
+    pub errors: usize,
+    /// Number of findings per severity level ordered from the highest level
+    pub severities: Vec<SeverityCount>,
+}
+
+/// Number of findings of a severity level
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SeverityCount {
+    pub level: String,
+    pub findings: usize,
+}
+
+/// The results of a single host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Host {
+    pub ip: String,
+    pub hostname: Option<String>,
+    pub alarms: usize,
+    pub logs: usize,
+    pub errors: usize,
+}
+
+/// A single result
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub oid: String,
+    /// The name of the VT or its OID when the VT is unknown
+    pub name: String,
+    pub ip: String,
+    pub hostname: Option<String>,
+    /// The port and protocol, e.g. `443/tcp`, or `general` when the result has no port
+    pub port: String,
+    /// Quality of detection of the result or of its VT
+    pub qod: Option<u8>,
+    pub message: String,
+}
+
+/// A VT that raised at least one alarm
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub oid: String,
+    /// The name of the VT or its OID when the VT is unknown
+    pub name: String,
+    pub family: Option<String>,
+    pub severity_vector: Option<String>,
+    /// The level of the severity within the severity profile of the report
+    pub severity: Option<String>,
+    pub summary: Option<String>,
+    pub solution: Option<String>,
+    /// Number of affected hosts
+    pub hosts: usize,
+    pub occurrences: Vec<Entry>,
+}
+
+/// The context handed to the templates
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Context {
+    pub scan_id: String,
+    /// Creation time of the report in RFC 3339
+    pub created: String,
+    pub summary: Summary,
+    /// Hosts ordered by their number of alarms
+    pub hosts: Vec<Host>,
+    /// Findings ordered by their number of affected hosts
+    pub findings: Vec<Finding>,
+    pub errors: Vec<Entry>,
+    pub branding: Branding,
+}
+
+/// Information about a VT used within a report
+#[derive(Debug, Clone, Default)]
+struct VtInfo {
+    name: String,
+    family: String,
+    severity_vector: Option<String>,
+    summary: Option<String>,
+    solution: Option<String>,
+    qod: u8,
+}
+
+/// A report of the results of a scan
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    scan_id: String,
+    results: Vec<models::Result>,
+    vts: HashMap<String, VtInfo>,
+    branding: Branding,
+    min_qod: Option<u8>,
+    profile: SeverityProfile,
+}
+
+fn port(result: &models::Result) -> String {
+    match (result.port, &result.protocol) {
+        (Some(port), Some(protocol)) => format!("{port}/{protocol}"),
+        (Some(port), None) => port.to_string(),
+        _ => "general".to_string(),
+    }
+}
+
+/// Returns the normalized IP address of a result.
+fn host_of(result: &models::Result) -> Option<String> {
+    models::normalize_host(result.ip_address.as_deref(), None)
+}
+
+impl Report {
+    /// Creates a report of the results of a scan
+    pub fn new(scan_id: &str, results: Vec<models::Result>) -> Self {
+        Self {
+            scan_id: scan_id.to_string(),
+            results,
+            vts: HashMap::new(),
+            branding: Branding::default(),
+            min_qod: None,
+            profile: SeverityProfile::default(),
+        }
+    }
+
+    /// Omits the results with a QoD below min_qod.
+    ///
+    /// Results without a QoD get the QoD of their VT, results whose QoD is unknown are kept.
+    pub fn with_min_qod(mut self, min_qod: Option<u8>) -> Self {
+        self.min_qod = min_qod;
+        self
+    }
+
+    /// Sets the branding shown in HTML and PDF reports
+    pub fn with_branding(mut self, branding: Branding) -> Self {
+        self.branding = branding;
+        self
+    }
+
+    /// Sets the profile mapping the severities of the findings to levels
+    pub fn with_severity_profile(mut self, profile: SeverityProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Adds the names, summaries and solutions of the VTs of the results
+    pub fn with_vts(mut self, vts: impl IntoIterator<Item = Nvt>) -> Self {
+        for vt in vts {
+            let tag = |key: TagKey| vt.tag.get(&key).map(|x| x.to_string());
+            let info = VtInfo {
+                name: vt.name.clone(),
+                family: vt.family.clone(),
+                severity_vector: tag(TagKey::SeverityVector)
+                    .or_else(|| tag(TagKey::CvssBaseVector)),
+                summary: tag(TagKey::Summary),
+                solution: tag(TagKey::Solution),
+                qod: vt.qod(),
+            };
+            self.vts.insert(vt.oid, info);
+        }
+        self
+    }
+
+    fn qod(&self, result: &models::Result) -> Option<u8> {
+        result.qod.or_else(|| {
+            result
+                .oid
+                .as_ref()
+                .and_then(|x| self.vts.get(x))
+                .map(|x| x.qod)
+        })
+    }
+
+    fn entry(&self, result: &models::Result) -> Entry {
+        let oid = result.oid.clone().unwrap_or_default();
+        Entry {
+            name: self
+                .vts
+                .get(&oid)
+                .map(|x| x.name.clone())
+                .unwrap_or_else(|| oid.clone()),
+            oid,
+            ip: host_of(result).unwrap_or_default(),
+            hostname: result.hostname.clone(),
+            port: port(result),
+            qod: self.qod(result),
+            message: result.message.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the context handed to the templates
+    pub fn context(&self) -> Context {
+        let mut hosts: BTreeMap<String, Host> = BTreeMap::new();
+        let mut findings: BTreeMap<String, Finding> = BTreeMap::new();
+        let mut errors = vec![];
+        let mut summary = Summary::default();
+        let mut seen = HashSet::new();
+        for result in &self.results {
+            let counted = matches!(
+                result.r_type,
+                ResultType::Alarm | ResultType::Log | ResultType::Error
+            );
+            let Some(ip) = host_of(result).filter(|_| counted) else {
+                continue;
+            };
+            if let (Some(min_qod), Some(qod)) = (self.min_qod, self.qod(result)) {
+                if qod < min_qod {
+                    continue;
+                }
+            }
+            let host = hosts.entry(ip.clone()).or_insert_with(|| Host {
+                ip,
+                ..Default::default()
+            });
+            if host.hostname.is_none() {
+                host.hostname.clone_from(&result.hostname);
+            }
+            match result.r_type {
+                ResultType::Alarm => {
+                    if result.finding_key().is_some_and(|x| !seen.insert(x)) {
+                        continue;
+                    }
+                    host.alarms += 1;
+                    summary.alarms += 1;
+                    let entry = self.entry(result);
+                    let vt = self.vts.get(&entry.oid);
+                    let severity_vector = vt.and_then(|x| x.severity_vector.clone());
+                    let severity = severity_vector
+                        .as_deref()
+                        .and_then(cvss::base_score)
+                        .and_then(|x| self.profile.level(x))
+                        .map(|x| x.to_string());
+                    findings
+                        .entry(entry.oid.clone())
+                        .or_insert_with(|| Finding {
+                            oid: entry.oid.clone(),
+                            name: entry.name.clone(),
+                            family: vt.map(|x| x.family.clone()),
+                            severity_vector,
+                            severity,
+                            summary: vt.and_then(|x| x.summary.clone()),
+                            solution: vt.and_then(|x| x.solution.clone()),
+                            ..Default::default()
+                        })
+                        .occurrences
+                        .push(entry);
+                }
+                ResultType::Log => {
+                    host.logs += 1;
+                    summary.logs += 1;
+                }
+                _ => {
+                    host.errors += 1;
+                    summary.errors += 1;
+                    errors.push(self.entry(result));
+                }
+            }
+        }
+        let mut findings: Vec<Finding> = findings
+            .into_values()
+            .map(|mut x| {
+                let mut affected: Vec<&str> = x.occurrences.iter().map(|x| x.ip.as_str()).collect();
+                affected.sort();
+                affected.dedup();
+                x.hosts = affected.len();
+                x
+            })
+            .collect();
+        findings.sort_by(|a, b| b.hosts.cmp(&a.hosts).then_with(|| a.oid.cmp(&b.oid)));
+        let mut hosts: Vec<Host> = hosts.into_values().collect();
+        hosts.sort_by(|a, b| b.alarms.cmp(&a.alarms).then_with(|| a.ip.cmp(&b.ip)));
+        summary.hosts = hosts.len();
+        summary.vulnerable_hosts = hosts.iter().filter(|x| x.alarms > 0).count();
+        summary.findings = findings.len();
+        summary.severities = self
+            .profile
+            .names()
+            .into_iter()
+            .map(|level| SeverityCount {
+                level: level.to_string(),
+                findings: findings
+                    .iter()
+                    .filter(|x| x.severity.as_deref() == Some(level))
+                    .count(),
+            })
+            .collect();
+        Context {
+            scan_id: self.scan_id.clone(),
+            created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            summary,
+            hosts,
+            findings,
+            errors,
+            branding: self.branding.clone(),
+        }
+    }
+
+    /// Renders the report with the shipped template of kind.
+    ///
+    /// For PDF the HTML to be printed is returned, use [Report::export] to get the PDF.
+    pub fn render(&self, kind: ReportKind, format: ReportFormat) -> Result<String, Error> {
+        self.render_template(kind.template(format), format)
+    }
+
+    /// Renders the report with a custom template.
+    ///
+    /// The template gets the [Context] of the report. Values are HTML escaped when format is
+    /// HTML.
+    pub fn render_template(&self, template: &str, format: ReportFormat) -> Result<String, Error> {
+        let mut tt = TinyTemplate::new();
+        if format == ReportFormat::Markdown {
+            tt.set_default_formatter(&tinytemplate::format_unescaped);
+        }
+        tt.add_template("report", template)?;
+        Ok(tt.render("report", &self.context())?)
+    }
+
+    /// Renders the report with the shipped template of kind and prints it to PDF when requested
+    pub fn export(
+        &self,
+        kind: ReportKind,
+        format: ReportFormat,
+        pdf: &PdfConverter,
+    ) -> Result<Vec<u8>, Error> {
+        self.export_template(kind.template(format), format, pdf)
+    }
+
+    /// Renders the report with a custom template and prints it to PDF when requested
+    pub fn export_template(
+        &self,
+        template: &str,
+        format: ReportFormat,
+        pdf: &PdfConverter,
+    ) -> Result<Vec<u8>, Error> {
+        let rendered = self.render_template(template, format)?;
+        match format {
+            ReportFormat::Pdf => pdf.convert(&rendered),
+            _ => Ok(rendered.into_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Protocol;
+
+    use super::*;
+
+    fn result(r_type: ResultType, ip: &str, oid: &str, port: Option<i16>) -> models::Result {
+        models::Result {
+            r_type,
+            ip_address: Some(ip.to_string()),
+            oid: Some(oid.to_string()),
+            port,
+            protocol: port.map(|_| Protocol::TCP),
+            message: Some(format!("<{oid}> on {ip}")),
+            ..Default::default()
+        }
+    }
+
+    fn report() -> Report {
+        let mut vt = Nvt {
+            oid: "1.2.1".to_string(),
+            name: "Apache HTTP Server < 2.4.58".to_string(),
+            family: "Web Servers".to_string(),
+            ..Default::default()
+        };
+        vt.tag
+            .insert(TagKey::Solution, "Update to version 2.4.58".into());
+        vt.tag.insert(
+            TagKey::SeverityVector,
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:L/I:L/A:L".into(),
+        );
+        Report::new(
+            "scan-1",
+            vec![
+                result(ResultType::HostStart, "192.168.0.1", "", None),
+                result(ResultType::Alarm, "192.168.0.1", "1.2.1", Some(443)),
+                result(ResultType::Alarm, "192.168.0.2", "1.2.1", Some(443)),
+                result(ResultType::Alarm, "192.168.0.2", "1.2.2", None),
+                result(ResultType::Log, "192.168.0.3", "1.2.3", None),
+                result(ResultType::Error, "192.168.0.3", "1.2.4", None),
+            ],
+        )
+        .with_vts([vt])
+    }
+
+    #[test]
+    fn context() {
+        let context = report().context();
+        assert_eq!(
+            context.summary,
+            Summary {
+                hosts: 3,
+                vulnerable_hosts: 2,
+                findings: 2,
+                alarms: 3,
+                logs: 1,
+                errors: 1,
+                severities: ["critical", "high", "medium", "low", "none"]
+                    .into_iter()
+                    .map(|level| SeverityCount {
+                        level: level.to_string(),
+                        findings: usize::from(level == "high"),
+                    })
+                    .collect(),
+            }
+        );
+        assert_eq!(context.hosts[0].ip, "192.168.0.2");
+        assert_eq!(context.hosts[0].alarms, 2);
+        assert_eq!(context.hosts[2].ip, "192.168.0.3");
+        assert_eq!(context.findings[0].oid, "1.2.1");
+        assert_eq!(context.findings[0].name, "Apache HTTP Server < 2.4.58");
+        assert_eq!(context.findings[0].hosts, 2);
+        assert_eq!(context.findings[0].severity.as_deref(), Some("high"));
+        assert_eq!(context.findings[0].occurrences[0].port, "443/tcp");
+        assert_eq!(context.findings[1].severity, None);
+        assert_eq!(context.findings[1].name, "1.2.2");
+        assert_eq!(context.findings[1].occurrences[0].port, "general");
+        assert_eq!(context.errors[0].oid, "1.2.4");
+    }
+
+    #[test]
+    fn min_qod() {
+        let mut vt = Nvt {
+            oid: "1.2.2".to_string(),
+            ..Default::default()
+        };
+        vt.tag.insert(TagKey::QodType, "remote_banner".into());
+        let mut results = vec![
+            result(ResultType::Alarm, "192.168.0.1", "1.2.1", None),
+            result(ResultType::Alarm, "192.168.0.1", "1.2.2", None),
+            result(ResultType::Alarm, "192.168.0.2", "1.2.3", None),
+            result(ResultType::Log, "192.168.0.2", "1.2.3", None),
+        ];
+        results[0].qod = Some(30);
+        results[3].qod = Some(97);
+        let report = Report::new("scan-1", results).with_vts([vt]);
+        let context = report.context();
+        assert_eq!(context.findings[0].occurrences[0].qod, Some(30));
+        assert_eq!(context.findings[1].occurrences[0].qod, Some(80));
+        assert_eq!(context.findings[2].occurrences[0].qod, None);
+
+        let context = report.with_min_qod(Some(70)).context();
+        let oids: Vec<_> = context.findings.iter().map(|x| x.oid.as_str()).collect();
+        assert_eq!(oids, vec!["1.2.2", "1.2.3"]);
+        assert_eq!(context.summary.alarms, 2);
+        assert_eq!(context.summary.logs, 1);
+        assert_eq!(context.summary.hosts, 2);
+    }
+
+    #[test]
+    fn duplicated_findings() {
+        let mut results = vec![
+            result(ResultType::Alarm, "2001:db8::1", "1.2.1", Some(443)),
+            result(ResultType::Alarm, "2001:DB8:0::1", "1.2.1", Some(443)),
+            result(ResultType::Alarm, "2001:db8::1", "1.2.1", Some(80)),
+        ];
+        results[1].protocol = None;
+        let context = Report::new("scan-1", results).context();
+        assert_eq!(context.summary.alarms, 2);
+        assert_eq!(context.findings[0].occurrences.len(), 2);
+        assert_eq!(context.summary.hosts, 1);
+        assert_eq!(context.hosts[0].ip, "2001:db8::1");
+    }
+
+    #[test]
+    fn shipped_templates() {
+        let report = report();
+        for kind in [ReportKind::Executive, ReportKind::Technical] {
+            let html = report.render(kind, ReportFormat::Html).unwrap();
+            assert!(html.contains("scan-1"), "{kind} html");
+            assert!(
+                html.contains("Apache HTTP Server &lt; 2.4.58"),
+                "{kind} html"
+            );
+            let markdown = report.render(kind, ReportFormat::Markdown).unwrap();
+            assert!(markdown.starts_with('#'), "{kind} markdown");
+            assert!(
+                markdown.contains("Apache HTTP Server < 2.4.58"),
+                "{kind} markdown"
+            );
+        }
+        let technical = report
+            .render(ReportKind::Technical, ReportFormat::Markdown)
+            .unwrap();
+        assert!(technical.contains("Update to version 2.4.58"));
+        assert!(technical.contains("<1.2.4> on 192.168.0.3"));
+    }
+
+    #[test]
+    fn severity_profile() {
+        let profile = SeverityProfile {
+            levels: vec![
+                cvss::SeverityLevel {
+                    name: "P1".to_string(),
+                    min: 7.5,
+                },
+                cvss::SeverityLevel {
+                    name: "P2".to_string(),
+                    min: 0.1,
+                },
+            ],
+        };
+        let report = report().with_severity_profile(profile);
+        let context = report.context();
+        assert_eq!(context.findings[0].severity.as_deref(), Some("P2"));
+        let severities: Vec<_> = context
+            .summary
+            .severities
+            .iter()
+            .map(|x| (x.level.as_str(), x.findings))
+            .collect();
+        assert_eq!(severities, vec![("P1", 0), ("P2", 1)]);
+        let markdown = report
+            .render(ReportKind::Executive, ReportFormat::Markdown)
+            .unwrap();
+        assert!(markdown.contains("| P2 | 1 |"));
+        assert!(markdown.contains("| Apache HTTP Server < 2.4.58 | P2 | 2 |"));
+    }
+
+    #[test]
+    fn custom_template() {
+        let rendered = report()
+            .render_template(
+                "{scan_id}: {{ for x in hosts }}{x.ip}={x.alarms} {{ endfor }}",
+                ReportFormat::Markdown,
+            )
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "scan-1: 192.168.0.2=2 192.168.0.1=1 192.168.0.3=0 "
+        );
+        assert!(report()
+            .render_template("{{ for x in }}", ReportFormat::Html)
+            .is_err());
+    }
+
+    #[test]
+    fn branding() {
+        let logo = std::env::temp_dir().join(format!("report-logo-{}.svg", std::process::id()));
+        std::fs::write(&logo, "<svg/>").unwrap();
+        let branding = Branding {
+            organization: Some("ACME <Security>".to_string()),
+            logo: Some(logo.to_string_lossy().to_string()),
+            color: Some("#1e7b34".to_string()),
+            footer: Some("Confidential".to_string()),
+        }
+        .embed_logo()
+        .unwrap();
+        std::fs::remove_file(&logo).unwrap();
+        assert_eq!(
+            branding.logo.as_deref(),
+            Some("data:image/svg+xml;base64,PHN2Zy8+")
+        );
+        let remote = Branding {
+            logo: Some("https://example.com/logo.png".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(remote.clone().embed_logo().unwrap(), remote);
+
+        let branded = report().with_branding(branding);
+        for kind in [ReportKind::Executive, ReportKind::Technical] {
+            let rendered = branded.render(kind, ReportFormat::Html).unwrap();
+            assert!(rendered.contains("ACME &lt;Security&gt;"), "{kind}");
+            assert!(
+                rendered.contains("data:image/svg+xml;base64,PHN2Zy8+"),
+                "{kind}"
+            );
+            assert!(rendered.contains("#1e7b34"), "{kind}");
+            assert!(rendered.contains("Confidential"), "{kind}");
+            let rendered = branded.render(kind, ReportFormat::Markdown).unwrap();
+            assert!(rendered.contains("ACME <Security>"), "{kind}");
+            assert!(rendered.contains("Confidential"), "{kind}");
+        }
+        let unbranded = report()
+            .render(ReportKind::Executive, ReportFormat::Html)
+            .unwrap();
+        assert!(!unbranded.contains("<img"));
+    }
+}
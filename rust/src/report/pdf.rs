@@ -0,0 +1,201 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Prints rendered HTML reports to PDF via an external tool.
+
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+
+/// The tool used to print HTML to PDF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfBackend {
+    /// Chromium or Chrome in headless mode
+    #[default]
+    Chromium,
+    /// wkhtmltopdf
+    Wkhtmltopdf,
+    /// WeasyPrint
+    Weasyprint,
+}
+
+impl PdfBackend {
+    fn command(&self) -> &'static str {
+        match self {
+            Self::Chromium => "chromium",
+            Self::Wkhtmltopdf => "wkhtmltopdf",
+            Self::Weasyprint => "weasyprint",
+        }
+    }
+
+    fn args(&self, input: &Path, output: &Path) -> Vec<OsString> {
+        match self {
+            Self::Chromium => {
+                let mut print_to = OsString::from("--print-to-pdf=");
+                print_to.push(output);
+                let mut url = OsString::from("file://");
+                url.push(input);
+                vec![
+                    "--headless".into(),
+                    "--disable-gpu".into(),
+                    "--no-sandbox".into(),
+                    "--no-pdf-header-footer".into(),
+                    print_to,
+                    url,
+                ]
+            }
+            Self::Wkhtmltopdf => vec![
+                "--quiet".into(),
+                "--enable-local-file-access".into(),
+                input.into(),
+                output.into(),
+            ],
+            Self::Weasyprint => vec![input.into(), output.into()],
+        }
+    }
+}
+
+impl FromStr for PdfBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chromium" | "chrome" => Ok(Self::Chromium),
+            "wkhtmltopdf" => Ok(Self::Wkhtmltopdf),
+            "weasyprint" => Ok(Self::Weasyprint),
+            x => Err(format!(
+                "unknown PDF backend {x}, expected chromium, wkhtmltopdf or weasyprint"
+            )),
+        }
+    }
+}
+
+impl Display for PdfBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.command())
+    }
+}
+
+/// Prints HTML to PDF by calling the tool of the backend.
+///
+/// The tool is looked up in `PATH` unless a command is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PdfConverter {
+    #[serde(default)]
+    pub backend: PdfBackend,
+    /// Path to the executable of the backend
+    #[serde(default)]
+    pub command: Option<PathBuf>,
+}
+
+/// Removes the working directory of a conversion when dropped
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new() -> std::io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "scannerlib-report-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+impl PdfConverter {
+    /// Creates a converter using the tool of backend found in `PATH`
+    pub fn new(backend: PdfBackend) -> Self {
+        Self {
+            backend,
+            command: None,
+        }
+    }
+
+    /// Sets the path to the executable of the backend
+    pub fn with_command(mut self, command: impl Into<PathBuf>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    /// Prints a HTML document to PDF.
+    ///
+    /// This blocks until the tool is finished.
+    pub fn convert(&self, html: &str) -> Result<Vec<u8>, Error> {
+        let pdf_error = |e: std::io::Error| Error::Pdf(e.to_string());
+        let dir = WorkDir::new().map_err(pdf_error)?;
+        let input = dir.0.join("report.html");
+        let output = dir.0.join("report.pdf");
+        std::fs::write(&input, html).map_err(pdf_error)?;
+        let command = self
+            .command
+            .clone()
+            .unwrap_or_else(|| self.backend.command().into());
+        let out = Command::new(&command)
+            .args(self.backend.args(&input, &output))
+            .output()
+            .map_err(|e| Error::Pdf(format!("{}: {e}", command.to_string_lossy())))?;
+        if !out.status.success() {
+            return Err(Error::Pdf(format!(
+                "{} exited with {}: {}",
+                command.to_string_lossy(),
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+        std::fs::read(&output).map_err(pdf_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let args = PdfBackend::Chromium.args(Path::new("/tmp/r.html"), Path::new("/tmp/r.pdf"));
+        assert!(args.contains(&"--headless".into()));
+        assert!(args.contains(&"--print-to-pdf=/tmp/r.pdf".into()));
+        assert_eq!(args.last(), Some(&"file:///tmp/r.html".into()));
+        assert_eq!(
+            PdfBackend::Weasyprint.args(Path::new("/tmp/r.html"), Path::new("/tmp/r.pdf")),
+            vec![OsString::from("/tmp/r.html"), OsString::from("/tmp/r.pdf")]
+        );
+        assert_eq!("wkhtmltopdf".parse(), Ok(PdfBackend::Wkhtmltopdf));
+        assert!("prince".parse::<PdfBackend>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn convert() {
+        // cp takes the same arguments as weasyprint and returns the input unchanged
+        let converter = PdfConverter::new(PdfBackend::Weasyprint).with_command("cp");
+        assert_eq!(
+            converter.convert("<p>report</p>").unwrap(),
+            b"<p>report</p>"
+        );
+        let converter = PdfConverter::new(PdfBackend::Weasyprint).with_command("false");
+        assert!(matches!(converter.convert(""), Err(Error::Pdf(_))));
+        let converter =
+            PdfConverter::new(PdfBackend::Weasyprint).with_command("/nonexistent/weasyprint");
+        assert!(matches!(converter.convert(""), Err(Error::Pdf(_))));
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Wall-clock budget of a scan.
+//!
+//! The budget starts when the scan starts. Once it is exhausted no further VT is launched, the
+//! remaining VTs of each host are reported as skipped. A VT that is still running may finish
+//! within the grace period, afterwards it is cancelled. A scan with skipped or cancelled VTs ends
+//! as partially completed.
+
+use std::time::{Duration, Instant};
+
+use crate::models::ScanPreference;
+
+/// Scan preference id containing the wall-clock budget of the scan in seconds, 0 disables it
+pub const SCAN_BUDGET: &str = "scan_budget";
+/// Scan preference id containing the seconds running VTs may continue after the budget is
+/// exhausted
+pub const SCAN_BUDGET_GRACE: &str = "scan_budget_grace";
+
+/// Grace period in seconds when it is not set
+pub const DEFAULT_GRACE: u64 = 60;
+
+/// Deadline of a scan and its grace period
+#[derive(Debug, Clone, Copy)]
+pub struct ScanBudget {
+    deadline: Instant,
+    grace: Duration,
+}
+
+impl ScanBudget {
+    /// Creates a budget starting now.
+    pub fn new(budget: Duration, grace: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + budget,
+            grace,
+        }
+    }
+
+    /// Creates a budget starting now when the scan preferences contain a budget.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let value = |id: &str| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .and_then(|x| x.value.trim().parse::<u64>().ok())
+        };
+        let budget = value(SCAN_BUDGET).filter(|x| *x > 0)?;
+        let grace = value(SCAN_BUDGET_GRACE).unwrap_or(DEFAULT_GRACE);
+        Some(Self::new(
+            Duration::from_secs(budget),
+            Duration::from_secs(grace),
+        ))
+    }
+
+    /// Returns true when no further VT may be launched.
+    pub fn is_exhausted(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Returns the time a running VT may continue before it is cancelled.
+    pub fn remaining(&self) -> Duration {
+        (self.deadline + self.grace).saturating_duration_since(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ScanBudget, DEFAULT_GRACE, SCAN_BUDGET, SCAN_BUDGET_GRACE};
+    use crate::models::ScanPreference;
+
+    fn preference(id: &str, value: &str) -> ScanPreference {
+        ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn from_preferences() {
+        assert!(ScanBudget::from_preferences(&[]).is_none());
+        assert!(ScanBudget::from_preferences(&[preference(SCAN_BUDGET, "0")]).is_none());
+        assert!(ScanBudget::from_preferences(&[preference(SCAN_BUDGET, "soon")]).is_none());
+
+        let budget = ScanBudget::from_preferences(&[preference(SCAN_BUDGET, "60")]).unwrap();
+        assert!(!budget.is_exhausted());
+        assert!(budget.remaining() > Duration::from_secs(60 + DEFAULT_GRACE - 1));
+
+        let budget = ScanBudget::from_preferences(&[
+            preference(SCAN_BUDGET, "60"),
+            preference(SCAN_BUDGET_GRACE, "0"),
+        ])
+        .unwrap();
+        assert!(budget.remaining() <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn exhausted() {
+        let budget = ScanBudget::new(Duration::ZERO, Duration::ZERO);
+        assert!(budget.is_exhausted());
+        assert_eq!(budget.remaining(), Duration::ZERO);
+
+        let budget = ScanBudget::new(Duration::ZERO, Duration::from_secs(10));
+        assert!(budget.is_exhausted());
+        assert!(budget.remaining() > Duration::ZERO);
+    }
+}
@@ -2,7 +2,9 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use crate::models::{Host, Protocol};
+use std::time::Duration;
+
+use crate::models::{Host, Protocol, Traffic};
 
 use crate::nasl::interpreter::InterpretError;
 use crate::scheduling::Stage;
@@ -22,6 +24,9 @@ pub enum ExecuteError {
     #[error("unable to handle parameter: {0}")]
     /// The parameter could not be processed
     Parameter(crate::models::Parameter),
+    #[error("unable to initialize plugins: {0}")]
+    /// A plugin failed to initialize for the scan
+    Plugin(crate::nasl::utils::FunctionErrorKind),
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +49,18 @@ pub enum ScriptResultKind {
     MissingMandatoryKey(String),
     /// Contains the error the script returned
     Error(InterpretError),
+    /// Script did not run because the knowledge base of the host was seeded from a previous scan
+    /// or an nmap report
+    Seeded,
+    /// Script was aborted because it did not finish within the contained timeout
+    Timeout(Duration),
+    /// Script did not run because the time budget of the scan was exhausted
+    Skipped,
+    /// Script was aborted because it did not finish within the grace period after the time
+    /// budget of the scan was exhausted
+    Cancelled,
+    /// Script did not run because scanning the host was stopped for the contained reason
+    Blocked(String),
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +76,10 @@ pub struct ScriptResult {
     pub kind: ScriptResultKind,
     /// The target of the result
     pub target: Host,
+    /// Wall time of the execution
+    pub duration: Duration,
+    /// Bytes transferred to and from the target
+    pub traffic: Traffic,
 }
 
 impl ScriptResult {
@@ -67,8 +88,25 @@ impl ScriptResult {
         matches!(&self.kind, ScriptResultKind::ReturnCode(0))
     }
     /// Returns true when the return code of the script not 0
+    ///
+    /// A script skipped because of a seeded knowledge base, cut off by the time budget of the
+    /// scan or not run on a blocked host did not fail.
     pub fn has_failed(&self) -> bool {
         !self.has_succeeded()
+            && !matches!(
+                self.kind,
+                ScriptResultKind::Seeded | ScriptResultKind::Blocked(_)
+            )
+            && !self.is_truncated()
+    }
+
+    /// Returns true when the script was skipped or cancelled because of the time budget of the
+    /// scan
+    pub fn is_truncated(&self) -> bool {
+        matches!(
+            self.kind,
+            ScriptResultKind::Skipped | ScriptResultKind::Cancelled
+        )
     }
 
     /// Returns true when the script didn't run
@@ -79,6 +117,9 @@ impl ScriptResult {
                 | ScriptResultKind::MissingMandatoryKey(_)
                 | ScriptResultKind::ContainsExcludedKey(_)
                 | ScriptResultKind::MissingPort(..)
+                | ScriptResultKind::Seeded
+                | ScriptResultKind::Skipped
+                | ScriptResultKind::Blocked(_)
         )
     }
 }
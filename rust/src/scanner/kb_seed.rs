@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Seeds the knowledge base of a scan with the detection results of a previous scan.
+//!
+//! Before the first VT runs the KB items of the configured namespaces are copied from the
+//! previous scan of the same host into the new scan. Hosts that were seeded skip the VTs of the
+//! configured families, all other hosts are scanned as usual. A previous knowledge base that is
+//! older than the maximum age is ignored, as are KB items that already expired.
+
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::models::{Host, Scan, ScanPreference};
+use crate::storage::{item::Nvt, ContextKey, Field, Kb, Storage, StorageError};
+
+/// Scan preference id containing the id of the scan the knowledge base is imported from
+pub const KB_SEED_SCAN_ID: &str = "kb_seed_scan_id";
+/// Scan preference id containing the comma separated KB key prefixes that are imported
+pub const KB_SEED_NAMESPACES: &str = "kb_seed_namespaces";
+/// Scan preference id containing the maximum age of the imported knowledge base in seconds
+pub const KB_SEED_MAX_AGE: &str = "kb_seed_max_age";
+/// Scan preference id containing the comma separated families that are skipped on seeded hosts
+pub const KB_SEED_SKIP_FAMILIES: &str = "kb_seed_skip_families";
+
+/// KB item containing the id of the scan the knowledge base of a host was seeded from
+pub const KB_SEEDED_FROM: &str = "Host/kb_seed/scan_id";
+
+/// KB key prefixes of the OS detection and the service mapping
+pub const DEFAULT_NAMESPACES: &[&str] = &["Host/OS/", "HostDetails/OS/", "Services/", "Known/"];
+
+/// Age in seconds after which a previous knowledge base is not imported anymore
+pub const DEFAULT_MAX_AGE: u64 = 86400;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+/// Imports selected KB namespaces of a previous scan
+#[derive(Debug, Clone)]
+pub struct KbSeed {
+    scan_id: String,
+    namespaces: Vec<String>,
+    max_age: u64,
+    skip_families: Vec<String>,
+    seeded: HashSet<Host>,
+}
+
+impl KbSeed {
+    /// Creates a KbSeed when the scan preferences contain a previous scan id.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let value = |id: &str| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .map(|x| x.value.trim())
+                .filter(|x| !x.is_empty())
+        };
+        let list = |x: &str| {
+            x.split(',')
+                .map(|x| x.trim().to_owned())
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Some(Self {
+            scan_id: value(KB_SEED_SCAN_ID)?.to_owned(),
+            namespaces: value(KB_SEED_NAMESPACES)
+                .map(list)
+                .unwrap_or_else(|| DEFAULT_NAMESPACES.iter().map(|x| x.to_string()).collect()),
+            max_age: value(KB_SEED_MAX_AGE)
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(DEFAULT_MAX_AGE),
+            skip_families: value(KB_SEED_SKIP_FAMILIES).map(list).unwrap_or_default(),
+            seeded: HashSet::new(),
+        })
+    }
+
+    /// Returns true when the KB item is within one of the namespaces and did not expire.
+    fn imports(&self, kb: &Kb, now: u64) -> bool {
+        self.namespaces.iter().any(|x| kb.key.starts_with(x)) && kb.expire.is_none_or(|x| x > now)
+    }
+
+    /// Returns the KB items of the previous scan of the host that would be imported.
+    fn importable<S: Storage>(
+        &self,
+        storage: &S,
+        host: &Host,
+        now: u64,
+    ) -> Result<Vec<Kb>, StorageError> {
+        let previous = ContextKey::Scan(self.scan_id.clone(), Some(host.clone()));
+        let Some(snapshot) = storage.retrieve_kb_snapshot(&previous)? else {
+            return Ok(vec![]);
+        };
+        if self.max_age > 0 && now.saturating_sub(snapshot.updated) > self.max_age {
+            tracing::debug!(%host, updated = snapshot.updated, "previous knowledge base is too old");
+            return Ok(vec![]);
+        }
+        Ok(snapshot
+            .items
+            .into_iter()
+            .filter(|x| self.imports(x, now))
+            .collect())
+    }
+
+    /// Returns the KB items that would be imported for the host without importing them.
+    pub fn preview<S: Storage>(&self, storage: &S, host: &Host) -> Vec<Kb> {
+        self.importable(storage, host, now()).unwrap_or_else(|e| {
+            tracing::warn!(%host, error=%e, "unable to read previous knowledge base");
+            vec![]
+        })
+    }
+
+    /// Copies the KB items of the previous scan of the host into the scan.
+    ///
+    /// Returns the number of imported KB items.
+    pub fn seed<S: Storage>(
+        &self,
+        storage: &S,
+        scan_id: &str,
+        host: &Host,
+        now: u64,
+    ) -> Result<usize, StorageError> {
+        let key = ContextKey::Scan(scan_id.to_owned(), Some(host.clone()));
+        let mut count = 0;
+        for kb in self.importable(storage, host, now)? {
+            storage.dispatch(&key, Field::KB(kb))?;
+            count += 1;
+        }
+        if count > 0 {
+            storage.dispatch(
+                &key,
+                Field::KB((KB_SEEDED_FROM, self.scan_id.clone()).into()),
+            )?;
+        }
+        Ok(count)
+    }
+
+    /// Seeds the knowledge base of each host of the scan.
+    ///
+    /// Hosts that could not be seeded are scanned completely.
+    pub fn apply<S: Storage>(mut self, storage: &S, scan: &Scan) -> Self {
+        let now = now();
+        for host in scan.target.all_hosts() {
+            match self.seed(storage, &scan.scan_id, host, now) {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::debug!(%host, count, from = self.scan_id, "seeded knowledge base");
+                    self.seeded.insert(host.clone());
+                }
+                Err(e) => tracing::warn!(%host, error=%e, "unable to seed knowledge base"),
+            }
+        }
+        self
+    }
+
+    /// Returns true when the VT does not need to run on the host because its detection results
+    /// were imported.
+    pub fn skips(&self, host: &Host, vt: &Nvt) -> bool {
+        self.seeded.contains(host) && self.skips_family(vt)
+    }
+
+    /// Returns true when the family of the VT is skipped on seeded hosts.
+    pub fn skips_family(&self, vt: &Nvt) -> bool {
+        self.skip_families.contains(&vt.family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{DefaultDispatcher, Dispatcher, Retrieve, Retriever};
+
+    use super::*;
+
+    fn preferences(values: &[(&str, &str)]) -> Vec<ScanPreference> {
+        values
+            .iter()
+            .map(|(id, value)| ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeds_selected_namespaces() {
+        let storage = DefaultDispatcher::new();
+        let host = "192.168.0.1".to_string();
+        let previous = ContextKey::Scan("previous".into(), Some(host.clone()));
+        let kbs = [
+            Kb::from(("Host/OS/Linux", 1)),
+            Kb::from(("Services/www", 80)),
+            Kb::from(("www/80/content", "hello")),
+            Kb {
+                key: "Services/ssh".into(),
+                value: 22.into(),
+                expire: Some(1),
+            },
+        ];
+        for kb in kbs {
+            storage.dispatch(&previous, Field::KB(kb)).unwrap();
+        }
+        let seed = KbSeed::from_preferences(&preferences(&[
+            (KB_SEED_SCAN_ID, "previous"),
+            (KB_SEED_SKIP_FAMILIES, "Service detection"),
+        ]))
+        .unwrap();
+        assert_eq!(seed.seed(&storage, "current", &host, now()).unwrap(), 2);
+        let current = ContextKey::Scan("current".into(), Some(host.clone()));
+        let found = |key: &str| {
+            storage
+                .retrieve(&current, Retrieve::KB(key.into()))
+                .unwrap()
+                .count()
+        };
+        assert_eq!(found("Host/OS/Linux"), 1);
+        assert_eq!(found("Services/www"), 1);
+        assert_eq!(found("Services/ssh"), 0);
+        assert_eq!(found("www/80/content"), 0);
+        assert_eq!(found(KB_SEEDED_FROM), 1);
+
+        let seed = seed.apply(
+            &storage,
+            &Scan {
+                scan_id: "next".into(),
+                target: crate::models::Target {
+                    hosts: vec![host.clone(), "192.168.0.2".into()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let vt = Nvt {
+            family: "Service detection".into(),
+            ..Default::default()
+        };
+        assert!(seed.skips(&host, &vt));
+        assert!(!seed.skips(&"192.168.0.2".to_string(), &vt));
+        // too old
+        assert_eq!(
+            seed.seed(&storage, "current", &host, now() + DEFAULT_MAX_AGE + 1)
+                .unwrap(),
+            0
+        );
+        assert!(KbSeed::from_preferences(&preferences(&[(KB_SEED_MAX_AGE, "1")])).is_none());
+    }
+}
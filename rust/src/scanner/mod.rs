@@ -11,33 +11,47 @@
 //! requirements. Finally, for a given VT and a given Host, the
 //! VT is then run to completion using the `VTRunner`.
 
+mod budget;
 mod error;
+mod kb_seed;
+mod nmap_import;
+mod os_detection;
+mod plan;
 mod running_scan;
 mod scan_runner;
 mod scanner_stack;
 mod vt_runner;
 
 pub use error::ExecuteError;
+pub use plan::plan_scan;
 pub use scan_runner::ScanRunner;
 pub use scanner_stack::ScannerStack;
 pub use scanner_stack::ScannerStackWithStorage;
 
 use async_trait::async_trait;
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::sync::RwLock;
 
 use crate::models::{
-    scanner::{Error, ScanDeleter, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper},
-    Scan,
+    scanner::{
+        Error, ScanDeleter, ScanPlanner, ScanResultFetcher, ScanResults, ScanStarter, ScanStopper,
+    },
+    Scan, ScanPlan, ScanPreference,
 };
 use crate::nasl::nasl_std_functions;
-use crate::nasl::syntax::{FSPluginLoader, Loader};
-use crate::nasl::utils::Executor;
-use crate::scheduling::WaveExecutionPlan;
+use crate::nasl::syntax::{CompileCache, FSPluginLoader, Loader};
+use crate::nasl::utils::{policy::BUILTINS_DISABLED, Executor};
+use crate::notus::PackageScanner;
+use crate::scheduling::{ExecutionPlaner, VTError, WaveExecutionPlan};
 use crate::storage::Storage;
 use crate::storage::{ContextKey, DefaultDispatcher};
 use running_scan::{RunningScan, RunningScanHandle};
 use scanner_stack::DefaultScannerStack;
+use vt_runner::PLUGINS_TIMEOUT;
 
 /// Allows starting, stopping and managing the results of new scans.
 pub struct Scanner<S: ScannerStack> {
@@ -45,6 +59,12 @@ pub struct Scanner<S: ScannerStack> {
     storage: Arc<S::Storage>,
     loader: Arc<S::Loader>,
     function_executor: Arc<Executor>,
+    plugin_timeout: Option<u64>,
+    disabled_builtins: Vec<String>,
+    package_scanner: Option<Arc<dyn PackageScanner>>,
+    compile_cache: Option<Arc<CompileCache>>,
+    include_graph: Option<PathBuf>,
+    pcap_directory: Option<PathBuf>,
 }
 
 impl<St, L> Scanner<(St, L)>
@@ -58,10 +78,85 @@ where
             storage: Arc::new(storage),
             loader: Arc::new(loader),
             function_executor: Arc::new(executor),
+            plugin_timeout: None,
+            disabled_builtins: vec![],
+            package_scanner: None,
+            compile_cache: None,
+            include_graph: None,
+            pcap_directory: None,
         }
     }
 }
 
+impl<S: ScannerStack> Scanner<S> {
+    /// Sets the timeout in seconds of each VT of scans that do not set the plugins_timeout
+    /// preference themselves.
+    pub fn with_plugin_timeout(mut self, plugin_timeout: Option<u64>) -> Self {
+        self.plugin_timeout = plugin_timeout;
+        self
+    }
+
+    /// Disables the builtin functions or modules for every scan in addition to the ones disabled by
+    /// the builtins_disabled preference of the scan.
+    pub fn with_disabled_builtins(mut self, disabled: Vec<String>) -> Self {
+        self.disabled_builtins = disabled;
+        self
+    }
+
+    /// Sets the notus instance used by builtin functions to check the gathered packages of a
+    /// target.
+    pub fn with_package_scanner(mut self, scanner: Arc<dyn PackageScanner>) -> Self {
+        self.package_scanner = Some(scanner);
+        self
+    }
+
+    /// Sets the cache the scripts of each scan and their includes are compiled with instead of
+    /// parsing them on each run.
+    pub fn with_compile_cache(mut self, cache: CompileCache) -> Self {
+        self.compile_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Sets the include graph of the feed, the includes of the VTs of each scan are loaded and
+    /// parsed once when it starts instead of on each include.
+    ///
+    /// The graph is read at the start of each scan so that it follows feed updates.
+    pub fn with_include_graph(mut self, path: Option<PathBuf>) -> Self {
+        self.include_graph = path;
+        self
+    }
+
+    /// Sets the directory the traffic of the VTs of each scan is recorded to as pcap files.
+    pub fn with_pcap_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.pcap_directory = directory;
+        self
+    }
+
+    /// Adds the default plugins_timeout to the preferences of the scan if it is missing and the
+    /// builtin functions disabled for every scan.
+    fn with_defaults(&self, mut scan: Scan) -> Scan {
+        if !self.disabled_builtins.is_empty() {
+            scan.scan_preferences.push(ScanPreference {
+                id: BUILTINS_DISABLED.to_string(),
+                value: self.disabled_builtins.join(","),
+            });
+        }
+        if let Some(timeout) = self.plugin_timeout {
+            if !scan
+                .scan_preferences
+                .iter()
+                .any(|x| x.id == PLUGINS_TIMEOUT)
+            {
+                scan.scan_preferences.push(ScanPreference {
+                    id: PLUGINS_TIMEOUT.to_string(),
+                    value: timeout.to_string(),
+                });
+            }
+        }
+        scan
+    }
+}
+
 impl Scanner<DefaultScannerStack> {
     /// Create a new scanner with the default stack.
     /// Requires the root path for the loader.
@@ -90,12 +185,25 @@ where
 #[async_trait]
 impl<S: ScannerStack + 'static> ScanStarter for Scanner<S> {
     async fn start_scan(&self, scan: Scan) -> Result<(), Error> {
+        let scan = self.with_defaults(scan);
         let storage = self.storage.clone();
         let loader = self.loader.clone();
         let function_executor = self.function_executor.clone();
+        let package_scanner = self.package_scanner.clone();
+        let compile_cache = self.compile_cache.clone();
+        let include_graph = self.include_graph.clone();
+        let pcap_directory = self.pcap_directory.clone();
         let id = scan.scan_id.clone();
-        let handle =
-            RunningScan::<S>::start::<WaveExecutionPlan>(scan, storage, loader, function_executor);
+        let handle = RunningScan::<S>::start::<WaveExecutionPlan>(
+            scan,
+            storage,
+            loader,
+            function_executor,
+            package_scanner,
+            compile_cache,
+            include_graph,
+            pcap_directory,
+        );
         self.running.write().await.insert(id, handle);
         Ok(())
     }
@@ -161,3 +269,19 @@ impl<S: ScannerStack> ScanResultFetcher for Scanner<S> {
         })
     }
 }
+
+#[async_trait]
+impl<S: ScannerStack> ScanPlanner for Scanner<S> {
+    async fn plan_scan(&self, scan: &Scan) -> Result<ScanPlan, Error> {
+        let scan = &self.with_defaults(scan.clone());
+        let make_scheduling_error = |e: VTError| Error::SchedulingError {
+            id: scan.scan_id.to_string(),
+            reason: e.to_string(),
+        };
+        let schedule = self
+            .storage
+            .execution_plan::<WaveExecutionPlan>(scan)
+            .map_err(make_scheduling_error)?;
+        plan_scan(&*self.storage, schedule, scan).map_err(make_scheduling_error)
+    }
+}
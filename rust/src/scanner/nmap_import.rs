@@ -0,0 +1,378 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Imports the open ports and services of an nmap XML report instead of scanning for them.
+//!
+//! Before the first VT runs the open ports and detected services of each host within the report
+//! are stored as the `Ports/*`, `Services/*` and `Known/*` KB items the port scanners and the
+//! service detection would have set. Hosts found in the report skip the VTs of the port scanner
+//! family, all other hosts are scanned as usual. A host is matched by any of its addresses or
+//! host names within the report.
+
+use std::collections::{HashMap, HashSet};
+
+use quick_xml::events::{BytesStart, Event};
+
+use crate::models::{Host, Scan, ScanPreference};
+use crate::storage::{item::Nvt, ContextKey, Field, Kb, Storage, StorageError};
+
+/// Scan preference id containing an nmap XML report
+pub const NMAP_IMPORT: &str = "nmap_import";
+
+/// Family of the VTs that are skipped on imported hosts
+pub const PORT_SCANNERS_FAMILY: &str = "Port scanners";
+
+/// Encapsulation of a port tunneled through SSL or TLS as used in `Transports/TCP/<port>`
+const ENCAPS_SSLV23: i64 = 2;
+
+/// Error while reading an nmap XML report
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("nmap report must be given inline, files on the scanner host are not read")]
+    NotInline,
+    #[error("invalid nmap report: {0}")]
+    Xml(#[from] quick_xml::Error),
+}
+
+/// Open port of a host as found by nmap
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Port {
+    protocol: String,
+    number: u16,
+    open: bool,
+    service: Option<String>,
+    tunnel: Option<String>,
+}
+
+impl Port {
+    /// Returns the name the service detection of the feed uses for the nmap service.
+    fn service(&self) -> Option<&str> {
+        match self.service.as_deref()? {
+            "" | "unknown" | "tcpwrapped" => None,
+            "http" | "http-alt" | "https" | "https-alt" => Some("www"),
+            "microsoft-ds" => Some("cifs445"),
+            x => Some(x),
+        }
+    }
+
+    fn kb_items(&self) -> Vec<Kb> {
+        let (protocol, port) = (self.protocol.as_str(), self.number as i64);
+        let mut items = vec![Kb::from((format!("Ports/{protocol}/{port}"), 1))];
+        if let Some(service) = self.service() {
+            let key = match protocol {
+                "udp" => format!("Services/udp/{service}"),
+                _ => format!("Services/{service}"),
+            };
+            items.push(Kb::from((key, port)));
+            items.push(Kb::from((format!("Known/{protocol}/{port}"), service)));
+        }
+        if protocol == "tcp" && self.tunnel.as_deref() == Some("ssl") {
+            items.push(Kb::from((format!("Transports/TCP/{port}"), ENCAPS_SSLV23)));
+        }
+        items
+    }
+}
+
+/// Host of an nmap report
+#[derive(Debug, Clone, Default)]
+struct ReportHost {
+    up: bool,
+    names: Vec<String>,
+    ports: Vec<Port>,
+}
+
+impl ReportHost {
+    fn kb_items(&self) -> Vec<Kb> {
+        let mut items = vec![
+            Kb::from(("Host/scanned", 1)),
+            Kb::from(("Host/scanners/nmap", 1)),
+        ];
+        items.extend(
+            self.ports
+                .iter()
+                .filter(|x| x.open)
+                .flat_map(|x| x.kb_items()),
+        );
+        items
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, quick_xml::Error> {
+    match element.try_get_attribute(name)? {
+        Some(x) => Ok(Some(x.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse(xml: &str) -> Result<Vec<ReportHost>, quick_xml::Error> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut hosts = vec![];
+    let mut host: Option<ReportHost> = None;
+    let mut port: Option<Port> = None;
+    loop {
+        let (element, empty) = match reader.read_event()? {
+            Event::Start(x) => (x, false),
+            Event::Empty(x) => (x, true),
+            Event::End(x) => {
+                match x.name().as_ref() {
+                    b"port" => {
+                        if let (Some(host), Some(port)) = (host.as_mut(), port.take()) {
+                            host.ports.push(port);
+                        }
+                    }
+                    b"host" => hosts.extend(host.take()),
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        match (element.name().as_ref(), host.as_mut()) {
+            (b"host", _) if !empty => host = Some(ReportHost::default()),
+            (b"status", Some(host)) => {
+                host.up = attribute(&element, "state")?.as_deref() == Some("up");
+            }
+            (b"address", Some(host)) => {
+                if let Some("ipv4" | "ipv6") = attribute(&element, "addrtype")?.as_deref() {
+                    host.names.extend(attribute(&element, "addr")?);
+                }
+            }
+            (b"hostname", Some(host)) => host.names.extend(attribute(&element, "name")?),
+            (b"port", Some(_)) if !empty => {
+                port = Some(Port {
+                    protocol: attribute(&element, "protocol")?.unwrap_or_default(),
+                    number: attribute(&element, "portid")?
+                        .and_then(|x| x.parse().ok())
+                        .unwrap_or_default(),
+                    ..Default::default()
+                });
+            }
+            (b"state", Some(_)) => {
+                if let Some(port) = port.as_mut() {
+                    port.open = attribute(&element, "state")?.as_deref() == Some("open");
+                }
+            }
+            (b"service", Some(_)) => {
+                if let Some(port) = port.as_mut() {
+                    port.service = attribute(&element, "name")?;
+                    port.tunnel = attribute(&element, "tunnel")?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(hosts)
+}
+
+/// Open ports and services of the hosts of an nmap report
+#[derive(Debug, Clone, Default)]
+pub struct NmapImport {
+    hosts: HashMap<Host, Vec<Kb>>,
+    imported: HashSet<Host>,
+}
+
+impl NmapImport {
+    /// Parses an nmap XML report, hosts that are not up are ignored.
+    pub fn parse(xml: &str) -> Result<Self, ImportError> {
+        let mut hosts = HashMap::new();
+        for host in parse(xml)?.into_iter().filter(|x| x.up) {
+            let items = host.kb_items();
+            for name in host.names {
+                hosts.insert(name.to_lowercase(), items.clone());
+            }
+        }
+        Ok(Self {
+            hosts,
+            imported: HashSet::new(),
+        })
+    }
+
+    /// Reads the report when the scan preferences contain one.
+    ///
+    /// The preference must contain the report itself, a path is refused so that a scan cannot
+    /// read files of the scanner host.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Option<Self> {
+        let value = preferences
+            .iter()
+            .find(|x| x.id == NMAP_IMPORT)
+            .map(|x| x.value.trim())
+            .filter(|x| !x.is_empty())?;
+        let report = if value.starts_with('<') {
+            Ok(value)
+        } else {
+            Err(ImportError::NotInline)
+        };
+        match report.and_then(Self::parse) {
+            Ok(import) => Some(import),
+            Err(error) => {
+                tracing::warn!(%error, "unable to import nmap report, scanning ports instead");
+                None
+            }
+        }
+    }
+
+    /// Returns the KB items of the host, none when the host is not within the report.
+    pub fn kb_items(&self, host: &Host) -> Option<&[Kb]> {
+        self.hosts.get(&host.to_lowercase()).map(|x| x.as_slice())
+    }
+
+    /// Stores the KB items of the host into the scan.
+    ///
+    /// Returns the number of imported KB items.
+    pub fn import<S: Storage>(
+        &self,
+        storage: &S,
+        scan_id: &str,
+        host: &Host,
+    ) -> Result<usize, StorageError> {
+        let key = ContextKey::Scan(scan_id.to_owned(), Some(host.clone()));
+        let items = self.kb_items(host).unwrap_or_default();
+        for kb in items {
+            storage.dispatch(&key, Field::KB(kb.clone()))?;
+        }
+        Ok(items.len())
+    }
+
+    /// Imports the open ports and services of each host of the scan.
+    ///
+    /// Hosts that could not be imported are scanned completely.
+    pub fn apply<S: Storage>(mut self, storage: &S, scan: &Scan) -> Self {
+        for host in scan.target.all_hosts() {
+            match self.import(storage, &scan.scan_id, host) {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::debug!(%host, count, "imported nmap results");
+                    self.imported.insert(host.clone());
+                }
+                Err(e) => tracing::warn!(%host, error=%e, "unable to import nmap results"),
+            }
+        }
+        self
+    }
+
+    /// Returns true when the VT does not need to run on the host because its ports were
+    /// imported.
+    pub fn skips(&self, host: &Host, vt: &Nvt) -> bool {
+        self.imported.contains(host) && Self::skips_family(vt)
+    }
+
+    /// Returns true when the VT is a port scanner.
+    pub fn skips_family(vt: &Nvt) -> bool {
+        vt.family == PORT_SCANNERS_FAMILY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{DefaultDispatcher, Retrieve, Retriever};
+
+    use super::*;
+
+    const REPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" args="nmap -sV -oX - 192.168.0.0/30" version="7.94">
+<host starttime="1" endtime="2"><status state="up" reason="arp-response"/>
+<address addr="192.168.0.1" addrtype="ipv4"/>
+<address addr="00:11:22:33:44:55" addrtype="mac"/>
+<hostnames><hostname name="gateway.example" type="PTR"/></hostnames>
+<ports><extraports state="closed" count="996"/>
+<port protocol="tcp" portid="22"><state state="open" reason="syn-ack"/><service name="ssh" product="OpenSSH" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="443"><state state="open" reason="syn-ack"/><service name="http" tunnel="ssl" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="8080"><state state="filtered" reason="no-response"/><service name="http-proxy" method="table" conf="3"/></port>
+<port protocol="udp" portid="161"><state state="open" reason="udp-response"/><service name="snmp" method="probed" conf="10"/></port>
+</ports>
+</host>
+<taskbegin task="NSE" time="3"/>
+<host><status state="down" reason="no-response"/>
+<address addr="192.168.0.2" addrtype="ipv4"/>
+</host>
+</nmaprun>
+"#;
+
+    fn keys(items: &[Kb]) -> Vec<String> {
+        let mut keys: Vec<_> = items.iter().map(|x| x.key.clone()).collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn parses_open_ports_and_services() {
+        let import = NmapImport::parse(REPORT).unwrap();
+        let items = import.kb_items(&"192.168.0.1".to_string()).unwrap();
+        assert_eq!(
+            keys(items),
+            vec![
+                "Host/scanned",
+                "Host/scanners/nmap",
+                "Known/tcp/22",
+                "Known/tcp/443",
+                "Known/udp/161",
+                "Ports/tcp/22",
+                "Ports/tcp/443",
+                "Ports/udp/161",
+                "Services/ssh",
+                "Services/udp/snmp",
+                "Services/www",
+                "Transports/TCP/443",
+            ]
+        );
+        assert_eq!(
+            keys(import.kb_items(&"Gateway.example".to_string()).unwrap()),
+            keys(items)
+        );
+        assert!(import.kb_items(&"192.168.0.2".to_string()).is_none());
+        assert!(NmapImport::parse("<nmaprun><host>").is_ok());
+        assert!(NmapImport::parse("<nmaprun><host></nmaprun>").is_err());
+    }
+
+    #[test]
+    fn imports_into_scan() {
+        let storage = DefaultDispatcher::new();
+        let host = "192.168.0.1".to_string();
+        let scan = Scan {
+            scan_id: "current".into(),
+            target: crate::models::Target {
+                hosts: vec![host.clone(), "192.168.0.3".into()],
+                ..Default::default()
+            },
+            scan_preferences: vec![ScanPreference {
+                id: NMAP_IMPORT.to_string(),
+                value: REPORT.to_string(),
+            }],
+            ..Default::default()
+        };
+        let import = NmapImport::from_preferences(&scan.scan_preferences)
+            .unwrap()
+            .apply(&storage, &scan);
+        let key = ContextKey::Scan("current".into(), Some(host.clone()));
+        let found = |key_name: &str| {
+            storage
+                .retrieve(&key, Retrieve::KB(key_name.into()))
+                .unwrap()
+                .filter_map(|x| match x {
+                    Field::KB(kb) => Some(kb.value.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(found("Services/www"), vec!["443"]);
+        assert_eq!(found("Ports/tcp/8080"), Vec::<String>::new());
+        let vt = Nvt {
+            family: PORT_SCANNERS_FAMILY.into(),
+            ..Default::default()
+        };
+        assert!(import.skips(&host, &vt));
+        assert!(!import.skips(&"192.168.0.3".to_string(), &vt));
+        // reports on the scanner host are not read
+        let path = std::env::temp_dir().join("nmap_import_report.xml");
+        std::fs::write(&path, REPORT).unwrap();
+        assert!(NmapImport::from_preferences(&[ScanPreference {
+            id: NMAP_IMPORT.to_string(),
+            value: path.to_string_lossy().to_string(),
+        }])
+        .is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
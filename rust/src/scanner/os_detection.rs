@@ -0,0 +1,172 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Determines the best OS guess of a host from the OS evidence of the VTs.
+//!
+//! VTs register a detected OS via `register_host_detail` of `host_details.inc`, which stores
+//! - `HostDetails/NVT/<oid>` the description of the detection method and
+//! - `HostDetails/NVT/<oid>/OS` the CPE and the name of the OS.
+//!
+//! After a host was scanned the evidence is combined by [OperatingSystem::determine] and stored
+//! as the host details `best_os_cpe` and `best_os_txt` with the confidence as QoD. Hosts the OS
+//! consolidation VT already ran for are left as they are.
+
+use std::collections::BTreeMap;
+
+use crate::models::{
+    self, Detail, OperatingSystem, OsEvidence, ResultType, Source, HOST_DETAIL_BEST_OS_CPE,
+    HOST_DETAIL_BEST_OS_TXT, OS_DETECTION_DESCRIPTION, OS_DETECTION_OID,
+};
+use crate::storage::{ContextKey, Field, Retrieve, Storage, StorageError};
+
+const PREFIX: &str = "HostDetails/NVT/";
+
+fn is_os_cpe(value: &str) -> bool {
+    value.starts_with("cpe:/o:") || value.starts_with("cpe:2.3:o:")
+}
+
+/// Returns the OS evidence of the KB of a host, none when the OS was already consolidated.
+pub fn evidence<S: Storage>(
+    storage: &S,
+    key: &ContextKey,
+) -> Result<Option<Vec<OsEvidence>>, StorageError> {
+    // methods and reported OS values by OID
+    let mut reported: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for field in storage.retrieve(key, Retrieve::KB(format!("{PREFIX}*")))? {
+        let Field::KB(kb) = field else {
+            continue;
+        };
+        let Some(rest) = kb.key.strip_prefix(PREFIX) else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((oid, _)) if oid == OS_DETECTION_OID => return Ok(None),
+            Some((oid, "OS")) => reported
+                .entry(oid.to_string())
+                .or_default()
+                .1
+                .push(kb.value.to_string()),
+            Some(_) => {}
+            None => reported.entry(rest.to_string()).or_default().0 = kb.value.to_string(),
+        }
+    }
+    let mut evidence = vec![];
+    for (oid, (method, values)) in reported {
+        let (cpes, names): (Vec<_>, Vec<_>) = values.into_iter().partition(|x| is_os_cpe(x));
+        let name = names.first().cloned();
+        if cpes.is_empty() {
+            evidence.extend(names.into_iter().map(|name| OsEvidence {
+                oid: oid.clone(),
+                method: method.clone(),
+                cpe: None,
+                name: Some(name),
+            }));
+        } else {
+            evidence.extend(cpes.into_iter().map(|cpe| OsEvidence {
+                oid: oid.clone(),
+                method: method.clone(),
+                cpe: Some(cpe),
+                name: name.clone(),
+            }));
+        }
+    }
+    Ok(Some(evidence))
+}
+
+/// Returns the host detail results of the OS of a host.
+pub fn host_details(host: &str, os: &OperatingSystem) -> Vec<models::Result> {
+    [
+        (HOST_DETAIL_BEST_OS_CPE, &os.cpe),
+        (HOST_DETAIL_BEST_OS_TXT, &os.name),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        Some(models::Result {
+            r_type: ResultType::HostDetail,
+            ip_address: Some(host.to_string()),
+            oid: Some(OS_DETECTION_OID.to_string()),
+            qod: Some(os.confidence),
+            detail: Some(Detail {
+                name: name.to_string(),
+                value: value.clone()?,
+                source: Source {
+                    s_type: "nvt".to_string(),
+                    name: OS_DETECTION_OID.to_string(),
+                    description: OS_DETECTION_DESCRIPTION.to_string(),
+                },
+            }),
+            ..Default::default()
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{DefaultDispatcher, Dispatcher, Kb};
+
+    use super::*;
+
+    fn dispatch(storage: &DefaultDispatcher, key: &ContextKey, kbs: &[(&str, &str)]) {
+        for (k, v) in kbs {
+            storage
+                .dispatch(key, Field::KB(Kb::from((*k, *v))))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn evidence_of_kb() {
+        let storage = DefaultDispatcher::new();
+        let key = ContextKey::Scan("1".into(), Some("192.168.0.1".into()));
+        dispatch(
+            &storage,
+            &key,
+            &[
+                ("HostDetails/NVT/1.2.1", "OS Detection (SSH Login)"),
+                ("HostDetails/NVT/1.2.1/OS", "cpe:/o:debian:debian_linux:12"),
+                ("HostDetails/NVT/1.2.1/OS", "Debian GNU/Linux 12"),
+                ("HostDetails/NVT/1.2.2", "ICMP based OS Fingerprinting"),
+                ("HostDetails/NVT/1.2.2/OS", "Microsoft Windows"),
+                ("HostDetails/NVT/1.2.3/port", "22"),
+            ],
+        );
+        let found = evidence(&storage, &key).unwrap().unwrap();
+        assert_eq!(
+            found,
+            vec![
+                OsEvidence {
+                    oid: "1.2.1".into(),
+                    method: "OS Detection (SSH Login)".into(),
+                    cpe: Some("cpe:/o:debian:debian_linux:12".into()),
+                    name: Some("Debian GNU/Linux 12".into()),
+                },
+                OsEvidence {
+                    oid: "1.2.2".into(),
+                    method: "ICMP based OS Fingerprinting".into(),
+                    cpe: None,
+                    name: Some("Microsoft Windows".into()),
+                },
+            ]
+        );
+        let os = OperatingSystem::determine(&found).unwrap();
+        let details = host_details("192.168.0.1", &os);
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].qod, Some(76));
+        assert_eq!(
+            details[0].detail.as_ref().unwrap().value,
+            "cpe:/o:debian:debian_linux:12"
+        );
+
+        dispatch(
+            &storage,
+            &key,
+            &[(
+                "HostDetails/NVT/1.3.6.1.4.1.25623.1.0.105937/best_os_cpe",
+                "cpe:/o:debian:debian_linux:12",
+            )],
+        );
+        assert_eq!(evidence(&storage, &key).unwrap(), None);
+    }
+}
@@ -0,0 +1,244 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Resolves what a scan would execute without running any VT.
+//!
+//! The schedule is resolved the same way as for a real scan and each VT is checked against the
+//! knowledge base that already exists for the host, including the items a seeded scan would
+//! import and the ports an nmap report would import. As no script is executed the keys set by earlier VTs are unknown, a VT missing a
+//! required key, mandatory key or port is therefore reported as conditional. A VT is skipped when
+//! an excluded key is already set or when its family is skipped on a seeded or imported host.
+
+use crate::models::{HostPlan, PlannedStatus, PlannedVT, Scan, ScanPlan};
+use crate::scheduling::VTError;
+use crate::storage::{types::Primitive, ContextKey, Field, Retrieve, Storage, StorageError};
+
+use super::error::ScriptResultKind;
+use super::kb_seed::KbSeed;
+use super::nmap_import::NmapImport;
+use super::scanner_stack::Schedule;
+use super::vt_runner::{check_keys, PluginTimeouts};
+
+fn reason(kind: &ScriptResultKind) -> String {
+    match kind {
+        ScriptResultKind::MissingRequiredKey(k) => format!("missing required key {k}"),
+        ScriptResultKind::MissingMandatoryKey(k) => format!("missing mandatory key {k}"),
+        ScriptResultKind::ContainsExcludedKey(k) => format!("contains excluded key {k}"),
+        ScriptResultKind::MissingPort(protocol, port) => format!("missing {protocol} port {port}"),
+        x => format!("{x:?}"),
+    }
+}
+
+/// Resolves the VTs that would be executed on each host of the scan.
+pub fn plan_scan<S, Sched>(storage: &S, schedule: Sched, scan: &Scan) -> Result<ScanPlan, VTError>
+where
+    S: Storage,
+    Sched: Schedule,
+{
+    let concurrent_vts = schedule.cache()?;
+    let seed = KbSeed::from_preferences(&scan.scan_preferences);
+    let nmap = NmapImport::from_preferences(&scan.scan_preferences);
+    let timeouts = PluginTimeouts::from_preferences(&scan.scan_preferences);
+    let hosts = scan
+        .target
+        .all_hosts()
+        .map(|host| {
+            let key = ContextKey::Scan(scan.scan_id.clone(), Some(host.clone()));
+            let imported = seed
+                .as_ref()
+                .map(|x| x.preview(storage, host))
+                .unwrap_or_default();
+            let ports = nmap.as_ref().and_then(|x| x.kb_items(host));
+            let lookup = |kb_key: &str| -> Result<Option<Primitive>, StorageError> {
+                let found = storage
+                    .retrieve(&key, Retrieve::KB(kb_key.to_string()))?
+                    .find_map(|x| match x {
+                        Field::KB(kb) => Some(kb.value),
+                        _ => None,
+                    });
+                Ok(found.or_else(|| {
+                    imported
+                        .iter()
+                        .chain(ports.unwrap_or_default())
+                        .find(|x| x.key == kb_key)
+                        .map(|x| x.value.clone())
+                }))
+            };
+            let vts = concurrent_vts
+                .iter()
+                .flat_map(|(stage, vts)| vts.iter().map(move |(vt, _)| (stage, vt)))
+                .map(|(stage, vt)| {
+                    let seeded =
+                        !imported.is_empty() && seed.as_ref().is_some_and(|x| x.skips_family(vt));
+                    let (status, reason) = if seeded {
+                        (PlannedStatus::Skipped, Some("seeded".to_string()))
+                    } else if ports.is_some() && NmapImport::skips_family(vt) {
+                        (
+                            PlannedStatus::Skipped,
+                            Some("imported from nmap".to_string()),
+                        )
+                    } else {
+                        match check_keys(vt, lookup) {
+                            Ok(()) => (PlannedStatus::Run, None),
+                            Err(x @ ScriptResultKind::ContainsExcludedKey(_)) => {
+                                (PlannedStatus::Skipped, Some(reason(&x)))
+                            }
+                            Err(x) => (PlannedStatus::Conditional, Some(reason(&x))),
+                        }
+                    };
+                    PlannedVT {
+                        oid: vt.oid.clone(),
+                        name: vt.name.clone(),
+                        stage: stage.to_string(),
+                        status,
+                        reason,
+                        timeout: timeouts.of(vt),
+                    }
+                })
+                .collect();
+            HostPlan {
+                host: host.clone(),
+                vts,
+            }
+        })
+        .collect();
+    Ok(ScanPlan::new(scan.scan_id.clone(), hosts))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Protocol;
+    use crate::models::ScanPreference;
+    use crate::scanner::scan_runner::tests::{setup, setup_success, GenerateScript};
+    use crate::scanner::vt_runner::{
+        DEFAULT_SCANNER_TIMEOUT, DEFAULT_TIMEOUT, PLUGINS_TIMEOUT, SCANNER_PLUGINS_TIMEOUT,
+    };
+    use crate::scheduling::{ExecutionPlaner, WaveExecutionPlan};
+    use crate::storage::item::{Nvt, NvtPreference, PreferenceType, ACT};
+    use crate::storage::Dispatcher;
+
+    use super::*;
+
+    #[test]
+    fn resolves_plan_from_existing_kb() {
+        let scripts = [
+            GenerateScript::with_dependencies("0", &[]).generate(),
+            GenerateScript::with_required_keys("1", &["key/a"]).generate(),
+            GenerateScript::with_excluded_keys("2", &["key/b"]).generate(),
+            GenerateScript::with_required_ports("3", &[(Protocol::TCP, "80")]).generate(),
+        ];
+        let ((storage, _, _), scan) = setup(&scripts);
+        let key = ContextKey::Scan(scan.scan_id.clone(), Some(scan.target.hosts[0].clone()));
+        storage
+            .dispatch(&key, Field::KB(("key/b", true).into()))
+            .unwrap();
+        let plan = || {
+            let schedule = storage.execution_plan::<WaveExecutionPlan>(&scan).unwrap();
+            plan_scan(&storage, schedule, &scan).unwrap()
+        };
+        let result = plan();
+        let status = |plan: &ScanPlan, oid: &str| {
+            let vt = plan.hosts[0].vts.iter().find(|x| x.oid == oid).unwrap();
+            (vt.status, vt.reason.clone())
+        };
+        assert_eq!(status(&result, "0"), (PlannedStatus::Run, None));
+        assert_eq!(
+            status(&result, "1"),
+            (
+                PlannedStatus::Conditional,
+                Some("missing required key key/a".into())
+            )
+        );
+        assert_eq!(
+            status(&result, "2"),
+            (
+                PlannedStatus::Skipped,
+                Some("contains excluded key key/b".into())
+            )
+        );
+        assert_eq!(
+            status(&result, "3"),
+            (
+                PlannedStatus::Conditional,
+                Some("missing tcp port 80".into())
+            )
+        );
+        assert_eq!((result.run, result.conditional, result.skipped), (1, 2, 1));
+        assert_eq!(result.max_duration, 3 * DEFAULT_TIMEOUT);
+
+        storage
+            .dispatch(&key, Field::KB(("key/a", 1).into()))
+            .unwrap();
+        assert_eq!(status(&plan(), "1"), (PlannedStatus::Run, None));
+    }
+
+    #[test]
+    fn imported_ports_are_known() {
+        let scripts = [
+            GenerateScript::with_required_ports("0", &[(Protocol::TCP, "80")]).generate(),
+            GenerateScript::with_required_ports("1", &[(Protocol::TCP, "22")]).generate(),
+        ];
+        let ((storage, _, _), mut scan) = setup(&scripts);
+        scan.scan_preferences.push(ScanPreference {
+            id: "nmap_import".to_string(),
+            value: r#"<nmaprun><host><status state="up"/>
+                <hostnames><hostname name="test.host"/></hostnames>
+                <ports><port protocol="tcp" portid="80"><state state="open"/></port></ports>
+                </host></nmaprun>"#
+                .to_string(),
+        });
+        let schedule = storage.execution_plan::<WaveExecutionPlan>(&scan).unwrap();
+        let result = plan_scan(&storage, schedule, &scan).unwrap();
+        let status = |oid: &str| {
+            let vt = result.hosts[0].vts.iter().find(|x| x.oid == oid).unwrap();
+            vt.status
+        };
+        assert_eq!(status("0"), PlannedStatus::Run);
+        assert_eq!(status("1"), PlannedStatus::Conditional);
+    }
+
+    #[test]
+    fn plugins_timeout_applies_without_script_timeout() {
+        let ((storage, _, _), mut scan) = setup_success();
+        scan.scan_preferences.push(ScanPreference {
+            id: PLUGINS_TIMEOUT.to_string(),
+            value: "10".to_string(),
+        });
+        let schedule = storage.execution_plan::<WaveExecutionPlan>(&scan).unwrap();
+        let result = plan_scan(&storage, schedule, &scan).unwrap();
+        assert!(result.hosts[0].vts.iter().all(|x| x.timeout == 10));
+        assert_eq!(result.max_duration, 30);
+    }
+
+    #[test]
+    fn timeouts_by_category() {
+        let scanner = Nvt {
+            category: ACT::Scanner,
+            ..Default::default()
+        };
+        let mut vt = Nvt::default();
+        let timeouts = PluginTimeouts::default();
+        assert_eq!(timeouts.of(&scanner), DEFAULT_SCANNER_TIMEOUT);
+        assert_eq!(timeouts.of(&vt), DEFAULT_TIMEOUT);
+
+        let preference = |id: &str, value: &str| ScanPreference {
+            id: id.to_string(),
+            value: value.to_string(),
+        };
+        let timeouts = PluginTimeouts::from_preferences(&[
+            preference(PLUGINS_TIMEOUT, "20"),
+            preference(SCANNER_PLUGINS_TIMEOUT, "600"),
+        ]);
+        assert_eq!(timeouts.of(&scanner), 600);
+        assert_eq!(timeouts.of(&vt), 20);
+        // the script_timeout of the VT takes precedence
+        vt.preferences.push(NvtPreference {
+            id: Some(0),
+            class: PreferenceType::Entry,
+            name: "timeout".to_string(),
+            default: "42".to_string(),
+        });
+        assert_eq!(timeouts.of(&vt), 42);
+    }
+}
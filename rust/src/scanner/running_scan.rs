@@ -1,21 +1,34 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex, PoisonError,
     },
     time::SystemTime,
 };
 
-use crate::models::{scanner::Error, HostInfo, Phase, Scan, Status};
+use crate::feed::IncludeGraph;
+use crate::models::{
+    self, scanner::Error, Heartbeat, Host, HostInfo, OperatingSystem, Phase, ResultType, Scan,
+    Status,
+};
+use crate::nasl::syntax::CompileCache;
 use crate::nasl::utils::Executor;
+use crate::notus::PackageScanner;
+use crate::storage::{ContextKey, Field, Storage as _};
 use crate::{
     scanner::scan_runner::ScanRunner,
     scheduling::{ExecutionPlan, ExecutionPlaner, VTError},
 };
 use futures::StreamExt;
 use tokio::{sync::RwLock, task::JoinHandle};
-use tracing::{debug, trace, warn};
+use tracing::{debug, trace, warn, Instrument as _};
 
+use super::error::ScriptResultKind;
+use super::os_detection;
 use super::ScannerStack;
 
 /// Takes care of running a single scan to completion.
@@ -26,8 +39,13 @@ pub struct RunningScan<S: ScannerStack> {
     storage: Arc<S::Storage>,
     loader: Arc<S::Loader>,
     function_executor: Arc<Executor>,
+    package_scanner: Option<Arc<dyn PackageScanner>>,
+    compile_cache: Option<Arc<CompileCache>>,
+    include_graph: Option<PathBuf>,
+    pcap_directory: Option<PathBuf>,
     keep_running: Arc<AtomicBool>,
     status: Arc<RwLock<Status>>,
+    heartbeat: Arc<Mutex<Heartbeat>>,
 }
 
 fn current_time_in_seconds(name: &'static str) -> u64 {
@@ -40,20 +58,38 @@ fn current_time_in_seconds(name: &'static str) -> u64 {
     }
 }
 
+/// Reads the include graph, the includes are not parsed in advance when it cannot be read.
+fn read_include_graph(path: &Path) -> Option<IncludeGraph> {
+    match File::open(path).and_then(|x| IncludeGraph::read(BufReader::new(x))) {
+        Ok(x) => Some(x),
+        Err(error) => {
+            warn!(path=%path.display(), %error, "unable to read include graph");
+            None
+        }
+    }
+}
+
 impl<S: ScannerStack> RunningScan<S> {
+    #[allow(clippy::too_many_arguments)]
     pub fn start<Sch: ExecutionPlan + 'static>(
         scan: Scan,
         storage: Arc<S::Storage>,
         loader: Arc<S::Loader>,
         function_executor: Arc<Executor>,
+        package_scanner: Option<Arc<dyn PackageScanner>>,
+        compile_cache: Option<Arc<CompileCache>>,
+        include_graph: Option<PathBuf>,
+        pcap_directory: Option<PathBuf>,
     ) -> RunningScanHandle
     where
         S: 'static,
     {
+        let span = tracing::info_span!("scan", scan_id = %scan.scan_id);
         let keep_running: Arc<AtomicBool> = Arc::new(true.into());
         let status = Arc::new(RwLock::new(Status {
             ..Default::default()
         }));
+        let heartbeat = Arc::new(Mutex::new(Heartbeat::default()));
         RunningScanHandle {
             handle: tokio::spawn(
                 Self {
@@ -61,14 +97,21 @@ impl<S: ScannerStack> RunningScan<S> {
                     storage,
                     loader,
                     function_executor,
+                    package_scanner,
+                    compile_cache,
+                    include_graph,
+                    pcap_directory,
                     keep_running: keep_running.clone(),
                     status: status.clone(),
+                    heartbeat: heartbeat.clone(),
                 }
                 // TODO run per target
-                .run::<Sch>(),
+                .run::<Sch>()
+                .instrument(span),
             ),
             keep_running,
             status,
+            heartbeat,
         }
     }
 
@@ -106,11 +149,23 @@ impl<S: ScannerStack> RunningScan<S> {
             schedule,
             &self.scan,
         )
+        .map(|x| {
+            x.with_package_scanner(self.package_scanner.clone())
+                .with_compile_cache(self.compile_cache.clone())
+                .with_include_graph(self.include_graph.as_deref().and_then(read_include_graph))
+                .with_pcap_directory(self.pcap_directory.clone())
+                .with_heartbeat(self.heartbeat.clone())
+        })
         .map_err(make_scheduling_error)
     }
 
     async fn run_to_completion<'a>(&self, runner: ScanRunner<'a, S>) -> Phase {
         let mut end_phase = Phase::Succeeded;
+        // OIDs of the scripts cut off by the time budget per host
+        let mut truncated: BTreeMap<Host, Truncated> = BTreeMap::new();
+        // reason and OIDs of the scripts not run per host scanning was stopped for
+        let mut blocked: BTreeMap<Host, (String, Vec<String>)> = BTreeMap::new();
+        let mut scanned: BTreeSet<Host> = BTreeSet::new();
         let mut stream = Box::pin(runner.stream());
         while let Some(it) = stream.next().await {
             match it {
@@ -121,8 +176,22 @@ impl<S: ScannerStack> RunningScan<S> {
                         host_info.register_finished_script(&result.target);
                     }
                     debug!(result=?result, "script finished");
+                    self.beat();
+                    scanned.insert(result.target.clone());
 
-                    if result.has_failed() {
+                    if let ScriptResultKind::Blocked(reason) = result.kind {
+                        blocked
+                            .entry(result.target)
+                            .or_insert_with(|| (reason, vec![]))
+                            .1
+                            .push(result.oid);
+                    } else if result.is_truncated() {
+                        let host = truncated.entry(result.target.clone()).or_default();
+                        match result.kind {
+                            ScriptResultKind::Cancelled => host.cancelled.push(result.oid),
+                            _ => host.skipped.push(result.oid),
+                        }
+                    } else if result.has_failed() {
                         end_phase = Phase::Failed;
                     }
                 }
@@ -136,14 +205,125 @@ impl<S: ScannerStack> RunningScan<S> {
                 break;
             }
         }
+        self.report_os(scanned);
+        if !truncated.is_empty() {
+            warn!(
+                hosts = truncated.len(),
+                "time budget exhausted, scan is partially completed"
+            );
+            self.report_truncated(truncated);
+            if end_phase == Phase::Succeeded {
+                end_phase = Phase::Partial;
+            }
+        }
+        if !blocked.is_empty() {
+            warn!(
+                hosts = blocked.len(),
+                "scanning of hosts was stopped, scan is partially completed"
+            );
+            self.report_blocked(blocked);
+            if end_phase == Phase::Succeeded {
+                end_phase = Phase::Partial;
+            }
+        }
         end_phase
     }
 
+    /// Marks progress of the scan.
+    fn beat(&self) {
+        let now = current_time_in_seconds("heartbeat");
+        let mut heartbeat = self
+            .heartbeat
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        heartbeat.time = heartbeat.time.max(now);
+    }
+
+    /// Stores the best OS guess of each host as host details
+    fn report_os(&self, hosts: BTreeSet<Host>) {
+        for host in hosts {
+            let key = ContextKey::Scan(self.scan.scan_id.clone(), Some(host.clone()));
+            let evidence = match os_detection::evidence(&*self.storage, &key) {
+                Ok(Some(evidence)) => evidence,
+                Ok(None) => continue,
+                Err(error) => {
+                    warn!(%error, host, "unable to get the OS evidence of the host");
+                    continue;
+                }
+            };
+            let Some(os) = OperatingSystem::determine(&evidence) else {
+                continue;
+            };
+            debug!(host, ?os, "determined OS");
+            for result in os_detection::host_details(&host, &os) {
+                self.dispatch_host_result(host.clone(), result);
+            }
+        }
+    }
+
+    /// Stores an error result per host scanning was stopped for containing the reason and the
+    /// scripts that did not run
+    fn report_blocked(&self, blocked: BTreeMap<Host, (String, Vec<String>)>) {
+        for (host, (reason, oids)) in blocked {
+            let message = format!(
+                "Scanning of the host was stopped: {reason}.\nSkipped {} VTs: {}",
+                oids.len(),
+                oids.join(", ")
+            );
+            self.store_host_result(host, ResultType::Error, message);
+        }
+    }
+
+    /// Stores a log result per host listing the scripts cut off by the time budget
+    fn report_truncated(&self, truncated: BTreeMap<Host, Truncated>) {
+        for (host, truncated) in truncated {
+            let mut message = "Time budget of the scan exhausted.".to_string();
+            for (what, oids) in [
+                ("Skipped", &truncated.skipped),
+                ("Cancelled", &truncated.cancelled),
+            ] {
+                if !oids.is_empty() {
+                    message.push_str(&format!("\n{what} {} VTs: {}", oids.len(), oids.join(", ")));
+                }
+            }
+            self.store_host_result(host, ResultType::Log, message);
+        }
+    }
+
+    fn store_host_result(&self, host: Host, r_type: ResultType, message: String) {
+        let result = models::Result {
+            r_type,
+            ip_address: Some(host.clone()),
+            message: Some(message),
+            ..Default::default()
+        };
+        self.dispatch_host_result(host, result);
+    }
+
+    fn dispatch_host_result(&self, host: Host, mut result: models::Result) {
+        result.labels = self
+            .scan
+            .target
+            .labels_of(&host)
+            .cloned()
+            .unwrap_or_default();
+        let key = ContextKey::Scan(self.scan.scan_id.clone(), Some(host));
+        if let Err(error) =
+            self.storage
+                .as_dispatcher()
+                .retry_dispatch(5, &key, Field::Result(result.into()))
+        {
+            warn!(%error, "unable to store the result of the host");
+        }
+    }
+
     async fn update_status_at_beginning_of_run(&self, host_info: HostInfo) {
         let mut status = self.status.write().await;
         status.status = Phase::Running;
         status.start_time = current_time_in_seconds("start_time").into();
         status.host_info = Some(host_info);
+        drop(status);
+        self.beat();
     }
 
     async fn update_status_at_end_of_run(&self, end_phase: Phase) {
@@ -157,12 +337,20 @@ impl<S: ScannerStack> RunningScan<S> {
     }
 }
 
+/// Scripts of a host cut off by the time budget of the scan
+#[derive(Debug, Default)]
+struct Truncated {
+    skipped: Vec<String>,
+    cancelled: Vec<String>,
+}
+
 /// A handle to a `RunningScan`. Can be used to obtain the status of
 /// the scan and to stop it.
 pub struct RunningScanHandle {
     handle: JoinHandle<Result<(), Error>>,
     keep_running: Arc<AtomicBool>,
     status: Arc<RwLock<Status>>,
+    heartbeat: Arc<Mutex<Heartbeat>>,
 }
 
 impl RunningScanHandle {
@@ -172,7 +360,16 @@ impl RunningScanHandle {
     }
 
     pub async fn status(&self) -> Status {
-        self.status.read().await.clone()
+        let mut status = self.status.read().await.clone();
+        let heartbeat = self
+            .heartbeat
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if heartbeat.time > 0 {
+            status.heartbeat = Some(heartbeat);
+        }
+        status
     }
 }
 
@@ -263,6 +460,53 @@ mod tests {
         assert_eq!(host_info.queued(), 0);
     }
 
+    #[tokio::test]
+    async fn scan_budget() {
+        use crate::models::ScanPreference;
+        use crate::scanner::budget::{SCAN_BUDGET, SCAN_BUDGET_GRACE};
+        use crate::storage::{ContextKey, Field, Retrieve, Retriever as _};
+
+        let ((storage, _, executor), mut scan) = setup_success();
+        for (id, value) in [(SCAN_BUDGET, "1"), (SCAN_BUDGET_GRACE, "0")] {
+            scan.scan_preferences.push(ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            });
+        }
+        let loader: fn(&str) -> String = |_| "i = 0; while (1) { i++; }".to_string();
+        let scanner: Scanner<TestStack> = Scanner::new(storage, loader, executor);
+        let id = scan.scan_id.clone();
+        scanner.start_scan(scan).await.unwrap();
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let results = scanner.fetch_results(id.clone()).await.unwrap();
+                if results.status.is_done() {
+                    return results.status;
+                }
+            }
+        })
+        .await
+        .expect("scan to finish");
+        assert_eq!(status.status, Phase::Partial);
+        let messages: Vec<String> = scanner
+            .storage
+            .retrieve(
+                &ContextKey::Scan(id, Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .unwrap()
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .filter(|x| x.starts_with("Time budget of the scan exhausted."))
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Skipped 2 VTs: 1, 2"));
+        assert!(messages[0].contains("Cancelled 1 VTs: 0"));
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn start_scan_success() {
@@ -286,6 +530,12 @@ mod tests {
             scan_results.status.host_info.is_some(),
             "host_info should be set"
         );
+        let heartbeat = scan_results
+            .status
+            .heartbeat
+            .expect("heartbeat of the scan");
+        assert!(heartbeat.last_script.is_some());
+        assert!(heartbeat.last_script_started.is_some());
         let host_info = scan_results.status.host_info.unwrap();
         assert_eq!(host_info.finished(), 2);
         assert_eq!(host_info.queued(), 0);
@@ -2,35 +2,93 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use crate::models::{Host, HostInfo, Scan};
-use crate::nasl::utils::Executor;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use crate::feed::IncludeGraph;
+use crate::models::{bool_preference, Heartbeat, Host, HostInfo, Parameter, Scan};
+use crate::nasl::interpreter::preload;
+use crate::nasl::syntax::{CompileCache, ParsedIncludes};
+use crate::nasl::utils::{
+    context::include_once_from_preferences, dns::HOST_NAME_LOOKUP, BuiltinPolicy,
+    ConnectionLimiter, DnsCache, Executor, Extensions, HostNames, KbCache, NetworkSource,
+    PacketRecorder, PluginConfig, RegexMode, TargetQueue, TrafficCounter,
+};
+use crate::nasl::utils::{Context, RegisterBase};
+use crate::notus::PackageScanner;
+use crate::storage::item::Nvt;
+use crate::storage::{ContextKey, Dispatcher, Field, Storage};
 use futures::{stream, Stream};
 
 use crate::scanner::ScannerStack;
-use crate::scheduling::{ConcurrentVT, VTError};
+use crate::scheduling::{ConcurrentVT, Stage, VTError};
 
-use super::error::{ExecuteError, ScriptResult};
+use super::budget::ScanBudget;
+use super::error::{ExecuteError, ScriptResult, ScriptResultKind};
+use super::kb_seed::KbSeed;
+use super::nmap_import::NmapImport;
 use super::scanner_stack::Schedule;
-use super::vt_runner::VTRunner;
+use super::vt_runner::{PluginTimeouts, VTRunner, REPORT_SCRIPT_TIMING};
+
+/// KB key containing the host whose script added the host to the scan
+pub const HOST_ADDED_BY: &str = "Host/added_by";
+
+/// Scan preference id listing the include files that are preloaded once per scan, separated by
+/// commas
+pub const PRELOAD_INCLUDES: &str = "preload_includes";
 
-#[derive(Default, Debug, Clone, Copy)]
-struct Position {
-    host: usize,
-    stage: usize,
-    vt: usize,
+fn preload_includes(scan: &Scan) -> Vec<String> {
+    scan.scan_preferences
+        .iter()
+        .find(|x| x.id == PRELOAD_INCLUDES)
+        .map(|x| {
+            x.value
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
-/// Provides an iterator over all hosts, stages and vts within the stage
-fn all_positions(hosts: Vec<Host>, vts: Vec<ConcurrentVT>) -> impl Iterator<Item = Position> {
-    hosts.into_iter().enumerate().flat_map(move |(host, _)| {
-        let vts = vts.clone();
+/// Interprets the include files preloaded for the scan, none when there are none.
+///
+/// When preloading fails the scripts include the files themselves.
+async fn preload_base(files: &[String], context: Context<'_>) -> Option<Arc<RegisterBase>> {
+    if files.is_empty() {
+        return None;
+    }
+    match preload(files, &context).await {
+        Ok(base) => {
+            tracing::debug!(files = ?base.included(), definitions = base.len(), "preloaded includes");
+            Some(Arc::new(base))
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "unable to preload includes, scripts include them themselves");
+            None
+        }
+    }
+}
+
+/// Stores the KB items collected while discovering a host and caches them for the other stages.
+fn prewarm_kb<S: Storage>(storage: &S, key: &ContextKey, cache: &KbCache) {
+    match cache.prewarm(storage.as_dispatcher(), storage.as_retriever(), key) {
+        Ok(count) => tracing::trace!(%key, count, "prewarmed kb items"),
+        Err(e) => tracing::warn!(%key, error = %e, "unable to prewarm kb items"),
+    }
+}
+
+type Job = (Stage, Nvt, Option<Vec<Parameter>>, Host);
+
+/// Provides an iterator over all stages and vts within the stage of a single host
+fn host_jobs(host: Host, vts: Vec<ConcurrentVT>) -> impl Iterator<Item = Job> + Send {
+    vts.into_iter().flat_map(move |(stage, vts)| {
+        let host = host.clone();
         vts.into_iter()
-            .enumerate()
-            .flat_map(move |(stage, (_, vts))| {
-                vts.into_iter()
-                    .enumerate()
-                    .map(move |(vt, _)| Position { host, stage, vt })
-            })
+            .map(move |(vt, param)| (stage, vt, param, host.clone()))
     })
 }
 
@@ -44,6 +102,26 @@ pub struct ScanRunner<'a, S: ScannerStack> {
     loader: &'a S::Loader,
     executor: &'a Executor,
     concurrent_vts: Vec<ConcurrentVT>,
+    dns: Arc<DnsCache>,
+    limiter: Arc<ConnectionLimiter>,
+    source: NetworkSource,
+    recorder: Arc<PacketRecorder>,
+    regex: RegexMode,
+    include_once: bool,
+    compile_cache: Option<Arc<CompileCache>>,
+    include_graph: Option<IncludeGraph>,
+    preload: Arc<Vec<String>>,
+    report_timing: bool,
+    timeouts: PluginTimeouts,
+    max_script_bytes: Option<u64>,
+    budget: Option<ScanBudget>,
+    seed: Option<Arc<KbSeed>>,
+    nmap: Option<Arc<NmapImport>>,
+    targets: Arc<TargetQueue>,
+    extensions: Arc<Extensions>,
+    policy: Arc<BuiltinPolicy>,
+    host_name_lookup: bool,
+    heartbeat: Arc<Mutex<Heartbeat>>,
 }
 
 impl<'a, Stack: ScannerStack> ScanRunner<'a, Stack> {
@@ -58,56 +136,324 @@ impl<'a, Stack: ScannerStack> ScanRunner<'a, Stack> {
         Sched: Schedule + 'a,
     {
         let concurrent_vts = schedule.cache()?;
+        let dns = Arc::new(DnsCache::from_preferences(&scan.scan_preferences));
+        let limiter = Arc::new(ConnectionLimiter::from_preferences(&scan.scan_preferences));
+        let source = NetworkSource::from(scan);
+        // gives builtin functions access to the preferences of the scan
+        let extensions = Arc::new(Extensions::default());
+        extensions.insert(PluginConfig::from(scan));
+        let recorder = Arc::new(
+            PacketRecorder::from_preferences(&scan.scan_preferences)
+                .with_interface(source.interface.clone()),
+        );
         Ok(Self {
             scan,
             storage,
             loader,
             executor,
             concurrent_vts,
+            dns,
+            limiter,
+            source,
+            recorder,
+            regex: RegexMode::from(scan),
+            include_once: include_once_from_preferences(&scan.scan_preferences),
+            compile_cache: None,
+            include_graph: None,
+            preload: Arc::new(preload_includes(scan)),
+            report_timing: bool_preference(&scan.scan_preferences, REPORT_SCRIPT_TIMING)
+                .unwrap_or_default(),
+            timeouts: PluginTimeouts::from_preferences(&scan.scan_preferences),
+            max_script_bytes: TrafficCounter::cap_from_preferences(&scan.scan_preferences),
+            budget: ScanBudget::from_preferences(&scan.scan_preferences),
+            seed: KbSeed::from_preferences(&scan.scan_preferences)
+                .map(|x| Arc::new(x.apply(storage, scan))),
+            nmap: NmapImport::from_preferences(&scan.scan_preferences)
+                .map(|x| Arc::new(x.apply(storage, scan))),
+            targets: Arc::new(TargetQueue::from_scan(scan)),
+            extensions,
+            policy: Arc::new(BuiltinPolicy::from_preferences(&scan.scan_preferences)),
+            host_name_lookup: bool_preference(&scan.scan_preferences, HOST_NAME_LOOKUP)
+                .unwrap_or(true),
+            heartbeat: Arc::default(),
         })
     }
 
+    /// Lets the builtin functions of the scan check gathered packages with notus.
+    pub fn with_package_scanner(self, scanner: Option<Arc<dyn PackageScanner>>) -> Self {
+        if let Some(scanner) = scanner {
+            self.extensions.insert(scanner);
+        }
+        self
+    }
+
+    /// Records the traffic of the scripts into the given directory, nothing is recorded without
+    /// one.
+    pub fn with_pcap_directory(mut self, directory: Option<PathBuf>) -> Self {
+        let recorder = (*self.recorder).clone().with_directory(directory);
+        self.recorder = Arc::new(recorder);
+        self
+    }
+
+    /// Sets the cache the scripts and their includes are compiled with instead of parsing them on
+    /// each run.
+    pub fn with_compile_cache(mut self, cache: Option<Arc<CompileCache>>) -> Self {
+        self.compile_cache = cache;
+        self
+    }
+
+    /// Sets the include graph the includes of the scheduled VTs are looked up in. They are loaded
+    /// and parsed once when the scan starts instead of on each include.
+    pub fn with_include_graph(mut self, graph: Option<IncludeGraph>) -> Self {
+        self.include_graph = graph;
+        self
+    }
+
+    /// Returns the includes of the scheduled VTs parsed in advance, none without include graph.
+    fn parse_includes(&self) -> Option<Arc<ParsedIncludes>> {
+        let graph = self.include_graph.as_ref()?;
+        let scripts = self
+            .concurrent_vts
+            .iter()
+            .flat_map(|(_, vts)| vts.iter().map(|(vt, _)| vt.filename.as_str()));
+        let includes = ParsedIncludes::load(
+            self.loader,
+            graph.resolve_all(scripts),
+            self.compile_cache.as_deref(),
+        );
+        tracing::debug!(
+            files = includes.len(),
+            "parsed includes of the scheduled VTs"
+        );
+        Some(Arc::new(includes))
+    }
+
+    /// Sets the heartbeat the started scripts are marked in.
+    pub fn with_heartbeat(mut self, heartbeat: Arc<Mutex<Heartbeat>>) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Returns the queue of hosts that are added to the scan while it is running.
+    pub fn target_queue(&self) -> Arc<TargetQueue> {
+        self.targets.clone()
+    }
+
     pub fn host_info(&self) -> HostInfo {
-        HostInfo::from_hosts_and_num_vts(&self.scan.target.hosts, self.concurrent_vts.len())
+        HostInfo::from_target_and_num_vts(&self.scan.target, self.concurrent_vts.len())
     }
 
     pub fn stream(self) -> impl Stream<Item = Result<ScriptResult, ExecuteError>> + 'a {
-        let data = all_positions(self.scan.target.hosts.clone(), self.concurrent_vts.clone()).map(
-            move |pos| {
-                let (stage, vts) = &self.concurrent_vts[pos.stage];
-                let (vt, param) = &vts[pos.vt];
-                let host = &self.scan.target.hosts[pos.host];
-                (
-                    *stage,
-                    vt.clone(),
-                    param.clone(),
-                    host.clone(),
-                    self.scan.scan_id.clone(),
-                )
-            },
-        );
+        let dns = self.dns.clone();
+        let limiter = self.limiter.clone();
+        let source = self.source.clone();
+        let recorder = self.recorder.clone();
+        let regex = self.regex;
+        let include_once = self.include_once;
+        let compile_cache = self.compile_cache.clone();
+        let parsed_includes = self.parse_includes();
+        let preload_files = self.preload.clone();
+        let report_timing = self.report_timing;
+        let timeouts = self.timeouts;
+        let max_script_bytes = self.max_script_bytes;
+        let budget = self.budget;
+        let seed = self.seed.clone();
+        let nmap = self.nmap.clone();
+        let targets = self.targets.clone();
+        let extensions = self.extensions.clone();
+        let policy = self.policy.clone();
+        let host_name_lookup = self.host_name_lookup;
+        let heartbeat = self.heartbeat.clone();
+        let data: Box<dyn Iterator<Item = Job> + Send + 'a> = Box::new(std::iter::empty());
+        let hosts: Vec<Host> = self.scan.target.all_hosts().cloned().collect();
+        let hosts = hosts.into_iter();
         // The usage of unfold here will prevent any real asynchronous running of VTs
         // and automatically guarantee that we stick to the scheduling requirements.
         // If this is changed, make sure to uphold the scheduling requirements in the
         // new implementation.
-        stream::unfold(data, move |mut data| async move {
-            if let Some((stage, vt, param, host, scan_id)) = data.next() {
-                let result = VTRunner::<Stack>::run(
-                    self.storage,
-                    self.loader,
-                    self.executor,
-                    &host,
-                    &vt,
-                    stage,
-                    param.as_ref(),
-                    &scan_id,
-                )
-                .await;
-                Some((result, data))
-            } else {
-                None
-            }
-        })
+        stream::unfold(
+            (data, hosts, false, None, None::<(ContextKey, Arc<KbCache>)>),
+            move |(mut data, mut hosts, initialized, mut base, mut kb)| {
+                let dns = dns.clone();
+                let limiter = limiter.clone();
+                let source = source.clone();
+                let recorder = recorder.clone();
+                let seed = seed.clone();
+                let nmap = nmap.clone();
+                let targets = targets.clone();
+                let extensions = extensions.clone();
+                let policy = policy.clone();
+                let preload_files = preload_files.clone();
+                let compile_cache = compile_cache.clone();
+                let parsed_includes = parsed_includes.clone();
+                let heartbeat = heartbeat.clone();
+                let concurrent_vts = self.concurrent_vts.clone();
+                async move {
+                    if !initialized {
+                        if let Err(e) = self
+                            .executor
+                            .init_scan(&PluginConfig::from(self.scan))
+                            .await
+                        {
+                            return Some((
+                                Err(ExecuteError::Plugin(e)),
+                                (data, hosts, true, base, kb),
+                            ));
+                        }
+                        let context = Context::new(
+                            ContextKey::Scan(self.scan.scan_id.clone(), None),
+                            String::new(),
+                            self.storage.as_dispatcher(),
+                            self.storage.as_retriever(),
+                            self.loader,
+                            self.executor,
+                        )
+                        .with_dns_cache(dns.clone())
+                        .with_connection_limiter(limiter.clone())
+                        .with_network_source(source.clone())
+                        .with_regex_mode(regex)
+                        .with_include_once(include_once)
+                        .with_compile_cache(compile_cache.clone())
+                        .with_parsed_includes(parsed_includes.clone())
+                        .with_target_queue(targets.clone())
+                        .with_extensions(extensions.clone())
+                        .with_builtin_policy(policy.clone());
+                        base = preload_base(&preload_files, context).await;
+                    }
+                    let mut job = data.next();
+                    while job.is_none() {
+                        if let Some((key, cache)) = kb.take() {
+                            prewarm_kb(self.storage, &key, &cache);
+                        }
+                        // hosts added by scripts are scanned after all other hosts
+                        let (host, origin) = match hosts.next() {
+                            Some(host) => (host, None),
+                            None => match targets.next() {
+                                Some(added) => (added.host, Some(added.origin)),
+                                None => break,
+                            },
+                        };
+                        let names = if host_name_lookup {
+                            HostNames::resolve(&host, &dns)
+                        } else {
+                            HostNames::from_target(&host)
+                        };
+                        let items = names
+                            .kb_items()
+                            .into_iter()
+                            .chain(origin.as_deref().map(|x| (HOST_ADDED_BY, x)));
+                        let key = ContextKey::Scan(self.scan.scan_id.clone(), Some(host.clone()));
+                        for (kb_key, value) in items {
+                            if let Err(e) = self
+                                .storage
+                                .dispatch(&key, Field::KB((kb_key, value).into()))
+                            {
+                                tracing::warn!(host, kb_key, error = %e, "unable to store host kb item");
+                            }
+                        }
+                        kb = Some((key, Arc::new(KbCache::default())));
+                        data = Box::new(host_jobs(host, concurrent_vts.clone()));
+                        job = data.next();
+                    }
+                    if let Some((stage, vt, param, host)) = job {
+                        // the service detection is done once the first other stage starts
+                        let kb_cache = match &kb {
+                            Some((key, cache)) => {
+                                if stage != Stage::Discovery && !cache.is_warm() {
+                                    prewarm_kb(self.storage, key, cache);
+                                }
+                                cache.clone()
+                            }
+                            None => Default::default(),
+                        };
+                        let skipped = if budget.is_some_and(|x| x.is_exhausted()) {
+                            Some(ScriptResultKind::Skipped)
+                        } else if let Some(reason) = limiter.blocked(&host) {
+                            Some(ScriptResultKind::Blocked(reason.to_string()))
+                        } else if seed.as_ref().is_some_and(|x| x.skips(&host, &vt))
+                            || nmap.as_ref().is_some_and(|x| x.skips(&host, &vt))
+                        {
+                            Some(ScriptResultKind::Seeded)
+                        } else {
+                            None
+                        };
+                        if let Some(kind) = skipped {
+                            let result = ScriptResult {
+                                oid: vt.oid.clone(),
+                                filename: vt.filename.clone(),
+                                stage,
+                                kind,
+                                target: host,
+                                duration: Duration::ZERO,
+                                traffic: Default::default(),
+                            };
+                            return Some((Ok(result), (data, hosts, true, base, kb)));
+                        }
+                        let now = SystemTime::now()
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .map(|x| x.as_secs())
+                            .unwrap_or_default();
+                        heartbeat
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .script_started(&vt.oid, now);
+                        let result = VTRunner::<Stack>::run(
+                            self.storage,
+                            self.loader,
+                            self.executor,
+                            dns,
+                            limiter,
+                            source,
+                            recorder,
+                            targets,
+                            extensions,
+                            policy,
+                            regex,
+                            include_once,
+                            compile_cache,
+                            parsed_includes,
+                            base.clone(),
+                            kb_cache,
+                            report_timing,
+                            timeouts,
+                            max_script_bytes,
+                            budget,
+                            &host,
+                            self.scan.target.labels_of(&host),
+                            self.scan.target.vhosts_of(&host),
+                            &vt,
+                            stage,
+                            param.as_ref(),
+                            &self.scan.scan_id,
+                        )
+                        .await;
+                        Some((result, (data, hosts, true, base, kb)))
+                    } else {
+                        let stats = dns.stats();
+                        tracing::debug!(
+                            hits = stats.hits,
+                            misses = stats.misses,
+                            hit_rate = stats.hit_rate(),
+                            "dns cache"
+                        );
+                        for (host, reason) in limiter.blocked_hosts() {
+                            tracing::info!(host, %reason, "scanning of host was stopped");
+                        }
+                        for (host, x) in limiter.responsiveness() {
+                            tracing::debug!(
+                                host,
+                                srtt = ?x.srtt(),
+                                error_rate = x.error_rate(),
+                                concurrency = x.concurrency(),
+                                samples = x.samples(),
+                                "host responsiveness"
+                            );
+                        }
+                        None
+                    }
+                }
+            },
+        )
     }
 }
 
@@ -115,17 +461,26 @@ impl<'a, Stack: ScannerStack> ScanRunner<'a, Stack> {
 pub(super) mod tests {
     use crate::models::Protocol;
     use crate::models::Scan;
+    use crate::models::ScanPreference;
     use crate::models::Target;
     use crate::models::VT;
     use crate::nasl::syntax::NaslValue;
+    use crate::nasl::utils::blocklist::{ABUSE_SIGNAL_RATE, ABUSE_SIGNAL_WINDOW};
+    use crate::nasl::utils::dns::{HOST_NAME_LOOKUP, KB_HOSTNAME};
+    use crate::nasl::utils::targets::MAX_ADDITIONAL_HOSTS;
     use crate::nasl::utils::Context;
     use crate::nasl::utils::Executor;
+    use crate::nasl::utils::FunctionErrorKind;
+    use crate::nasl::utils::Plugin;
+    use crate::nasl::utils::PluginConfig;
     use crate::nasl::utils::Register;
+    use crate::nasl::utils::Risk;
     use crate::nasl::{interpreter::CodeInterpreter, nasl_std_functions};
     use crate::scanner::{
-        error::{ExecuteError, ScriptResult},
-        scan_runner::ScanRunner,
-        vt_runner::generate_port_kb_key,
+        budget::{SCAN_BUDGET, SCAN_BUDGET_GRACE},
+        error::{ExecuteError, ScriptResult, ScriptResultKind},
+        scan_runner::{ScanRunner, HOST_ADDED_BY, PRELOAD_INCLUDES},
+        vt_runner::{generate_port_kb_key, PLUGINS_TIMEOUT, REPORT_SCRIPT_TIMING},
     };
     use crate::scheduling::{ExecutionPlaner, WaveExecutionPlan};
     use crate::storage::item::NVTField;
@@ -179,6 +534,8 @@ pub(super) mod tests {
                     parameters: vec![],
                 })
                 .collect(),
+            lineage: None,
+            imported: None,
         };
         let executor = nasl_std_functions();
         ((storage, loader, executor), scan)
@@ -378,6 +735,8 @@ exit({rc});
                     parameters: vec![],
                 })
                 .collect(),
+            lineage: None,
+            imported: None,
         };
 
         let executor = nasl_std_functions();
@@ -510,4 +869,562 @@ exit({rc});
         assert_eq!(success.len(), 1);
         assert_eq!(failure.len(), 1);
     }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn report_script_timing() {
+        let ((storage, loader, executor), mut scan) = setup_success();
+        scan.scan_preferences.push(ScanPreference {
+            id: REPORT_SCRIPT_TIMING.to_string(),
+            value: "true".to_string(),
+        });
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 3);
+        let logs = storage
+            .retrieve(
+                &ContextKey::Scan("sid".into(), Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .filter(|x| x.starts_with("Execution time of "))
+            .count();
+        assert_eq!(logs, 3);
+    }
+
+    #[tokio::test]
+    async fn plugins_timeout() {
+        let ((storage, _, executor), mut scan) =
+            setup(&[GenerateScript::with_dependencies("0", &[]).generate()]);
+        scan.scan_preferences.push(ScanPreference {
+            id: PLUGINS_TIMEOUT.to_string(),
+            value: "1".to_string(),
+        });
+        let loader = |_: &str| "i = 0; while (1) { i++; }".to_string();
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        let result = results[0].as_ref().expect("script result");
+        assert!(result.has_failed());
+        assert!(matches!(
+            result.kind,
+            ScriptResultKind::Timeout(x) if x == std::time::Duration::from_secs(1)
+        ));
+        let errors = storage
+            .retrieve(
+                &ContextKey::Scan("sid".into(), Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .filter(|x| x == "NVT timed out after 1 seconds.")
+            .count();
+        assert_eq!(errors, 1);
+    }
+
+    #[tokio::test]
+    async fn preloaded_includes() {
+        let ((storage, _, executor), mut scan) =
+            setup(&[GenerateScript::with_dependencies("0", &[]).generate()]);
+        scan.scan_preferences.push(ScanPreference {
+            id: PRELOAD_INCLUDES.to_string(),
+            value: "lib.inc".to_string(),
+        });
+        let loader = |key: &str| match key {
+            "lib.inc" => "code = 42; function rc() { return code; }".to_string(),
+            _ => "exit(rc());".to_string(),
+        };
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        let result = results[0].as_ref().expect("script result");
+        assert!(matches!(result.kind, ScriptResultKind::ReturnCode(42)));
+    }
+
+    #[tokio::test]
+    async fn compiled_scripts() {
+        use crate::nasl::syntax::{code_hash, CompileCache};
+
+        let ((storage, _, executor), scan) =
+            setup(&[GenerateScript::with_dependencies("0", &[]).generate()]);
+        let lib = "function rc() { return 40 + 2; }";
+        let script = r#"include("lib.inc"); exit(rc());"#;
+        let loader = |key: &str| match key {
+            "lib.inc" => lib.to_string(),
+            _ => script.to_string(),
+        };
+        let root = std::env::temp_dir().join(format!("scan-compile-cache-{}", std::process::id()));
+        let cache = CompileCache::new(&root).unwrap();
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan)
+                .expect("runner")
+                .with_compile_cache(Some(std::sync::Arc::new(cache.clone())));
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        let result = results[0].as_ref().expect("script result");
+        assert!(matches!(result.kind, ScriptResultKind::ReturnCode(42)));
+        // the script as well as its include are compiled
+        assert!(cache.get(&code_hash(script)).is_some());
+        assert!(cache.get(&code_hash(lib)).is_some());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn parsed_includes() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use crate::feed::IncludeGraph;
+
+        let scripts = [
+            GenerateScript::with_dependencies("0", &[]).generate(),
+            GenerateScript::with_dependencies("1", &[]).generate(),
+        ];
+        let filenames: Vec<_> = scripts.iter().map(|(_, x)| x.filename.clone()).collect();
+        let ((storage, _, executor), scan) = setup(&scripts);
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = loads.clone();
+        let loader = move |key: &str| match key {
+            "lib.inc" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "function rc() { return 42; }".to_string()
+            }
+            _ => r#"include("lib.inc"); exit(rc());"#.to_string(),
+        };
+        let mut graph = IncludeGraph::new();
+        for filename in &filenames {
+            graph.insert(&loader, filename).unwrap();
+        }
+        loads.store(0, Ordering::SeqCst);
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan)
+                .expect("runner")
+                .with_include_graph(Some(graph));
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 2);
+        for result in results {
+            let result = result.expect("script result");
+            assert!(matches!(result.kind, ScriptResultKind::ReturnCode(42)));
+        }
+        // the include is loaded once for the scan instead of by each script
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_builtin() {
+        let ((storage, _, executor), mut scan) =
+            setup(&[GenerateScript::with_dependencies("0", &[]).generate()]);
+        scan.scan_preferences.push(ScanPreference {
+            id: crate::nasl::utils::policy::BUILTINS_DISABLED.to_string(),
+            value: "string".to_string(),
+        });
+        // scripts can check whether a function is available before calling it
+        let loader = |_: &str| {
+            "if (!defined_func('strlen')) security_message(data: 'fallback'); strlen('a');"
+                .to_string()
+        };
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].as_ref().expect("script result").has_failed());
+        let messages: Vec<_> = storage
+            .retrieve(
+                &ContextKey::Scan("sid".into(), Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "fallback",
+                "NVT called the disabled builtin function strlen."
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_builtin() {
+        let ((storage, _, mut executor), scan) =
+            setup(&[GenerateScript::with_dependencies("0", &[]).generate()]);
+        executor.with_risk("strlen", Risk::Destructive);
+        let loader = |_: &str| {
+            "if (!defined_func('strlen')) security_message(data: 'fallback'); strlen('a');"
+                .to_string()
+        };
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].as_ref().expect("script result").has_failed());
+        let messages: Vec<_> = storage
+            .retrieve(
+                &ContextKey::Scan("sid".into(), Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "fallback",
+                "Refused to call the destructive builtin function strlen as safe_checks is enabled."
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_budget() {
+        let ((storage, _, executor), mut scan) = setup_success();
+        for (id, value) in [(SCAN_BUDGET, "1"), (SCAN_BUDGET_GRACE, "0")] {
+            scan.scan_preferences.push(ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            });
+        }
+        let loader = |_: &str| "i = 0; while (1) { i++; }".to_string();
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        let kinds: Vec<_> = results
+            .iter()
+            .map(|x| x.as_ref().expect("script result"))
+            .inspect(|x| assert!(x.is_truncated() && !x.has_failed()))
+            .map(|x| &x.kind)
+            .collect();
+        // the first VT is cancelled once the budget is exhausted, the others are not launched
+        assert!(matches!(
+            kinds[..],
+            [
+                ScriptResultKind::Cancelled,
+                ScriptResultKind::Skipped,
+                ScriptResultKind::Skipped
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn stops_scanning_blocked_host() {
+        let ((storage, _, executor), mut scan) = setup_success();
+        scan.target.hosts = vec!["127.0.0.1".to_string()];
+        for (id, value) in [(ABUSE_SIGNAL_RATE, "1"), (ABUSE_SIGNAL_WINDOW, "1")] {
+            scan.scan_preferences.push(ScanPreference {
+                id: id.to_string(),
+                value: value.to_string(),
+            });
+        }
+        // connections to a closed port are reset
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let loader = move |_: &str| format!("open_sock_tcp({port});");
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        let results: Vec<_> = results
+            .iter()
+            .map(|x| x.as_ref().expect("script result"))
+            .inspect(|x| assert!(!x.has_failed()))
+            .collect();
+        assert!(results[0].has_succeeded());
+        for result in &results[1..] {
+            assert!(matches!(
+                &result.kind,
+                ScriptResultKind::Blocked(reason) if reason.contains("reset")
+            ));
+        }
+    }
+
+    #[derive(Default)]
+    struct Lifecycle {
+        events: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    crate::function_set! { Lifecycle, sync_stateful, () }
+
+    #[async_trait::async_trait]
+    impl Plugin for Lifecycle {
+        async fn init_scan(&mut self, config: &PluginConfig) -> Result<(), FunctionErrorKind> {
+            let timeout: Option<u64> = config.get("lifecycle_timeout")?;
+            let event = format!("scan {} {timeout:?}", config.scan_id());
+            self.events.lock().unwrap().push(event);
+            Ok(())
+        }
+
+        async fn init_script(&mut self, _: &Context<'_>) -> Result<(), FunctionErrorKind> {
+            self.events.lock().unwrap().push("start".into());
+            Ok(())
+        }
+
+        async fn finish_script(&mut self, _: &Context<'_>) {
+            self.events.lock().unwrap().push("finish".into());
+        }
+    }
+
+    #[tokio::test]
+    async fn plugin_lifecycle() {
+        let ((storage, loader, mut executor), mut scan) = setup_success();
+        let lifecycle = Lifecycle::default();
+        let events = lifecycle.events.clone();
+        executor.add_plugin(lifecycle);
+        let mut preference = |value: &str| {
+            scan.scan_preferences = vec![ScanPreference {
+                id: "lifecycle_timeout".to_string(),
+                value: value.to_string(),
+            }];
+            scan.clone()
+        };
+        let (storage, loader, executor) = (&storage, &loader, &executor);
+        let run = |scan: Scan| async move {
+            let schedule = storage
+                .execution_plan::<WaveExecutionPlan>(&scan)
+                .expect("schedule");
+            let runner: ScanRunner<(_, _)> =
+                ScanRunner::new(storage, loader, executor, schedule, &scan).expect("runner");
+            runner.stream().collect::<Vec<_>>().await
+        };
+        let results = run(preference("5")).await;
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|x| x.as_ref().unwrap().has_succeeded()));
+        let expected: Vec<String> = ["scan sid Some(5)"]
+            .into_iter()
+            .chain(["start", "finish"].repeat(3))
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(*events.lock().unwrap(), expected);
+
+        let results = run(preference("five")).await;
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[0], Err(ExecuteError::Plugin(_))));
+    }
+
+    #[tokio::test]
+    async fn scans_added_hosts() {
+        let code = r#"
+if (description)
+{
+  script_oid("0");
+  script_category(ACT_GATHER_INFO);
+  exit(0);
+}
+add_scan_target(host: "other.host");
+add_scan_target(host: "third.host");
+log_message(data: "Hello world.");
+exit(0);
+"#;
+        let scripts = [(
+            code.to_string(),
+            parse_meta_data("0.nasl", code).expect("metadata"),
+        )];
+        let ((storage, _, executor), mut scan) = setup(&scripts);
+        scan.scan_preferences = vec![
+            ScanPreference {
+                id: MAX_ADDITIONAL_HOSTS.to_string(),
+                value: "1".to_string(),
+            },
+            ScanPreference {
+                id: HOST_NAME_LOOKUP.to_string(),
+                value: "no".to_string(),
+            },
+        ];
+        let loader = |_: &str| code.to_string();
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let targets = runner.target_queue();
+        let results = runner.stream().collect::<Vec<_>>().await;
+        let hosts: Vec<_> = results
+            .into_iter()
+            .map(|x| x.expect("result").target)
+            .collect();
+        assert_eq!(hosts, vec!["test.host", "other.host"]);
+        assert_eq!(targets.added(), 1);
+        let kb = |key: &str| {
+            storage
+                .retrieve(
+                    &ContextKey::Scan("sid".into(), Some("other.host".into())),
+                    Retrieve::KB(key.to_string()),
+                )
+                .expect("kb")
+                .next()
+        };
+        assert_eq!(
+            kb(HOST_ADDED_BY),
+            Some(Field::KB((HOST_ADDED_BY, "test.host").into()))
+        );
+        assert_eq!(
+            kb(KB_HOSTNAME),
+            Some(Field::KB((KB_HOSTNAME, "other.host").into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn forks_over_vhosts() {
+        let code = r#"
+if (description)
+{
+  script_oid("0");
+  script_category(ACT_GATHER_INFO);
+  exit(0);
+}
+name = get_host_name();
+if (get_host_name() == name)
+  log_message(data: name);
+exit(0);
+"#;
+        let scripts = [(
+            code.to_string(),
+            parse_meta_data("0.nasl", code).expect("metadata"),
+        )];
+        let ((storage, _, executor), mut scan) = setup(&scripts);
+        scan.scan_preferences = vec![ScanPreference {
+            id: HOST_NAME_LOOKUP.to_string(),
+            value: "no".to_string(),
+        }];
+        scan.target.vhosts = [(
+            "test.host".to_string(),
+            vec!["www.test.host".to_string(), "TEST.host".to_string()],
+        )]
+        .into();
+        let loader = |_: &str| code.to_string();
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+        let mut logs: Vec<_> = storage
+            .retrieve(
+                &ContextKey::Scan("sid".into(), Some("test.host".into())),
+                Retrieve::Result(None),
+            )
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => Some((x.hostname, x.message)),
+                _ => None,
+            })
+            .collect();
+        logs.sort();
+        let expected = |name: &str| (Some(name.to_string()), Some(name.to_string()));
+        assert_eq!(logs, vec![expected("test.host"), expected("www.test.host")]);
+    }
+
+    #[tokio::test]
+    async fn prewarmed_kb() {
+        let discovery = r#"
+if (description)
+{
+  script_oid("0");
+  script_category(ACT_GATHER_INFO);
+  exit(0);
+}
+set_kb_item(name: "Ports/tcp/80", value: 1);
+set_kb_item(name: "Services/www", value: 80);
+exit(0);
+"#;
+        let attack = r#"
+if (description)
+{
+  script_oid("1");
+  script_category(ACT_ATTACK);
+  script_require_keys("Ports/tcp/80");
+  exit(0);
+}
+log_message(data: get_kb_item("Services/www"));
+exit(0);
+"#;
+        let scripts = [("0.nasl", discovery), ("1.nasl", attack)].map(|(name, code)| {
+            (
+                code.to_string(),
+                parse_meta_data(name, code).expect("metadata"),
+            )
+        });
+        let ((storage, _, executor), mut scan) = setup(&scripts);
+        scan.scan_preferences = vec![ScanPreference {
+            id: HOST_NAME_LOOKUP.to_string(),
+            value: "no".to_string(),
+        }];
+        let loader = move |key: &str| match key {
+            "0.nasl" => discovery.to_string(),
+            _ => attack.to_string(),
+        };
+        let schedule = storage
+            .execution_plan::<WaveExecutionPlan>(&scan)
+            .expect("schedule");
+        let runner: ScanRunner<(_, _)> =
+            ScanRunner::new(&storage, &loader, &executor, schedule, &scan).expect("runner");
+        let results = runner.stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|x| x.as_ref().is_ok_and(|x| x.has_succeeded())));
+        let key = ContextKey::Scan("sid".into(), Some("test.host".into()));
+        let messages: Vec<_> = storage
+            .retrieve(&key, Retrieve::Result(None))
+            .expect("results")
+            .filter_map(|x| match x {
+                Field::Result(x) => x.message,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(messages, vec!["80"]);
+        let stored = storage
+            .retrieve(&key, Retrieve::KB("Ports/*".to_string()))
+            .expect("kb")
+            .count();
+        assert_eq!(stored, 1);
+    }
 }
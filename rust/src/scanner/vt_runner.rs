@@ -1,29 +1,141 @@
-use crate::models::{Host, Parameter, Protocol, ScanId};
-use crate::nasl::syntax::{Loader, NaslValue};
-use crate::nasl::utils::{Executor, Register};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::models::{self, Host, Labels, Parameter, Protocol, ResultType, ScanId, ScanPreference};
+use crate::nasl::syntax::{CompileCache, Loader, NaslValue, ParsedIncludes};
+use crate::nasl::utils::{
+    BuiltinPolicy, ConnectionLimiter, DnsCache, Executor, Extensions, KbCache, NetworkSource,
+    PacketRecorder, RegexMode, Register, RegisterBase, TargetQueue, TrafficCounter,
+};
 use crate::scheduling::Stage;
-use crate::storage::item::Nvt;
-use crate::storage::{types::Primitive, Retriever, Storage};
-use crate::storage::{ContextKey, Field, Retrieve, StorageError};
+use crate::storage::item::{Nvt, ACT};
+use crate::storage::{types::Primitive, Storage};
+use crate::storage::{ContextKey, Field, StorageError};
 use futures::StreamExt;
-use tracing::{error_span, trace, warn};
+use tracing::{debug_span, error_span, trace, warn, Instrument as _};
 
-use crate::nasl::interpreter::CodeInterpreter;
+use crate::nasl::interpreter::{
+    CodeInterpreter, FunctionError, InterpretError, InterpretErrorKind,
+};
 use crate::nasl::prelude::*;
 
 use super::ExecuteError;
 use super::{
+    budget::ScanBudget,
     error::{ScriptResult, ScriptResultKind},
     ScannerStack,
 };
 
+/// Scan preference id enabling a log result containing the wall time of each executed VT
+pub const REPORT_SCRIPT_TIMING: &str = "report_script_timing";
+
+/// Scan preference id of the timeout in seconds of VTs that do not set one via script_timeout
+pub const PLUGINS_TIMEOUT: &str = "plugins_timeout";
+
+/// Scan preference id of the timeout in seconds of ACT_SCANNER VTs that do not set one via
+/// script_timeout
+pub const SCANNER_PLUGINS_TIMEOUT: &str = "scanner_plugins_timeout";
+
+/// Timeout of a VT in seconds when neither the VT nor the scan sets one
+pub const DEFAULT_TIMEOUT: u64 = 320;
+
+/// Timeout of an ACT_SCANNER VT in seconds when neither the VT nor the scan sets one
+pub const DEFAULT_SCANNER_TIMEOUT: u64 = 36000;
+
+/// The timeouts of the VTs of a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PluginTimeouts {
+    plugins: u64,
+    scanner: u64,
+}
+
+impl Default for PluginTimeouts {
+    fn default() -> Self {
+        Self {
+            plugins: DEFAULT_TIMEOUT,
+            scanner: DEFAULT_SCANNER_TIMEOUT,
+        }
+    }
+}
+
+impl PluginTimeouts {
+    /// Reads the plugins_timeout and scanner_plugins_timeout scan preferences, 0 is treated as
+    /// not set.
+    pub fn from_preferences(preferences: &[ScanPreference]) -> Self {
+        let value = |id: &str| {
+            preferences
+                .iter()
+                .find(|x| x.id == id)
+                .and_then(|x| x.value.trim().parse().ok())
+                .filter(|x| *x > 0)
+        };
+        let default = Self::default();
+        Self {
+            plugins: value(PLUGINS_TIMEOUT).unwrap_or(default.plugins),
+            scanner: value(SCANNER_PLUGINS_TIMEOUT).unwrap_or(default.scanner),
+        }
+    }
+
+    /// Returns the timeout of a VT in seconds.
+    ///
+    /// The script_timeout of the VT takes precedence, otherwise scanner_plugins_timeout applies
+    /// to ACT_SCANNER VTs and plugins_timeout to all others like in openvas.
+    pub fn of(&self, vt: &Nvt) -> u64 {
+        vt.preferences
+            .iter()
+            .find(|x| x.id == Some(0))
+            .and_then(|x| x.default.parse().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(match vt.category {
+                ACT::Scanner => self.scanner,
+                _ => self.plugins,
+            })
+    }
+}
+
+/// Returns the name of the builtin function when the script failed by calling a disabled one.
+fn disabled_function(kind: &ScriptResultKind) -> Option<&str> {
+    match kind {
+        ScriptResultKind::Error(InterpretError {
+            kind:
+                InterpretErrorKind::FunctionCallError(FunctionError {
+                    kind: FunctionErrorKind::Disabled(function),
+                    ..
+                }),
+            ..
+        }) => Some(function),
+        _ => None,
+    }
+}
+
 /// Runs a single VT to completion on a single host.
 pub struct VTRunner<'a, S: ScannerStack> {
     storage: &'a S::Storage,
     loader: &'a S::Loader,
     executor: &'a Executor,
+    dns: Arc<DnsCache>,
+    limiter: Arc<ConnectionLimiter>,
+    source: NetworkSource,
+    recorder: Arc<PacketRecorder>,
+    targets: Arc<TargetQueue>,
+    extensions: Arc<Extensions>,
+    policy: Arc<BuiltinPolicy>,
+    regex: RegexMode,
+    include_once: bool,
+    compile_cache: Option<Arc<CompileCache>>,
+    parsed_includes: Option<Arc<ParsedIncludes>>,
+    base: Option<Arc<RegisterBase>>,
+    kb_cache: Arc<KbCache>,
+    report_timing: bool,
+    timeouts: PluginTimeouts,
+    budget: Option<ScanBudget>,
+    traffic: TrafficCounter,
 
     target: &'a Host,
+    labels: Option<&'a Labels>,
+    vhosts: &'a [String],
     vt: &'a Nvt,
     stage: Stage,
     param: Option<&'a Vec<Parameter>>,
@@ -36,7 +148,26 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
         storage: &'a Stack::Storage,
         loader: &'a Stack::Loader,
         executor: &'a Executor,
+        dns: Arc<DnsCache>,
+        limiter: Arc<ConnectionLimiter>,
+        source: NetworkSource,
+        recorder: Arc<PacketRecorder>,
+        targets: Arc<TargetQueue>,
+        extensions: Arc<Extensions>,
+        policy: Arc<BuiltinPolicy>,
+        regex: RegexMode,
+        include_once: bool,
+        compile_cache: Option<Arc<CompileCache>>,
+        parsed_includes: Option<Arc<ParsedIncludes>>,
+        base: Option<Arc<RegisterBase>>,
+        kb_cache: Arc<KbCache>,
+        report_timing: bool,
+        timeouts: PluginTimeouts,
+        max_script_bytes: Option<u64>,
+        budget: Option<ScanBudget>,
         target: &'a Host,
+        labels: Option<&'a Labels>,
+        vhosts: &'a [String],
         vt: &'a Nvt,
         stage: Stage,
         param: Option<&'a Vec<Parameter>>,
@@ -46,13 +177,33 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
             storage,
             loader,
             executor,
+            dns,
+            limiter,
+            source,
+            recorder,
+            targets,
+            extensions,
+            policy,
+            regex,
+            include_once,
+            compile_cache,
+            parsed_includes,
+            base,
+            kb_cache,
+            report_timing,
+            timeouts,
+            budget,
+            traffic: TrafficCounter::new(max_script_bytes),
             target,
+            labels,
+            vhosts,
             vt,
             stage,
             param,
             scan_id,
         };
-        s.execute().await
+        let span = debug_span!("vt", oid = %vt.oid, target = %target, %stage);
+        s.execute().instrument(span).await
     }
 
     fn parameter(
@@ -73,115 +224,29 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
         Ok(())
     }
 
-    fn check_key<A, B, C>(
-        &self,
-        key: &ContextKey,
-        kb_key: &str,
-        result_none: A,
-        result_some: B,
-        result_err: C,
-    ) -> Result<(), ScriptResultKind>
-    where
-        A: Fn() -> Option<ScriptResultKind>,
-        B: Fn(Primitive) -> Option<ScriptResultKind>,
-        C: Fn(StorageError) -> Option<ScriptResultKind>,
-    {
+    /// Returns the first value of the KB item of the current host
+    fn lookup(&self, kb_key: &str) -> Result<Option<Primitive>, StorageError> {
+        let key = self.generate_key();
         let _span = error_span!("kb_item", %key, kb_key).entered();
-        let result = match self.storage.retrieve(key, Retrieve::KB(kb_key.to_string())) {
-            Ok(mut x) => {
-                let x = x.next();
-                if let Some(x) = x {
-                    match x {
-                        Field::KB(kb) => {
-                            trace!(value=?kb.value, "found");
-                            result_some(kb.value)
-                        }
-                        x => {
-                            trace!(field=?x, "found but it is not a KB item");
-                            result_none()
-                        }
-                    }
-                } else {
+        match self
+            .kb_cache
+            .retrieve(self.storage.as_retriever(), &key, kb_key)
+        {
+            Ok(x) => match x.into_iter().next() {
+                Some(kb) => {
+                    trace!(value=?kb.value, "found");
+                    Ok(Some(kb.value))
+                }
+                None => {
                     trace!("not found");
-                    result_none()
+                    Ok(None)
                 }
-            }
+            },
             Err(e) => {
                 warn!(error=%e, "storage error");
-                result_err(e)
+                Err(e)
             }
-        };
-        match result {
-            None => Ok(()),
-            Some(x) => Err(x),
-        }
-    }
-
-    fn check_keys(&self, vt: &Nvt) -> Result<(), ScriptResultKind> {
-        let key = self.generate_key();
-        let check_required_key = |k: &str| {
-            self.check_key(
-                &key,
-                k,
-                || Some(ScriptResultKind::MissingRequiredKey(k.into())),
-                |_| None,
-                |_| Some(ScriptResultKind::MissingRequiredKey(k.into())),
-            )
-        };
-        for k in &vt.required_keys {
-            check_required_key(k)?
-        }
-
-        let check_mandatory_key = |k: &str| {
-            self.check_key(
-                &key,
-                k,
-                || Some(ScriptResultKind::MissingMandatoryKey(k.into())),
-                |_| None,
-                |_| Some(ScriptResultKind::MissingMandatoryKey(k.into())),
-            )
-        };
-        for k in &vt.mandatory_keys {
-            check_mandatory_key(k)?
-        }
-
-        let check_exclude_key = |k: &str| {
-            self.check_key(
-                &key,
-                k,
-                || None,
-                |_| Some(ScriptResultKind::ContainsExcludedKey(k.into())),
-                |_| None,
-            )
-        };
-        for k in &vt.excluded_keys {
-            check_exclude_key(k)?
-        }
-
-        let check_port = |pt: Protocol, port: &str| {
-            let kbk = generate_port_kb_key(pt, port);
-            self.check_key(
-                &key,
-                &kbk,
-                || Some(ScriptResultKind::MissingPort(pt, port.to_string())),
-                |v| {
-                    if v.into() {
-                        None
-                    } else {
-                        Some(ScriptResultKind::MissingPort(pt, port.to_string()))
-                    }
-                },
-                |_| Some(ScriptResultKind::MissingPort(pt, port.to_string())),
-            )
-        };
-        for k in &vt.required_ports {
-            check_port(Protocol::TCP, k)?
-        }
-        for k in &vt.required_udp_ports {
-            check_port(Protocol::UDP, k)?
         }
-
-        Ok(())
     }
 
     // TODO: probably better to enhance ContextKey::Scan to contain target and scan_id?
@@ -190,10 +255,16 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
     }
 
     async fn get_result_kind(&self, code: &str, register: Register) -> ScriptResultKind {
-        if let Err(e) = self.check_keys(self.vt) {
+        if let Err(e) = check_keys(self.vt, |k| self.lookup(k)) {
             return e;
         }
 
+        let recording = if self.recorder.is_enabled(&self.vt.oid) {
+            let addrs = self.dns.lookup(self.target).unwrap_or_default();
+            self.recorder.start(self.target, &self.vt.oid, &addrs)
+        } else {
+            None
+        };
         let context = Context::new(
             self.generate_key(),
             self.target.clone(),
@@ -201,8 +272,64 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
             self.storage.as_retriever(),
             self.loader,
             self.executor,
-        );
-        let mut results = Box::pin(CodeInterpreter::new(code, register, &context).stream());
+        )
+        .with_dns_cache(self.dns.clone())
+        .with_connection_limiter(self.limiter.clone())
+        .with_network_source(self.source.clone())
+        .with_regex_mode(self.regex)
+        .with_include_once(self.include_once)
+        .with_compile_cache(self.compile_cache.clone())
+        .with_parsed_includes(self.parsed_includes.clone())
+        .with_kb_cache(Some(self.kb_cache.clone()))
+        .with_target_queue(self.targets.clone())
+        .with_extensions(self.extensions.clone())
+        .with_builtin_policy(self.policy.clone())
+        .with_recording(recording.as_ref())
+        .with_traffic(Some(&self.traffic))
+        .with_labels(self.labels)
+        .with_vhosts(self.vhosts)
+        .with_nvt(Some(self.vt));
+        let timeout = self.timeout();
+        let kind = match self.executor.init_script(&context).await {
+            Ok(()) => tokio::time::timeout(timeout, Self::interpret(code, register, &context))
+                .await
+                .unwrap_or_else(|_| match self.budget {
+                    Some(x) if x.remaining().is_zero() => ScriptResultKind::Cancelled,
+                    _ => ScriptResultKind::Timeout(timeout),
+                }),
+            Err(e) => ScriptResultKind::Error(FunctionError::new("init_script", e).into()),
+        };
+        self.executor.finish_script(&context).await;
+        // a script failing because scanning of the host was stopped meanwhile did not fail
+        match (kind, self.limiter.blocked(self.target)) {
+            (ScriptResultKind::Error(_), Some(reason)) => {
+                ScriptResultKind::Blocked(reason.to_string())
+            }
+            (kind, _) => kind,
+        }
+    }
+
+    /// Returns the time the VT may run before it gets aborted
+    ///
+    /// A VT running when the time budget of the scan is exhausted is aborted after the grace
+    /// period.
+    fn timeout(&self) -> Duration {
+        let timeout = Duration::from_secs(self.timeouts.of(self.vt));
+        match self.budget {
+            Some(x) => timeout.min(x.remaining()),
+            None => timeout,
+        }
+    }
+
+    async fn interpret(code: &str, register: Register, context: &Context<'_>) -> ScriptResultKind {
+        let interpreter = match context.compile_cache() {
+            Some(cache) => match cache.compile(code) {
+                Ok(statements) => CodeInterpreter::from_statements(statements, register, context),
+                Err(e) => return ScriptResultKind::Error(e.into()),
+            },
+            None => CodeInterpreter::new(code, register, context),
+        };
+        let mut results = Box::pin(interpreter.stream());
         while let Some(r) = results.next().await {
             match r {
                 Ok(NaslValue::Exit(x)) => return ScriptResultKind::ReturnCode(x),
@@ -217,23 +344,205 @@ impl<'a, Stack: ScannerStack> VTRunner<'a, Stack> {
 
     async fn execute(mut self) -> Result<ScriptResult, ExecuteError> {
         let code = self.loader.load(&self.vt.filename)?;
-        let mut register = Register::default();
+        let mut register = match &self.base {
+            Some(base) => Register::layered(base.clone()),
+            None => Register::default(),
+        };
         self.set_parameters(&mut register)?;
 
         // currently scans are limited to the target as well as the id.
         tracing::debug!("running");
+        let start = Instant::now();
         let kind = self.get_result_kind(&code, register).await;
-        tracing::debug!(result=?kind, "finished");
-        Ok(ScriptResult {
+        let duration = start.elapsed();
+        tracing::debug!(result=?kind, ?duration, "finished");
+        let result = ScriptResult {
             oid: self.vt.oid.clone(),
             filename: self.vt.filename.clone(),
             stage: self.stage,
             kind,
             target: self.target.clone(),
-        })
+            duration,
+            traffic: self.traffic.totals(),
+        };
+        if let Some(cap) = self.traffic.exceeded() {
+            warn!(
+                oid = self.vt.oid,
+                target = self.target,
+                cap,
+                "VT exceeded the byte cap"
+            );
+            self.report(
+                ResultType::Error,
+                format!("NVT exceeded the cap of {cap} bytes."),
+            )?;
+        }
+        if let ScriptResultKind::Timeout(timeout) = &result.kind {
+            warn!(
+                oid = self.vt.oid,
+                target = self.target,
+                ?timeout,
+                "VT timed out"
+            );
+            self.report(
+                ResultType::Error,
+                format!("NVT timed out after {} seconds.", timeout.as_secs()),
+            )?;
+        }
+        if let Some(function) = disabled_function(&result.kind) {
+            warn!(
+                oid = self.vt.oid,
+                target = self.target,
+                function,
+                "VT called a disabled builtin function"
+            );
+            self.report(
+                ResultType::Error,
+                format!("NVT called the disabled builtin function {function}."),
+            )?;
+        }
+        if self.report_timing && !result.has_not_run() {
+            self.report_duration(duration)?;
+        }
+        Ok(result)
+    }
+
+    /// Stores a log result containing the wall time of the script
+    fn report_duration(&self, duration: Duration) -> Result<(), ExecuteError> {
+        self.report(
+            ResultType::Log,
+            format!(
+                "Execution time of {}: {}.{:06} s",
+                self.vt.filename,
+                duration.as_secs(),
+                duration.subsec_micros()
+            ),
+        )
+    }
+
+    /// Stores a result of the script for the current host
+    fn report(&self, r_type: ResultType, message: String) -> Result<(), ExecuteError> {
+        let result = models::Result {
+            id: 0,
+            r_type,
+            ip_address: Some(self.target.clone()),
+            hostname: None,
+            oid: Some(self.vt.oid.clone()),
+            qod: Some(self.vt.qod()),
+            port: None,
+            protocol: None,
+            message: Some(message),
+            detail: None,
+            pcap: None,
+            traffic: Some(self.traffic.totals()),
+            labels: self.labels.cloned().unwrap_or_default(),
+            triage: None,
+        };
+        self.storage.as_dispatcher().retry_dispatch(
+            5,
+            &self.generate_key(),
+            Field::Result(result.into()),
+        )?;
+        Ok(())
     }
 }
 
 pub(crate) fn generate_port_kb_key(protocol: crate::models::Protocol, port: &str) -> String {
     format!("Ports/{protocol}/{port}")
 }
+
+fn check_key<L, A, B, C>(
+    lookup: &L,
+    kb_key: &str,
+    result_none: A,
+    result_some: B,
+    result_err: C,
+) -> Result<(), ScriptResultKind>
+where
+    L: Fn(&str) -> Result<Option<Primitive>, StorageError>,
+    A: Fn() -> Option<ScriptResultKind>,
+    B: Fn(Primitive) -> Option<ScriptResultKind>,
+    C: Fn(StorageError) -> Option<ScriptResultKind>,
+{
+    let result = match lookup(kb_key) {
+        Ok(Some(x)) => result_some(x),
+        Ok(None) => result_none(),
+        Err(e) => result_err(e),
+    };
+    match result {
+        None => Ok(()),
+        Some(x) => Err(x),
+    }
+}
+
+/// Verifies the required, mandatory and excluded keys as well as the required ports of a VT.
+///
+/// The lookup returns the value of a KB item of the host the VT runs on.
+pub(crate) fn check_keys<L>(vt: &Nvt, lookup: L) -> Result<(), ScriptResultKind>
+where
+    L: Fn(&str) -> Result<Option<Primitive>, StorageError>,
+{
+    let check_required_key = |k: &str| {
+        check_key(
+            &lookup,
+            k,
+            || Some(ScriptResultKind::MissingRequiredKey(k.into())),
+            |_| None,
+            |_| Some(ScriptResultKind::MissingRequiredKey(k.into())),
+        )
+    };
+    for k in &vt.required_keys {
+        check_required_key(k)?
+    }
+
+    let check_mandatory_key = |k: &str| {
+        check_key(
+            &lookup,
+            k,
+            || Some(ScriptResultKind::MissingMandatoryKey(k.into())),
+            |_| None,
+            |_| Some(ScriptResultKind::MissingMandatoryKey(k.into())),
+        )
+    };
+    for k in &vt.mandatory_keys {
+        check_mandatory_key(k)?
+    }
+
+    let check_exclude_key = |k: &str| {
+        check_key(
+            &lookup,
+            k,
+            || None,
+            |_| Some(ScriptResultKind::ContainsExcludedKey(k.into())),
+            |_| None,
+        )
+    };
+    for k in &vt.excluded_keys {
+        check_exclude_key(k)?
+    }
+
+    let check_port = |pt: Protocol, port: &str| {
+        let kbk = generate_port_kb_key(pt, port);
+        check_key(
+            &lookup,
+            &kbk,
+            || Some(ScriptResultKind::MissingPort(pt, port.to_string())),
+            |v| {
+                if v.into() {
+                    None
+                } else {
+                    Some(ScriptResultKind::MissingPort(pt, port.to_string()))
+                }
+            },
+            |_| Some(ScriptResultKind::MissingPort(pt, port.to_string())),
+        )
+    };
+    for k in &vt.required_ports {
+        check_port(Protocol::TCP, k)?
+    }
+    for k in &vt.required_udp_ports {
+        check_port(Protocol::UDP, k)?
+    }
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::{arg, value_parser, Arg, ArgAction, Command};
+use scannerlib::storage::infisto::{
+    archive::{self, Backup, Header},
+    IndexedByteStorage, IndexedFileStorer,
+};
+
+use crate::{CliError, CliErrorKind};
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("admin")
+            .about("Administrates the state of openvasd")
+            .subcommand_required(true)
+            .subcommand(Command::new("backup")
+                .about("Writes scans, their status and results of the file storage of openvasd into a backup")
+                .arg(arg!(-p --"storage-path" <DIR> "Path of the file storage.").required(true)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-o --output <FILE> "File or directory the backup is written to, by default the current directory.").required(false)
+                    .value_parser(value_parser!(PathBuf)))
+            )
+            .subcommand(Command::new("restore")
+                .about("Restores a backup into the file storage of openvasd")
+                .arg(arg!(-p --"storage-path" <DIR> "Path of the file storage.").required(true)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--at <TIME> "Restores the latest backup of the directory created until TIME, either RFC 3339 or unix timestamp.").required(false))
+                .arg(arg!(-f --force "Restores into a storage that is not empty.").required(false).action(ArgAction::SetTrue))
+                .arg(Arg::new("backup").required(true).value_parser(value_parser!(PathBuf))
+                    .help("Path to the backup or to a directory containing backups."))
+            ),
+    ))
+}
+
+fn corrupt(filename: &Path, e: impl std::fmt::Display) -> CliError {
+    CliError {
+        filename: filename.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e.to_string()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+fn storage(path: &Path) -> Result<IndexedFileStorer, CliError> {
+    IndexedFileStorer::init(path).map_err(|e| corrupt(path, e))
+}
+
+fn backup(path: &Path, output: Option<&Path>) -> Result<(), CliError> {
+    let storage = storage(path)?;
+    let created = now();
+    let name = format!("openvasd-backup-{created}.gz");
+    let output = match output {
+        Some(x) if x.is_dir() => x.join(name),
+        Some(x) => x.to_path_buf(),
+        None => PathBuf::from(name),
+    };
+    let mut out = BufWriter::new(File::create(&output).map_err(|e| corrupt(&output, e))?);
+    let header = archive::backup(&storage, created, &mut out).map_err(|e| corrupt(path, e))?;
+    out.flush().map_err(|e| corrupt(&output, e))?;
+    tracing::info!(keys = header.keys, "Stored backup");
+    println!("{}", output.to_string_lossy());
+    Ok(())
+}
+
+/// Parses a point in time as RFC 3339 or unix timestamp.
+fn parse_time(time: &str) -> Result<u64, CliError> {
+    time.parse::<u64>().or_else(|_| {
+        chrono::DateTime::parse_from_rfc3339(time)
+            .map(|x| x.timestamp().max(0) as u64)
+            .map_err(|e| corrupt(Path::new(""), format!("invalid time {time}: {e}")))
+    })
+}
+
+fn header(path: &Path) -> Result<Header, CliError> {
+    let file = File::open(path).map_err(|e| corrupt(path, e))?;
+    Backup::open(file)
+        .map(|x| x.header().clone())
+        .map_err(|e| corrupt(path, e))
+}
+
+/// Returns the newest backup within the directory created until the given time.
+///
+/// Files that are not a backup are ignored.
+fn select(dir: &Path, at: Option<u64>) -> Result<PathBuf, CliError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| corrupt(dir, e))?;
+    entries
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.is_file())
+        .filter_map(|x| header(&x).ok().map(|h| (h.created, x)))
+        .filter(|(created, _)| at.is_none_or(|at| *created <= at))
+        .max()
+        .map(|(_, x)| x)
+        .ok_or_else(|| corrupt(dir, "no backup found"))
+}
+
+fn restore(path: &Path, input: &Path, at: Option<u64>, force: bool) -> Result<(), CliError> {
+    let input = if input.is_dir() {
+        select(input, at)?
+    } else {
+        input.to_path_buf()
+    };
+    let mut storage = storage(path)?;
+    let existing = storage.keys().map_err(|e| corrupt(path, e))?;
+    if !force && !existing.is_empty() {
+        return Err(corrupt(
+            path,
+            format!(
+                "storage contains {} keys, use --force to restore anyway",
+                existing.len()
+            ),
+        ));
+    }
+    let file = File::open(&input).map_err(|e| corrupt(&input, e))?;
+    let header = Backup::open(file)
+        .and_then(|x| x.restore(&mut storage))
+        .map_err(|e| corrupt(&input, e))?;
+    tracing::info!(
+        keys = header.keys,
+        created = header.created,
+        "Restored {}",
+        input.to_string_lossy()
+    );
+    Ok(())
+}
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "admin")?;
+    let path = |args: &clap::ArgMatches, name: &str| args.get_one::<PathBuf>(name).cloned();
+    Some(match args.subcommand() {
+        Some(("backup", args)) => {
+            let storage = path(args, "storage-path").unwrap_or_default();
+            backup(&storage, path(args, "output").as_deref())
+        }
+        Some(("restore", args)) => {
+            let storage = path(args, "storage-path").unwrap_or_default();
+            let input = path(args, "backup").unwrap_or_default();
+            let force = args.get_one::<bool>("force").cloned().unwrap_or_default();
+            match args.get_one::<String>("at").map(|x| parse_time(x)) {
+                Some(Err(e)) => Err(e),
+                Some(Ok(at)) => restore(&storage, &input, Some(at), force),
+                None => restore(&storage, &input, None, force),
+            }
+        }
+        _ => Err(CliError {
+            filename: "".to_string(),
+            kind: CliErrorKind::WrongAction,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_in_time() {
+        let base = Path::new("/tmp/scannerctl/unittest_admin");
+        let _ = std::fs::remove_dir_all(base);
+        let source = base.join("source");
+        let backups = base.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        let mut files = storage(&source).unwrap();
+        for (created, data) in [(10, "first"), (20, "second"), (30, "third")] {
+            files.put("scan_1", data).unwrap();
+            let out = File::create(backups.join(format!("{created}.gz"))).unwrap();
+            archive::backup(&files, created, out).unwrap();
+        }
+        std::fs::write(backups.join("README"), "no backup").unwrap();
+
+        assert_eq!(select(&backups, None).unwrap(), backups.join("30.gz"));
+        assert_eq!(select(&backups, Some(25)).unwrap(), backups.join("20.gz"));
+        assert!(select(&backups, Some(5)).is_err());
+
+        let target = base.join("target");
+        restore(&target, &backups, Some(parse_time("25").unwrap()), false).unwrap();
+        assert!(restore(&target, &backups, None, false).is_err());
+        restore(&target, &backups, None, true).unwrap();
+        let restored: Vec<Vec<u8>> = storage(&target)
+            .unwrap()
+            .by_range("scan_1", scannerlib::storage::infisto::Range::All)
+            .unwrap();
+        assert_eq!(restored, vec![b"third"]);
+        assert_eq!(parse_time("1970-01-01T00:01:00Z").unwrap(), 60);
+        assert!(parse_time("yesterday").is_err());
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
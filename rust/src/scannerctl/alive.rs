@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::time::Duration;
+
+use clap::{arg, value_parser, ArgAction, Command};
+use scannerlib::alive::AliveTest;
+use scannerlib::models::AliveTestMethods;
+
+use crate::{CliError, CliErrorKind};
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("alive")
+            .about("Prints the alive status of each target host as json without running any VTs.")
+            .arg(
+                arg!(-t --targets <TARGETS> "Comma separated hosts to test.")
+                    .required(true)
+                    .value_delimiter(',')
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                arg!(-p --ports <PORTS> "Comma separated TCP ports used by tcp_ack and tcp_syn.")
+                    .required(false)
+                    .value_delimiter(',')
+                    .value_parser(value_parser!(u16))
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                arg!(-m --methods <METHODS> "Comma separated methods: icmp, tcp_ack, tcp_syn, arp, consider_alive.")
+                    .required(false)
+                    .value_delimiter(',')
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                arg!(--timeout <SECONDS> "Time to wait for an answer of a single probe.")
+                    .required(false)
+                    .value_parser(value_parser!(u64)),
+            ),
+    ))
+}
+
+fn method(name: &str) -> Result<AliveTestMethods, CliError> {
+    match name.trim() {
+        "icmp" => Ok(AliveTestMethods::Icmp),
+        "tcp_ack" => Ok(AliveTestMethods::TcpAck),
+        "tcp_syn" => Ok(AliveTestMethods::TcpSyn),
+        "arp" => Ok(AliveTestMethods::Arp),
+        "consider_alive" => Ok(AliveTestMethods::ConsiderAlive),
+        x => Err(CliError {
+            filename: "".to_string(),
+            kind: CliErrorKind::Corrupt(format!("Unknown alive test method: {x}")),
+        }),
+    }
+}
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "alive")?;
+    let targets: Vec<String> = args
+        .get_many::<String>("targets")
+        .expect("targets is required")
+        .cloned()
+        .collect();
+    let ports: Vec<u16> = args
+        .get_many::<u16>("ports")
+        .map(|x| x.cloned().collect())
+        .unwrap_or_default();
+    let methods = match args
+        .get_many::<String>("methods")
+        .map(|x| x.map(|x| method(x)).collect::<Result<Vec<_>, _>>())
+        .transpose()
+    {
+        Ok(x) => x.unwrap_or_default(),
+        Err(e) => return Some(Err(e)),
+    };
+    let mut test = AliveTest::default().with_methods(methods).with_ports(ports);
+    if let Some(timeout) = args.get_one::<u64>("timeout") {
+        test = test.with_timeout(Duration::from_secs(*timeout));
+    }
+    let status = tokio::task::spawn_blocking(move || test.check_all(&targets))
+        .await
+        .expect("alive test panicked");
+    Some(
+        serde_json::to_string_pretty(&status)
+            .map(|x| println!("{x}"))
+            .map_err(|e| CliError {
+                filename: "".to_string(),
+                kind: CliErrorKind::Corrupt(format!("{e:?}")),
+            }),
+    )
+}
@@ -0,0 +1,329 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Generates shell completions from the command tree of scannerctl.
+//!
+//! Subcommands and options are completed for each position within the tree, as are the possible
+//! values of options. Other values and positional arguments fall back to the file completion of
+//! the shell.
+
+use std::fmt::Write;
+
+use clap::{Arg, ArgMatches, Command};
+
+use crate::CliError;
+
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(
+        Command::new("completion")
+            .about("Prints the shell completion script of scannerctl.")
+            .arg(
+                Arg::new("shell")
+                    .required(true)
+                    .value_parser(SHELLS)
+                    .help("The shell to complete for."),
+            )
+            .after_help(
+                "Examples:\n  \
+                 scannerctl completion bash > /etc/bash_completion.d/scannerctl\n  \
+                 scannerctl completion zsh > \"${fpath[1]}/_scannerctl\"\n  \
+                 scannerctl completion fish > ~/.config/fish/completions/scannerctl.fish",
+            ),
+    )
+}
+
+/// A command of the tree with the names of the commands leading to it
+struct Node<'a> {
+    path: Vec<&'a str>,
+    cmd: &'a Command,
+}
+
+impl Node<'_> {
+    fn id(&self) -> String {
+        self.path.join("__")
+    }
+
+    /// Returns the names of the subcommands and their description.
+    fn subcommands(&self) -> Vec<(&str, String)> {
+        self.cmd
+            .get_subcommands()
+            .filter(|x| !x.is_hide_set())
+            .map(|x| (x.get_name(), about(x.get_about())))
+            .collect()
+    }
+
+    /// Returns the short and long flag of each option and its description.
+    fn options(&self) -> Vec<(Option<char>, Option<&str>, String)> {
+        self.cmd
+            .get_arguments()
+            .filter(|x| !x.is_positional() && !x.is_hide_set())
+            .map(|x| (x.get_short(), x.get_long(), about(x.get_help())))
+            .collect()
+    }
+
+    /// Returns the flags of each option taking one of a fixed set of values and those values.
+    fn values(&self) -> Vec<(Vec<String>, Vec<String>)> {
+        self.cmd
+            .get_arguments()
+            .filter(|x| !x.is_positional() && !x.is_hide_set())
+            .filter(|x| x.get_action().takes_values())
+            .map(|x| {
+                let flags = x
+                    .get_long()
+                    .map(|x| format!("--{x}"))
+                    .into_iter()
+                    .chain(x.get_short().map(|x| format!("-{x}")))
+                    .collect();
+                let values = x
+                    .get_possible_values()
+                    .iter()
+                    .filter(|x| !x.is_hide_set())
+                    .map(|x| x.get_name().to_string())
+                    .collect();
+                (flags, values)
+            })
+            .filter(|(_, values): &(Vec<String>, Vec<String>)| !values.is_empty())
+            .collect()
+    }
+
+    /// Returns the words completed at this command.
+    fn words(&self) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .subcommands()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for (short, long, _) in self.options() {
+            words.extend(long.map(|x| format!("--{x}")));
+            words.extend(short.map(|x| format!("-{x}")));
+        }
+        words
+    }
+}
+
+fn about(text: Option<&clap::builder::StyledStr>) -> String {
+    let text = text.map(|x| x.to_string()).unwrap_or_default();
+    let line = text.lines().next().unwrap_or_default();
+    line.trim_end_matches('.').replace('\'', "")
+}
+
+/// Returns all commands of the tree, parents before their subcommands.
+///
+/// The subcommands of the generated `help` command are skipped as they mirror the tree.
+fn nodes(cmd: &Command) -> Vec<Node<'_>> {
+    fn collect<'a>(path: Vec<&'a str>, cmd: &'a Command, result: &mut Vec<Node<'a>>) {
+        result.push(Node {
+            path: path.clone(),
+            cmd,
+        });
+        for sub in cmd
+            .get_subcommands()
+            .filter(|x| !x.is_hide_set() && x.get_name() != "help")
+        {
+            let mut path = path.clone();
+            path.push(sub.get_name());
+            collect(path, sub, result);
+        }
+    }
+    let mut result = vec![];
+    collect(vec![cmd.get_name()], cmd, &mut result);
+    result
+}
+
+fn bash(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let nodes = nodes(cmd);
+    let mut out = String::new();
+    let _ = writeln!(out, "_{name}() {{");
+    let _ = writeln!(out, "    local cur path opts i");
+    let _ = writeln!(out, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"");
+    let _ = writeln!(out, "    path=\"{name}\"");
+    let _ = writeln!(out, "    for ((i = 1; i < COMP_CWORD; i++)); do");
+    let _ = writeln!(out, "        case \"${{path}}__${{COMP_WORDS[i]}}\" in");
+    for node in nodes.iter().skip(1) {
+        let _ = writeln!(out, "            {0}) path=\"{0}\" ;;", node.id());
+    }
+    let _ = writeln!(out, "        esac");
+    let _ = writeln!(out, "    done");
+    let _ = writeln!(
+        out,
+        "    case \"${{path}}__${{COMP_WORDS[COMP_CWORD-1]}}\" in"
+    );
+    for node in &nodes {
+        for (flags, values) in node.values() {
+            let cases: Vec<String> = flags
+                .iter()
+                .map(|x| format!("{}__{x}", node.id()))
+                .collect();
+            let _ = writeln!(
+                out,
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\")); return ;;",
+                cases.join("|"),
+                values.join(" ")
+            );
+        }
+    }
+    let _ = writeln!(out, "    esac");
+    let _ = writeln!(out, "    case \"$path\" in");
+    for node in &nodes {
+        let _ = writeln!(
+            out,
+            "        {}) opts=\"{}\" ;;",
+            node.id(),
+            node.words().join(" ")
+        );
+    }
+    let _ = writeln!(out, "    esac");
+    let _ = writeln!(
+        out,
+        "    COMPREPLY=($(compgen -W \"${{opts}}\" -- \"${{cur}}\"))"
+    );
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out, "complete -o default -F _{name} {name}");
+    out
+}
+
+fn zsh(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    format!(
+        "#compdef {name}\n\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+        bash(cmd)
+    )
+}
+
+fn fish(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let nodes = nodes(cmd);
+    let mut out = String::new();
+    let _ = writeln!(out, "function __fish_{name}_path");
+    let _ = writeln!(out, "    set -l path {name}");
+    let _ = writeln!(out, "    for word in (commandline -opc)[2..-1]");
+    let _ = writeln!(out, "        switch \"$path\"__\"$word\"");
+    let ids: Vec<String> = nodes.iter().skip(1).map(|x| x.id()).collect();
+    let _ = writeln!(out, "            case {}", ids.join(" "));
+    let _ = writeln!(out, "                set path \"$path\"__\"$word\"");
+    let _ = writeln!(out, "        end");
+    let _ = writeln!(out, "    end");
+    let _ = writeln!(out, "    echo $path");
+    let _ = writeln!(out, "end");
+    let _ = writeln!(out);
+    for node in &nodes {
+        let condition = format!("-n 'test (__fish_{name}_path) = {}'", node.id());
+        for (sub, about) in node.subcommands() {
+            let _ = writeln!(
+                out,
+                "complete -c {name} {condition} -f -a '{sub}' -d '{about}'"
+            );
+        }
+        let values = node.values();
+        for (short, long, about) in node.options() {
+            let mut line = format!("complete -c {name} {condition}");
+            if let Some(short) = short {
+                let _ = write!(line, " -s {short}");
+            }
+            if let Some(long) = long {
+                let _ = write!(line, " -l {long}");
+                if let Some((_, values)) = values
+                    .iter()
+                    .find(|(flags, _)| flags.contains(&format!("--{long}")))
+                {
+                    let _ = write!(line, " -x -a '{}'", values.join(" "));
+                }
+            }
+            let _ = writeln!(out, "{line} -d '{about}'");
+        }
+    }
+    out
+}
+
+/// Returns the completion script of the given shell for the command.
+pub fn generate(cmd: &Command, shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash(cmd)),
+        "zsh" => Some(zsh(cmd)),
+        "fish" => Some(fish(cmd)),
+        _ => None,
+    }
+}
+
+pub fn run(cmd: &Command, root: &ArgMatches) -> Option<Result<(), CliError>> {
+    let args = root.subcommand_matches("completion")?;
+    let shell = args.get_one::<String>("shell")?;
+    // the tree is built lazily, the generated flags and subcommands are only added afterwards
+    let mut cmd = cmd.clone();
+    cmd.build();
+    print!("{}", generate(&cmd, shell)?);
+    Some(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Command {
+        let mut cmd = Command::new("ctl")
+            .version("1.0")
+            .arg(
+                Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .help("More output."),
+            )
+            .subcommand(
+                Command::new("feed").about("Handles feeds.").subcommand(
+                    Command::new("update")
+                        .arg(Arg::new("path").long("path"))
+                        .arg(
+                            Arg::new("format")
+                                .short('f')
+                                .long("format")
+                                .value_parser(["text", "json"]),
+                        ),
+                ),
+            )
+            .subcommand(Command::new("syntax").arg(Arg::new("file")));
+        cmd.build();
+        cmd
+    }
+
+    #[test]
+    fn command_tree() {
+        crate::cli().debug_assert();
+    }
+
+    #[test]
+    fn bash() {
+        let script = generate(&example(), "bash").unwrap();
+        assert!(script.contains("ctl__feed__update) path=\"ctl__feed__update\" ;;"));
+        assert!(script
+            .contains("ctl) opts=\"feed syntax help --verbose -v --help -h --version -V\" ;;"));
+        assert!(script.contains("ctl__feed__update) opts=\"--path --format -f --help -h\" ;;"));
+        assert!(script.contains(
+            "ctl__feed__update__--format|ctl__feed__update__-f) \
+             COMPREPLY=($(compgen -W \"text json\" -- \"${cur}\")); return ;;"
+        ));
+        assert!(script.ends_with("complete -o default -F _ctl ctl\n"));
+        assert!(generate(&example(), "zsh")
+            .unwrap()
+            .starts_with("#compdef ctl\n"));
+    }
+
+    #[test]
+    fn fish() {
+        let script = generate(&example(), "fish").unwrap();
+        assert!(script.contains("case ctl__feed ctl__feed__update ctl__syntax\n"));
+        assert!(script.contains(
+            "complete -c ctl -n 'test (__fish_ctl_path) = ctl' -f -a 'feed' -d 'Handles feeds'"
+        ));
+        assert!(script.contains(
+            "complete -c ctl -n 'test (__fish_ctl_path) = ctl__feed__update' -l path -d ''"
+        ));
+        assert!(script.contains(
+            "complete -c ctl -n 'test (__fish_ctl_path) = ctl__feed__update' -s f -l format \
+             -x -a 'text json' -d ''"
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Applies the config file of scannerctl to the command tree.
+//!
+//! The config is a TOML file whose keys are the long names of options. Tables contain the options
+//! of the subcommand of the same name:
+//!
+//! ```toml
+//! log-level = "debug"
+//! output-format = "json"
+//!
+//! [feed.update]
+//! vts-path = "/var/lib/openvas/plugins"
+//! ```
+//!
+//! The values become the defaults of the options, arguments given on the command line or via
+//! environment variables still take precedence.
+
+use std::{ffi::OsString, path::PathBuf};
+
+use clap::Command;
+
+use crate::{CliError, CliErrorKind};
+
+/// Environment variable of the path of the config file
+pub const CONFIG_ENV: &str = "SCANNERCTL_CONFIG";
+
+/// Returns the path of the config file given by `--config` or the environment.
+///
+/// The defaults must be known before the arguments are parsed, therefore the option is looked
+/// up without the command tree.
+fn path(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|x| x.strip_prefix("--config=")) {
+            return Some(path.into());
+        }
+    }
+    std::env::var_os(CONFIG_ENV).map(PathBuf::from)
+}
+
+fn invalid(path: &str, msg: String) -> CliError {
+    CliError {
+        filename: path.to_string(),
+        kind: CliErrorKind::Corrupt(msg),
+    }
+}
+
+/// Returns the values of a config entry as option values.
+fn values(value: &toml::Value) -> Option<Vec<String>> {
+    match value {
+        toml::Value::String(x) => Some(vec![x.clone()]),
+        toml::Value::Integer(x) => Some(vec![x.to_string()]),
+        toml::Value::Float(x) => Some(vec![x.to_string()]),
+        toml::Value::Boolean(x) => Some(vec![x.to_string()]),
+        toml::Value::Array(x) => x
+            .iter()
+            .map(|x| values(x).filter(|x| x.len() == 1).map(|mut x| x.remove(0)))
+            .collect(),
+        toml::Value::Datetime(_) | toml::Value::Table(_) => None,
+    }
+}
+
+/// Sets the values of the config as defaults of the options of the command and its subcommands.
+pub fn apply(mut cmd: Command, config: &toml::Table, path: &str) -> Result<Command, CliError> {
+    for (key, value) in config {
+        if let toml::Value::Table(table) = value {
+            let Some(sub) = cmd.find_subcommand(key).cloned() else {
+                return Err(invalid(
+                    path,
+                    format!("unknown command {key} of {}", cmd.get_name()),
+                ));
+            };
+            let sub = apply(sub, table, path)?;
+            cmd = cmd.mut_subcommand(key, |_| sub);
+            continue;
+        }
+        let Some(id) = cmd
+            .get_arguments()
+            .find(|x| x.get_long() == Some(key.as_str()))
+            .map(|x| x.get_id().clone())
+        else {
+            return Err(invalid(
+                path,
+                format!("unknown option {key} of {}", cmd.get_name()),
+            ));
+        };
+        let Some(values) = values(value) else {
+            return Err(invalid(path, format!("invalid value of {key}")));
+        };
+        // the command tree lives until scannerctl exits
+        let values: Vec<&'static str> = values
+            .into_iter()
+            .map(|x| &*Box::leak(x.into_boxed_str()))
+            .collect();
+        cmd = cmd.mut_arg(id, |x| x.required(false).default_values(values));
+    }
+    Ok(cmd)
+}
+
+/// Applies the config file given by the arguments or the environment to the command tree.
+pub fn load(cmd: Command, args: impl IntoIterator<Item = OsString>) -> Result<Command, CliError> {
+    let Some(path) = path(args) else {
+        return Ok(cmd);
+    };
+    let config = std::fs::read_to_string(&path).map_err(|e| CliError::load_error(e, &path))?;
+    let path = path.to_string_lossy();
+    let config: toml::Table = toml::from_str(&config).map_err(|e| invalid(&path, e.to_string()))?;
+    apply(cmd, &config, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{arg, value_parser, ArgAction};
+
+    use super::*;
+
+    fn example() -> Command {
+        Command::new("ctl")
+            .arg(
+                arg!(--"log-level" <LEVEL> "Log level")
+                    .required(false)
+                    .global(true),
+            )
+            .subcommand(
+                Command::new("feed").subcommand(
+                    Command::new("update")
+                        .arg(arg!(--path <PATH> "Feed").required(true))
+                        .arg(
+                            arg!(--limit <N> "Limit")
+                                .required(false)
+                                .value_parser(value_parser!(usize)),
+                        )
+                        .arg(arg!(--json "Json").action(ArgAction::SetTrue))
+                        .arg(arg!(--ignore <OID> "Ignored").num_args(1..).required(false)),
+                ),
+            )
+    }
+
+    fn config(toml: &str) -> Result<Command, CliError> {
+        apply(example(), &toml::from_str(toml).unwrap(), "scannerctl.toml")
+    }
+
+    #[test]
+    fn defaults() {
+        let cmd = config(
+            r#"
+log-level = "debug"
+[feed.update]
+path = "/var/lib/openvas/plugins"
+limit = 10
+json = true
+ignore = ["1.2", "1.3"]
+"#,
+        )
+        .unwrap();
+        cmd.clone().debug_assert();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["ctl", "feed", "update", "--limit", "5"])
+            .unwrap();
+        assert_eq!(
+            matches.get_one::<String>("log-level").map(|x| x.as_str()),
+            Some("debug")
+        );
+        let (_, feed) = matches.subcommand().unwrap();
+        let (_, update) = feed.subcommand().unwrap();
+        assert_eq!(
+            update.get_one::<String>("path").map(|x| x.as_str()),
+            Some("/var/lib/openvas/plugins")
+        );
+        // the command line takes precedence
+        assert_eq!(update.get_one::<usize>("limit"), Some(&5));
+        assert_eq!(update.get_one::<bool>("json"), Some(&true));
+        let ignored: Vec<_> = update.get_many::<String>("ignore").unwrap().collect();
+        assert_eq!(ignored, ["1.2", "1.3"]);
+    }
+
+    #[test]
+    fn unknown_entries() {
+        assert!(config("unknown = 1").is_err());
+        assert!(config("[unknown]\npath = \"x\"").is_err());
+        assert!(config("[feed.update]\npath = { a = 1 }").is_err());
+    }
+
+    #[test]
+    fn config_path() {
+        let args = |x: &[&str]| x.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            path(args(&["ctl", "feed", "--config", "a.toml"])),
+            Some("a.toml".into())
+        );
+        assert_eq!(
+            path(args(&["ctl", "--config=b.toml", "feed"])),
+            Some("b.toml".into())
+        );
+        assert_eq!(
+            path(args(&["ctl", "execute", "--", "--config", "c.toml"])),
+            std::env::var_os(CONFIG_ENV).map(PathBuf::from)
+        );
+    }
+}
@@ -31,6 +31,10 @@ pub enum CliErrorKind {
     StorageError(StorageError),
     SyntaxError(SyntaxError),
     Corrupt(String),
+    PartialFailure {
+        failed: usize,
+        total: usize,
+    },
 }
 
 impl From<ExecuteError> for CliErrorKind {
@@ -83,6 +87,9 @@ impl Display for CliErrorKind {
             CliErrorKind::SyntaxError(e) => write!(f, "{e}"),
             CliErrorKind::Corrupt(x) => write!(f, "Corrupt: {x}"),
             CliErrorKind::ExecuteError(x) => write!(f, "{x}"),
+            CliErrorKind::PartialFailure { failed, total } => {
+                write!(f, "{failed} of {total} files failed")
+            }
         }
     }
 }
@@ -142,6 +149,7 @@ impl From<VerifyError> for CliError {
                 "Signature check enabled but missing keyring. Set GNUPGHOME environment variable."
             }
             VerifyError::BadSignature(_) => "Bad signature",
+            VerifyError::MissingSignature(key) => key,
         };
         Self {
             filename: filename.to_string(),
@@ -191,6 +199,7 @@ impl From<feed::UpdateError> for CliError {
                 CliErrorKind::Corrupt("description run without exit.".to_string())
             }
             feed::UpdateErrorKind::VerifyError(e) => CliErrorKind::Corrupt(e.to_string()),
+            feed::UpdateErrorKind::InvalidNvt(e) => CliErrorKind::Corrupt(e.to_string()),
         };
         CliError {
             filename: value.key,
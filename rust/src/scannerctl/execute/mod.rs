@@ -6,10 +6,11 @@ use std::{fs, path::PathBuf};
 
 use clap::{arg, value_parser, Arg, ArgAction, Command};
 use futures::StreamExt;
-use scannerlib::feed::{HashSumNameLoader, Update};
+use scannerlib::feed::{LayeredNameLoader, Update};
 use scannerlib::models::Scan;
+use scannerlib::nasl::utils::DynamicLoader;
 use scannerlib::nasl::{nasl_std_functions, FSPluginLoader};
-use scannerlib::scanner::ScanRunner;
+use scannerlib::scanner::{plan_scan, ScanRunner};
 use scannerlib::scheduling::{ExecutionPlaner, WaveExecutionPlan};
 use tracing::{info, warn, warn_span};
 
@@ -51,6 +52,12 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
         .get_one::<bool>("schedule")
         .cloned()
         .unwrap_or_default();
+    let dry_run = args.get_one::<bool>("dry-run").cloned().unwrap_or_default();
+    let plugins = args.get_one::<PathBuf>("plugins").cloned();
+    let plugin_signature_check = args
+        .get_one::<bool>("plugin-signature-check")
+        .cloned()
+        .unwrap_or_default();
 
     let feed = args
         .get_one::<PathBuf>("path")
@@ -59,8 +66,10 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
     let storage = scannerlib::storage::DefaultDispatcher::new();
     info!("loading feed. This may take a while.");
 
-    let loader = FSPluginLoader::new(feed);
-    let verifier = HashSumNameLoader::sha256(&loader)?;
+    let overlays = args.get_many::<PathBuf>("overlay").into_iter().flatten();
+    let loader = FSPluginLoader::new(feed).with_overlays(overlays);
+    let layers = loader.layers();
+    let verifier = LayeredNameLoader::sha256(&layers)?;
     let updater = Update::init("1", 5, &loader, &storage, verifier);
     updater.perform_update().await?;
 
@@ -68,7 +77,16 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
         .execution_plan::<WaveExecutionPlan>(&scan)
         .expect("expected to be schedulable");
     info!("creating scheduling plan");
-    if schedule_only {
+    if dry_run {
+        let plan = plan_scan(&storage, schedule, &scan)
+            .map_err(|e| e.to_string())
+            .and_then(|x| serde_json::to_string_pretty(&x).map_err(|e| format!("{e:?}")))
+            .map_err(|e| CliError {
+                filename: "".to_string(),
+                kind: CliErrorKind::Corrupt(e),
+            })?;
+        println!("{plan}");
+    } else if schedule_only {
         for (i, r) in schedule.enumerate() {
             let (stage, vts) = r.expect("should be resolvable");
             print!("{i} - {stage}:\t");
@@ -81,7 +99,17 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
             );
         }
     } else {
-        let executor = nasl_std_functions();
+        let mut executor = nasl_std_functions();
+        if let Some(path) = plugins {
+            let loaded = DynamicLoader::new(&path)
+                .with_signature_check(plugin_signature_check)
+                .load(&mut executor)
+                .map_err(|e| CliError {
+                    filename: path.to_string_lossy().to_string(),
+                    kind: CliErrorKind::Corrupt(e.to_string()),
+                })?;
+            info!(?loaded, "loaded builtin function plugins");
+        }
         let runner: ScanRunner<(_, _)> =
             ScanRunner::new(&storage, &loader, &executor, schedule, &scan).unwrap();
         let mut results = Box::pin(runner.stream());
@@ -102,6 +130,7 @@ async fn scan(args: &clap::ArgMatches) -> Result<(), CliError> {
                 }
             }
         }
+        executor.shutdown().await;
     }
 
     Ok(())
@@ -114,12 +143,30 @@ async fn script(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
         _ => unreachable!("path is set to required"),
     };
     let target = args.get_one::<String>("target").cloned();
+    let cache = args.get_one::<PathBuf>("compile-cache").cloned();
+    let parallel = args
+        .get_one::<bool>("parallel")
+        .cloned()
+        .unwrap_or_default();
+    let cassette = match (
+        args.get_one::<PathBuf>("record").cloned(),
+        args.get_one::<PathBuf>("replay").cloned(),
+    ) {
+        (Some(path), _) => Some(interpret::CassetteFile::Record(path)),
+        (None, Some(path)) => Some(interpret::CassetteFile::Replay(path)),
+        (None, None) => None,
+    };
+    let trace = args.get_one::<PathBuf>("trace").cloned();
     Some(
         interpret::run(
             &Db::InMemory,
             feed.clone(),
             &script.to_string(),
             target.clone(),
+            cache,
+            parallel,
+            cassette,
+            trace,
         )
         .await,
     )
@@ -128,6 +175,10 @@ pub fn extend_args(cmd: Command) -> Command {
     cmd.subcommand(crate::add_verbose(
         Command::new("execute")
             .about("Executes either a script or scan.")
+            .after_help("Examples:
+  scannerctl execute script examples/hello.nasl
+  scannerctl execute script -p /var/lib/openvas/plugins -t 192.168.0.1 1.3.6.1.4.1.25623.1.0.10330
+  scannerctl execute scan -p /var/lib/openvas/plugins --dry-run scan.json")
             .subcommand(
                 Command::new("script")
                     .about(
@@ -141,7 +192,33 @@ When ID is used than a valid feed path must be given within the path parameter."
                             .value_parser(value_parser!(PathBuf)),
                     )
                     .arg(Arg::new("script").required(true))
-                    .arg(arg!(-t --target <HOST> "Target to scan").required(false)),
+                    .arg(arg!(-t --target <HOST> "Target to scan").required(false))
+                    .arg(
+                        arg!(--"compile-cache" <DIR> "Directory to cache compiled scripts in.")
+                            .required(false)
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        arg!(--parallel "Experimental: interprets independent top-level statements concurrently.")
+                            .required(false)
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        arg!(--record <FILE> "Records the calls of network functions into the given cassette.")
+                            .required(false)
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        arg!(--replay <FILE> "Replays the calls of network functions from the given cassette instead of accessing the network.")
+                            .required(false)
+                            .conflicts_with("record")
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(
+                        arg!(--trace <FILE> "Records each executed statement with the changed variables and returned value into the given file.")
+                            .required(false)
+                            .value_parser(value_parser!(PathBuf)),
+                    ),
             )
             .subcommand(
                 Command::new("scan")
@@ -153,8 +230,21 @@ When ID is used than a valid feed path must be given within the path parameter."
                             .required(true)
                             .value_parser(value_parser!(PathBuf)),
                     )
+                    .arg(
+                        arg!(--overlay <DIR> "Directory stacked on top of the feed, files of a later overlay take precedence.")
+                            .required(false)
+                            .action(ArgAction::Append)
+                            .value_parser(value_parser!(PathBuf)),
+                    )
                     .arg(arg!(--schedule "Prints just the schedule without executing the scan").required(false).action(ArgAction::SetTrue))
+                    .arg(arg!(--"dry-run" "Prints the VTs that would run on each host as json without executing the scan").required(false).action(ArgAction::SetTrue))
                     .arg(arg!(-i --input "Parses scan json from stdin.").required(false).action(ArgAction::SetTrue))
+                    .arg(
+                        arg!(--plugins <DIR> "Directory containing shared objects defining additional builtin functions.")
+                            .required(false)
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(arg!(--"plugin-signature-check" "Requires each plugin to be listed in the signed sha256sums file of the plugin directory.").required(false).action(ArgAction::SetTrue))
                     .arg(Arg::new("json").required(false).value_parser(value_parser!(PathBuf)))
             )
             // this is here for downwards compatible reasons and should be moved to the script
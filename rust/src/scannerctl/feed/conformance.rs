@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{Conformance, ReferenceSource};
+
+use crate::{CliError, CliErrorKind};
+
+pub struct Options {
+    pub path: PathBuf,
+    pub target: Option<String>,
+    pub openvas_nasl: Option<PathBuf>,
+    pub golden: bool,
+    pub record: bool,
+    pub ignored: Vec<String>,
+    pub json: bool,
+}
+
+pub async fn run(options: Options) -> Result<(), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: options.path.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let openvas_nasl = match (options.golden, options.openvas_nasl) {
+        (true, _) => None,
+        (false, Some(x)) => Some(x),
+        (false, None) => Conformance::find_openvas_nasl(),
+    };
+    match &openvas_nasl {
+        Some(x) => tracing::info!(binary=?x, "using openvas-nasl as reference"),
+        None => tracing::info!("using golden files as reference"),
+    }
+    let mut suite = Conformance::new(&options.path)
+        .with_openvas_nasl(openvas_nasl)
+        .with_ignored(options.ignored);
+    if let Some(target) = &options.target {
+        suite = suite.with_target(target);
+    }
+    if options.record {
+        for case in suite.cases().map_err(|e| corrupt(e.to_string()))? {
+            suite.record(&case).map_err(|e| corrupt(e.to_string()))?;
+            println!("recorded {}", suite.golden_path(&case).display());
+        }
+        return Ok(());
+    }
+    let report = suite.run().await.map_err(|e| corrupt(e.to_string()))?;
+    if options.json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+        return Ok(());
+    }
+    for case in &report.cases {
+        let status = match (&case.error, case.reference) {
+            (Some(_), _) => "error",
+            (None, None) => "no reference",
+            (None, Some(_)) if case.differences.is_empty() => "ok",
+            (None, Some(_)) => "differs",
+        };
+        let source = match case.reference {
+            Some(ReferenceSource::Openvas) => " (openvas-nasl)",
+            Some(ReferenceSource::Golden) => " (golden)",
+            None => "",
+        };
+        println!("{}: {status}{source}", case.name);
+        if let Some(error) = &case.error {
+            println!("    {error}");
+        }
+        for difference in &case.differences {
+            println!("    {difference}");
+        }
+    }
+    println!(
+        "{} of {} compared cases match ({:.1}%).",
+        report.matching(),
+        report.compared(),
+        report.parity() * 100.0
+    );
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{KbGraph, KbKey, NaslFileFinder};
+
+use crate::{CliError, CliErrorKind};
+
+fn print(kind: &str, keys: &[KbKey]) {
+    for x in keys {
+        println!("{kind:<10} {}  {}", x.key, x.files.join(", "));
+    }
+}
+
+pub fn run(path: PathBuf, json: bool, ignored: Vec<String>) -> Result<(), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: path.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let base = path.to_string_lossy().to_string();
+    let finder = NaslFileFinder::new(&base, true);
+    let files = NaslFileFinder::new(&base, true)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.to_string()))?;
+    tracing::info!(files = files.len(), "collecting KB keys");
+    let report = KbGraph::new(&finder, files).report(&ignored);
+    if json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+        return Ok(());
+    }
+    print("unwritten", &report.unwritten);
+    print("unread", &report.unread);
+    println!(
+        "{} files write {} and read {} keys: {} keys are never written, {} keys are never read.",
+        report.files,
+        report.written,
+        report.read,
+        report.unwritten.len(),
+        report.unread.len()
+    );
+    for (file, reason) in &report.unparsable {
+        tracing::warn!(file, reason, "unable to analyze");
+    }
+    Ok(())
+}
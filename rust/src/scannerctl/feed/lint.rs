@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{LintRule, LintSeverity, NaslFileFinder, TagLinter};
+
+use crate::{CliError, CliErrorKind};
+
+pub fn run(
+    path: PathBuf,
+    json: bool,
+    severities: Vec<(LintRule, LintSeverity)>,
+) -> Result<(), CliError> {
+    let filename = path.to_string_lossy().to_string();
+    let corrupt = |e: String| CliError {
+        filename: filename.clone(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let finder = NaslFileFinder::new(&filename, true);
+    let scripts = NaslFileFinder::new(&filename, true)
+        .filter_map(|x| match x {
+            Ok(x) if x.ends_with(".nasl") => Some(Ok(x)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.to_string()))?;
+    tracing::info!(scripts = scripts.len(), "linting script tags");
+    let linter = severities
+        .into_iter()
+        .fold(TagLinter::default(), |linter, (rule, severity)| {
+            linter.with_severity(rule, severity)
+        });
+    let report = linter.lint(&finder, scripts);
+    if json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+    } else {
+        for finding in &report.findings {
+            println!("{finding}");
+        }
+        println!(
+            "{} scripts linted: {} errors, {} warnings, {} infos.",
+            report.scripts,
+            report.count(LintSeverity::Error),
+            report.count(LintSeverity::Warning),
+            report.count(LintSeverity::Info)
+        );
+    }
+    for (file, reason) in &report.unparsable {
+        tracing::warn!(file, reason, "unable to lint");
+    }
+    if report.count(LintSeverity::Error) > 0 {
+        return Err(CliError {
+            filename,
+            kind: CliErrorKind::PartialFailure {
+                failed: report.failed_scripts(),
+                total: report.scripts,
+            },
+        });
+    }
+    Ok(())
+}
+
+/// Parses a severity override in the form `<rule>=<severity>`.
+pub fn parse_severity(value: &str) -> Result<(LintRule, LintSeverity), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: String::new(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let (rule, severity) = value
+        .split_once('=')
+        .ok_or_else(|| corrupt(format!("expected <rule>=<severity>, got {value}")))?;
+    Ok((
+        rule.trim().parse().map_err(corrupt)?,
+        severity.trim().parse().map_err(corrupt)?,
+    ))
+}
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{FileMetrics, MetricsReport, NaslFileFinder};
+
+use crate::{CliError, CliErrorKind};
+
+/// Metrics the files can be ordered by
+pub const SORT_BY: [&str; 6] = [
+    "complexity",
+    "statements",
+    "nesting",
+    "network",
+    "fan-in",
+    "fan-out",
+];
+
+fn metric(sort_by: &str) -> fn(&FileMetrics) -> usize {
+    match sort_by {
+        "statements" => |x| x.statements,
+        "nesting" => |x| x.max_nesting,
+        "network" => |x| x.network_calls,
+        "fan-in" => |x| x.fan_in,
+        "fan-out" => |x| x.fan_out,
+        _ => |x| x.complexity,
+    }
+}
+
+pub fn run(path: PathBuf, json: bool, sort_by: &str, top: usize) -> Result<(), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: path.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let base = path.to_string_lossy().to_string();
+    let finder = NaslFileFinder::new(&base, true);
+    let files = NaslFileFinder::new(&base, true)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.to_string()))?;
+    tracing::info!(files = files.len(), "collecting metrics");
+    let mut report = MetricsReport::new(&finder, files);
+    if json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+        return Ok(());
+    }
+    let metric = metric(sort_by);
+    report.files.sort_by_key(|x| std::cmp::Reverse(metric(x)));
+    println!(
+        "{:>10} {:>10} {:>7} {:>7} {:>6} {:>7}  file",
+        "complexity", "statements", "nesting", "network", "fan-in", "fan-out"
+    );
+    for x in report.files.iter().take(top) {
+        println!(
+            "{:>10} {:>10} {:>7} {:>7} {:>6} {:>7}  {}",
+            x.complexity, x.statements, x.max_nesting, x.network_calls, x.fan_in, x.fan_out, x.file
+        );
+    }
+    let summary = &report.summary;
+    println!(
+        "{} files contain {} statements and {} network calls, complexity {} in total and {} at most, nesting {} at most.",
+        summary.files,
+        summary.statements,
+        summary.network_calls,
+        summary.complexity,
+        summary.max_complexity,
+        summary.max_nesting
+    );
+    for (file, reason) in &report.unparsable {
+        tracing::warn!(file, reason, "unable to analyze");
+    }
+    Ok(())
+}
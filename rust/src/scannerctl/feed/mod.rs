@@ -2,7 +2,16 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
+pub mod conformance;
+pub mod kb_graph;
+pub mod lint;
+pub mod metrics;
+pub mod parity;
+pub mod taint;
+pub mod transpile;
+pub mod trial;
 pub mod update;
+pub mod upload;
 use std::{
     io,
     path::{Path, PathBuf},
@@ -21,9 +30,10 @@ use scannerlib::{
     },
 };
 
-use scannerlib::feed::{FeedReplacer, ReplaceCommand};
 use scannerlib::storage::{item::PerItemDispatcher, StorageError};
 
+use update::FeedLoader;
+
 use crate::{get_path_from_openvas, notusupdate, read_openvas_config, CliError, CliErrorKind};
 
 pub fn extend_args(cmd: Command) -> Command {
@@ -32,21 +42,103 @@ pub fn extend_args(cmd: Command) -> Command {
             Command::new("feed")
                 .about("Handles feed related tasks")
                 .subcommand_required(true)
+                .after_help("Examples:
+  scannerctl feed update --vts-path /var/lib/openvas/plugins --notus-path /var/lib/notus/advisories
+  scannerctl feed lint-tags -p /var/lib/openvas/plugins -s unknown-tag=off
+  scannerctl feed metrics -p /var/lib/openvas/plugins --sort network --top 10")
                 .subcommand(Command::new("update")
                 .about("Runs nasl scripts in description mode and updates data into redis")
                 .arg(arg!(-v --"vts-only" "Load only nvts into redis cache").required(false).action(ArgAction::SetTrue))
                 .arg(arg!(-n --"notus-only" "Load only Notus advisories into redis cache").required(false).action(ArgAction::SetTrue))
                 .arg(arg!(--"vts-path" <FILE> "Path to the feed.").required(false)
                      .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--overlay <DIR> "Directory stacked on top of the feed, files of a later overlay take precedence. Requires its own sha256sums file.").required(false)
+                     .action(ArgAction::Append)
+                     .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--index <FILE> "Packs the feed into the given index file and loads the scripts from it via mmap.").required(false)
+                     .conflicts_with("overlay")
+                     .value_parser(value_parser!(PathBuf)))
                 .arg(arg!(--"notus-path" <FILE> "Path to the notus advisories.").required(false)
                      .value_parser(value_parser!(PathBuf)))
                 .arg(arg!(-x --"signature-check" "Enable NASL signature check.").required(false).action(ArgAction::SetTrue))
                 .arg(arg!(-r --redis <VALUE> "Redis url. Must either start `unix://` or `redis://`.").required(false))
+                .arg(arg!(--"include-graph" <FILE> "Stores the include graph of the feed as json into the given file.").required(false)
+                     .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--report <FILE> "Stores the outcome and duration of each nasl script as json into the given file.").required(false)
+                     .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--openvasd <URL> "Uploads the nvts to a remote openvasd instead of redis.").required(false)
+                     .conflicts_with_all(["redis", "notus-only"]))
+                .arg(arg!(--"api-key" <KEY> "API key used to authenticate against the remote openvasd.").required(false)
+                     .requires("openvasd"))
                 )
                 .subcommand(Command::new("transform")
                 .about("Runs nasl scripts in description mode and returns it as a json array into stdout")
                 .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
                     .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--overlay <DIR> "Directory stacked on top of the feed, files of a later overlay take precedence. Requires its own sha256sums file.").required(false)
+                     .action(ArgAction::Append)
+                     .value_parser(value_parser!(PathBuf)))
+                )
+                .subcommand(Command::new("parity")
+                .about("Lists the functions called within the feed that are not builtin, ranked by the number of dependent scripts")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--json "Prints the report as json.").required(false).action(ArgAction::SetTrue))
+                .arg(arg!(-l --limit <AMOUNT> "Prints only the given amount of missing functions.").required(false)
+                    .value_parser(value_parser!(usize)))
+                )
+                .subcommand(Command::new("lint-tags")
+                .about("Checks the script_tag values of each script against the feed conventions")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--json "Prints the findings as json.").required(false).action(ArgAction::SetTrue))
+                .arg(arg!(-s --severity <RULE_SEVERITY> "Overrides the severity of a rule, e.g. `unknown-tag=off`. The severity is one of off, info, warning or error.").required(false)
+                    .action(ArgAction::Append))
+                )
+                .subcommand(Command::new("kb-graph")
+                .about("Lists the KB keys read by scripts that no script writes and the keys written that no script reads")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-i --ignore <PREFIX> "Ignores KB keys starting with the prefix, e.g. keys written by the scanner.").required(false).action(ArgAction::Append))
+                .arg(arg!(--json "Prints the report as json.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("metrics")
+                .about("Reports complexity metrics per script and inc file to find refactor candidates and costly VTs")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-s --sort <METRIC> "Metric the files are ordered by.").required(false)
+                    .value_parser(metrics::SORT_BY).default_value("complexity"))
+                .arg(arg!(-n --top <AMOUNT> "Amount of files printed.").required(false)
+                    .value_parser(value_parser!(usize)).default_value("20"))
+                .arg(arg!(--json "Prints the metrics of all files as json.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("lint-taint")
+                .about("Reports data of the target or the user flowing unsanitized into commands, SQL statements or raw sends")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-s --sanitizer <FUNCTION> "Treats the return value of the function as sanitized, e.g. a feed specific escape helper.").required(false).action(ArgAction::Append))
+                .arg(arg!(--json "Prints the findings as json.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("try")
+                .about("Stacks a directory of modified scripts on top of the feed and checks the syntax and description run of the changed scripts and the scripts including them")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-c --changes <DIR> "Directory containing the modified scripts.").required(true)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--json "Prints the report as json.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("conformance")
+                .about("Runs a suite of nasl scripts and compares the KB items and results with openvas-nasl or the golden files of the suite")
+                .arg(arg!(-p --path <DIR> "Path to the suite.").required(true)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-t --target <HOST> "Target the scripts run against.").required(false))
+                .arg(arg!(--"openvas-nasl" <FILE> "Path to openvas-nasl, by default it is searched within PATH.").required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--golden "Compares with the golden files even when openvas-nasl is available.").required(false).action(ArgAction::SetTrue)
+                    .conflicts_with_all(["openvas-nasl", "record"]))
+                .arg(arg!(--record "Records the golden files of the suite with openvas-nasl.").required(false).action(ArgAction::SetTrue))
+                .arg(arg!(-i --ignore <PREFIX> "Ignores KB items starting with the prefix.").required(false).action(ArgAction::Append))
+                .arg(arg!(--json "Prints the report as json.").required(false).action(ArgAction::SetTrue))
                 )
                 .subcommand(Command::new("transpile")
                 .about("Transforms each nasl script and inc file based on the given rules.")
@@ -54,6 +146,26 @@ pub fn extend_args(cmd: Command) -> Command {
                     .value_parser(value_parser!(PathBuf)))
                 .arg(arg!(-r --rules <FILE> "Path to transpiler rules.").required(true)
                     .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--"dry-run" "Prints the changed lines per file instead of writing them.").required(false).action(ArgAction::SetTrue))
+                .arg(arg!(-w --workers <AMOUNT> "Amount of files transpiled in parallel, by default one per CPU.").required(false)
+                    .value_parser(value_parser!(usize)))
+                .arg(arg!(--"preserve-times" "Keeps the access and modification times of changed files.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("deprecate")
+                .about("Rewrites the nasl scripts of the given oids to the deprecated stub.")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-o --oid <OID> "OID of the script to deprecate.").required(true)
+                    .action(ArgAction::Append))
+                .arg(arg!(--"dry-run" "Prints the changed lines per file instead of writing them.").required(false).action(ArgAction::SetTrue))
+                )
+                .subcommand(Command::new("rename-kb")
+                .about("Renames KB keys used by the nasl scripts and inc files based on a mapping file.")
+                .arg(arg!(-p --path <FILE> "Path to the feed.") .required(false)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-m --mapping <FILE> "Path to the mapping of old to new keys.").required(true)
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--"dry-run" "Prints the changed lines per file instead of writing them.").required(false).action(ArgAction::SetTrue))
                 )
         ))
 }
@@ -76,9 +188,15 @@ pub async fn update_vts(
     signature_check: bool,
     args: &clap::ArgMatches,
 ) -> Result<(), CliError> {
-    let path = get_vts_path("vts-path", args);
+    let path = get_vts_path("vts-path", args)?;
+    let loader = get_update_loader(args)?;
     let dispatcher = get_dispatcher(redis, &path, FEEDUPDATE_SELECTOR)?;
-    update::run(dispatcher, path, signature_check).await
+    let report = args.get_one::<PathBuf>("report").map(|x| x.as_path());
+    update::run(dispatcher, &loader, signature_check, report).await?;
+    if let Some(output) = args.get_one::<PathBuf>("include-graph") {
+        update::store_include_graph(&loader, output)?;
+    }
+    Ok(())
 }
 
 pub async fn update_notus(
@@ -103,14 +221,38 @@ pub async fn update_notus(
 }
 
 pub async fn update(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let signature_check = args
+        .get_one::<bool>("signature-check")
+        .cloned()
+        .unwrap_or(false);
+    if let Some(url) = args.get_one::<String>("openvasd") {
+        let loader = match get_update_loader(args) {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+        let api_key = args.get_one::<String>("api-key").map(|x| x.as_str());
+        let report = args.get_one::<PathBuf>("report").map(|x| x.as_path());
+        return Some(upload::run(url, api_key, &loader, signature_check, report).await);
+    }
     let redis = match args.get_one::<String>("redis").cloned() {
         Some(x) => x,
         None => {
-            let config =
-                read_openvas_config().expect("openvas -s must be executable when path is not set");
-            let dba = config
-                .get("default", "db_address")
-                .expect("openvas -s must contain db_address");
+            let dba = match read_openvas_config().and_then(|config| {
+                config
+                    .get("default", "db_address")
+                    .ok_or_else(|| CliErrorKind::Openvas {
+                        args: "-s".to_owned().into(),
+                        err_msg: "missing db_address".to_owned(),
+                    })
+            }) {
+                Ok(x) => x,
+                Err(kind) => {
+                    return Some(Err(CliError {
+                        filename: "".to_string(),
+                        kind,
+                    }))
+                }
+            };
 
             if dba.starts_with("redis://") || dba.starts_with("unix://") {
                 dba
@@ -121,10 +263,6 @@ pub async fn update(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
             }
         }
     };
-    let signature_check = args
-        .get_one::<bool>("signature-check")
-        .cloned()
-        .unwrap_or(false);
 
     let loadup_notus_only = args.get_one::<bool>("notus-only").cloned().unwrap_or(false);
 
@@ -147,12 +285,33 @@ pub async fn update(args: &clap::ArgMatches) -> Option<Result<(), CliError>> {
     }
 }
 
-fn get_vts_path(key: &str, args: &clap::ArgMatches) -> PathBuf {
-    args.get_one::<PathBuf>(key).cloned().unwrap_or_else(|| {
-        let config =
-            read_openvas_config().expect("openvas -s must be executable when path is not set");
-        get_path_from_openvas(config)
-    })
+/// Returns a loader of the feed path stored in key stacked with the given overlays.
+///
+/// When the path is an archive the feed is read from the archive instead.
+fn get_loader(key: &str, args: &clap::ArgMatches) -> Result<FeedLoader, CliError> {
+    let overlays = args.get_many::<PathBuf>("overlay").into_iter().flatten();
+    FeedLoader::new(get_vts_path(key, args)?, overlays)
+}
+
+/// Returns the loader of the feed to update, the feed is packed into the index first when set.
+fn get_update_loader(args: &clap::ArgMatches) -> Result<FeedLoader, CliError> {
+    let loader = get_loader("vts-path", args)?;
+    match args.get_one::<PathBuf>("index") {
+        Some(index) => loader.index(index),
+        None => Ok(loader),
+    }
+}
+
+fn get_vts_path(key: &str, args: &clap::ArgMatches) -> Result<PathBuf, CliError> {
+    match args.get_one::<PathBuf>(key).cloned() {
+        Some(x) => Ok(x),
+        None => read_openvas_config()
+            .and_then(get_path_from_openvas)
+            .map_err(|kind| CliError {
+                filename: "".to_string(),
+                kind,
+            }),
+    }
 }
 
 pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
@@ -160,11 +319,14 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
     match args.subcommand() {
         Some(("update", args)) => update(args).await,
         Some(("transform", args)) => {
-            let path = get_vts_path("path", args);
+            let loader = match get_loader("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
 
             let mut o = ArrayWrapper::new(io::stdout());
             let dispatcher = ItemDispatcher::as_dispatcher(&mut o);
-            Some(match update::run(dispatcher, path, false).await {
+            Some(match update::run(dispatcher, &loader, false, None).await {
                 Ok(_) => o.end().map_err(StorageError::from).map_err(|se| CliError {
                     filename: "".to_string(),
                     kind: se.into(),
@@ -173,59 +335,153 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
             })
         }
 
+        Some(("parity", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let json = crate::json_output(args);
+            let limit = args.get_one::<usize>("limit").cloned();
+            Some(parity::run(path, json, limit))
+        }
+
+        Some(("lint-tags", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let json = crate::json_output(args);
+            let severities = match args
+                .get_many::<String>("severity")
+                .into_iter()
+                .flatten()
+                .map(|x| lint::parse_severity(x))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            Some(lint::run(path, json, severities))
+        }
+
+        Some(("kb-graph", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let json = crate::json_output(args);
+            let ignored = args
+                .get_many::<String>("ignore")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default();
+            Some(kb_graph::run(path, json, ignored))
+        }
+
+        Some(("metrics", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let json = crate::json_output(args);
+            let sort_by = args.get_one::<String>("sort")?;
+            let top = args.get_one::<usize>("top").cloned().unwrap_or(20);
+            Some(metrics::run(path, json, sort_by, top))
+        }
+
+        Some(("lint-taint", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let json = crate::json_output(args);
+            let sanitizers = args
+                .get_many::<String>("sanitizer")
+                .map(|x| x.cloned().collect())
+                .unwrap_or_default();
+            Some(taint::run(path, json, sanitizers))
+        }
+
+        Some(("try", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let changes = match args.get_one::<PathBuf>("changes").cloned() {
+                Some(x) => x,
+                None => unreachable!("changes is set to required"),
+            };
+            let json = crate::json_output(args);
+            Some(trial::run(path, changes, json).await)
+        }
+
+        Some(("conformance", args)) => {
+            let flag = |id: &str| args.get_one::<bool>(id).cloned().unwrap_or_default();
+            let options = conformance::Options {
+                path: match args.get_one::<PathBuf>("path").cloned() {
+                    Some(x) => x,
+                    None => unreachable!("path is set to required"),
+                },
+                target: args.get_one::<String>("target").cloned(),
+                openvas_nasl: args.get_one::<PathBuf>("openvas-nasl").cloned(),
+                golden: flag("golden"),
+                record: flag("record"),
+                ignored: args
+                    .get_many::<String>("ignore")
+                    .map(|x| x.cloned().collect())
+                    .unwrap_or_default(),
+                json: crate::json_output(args),
+            };
+            Some(conformance::run(options).await)
+        }
+
         Some(("transpile", args)) => {
-            let path = get_vts_path("path", args);
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
             let rules = match args.get_one::<PathBuf>("rules").cloned() {
                 Some(x) => x,
                 None => unreachable!("rules is set to required"),
             };
+            let dry_run = args.get_one::<bool>("dry-run").cloned().unwrap_or_default();
+            let workers = args.get_one::<usize>("workers").cloned();
+            let preserve_times = args.get_flag("preserve-times");
+            Some(transpile::run(
+                path,
+                rules,
+                dry_run,
+                workers,
+                preserve_times,
+                verbose,
+            ))
+        }
 
-            #[derive(serde::Deserialize, serde::Serialize)]
-            struct Wrapper {
-                cmds: Vec<ReplaceCommand>,
-            }
+        Some(("deprecate", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let oids = args
+                .get_many::<String>("oid")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let dry_run = args.get_one::<bool>("dry-run").cloned().unwrap_or_default();
+            Some(transpile::deprecate(path, oids, dry_run, verbose))
+        }
 
-            let rules = std::fs::read_to_string(rules).unwrap();
-            let rules: Wrapper = toml::from_str(&rules).unwrap();
-            let rules = rules.cmds;
-            let base = path.to_str().unwrap_or_default();
-            for r in FeedReplacer::new(base, &rules) {
-                let name = r.unwrap();
-                if let Some((name, content)) = name {
-                    use std::io::Write;
-                    let f = std::fs::OpenOptions::new()
-                        .write(true)
-                        .truncate(true)
-                        .open(&name)
-                        .map_err(|e| {
-                            let kind = CliErrorKind::Corrupt(format!("unable to open {name}: {e}"));
-                            CliError {
-                                filename: name.clone(),
-                                kind,
-                            }
-                        });
-                    match f.and_then(|mut f| {
-                        f.write_all(content.as_bytes()).map_err(|e| {
-                            let kind =
-                                CliErrorKind::Corrupt(format!("unable to write {name}: {e}"));
-                            CliError {
-                                filename: name.clone(),
-                                kind,
-                            }
-                        })
-                    }) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            return Some(Err(e));
-                        }
-                    }
-
-                    if verbose > 0 {
-                        eprintln!("changed {name}");
-                    }
-                }
-            }
-            Some(Ok(()))
+        Some(("rename-kb", args)) => {
+            let path = match get_vts_path("path", args) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let mapping = match args.get_one::<PathBuf>("mapping").cloned() {
+                Some(x) => x,
+                None => unreachable!("mapping is set to required"),
+            };
+            let dry_run = args.get_one::<bool>("dry-run").cloned().unwrap_or_default();
+            Some(transpile::rename_kb(path, mapping, dry_run, verbose))
         }
         _ => unreachable!("subcommand_required prevents None"),
     }
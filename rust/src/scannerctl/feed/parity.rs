@@ -0,0 +1,49 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{FeedAnalysis, NaslFileFinder};
+use scannerlib::nasl::nasl_std_functions;
+
+use crate::{CliError, CliErrorKind};
+
+pub fn run(path: PathBuf, json: bool, limit: Option<usize>) -> Result<(), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: path.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let base = path.to_string_lossy().to_string();
+    let finder = NaslFileFinder::new(&base, true);
+    let scripts = NaslFileFinder::new(&base, true)
+        .filter_map(|x| match x {
+            Ok(x) if x.ends_with(".nasl") => Some(Ok(x)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.to_string()))?;
+    tracing::info!(scripts = scripts.len(), "analyzing feed");
+    let mut report = FeedAnalysis::new(&finder, scripts).report(&nasl_std_functions());
+    if let Some(limit) = limit {
+        report.missing.truncate(limit);
+    }
+    if json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+        return Ok(());
+    }
+    println!(
+        "{} scripts call {} functions, {} of them are builtin.",
+        report.scripts, report.called, report.implemented
+    );
+    println!("{:>5} {:>8} {:>6}  name", "rank", "scripts", "files");
+    for (i, x) in report.missing.iter().enumerate() {
+        println!("{:>5} {:>8} {:>6}  {}", i + 1, x.scripts, x.files, x.name);
+    }
+    for (file, reason) in &report.unparsable {
+        tracing::warn!(file, reason, "unable to analyze");
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::{NaslFileFinder, TaintLinter};
+
+use crate::{CliError, CliErrorKind};
+
+pub fn run(path: PathBuf, json: bool, sanitizers: Vec<String>) -> Result<(), CliError> {
+    let filename = path.to_string_lossy().to_string();
+    let corrupt = |e: String| CliError {
+        filename: filename.clone(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let finder = NaslFileFinder::new(&filename, true);
+    let files = NaslFileFinder::new(&filename, true)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.to_string()))?;
+    tracing::info!(files = files.len(), "analyzing data flow");
+    let linter = sanitizers
+        .iter()
+        .fold(TaintLinter::default(), |linter, x| linter.with_sanitizer(x));
+    let report = linter.lint(&finder, files);
+    if json {
+        let output = serde_json::to_string_pretty(&report).map_err(|e| corrupt(e.to_string()))?;
+        println!("{output}");
+    } else {
+        for finding in &report.findings {
+            println!("{finding}");
+        }
+        println!(
+            "{} files analyzed: {} findings in {} files.",
+            report.files,
+            report.findings.len(),
+            report.failed_files()
+        );
+    }
+    for (file, reason) in &report.unparsable {
+        tracing::warn!(file, reason, "unable to analyze");
+    }
+    if !report.findings.is_empty() {
+        return Err(CliError {
+            filename,
+            kind: CliErrorKind::PartialFailure {
+                failed: report.failed_files(),
+                total: report.files,
+            },
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,246 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use scannerlib::feed::{
+    BatchReplacer, FeedDeprecator, FeedKbRenamer, FeedReplacer, ReplaceCommand,
+};
+use scannerlib::nasl::syntax::load_non_utf8_path;
+
+use crate::{CliError, CliErrorKind};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Wrapper {
+    cmds: Vec<ReplaceCommand>,
+}
+
+/// Amount of lines removed and added by a change
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct LineChanges {
+    removed: usize,
+    added: usize,
+}
+
+impl LineChanges {
+    /// Compares the lines of both versions after stripping the common beginning and end.
+    fn new(old: &str, new: &str) -> Self {
+        let old: Vec<_> = old.lines().collect();
+        let new: Vec<_> = new.lines().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        Self {
+            removed: old.len() - prefix - suffix,
+            added: new.len() - prefix - suffix,
+        }
+    }
+}
+
+fn load_rules(rules: &PathBuf) -> Result<Vec<ReplaceCommand>, CliError> {
+    let content = std::fs::read_to_string(rules).map_err(|e| CliError::load_error(e, rules))?;
+    toml::from_str::<Wrapper>(&content)
+        .map(|x| x.cmds)
+        .map_err(|e| CliError {
+            filename: rules.to_string_lossy().to_string(),
+            kind: CliErrorKind::Corrupt(e.to_string()),
+        })
+}
+
+fn write(name: &str, content: &str) -> Result<(), CliError> {
+    let corrupt = |e: String| CliError {
+        filename: name.to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(name)
+        .map_err(|e| corrupt(format!("unable to open {name}: {e}")))?;
+    f.write_all(content.as_bytes())
+        .map_err(|e| corrupt(format!("unable to write {name}: {e}")))
+}
+
+/// Applies the rules to each nasl script and inc file of the feed.
+///
+/// On a dry run the files are not changed, instead a summary of the changed lines per file is
+/// printed. Otherwise the files are handled on the given amount of worker threads, by default one
+/// per CPU, and the amount of replaced statements per rule is printed.
+pub fn run(
+    path: PathBuf,
+    rules: PathBuf,
+    dry_run: bool,
+    workers: Option<usize>,
+    preserve_times: bool,
+    verbose: u8,
+) -> Result<(), CliError> {
+    let rules = load_rules(&rules)?;
+    let base = path.to_str().unwrap_or_default();
+    if dry_run {
+        return apply(base, FeedReplacer::new(base, &rules), dry_run, verbose);
+    }
+    let mut replacer = BatchReplacer::new(base, &rules).with_preserved_times(preserve_times);
+    if let Some(workers) = workers {
+        replacer = replacer.with_workers(workers);
+    }
+    let summary = replacer.run().map_err(|e| CliError {
+        filename: base.to_string(),
+        kind: CliErrorKind::Corrupt(e.to_string()),
+    })?;
+    if verbose > 0 {
+        for name in &summary.changed {
+            eprintln!("changed {name}");
+        }
+    }
+    for (i, replaced) in summary.replaced.iter().enumerate() {
+        println!("rule {}: {replaced} statements replaced", i + 1);
+    }
+    println!(
+        "{} of {} files changed.",
+        summary.changed.len(),
+        summary.files
+    );
+    for (name, e) in &summary.failed {
+        eprintln!("{name}: {e}");
+    }
+    if summary.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError {
+            filename: base.to_string(),
+            kind: CliErrorKind::Corrupt(format!(
+                "{} files could not be transpiled",
+                summary.failed.len()
+            )),
+        })
+    }
+}
+
+/// Rewrites the scripts of the given oids to the deprecated stub.
+///
+/// Oids without a script within the feed are reported as an error after the found scripts are
+/// rewritten.
+pub fn deprecate(
+    path: PathBuf,
+    oids: Vec<String>,
+    dry_run: bool,
+    verbose: u8,
+) -> Result<(), CliError> {
+    let base = path.to_str().unwrap_or_default();
+    let mut deprecator = FeedDeprecator::new(base, oids);
+    apply(base, &mut deprecator, dry_run, verbose)?;
+    if deprecator.missing().is_empty() {
+        Ok(())
+    } else {
+        Err(CliError {
+            filename: base.to_string(),
+            kind: CliErrorKind::Corrupt(format!(
+                "no script found for {}",
+                deprecator.missing().join(", ")
+            )),
+        })
+    }
+}
+
+/// Renames the KB keys within the feed based on the mapping file.
+///
+/// The mapping file contains the old keys with their new key, e.g. `"www/banner" = "www/raw"`.
+/// Keys computed at runtime that may match an old key are printed afterwards as they have to be
+/// renamed manually.
+pub fn rename_kb(
+    path: PathBuf,
+    mapping: PathBuf,
+    dry_run: bool,
+    verbose: u8,
+) -> Result<(), CliError> {
+    let content =
+        std::fs::read_to_string(&mapping).map_err(|e| CliError::load_error(e, &mapping))?;
+    let mapping: BTreeMap<String, String> = toml::from_str(&content).map_err(|e| CliError {
+        filename: mapping.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e.to_string()),
+    })?;
+    let base = path.to_str().unwrap_or_default();
+    let mut renamer = FeedKbRenamer::new(base, mapping);
+    apply(base, &mut renamer, dry_run, verbose)?;
+    for key in renamer.dynamic() {
+        println!(
+            "{}:{}: {} uses the dynamic key {}",
+            key.file, key.line, key.function, key.key
+        );
+    }
+    if !renamer.dynamic().is_empty() {
+        println!(
+            "{} dynamic keys may need to be renamed manually.",
+            renamer.dynamic().len()
+        );
+    }
+    Ok(())
+}
+
+fn apply<I, E>(base: &str, changes: I, dry_run: bool, verbose: u8) -> Result<(), CliError>
+where
+    I: Iterator<Item = Result<Option<(String, String)>, E>>,
+    E: std::fmt::Display,
+{
+    let mut changed = 0;
+    let mut total = LineChanges::default();
+    for r in changes {
+        let (name, content) = match r {
+            Ok(Some(x)) => x,
+            Ok(None) => continue,
+            Err(e) => {
+                return Err(CliError {
+                    filename: base.to_string(),
+                    kind: CliErrorKind::Corrupt(e.to_string()),
+                })
+            }
+        };
+        changed += 1;
+        if dry_run {
+            let original = load_non_utf8_path(&name).map_err(|e| CliError {
+                filename: name.clone(),
+                kind: CliErrorKind::LoadError(e),
+            })?;
+            let changes = LineChanges::new(&original, &content);
+            total.removed += changes.removed;
+            total.added += changes.added;
+            println!("{name}: -{} +{}", changes.removed, changes.added);
+            continue;
+        }
+        write(&name, &content)?;
+        if verbose > 0 {
+            eprintln!("changed {name}");
+        }
+    }
+    if dry_run {
+        println!(
+            "{changed} files would be changed, -{} +{} lines.",
+            total.removed, total.added
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineChanges;
+
+    #[test]
+    fn line_changes() {
+        let changes = |old, new| {
+            let LineChanges { removed, added } = LineChanges::new(old, new);
+            (removed, added)
+        };
+        assert_eq!(changes("a\nb\nc", "a\nb\nc"), (0, 0));
+        assert_eq!(changes("a\nb\nc", "a\nx\nc"), (1, 1));
+        assert_eq!(changes("a\nb\nc", "a\nx\ny\nc"), (1, 2));
+        assert_eq!(changes("a\nb\nc", "a\nc"), (1, 0));
+        assert_eq!(changes("a\nb", "a\nb\nc"), (0, 1));
+        assert_eq!(changes("a\na", "a\na\na"), (0, 1));
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::PathBuf;
+
+use scannerlib::feed::Trial;
+use scannerlib::nasl::FSPluginLoader;
+
+use crate::{CliError, CliErrorKind};
+
+/// Stacks the changes on top of the feed and checks the changed files and their dependents.
+pub async fn run(path: PathBuf, changes: PathBuf, json: bool) -> Result<(), CliError> {
+    let filename = changes.to_string_lossy().to_string();
+    let loader = FSPluginLoader::new(path).with_overlays([&changes]);
+    let report = Trial::new(&loader).run().await?;
+    if json {
+        report.write(std::io::stdout())?;
+        println!();
+    } else {
+        for file in report.failures() {
+            println!("{}: {}", file.key, file.outcome);
+        }
+        println!(
+            "{} files checked, {} failed.",
+            report.total(),
+            report.failed()
+        );
+    }
+    if report.failed() > 0 {
+        return Err(CliError {
+            filename,
+            kind: CliErrorKind::PartialFailure {
+                failed: report.failed(),
+                total: report.total(),
+            },
+        });
+    }
+    Ok(())
+}
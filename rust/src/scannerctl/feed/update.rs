@@ -2,29 +2,145 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
 
 use scannerlib::storage::Dispatcher;
 use scannerlib::{
     feed,
-    nasl::{syntax::LoadError, FSPluginLoader},
+    nasl::{
+        syntax::{ArchiveLoader, ContentReader, LoadError, MappedLoader},
+        FSPluginLoader,
+    },
 };
 
 use crate::{CliError, CliErrorKind};
 
-pub async fn run<S>(storage: S, path: PathBuf, signature_check: bool) -> Result<(), CliError>
+/// The feed to update, either a directory including its overlays or an archive
+pub enum FeedLoader {
+    /// A feed directory
+    Directory(FSPluginLoader),
+    /// A feed archive
+    Archive(ArchiveLoader),
+    /// A memory mapped index of a feed directory
+    Mapped(MappedLoader),
+}
+
+impl FeedLoader {
+    /// Returns an archive loader when path is an archive, otherwise a directory loader stacked
+    /// with the given overlays.
+    pub fn new<I, P>(path: PathBuf, overlays: I) -> Result<Self, CliError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        if !ArchiveLoader::is_archive(&path) {
+            return Ok(Self::Directory(
+                FSPluginLoader::new(path).with_overlays(overlays),
+            ));
+        }
+        if overlays.into_iter().next().is_some() {
+            return Err(CliError {
+                filename: path.to_string_lossy().to_string(),
+                kind: CliErrorKind::Corrupt("overlays are not supported for archives".to_string()),
+            });
+        }
+        ArchiveLoader::open(&path)
+            .map(Self::Archive)
+            .map_err(|e| CliError {
+                filename: path.to_string_lossy().to_string(),
+                kind: e.into(),
+            })
+    }
+
+    /// Packs the feed directory into the index and maps the index into memory.
+    pub fn index(self, index: &Path) -> Result<Self, CliError> {
+        let root = match self {
+            Self::Directory(x) if x.overlays().is_empty() => x.root().to_owned(),
+            Self::Directory(x) => {
+                return Err(CliError {
+                    filename: x.root().to_string_lossy().to_string(),
+                    kind: CliErrorKind::Corrupt(
+                        "overlays are not supported for an index".to_string(),
+                    ),
+                })
+            }
+            Self::Archive(x) => {
+                return Err(CliError {
+                    filename: x.path().to_string_lossy().to_string(),
+                    kind: CliErrorKind::Corrupt("an index requires a feed directory".to_string()),
+                })
+            }
+            Self::Mapped(_) => return Ok(self),
+        };
+        let load_error = |e: LoadError| CliError {
+            filename: index.to_string_lossy().to_string(),
+            kind: e.into(),
+        };
+        let files = MappedLoader::build(&root, index).map_err(load_error)?;
+        tracing::info!(files, "packed {root:?} into {index:?}");
+        MappedLoader::open(index)
+            .map(Self::Mapped)
+            .map_err(load_error)
+    }
+}
+
+/// Runs each script of the feed in description mode.
+///
+/// When output is given the per file report is stored as json into it.
+pub async fn run<S>(
+    storage: S,
+    loader: &FeedLoader,
+    signature_check: bool,
+    output: Option<&Path>,
+) -> Result<(), CliError>
 where
     S: Sync + Send + Dispatcher,
 {
-    tracing::debug!("description run syntax in {path:?}.");
-    // needed to strip the root path so that we can build a relative path
-    // e.g. 2006/something.nasl
-    let loader = FSPluginLoader::new(path);
-    let verifier = feed::HashSumNameLoader::sha256(&loader)?;
-    let updater = feed::Update::init("1", 5, &loader, &storage, verifier);
+    match loader {
+        FeedLoader::Directory(x) => {
+            run_layers(storage, x, &x.layers(), signature_check, output).await
+        }
+        FeedLoader::Archive(x) => {
+            run_layers(storage, x, std::slice::from_ref(x), signature_check, output).await
+        }
+        FeedLoader::Mapped(x) => {
+            run_layers(storage, x, std::slice::from_ref(x), signature_check, output).await
+        }
+    }
+}
+
+/// Runs each script of the loader in description mode.
+///
+/// The loader is used to load the scripts while the layers are used to verify them, for a feed
+/// without overlays the layers only contain the loader itself.
+async fn run_layers<S, L>(
+    storage: S,
+    loader: &L,
+    layers: &[L],
+    signature_check: bool,
+    output: Option<&Path>,
+) -> Result<(), CliError>
+where
+    S: Sync + Send + Dispatcher,
+    L: ContentReader,
+{
+    let root = loader.root_path().map_err(|e| CliError {
+        filename: "".to_string(),
+        kind: e.into(),
+    })?;
+    tracing::debug!("description run syntax in {root:?}.");
+    let verifier = feed::LayeredNameLoader::sha256(layers)?;
+    let updater = feed::Update::init("1", 5, loader, &storage, verifier);
 
     if signature_check {
-        match updater.verify_signature() {
+        // each layer has its own signed sums file
+        match feed::Keyring::from_env().and_then(|keyring| {
+            layers
+                .iter()
+                .try_for_each(|x| feed::check_content_signature(&keyring, x))
+        }) {
             Ok(_) => tracing::info!("Signature check succsessful"),
             Err(feed::VerifyError::MissingKeyring) => {
                 tracing::warn!("Signature check enabled but missing keyring");
@@ -49,7 +165,73 @@ where
         tracing::warn!("Signature check disabled");
     }
 
-    updater.perform_update().await?;
+    // a broken script must not prevent the update of the others
+    let mut results = Box::pin(updater.report_stream());
+    let mut report = feed::UpdateReport::default();
+    while let Some((result, file)) = results.next().await {
+        match result {
+            Ok(key) => tracing::trace!(key, "updated"),
+            Err(e) if matches!(e.kind, feed::UpdateErrorKind::StorageError(_)) => {
+                return Err(e.into())
+            }
+            Err(e) => tracing::warn!("{}", CliError::from(e)),
+        }
+        report.push(file);
+    }
+    if let Some(output) = output {
+        store_report(&report, output)?;
+    }
+    if report.failed() > 0 {
+        return Err(CliError {
+            filename: root,
+            kind: CliErrorKind::PartialFailure {
+                failed: report.failed(),
+                total: report.total(),
+            },
+        });
+    }
+
+    Ok(())
+}
 
+/// Stores the per file report of a feed update as json in output.
+fn store_report(report: &feed::UpdateReport, output: &Path) -> Result<(), CliError> {
+    let file = std::fs::File::create(output).map_err(|e| CliError::load_error(e, output))?;
+    report
+        .write(std::io::BufWriter::new(file))
+        .map_err(|e| CliError::load_error(e.into(), output))?;
+    tracing::info!(
+        files = report.total(),
+        failed = report.failed(),
+        "stored update report in {output:?}"
+    );
+    Ok(())
+}
+
+/// Builds the include graph of the feed including its overlays and stores it as json in output.
+pub fn store_include_graph(loader: &FeedLoader, output: &Path) -> Result<(), CliError> {
+    let graph = match loader {
+        FeedLoader::Directory(x) => {
+            tracing::debug!("building include graph of {:?}.", x.root());
+            let layers = x.layers();
+            let verifier = feed::LayeredNameLoader::sha256(&layers)?;
+            feed::IncludeGraph::from_feed(x, verifier)?
+        }
+        FeedLoader::Archive(x) => {
+            tracing::debug!("building include graph of {:?}.", x.path());
+            let verifier = feed::HashSumNameLoader::sha256(x)?;
+            feed::IncludeGraph::from_feed(x, verifier)?
+        }
+        FeedLoader::Mapped(x) => {
+            tracing::debug!("building include graph of {:?}.", x.path());
+            let verifier = feed::HashSumNameLoader::sha256(x)?;
+            feed::IncludeGraph::from_feed(x, verifier)?
+        }
+    };
+    let file = std::fs::File::create(output).map_err(|e| CliError::load_error(e, output))?;
+    graph
+        .write(std::io::BufWriter::new(file))
+        .map_err(|e| CliError::load_error(e, output))?;
+    tracing::info!(files = graph.len(), "stored include graph in {output:?}");
     Ok(())
 }
@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::path::Path;
+
+use scannerlib::storage::{
+    json::{ArrayWrapper, ItemDispatcher},
+    StorageError,
+};
+
+use crate::{CliError, CliErrorKind};
+
+use super::update::{self, FeedLoader};
+
+/// Runs the description run of the feed and pushes the resulting NVTs as a json array to
+/// `{url}/vts` of a remote openvasd.
+///
+/// The body is the same json array that is printed by `feed transform`.
+pub async fn run(
+    url: &str,
+    api_key: Option<&str>,
+    loader: &FeedLoader,
+    signature_check: bool,
+    report: Option<&Path>,
+) -> Result<(), CliError> {
+    let mut buf = ArrayWrapper::new(Vec::new());
+    let dispatcher = ItemDispatcher::as_dispatcher(&mut buf);
+    update::run(dispatcher, loader, signature_check, report).await?;
+    buf.end()
+        .map_err(StorageError::from)
+        .map_err(|se| CliError {
+            filename: "".to_string(),
+            kind: se.into(),
+        })?;
+    let body = buf.into_inner();
+
+    let endpoint = format!("{}/vts", url.trim_end_matches('/'));
+    tracing::info!(%endpoint, bytes = body.len(), "uploading NVTs");
+    let mut request = reqwest::Client::new()
+        .post(&endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(key) = api_key {
+        request = request.header("x-api-key", key);
+    }
+    let upload_error = |e: String| CliError {
+        filename: endpoint.clone(),
+        kind: CliErrorKind::Corrupt(format!("unable to upload feed: {e}")),
+    };
+    let response = request
+        .send()
+        .await
+        .map_err(|e| upload_error(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(upload_error(format!(
+            "remote responded {}",
+            response.status()
+        )));
+    }
+    Ok(())
+}
@@ -5,14 +5,16 @@
 use std::{
     fs::{self},
     path::PathBuf,
+    sync::Arc,
 };
 
 use futures::StreamExt;
-use scannerlib::nasl::interpreter::CodeInterpreter;
+use scannerlib::nasl::interpreter::{CodeInterpreter, Trace};
 use scannerlib::nasl::{
     interpreter::{FunctionError, InterpretErrorKind},
     prelude::*,
-    syntax::{load_non_utf8_path, LoadError},
+    syntax::{load_non_utf8_path, CompileCache, LoadError},
+    utils::Cassette,
     Loader, NoOpLoader,
 };
 use scannerlib::storage::redis::FEEDUPDATE_SELECTOR;
@@ -29,14 +31,28 @@ use scannerlib::{
 
 use crate::{CliError, CliErrorKind, Db};
 
+/// Records the network calls of a script into a cassette file or replays them from it
+pub enum CassetteFile {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
 struct Run<L, S> {
     context_builder: ContextFactory<L, S>,
-    _target: String,
+    cache: Option<CompileCache>,
+    parallel: bool,
+    cassette: Option<(Arc<Cassette>, PathBuf)>,
+    trace: Option<(Arc<Trace>, PathBuf)>,
+    target: String,
     scan_id: String,
 }
 
 struct RunBuilder<L, S> {
     loader: L,
+    cache: Option<CompileCache>,
+    parallel: bool,
+    cassette: Option<(Arc<Cassette>, PathBuf)>,
+    trace: Option<PathBuf>,
     storage: S,
     target: String,
     scan_id: String,
@@ -47,6 +63,10 @@ impl Default for RunBuilder<NoOpLoader, DefaultDispatcher> {
         Self {
             storage: DefaultDispatcher::default(),
             loader: NoOpLoader::default(),
+            cache: None,
+            parallel: false,
+            cassette: None,
+            trace: None,
             target: String::default(),
             scan_id: "scannerctl".to_string(),
         }
@@ -61,6 +81,10 @@ where
     pub fn storage<S2>(self, s: S2) -> RunBuilder<L, S2> {
         RunBuilder {
             loader: self.loader,
+            cache: self.cache,
+            parallel: self.parallel,
+            cassette: self.cassette,
+            trace: self.trace,
             storage: s,
             target: self.target,
             scan_id: self.scan_id,
@@ -70,6 +94,10 @@ where
     pub fn loader<L2>(self, l: L2) -> RunBuilder<L2, S> {
         RunBuilder {
             loader: l,
+            cache: self.cache,
+            parallel: self.parallel,
+            cassette: self.cassette,
+            trace: self.trace,
             storage: self.storage,
             target: self.target,
             scan_id: self.scan_id,
@@ -86,11 +114,39 @@ where
         self
     }
 
+    pub fn cache(mut self, cache: Option<CompileCache>) -> RunBuilder<L, S> {
+        self.cache = cache;
+        self
+    }
+
+    pub fn parallel(mut self, parallel: bool) -> RunBuilder<L, S> {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn cassette(mut self, cassette: Option<(Arc<Cassette>, PathBuf)>) -> RunBuilder<L, S> {
+        self.cassette = cassette;
+        self
+    }
+
+    pub fn trace(mut self, trace: Option<PathBuf>) -> RunBuilder<L, S> {
+        self.trace = trace;
+        self
+    }
+
     pub fn build(self) -> Run<L, S> {
+        let mut context_builder = ContextFactory::new(self.loader, self.storage);
+        if let Some((cassette, _)) = &self.cassette {
+            context_builder.functions.with_cassette(cassette.clone());
+        }
         Run {
-            context_builder: ContextFactory::new(self.loader, self.storage),
+            context_builder,
+            cache: self.cache,
+            parallel: self.parallel,
+            cassette: self.cassette,
+            trace: self.trace.map(|x| (Arc::new(Trace::new()), x)),
             scan_id: self.scan_id,
-            _target: self.target,
+            target: self.target,
         }
     }
 }
@@ -125,16 +181,36 @@ where
     }
 
     async fn run(&self, script: &str) -> Result<(), CliErrorKind> {
+        // a failed run is stored as well, the cassette then shows the calls leading to it
+        let result = self.interpret(script).await;
+        self.finish_cassette()?;
+        self.finish_trace()?;
+        result
+    }
+
+    async fn interpret(&self, script: &str) -> Result<(), CliErrorKind> {
+        let target = Some(self.target.clone()).filter(|x| !x.is_empty());
         let context = self
             .context_builder
-            // TODO: use proper  target
-            .build(ContextKey::Scan(self.scan_id.clone(), None));
+            .build(ContextKey::Scan(self.scan_id.clone(), target));
         let register = RegisterBuilder::build();
         let code = self.load(script)?;
-        let results: Vec<_> = CodeInterpreter::new(&code, register, &context)
-            .stream()
-            .collect()
-            .await;
+        let interpreter = match &self.cache {
+            Some(cache) => {
+                CodeInterpreter::from_statements(cache.compile(&code)?, register, &context)
+            }
+            None => CodeInterpreter::new(&code, register, &context),
+        };
+        let interpreter = if self.parallel {
+            interpreter.parallel()
+        } else {
+            interpreter
+        };
+        let interpreter = match &self.trace {
+            Some((trace, _)) => interpreter.traced(trace.clone()),
+            None => interpreter,
+        };
+        let results: Vec<_> = interpreter.stream().collect().await;
         for result in results {
             let r = match result {
                 Ok(x) => x,
@@ -150,7 +226,11 @@ where
                 },
             };
             match r {
-                NaslValue::Exit(rc) => std::process::exit(rc as i32),
+                NaslValue::Exit(rc) => {
+                    self.finish_cassette()?;
+                    self.finish_trace()?;
+                    std::process::exit(rc as i32)
+                }
                 _ => {
                     tracing::debug!("=> {r:?}", r = r);
                 }
@@ -159,6 +239,44 @@ where
 
         Ok(())
     }
+
+    /// Stores the executed statements.
+    fn finish_trace(&self) -> Result<(), CliErrorKind> {
+        let Some((trace, path)) = &self.trace else {
+            return Ok(());
+        };
+        let file = fs::File::create(path).map_err(|e| LoadError::Dirty(e.to_string()))?;
+        trace
+            .write(std::io::BufWriter::new(file))
+            .map_err(|e| LoadError::Dirty(e.to_string()))?;
+        tracing::info!(steps = trace.steps().len(), "stored trace in {path:?}");
+        Ok(())
+    }
+
+    /// Stores a recorded cassette, a replayed one is checked for calls that were not replayed.
+    fn finish_cassette(&self) -> Result<(), CliErrorKind> {
+        let Some((cassette, path)) = &self.cassette else {
+            return Ok(());
+        };
+        if cassette.is_replaying() {
+            if cassette.remaining() > 0 {
+                tracing::warn!(
+                    remaining = cassette.remaining(),
+                    "not all recorded calls got replayed"
+                );
+            }
+            return Ok(());
+        }
+        let file = fs::File::create(path).map_err(|e| LoadError::Dirty(e.to_string()))?;
+        cassette
+            .write(std::io::BufWriter::new(file))
+            .map_err(|e| LoadError::Dirty(e.to_string()))?;
+        tracing::info!(
+            calls = cassette.interactions().len(),
+            "stored cassette in {path:?}"
+        );
+        Ok(())
+    }
 }
 
 fn create_redis_storage(url: &str) -> PerItemDispatcher<redis::CacheDispatcher<redis::RedisCtx>> {
@@ -193,15 +311,41 @@ fn load_feed_by_json(store: &DefaultDispatcher, path: &PathBuf) -> Result<(), Cl
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     db: &Db,
     feed: Option<PathBuf>,
     script: &str,
     target: Option<String>,
+    cache: Option<PathBuf>,
+    parallel: bool,
+    cassette: Option<CassetteFile>,
+    trace: Option<PathBuf>,
 ) -> Result<(), CliError> {
+    let cache = cache
+        .map(CompileCache::new)
+        .transpose()
+        .map_err(|e| CliError {
+            filename: script.to_string(),
+            kind: CliErrorKind::Corrupt(format!("unable to create compile cache: {e}")),
+        })?;
+    let cassette = match cassette {
+        Some(CassetteFile::Record(path)) => Some((Arc::new(Cassette::record()), path)),
+        Some(CassetteFile::Replay(path)) => {
+            let cassette = fs::File::open(&path)
+                .and_then(|x| Cassette::read(std::io::BufReader::new(x)))
+                .map_err(|e| CliError::load_error(e, &path))?;
+            Some((Arc::new(cassette), path))
+        }
+        None => None,
+    };
     let builder = RunBuilder::default()
         .target(target.unwrap_or_default())
-        .scan_id(format!("scannerctl-{script}"));
+        .cassette(cassette)
+        .trace(trace)
+        .scan_id(format!("scannerctl-{script}"))
+        .cache(cache)
+        .parallel(parallel);
     let result = match (db, feed) {
         (Db::Redis(url), None) => {
             builder
@@ -215,7 +359,7 @@ pub async fn run(
             let storage = create_redis_storage(url);
             let loader = FSPluginLoader::new(path);
             load_feed_by_exec(&storage, &loader).await?;
-            let builder = RunBuilder::default().loader(loader);
+            let builder = builder.loader(loader);
             builder.storage(storage).build().run(script).await
         }
         (Db::InMemory, Some(path)) => {
@@ -228,7 +372,7 @@ pub async fn run(
                 load_feed_by_exec(&storage, &loader).await?
             }
 
-            let builder = RunBuilder::default().loader(loader);
+            let builder = builder.loader(loader);
             builder.storage(storage).build().run(script).await
         }
     };
@@ -3,13 +3,20 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 #![doc = include_str!("README.md")]
+mod admin;
+mod alive;
+mod completion;
+mod config;
 mod error;
 mod execute;
 mod feed;
 mod interpret;
 mod notusupdate;
+mod report;
+mod rescan;
 mod scanconfig;
 mod syntax;
+mod trace;
 
 use configparser::ini::Ini;
 pub use error::*;
@@ -17,7 +24,24 @@ pub use error::*;
 use scannerlib::storage::StorageError;
 use std::{path::PathBuf, process};
 
-use clap::{arg, ArgAction, ArgMatches, Command};
+use clap::{arg, builder::TypedValueParser, value_parser, ArgAction, ArgMatches, Command};
+
+/// Format of the results printed by a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Returns true when a command prints its results as JSON, either via its `--json` flag or via
+/// the global `--output-format`.
+pub fn json_output(args: &ArgMatches) -> bool {
+    matches!(args.try_get_one::<bool>("json"), Ok(Some(true)))
+        || matches!(
+            args.try_get_one::<OutputFormat>("output-format"),
+            Ok(Some(OutputFormat::Json))
+        )
+}
 
 #[derive(Debug, Clone)]
 pub enum Db {
@@ -43,46 +67,112 @@ fn read_openvas_config() -> Result<Ini, CliErrorKind> {
     Ok(config)
 }
 
-fn get_path_from_openvas(config: Ini) -> PathBuf {
-    PathBuf::from(
-        config
-            .get("default", "plugins_folder")
-            .expect("openvas -s must contain plugins_folder"),
-    )
+fn get_path_from_openvas(config: Ini) -> Result<PathBuf, CliErrorKind> {
+    config
+        .get("default", "plugins_folder")
+        .map(PathBuf::from)
+        .ok_or_else(|| CliErrorKind::Openvas {
+            args: "-s".to_owned().into(),
+            err_msg: "missing plugins_folder".to_owned(),
+        })
 }
 
-#[tokio::main]
-async fn main() {
-    let matches = add_verbose(
+const EXAMPLES: &str = "Examples:
+  scannerctl syntax --deep /var/lib/openvas/plugins
+  scannerctl execute script -t 192.168.0.1 examples/hello.nasl
+  scannerctl feed update --vts-path /var/lib/openvas/plugins --signature-check
+  scannerctl notus --path /var/lib/notus/products debian_12 -l openssl-3.0.11-1~deb12u1
+  scannerctl --log-level debug report --kind technical -o report.html results.json
+  scannerctl --output-format json feed lint-tags /var/lib/openvas/plugins
+  scannerctl --config scannerctl.toml feed update
+  scannerctl completion bash > /etc/bash_completion.d/scannerctl
+
+Run `scannerctl help <COMMAND>` for the options and examples of a command.";
+
+/// Returns the command tree of scannerctl.
+fn cli() -> Command {
+    let cmd = add_verbose(
         Command::new("scannerctl")
             .version("1.0")
             .about("Is a CLI tool around NASL.")
-            .subcommand_required(true),
+            .subcommand_required(true)
+            .arg(
+                arg!(--"log-level" <LEVEL> "Sets the log level, overrides --verbose.")
+                    .required(false)
+                    .global(true)
+                    .env("SCANNERCTL_LOG_LEVEL")
+                    .value_parser(
+                        clap::builder::PossibleValuesParser::new([
+                            "error", "warn", "info", "debug", "trace",
+                        ])
+                        .map(|x| x.parse::<tracing::Level>().expect("valid log level")),
+                    ),
+            )
+            .arg(
+                arg!(--"output-format" <FORMAT> "Prints the results as text or json, for commands supporting json.")
+                    .required(false)
+                    .global(true)
+                    .env("SCANNERCTL_OUTPUT_FORMAT")
+                    .value_parser(value_parser!(OutputFormat)),
+            )
+            .arg(
+                arg!(--config <FILE> "Reads the defaults of options from a TOML file.")
+                    .required(false)
+                    .global(true)
+                    .env(config::CONFIG_ENV)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .after_help(EXAMPLES),
     );
-    let matches = syntax::extend_args(matches);
-    let matches = scanconfig::extend_args(matches);
-    let matches = execute::extend_args(matches);
-    let matches = notusupdate::scanner::extend_args(matches);
-    let matches = feed::extend_args(matches).get_matches();
-    let result = run(&matches).await;
+    let cmd = syntax::extend_args(cmd);
+    let cmd = scanconfig::extend_args(cmd);
+    let cmd = execute::extend_args(cmd);
+    let cmd = notusupdate::scanner::extend_args(cmd);
+    let cmd = alive::extend_args(cmd);
+    let cmd = report::extend_args(cmd);
+    let cmd = admin::extend_args(cmd);
+    let cmd = rescan::extend_args(cmd);
+    let cmd = trace::extend_args(cmd);
+    let cmd = completion::extend_args(cmd);
+    feed::extend_args(cmd)
+}
+
+#[tokio::main]
+async fn main() {
+    let cmd = match config::load(cli(), std::env::args_os()) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let matches = cmd.clone().get_matches();
+    let result = run(&cmd, &matches).await;
 
     match result {
         Ok(_) => {}
-        Err(e) => match e.kind {
-            CliErrorKind::StorageError(StorageError::UnexpectedData(x)) => match &x as &str {
-                "BrokenPipe" => {}
-                _ => panic!("Unexpected data within dispatcher: {x}"),
-            },
+        Err(e) => match &e.kind {
+            CliErrorKind::StorageError(StorageError::UnexpectedData(x)) if x == "BrokenPipe" => {}
+            CliErrorKind::PartialFailure { .. } => {
+                tracing::warn!("{e}");
+                std::process::exit(2);
+            }
             CliErrorKind::InterpretError(_) | CliErrorKind::SyntaxError(_) => {
                 tracing::warn!("script error, {e}");
                 std::process::exit(1);
             }
-            _ => panic!("{e}"),
+            _ => {
+                tracing::error!("{e}");
+                std::process::exit(1);
+            }
         },
     }
 }
 
-async fn run(matches: &ArgMatches) -> Result<(), CliError> {
+async fn run(cmd: &Command, matches: &ArgMatches) -> Result<(), CliError> {
+    if let Some(result) = completion::run(cmd, matches) {
+        return result;
+    }
     if let Some(result) = feed::run(matches).await {
         return result;
     }
@@ -98,6 +188,21 @@ async fn run(matches: &ArgMatches) -> Result<(), CliError> {
     if let Some(result) = notusupdate::scanner::run(matches).await {
         return result;
     }
+    if let Some(result) = alive::run(matches).await {
+        return result;
+    }
+    if let Some(result) = report::run(matches).await {
+        return result;
+    }
+    if let Some(result) = admin::run(matches).await {
+        return result;
+    }
+    if let Some(result) = rescan::run(matches).await {
+        return result;
+    }
+    if let Some(result) = trace::run(matches).await {
+        return result;
+    }
     Err(CliError {
         filename: "".to_string(),
         kind: CliErrorKind::Corrupt(format!(
@@ -107,8 +212,10 @@ async fn run(matches: &ArgMatches) -> Result<(), CliError> {
     })
 }
 
-pub fn set_logging(level: u8) {
-    let lv = if level > 1 {
+pub fn set_logging(level: u8, log_level: Option<tracing::Level>) {
+    let lv = if let Some(log_level) = log_level {
+        log_level
+    } else if level > 1 {
         tracing::Level::TRACE
     } else if level > 0 {
         tracing::Level::DEBUG
@@ -136,6 +243,9 @@ pub fn get_args_set_logging<'a>(
     let verbose = root.get_one::<u8>("verbose").cloned().unwrap_or_default();
     let args = root.subcommand_matches(name)?;
     let verbose = args.get_one::<u8>("verbose").cloned().unwrap_or(verbose);
-    set_logging(verbose);
+    set_logging(
+        verbose,
+        args.get_one::<tracing::Level>("log-level").cloned(),
+    );
     Some((args, verbose))
 }
@@ -3,19 +3,31 @@
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
 use std::{
-    io,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
     path::{Path, PathBuf},
 };
 
 use clap::{arg, value_parser, Arg, ArgAction, Command};
-use scannerlib::notus::{FSProductLoader, Notus};
+use scannerlib::models::NotusResults;
+use scannerlib::notus::{
+    image::ImageInventory,
+    sbom::SbomInventory,
+    vex::{VexDocument, VexFormat},
+    FSProductLoader, Notus,
+};
 
-use crate::CliError;
+use crate::{CliError, CliErrorKind};
 
 pub fn extend_args(cmd: Command) -> Command {
     cmd.subcommand(crate::add_verbose(
         Command::new("notus")
             .about("does use notus products to compare packages against known vulnerabilities.")
+            .after_help("Examples:
+  scannerctl notus -p /var/lib/notus/products debian_12 -l openssl-3.0.11-1~deb12u1,curl-7.88.1-10
+  scannerctl notus -p /var/lib/notus/products --sbom sbom.json --format openvex
+  scannerctl notus -p /var/lib/notus/products --image nginx.tar")
             .arg(
                 arg!(-p --path <FILE> "Path to the product feed.")
                     .required(true)
@@ -28,9 +40,30 @@ pub fn extend_args(cmd: Command) -> Command {
             )
             .arg(
                 arg!(-l --"pkg-list" <STRING> "Comma separated list of packages.")
-                    .required_unless_present("input"),
+                    .required_unless_present_any(["input", "image", "sbom"]),
+            )
+            .arg(
+                arg!(--image <FILE> "Exported filesystem or saved container image to read the os and packages from.")
+                    .required(false)
+                    .conflicts_with_all(["input", "pkg-list", "os"])
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(--sbom <FILE> "CycloneDX or SPDX SBOM in JSON to read the packages from.")
+                    .required(false)
+                    .conflicts_with_all(["input", "pkg-list", "image"])
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(--format <FORMAT> "Prints the results as VEX document, either openvex or cyclonedx.")
+                    .required(false)
+                    .value_parser(value_parser!(VexFormat)),
             )
-            .arg(Arg::new("os").required(true).action(ArgAction::Append)),
+            .arg(
+                Arg::new("os")
+                    .required_unless_present_any(["image", "sbom"])
+                    .action(ArgAction::Append),
+            ),
     ))
 }
 
@@ -40,6 +73,15 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
 
     let stdin = args.get_one::<bool>("input").cloned().unwrap_or_default();
 
+    let format = args.get_one::<VexFormat>("format").cloned();
+
+    if let Some(image) = args.get_one::<PathBuf>("image") {
+        return Some(execute_image(image, products_path, format));
+    }
+    if let Some(sbom) = args.get_one::<PathBuf>("sbom") {
+        let os = args.get_one::<String>("os").map(|x| x as &str);
+        return Some(execute_sbom(sbom, os, products_path, format));
+    }
     let os = args.get_one::<String>("os").unwrap();
     let pkg_list = args.get_one::<String>("pkg-list");
     Some(execute(
@@ -47,14 +89,33 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
         os,
         products_path,
         stdin,
+        format,
     ))
 }
 
+/// Prints the results as JSON or as VEX document
+fn print(
+    os: &str,
+    results: &NotusResults,
+    purls: HashMap<String, String>,
+    format: Option<VexFormat>,
+) -> Result<(), CliError> {
+    match format {
+        None => serde_json::to_writer_pretty(io::stdout(), results)?,
+        Some(format) => {
+            let document = VexDocument::new(os).with_purls(purls);
+            serde_json::to_writer_pretty(io::stdout(), &document.render(format, results))?
+        }
+    }
+    Ok(())
+}
+
 fn execute<T>(
     pkg_list: Option<&str>,
     os: &str,
     products_path: T,
     stdin: bool,
+    format: Option<VexFormat>,
 ) -> Result<(), CliError>
 where
     T: AsRef<Path>,
@@ -69,6 +130,59 @@ where
 
     let mut notus = Notus::new(loader, false);
     tracing::debug!(?packages, "going to scan");
-    serde_json::to_writer_pretty(io::stdout(), &notus.scan(os, &packages)?)?;
-    Ok(())
+    print(os, &notus.scan(os, &packages)?, HashMap::new(), format)
+}
+
+fn execute_image<T>(
+    image: &Path,
+    products_path: T,
+    format: Option<VexFormat>,
+) -> Result<(), CliError>
+where
+    T: AsRef<Path>,
+{
+    let file = File::open(image).map_err(|e| CliError::load_error(e, image))?;
+    let inventory = ImageInventory::read(BufReader::new(file)).map_err(|e| CliError {
+        filename: image.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e.to_string()),
+    })?;
+    let os = inventory.product();
+    tracing::debug!(
+        os,
+        packages = inventory.packages.len(),
+        "going to scan image"
+    );
+    let loader = FSProductLoader::new(products_path)?;
+    let mut notus = Notus::new(loader, false);
+    let results = notus.scan(&os, &inventory.packages)?;
+    print(&os, &results, HashMap::new(), format)
+}
+
+fn execute_sbom<T>(
+    sbom: &Path,
+    os: Option<&str>,
+    products_path: T,
+    format: Option<VexFormat>,
+) -> Result<(), CliError>
+where
+    T: AsRef<Path>,
+{
+    let corrupt = |e: String| CliError {
+        filename: sbom.to_string_lossy().to_string(),
+        kind: CliErrorKind::Corrupt(e),
+    };
+    let document = std::fs::read(sbom).map_err(|e| CliError::load_error(e, sbom))?;
+    let inventory = SbomInventory::parse(&document).map_err(|e| corrupt(e.to_string()))?;
+    // the given os takes precedence, e.g. for SBOMs without distribution information
+    let os = os
+        .map(|x| x.to_string())
+        .or(inventory.product)
+        .ok_or_else(|| corrupt("unable to detect the os, it must be given".to_string()))?;
+    if !inventory.unmatched.is_empty() {
+        tracing::warn!(unmatched = ?inventory.unmatched, "Components not checked by notus");
+    }
+    let loader = FSProductLoader::new(products_path)?;
+    let mut notus = Notus::new(loader, false);
+    let results = notus.scan(&os, &inventory.packages)?;
+    print(&os, &results, inventory.purls, format)
 }
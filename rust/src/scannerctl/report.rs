@@ -0,0 +1,182 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{collections::HashSet, io::Write, path::PathBuf, sync::Arc};
+
+use clap::{arg, value_parser, Arg, ArgAction, Command};
+use scannerlib::models;
+use scannerlib::nasl::FSPluginLoader;
+use scannerlib::report::{Branding, PdfBackend, PdfConverter, Report, ReportFormat, ReportKind};
+use scannerlib::storage::{DefaultDispatcher, Retriever};
+
+use crate::{CliError, CliErrorKind};
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("report")
+            .about("Renders the results of a scan, as served by openvasd, into a HTML, Markdown or PDF report.")
+            .after_help("Examples:
+  scannerctl report -p /var/lib/openvas/plugins -o report.html results.json
+  curl -s localhost:3000/scans/<ID>/results | scannerctl report -i -k technical -f markdown")
+            .arg(
+                arg!(-k --kind <KIND> "The kind of report: executive or technical.")
+                    .required(false)
+                    .value_parser(value_parser!(ReportKind)),
+            )
+            .arg(
+                arg!(-f --format <FORMAT> "The format of the report: html, markdown or pdf.")
+                    .required(false)
+                    .value_parser(value_parser!(ReportFormat)),
+            )
+            .arg(
+                arg!(-t --template <FILE> "Path to a custom template, replaces the shipped template of the kind.")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(-p --path <FILE> "Path to the feed, used to add names and solutions of the VTs.")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(arg!(-s --"scan-id" <ID> "The id of the scan shown in the report.").required(false))
+            .arg(
+                arg!(--"min-qod" <QOD> "Omits results with a quality of detection below QOD, from 0 to 100.")
+                    .required(false)
+                    .value_parser(value_parser!(u8).range(0..=100)),
+            )
+            .arg(
+                arg!(-o --output <FILE> "Writes the report to a file instead of stdout.")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(--"pdf-backend" <BACKEND> "The tool printing the PDF: chromium, wkhtmltopdf or weasyprint.")
+                    .required(false)
+                    .value_parser(value_parser!(PdfBackend)),
+            )
+            .arg(
+                arg!(--"pdf-command" <FILE> "Path to the executable of the PDF backend.")
+                    .required(false)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(arg!(--organization <NAME> "The organization shown in the report.").required(false))
+            .arg(arg!(--logo <FILE> "Path or URL of a logo shown in the report.").required(false))
+            .arg(arg!(--color <COLOR> "CSS color of headings and table headers.").required(false))
+            .arg(arg!(--footer <TEXT> "Text shown at the end of the report.").required(false))
+            .arg(arg!(-i --input "Parses the results from stdin.").required(false).action(ArgAction::SetTrue))
+            .arg(Arg::new("results").required_unless_present("input").help("Path to a json file containing the results.")),
+    ))
+}
+
+fn corrupt(filename: &str, e: impl std::fmt::Debug) -> CliError {
+    CliError {
+        filename: filename.to_string(),
+        kind: CliErrorKind::Corrupt(format!("{e:?}")),
+    }
+}
+
+struct Options {
+    kind: ReportKind,
+    format: ReportFormat,
+    template: Option<PathBuf>,
+    feed: Option<PathBuf>,
+    scan_id: String,
+    min_qod: Option<u8>,
+    results: Option<String>,
+    output: Option<PathBuf>,
+    pdf: PdfConverter,
+    branding: Branding,
+}
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "report")?;
+    let string = |name: &str| args.get_one::<String>(name).cloned();
+    let path = |name: &str| args.get_one::<PathBuf>(name).cloned();
+    let results = if args.get_one::<bool>("input").cloned().unwrap_or_default() {
+        None
+    } else {
+        string("results")
+    };
+    let options = Options {
+        kind: args
+            .get_one::<ReportKind>("kind")
+            .cloned()
+            .unwrap_or_default(),
+        format: args
+            .get_one::<ReportFormat>("format")
+            .cloned()
+            .unwrap_or_default(),
+        template: path("template"),
+        feed: path("path"),
+        scan_id: string("scan-id").unwrap_or_default(),
+        min_qod: args.get_one::<u8>("min-qod").cloned(),
+        results,
+        output: path("output"),
+        pdf: PdfConverter {
+            backend: args
+                .get_one::<PdfBackend>("pdf-backend")
+                .cloned()
+                .unwrap_or_default(),
+            command: path("pdf-command"),
+        },
+        branding: Branding {
+            organization: string("organization"),
+            logo: string("logo"),
+            color: string("color"),
+            footer: string("footer"),
+        },
+    };
+    Some(execute(options).await)
+}
+
+async fn execute(options: Options) -> Result<(), CliError> {
+    let results: Vec<models::Result> = match &options.results {
+        Some(path) => {
+            let file = std::fs::File::open(path).map_err(|e| corrupt(path, e))?;
+            serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| corrupt(path, e))?
+        }
+        None => {
+            tracing::debug!("reading results from stdin");
+            serde_json::from_reader(std::io::stdin()).map_err(|e| corrupt("", e))?
+        }
+    };
+    let mut vts = vec![];
+    if let Some(feed) = options.feed {
+        tracing::info!("loading feed. This may take a while.");
+        let storage = Arc::new(DefaultDispatcher::new());
+        let loader = crate::feed::update::FeedLoader::Directory(FSPluginLoader::new(feed));
+        crate::feed::update::run(Arc::clone(&storage), &loader, false, None).await?;
+        tracing::info!("feed loaded.");
+        let oids: HashSet<&String> = results.iter().filter_map(|x| x.oid.as_ref()).collect();
+        vts = storage
+            .vts()
+            .map_err(|e| corrupt("", e))?
+            .filter(|x| oids.contains(&x.oid))
+            .collect();
+    }
+    let logo = options.branding.logo.clone().unwrap_or_default();
+    let branding = options
+        .branding
+        .embed_logo()
+        .map_err(|e| corrupt(&logo, e))?;
+    let report = Report::new(&options.scan_id, results)
+        .with_vts(vts)
+        .with_branding(branding)
+        .with_min_qod(options.min_qod);
+    let out = match options.template {
+        Some(path) => {
+            let template =
+                std::fs::read_to_string(&path).map_err(|e| corrupt(&path.to_string_lossy(), e))?;
+            report.export_template(&template, options.format, &options.pdf)
+        }
+        None => report.export(options.kind, options.format, &options.pdf),
+    }
+    .map_err(|e| corrupt("", e))?;
+    match options.output {
+        Some(path) => std::fs::write(&path, out).map_err(|e| corrupt(&path.to_string_lossy(), e)),
+        None => std::io::stdout()
+            .write_all(&out)
+            .map_err(|e| corrupt("", e)),
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use clap::{arg, Arg, ArgAction, Command};
+use scannerlib::models::Rescan;
+
+use crate::{CliError, CliErrorKind};
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("rescan")
+            .about("Re-runs selected hosts and VTs of a finished scan of a remote openvasd.")
+            .arg(arg!(--openvasd <URL> "URL of the remote openvasd.").required(true))
+            .arg(
+                arg!(--"api-key" <KEY> "API key used to authenticate against the remote openvasd.")
+                    .required(false),
+            )
+            .arg(
+                arg!(--host <HOST> "Host to re-run, may be given multiple times.")
+                    .required(false)
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                arg!(--vt <OID> "OID of a VT to re-run, may be given multiple times.")
+                    .required(false)
+                    .action(ArgAction::Append),
+            )
+            .arg(arg!(--start "Starts the follow-up scan right away.").required(false))
+            .arg(Arg::new("scan-id").required(true)),
+    ))
+}
+
+/// Creates the follow-up scan via `POST {url}/scans/{id}/rescan` and prints its ID.
+async fn rescan(
+    url: &str,
+    api_key: Option<&str>,
+    id: &str,
+    rescan: &Rescan,
+    start: bool,
+) -> Result<(), CliError> {
+    let mut endpoint = format!("{}/scans/{id}/rescan", url.trim_end_matches('/'));
+    if start {
+        endpoint.push_str("?start=true");
+    }
+    let rescan_error = |e: String| CliError {
+        filename: endpoint.clone(),
+        kind: CliErrorKind::Corrupt(format!("unable to rescan: {e}")),
+    };
+    let body = serde_json::to_vec(rescan).map_err(|e| rescan_error(e.to_string()))?;
+    let mut request = reqwest::Client::new()
+        .post(&endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(key) = api_key {
+        request = request.header("x-api-key", key);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| rescan_error(e.to_string()))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| rescan_error(e.to_string()))?;
+    if !status.is_success() {
+        return Err(rescan_error(format!("remote responded {status} {text}")));
+    }
+    let new_id: String = serde_json::from_str(&text).map_err(|e| rescan_error(e.to_string()))?;
+    println!("{new_id}");
+    Ok(())
+}
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "rescan")?;
+    let values = |name: &str| -> Vec<String> {
+        args.get_many::<String>(name)
+            .map(|x| x.cloned().collect())
+            .unwrap_or_default()
+    };
+    let selection = Rescan {
+        hosts: values("host"),
+        vts: values("vt"),
+    };
+    let url = args.get_one::<String>("openvasd")?;
+    let id = args.get_one::<String>("scan-id")?;
+    let api_key = args.get_one::<String>("api-key").map(|x| x.as_str());
+    let start = args.get_flag("start");
+    Some(rescan(url, api_key, id, &selection, start).await)
+}
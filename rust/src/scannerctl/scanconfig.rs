@@ -7,6 +7,7 @@ use std::{io::BufReader, path::PathBuf, sync::Arc};
 
 use clap::{arg, value_parser, Arg, ArgAction, Command};
 use scannerlib::models::{Parameter, Port, Protocol, Scan, VT};
+use scannerlib::nasl::FSPluginLoader;
 use scannerlib::storage::{ContextKey, DefaultDispatcher, Retriever, StorageError};
 use serde::Deserialize;
 
@@ -79,7 +80,7 @@ async fn execute(
     let feed = match feed {
         Some(feed) => feed.to_owned(),
         None => read_openvas_config()
-            .map(get_path_from_openvas)
+            .and_then(get_path_from_openvas)
             .map_err(|e| CliError {
                 filename: "".to_string(),
                 kind: CliErrorKind::Corrupt(format!("{e:?}")),
@@ -87,7 +88,8 @@ async fn execute(
     };
 
     tracing::info!("loading feed. This may take a while.");
-    crate::feed::update::run(Arc::clone(&storage), feed.to_owned(), false).await?;
+    let loader = crate::feed::update::FeedLoader::Directory(FSPluginLoader::new(feed));
+    crate::feed::update::run(Arc::clone(&storage), &loader, false, None).await?;
     tracing::info!("feed loaded.");
     let ports = match port_list {
         Some(ports) => {
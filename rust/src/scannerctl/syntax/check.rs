@@ -2,9 +2,13 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use scannerlib::nasl::syntax::load_non_utf8_path;
+use scannerlib::feed::IncludeGraph;
+use scannerlib::nasl::syntax::{load_non_utf8_path, FSPluginLoader, Loader};
 use scannerlib::nasl::syntax::{parse, Statement, SyntaxError};
 use walkdir::WalkDir;
 
@@ -25,8 +29,107 @@ fn read<P: AsRef<Path>>(path: P) -> Result<Vec<Result<Statement, SyntaxError>>,
     Ok(parse(&code).collect())
 }
 
-fn print_results(path: &Path, verbose: bool) -> Result<usize, CliError> {
+/// Outcome of checking a single include
+#[derive(Debug, Clone)]
+enum Include {
+    /// The include exists and parses, contains its direct includes
+    Valid(Vec<String>),
+    /// The include is missing or cannot be parsed, contains the reason
+    Broken(String),
+}
+
+/// Resolves the includes of scripts through a loader and verifies that they exist and parse.
+///
+/// Each include is only loaded and parsed once.
+struct IncludeChecker<L> {
+    loader: L,
+    checked: HashMap<String, Include>,
+}
+
+impl<L> IncludeChecker<L>
+where
+    L: Loader,
+{
+    fn new(loader: L) -> Self {
+        Self {
+            loader,
+            checked: HashMap::new(),
+        }
+    }
+
+    fn check(&mut self, name: &str) -> &Include {
+        if !self.checked.contains_key(name) {
+            let include = match self.loader.load(name) {
+                Err(e) => Include::Broken(e.to_string()),
+                Ok(code) => {
+                    let errors: Vec<_> = parse(&code)
+                        .filter_map(|x| x.err())
+                        .map(|x| x.to_string())
+                        .collect();
+                    if errors.is_empty() {
+                        Include::Valid(IncludeGraph::direct_includes(&code).unwrap_or_default())
+                    } else {
+                        Include::Broken(errors.join("; "))
+                    }
+                }
+            };
+            self.checked.insert(name.to_string(), include);
+        }
+        &self.checked[name]
+    }
+
+    /// Returns the missing or broken includes of the code, including the transitive ones,
+    /// together with the reason.
+    ///
+    /// Includes after a syntax error of the code itself are not resolved.
+    fn broken(&mut self, code: &str) -> Vec<(String, String)> {
+        let mut todo = IncludeGraph::direct_includes(code).unwrap_or_default();
+        let mut seen = HashSet::new();
+        let mut result = vec![];
+        let mut i = 0;
+        while let Some(name) = todo.get(i).cloned() {
+            i += 1;
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            match self.check(&name) {
+                Include::Valid(includes) => todo.extend(includes.clone()),
+                Include::Broken(reason) => result.push((name, reason.clone())),
+            }
+        }
+        result
+    }
+}
+
+fn print_includes<L: Loader>(
+    path: &Path,
+    includes: &mut IncludeChecker<L>,
+) -> Result<usize, CliError> {
+    let code = load_non_utf8_path(path).map_err(|e| CliError {
+        kind: e.into(),
+        filename: format!("{path:?}"),
+    })?;
+    let broken = includes.broken(&code);
+    if !broken.is_empty() {
+        eprintln!("# Broken includes in {path:?}");
+    }
+    for (name, reason) in &broken {
+        eprintln!("{name}: {reason}");
+    }
+    Ok(broken.len())
+}
+
+fn print_results(
+    path: &Path,
+    verbose: bool,
+    includes: Option<&mut IncludeChecker<FSPluginLoader>>,
+) -> Result<usize, CliError> {
     let mut errors = 0;
+    if let Some(includes) = includes {
+        if path.extension().is_some_and(|x| x == "nasl") {
+            errors += print_includes(path, includes)?;
+        }
+    }
 
     if verbose {
         println!("# {path:?}");
@@ -54,7 +157,17 @@ fn print_results(path: &Path, verbose: bool) -> Result<usize, CliError> {
     Ok(errors)
 }
 
-pub fn run(path: &PathBuf, verbose: bool, no_progress: bool) -> Result<(), CliError> {
+/// Verifies the syntax of the nasl file or of all nasl and inc files within the directory.
+///
+/// When `includes` is set each nasl script is checked for includes that are missing or do not
+/// parse, they are resolved from the given directory.
+pub fn run(
+    path: &PathBuf,
+    verbose: bool,
+    no_progress: bool,
+    includes: Option<PathBuf>,
+) -> Result<(), CliError> {
+    let mut includes = includes.map(|x| IncludeChecker::new(FSPluginLoader::new(x)));
     let mut parsed: usize = 0;
     let mut skipped: usize = 0;
     let mut errors: usize = 0;
@@ -74,13 +187,13 @@ pub fn run(path: &PathBuf, verbose: bool, no_progress: bool) -> Result<(), CliEr
             if !matches!(ext.as_str(), "nasl" | "inc") {
                 skipped += 1;
             } else {
-                errors += print_results(entry.path(), verbose)?;
+                errors += print_results(entry.path(), verbose, includes.as_mut())?;
                 parsed += 1;
             }
         }
         println!();
     } else {
-        errors += print_results(path.as_path(), verbose)?;
+        errors += print_results(path.as_path(), verbose, includes.as_mut())?;
         parsed += 1;
     }
     println!("skipped: {skipped} files; parsed: {parsed} files; errors: {errors}");
@@ -89,3 +202,39 @@ pub fn run(path: &PathBuf, verbose: bool, no_progress: bool) -> Result<(), CliEr
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use scannerlib::nasl::syntax::LoadError;
+
+    use super::*;
+
+    struct Files(HashMap<&'static str, &'static str>);
+
+    impl Loader for Files {
+        fn load(&self, key: &str) -> Result<String, LoadError> {
+            self.0
+                .get(key)
+                .map(|x| x.to_string())
+                .ok_or_else(|| LoadError::NotFound(key.to_string()))
+        }
+
+        fn root_path(&self) -> Result<String, LoadError> {
+            Ok(String::default())
+        }
+    }
+
+    #[test]
+    fn broken_includes() {
+        let mut includes = IncludeChecker::new(Files(HashMap::from([
+            ("a.inc", "include(\"b.inc\"); include(\"c.inc\");"),
+            ("b.inc", "include(\"a.inc\");"),
+            ("c.inc", "if (a) {"),
+        ])));
+        let broken = includes.broken("include(\"a.inc\"); include(\"missing.inc\");");
+        let names: Vec<_> = broken.iter().map(|(x, _)| x.as_str()).collect();
+        assert_eq!(names, vec!["missing.inc", "c.inc"]);
+        assert_eq!(broken[0].1, "missing.inc not found.");
+        assert!(includes.broken("include(\"b.inc\");").len() == 1);
+    }
+}
@@ -17,8 +17,18 @@ pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
         _ => unreachable!("path is set to required"),
     };
     let quiet = args.get_one::<bool>("quiet").cloned().unwrap_or_default();
+    // includes are resolved from the feed, by default the checked directory
+    let includes = args.get_flag("deep").then(|| {
+        args.get_one::<PathBuf>("feed").cloned().unwrap_or_else(|| {
+            if path.is_dir() {
+                path.clone()
+            } else {
+                path.parent().map(|x| x.to_path_buf()).unwrap_or_default()
+            }
+        })
+    });
 
-    Some(check::run(&path, verbose > 0, quiet))
+    Some(check::run(&path, verbose > 0, quiet, includes))
 }
 
 pub fn extend_args(cmd: Command) -> Command {
@@ -34,6 +44,17 @@ pub fn extend_args(cmd: Command) -> Command {
                 arg!(-q --quiet "Prints only error output and no progress.")
                     .required(false)
                     .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(-d --deep "Verifies that the includes of each nasl script exist and parse.")
+                    .required(false)
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(--feed <DIR> "Directory the includes are resolved from, by default the given path or the directory of the given file.")
+                    .required(false)
+                    .requires("deep")
+                    .value_parser(value_parser!(PathBuf)),
             ),
     ))
 }
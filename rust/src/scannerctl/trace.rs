@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+use std::{fs, io::BufReader, path::PathBuf};
+
+use clap::{arg, value_parser, Arg, Command};
+use scannerlib::nasl::{interpreter::Trace, utils::cassette::Value};
+
+use crate::CliError;
+
+/// Values longer than that are shortened
+const MAX_VALUE_LENGTH: usize = 120;
+
+pub fn extend_args(cmd: Command) -> Command {
+    cmd.subcommand(crate::add_verbose(
+        Command::new("trace")
+            .about("Works with the traces recorded by `execute script --trace`.")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("view")
+                    .about("Prints the executed statements of a trace together with the changed variables and returned values.")
+                    .arg(
+                        arg!(-d --depth <LEVELS> "Hides statements nested deeper than the given levels below the top-level statements.")
+                            .required(false)
+                            .value_parser(value_parser!(usize)),
+                    )
+                    .arg(arg!(--variable <NAME> "Prints only the statements changing the variable.").required(false))
+                    .arg(Arg::new("trace").required(true).value_parser(value_parser!(PathBuf))),
+            ),
+    ))
+}
+
+fn render(value: &Value) -> String {
+    let list = |x: Vec<String>| x.join(", ");
+    match value {
+        Value::String(x) => format!("{x:?}"),
+        Value::Data(x) => format!("b{:?}", String::from_utf8_lossy(x)),
+        Value::Number(x) => x.to_string(),
+        Value::Boolean(true) => "TRUE".to_string(),
+        Value::Boolean(false) => "FALSE".to_string(),
+        Value::Null => "NULL".to_string(),
+        Value::Array(x) => format!("[{}]", list(x.iter().map(render).collect())),
+        Value::Fork(x) => format!("fork({})", list(x.iter().map(render).collect())),
+        Value::Dict(x) => format!(
+            "{{{}}}",
+            list(
+                x.iter()
+                    .map(|(k, v)| format!("{k}: {}", render(v)))
+                    .collect()
+            )
+        ),
+    }
+}
+
+fn shorten(value: String) -> String {
+    match value.char_indices().nth(MAX_VALUE_LENGTH) {
+        Some((i, _)) => format!("{}...", &value[..i]),
+        None => value,
+    }
+}
+
+fn view(path: PathBuf, depth: Option<usize>, variable: Option<String>) -> Result<(), CliError> {
+    let steps = fs::File::open(&path)
+        .and_then(|x| Trace::read(BufReader::new(x)))
+        .map_err(|e| CliError::load_error(e, &path))?;
+    let top = steps.iter().map(|x| x.depth).min().unwrap_or_default();
+    for step in steps.iter().filter(|x| {
+        depth.map(|d| x.depth - top <= d).unwrap_or(true)
+            && variable
+                .as_ref()
+                .map(|v| x.changed.contains_key(v))
+                .unwrap_or(true)
+    }) {
+        let indent = "  ".repeat(step.depth - top);
+        let outcome = match (&step.error, &step.result) {
+            (Some(e), _) => format!(" !! {e}"),
+            (None, Some(x)) if x != &Value::Null => format!(" => {}", shorten(render(x))),
+            _ => String::new(),
+        };
+        println!("{:>5} {indent}{}{outcome}", step.line, step.statement);
+        for (name, value) in &step.changed {
+            println!("      {indent}  {name} = {}", shorten(render(value)));
+        }
+    }
+    Ok(())
+}
+
+pub async fn run(root: &clap::ArgMatches) -> Option<Result<(), CliError>> {
+    let (args, _) = crate::get_args_set_logging(root, "trace")?;
+    match args.subcommand() {
+        Some(("view", args)) => {
+            let path = match args.get_one::<PathBuf>("trace").cloned() {
+                Some(x) => x,
+                None => unreachable!("trace is set to required"),
+            };
+            let depth = args.get_one::<usize>("depth").cloned();
+            let variable = args.get_one::<String>("variable").cloned();
+            Some(view(path, depth, variable))
+        }
+        _ => None,
+    }
+}
@@ -18,6 +18,15 @@ use thiserror::Error;
 
 pub use wave::WaveExecutionPlan;
 
+/// Scan preference to schedule deprecated VTs requested by the scan.
+///
+/// Deprecated VTs exit right after the description run, so they are skipped by default.
+pub const INCLUDE_DEPRECATED: &str = "include_deprecated";
+
+fn include_deprecated(scan: &Scan) -> bool {
+    crate::models::bool_preference(&scan.scan_preferences, INCLUDE_DEPRECATED).unwrap_or_default()
+}
+
 /// Error cases for VTFetcher
 #[derive(Error, Debug, Clone)]
 pub enum VTError {
@@ -221,6 +230,7 @@ where
             .into_iter()
             .map(|x| NVTField::Oid(x.oid).into())
             .collect::<Vec<_>>();
+        let include_deprecated = include_deprecated(scan);
         let mut results = core::array::from_fn(|_| E::default());
         let mut vts = Vec::new();
         let mut unknown_dependencies = Vec::new();
@@ -233,6 +243,10 @@ where
             })
             .enumerate()
         {
+            if x.is_deprecated() && !include_deprecated {
+                tracing::debug!(oid = x.oid, "skipping deprecated VT");
+                continue;
+            }
             let params: Option<Vec<Parameter>> = scan.vts.get(i).map(|x| x.parameters.clone());
             unknown_dependencies.extend(
                 x.dependencies
@@ -281,12 +295,15 @@ where
 #[cfg(test)]
 mod tests {
     use crate::models::Scan;
+    use crate::models::ScanPreference;
     use crate::models::VT;
 
     use crate::scheduling::ExecutionPlaner;
     use crate::scheduling::Stage;
     use crate::scheduling::WaveExecutionPlan;
+    use crate::scheduling::INCLUDE_DEPRECATED;
     use crate::storage::item::Nvt;
+    use crate::storage::item::{TagKey, TagValue};
     use crate::storage::ContextKey;
     use crate::storage::DefaultDispatcher;
     use crate::storage::Dispatcher;
@@ -339,4 +356,53 @@ mod tests {
             results.filter_map(|x| x.ok()).collect::<Vec<_>>()
         )
     }
+
+    #[test]
+    fn skip_deprecated() {
+        let feed = vec![
+            Nvt {
+                oid: "0".to_string(),
+                filename: "/0".to_string(),
+                ..Default::default()
+            },
+            Nvt {
+                oid: "1".to_string(),
+                filename: "/1".to_string(),
+                tag: [(TagKey::Deprecated, TagValue::Boolean(true))].into(),
+                ..Default::default()
+            },
+        ];
+        let retrieve = DefaultDispatcher::new();
+        feed.clone().into_iter().for_each(|x| {
+            retrieve
+                .dispatch(&ContextKey::default(), x.into())
+                .expect("should store");
+        });
+        let mut scan = Scan {
+            vts: feed
+                .iter()
+                .map(|x| VT {
+                    oid: x.oid.clone(),
+                    parameters: vec![],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let oids = |scan: &Scan| {
+            let mut oids = retrieve
+                .execution_plan::<WaveExecutionPlan>(scan)
+                .expect("no error expected")
+                .filter_map(|x| x.ok())
+                .flat_map(|(_, vts)| vts.into_iter().map(|(x, _)| x.oid))
+                .collect::<Vec<_>>();
+            oids.sort();
+            oids
+        };
+        assert_eq!(oids(&scan), vec!["0"]);
+        scan.scan_preferences.push(ScanPreference {
+            id: INCLUDE_DEPRECATED.to_string(),
+            value: "true".to_string(),
+        });
+        assert_eq!(oids(&scan), vec!["0", "1"]);
+    }
 }
@@ -0,0 +1,233 @@
+// SPDX-FileCopyrightText: 2024 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later WITH x11vnc-openssl-exception
+
+//! Portable archive of all data sets of a storage.
+//!
+//! A backup is a gzip compressed file consisting of the [FORMAT] line, the [Header] as JSON line
+//! and a JSON line per key containing its base64 encoded data sets. The data sets are copied as
+//! they are stored, so the data of an encrypted storage stays encrypted and can only be read with
+//! the same key after it got restored.
+
+use std::io::{BufRead, BufReader, Read, Write};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    base::{IndexedByteStorage, Range},
+    error::{Error, IoErrorKind},
+};
+
+/// First line of each backup
+pub const FORMAT: &str = "infisto-backup/1";
+
+/// Describes a backup
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    /// Unix timestamp of the creation of the backup
+    pub created: u64,
+    /// Amount of contained keys
+    pub keys: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    data: Vec<String>,
+}
+
+fn write_line<W: Write>(out: &mut W, line: &[u8]) -> Result<(), Error> {
+    out.write_all(line)
+        .and_then(|_| out.write_all(b"\n"))
+        .map_err(|e| Error::IoError(IoErrorKind::Write, e.kind()))
+}
+
+fn read_line<R: BufRead>(input: &mut R) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(line)),
+        Err(e) => Err(Error::IoError(IoErrorKind::Read, e.kind())),
+    }
+}
+
+fn invalid(e: impl std::fmt::Display) -> Error {
+    Error::Backup(e.to_string())
+}
+
+/// Returns true when the key names a file within the storage directory.
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains(['/', '\\']) && !key.contains("..")
+}
+
+/// Writes all keys of the storage to out.
+///
+/// Keys of which only a part got stored are skipped.
+pub fn backup<S, W>(storage: &S, created: u64, out: W) -> Result<Header, Error>
+where
+    S: IndexedByteStorage,
+    W: Write,
+{
+    let keys: Vec<String> = storage
+        .keys()?
+        .into_iter()
+        .filter(|x| match storage.indices(x) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!(key = x, %e, "skipping incomplete key");
+                false
+            }
+        })
+        .collect();
+    let header = Header {
+        created,
+        keys: keys.len(),
+    };
+    let mut out = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+    write_line(&mut out, FORMAT.as_bytes())?;
+    write_line(&mut out, &serde_json::to_vec(&header).map_err(invalid)?)?;
+    for key in keys {
+        let data: Vec<Vec<u8>> = storage.by_range(&key, Range::All)?;
+        let entry = Entry {
+            data: data.iter().map(|x| BASE64.encode(x)).collect(),
+            key,
+        };
+        write_line(&mut out, &serde_json::to_vec(&entry).map_err(invalid)?)?;
+    }
+    out.finish()
+        .map_err(|e| Error::IoError(IoErrorKind::Write, e.kind()))?;
+    Ok(header)
+}
+
+/// Reader of a backup
+pub struct Backup<R> {
+    input: BufReader<flate2::read::GzDecoder<R>>,
+    header: Header,
+}
+
+impl<R: Read> Backup<R> {
+    /// Verifies the format and reads the header of a backup.
+    pub fn open(input: R) -> Result<Self, Error> {
+        let mut input = BufReader::new(flate2::read::GzDecoder::new(input));
+        match read_line(&mut input) {
+            Ok(Some(line)) if line.trim_end() == FORMAT => {}
+            _ => return Err(invalid("not a backup")),
+        }
+        let header = read_line(&mut input)?.ok_or_else(|| invalid("missing header"))?;
+        let header = serde_json::from_str(&header).map_err(invalid)?;
+        Ok(Self { input, header })
+    }
+
+    /// Returns the header of the backup.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Stores each key of the backup, replacing the data of existing keys.
+    ///
+    /// Fails when the backup contains less keys than announced by its header, e.g. when it got
+    /// truncated, the keys read until then are restored. Keys containing a path separator or `..`
+    /// are refused, as they would be written outside of the storage.
+    pub fn restore<S>(mut self, storage: &mut S) -> Result<Header, Error>
+    where
+        S: IndexedByteStorage,
+    {
+        let mut restored = 0;
+        while let Some(line) = read_line(&mut self.input)? {
+            let entry: Entry = serde_json::from_str(&line).map_err(invalid)?;
+            if !is_valid_key(&entry.key) {
+                return Err(invalid(format!("invalid key {:?}", entry.key)));
+            }
+            let data = entry
+                .data
+                .iter()
+                .map(|x| BASE64.decode(x))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(invalid)?;
+            storage.replace_all(&entry.key, &data)?;
+            restored += 1;
+        }
+        if restored != self.header.keys {
+            return Err(invalid(format!(
+                "contains {restored} of {} keys",
+                self.header.keys
+            )));
+        }
+        Ok(self.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::infisto::{ChaCha20IndexFileStorer, IndexedFileStorer};
+
+    #[test]
+    fn backup_and_restore() {
+        let source = "/tmp/openvasd/unittest_backup_source";
+        let target = "/tmp/openvasd/unittest_backup_target";
+        let _ = std::fs::remove_dir_all(source);
+        let _ = std::fs::remove_dir_all(target);
+        let files = IndexedFileStorer::init(source).unwrap();
+        let mut store = ChaCha20IndexFileStorer::new(files.clone(), "key");
+        store.put("scan_1", "scan").unwrap();
+        store.append_all("results_1", &["a", "b", "c"]).unwrap();
+
+        let mut archive = vec![];
+        let header = backup(&files, 42, &mut archive).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                created: 42,
+                keys: 2
+            }
+        );
+
+        let mut restored = IndexedFileStorer::init(target).unwrap();
+        restored.put("results_1", "stale").unwrap();
+        let backup = Backup::open(archive.as_slice()).unwrap();
+        assert_eq!(backup.header().created, 42);
+        backup.restore(&mut restored).unwrap();
+        let store = ChaCha20IndexFileStorer::new(restored, "key");
+        let results: Vec<Vec<u8>> = store.by_range("results_1", Range::All).unwrap();
+        assert_eq!(results, vec![b"a", b"b", b"c"]);
+        let scan: Vec<Vec<u8>> = store.by_range("scan_1", Range::All).unwrap();
+        assert_eq!(scan, vec![b"scan"]);
+
+        let truncated = &archive[..archive.len() / 2];
+        assert!(Backup::open(truncated)
+            .and_then(|x| x.restore(&mut files.clone()))
+            .is_err());
+        assert!(Backup::open(&b"not a backup"[..]).is_err());
+        // no temporary files are left
+        assert_eq!(
+            store.keys().unwrap(),
+            vec!["results_1".to_string(), "scan_1".to_string()]
+        );
+        std::fs::remove_dir_all(source).unwrap();
+        std::fs::remove_dir_all(target).unwrap();
+    }
+    #[test]
+    fn refuses_path_traversal() {
+        let target = "/tmp/openvasd/unittest_backup_traversal";
+        let _ = std::fs::remove_dir_all(target);
+        let mut storage = IndexedFileStorer::init(target).unwrap();
+        for key in ["../escaped", "a/b", "a\\b", ""] {
+            let mut archive = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+            write_line(&mut archive, FORMAT.as_bytes()).unwrap();
+            write_line(&mut archive, br#"{"created":1,"keys":1}"#).unwrap();
+            let entry = Entry {
+                key: key.to_string(),
+                data: vec![BASE64.encode("data")],
+            };
+            write_line(&mut archive, &serde_json::to_vec(&entry).unwrap()).unwrap();
+            let archive = archive.finish().unwrap();
+            let result = Backup::open(archive.as_slice()).and_then(|x| x.restore(&mut storage));
+            assert!(matches!(result, Err(Error::Backup(_))), "{key:?}");
+        }
+        assert!(storage.keys().unwrap().is_empty());
+        assert!(!std::path::Path::new("/tmp/openvasd/escaped.dat").exists());
+        std::fs::remove_dir_all(target).unwrap();
+    }
+}
@@ -76,6 +76,15 @@ pub trait IndexedByteStorage {
         T: AsRef<[u8]>;
     /// Removes idx and data file of given key.
     fn remove(&mut self, key: &str) -> Result<(), Error>;
+    /// Replaces the data of the given key with the given data sets.
+    fn replace_all<T>(&mut self, key: &str, data: &[T]) -> Result<(), Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        // there is nothing to replace when the key does not exist yet
+        let _ = self.remove(key);
+        self.append_all(key, data)
+    }
     /// Returns the data for the given key and range.
     fn by_range<T>(&self, key: &str, range: Range) -> Result<Vec<T>, Error>
     where
@@ -104,6 +113,24 @@ pub trait IndexedByteStorage {
 
     /// Returns all the indices of the data for the given key.
     fn indices(&self, key: &str) -> Result<Vec<Index>, Error>;
+
+    /// Returns the keys of the stored data, including keys of which only a part got stored.
+    fn keys(&self) -> Result<Vec<String>, Error> {
+        Ok(vec![])
+    }
+
+    /// Returns the amount of bytes used by the stored data.
+    fn size(&self) -> Result<u64, Error> {
+        Ok(0)
+    }
+
+    /// Removes the bytes of the given key that are not referenced by its index.
+    ///
+    /// A key of which only the index or only the data exists is removed. Returns the amount of
+    /// reclaimed bytes.
+    fn compact(&mut self, _key: &str) -> Result<u64, Error> {
+        Ok(0)
+    }
 }
 
 impl IndexedFileStorer {
@@ -229,6 +256,70 @@ impl IndexedFileStorer {
         Ok(index)
     }
 
+    fn file_len(&self, file: &str) -> Option<u64> {
+        fs::metadata(self.base.join(file)).ok().map(|x| x.len())
+    }
+
+    /// Returns the dat and idx files within the base path.
+    fn files(&self) -> Result<Vec<(String, u64)>, Error> {
+        let entries = fs::read_dir(&self.base)
+            .map_err(|e| Error::IoError(IoErrorKind::FileOpen, e.kind()))?;
+        Ok(entries
+            .filter_map(|x| x.ok())
+            .filter_map(|x| {
+                let name = x.file_name().into_string().ok()?;
+                let len = x.metadata().ok().filter(|x| x.is_file())?.len();
+                (name.ends_with(".dat") || name.ends_with(".idx")).then_some((name, len))
+            })
+            .collect())
+    }
+
+    /// Rewrites the data file of key to only contain the data referenced by the index.
+    ///
+    /// Data sets that are no longer referenced, e.g. due to an interrupted append, are dropped.
+    /// Returns the amount of reclaimed bytes.
+    pub fn rewrite(&self, key: &str) -> Result<u64, Error> {
+        let dat = format!("{}.dat", key);
+        let idx = format!("{}.idx", key);
+        let remove = |file: &str, len: u64| {
+            fs::remove_file(self.base.join(file))
+                .map(|_| len)
+                .map_err(|e| Error::IoError(IoErrorKind::Remove, e.kind()))
+        };
+        let size = match (self.file_len(&dat), self.file_len(&idx)) {
+            (Some(size), Some(_)) => size,
+            (Some(size), None) => return remove(&dat, size),
+            (None, Some(size)) => return remove(&idx, size),
+            (None, None) => return Ok(0),
+        };
+        let index = self.load_index(key)?;
+        let used: usize = index.iter().map(|x| x.end - x.start).sum();
+        if used as u64 >= size {
+            return Ok(0);
+        }
+        let data: Vec<Vec<u8>> = self.by_indices(key, &index)?;
+        let tmp = format!("{}.compacting", key);
+        let path = self.base.join(&tmp);
+        let mut file = open_file(
+            &path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )?;
+        let mut index = Vec::with_capacity(data.len());
+        let mut start = 0;
+        for d in &data {
+            write_file(&mut file, d)?;
+            index.push(Index {
+                start,
+                end: start + d.len(),
+            });
+            start += d.len();
+        }
+        fs::rename(&path, self.base.join(&dat))
+            .map_err(|e| Error::IoError(IoErrorKind::Write, e.kind()))?;
+        self.store_index(&index, key)?;
+        Ok(size - start as u64)
+    }
+
     /// Removes dat and idx files from the file system.
     pub fn clean(&self, key: &str) -> Result<(), Error> {
         let remove_file = |path| {
@@ -272,10 +363,55 @@ impl IndexedByteStorage for IndexedFileStorer {
         self.clean(key)
     }
 
+    /// Writes the data sets to temporary files that replace the ones of the key afterwards, so
+    /// that the previous data is kept when writing fails.
+    fn replace_all<T>(&mut self, key: &str, data: &[T]) -> Result<(), Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        if data.is_empty() {
+            let _ = self.clean(key);
+            return Ok(());
+        }
+        let tmp = format!("{}.replacing", key);
+        let _ = self.clean(&tmp);
+        self.append_all(&tmp, data)?;
+        for ext in ["dat", "idx"] {
+            fs::rename(
+                self.base.join(format!("{}.{}", tmp, ext)),
+                self.base.join(format!("{}.{}", key, ext)),
+            )
+            .map_err(|e| Error::IoError(IoErrorKind::Write, e.kind()))?;
+        }
+        Ok(())
+    }
+
     fn indices(&self, key: &str) -> Result<Vec<Index>, Error> {
         self.load_index(key)
     }
 
+    fn keys(&self) -> Result<Vec<String>, Error> {
+        let mut keys: Vec<String> = self
+            .files()?
+            .into_iter()
+            .map(|(mut name, _)| {
+                name.truncate(name.len() - 4);
+                name
+            })
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn size(&self) -> Result<u64, Error> {
+        Ok(self.files()?.iter().map(|(_, len)| len).sum())
+    }
+
+    fn compact(&mut self, key: &str) -> Result<u64, Error> {
+        self.rewrite(key)
+    }
+
     fn by_indices<T>(&self, key: &str, indices: &[Index]) -> Result<Vec<T>, Error>
     where
         T: TryFrom<Vec<u8>>,
@@ -459,6 +595,19 @@ impl IndexedByteStorage for CachedIndexFileStorer {
         Ok(())
     }
 
+    fn replace_all<T>(&mut self, key: &str, data: &[T]) -> Result<(), Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.base.replace_all(key, data)?;
+        for entry in self.cache.iter_mut() {
+            if entry.as_ref().is_some_and(|(k, _)| k == key) {
+                *entry = None;
+            }
+        }
+        Ok(())
+    }
+
     fn indices(&self, key: &str) -> Result<Vec<Index>, Error> {
         if let Some((_, fi)) = self.find_index(key) {
             Ok(fi.clone())
@@ -467,6 +616,25 @@ impl IndexedByteStorage for CachedIndexFileStorer {
         }
     }
 
+    fn keys(&self) -> Result<Vec<String>, Error> {
+        self.base.keys()
+    }
+
+    fn size(&self) -> Result<u64, Error> {
+        self.base.size()
+    }
+
+    /// Compacts the files of the key and drops its cached index.
+    fn compact(&mut self, key: &str) -> Result<u64, Error> {
+        let reclaimed = self.base.rewrite(key)?;
+        for entry in self.cache.iter_mut() {
+            if entry.as_ref().is_some_and(|(k, _)| k == key) {
+                *entry = None;
+            }
+        }
+        Ok(reclaimed)
+    }
+
     fn by_indices<T>(&self, key: &str, indices: &[Index]) -> Result<Vec<T>, Error>
     where
         T: TryFrom<Vec<u8>>,
@@ -764,3 +932,34 @@ mod indexed {
         store.clean(key).unwrap();
     }
 }
+
+#[cfg(test)]
+mod compact {
+    use super::*;
+    const BASE: &str = "/tmp/openvasd/unittest_compact";
+
+    #[test]
+    fn unreferenced_data() {
+        let mut store = CachedIndexFileStorer::init(BASE).unwrap();
+        store.append_all("a", &["Hello", " ", "World"]).unwrap();
+        let index = store.indices("a").unwrap();
+        // an append interrupted before the index got stored
+        store.base.store_index(&index[..2], "a").unwrap();
+        store.put("c", "only data").unwrap();
+        fs::remove_file(Path::new(BASE).join("c.idx")).unwrap();
+        assert_eq!(store.keys().unwrap(), vec!["a", "c"]);
+
+        assert_eq!(store.compact("a").unwrap(), 5);
+        assert_eq!(store.compact("a").unwrap(), 0);
+        assert_eq!(store.compact("c").unwrap(), 9);
+        assert_eq!(store.keys().unwrap(), vec!["a"]);
+        store.append("a", "there").unwrap();
+        let data: Vec<Vec<u8>> = store.by_range("a", Range::All).unwrap();
+        assert_eq!(data.concat(), b"Hello there");
+        assert_eq!(
+            store.size().unwrap(),
+            11 + store.base.file_len("a.idx").unwrap()
+        );
+        store.remove("a").unwrap();
+    }
+}
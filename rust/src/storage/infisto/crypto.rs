@@ -152,10 +152,33 @@ where
         self.store.remove(key)
     }
 
+    fn replace_all<T>(&mut self, key: &str, data: &[T]) -> Result<(), Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        let data = data
+            .iter()
+            .map(|d| Self::encrypt(&self.key, d.as_ref().to_vec()))
+            .collect::<Vec<_>>();
+        self.store.replace_all(key, &data)
+    }
+
     fn indices(&self, key: &str) -> Result<Vec<Index>, Error> {
         self.store.indices(key)
     }
 
+    fn keys(&self) -> Result<Vec<String>, Error> {
+        self.store.keys()
+    }
+
+    fn size(&self) -> Result<u64, Error> {
+        self.store.size()
+    }
+
+    fn compact(&mut self, key: &str) -> Result<u64, Error> {
+        self.store.compact(key)
+    }
+
     fn by_indices<T>(&self, key: &str, indices: &[Index]) -> Result<Vec<T>, Error>
     where
         T: TryFrom<Vec<u8>>,
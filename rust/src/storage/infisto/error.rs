@@ -9,6 +9,9 @@ pub enum Error {
     /// The index could not be serialized.
     #[error("Could not serialize index")]
     Serialize,
+    /// The backup could not be read.
+    #[error("Invalid backup: {0}")]
+    Backup(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
@@ -4,6 +4,7 @@
 
 #![doc = include_str!("README.md")]
 
+pub mod archive;
 mod base;
 mod crypto;
 mod error;
@@ -10,11 +10,13 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::models::{self, Vulnerability, VulnerabilityData};
+use thiserror::Error;
+
+use crate::models::{self, cvss, Reference, Vulnerability, VulnerabilityData};
 
 use crate::storage::{
-    time::AsUnixTimeStamp, types, ContextKey, Dispatcher, Field, Kb, NotusAdvisory, Remover,
-    Retriever, StorageError,
+    time::AsUnixTimeStamp, types, ContextKey, Dispatcher, Field, Kb, KbSnapshot, NotusAdvisory,
+    Remover, Retriever, StorageError,
 };
 
 use super::{FieldKeyResult, Retrieve};
@@ -501,6 +503,44 @@ pub struct Nvt {
     pub family: String,
 }
 
+/// Describes why an assembled NVT is rejected
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum NvtError {
+    /// The script did not set an OID
+    #[error("missing OID")]
+    MissingOid,
+    /// The OID is not a dotted sequence of numbers
+    #[error("invalid OID '{0}'")]
+    InvalidOid(String),
+    /// The value of a tag is not valid for its key
+    #[error("invalid value '{value}' of tag {key}")]
+    InvalidTag {
+        /// Key of the tag
+        key: TagKey,
+        /// Value of the tag
+        value: String,
+    },
+    /// The reference is not valid for its type
+    #[error("invalid reference '{id}' of type '{class}'")]
+    InvalidReference {
+        /// Type of the reference
+        class: String,
+        /// ID of the reference
+        id: String,
+    },
+}
+
+/// Returns true when the OID consists of numbers separated by dots.
+fn is_oid(oid: &str) -> bool {
+    oid.split('.')
+        .all(|x| !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit()))
+}
+
+/// Returns true when the vector can be scored, CVSS v4 vectors cannot be scored yet.
+fn is_cvss_vector(vector: &str) -> bool {
+    vector.starts_with("CVSS:4.") || cvss::base_score(vector).is_some()
+}
+
 impl Display for Nvt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "VT {} ({})", self.oid, self.filename)
@@ -508,6 +548,72 @@ impl Display for Nvt {
 }
 
 impl Nvt {
+    /// Returns true when the script is marked as deprecated via the deprecated tag.
+    pub fn is_deprecated(&self) -> bool {
+        matches!(
+            self.tag.get(&TagKey::Deprecated),
+            Some(TagValue::Boolean(true))
+        )
+    }
+
+    /// Returns the quality of detection of the script from 0 to 100.
+    ///
+    /// A qod tag takes precedence over the qod_type tag, scripts without both get the QoD of the
+    /// default type.
+    pub fn qod(&self) -> u8 {
+        if let Some(qod) = self
+            .tag
+            .get(&TagKey::Qod)
+            .and_then(|x| x.to_string().parse::<u8>().ok())
+            .filter(|x| *x <= 100)
+        {
+            return qod;
+        }
+        let qod_type = self
+            .tag
+            .get(&TagKey::QodType)
+            .and_then(|x| QodType::from_str(&x.to_string()).ok())
+            .unwrap_or(QodType::Default);
+        i64::from(qod_type) as u8
+    }
+
+    /// Validates an NVT assembled by a description run before it is stored.
+    ///
+    /// The category and the tag keys are typed already; the OID, the values of the tags that
+    /// have a format and the references are checked.
+    pub fn validate(&self) -> Result<(), NvtError> {
+        if self.oid.is_empty() {
+            return Err(NvtError::MissingOid);
+        }
+        if !is_oid(&self.oid) {
+            return Err(NvtError::InvalidOid(self.oid.clone()));
+        }
+        for (key, value) in &self.tag {
+            let valid = match key {
+                TagKey::Qod => value.to_string().parse::<u8>().is_ok_and(|x| x <= 100),
+                TagKey::CvssBaseVector | TagKey::SeverityVector => {
+                    is_cvss_vector(&value.to_string())
+                }
+                _ => true,
+            };
+            if !valid {
+                return Err(NvtError::InvalidTag {
+                    key: *key,
+                    value: value.to_string(),
+                });
+            }
+        }
+        for reference in &self.references {
+            if Reference::resolve([(reference.class(), reference.id())]).is_empty() {
+                return Err(NvtError::InvalidReference {
+                    class: reference.class.clone(),
+                    id: reference.id.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Returns Err with the feed_version if it is a version Ok otherwise
     pub fn set_from_field(&mut self, field: NVTField) -> Result<(), String> {
         match field {
@@ -956,6 +1062,10 @@ where
         self.dispatcher.retrieve(key, scope)
     }
 
+    fn retrieve_kb_snapshot(&self, key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        self.dispatcher.retrieve_kb_snapshot(key)
+    }
+
     fn retrieve_by_field(
         &self,
         field: Field,
@@ -1032,4 +1142,52 @@ mod tests {
         summary => Summary,
         vuldetect => Vuldetect
     }
+
+    #[test]
+    fn nvt_qod() {
+        use super::*;
+        let mut nvt = Nvt::default();
+        assert_eq!(nvt.qod(), 70);
+        nvt.tag.insert(
+            TagKey::QodType,
+            TagValue::parse(TagKey::QodType, "package").unwrap(),
+        );
+        assert_eq!(nvt.qod(), 97);
+        nvt.tag
+            .insert(TagKey::Qod, TagValue::parse(TagKey::Qod, "30").unwrap());
+        assert_eq!(nvt.qod(), 30);
+        nvt.tag.insert(TagKey::Qod, TagValue::from(101));
+        assert_eq!(nvt.qod(), 97);
+    }
+
+    #[test]
+    fn nvt_validate() {
+        use super::*;
+        let mut nvt = Nvt::default();
+        assert_eq!(nvt.validate(), Err(NvtError::MissingOid));
+        nvt.oid = "1.3.6.1.4.1.25623.1.0.10330".into();
+        assert_eq!(nvt.validate(), Ok(()));
+        nvt.tag.insert(
+            TagKey::SeverityVector,
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H".into(),
+        );
+        nvt.tag.insert(TagKey::Qod, TagValue::from(101));
+        assert_eq!(
+            nvt.validate(),
+            Err(NvtError::InvalidTag {
+                key: TagKey::Qod,
+                value: "101".into()
+            })
+        );
+        nvt.tag.insert(TagKey::Qod, TagValue::from(80));
+        nvt.references.push(("cve", "CVE-2021-44228").into());
+        assert_eq!(nvt.validate(), Ok(()));
+        nvt.references.push(("cve", "2021-44228").into());
+        assert!(matches!(
+            nvt.validate(),
+            Err(NvtError::InvalidReference { .. })
+        ));
+        nvt.oid = "1.3.6.x".into();
+        assert_eq!(nvt.validate(), Err(NvtError::InvalidOid("1.3.6.x".into())));
+    }
 }
@@ -38,6 +38,11 @@ where
     pub fn end(&mut self) -> io::Result<()> {
         self.w.write_all(b"]")
     }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
 }
 
 impl<W> Write for ArrayWrapper<W>
@@ -144,7 +149,7 @@ where
                 let kbs = self.kbs.lock().map_err(StorageError::from)?;
                 let kbs = kbs.clone();
                 kbs.into_iter()
-                    .filter(move |x| x.key == s)
+                    .filter(move |x| storage::matches_kb_key(&s, &x.key))
                     .map(|x| storage::Field::KB(x.clone()))
             }),
         })
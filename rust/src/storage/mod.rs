@@ -21,6 +21,7 @@ use std::{
     fmt::Display,
     io,
     sync::{Arc, PoisonError, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
 use types::Primitive;
@@ -126,6 +127,19 @@ where
     }
 }
 
+/// The KB items of a context key at a certain time
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct KbSnapshot {
+    /// Unix timestamp of the last modification of the KB items
+    pub updated: u64,
+    /// The KB items
+    pub items: Vec<Kb>,
+}
+
 /// Redefine Vulnerability so that other libraries using that don't have to include models
 pub type NotusAdvisory = VulnerabilityData;
 
@@ -253,6 +267,17 @@ pub trait Dispatcher: Sync + Send {
     /// Some database require a cleanup therefore this method is called when a script finishes.
     fn on_exit(&self, key: &ContextKey) -> Result<(), StorageError>;
 
+    /// Distributes the given fields under a key at once.
+    ///
+    /// Storages that support it write the fields within a single transaction, by default they are
+    /// dispatched one after another.
+    fn dispatch_batch(&self, key: &ContextKey, fields: Vec<Field>) -> Result<(), StorageError> {
+        for field in fields {
+            self.dispatch(key, field)?;
+        }
+        Ok(())
+    }
+
     /// Retries a dispatch for the amount of retries when a retrievable error occurs.
     fn retry_dispatch(
         &self,
@@ -317,6 +342,10 @@ where
         self.as_ref().on_exit(key)
     }
 
+    fn dispatch_batch(&self, key: &ContextKey, fields: Vec<Field>) -> Result<(), StorageError> {
+        self.as_ref().dispatch_batch(key, fields)
+    }
+
     fn retry_dispatch(
         &self,
         retries: usize,
@@ -358,6 +387,10 @@ where
         self.as_ref().retrieve(key, scope)
     }
 
+    fn retrieve_kb_snapshot(&self, key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        self.as_ref().retrieve_kb_snapshot(key)
+    }
+
     fn retrieve_by_field(&self, field: Field, scope: Retrieve) -> FieldKeyResult {
         self.as_ref().retrieve_by_field(field, scope)
     }
@@ -425,6 +458,7 @@ pub struct DefaultDispatcher {
     feed_version: Arc<RwLock<String>>,
     advisories: Arc<RwLock<HashSet<NotusAdvisory>>>,
     kbs: Arc<RwLock<Kbs>>,
+    kbs_updated: Arc<RwLock<HashMap<ContextKey, u64>>>,
     results: Arc<RwLock<Results>>,
 }
 
@@ -476,9 +510,25 @@ impl DefaultDispatcher {
         Ok(())
     }
 
+    fn kb_updated(&self, ck: &ContextKey) -> Result<(), StorageError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        self.kbs_updated.as_ref().write()?.insert(ck.clone(), now);
+        Ok(())
+    }
+
     fn cache_kb(&self, ck: ContextKey, kb: Kb) -> Result<(), StorageError> {
+        self.cache_kbs(ck, vec![kb])
+    }
+
+    /// Caches the KB items while holding the lock once.
+    fn cache_kbs(&self, ck: ContextKey, kbs: Vec<Kb>) -> Result<(), StorageError> {
+        self.kb_updated(&ck)?;
         let mut data = self.kbs.as_ref().write()?;
-        if let Some(scan_entry) = data.get_mut(&ck) {
+        let scan_entry = data.entry(ck).or_default();
+        for kb in kbs {
             if let Some(kb_entry) = scan_entry.get_mut(&kb.key) {
                 if !kb_entry.iter().any(|x| x.value == kb.value) {
                     kb_entry.push(kb);
@@ -486,15 +536,12 @@ impl DefaultDispatcher {
             } else {
                 scan_entry.insert(kb.key.clone(), vec![kb]);
             }
-        } else {
-            let mut scan_entry = HashMap::new();
-            scan_entry.insert(kb.key.clone(), vec![kb]);
-            data.insert(ck, scan_entry);
         }
         Ok(())
     }
 
     fn replace_kb(&self, ck: &ContextKey, kb: Kb) -> Result<(), StorageError> {
+        self.kb_updated(ck)?;
         let mut data = self.kbs.as_ref().write()?;
         if let Some(scan_entry) = data.get_mut(ck) {
             if let Some(kb_entry) = scan_entry.get_mut(&kb.key) {
@@ -537,6 +584,15 @@ impl DefaultDispatcher {
         Ok(vts.chain(notus))
     }
 
+    /// Replaces all stored nasl_vts at once and resets the feed version
+    pub fn replace_vts(&self, vts: Vts) -> Result<(), StorageError> {
+        let mut data = self.vts.write()?;
+        let mut version = self.feed_version.write()?;
+        *data = vts;
+        *version = String::new();
+        Ok(())
+    }
+
     /// Removes all stored nasl_vts
     pub fn clean_vts(&self) -> Result<(), StorageError> {
         let mut vts = self.vts.write()?;
@@ -562,9 +618,11 @@ impl Remover for DefaultDispatcher {
     ) -> Result<Option<Vec<Kb>>, StorageError> {
         let mut kbs = self.kbs.write().unwrap();
         Ok(match kb_key {
-            None => kbs
-                .remove(key)
-                .map(|x| x.values().flat_map(|x| x.clone()).collect()),
+            None => {
+                self.kbs_updated.write()?.remove(key);
+                kbs.remove(key)
+                    .map(|x| x.values().flat_map(|x| x.clone()).collect())
+            }
             Some(x) => {
                 if let Some(kbs) = kbs.get_mut(key) {
                     kbs.remove(&x)
@@ -626,6 +684,25 @@ impl Dispatcher for DefaultDispatcher {
     fn on_exit(&self, _: &ContextKey) -> Result<(), StorageError> {
         Ok(())
     }
+
+    fn dispatch_batch(&self, key: &ContextKey, fields: Vec<Field>) -> Result<(), StorageError> {
+        let (kbs, others): (Vec<_>, Vec<_>) =
+            fields.into_iter().partition(|x| matches!(x, Field::KB(_)));
+        let kbs: Vec<Kb> = kbs
+            .into_iter()
+            .filter_map(|x| match x {
+                Field::KB(kb) => Some(kb),
+                _ => None,
+            })
+            .collect();
+        if !kbs.is_empty() {
+            self.cache_kbs(key.clone(), kbs)?;
+        }
+        for field in others {
+            self.dispatch(key, field)?;
+        }
+        Ok(())
+    }
 }
 
 /// Holds iterator in memory
@@ -683,6 +760,24 @@ impl Retriever for DefaultDispatcher {
                 };
                 Ok(Box::new(data.into_iter()))
             }
+            Retrieve::KB(kb_id) if kb_id.contains('*') => {
+                let kbs = self.kbs.as_ref().read()?;
+                let mut matching = kbs
+                    .get(key)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(k, _)| matches_kb_key(&kb_id, k))
+                    .collect::<Vec<_>>();
+                matching.sort_by(|a, b| a.0.cmp(b.0));
+                let kbs = matching
+                    .into_iter()
+                    .flat_map(|(_, kbs)| kbs.clone())
+                    .collect::<Vec<_>>();
+                let data = InMemoryDataWrapper {
+                    inner: Box::new(kbs.into_iter().map(|x| x.into())),
+                };
+                Ok(Box::new(data.into_iter()))
+            }
             Retrieve::KB(kb_id) => {
                 let kbs = self.kbs.as_ref().read()?;
                 // TODO: maybe return all when x is empty?
@@ -745,6 +840,24 @@ impl Retriever for DefaultDispatcher {
         }
     }
 
+    fn retrieve_kb_snapshot(&self, key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        let kbs = self.kbs.as_ref().read()?;
+        let Some(kbs) = kbs.get(key) else {
+            return Ok(None);
+        };
+        let updated = self
+            .kbs_updated
+            .as_ref()
+            .read()?
+            .get(key)
+            .copied()
+            .unwrap_or_default();
+        Ok(Some(KbSnapshot {
+            updated,
+            items: kbs.values().flatten().cloned().collect(),
+        }))
+    }
+
     fn retrieve_by_field(
         &self,
         field: Field,
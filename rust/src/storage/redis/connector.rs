@@ -9,6 +9,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
+use std::time::Duration;
 
 use super::dberror::DbError;
 use super::dberror::RedisStorageResult;
@@ -181,10 +182,35 @@ pub const FEEDUPDATE_SELECTOR: &[NameSpaceSelector] =
 pub const NOTUSUPDATE_SELECTOR: &[NameSpaceSelector] =
     &[NameSpaceSelector::Key(NOTUS_KEY), NameSpaceSelector::Free];
 
+/// A list command that can be sent within a pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListCommand {
+    /// Deletes the key
+    Del(String),
+    /// Appends the values to the list of the key
+    RPush(String, Vec<String>),
+    /// Prepends the values to the list of the key
+    LPush(String, Vec<String>),
+}
+
 pub trait RedisWrapper {
     fn rpush<T: ToRedisArgs>(&mut self, key: &str, val: T) -> RedisStorageResult<()>;
     fn lpush<T: ToRedisArgs>(&mut self, key: &str, val: T) -> RedisStorageResult<()>;
     fn del(&mut self, key: &str) -> RedisStorageResult<()>;
+    /// Executes the commands in the given order.
+    ///
+    /// Implementations should send all commands within a single round trip, the default executes
+    /// them one by one.
+    fn pipeline(&mut self, commands: Vec<ListCommand>) -> RedisStorageResult<()> {
+        for command in commands {
+            match command {
+                ListCommand::Del(key) => self.del(&key)?,
+                ListCommand::RPush(key, values) => self.rpush(&key, values)?,
+                ListCommand::LPush(key, values) => self.lpush(&key, values)?,
+            }
+        }
+        Ok(())
+    }
     fn lindex(&mut self, key: &str, index: isize) -> RedisStorageResult<String>;
     fn lrange(&mut self, key: &str, start: isize, end: isize) -> RedisStorageResult<Vec<String>>;
     fn keys(&mut self, pattern: &str) -> RedisStorageResult<Vec<String>>;
@@ -195,63 +221,52 @@ impl RedisWrapper for RedisCtx {
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn rpush<T: ToRedisArgs>(&mut self, key: &str, val: T) -> RedisStorageResult<()> {
-        self.kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .rpush(key, val)
-            .map_err(|e| e.into())
+        self.connection()?.rpush(key, val).map_err(|e| e.into())
     }
 
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn lpush<T: ToRedisArgs>(&mut self, key: &str, val: T) -> RedisStorageResult<()> {
-        self.kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .lpush(key, val)
-            .map_err(|e| e.into())
+        self.connection()?.lpush(key, val).map_err(|e| e.into())
     }
 
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn del(&mut self, key: &str) -> RedisStorageResult<()> {
-        self.kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .del(key)
-            .map_err(|e| e.into())
+        self.connection()?.del(key).map_err(|e| e.into())
+    }
+
+    fn pipeline(&mut self, commands: Vec<ListCommand>) -> RedisStorageResult<()> {
+        let mut pipe = redis::pipe();
+        for command in commands {
+            match command {
+                ListCommand::Del(key) => pipe.del(key).ignore(),
+                ListCommand::RPush(key, values) => pipe.rpush(key, values).ignore(),
+                ListCommand::LPush(key, values) => pipe.lpush(key, values).ignore(),
+            };
+        }
+        pipe.query::<()>(self.connection()?)?;
+        Ok(())
     }
 
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn lindex(&mut self, key: &str, index: isize) -> RedisStorageResult<String> {
-        let ret: RedisValueHandler = self
-            .kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .lindex(key, index)?;
+        let ret: RedisValueHandler = self.connection()?.lindex(key, index)?;
         Ok(ret.v)
     }
 
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn lrange(&mut self, key: &str, start: isize, end: isize) -> RedisStorageResult<Vec<String>> {
-        let ret = self
-            .kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .lrange(key, start, end)?;
+        let ret = self.connection()?.lrange(key, start, end)?;
         Ok(ret)
     }
 
     ///Wrapper function to avoid accessing kb member directly.
     #[inline(always)]
     fn keys(&mut self, pattern: &str) -> RedisStorageResult<Vec<String>> {
-        let ret: Vec<String> = self
-            .kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .keys(pattern)?;
+        let ret: Vec<String> = self.connection()?.keys(pattern)?;
         Ok(ret)
     }
 
@@ -264,8 +279,7 @@ impl RedisWrapper for RedisCtx {
             .cmd("DEL")
             .arg(key)
             .ignore()
-            .query(&mut self.kb.as_mut().unwrap())
-            .unwrap();
+            .query(self.connection()?)?;
         // Since items are lpushed, the returned vector must be reversed to keep the order.
         let mut status = ret.0;
         status.reverse();
@@ -536,32 +550,35 @@ pub trait RedisAddNvt: RedisWrapper {
         let (cves, bids, xrefs) = Self::refs(&nvt.references);
 
         let key_name = format!("nvt:{oid}");
-        let values = [
-            &filename,
-            &required_keys,
-            &mandatory_keys,
-            &excluded_keys,
-            &required_udp_ports,
-            &required_ports,
-            &dependencies,
-            &tags,
-            &cves,
-            &bids,
-            &xrefs,
-            &category,
-            &family,
-            &name,
+        let values = vec![
+            filename.clone(),
+            required_keys,
+            mandatory_keys,
+            excluded_keys,
+            required_udp_ports,
+            required_ports,
+            dependencies,
+            tags,
+            cves,
+            bids,
+            xrefs,
+            category,
+            family,
+            name,
+        ];
+        // All fields of a NVT are sent at once to not wait for each command while uploading a
+        // whole feed.
+        let mut commands = vec![
+            ListCommand::Del(key_name.clone()),
+            ListCommand::RPush(key_name, values),
         ];
-        self.del(&key_name)?;
-        self.rpush(&key_name, &values)?;
 
         // Add preferences
         let prefs = Self::prefs(&nvt.preferences);
         if !prefs.is_empty() {
             let key_name = format!("oid:{oid}:prefs");
-            self.del(&key_name)?;
-            self.lpush(&key_name, prefs)?;
-            //self.kb.lpush(&key_name, prefs)?;
+            commands.push(ListCommand::Del(key_name.clone()));
+            commands.push(ListCommand::LPush(key_name, prefs));
         }
 
         // Stores the OID under the filename key. This key is currently used
@@ -572,8 +589,8 @@ pub trait RedisAddNvt: RedisWrapper {
         // under the filename key is added.
         // Once openvas is no longer used, the dummy item can be removed.
         let key_name = format!("filename:{filename}");
-        self.rpush(&key_name, &["1", &oid])?;
-        Ok(())
+        commands.push(ListCommand::RPush(key_name, vec!["1".to_string(), oid]));
+        self.pipeline(commands)
     }
 }
 
@@ -581,9 +598,27 @@ impl RedisAddNvt for RedisCtx {}
 
 impl RedisCtx {
     pub fn open(address: &str, selector: &[NameSpaceSelector]) -> RedisStorageResult<Self> {
+        Self::open_with_timeout(address, selector, None)
+    }
+
+    /// Opens a connection like [RedisCtx::open], with a timeout connecting as well as reading
+    /// and writing each command fails when it is not done in time.
+    pub fn open_with_timeout(
+        address: &str,
+        selector: &[NameSpaceSelector],
+        timeout: Option<Duration>,
+    ) -> RedisStorageResult<Self> {
         let client = redis::Client::open(address)?;
 
-        let mut kb = client.get_connection()?;
+        let mut kb = match timeout {
+            Some(timeout) => {
+                let kb = client.get_connection_with_timeout(timeout)?;
+                kb.set_read_timeout(Some(timeout))?;
+                kb.set_write_timeout(Some(timeout))?;
+                kb
+            }
+            None => client.get_connection()?,
+        };
         for s in selector {
             match s.select(&mut kb) {
                 Ok(x) => {
@@ -599,6 +634,14 @@ impl RedisCtx {
         Err(DbError::NoAvailDbErr)
     }
 
+    /// Returns the connection or an error when there is none
+    fn connection(&mut self) -> RedisStorageResult<&mut Connection> {
+        let db = self.db;
+        self.kb
+            .as_mut()
+            .ok_or_else(|| DbError::ConnectionLost(format!("no connection to db {db}")))
+    }
+
     /// Delete an entry from the in-use namespace's list
     fn release_namespace(&mut self) -> RedisStorageResult<()> {
         // Remove the entry from the in-use list and return to the original namespace
@@ -611,39 +654,31 @@ impl RedisCtx {
             .cmd("SELECT")
             .arg(self.db)
             .ignore()
-            .query::<()>(&mut self.kb.as_mut().expect("Valid redis connection"))?;
+            .query::<()>(self.connection()?)?;
         Ok(())
     }
 
     /// Delete all keys in the namespace and release the it
     pub fn delete_namespace(&mut self) -> RedisStorageResult<()> {
-        Cmd::new()
-            .arg("FLUSHDB")
-            .query::<()>(&mut self.kb.as_mut().expect("Valid redis connection"))?;
+        Cmd::new().arg("FLUSHDB").query::<()>(self.connection()?)?;
         self.release_namespace()?;
         Ok(())
     }
 
     /// Clean up the namespace.
     pub fn flush_namespace(&mut self) -> RedisStorageResult<()> {
-        Cmd::new()
-            .arg("FLUSHDB")
-            .query::<()>(&mut self.kb.as_mut().expect("Valid redis connection"))?;
+        Cmd::new().arg("FLUSHDB").query::<()>(self.connection()?)?;
         Ok(())
     }
 
     //Wrapper function to avoid accessing kb member directly.
     pub fn set_value<T: ToRedisArgs>(&mut self, key: &str, val: T) -> RedisStorageResult<()> {
-        () = self
-            .kb
-            .as_mut()
-            .expect("Valid redis connection")
-            .set(key, val)?;
+        () = self.connection()?.set(key, val)?;
         Ok(())
     }
 
     pub fn value(&mut self, key: &str) -> RedisStorageResult<String> {
-        let ret: RedisValueHandler = self.kb.as_mut().expect("Valid redis connection").get(key)?;
+        let ret: RedisValueHandler = self.connection()?.get(key)?;
         Ok(ret.v)
     }
 }
@@ -712,6 +747,31 @@ impl CacheDispatcher<RedisCtx> {
     pub fn flushdb(&self) -> RedisStorageResult<()> {
         self.lock_cache()?.flush_namespace()
     }
+
+    /// Replaces the NVTs of the feed namespace
+    ///
+    /// The NVTs are stored within a free namespace first, which only takes over the feed version
+    /// when all of them are stored. On failure the previous NVTs are kept.
+    pub fn replace_nvts(
+        redis_url: &str,
+        nvts: impl IntoIterator<Item = Nvt>,
+    ) -> Result<(), StorageError> {
+        let mut current = RedisCtx::open(redis_url, FEEDUPDATE_SELECTOR)?;
+        let version = current.lindex(CACHE_KEY, 0)?;
+        let staging = Self::init(redis_url, &[NameSpaceSelector::Free])?;
+        let stored = nvts
+            .into_iter()
+            .try_for_each(|nvt| staging.dispatch_nvt(nvt))
+            .and_then(|_| staging.dispatch_feed_version(version));
+        if let Err(e) = stored {
+            if let Err(e) = staging.reset() {
+                tracing::warn!(%e, "unable to release the staging namespace");
+            }
+            return Err(e);
+        }
+        current.delete_namespace()?;
+        Ok(())
+    }
 }
 
 impl<S> ItemDispatcher for CacheDispatcher<S>
@@ -757,7 +817,7 @@ where
                 let kbs = self.kbs.lock().map_err(StorageError::from)?;
                 let kbs = kbs.clone();
                 kbs.into_iter()
-                    .filter(move |x| x.key == s)
+                    .filter(move |x| storage::matches_kb_key(&s, &x.key))
                     .map(move |x| Field::KB(x.clone()))
             }),
         })
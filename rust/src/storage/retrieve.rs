@@ -6,7 +6,7 @@ use crate::{
     models,
     storage::{
         item::{NVTField, NVTKey, Nvt},
-        ContextKey, Field, StorageError,
+        ContextKey, Field, KbSnapshot, StorageError,
     },
 };
 
@@ -18,6 +18,8 @@ pub enum Retrieve {
     /// Metadata of the NASL script.
     NVT(Option<NVTKey>),
     /// Knowledge Base item
+    ///
+    /// The key may contain `*` wildcards matching any amount of characters, see [matches_kb_key].
     KB(String),
     /// Metadata of the Notus advisory
     NotusAdvisory(Option<String>),
@@ -79,7 +81,7 @@ impl Retrieve {
 
             Retrieve::KB(s) => {
                 if let Field::KB(kb) = field {
-                    &kb.key == s
+                    matches_kb_key(s, &kb.key)
                 } else {
                     false
                 }
@@ -98,6 +100,34 @@ impl Retrieve {
     }
 }
 
+/// Returns true when the KB key matches the pattern.
+///
+/// Like the glob matching of KB keys in openvas each `*` in the pattern matches any amount of
+/// characters, e.g. `Services/*` matches `Services/www` as well as `Services/www/80`. Without a
+/// wildcard the key must be equal to the pattern.
+pub fn matches_kb_key(pattern: &str, key: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // split always returns at least one element
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = key.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
 /// Result of a heap stored iterator or StorageError
 pub type FieldResult = Result<Box<dyn Iterator<Item = Field>>, StorageError>;
 
@@ -148,6 +178,15 @@ pub trait Retriever: Send + Sync {
             .next())
     }
 
+    /// Returns all KB items of the key together with the time they were last modified.
+    ///
+    /// Is used to seed the knowledge base of a new scan with the results of a previous one.
+    /// Returns None when the storage does not support exporting a knowledge base or when there
+    /// are no KB items.
+    fn retrieve_kb_snapshot(&self, _key: &ContextKey) -> Result<Option<KbSnapshot>, StorageError> {
+        Ok(None)
+    }
+
     /// Gets Fields find by field and scope.
     fn retrieve_by_field(&self, field: Field, scope: Retrieve) -> FieldKeyResult;
 
@@ -176,3 +215,24 @@ impl Retriever for NoOpRetriever {
         Ok(Box::new(vec![].into_iter()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::matches_kb_key;
+
+    #[test]
+    fn kb_key_wildcards() {
+        assert!(matches_kb_key("Services/www", "Services/www"));
+        assert!(!matches_kb_key("Services/www", "Services/www/80"));
+        assert!(matches_kb_key("Services/*", "Services/www"));
+        assert!(matches_kb_key("Services/*", "Services/www/80"));
+        assert!(matches_kb_key("Services/*", "Services/"));
+        assert!(!matches_kb_key("Services/*", "Ports/tcp/80"));
+        assert!(matches_kb_key("Ports/*/80", "Ports/tcp/80"));
+        assert!(!matches_kb_key("Ports/*/80", "Ports/tcp/8080/"));
+        assert!(matches_kb_key("*/80", "Ports/tcp/80"));
+        assert!(matches_kb_key("*", "anything"));
+        assert!(matches_kb_key("a*b*c", "abbc"));
+        assert!(!matches_kb_key("a*bc*c", "abc"));
+    }
+}